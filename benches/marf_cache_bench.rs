@@ -0,0 +1,85 @@
+#[macro_use]
+extern crate criterion;
+extern crate blockstack_lib;
+extern crate rand;
+
+use blockstack_lib::chainstate::burn::BlockHeaderHash;
+use blockstack_lib::chainstate::stacks::index::{
+    marf::MARF, storage::TRIE_NODE_CACHE_DEFAULT_CAPACITY, MARFValue, MarfTrieId,
+};
+
+use criterion::Criterion;
+use rand::prelude::*;
+use std::fs;
+
+fn block_header(i: u32) -> BlockHeaderHash {
+    let mut bytes = (i + 1).to_le_bytes().to_vec();
+    bytes.resize(32, 0);
+    BlockHeaderHash::from_bytes(bytes.as_slice()).unwrap()
+}
+
+fn setup_marf(filename: &str, blocks: u32, writes_per_block: u32) {
+    if fs::metadata(filename).is_ok() {
+        fs::remove_file(filename).unwrap();
+    };
+    let mut marf = MARF::from_path(filename).unwrap();
+    let mut parent = BlockHeaderHash::sentinel();
+
+    for i in 0..blocks {
+        let next = block_header(i);
+        marf.begin(&parent, &next).unwrap();
+        for k in 0..writes_per_block {
+            let key = format!("{}::{}", i, k);
+            marf.insert(&key, MARFValue::from_value(&key)).unwrap();
+        }
+        marf.commit().unwrap();
+        parent = next;
+    }
+}
+
+/// Look up keys the way block validation does: the same tip, many keys, with each block's reads
+/// overlapping with the last.
+fn benchmark_marf_block_reads(
+    filename: &str,
+    cache_capacity: usize,
+    blocks: u32,
+    writes_per_block: u32,
+    reads_per_block: u32,
+) {
+    let mut marf: MARF<BlockHeaderHash> = MARF::from_path(filename).unwrap();
+    marf.set_node_cache_capacity(cache_capacity);
+
+    let tip = block_header(blocks - 1);
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..blocks {
+        for _ in 0..reads_per_block {
+            let i: u32 = rng.gen_range(0, blocks);
+            let k: u32 = rng.gen_range(0, writes_per_block);
+            let key = format!("{}::{}", i, k);
+            marf.get(&tip, &key).unwrap().unwrap();
+        }
+    }
+}
+
+pub fn node_cache_benchmark(c: &mut Criterion) {
+    setup_marf("/tmp/marf_cache_bench.sqlite", 100, 500);
+
+    c.bench_function("marf_block_reads_100b_500W_1kR_uncached", |b| {
+        b.iter(|| benchmark_marf_block_reads("/tmp/marf_cache_bench.sqlite", 0, 100, 500, 1000))
+    });
+    c.bench_function("marf_block_reads_100b_500W_1kR_cached", |b| {
+        b.iter(|| {
+            benchmark_marf_block_reads(
+                "/tmp/marf_cache_bench.sqlite",
+                TRIE_NODE_CACHE_DEFAULT_CAPACITY,
+                100,
+                500,
+                1000,
+            )
+        })
+    });
+}
+
+criterion_group!(benches, node_cache_benchmark);
+criterion_main!(benches);
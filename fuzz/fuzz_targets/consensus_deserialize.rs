@@ -0,0 +1,10 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use blockstack_lib::chainstate::stacks::fuzz::check_consensus_deserialize;
+
+// Feed arbitrary bytes to the consensus decoders for transactions, blocks, microblocks, and
+// p2p messages.  Run with `cargo fuzz run consensus_deserialize` from the repository root.
+fuzz_target!(|data: &[u8]| {
+    check_consensus_deserialize(data);
+});
@@ -0,0 +1,252 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Bech32 encoder and decoder for version-0 segregated witness addresses (BIP-173)
+
+use address::Error;
+
+const BECH32_CHARSET: &'static [u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+const BECH32_GENERATORS: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+
+/// Longest string that can be bech32-encoded
+const BECH32_MAX_LEN: usize = 90;
+
+fn bech32_polymod(values: &[u8]) -> u32 {
+    let mut chk: u32 = 1;
+    for v in values.iter() {
+        let top = chk >> 25;
+        chk = ((chk & 0x1ffffff) << 5) ^ (*v as u32);
+        for i in 0..5 {
+            if (top >> i) & 1 == 1 {
+                chk ^= BECH32_GENERATORS[i];
+            }
+        }
+    }
+    chk
+}
+
+fn hrp_expand(hrp: &[u8]) -> Vec<u8> {
+    let mut ret = Vec::with_capacity(hrp.len() * 2 + 1);
+    for c in hrp.iter() {
+        ret.push(*c >> 5);
+    }
+    ret.push(0);
+    for c in hrp.iter() {
+        ret.push(*c & 0x1f);
+    }
+    ret
+}
+
+fn create_checksum(hrp: &[u8], data: &[u8]) -> [u8; 6] {
+    let mut values = hrp_expand(hrp);
+    values.extend_from_slice(data);
+    values.extend_from_slice(&[0u8; 6]);
+    let polymod = bech32_polymod(&values) ^ 1;
+
+    let mut ret = [0u8; 6];
+    for i in 0..6 {
+        ret[i] = ((polymod >> (5 * (5 - i))) & 0x1f) as u8;
+    }
+    ret
+}
+
+fn verify_checksum(hrp: &[u8], data: &[u8]) -> bool {
+    let mut values = hrp_expand(hrp);
+    values.extend_from_slice(data);
+    bech32_polymod(&values) == 1
+}
+
+/// Regroup a sequence of `from`-bit words into `to`-bit words.
+/// If `pad` is false, then leftover bits must be zero padding.
+pub fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Result<Vec<u8>, Error> {
+    let mut acc: u32 = 0;
+    let mut bits: u32 = 0;
+    let maxv: u32 = (1 << to) - 1;
+    let max_acc: u32 = (1 << (from + to - 1)) - 1;
+    let mut ret = Vec::with_capacity((data.len() * from as usize) / to as usize + 1);
+
+    for value in data.iter() {
+        let v = *value as u32;
+        if (v >> from) != 0 {
+            return Err(Error::BadByte(*value));
+        }
+        acc = ((acc << from) | v) & max_acc;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            ret.push(((acc >> bits) & maxv) as u8);
+        }
+    }
+
+    if pad {
+        if bits > 0 {
+            ret.push(((acc << (to - bits)) & maxv) as u8);
+        }
+    } else if bits >= from || ((acc << (to - bits)) & maxv) != 0 {
+        return Err(Error::Other("Invalid bech32 padding".to_string()));
+    }
+
+    Ok(ret)
+}
+
+/// Encode a human-readable part and a sequence of 5-bit words as a bech32 string
+pub fn encode(hrp: &str, data: &[u8]) -> Result<String, Error> {
+    let hrp_bytes = hrp.to_lowercase().into_bytes();
+    if hrp_bytes.len() == 0 || hrp_bytes.len() + data.len() + 7 > BECH32_MAX_LEN {
+        return Err(Error::InvalidLength(hrp_bytes.len() + data.len() + 7));
+    }
+
+    let checksum = create_checksum(&hrp_bytes, data);
+    let mut ret = String::with_capacity(hrp_bytes.len() + data.len() + 7);
+    ret.push_str(&hrp.to_lowercase());
+    ret.push('1');
+    for d in data.iter().chain(checksum.iter()) {
+        if *d >= 32 {
+            return Err(Error::BadByte(*d));
+        }
+        ret.push(BECH32_CHARSET[*d as usize] as char);
+    }
+    Ok(ret)
+}
+
+/// Decode a bech32 string into its (lowercase) human-readable part and its 5-bit data words,
+/// without the checksum.
+pub fn decode(s: &str) -> Result<(String, Vec<u8>), Error> {
+    if s.len() > BECH32_MAX_LEN {
+        return Err(Error::InvalidLength(s.len()));
+    }
+
+    // no mixed case
+    if s.to_lowercase() != s && s.to_uppercase() != s {
+        return Err(Error::Other("Mixed-case bech32 string".to_string()));
+    }
+
+    let s = s.to_lowercase();
+    let sep = match s.rfind('1') {
+        Some(idx) => idx,
+        None => {
+            return Err(Error::Other("Missing bech32 separator".to_string()));
+        }
+    };
+
+    if sep == 0 || sep + 7 > s.len() {
+        return Err(Error::InvalidLength(s.len()));
+    }
+
+    let hrp = &s[0..sep];
+    for c in hrp.bytes() {
+        if c < 33 || c > 126 {
+            return Err(Error::BadByte(c));
+        }
+    }
+
+    let mut data = Vec::with_capacity(s.len() - sep - 1);
+    for c in s[sep + 1..].bytes() {
+        match BECH32_CHARSET.iter().position(|x| *x == c) {
+            Some(v) => data.push(v as u8),
+            None => {
+                return Err(Error::BadByte(c));
+            }
+        }
+    }
+
+    if !verify_checksum(hrp.as_bytes(), &data) {
+        return Err(Error::Other("Invalid bech32 checksum".to_string()));
+    }
+
+    data.truncate(data.len() - 6);
+    Ok((hrp.to_string(), data))
+}
+
+/// Encode a version-0 witness program as a segwit address
+pub fn encode_segwit_address(hrp: &str, program: &[u8]) -> Result<String, Error> {
+    if program.len() != 20 && program.len() != 32 {
+        return Err(Error::InvalidLength(program.len()));
+    }
+    let mut data = vec![0u8];
+    data.append(&mut convert_bits(program, 8, 5, true)?);
+    encode(hrp, &data)
+}
+
+/// Decode a segwit address with the given human-readable part into its version-0 witness
+/// program.
+pub fn decode_segwit_address(hrp: &str, addr: &str) -> Result<Vec<u8>, Error> {
+    let (addr_hrp, data) = decode(addr)?;
+    if addr_hrp != hrp.to_lowercase() {
+        return Err(Error::Other(format!(
+            "Unexpected bech32 human-readable part '{}'",
+            addr_hrp
+        )));
+    }
+    if data.len() == 0 {
+        return Err(Error::EmptyData);
+    }
+    if data[0] != 0 {
+        return Err(Error::InvalidVersion(data[0]));
+    }
+
+    let program = convert_bits(&data[1..], 5, 8, false)?;
+    if program.len() != 20 && program.len() != 32 {
+        return Err(Error::InvalidLength(program.len()));
+    }
+    Ok(program)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use util::hash::{hex_bytes, to_hex};
+
+    #[test]
+    fn test_segwit_address_vectors() {
+        // from BIP-173
+        let fixtures = vec![
+            (
+                "bc",
+                "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4",
+                "751e76e8199196d454941c45d1b3a323f1433bd6",
+            ),
+            (
+                "tb",
+                "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7",
+                "1863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+            ),
+        ];
+
+        for (hrp, addr, program_hex) in fixtures {
+            let program = decode_segwit_address(hrp, addr).unwrap();
+            assert_eq!(to_hex(&program), program_hex);
+
+            let encoded = encode_segwit_address(hrp, &hex_bytes(program_hex).unwrap()).unwrap();
+            assert_eq!(encoded, addr.to_lowercase());
+        }
+    }
+
+    #[test]
+    fn test_segwit_address_invalid() {
+        // wrong hrp
+        assert!(decode_segwit_address("tb", "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4").is_err());
+        // bad checksum
+        assert!(decode_segwit_address("bc", "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5").is_err());
+        // mixed case
+        assert!(decode_segwit_address("bc", "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kV8f3t4").is_err());
+        // invalid character
+        assert!(decode_segwit_address("bc", "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3tb").is_err());
+        // program of the wrong length
+        assert!(encode_segwit_address("bc", &[0u8; 21]).is_err());
+    }
+}
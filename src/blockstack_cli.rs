@@ -63,6 +63,7 @@ This CLI has these methods:
   contract-call    used to generate and sign a contract-call transaction
   generate-sk      used to generate a secret key for transaction signing
   token-transfer   used to generate and sign a transfer transaction
+  sponsor          used to sign a sponsored transaction as its sponsor, paying its fee
   addresses        used to get both Bitcoin and Stacks addresses from a private key
 
 For usage information on those methods, call `blockstack-cli [method] -h`
@@ -70,6 +71,8 @@ For usage information on those methods, call `blockstack-cli [method] -h`
 `blockstack-cli` accepts flag options as well:
 
    --testnet       instruct the transaction generator to use a testnet version byte instead of MAINNET (default)
+   --sponsored     generate a sponsored transaction, signed only by its origin.  Its fee-rate must
+                   be 0, since the sponsor sets and pays the fee (see `blockstack-cli sponsor -h`).
 
 ";
 
@@ -107,6 +110,13 @@ The transfer command generates and signs a STX transfer transaction. If successf
 this command outputs the hex string encoding of the transaction to stdout, and exits with
 code 0";
 
+const SPONSOR_USAGE: &str = "blockstack-cli (options) sponsor [sponsor-secret-key-hex] [fee-rate] [sponsor-nonce] [origin-signed-transaction-hex]
+
+The sponsor command takes a sponsored transaction that its origin has already signed (generated
+with --sponsored), sets the sponsor's fee-rate and nonce, and signs it as the sponsor. If
+successful, this command outputs the hex string encoding of the transaction to stdout, and exits
+with code 0";
+
 const GENERATE_USAGE: &str = "blockstack-cli (options) generate-sk
 
 This method generates a secret key, outputting the hex encoding of the
@@ -256,6 +266,28 @@ fn make_standard_single_sig_tx(
     tx
 }
 
+/// Make a sponsored transaction for the origin to sign.  The sponsor spending condition is the
+/// sentinel the origin commits to; the sponsor replaces it when it signs.
+fn make_sponsored_single_sig_tx(
+    version: TransactionVersion,
+    chain_id: u32,
+    payload: TransactionPayload,
+    publicKey: &StacksPublicKey,
+    nonce: u64,
+) -> StacksTransaction {
+    let mut spending_condition =
+        TransactionSpendingCondition::new_singlesig_p2pkh(publicKey.clone())
+            .expect("Failed to create p2pkh spending condition from public key.");
+    spending_condition.set_nonce(nonce);
+    let auth = TransactionAuth::Sponsored(
+        spending_condition,
+        TransactionSpendingCondition::new_initial_sighash(),
+    );
+    let mut tx = StacksTransaction::new(version, auth, payload);
+    tx.chain_id = chain_id;
+    tx
+}
+
 fn sign_transaction_single_sig_standard(
     transaction: &str,
     secret_key: &StacksPrivateKey,
@@ -271,10 +303,66 @@ fn sign_transaction_single_sig_standard(
         .ok_or("TX did not finish signing -- was this a standard single signature transaction?")?)
 }
 
+fn sign_transaction_single_sig_sponsored_origin(
+    transaction: &str,
+    secret_key: &StacksPrivateKey,
+) -> Result<StacksTransaction, CliError> {
+    let transaction =
+        StacksTransaction::consensus_deserialize(&mut io::Cursor::new(&hex_bytes(transaction)?))?;
+
+    let mut tx_signer = StacksTransactionSigner::new(&transaction);
+    tx_signer.sign_origin(secret_key)?;
+
+    // the sponsor has yet to sign
+    Ok(tx_signer.get_tx_incomplete())
+}
+
+/// Make and sign a single-sig transaction for the given origin, returning its hex encoding.
+/// If `sponsored` is set, only the origin signs it, and it must be passed on to a sponsor.
+fn make_signed_single_sig_tx(
+    version: TransactionVersion,
+    chain_id: u32,
+    payload: TransactionPayload,
+    sk_origin: &StacksPrivateKey,
+    nonce: u64,
+    fee_rate: u64,
+    sponsored: bool,
+) -> Result<String, CliError> {
+    let pk_origin = StacksPublicKey::from_private(sk_origin);
+    let unsigned_tx = if sponsored {
+        if fee_rate != 0 {
+            return Err(
+                "Sponsored transactions must have a fee-rate of 0 -- the sponsor sets the fee"
+                    .into(),
+            );
+        }
+        make_sponsored_single_sig_tx(version, chain_id, payload, &pk_origin, nonce)
+    } else {
+        make_standard_single_sig_tx(version, chain_id, payload, &pk_origin, nonce, fee_rate)
+    };
+
+    let mut unsigned_tx_bytes = vec![];
+    unsigned_tx
+        .consensus_serialize(&mut unsigned_tx_bytes)
+        .expect("FATAL: invalid transaction");
+    let signed_tx = if sponsored {
+        sign_transaction_single_sig_sponsored_origin(&to_hex(&unsigned_tx_bytes), sk_origin)?
+    } else {
+        sign_transaction_single_sig_standard(&to_hex(&unsigned_tx_bytes), sk_origin)?
+    };
+
+    let mut signed_tx_bytes = vec![];
+    signed_tx
+        .consensus_serialize(&mut signed_tx_bytes)
+        .expect("FATAL: invalid signed transaction");
+    Ok(to_hex(&signed_tx_bytes))
+}
+
 fn handle_contract_publish(
     args: &[String],
     version: TransactionVersion,
     chain_id: u32,
+    sponsored: bool,
 ) -> Result<String, CliError> {
     if args.len() >= 1 && args[0] == "-h" {
         return Err(CliError::Message(format!("USAGE:\n {}", PUBLISH_USAGE)));
@@ -302,32 +390,22 @@ fn handle_contract_publish(
     let sk_publisher = StacksPrivateKey::from_hex(sk_publisher)?;
 
     let payload = make_contract_publish(contract_name.clone(), contract_contents)?;
-    let unsigned_tx = make_standard_single_sig_tx(
+    make_signed_single_sig_tx(
         version,
         chain_id,
         payload.into(),
-        &StacksPublicKey::from_private(&sk_publisher),
+        &sk_publisher,
         nonce,
         fee_rate,
-    );
-    let mut unsigned_tx_bytes = vec![];
-    unsigned_tx
-        .consensus_serialize(&mut unsigned_tx_bytes)
-        .expect("FATAL: invalid transaction");
-    let signed_tx =
-        sign_transaction_single_sig_standard(&to_hex(&unsigned_tx_bytes), &sk_publisher)?;
-
-    let mut signed_tx_bytes = vec![];
-    signed_tx
-        .consensus_serialize(&mut signed_tx_bytes)
-        .expect("FATAL: invalid signed transaction");
-    Ok(to_hex(&signed_tx_bytes))
+        sponsored,
+    )
 }
 
 fn handle_contract_call(
     args: &[String],
     version: TransactionVersion,
     chain_id: u32,
+    sponsored: bool,
 ) -> Result<String, CliError> {
     if args.len() >= 1 && args[0] == "-h" {
         return Err(CliError::Message(format!("USAGE:\n {}", CALL_USAGE)));
@@ -361,7 +439,7 @@ fn handle_contract_call(
         let input = &val_args[arg_iterator + 1];
         let value = match eval_method.as_str() {
             "-x" => {
-                Value::try_deserialize_hex_untyped(input)?
+                Value::deserialize_from_hex(input, None)?
             },
             "-e" => {
                 vm::execute(input)?
@@ -384,32 +462,22 @@ fn handle_contract_call(
         function_name.clone(),
         values,
     )?;
-    let unsigned_tx = make_standard_single_sig_tx(
+    make_signed_single_sig_tx(
         version,
         chain_id,
         payload.into(),
-        &StacksPublicKey::from_private(&sk_origin),
+        &sk_origin,
         nonce,
         fee_rate,
-    );
-
-    let mut unsigned_tx_bytes = vec![];
-    unsigned_tx
-        .consensus_serialize(&mut unsigned_tx_bytes)
-        .expect("FATAL: invalid transaction");
-    let signed_tx = sign_transaction_single_sig_standard(&to_hex(&unsigned_tx_bytes), &sk_origin)?;
-
-    let mut signed_tx_bytes = vec![];
-    signed_tx
-        .consensus_serialize(&mut signed_tx_bytes)
-        .expect("FATAL: invalid signed transaction");
-    Ok(to_hex(&signed_tx_bytes))
+        sponsored,
+    )
 }
 
 fn handle_token_transfer(
     args: &[String],
     version: TransactionVersion,
     chain_id: u32,
+    sponsored: bool,
 ) -> Result<String, CliError> {
     if args.len() >= 1 && args[0] == "-h" {
         return Err(CliError::Message(format!(
@@ -442,19 +510,46 @@ fn handle_token_transfer(
     };
 
     let payload = TransactionPayload::TokenTransfer(recipient_address, *amount, memo);
-    let unsigned_tx = make_standard_single_sig_tx(
-        version,
-        chain_id,
-        payload,
-        &StacksPublicKey::from_private(&sk_origin),
-        nonce,
-        fee_rate,
-    );
-    let mut unsigned_tx_bytes = vec![];
-    unsigned_tx
-        .consensus_serialize(&mut unsigned_tx_bytes)
-        .expect("FATAL: invalid transaction");
-    let signed_tx = sign_transaction_single_sig_standard(&to_hex(&unsigned_tx_bytes), &sk_origin)?;
+    make_signed_single_sig_tx(
+        version, chain_id, payload, &sk_origin, nonce, fee_rate, sponsored,
+    )
+}
+
+fn handle_sponsor(args: &[String], _version: TransactionVersion) -> Result<String, CliError> {
+    if args.len() >= 1 && args[0] == "-h" {
+        return Err(CliError::Message(format!("USAGE:\n {}", SPONSOR_USAGE)));
+    }
+    if args.len() != 4 {
+        return Err(CliError::Message(format!(
+            "Incorrect argument count supplied \n\nUSAGE:\n {}",
+            SPONSOR_USAGE
+        )));
+    }
+    let sk_sponsor = StacksPrivateKey::from_hex(&args[0])?;
+    let fee_rate = args[1].parse()?;
+    let nonce = args[2].parse()?;
+    let origin_signed_tx =
+        StacksTransaction::consensus_deserialize(&mut io::Cursor::new(&hex_bytes(&args[3])?))?;
+
+    if !origin_signed_tx.auth.is_sponsored() {
+        return Err("Transaction is not sponsored -- was it generated with --sponsored?".into());
+    }
+
+    let mut spending_condition = TransactionSpendingCondition::new_singlesig_p2pkh(
+        StacksPublicKey::from_private(&sk_sponsor),
+    )
+    .expect("Failed to create p2pkh spending condition from public key.");
+    spending_condition.set_nonce(nonce);
+    spending_condition.set_fee_rate(fee_rate);
+
+    // verifies the origin's signature before the sponsor commits to it
+    let mut tx_signer = StacksTransactionSigner::new_sponsor(&origin_signed_tx, spending_condition)
+        .map_err(|e| CliError::Message(format!("Failed to sponsor transaction: {}", e)))?;
+    tx_signer.sign_sponsor(&sk_sponsor)?;
+
+    let signed_tx = tx_signer
+        .get_tx()
+        .ok_or("TX did not finish signing -- was the origin fully signed?")?;
 
     let mut signed_tx_bytes = vec![];
     signed_tx
@@ -632,6 +727,13 @@ fn main_handler(mut argv: Vec<String>) -> Result<String, CliError> {
         TransactionVersion::Mainnet
     };
 
+    let sponsored = if let Some(ix) = argv.iter().position(|x| x == "--sponsored") {
+        argv.remove(ix);
+        true
+    } else {
+        false
+    };
+
     let chain_id = if tx_version == TransactionVersion::Testnet {
         TESTNET_CHAIN_ID
     } else {
@@ -640,9 +742,10 @@ fn main_handler(mut argv: Vec<String>) -> Result<String, CliError> {
 
     if let Some((method, args)) = argv.split_first() {
         match method.as_str() {
-            "contract-call" => handle_contract_call(args, tx_version, chain_id),
-            "publish" => handle_contract_publish(args, tx_version, chain_id),
-            "token-transfer" => handle_token_transfer(args, tx_version, chain_id),
+            "contract-call" => handle_contract_call(args, tx_version, chain_id, sponsored),
+            "publish" => handle_contract_publish(args, tx_version, chain_id, sponsored),
+            "token-transfer" => handle_token_transfer(args, tx_version, chain_id, sponsored),
+            "sponsor" => handle_sponsor(args, tx_version),
             "generate-sk" => generate_secret_key(args, tx_version),
             "addresses" => get_addresses(args, tx_version),
             "decode-tx" => decode_transaction(args, tx_version),
@@ -900,6 +1003,91 @@ mod test {
         );
     }
 
+    #[test]
+    fn simple_sponsored_tx() {
+        let tt_args = [
+            "--testnet",
+            "--sponsored",
+            "token-transfer",
+            "043ff5004e3d695060fa48ac94c96049b8c14ef441c50a184a6a3875d2a000f3",
+            "0",
+            "3",
+            "ST1A14RBKJ289E3DP89QAZE2RRHDPWP5RHMYFRCHV",
+            "10",
+        ];
+
+        let origin_signed_tx = main_handler(to_string_vec(&tt_args)).unwrap();
+
+        let sponsor_args = [
+            "--testnet",
+            "sponsor",
+            "2945c6be8758994652a498f0445d534d0fadb0b2025b37c72297b059ebf887ed01",
+            "200",
+            "7",
+            origin_signed_tx.as_str(),
+        ];
+
+        let signed_tx_hex = main_handler(to_string_vec(&sponsor_args)).unwrap();
+        let signed_tx = StacksTransaction::consensus_deserialize(&mut io::Cursor::new(
+            &hex_bytes(&signed_tx_hex).unwrap(),
+        ))
+        .unwrap();
+
+        assert!(signed_tx.verify().is_ok());
+        assert_eq!(
+            signed_tx.sponsor_address().unwrap().to_string(),
+            "ST36T883PDD2EK4PHVTA5GFHC8NQW6558XJQX6Q3K"
+        );
+        assert_eq!(signed_tx.get_origin().nonce(), 3);
+        assert_eq!(signed_tx.get_sponsor_nonce(), Some(7));
+        assert_eq!(signed_tx.get_fee_rate(), 200);
+
+        // the sponsor pays the fee
+        let tt_args = [
+            "--testnet",
+            "--sponsored",
+            "token-transfer",
+            "043ff5004e3d695060fa48ac94c96049b8c14ef441c50a184a6a3875d2a000f3",
+            "1",
+            "3",
+            "ST1A14RBKJ289E3DP89QAZE2RRHDPWP5RHMYFRCHV",
+            "10",
+        ];
+
+        assert!(
+            format!("{}", main_handler(to_string_vec(&tt_args)).unwrap_err())
+                .contains("fee-rate of 0")
+        );
+
+        // only sponsored transactions can be sponsored
+        let tt_args = [
+            "--testnet",
+            "token-transfer",
+            "043ff5004e3d695060fa48ac94c96049b8c14ef441c50a184a6a3875d2a000f3",
+            "1",
+            "3",
+            "ST1A14RBKJ289E3DP89QAZE2RRHDPWP5RHMYFRCHV",
+            "10",
+        ];
+
+        let standard_tx = main_handler(to_string_vec(&tt_args)).unwrap();
+
+        let sponsor_args = [
+            "--testnet",
+            "sponsor",
+            "2945c6be8758994652a498f0445d534d0fadb0b2025b37c72297b059ebf887ed01",
+            "200",
+            "7",
+            standard_tx.as_str(),
+        ];
+
+        assert!(format!(
+            "{}",
+            main_handler(to_string_vec(&sponsor_args)).unwrap_err()
+        )
+        .contains("not sponsored"));
+    }
+
     #[test]
     fn simple_addresses() {
         let addr_args = [
@@ -23,6 +23,7 @@ use util::hash::Hash160;
 use util::log;
 
 use address::b58 as base58;
+use address::bech32;
 use address::c32::c32_address;
 use deps::bitcoin::blockdata::script::Builder as BtcScriptBuilder;
 
@@ -38,6 +39,8 @@ use chainstate::stacks::{
 pub enum BitcoinAddressType {
     PublicKeyHash,
     ScriptHash,
+    /// Native (bech32) version-0 pay-to-witness-pubkey-hash
+    WitnessPubkeyHash,
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
@@ -52,19 +55,36 @@ pub const ADDRESS_VERSION_MAINNET_MULTISIG: u8 = 5;
 pub const ADDRESS_VERSION_TESTNET_SINGLESIG: u8 = 111;
 pub const ADDRESS_VERSION_TESTNET_MULTISIG: u8 = 196;
 
+pub const SEGWIT_HRP_MAINNET: &'static str = "bc";
+pub const SEGWIT_HRP_TESTNET: &'static str = "tb";
+pub const SEGWIT_HRP_REGTEST: &'static str = "bcrt";
+
+pub fn network_id_to_segwit_hrp(network_id: BitcoinNetworkType) -> &'static str {
+    match network_id {
+        BitcoinNetworkType::Mainnet => SEGWIT_HRP_MAINNET,
+        BitcoinNetworkType::Testnet => SEGWIT_HRP_TESTNET,
+        BitcoinNetworkType::Regtest => SEGWIT_HRP_REGTEST,
+    }
+}
+
 pub fn address_type_to_version_byte(
     addrtype: BitcoinAddressType,
     network_id: BitcoinNetworkType,
 ) -> u8 {
+    // NOTE: a p2wpkh address has the same hash as the p2pkh address of the same key, so it maps
+    // to the single-sig version byte.
     match (addrtype, network_id) {
-        (BitcoinAddressType::PublicKeyHash, BitcoinNetworkType::Mainnet) => {
+        (BitcoinAddressType::PublicKeyHash, BitcoinNetworkType::Mainnet)
+        | (BitcoinAddressType::WitnessPubkeyHash, BitcoinNetworkType::Mainnet) => {
             ADDRESS_VERSION_MAINNET_SINGLESIG
         }
         (BitcoinAddressType::ScriptHash, BitcoinNetworkType::Mainnet) => {
             ADDRESS_VERSION_MAINNET_MULTISIG
         }
         (BitcoinAddressType::PublicKeyHash, BitcoinNetworkType::Testnet)
-        | (BitcoinAddressType::PublicKeyHash, BitcoinNetworkType::Regtest) => {
+        | (BitcoinAddressType::PublicKeyHash, BitcoinNetworkType::Regtest)
+        | (BitcoinAddressType::WitnessPubkeyHash, BitcoinNetworkType::Testnet)
+        | (BitcoinAddressType::WitnessPubkeyHash, BitcoinNetworkType::Regtest) => {
             ADDRESS_VERSION_TESTNET_SINGLESIG
         }
         (BitcoinAddressType::ScriptHash, BitcoinNetworkType::Testnet)
@@ -168,6 +188,33 @@ impl BitcoinAddress {
         })
     }
 
+    /// Instantiate a p2wpkh address from a bech32 string.
+    /// The network type is determined by the human-readable part.
+    pub fn from_bech32(addr: &str) -> Result<BitcoinAddress, btc_error> {
+        let lower = addr.to_lowercase();
+        let network_id = if lower.starts_with(&format!("{}1", SEGWIT_HRP_REGTEST)) {
+            BitcoinNetworkType::Regtest
+        } else if lower.starts_with(&format!("{}1", SEGWIT_HRP_TESTNET)) {
+            BitcoinNetworkType::Testnet
+        } else if lower.starts_with(&format!("{}1", SEGWIT_HRP_MAINNET)) {
+            BitcoinNetworkType::Mainnet
+        } else {
+            test_debug!("Invalid address: unrecognized segwit prefix");
+            return Err(btc_error::InvalidByteSequence);
+        };
+
+        let program = bech32::decode_segwit_address(network_id_to_segwit_hrp(network_id), addr)
+            .map_err(|_e| btc_error::InvalidByteSequence)?;
+
+        // only p2wpkh programs fit in an address hash
+        if program.len() != 20 {
+            test_debug!("Invalid address: {}-byte witness program", program.len());
+            return Err(btc_error::InvalidByteSequence);
+        }
+
+        BitcoinAddress::from_bytes(network_id, BitcoinAddressType::WitnessPubkeyHash, &program)
+    }
+
     /// Instantiate an address from a scriptpubkey
     /// If we don't recognize it, then return None
     pub fn from_scriptpubkey(
@@ -200,6 +247,16 @@ impl BitcoinAddress {
                 addrtype: BitcoinAddressType::ScriptHash,
                 bytes: Hash160(my_bytes),
             })
+        } else if scriptpubkey.len() == 22 && scriptpubkey[0..2] == [0x00, 0x14] {
+            let mut my_bytes = [0; 20];
+            let b = &scriptpubkey[2..22];
+            my_bytes.copy_from_slice(b);
+
+            Some(BitcoinAddress {
+                network_id: network_id,
+                addrtype: BitcoinAddressType::WitnessPubkeyHash,
+                bytes: Hash160(my_bytes),
+            })
         } else {
             None
         }
@@ -223,6 +280,18 @@ impl BitcoinAddress {
         base58::check_encode_slice(&versioned_bytes)
     }
 
+    /// Encode a p2wpkh address as bech32.  Returns None for legacy addresses.
+    pub fn to_bech32(&self) -> Option<String> {
+        match self.addrtype {
+            BitcoinAddressType::WitnessPubkeyHash => bech32::encode_segwit_address(
+                network_id_to_segwit_hrp(self.network_id),
+                self.bytes.as_bytes(),
+            )
+            .ok(),
+            _ => None,
+        }
+    }
+
     pub fn to_c32(&self) -> String {
         let version_byte = address_type_to_version_byte(self.addrtype, self.network_id);
         let c32_address_byte = to_c32_version_byte(version_byte).unwrap(); // NOTE: should never panic, since (addrtype, network_id) always maps to a valid Bitcoin version byte
@@ -254,6 +323,29 @@ impl BitcoinAddress {
             script_pubkey,
         }
     }
+
+    pub fn to_p2wpkh_tx_out(bytes: &Hash160, value: u64) -> TxOut {
+        let script_pubkey = BtcScriptBuilder::new()
+            .push_int(0)
+            .push_slice(&bytes.0)
+            .into_script();
+        TxOut {
+            value,
+            script_pubkey,
+        }
+    }
+
+    pub fn to_tx_out(&self, value: u64) -> TxOut {
+        match self.addrtype {
+            BitcoinAddressType::PublicKeyHash => {
+                BitcoinAddress::to_p2pkh_tx_out(&self.bytes, value)
+            }
+            BitcoinAddressType::ScriptHash => BitcoinAddress::to_p2sh_tx_out(&self.bytes, value),
+            BitcoinAddressType::WitnessPubkeyHash => {
+                BitcoinAddress::to_p2wpkh_tx_out(&self.bytes, value)
+            }
+        }
+    }
 }
 
 impl Address for BitcoinAddress {
@@ -264,7 +356,10 @@ impl Address for BitcoinAddress {
     fn from_string(s: &str) -> Option<BitcoinAddress> {
         match BitcoinAddress::from_b58(s) {
             Ok(a) => Some(a),
-            Err(_e) => None,
+            Err(_e) => match BitcoinAddress::from_bech32(s) {
+                Ok(a) => Some(a),
+                Err(_e) => None,
+            },
         }
     }
 
@@ -275,7 +370,10 @@ impl Address for BitcoinAddress {
 
 impl std::fmt::Display for BitcoinAddress {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.to_b58().fmt(f)
+        match self.to_bech32() {
+            Some(s) => s.fmt(f),
+            None => self.to_b58().fmt(f),
+        }
     }
 }
 
@@ -283,6 +381,7 @@ impl std::fmt::Display for BitcoinAddress {
 mod tests {
     use super::{BitcoinAddress, BitcoinAddressType};
     use burnchains::bitcoin::BitcoinNetworkType;
+    use burnchains::Address;
     use util::hash::{hex_bytes, Hash160};
     use util::log;
 
@@ -394,6 +493,16 @@ mod tests {
                     bytes: Hash160::from_hex("2c2edf39b098e05cf770e6b5a2fcedb54ee4fe05").unwrap(),
                 }),
             },
+            ScriptFixture {
+                scriptpubkey: hex_bytes("0014751e76e8199196d454941c45d1b3a323f1433bd6")
+                    .unwrap()
+                    .to_vec(),
+                result: Some(BitcoinAddress {
+                    addrtype: BitcoinAddressType::WitnessPubkeyHash,
+                    network_id: BitcoinNetworkType::Mainnet,
+                    bytes: Hash160::from_hex("751e76e8199196d454941c45d1b3a323f1433bd6").unwrap(),
+                }),
+            },
             ScriptFixture {
                 scriptpubkey: hex_bytes("002c2edf39b098e05cf770e6b5a2fcedb54ee4fe05")
                     .unwrap()
@@ -434,4 +543,62 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_bech32() {
+        let addr =
+            BitcoinAddress::from_bech32("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4").unwrap();
+        assert_eq!(
+            addr,
+            BitcoinAddress {
+                addrtype: BitcoinAddressType::WitnessPubkeyHash,
+                network_id: BitcoinNetworkType::Mainnet,
+                bytes: Hash160::from_hex("751e76e8199196d454941c45d1b3a323f1433bd6").unwrap(),
+            }
+        );
+        assert_eq!(
+            addr.to_string(),
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string()
+        );
+        assert_eq!(
+            BitcoinAddress::from_string("BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4"),
+            Some(addr.clone())
+        );
+
+        // same hash as the p2pkh address of the same key
+        let legacy = BitcoinAddress::from_b58("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH").unwrap();
+        assert_eq!(legacy.bytes, addr.bytes);
+        assert_eq!(
+            addr.to_tx_out(5500).script_pubkey.to_bytes(),
+            hex_bytes("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap()
+        );
+        assert_eq!(
+            BitcoinAddress::from_scriptpubkey(
+                BitcoinNetworkType::Mainnet,
+                &addr.to_tx_out(5500).script_pubkey.to_bytes()
+            ),
+            Some(addr.clone())
+        );
+
+        // regtest round-trip
+        let regtest_addr = BitcoinAddress {
+            network_id: BitcoinNetworkType::Regtest,
+            ..addr.clone()
+        };
+        let regtest_str = regtest_addr.to_bech32().unwrap();
+        assert!(regtest_str.starts_with("bcrt1q"));
+        assert_eq!(
+            BitcoinAddress::from_bech32(&regtest_str).unwrap(),
+            regtest_addr
+        );
+
+        // legacy addresses have no bech32 encoding
+        assert_eq!(legacy.to_bech32(), None);
+
+        // p2wsh programs don't fit in an address hash
+        assert!(BitcoinAddress::from_bech32(
+            "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7"
+        )
+        .is_err());
+    }
 }
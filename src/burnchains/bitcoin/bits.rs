@@ -68,6 +68,13 @@ impl BitcoinTxInput {
                     AddressHashMode::SerializeP2WSH
                 }
             }
+            BitcoinInputType::SegwitNative => {
+                if self.keys.len() == 1 {
+                    AddressHashMode::SerializeP2PKH
+                } else {
+                    AddressHashMode::SerializeP2WSH
+                }
+            }
         };
 
         let h = public_keys_to_address_hash(&hash_mode, self.num_required, &self.keys);
@@ -452,6 +459,56 @@ impl BitcoinTxInput {
         }
     }
 
+    /// parse a native p2wpkh witness, given an empty scriptsig
+    fn from_bitcoin_p2wpkh_witness(witness: &Vec<Vec<u8>>) -> Option<BitcoinTxInput> {
+        // witness format: <sig> <pubkey>
+        if witness.len() != 2 {
+            test_debug!("Not a p2wpkh witness: {} items", witness.len());
+            return None;
+        }
+
+        let mut tx_input = BitcoinTxInput::from_bitcoin_witness_pubkey_vecs(1, &witness[1..])?;
+        tx_input.in_type = BitcoinInputType::SegwitNative;
+        Some(tx_input)
+    }
+
+    /// parse a native p2wsh multisig witness, given an empty scriptsig
+    fn from_bitcoin_p2wsh_multisig_witness(witness: &Vec<Vec<u8>>) -> Option<BitcoinTxInput> {
+        // witness format: <> <sig1> <sig2> ... <sig_m> MULTISIG_REDEEM_SCRIPT
+        if witness.len() < 3 || witness[0].len() != 0 {
+            test_debug!("Not a p2wsh multisig witness");
+            return None;
+        }
+
+        let num_expected_sigs = witness.len() - 2;
+        let redeem_script = &witness[witness.len() - 1];
+
+        let mut tx_input = BitcoinTxInput::from_bitcoin_multisig_redeem_script(
+            &Instruction::PushBytes(&redeem_script[..]),
+            true,
+        )?;
+
+        if num_expected_sigs != tx_input.num_required {
+            test_debug!(
+                "Not a witness multisig script: {} signatures, {} required",
+                num_expected_sigs,
+                tx_input.num_required
+            );
+            return None;
+        }
+
+        tx_input.in_type = BitcoinInputType::SegwitNative;
+        Some(tx_input)
+    }
+
+    /// Parse the witness of a native segwit input as either p2wpkh or p2wsh multisig
+    pub fn from_bitcoin_native_witness(witness: &Vec<Vec<u8>>) -> Option<BitcoinTxInput> {
+        match BitcoinTxInput::from_bitcoin_p2wpkh_witness(witness) {
+            Some(tx_input) => Some(tx_input),
+            None => BitcoinTxInput::from_bitcoin_p2wsh_multisig_witness(witness),
+        }
+    }
+
     /// parse a script-sig as either p2pkh scriptsig or p2sh multisig scriptsig
     /// does NOT work with segwit
     fn from_bitcoin_script_sig(script_sig: &Script) -> Option<BitcoinTxInput> {
@@ -494,8 +551,13 @@ impl BitcoinTxInput {
                 BitcoinTxInput::from_bitcoin_script_sig(&txin.script_sig)
             }
             _ => {
-                // possibly a segwit p2wpkh-over-p2sh or multisig p2wsh-over-p2sh transaction
-                BitcoinTxInput::from_bitcoin_witness_script_sig(&txin.script_sig, &txin.witness)
+                if txin.script_sig.is_empty() {
+                    // possibly a native p2wpkh or multisig p2wsh transaction
+                    BitcoinTxInput::from_bitcoin_native_witness(&txin.witness)
+                } else {
+                    // possibly a segwit p2wpkh-over-p2sh or multisig p2wsh-over-p2sh transaction
+                    BitcoinTxInput::from_bitcoin_witness_script_sig(&txin.script_sig, &txin.witness)
+                }
             }
         }
     }
@@ -521,7 +583,15 @@ impl BitcoinTxOutput {
                 BitcoinAddressType::ScriptHash,
                 &script_bytes[2..22].to_vec(),
             )
+        } else if script_pubkey.is_v0_p2wpkh() {
+            BitcoinAddress::from_bytes(
+                network_id,
+                BitcoinAddressType::WitnessPubkeyHash,
+                &script_bytes[2..22].to_vec(),
+            )
         } else {
+            // NOTE: p2wsh outputs carry a 32-byte witness program, which does not fit into an
+            // address hash, so they are not recognized as recipients.
             Err(btc_error::InvalidByteSequence)
         };
 
@@ -559,6 +629,7 @@ mod tests {
     use util::hash::hex_bytes;
 
     use deps::bitcoin::blockdata::script::{Builder, Script};
+    use deps::bitcoin::blockdata::transaction::{OutPoint, TxIn as BtcTxIn};
 
     use burnchains::bitcoin::address::{BitcoinAddress, BitcoinAddressType};
     use burnchains::bitcoin::keys::BitcoinPublicKey;
@@ -1036,6 +1107,135 @@ mod tests {
         }
     }
 
+    #[test]
+    fn tx_input_segwit_native() {
+        let sig = hex_bytes("304402204686573485d6a7cc7e40d9a95f5e87eafbf4eabfc38863498fd022b18a4da4fc0220036d715f2bc7b16b3a264500d1944ca3cad3c3e9d87a01cf917ecf06e436952401").unwrap();
+        let pubk = hex_bytes("02d341f728783eb93e6fb5921a1ebe9d149e941de31e403cd69afa2f0f1e698e81")
+            .unwrap();
+        let multisig_witness = vec![
+            hex_bytes("").unwrap(),
+            hex_bytes("3044022064c5b5f61baad8bb8ecad98666b99e09f1777ef805df41a1c7926f8468b6b6df02205eac177c77f274acb670cd24d504f01b27de767e0241c818c91e479cb0ddcf1801").unwrap(),
+            hex_bytes("30440220053ce777bc7bb842d8eef83769a027797567624ab9eed5722889ed3192f431b30220256e8aaef8de2a571198acde708fcbca02fb18780ac470c0d7f811734af729af01").unwrap(),
+            hex_bytes("522102d341f728783eb93e6fb5921a1ebe9d149e941de31e403cd69afa2f0f1e698e812102f21b29694df4c2188bee97103d10d017d1865fb40528f25589af9db6e0786b6521028791dc45c049107fb99e673265a38a096536aacdf78aa90710a32fff7750f9f953ae").unwrap()
+        ];
+
+        let tx_fixtures_native = vec![
+            ScriptWitnessFixture {
+                // p2wpkh
+                script: Script::new(),
+                witness: vec![sig.clone(), pubk.clone()],
+                result: Some(BitcoinTxInput {
+                    num_required: 1,
+                    keys: vec![BitcoinPublicKey::from_slice(&pubk).unwrap()],
+                    in_type: BitcoinInputType::SegwitNative,
+                }),
+            },
+            ScriptWitnessFixture {
+                // p2wsh multisig
+                script: Script::new(),
+                witness: multisig_witness.clone(),
+                result: Some(BitcoinTxInput {
+                    num_required: 2,
+                    keys: vec![
+                        BitcoinPublicKey::from_hex(
+                            "02d341f728783eb93e6fb5921a1ebe9d149e941de31e403cd69afa2f0f1e698e81",
+                        )
+                        .unwrap(),
+                        BitcoinPublicKey::from_hex(
+                            "02f21b29694df4c2188bee97103d10d017d1865fb40528f25589af9db6e0786b65",
+                        )
+                        .unwrap(),
+                        BitcoinPublicKey::from_hex(
+                            "028791dc45c049107fb99e673265a38a096536aacdf78aa90710a32fff7750f9f9",
+                        )
+                        .unwrap(),
+                    ],
+                    in_type: BitcoinInputType::SegwitNative,
+                }),
+            },
+            ScriptWitnessFixture {
+                // invalid p2wpkh: last witness entry isn't a public key
+                script: Script::new(),
+                witness: vec![pubk.clone(), sig.clone()],
+                result: None,
+            },
+            ScriptWitnessFixture {
+                // invalid p2wsh multisig: missing a signature
+                script: Script::new(),
+                witness: vec![
+                    multisig_witness[0].clone(),
+                    multisig_witness[1].clone(),
+                    multisig_witness[3].clone(),
+                ],
+                result: None,
+            },
+        ];
+
+        for fixture in tx_fixtures_native {
+            let txin = BtcTxIn {
+                previous_output: OutPoint::null(),
+                script_sig: fixture.script.clone(),
+                sequence: 0xffffffff,
+                witness: fixture.witness.clone(),
+            };
+            let tx_opt = BitcoinTxInput::from_bitcoin_txin(&txin);
+            assert_eq!(tx_opt, fixture.result);
+        }
+
+        // a native p2wpkh input has the same address bits as a p2pkh input with the same key
+        let p2wpkh_input = BitcoinTxInput {
+            num_required: 1,
+            keys: vec![BitcoinPublicKey::from_slice(&pubk).unwrap()],
+            in_type: BitcoinInputType::SegwitNative,
+        };
+        let p2pkh_input = BitcoinTxInput {
+            in_type: BitcoinInputType::Standard,
+            ..p2wpkh_input.clone()
+        };
+        assert_eq!(
+            p2wpkh_input.to_address_bits(),
+            p2pkh_input.to_address_bits()
+        );
+    }
+
+    #[test]
+    fn tx_output_p2wpkh() {
+        let amount = 123;
+        let script =
+            Builder::from(hex_bytes("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap())
+                .into_script();
+        let tx_output_opt = BitcoinTxOutput::from_bitcoin_script_pubkey(
+            BitcoinNetworkType::Mainnet,
+            &script,
+            amount,
+        );
+        assert_eq!(
+            tx_output_opt,
+            Some(BitcoinTxOutput {
+                units: amount,
+                address: BitcoinAddress::from_bytes(
+                    BitcoinNetworkType::Mainnet,
+                    BitcoinAddressType::WitnessPubkeyHash,
+                    &hex_bytes("751e76e8199196d454941c45d1b3a323f1433bd6").unwrap(),
+                )
+                .unwrap(),
+            })
+        );
+
+        // p2wsh outputs are not recognized
+        let script = Builder::from(
+            hex_bytes("00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262")
+                .unwrap(),
+        )
+        .into_script();
+        assert!(BitcoinTxOutput::from_bitcoin_script_pubkey(
+            BitcoinNetworkType::Mainnet,
+            &script,
+            amount
+        )
+        .is_none());
+    }
+
     #[test]
     fn tx_output_strange() {
         let tx_fixtures_strange: Vec<ScriptFixture<Option<BitcoinTxOutput>>> = vec![
@@ -27,7 +27,7 @@ use deps::bitcoin::network::serialize::BitcoinHash;
 
 use deps::bitcoin::util::hash::bitcoin_merkle_root;
 
-use burnchains::bitcoin::address::BitcoinAddress;
+use burnchains::bitcoin::address::{BitcoinAddress, BitcoinAddressType};
 use burnchains::bitcoin::bits;
 use burnchains::bitcoin::indexer::BitcoinIndexer;
 use burnchains::bitcoin::keys::BitcoinPublicKey;
@@ -51,6 +51,12 @@ use burnchains::{
     BurnchainBlock, BurnchainHeaderHash, BurnchainTransaction, MagicBytes, Txid, MAGIC_BYTES_LENGTH,
 };
 
+use core::epoch::{
+    STACKS_EPOCH_ACTIVATIONS_MAINNET, STACKS_EPOCH_ACTIVATIONS_REGTEST,
+    STACKS_EPOCH_ACTIVATIONS_TESTNET,
+};
+use core::{StacksEpoch, StacksEpochId};
+
 use util::hash::to_hex;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -397,14 +403,51 @@ impl BitcoinBlockParser {
         }
     }
 
+    /// Are native segwit inputs and outputs recognized at this block height?  They are only
+    /// recognized from Stacks 2.1 on.
+    fn is_segwit_native_active(&self, block_height: u64) -> bool {
+        let activations = match self.network_id {
+            BitcoinNetworkType::Mainnet => STACKS_EPOCH_ACTIVATIONS_MAINNET,
+            BitcoinNetworkType::Testnet => STACKS_EPOCH_ACTIVATIONS_TESTNET,
+            BitcoinNetworkType::Regtest => STACKS_EPOCH_ACTIVATIONS_REGTEST,
+        };
+        let epochs = StacksEpoch::all(0, activations);
+        StacksEpoch::epoch_id_at(&epochs, block_height) >= StacksEpochId::Epoch21
+    }
+
+    /// Does this parsed transaction spend a native segwit input, or pay a native segwit output?
+    fn is_segwit_native(bitcoin_tx: &BitcoinTransaction) -> bool {
+        bitcoin_tx
+            .inputs
+            .iter()
+            .any(|inp| inp.in_type == BitcoinInputType::SegwitNative)
+            || bitcoin_tx
+                .outputs
+                .iter()
+                .any(|outp| outp.address.addrtype == BitcoinAddressType::WitnessPubkeyHash)
+    }
+
     /// Given a Bitcoin block, extract the transactions that have OP_RETURN <magic>.
     /// All outputs must also either be p2pkh or p2sh, and all inputs must encode
-    /// eiher a p2pkh or multisig p2sh scriptsig.
+    /// eiher a p2pkh or multisig p2sh scriptsig.  From Stacks 2.1 on, inputs may also be native
+    /// p2wpkh or p2wsh multisig witnesses; before then, transactions with them are ignored.
     pub fn parse_block(&self, block: &Block, block_height: u64) -> BitcoinBlock {
+        let segwit_native_active = self.is_segwit_native_active(block_height);
         let mut accepted_txs = vec![];
         for i in 0..block.txdata.len() {
             let tx = &block.txdata[i];
             match self.parse_tx(tx, i) {
+                Some(ref bitcoin_tx)
+                    if !segwit_native_active
+                        && BitcoinBlockParser::is_segwit_native(bitcoin_tx) =>
+                {
+                    test_debug!(
+                        "Ignore native segwit tx {:?} at height {} before Stacks 2.1",
+                        &bitcoin_tx.txid,
+                        block_height
+                    );
+                    continue;
+                }
                 Some(bitcoin_tx) => {
                     accepted_txs.push(bitcoin_tx);
                 }
@@ -479,7 +522,9 @@ mod tests {
     use util::hash::hex_bytes;
 
     use deps::bitcoin::blockdata::block::{Block, LoneBlockHeader};
-    use deps::bitcoin::blockdata::transaction::Transaction;
+    use deps::bitcoin::blockdata::opcodes::All as btc_opcodes;
+    use deps::bitcoin::blockdata::script::Builder;
+    use deps::bitcoin::blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut};
     use deps::bitcoin::network::encodable::VarInt;
     use deps::bitcoin::network::serialize::deserialize;
 
@@ -965,4 +1010,80 @@ mod tests {
             assert_eq!(parsed_block_opt, block_fixture.result);
         }
     }
+
+    #[test]
+    fn parse_block_segwit_native_gated_by_epoch() {
+        // an "id" op funded by a native p2wpkh input
+        let mut op_return = b"id+".to_vec();
+        op_return.extend_from_slice(&[0x01; 20]);
+        let segwit_tx = Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Builder::new().into_script(),
+                sequence: 0xffffffff,
+                witness: vec![
+                    hex_bytes("304402204686573485d6a7cc7e40d9a95f5e87eafbf4eabfc38863498fd022b18a4da4fc0220036d715f2bc7b16b3a264500d1944ca3cad3c3e9d87a01cf917ecf06e436952401").unwrap(),
+                    hex_bytes("02d341f728783eb93e6fb5921a1ebe9d149e941de31e403cd69afa2f0f1e698e81").unwrap(),
+                ],
+            }],
+            output: vec![
+                TxOut {
+                    value: 0,
+                    script_pubkey: Builder::new()
+                        .push_opcode(btc_opcodes::OP_RETURN)
+                        .push_slice(&op_return)
+                        .into_script(),
+                },
+                TxOut {
+                    value: 5500,
+                    script_pubkey: Builder::new()
+                        .push_opcode(btc_opcodes::OP_DUP)
+                        .push_opcode(btc_opcodes::OP_HASH160)
+                        .push_slice(&[0x02; 20])
+                        .push_opcode(btc_opcodes::OP_EQUALVERIFY)
+                        .push_opcode(btc_opcodes::OP_CHECKSIG)
+                        .into_script(),
+                },
+            ],
+        };
+        // NAME_UPDATE transaction with 3 singlesig inputs
+        let standard_tx = make_tx("010000000320a081bcd1a80d9c1945f863d29dc84278411ed74cb6dcba30541bf8d5770542020000008b483045022100be57031bf2c095945ba2876e97b3f86ee051643a29b908f22ed45ccf58620103022061e056e5f48c5a51c66604a1ca28e4bfaabab1478424c9bbb396cc6afe5c222e0141040fadbbcea0ff3b05f03195b41cd991d7a0af8bd38559943aec99cbdaf0b22cc806b9a4f07579934774cc0c155e781d45c989f94336765e88a66d91cfb9f060b0feffffff20a081bcd1a80d9c1945f863d29dc84278411ed74cb6dcba30541bf8d5770542010000008b483045022100fd9c04b330810694cb4bfef793b193f9cbfaa07325700f217b9cb03e5207005302202f07e7c9c6774c5619a043752444f6da6fd81b9d9d008ec965796d87271598de0141040fadbbcea0ff3b05f03195b41cd991d7a0af8bd38559943aec99cbdaf0b22cc806b9a4f07579934774cc0c155e781d45c989f94336765e88a66d91cfb9f060b0feffffff20a081bcd1a80d9c1945f863d29dc84278411ed74cb6dcba30541bf8d5770542040000008a47304402205e24943a40b8ef876cc218a7e8994f4be7afb7aa02403bb73510fac01b33ead3022033e5fb811c396b2fb50a825cd1d86e82eb83483901a1793d0eb15e3e9f1d1c5b814104c77f262dda02580d65c9069a8a34c56bd77325bba4110b693b90216f5a3edc0bebc8ce28d61aa86b414aa91ecb29823b11aeed06098fcd97fee4bc73d54b1e96feffffff030000000000000000296a2769642bfae543ff5672fb607fe15e16b1c3ef38737c631c7c5d911c6617993c21fba731363f1cfe6c6b0000000000001976a914395f3643cea07ec4eec73b4d9a973dcce56b9bf188acc5120100000000001976a9149f2660e75380675206b6f1e2b4f106ae33266be488ac00000000").unwrap();
+
+        let header = make_block_header("000000209cef4ccd19f4294dd5c762aab6d9577fb4412cd4c0a662a953a8b7969697bc1ddab52e6f053758022fb92f04388eb5fdd87046776e9c406880e728b48e6930aff462fc5bffff7f2000000000").unwrap();
+        let block = Block {
+            header: header.header,
+            txdata: vec![standard_tx, segwit_tx],
+        };
+
+        let parser = BitcoinBlockParser::new(BitcoinNetworkType::Testnet, MagicBytes([105, 100])); // "id"
+
+        // the testnet block before Stacks 2.1 ignores the segwit-funded op
+        let parsed_block = parser.parse_block(&block, 2_422_100);
+        assert_eq!(parsed_block.txs.len(), 1);
+        assert_eq!(parsed_block.txs[0].vtxindex, 0);
+        assert_eq!(
+            parsed_block.txs[0].inputs[0].in_type,
+            BitcoinInputType::Standard
+        );
+
+        // from Stacks 2.1 on, it is accepted
+        let parsed_block = parser.parse_block(&block, 2_422_101);
+        assert_eq!(parsed_block.txs.len(), 2);
+        assert_eq!(parsed_block.txs[1].vtxindex, 1);
+        assert_eq!(parsed_block.txs[1].opcode, '+' as u8);
+        assert_eq!(parsed_block.txs[1].data, vec![0x01; 20]);
+        assert_eq!(
+            parsed_block.txs[1].inputs,
+            vec![BitcoinTxInput {
+                keys: vec![BitcoinPublicKey::from_hex(
+                    "02d341f728783eb93e6fb5921a1ebe9d149e941de31e403cd69afa2f0f1e698e81"
+                )
+                .unwrap()],
+                num_required: 1,
+                in_type: BitcoinInputType::SegwitNative,
+            }]
+        );
+    }
 }
@@ -15,7 +15,9 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use rand::{thread_rng, Rng};
+use std::cmp;
 use std::fs;
+use std::mem;
 use std::net;
 use std::net::Shutdown;
 use std::ops::Deref;
@@ -92,6 +94,8 @@ pub struct BitcoinIndexerConfig {
     pub spv_headers_path: String,
     pub first_block: u64,
     pub magic_bytes: MagicBytes,
+    /// other (host, port) Bitcoin peers to try, in order, if the current one is unreachable
+    pub fallback_peers: Vec<(String, u16)>,
 }
 
 #[derive(Debug)]
@@ -125,6 +129,7 @@ impl BitcoinIndexerConfig {
             spv_headers_path: "./spv-headers.dat".to_string(),
             first_block: FIRST_BLOCK_MAINNET,
             magic_bytes: BLOCKSTACK_MAGIC_MAINNET.clone(),
+            fallback_peers: vec![],
         }
     }
 
@@ -140,6 +145,7 @@ impl BitcoinIndexerConfig {
             spv_headers_path: spv_headers_path,
             first_block: 0,
             magic_bytes: BLOCKSTACK_MAGIC_MAINNET.clone(),
+            fallback_peers: vec![],
         }
     }
 
@@ -292,6 +298,7 @@ impl BitcoinIndexerConfig {
                     spv_headers_path: spv_headers_path.to_string(),
                     first_block: first_block,
                     magic_bytes: blockstack_magic,
+                    fallback_peers: vec![],
                 };
 
                 Ok(cfg)
@@ -347,6 +354,24 @@ impl BitcoinIndexer {
         }
     }
 
+    /// Switch to the next fallback Bitcoin peer, if there is one.  The peer we leave goes to the
+    /// back of the fallback list, so repeated failovers cycle through all peers round-robin.
+    /// Returns true if we switched peers.
+    pub fn failover_peer(&mut self) -> bool {
+        if self.config.fallback_peers.len() == 0 {
+            return false;
+        }
+        let (next_host, next_port) = self.config.fallback_peers.remove(0);
+        let prev_host = mem::replace(&mut self.config.peer_host, next_host);
+        let prev_port = mem::replace(&mut self.config.peer_port, next_port);
+        warn!(
+            "Failing over from Bitcoin peer {}:{} to {}:{}",
+            &prev_host, prev_port, &self.config.peer_host, self.config.peer_port
+        );
+        self.config.fallback_peers.push((prev_host, prev_port));
+        true
+    }
+
     /// (re)connect to our configured network peer.
     /// Sets self.runtime.sock to a new socket referring to our configured
     /// Bitcoin peer.  If we fail to connect, this method sets the socket
@@ -539,6 +564,7 @@ impl BitcoinIndexer {
         canonical_spv_client: &SpvClient,
         reorg_headers_path: &str,
         start_block: u64,
+        end_block: u64,
     ) -> Result<SpvClient, btc_error> {
         if PathBuf::from(&reorg_headers_path).exists() {
             fs::remove_file(&reorg_headers_path).map_err(|e| {
@@ -551,7 +577,7 @@ impl BitcoinIndexer {
         let mut reorg_spv_client = SpvClient::new(
             &reorg_headers_path,
             start_block,
-            Some(start_block + REORG_BATCH_SIZE),
+            Some(end_block),
             self.runtime.network_id,
             true,
             true,
@@ -599,69 +625,73 @@ impl BitcoinIndexer {
             e
         })?;
 
+        // nothing at or below the last checkpoint can be reorged, so never search below it
+        let reorg_floor = get_last_checkpoint_height(
+            self.runtime.network_id,
+            canonical_end_block.saturating_sub(1),
+        );
+
         // bootstrap reorg client
-        let mut start_block = canonical_end_block.saturating_sub(REORG_BATCH_SIZE);
+        let mut start_block = cmp::max(
+            canonical_end_block.saturating_sub(REORG_BATCH_SIZE),
+            reorg_floor,
+        );
+        let mut end_block = start_block + REORG_BATCH_SIZE;
+        let mut search_distance = REORG_BATCH_SIZE;
         let mut reorg_spv_client =
-            self.setup_reorg_headers(&orig_spv_client, reorg_headers_path, start_block)?;
-        let mut discontiguous_header_error_count = 0;
+            self.setup_reorg_headers(&orig_spv_client, reorg_headers_path, start_block, end_block)?;
 
         while !found_common_ancestor {
             debug!(
                 "Search for reorg'ed Bitcoin headers from {} - {}",
-                start_block,
-                start_block + REORG_BATCH_SIZE
+                start_block, end_block
             );
 
             // get new headers, starting off of start_block.  Feed them into the given
             // reorg_spv_client.
-            match load_reorg_headers(
-                self,
-                &mut reorg_spv_client,
-                start_block,
-                Some(start_block + REORG_BATCH_SIZE),
-            ) {
+            match load_reorg_headers(self, &mut reorg_spv_client, start_block, Some(end_block)) {
                 Ok(_) => {}
-                Err(btc_error::NoncontiguousHeader) | Err(btc_error::InvalidPoW) => {
+                Err(btc_error::NoncontiguousHeader)
+                | Err(btc_error::InvalidPoW)
+                | Err(btc_error::CheckpointMismatch) => {
                     warn!(
                         "Received invalid headers from {} - {} -- possible reorg in progress",
-                        start_block,
-                        start_block + REORG_BATCH_SIZE
+                        start_block, end_block
                     );
-                    if start_block == 0 {
-                        // reorg all the way back to genesis
-                        new_tip = 0;
+                    if start_block <= reorg_floor {
+                        // reorg all the way back to the last checkpoint (or genesis)
+                        new_tip = reorg_floor;
                         break;
                     }
 
-                    // try again
-                    discontiguous_header_error_count += 1;
-                    start_block = start_block
-                        .saturating_sub(REORG_BATCH_SIZE * discontiguous_header_error_count);
+                    // try again, further back
+                    search_distance = search_distance.saturating_mul(2);
+                    end_block = start_block + REORG_BATCH_SIZE;
+                    start_block =
+                        cmp::max(start_block.saturating_sub(search_distance), reorg_floor);
                     reorg_spv_client = self.setup_reorg_headers(
                         &orig_spv_client,
                         reorg_headers_path,
                         start_block,
+                        end_block,
                     )?;
                     continue;
                 }
                 Err(e) => {
                     error!(
                         "Failed to fetch Bitcoin headers from {} - {}: {:?}",
-                        start_block,
-                        start_block + REORG_BATCH_SIZE,
-                        &e
+                        start_block, end_block, &e
                     );
                     return Err(e);
                 }
             }
 
             let reorg_headers = reorg_spv_client
-                .read_block_headers(start_block, start_block + REORG_BATCH_SIZE)
+                .read_block_headers(start_block, end_block)
                 .map_err(|e| {
                     error!(
                         "Failed to read reorg Bitcoin headers from {} to {}",
-                        start_block,
-                        start_block + REORG_BATCH_SIZE
+                        start_block, end_block
                     );
                     e
                 })?;
@@ -670,28 +700,27 @@ impl BitcoinIndexer {
                 // chain shrank considerably
                 info!(
                     "Missing Bitcoin headers in block range {}-{} -- did the Bitcoin chain shrink?",
-                    start_block,
-                    start_block + REORG_BATCH_SIZE
+                    start_block, end_block
                 );
-                if start_block == 0 {
+                if start_block <= reorg_floor {
                     // reorg chain is empty
-                    new_tip = 0;
+                    new_tip = reorg_floor;
                     break;
                 }
 
-                start_block = start_block.saturating_sub(REORG_BATCH_SIZE);
-                reorg_spv_client.set_scan_range(start_block, Some(start_block + REORG_BATCH_SIZE));
+                start_block = cmp::max(start_block.saturating_sub(REORG_BATCH_SIZE), reorg_floor);
+                end_block = start_block + REORG_BATCH_SIZE;
+                reorg_spv_client.set_scan_range(start_block, Some(end_block));
                 continue;
             }
 
             // got reorg headers.  Find the equivalent headers in our canonical history
             let canonical_headers = orig_spv_client
-                .read_block_headers(start_block, start_block + REORG_BATCH_SIZE)
+                .read_block_headers(start_block, end_block)
                 .map_err(|e| {
                     error!(
                         "Failed to read canonical headers from {} to {}",
-                        start_block,
-                        start_block + REORG_BATCH_SIZE
+                        start_block, end_block
                     );
                     e
                 })?;
@@ -741,14 +770,23 @@ impl BitcoinIndexer {
                 start_block, max_height
             );
 
-            if start_block == 0 {
+            if start_block <= reorg_floor {
+                // the fork starts right after the last checkpoint (or genesis)
+                new_tip = reorg_floor;
                 break;
             }
 
-            // try again
-            start_block = start_block.saturating_sub(REORG_BATCH_SIZE);
-            reorg_spv_client =
-                self.setup_reorg_headers(&orig_spv_client, reorg_headers_path, start_block)?;
+            // the fork is deeper than we've looked so far.  Search twice as far back, making
+            // sure the new range still overlaps the one we just searched.
+            search_distance = search_distance.saturating_mul(2);
+            end_block = start_block + REORG_BATCH_SIZE;
+            start_block = cmp::max(start_block.saturating_sub(search_distance), reorg_floor);
+            reorg_spv_client = self.setup_reorg_headers(
+                &orig_spv_client,
+                reorg_headers_path,
+                start_block,
+                end_block,
+            )?;
         }
 
         debug!("Bitcoin headers history is consistent up to {}", new_tip);
@@ -813,7 +851,7 @@ impl BurnchainIndexer for BitcoinIndexer {
         let mut indexer = BitcoinIndexer::from_file(bitcoin_network_id, &conf_path_str)
             .map_err(burnchain_error::Bitcoin)?;
 
-        SpvClient::new(
+        let mut spv_client = SpvClient::new(
             &indexer.config.spv_headers_path,
             0,
             None,
@@ -823,6 +861,11 @@ impl BurnchainIndexer for BitcoinIndexer {
         )
         .map_err(burnchain_error::Bitcoin)?;
 
+        // make sure the headers we stored before are still sound before building on them
+        spv_client
+            .check_integrity()
+            .map_err(burnchain_error::Bitcoin)?;
+
         indexer.connect()?;
         Ok(indexer)
     }
@@ -1399,6 +1442,7 @@ mod test {
             spv_headers_path: "/tmp/test_indexer_sync_headers.db".to_string(),
             first_block: 0,
             magic_bytes: MagicBytes([105, 100]),
+            fallback_peers: vec![],
         };
 
         if fs::metadata(&indexer_conf.spv_headers_path).is_ok() {
@@ -1409,4 +1453,42 @@ mod test {
         let last_block = indexer.sync_headers(0, None).unwrap();
         eprintln!("sync'ed to block {}", last_block);
     }
+
+    #[test]
+    fn test_failover_peer() {
+        let mut indexer_conf = BitcoinIndexerConfig::default_regtest("/tmp/unused".to_string());
+        let mut indexer = BitcoinIndexer::new(
+            indexer_conf.clone(),
+            BitcoinIndexerRuntime::new(BitcoinNetworkType::Regtest),
+        );
+
+        // nothing to fail over to
+        assert!(!indexer.failover_peer());
+        assert_eq!(indexer.config, indexer_conf);
+
+        indexer_conf.fallback_peers = vec![
+            ("127.0.0.2".to_string(), 18445),
+            ("127.0.0.3".to_string(), 18446),
+        ];
+        let mut indexer = BitcoinIndexer::new(
+            indexer_conf,
+            BitcoinIndexerRuntime::new(BitcoinNetworkType::Regtest),
+        );
+
+        // peers are visited round-robin
+        let mut visited = vec![];
+        for _ in 0..3 {
+            assert!(indexer.failover_peer());
+            visited.push((indexer.config.peer_host.clone(), indexer.config.peer_port));
+        }
+        assert_eq!(
+            visited,
+            vec![
+                ("127.0.0.2".to_string(), 18445),
+                ("127.0.0.3".to_string(), 18446),
+                ("127.0.0.1".to_string(), 18444),
+            ]
+        );
+        assert_eq!(indexer.config.fallback_peers.len(), 2);
+    }
 }
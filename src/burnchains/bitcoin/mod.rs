@@ -90,6 +90,8 @@ pub enum Error {
     BlockchainHeight,
     /// Request timed out
     TimedOut,
+    /// Header does not match a hard-coded checkpoint
+    CheckpointMismatch,
 }
 
 impl fmt::Display for Error {
@@ -114,6 +116,7 @@ impl fmt::Display for Error {
             Error::ConfigError(ref e_str) => fmt::Display::fmt(e_str, f),
             Error::BlockchainHeight => write!(f, "Value is beyond the end of the blockchain"),
             Error::TimedOut => write!(f, "Request timed out"),
+            Error::CheckpointMismatch => write!(f, "Header does not match checkpoint"),
         }
     }
 }
@@ -140,6 +143,7 @@ impl error::Error for Error {
             Error::ConfigError(ref _e_str) => None,
             Error::BlockchainHeight => None,
             Error::TimedOut => None,
+            Error::CheckpointMismatch => None,
         }
     }
 }
@@ -167,6 +171,8 @@ pub struct BitcoinTxOutput {
 pub enum BitcoinInputType {
     Standard,
     SegwitP2SH,
+    /// Native (bech32) p2wpkh or p2wsh input -- the scriptsig is empty
+    SegwitNative,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
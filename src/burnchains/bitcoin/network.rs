@@ -178,6 +178,8 @@ impl BitcoinIndexer {
     pub fn connect_handshake_backoff(&mut self) -> Result<u64, btc_error> {
         let mut backoff: f64 = 1.0;
         let mut rng = thread_rng();
+        let num_peers = self.config.fallback_peers.len() + 1;
+        let mut failed_peers = 0;
 
         loop {
             let connection_result = self.connect();
@@ -213,6 +215,12 @@ impl BitcoinIndexer {
                 }
             }
 
+            // try every other configured peer before backing off
+            failed_peers += 1;
+            if self.failover_peer() && failed_peers % num_peers != 0 {
+                continue;
+            }
+
             // don't sleep more than 60 seconds
             if backoff > 60.0 {
                 backoff = 60.0;
@@ -64,6 +64,106 @@ const GENESIS_BLOCK_HASH_REGTEST: &'static str =
 pub const BLOCK_DIFFICULTY_CHUNK_SIZE: u64 = 2016;
 const BLOCK_DIFFICULTY_INTERVAL: u32 = 14 * 24 * 60 * 60; // two weeks, in seconds
 
+// testnet lets a block be mined at the minimum difficulty if it arrives this long after its parent
+const TESTNET_MIN_DIFFICULTY_BLOCK_DELAY: u32 = 20 * 60; // twenty minutes, in seconds
+const TESTNET_MIN_DIFFICULTY_BITS: u32 = 0x1d00ffff;
+
+// how many headers to load at once when checking the headers DB
+const INTEGRITY_CHECK_BATCH_SIZE: u64 = 2016;
+
+// Well-known (height, block hash) pairs.  Any header chain that disagrees with one of these is
+// rejected, and we never look for a reorg below the highest one we have.
+const CHECKPOINTS_MAINNET: &[(u64, &'static str)] = &[
+    (
+        11111,
+        "0000000069e244f73d78e8fd29ba2fd2ed618bd6fa2ee92559f542fdb26e7c1d",
+    ),
+    (
+        33333,
+        "000000002dd5588a74784eaa7ab0507a18ad16a236e7b1ce69f00d7ddfb5d0a6",
+    ),
+    (
+        74000,
+        "0000000000573993a3c9e41ce34471c079dcf5f52a0e824a81e7f953b8661a20",
+    ),
+    (
+        105000,
+        "00000000000291ce28027faea320c8d2b054b2e0fe44a773f3eefb151d6bdc97",
+    ),
+    (
+        134444,
+        "00000000000005b12ffd4cd315cd34ffd4a594f430ac814c91184a0d42d2b0fe",
+    ),
+    (
+        168000,
+        "000000000000099e61ea72015e79632f216fe6cb33d7899acb35b75c8303b763",
+    ),
+    (
+        193000,
+        "000000000000059f452a5f7340de6682a977387c17010ff6e6c3bd83ca8b1317",
+    ),
+    (
+        210000,
+        "000000000000048b95347e83192f69cf0366076336c639f9b7228e9ba171342e",
+    ),
+    (
+        216116,
+        "00000000000001b4f4b433e81ee46494af945cf96014816a4e2370f11b23df4e",
+    ),
+    (
+        225430,
+        "00000000000001c108384350f74090433e7fcf79a606b8e797f065b130575932",
+    ),
+    (
+        250000,
+        "000000000000003887df1f29024b06fc2200b55f8af8f35453d7be294df2d214",
+    ),
+    (
+        279000,
+        "0000000000000001ae8c72a0b0c301f67e3afca10e819efa9041e458e9bd7e40",
+    ),
+    (
+        295000,
+        "00000000000000004d9b4ef50f0f9d686fd69db2e03af35a100370c64632a983",
+    ),
+];
+
+const CHECKPOINTS_TESTNET: &[(u64, &'static str)] = &[(
+    546,
+    "000000002a936ca763904c3c35fce2f3556c559c0214345d31b1bcebf76acb70",
+)];
+
+const CHECKPOINTS_REGTEST: &[(u64, &'static str)] = &[];
+
+fn get_checkpoints(network_id: BitcoinNetworkType) -> &'static [(u64, &'static str)] {
+    match network_id {
+        BitcoinNetworkType::Mainnet => CHECKPOINTS_MAINNET,
+        BitcoinNetworkType::Testnet => CHECKPOINTS_TESTNET,
+        BitcoinNetworkType::Regtest => CHECKPOINTS_REGTEST,
+    }
+}
+
+/// Get the block hash a header at the given height must have, if there is a checkpoint there
+pub fn get_checkpoint(network_id: BitcoinNetworkType, height: u64) -> Option<Sha256dHash> {
+    get_checkpoints(network_id)
+        .iter()
+        .find(|(checkpoint_height, _)| *checkpoint_height == height)
+        .map(|(_, hash_hex)| {
+            Sha256dHash::from_hex(hash_hex).expect("FATAL: invalid checkpoint block hash")
+        })
+}
+
+/// Get the height of the highest checkpoint at or below the given height (0 if there is none).
+/// Headers at or below this height can never be reorged.
+pub fn get_last_checkpoint_height(network_id: BitcoinNetworkType, max_height: u64) -> u64 {
+    get_checkpoints(network_id)
+        .iter()
+        .map(|(checkpoint_height, _)| *checkpoint_height)
+        .filter(|checkpoint_height| *checkpoint_height <= max_height)
+        .max()
+        .unwrap_or(0)
+}
+
 const SPV_SQL: &[&'static str] = &[r#"
     CREATE TABLE headers(
         version INTEGER NOT NULL,
@@ -270,8 +370,57 @@ impl SpvClient {
         return Ok(());
     }
 
+    /// Verify that the given headers agree with every checkpoint they cover.
+    /// `first_height` is the height of the first header.
+    fn validate_header_checkpoints(
+        network_id: BitcoinNetworkType,
+        first_height: u64,
+        headers: &Vec<LoneBlockHeader>,
+    ) -> Result<(), btc_error> {
+        for (i, header) in headers.iter().enumerate() {
+            let height = first_height + (i as u64);
+            if let Some(checkpoint_hash) = get_checkpoint(network_id, height) {
+                if header.header.bitcoin_hash() != checkpoint_hash {
+                    warn!(
+                        "Bad SPV header for block {}: hash {} != checkpoint {}",
+                        height,
+                        header.header.bitcoin_hash(),
+                        checkpoint_hash
+                    );
+                    return Err(btc_error::CheckpointMismatch);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Is this header a testnet block mined at the minimum difficulty because its parent is
+    /// more than twenty minutes older than it?
+    fn is_min_difficulty_block(
+        &self,
+        block_height: u64,
+        header: &LoneBlockHeader,
+    ) -> Result<bool, btc_error> {
+        if self.network_id != BitcoinNetworkType::Testnet
+            || header.header.bits != TESTNET_MIN_DIFFICULTY_BITS
+            || block_height == 0
+        {
+            return Ok(false);
+        }
+        match self.read_block_header(block_height - 1)? {
+            Some(parent) => Ok(header.header.time
+                > parent
+                    .header
+                    .time
+                    .saturating_add(TESTNET_MIN_DIFFICULTY_BLOCK_DELAY)),
+            None => Ok(false),
+        }
+    }
+
     /// Verify that the given headers have the correct amount of work to be appended to our
-    /// local header chain.  Checks the difficulty between [interval, interval+1]
+    /// local header chain.  Checks the difficulty of every interval in [interval_start,
+    /// interval_end).  The first interval's difficulty is fixed by the genesis block, so it is not
+    /// checked.
     fn validate_header_work(
         &self,
         interval_start: u64,
@@ -283,7 +432,8 @@ impl SpvClient {
             interval_end * BLOCK_DIFFICULTY_CHUNK_SIZE
         );
         assert!(interval_start <= interval_end);
-        if interval_start == 0 {
+        let interval_start = cmp::max(interval_start, 1);
+        if interval_start >= interval_end {
             return Ok(());
         }
 
@@ -305,18 +455,22 @@ impl SpvClient {
                         return Ok(());
                     }
                     Some(header_i) => {
-                        if header_i.header.bits != bits {
+                        let target = if header_i.header.bits == bits {
+                            difficulty
+                        } else if self.is_min_difficulty_block(block_height, &header_i)? {
+                            header_i.header.target()
+                        } else {
                             error!("bits mismatch at block {} of {} (offset {} interval {} of {}-{}): {:08x} != {:08x}",
                                    block_height, self.headers_path, block_height % BLOCK_DIFFICULTY_CHUNK_SIZE, i, interval_start, interval_end, header_i.header.bits, bits);
                             return Err(btc_error::InvalidPoW);
-                        }
+                        };
                         let header_hash = header_i.header.bitcoin_hash().into_le();
-                        if difficulty <= header_hash {
+                        if target <= header_hash {
                             error!(
                                 "block {} hash {} has less work than difficulty {} in {}",
                                 block_height,
                                 header_i.header.bitcoin_hash(),
-                                difficulty,
+                                target,
                                 self.headers_path
                             );
                             return Err(btc_error::InvalidPoW);
@@ -490,10 +644,11 @@ impl SpvClient {
                     e
                 })?;
 
-            // check work
+            // check work in every difficulty interval the new headers touch, including the ones
+            // they span if they cross a retarget boundary
             let chain_tip = self.get_headers_height()?;
             self.validate_header_work(
-                (chain_tip - 1) / BLOCK_DIFFICULTY_CHUNK_SIZE,
+                (insert_height + 1) / BLOCK_DIFFICULTY_CHUNK_SIZE,
                 chain_tip / BLOCK_DIFFICULTY_CHUNK_SIZE + 1,
             )
             .map_err(|e| {
@@ -591,6 +746,8 @@ impl SpvClient {
             e
         })?;
 
+        SpvClient::validate_header_checkpoints(self.network_id, start_height + 1, &block_headers)?;
+
         let parent_header = match self.read_block_header(start_height)? {
             Some(header) => header,
             None => {
@@ -644,6 +801,8 @@ impl SpvClient {
             e
         })?;
 
+        SpvClient::validate_header_checkpoints(self.network_id, start_height + 1, &block_headers)?;
+
         match self.read_block_header(end_height)? {
             Some(child_header) => {
                 // contiguous?
@@ -706,6 +865,71 @@ impl SpvClient {
         Ok(())
     }
 
+    /// Check the headers DB on startup: the headers must be contiguous, start at the genesis
+    /// block, and agree with every checkpoint.  Everything from the first bad header onward is
+    /// dropped so it can be fetched again.
+    /// Returns the height of the highest header that passed.
+    pub fn check_integrity(&mut self) -> Result<u64, btc_error> {
+        assert!(self.readwrite, "SPV header DB is open read-only");
+
+        let headers_height = self.get_headers_height()?;
+        let mut prev_header: Option<LoneBlockHeader> = None;
+        let mut bad_height = None;
+        let mut height = 0;
+
+        while height < headers_height && bad_height.is_none() {
+            let headers = self.read_block_headers(height, height + INTEGRITY_CHECK_BATCH_SIZE)?;
+            if headers.len() == 0 {
+                warn!(
+                    "Missing SPV header for block {} in {}",
+                    height, &self.headers_path
+                );
+                bad_height = Some(height);
+                break;
+            }
+
+            for (i, header) in headers.iter().enumerate() {
+                let header_height = height + (i as u64);
+                if let Some(ref prev) = prev_header {
+                    if header.header.prev_blockhash != prev.header.bitcoin_hash() {
+                        warn!(
+                            "Discontiguous SPV header for block {} in {}",
+                            header_height, &self.headers_path
+                        );
+                        bad_height = Some(header_height);
+                        break;
+                    }
+                }
+                if let Some(checkpoint_hash) = get_checkpoint(self.network_id, header_height) {
+                    if header.header.bitcoin_hash() != checkpoint_hash {
+                        warn!(
+                            "SPV header for block {} in {} does not match checkpoint {}",
+                            header_height, &self.headers_path, checkpoint_hash
+                        );
+                        bad_height = Some(header_height);
+                        break;
+                    }
+                }
+                prev_header = Some(header.clone());
+            }
+            height += headers.len() as u64;
+        }
+
+        match bad_height {
+            Some(bad_height) => {
+                // the genesis header is always (re)written on open, so this is at least 1
+                let last_good_height = bad_height.saturating_sub(1);
+                warn!(
+                    "SPV headers DB {} is corrupt at block {}; dropping headers after {}",
+                    &self.headers_path, bad_height, last_good_height
+                );
+                self.drop_headers(last_good_height)?;
+                Ok(last_good_height)
+            }
+            None => Ok(headers_height.saturating_sub(1)),
+        }
+    }
+
     /// Determine the target difficult over a given difficulty adjustment interval
     /// the `interval` parameter is the difficulty interval -- a 2016-block interval.
     /// Returns (new bits, new target)
@@ -1236,6 +1460,150 @@ mod test {
             .unwrap();
     }
 
+    #[test]
+    fn test_spv_checkpoints() {
+        assert_eq!(
+            get_last_checkpoint_height(BitcoinNetworkType::Mainnet, 0),
+            0
+        );
+        assert_eq!(
+            get_last_checkpoint_height(BitcoinNetworkType::Mainnet, 11110),
+            0
+        );
+        assert_eq!(
+            get_last_checkpoint_height(BitcoinNetworkType::Mainnet, 11111),
+            11111
+        );
+        assert_eq!(
+            get_last_checkpoint_height(BitcoinNetworkType::Mainnet, 250001),
+            250000
+        );
+        assert_eq!(
+            get_last_checkpoint_height(BitcoinNetworkType::Regtest, 1000000),
+            0
+        );
+
+        assert_eq!(
+            get_checkpoint(BitcoinNetworkType::Testnet, 546),
+            Some(
+                Sha256dHash::from_hex(
+                    "000000002a936ca763904c3c35fce2f3556c559c0214345d31b1bcebf76acb70"
+                )
+                .unwrap()
+            )
+        );
+        assert_eq!(get_checkpoint(BitcoinNetworkType::Testnet, 547), None);
+
+        // a header at a checkpoint height must have the checkpoint's hash
+        let headers = vec![get_genesis_regtest_header()];
+        SpvClient::validate_header_checkpoints(BitcoinNetworkType::Testnet, 545, &headers).unwrap();
+        match SpvClient::validate_header_checkpoints(BitcoinNetworkType::Testnet, 546, &headers) {
+            Err(btc_error::CheckpointMismatch) => {}
+            x => panic!("Expected checkpoint mismatch, got {:?}", &x),
+        }
+    }
+
+    #[test]
+    fn test_spv_check_integrity() {
+        let path = "/tmp/test-spv-check_integrity.dat";
+        if fs::metadata(path).is_ok() {
+            fs::remove_file(path).unwrap();
+        }
+
+        let headers = vec![
+            LoneBlockHeader {
+                header: BlockHeader {
+                    bits: 545259519,
+                    merkle_root: Sha256dHash::from_hex(
+                        "20bee96458517fc5082a9720ce6207b5742f2b18e4e0a7e7373342725d80f88c",
+                    )
+                    .unwrap(),
+                    nonce: 2,
+                    prev_blockhash: Sha256dHash::from_hex(
+                        "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206",
+                    )
+                    .unwrap(),
+                    time: 1587626881,
+                    version: 0x20000000,
+                },
+                tx_count: VarInt(0),
+            },
+            LoneBlockHeader {
+                header: BlockHeader {
+                    bits: 545259519,
+                    merkle_root: Sha256dHash::from_hex(
+                        "39d1a6f1ee7a5903797f92ec89e4c58549013f38114186fc2eb6e5218cb2d0ac",
+                    )
+                    .unwrap(),
+                    nonce: 1,
+                    prev_blockhash: Sha256dHash::from_hex(
+                        "606d31daaaa5919f3720d8440dd99d31f2a4e4189c65879f19ae43268425e74b",
+                    )
+                    .unwrap(),
+                    time: 1587626882,
+                    version: 0x20000000,
+                },
+                tx_count: VarInt(0),
+            },
+            LoneBlockHeader {
+                header: BlockHeader {
+                    bits: 545259519,
+                    merkle_root: Sha256dHash::from_hex(
+                        "a7e04ed25f589938eb5627abb7b5913dd77b8955bcdf72d7f111d0a71e346e47",
+                    )
+                    .unwrap(),
+                    nonce: 4,
+                    prev_blockhash: Sha256dHash::from_hex(
+                        "2fa2f451ac27f0e5cd3760ba6cdf34ef46adb76a44d96bc0f3bf3e713dd955f0",
+                    )
+                    .unwrap(),
+                    time: 1587626882,
+                    version: 0x20000000,
+                },
+                tx_count: VarInt(0),
+            },
+        ];
+
+        let mut spv_client =
+            SpvClient::new(path, 0, None, BitcoinNetworkType::Regtest, true, false).unwrap();
+        spv_client
+            .insert_block_headers_after(0, headers.clone())
+            .unwrap();
+
+        // intact
+        assert_eq!(spv_client.check_integrity().unwrap(), 3);
+        assert_eq!(spv_client.get_headers_height().unwrap(), 4);
+
+        // a gap drops everything after it
+        {
+            let mut tx = spv_client.tx_begin().unwrap();
+            SpvClient::insert_block_header(&mut tx, headers[0].header.clone(), 5).unwrap();
+            tx.commit().unwrap();
+        }
+        assert_eq!(spv_client.get_headers_height().unwrap(), 6);
+        assert_eq!(spv_client.check_integrity().unwrap(), 3);
+        assert_eq!(spv_client.get_headers_height().unwrap(), 4);
+
+        // a header that doesn't build on its parent drops it and everything after it
+        {
+            let mut tx = spv_client.tx_begin().unwrap();
+            SpvClient::insert_block_header(&mut tx, headers[2].header.clone(), 2).unwrap();
+            tx.commit().unwrap();
+        }
+        assert_eq!(spv_client.check_integrity().unwrap(), 1);
+        assert_eq!(spv_client.get_headers_height().unwrap(), 2);
+        assert_eq!(
+            spv_client.read_block_headers(1, 10).unwrap(),
+            vec![headers[0].clone()]
+        );
+
+        // and the headers can be fetched again
+        spv_client
+            .insert_block_headers_after(1, headers[1..].to_vec())
+            .unwrap();
+        assert_eq!(spv_client.check_integrity().unwrap(), 3);
+    }
+
     #[test]
     fn test_spv_check_pow() {
         if !env::var("BLOCKSTACK_SPV_HEADERS_DB").is_ok() {
@@ -50,6 +50,8 @@ use burnchains::bitcoin::address::address_type_to_version_byte;
 use burnchains::bitcoin::address::to_c32_version_byte;
 use burnchains::bitcoin::address::BitcoinAddress;
 use burnchains::bitcoin::address::BitcoinAddressType;
+use burnchains::bitcoin::indexer::BITCOIN_MAINNET as BITCOIN_NETWORK_ID_MAINNET;
+use burnchains::bitcoin::indexer::BITCOIN_TESTNET as BITCOIN_NETWORK_ID_TESTNET;
 use burnchains::bitcoin::BitcoinNetworkType;
 use burnchains::bitcoin::{BitcoinInputType, BitcoinTxInput, BitcoinTxOutput};
 
@@ -57,7 +59,7 @@ use chainstate::burn::db::sortdb::{PoxId, SortitionDB, SortitionHandleConn, Sort
 use chainstate::burn::distribution::BurnSamplePoint;
 use chainstate::burn::operations::{
     BlockstackOperation, BlockstackOperationType, LeaderBlockCommitOp, LeaderKeyRegisterOp,
-    UserBurnSupportOp,
+    PreStxOp, TransferStxOp, UserBurnSupportOp,
 };
 use chainstate::burn::{BlockSnapshot, Opcodes};
 
@@ -76,6 +78,12 @@ use util::hash::to_hex;
 use util::log;
 use util::vrf::VRFPublicKey;
 
+use core::epoch::{
+    STACKS_EPOCH_ACTIVATIONS_MAINNET, STACKS_EPOCH_ACTIVATIONS_REGTEST,
+    STACKS_EPOCH_ACTIVATIONS_TESTNET,
+};
+use core::StacksEpoch;
+use core::StacksEpochId;
 use core::NETWORK_ID_MAINNET;
 use core::NETWORK_ID_TESTNET;
 use core::PEER_VERSION;
@@ -124,11 +132,13 @@ impl BurnchainStateTransition {
         let mut all_user_burns: HashMap<Txid, UserBurnSupportOp> = HashMap::new();
         let mut all_block_commits: HashMap<Txid, LeaderBlockCommitOp> = HashMap::new();
 
-        // accept all leader keys we found.
+        // accept all leader keys and STX operations we found.
         // don't treat block commits and user burn supports just yet.
         for i in 0..block_ops.len() {
             match block_ops[i] {
-                BlockstackOperationType::LeaderKeyRegister(_) => {
+                BlockstackOperationType::LeaderKeyRegister(_)
+                | BlockstackOperationType::PreStx(_)
+                | BlockstackOperationType::TransferStx(_) => {
                     accepted_ops.push(block_ops[i].clone());
                 }
                 BlockstackOperationType::LeaderBlockCommit(ref op) => {
@@ -229,6 +239,22 @@ impl BurnchainSigner {
                     }
                 }
             }
+            BitcoinInputType::SegwitNative => {
+                // a native p2wpkh witness program is the p2pkh hash of the same key
+                if inp.num_required == 1 && inp.keys.len() == 1 {
+                    BurnchainSigner {
+                        hash_mode: AddressHashMode::SerializeP2PKH,
+                        num_sigs: inp.num_required,
+                        public_keys: inp.keys.clone(),
+                    }
+                } else {
+                    BurnchainSigner {
+                        hash_mode: AddressHashMode::SerializeP2WSH,
+                        num_sigs: inp.num_required,
+                        public_keys: inp.keys.clone(),
+                    }
+                }
+            }
             BitcoinInputType::SegwitP2SH => {
                 if inp.num_required == 1 && inp.keys.len() == 1 {
                     BurnchainSigner {
@@ -392,6 +418,16 @@ impl Burnchain {
         )
     }
 
+    /// Get this network's Stacks epochs.  Stacks 2.0 starts at the first burnchain block.
+    pub fn get_stacks_epochs(&self) -> Vec<StacksEpoch> {
+        let activations = match self.network_id {
+            BITCOIN_NETWORK_ID_MAINNET => STACKS_EPOCH_ACTIVATIONS_MAINNET,
+            BITCOIN_NETWORK_ID_TESTNET => STACKS_EPOCH_ACTIVATIONS_TESTNET,
+            _ => STACKS_EPOCH_ACTIVATIONS_REGTEST,
+        };
+        StacksEpoch::all(self.first_block_height, activations)
+    }
+
     #[cfg(test)]
     pub fn default_unittest(
         first_block_height: u64,
@@ -516,6 +552,7 @@ impl Burnchain {
             first_block_height,
             &first_block_header_hash,
             first_block_header_timestamp,
+            &self.get_stacks_epochs(),
             readwrite,
         )?;
         let burnchaindb = BurnchainDB::connect(
@@ -550,11 +587,15 @@ impl Burnchain {
         Ok((sortition_db, burnchain_db))
     }
 
-    /// Try to parse a burnchain transaction into a Blockstack operation
+    /// Try to parse a burnchain transaction into a Blockstack operation.  `epoch_id` is the
+    /// Stacks epoch of the burnchain block; the STX operations are only recognized from Stacks
+    /// 2.1 on.
     pub fn classify_transaction(
         block_header: &BurnchainBlockHeader,
         burn_tx: &BurnchainTransaction,
+        epoch_id: StacksEpochId,
     ) -> Option<BlockstackOperationType> {
+        let stx_ops_active = epoch_id >= StacksEpochId::Epoch21;
         match burn_tx.opcode() {
             x if x == Opcodes::LeaderKeyRegister as u8 => {
                 match LeaderKeyRegisterOp::from_tx(block_header, burn_tx) {
@@ -598,6 +639,34 @@ impl Burnchain {
                     }
                 }
             }
+            x if x == Opcodes::PreStx as u8 && stx_ops_active => {
+                match PreStxOp::from_tx(block_header, burn_tx) {
+                    Ok(op) => Some(BlockstackOperationType::PreStx(op)),
+                    Err(e) => {
+                        warn!(
+                            "Failed to parse pre-STX tx {} data {}: {:?}",
+                            &burn_tx.txid(),
+                            &to_hex(&burn_tx.data()[..]),
+                            e
+                        );
+                        None
+                    }
+                }
+            }
+            x if x == Opcodes::TransferStx as u8 && stx_ops_active => {
+                match TransferStxOp::from_tx(block_header, burn_tx) {
+                    Ok(op) => Some(BlockstackOperationType::TransferStx(op)),
+                    Err(e) => {
+                        warn!(
+                            "Failed to parse transfer STX tx {} data {}: {:?}",
+                            &burn_tx.txid(),
+                            &to_hex(&burn_tx.data()[..]),
+                            e
+                        );
+                        None
+                    }
+                }
+            }
             _ => None,
         }
     }
@@ -712,6 +781,7 @@ impl Burnchain {
 
     /// Top-level entry point to check and process a block.
     pub fn process_block(
+        burnchain: &Burnchain,
         burnchain_db: &mut BurnchainDB,
         block: &BurnchainBlock,
     ) -> Result<BurnchainBlockHeader, burnchain_error> {
@@ -721,7 +791,7 @@ impl Burnchain {
             &block.block_hash()
         );
 
-        let _blockstack_txs = burnchain_db.store_new_burnchain_block(&block)?;
+        let _blockstack_txs = burnchain_db.store_new_burnchain_block(burnchain, &block)?;
 
         let header = block.header();
 
@@ -743,7 +813,7 @@ impl Burnchain {
         );
 
         let header = block.header();
-        let blockstack_txs = burnchain_db.store_new_burnchain_block(&block)?;
+        let blockstack_txs = burnchain_db.store_new_burnchain_block(burnchain, &block)?;
 
         let sortition_tip = SortitionDB::get_canonical_sortition_tip(db.conn())?;
 
@@ -1114,6 +1184,7 @@ impl Burnchain {
 
         let mut downloader = indexer.downloader();
         let mut parser = indexer.parser();
+        let burnchain_config = self.clone();
 
         // TODO: don't re-process blocks.  See if the block hash is already present in the burn db,
         // and if so, do nothing.
@@ -1178,7 +1249,11 @@ impl Burnchain {
                     }
 
                     let insert_start = get_epoch_time_ms();
-                    last_processed = Burnchain::process_block(&mut burnchain_db, &burnchain_block)?;
+                    last_processed = Burnchain::process_block(
+                        &burnchain_config,
+                        &mut burnchain_db,
+                        &burnchain_block,
+                    )?;
                     if !coord_comm.announce_new_burn_block() {
                         return Err(burnchain_error::CoordinatorClosed);
                     }
@@ -2001,6 +2076,12 @@ pub mod tests {
                     BlockstackOperationType::UserBurnSupport(ref mut op) => {
                         op.burn_header_hash = block_124_hash.clone();
                     }
+                    BlockstackOperationType::PreStx(ref mut op) => {
+                        op.burn_header_hash = block_124_hash.clone();
+                    }
+                    BlockstackOperationType::TransferStx(ref mut op) => {
+                        op.burn_header_hash = block_124_hash.clone();
+                    }
                 }
             }
 
@@ -2009,11 +2090,7 @@ pub mod tests {
                 &block_ops_124
                     .clone()
                     .into_iter()
-                    .map(|bo| match bo {
-                        BlockstackOperationType::LeaderBlockCommit(ref op) => op.txid.clone(),
-                        BlockstackOperationType::LeaderKeyRegister(ref op) => op.txid.clone(),
-                        BlockstackOperationType::UserBurnSupport(ref op) => op.txid.clone(),
-                    })
+                    .map(|bo| bo.txid())
                     .collect(),
             );
             let block_prev_chs_124 = vec![
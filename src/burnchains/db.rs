@@ -28,6 +28,8 @@ use chainstate::burn::operations::BlockstackOperationType;
 
 use chainstate::stacks::index::MarfTrieId;
 
+use core::StacksEpoch;
+
 use util::db::{
     query_row, query_rows, tx_begin_immediate, tx_busy_handler, u64_to_sql, Error as DBError,
     FromColumn, FromRow,
@@ -271,9 +273,28 @@ impl BurnchainDB {
         })
     }
 
+    /// Forget every burnchain block above `burn_height`, so they get downloaded again.  Returns
+    /// the number of blocks removed.
+    pub fn rewind(&mut self, burn_height: u64) -> Result<u64, BurnchainError> {
+        let db_tx = self.tx_begin()?;
+        let args: &[&dyn ToSql] = &[&u64_to_sql(burn_height)?];
+        db_tx.sql_tx.execute(
+            "DELETE FROM burnchain_db_block_ops WHERE block_hash IN \
+             (SELECT block_hash FROM burnchain_db_block_headers WHERE block_height > ?1)",
+            args,
+        )?;
+        let num_removed = db_tx.sql_tx.execute(
+            "DELETE FROM burnchain_db_block_headers WHERE block_height > ?1",
+            args,
+        )?;
+        db_tx.commit()?;
+        Ok(num_removed as u64)
+    }
+
     /// Filter out the burnchain block's transactions that could be blockstack transactions.
     /// Return the ordered list of blockstack operations by vtxindex
     fn get_blockstack_transactions(
+        burnchain: &Burnchain,
         block: &BurnchainBlock,
         block_header: &BurnchainBlockHeader,
     ) -> Vec<BlockstackOperationType> {
@@ -282,19 +303,23 @@ impl BurnchainDB {
             block.block_height(),
             &block.block_hash()
         );
+        let epoch_id =
+            StacksEpoch::epoch_id_at(&burnchain.get_stacks_epochs(), block_header.block_height);
         block
             .txs()
             .iter()
-            .filter_map(|tx| Burnchain::classify_transaction(block_header, &tx))
+            .filter_map(|tx| Burnchain::classify_transaction(block_header, &tx, epoch_id))
             .collect()
     }
 
     pub fn store_new_burnchain_block(
         &mut self,
+        burnchain: &Burnchain,
         block: &BurnchainBlock,
     ) -> Result<Vec<BlockstackOperationType>, BurnchainError> {
         let header = block.header();
-        let mut blockstack_ops = BurnchainDB::get_blockstack_transactions(block, &header);
+        let mut blockstack_ops =
+            BurnchainDB::get_blockstack_transactions(burnchain, block, &header);
         apply_blockstack_txs_safety_checks(header.block_height, &mut blockstack_ops);
 
         let db_tx = self.tx_begin()?;
@@ -348,6 +373,7 @@ mod tests {
         let first_bhh = BurnchainHeaderHash([0; 32]);
         let first_timestamp = 321;
         let first_height = 1;
+        let burnchain = Burnchain::default_unittest(first_height, &first_bhh);
 
         let mut burnchain_db =
             BurnchainDB::connect(":memory:", first_height, &first_bhh, first_timestamp, true)
@@ -372,7 +398,7 @@ mod tests {
             485,
         ));
         let ops = burnchain_db
-            .store_new_burnchain_block(&canonical_block)
+            .store_new_burnchain_block(&burnchain, &canonical_block)
             .unwrap();
         assert_eq!(ops.len(), 0);
 
@@ -410,7 +436,7 @@ mod tests {
         ));
 
         let ops = burnchain_db
-            .store_new_burnchain_block(&non_canonical_block)
+            .store_new_burnchain_block(&burnchain, &non_canonical_block)
             .unwrap();
         assert_eq!(ops.len(), expected_ops.len());
         for op in ops.iter() {
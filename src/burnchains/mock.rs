@@ -0,0 +1,667 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A deterministic, in-process burnchain.  Blocks are mined instantly by whoever holds a
+//! `MockBurnchain` handle, and contain exactly the transactions they were given.  The
+//! `MockIndexer` syncs from it the same way the `BitcoinIndexer` syncs from bitcoind, so the
+//! rest of the node can't tell the difference.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use burnchains::bitcoin::address::{
+    to_b52_version_byte, version_byte_to_address_type, BitcoinAddress,
+};
+use burnchains::bitcoin::keys::BitcoinPublicKey;
+use burnchains::bitcoin::{
+    BitcoinBlock, BitcoinInputType, BitcoinTransaction, BitcoinTxInput, BitcoinTxOutput,
+};
+use burnchains::indexer::{
+    BurnBlockIPC, BurnHeaderIPC, BurnchainBlockDownloader, BurnchainBlockParser, BurnchainIndexer,
+};
+use burnchains::Error as burnchain_error;
+use burnchains::{BurnchainBlock, BurnchainHeaderHash, Txid};
+
+use chainstate::stacks::StacksAddress;
+
+use util::hash::Sha512Trunc256Sum;
+
+/// Height of the mock burnchain's genesis block
+pub const MOCK_FIRST_BLOCK_HEIGHT: u64 = 0;
+
+/// Timestamp of the mock burnchain's genesis block.  Each block after it comes one second later.
+pub const MOCK_FIRST_BLOCK_TIMESTAMP: u64 = 1_600_000_000;
+
+lazy_static! {
+    // mock burnchains shared by every indexer opened on the same working directory
+    static ref MOCK_BURNCHAINS: Mutex<HashMap<String, MockBurnchain>> =
+        Mutex::new(HashMap::new());
+}
+
+struct MockBurnchainState {
+    /// block hashes of the canonical fork, indexed by height
+    canonical: Vec<BurnchainHeaderHash>,
+    /// every block ever mined, including orphaned ones
+    blocks: HashMap<BurnchainHeaderHash, BitcoinBlock>,
+    /// number of blocks and transactions made so far, so that every hash is unique
+    nonce: u64,
+}
+
+#[derive(Clone)]
+pub struct MockBurnchain {
+    state: Arc<Mutex<MockBurnchainState>>,
+}
+
+impl MockBurnchain {
+    /// Make a new mock burnchain that contains only its genesis block
+    pub fn new() -> MockBurnchain {
+        let genesis_hash = MockBurnchain::make_block_hash(
+            &BurnchainHeaderHash([0u8; 32]),
+            MOCK_FIRST_BLOCK_HEIGHT,
+            0,
+        );
+        let genesis = BitcoinBlock::new(
+            MOCK_FIRST_BLOCK_HEIGHT,
+            &genesis_hash,
+            &BurnchainHeaderHash([0u8; 32]),
+            &vec![],
+            MOCK_FIRST_BLOCK_TIMESTAMP,
+        );
+
+        let mut blocks = HashMap::new();
+        blocks.insert(genesis_hash.clone(), genesis);
+
+        MockBurnchain {
+            state: Arc::new(Mutex::new(MockBurnchainState {
+                canonical: vec![genesis_hash],
+                blocks: blocks,
+                nonce: 1,
+            })),
+        }
+    }
+
+    /// Get the mock burnchain for a working directory, creating it if need be.
+    /// Indexers opened on this working directory will sync from it.
+    pub fn open(working_dir: &str) -> MockBurnchain {
+        let mut chains = MOCK_BURNCHAINS
+            .lock()
+            .expect("FATAL: mock burnchain registry lock poisoned");
+        chains
+            .entry(working_dir.to_string())
+            .or_insert_with(MockBurnchain::new)
+            .clone()
+    }
+
+    fn make_block_hash(
+        parent: &BurnchainHeaderHash,
+        height: u64,
+        nonce: u64,
+    ) -> BurnchainHeaderHash {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(parent.as_bytes());
+        bytes.extend_from_slice(&height.to_be_bytes());
+        bytes.extend_from_slice(&nonce.to_be_bytes());
+        BurnchainHeaderHash(Sha512Trunc256Sum::from_data(&bytes).0)
+    }
+
+    /// Make a burnchain transaction signed by a single public key.  Its txid is unique to this
+    /// mock burnchain, but is the same every time a test runs.
+    pub fn make_transaction(
+        &self,
+        opcode: u8,
+        data: Vec<u8>,
+        signer: &BitcoinPublicKey,
+        outputs: Vec<BitcoinTxOutput>,
+    ) -> BitcoinTransaction {
+        let mut state = self
+            .state
+            .lock()
+            .expect("FATAL: mock burnchain lock poisoned");
+
+        let mut bytes = vec![opcode];
+        bytes.extend_from_slice(&state.nonce.to_be_bytes());
+        bytes.extend_from_slice(&data);
+        state.nonce += 1;
+
+        BitcoinTransaction {
+            txid: Txid(Sha512Trunc256Sum::from_data(&bytes).0),
+            vtxindex: 0,
+            opcode: opcode,
+            data: data,
+            inputs: vec![BitcoinTxInput {
+                keys: vec![signer.clone()],
+                num_required: 1,
+                in_type: BitcoinInputType::Standard,
+            }],
+            outputs: outputs,
+        }
+    }
+
+    /// Make a transaction output that pays `units` to `address`
+    pub fn make_output(address: &BitcoinAddress, units: u64) -> BitcoinTxOutput {
+        BitcoinTxOutput {
+            address: address.clone(),
+            units: units,
+        }
+    }
+
+    /// Make a transaction output that pays `units` to the Bitcoin address of a Stacks address
+    pub fn make_stacks_output(address: &StacksAddress, units: u64) -> BitcoinTxOutput {
+        let btc_version = to_b52_version_byte(address.version)
+            .expect("BUG: failed to decode Stacks version byte to Bitcoin version byte");
+        let (addrtype, network_id) = version_byte_to_address_type(btc_version)
+            .expect("BUG: failed to decode Bitcoin version byte");
+        let address = BitcoinAddress {
+            addrtype: addrtype,
+            network_id: network_id,
+            bytes: address.bytes.clone(),
+        };
+        MockBurnchain::make_output(&address, units)
+    }
+
+    /// Mine a block on top of the canonical tip with the given transactions, in order.
+    /// Their vtxindexes are assigned by position, after the (implicit) coinbase.
+    /// Returns the new block's hash.
+    pub fn mine_block(&self, txs: Vec<BitcoinTransaction>) -> BurnchainHeaderHash {
+        let mut state = self
+            .state
+            .lock()
+            .expect("FATAL: mock burnchain lock poisoned");
+
+        let parent_hash = state
+            .canonical
+            .last()
+            .expect("FATAL: mock burnchain has no genesis block")
+            .clone();
+        let height = state.canonical.len() as u64 + MOCK_FIRST_BLOCK_HEIGHT;
+        let block_hash = MockBurnchain::make_block_hash(&parent_hash, height, state.nonce);
+        state.nonce += 1;
+
+        let txs = txs
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut tx)| {
+                tx.vtxindex = (i as u32) + 1;
+                tx
+            })
+            .collect();
+
+        let block = BitcoinBlock::new(
+            height,
+            &block_hash,
+            &parent_hash,
+            &txs,
+            MOCK_FIRST_BLOCK_TIMESTAMP + height - MOCK_FIRST_BLOCK_HEIGHT,
+        );
+
+        debug!(
+            "Mock burnchain mined block {} at height {} with {} txs",
+            &block_hash,
+            height,
+            block.txs.len()
+        );
+
+        state.blocks.insert(block_hash.clone(), block);
+        state.canonical.push(block_hash.clone());
+        block_hash
+    }
+
+    /// Mine `count` empty blocks on top of the canonical tip
+    pub fn mine_empty_blocks(&self, count: u64) -> BurnchainHeaderHash {
+        let mut tip = self.get_tip_hash();
+        for _ in 0..count {
+            tip = self.mine_block(vec![]);
+        }
+        tip
+    }
+
+    /// Orphan every canonical block above the given height, so that the next block mined builds
+    /// a fork off of the block at this height.  The orphaned blocks can still be downloaded.
+    pub fn fork_at(&self, height: u64) {
+        let mut state = self
+            .state
+            .lock()
+            .expect("FATAL: mock burnchain lock poisoned");
+        assert!(
+            height >= MOCK_FIRST_BLOCK_HEIGHT,
+            "Cannot fork before the mock genesis block"
+        );
+        let len = (height - MOCK_FIRST_BLOCK_HEIGHT + 1) as usize;
+        state.canonical.truncate(len);
+    }
+
+    pub fn get_tip_height(&self) -> u64 {
+        let state = self
+            .state
+            .lock()
+            .expect("FATAL: mock burnchain lock poisoned");
+        (state.canonical.len() as u64) - 1 + MOCK_FIRST_BLOCK_HEIGHT
+    }
+
+    pub fn get_tip_hash(&self) -> BurnchainHeaderHash {
+        let state = self
+            .state
+            .lock()
+            .expect("FATAL: mock burnchain lock poisoned");
+        state
+            .canonical
+            .last()
+            .expect("FATAL: mock burnchain has no genesis block")
+            .clone()
+    }
+
+    /// Get the canonical block at a given height
+    pub fn get_block_at(&self, height: u64) -> Option<BitcoinBlock> {
+        let state = self
+            .state
+            .lock()
+            .expect("FATAL: mock burnchain lock poisoned");
+        if height < MOCK_FIRST_BLOCK_HEIGHT {
+            return None;
+        }
+        state
+            .canonical
+            .get((height - MOCK_FIRST_BLOCK_HEIGHT) as usize)
+            .and_then(|hash| state.blocks.get(hash))
+            .cloned()
+    }
+
+    /// Get any block mined on this burnchain, canonical or not
+    pub fn get_block(&self, block_hash: &BurnchainHeaderHash) -> Option<BitcoinBlock> {
+        let state = self
+            .state
+            .lock()
+            .expect("FATAL: mock burnchain lock poisoned");
+        state.blocks.get(block_hash).cloned()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockHeaderIPC {
+    pub block_hash: BurnchainHeaderHash,
+    pub parent_block_hash: BurnchainHeaderHash,
+    pub block_height: u64,
+    pub timestamp: u64,
+}
+
+impl MockHeaderIPC {
+    fn from_block(block: &BitcoinBlock) -> MockHeaderIPC {
+        MockHeaderIPC {
+            block_hash: block.block_hash.clone(),
+            parent_block_hash: block.parent_block_hash.clone(),
+            block_height: block.block_height,
+            timestamp: block.timestamp,
+        }
+    }
+}
+
+impl BurnHeaderIPC for MockHeaderIPC {
+    type H = MockHeaderIPC;
+
+    fn header(&self) -> MockHeaderIPC {
+        self.clone()
+    }
+
+    fn height(&self) -> u64 {
+        self.block_height
+    }
+
+    /// Bitcoin-style (little-endian) header hash, which the burnchain reverses into a
+    /// BurnchainHeaderHash.
+    fn header_hash(&self) -> [u8; 32] {
+        let mut bytes = self.block_hash.0;
+        bytes.reverse();
+        bytes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockBlockIPC {
+    pub header_data: MockHeaderIPC,
+    pub block: BitcoinBlock,
+}
+
+impl BurnBlockIPC for MockBlockIPC {
+    type H = MockHeaderIPC;
+    type B = BitcoinBlock;
+
+    fn header(&self) -> MockHeaderIPC {
+        self.header_data.clone()
+    }
+
+    fn height(&self) -> u64 {
+        self.header_data.height()
+    }
+
+    fn block(&self) -> BitcoinBlock {
+        self.block.clone()
+    }
+}
+
+pub struct MockBlockDownloader {
+    chain: MockBurnchain,
+}
+
+impl BurnchainBlockDownloader for MockBlockDownloader {
+    type H = MockHeaderIPC;
+    type B = MockBlockIPC;
+
+    fn download(&mut self, header: &MockHeaderIPC) -> Result<MockBlockIPC, burnchain_error> {
+        match self.chain.get_block(&header.block_hash) {
+            Some(block) => Ok(MockBlockIPC {
+                header_data: header.clone(),
+                block: block,
+            }),
+            None => Err(burnchain_error::UnknownBlock(header.block_hash.clone())),
+        }
+    }
+}
+
+pub struct MockBlockParser {}
+
+impl BurnchainBlockParser for MockBlockParser {
+    type D = MockBlockDownloader;
+
+    fn parse(&mut self, ipc_block: &MockBlockIPC) -> Result<BurnchainBlock, burnchain_error> {
+        Ok(BurnchainBlock::Bitcoin(ipc_block.block.clone()))
+    }
+}
+
+pub struct MockIndexer {
+    chain: MockBurnchain,
+    working_dir: String,
+    /// headers synced so far, indexed by height
+    headers: Vec<MockHeaderIPC>,
+}
+
+impl MockIndexer {
+    /// Make an indexer that syncs from the given mock burnchain.  It starts out knowing only
+    /// the genesis block.
+    pub fn new(working_dir: &str, chain: MockBurnchain) -> MockIndexer {
+        let genesis = chain
+            .get_block_at(MOCK_FIRST_BLOCK_HEIGHT)
+            .expect("FATAL: mock burnchain has no genesis block");
+        MockIndexer {
+            chain: chain,
+            working_dir: working_dir.to_string(),
+            headers: vec![MockHeaderIPC::from_block(&genesis)],
+        }
+    }
+
+    pub fn chain(&self) -> &MockBurnchain {
+        &self.chain
+    }
+}
+
+impl BurnchainIndexer for MockIndexer {
+    type P = MockBlockParser;
+
+    /// Sync from the mock burnchain registered for this working directory
+    fn init(working_dir: &String, _network_name: &String) -> Result<MockIndexer, burnchain_error> {
+        Ok(MockIndexer::new(
+            working_dir,
+            MockBurnchain::open(working_dir),
+        ))
+    }
+
+    /// Nothing to connect to
+    fn connect(&mut self) -> Result<(), burnchain_error> {
+        Ok(())
+    }
+
+    fn get_first_block_height(&self) -> u64 {
+        MOCK_FIRST_BLOCK_HEIGHT
+    }
+
+    fn get_first_block_header_hash(&self) -> Result<BurnchainHeaderHash, burnchain_error> {
+        Ok(self.headers[0].block_hash.clone())
+    }
+
+    fn get_first_block_header_timestamp(&self) -> Result<u64, burnchain_error> {
+        Ok(self.headers[0].timestamp)
+    }
+
+    /// Headers are kept in RAM, so there is no headers file.  Report the working directory so
+    /// the burnchain treats the (always-present) genesis header as already synced.
+    fn get_headers_path(&self) -> String {
+        self.working_dir.clone()
+    }
+
+    /// Number of headers we have (1-indexed, like the SPV client)
+    fn get_headers_height(&self) -> Result<u64, burnchain_error> {
+        Ok(self.headers.len() as u64 + MOCK_FIRST_BLOCK_HEIGHT)
+    }
+
+    /// Find the height of the highest header we have that is still canonical
+    fn find_chain_reorg(&mut self) -> Result<u64, burnchain_error> {
+        for header in self.headers.iter().rev() {
+            if let Some(block) = self.chain.get_block_at(header.block_height) {
+                if block.block_hash == header.block_hash {
+                    return Ok(header.block_height);
+                }
+            }
+        }
+        Ok(MOCK_FIRST_BLOCK_HEIGHT)
+    }
+
+    /// Copy canonical headers after start_height, up to and including end_height if given.
+    /// Returns the height of the last header fetched.
+    fn sync_headers(
+        &mut self,
+        start_height: u64,
+        end_height: Option<u64>,
+    ) -> Result<u64, burnchain_error> {
+        if end_height.is_some() && end_height <= Some(start_height) {
+            return Ok(end_height.unwrap());
+        }
+
+        let last_height = match end_height {
+            Some(end_height) => end_height.min(self.chain.get_tip_height()),
+            None => self.chain.get_tip_height(),
+        };
+
+        let first_new_height =
+            (self.headers.len() as u64 + MOCK_FIRST_BLOCK_HEIGHT).min(start_height + 1);
+        self.headers
+            .truncate((first_new_height - MOCK_FIRST_BLOCK_HEIGHT) as usize);
+
+        for height in first_new_height..(last_height + 1) {
+            let block = self
+                .chain
+                .get_block_at(height)
+                .ok_or(burnchain_error::MissingHeaders)?;
+            self.headers.push(MockHeaderIPC::from_block(&block));
+        }
+
+        Ok(self.headers.len() as u64 - 1 + MOCK_FIRST_BLOCK_HEIGHT)
+    }
+
+    /// Drop headers after a given height -- i.e. to accomodate a reorg
+    fn drop_headers(&mut self, new_height: u64) -> Result<(), burnchain_error> {
+        let len = (new_height.max(MOCK_FIRST_BLOCK_HEIGHT) - MOCK_FIRST_BLOCK_HEIGHT + 1) as usize;
+        self.headers.truncate(len);
+        Ok(())
+    }
+
+    /// Read synced headers in the range [start_block, end_block)
+    fn read_headers(
+        &self,
+        start_block: u64,
+        end_block: u64,
+    ) -> Result<Vec<MockHeaderIPC>, burnchain_error> {
+        Ok(self
+            .headers
+            .iter()
+            .filter(|hdr| hdr.block_height >= start_block && hdr.block_height < end_block)
+            .cloned()
+            .collect())
+    }
+
+    fn downloader(&self) -> MockBlockDownloader {
+        MockBlockDownloader {
+            chain: self.chain.clone(),
+        }
+    }
+
+    fn parser(&self) -> MockBlockParser {
+        MockBlockParser {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    use burnchains::bitcoin::address::BitcoinAddressType;
+    use burnchains::bitcoin::BitcoinNetworkType;
+    use burnchains::db::BurnchainDB;
+    use burnchains::Burnchain;
+
+    use chainstate::burn::operations::BlockstackOperationType;
+    use chainstate::burn::Opcodes;
+    use chainstate::coordinator::comm::CoordinatorCommunication;
+
+    use util::hash::Hash160;
+
+    fn setup_burnchain(working_dir: &str, chain: &MockBurnchain) -> Burnchain {
+        if fs::metadata(working_dir).is_ok() {
+            fs::remove_dir_all(working_dir).unwrap();
+        }
+        fs::create_dir_all(working_dir).unwrap();
+
+        let mut burnchain = Burnchain::new(working_dir, "bitcoin", "regtest").unwrap();
+        burnchain.first_block_height = MOCK_FIRST_BLOCK_HEIGHT;
+        burnchain.first_block_hash = chain
+            .get_block_at(MOCK_FIRST_BLOCK_HEIGHT)
+            .unwrap()
+            .block_hash;
+        burnchain
+    }
+
+    fn open_burnchain_db(burnchain: &Burnchain, indexer: &MockIndexer) -> BurnchainDB {
+        let (_, burnchain_db) = burnchain.connect_db(indexer, false).unwrap();
+        burnchain_db
+    }
+
+    #[test]
+    fn test_mock_burnchain_deterministic() {
+        let chain_1 = MockBurnchain::new();
+        let chain_2 = MockBurnchain::new();
+
+        assert_eq!(chain_1.mine_empty_blocks(5), chain_2.mine_empty_blocks(5));
+        assert_eq!(chain_1.get_tip_height(), 5);
+
+        // re-mining a height on a fork yields a different block
+        let orphaned = chain_1.get_tip_hash();
+        chain_1.fork_at(4);
+        let replacement = chain_1.mine_block(vec![]);
+        assert_eq!(chain_1.get_tip_height(), 5);
+        assert!(orphaned != replacement);
+        assert!(chain_1.get_block(&orphaned).is_some());
+        assert_eq!(chain_1.get_block_at(5).unwrap().block_hash, replacement);
+    }
+
+    #[test]
+    fn test_mock_indexer_sync_and_reorg() {
+        let working_dir = "/tmp/test-mock-indexer-sync-and-reorg";
+        let chain = MockBurnchain::new();
+        let mut burnchain = setup_burnchain(working_dir, &chain);
+        let mut indexer = MockIndexer::new(working_dir, chain.clone());
+        let (_, comms) = CoordinatorCommunication::instantiate();
+
+        // a scripted transaction shows up as a burnchain operation
+        let signer = BitcoinPublicKey::from_hex(
+            "02d8015134d9db8178ac93acbc43170a2f20febba5087a5b0437058765ad5133d0",
+        )
+        .unwrap();
+        let address = BitcoinAddress {
+            addrtype: BitcoinAddressType::PublicKeyHash,
+            network_id: BitcoinNetworkType::Regtest,
+            bytes: Hash160([0x11; 20]),
+        };
+        let pre_stx = chain.make_transaction(
+            Opcodes::PreStx as u8,
+            vec![],
+            &signer,
+            vec![MockBurnchain::make_output(&address, 5500)],
+        );
+
+        chain.mine_empty_blocks(1);
+        let pre_stx_block = chain.mine_block(vec![pre_stx.clone()]);
+        chain.mine_empty_blocks(1);
+
+        let tip = burnchain
+            .sync_with_indexer(&mut indexer, comms.clone(), None, None)
+            .unwrap();
+        assert_eq!(tip.block_height, 3);
+        assert_eq!(tip.block_hash, chain.get_tip_hash());
+
+        let burnchain_db = open_burnchain_db(&burnchain, &indexer);
+        let block_data = burnchain_db.get_burnchain_block(&pre_stx_block).unwrap();
+        assert_eq!(block_data.ops.len(), 1);
+        match block_data.ops[0] {
+            BlockstackOperationType::PreStx(ref op) => {
+                assert_eq!(op.txid, pre_stx.txid);
+                assert_eq!(op.vtxindex, 1);
+            }
+            ref op => panic!("Unexpected op {:?}", op),
+        }
+
+        // nothing new to sync
+        let tip = burnchain
+            .sync_with_indexer(&mut indexer, comms.clone(), None, None)
+            .unwrap();
+        assert_eq!(tip.block_height, 3);
+
+        // reorg to a longer fork that doesn't have the PreStx
+        chain.fork_at(1);
+        let new_tip = chain.mine_empty_blocks(3);
+
+        let tip = burnchain
+            .sync_with_indexer(&mut indexer, comms.clone(), None, None)
+            .unwrap();
+        assert_eq!(tip.block_height, 4);
+        assert_eq!(tip.block_hash, new_tip);
+
+        let burnchain_db = open_burnchain_db(&burnchain, &indexer);
+        assert_eq!(
+            burnchain_db.get_canonical_chain_tip().unwrap().block_hash,
+            new_tip
+        );
+        for height in 2..5 {
+            let block = chain.get_block_at(height).unwrap();
+            let block_data = burnchain_db.get_burnchain_block(&block.block_hash).unwrap();
+            assert_eq!(block_data.header.parent_block_hash, block.parent_block_hash);
+            assert_eq!(block_data.ops.len(), 0);
+        }
+    }
+
+    #[test]
+    fn test_mock_indexer_init_shares_chain() {
+        let working_dir = "/tmp/test-mock-indexer-init-shares-chain".to_string();
+        let chain = MockBurnchain::open(&working_dir);
+        chain.mine_empty_blocks(2);
+
+        let mut indexer = MockIndexer::init(&working_dir, &"regtest".to_string()).unwrap();
+        assert_eq!(indexer.sync_headers(0, None).unwrap(), 2);
+        assert_eq!(indexer.get_headers_height().unwrap(), 3);
+        assert_eq!(
+            indexer.read_headers(2, 3).unwrap()[0].block_hash,
+            chain.get_tip_hash()
+        );
+    }
+}
@@ -19,6 +19,8 @@ pub mod bitcoin;
 pub mod burnchain;
 pub mod db;
 pub mod indexer;
+pub mod mock;
+pub mod timing;
 
 use std::default::Default;
 use std::error;
@@ -0,0 +1,218 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Estimates when a future burnchain block will arrive.
+//!
+//! Burnchain blocks are modeled as a Poisson process, whose mean inter-block time is measured
+//! from the timestamps of the most recent burnchain blocks on the canonical sortition fork.
+//! Individual block timestamps are only loosely constrained by the burnchain, so the mean is
+//! taken over the whole sample window instead of over per-block intervals.
+
+use chainstate::burn::db::sortdb::SortitionDB;
+use chainstate::burn::BlockSnapshot;
+
+use util::db::Error as db_error;
+
+/// Number of recent burnchain blocks to measure the mean block time over (about a day of
+/// Bitcoin blocks).
+pub const BURN_BLOCK_TIME_SAMPLE_WINDOW: u64 = 144;
+
+/// If fewer than this many intervals can be measured, fall back to the nominal block time.
+pub const BURN_BLOCK_TIME_MIN_SAMPLES: u64 = 6;
+
+/// Nominal burnchain block time, in seconds.
+pub const DEFAULT_BURN_BLOCK_TIME_SECS: u64 = 600;
+
+/// Measured burnchain block timing, as of a given burnchain tip.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BurnBlockTimeModel {
+    /// height of the burnchain tip the model was measured at
+    pub tip_height: u64,
+    /// timestamp of the burnchain tip
+    pub tip_timestamp: u64,
+    /// number of inter-block intervals measured (0 if the nominal block time is used)
+    pub num_samples: u64,
+    /// mean inter-block time, in seconds
+    pub mean_block_time: f64,
+}
+
+/// Estimated wall-clock arrival of a burnchain block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BurnBlockEta {
+    pub target_height: u64,
+    pub blocks_remaining: u64,
+    /// expected number of seconds until the block arrives
+    pub seconds: u64,
+    /// lower and upper bounds of a ~95% confidence interval on `seconds`
+    pub seconds_low: u64,
+    pub seconds_high: u64,
+    /// expected arrival time, in seconds since the epoch
+    pub timestamp: u64,
+}
+
+impl BurnBlockTimeModel {
+    /// Model the block time from the timestamps of two blocks in the same fork.  Falls back to
+    /// the nominal block time if the blocks are too close together, or if their timestamps are
+    /// not increasing.
+    pub fn new(
+        tip_height: u64,
+        tip_timestamp: u64,
+        oldest_height: u64,
+        oldest_timestamp: u64,
+    ) -> BurnBlockTimeModel {
+        let num_samples = tip_height.saturating_sub(oldest_height);
+        if num_samples < BURN_BLOCK_TIME_MIN_SAMPLES || tip_timestamp <= oldest_timestamp {
+            return BurnBlockTimeModel::nominal(tip_height, tip_timestamp);
+        }
+
+        BurnBlockTimeModel {
+            tip_height,
+            tip_timestamp,
+            num_samples,
+            mean_block_time: ((tip_timestamp - oldest_timestamp) as f64) / (num_samples as f64),
+        }
+    }
+
+    /// Model that assumes the nominal burnchain block time.
+    pub fn nominal(tip_height: u64, tip_timestamp: u64) -> BurnBlockTimeModel {
+        BurnBlockTimeModel {
+            tip_height,
+            tip_timestamp,
+            num_samples: 0,
+            mean_block_time: DEFAULT_BURN_BLOCK_TIME_SECS as f64,
+        }
+    }
+
+    /// Measure the block time over the last `window` blocks of the canonical burnchain fork.
+    pub fn from_sortdb(sortdb: &SortitionDB, window: u64) -> Result<BurnBlockTimeModel, db_error> {
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())?;
+        BurnBlockTimeModel::from_tip(sortdb, &tip, window)
+    }
+
+    /// Measure the block time over the `window` blocks leading up to (and including) `tip`.
+    pub fn from_tip(
+        sortdb: &SortitionDB,
+        tip: &BlockSnapshot,
+        window: u64,
+    ) -> Result<BurnBlockTimeModel, db_error> {
+        let oldest_height = tip
+            .block_height
+            .saturating_sub(window)
+            .max(sortdb.first_block_height);
+
+        match SortitionDB::get_ancestor_snapshot(
+            &sortdb.index_conn(),
+            oldest_height,
+            &tip.sortition_id,
+        )? {
+            Some(oldest) => Ok(BurnBlockTimeModel::new(
+                tip.block_height,
+                tip.burn_header_timestamp,
+                oldest.block_height,
+                oldest.burn_header_timestamp,
+            )),
+            None => Ok(BurnBlockTimeModel::nominal(
+                tip.block_height,
+                tip.burn_header_timestamp,
+            )),
+        }
+    }
+
+    /// Estimate when the block at `target_height` will arrive, as seen at time `now`.
+    ///
+    /// Since block arrivals are memoryless, the time already elapsed since the tip has no
+    /// bearing on the estimate:  the sum of `n` exponentially-distributed intervals with mean
+    /// `m` has mean `n * m` and standard deviation `sqrt(n) * m`, starting from `now`.
+    /// If the target block has already arrived, then the estimate is 0 seconds from `now`.
+    pub fn estimate(&self, target_height: u64, now: u64) -> BurnBlockEta {
+        let blocks_remaining = target_height.saturating_sub(self.tip_height);
+        if blocks_remaining == 0 {
+            return BurnBlockEta {
+                target_height,
+                blocks_remaining,
+                seconds: 0,
+                seconds_low: 0,
+                seconds_high: 0,
+                timestamp: now,
+            };
+        }
+
+        let mean = (blocks_remaining as f64) * self.mean_block_time;
+        let stddev = (blocks_remaining as f64).sqrt() * self.mean_block_time;
+        let seconds = mean.round() as u64;
+
+        BurnBlockEta {
+            target_height,
+            blocks_remaining,
+            seconds,
+            seconds_low: (mean - 1.96 * stddev).max(0.0).round() as u64,
+            seconds_high: (mean + 1.96 * stddev).round() as u64,
+            timestamp: now.saturating_add(seconds),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_burn_block_time_model() {
+        // 144 blocks over 1 day
+        let model = BurnBlockTimeModel::new(1144, 1_000_086_400, 1000, 1_000_000_000);
+        assert_eq!(model.num_samples, 144);
+        assert_eq!(model.mean_block_time, 600.0);
+
+        // too few samples
+        let model = BurnBlockTimeModel::new(1005, 1_000_000_005, 1000, 1_000_000_000);
+        assert_eq!(model, BurnBlockTimeModel::nominal(1005, 1_000_000_005));
+
+        // timestamps out of order
+        let model = BurnBlockTimeModel::new(1144, 1_000_000_000, 1000, 1_000_000_001);
+        assert_eq!(model, BurnBlockTimeModel::nominal(1144, 1_000_000_000));
+
+        // fast blocks
+        let model = BurnBlockTimeModel::new(1100, 1_000_030_000, 1000, 1_000_000_000);
+        assert_eq!(model.mean_block_time, 300.0);
+    }
+
+    #[test]
+    fn test_burn_block_eta() {
+        let model = BurnBlockTimeModel::new(1144, 1_000_086_400, 1000, 1_000_000_000);
+
+        let eta = model.estimate(1144 + 144, 1_000_100_000);
+        assert_eq!(eta.blocks_remaining, 144);
+        assert_eq!(eta.seconds, 86400);
+        assert_eq!(eta.timestamp, 1_000_186_400);
+        // 1.96 * sqrt(144) * 600 = 14112
+        assert_eq!(eta.seconds_low, 86400 - 14112);
+        assert_eq!(eta.seconds_high, 86400 + 14112);
+
+        // a single block can arrive at any moment
+        let eta = model.estimate(1145, 1_000_100_000);
+        assert_eq!(eta.seconds, 600);
+        assert_eq!(eta.seconds_low, 0);
+        assert_eq!(eta.seconds_high, 1776);
+
+        // already arrived
+        for height in [1000, 1144].iter() {
+            let eta = model.estimate(*height, 1_000_100_000);
+            assert_eq!(eta.blocks_remaining, 0);
+            assert_eq!(eta.seconds, 0);
+            assert_eq!(eta.timestamp, 1_000_100_000);
+        }
+    }
+}
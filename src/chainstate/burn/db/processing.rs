@@ -76,6 +76,22 @@ impl<'a> SortitionHandleTx<'a> {
                     BurnchainError::OpError(e)
                 })
             }
+            BlockstackOperationType::PreStx(ref op) => op.check(burnchain, self).map_err(|e| {
+                warn!(
+                    "REJECTED({}) pre-STX {} at {},{}: {:?}",
+                    op.block_height, &op.txid, op.block_height, op.vtxindex, &e
+                );
+                BurnchainError::OpError(e)
+            }),
+            BlockstackOperationType::TransferStx(ref op) => {
+                op.check(burnchain, self).map_err(|e| {
+                    warn!(
+                        "REJECTED({}) transfer STX {} at {},{}: {:?}",
+                        op.block_height, &op.txid, op.block_height, op.vtxindex, &e
+                    );
+                    BurnchainError::OpError(e)
+                })
+            }
         }
     }
 
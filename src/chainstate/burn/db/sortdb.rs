@@ -32,8 +32,8 @@ use std::{cmp, fmt, fs, str::FromStr};
 use util::db::tx_begin_immediate;
 use util::db::Error as db_error;
 use util::db::{
-    db_mkdirs, query_count, query_row, query_row_columns, query_row_panic, query_rows, u64_to_sql,
-    FromColumn, FromRow, IndexDBConn, IndexDBTx,
+    db_mkdirs, query_count, query_row, query_row_columns, query_row_panic, query_rows,
+    sqlite_quick_check, u64_to_sql, FromColumn, FromRow, IndexDBConn, IndexDBTx, IntegrityReport,
 };
 use util::get_epoch_time_secs;
 
@@ -46,11 +46,12 @@ use chainstate::burn::{
 
 use chainstate::coordinator::{Error as CoordinatorError, PoxAnchorBlockStatus, RewardCycleInfo};
 use core::CHAINSTATE_VERSION;
+use core::{StacksEpoch, StacksEpochId};
 
 use chainstate::burn::operations::{
     leader_block_commit::{RewardSetInfo, OUTPUTS_PER_COMMIT},
     BlockstackOperation, BlockstackOperationType, LeaderBlockCommitOp, LeaderKeyRegisterOp,
-    UserBurnSupportOp,
+    PreStxOp, TransferStxOp, UserBurnSupportOp,
 };
 
 use burnchains::{Address, BurnchainHeaderHash, PublicKey, Txid};
@@ -65,6 +66,7 @@ use chainstate::stacks::db::{StacksChainState, StacksHeaderInfo};
 use chainstate::stacks::index::marf::MarfConnection;
 use chainstate::stacks::index::marf::MARF;
 use chainstate::stacks::index::storage::TrieFileStorage;
+use chainstate::stacks::index::trie_sql;
 use chainstate::stacks::index::{Error as MARFError, MARFValue, MarfTrieId, TrieHash};
 use chainstate::stacks::StacksAddress;
 use chainstate::stacks::StacksPublicKey;
@@ -303,6 +305,57 @@ impl FromRow<UserBurnSupportOp> for UserBurnSupportOp {
     }
 }
 
+impl FromRow<PreStxOp> for PreStxOp {
+    fn from_row<'a>(row: &'a Row) -> Result<PreStxOp, db_error> {
+        let txid = Txid::from_column(row, "txid")?;
+        let vtxindex: u32 = row.get("vtxindex");
+        let block_height = u64::from_column(row, "block_height")?;
+        let burn_header_hash = BurnchainHeaderHash::from_column(row, "burn_header_hash")?;
+
+        let output = StacksAddress::from_column(row, "output")?;
+
+        Ok(PreStxOp {
+            output,
+
+            txid,
+            vtxindex,
+            block_height,
+            burn_header_hash,
+        })
+    }
+}
+
+impl FromRow<TransferStxOp> for TransferStxOp {
+    fn from_row<'a>(row: &'a Row) -> Result<TransferStxOp, db_error> {
+        let txid = Txid::from_column(row, "txid")?;
+        let vtxindex: u32 = row.get("vtxindex");
+        let block_height = u64::from_column(row, "block_height")?;
+        let burn_header_hash = BurnchainHeaderHash::from_column(row, "burn_header_hash")?;
+
+        let sender = StacksAddress::from_column(row, "sender")?;
+        let recipient = StacksAddress::from_column(row, "recipient")?;
+        let transfered_ustx_str: String = row.get("transfered_ustx");
+        let memo_hex: String = row.get("memo");
+
+        let transfered_ustx = transfered_ustx_str
+            .parse::<u128>()
+            .map_err(|_e| db_error::ParseError)?;
+        let memo = hex_bytes(&memo_hex).map_err(|_e| db_error::ParseError)?;
+
+        Ok(TransferStxOp {
+            sender,
+            recipient,
+            transfered_ustx,
+            memo,
+
+            txid,
+            vtxindex,
+            block_height,
+            burn_header_hash,
+        })
+    }
+}
+
 struct AcceptedStacksBlockHeader {
     pub tip_consensus_hash: ConsensusHash, // PoX tip
     pub consensus_hash: ConsensusHash,     // stacks block consensus hash
@@ -326,6 +379,21 @@ impl FromRow<AcceptedStacksBlockHeader> for AcceptedStacksBlockHeader {
     }
 }
 
+impl FromRow<StacksEpoch> for StacksEpoch {
+    fn from_row<'a>(row: &'a Row) -> Result<StacksEpoch, db_error> {
+        let start_height = u64::from_column(row, "start_block_height")?;
+        let end_height = u64::from_column(row, "end_block_height")?;
+        let epoch_id_u32: u32 = row.get("epoch_id");
+        let epoch_id = StacksEpochId::try_from(epoch_id_u32).map_err(|_e| db_error::ParseError)?;
+
+        Ok(StacksEpoch {
+            epoch_id,
+            start_height,
+            end_height,
+        })
+    }
+}
+
 const BURNDB_SETUP: &'static [&'static str] = &[
     r#"
     PRAGMA foreign_keys = ON;
@@ -438,6 +506,39 @@ const BURNDB_SETUP: &'static [&'static str] = &[
         FOREIGN KEY(sortition_id) REFERENCES snapshots(sortition_id)
     );"#,
     r#"
+    -- addresses registered to send STX through burnchain operations
+    CREATE TABLE pre_stx(
+        txid TEXT NOT NULL,
+        vtxindex INTEGER NOT NULL,
+        block_height INTEGER NOT NULL,
+        burn_header_hash TEXT NOT NULL,
+        sortition_id TEXT NOT NULL,
+
+        output TEXT NOT NULL,
+
+        PRIMARY KEY(txid,sortition_id),
+        FOREIGN KEY(sortition_id) REFERENCES snapshots(sortition_id)
+    );"#,
+    r#"
+    -- STX transfers sent through the burnchain, to be materialized by the Stacks chain
+    CREATE TABLE transfer_stx(
+        txid TEXT NOT NULL,
+        vtxindex INTEGER NOT NULL,
+        block_height INTEGER NOT NULL,
+        burn_header_hash TEXT NOT NULL,
+        sortition_id TEXT NOT NULL,
+
+        sender TEXT NOT NULL,
+        recipient TEXT NOT NULL,
+        transfered_ustx TEXT NOT NULL,     -- use text to encode really big numbers
+        memo TEXT NOT NULL,
+
+        PRIMARY KEY(txid,sortition_id),
+        FOREIGN KEY(sortition_id) REFERENCES snapshots(sortition_id)
+    );
+    CREATE INDEX transfer_stx_burn_header_hashes ON transfer_stx(burn_header_hash);
+    "#,
+    r#"
     CREATE TABLE canonical_accepted_stacks_blocks(
         tip_consensus_hash TEXT NOT NULL,
         consensus_hash TEXT NOT NULL,
@@ -454,6 +555,16 @@ const BURNDB_SETUP: &'static [&'static str] = &[
     "#,
 ];
 
+// kept apart from BURNDB_SETUP so it can be added to DBs made before epochs were stored
+const EPOCHS_SETUP: &'static str = r#"
+    CREATE TABLE epochs(
+        start_block_height INTEGER NOT NULL,
+        end_block_height INTEGER NOT NULL,
+        epoch_id INTEGER NOT NULL,
+        PRIMARY KEY(start_block_height, epoch_id)
+    );
+    "#;
+
 pub struct SortitionDB {
     pub readwrite: bool,
     pub marf: MARF<SortitionId>,
@@ -600,6 +711,9 @@ impl db_keys {
     pub fn vrf_key_status(key: &VRFPublicKey) -> String {
         format!("sortition_db::vrf::{}", key.to_hex())
     }
+    pub fn pre_stx_address(address: &StacksAddress) -> String {
+        format!("sortition_db::pre_stx::{}", address)
+    }
     pub fn stacks_block_present(block_hash: &BlockHeaderHash) -> String {
         format!("sortition_db::sortition_block_hash::{}", block_hash)
     }
@@ -873,6 +987,24 @@ impl<'a> SortitionHandleTx<'a> {
         Ok(key_status)
     }
 
+    /// Get the ID of the Stacks epoch that contains this burnchain block height.  Heights past
+    /// the end of the schedule follow the latest epoch's rules.
+    pub fn get_stacks_epoch_id(&self, height: u64) -> Result<StacksEpochId, db_error> {
+        let epoch_id = SortitionDB::get_stacks_epoch(self.tx(), height)?
+            .map(|epoch| epoch.epoch_id)
+            .unwrap_or_else(StacksEpochId::latest);
+        Ok(epoch_id)
+    }
+
+    /// Has this address been registered by a PreStxOp in this fork?
+    pub fn has_pre_stx_address(&mut self, address: &StacksAddress) -> Result<bool, db_error> {
+        let chain_tip = self.context.chain_tip.clone();
+        let registered = self
+            .get_indexed(&chain_tip, &db_keys::pre_stx_address(address))?
+            .is_some();
+        Ok(registered)
+    }
+
     fn check_fresh_consensus_hash<F>(
         &mut self,
         consensus_hash_lifetime: u64,
@@ -1825,13 +1957,21 @@ impl SortitionDB {
 
     /// Open the burn database at the given path.  Open read-only or read/write.
     /// If opened for read/write and it doesn't exist, instantiate it.
+    /// `epochs` is the network's epoch schedule.  It must match the one the database was
+    /// created with.
     pub fn connect(
         path: &str,
         first_block_height: u64,
         first_burn_hash: &BurnchainHeaderHash,
         first_burn_header_timestamp: u64,
+        epochs: &[StacksEpoch],
         readwrite: bool,
     ) -> Result<SortitionDB, db_error> {
+        StacksEpoch::validate_epochs(epochs).map_err(|msg| {
+            error!("Invalid Stacks epochs: {}", &msg);
+            db_error::Other(msg)
+        })?;
+
         let create_flag = match fs::metadata(path) {
             Err(e) => {
                 if e.kind() == ErrorKind::NotFound {
@@ -1872,6 +2012,7 @@ impl SortitionDB {
                 first_block_height,
                 first_burn_hash,
                 first_burn_header_timestamp,
+                epochs,
             )?;
         } else {
             // validate -- must contain the given first block and first block hash
@@ -1884,12 +2025,15 @@ impl SortitionDB {
                        snapshot.is_initial(), snapshot.block_height, &snapshot.burn_header_hash, first_block_height, first_burn_hash);
                 return Err(db_error::Corruption);
             }
+
+            db.check_epochs(epochs)?;
         }
 
         Ok(db)
     }
 
-    /// Open a burn database at random tmp dir (used for testing)
+    /// Open a burn database at random tmp dir (used for testing).  It follows the latest
+    /// epoch's rules from the first block on.
     #[cfg(test)]
     pub fn connect_test(
         first_block_height: u64,
@@ -1905,6 +2049,7 @@ impl SortitionDB {
             first_block_height,
             first_burn_hash,
             get_epoch_time_secs(),
+            &StacksEpoch::all_latest(first_block_height),
             true,
         )
     }
@@ -1914,6 +2059,7 @@ impl SortitionDB {
         first_block_height: u64,
         first_burn_header_hash: &BurnchainHeaderHash,
         first_burn_header_timestamp: u64,
+        epochs: &[StacksEpoch],
     ) -> Result<(), db_error> {
         debug!("Instantiate SortDB");
         let mut db_tx = SortitionHandleTx::begin(self, &SortitionId::sentinel())?;
@@ -1935,6 +2081,8 @@ impl SortitionDB {
         for row_text in BURNDB_SETUP {
             db_tx.execute(row_text, NO_PARAMS)?;
         }
+        db_tx.execute(EPOCHS_SETUP, NO_PARAMS)?;
+        SortitionDB::insert_epochs(&db_tx, epochs)?;
 
         db_tx.execute(
             "INSERT INTO db_config (version) VALUES (?1)",
@@ -1959,6 +2107,81 @@ impl SortitionDB {
         Ok(())
     }
 
+    fn insert_epochs(conn: &Connection, epochs: &[StacksEpoch]) -> Result<(), db_error> {
+        for epoch in epochs.iter() {
+            let args: [&dyn ToSql; 3] = [
+                &u64_to_sql(epoch.start_height)?,
+                &u64_to_sql(epoch.end_height)?,
+                &(epoch.epoch_id as u32),
+            ];
+            conn.execute(
+                "INSERT INTO epochs (start_block_height, end_block_height, epoch_id) VALUES (?1, ?2, ?3)",
+                &args,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Check that the database has the given epochs.  A database made before epochs were
+    /// stored gets them now.  If it's open read-only, they're kept in a temporary table that
+    /// lasts as long as this connection, and the database itself is left alone.
+    fn check_epochs(&mut self, epochs: &[StacksEpoch]) -> Result<(), db_error> {
+        let has_epochs = query_count(
+            self.conn(),
+            &"SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'epochs'"
+                .to_string(),
+            NO_PARAMS,
+        )? > 0;
+
+        if !has_epochs && !self.readwrite {
+            debug!("Add temporary Stacks epochs to read-only sortdb");
+            self.conn().execute(
+                &EPOCHS_SETUP.replacen("CREATE TABLE", "CREATE TEMP TABLE", 1),
+                NO_PARAMS,
+            )?;
+            SortitionDB::insert_epochs(self.conn(), epochs)?;
+            return Ok(());
+        }
+
+        if !has_epochs {
+            debug!("Add Stacks epochs to sortdb");
+            let db_tx = SortitionHandleTx::begin(self, &SortitionId::sentinel())?;
+            db_tx.execute(EPOCHS_SETUP, NO_PARAMS)?;
+            SortitionDB::insert_epochs(&db_tx, epochs)?;
+            db_tx.commit()?;
+            return Ok(());
+        }
+
+        let stored_epochs = SortitionDB::get_stacks_epochs(self.conn())?;
+        if stored_epochs != epochs {
+            error!(
+                "Stacks epochs do not match the ones in the sortition DB: expected {:?}, got {:?}",
+                epochs, &stored_epochs
+            );
+            return Err(db_error::Other(
+                "Stacks epochs do not match the sortition DB".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Get the Stacks epoch that contains the given burnchain block height
+    pub fn get_stacks_epoch(
+        conn: &Connection,
+        height: u64,
+    ) -> Result<Option<StacksEpoch>, db_error> {
+        let qry =
+            "SELECT * FROM epochs WHERE start_block_height <= ?1 AND ?1 < end_block_height LIMIT 1";
+        let args: [&dyn ToSql; 1] = [&u64_to_sql(height)?];
+        query_row(conn, qry, &args)
+    }
+
+    /// Get all of the Stacks epochs, in order
+    pub fn get_stacks_epochs(conn: &Connection) -> Result<Vec<StacksEpoch>, db_error> {
+        let qry = "SELECT * FROM epochs ORDER BY start_block_height ASC, epoch_id ASC";
+        query_rows(conn, qry, NO_PARAMS)
+    }
+
     /// Get a block commit by its content-addressed location in a specific sortition.
     pub fn get_block_commit(
         conn: &Connection,
@@ -2231,6 +2454,154 @@ impl SortitionDB {
         .flatten()
     }
 
+    /// Forget every sortition above `burn_height` in every fork, so that the chains coordinator
+    /// re-evaluates those burnchain blocks the next time it runs.  The snapshots, their burnchain
+    /// operations and their MARF tries are deleted outright.
+    /// The database at `path` must not be open anywhere else, since open handles cache MARF data.
+    /// Returns the number of snapshots that were removed.
+    pub fn rewind(path: &str, burn_height: u64) -> Result<u64, BurnchainError> {
+        let mut db = SortitionDB::open(path, true)?;
+        if burn_height < db.first_block_height {
+            return Err(BurnchainError::DBError(db_error::Other(format!(
+                "Cannot rewind below the first burnchain block height {}",
+                db.first_block_height
+            ))));
+        }
+
+        let db_tx = db.tx_begin()?;
+        let sortition_ids: Vec<SortitionId> = query_row_columns(
+            db_tx.tx(),
+            &"SELECT sortition_id FROM snapshots WHERE block_height > ?1".to_string(),
+            &[&u64_to_sql(burn_height)?],
+            "sortition_id",
+        )?;
+        let consensus_hashes: Vec<ConsensusHash> = query_row_columns(
+            db_tx.tx(),
+            &"SELECT consensus_hash FROM snapshots WHERE block_height > ?1".to_string(),
+            &[&u64_to_sql(burn_height)?],
+            "consensus_hash",
+        )?;
+
+        for consensus_hash in consensus_hashes.iter() {
+            db_tx.tx().execute(
+                "DELETE FROM canonical_accepted_stacks_blocks WHERE tip_consensus_hash = ?1 OR consensus_hash = ?1",
+                &[consensus_hash],
+            )?;
+        }
+
+        for sortition_id in sortition_ids.iter() {
+            for table in [
+                "leader_keys",
+                "block_commits",
+                "user_burn_support",
+                "pre_stx",
+                "transfer_stx",
+                "snapshot_transition_ops",
+                "snapshots",
+            ]
+            .iter()
+            {
+                db_tx.tx().execute(
+                    &format!("DELETE FROM {} WHERE sortition_id = ?1", table),
+                    &[sortition_id],
+                )?;
+            }
+            db_tx.tx().execute(
+                "DELETE FROM marf_data WHERE block_hash = ?1",
+                &[sortition_id],
+            )?;
+        }
+
+        db_tx.commit()?;
+
+        info!(
+            "Rewound sortition DB to burn height {}: removed {} snapshots",
+            burn_height,
+            sortition_ids.len()
+        );
+        Ok(sortition_ids.len() as u64)
+    }
+
+    /// Check the sortition DB at `path` for damage left by an unclean shutdown:  SQLite-level
+    /// corruption, MARF tries among the `max_tries` most recent ones that can't be read in full,
+    /// and stale MARF extension locks.
+    /// If `repair` is true, stale locks are cleared, and the DB is rewound to the burn block height
+    /// below the lowest damaged trie, so the chains coordinator re-evaluates everything above it.
+    /// SQLite-level corruption can't be repaired in place.
+    /// The database at `path` must not be open anywhere else.
+    pub fn check_integrity(
+        path: &str,
+        max_tries: u32,
+        repair: bool,
+    ) -> Result<IntegrityReport, BurnchainError> {
+        let mut report = IntegrityReport::default();
+
+        let rewind_height = {
+            let mut db = SortitionDB::open(path, repair)?;
+
+            let damage = sqlite_quick_check(db.conn()).map_err(db_error::SqliteError)?;
+            if damage.len() > 0 {
+                for msg in damage.into_iter() {
+                    report
+                        .unrepairable
+                        .push(format!("Sortition DB is corrupt: {}", msg));
+                }
+                return Ok(report);
+            }
+
+            let num_locks = trie_sql::count_locks(db.conn()).map_err(db_error::IndexError)?;
+            if num_locks > 0 {
+                report.problems.push(format!(
+                    "Sortition DB MARF has {} stale extension lock(s)",
+                    num_locks
+                ));
+                if repair {
+                    trie_sql::clear_lock_data(db.conn()).map_err(db_error::IndexError)?;
+                    report
+                        .repairs
+                        .push("Cleared the sortition DB MARF's extension locks".to_string());
+                }
+            }
+
+            let bad_tries = db
+                .marf
+                .check_tries(max_tries)
+                .map_err(db_error::IndexError)?;
+
+            let mut rewind_height: Option<u64> = None;
+            for (sortition_id, reason) in bad_tries.into_iter() {
+                let snapshot_opt = SortitionDB::get_block_snapshot(db.conn(), &sortition_id)?;
+                match snapshot_opt {
+                    Some(ref snapshot) if snapshot.block_height > db.first_block_height => {
+                        report.problems.push(format!(
+                            "Sortition {} at burn height {} has a damaged MARF trie: {}",
+                            &sortition_id, snapshot.block_height, &reason
+                        ));
+                        let height = snapshot.block_height - 1;
+                        rewind_height = Some(cmp::min(height, rewind_height.unwrap_or(height)));
+                    }
+                    _ => {
+                        report.unrepairable.push(format!(
+                            "Sortition {} has a damaged MARF trie, and can't be rewound: {}",
+                            &sortition_id, &reason
+                        ));
+                    }
+                }
+            }
+            rewind_height
+        };
+
+        if let (true, Some(height)) = (repair, rewind_height) {
+            let num_sortitions = SortitionDB::rewind(path, height)?;
+            report.repairs.push(format!(
+                "Rewound the sortition DB to burn height {}, removing {} sortition(s)",
+                height, num_sortitions
+            ));
+        }
+
+        Ok(report)
+    }
+
     pub fn invalidate_descendants_of(
         &mut self,
         burn_block: &BurnchainHeaderHash,
@@ -2641,6 +3012,29 @@ impl SortitionDB {
         query_rows(conn, qry, args)
     }
 
+    /// Get the snapshots and block commits of the `num_blocks` most recent burn blocks in the
+    /// fork ending at `tip`, most recent first.
+    pub fn get_recent_block_commits<C: SortitionContext>(
+        ic: &IndexDBConn<'_, C, SortitionId>,
+        tip: &BlockSnapshot,
+        num_blocks: u64,
+    ) -> Result<Vec<(BlockSnapshot, Vec<LeaderBlockCommitOp>)>, db_error> {
+        let mut recent = vec![];
+        for block_height in (0..=tip.block_height).rev().take(num_blocks as usize) {
+            let snapshot =
+                match SortitionDB::get_ancestor_snapshot(ic, block_height, &tip.sortition_id)? {
+                    Some(sn) => sn,
+                    None => {
+                        break;
+                    }
+                };
+            let block_commits =
+                SortitionDB::get_block_commits_by_block(ic, &snapshot.sortition_id)?;
+            recent.push((snapshot, block_commits));
+        }
+        Ok(recent)
+    }
+
     /// Get all leader keys registered in a block on the burn chain's history in this fork.
     /// Returns the list of leader keys in order by vtxindex.
     pub fn get_leader_keys_by_block(
@@ -2653,6 +3047,55 @@ impl SortitionDB {
         query_rows(conn, qry, args)
     }
 
+    /// Get all STX transfers accepted in a burnchain block.  Every sortition of the block
+    /// accepts the same transfers, since whether a sender is registered only depends on the
+    /// burnchain blocks before it.
+    /// Returns the list of transfers in order by vtxindex.
+    pub fn get_transfer_stx_ops(
+        conn: &Connection,
+        burn_header_hash: &BurnchainHeaderHash,
+    ) -> Result<Vec<TransferStxOp>, db_error> {
+        let qry = "SELECT DISTINCT txid, vtxindex, block_height, burn_header_hash, sender, recipient, transfered_ustx, memo \
+                   FROM transfer_stx WHERE burn_header_hash = ?1 ORDER BY vtxindex ASC";
+        let args: &[&dyn ToSql] = &[burn_header_hash];
+
+        query_rows(conn, qry, args)
+    }
+
+    /// Get all STX transfers accepted in the burnchain blocks from `start_height` up to and
+    /// including `burn_tip`, on `burn_tip`'s fork of the burnchain.
+    /// Returns the list of transfers in the order they were sent.
+    pub fn get_transfer_stx_ops_since(
+        conn: &Connection,
+        start_height: u64,
+        burn_tip: &BurnchainHeaderHash,
+    ) -> Result<Vec<TransferStxOp>, db_error> {
+        let qry = "SELECT * FROM snapshots WHERE burn_header_hash = ?1 LIMIT 1";
+        let mut ops = vec![];
+        let mut cursor = burn_tip.clone();
+        loop {
+            let args: &[&dyn ToSql] = &[&cursor];
+            let sn: BlockSnapshot = match query_row(conn, qry, args)? {
+                Some(sn) => sn,
+                None => break,
+            };
+            if sn.block_height < start_height {
+                break;
+            }
+
+            let mut block_ops = SortitionDB::get_transfer_stx_ops(conn, &sn.burn_header_hash)?;
+            block_ops.reverse();
+            ops.append(&mut block_ops);
+
+            if sn.block_height == start_height || sn.is_initial() {
+                break;
+            }
+            cursor = sn.parent_burn_header_hash;
+        }
+        ops.reverse();
+        Ok(ops)
+    }
+
     pub fn get_block_winning_vtxindex(
         conn: &Connection,
         sortition: &SortitionId,
@@ -2782,6 +3225,35 @@ impl SortitionDB {
         })
     }
 
+    /// Find where a VRF public key was registered in the fork ending at `tip`, if it was.  If it
+    /// was registered more than once, the latest registration is returned.
+    pub fn get_leader_key_by_public_key<C: SortitionContext>(
+        ic: &IndexDBConn<'_, C, SortitionId>,
+        public_key: &VRFPublicKey,
+        tip: &SortitionId,
+    ) -> Result<Option<LeaderKeyRegisterOp>, db_error> {
+        let public_key_hex = public_key.to_hex();
+        let qry = "SELECT DISTINCT block_height FROM leader_keys WHERE public_key = ?1 ORDER BY block_height DESC".to_string();
+        let key_block_heights: Vec<u64> =
+            query_row_columns(ic, &qry, &[&public_key_hex], "block_height")?;
+        for key_block_height in key_block_heights.into_iter() {
+            let ancestor_snapshot =
+                match SortitionDB::get_ancestor_snapshot(ic, key_block_height, tip)? {
+                    Some(sn) => sn,
+                    None => {
+                        continue;
+                    }
+                };
+
+            let qry = "SELECT * FROM leader_keys WHERE sortition_id = ?1 AND public_key = ?2 ORDER BY vtxindex DESC LIMIT 1";
+            let args: &[&dyn ToSql] = &[&ancestor_snapshot.sortition_id, &public_key_hex];
+            if let Some(leader_key) = query_row(ic, qry, args)? {
+                return Ok(Some(leader_key));
+            }
+        }
+        Ok(None)
+    }
+
     /// Get a block commit by its committed block
     pub fn get_block_commit_for_stacks_block(
         conn: &Connection,
@@ -2887,6 +3359,26 @@ impl SortitionDB {
             return Ok(Some(BlockstackOperationType::UserBurnSupport(user_burn)));
         }
 
+        // pre-STX?
+        let pre_stx_sql = "SELECT * FROM pre_stx WHERE txid = ?1 LIMIT 1";
+
+        let pre_stx_res = query_row_panic(conn, &pre_stx_sql, &args, || {
+            "Multiple pre-STX ops with same txid".to_string()
+        })?;
+        if let Some(pre_stx) = pre_stx_res {
+            return Ok(Some(BlockstackOperationType::PreStx(pre_stx)));
+        }
+
+        // transfer STX?
+        let transfer_stx_sql = "SELECT * FROM transfer_stx WHERE txid = ?1 LIMIT 1";
+
+        let transfer_stx_res = query_row_panic(conn, &transfer_stx_sql, &args, || {
+            "Multiple transfer STX ops with same txid".to_string()
+        })?;
+        if let Some(transfer_stx) = transfer_stx_res {
+            return Ok(Some(BlockstackOperationType::TransferStx(transfer_stx)));
+        }
+
         Ok(None)
     }
 }
@@ -2991,6 +3483,20 @@ impl<'a> SortitionHandleTx<'a> {
                 );
                 self.insert_user_burn(op, sort_id)
             }
+            BlockstackOperationType::PreStx(ref op) => {
+                info!(
+                    "ACCEPTED({}) pre-STX {} at {},{}",
+                    op.block_height, &op.txid, op.block_height, op.vtxindex
+                );
+                self.insert_pre_stx(op, sort_id)
+            }
+            BlockstackOperationType::TransferStx(ref op) => {
+                info!(
+                    "ACCEPTED({}) transfer STX {} at {},{}",
+                    op.block_height, &op.txid, op.block_height, op.vtxindex
+                );
+                self.insert_transfer_stx(op, sort_id)
+            }
         }
     }
 
@@ -3100,6 +3606,59 @@ impl<'a> SortitionHandleTx<'a> {
         Ok(())
     }
 
+    /// Insert a pre-STX registration.
+    /// No validity checking will be done, beyond what is encoded in the pre_stx table
+    /// constraints.
+    /// The corresponding snapshot must already be inserted
+    fn insert_pre_stx(&mut self, op: &PreStxOp, sort_id: &SortitionId) -> Result<(), db_error> {
+        assert!(op.block_height < BLOCK_HEIGHT_MAX);
+
+        let args: &[&dyn ToSql] = &[
+            &op.txid,
+            &op.vtxindex,
+            &u64_to_sql(op.block_height)?,
+            &op.burn_header_hash,
+            &op.output.to_string(),
+            sort_id,
+        ];
+
+        self.execute("INSERT INTO pre_stx (txid, vtxindex, block_height, burn_header_hash, output, sortition_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6)", args)?;
+
+        Ok(())
+    }
+
+    /// Insert an STX transfer.
+    /// No validity checking will be done, beyond what is encoded in the transfer_stx table
+    /// constraints.
+    /// The corresponding snapshot must already be inserted
+    fn insert_transfer_stx(
+        &mut self,
+        op: &TransferStxOp,
+        sort_id: &SortitionId,
+    ) -> Result<(), db_error> {
+        assert!(op.block_height < BLOCK_HEIGHT_MAX);
+
+        // represent the amount as TEXT
+        let transfered_ustx_str = format!("{}", op.transfered_ustx);
+
+        let args: &[&dyn ToSql] = &[
+            &op.txid,
+            &op.vtxindex,
+            &u64_to_sql(op.block_height)?,
+            &op.burn_header_hash,
+            &op.sender.to_string(),
+            &op.recipient.to_string(),
+            &transfered_ustx_str,
+            &to_hex(&op.memo),
+            sort_id,
+        ];
+
+        self.execute("INSERT INTO transfer_stx (txid, vtxindex, block_height, burn_header_hash, sender, recipient, transfered_ustx, memo, sortition_id) \
+                      VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)", args)?;
+
+        Ok(())
+    }
+
     /// Insert a snapshots row from a block's-worth of operations.
     /// Do not call directly -- use append_chain_tip_snapshot to preserve the fork table structure.
     fn insert_block_snapshot(&self, snapshot: &BlockSnapshot) -> Result<(), db_error> {
@@ -3195,6 +3754,14 @@ impl<'a> SortitionHandleTx<'a> {
             }
         }
 
+        // record each address registered to send STX through the burnchain
+        for block_op in block_ops {
+            if let BlockstackOperationType::PreStx(ref data) = block_op {
+                keys.push(db_keys::pre_stx_address(&data.output));
+                values.push(data.txid.to_hex());
+            }
+        }
+
         // map burnchain header hashes to sortition ids
         keys.push(db_keys::sortition_id_for_bhh(&snapshot.burn_header_hash));
         values.push(snapshot.sortition_id.to_hex());
@@ -3606,6 +4173,27 @@ mod tests {
             )
             .unwrap();
             assert!(leader_key_none.is_none());
+
+            let leader_key_opt = SortitionDB::get_leader_key_by_public_key(
+                &ic,
+                &leader_key.public_key,
+                &new_snapshot.sortition_id,
+            )
+            .unwrap();
+            assert_eq!(leader_key_opt, Some(leader_key.clone()));
+
+            let other_public_key = VRFPublicKey::from_bytes(
+                &hex_bytes("bb519494643f79f1dea0350e6fb9a1da88dfdb6137117fc2523824a8aa44fe1c")
+                    .unwrap(),
+            )
+            .unwrap();
+            let leader_key_none = SortitionDB::get_leader_key_by_public_key(
+                &ic,
+                &other_public_key,
+                &new_snapshot.sortition_id,
+            )
+            .unwrap();
+            assert!(leader_key_none.is_none());
         }
     }
 
@@ -3724,6 +4312,29 @@ mod tests {
         let empty_snapshot =
             test_append_snapshot(&mut db, BurnchainHeaderHash([0x05; 32]), &vec![]);
 
+        // test get_recent_block_commits()
+        {
+            let ic = db.index_conn();
+            let recent = SortitionDB::get_recent_block_commits(&ic, &empty_snapshot, 3).unwrap();
+            let recent: Vec<_> = recent
+                .into_iter()
+                .map(|(sn, commits)| (sn.block_height, commits))
+                .collect();
+            assert_eq!(
+                recent,
+                vec![
+                    (empty_snapshot.block_height, vec![]),
+                    (snapshot_consumed.block_height, vec![block_commit.clone()]),
+                    (snapshot.block_height, vec![]),
+                ]
+            );
+
+            // stops at the first block
+            let recent = SortitionDB::get_recent_block_commits(&ic, &empty_snapshot, 1000).unwrap();
+            assert_eq!(recent.len(), 4);
+            assert_eq!(recent[3].0.block_height, block_height);
+        }
+
         // test get_block_commit_parent()
         {
             let ic = db.index_conn();
@@ -6086,4 +6697,252 @@ mod tests {
         );
         assert_eq!(last_snapshot.canonical_stacks_tip_height, 8);
     }
+
+    #[test]
+    fn test_stacks_epochs() {
+        let first_burn_hash = BurnchainHeaderHash::from_hex(
+            "10000000000000000000000000000000000000000000000000000000000000ff",
+        )
+        .unwrap();
+        let mut buf = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut buf);
+        let path = format!("/tmp/test-blockstack-sortdb-epochs-{}", to_hex(&buf));
+        let epochs = StacksEpoch::all(100, &[]);
+
+        let db = SortitionDB::connect(
+            &path,
+            100,
+            &first_burn_hash,
+            get_epoch_time_secs(),
+            &epochs,
+            true,
+        )
+        .unwrap();
+        assert_eq!(SortitionDB::get_stacks_epochs(db.conn()).unwrap(), epochs);
+        assert_eq!(
+            SortitionDB::get_stacks_epoch(db.conn(), 99)
+                .unwrap()
+                .unwrap()
+                .epoch_id,
+            StacksEpochId::Epoch10
+        );
+        assert_eq!(
+            SortitionDB::get_stacks_epoch(db.conn(), 100)
+                .unwrap()
+                .unwrap()
+                .epoch_id,
+            StacksEpochId::Epoch20
+        );
+        drop(db);
+
+        // reopening with the same epochs works
+        SortitionDB::connect(
+            &path,
+            100,
+            &first_burn_hash,
+            get_epoch_time_secs(),
+            &epochs,
+            false,
+        )
+        .unwrap();
+
+        // reopening with different epochs does not
+        match SortitionDB::connect(
+            &path,
+            100,
+            &first_burn_hash,
+            get_epoch_time_secs(),
+            &StacksEpoch::all(50, &[]),
+            true,
+        ) {
+            Err(db_error::Other(_)) => {}
+            Err(e) => panic!("Unexpected error: {:?}", &e),
+            Ok(_) => panic!("Opened sortdb with mismatched epochs"),
+        }
+
+        // invalid epochs are rejected
+        let mut bad_epochs = epochs.clone();
+        bad_epochs.pop();
+        assert!(SortitionDB::connect(
+            &path,
+            100,
+            &first_burn_hash,
+            get_epoch_time_secs(),
+            &bad_epochs,
+            true,
+        )
+        .is_err());
+
+        // a DB made before epochs were stored isn't written to when it's opened read-only
+        let db = SortitionDB::open(&path, true).unwrap();
+        db.conn().execute("DROP TABLE epochs", NO_PARAMS).unwrap();
+        drop(db);
+
+        let has_epochs_table = |conn: &Connection| {
+            query_count(
+                conn,
+                &"SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'epochs'"
+                    .to_string(),
+                NO_PARAMS,
+            )
+            .unwrap()
+                > 0
+        };
+
+        let db = SortitionDB::connect(
+            &path,
+            100,
+            &first_burn_hash,
+            get_epoch_time_secs(),
+            &epochs,
+            false,
+        )
+        .unwrap();
+        assert_eq!(SortitionDB::get_stacks_epochs(db.conn()).unwrap(), epochs);
+        assert!(!has_epochs_table(db.conn()));
+        drop(db);
+
+        let db = SortitionDB::open(&path, false).unwrap();
+        assert!(!has_epochs_table(db.conn()));
+        drop(db);
+
+        // ...and gets them when it's opened read/write
+        let db = SortitionDB::connect(
+            &path,
+            100,
+            &first_burn_hash,
+            get_epoch_time_secs(),
+            &epochs,
+            true,
+        )
+        .unwrap();
+        assert!(has_epochs_table(db.conn()));
+        assert_eq!(SortitionDB::get_stacks_epochs(db.conn()).unwrap(), epochs);
+    }
+
+    #[test]
+    fn test_rewind() {
+        let first_burn_hash = BurnchainHeaderHash::from_hex(
+            "10000000000000000000000000000000000000000000000000000000000000ff",
+        )
+        .unwrap();
+        let mut buf = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut buf);
+        let path = format!("/tmp/test-blockstack-sortdb-rewind-{}", to_hex(&buf));
+
+        let mut db = SortitionDB::connect(
+            &path,
+            0,
+            &first_burn_hash,
+            get_epoch_time_secs(),
+            &StacksEpoch::all(0, &[]),
+            true,
+        )
+        .unwrap();
+        let first_snapshot = SortitionDB::get_first_block_snapshot(db.conn()).unwrap();
+
+        // two forks that diverge after height 2
+        make_fork_run(&mut db, &first_snapshot, 5, 0);
+        let fork_snapshot = SortitionDB::get_block_snapshot(db.conn(), &SortitionId([0x01; 32]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(fork_snapshot.block_height, 2);
+        make_fork_run(&mut db, &fork_snapshot, 3, 0x80);
+        drop(db);
+
+        // both forks lose heights 3 through 5
+        assert_eq!(SortitionDB::rewind(&path, 2).unwrap(), 6);
+
+        let mut db = SortitionDB::open(&path, true).unwrap();
+        let tip = SortitionDB::get_canonical_burn_chain_tip(db.conn()).unwrap();
+        assert_eq!(tip.sortition_id, fork_snapshot.sortition_id);
+        for i in 2..5 {
+            assert!(
+                SortitionDB::get_block_snapshot(db.conn(), &SortitionId([i; 32]))
+                    .unwrap()
+                    .is_none()
+            );
+            assert!(
+                SortitionDB::get_block_snapshot(db.conn(), &SortitionId([i | 0x80; 32]))
+                    .unwrap()
+                    .is_none()
+            );
+        }
+
+        // the rewound sortitions can be processed again
+        make_fork_run(&mut db, &tip, 3, 0);
+        let tip = SortitionDB::get_canonical_burn_chain_tip(db.conn()).unwrap();
+        assert_eq!(tip.block_height, 5);
+        assert_eq!(tip.sortition_id, SortitionId([0x04; 32]));
+
+        // nothing above the tip, so nothing to rewind
+        drop(db);
+        assert_eq!(SortitionDB::rewind(&path, 5).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_check_integrity() {
+        let first_burn_hash = BurnchainHeaderHash::from_hex(
+            "10000000000000000000000000000000000000000000000000000000000000ff",
+        )
+        .unwrap();
+        let mut buf = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut buf);
+        let path = format!(
+            "/tmp/test-blockstack-sortdb-check-integrity-{}",
+            to_hex(&buf)
+        );
+
+        let mut db = SortitionDB::connect(
+            &path,
+            0,
+            &first_burn_hash,
+            get_epoch_time_secs(),
+            &StacksEpoch::all(0, &[]),
+            true,
+        )
+        .unwrap();
+        let first_snapshot = SortitionDB::get_first_block_snapshot(db.conn()).unwrap();
+        make_fork_run(&mut db, &first_snapshot, 5, 0);
+        drop(db);
+
+        assert!(SortitionDB::check_integrity(&path, 100, false)
+            .unwrap()
+            .is_ok());
+
+        // cut off the end of the trie at height 4, as if it had only been partly written
+        let db = SortitionDB::open(&path, true).unwrap();
+        db.conn()
+            .execute(
+                "UPDATE marf_data SET data = substr(data, 1, 40) WHERE block_hash = ?1",
+                &[&SortitionId([0x03; 32])],
+            )
+            .unwrap();
+        drop(db);
+
+        // found, but not repaired
+        let report = SortitionDB::check_integrity(&path, 100, false).unwrap();
+        assert_eq!(report.problems.len(), 1);
+        assert_eq!(report.repairs.len(), 0);
+        assert_eq!(report.unrepairable.len(), 0);
+
+        // too old to be checked
+        assert!(SortitionDB::check_integrity(&path, 1, false)
+            .unwrap()
+            .is_ok());
+
+        // repaired by rewinding to the height below it
+        let report = SortitionDB::check_integrity(&path, 100, true).unwrap();
+        assert_eq!(report.problems.len(), 1);
+        assert_eq!(report.repairs.len(), 1);
+
+        let db = SortitionDB::open(&path, false).unwrap();
+        let tip = SortitionDB::get_canonical_burn_chain_tip(db.conn()).unwrap();
+        assert_eq!(tip.block_height, 3);
+        drop(db);
+
+        assert!(SortitionDB::check_integrity(&path, 100, false)
+            .unwrap()
+            .is_ok());
+    }
 }
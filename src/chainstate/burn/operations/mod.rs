@@ -17,6 +17,8 @@
 pub mod leader_block_commit;
 /// This module contains all burn-chain operations
 pub mod leader_key_register;
+pub mod pre_stx;
+pub mod transfer_stx;
 pub mod user_burn_support;
 
 use std::convert::From;
@@ -77,6 +79,12 @@ pub enum Error {
     // all the things that can go wrong with user burn supports
     UserBurnSupportBadConsensusHash,
     UserBurnSupportNoLeaderKey,
+
+    // all the things that can go wrong with STX operations
+    StxOpsNotActive,
+    TransferStxMustBePositive,
+    TransferStxSelfSend,
+    TransferStxNoPreStx,
 }
 
 impl fmt::Display for Error {
@@ -117,6 +125,16 @@ impl fmt::Display for Error {
                 f,
                 "User burn support does not match a registered leader key"
             ),
+
+            Error::StxOpsNotActive => {
+                write!(f, "STX operations are not active before Stacks 2.1")
+            }
+            Error::TransferStxMustBePositive => write!(f, "Transfer STX must be positive amount"),
+            Error::TransferStxSelfSend => write!(f, "Transfer STX must not send to self"),
+            Error::TransferStxNoPreStx => write!(
+                f,
+                "Transfer STX sender was not registered by an earlier pre-STX operation"
+            ),
         }
     }
 }
@@ -191,6 +209,31 @@ pub struct UserBurnSupportOp {
     pub burn_header_hash: BurnchainHeaderHash, // hash of burnchain block with this tx
 }
 
+#[derive(Debug, PartialEq, Clone, Eq, Serialize, Deserialize)]
+pub struct PreStxOp {
+    pub output: StacksAddress, // address that may now send STX through burnchain operations
+
+    // common to all transactions
+    pub txid: Txid,                            // transaction ID
+    pub vtxindex: u32,                         // index in the block where this tx occurs
+    pub block_height: u64,                     // block height at which this tx occurs
+    pub burn_header_hash: BurnchainHeaderHash, // hash of burnchain block with this tx
+}
+
+#[derive(Debug, PartialEq, Clone, Eq, Serialize, Deserialize)]
+pub struct TransferStxOp {
+    pub sender: StacksAddress, // address of the tx's first input; must be registered by a PreStxOp
+    pub recipient: StacksAddress,
+    pub transfered_ustx: u128,
+    pub memo: Vec<u8>,
+
+    // common to all transactions
+    pub txid: Txid,                            // transaction ID
+    pub vtxindex: u32,                         // index in the block where this tx occurs
+    pub block_height: u64,                     // block height at which this tx occurs
+    pub burn_header_hash: BurnchainHeaderHash, // hash of burnchain block with this tx
+}
+
 pub trait BlockstackOperation {
     fn from_tx(
         block_header: &BurnchainBlockHeader,
@@ -205,6 +248,8 @@ pub enum BlockstackOperationType {
     LeaderKeyRegister(LeaderKeyRegisterOp),
     LeaderBlockCommit(LeaderBlockCommitOp),
     UserBurnSupport(UserBurnSupportOp),
+    PreStx(PreStxOp),
+    TransferStx(TransferStxOp),
 }
 
 impl BlockstackOperationType {
@@ -213,6 +258,8 @@ impl BlockstackOperationType {
             BlockstackOperationType::LeaderKeyRegister(_) => Opcodes::LeaderKeyRegister,
             BlockstackOperationType::LeaderBlockCommit(_) => Opcodes::LeaderBlockCommit,
             BlockstackOperationType::UserBurnSupport(_) => Opcodes::UserBurnSupport,
+            BlockstackOperationType::PreStx(_) => Opcodes::PreStx,
+            BlockstackOperationType::TransferStx(_) => Opcodes::TransferStx,
         }
     }
 
@@ -221,6 +268,8 @@ impl BlockstackOperationType {
             BlockstackOperationType::LeaderKeyRegister(ref data) => data.txid.clone(),
             BlockstackOperationType::LeaderBlockCommit(ref data) => data.txid.clone(),
             BlockstackOperationType::UserBurnSupport(ref data) => data.txid.clone(),
+            BlockstackOperationType::PreStx(ref data) => data.txid.clone(),
+            BlockstackOperationType::TransferStx(ref data) => data.txid.clone(),
         }
     }
 
@@ -229,6 +278,8 @@ impl BlockstackOperationType {
             BlockstackOperationType::LeaderKeyRegister(ref data) => data.vtxindex,
             BlockstackOperationType::LeaderBlockCommit(ref data) => data.vtxindex,
             BlockstackOperationType::UserBurnSupport(ref data) => data.vtxindex,
+            BlockstackOperationType::PreStx(ref data) => data.vtxindex,
+            BlockstackOperationType::TransferStx(ref data) => data.vtxindex,
         }
     }
 
@@ -237,6 +288,8 @@ impl BlockstackOperationType {
             BlockstackOperationType::LeaderKeyRegister(ref data) => data.block_height,
             BlockstackOperationType::LeaderBlockCommit(ref data) => data.block_height,
             BlockstackOperationType::UserBurnSupport(ref data) => data.block_height,
+            BlockstackOperationType::PreStx(ref data) => data.block_height,
+            BlockstackOperationType::TransferStx(ref data) => data.block_height,
         }
     }
 
@@ -245,6 +298,8 @@ impl BlockstackOperationType {
             BlockstackOperationType::LeaderKeyRegister(ref data) => data.burn_header_hash.clone(),
             BlockstackOperationType::LeaderBlockCommit(ref data) => data.burn_header_hash.clone(),
             BlockstackOperationType::UserBurnSupport(ref data) => data.burn_header_hash.clone(),
+            BlockstackOperationType::PreStx(ref data) => data.burn_header_hash.clone(),
+            BlockstackOperationType::TransferStx(ref data) => data.burn_header_hash.clone(),
         }
     }
 
@@ -254,6 +309,8 @@ impl BlockstackOperationType {
             BlockstackOperationType::LeaderKeyRegister(ref mut data) => data.block_height = height,
             BlockstackOperationType::LeaderBlockCommit(ref mut data) => data.block_height = height,
             BlockstackOperationType::UserBurnSupport(ref mut data) => data.block_height = height,
+            BlockstackOperationType::PreStx(ref mut data) => data.block_height = height,
+            BlockstackOperationType::TransferStx(ref mut data) => data.block_height = height,
         };
     }
 
@@ -267,6 +324,8 @@ impl BlockstackOperationType {
                 data.burn_header_hash = hash
             }
             BlockstackOperationType::UserBurnSupport(ref mut data) => data.burn_header_hash = hash,
+            BlockstackOperationType::PreStx(ref mut data) => data.burn_header_hash = hash,
+            BlockstackOperationType::TransferStx(ref mut data) => data.burn_header_hash = hash,
         };
     }
 }
@@ -283,6 +342,12 @@ impl fmt::Display for BlockstackOperationType {
             BlockstackOperationType::UserBurnSupport(ref user_burn_support) => {
                 fmt::Display::fmt(&format!("{:?}", user_burn_support), f)
             }
+            BlockstackOperationType::PreStx(ref pre_stx) => {
+                fmt::Display::fmt(&format!("{:?}", pre_stx), f)
+            }
+            BlockstackOperationType::TransferStx(ref transfer_stx) => {
+                fmt::Display::fmt(&format!("{:?}", transfer_stx), f)
+            }
         }
     }
 }
@@ -295,3 +360,7 @@ pub fn parse_u32_from_be(bytes: &[u8]) -> Option<u32> {
 pub fn parse_u16_from_be(bytes: &[u8]) -> Option<u16> {
     bytes.try_into().ok().map(u16::from_be_bytes)
 }
+
+pub fn parse_u128_from_be(bytes: &[u8]) -> Option<u128> {
+    bytes.try_into().ok().map(u128::from_be_bytes)
+}
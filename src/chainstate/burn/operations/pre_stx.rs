@@ -0,0 +1,247 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::{Read, Write};
+
+use chainstate::burn::db::sortdb::SortitionHandleTx;
+use chainstate::burn::operations::Error as op_error;
+use chainstate::burn::operations::{BlockstackOperation, PreStxOp};
+use chainstate::burn::Opcodes;
+
+use core::StacksEpochId;
+
+use burnchains::Address;
+use burnchains::Burnchain;
+use burnchains::BurnchainBlockHeader;
+use burnchains::BurnchainHeaderHash;
+use burnchains::BurnchainTransaction;
+
+use net::codec::write_next;
+use net::Error as net_error;
+use net::StacksMessageCodec;
+
+impl PreStxOp {
+    fn parse_from_tx(
+        block_height: u64,
+        block_hash: &BurnchainHeaderHash,
+        tx: &BurnchainTransaction,
+    ) -> Result<PreStxOp, op_error> {
+        /*
+            Wire format:
+
+            0      2  3
+            |------|--|
+             magic  op
+
+            The first output is the address that may now send STX through burnchain operations.
+        */
+        let inputs = tx.get_signers();
+        let outputs = tx.get_recipients();
+
+        if inputs.len() == 0 || outputs.len() == 0 {
+            test_debug!(
+                "Invalid tx: inputs: {}, outputs: {}",
+                inputs.len(),
+                outputs.len()
+            );
+            return Err(op_error::InvalidInput);
+        }
+
+        if tx.opcode() != Opcodes::PreStx as u8 {
+            test_debug!("Invalid tx: invalid opcode {}", tx.opcode());
+            return Err(op_error::InvalidInput);
+        }
+
+        if outputs[0].address.is_burn() {
+            test_debug!("Invalid tx: cannot register the burn address");
+            return Err(op_error::ParseError);
+        }
+
+        Ok(PreStxOp {
+            output: outputs[0].address.clone(),
+
+            txid: tx.txid(),
+            vtxindex: tx.vtxindex(),
+            block_height: block_height,
+            burn_header_hash: block_hash.clone(),
+        })
+    }
+}
+
+impl StacksMessageCodec for PreStxOp {
+    /*
+        Wire format:
+
+        0      2  3
+        |------|--|
+         magic  op
+    */
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), net_error> {
+        write_next(fd, &(Opcodes::PreStx as u8))?;
+        Ok(())
+    }
+
+    fn consensus_deserialize<R: Read>(_fd: &mut R) -> Result<PreStxOp, net_error> {
+        // Op deserialized through burchain indexer
+        unimplemented!();
+    }
+}
+
+impl BlockstackOperation for PreStxOp {
+    fn from_tx(
+        block_header: &BurnchainBlockHeader,
+        tx: &BurnchainTransaction,
+    ) -> Result<PreStxOp, op_error> {
+        PreStxOp::parse_from_tx(block_header.block_height, &block_header.block_hash, tx)
+    }
+}
+
+impl PreStxOp {
+    pub fn check(
+        &self,
+        _burnchain: &Burnchain,
+        tx: &mut SortitionHandleTx,
+    ) -> Result<(), op_error> {
+        if tx.get_stacks_epoch_id(self.block_height)? < StacksEpochId::Epoch21 {
+            warn!(
+                "Invalid pre-STX: STX operations are not active at burn height {}",
+                self.block_height
+            );
+            return Err(op_error::StxOpsNotActive);
+        }
+
+        // any address may register itself
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use burnchains::bitcoin::address::{BitcoinAddress, BitcoinAddressType};
+    use burnchains::bitcoin::keys::BitcoinPublicKey;
+    use burnchains::bitcoin::{
+        BitcoinInputType, BitcoinNetworkType, BitcoinTransaction, BitcoinTxInput, BitcoinTxOutput,
+    };
+    use burnchains::Txid;
+
+    use chainstate::burn::operations::BlockstackOperationType;
+    use chainstate::stacks::StacksAddress;
+
+    use util::get_epoch_time_secs;
+    use util::hash::Hash160;
+
+    fn make_tx(opcode: u8, outputs: Vec<BitcoinTxOutput>) -> BurnchainTransaction {
+        BurnchainTransaction::Bitcoin(BitcoinTransaction {
+            txid: Txid([0x11; 32]),
+            vtxindex: 3,
+            opcode: opcode,
+            data: vec![],
+            inputs: vec![BitcoinTxInput {
+                keys: vec![BitcoinPublicKey::from_hex(
+                    "02d8015134d9db8178ac93acbc43170a2f20febba5087a5b0437058765ad5133d0",
+                )
+                .unwrap()],
+                num_required: 1,
+                in_type: BitcoinInputType::Standard,
+            }],
+            outputs: outputs,
+        })
+    }
+
+    fn make_output(bytes: Hash160) -> BitcoinTxOutput {
+        BitcoinTxOutput {
+            address: BitcoinAddress {
+                addrtype: BitcoinAddressType::PublicKeyHash,
+                network_id: BitcoinNetworkType::Testnet,
+                bytes: bytes,
+            },
+            units: 5500,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let header = BurnchainBlockHeader {
+            block_height: 694,
+            block_hash: BurnchainHeaderHash([0x22; 32]),
+            parent_block_hash: BurnchainHeaderHash([0x33; 32]),
+            num_txs: 1,
+            timestamp: get_epoch_time_secs(),
+        };
+
+        let output = make_output(Hash160([0x44; 20]));
+        let op = PreStxOp::from_tx(
+            &header,
+            &make_tx(Opcodes::PreStx as u8, vec![output.clone()]),
+        )
+        .unwrap();
+        assert_eq!(
+            op,
+            PreStxOp {
+                output: StacksAddress::from_bitcoin_address(&output.address),
+                txid: Txid([0x11; 32]),
+                vtxindex: 3,
+                block_height: 694,
+                burn_header_hash: BurnchainHeaderHash([0x22; 32]),
+            }
+        );
+
+        // wrong opcode
+        assert!(PreStxOp::from_tx(
+            &header,
+            &make_tx(Opcodes::TransferStx as u8, vec![output.clone()])
+        )
+        .is_err());
+
+        // no outputs
+        assert!(PreStxOp::from_tx(&header, &make_tx(Opcodes::PreStx as u8, vec![])).is_err());
+
+        // can't register the burn address
+        assert!(PreStxOp::from_tx(
+            &header,
+            &make_tx(
+                Opcodes::PreStx as u8,
+                vec![make_output(Hash160([0x00; 20]))]
+            )
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_classify_gated_by_epoch() {
+        let header = BurnchainBlockHeader {
+            block_height: 694,
+            block_hash: BurnchainHeaderHash([0x22; 32]),
+            parent_block_hash: BurnchainHeaderHash([0x33; 32]),
+            num_txs: 1,
+            timestamp: get_epoch_time_secs(),
+        };
+        let tx = make_tx(
+            Opcodes::PreStx as u8,
+            vec![make_output(Hash160([0x44; 20]))],
+        );
+
+        // before Stacks 2.1, the opcode means nothing
+        assert!(Burnchain::classify_transaction(&header, &tx, StacksEpochId::Epoch20).is_none());
+        match Burnchain::classify_transaction(&header, &tx, StacksEpochId::Epoch21) {
+            Some(BlockstackOperationType::PreStx(op)) => {
+                assert_eq!(op, PreStxOp::from_tx(&header, &tx).unwrap())
+            }
+            op => panic!("Unexpected operation: {:?}", &op),
+        }
+    }
+}
@@ -0,0 +1,435 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::{Read, Write};
+
+use chainstate::burn::db::sortdb::SortitionHandleTx;
+use chainstate::burn::operations::Error as op_error;
+use chainstate::burn::operations::{parse_u128_from_be, BlockstackOperation, TransferStxOp};
+use chainstate::burn::Opcodes;
+use chainstate::stacks::{
+    StacksAddress, C32_ADDRESS_VERSION_MAINNET_MULTISIG, C32_ADDRESS_VERSION_MAINNET_SINGLESIG,
+};
+
+use burnchains::Address;
+use burnchains::Burnchain;
+use burnchains::BurnchainBlockHeader;
+use burnchains::BurnchainHeaderHash;
+use burnchains::BurnchainSigner;
+use burnchains::BurnchainTransaction;
+
+use core::StacksEpochId;
+
+use net::codec::write_next;
+use net::Error as net_error;
+use net::StacksMessageCodec;
+
+use util::hash::Hash160;
+
+/// Largest memo that fits in an 80-byte OP_RETURN after the magic, opcode and amount
+pub const TRANSFER_STX_MAX_MEMO_LEN: usize = 61;
+
+// return type for parse_data (below)
+struct ParsedData {
+    transfered_ustx: u128,
+    memo: Vec<u8>,
+}
+
+impl TransferStxOp {
+    fn parse_data(data: &Vec<u8>) -> Option<ParsedData> {
+        /*
+            Wire format:
+
+            0      2  3                             19        80
+            |------|--|-----------------------------|---------|
+             magic  op     uSTX to transfer (u128)     memo (up to 61 bytes)
+
+             Note that `data` is missing the first 3 bytes -- the magic and op have been stripped
+        */
+        if data.len() < 16 || data.len() > 16 + TRANSFER_STX_MAX_MEMO_LEN {
+            warn!("TRANSFER_STX payload is malformed ({} bytes)", data.len());
+            return None;
+        }
+
+        let transfered_ustx = parse_u128_from_be(&data[0..16]).unwrap();
+        let memo = data[16..].to_vec();
+
+        Some(ParsedData {
+            transfered_ustx,
+            memo,
+        })
+    }
+
+    /// The sender is the address of the transaction's first input, on the same network as the
+    /// recipient.
+    fn sender_address(signer: &BurnchainSigner, recipient: &StacksAddress) -> StacksAddress {
+        let mainnet = recipient.version == C32_ADDRESS_VERSION_MAINNET_SINGLESIG
+            || recipient.version == C32_ADDRESS_VERSION_MAINNET_MULTISIG;
+        let version = if mainnet {
+            signer.hash_mode.to_version_mainnet()
+        } else {
+            signer.hash_mode.to_version_testnet()
+        };
+        StacksAddress {
+            version: version,
+            bytes: Hash160::from_bytes(&signer.to_address_bits())
+                .expect("FATAL: address bits are not a Hash160"),
+        }
+    }
+
+    fn parse_from_tx(
+        block_height: u64,
+        block_hash: &BurnchainHeaderHash,
+        tx: &BurnchainTransaction,
+    ) -> Result<TransferStxOp, op_error> {
+        let inputs = tx.get_signers();
+        let outputs = tx.get_recipients();
+
+        if inputs.len() == 0 || outputs.len() == 0 {
+            test_debug!(
+                "Invalid tx: inputs: {}, outputs: {}",
+                inputs.len(),
+                outputs.len()
+            );
+            return Err(op_error::InvalidInput);
+        }
+
+        if tx.opcode() != Opcodes::TransferStx as u8 {
+            test_debug!("Invalid tx: invalid opcode {}", tx.opcode());
+            return Err(op_error::InvalidInput);
+        }
+
+        // outputs[0] is the recipient
+        if outputs[0].address.is_burn() {
+            test_debug!("Invalid tx: cannot transfer STX to the burn address");
+            return Err(op_error::ParseError);
+        }
+
+        let data = match TransferStxOp::parse_data(&tx.data()) {
+            None => {
+                test_debug!("Invalid tx data");
+                return Err(op_error::ParseError);
+            }
+            Some(d) => d,
+        };
+
+        let recipient = outputs[0].address.clone();
+        let sender = TransferStxOp::sender_address(&inputs[0], &recipient);
+
+        Ok(TransferStxOp {
+            sender: sender,
+            recipient: recipient,
+            transfered_ustx: data.transfered_ustx,
+            memo: data.memo,
+
+            txid: tx.txid(),
+            vtxindex: tx.vtxindex(),
+            block_height: block_height,
+            burn_header_hash: block_hash.clone(),
+        })
+    }
+}
+
+impl StacksMessageCodec for TransferStxOp {
+    /*
+        Wire format:
+
+        0      2  3                             19        80
+        |------|--|-----------------------------|---------|
+         magic  op     uSTX to transfer (u128)     memo (up to 61 bytes)
+    */
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), net_error> {
+        if self.memo.len() > TRANSFER_STX_MAX_MEMO_LEN {
+            return Err(net_error::SerializeError(format!(
+                "Memo is longer than {} bytes",
+                TRANSFER_STX_MAX_MEMO_LEN
+            )));
+        }
+        write_next(fd, &(Opcodes::TransferStx as u8))?;
+        fd.write_all(&self.transfered_ustx.to_be_bytes())
+            .map_err(net_error::WriteError)?;
+        fd.write_all(&self.memo).map_err(net_error::WriteError)?;
+        Ok(())
+    }
+
+    fn consensus_deserialize<R: Read>(_fd: &mut R) -> Result<TransferStxOp, net_error> {
+        // Op deserialized through burchain indexer
+        unimplemented!();
+    }
+}
+
+impl BlockstackOperation for TransferStxOp {
+    fn from_tx(
+        block_header: &BurnchainBlockHeader,
+        tx: &BurnchainTransaction,
+    ) -> Result<TransferStxOp, op_error> {
+        TransferStxOp::parse_from_tx(block_header.block_height, &block_header.block_hash, tx)
+    }
+}
+
+impl TransferStxOp {
+    pub fn check(
+        &self,
+        _burnchain: &Burnchain,
+        tx: &mut SortitionHandleTx,
+    ) -> Result<(), op_error> {
+        if tx.get_stacks_epoch_id(self.block_height)? < StacksEpochId::Epoch21 {
+            warn!(
+                "Invalid transfer STX: STX operations are not active at burn height {}",
+                self.block_height
+            );
+            return Err(op_error::StxOpsNotActive);
+        }
+
+        if self.transfered_ustx == 0 {
+            warn!("Invalid transfer STX: must transfer a positive amount");
+            return Err(op_error::TransferStxMustBePositive);
+        }
+
+        if self.sender == self.recipient {
+            warn!(
+                "Invalid transfer STX: sender and recipient are both {}",
+                &self.sender
+            );
+            return Err(op_error::TransferStxSelfSend);
+        }
+
+        /////////////////////////////////////////////////////////////////////////////////////
+        // The sender must have been registered by a PreStxOp in an earlier block of this
+        // fork.  Whether or not the sender can afford the transfer is decided when a Stacks
+        // block materializes it.
+        /////////////////////////////////////////////////////////////////////////////////////
+        if !tx.has_pre_stx_address(&self.sender)? {
+            warn!(
+                "Invalid transfer STX: sender {} has not been registered",
+                &self.sender
+            );
+            return Err(op_error::TransferStxNoPreStx);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use address::AddressHashMode;
+    use burnchains::bitcoin::address::{BitcoinAddress, BitcoinAddressType};
+    use burnchains::bitcoin::keys::BitcoinPublicKey;
+    use burnchains::bitcoin::{
+        BitcoinInputType, BitcoinNetworkType, BitcoinTransaction, BitcoinTxInput, BitcoinTxOutput,
+    };
+    use burnchains::Txid;
+
+    use chainstate::burn::db::sortdb::SortitionDB;
+    use chainstate::burn::operations::PreStxOp;
+    use chainstate::stacks::C32_ADDRESS_VERSION_TESTNET_SINGLESIG;
+
+    use core::StacksEpoch;
+
+    use rand;
+    use rand::RngCore;
+
+    use util::get_epoch_time_secs;
+    use util::hash::to_hex;
+
+    fn make_tx(opcode: u8, data: Vec<u8>, outputs: Vec<BitcoinTxOutput>) -> BurnchainTransaction {
+        BurnchainTransaction::Bitcoin(BitcoinTransaction {
+            txid: Txid([0x11; 32]),
+            vtxindex: 3,
+            opcode: opcode,
+            data: data,
+            inputs: vec![BitcoinTxInput {
+                keys: vec![BitcoinPublicKey::from_hex(
+                    "02d8015134d9db8178ac93acbc43170a2f20febba5087a5b0437058765ad5133d0",
+                )
+                .unwrap()],
+                num_required: 1,
+                in_type: BitcoinInputType::Standard,
+            }],
+            outputs: outputs,
+        })
+    }
+
+    fn make_output(bytes: Hash160) -> BitcoinTxOutput {
+        BitcoinTxOutput {
+            address: BitcoinAddress {
+                addrtype: BitcoinAddressType::PublicKeyHash,
+                network_id: BitcoinNetworkType::Testnet,
+                bytes: bytes,
+            },
+            units: 5500,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let header = BurnchainBlockHeader {
+            block_height: 694,
+            block_hash: BurnchainHeaderHash([0x22; 32]),
+            parent_block_hash: BurnchainHeaderHash([0x33; 32]),
+            num_txs: 1,
+            timestamp: get_epoch_time_secs(),
+        };
+
+        let mut data = 12345u128.to_be_bytes().to_vec();
+        data.extend_from_slice(b"hello");
+
+        let output = make_output(Hash160([0x44; 20]));
+        let tx = make_tx(
+            Opcodes::TransferStx as u8,
+            data.clone(),
+            vec![output.clone()],
+        );
+        let op = TransferStxOp::from_tx(&header, &tx).unwrap();
+
+        let sender_bytes = match tx {
+            BurnchainTransaction::Bitcoin(ref btc) => {
+                BurnchainSigner::from_bitcoin_input(&btc.inputs[0]).to_address_bits()
+            }
+        };
+        assert_eq!(
+            op,
+            TransferStxOp {
+                sender: StacksAddress {
+                    version: C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+                    bytes: Hash160::from_bytes(&sender_bytes).unwrap(),
+                },
+                recipient: StacksAddress::from_bitcoin_address(&output.address),
+                transfered_ustx: 12345,
+                memo: b"hello".to_vec(),
+                txid: Txid([0x11; 32]),
+                vtxindex: 3,
+                block_height: 694,
+                burn_header_hash: BurnchainHeaderHash([0x22; 32]),
+            }
+        );
+        assert_eq!(
+            AddressHashMode::SerializeP2PKH.to_version_testnet(),
+            op.sender.version
+        );
+
+        // serializes to the same payload
+        let mut bytes = vec![];
+        op.consensus_serialize(&mut bytes).unwrap();
+        assert_eq!(bytes[0], Opcodes::TransferStx as u8);
+        assert_eq!(bytes[1..].to_vec(), data);
+
+        // wrong opcode
+        assert!(TransferStxOp::from_tx(
+            &header,
+            &make_tx(Opcodes::PreStx as u8, data.clone(), vec![output.clone()])
+        )
+        .is_err());
+
+        // too short
+        assert!(TransferStxOp::from_tx(
+            &header,
+            &make_tx(
+                Opcodes::TransferStx as u8,
+                data[0..15].to_vec(),
+                vec![output.clone()]
+            )
+        )
+        .is_err());
+
+        // memo too long
+        let mut long_data = data.clone();
+        long_data.extend_from_slice(&[0u8; TRANSFER_STX_MAX_MEMO_LEN]);
+        assert!(TransferStxOp::from_tx(
+            &header,
+            &make_tx(Opcodes::TransferStx as u8, long_data, vec![output.clone()])
+        )
+        .is_err());
+
+        // can't send to the burn address
+        assert!(TransferStxOp::from_tx(
+            &header,
+            &make_tx(
+                Opcodes::TransferStx as u8,
+                data.clone(),
+                vec![make_output(Hash160([0x00; 20]))]
+            )
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_check_gated_by_epoch() {
+        let first_burn_hash = BurnchainHeaderHash([0x01; 32]);
+        let mut buf = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut buf);
+        let path = format!("/tmp/test-blockstack-sortdb-stx-ops-{}", to_hex(&buf));
+        let epochs = StacksEpoch::all(100, &[(StacksEpochId::Epoch21, 200)]);
+        let mut db = SortitionDB::connect(
+            &path,
+            100,
+            &first_burn_hash,
+            get_epoch_time_secs(),
+            &epochs,
+            true,
+        )
+        .unwrap();
+        let burnchain = Burnchain::default_unittest(100, &first_burn_hash);
+
+        let sender = StacksAddress {
+            version: C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            bytes: Hash160([0x01; 20]),
+        };
+        let mut pre_stx = PreStxOp {
+            output: sender.clone(),
+            txid: Txid([0x11; 32]),
+            vtxindex: 1,
+            block_height: 150,
+            burn_header_hash: BurnchainHeaderHash([0x22; 32]),
+        };
+        let mut transfer_stx = TransferStxOp {
+            sender: sender,
+            recipient: StacksAddress {
+                version: C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+                bytes: Hash160([0x02; 20]),
+            },
+            transfered_ustx: 12345,
+            memo: vec![],
+            txid: Txid([0x12; 32]),
+            vtxindex: 2,
+            block_height: 150,
+            burn_header_hash: BurnchainHeaderHash([0x22; 32]),
+        };
+
+        let mut tx = db.tx_begin_at_tip();
+
+        // before Stacks 2.1, STX operations are rejected outright...
+        match pre_stx.check(&burnchain, &mut tx) {
+            Err(op_error::StxOpsNotActive) => {}
+            res => panic!("Unexpected result: {:?}", &res),
+        }
+        match transfer_stx.check(&burnchain, &mut tx) {
+            Err(op_error::StxOpsNotActive) => {}
+            res => panic!("Unexpected result: {:?}", &res),
+        }
+
+        // ...and from then on, they're checked as usual
+        pre_stx.block_height = 200;
+        transfer_stx.block_height = 200;
+        pre_stx.check(&burnchain, &mut tx).unwrap();
+        match transfer_stx.check(&burnchain, &mut tx) {
+            Err(op_error::TransferStxNoPreStx) => {}
+            res => panic!("Unexpected result: {:?}", &res),
+        }
+    }
+}
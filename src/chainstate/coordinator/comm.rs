@@ -14,12 +14,25 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+//! Signaling between the chains coordinator and the rest of the node.
+//!
+//! The coordinator thread (`ChainsCoordinator::run`) is the only writer that processes
+//! sortitions and appends Stacks blocks to the chainstate.  Other subsystems never process
+//! blocks themselves: the burnchain downloader stores burnchain blocks and calls
+//! `CoordinatorChannels::announce_new_burn_block()`, and the relayer stores staging blocks and
+//! calls `CoordinatorChannels::announce_new_stacks_block()`.  Announcements of the same kind
+//! coalesce until the coordinator wakes up, since each one just means "there may be new work".
+//! Threads that need to wait for the coordinator to catch up use the processed counters and
+//! `wait_for_sortitions_processed()` / `wait_for_stacks_blocks_processed()`, and
+//! `stop_chains_coordinator()` makes it exit between events.  `announce_flush_chainstate()` asks
+//! it to checkpoint the databases it writes, for operators who want them flushed to disk.
+
+use std::process;
 use std::sync::{
     atomic::{AtomicU64, Ordering},
     Arc, Condvar, Mutex, RwLock,
 };
 use std::time::{Duration, Instant};
-use std::{process, thread};
 
 /// Trait for use by the ChainsCoordinator
 ///
@@ -31,6 +44,16 @@ pub trait CoordinatorNotices {
 pub struct ArcCounterCoordinatorNotices {
     pub stacks_blocks_processed: Arc<AtomicU64>,
     pub sortitions_processed: Arc<AtomicU64>,
+    pub processed_wakeup: Arc<ProcessedWakeup>,
+}
+
+/// Wakeup for threads waiting on the coordinator to
+///   process sortitions or Stacks blocks. The coordinator
+///   notifies it after bumping its processed counters, so
+///   waiters don't have to poll those counters.
+pub struct ProcessedWakeup {
+    lock: Mutex<()>,
+    wakeup: Condvar,
 }
 
 impl CoordinatorNotices for () {
@@ -41,9 +64,44 @@ impl CoordinatorNotices for () {
 impl CoordinatorNotices for ArcCounterCoordinatorNotices {
     fn notify_stacks_block_processed(&mut self) {
         self.stacks_blocks_processed.fetch_add(1, Ordering::SeqCst);
+        self.processed_wakeup.notify();
     }
     fn notify_sortition_processed(&mut self) {
         self.sortitions_processed.fetch_add(1, Ordering::SeqCst);
+        self.processed_wakeup.notify();
+    }
+}
+
+impl ProcessedWakeup {
+    fn new() -> ProcessedWakeup {
+        ProcessedWakeup {
+            lock: Mutex::new(()),
+            wakeup: Condvar::new(),
+        }
+    }
+
+    /// Wake up every thread blocked in `wait_for()`.
+    ///   Must be called _after_ the counter has been updated.
+    pub fn notify(&self) {
+        let _guard = self.lock.lock().unwrap();
+        self.wakeup.notify_all();
+    }
+
+    /// Block until `counter` exceeds `current`, or until `timeout_millis` elapses.
+    ///   Returns false on timeout.
+    pub fn wait_for(&self, counter: &AtomicU64, current: u64, timeout_millis: u64) -> bool {
+        let deadline = Instant::now() + Duration::from_millis(timeout_millis);
+        // holding the lock while checking the counter guarantees that a
+        //  notify() issued after the check is not lost.
+        let mut guard = self.lock.lock().unwrap();
+        while counter.load(Ordering::SeqCst) <= current {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            guard = self.wakeup.wait_timeout(guard, deadline - now).unwrap().0;
+        }
+        true
     }
 }
 
@@ -60,6 +118,8 @@ pub struct CoordinatorChannels {
     stacks_blocks_processed: Arc<AtomicU64>,
     /// how many sortitions have been processed by this Coordinator thread since startup?
     sortitions_processed: Arc<AtomicU64>,
+    /// notified whenever one of the above counters is bumped
+    processed_wakeup: Arc<ProcessedWakeup>,
 }
 
 /// Notification struct for communicating to
@@ -68,6 +128,7 @@ pub struct CoordinatorChannels {
 struct SignalBools {
     new_stacks_block: bool,
     new_burn_block: bool,
+    flush_chainstate: bool,
     stop: bool,
 }
 
@@ -83,6 +144,7 @@ pub struct CoordinatorReceivers {
     signal_wakeup: Arc<Condvar>,
     pub stacks_blocks_processed: Arc<AtomicU64>,
     pub sortitions_processed: Arc<AtomicU64>,
+    pub processed_wakeup: Arc<ProcessedWakeup>,
 }
 
 /// Static struct used to hold all the static methods
@@ -92,13 +154,14 @@ pub struct CoordinatorCommunication;
 pub enum CoordinatorEvents {
     NEW_STACKS_BLOCK,
     NEW_BURN_BLOCK,
+    FLUSH_CHAINSTATE,
     STOP,
     TIMEOUT,
 }
 
 impl SignalBools {
     fn activated_signal(&self) -> bool {
-        self.stop || self.new_stacks_block || self.new_burn_block
+        self.stop || self.new_stacks_block || self.new_burn_block || self.flush_chainstate
     }
     fn receive_signal(&mut self) -> CoordinatorEvents {
         if self.stop {
@@ -109,6 +172,9 @@ impl SignalBools {
         } else if self.new_stacks_block {
             self.new_stacks_block = false;
             return CoordinatorEvents::NEW_STACKS_BLOCK;
+        } else if self.flush_chainstate {
+            self.flush_chainstate = false;
+            return CoordinatorEvents::FLUSH_CHAINSTATE;
         } else {
             return CoordinatorEvents::TIMEOUT;
         }
@@ -140,6 +206,13 @@ impl CoordinatorChannels {
         !bools.stop
     }
 
+    pub fn announce_flush_chainstate(&self) -> bool {
+        let mut bools = self.signal_bools.lock().unwrap();
+        bools.flush_chainstate = true;
+        self.signal_wakeup.notify_all();
+        !bools.stop
+    }
+
     pub fn stop_chains_coordinator(&self) -> bool {
         let mut bools = self.signal_bools.lock().unwrap();
         bools.stop = true;
@@ -156,27 +229,13 @@ impl CoordinatorChannels {
     }
 
     pub fn wait_for_sortitions_processed(&self, current: u64, timeout_millis: u64) -> bool {
-        let start = Instant::now();
-        while self.get_sortitions_processed() <= current {
-            if start.elapsed() > Duration::from_millis(timeout_millis) {
-                return false;
-            }
-            thread::sleep(Duration::from_millis(100));
-            std::sync::atomic::spin_loop_hint();
-        }
-        return true;
+        self.processed_wakeup
+            .wait_for(&self.sortitions_processed, current, timeout_millis)
     }
 
     pub fn wait_for_stacks_blocks_processed(&self, current: u64, timeout_millis: u64) -> bool {
-        let start = Instant::now();
-        while self.get_stacks_blocks_processed() <= current {
-            if start.elapsed() > Duration::from_millis(timeout_millis) {
-                return false;
-            }
-            thread::sleep(Duration::from_millis(100));
-            std::sync::atomic::spin_loop_hint();
-        }
-        return true;
+        self.processed_wakeup
+            .wait_for(&self.stacks_blocks_processed, current, timeout_millis)
     }
 }
 
@@ -185,6 +244,7 @@ impl CoordinatorCommunication {
         let signal_bools = Arc::new(Mutex::new(SignalBools {
             new_stacks_block: false,
             new_burn_block: false,
+            flush_chainstate: false,
             stop: false,
         }));
 
@@ -192,6 +252,7 @@ impl CoordinatorCommunication {
 
         let stacks_blocks_processed = Arc::new(AtomicU64::new(0));
         let sortitions_processed = Arc::new(AtomicU64::new(0));
+        let processed_wakeup = Arc::new(ProcessedWakeup::new());
 
         let senders = CoordinatorChannels {
             signal_bools: signal_bools.clone(),
@@ -199,6 +260,7 @@ impl CoordinatorCommunication {
             stacks_blocks_processed: stacks_blocks_processed.clone(),
 
             sortitions_processed: sortitions_processed.clone(),
+            processed_wakeup: processed_wakeup.clone(),
         };
 
         let rcvrs = CoordinatorReceivers {
@@ -206,6 +268,7 @@ impl CoordinatorCommunication {
             signal_wakeup: signal_wakeup,
             stacks_blocks_processed,
             sortitions_processed,
+            processed_wakeup,
         };
 
         (rcvrs, senders)
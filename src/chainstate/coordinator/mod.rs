@@ -33,9 +33,14 @@ use chainstate::stacks::{
     db::{accounts::MinerReward, ClarityTx, MinerRewardInfo, StacksChainState, StacksHeaderInfo},
     events::StacksTransactionReceipt,
     Error as ChainstateError, StacksAddress, StacksBlock, StacksBlockHeader, StacksBlockId,
+    TransactionPayload,
 };
 use monitoring::increment_stx_blocks_processed_counter;
+use net::atlas::{AtlasDB, AttachmentInstance};
+use util::db::sqlite_wal_checkpoint;
 use util::db::Error as DBError;
+use util::get_epoch_time_secs;
+use util::log;
 use vm::{
     costs::ExecutionCost,
     types::{PrincipalData, QualifiedContractIdentifier},
@@ -143,6 +148,7 @@ pub struct ChainsCoordinator<
     dispatcher: Option<&'a T>,
     reward_set_provider: R,
     notifier: N,
+    atlas_db: Option<AtlasDB>,
 }
 
 #[derive(Debug)]
@@ -241,6 +247,7 @@ impl<'a, T: BlockEventDispatcher>
         block_limit: ExecutionCost,
         dispatcher: &mut T,
         comms: CoordinatorReceivers,
+        atlas_db: Option<AtlasDB>,
         boot_block_exec: F,
     ) where
         F: FnOnce(&mut ClarityTx),
@@ -248,6 +255,7 @@ impl<'a, T: BlockEventDispatcher>
     {
         let stacks_blocks_processed = comms.stacks_blocks_processed.clone();
         let sortitions_processed = comms.sortitions_processed.clone();
+        let processed_wakeup = comms.processed_wakeup.clone();
 
         let sortition_db = SortitionDB::open(&burnchain.get_db_path(), true).unwrap();
         let burnchain_blocks_db =
@@ -294,6 +302,7 @@ impl<'a, T: BlockEventDispatcher>
         let arc_notices = ArcCounterCoordinatorNotices {
             stacks_blocks_processed,
             sortitions_processed,
+            processed_wakeup,
         };
 
         let mut inst = ChainsCoordinator {
@@ -307,6 +316,7 @@ impl<'a, T: BlockEventDispatcher>
             dispatcher: Some(dispatcher),
             notifier: arc_notices,
             reward_set_provider: OnChainRewardSetProvider(),
+            atlas_db,
         };
 
         loop {
@@ -324,6 +334,12 @@ impl<'a, T: BlockEventDispatcher>
                         warn!("Error processing new burn block: {:?}", e);
                     }
                 }
+                CoordinatorEvents::FLUSH_CHAINSTATE => {
+                    debug!("Received chainstate flush notice");
+                    if let Err(e) = inst.handle_flush_chainstate() {
+                        warn!("Error flushing chainstate: {:?}", e);
+                    }
+                }
                 CoordinatorEvents::STOP => {
                     debug!("Received stop notice");
                     return;
@@ -363,6 +379,7 @@ impl<'a, T: BlockEventDispatcher, U: RewardSetProvider> ChainsCoordinator<'a, T,
             dispatcher: None,
             reward_set_provider,
             notifier: (),
+            atlas_db: None,
         }
     }
 }
@@ -470,6 +487,22 @@ fn dispatcher_announce_burn_ops<T: BlockEventDispatcher>(
 impl<'a, T: BlockEventDispatcher, N: CoordinatorNotices, U: RewardSetProvider>
     ChainsCoordinator<'a, T, N, U>
 {
+    /// Checkpoint the write-ahead logs of the chainstate and sortition databases into their
+    /// database files.  The coordinator does this since it is their only writer.
+    pub fn handle_flush_chainstate(&mut self) -> Result<(), Error> {
+        for conn in [
+            &self.chain_state_db.blocks_db,
+            self.chain_state_db.headers_db(),
+            self.sortition_db.conn(),
+        ]
+        .iter()
+        {
+            sqlite_wal_checkpoint(conn).map_err(|e| Error::DBError(DBError::SqliteError(e)))?;
+        }
+        info!("Flushed chainstate and sortition databases to disk");
+        Ok(())
+    }
+
     pub fn handle_new_stacks_block(&mut self) -> Result<(), Error> {
         if let Some(pox_anchor) = self.process_ready_blocks()? {
             self.process_new_pox_anchor(pox_anchor)
@@ -550,12 +583,14 @@ impl<'a, T: BlockEventDispatcher, N: CoordinatorNotices, U: RewardSetProvider>
 
             debug!(
                 "Sortition processed: {} (tip {} height {})",
-                &sortition_id, &next_snapshot.burn_header_hash, next_snapshot.block_height
+                &sortition_id, &next_snapshot.burn_header_hash, next_snapshot.block_height;
+                "event" => "sortition_processed"
             );
 
             if sortition_tip_snapshot.block_height < header.block_height {
                 // bump canonical sortition...
                 self.canonical_sortition_tip = Some(sortition_id.clone());
+                log::set_log_burn_height(next_snapshot.block_height);
                 canonical_sortition_tip = sortition_id;
             }
 
@@ -628,12 +663,35 @@ impl<'a, T: BlockEventDispatcher, N: CoordinatorNotices, U: RewardSetProvider>
                     ));
                     let new_canonical_stacks_block =
                         new_canonical_block_snapshot.get_canonical_stacks_block_id();
+                    log::set_log_stacks_tip(&new_canonical_stacks_block.to_hex());
                     self.canonical_chain_tip = Some(new_canonical_stacks_block);
-                    debug!("Bump blocks processed");
+                    debug!("Bump blocks processed"; "event" => "stacks_block_processed");
                     self.notifier.notify_stacks_block_processed();
                     increment_stx_blocks_processed_counter();
                     let block_hash = block_receipt.header.anchored_header.block_hash();
 
+                    if let Some(ref atlas_db) = self.atlas_db {
+                        let index_block_hash = StacksBlockId::new(
+                            &block_receipt.header.consensus_hash,
+                            &block_hash,
+                        );
+                        let instances = AttachmentInstance::find_in_receipts(
+                            atlas_db.config(),
+                            &block_receipt.tx_receipts,
+                            &index_block_hash,
+                            block_receipt.header.block_height,
+                        );
+                        let now = get_epoch_time_secs();
+                        for instance in instances.iter() {
+                            if let Err(e) = atlas_db.insert_attachment_instance(instance, now) {
+                                warn!(
+                                    "Failed to record attachment {} declared in {}: {:?}",
+                                    &instance.content_hash, &index_block_hash, &e
+                                );
+                            }
+                        }
+                    }
+
                     if let Some(dispatcher) = self.dispatcher {
                         let metadata = &block_receipt.header;
                         let winner_txid = SortitionDB::get_block_snapshot_for_winning_stacks_block(
@@ -683,7 +741,17 @@ impl<'a, T: BlockEventDispatcher, N: CoordinatorNotices, U: RewardSetProvider>
                     }
                 }
             }
-            // TODO: do something with a poison result
+            if let Some(TransactionPayload::PoisonMicroblock(header_1, header_2)) = block_result.1 {
+                // the block's parent microblock stream forks, so the block will not be
+                // processed.  The miner who produced the stream can be punished with a
+                // PoisonMicroblock transaction carrying these two headers.
+                warn!(
+                    "Microblock stream fork detected: microblocks {} and {} both have sequence {}",
+                    &header_1.block_hash(),
+                    &header_2.block_hash(),
+                    header_1.sequence
+                );
+            }
 
             let sortdb_handle = self.sortition_db.tx_handle_begin(canonical_sortition_tip)?;
             processed_blocks = self.chain_state_db.process_blocks(sortdb_handle, 1)?;
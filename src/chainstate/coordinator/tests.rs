@@ -141,6 +141,7 @@ pub fn setup_states(paths: &[&str], vrf_keys: &[VRFPrivateKey], committers: &[St
             burnchain.first_block_height,
             &burnchain.first_block_hash,
             0,
+            &burnchain.get_stacks_epochs(),
             true,
         )
         .unwrap();
@@ -2047,3 +2048,36 @@ fn preprocess_block(
         )
         .unwrap();
 }
+
+#[test]
+fn test_coordinator_processed_wakeup() {
+    use chainstate::coordinator::comm::{ArcCounterCoordinatorNotices, CoordinatorNotices};
+
+    let (receivers, channels) = CoordinatorCommunication::instantiate();
+
+    // nothing processed yet, so waiting times out
+    assert!(!channels.wait_for_sortitions_processed(0, 10));
+    assert!(!channels.wait_for_stacks_blocks_processed(0, 10));
+
+    let mut notices = ArcCounterCoordinatorNotices {
+        stacks_blocks_processed: receivers.stacks_blocks_processed.clone(),
+        sortitions_processed: receivers.sortitions_processed.clone(),
+        processed_wakeup: receivers.processed_wakeup.clone(),
+    };
+
+    let waiter_channels = channels.clone();
+    let waiter = std::thread::spawn(move || {
+        waiter_channels.wait_for_sortitions_processed(0, 60_000)
+            && waiter_channels.wait_for_stacks_blocks_processed(0, 60_000)
+    });
+
+    notices.notify_sortition_processed();
+    notices.notify_stacks_block_processed();
+
+    assert!(waiter.join().unwrap());
+    assert_eq!(channels.get_sortitions_processed(), 1);
+    assert_eq!(channels.get_stacks_blocks_processed(), 1);
+
+    // already past the given count, so no waiting
+    assert!(channels.wait_for_sortitions_processed(0, 0));
+}
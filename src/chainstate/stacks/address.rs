@@ -197,6 +197,9 @@ impl StacksAddress {
                 BitcoinAddress::to_p2pkh_tx_out(&self.bytes, value)
             }
             BitcoinAddressType::ScriptHash => BitcoinAddress::to_p2sh_tx_out(&self.bytes, value),
+            BitcoinAddressType::WitnessPubkeyHash => {
+                BitcoinAddress::to_p2wpkh_tx_out(&self.bytes, value)
+            }
         }
     }
 }
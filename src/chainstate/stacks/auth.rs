@@ -322,6 +322,32 @@ impl MultisigSpendingCondition {
 
         Ok(cur_sighash)
     }
+
+    /// Replay the signatures accumulated so far in a (possibly partially-signed) spending
+    /// condition, and return the sighash that the next signer must sign.
+    /// Unlike verify(), this does not require all signatures to be present, and so it does not
+    /// check the public keys against the signer hash.
+    pub fn next_sighash(
+        &self,
+        initial_sighash: &Txid,
+        cond_code: &TransactionAuthFlags,
+    ) -> Result<Txid, net_error> {
+        let mut cur_sighash = initial_sighash.clone();
+        for field in self.fields.iter() {
+            if let TransactionAuthField::Signature(ref pubkey_encoding, ref sigbuf) = field {
+                let (_, next_sighash) = TransactionSpendingCondition::next_verification(
+                    &cur_sighash,
+                    cond_code,
+                    self.fee_rate,
+                    self.nonce,
+                    pubkey_encoding,
+                    sigbuf,
+                )?;
+                cur_sighash = next_sighash;
+            }
+        }
+        Ok(cur_sighash)
+    }
 }
 
 impl StacksMessageCodec for SinglesigSpendingCondition {
@@ -828,6 +854,27 @@ impl TransactionSpendingCondition {
         Ok((pubk, next_sighash))
     }
 
+    /// Get the sighash that the next signer of this spending condition must sign, given the
+    /// signatures it has so far.
+    pub fn next_sighash(
+        &self,
+        initial_sighash: &Txid,
+        cond_code: &TransactionAuthFlags,
+    ) -> Result<Txid, net_error> {
+        match *self {
+            TransactionSpendingCondition::Singlesig(ref data) => {
+                if data.signature == MessageSignature::empty() {
+                    Ok(initial_sighash.clone())
+                } else {
+                    data.verify(initial_sighash, cond_code)
+                }
+            }
+            TransactionSpendingCondition::Multisig(ref data) => {
+                data.next_sighash(initial_sighash, cond_code)
+            }
+        }
+    }
+
     /// Verify all signatures
     pub fn verify(
         &self,
@@ -702,16 +702,20 @@ impl StacksMicroblockHeader {
         Ok(())
     }
 
-    pub fn check_recover_pubkey(&self) -> Result<Hash160, net_error> {
-        let mut digest_bits = [0u8; 32];
+    /// Hash of what the signature signs:  the header with an empty signature.  Unlike the block
+    /// hash, this does not change if the signature is malleated.
+    pub fn signature_digest(&self) -> Sha512Trunc256Sum {
         let mut sha2 = Sha512Trunc256::new();
-
         self.serialize(&mut sha2, true)
             .expect("BUG: failed to serialize to a vec");
-        digest_bits.copy_from_slice(sha2.result().as_slice());
+        Sha512Trunc256Sum::from_hasher(sha2)
+    }
+
+    pub fn check_recover_pubkey(&self) -> Result<Hash160, net_error> {
+        let digest_bits = self.signature_digest();
 
-        let mut pubk =
-            StacksPublicKey::recover_to_pubkey(&digest_bits, &self.signature).map_err(|_ve| {
+        let mut pubk = StacksPublicKey::recover_to_pubkey(digest_bits.as_bytes(), &self.signature)
+            .map_err(|_ve| {
                 test_debug!(
                     "Failed to verify signature: failed to recover public key from {:?}: {:?}",
                     &self.signature,
@@ -25,7 +25,7 @@ use chainstate::stacks::db::blocks::*;
 use chainstate::stacks::db::*;
 use chainstate::stacks::Error;
 use chainstate::stacks::*;
-use vm::clarity::{ClarityConnection, ClarityTransactionConnection};
+use vm::clarity::{ClarityConnection, ClarityInstance, ClarityTransactionConnection};
 use vm::database::marf::*;
 use vm::database::*;
 use vm::types::*;
@@ -35,6 +35,8 @@ use util::db::*;
 
 use vm::get_stx_balance_snapshot;
 
+use core::StacksEpochId;
+
 pub type MinerPaymentCache = HashMap<StacksBlockId, Vec<MinerPaymentSchedule>>;
 
 impl StacksAccount {
@@ -59,6 +61,16 @@ pub struct MinerReward {
     pub vtxindex: u32, // will be 0 for the reward to the miner, and >0 for user burn supports
 }
 
+/// A reward an address earned in one block, by mining it or by supporting its miner
+#[derive(Debug, Clone, PartialEq)]
+pub struct MinerRewardRecord {
+    pub schedule: MinerPaymentSchedule,
+    /// height of the Stacks block that pays the reward out
+    pub maturity_height: u64,
+    /// what the reward paid, once it has matured
+    pub paid: Option<MinerReward>,
+}
+
 impl FromRow<MinerPaymentSchedule> for MinerPaymentSchedule {
     fn from_row<'a>(row: &'a Row) -> Result<MinerPaymentSchedule, db_error> {
         let address = StacksAddress::from_column(row, "address")?;
@@ -115,6 +127,19 @@ impl FromRow<MinerPaymentSchedule> for MinerPaymentSchedule {
     }
 }
 
+impl MinerPaymentSchedule {
+    /// The share of the streamed tx fees this block confirmed that goes to the miner who produced
+    /// the microblocks -- i.e. the parent block's miner
+    pub fn streamed_fees_produced(&self) -> u128 {
+        self.tx_fees_streamed * MINER_FEE_STREAMED_PRODUCER_PERCENT / 100
+    }
+
+    /// The share of the streamed tx fees this block confirmed that goes to its own miner
+    pub fn streamed_fees_confirmed(&self) -> u128 {
+        self.tx_fees_streamed - self.streamed_fees_produced()
+    }
+}
+
 impl MinerReward {
     pub fn total(&self) -> u128 {
         self.coinbase
@@ -408,12 +433,12 @@ impl StacksChainState {
     }
 
     /// Get the scheduled miner rewards in a particular Stacks fork at a particular height
-    pub fn get_scheduled_block_rewards_in_fork<'a>(
-        tx: &mut StacksDBTx<'a>,
+    pub fn get_scheduled_block_rewards_in_fork<D: StacksDBIndexed>(
+        db: &mut D,
         tip: &StacksHeaderInfo,
         block_height: u64,
     ) -> Result<Vec<MinerPaymentSchedule>, Error> {
-        let ancestor_info = match StacksChainState::get_tip_ancestor(tx, tip, block_height)? {
+        let ancestor_info = match StacksChainState::get_tip_ancestor(db, tip, block_height)? {
             Some(info) => info,
             None => {
                 test_debug!("No ancestor at height {}", block_height);
@@ -426,7 +451,8 @@ impl StacksChainState {
             &ancestor_info.anchored_header.block_hash(),
             &ancestor_info.consensus_hash,
         ];
-        let rows = query_rows::<MinerPaymentSchedule, _>(tx, &qry, args).map_err(Error::DBError)?;
+        let rows = query_rows::<MinerPaymentSchedule, _>(db.sqlite(), &qry, args)
+            .map_err(Error::DBError)?;
         test_debug!(
             "{} rewards in {}/{}",
             rows.len(),
@@ -491,11 +517,16 @@ impl StacksChainState {
     ///
     /// There must be MINER_REWARD_WINDOW items in the sample.
     ///
-    /// TODO: this is incomplete -- it does not calculate transaction fees.  This is just stubbed
-    /// out for now -- it only grants miners and user burn supports their coinbases.
+    /// Only the block's miner earns transaction fees, and only if the block that pays the reward
+    /// out follows the rules of Stacks 2.1 or later (`epoch_id`).  It gets all of its block's
+    /// anchored tx fees, and splits the streamed tx fees with the miners before and after it:  it
+    /// gets MINER_FEE_STREAMED_PRODUCER_PERCENT of the fees in the microblocks it produced (which
+    /// sample[1], its child, confirmed), and the rest of the fees in its parent's microblocks
+    /// (which it confirmed).
     fn calculate_miner_reward(
         miner: &MinerPaymentSchedule,
         sample: &Vec<(MinerPaymentSchedule, Vec<MinerPaymentSchedule>)>,
+        epoch_id: StacksEpochId,
     ) -> MinerReward {
         for i in 0..sample.len() {
             assert!(sample[i].0.miner);
@@ -581,14 +612,31 @@ impl StacksChainState {
                 / (burn_total as u128)
         };
 
-        // TODO: missing transaction fee calculation
+        ////////////////////// transaction fees /////////////////////////////////////
+        let (tx_fees_anchored, tx_fees_streamed_produced, tx_fees_streamed_confirmed) =
+            if miner.miner && epoch_id >= StacksEpochId::Epoch21 {
+                let tx_fees_streamed_produced = match sample.get(1) {
+                    Some((child_miner, _)) => child_miner.streamed_fees_produced(),
+                    None => 0,
+                };
+                (
+                    miner.tx_fees_anchored,
+                    tx_fees_streamed_produced,
+                    miner.streamed_fees_confirmed(),
+                )
+            } else {
+                // user burn supports only share the coinbase, and so does everyone before
+                // Stacks 2.1
+                (0, 0, 0)
+            };
+
         let miner_reward = MinerReward {
             address: miner.address.clone(),
             coinbase: coinbase_reward,
             tx_fees_anchored_shared: 0,
-            tx_fees_anchored_exclusive: 0,
-            tx_fees_streamed_produced: 0,
-            tx_fees_streamed_confirmed: 0,
+            tx_fees_anchored_exclusive: tx_fees_anchored,
+            tx_fees_streamed_produced,
+            tx_fees_streamed_confirmed,
             vtxindex: miner.vtxindex,
         };
         miner_reward
@@ -600,9 +648,12 @@ impl StacksChainState {
     /// scheduled miner payments to the cache.  Miner payments are immutable once written, and are
     /// keyed to the index block hash (which is globally unique), so once cached, no invalidation
     /// should be necessary.
-    pub fn find_mature_miner_rewards(
-        tx: &mut StacksDBTx,
+    /// `epoch_id` is the epoch of the block that pays the rewards out -- i.e. of the child of
+    /// `tip` (see get_child_epoch()).
+    pub fn find_mature_miner_rewards<D: StacksDBIndexed>(
+        db: &mut D,
         tip: &StacksHeaderInfo,
+        epoch_id: StacksEpochId,
         mut cache: Option<&mut MinerPaymentCache>,
     ) -> Result<Option<(Vec<MinerReward>, MinerRewardInfo)>, Error> {
         if tip.block_height <= MINER_REWARD_MATURITY + MINER_REWARD_WINDOW {
@@ -611,7 +662,7 @@ impl StacksChainState {
         }
 
         let latest_matured_miners = StacksChainState::get_scheduled_block_rewards_in_fork(
-            tx,
+            db,
             tip,
             tip.block_height - MINER_REWARD_MATURITY,
         )?;
@@ -636,13 +687,15 @@ impl StacksChainState {
                     miner_rewards.clone()
                 } else {
                     debug!("CACHE MISS MINER REWARDS {}", &index_block_hash);
-                    let rewards =
-                        StacksChainState::get_scheduled_block_rewards(tx, &index_block_hash)?;
+                    let rewards = StacksChainState::get_scheduled_block_rewards(
+                        db.sqlite(),
+                        &index_block_hash,
+                    )?;
                     cache.insert(index_block_hash.clone(), rewards.clone());
                     rewards
                 }
             } else {
-                StacksChainState::get_scheduled_block_rewards(tx, &index_block_hash)?
+                StacksChainState::get_scheduled_block_rewards(db.sqlite(), &index_block_hash)?
             };
 
             assert!(
@@ -692,7 +745,7 @@ impl StacksChainState {
 
         if cfg!(test) {
             let rs_before = StacksChainState::get_scheduled_block_rewards_in_fork(
-                tx,
+                db,
                 tip,
                 tip.block_height - MINER_REWARD_MATURITY - MINER_REWARD_WINDOW,
             )?;
@@ -703,7 +756,7 @@ impl StacksChainState {
             assert_eq!(matured_miners_list, rs_before);
 
             let rs_after = StacksChainState::get_scheduled_block_rewards_in_fork(
-                tx,
+                db,
                 tip,
                 tip.block_height - MINER_REWARD_MATURITY,
             )?;
@@ -715,19 +768,105 @@ impl StacksChainState {
         let matured_rewards_info = MinerRewardInfo {
             from_stacks_block_hash: matured_miners.0.block_hash.clone(),
             from_block_consensus_hash: matured_miners.0.consensus_hash.clone(),
+            from_stacks_block_height: matured_miners.0.stacks_block_height,
         };
 
         let mut rewards = vec![];
-        let miner_reward =
-            StacksChainState::calculate_miner_reward(&matured_miners.0, &scheduled_payments);
+        let miner_reward = StacksChainState::calculate_miner_reward(
+            &matured_miners.0,
+            &scheduled_payments,
+            epoch_id,
+        );
         rewards.push(miner_reward);
 
         for user_reward in matured_miners.1.iter() {
-            let reward = StacksChainState::calculate_miner_reward(user_reward, &scheduled_payments);
+            let reward = StacksChainState::calculate_miner_reward(
+                user_reward,
+                &scheduled_payments,
+                epoch_id,
+            );
             rewards.push(reward);
         }
         Ok(Some((rewards, matured_rewards_info)))
     }
+
+    /// Get the epoch whose rules the child of `parent_tip` follows.  The child is the block that
+    /// pays out the rewards that matured by `parent_tip`.
+    pub fn get_child_epoch(
+        headers_db: &DBConn,
+        burn_dbconn: &dyn BurnStateDB,
+        parent_tip: &StacksHeaderInfo,
+    ) -> StacksEpochId {
+        let parent_index_block = StacksChainState::get_parent_index_block(
+            &parent_tip.consensus_hash,
+            &parent_tip.anchored_header.block_hash(),
+        );
+        ClarityInstance::get_epoch_of(&parent_index_block, headers_db, burn_dbconn)
+    }
+
+    /// Get the rewards `address` earned in the `max_blocks` most recent blocks it mined or
+    /// supported in the fork ending at `tip`, most recent first.
+    /// A reward that has matured by `tip` comes with what it paid out.  This does not account
+    /// for a reward forfeited because its miner signed conflicting microblocks.
+    pub fn get_miner_reward_records<D: StacksDBIndexed>(
+        db: &mut D,
+        burn_dbconn: &dyn BurnStateDB,
+        tip: &StacksHeaderInfo,
+        address: &StacksAddress,
+        max_blocks: u64,
+    ) -> Result<Vec<MinerRewardRecord>, Error> {
+        let qry = "SELECT * FROM payments WHERE address = ?1 AND stacks_block_height <= ?2 ORDER BY stacks_block_height DESC, vtxindex ASC".to_string();
+        let args: &[&dyn ToSql] = &[&address.to_string(), &u64_to_sql(tip.block_height)?];
+        let schedules = query_rows::<MinerPaymentSchedule, _>(db.sqlite(), &qry, args)
+            .map_err(Error::DBError)?;
+
+        let mut records = vec![];
+        for schedule in schedules.into_iter() {
+            if records.len() as u64 >= max_blocks {
+                break;
+            }
+
+            // only count blocks in this fork
+            let in_fork =
+                match StacksChainState::get_tip_ancestor(db, tip, schedule.stacks_block_height)? {
+                    Some(ancestor) => {
+                        ancestor.consensus_hash == schedule.consensus_hash
+                            && ancestor.anchored_header.block_hash() == schedule.block_hash
+                    }
+                    None => false,
+                };
+            if !in_fork {
+                continue;
+            }
+
+            // paid out by the block whose parent is MINER_REWARD_MATURITY + MINER_REWARD_WINDOW
+            // blocks later
+            let maturity_height =
+                schedule.stacks_block_height + MINER_REWARD_MATURITY + MINER_REWARD_WINDOW + 1;
+            let paid = if maturity_height <= tip.block_height {
+                let payer_parent =
+                    StacksChainState::get_tip_ancestor(db, tip, maturity_height - 1)?
+                        .ok_or(Error::NoSuchBlockError)?;
+                let payer_epoch =
+                    StacksChainState::get_child_epoch(db.sqlite(), burn_dbconn, &payer_parent);
+                StacksChainState::find_mature_miner_rewards(db, &payer_parent, payer_epoch, None)?
+                    .and_then(|(rewards, _)| {
+                        rewards
+                            .into_iter()
+                            .find(|reward| reward.vtxindex == schedule.vtxindex)
+                    })
+            } else {
+                None
+            };
+
+            records.push(MinerRewardRecord {
+                schedule,
+                maturity_height,
+                paid,
+            });
+        }
+        Ok(records)
+    }
 }
 
 #[cfg(test)]
@@ -1048,8 +1187,11 @@ mod test {
 
         let mut rewards = vec![];
         for matured_miner in matured_miners {
-            let reward =
-                StacksChainState::calculate_miner_reward(&matured_miner, &scheduled_payments);
+            let reward = StacksChainState::calculate_miner_reward(
+                &matured_miner,
+                &scheduled_payments,
+                StacksEpochId::Epoch21,
+            );
             rewards.push(reward);
         }
         Ok(Some(rewards))
@@ -1108,14 +1250,19 @@ mod test {
 
             if i < (MINER_REWARD_MATURITY + MINER_REWARD_WINDOW) {
                 let mut tx = chainstate.headers_tx_begin().unwrap();
-                let rewards_opt =
-                    StacksChainState::find_mature_miner_rewards(&mut tx, &parent_tip, None)
-                        .unwrap();
+                let rewards_opt = StacksChainState::find_mature_miner_rewards(
+                    &mut tx,
+                    &parent_tip,
+                    StacksEpochId::Epoch21,
+                    None,
+                )
+                .unwrap();
                 assert!(rewards_opt.is_none()); // not mature yet
 
                 let rewards_opt = StacksChainState::find_mature_miner_rewards(
                     &mut tx,
                     &parent_tip,
+                    StacksEpochId::Epoch21,
                     Some(&mut cache),
                 )
                 .unwrap();
@@ -1134,6 +1281,7 @@ mod test {
                 let reward = StacksChainState::calculate_miner_reward(
                     &matured_miner,
                     &expected_scheduled_payments,
+                    StacksEpochId::Epoch21,
                 );
                 rewards.push(reward);
             }
@@ -1146,17 +1294,26 @@ mod test {
 
         assert_eq!(legacy_rewards, expected_rewards);
 
-        let (rewards, _) = StacksChainState::find_mature_miner_rewards(&mut tx, &parent_tip, None)
-            .unwrap()
-            .unwrap();
+        let (rewards, _) = StacksChainState::find_mature_miner_rewards(
+            &mut tx,
+            &parent_tip,
+            StacksEpochId::Epoch21,
+            None,
+        )
+        .unwrap()
+        .unwrap();
 
         assert_eq!(rewards.len(), 2);
         assert_eq!(rewards, expected_rewards);
 
-        let (rewards_cached, _) =
-            StacksChainState::find_mature_miner_rewards(&mut tx, &parent_tip, Some(&mut cache))
-                .unwrap()
-                .unwrap();
+        let (rewards_cached, _) = StacksChainState::find_mature_miner_rewards(
+            &mut tx,
+            &parent_tip,
+            StacksEpochId::Epoch21,
+            Some(&mut cache),
+        )
+        .unwrap()
+        .unwrap();
 
         assert_eq!(rewards_cached, rewards);
         assert_eq!(rewards_cached, expected_rewards);
@@ -1165,6 +1322,7 @@ mod test {
         let (rewards_cached, _) = StacksChainState::find_mature_miner_rewards(
             &mut tx,
             &parent_tip,
+            StacksEpochId::Epoch21,
             Some(&mut empty_cache),
         )
         .unwrap()
@@ -1174,6 +1332,157 @@ mod test {
         assert_eq!(rewards_cached, expected_rewards);
     }
 
+    #[test]
+    fn get_miner_reward_records() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "get_miner_reward_records");
+        let miner_1 =
+            StacksAddress::from_string(&"SP1A2K3ENNA6QQ7G8DVJXM24T6QMBDVS7D0TRTAR5".to_string())
+                .unwrap();
+        let user_1 =
+            StacksAddress::from_string(&"SP2837ZMC89J40K4YTS64B00M7065C6X46JX6ARG0".to_string())
+                .unwrap();
+        let miner_2 =
+            StacksAddress::from_string(&"SP8WWTGMNCCSB88QF4VYWN69PAMQRMF34FCT498G".to_string())
+                .unwrap();
+
+        let mut parent_tip = StacksHeaderInfo::genesis_block_header_info(TrieHash([0u8; 32]), 0);
+        let num_blocks = MINER_REWARD_MATURITY + MINER_REWARD_WINDOW + 3;
+        for i in 0..num_blocks {
+            let mut miner_reward =
+                make_dummy_miner_payment_schedule(&miner_1, 500, 100, 50, 1000, 1100);
+            miner_reward.stacks_block_height = i + 1;
+            let mut user_reward =
+                make_dummy_user_payment_schedule(&user_1, 500, 0, 0, 100, 1100, 1);
+            user_reward.stacks_block_height = i + 1;
+            let mut user_supports = vec![StagingUserBurnSupport::from_miner_payment_schedule(
+                &user_reward,
+            )];
+            parent_tip = advance_tip(
+                &mut chainstate,
+                &parent_tip,
+                &mut miner_reward,
+                &mut user_supports,
+            );
+        }
+        assert_eq!(parent_tip.block_height, num_blocks);
+
+        let mut conn = chainstate.index_conn();
+        let records = StacksChainState::get_miner_reward_records(
+            &mut conn,
+            &NULL_BURN_STATE_DB,
+            &parent_tip,
+            &miner_1,
+            100,
+        )
+        .unwrap();
+        assert_eq!(records.len() as u64, num_blocks);
+        for (i, record) in records.iter().enumerate() {
+            let block_height = num_blocks - (i as u64);
+            assert_eq!(record.schedule.stacks_block_height, block_height);
+            assert_eq!(
+                record.maturity_height,
+                block_height + MINER_REWARD_MATURITY + MINER_REWARD_WINDOW + 1
+            );
+            // only the first two blocks have matured
+            assert_eq!(record.paid.is_some(), block_height <= 2);
+        }
+
+        // the miner shares the coinbase with its supporter, and keeps the tx fees
+        let paid = records.last().unwrap().paid.clone().unwrap();
+        assert_eq!(paid.coinbase, 500 * 1000 / 1100);
+        assert_eq!(paid.tx_fees_anchored_exclusive, 100);
+        assert_eq!(paid.tx_fees_streamed_produced, 20);
+        assert_eq!(paid.tx_fees_streamed_confirmed, 30);
+
+        let records = StacksChainState::get_miner_reward_records(
+            &mut conn,
+            &NULL_BURN_STATE_DB,
+            &parent_tip,
+            &user_1,
+            100,
+        )
+        .unwrap();
+        assert_eq!(records.len() as u64, num_blocks);
+        let paid = records.last().unwrap().paid.clone().unwrap();
+        assert_eq!(paid.coinbase, 500 * 100 / 1100);
+        assert_eq!(paid.total(), paid.coinbase);
+
+        let records = StacksChainState::get_miner_reward_records(
+            &mut conn,
+            &NULL_BURN_STATE_DB,
+            &parent_tip,
+            &miner_1,
+            2,
+        )
+        .unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].schedule.stacks_block_height, num_blocks);
+
+        let records = StacksChainState::get_miner_reward_records(
+            &mut conn,
+            &NULL_BURN_STATE_DB,
+            &parent_tip,
+            &miner_2,
+            100,
+        )
+        .unwrap();
+        assert_eq!(records.len(), 0);
+    }
+
+    #[test]
+    fn miner_reward_one_miner_tx_fees() {
+        let mut sample = vec![];
+        let miner_1 =
+            StacksAddress::from_string(&"SP1A2K3ENNA6QQ7G8DVJXM24T6QMBDVS7D0TRTAR5".to_string())
+                .unwrap();
+        let user_1 =
+            StacksAddress::from_string(&"SP2837ZMC89J40K4YTS64B00M7065C6X46JX6ARG0".to_string())
+                .unwrap();
+        let participant = make_dummy_miner_payment_schedule(&miner_1, 500, 100, 50, 1000, 1000);
+        let user = make_dummy_user_payment_schedule(&user_1, 500, 0, 0, 1000, 1000, 1);
+        sample.push((participant.clone(), vec![user.clone()]));
+
+        // the child block confirms 200 in streamed tx fees
+        let child = make_dummy_miner_payment_schedule(&miner_1, 500, 0, 200, 1000, 1000);
+        sample.push((child, vec![]));
+
+        let reward_miner_1 =
+            StacksChainState::calculate_miner_reward(&participant, &sample, StacksEpochId::Epoch21);
+        let reward_user_1 =
+            StacksChainState::calculate_miner_reward(&user, &sample, StacksEpochId::Epoch21);
+
+        // the miner gets its anchored tx fees, 40% of the fees in the microblocks it produced,
+        // and 60% of the fees in the microblocks it confirmed
+        assert_eq!(reward_miner_1.coinbase, 250);
+        assert_eq!(reward_miner_1.tx_fees_anchored_shared, 0);
+        assert_eq!(reward_miner_1.tx_fees_anchored_exclusive, 100);
+        assert_eq!(reward_miner_1.tx_fees_streamed_produced, 80);
+        assert_eq!(reward_miner_1.tx_fees_streamed_confirmed, 30);
+        assert_eq!(reward_miner_1.total(), 460);
+
+        // the user only shares the coinbase
+        assert_eq!(reward_user_1.coinbase, 250);
+        assert_eq!(reward_user_1.total(), 250);
+
+        // no child block, so nothing produced has been confirmed
+        let reward_miner_1 = StacksChainState::calculate_miner_reward(
+            &participant,
+            &sample[0..1].to_vec(),
+            StacksEpochId::Epoch21,
+        );
+        assert_eq!(reward_miner_1.tx_fees_streamed_produced, 0);
+        assert_eq!(reward_miner_1.tx_fees_streamed_confirmed, 30);
+
+        // before Stacks 2.1, the miner only gets its share of the coinbase
+        let reward_miner_1 =
+            StacksChainState::calculate_miner_reward(&participant, &sample, StacksEpochId::Epoch20);
+        assert_eq!(reward_miner_1.coinbase, 250);
+        assert_eq!(reward_miner_1.tx_fees_anchored_exclusive, 0);
+        assert_eq!(reward_miner_1.tx_fees_streamed_produced, 0);
+        assert_eq!(reward_miner_1.tx_fees_streamed_confirmed, 0);
+        assert_eq!(reward_miner_1.total(), 250);
+    }
+
     #[test]
     fn miner_reward_one_miner_no_tx_fees_no_users() {
         let mut sample = vec![];
@@ -1188,7 +1497,8 @@ mod test {
                 make_dummy_miner_payment_schedule(&miner_1, 500, 0, 0, 1000, 1000);
         }
 
-        let reward = StacksChainState::calculate_miner_reward(&participant, &sample);
+        let reward =
+            StacksChainState::calculate_miner_reward(&participant, &sample, StacksEpochId::Epoch21);
 
         // miner should have received the entire coinbase
         assert_eq!(reward.coinbase, 500);
@@ -1213,7 +1523,8 @@ mod test {
             sample.push((next_participant, vec![]));
         }
 
-        let reward = StacksChainState::calculate_miner_reward(&participant, &sample);
+        let reward =
+            StacksChainState::calculate_miner_reward(&participant, &sample, StacksEpochId::Epoch21);
 
         // miner should have received the entire coinbase
         assert_eq!(reward.coinbase, 500);
@@ -1242,8 +1553,10 @@ mod test {
             sample.push((next_participant, vec![]));
         }
 
-        let reward_miner_1 = StacksChainState::calculate_miner_reward(&participant, &sample);
-        let reward_user_1 = StacksChainState::calculate_miner_reward(&user, &sample);
+        let reward_miner_1 =
+            StacksChainState::calculate_miner_reward(&participant, &sample, StacksEpochId::Epoch21);
+        let reward_user_1 =
+            StacksChainState::calculate_miner_reward(&user, &sample, StacksEpochId::Epoch21);
 
         // miner should have received 1/4 the coinbase
         assert_eq!(reward_miner_1.coinbase, 125);
@@ -1287,8 +1600,8 @@ mod test {
             sample.push((next_participant, vec![next_user]));
         }
 
-        let reward_miner_1 = StacksChainState::calculate_miner_reward(&participant, &sample);
-        let reward_user_1 = StacksChainState::calculate_miner_reward(&user, &sample);
+        let reward_miner_1 = StacksChainState::calculate_miner_reward(&participant, &sample, StacksEpochId::Epoch21);
+        let reward_user_1 = StacksChainState::calculate_miner_reward(&user, &sample, StacksEpochId::Epoch21);
 
         // miner should have received 1/4 the coinbase, and miner should have received only shared
         assert_eq!(reward_miner_1.coinbase, 125);
@@ -1344,9 +1657,9 @@ mod test {
             sample.push((next_participant, vec![next_user]));
         }
 
-        let reward_miner_1 = StacksChainState::calculate_miner_reward(&participant_1, &sample);
-        let reward_miner_2 = StacksChainState::calculate_miner_reward(&participant_2, &sample);
-        let reward_user_1 = StacksChainState::calculate_miner_reward(&user, &sample);
+        let reward_miner_1 = StacksChainState::calculate_miner_reward(&participant_1, &sample, StacksEpochId::Epoch21);
+        let reward_miner_2 = StacksChainState::calculate_miner_reward(&participant_2, &sample, StacksEpochId::Epoch21);
+        let reward_user_1 = StacksChainState::calculate_miner_reward(&user, &sample, StacksEpochId::Epoch21);
 
         // if miner 1 won, then it should have received 1/4 the coinbase, and miner should have received only shared tx fees
         assert_eq!(reward_miner_1.coinbase, 125);
@@ -1411,9 +1724,9 @@ mod test {
             sample.push((next_participant, vec![next_user]));
         }
 
-        let reward_miner_1 = StacksChainState::calculate_miner_reward(&participant_1, &sample);
-        let reward_miner_2 = StacksChainState::calculate_miner_reward(&participant_2, &sample);
-        let reward_user_1 = StacksChainState::calculate_miner_reward(&user, &sample);
+        let reward_miner_1 = StacksChainState::calculate_miner_reward(&participant_1, &sample, StacksEpochId::Epoch21);
+        let reward_miner_2 = StacksChainState::calculate_miner_reward(&participant_2, &sample, StacksEpochId::Epoch21);
+        let reward_user_1 = StacksChainState::calculate_miner_reward(&user, &sample, StacksEpochId::Epoch21);
 
         let expected_shared =
             if (500 * fill_cutoff) & 0x0000000000000000ffffffffffffffff > 0x00000000000000007fffffffffffffff {
@@ -1486,9 +1799,9 @@ mod test {
             sample.push((next_participant, vec![next_user]));
         }
 
-        let reward_miner_1 = StacksChainState::calculate_miner_reward(&participant_1, &sample);
-        let reward_miner_2 = StacksChainState::calculate_miner_reward(&participant_2, &sample);
-        let reward_user_1 = StacksChainState::calculate_miner_reward(&user, &sample);
+        let reward_miner_1 = StacksChainState::calculate_miner_reward(&participant_1, &sample, StacksEpochId::Epoch21);
+        let reward_miner_2 = StacksChainState::calculate_miner_reward(&participant_2, &sample, StacksEpochId::Epoch21);
+        let reward_user_1 = StacksChainState::calculate_miner_reward(&user, &sample, StacksEpochId::Epoch21);
 
         let expected_shared =
             if (500 * fill_cutoff) & 0x0000000000000000ffffffffffffffff > 0x00000000000000007fffffffffffffff {
@@ -26,6 +26,7 @@ use std::io::{Read, Seek, SeekFrom, Write};
 use rusqlite::Connection;
 use rusqlite::DatabaseName;
 
+use core::fees::fee_rate_per_byte;
 use core::mempool::MAXIMUM_MEMPOOL_TX_CHAINING;
 use core::*;
 
@@ -34,6 +35,7 @@ use chainstate::burn::operations::*;
 use chainstate::stacks::db::accounts::MinerReward;
 use chainstate::stacks::db::transactions::TransactionNonceMismatch;
 use chainstate::stacks::db::*;
+use chainstate::stacks::events::TransactionReceiptData;
 use chainstate::stacks::index::MarfTrieId;
 use chainstate::stacks::Error;
 use chainstate::stacks::*;
@@ -45,22 +47,26 @@ use std::path::{Path, PathBuf};
 use util::db::Error as db_error;
 use util::db::{
     query_count, query_int, query_row, query_row_columns, query_row_panic, query_rows,
-    tx_busy_handler, DBConn, FromColumn, FromRow,
+    sqlite_set_wal_mode, tx_busy_handler, DBConn, FromColumn, FromRow,
 };
 
 use util::db::u64_to_sql;
 use util::get_epoch_time_secs;
 use util::hash::to_hex;
+use util::hash::{MerkleTree, Sha512Trunc256Sum};
 use util::strings::StacksString;
 
 use util::retry::BoundReader;
 
 use chainstate::burn::db::sortdb::*;
 
+use net::divergence::{self, RejectedBlockRecord};
 use net::BlocksInvData;
 use net::Error as net_error;
 use net::MAX_MESSAGE_LEN;
 
+use monitoring;
+
 use vm::types::{
     AssetIdentifier, PrincipalData, QualifiedContractIdentifier, StandardPrincipalData, TupleData,
     TypeSignature, Value,
@@ -69,7 +75,10 @@ use vm::types::{
 use vm::contexts::AssetMap;
 
 use vm::analysis::run_analysis;
+use vm::analysis::strict_checker::{check_strict, StrictProfile};
 use vm::ast::build_ast;
+use vm::costs::ExecutionCost;
+use vm::costs::LimitedCostTracker;
 
 use vm::clarity::{ClarityBlockConnection, ClarityConnection, ClarityInstance};
 
@@ -77,6 +86,7 @@ pub use vm::analysis::errors::{CheckError, CheckErrors};
 
 use vm::database::BurnStateDB;
 use vm::database::ClarityDatabase;
+use vm::database::MicroblockPoisonReport;
 
 use vm::contracts::Contract;
 
@@ -123,6 +133,87 @@ pub struct StagingUserBurnSupport {
     pub vtxindex: u32,
 }
 
+/// Why a transaction in a proposed block is invalid
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionValidationError {
+    pub tx_index: usize,
+    pub txid: Txid,
+    pub reason: String,
+}
+
+/// The outcome of validating a proposed anchored block without storing it
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockProposalValidation {
+    /// problems with the block as a whole
+    pub block_errors: Vec<String>,
+    /// problems with individual transactions, in block order
+    pub tx_errors: Vec<TransactionValidationError>,
+    /// the state root the block would produce, if its transactions could be evaluated
+    pub state_index_root: Option<TrieHash>,
+    /// the execution cost of the block's transactions (not including its parent microblocks)
+    pub execution_cost: ExecutionCost,
+    pub total_fees: u128,
+}
+
+impl BlockProposalValidation {
+    pub fn is_valid(&self) -> bool {
+        self.block_errors.len() == 0 && self.tx_errors.len() == 0
+    }
+}
+
+/// The outcome of re-executing a processed block and comparing the result to what was recorded
+/// when it was first processed
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockReplay {
+    pub index_block_hash: StacksBlockId,
+    pub block_height: u64,
+    /// the state root in the block's header
+    pub expected_state_root: TrieHash,
+    /// the state root re-executing the block reached, if it could be re-executed
+    pub state_root: Option<TrieHash>,
+    /// whether or not this node recorded the block's transaction receipts.  Blocks processed
+    /// before receipts were stored have none to compare against.
+    pub receipts_recorded: bool,
+    /// differences between the replay and the recorded state root and receipts, and the reason
+    /// re-executing failed, if it did
+    pub mismatches: Vec<String>,
+}
+
+impl BlockReplay {
+    pub fn is_consistent(&self) -> bool {
+        self.mismatches.len() == 0
+    }
+}
+
+/// What evaluating a block on top of its parent's state produced
+pub struct BlockEvaluation {
+    pub microblock_fees: u128,
+    pub microblock_burns: u128,
+    pub microblock_receipts: Vec<StacksTransactionReceipt>,
+    pub microblock_cost: ExecutionCost,
+    pub block_fees: u128,
+    pub block_burns: u128,
+    pub tx_receipts: Vec<StacksTransactionReceipt>,
+    /// the execution cost of the block's transactions (not including its parent microblocks)
+    pub block_cost: ExecutionCost,
+    /// the block's transactions that failed, by index.  If there are any, none of the block's
+    /// state changes after its transactions were applied.
+    pub tx_errors: Vec<(usize, Error)>,
+    /// liquid uSTX created by matured miner rewards
+    pub new_liquid_miner_ustx: u128,
+    /// liquid uSTX created by unlocks
+    pub new_unlocked_ustx: u128,
+}
+
+/// Why a block could not be evaluated at all
+#[derive(Debug)]
+pub enum BlockEvaluationError {
+    /// this parent microblock is invalid
+    Microblock(Error, BlockHeaderHash),
+    /// the chainstate could not be read or written
+    Chainstate(Error),
+}
+
 #[derive(Debug)]
 pub enum MemPoolRejection {
     SerializationFailure(net_error),
@@ -141,8 +232,14 @@ pub enum MemPoolRejection {
     BadAddressVersionByte,
     NoCoinbaseViaMempool,
     NoSuchChainTip(ConsensusHash, BlockHeaderHash),
-    ConflictingNonceInMempool,
+    /// another transaction with the same nonce and at least the same fee is already in the
+    /// mempool:  its txid and fee
+    ConflictingNonceInMempool(Txid, u64),
     TooMuchChaining,
+    /// the mempool's pending lane, or the transaction's origin's or sponsor's share of it, is full
+    TooManyPending,
+    /// a smart contract failed the node's strict analysis profile
+    StrictAnalysisFailure(CheckError),
     DBError(db_error),
     Other(String),
 }
@@ -159,6 +256,10 @@ impl MemPoolRejection {
                 "TooMuchChaining",
                 Some(json!({"message": "Nonce would exceed chaining limit in mempool"})),
             ),
+            TooManyPending => (
+                "TooManyPending",
+                Some(json!({"message": "Too many transactions are waiting for earlier nonces"})),
+            ),
             FailedToValidate(e) => (
                 "SignatureValidation",
                 Some(json!({"message": e.to_string()})),
@@ -192,11 +293,33 @@ impl MemPoolRejection {
             ),
             NoSuchContract => ("NoSuchContract", None),
             NoSuchPublicFunction => ("NoSuchPublicFunction", None),
-            BadFunctionArgument(e) => (
-                "BadFunctionArgument",
-                Some(json!({"message": e.to_string()})),
+            BadFunctionArgument(e) => {
+                let reason_data = match e.err {
+                    CheckErrors::IncorrectArgumentCount(expected, actual) => json!({
+                        "message": e.to_string(),
+                        "expected_args": expected,
+                        "actual_args": actual
+                    }),
+                    CheckErrors::TypeError(ref expected, ref actual) => json!({
+                        "message": e.to_string(),
+                        "expected_type": expected.to_string(),
+                        "actual_type": actual.to_string()
+                    }),
+                    _ => json!({"message": e.to_string()}),
+                };
+                ("BadFunctionArgument", Some(reason_data))
+            }
+            ConflictingNonceInMempool(conflicting_txid, conflicting_fee) => (
+                "ConflictingNonceInMempool",
+                Some(json!({
+                    "txid": conflicting_txid.to_hex(),
+                    "fee": conflicting_fee
+                })),
+            ),
+            StrictAnalysisFailure(e) => (
+                "StrictAnalysis",
+                Some(json!({"message": e.diagnostic.message})),
             ),
-            ConflictingNonceInMempool => ("ConflictingNonceInMempool", None),
             ContractAlreadyExists(id) => (
                 "ContractAlreadyExists",
                 Some(json!({ "contract_identifier": id.to_string() })),
@@ -352,6 +475,7 @@ impl BlockStreamData {
             rowid: None,
             offset: 0,
             total_bytes: 0,
+            end: None,
             is_microblock: false,
             seq: 0,
             in_staging: false,
@@ -364,6 +488,7 @@ impl BlockStreamData {
             rowid: None,
             offset: 0,
             total_bytes: 0,
+            end: None,
             is_microblock: true,
             seq: 0,
             in_staging: false,
@@ -379,12 +504,21 @@ impl BlockStreamData {
             rowid: None,
             offset: 0,
             total_bytes: 0,
+            end: None,
             is_microblock: true,
             seq: seq,
             in_staging: true,
         }
     }
 
+    /// Stream only the bytes from `start` up to (but not including) `end`.  Only meaningful for
+    /// data read out of the chunk store, i.e. blocks and confirmed microblock streams.
+    pub fn with_range(mut self, start: u64, end: u64) -> BlockStreamData {
+        self.offset = start;
+        self.end = Some(end);
+        self
+    }
+
     pub fn stream_to<W: Write>(
         &mut self,
         chainstate: &mut StacksChainState,
@@ -488,6 +622,7 @@ impl StacksChainState {
             .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
         conn.busy_handler(Some(tx_busy_handler))
             .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+        sqlite_set_wal_mode(&conn).map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
 
         if create_flag {
             // instantiate!
@@ -759,6 +894,89 @@ impl StacksChainState {
         StacksChainState::free_block(blocks_path, consensus_hash, &block_header.block_hash())
     }
 
+    /// Truncate a file in the chunk store, if it exists and is not already empty.
+    /// Returns true if it was truncated.
+    fn truncate_chunk(
+        blocks_path: &String,
+        index_block_hash: &StacksBlockId,
+    ) -> Result<bool, Error> {
+        let path = StacksChainState::get_index_block_path(blocks_path, index_block_hash)?;
+        match StacksChainState::get_file_size(&path) {
+            Ok(0) => Ok(false),
+            Ok(_) => {
+                fs::OpenOptions::new()
+                    .read(false)
+                    .write(true)
+                    .truncate(true)
+                    .open(&path)
+                    .map_err(|e| Error::DBError(db_error::IOError(e)))?;
+                Ok(true)
+            }
+            Err(Error::DBError(db_error::NotFoundError)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Prune the bodies of processed anchored blocks, and of the microblock streams they
+    /// confirmed, once they are buried under at least `confirmations` processed blocks.
+    /// Pruned blocks are truncated in the chunk store just like invalid blocks, so they are
+    /// still recognized as processed (and not downloaded again), but are no longer reported in
+    /// this node's inventory or served to peers.  Headers and chainstate are untouched.
+    /// Returns the number of anchored blocks pruned.
+    pub fn prune_block_bodies(&mut self, confirmations: u64) -> Result<u64, Error> {
+        let max_heights = StacksChainState::read_i64s(
+            &self.blocks_db,
+            "SELECT MAX(height) FROM staging_blocks WHERE processed = 1 AND orphaned = 0",
+            NO_PARAMS,
+        )?;
+        let max_height = match max_heights.first() {
+            Some(h) => *h as u64,
+            None => {
+                return Ok(0);
+            }
+        };
+        if max_height < confirmations {
+            return Ok(0);
+        }
+        let prune_height = max_height - confirmations;
+
+        let sql = "SELECT * FROM staging_blocks WHERE processed = 1 AND height <= ?1";
+        let args: &[&dyn ToSql] = &[&u64_to_sql(prune_height)?];
+        let blocks =
+            query_rows::<StagingBlock, _>(&self.blocks_db, sql, args).map_err(Error::DBError)?;
+
+        let mut num_pruned = 0;
+        for block in blocks.into_iter() {
+            let index_block_hash = StacksBlockHeader::make_index_block_hash(
+                &block.consensus_hash,
+                &block.anchored_block_hash,
+            );
+            if let Some(microblock_index_hash) =
+                self.get_confirmed_microblock_index_hash(&index_block_hash)?
+            {
+                StacksChainState::truncate_chunk(&self.blocks_path, &microblock_index_hash)?;
+            }
+            if StacksChainState::truncate_chunk(&self.blocks_path, &index_block_hash)? {
+                debug!(
+                    "Pruned block {}/{} ({}) at height {}",
+                    &block.consensus_hash,
+                    &block.anchored_block_hash,
+                    &index_block_hash,
+                    block.height
+                );
+                num_pruned += 1;
+            }
+        }
+
+        if num_pruned > 0 {
+            info!(
+                "Pruned {} block(s) at or below height {}",
+                num_pruned, prune_height
+            );
+        }
+        Ok(num_pruned)
+    }
+
     /// Get a list of all anchored blocks' hashes, and their burnchain headers
     pub fn list_blocks(
         blocks_conn: &DBConn,
@@ -1938,6 +2156,49 @@ impl StacksChainState {
         Ok(())
     }
 
+    /// Save a copy of a block we're about to reject, so a diagnostic bundle can be produced if
+    /// it turns out the rest of the network accepted it (see `net::divergence`).  Failing to
+    /// save it is not an error.
+    fn save_rejected_block(
+        root_path: &str,
+        staging_block: &StagingBlock,
+        block: &StacksBlock,
+        microblocks: &Vec<StacksMicroblock>,
+        parent_header_info: &StacksHeaderInfo,
+        burn_block_height: u64,
+        reason: &str,
+    ) {
+        let record = RejectedBlockRecord {
+            consensus_hash: staging_block.consensus_hash.clone(),
+            block_hash: staging_block.anchored_block_hash.clone(),
+            index_block_hash: StacksBlockHeader::make_index_block_hash(
+                &staging_block.consensus_hash,
+                &staging_block.anchored_block_hash,
+            ),
+            burn_block_height,
+            stacks_block_height: staging_block.height,
+            parent_index_block_hash: parent_header_info.index_block_hash(),
+            parent_state_index_root: parent_header_info.anchored_header.state_index_root.clone(),
+            state_index_root: block.header.state_index_root.clone(),
+            num_microblocks: microblocks.len() as u64,
+            commit_burn: staging_block.commit_burn,
+            sortition_burn: staging_block.sortition_burn,
+            reason: reason.to_string(),
+            rejected_at: get_epoch_time_secs(),
+        };
+        if let Err(e) = divergence::save_rejected_block(
+            root_path,
+            &record,
+            &staging_block.block_data,
+            &microblocks.serialize_to_vec(),
+        ) {
+            warn!(
+                "Failed to save rejected block {}/{}: {:?}",
+                &staging_block.consensus_hash, &staging_block.anchored_block_hash, &e
+            );
+        }
+    }
+
     /// Clear out a staging block -- mark it as processed.
     /// Mark its children as attachable.
     /// Idempotent.
@@ -2414,6 +2675,11 @@ impl StacksChainState {
             .seek(SeekFrom::Start(stream.offset))
             .map_err(Error::ReadError)?;
 
+        let count = match stream.end {
+            Some(end) => cmp::min(count, end.saturating_sub(stream.offset)),
+            None => count,
+        };
+
         let mut buf = vec![0u8; count as usize];
         let nr = input.read(&mut buf).map_err(Error::ReadError)?;
         fd.write_all(&buf[0..nr]).map_err(Error::WriteError)?;
@@ -2910,6 +3176,21 @@ impl StacksChainState {
             .get_block_snapshot(&block_commit.burn_header_hash)?
             .expect("FATAL: have block commit but no block snapshot");
 
+        // the block must have been mined in an epoch that has Stacks 2.0 blocks
+        match SortitionDB::get_stacks_epoch(db_handle.conn(), burn_chain_tip.block_height)? {
+            Some(epoch) if epoch.epoch_id >= StacksEpochId::Epoch20 => {}
+            epoch_opt => {
+                warn!(
+                    "Invalid block, mined in epoch {:?} at burn height {}: {}/{}",
+                    epoch_opt.map(|epoch| epoch.epoch_id),
+                    burn_chain_tip.block_height,
+                    consensus_hash,
+                    block_hash
+                );
+                return Ok(None);
+            }
+        }
+
         // this is the penultimate burnchain snapshot with the VRF seed that this
         // block's miner had to prove on to generate the block-commit and block itself.
         let penultimate_sortition_snapshot = db_handle
@@ -3245,7 +3526,7 @@ impl StacksChainState {
     }
 
     /// Get the coinbase at this block height, in microSTX
-    fn get_coinbase_reward(block_height: u64) -> u128 {
+    pub fn get_coinbase_reward(block_height: u64) -> u128 {
         /*
         From the token whitepaper:
 
@@ -3662,27 +3943,6 @@ impl StacksChainState {
         Ok((fees, burns, receipts))
     }
 
-    /// Process a single anchored block.
-    /// Return the fees and burns.
-    fn process_block_transactions<'a>(
-        clarity_tx: &mut ClarityTx<'a>,
-        block: &StacksBlock,
-    ) -> Result<(u128, u128, Vec<StacksTransactionReceipt>), Error> {
-        let mut fees = 0u128;
-        let mut burns = 0u128;
-        let mut receipts = vec![];
-        for tx in block.txs.iter() {
-            let (tx_fee, tx_receipt) =
-                StacksChainState::process_transaction(clarity_tx, tx, false)?;
-            fees = fees.checked_add(tx_fee as u128).expect("Fee overflow");
-            burns = burns
-                .checked_add(tx_receipt.stx_burned as u128)
-                .expect("Burns overflow");
-            receipts.push(tx_receipt);
-        }
-        Ok((fees, burns, receipts))
-    }
-
     /// Process a single matured miner reward.
     /// Grant it STX tokens.
     fn process_matured_miner_reward<'a>(
@@ -3716,20 +3976,150 @@ impl StacksChainState {
         Ok(())
     }
 
+    /// Record that the anchored block at this height signs its microblocks with the given public
+    /// key hash.  Poison-microblock reports are only processed from Stacks 2.1 on, so blocks
+    /// before then record nothing, and leave their state root as it was.
+    pub fn insert_microblock_pubkey_hash<'a>(
+        clarity_tx: &mut ClarityTx<'a>,
+        pubkey_hash: &Hash160,
+        block_height: u64,
+    ) -> Result<(), Error> {
+        if clarity_tx.get_epoch() < StacksEpochId::Epoch21 {
+            return Ok(());
+        }
+
+        clarity_tx
+            .connection()
+            .as_transaction(|x| {
+                x.with_clarity_db(|ref mut db| {
+                    db.insert_microblock_pubkey_hash_height(pubkey_hash, block_height);
+                    Ok(())
+                })
+            })
+            .map_err(Error::ClarityError)
+    }
+
+    /// Find the STX transfers sent through the burnchain that a block must materialize:  those
+    /// accepted in the burnchain blocks from the one that elected its parent (at
+    /// `parent_burn_header_height`) up to and including `burn_tip`, the burnchain block its miner
+    /// built on.
+    pub fn get_transfer_stx_ops(
+        sortdb_conn: &Connection,
+        parent_burn_header_height: u32,
+        burn_tip: &BurnchainHeaderHash,
+    ) -> Result<Vec<TransferStxOp>, Error> {
+        SortitionDB::get_transfer_stx_ops_since(
+            sortdb_conn,
+            parent_burn_header_height as u64,
+            burn_tip,
+        )
+        .map_err(Error::DBError)
+    }
+
+    /// Materialize STX transfers sent through the burnchain.  A transfer its sender can't afford
+    /// is skipped.
+    pub fn process_transfer_stx_ops<'a>(
+        clarity_tx: &mut ClarityTx<'a>,
+        operations: &Vec<TransferStxOp>,
+    ) {
+        for op in operations.iter() {
+            let sender = op.sender.to_account_principal();
+            let recipient = op.recipient.to_account_principal();
+            let result = clarity_tx
+                .connection()
+                .as_transaction(|x| x.run_stx_transfer(&sender, &recipient, op.transfered_ustx));
+            match result {
+                Ok((Value::Response(ref resp), ..)) if !resp.committed => {
+                    warn!(
+                        "Skipped transfer STX {} of {} uSTX from {} to {}: {}",
+                        &op.txid, op.transfered_ustx, &op.sender, &op.recipient, &resp.data
+                    );
+                }
+                Ok(_) => {
+                    debug!(
+                        "Processed transfer STX {} of {} uSTX from {} to {}",
+                        &op.txid, op.transfered_ustx, &op.sender, &op.recipient
+                    );
+                }
+                Err(e) => {
+                    warn!("Skipped transfer STX {}: {:?}", &op.txid, &e);
+                }
+            }
+        }
+    }
+
+    /// Get the poison-microblock report filed against the miner of the anchored block at this
+    /// height, if there is one in this fork.
+    pub fn get_microblock_poison_report<'a>(
+        clarity_tx: &mut ClarityTx<'a>,
+        block_height: u64,
+    ) -> Result<Option<MicroblockPoisonReport>, Error> {
+        if clarity_tx.get_epoch() < StacksEpochId::Epoch21 {
+            return Ok(None);
+        }
+
+        clarity_tx
+            .connection()
+            .as_transaction(|x| {
+                x.with_clarity_db(|ref mut db| Ok(db.get_microblock_poison_report(block_height)))
+            })
+            .map_err(Error::ClarityError)
+    }
+
+    /// The reward paid to whoever reported a miner for signing conflicting microblocks:  a
+    /// fraction of everything the miner forfeits.
+    fn make_poison_microblock_commission(
+        miner_reward: &MinerReward,
+        reporter: &StandardPrincipalData,
+    ) -> MinerReward {
+        let commission = |amount: u128| amount * POISON_MICROBLOCK_COMMISSION_FRACTION / 100;
+        MinerReward {
+            address: StacksAddress::from(reporter.clone()),
+            coinbase: commission(miner_reward.coinbase),
+            tx_fees_anchored_shared: commission(miner_reward.tx_fees_anchored_shared),
+            tx_fees_anchored_exclusive: commission(miner_reward.tx_fees_anchored_exclusive),
+            tx_fees_streamed_produced: commission(miner_reward.tx_fees_streamed_produced),
+            tx_fees_streamed_confirmed: commission(miner_reward.tx_fees_streamed_confirmed),
+            vtxindex: miner_reward.vtxindex,
+        }
+    }
+
     /// Process matured miner rewards for this block.
+    /// If the miner of the block whose rewards matured was reported for signing conflicting
+    /// microblocks, it forfeits its reward, and the reporter's commission replaces it in
+    /// `miner_rewards`.  User burn supports are paid as usual.
     /// Returns the number of liquid uSTX created -- i.e. the coinbase
     pub fn process_matured_miner_rewards<'a>(
         clarity_tx: &mut ClarityTx<'a>,
-        miner_rewards: &Vec<MinerReward>,
+        miner_rewards: &mut Vec<MinerReward>,
+        miner_rewards_info: &MinerRewardInfo,
     ) -> Result<u128, Error> {
         // must all be in order by vtxindex, and the first reward (the miner's) must have vtxindex 0
         assert!(miner_rewards.len() > 0);
         assert!(miner_rewards[0].vtxindex == 0);
-        let coinbase_reward = miner_rewards[0].coinbase;
         for i in 0..miner_rewards.len() - 1 {
             assert!(miner_rewards[i].vtxindex < miner_rewards[i + 1].vtxindex);
         }
 
+        if let Some(report) = StacksChainState::get_microblock_poison_report(
+            clarity_tx,
+            miner_rewards_info.from_stacks_block_height,
+        )? {
+            info!(
+                "Miner {} of {}/{} forfeits its reward for signing conflicting microblocks (sequence {}); reported by {}",
+                &miner_rewards[0].address,
+                &miner_rewards_info.from_block_consensus_hash,
+                &miner_rewards_info.from_stacks_block_hash,
+                report.sequence,
+                &report.reporter
+            );
+            miner_rewards[0] = StacksChainState::make_poison_microblock_commission(
+                &miner_rewards[0],
+                &report.reporter,
+            );
+        }
+        let coinbase_reward = miner_rewards[0].coinbase;
+
         // store each reward into the miner trust fund contract in the chain boot code
         for reward in miner_rewards.iter() {
             StacksChainState::process_matured_miner_reward(clarity_tx, reward)?;
@@ -3744,6 +4134,106 @@ impl StacksChainState {
         Ok(0)
     }
 
+    /// Evaluate a block on top of its parent's state in `clarity_tx`: its parent microblocks'
+    /// transactions, its own transactions, and then the STX transfers sent through the burnchain,
+    /// its microblock public key hash, the matured miner rewards, and STX unlocks.  Processing,
+    /// validating and replaying a block all go through here.
+    /// If `all_tx_errors` is set, every one of the block's transactions is evaluated even after
+    /// one fails, so that they can all be reported.
+    /// The caller commits or rolls back `clarity_tx`.
+    fn evaluate_block<'a>(
+        clarity_tx: &mut ClarityTx<'a>,
+        block: &StacksBlock,
+        microblocks: &Vec<StacksMicroblock>,
+        transfer_stx_ops: &Vec<TransferStxOp>,
+        matured_rewards: &mut Vec<MinerReward>,
+        matured_rewards_info: Option<&MinerRewardInfo>,
+        all_tx_errors: bool,
+    ) -> Result<BlockEvaluation, BlockEvaluationError> {
+        // process microblock stream
+        let (microblock_fees, microblock_burns, microblock_receipts) =
+            StacksChainState::process_microblocks_transactions(clarity_tx, microblocks)
+                .map_err(|(e, mblock_hash)| BlockEvaluationError::Microblock(e, mblock_hash))?;
+        let microblock_cost = clarity_tx.cost_so_far();
+
+        // process anchored block
+        let mut block_fees = 0u128;
+        let mut block_burns = 0u128;
+        let mut tx_receipts = vec![];
+        let mut tx_errors = vec![];
+        for (tx_index, tx) in block.txs.iter().enumerate() {
+            // a proposal's failures are reported to its sender, so don't log them too
+            match StacksChainState::process_transaction(clarity_tx, tx, all_tx_errors) {
+                Ok((tx_fee, tx_receipt)) => {
+                    block_fees = block_fees
+                        .checked_add(tx_fee as u128)
+                        .expect("Fee overflow");
+                    block_burns = block_burns
+                        .checked_add(tx_receipt.stx_burned as u128)
+                        .expect("Burns overflow");
+                    tx_receipts.push(tx_receipt);
+                }
+                Err(e) => {
+                    tx_errors.push((tx_index, e));
+                    if !all_tx_errors {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut block_cost = clarity_tx.cost_so_far();
+        block_cost
+            .sub(&microblock_cost)
+            .expect("BUG: microblock cost + block cost < block cost");
+
+        let mut evaluation = BlockEvaluation {
+            microblock_fees,
+            microblock_burns,
+            microblock_receipts,
+            microblock_cost,
+            block_fees,
+            block_burns,
+            tx_receipts,
+            block_cost,
+            tx_errors,
+            new_liquid_miner_ustx: 0,
+            new_unlocked_ustx: 0,
+        };
+        if evaluation.tx_errors.len() > 0 {
+            return Ok(evaluation);
+        }
+
+        // materialize STX transfers sent through the burnchain
+        StacksChainState::process_transfer_stx_ops(clarity_tx, transfer_stx_ops);
+
+        // remember which anchored block signs microblocks with this key, so a poison-microblock
+        // transaction can find its miner's reward later
+        StacksChainState::insert_microblock_pubkey_hash(
+            clarity_tx,
+            &block.header.microblock_pubkey_hash,
+            block.header.total_work.work,
+        )
+        .map_err(BlockEvaluationError::Chainstate)?;
+
+        // grant matured miner rewards
+        if let Some(rewards_info) = matured_rewards_info {
+            // grant in order by miner, then users
+            evaluation.new_liquid_miner_ustx = StacksChainState::process_matured_miner_rewards(
+                clarity_tx,
+                matured_rewards,
+                rewards_info,
+            )
+            .map_err(BlockEvaluationError::Chainstate)?;
+        }
+
+        // unlock any uSTX
+        evaluation.new_unlocked_ustx = StacksChainState::process_stx_unlocks(clarity_tx)
+            .map_err(BlockEvaluationError::Chainstate)?;
+
+        Ok(evaluation)
+    }
+
     /// Process the next pre-processed staging block.
     /// We've already processed parent_chain_tip.  chain_tip refers to a block we have _not_
     /// processed yet.
@@ -3767,6 +4257,7 @@ impl StacksChainState {
         burnchain_commit_burn: u64,
         burnchain_sortition_burn: u64,
         user_burns: &Vec<StagingUserBurnSupport>,
+        transfer_stx_ops: &Vec<TransferStxOp>,
     ) -> Result<StacksEpochReceipt, Error> {
         debug!(
             "Process block {:?} with {} transactions",
@@ -3778,10 +4269,16 @@ impl StacksChainState {
         let next_block_height = block.header.total_work.work;
 
         // find matured miner rewards, so we can grant them within the Clarity DB tx.
-        let (matured_rewards, matured_rewards_info) =
+        let epoch_id = StacksChainState::get_child_epoch(
+            chainstate_tx.headers_tx.sqlite(),
+            burn_dbconn,
+            parent_chain_tip,
+        );
+        let (mut matured_rewards, matured_rewards_info) =
             match StacksChainState::find_mature_miner_rewards(
                 &mut chainstate_tx.headers_tx,
                 parent_chain_tip,
+                epoch_id,
                 Some(chainstate_tx.miner_payment_cache),
             )? {
                 Some((rewards, rewards_info)) => (rewards, Some(rewards_info)),
@@ -3846,79 +4343,63 @@ impl StacksChainState {
                 &MINER_BLOCK_HEADER_HASH,
             );
 
-            // process microblock stream
-            let (microblock_fees, microblock_burns, mut microblock_txs_receipts) =
-                match StacksChainState::process_microblocks_transactions(
-                    &mut clarity_tx,
-                    &microblocks,
-                ) {
-                    Err((e, offending_mblock_header_hash)) => {
-                        let msg = format!(
-                            "Invalid Stacks microblocks {},{} (offender {}): {:?}",
-                            block.header.parent_microblock,
-                            block.header.parent_microblock_sequence,
-                            offending_mblock_header_hash,
-                            &e
-                        );
-                        warn!("{}", &msg);
+            let mut evaluation = match StacksChainState::evaluate_block(
+                &mut clarity_tx,
+                block,
+                microblocks,
+                transfer_stx_ops,
+                &mut matured_rewards,
+                matured_rewards_info.as_ref(),
+                false,
+            ) {
+                Ok(evaluation) => evaluation,
+                Err(BlockEvaluationError::Microblock(e, offending_mblock_header_hash)) => {
+                    let msg = format!(
+                        "Invalid Stacks microblocks {},{} (offender {}): {:?}",
+                        block.header.parent_microblock,
+                        block.header.parent_microblock_sequence,
+                        offending_mblock_header_hash,
+                        &e
+                    );
+                    warn!("{}", &msg);
 
-                        clarity_tx.rollback_block();
-                        return Err(Error::InvalidStacksMicroblock(
-                            msg,
-                            offending_mblock_header_hash,
-                        ));
-                    }
-                    Ok((fees, burns, events)) => (fees, burns, events),
-                };
+                    clarity_tx.rollback_block();
+                    return Err(Error::InvalidStacksMicroblock(
+                        msg,
+                        offending_mblock_header_hash,
+                    ));
+                }
+                Err(BlockEvaluationError::Chainstate(e)) => {
+                    clarity_tx.rollback_block();
+                    return Err(e);
+                }
+            };
 
-            let microblock_cost = clarity_tx.cost_so_far();
             debug!("\n\nAppend block {}/{} off of {}/{}\nStacks block height: {}, Total Burns: {}\nMicroblock parent: {} (seq {}) (count {})\n", 
                    chain_tip_consensus_hash, block.block_hash(), parent_consensus_hash, parent_block_hash,
                    block.header.total_work.work, block.header.total_work.burn,
                    last_microblock_hash, last_microblock_seq, microblocks.len());
 
-            // process anchored block
-            let (block_fees, block_burns, mut txs_receipts) =
-                match StacksChainState::process_block_transactions(&mut clarity_tx, &block) {
-                    Err(e) => {
-                        let msg = format!("Invalid Stacks block {}: {:?}", block.block_hash(), &e);
-                        warn!("{}", &msg);
-
-                        clarity_tx.rollback_block();
-                        return Err(Error::InvalidStacksBlock(msg));
-                    }
-                    Ok((block_fees, block_burns, txs_receipts)) => {
-                        (block_fees, block_burns, txs_receipts)
-                    }
-                };
-
-            let mut block_cost = clarity_tx.cost_so_far();
-            block_cost
-                .sub(&microblock_cost)
-                .expect("BUG: microblock cost + block cost < block cost");
+            if let Some((_, e)) = evaluation.tx_errors.first() {
+                let msg = format!("Invalid Stacks block {}: {:?}", block.block_hash(), e);
+                warn!("{}", &msg);
 
-            // grant matured miner rewards
-            let new_liquid_miner_ustx = if matured_rewards.len() > 0 {
-                // grant in order by miner, then users
-                StacksChainState::process_matured_miner_rewards(&mut clarity_tx, &matured_rewards)?
-            } else {
-                0
-            };
+                clarity_tx.rollback_block();
+                return Err(Error::InvalidStacksBlock(msg));
+            }
 
             // total burns
-            let total_burnt = block_burns
-                .checked_add(microblock_burns)
+            let total_burnt = evaluation
+                .block_burns
+                .checked_add(evaluation.microblock_burns)
                 .expect("Overflow: Too many STX burnt");
 
-            // unlock any uSTX
-            let new_unlocked_ustx = StacksChainState::process_stx_unlocks(&mut clarity_tx)?;
-
             // calculate total liquid STX
             let total_liquid_ustx = parent_chain_tip
                 .total_liquid_ustx
-                .checked_add(new_liquid_miner_ustx)
+                .checked_add(evaluation.new_liquid_miner_ustx)
                 .expect("FATAL: uSTX overflow")
-                .checked_add(new_unlocked_ustx)
+                .checked_add(evaluation.new_unlocked_ustx)
                 .expect("FATAL: uSTX overflow")
                 .checked_sub(total_burnt)
                 .expect("FATAL: uSTX underflow");
@@ -3941,6 +4422,7 @@ impl StacksChainState {
 
             // good to go!
             clarity_tx.commit_to_block(chain_tip_consensus_hash, &block.block_hash());
+            monitoring::observe_block_execution_cost(&evaluation.block_cost);
 
             // calculate reward for this block's miner
             let scheduled_miner_reward = StacksChainState::make_scheduled_miner_reward(
@@ -3950,8 +4432,8 @@ impl StacksChainState {
                 &block,
                 chain_tip_consensus_hash,
                 next_block_height,
-                block_fees, // TODO: calculate (STX/compute unit) * (compute used)
-                microblock_fees,
+                evaluation.block_fees, // TODO: calculate (STX/compute unit) * (compute used)
+                evaluation.microblock_fees,
                 total_burnt,
                 burnchain_commit_burn,
                 burnchain_sortition_burn,
@@ -3959,13 +4441,14 @@ impl StacksChainState {
             ) // TODO: calculate total compute budget and scale up
             .expect("FATAL: parsed and processed a block without a coinbase");
 
-            txs_receipts.append(&mut microblock_txs_receipts);
+            let mut txs_receipts = evaluation.tx_receipts;
+            txs_receipts.append(&mut evaluation.microblock_receipts);
 
             (
                 scheduled_miner_reward,
                 txs_receipts,
-                microblock_cost,
-                block_cost,
+                evaluation.microblock_cost,
+                evaluation.block_cost,
                 total_liquid_ustx,
             )
         };
@@ -3993,6 +4476,12 @@ impl StacksChainState {
         .expect("FATAL: failed to advance chain tip");
 
         chainstate_tx.log_transactions_processed(&new_tip.index_block_hash(), &tx_receipts);
+        chainstate_tx.store_transaction_receipts(
+            &new_tip.index_block_hash(),
+            new_tip.block_height,
+            &tx_receipts,
+        )?;
+        chainstate_tx.update_fork_tips(&parent_chain_tip.index_block_hash(), &new_tip)?;
 
         let epoch_receipt = StacksEpochReceipt {
             header: new_tip,
@@ -4032,6 +4521,439 @@ impl StacksChainState {
         return true;
     }
 
+    /// Statically check each transaction of a proposed anchored block, returning the reason it
+    /// is invalid if it is.
+    fn check_proposed_transaction(
+        mainnet: bool,
+        chain_id: u32,
+        tx_index: usize,
+        tx: &StacksTransaction,
+    ) -> Option<String> {
+        if let Err(e) = tx.verify() {
+            return Some(format!("Bad signature: {}", e));
+        }
+        if mainnet != tx.is_mainnet() {
+            return Some(format!(
+                "Wrong network: expected {}",
+                if mainnet { "mainnet" } else { "testnet" }
+            ));
+        }
+        if tx.chain_id != chain_id {
+            return Some(format!(
+                "Wrong chain ID: expected {:08x}, got {:08x}",
+                chain_id, tx.chain_id
+            ));
+        }
+        if tx.anchor_mode == TransactionAnchorMode::OffChainOnly {
+            return Some("Off-chain-only transaction in an anchored block".to_string());
+        }
+        match (tx_index, &tx.payload) {
+            (0, TransactionPayload::Coinbase(_)) => None,
+            (0, _) => Some("First transaction is not a coinbase".to_string()),
+            (_, TransactionPayload::Coinbase(_)) => {
+                Some("Coinbase is not the first transaction".to_string())
+            }
+            (_, _) => None,
+        }
+    }
+
+    /// Run the same static and dynamic checks on a proposed anchored block that we would run on
+    /// it if it were mined on top of `parent_tip`, but without storing it or its state.  The
+    /// parent microblocks it confirms must already be known to this node.  All of the block's
+    /// transactions are evaluated, so every failing transaction is reported, not just the first.
+    pub fn validate_block_proposal(
+        &self,
+        burn_dbconn: &dyn BurnStateDB,
+        parent_tip: &StacksHeaderInfo,
+        block: &StacksBlock,
+        transfer_stx_ops: &Vec<TransferStxOp>,
+    ) -> Result<BlockProposalValidation, Error> {
+        let mut validation = BlockProposalValidation {
+            block_errors: vec![],
+            tx_errors: vec![],
+            state_index_root: None,
+            execution_cost: ExecutionCost::zero(),
+            total_fees: 0,
+        };
+
+        // block must attach to the given parent
+        if !block.is_first_mined()
+            && !StacksChainState::check_block_attachment(&parent_tip.anchored_header, &block.header)
+        {
+            validation.block_errors.push(format!(
+                "Block does not attach to parent {}/{} at height {}",
+                &parent_tip.consensus_hash,
+                &parent_tip.anchored_header.block_hash(),
+                parent_tip.block_height
+            ));
+            return Ok(validation);
+        }
+
+        if block.txs.len() == 0 {
+            validation
+                .block_errors
+                .push("Block has no transactions".to_string());
+            return Ok(validation);
+        }
+
+        let txid_vecs = block
+            .txs
+            .iter()
+            .map(|tx| tx.txid().as_bytes().to_vec())
+            .collect();
+        let tx_merkle_root = MerkleTree::<Sha512Trunc256Sum>::new(&txid_vecs).root();
+        if tx_merkle_root != block.header.tx_merkle_root {
+            validation.block_errors.push(format!(
+                "Tx Merkle root mismatch: expected {}, got {}",
+                &tx_merkle_root, &block.header.tx_merkle_root
+            ));
+        }
+
+        let mut txids = HashSet::new();
+        for (tx_index, tx) in block.txs.iter().enumerate() {
+            let txid = tx.txid();
+            let reason = if !txids.insert(txid.clone()) {
+                Some("Duplicate transaction".to_string())
+            } else {
+                StacksChainState::check_proposed_transaction(
+                    self.mainnet,
+                    self.chain_id,
+                    tx_index,
+                    tx,
+                )
+            };
+            if let Some(reason) = reason {
+                validation.tx_errors.push(TransactionValidationError {
+                    tx_index,
+                    txid,
+                    reason,
+                });
+            }
+        }
+
+        if validation.tx_errors.len() > 0 {
+            // don't bother evaluating transactions that could never be mined
+            return Ok(validation);
+        }
+
+        let (parent_consensus_hash, parent_block_hash) = if block.is_first_mined() {
+            (
+                FIRST_BURNCHAIN_CONSENSUS_HASH.clone(),
+                FIRST_STACKS_BLOCK_HASH.clone(),
+            )
+        } else {
+            (
+                parent_tip.consensus_hash.clone(),
+                parent_tip.anchored_header.block_hash(),
+            )
+        };
+
+        // the parent microblocks this block confirms
+        let parent_microblocks = if block.header.parent_microblock == EMPTY_MICROBLOCK_PARENT_HASH {
+            vec![]
+        } else {
+            let mblocks = StacksChainState::load_staging_microblock_stream(
+                &self.blocks_db,
+                &self.blocks_path,
+                &parent_consensus_hash,
+                &parent_block_hash,
+                block.header.parent_microblock_sequence,
+            )?
+            .unwrap_or(vec![]);
+
+            let connects = mblocks
+                .last()
+                .map(|last| last.block_hash() == block.header.parent_microblock)
+                .unwrap_or(false);
+            if !connects {
+                validation.block_errors.push(format!(
+                    "Unknown parent microblock {} (seq {})",
+                    &block.header.parent_microblock, block.header.parent_microblock_sequence
+                ));
+                return Ok(validation);
+            }
+            mblocks
+        };
+
+        // evaluate in a scratch chainstate, and throw away the result
+        let (mut chainstate, _) = self.reopen()?;
+
+        let (mut matured_rewards, matured_rewards_info) = {
+            let epoch_id =
+                StacksChainState::get_child_epoch(chainstate.headers_db(), burn_dbconn, parent_tip);
+            match StacksChainState::find_mature_miner_rewards(
+                &mut chainstate.index_conn(),
+                parent_tip,
+                epoch_id,
+                None,
+            )? {
+                Some((rewards, rewards_info)) => (rewards, Some(rewards_info)),
+                None => (vec![], None),
+            }
+        };
+
+        let mut clarity_tx = chainstate.block_begin(
+            burn_dbconn,
+            &parent_consensus_hash,
+            &parent_block_hash,
+            &MINER_BLOCK_CONSENSUS_HASH,
+            &MINER_BLOCK_HEADER_HASH,
+        );
+
+        let evaluation = match StacksChainState::evaluate_block(
+            &mut clarity_tx,
+            block,
+            &parent_microblocks,
+            transfer_stx_ops,
+            &mut matured_rewards,
+            matured_rewards_info.as_ref(),
+            true,
+        ) {
+            Ok(evaluation) => evaluation,
+            Err(BlockEvaluationError::Microblock(e, mblock_hash)) => {
+                clarity_tx.rollback_block();
+                validation.block_errors.push(format!(
+                    "Invalid parent microblock {}: {:?}",
+                    &mblock_hash, &e
+                ));
+                return Ok(validation);
+            }
+            Err(BlockEvaluationError::Chainstate(e)) => {
+                clarity_tx.rollback_block();
+                return Err(e);
+            }
+        };
+
+        validation.execution_cost = evaluation.block_cost;
+        validation.total_fees = evaluation.block_fees;
+
+        if evaluation.tx_errors.len() > 0 {
+            // the state root is meaningless if any transaction could not be evaluated
+            clarity_tx.rollback_block();
+            for (tx_index, e) in evaluation.tx_errors.iter() {
+                validation.tx_errors.push(TransactionValidationError {
+                    tx_index: *tx_index,
+                    txid: block.txs[*tx_index].txid(),
+                    reason: format!("{:?}", e),
+                });
+            }
+            return Ok(validation);
+        }
+
+        let root_hash = clarity_tx.get_root_hash();
+        clarity_tx.rollback_block();
+
+        if root_hash != block.header.state_index_root {
+            validation.block_errors.push(format!(
+                "State root mismatch: expected {}, got {}",
+                &root_hash, &block.header.state_index_root
+            ));
+        }
+        validation.state_index_root = Some(root_hash);
+        Ok(validation)
+    }
+
+    /// Re-execute a processed block on top of its parent's state, in a scratch chainstate, and
+    /// compare the state root and transaction receipts it produces to the ones recorded when the
+    /// block was processed.  A difference means that block processing is non-deterministic, or
+    /// that the chainstate is corrupt.
+    pub fn replay_block(
+        &self,
+        sortdb_conn: &SortitionDBConn,
+        index_block_hash: &StacksBlockId,
+    ) -> Result<BlockReplay, Error> {
+        let header_info = StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            self.headers_db(),
+            index_block_hash,
+        )?
+        .ok_or(Error::NoSuchBlockError)?;
+
+        if header_info.block_height == 0 {
+            // the boot block isn't executed
+            return Err(Error::NoSuchBlockError);
+        }
+
+        let parent_index_block_hash =
+            StacksChainState::get_parent_block_id(self.headers_db(), index_block_hash)?
+                .ok_or(Error::NoSuchBlockError)?;
+        let parent_tip = StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            self.headers_db(),
+            &parent_index_block_hash,
+        )?
+        .ok_or(Error::NoSuchBlockError)?;
+
+        let block_hash = header_info.anchored_header.block_hash();
+        let block = StacksChainState::load_block(
+            &self.blocks_path,
+            &header_info.consensus_hash,
+            &block_hash,
+        )?
+        .ok_or(Error::NoSuchBlockError)?;
+
+        let mut replay = BlockReplay {
+            index_block_hash: index_block_hash.clone(),
+            block_height: header_info.block_height,
+            expected_state_root: header_info.anchored_header.state_index_root.clone(),
+            state_root: None,
+            receipts_recorded: false,
+            mismatches: vec![],
+        };
+
+        let (parent_consensus_hash, parent_block_hash) = if block.is_first_mined() {
+            (
+                FIRST_BURNCHAIN_CONSENSUS_HASH.clone(),
+                FIRST_STACKS_BLOCK_HASH.clone(),
+            )
+        } else {
+            (
+                parent_tip.consensus_hash.clone(),
+                parent_tip.anchored_header.block_hash(),
+            )
+        };
+
+        let parent_microblocks = if block.header.parent_microblock == EMPTY_MICROBLOCK_PARENT_HASH {
+            vec![]
+        } else {
+            let mblocks = StacksChainState::load_staging_microblock_stream(
+                &self.blocks_db,
+                &self.blocks_path,
+                &parent_consensus_hash,
+                &parent_block_hash,
+                block.header.parent_microblock_sequence,
+            )?
+            .unwrap_or(vec![]);
+
+            let connects = mblocks
+                .last()
+                .map(|last| last.block_hash() == block.header.parent_microblock)
+                .unwrap_or(false);
+            if !connects {
+                replay.mismatches.push(format!(
+                    "Parent microblock {} (seq {}) is not stored",
+                    &block.header.parent_microblock, block.header.parent_microblock_sequence
+                ));
+                return Ok(replay);
+            }
+            mblocks
+        };
+
+        let snapshot =
+            SortitionDB::get_block_snapshot_consensus(sortdb_conn, &header_info.consensus_hash)?
+                .ok_or(Error::NoSuchBlockError)?;
+        let transfer_stx_ops = StacksChainState::get_transfer_stx_ops(
+            sortdb_conn,
+            parent_tip.burn_header_height,
+            &snapshot.parent_burn_header_hash,
+        )?;
+
+        // evaluate in a scratch chainstate, and throw away the result
+        let (mut chainstate, _) = self.reopen()?;
+
+        let (mut matured_rewards, matured_rewards_info) = {
+            let epoch_id = StacksChainState::get_child_epoch(
+                chainstate.headers_db(),
+                sortdb_conn,
+                &parent_tip,
+            );
+            match StacksChainState::find_mature_miner_rewards(
+                &mut chainstate.index_conn(),
+                &parent_tip,
+                epoch_id,
+                None,
+            )? {
+                Some((rewards, rewards_info)) => (rewards, Some(rewards_info)),
+                None => (vec![], None),
+            }
+        };
+
+        let mut clarity_tx = chainstate.block_begin(
+            sortdb_conn,
+            &parent_consensus_hash,
+            &parent_block_hash,
+            &MINER_BLOCK_CONSENSUS_HASH,
+            &MINER_BLOCK_HEADER_HASH,
+        );
+
+        let evaluation = match StacksChainState::evaluate_block(
+            &mut clarity_tx,
+            &block,
+            &parent_microblocks,
+            &transfer_stx_ops,
+            &mut matured_rewards,
+            matured_rewards_info.as_ref(),
+            false,
+        ) {
+            Ok(evaluation) => evaluation,
+            Err(BlockEvaluationError::Microblock(e, mblock_hash)) => {
+                clarity_tx.rollback_block();
+                replay.mismatches.push(format!(
+                    "Failed to re-execute parent microblock {}: {:?}",
+                    &mblock_hash, &e
+                ));
+                return Ok(replay);
+            }
+            Err(BlockEvaluationError::Chainstate(e)) => {
+                clarity_tx.rollback_block();
+                return Err(e);
+            }
+        };
+
+        if let Some((_, e)) = evaluation.tx_errors.first() {
+            clarity_tx.rollback_block();
+            replay
+                .mismatches
+                .push(format!("Failed to re-execute block: {:?}", e));
+            return Ok(replay);
+        }
+
+        let mut receipts = evaluation.tx_receipts;
+        let mut microblock_receipts = evaluation.microblock_receipts;
+        receipts.append(&mut microblock_receipts);
+
+        let state_root = clarity_tx.get_root_hash();
+        clarity_tx.rollback_block();
+        let state_root = clarity_tx.get_root_hash();
+        clarity_tx.rollback_block();
+
+        if state_root != replay.expected_state_root {
+            replay.mismatches.push(format!(
+                "State root mismatch: recorded {}, replayed {}",
+                &replay.expected_state_root, &state_root
+            ));
+        }
+        replay.state_root = Some(state_root);
+
+        let recorded_receipts = self.get_block_transaction_receipts(index_block_hash)?;
+        replay.receipts_recorded = recorded_receipts.len() > 0;
+        if replay.receipts_recorded {
+            if recorded_receipts.len() != receipts.len() {
+                replay.mismatches.push(format!(
+                    "Receipt count mismatch: recorded {}, replayed {}",
+                    recorded_receipts.len(),
+                    receipts.len()
+                ));
+            }
+            for (tx_index, (recorded, receipt)) in
+                recorded_receipts.iter().zip(receipts.iter()).enumerate()
+            {
+                let replayed = TransactionReceiptData::from_receipt(
+                    receipt,
+                    index_block_hash,
+                    header_info.block_height,
+                    tx_index as u32,
+                );
+                if *recorded != replayed {
+                    replay.mismatches.push(format!(
+                        "Receipt mismatch for transaction {} ({}): recorded {:?}, replayed {:?}",
+                        tx_index, &replayed.txid, recorded, &replayed
+                    ));
+                }
+            }
+        }
+
+        Ok(replay)
+    }
+
     /// Find and process the next staging block.
     /// Return the next chain tip if we processed this block, or None if we couldn't.
     /// Return a poison microblock transaction payload if the microblock stream contains a
@@ -4043,6 +4965,7 @@ impl StacksChainState {
         &mut self,
         sort_tx: &mut SortitionHandleTx,
     ) -> Result<(Option<StacksEpochReceipt>, Option<TransactionPayload>), Error> {
+        let root_path = self.root_path.clone();
         let (mut chainstate_tx, clarity_instance) = self.chainstate_tx_begin()?;
 
         let blocks_path = chainstate_tx.blocks_tx.get_blocks_path().clone();
@@ -4065,13 +4988,14 @@ impl StacksChainState {
                 }
             };
 
-        let (burn_header_hash, burn_header_height, burn_header_timestamp) =
+        let (burn_header_hash, parent_burn_header_hash, burn_header_height, burn_header_timestamp) =
             match SortitionDB::get_block_snapshot_consensus(
                 sort_tx,
                 &next_staging_block.consensus_hash,
             )? {
                 Some(sn) => (
                     sn.burn_header_hash,
+                    sn.parent_burn_header_hash,
                     sn.block_height as u32,
                     sn.burn_header_timestamp,
                 ),
@@ -4187,6 +5111,16 @@ impl StacksChainState {
             );
             warn!("{}", &msg);
 
+            StacksChainState::save_rejected_block(
+                &root_path,
+                &next_staging_block,
+                &block,
+                &next_microblocks,
+                &parent_block_header_info,
+                burn_header_height as u64,
+                &msg,
+            );
+
             // clear out
             StacksChainState::set_block_processed(
                 &mut chainstate_tx.blocks_tx,
@@ -4214,6 +5148,16 @@ impl StacksChainState {
             );
             warn!("{}", &msg);
 
+            StacksChainState::save_rejected_block(
+                &root_path,
+                &next_staging_block,
+                &block,
+                &next_microblocks,
+                &parent_block_header_info,
+                burn_header_height as u64,
+                &msg,
+            );
+
             // clear out
             StacksChainState::set_block_processed(
                 &mut chainstate_tx.blocks_tx,
@@ -4294,6 +5238,14 @@ impl StacksChainState {
             &next_staging_block.anchored_block_hash,
         )?;
 
+        // find STX transfers sent through the burnchain since the parent block was elected, up to
+        // the burnchain block this block's miner built on
+        let transfer_stx_ops = StacksChainState::get_transfer_stx_ops(
+            sort_tx,
+            parent_block_header_info.burn_header_height,
+            &parent_burn_header_hash,
+        )?;
+
         // attach the block to the chain state and calculate the next chain tip.
         // Execute the confirmed microblocks' transactions against the chain state, and then
         // execute the anchored block's transactions against the chain state.
@@ -4311,6 +5263,7 @@ impl StacksChainState {
             next_staging_block.commit_burn,
             next_staging_block.sortition_burn,
             &user_supports,
+            &transfer_stx_ops,
         ) {
             Ok(next_chain_tip_info) => next_chain_tip_info,
             Err(e) => {
@@ -4322,6 +5275,15 @@ impl StacksChainState {
                     &next_staging_block.consensus_hash,
                     &block.block_hash()
                 );
+                StacksChainState::save_rejected_block(
+                    &root_path,
+                    &next_staging_block,
+                    &block,
+                    &next_microblocks,
+                    &parent_block_header_info,
+                    burn_header_height as u64,
+                    &format!("{:?}", &e),
+                );
                 StacksChainState::set_block_processed(
                     &mut chainstate_tx.blocks_tx,
                     None,
@@ -4600,6 +5562,119 @@ impl StacksChainState {
         })
     }
 
+    /// Can the paying account of a transaction afford it, on top of the given chain tip?  Used
+    /// for transactions that wait in the mempool's pending lane:  their nonces are ahead of their
+    /// accounts' next nonces, so will_admit_mempool_tx() rejects them before checking funds.
+    pub fn will_admit_pending_mempool_tx(
+        &mut self,
+        current_consensus_hash: &ConsensusHash,
+        current_block: &BlockHeaderHash,
+        tx: &StacksTransaction,
+    ) -> Result<(), MemPoolRejection> {
+        let current_tip =
+            StacksChainState::get_parent_index_block(current_consensus_hash, current_block);
+        self.with_read_only_clarity_tx(&NULL_BURN_STATE_DB, &current_tip, |conn| {
+            let fee = tx.get_fee_rate() as u128;
+            let origin = StacksChainState::get_account(conn, &tx.origin_address().into());
+            let payer = match tx.sponsor_address() {
+                Some(sponsor_address) => {
+                    StacksChainState::get_account(conn, &sponsor_address.into())
+                }
+                None => origin.clone(),
+            };
+            let block_height = conn.with_clarity_db_readonly(|ref mut db| {
+                db.get_current_burnchain_block_height() as u64
+            });
+
+            match tx.payload {
+                TransactionPayload::TokenTransfer(_, amount, _) => {
+                    if origin != payer && !payer.stx_balance.can_transfer(fee, block_height) {
+                        return Err(MemPoolRejection::NotEnoughFunds(
+                            fee,
+                            payer
+                                .stx_balance
+                                .get_available_balance_at_block(block_height),
+                        ));
+                    }
+                    let total_spent = (amount as u128) + if origin == payer { fee } else { 0 };
+                    if !origin.stx_balance.can_transfer(total_spent, block_height) {
+                        return Err(MemPoolRejection::NotEnoughFunds(
+                            total_spent,
+                            origin
+                                .stx_balance
+                                .get_available_balance_at_block(block_height),
+                        ));
+                    }
+                }
+                _ => {
+                    if !payer.stx_balance.can_transfer(fee, block_height) {
+                        return Err(MemPoolRejection::NotEnoughFunds(
+                            fee,
+                            payer
+                                .stx_balance
+                                .get_available_balance_at_block(block_height),
+                        ));
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Check a smart contract transaction's code against a strict lint profile, on top of the
+    /// given chain tip.  Contracts that fail to parse or type-check are not rejected here --
+    /// they are still valid transactions, which the chain will process as failed deployments.
+    pub fn will_admit_strict_contract(
+        &mut self,
+        current_consensus_hash: &ConsensusHash,
+        current_block: &BlockHeaderHash,
+        tx: &StacksTransaction,
+        profile: &StrictProfile,
+    ) -> Result<(), MemPoolRejection> {
+        let (name, code_body) = match tx.payload {
+            TransactionPayload::SmartContract(TransactionSmartContract {
+                ref name,
+                ref code_body,
+            }) => (name, code_body),
+            _ => {
+                return Ok(());
+            }
+        };
+
+        let contract_identifier =
+            QualifiedContractIdentifier::new(tx.origin_address().into(), name.clone());
+        let mut contract_ast =
+            match build_ast(&contract_identifier, &code_body.to_string(), &mut ()) {
+                Ok(ast) => ast,
+                Err(_) => {
+                    return Ok(());
+                }
+            };
+
+        let current_tip =
+            StacksChainState::get_parent_index_block(current_consensus_hash, current_block);
+        self.with_read_only_clarity_tx(&NULL_BURN_STATE_DB, &current_tip, |conn| {
+            conn.with_analysis_db_readonly(|db| {
+                let contract_analysis = match run_analysis(
+                    &contract_identifier,
+                    &mut contract_ast.expressions,
+                    db,
+                    false,
+                    LimitedCostTracker::new_max_limit(),
+                    StacksEpochId::latest(),
+                ) {
+                    Ok(analysis) => analysis,
+                    Err(_) => {
+                        return Ok(());
+                    }
+                };
+                check_strict(&contract_analysis, profile)
+                    .map(|_warnings| ())
+                    .map_err(MemPoolRejection::StrictAnalysisFailure)
+            })
+        })
+    }
+
     /// Given an outstanding clarity connection, can we append the tx to the chain state?
     /// Used when mining transactions.
     pub fn can_include_tx<T: ClarityConnection>(
@@ -4619,7 +5694,7 @@ impl StacksChainState {
         // 3: it must pay a tx fee
         let fee = tx.get_fee_rate();
 
-        if fee < MINIMUM_TX_FEE || fee / tx_size < MINIMUM_TX_FEE_RATE_PER_BYTE {
+        if fee < MINIMUM_TX_FEE || fee_rate_per_byte(fee, tx_size) < MINIMUM_TX_FEE_RATE_PER_BYTE {
             return Err(MemPoolRejection::FeeTooLow(
                 fee,
                 cmp::max(MINIMUM_TX_FEE, tx_size * MINIMUM_TX_FEE_RATE_PER_BYTE),
@@ -4768,10 +5843,18 @@ impl StacksChainState {
                 if microblock_header_1.sequence != microblock_header_2.sequence
                     || microblock_header_1.prev_block != microblock_header_2.prev_block
                     || microblock_header_1.version != microblock_header_2.version
+                    || microblock_header_1.signature_digest()
+                        == microblock_header_2.signature_digest()
                 {
                     return Err(MemPoolRejection::PoisonMicroblocksDoNotConflict);
                 }
 
+                if !microblock_header_1.signature.is_low_s()
+                    || !microblock_header_2.signature.is_low_s()
+                {
+                    return Err(MemPoolRejection::InvalidMicroblocks);
+                }
+
                 let microblock_pkh_1 = microblock_header_1
                     .check_recover_pubkey()
                     .map_err(|_e| MemPoolRejection::InvalidMicroblocks)?;
@@ -5535,6 +6618,81 @@ pub mod test {
         assert_block_stored_not_staging(&mut chainstate, &ConsensusHash([2u8; 20]), &block);
     }
 
+    #[test]
+    fn stacks_db_prune_block_bodies() {
+        let mut chainstate =
+            instantiate_chainstate(false, 0x80000000, "stacks_db_prune_block_bodies");
+        let privk = StacksPrivateKey::from_hex(
+            "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
+        )
+        .unwrap();
+
+        let mut blocks = vec![];
+        for i in 0..5 {
+            let mut block = make_empty_coinbase_block(&privk);
+            block.header.total_work.work = i + 1;
+            let consensus_hash = ConsensusHash([(i + 2) as u8; 20]);
+
+            store_staging_block(
+                &mut chainstate,
+                &consensus_hash,
+                &block,
+                &ConsensusHash([1u8; 20]),
+                1,
+                2,
+            );
+            blocks.push((consensus_hash, block));
+        }
+
+        // only processed blocks get pruned
+        for (consensus_hash, block) in blocks[0..4].iter() {
+            set_block_processed(&mut chainstate, consensus_hash, &block.block_hash(), true);
+        }
+
+        // nothing is buried deeply enough yet
+        assert_eq!(chainstate.prune_block_bodies(4).unwrap(), 0);
+
+        // blocks at heights 1 and 2 are buried under 2 blocks
+        assert_eq!(chainstate.prune_block_bodies(2).unwrap(), 2);
+        for (i, (consensus_hash, block)) in blocks.iter().enumerate() {
+            let index_block_hash =
+                StacksBlockHeader::make_index_block_hash(consensus_hash, &block.block_hash());
+
+            // still counts as stored, so it won't be downloaded again
+            assert!(StacksChainState::has_block_indexed(
+                &chainstate.blocks_path,
+                &index_block_hash
+            )
+            .unwrap());
+
+            let loaded = StacksChainState::load_block(
+                &chainstate.blocks_path,
+                consensus_hash,
+                &block.block_hash(),
+            )
+            .unwrap();
+            if i < 2 {
+                assert!(loaded.is_none());
+            } else {
+                assert_eq!(loaded.unwrap(), *block);
+            }
+        }
+
+        // idempotent
+        assert_eq!(chainstate.prune_block_bodies(2).unwrap(), 0);
+
+        // the unprocessed block is never pruned
+        assert_eq!(chainstate.prune_block_bodies(0).unwrap(), 2);
+        assert!(StacksChainState::load_staging_block_data(
+            &chainstate.blocks_db,
+            &chainstate.blocks_path,
+            &blocks[4].0,
+            &blocks[4].1.block_hash()
+        )
+        .unwrap()
+        .is_some());
+    }
+
     #[test]
     fn stacks_db_staging_block_load_store_reject() {
         let mut chainstate = instantiate_chainstate(
@@ -7599,6 +8757,19 @@ pub mod test {
         // should decode back into the block
         let staging_block = StacksBlock::consensus_deserialize(&mut &all_block_bytes[..]).unwrap();
         assert_eq!(staging_block, block);
+
+        // stream only a range of it
+        let mut stream = BlockStreamData::new_block(index_block_header.clone()).with_range(10, 50);
+        let mut range_bytes = vec![];
+        loop {
+            let mut next_bytes =
+                stream_chunk_to_vec(&chainstate.blocks_path, &mut stream, 16).unwrap();
+            if next_bytes.len() == 0 {
+                break;
+            }
+            range_bytes.append(&mut next_bytes);
+        }
+        assert_eq!(range_bytes, all_block_bytes[10..50].to_vec());
     }
 
     #[test]
@@ -8127,6 +9298,7 @@ pub mod test {
                         vrf_proof,
                         Hash160([tenure_id as u8; 20]),
                         &coinbase_tx,
+                        &vec![],
                         ExecutionCost::max_value(),
                     )
                     .unwrap();
@@ -8166,6 +9338,33 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn mempool_rejection_bad_function_argument_json() {
+        let txid = Txid([0x11; 32]);
+
+        let e =
+            MemPoolRejection::BadFunctionArgument(CheckErrors::IncorrectArgumentCount(1, 2).into());
+        let json = e.into_json(&txid);
+        assert_eq!(json["reason"], "BadFunctionArgument");
+        assert_eq!(json["reason_data"]["expected_args"], 1);
+        assert_eq!(json["reason_data"]["actual_args"], 2);
+
+        let e = MemPoolRejection::BadFunctionArgument(
+            CheckErrors::TypeError(TypeSignature::UIntType, TypeSignature::IntType).into(),
+        );
+        let json = e.into_json(&txid);
+        assert_eq!(json["reason"], "BadFunctionArgument");
+        assert_eq!(json["reason_data"]["expected_type"], "uint");
+        assert_eq!(json["reason_data"]["actual_type"], "int");
+
+        let e = MemPoolRejection::BadFunctionArgument(
+            CheckErrors::NoSuchContract("foo".to_string()).into(),
+        );
+        let json = e.into_json(&txid);
+        assert!(json["reason_data"]["message"].is_string());
+        assert!(json["reason_data"].get("expected_args").is_none());
+    }
+
     // TODO: test multiple anchored blocks confirming the same microblock stream (in the same
     // place, and different places, with/without orphans)
     // TODO: process_next_staging_block
@@ -31,6 +31,7 @@ use chainstate::stacks::*;
 use std::path::{Path, PathBuf};
 use vm::costs::ExecutionCost;
 
+use util::db::u64_to_sql;
 use util::db::Error as db_error;
 use util::db::{
     query_count, query_row, query_row_columns, query_row_panic, query_rows, DBConn, FromColumn,
@@ -283,19 +284,20 @@ impl StacksChainState {
     }
 
     /// Get an ancestor block header
-    pub fn get_tip_ancestor<'a>(
-        tx: &mut StacksDBTx<'a>,
+    pub fn get_tip_ancestor<D: StacksDBIndexed>(
+        db: &mut D,
         tip: &StacksHeaderInfo,
         height: u64,
     ) -> Result<Option<StacksHeaderInfo>, Error> {
         assert!(tip.block_height >= height);
-        match tx
+        match db
             .get_ancestor_block_hash(height, &tip.index_block_hash())
             .map_err(Error::DBError)?
         {
-            Some(bhh) => {
-                StacksChainState::get_stacks_block_header_info_by_index_block_hash(tx, &bhh)
-            }
+            Some(bhh) => StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+                db.sqlite(),
+                &bhh,
+            ),
             None => Ok(None),
         }
     }
@@ -337,4 +339,15 @@ impl StacksChainState {
         let mut rows = query_row_columns::<StacksBlockId, _>(conn, &sql, args, "parent_block_id")?;
         Ok(rows.pop())
     }
+    /// Get the headers of all processed blocks, in every fork, whose heights are between
+    /// `start_height` and `end_height` inclusive, lowest first
+    pub fn get_stacks_block_headers_in_range(
+        conn: &Connection,
+        start_height: u64,
+        end_height: u64,
+    ) -> Result<Vec<StacksHeaderInfo>, Error> {
+        let sql = "SELECT * FROM block_headers WHERE block_height >= ?1 AND block_height <= ?2 ORDER BY block_height ASC, index_block_hash ASC";
+        let args: &[&dyn ToSql] = &[&u64_to_sql(start_height)?, &u64_to_sql(end_height)?];
+        query_rows(conn, sql, args).map_err(Error::DBError)
+    }
 }
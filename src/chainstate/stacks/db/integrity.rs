@@ -0,0 +1,355 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Integrity checks over a chainstate's databases.
+//!
+//! Processing a Stacks block commits to three databases, one after the other:  the Clarity MARF
+//! and its side store, then the headers DB, then the staging blocks DB.  If the node stops in
+//! between, the block is left half-processed, and the node fails when it tries to process it
+//! again.  The check here finds such blocks and rolls back whatever was committed for them, so
+//! they get processed again from scratch.  It also finds damage that can't be rolled back, so
+//! the node can refuse to start with a clear message instead of panicking later on.
+
+use std::fs;
+use std::path::PathBuf;
+
+use rusqlite::types::ToSql;
+use rusqlite::Connection;
+use rusqlite::OpenFlags;
+
+use chainstate::stacks::db::*;
+use chainstate::stacks::index::marf::MARF;
+use chainstate::stacks::index::trie_sql;
+use chainstate::stacks::Error;
+use chainstate::stacks::*;
+
+use util::db::Error as db_error;
+use util::db::{
+    query_count, query_row, query_row_columns, sqlite_quick_check, tx_begin_immediate,
+    tx_busy_handler, u64_to_sql, DBConn, IntegrityReport,
+};
+
+use core::FIRST_BURNCHAIN_CONSENSUS_HASH;
+use core::FIRST_STACKS_BLOCK_HASH;
+
+/// Open an existing SQLite DB for the integrity check
+fn open_existing_db(path: &str) -> Result<DBConn, Error> {
+    let mut conn = DBConn::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)
+        .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+    conn.busy_handler(Some(tx_busy_handler))
+        .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+    Ok(conn)
+}
+
+impl StacksChainState {
+    /// Check the chainstate of the given chain under `path_str` for damage left by an unclean
+    /// shutdown:  SQLite-level corruption, MARF tries among the `max_blocks` most recent ones
+    /// that can't be read in full, stale MARF extension locks, and blocks that were left
+    /// half-processed.
+    /// If `repair` is true, stale locks are cleared, and half-processed blocks are rolled back so
+    /// that they will be processed again.  Corruption and damaged tries can't be repaired in
+    /// place; the chainstate has to be restored from a snapshot, or synced again.
+    /// The chainstate must not be open anywhere else.
+    pub fn check_integrity(
+        mainnet: bool,
+        chain_id: u32,
+        path_str: &str,
+        max_blocks: u32,
+        repair: bool,
+    ) -> Result<IntegrityReport, Error> {
+        let mut report = IntegrityReport::default();
+
+        let mut chain_path = PathBuf::from(path_str);
+        chain_path.push(StacksChainState::chain_dir_name(mainnet, chain_id));
+        if fs::metadata(&chain_path).is_err() {
+            // nothing to check
+            return Ok(report);
+        }
+
+        let headers_index_path = chain_path.join("vm").join("index");
+        let clarity_root =
+            StacksChainState::get_clarity_state_index_root(mainnet, chain_id, path_str);
+        let clarity_marf_path = clarity_root.join("marf");
+        let clarity_data_path = clarity_root.join("data.sqlite");
+        let blocks_db_path = chain_path.join("blocks").join("staging.db");
+
+        let mut paths = vec![];
+        for path in [
+            &headers_index_path,
+            &clarity_marf_path,
+            &clarity_data_path,
+            &blocks_db_path,
+        ]
+        .iter()
+        {
+            if fs::metadata(path).is_err() {
+                report
+                    .unrepairable
+                    .push(format!("Chainstate DB {} is missing", path.display()));
+                continue;
+            }
+            let path_str = path
+                .to_str()
+                .ok_or_else(|| Error::DBError(db_error::ParseError))?
+                .to_string();
+            paths.push(path_str);
+        }
+        if report.unrepairable.len() > 0 {
+            return Ok(report);
+        }
+
+        let (headers_index_path, clarity_marf_path, clarity_data_path, blocks_db_path) =
+            (&paths[0], &paths[1], &paths[2], &paths[3]);
+
+        let mut headers_conn = open_existing_db(headers_index_path)?;
+        let mut clarity_conn = open_existing_db(clarity_marf_path)?;
+        let mut clarity_data_conn = open_existing_db(clarity_data_path)?;
+        let blocks_conn = open_existing_db(blocks_db_path)?;
+
+        for (path, conn) in [
+            (headers_index_path, &headers_conn),
+            (clarity_marf_path, &clarity_conn),
+            (clarity_data_path, &clarity_data_conn),
+            (blocks_db_path, &blocks_conn),
+        ]
+        .iter()
+        {
+            for msg in sqlite_quick_check(conn)?.into_iter() {
+                report
+                    .unrepairable
+                    .push(format!("Chainstate DB {} is corrupt: {}", path, msg));
+            }
+        }
+        if report.unrepairable.len() > 0 {
+            return Ok(report);
+        }
+
+        for (name, path, conn) in [
+            ("headers", headers_index_path, &headers_conn),
+            ("Clarity", clarity_marf_path, &clarity_conn),
+        ]
+        .iter()
+        {
+            let num_locks = trie_sql::count_locks(conn)?;
+            if num_locks > 0 {
+                report.problems.push(format!(
+                    "Chainstate {} MARF has {} stale extension lock(s)",
+                    name, num_locks
+                ));
+                if repair {
+                    trie_sql::clear_lock_data(conn)?;
+                    report.repairs.push(format!(
+                        "Cleared the chainstate {} MARF's extension locks",
+                        name
+                    ));
+                }
+            }
+
+            let mut marf = MARF::<StacksBlockId>::from_path(path)?;
+            for (block_id, reason) in marf.check_tries(max_blocks)?.into_iter() {
+                report.unrepairable.push(format!(
+                    "Stacks block {} has a damaged {} MARF trie: {}",
+                    &block_id, name, &reason
+                ));
+            }
+        }
+        if report.unrepairable.len() > 0 {
+            return Ok(report);
+        }
+
+        let genesis_block_id = StacksBlockHeader::make_index_block_hash(
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+        );
+
+        // blocks whose Clarity state was committed, but whose header wasn't
+        let mut half_processed = vec![];
+        let clarity_block_ids: Vec<(u32, StacksBlockId)> =
+            trie_sql::get_latest_confirmed_block_hashes(&clarity_conn, max_blocks)?;
+        for (_, block_id) in clarity_block_ids.into_iter() {
+            if block_id == StacksBlockId::sentinel() {
+                continue;
+            }
+            if StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+                &headers_conn,
+                &block_id,
+            )?
+            .is_some()
+            {
+                continue;
+            }
+            if block_id == genesis_block_id {
+                report.unrepairable.push(
+                    "The boot code was not completely installed; the chainstate has to be created again"
+                        .to_string(),
+                );
+                return Ok(report);
+            }
+            report.problems.push(format!(
+                "Stacks block {} has Clarity state but no header",
+                &block_id
+            ));
+            half_processed.push(block_id);
+        }
+
+        // blocks whose header was committed, but which weren't marked as processed
+        let recent_headers_sql =
+            "SELECT index_block_hash FROM block_headers ORDER BY block_height DESC LIMIT ?1"
+                .to_string();
+        let recent_block_ids: Vec<StacksBlockId> = query_row_columns(
+            &headers_conn,
+            &recent_headers_sql,
+            &[&u64_to_sql(max_blocks as u64)?],
+            "index_block_hash",
+        )?;
+        for block_id in recent_block_ids.into_iter() {
+            let processed: Option<i64> = query_row(
+                &blocks_conn,
+                "SELECT processed FROM staging_blocks WHERE index_block_hash = ?1 AND orphaned = 0",
+                &[&block_id],
+            )?;
+            if processed != Some(0) {
+                continue;
+            }
+            let num_children = query_count(
+                &headers_conn,
+                &"SELECT COUNT(*) FROM block_headers WHERE parent_block_id = ?1".to_string(),
+                &[&block_id],
+            )?;
+            if num_children > 0 {
+                report.unrepairable.push(format!(
+                    "Stacks block {} is not marked as processed, but has processed children",
+                    &block_id
+                ));
+                continue;
+            }
+            report.problems.push(format!(
+                "Stacks block {} has a header but is not marked as processed",
+                &block_id
+            ));
+            half_processed.push(block_id);
+        }
+
+        if !repair {
+            return Ok(report);
+        }
+
+        for block_id in half_processed.into_iter() {
+            StacksChainState::rollback_half_processed_block(
+                &mut headers_conn,
+                &mut clarity_conn,
+                &mut clarity_data_conn,
+                &block_id,
+            )?;
+            report.repairs.push(format!(
+                "Rolled back Stacks block {}, so it will be processed again",
+                &block_id
+            ));
+        }
+
+        Ok(report)
+    }
+
+    /// Remove everything that processing a block committed to the headers DB and the Clarity
+    /// state.  The block must not have any processed children.
+    /// The headers are rolled back first, so if this is interrupted, the block is still found
+    /// by the next check.
+    fn rollback_half_processed_block(
+        headers_conn: &mut Connection,
+        clarity_conn: &mut Connection,
+        clarity_data_conn: &mut Connection,
+        block_id: &StacksBlockId,
+    ) -> Result<(), Error> {
+        warn!("Roll back half-processed Stacks block {}", block_id);
+
+        let header_info_opt = StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            headers_conn,
+            block_id,
+        )?;
+        let parent_block_id_opt = StacksChainState::get_parent_block_id(headers_conn, block_id)?;
+
+        {
+            let tx = tx_begin_immediate(headers_conn)?;
+            if let Some(header_info) = header_info_opt {
+                tx.execute(
+                    "DELETE FROM user_supporters WHERE block_hash = ?1 AND consensus_hash = ?2",
+                    &[
+                        &header_info.anchored_header.block_hash() as &dyn ToSql,
+                        &header_info.consensus_hash,
+                    ],
+                )?;
+            }
+            for table in [
+                "block_headers",
+                "payments",
+                "transaction_receipts",
+                "fork_tips",
+                #[cfg(feature = "tx_log")]
+                "transactions",
+            ]
+            .iter()
+            {
+                tx.execute(
+                    &format!("DELETE FROM {} WHERE index_block_hash = ?1", table),
+                    &[block_id],
+                )?;
+            }
+            trie_sql::drop_confirmed_trie(&tx, block_id)?;
+
+            // the parent is a fork tip again, unless it has other children
+            if let Some(parent_block_id) = parent_block_id_opt {
+                let num_siblings = query_count(
+                    &tx,
+                    &"SELECT COUNT(*) FROM block_headers WHERE parent_block_id = ?1".to_string(),
+                    &[&parent_block_id],
+                )?;
+                let parent_info_opt =
+                    StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+                        &tx,
+                        &parent_block_id,
+                    )?;
+                if let (0, Some(parent_info)) = (num_siblings, parent_info_opt) {
+                    let args: &[&dyn ToSql] = &[
+                        &parent_block_id,
+                        &parent_info.consensus_hash,
+                        &parent_info.anchored_header.block_hash(),
+                        &u64_to_sql(parent_info.block_height)?,
+                        &u64_to_sql(parent_info.anchored_header.total_work.burn)?,
+                    ];
+                    tx.execute("INSERT OR REPLACE INTO fork_tips (index_block_hash, consensus_hash, block_hash, block_height, total_burn) VALUES (?1, ?2, ?3, ?4, ?5)", args)?;
+                }
+            }
+            tx.commit()?;
+        }
+
+        {
+            let tx = tx_begin_immediate(clarity_data_conn)?;
+            tx.execute(
+                "DELETE FROM metadata_table WHERE blockhash = ?1",
+                &[block_id],
+            )?;
+            tx.commit()?;
+        }
+
+        {
+            let tx = tx_begin_immediate(clarity_conn)?;
+            trie_sql::drop_confirmed_trie(&tx, block_id)?;
+            tx.commit()?;
+        }
+
+        Ok(())
+    }
+}
@@ -18,6 +18,8 @@ pub mod accounts;
 pub mod blocks;
 pub mod contracts;
 pub mod headers;
+pub mod integrity;
+pub mod reader;
 pub mod transactions;
 pub mod unconfirmed;
 
@@ -28,6 +30,7 @@ use rusqlite::Row;
 use rusqlite::Transaction;
 use rusqlite::NO_PARAMS;
 
+use std::cmp;
 use std::fmt;
 use std::fs;
 use std::io;
@@ -36,6 +39,7 @@ use std::io::prelude::*;
 use core::*;
 
 use burnchains::Address;
+use burnchains::Txid;
 
 use chainstate::burn::db::sortdb::{SortitionDB, SortitionDBConn};
 use chainstate::burn::ConsensusHash;
@@ -58,8 +62,9 @@ use std::path::{Path, PathBuf};
 
 use util::db::Error as db_error;
 use util::db::{
-    db_mkdirs, query_count, query_row, tx_begin_immediate, tx_busy_handler, DBConn, DBTx,
-    FromColumn, FromRow, IndexDBTx,
+    db_mkdirs, get_ancestor_block_hash, get_ancestor_block_height, query_count, query_row,
+    query_rows, tx_begin_immediate, tx_busy_handler, u64_to_sql, DBConn, DBTx, FromColumn, FromRow,
+    IndexDBConn, IndexDBTx,
 };
 
 use util::hash::to_hex;
@@ -148,6 +153,7 @@ pub struct StacksHeaderInfo {
 pub struct MinerRewardInfo {
     pub from_block_consensus_hash: ConsensusHash,
     pub from_stacks_block_hash: BlockHeaderHash,
+    pub from_stacks_block_height: u64,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -209,6 +215,47 @@ impl FromRow<DBConfig> for DBConfig {
     }
 }
 
+/// A stored transaction receipt, and the block it was mined in
+struct TransactionReceiptRow {
+    index_block_hash: StacksBlockId,
+    receipt: TransactionReceiptData,
+}
+
+impl FromRow<TransactionReceiptRow> for TransactionReceiptRow {
+    fn from_row<'a>(row: &'a Row) -> Result<TransactionReceiptRow, db_error> {
+        let index_block_hash = StacksBlockId::from_column(row, "index_block_hash")?;
+        let receipt_json: String = row.get("receipt");
+        let receipt = serde_json::from_str(&receipt_json).map_err(|_| db_error::ParseError)?;
+        Ok(TransactionReceiptRow {
+            index_block_hash,
+            receipt,
+        })
+    }
+}
+
+/// The tip of a Stacks fork: a processed block that no processed block builds on (yet)
+#[derive(Debug, Clone, PartialEq)]
+pub struct StacksForkTip {
+    pub index_block_hash: StacksBlockId,
+    pub consensus_hash: ConsensusHash,
+    pub block_hash: BlockHeaderHash,
+    pub block_height: u64,
+    /// total burn of all the blocks in this fork, from genesis to this tip
+    pub total_burn: u64,
+}
+
+impl FromRow<StacksForkTip> for StacksForkTip {
+    fn from_row<'a>(row: &'a Row) -> Result<StacksForkTip, db_error> {
+        Ok(StacksForkTip {
+            index_block_hash: StacksBlockId::from_column(row, "index_block_hash")?,
+            consensus_hash: ConsensusHash::from_column(row, "consensus_hash")?,
+            block_hash: BlockHeaderHash::from_column(row, "block_hash")?,
+            block_height: u64::from_column(row, "block_height")?,
+            total_burn: u64::from_column(row, "total_burn")?,
+        })
+    }
+}
+
 impl FromRow<StacksHeaderInfo> for StacksHeaderInfo {
     fn from_row<'a>(row: &'a Row) -> Result<StacksHeaderInfo, db_error> {
         let block_height = u64::from_column(row, "block_height")?;
@@ -242,6 +289,46 @@ impl FromRow<StacksHeaderInfo> for StacksHeaderInfo {
 }
 
 pub type StacksDBTx<'a> = IndexDBTx<'a, (), StacksBlockId>;
+pub type StacksDBConn<'a> = IndexDBConn<'a, (), StacksBlockId>;
+
+/// A handle on the (indexed) stacks chainstate DB that can look up ancestor blocks -- either a
+/// read-only connection, or a transaction
+pub trait StacksDBIndexed {
+    fn get_ancestor_block_hash(
+        &mut self,
+        block_height: u64,
+        tip_index_hash: &StacksBlockId,
+    ) -> Result<Option<StacksBlockId>, db_error>;
+    fn sqlite(&self) -> &DBConn;
+}
+
+impl<'a> StacksDBIndexed for StacksDBConn<'a> {
+    fn get_ancestor_block_hash(
+        &mut self,
+        block_height: u64,
+        tip_index_hash: &StacksBlockId,
+    ) -> Result<Option<StacksBlockId>, db_error> {
+        IndexDBConn::get_ancestor_block_hash(self, block_height, tip_index_hash)
+    }
+
+    fn sqlite(&self) -> &DBConn {
+        self.conn()
+    }
+}
+
+impl<'a> StacksDBIndexed for StacksDBTx<'a> {
+    fn get_ancestor_block_hash(
+        &mut self,
+        block_height: u64,
+        tip_index_hash: &StacksBlockId,
+    ) -> Result<Option<StacksBlockId>, db_error> {
+        IndexDBTx::get_ancestor_block_hash(self, block_height, tip_index_hash)
+    }
+
+    fn sqlite(&self) -> &DBConn {
+        self.tx()
+    }
+}
 
 pub struct BlocksDBTx<'a> {
     pub tx: DBTx<'a>,
@@ -305,6 +392,15 @@ impl<'a> ClarityTx<'a> {
         self.block.cost_so_far()
     }
 
+    pub fn get_epoch(&self) -> StacksEpochId {
+        self.block.get_epoch()
+    }
+
+    #[cfg(test)]
+    pub fn set_epoch(&mut self, epoch: StacksEpochId) {
+        self.block.set_epoch(epoch);
+    }
+
     #[cfg(test)]
     pub fn commit_block(self) -> () {
         self.block.commit_block();
@@ -387,6 +483,58 @@ impl<'a> ChainstateTx<'a> {
         _events: &[StacksTransactionReceipt],
     ) {
     }
+
+    /// Record the receipts of the transactions in a newly-processed block, so they can be
+    /// looked up by txid later.
+    pub fn store_transaction_receipts(
+        &self,
+        block_id: &StacksBlockId,
+        block_height: u64,
+        receipts: &[StacksTransactionReceipt],
+    ) -> Result<(), Error> {
+        let insert = "INSERT OR REPLACE INTO transaction_receipts (txid, index_block_hash, block_height, receipt) VALUES (?1, ?2, ?3, ?4)";
+        for (tx_index, receipt) in receipts.iter().enumerate() {
+            let receipt_data = TransactionReceiptData::from_receipt(
+                receipt,
+                block_id,
+                block_height,
+                tx_index as u32,
+            );
+            let receipt_json = serde_json::to_string(&receipt_data)
+                .expect("FATAL: failed to serialize transaction receipt");
+            let params: &[&dyn ToSql] = &[
+                &receipt.transaction.txid(),
+                block_id,
+                &u64_to_sql(block_height)?,
+                &receipt_json,
+            ];
+            self.headers_tx.tx().execute(insert, params)?;
+        }
+        Ok(())
+    }
+
+    /// Record that a newly-processed block is a fork tip, and that its parent no longer is.
+    pub fn update_fork_tips(
+        &self,
+        parent_block_id: &StacksBlockId,
+        new_tip: &StacksHeaderInfo,
+    ) -> Result<(), Error> {
+        self.headers_tx.tx().execute(
+            "DELETE FROM fork_tips WHERE index_block_hash = ?1",
+            &[parent_block_id],
+        )?;
+
+        let insert = "INSERT OR REPLACE INTO fork_tips (index_block_hash, consensus_hash, block_hash, block_height, total_burn) VALUES (?1, ?2, ?3, ?4, ?5)";
+        let params: &[&dyn ToSql] = &[
+            &new_tip.index_block_hash(),
+            &new_tip.consensus_hash,
+            &new_tip.anchored_header.block_hash(),
+            &u64_to_sql(new_tip.block_height)?,
+            &u64_to_sql(new_tip.anchored_header.total_work.burn)?,
+        ];
+        self.headers_tx.tx().execute(insert, params)?;
+        Ok(())
+    }
 }
 
 /// Opaque structure for streaming block and microblock data from disk
@@ -396,6 +544,7 @@ pub struct BlockStreamData {
     rowid: Option<i64>,        // used when reading a blob out of staging
     offset: u64, // offset into whatever is being read (the blob, or the file in the chunk store)
     total_bytes: u64, // total number of bytes read.
+    end: Option<u64>, // offset to stop reading at, if only a range of the data is streamed
 
     // used only for microblocks
     is_microblock: bool,
@@ -403,6 +552,32 @@ pub struct BlockStreamData {
     in_staging: bool,
 }
 
+/// Receipts of processed transactions.  Chainstate DBs created before this table existed get it
+/// when they are next opened, and only hold receipts for blocks processed since then.
+const TRANSACTION_RECEIPTS_SQL: &'static str = r#"
+    CREATE TABLE IF NOT EXISTS transaction_receipts(
+        txid TEXT NOT NULL,
+        index_block_hash TEXT NOT NULL,     -- a transaction can be mined in more than one fork
+        block_height INTEGER NOT NULL,
+        receipt TEXT NOT NULL,              -- JSON-encoded TransactionReceiptData
+        PRIMARY KEY(txid,index_block_hash)
+    );
+    CREATE INDEX IF NOT EXISTS transaction_receipts_block_index ON transaction_receipts(index_block_hash);
+    "#;
+
+/// Tips of every Stacks fork this node has processed blocks in.  Chainstate DBs created before
+/// this table existed get it when they are next opened, and only track forks from then on.
+const FORK_TIPS_SQL: &'static str = r#"
+    CREATE TABLE IF NOT EXISTS fork_tips(
+        index_block_hash TEXT PRIMARY KEY,
+        consensus_hash TEXT NOT NULL,
+        block_hash TEXT NOT NULL,
+        block_height INTEGER NOT NULL,
+        total_burn INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS fork_tips_block_height ON fork_tips(block_height);
+    "#;
+
 // TODO: keep track of when microblock equivocations occur (maybe in the MARF?), so that once we
 // process a PoisonMicroblock transaction, no further blocks may build off of any descendent fork.
 const STACKS_CHAIN_STATE_SQL: &'static [&'static str] = &[
@@ -517,6 +692,10 @@ pub const MINER_FEE_MINIMUM_BLOCK_USAGE: u64 = 80; // miner must share the first
 
 pub const MINER_FEE_WINDOW: u64 = 24; // number of blocks (B) used to smooth over the fraction of tx fees they share from anchored blocks
 
+pub const MINER_FEE_STREAMED_PRODUCER_PERCENT: u128 = 40; // miner who produced a microblock stream gets P% of its tx fees, and the miner who confirmed it gets the rest
+
+pub const POISON_MICROBLOCK_COMMISSION_FRACTION: u128 = 5; // reporter of an equivocating miner gets F% of the miner's forfeited reward
+
 impl StacksChainState {
     fn instantiate_headers_db(
         mainnet: bool,
@@ -532,6 +711,8 @@ impl StacksChainState {
             for cmd in STACKS_CHAIN_STATE_SQL {
                 tx.execute(cmd, NO_PARAMS)?;
             }
+            tx.execute_batch(TRANSACTION_RECEIPTS_SQL)?;
+            tx.execute_batch(FORK_TIPS_SQL)?;
 
             tx.execute(
                 "INSERT INTO db_config (version,mainnet,chain_id) VALUES (?1,?2,?3)",
@@ -592,6 +773,9 @@ impl StacksChainState {
                 return Err(Error::InvalidChainstateDB);
             }
 
+            marf.sqlite_conn().execute_batch(TRANSACTION_RECEIPTS_SQL)?;
+            marf.sqlite_conn().execute_batch(FORK_TIPS_SQL)?;
+
             Ok(marf)
         }
     }
@@ -838,6 +1022,24 @@ impl StacksChainState {
         StacksChainState::open_and_exec(mainnet, chain_id, path_str, None, |_| {}, block_limit)
     }
 
+    fn chain_dir_name(mainnet: bool, chain_id: u32) -> String {
+        if mainnet {
+            format!("chain-{}-mainnet", &to_hex(&chain_id.to_le_bytes()))
+        } else {
+            format!("chain-{}-testnet", &to_hex(&chain_id.to_le_bytes()))
+        }
+    }
+
+    /// The directory under the chainstate path `path_str` holding the Clarity MARF and side store
+    /// of the given chain, whether or not it exists yet
+    pub fn get_clarity_state_index_root(mainnet: bool, chain_id: u32, path_str: &str) -> PathBuf {
+        let mut path = PathBuf::from(path_str);
+        path.push(StacksChainState::chain_dir_name(mainnet, chain_id));
+        path.push("vm");
+        path.push("clarity");
+        path
+    }
+
     pub fn open_and_exec<F>(
         mainnet: bool,
         chain_id: u32,
@@ -851,13 +1053,7 @@ impl StacksChainState {
     {
         let mut path = PathBuf::from(path_str);
 
-        let chain_id_str = if mainnet {
-            format!("chain-{}-mainnet", &to_hex(&chain_id.to_le_bytes()))
-        } else {
-            format!("chain-{}-testnet", &to_hex(&chain_id.to_le_bytes()))
-        };
-
-        path.push(chain_id_str);
+        path.push(StacksChainState::chain_dir_name(mainnet, chain_id));
         StacksChainState::mkdirs(&path)?;
 
         let mut blocks_path = path.clone();
@@ -964,6 +1160,11 @@ impl StacksChainState {
         Ok(StacksDBTx::new(&mut self.headers_state_index, ()))
     }
 
+    /// Open a read-only connection to the (indexed) stacks chainstate DB.
+    pub fn index_conn<'a>(&'a self) -> StacksDBConn<'a> {
+        StacksDBConn::new(&self.headers_state_index, ())
+    }
+
     /// Begin a transaction against our staging block index DB.
     pub fn blocks_tx_begin<'a>(&'a mut self) -> Result<BlocksDBTx<'a>, Error> {
         let tx = tx_begin_immediate(&mut self.blocks_db)?;
@@ -1034,6 +1235,80 @@ impl StacksChainState {
         self.headers_state_index.sqlite_conn()
     }
 
+    /// Get the receipt of a transaction mined in the fork that ends at `tip`, if any.
+    pub fn get_transaction_receipt(
+        &self,
+        tip: &StacksBlockId,
+        txid: &Txid,
+    ) -> Result<Option<TransactionReceiptData>, Error> {
+        let sql = "SELECT * FROM transaction_receipts WHERE txid = ?1";
+        let rows: Vec<TransactionReceiptRow> = query_rows(self.headers_db(), sql, &[txid])?;
+        for row in rows.into_iter() {
+            if get_ancestor_block_height(&self.headers_state_index, &row.index_block_hash, tip)?
+                .is_some()
+            {
+                return Ok(Some(row.receipt));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Get the tips of all known Stacks forks at or above `min_height`, highest first.
+    pub fn get_fork_tips(&self, min_height: u64) -> Result<Vec<StacksForkTip>, Error> {
+        let sql = "SELECT * FROM fork_tips WHERE block_height >= ?1 ORDER BY block_height DESC, total_burn DESC";
+        let rows = query_rows(self.headers_db(), sql, &[&u64_to_sql(min_height)?])?;
+        Ok(rows)
+    }
+
+    /// Find the highest block that the forks ending at `tip_a` and `tip_b` have in common.
+    /// Returns its height and index block hash, or None if either tip is unknown.
+    pub fn get_fork_point(
+        &self,
+        tip_a: &StacksForkTip,
+        tip_b: &StacksForkTip,
+    ) -> Result<Option<(u64, StacksBlockId)>, Error> {
+        let ancestor_at = |height: u64, tip: &StacksForkTip| {
+            get_ancestor_block_hash(&self.headers_state_index, height, &tip.index_block_hash)
+        };
+
+        // forks share a prefix, so binary-search for the last height at which they agree
+        let mut lo = 0;
+        let mut hi = cmp::min(tip_a.block_height, tip_b.block_height);
+        let mut fork_point = match (ancestor_at(lo, tip_a)?, ancestor_at(lo, tip_b)?) {
+            (Some(a), Some(b)) if a == b => a,
+            _ => return Ok(None),
+        };
+        while lo < hi {
+            let mid = lo + (hi - lo + 1) / 2;
+            match (ancestor_at(mid, tip_a)?, ancestor_at(mid, tip_b)?) {
+                (Some(a), Some(b)) if a == b => {
+                    lo = mid;
+                    fork_point = a;
+                }
+                (Some(_), Some(_)) => {
+                    hi = mid - 1;
+                }
+                _ => return Ok(None),
+            }
+        }
+        Ok(Some((lo, fork_point)))
+    }
+
+    /// Get the receipts of all the transactions processed with the given block, in order.
+    pub fn get_block_transaction_receipts(
+        &self,
+        index_block_hash: &StacksBlockId,
+    ) -> Result<Vec<TransactionReceiptData>, Error> {
+        let sql = "SELECT * FROM transaction_receipts WHERE index_block_hash = ?1";
+        let mut receipts: Vec<TransactionReceiptData> =
+            query_rows::<TransactionReceiptRow, _>(self.headers_db(), sql, &[index_block_hash])?
+                .into_iter()
+                .map(|row| row.receipt)
+                .collect();
+        receipts.sort_by_key(|receipt| receipt.tx_index);
+        Ok(receipts)
+    }
+
     /// Begin processing an epoch's transactions within the context of a chainstate transaction
     pub fn chainstate_block_begin<'a>(
         chainstate_tx: &'a ChainstateTx<'a>,
@@ -0,0 +1,180 @@
+/*
+ copyright: (c) 2013-2020 by Blockstack PBC, a public benefit corporation.
+
+ This file is part of Blockstack.
+
+ Blockstack is free software. You may redistribute or modify
+ it under the terms of the GNU General Public License as published by
+ the Free Software Foundation, either version 3 of the License or
+ (at your option) any later version.
+
+ Blockstack is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY, including without the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU General Public License for more details.
+
+ You should have received a copy of the GNU General Public License
+ along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::path::PathBuf;
+
+use chainstate::stacks::db::StacksChainState;
+use chainstate::stacks::index::marf::MARF;
+use chainstate::stacks::index::storage::TrieFileStorage;
+use chainstate::stacks::Error;
+use chainstate::stacks::*;
+
+use util::db::DBConn;
+use util::db::Error as db_error;
+
+use vm::clarity::{ClarityInstance, ClarityReadOnlyConnection};
+use vm::costs::ExecutionCost;
+use vm::database::marf::MarfedKV;
+use vm::database::BurnStateDB;
+
+/// A read-only view of the chain state, for answering queries (e.g. from the RPC interface)
+/// while another `StacksChainState` on the same path processes blocks.  It opens the Clarity
+/// MARF, the Clarity side-store, and the headers MARF without write access.  All of them are in
+/// WAL mode, so a read sees the state as of the last committed block and never waits for (or
+/// holds up) the writer.
+///
+/// Only confirmed state can be read this way -- the unconfirmed microblock state lives in the
+/// `StacksChainState` that maintains it.
+pub struct ChainstateReader {
+    pub mainnet: bool,
+    pub chain_id: u32,
+    clarity_state: ClarityInstance,
+    headers_state_index: MARF<StacksBlockId>,
+}
+
+impl ChainstateReader {
+    /// Open a reader on the chain state whose Clarity MARF and side-store are in
+    /// `clarity_state_index_root`, and whose headers MARF is at `header_index_path`.
+    pub fn open(
+        mainnet: bool,
+        chain_id: u32,
+        clarity_state_index_root: &str,
+        header_index_path: &str,
+        block_limit: ExecutionCost,
+    ) -> Result<ChainstateReader, Error> {
+        let headers_state_index = TrieFileStorage::open_readonly(header_index_path)
+            .map(MARF::from_storage)
+            .map_err(|e| Error::DBError(db_error::IndexError(e)))?;
+
+        let vm_state = MarfedKV::open_readonly(
+            clarity_state_index_root,
+            Some(&StacksBlockHeader::make_index_block_hash(
+                &MINER_BLOCK_CONSENSUS_HASH,
+                &MINER_BLOCK_HEADER_HASH,
+            )),
+        )
+        .map_err(|e| Error::ClarityError(e.into()))?;
+
+        Ok(ChainstateReader {
+            mainnet,
+            chain_id,
+            clarity_state: ClarityInstance::new(vm_state, block_limit),
+            headers_state_index,
+        })
+    }
+
+    /// Open a reader on the same chain state as `chainstate`
+    pub fn from_chainstate(chainstate: &StacksChainState) -> Result<ChainstateReader, Error> {
+        // the headers MARF is vm/index, next to the Clarity state in vm/clarity
+        let mut header_index_path = PathBuf::from(&chainstate.clarity_state_index_root);
+        header_index_path.pop();
+        header_index_path.push("index");
+        let header_index_path = header_index_path
+            .to_str()
+            .ok_or_else(|| Error::DBError(db_error::ParseError))?
+            .to_string();
+
+        ChainstateReader::open(
+            chainstate.mainnet,
+            chainstate.chain_id,
+            &chainstate.clarity_state_index_root,
+            &header_index_path,
+            chainstate.block_limit.clone(),
+        )
+    }
+
+    pub fn headers_db(&self) -> &DBConn {
+        self.headers_state_index.sqlite_conn()
+    }
+
+    /// Run to_do on the Clarity VM state as of the confirmed block `tip`
+    pub fn with_read_only_clarity_tx<F, R>(
+        &mut self,
+        burn_dbconn: &dyn BurnStateDB,
+        tip: &StacksBlockId,
+        to_do: F,
+    ) -> R
+    where
+        F: FnOnce(&mut ClarityReadOnlyConnection) -> R,
+    {
+        let mut conn = self.clarity_state.read_only_connection(
+            tip,
+            self.headers_state_index.sqlite_conn(),
+            burn_dbconn,
+        );
+        let result = to_do(&mut conn);
+        conn.done();
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chainstate::stacks::boot::*;
+    use chainstate::stacks::db::test::*;
+    use core::*;
+
+    use vm::database::NULL_BURN_STATE_DB;
+    use vm::representations::ContractName;
+    use vm::types::*;
+
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_chainstate_reader_boot_code() {
+        let chainstate = instantiate_chainstate(false, 0x80000000, "chainstate-reader-boot-code");
+        let mut reader = ChainstateReader::from_chainstate(&chainstate).unwrap();
+
+        let boot_tip = StacksBlockHeader::make_index_block_hash(
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+        );
+        let boot_code_address =
+            StacksAddress::from_string(&STACKS_BOOT_CODE_CONTRACT_ADDRESS.to_string()).unwrap();
+
+        for (boot_contract_name, _) in STACKS_BOOT_CODE_TESTNET.iter() {
+            let boot_contract_id = QualifiedContractIdentifier::new(
+                StandardPrincipalData::from(boot_code_address.clone()),
+                ContractName::try_from(boot_contract_name.to_string()).unwrap(),
+            );
+            let contract_res =
+                reader.with_read_only_clarity_tx(&NULL_BURN_STATE_DB, &boot_tip, |conn| {
+                    StacksChainState::get_contract(conn, &boot_contract_id).unwrap()
+                });
+            assert!(contract_res.is_some());
+        }
+
+        // the reader is independent of the chainstate it was made from
+        drop(chainstate);
+        let contract_res =
+            reader.with_read_only_clarity_tx(&NULL_BURN_STATE_DB, &boot_tip, |conn| {
+                StacksChainState::get_contract(
+                    conn,
+                    &QualifiedContractIdentifier::new(
+                        StandardPrincipalData::from(boot_code_address.clone()),
+                        ContractName::try_from("not-a-contract".to_string()).unwrap(),
+                    ),
+                )
+                .unwrap()
+            });
+        assert!(contract_res.is_none());
+    }
+}
@@ -25,6 +25,7 @@ use std::fs;
 use chainstate::stacks::db::*;
 use chainstate::stacks::Error;
 use chainstate::stacks::*;
+use core::StacksEpochId;
 
 use std::path::{Path, PathBuf};
 
@@ -33,7 +34,7 @@ use util::db::{query_count, query_rows, DBConn};
 
 use util::strings::StacksString;
 
-use util::hash::to_hex;
+use util::hash::{to_hex, Hash160};
 
 use chainstate::burn::db::sortdb::*;
 
@@ -47,7 +48,7 @@ use vm::analysis::run_analysis;
 use vm::ast::build_ast;
 use vm::costs::ExecutionCost;
 
-use vm::types::{AssetIdentifier, Value};
+use vm::types::{AssetIdentifier, TupleData, Value};
 
 use vm::clarity::{
     ClarityBlockConnection, ClarityConnection, ClarityInstance, ClarityTransactionConnection,
@@ -163,6 +164,22 @@ impl StacksTransactionReceipt {
         }
     }
 
+    pub fn from_poison_microblock(
+        tx: StacksTransaction,
+        result: Value,
+        cost: ExecutionCost,
+    ) -> StacksTransactionReceipt {
+        StacksTransactionReceipt {
+            transaction: tx,
+            events: vec![],
+            post_condition_aborted: false,
+            result: result,
+            stx_burned: 0,
+            contract_analysis: None,
+            execution_cost: cost,
+        }
+    }
+
     pub fn from_coinbase(tx: StacksTransaction) -> StacksTransactionReceipt {
         StacksTransactionReceipt {
             transaction: tx,
@@ -561,6 +578,20 @@ impl StacksChainState {
         return true;
     }
 
+    /// How many STX a post-condition-aborted transaction burned.  Its state changes were rolled
+    /// back, so from Stacks 2.1 on nothing it burned counts.  Before then, the STX it would have
+    /// burned are still counted against the liquid supply.
+    fn get_condition_aborted_stx_burned(
+        clarity_tx: &ClarityTransactionConnection,
+        assets: &AssetMap,
+    ) -> u128 {
+        if clarity_tx.get_epoch() >= StacksEpochId::Epoch21 {
+            0
+        } else {
+            assets.get_stx_burned_total()
+        }
+    }
+
     /// Process the transaction's payload, and run the post-conditions against the resulting state.
     /// Returns the number of STX burned.
     pub fn process_transaction_payload(
@@ -651,7 +682,7 @@ impl StacksChainState {
                                     tx.clone(),
                                     events,
                                     value.expect("BUG: Post condition contract call must provide would-have-been-returned value"),
-                                    assets.get_stx_burned_total(),
+                                    StacksChainState::get_condition_aborted_stx_burned(clarity_tx, &assets),
                                     total_cost);
                                 return Ok(receipt);
                             },
@@ -781,8 +812,9 @@ impl StacksChainState {
                                 Err(e)
                             },
                             clarity_error::AbortedByCallback(_, assets, events) => {
+                                let burned = StacksChainState::get_condition_aborted_stx_burned(clarity_tx, &assets);
                                 let receipt = StacksTransactionReceipt::from_condition_aborted_smart_contract(
-                                    tx.clone(), events, assets.get_stx_burned_total(), contract_analysis, total_cost);
+                                    tx.clone(), events, burned, contract_analysis, total_cost);
                                 return Ok(receipt);
                             },
                             // runtime errors are okay -- we just have an empty asset map
@@ -815,7 +847,7 @@ impl StacksChainState {
                 );
                 Ok(receipt)
             }
-            TransactionPayload::PoisonMicroblock(ref _mblock_header_1, ref _mblock_header_2) => {
+            TransactionPayload::PoisonMicroblock(ref mblock_header_1, ref mblock_header_2) => {
                 // post-conditions are not allowed for this variant, since they're non-sensical.
                 // Their presence in this variant makes the transaction invalid.
                 if tx.post_conditions.len() > 0 {
@@ -825,8 +857,29 @@ impl StacksChainState {
                     return Err(Error::InvalidStacksTransaction(msg, false));
                 }
 
-                // TODO: actually implement this, but not necessarily for this PR
-                panic!("Not implemented yet");
+                if clarity_tx.get_epoch() < StacksEpochId::Epoch21 {
+                    let msg = format!("Invalid Stacks transaction: PoisonMicroblock transactions are not supported before Stacks 2.1");
+                    warn!("{}", &msg);
+
+                    return Err(Error::InvalidStacksTransaction(msg, false));
+                }
+
+                let cost_before = clarity_tx.cost_so_far();
+                let value = StacksChainState::process_poison_microblock(
+                    clarity_tx,
+                    tx,
+                    mblock_header_1,
+                    mblock_header_2,
+                )?;
+
+                let mut total_cost = clarity_tx.cost_so_far();
+                total_cost
+                    .sub(&cost_before)
+                    .expect("BUG: total block cost decreased");
+
+                let receipt =
+                    StacksTransactionReceipt::from_poison_microblock(tx.clone(), value, total_cost);
+                Ok(receipt)
             }
             TransactionPayload::Coinbase(_) => {
                 // no-op; not handled here
@@ -837,6 +890,161 @@ impl StacksChainState {
         }
     }
 
+    /// Check that two microblock headers prove that a miner equivocated:  they must sign
+    /// different content, claim the same position in the same stream, and be signed by the same
+    /// key with low-S signatures.  Comparing the signed content rather than the block hashes
+    /// keeps anyone from forging a "conflict" by malleating the signature of a single header.
+    /// Returns the hash of the miner's microblock public key.
+    pub fn check_poison_microblock_headers(
+        mblock_header_1: &StacksMicroblockHeader,
+        mblock_header_2: &StacksMicroblockHeader,
+    ) -> Result<Hash160, Error> {
+        if mblock_header_1.signature_digest() == mblock_header_2.signature_digest() {
+            let msg = format!(
+                "Invalid PoisonMicroblock: microblocks {} and {} sign the same header",
+                mblock_header_1.block_hash(),
+                mblock_header_2.block_hash()
+            );
+            warn!("{}", &msg);
+            return Err(Error::InvalidStacksTransaction(msg, false));
+        }
+
+        for mblock_header in [mblock_header_1, mblock_header_2].iter() {
+            if !mblock_header.signature.is_low_s() {
+                let msg = format!(
+                    "Invalid PoisonMicroblock: microblock {} has a high-S signature",
+                    mblock_header.block_hash()
+                );
+                warn!("{}", &msg);
+                return Err(Error::InvalidStacksTransaction(msg, false));
+            }
+        }
+
+        if mblock_header_1.sequence != mblock_header_2.sequence
+            || mblock_header_1.prev_block != mblock_header_2.prev_block
+            || mblock_header_1.version != mblock_header_2.version
+        {
+            let msg = format!(
+                "Invalid PoisonMicroblock: microblocks {} and {} do not conflict",
+                mblock_header_1.block_hash(),
+                mblock_header_2.block_hash()
+            );
+            warn!("{}", &msg);
+            return Err(Error::InvalidStacksTransaction(msg, false));
+        }
+
+        let pubkey_hash_1 = mblock_header_1.check_recover_pubkey().map_err(|_e| {
+            Error::InvalidStacksTransaction(
+                format!(
+                    "Invalid PoisonMicroblock: failed to recover public key of microblock {}",
+                    mblock_header_1.block_hash()
+                ),
+                false,
+            )
+        })?;
+        let pubkey_hash_2 = mblock_header_2.check_recover_pubkey().map_err(|_e| {
+            Error::InvalidStacksTransaction(
+                format!(
+                    "Invalid PoisonMicroblock: failed to recover public key of microblock {}",
+                    mblock_header_2.block_hash()
+                ),
+                false,
+            )
+        })?;
+
+        if pubkey_hash_1 != pubkey_hash_2 {
+            let msg = format!(
+                "Invalid PoisonMicroblock: microblocks {} and {} are signed by different keys",
+                mblock_header_1.block_hash(),
+                mblock_header_2.block_hash()
+            );
+            warn!("{}", &msg);
+            return Err(Error::InvalidStacksTransaction(msg, false));
+        }
+
+        Ok(pubkey_hash_1)
+    }
+
+    /// Process a PoisonMicroblock transaction.  The miner who signed the two conflicting
+    /// microblocks forfeits its reward for the anchored block they build on, and the transaction's
+    /// origin collects a commission when that reward matures (see
+    /// `process_matured_miner_rewards`).  The report must be mined before the reward matures.
+    /// If the miner was already reported, the report with the lowest sequence number stands.
+    /// Returns the standing report as a tuple.
+    fn process_poison_microblock(
+        clarity_tx: &mut ClarityTransactionConnection,
+        tx: &StacksTransaction,
+        mblock_header_1: &StacksMicroblockHeader,
+        mblock_header_2: &StacksMicroblockHeader,
+    ) -> Result<Value, Error> {
+        let pubkey_hash =
+            StacksChainState::check_poison_microblock_headers(mblock_header_1, mblock_header_2)?;
+        let reporter = StandardPrincipalData::from(tx.origin_address());
+        let sequence = mblock_header_1.sequence;
+
+        let (current_height, mblock_pubkey_height_opt, report_opt) = clarity_tx
+            .with_clarity_db(|ref mut db| {
+                let current_height = db.get_current_block_height() as u64;
+                let mblock_pubkey_height_opt = db.get_microblock_pubkey_hash_height(&pubkey_hash);
+                let report_opt = match mblock_pubkey_height_opt {
+                    Some(height) => db.get_microblock_poison_report(height),
+                    None => None,
+                };
+                Ok((current_height, mblock_pubkey_height_opt, report_opt))
+            })
+            .map_err(Error::ClarityError)?;
+
+        let mblock_pubkey_height = match mblock_pubkey_height_opt {
+            Some(height) => height,
+            None => {
+                let msg = format!(
+                    "Invalid PoisonMicroblock: no anchored block in this fork signs microblocks with {}",
+                    &pubkey_hash
+                );
+                warn!("{}", &msg);
+                return Err(Error::InvalidStacksTransaction(msg, false));
+            }
+        };
+
+        if mblock_pubkey_height + MINER_REWARD_MATURITY + MINER_REWARD_WINDOW < current_height {
+            let msg = format!(
+                "Invalid PoisonMicroblock: the reward for the block at height {} has matured",
+                mblock_pubkey_height
+            );
+            warn!("{}", &msg);
+            return Err(Error::InvalidStacksTransaction(msg, false));
+        }
+
+        let (reporter, sequence) = match report_opt {
+            Some(ref report) if report.sequence <= sequence => {
+                debug!(
+                    "Miner of block at height {} was already reported by {} (sequence {})",
+                    mblock_pubkey_height, &report.reporter, report.sequence
+                );
+                (report.reporter.clone(), report.sequence)
+            }
+            _ => {
+                clarity_tx
+                    .with_clarity_db(|ref mut db| {
+                        db.insert_microblock_poison(mblock_pubkey_height, &reporter, sequence);
+                        Ok(())
+                    })
+                    .map_err(Error::ClarityError)?;
+                (reporter, sequence)
+            }
+        };
+
+        let report = TupleData::from_data(vec![
+            (
+                "reporter".into(),
+                Value::Principal(PrincipalData::Standard(reporter)),
+            ),
+            ("sequence".into(), Value::UInt(sequence as u128)),
+        ])
+        .expect("FATAL: failed to construct poison-microblock report tuple");
+        Ok(Value::Tuple(report))
+    }
+
     /// Process a transaction.  Return the fee and the transaction receipt
     pub fn process_transaction(
         clarity_block: &mut ClarityTx,
@@ -895,6 +1103,8 @@ pub mod test {
     use chainstate::stacks::Error;
     use chainstate::stacks::*;
     use chainstate::*;
+    use util::hash::hex_bytes;
+    use util::secp256k1::MessageSignature;
 
     use vm::contracts::Contract;
     use vm::database::NULL_BURN_STATE_DB;
@@ -6674,5 +6884,323 @@ pub mod test {
             assert!(false)
         };
     }
-    // TODO: test poison microblock
+
+    #[test]
+    fn process_post_conditions_stx_burn_aborted() {
+        let contract = "
+        (define-public (burn-stx (amount uint))
+            (stx-burn? amount tx-sender))";
+
+        let privk = StacksPrivateKey::from_hex(
+            "6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001",
+        )
+        .unwrap();
+        let auth = TransactionAuth::from_p2pkh(&privk).unwrap();
+        let addr = auth.origin().address_testnet();
+
+        let balances = vec![(addr.clone(), 1000)];
+
+        let mut chainstate = instantiate_chainstate_with_balances(
+            false,
+            0x80000000,
+            "process-post-conditions-stx-burn-aborted",
+            balances,
+        );
+
+        let mut tx_contract_create = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            auth.clone(),
+            TransactionPayload::new_smart_contract(
+                &"hello-world".to_string(),
+                &contract.to_string(),
+            )
+            .unwrap(),
+        );
+
+        tx_contract_create.chain_id = 0x80000000;
+        tx_contract_create.set_fee_rate(0);
+
+        let mut signer = StacksTransactionSigner::new(&tx_contract_create);
+        signer.sign_origin(&privk).unwrap();
+
+        let signed_contract_tx = signer.get_tx().unwrap();
+
+        let mut signed_burn_txs = vec![];
+        for (nonce, amount) in [(1, 123u128), (2, 123u128), (3, 100u128)].iter() {
+            let mut tx_contract_call = StacksTransaction::new(
+                TransactionVersion::Testnet,
+                auth.clone(),
+                TransactionPayload::new_contract_call(
+                    addr.clone(),
+                    "hello-world",
+                    "burn-stx",
+                    vec![Value::UInt(*amount)],
+                )
+                .unwrap(),
+            );
+
+            tx_contract_call.chain_id = 0x80000000;
+            tx_contract_call.set_fee_rate(0);
+            tx_contract_call.set_origin_nonce(*nonce);
+            tx_contract_call.post_condition_mode = TransactionPostConditionMode::Deny;
+            tx_contract_call.add_post_condition(TransactionPostCondition::STX(
+                PostConditionPrincipal::Origin,
+                FungibleConditionCode::SentLe,
+                100,
+            ));
+
+            let mut signer = StacksTransactionSigner::new(&tx_contract_call);
+            signer.sign_origin(&privk).unwrap();
+            signed_burn_txs.push(signer.get_tx().unwrap());
+        }
+
+        let mut conn = chainstate.block_begin(
+            &NULL_BURN_STATE_DB,
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+            &ConsensusHash([1u8; 20]),
+            &BlockHeaderHash([1u8; 32]),
+        );
+
+        StacksChainState::process_transaction(&mut conn, &signed_contract_tx, false).unwrap();
+
+        // burning more than the post-condition allows is aborted.  Before Stacks 2.1, the STX it
+        // would have burned still count as burned.
+        conn.set_epoch(StacksEpochId::Epoch20);
+        let (_, receipt) =
+            StacksChainState::process_transaction(&mut conn, &signed_burn_txs[0], false).unwrap();
+        assert!(receipt.post_condition_aborted);
+        assert_eq!(receipt.stx_burned, 123);
+
+        let account = StacksChainState::get_account(&mut conn, &addr.to_account_principal());
+        assert_eq!(account.nonce, 2);
+        assert_eq!(account.stx_balance.amount_unlocked, 1000);
+
+        // from Stacks 2.1 on, it burns nothing
+        conn.set_epoch(StacksEpochId::Epoch21);
+        let (_, receipt) =
+            StacksChainState::process_transaction(&mut conn, &signed_burn_txs[1], false).unwrap();
+        assert!(receipt.post_condition_aborted);
+        assert_eq!(receipt.stx_burned, 0);
+
+        let account = StacksChainState::get_account(&mut conn, &addr.to_account_principal());
+        assert_eq!(account.nonce, 3);
+        assert_eq!(account.stx_balance.amount_unlocked, 1000);
+
+        // burning within the post-condition goes through
+        let (_, receipt) =
+            StacksChainState::process_transaction(&mut conn, &signed_burn_txs[2], false).unwrap();
+        assert!(!receipt.post_condition_aborted);
+        assert_eq!(receipt.stx_burned, 100);
+
+        let account = StacksChainState::get_account(&mut conn, &addr.to_account_principal());
+        assert_eq!(account.nonce, 4);
+        assert_eq!(account.stx_balance.amount_unlocked, 900);
+
+        conn.commit_block();
+    }
+
+    fn make_poison_microblock_tx(
+        privk: &StacksPrivateKey,
+        nonce: u64,
+        mblock_header_1: StacksMicroblockHeader,
+        mblock_header_2: StacksMicroblockHeader,
+    ) -> StacksTransaction {
+        let auth = TransactionAuth::from_p2pkh(privk).unwrap();
+        let mut tx_poison = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            auth,
+            TransactionPayload::PoisonMicroblock(mblock_header_1, mblock_header_2),
+        );
+
+        tx_poison.chain_id = 0x80000000;
+        tx_poison.set_fee_rate(0);
+        tx_poison.set_origin_nonce(nonce);
+
+        let mut signer = StacksTransactionSigner::new(&tx_poison);
+        signer.sign_origin(privk).unwrap();
+        signer.get_tx().unwrap()
+    }
+
+    /// The same signature with s negated, i.e. replaced with n - s for the secp256k1 group order
+    /// n.  It still recovers the signer's key.
+    fn malleate_signature(sig: &MessageSignature) -> MessageSignature {
+        let order =
+            hex_bytes("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141").unwrap();
+        let mut malleated = sig.clone();
+        // negating s flips the parity of R
+        malleated.0[0] ^= 1;
+
+        let mut borrow = 0u16;
+        for i in (0..32).rev() {
+            let subtrahend = (sig.0[33 + i] as u16) + borrow;
+            let minuend = order[i] as u16;
+            if minuend >= subtrahend {
+                malleated.0[33 + i] = (minuend - subtrahend) as u8;
+                borrow = 0;
+            } else {
+                malleated.0[33 + i] = (minuend + 256 - subtrahend) as u8;
+                borrow = 1;
+            }
+        }
+        malleated
+    }
+
+    fn make_signed_microblock_header(
+        mblock_privk: &StacksPrivateKey,
+        sequence: u16,
+        tx_merkle_root: Sha512Trunc256Sum,
+    ) -> StacksMicroblockHeader {
+        let mut header =
+            StacksMicroblockHeader::first_unsigned(&BlockHeaderHash([0x11; 32]), &tx_merkle_root);
+        header.sequence = sequence;
+        header.sign(mblock_privk).unwrap();
+        header
+    }
+
+    #[test]
+    fn process_poison_microblock_transaction() {
+        let mut chainstate =
+            instantiate_chainstate(false, 0x80000000, "process-poison-microblock-transaction");
+
+        let privk = StacksPrivateKey::from_hex(
+            "6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001",
+        )
+        .unwrap();
+        let addr = TransactionAuth::from_p2pkh(&privk)
+            .unwrap()
+            .origin()
+            .address_testnet();
+        let privk_2 = StacksPrivateKey::from_hex(
+            "7e3af4db6af6b3c67e2c6c6d7d5983b519f4d9b3a6e00580ae96dcace3bde8bc01",
+        )
+        .unwrap();
+
+        let mblock_privk = StacksPrivateKey::new();
+        let mblock_pubkey_hash =
+            Hash160::from_node_public_key(&StacksPublicKey::from_private(&mblock_privk));
+        let other_mblock_privk = StacksPrivateKey::new();
+
+        let header_1 =
+            make_signed_microblock_header(&mblock_privk, 3, Sha512Trunc256Sum([1u8; 32]));
+        let header_2 =
+            make_signed_microblock_header(&mblock_privk, 3, Sha512Trunc256Sum([2u8; 32]));
+
+        let mut conn = chainstate.block_begin(
+            &NULL_BURN_STATE_DB,
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+            &ConsensusHash([1u8; 20]),
+            &BlockHeaderHash([1u8; 32]),
+        );
+
+        let signed_tx = make_poison_microblock_tx(&privk, 0, header_1.clone(), header_2.clone());
+
+        // before Stacks 2.1, blocks don't record their microblock keys, and poison-microblock
+        // transactions are invalid
+        conn.set_epoch(StacksEpochId::Epoch20);
+        StacksChainState::insert_microblock_pubkey_hash(&mut conn, &mblock_pubkey_hash, 1).unwrap();
+        assert!(StacksChainState::process_transaction(&mut conn, &signed_tx, false).is_err());
+        conn.set_epoch(StacksEpochId::Epoch21);
+
+        // no block in this fork signs microblocks with this key yet
+        assert!(StacksChainState::process_transaction(&mut conn, &signed_tx, false).is_err());
+
+        StacksChainState::insert_microblock_pubkey_hash(&mut conn, &mblock_pubkey_hash, 1).unwrap();
+
+        // a copy of a header with a malleated signature has a different block hash and recovers
+        // the same key, but doesn't conflict with the original
+        let mut malleated_header_1 = header_1.clone();
+        malleated_header_1.signature = malleate_signature(&header_1.signature);
+        assert!(malleated_header_1.block_hash() != header_1.block_hash());
+        assert_eq!(
+            malleated_header_1.check_recover_pubkey().unwrap(),
+            header_1.check_recover_pubkey().unwrap()
+        );
+        let mut malleated_header_2 = header_2.clone();
+        malleated_header_2.signature = malleate_signature(&header_2.signature);
+
+        assert!(StacksChainState::check_poison_microblock_headers(&header_1, &header_2).is_ok());
+        assert!(
+            StacksChainState::check_poison_microblock_headers(&header_1, &malleated_header_1)
+                .is_err()
+        );
+        assert!(
+            StacksChainState::check_poison_microblock_headers(&header_1, &malleated_header_2)
+                .is_err()
+        );
+
+        // headers must sign different content, conflict, share a signer, and have low-S
+        // signatures
+        let bad_headers = vec![
+            (header_1.clone(), header_1.clone()),
+            (header_1.clone(), malleated_header_1.clone()),
+            (header_1.clone(), malleated_header_2.clone()),
+            (
+                header_1.clone(),
+                make_signed_microblock_header(&mblock_privk, 4, Sha512Trunc256Sum([2u8; 32])),
+            ),
+            (
+                header_1.clone(),
+                make_signed_microblock_header(&other_mblock_privk, 3, Sha512Trunc256Sum([2u8; 32])),
+            ),
+        ];
+        for (bad_header_1, bad_header_2) in bad_headers.into_iter() {
+            let signed_tx = make_poison_microblock_tx(&privk, 0, bad_header_1, bad_header_2);
+            assert!(StacksChainState::process_transaction(&mut conn, &signed_tx, false).is_err());
+        }
+
+        let (fee, receipt) =
+            StacksChainState::process_transaction(&mut conn, &signed_tx, false).unwrap();
+        assert_eq!(fee, 0);
+        assert_eq!(
+            receipt.result,
+            Value::Tuple(
+                TupleData::from_data(vec![
+                    (
+                        "reporter".into(),
+                        Value::Principal(addr.to_account_principal())
+                    ),
+                    ("sequence".into(), Value::UInt(3)),
+                ])
+                .unwrap()
+            )
+        );
+
+        // a later report of a later equivocation does not replace the first report
+        let header_3 =
+            make_signed_microblock_header(&mblock_privk, 5, Sha512Trunc256Sum([3u8; 32]));
+        let header_4 =
+            make_signed_microblock_header(&mblock_privk, 5, Sha512Trunc256Sum([4u8; 32]));
+        let signed_tx = make_poison_microblock_tx(&privk_2, 0, header_3, header_4);
+        StacksChainState::process_transaction(&mut conn, &signed_tx, false).unwrap();
+
+        let report = StacksChainState::get_microblock_poison_report(&mut conn, 1)
+            .unwrap()
+            .unwrap();
+        assert_eq!(report.reporter, StandardPrincipalData::from(addr.clone()));
+        assert_eq!(report.sequence, 3);
+
+        // ...but a report of an earlier equivocation does
+        let header_5 =
+            make_signed_microblock_header(&mblock_privk, 1, Sha512Trunc256Sum([5u8; 32]));
+        let header_6 =
+            make_signed_microblock_header(&mblock_privk, 1, Sha512Trunc256Sum([6u8; 32]));
+        let signed_tx = make_poison_microblock_tx(&privk_2, 1, header_5, header_6);
+        StacksChainState::process_transaction(&mut conn, &signed_tx, false).unwrap();
+
+        let report = StacksChainState::get_microblock_poison_report(&mut conn, 1)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            StacksAddress::from(report.reporter),
+            TransactionAuth::from_p2pkh(&privk_2)
+                .unwrap()
+                .origin()
+                .address_testnet()
+        );
+        assert_eq!(report.sequence, 1);
+
+        conn.commit_block();
+    }
 }
@@ -832,4 +832,167 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_unconfirmed_refresh_resumed_microblock_stream() {
+        let privk = StacksPrivateKey::new();
+        let addr = StacksAddress::from_public_keys(
+            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            &AddressHashMode::SerializeP2PKH,
+            1,
+            &vec![StacksPublicKey::from_private(&privk)],
+        )
+        .unwrap();
+
+        let initial_balance = 1000000000;
+        let mut peer_config = TestPeerConfig::new(
+            "test_unconfirmed_refresh_resumed_microblock_stream",
+            7004,
+            7005,
+        );
+        peer_config.initial_balances = vec![(addr.to_account_principal(), initial_balance)];
+
+        let mut peer = TestPeer::new(peer_config);
+
+        let microblock_privkey = StacksPrivateKey::new();
+        let microblock_pubkeyhash =
+            Hash160::from_node_public_key(&StacksPublicKey::from_private(&microblock_privkey));
+
+        let tip = SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+            .unwrap();
+
+        let mut anchor_size = 0;
+        let mut anchor_cost = ExecutionCost::zero();
+
+        let (burn_ops, stacks_block, _) = peer.make_tenure(
+            |ref mut miner, ref mut sortdb, ref mut chainstate, vrf_proof, _, _| {
+                let parent_tip =
+                    StacksChainState::get_genesis_header_info(chainstate.headers_db()).unwrap();
+                let block_builder = StacksBlockBuilder::make_block_builder(
+                    &parent_tip,
+                    vrf_proof,
+                    tip.total_burn,
+                    microblock_pubkeyhash,
+                )
+                .unwrap();
+
+                let coinbase_tx = make_coinbase(miner, 0);
+                let (anchored_block, anchored_block_size, anchored_block_cost) =
+                    StacksBlockBuilder::make_anchored_block_from_txs(
+                        block_builder,
+                        chainstate,
+                        &sortdb.index_conn(),
+                        vec![coinbase_tx],
+                    )
+                    .unwrap();
+
+                anchor_size = anchored_block_size;
+                anchor_cost = anchored_block_cost;
+                (anchored_block, vec![])
+            },
+        );
+
+        let (_, _, consensus_hash) = peer.next_burnchain_block(burn_ops.clone());
+        peer.process_stacks_epoch_at_tip(&stacks_block, &vec![]);
+
+        let recv_addr =
+            StacksAddress::from_string("ST1H1B54MY50RMBRRKS7GV2ZWG79RZ1RQ1ETW4E01").unwrap();
+
+        let make_stx_transfer = |nonce: u64| {
+            let auth = TransactionAuth::Standard(
+                TransactionSpendingCondition::new_singlesig_p2pkh(StacksPublicKey::from_private(
+                    &privk,
+                ))
+                .unwrap(),
+            );
+            let mut tx_stx_transfer = StacksTransaction::new(
+                TransactionVersion::Testnet,
+                auth,
+                TransactionPayload::TokenTransfer(
+                    recv_addr.clone().into(),
+                    1,
+                    TokenTransferMemo([0u8; 34]),
+                ),
+            );
+
+            tx_stx_transfer.chain_id = 0x80000000;
+            tx_stx_transfer.post_condition_mode = TransactionPostConditionMode::Allow;
+            tx_stx_transfer.set_fee_rate(0);
+            tx_stx_transfer.set_origin_nonce(nonce);
+
+            let mut signer = StacksTransactionSigner::new(&tx_stx_transfer);
+            signer.sign_origin(&privk).unwrap();
+            MemPoolTxInfo::from_tx(
+                signer.get_tx().unwrap(),
+                0,
+                consensus_hash.clone(),
+                stacks_block.block_hash(),
+                0,
+            )
+        };
+
+        // mine each microblock with a fresh builder, as a miner would between network passes.
+        // Each round re-offers the last round's transactions, which must not be mined again.
+        let mut microblocks: Vec<StacksMicroblock> = vec![];
+        for i in 0..3 {
+            let mut txs = vec![];
+            if i > 0 {
+                txs.push(make_stx_transfer(2 * (i - 1) + 1));
+            }
+            txs.push(make_stx_transfer(2 * i));
+            txs.push(make_stx_transfer(2 * i + 1));
+
+            let sortdb = peer.sortdb.take().unwrap();
+            let microblock = {
+                let sort_iconn = sortdb.index_conn();
+                let mut microblock_builder = StacksMicroblockBuilder::resume(
+                    stacks_block.block_hash(),
+                    consensus_hash.clone(),
+                    peer.chainstate(),
+                    &sort_iconn,
+                    anchor_cost.clone(),
+                    anchor_size,
+                    &microblocks,
+                )
+                .unwrap();
+                microblock_builder
+                    .mine_next_microblock_from_txs(txs, &microblock_privkey)
+                    .unwrap()
+            };
+            peer.sortdb = Some(sortdb);
+
+            assert_eq!(microblock.header.sequence, i as u16);
+            assert_eq!(microblock.txs.len(), 2);
+            if let Some(prev_microblock) = microblocks.last() {
+                assert_eq!(microblock.header.prev_block, prev_microblock.block_hash());
+            }
+            microblocks.push(microblock);
+        }
+
+        for mblock in microblocks.iter() {
+            assert!(peer
+                .chainstate()
+                .preprocess_streamed_microblock(&consensus_hash, &stacks_block.block_hash(), mblock)
+                .unwrap());
+        }
+
+        let sortdb = peer.sortdb.take().unwrap();
+        let canonical_tip =
+            StacksBlockHeader::make_index_block_hash(&consensus_hash, &stacks_block.block_hash());
+        peer.chainstate()
+            .reload_unconfirmed_state(&sortdb.index_conn(), canonical_tip)
+            .unwrap();
+
+        let recv_balance = peer
+            .chainstate()
+            .with_read_only_unconfirmed_clarity_tx(&sortdb.index_conn(), |clarity_tx| {
+                clarity_tx.with_clarity_db_readonly(|clarity_db| {
+                    clarity_db.get_account_stx_balance(&recv_addr.into())
+                })
+            })
+            .unwrap();
+        peer.sortdb = Some(sortdb);
+
+        assert_eq!(recv_balance.amount_unlocked, 6);
+    }
 }
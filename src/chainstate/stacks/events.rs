@@ -16,8 +16,7 @@
 
 use super::StacksAddress;
 use burnchains::Txid;
-use chainstate::stacks::StacksTransaction;
-use net::StacksMessageCodec;
+use chainstate::stacks::{StacksBlockId, StacksTransaction};
 use vm::analysis::ContractAnalysis;
 use vm::costs::ExecutionCost;
 use vm::types::{
@@ -35,6 +34,61 @@ pub struct StacksTransactionReceipt {
     pub execution_cost: ExecutionCost,
 }
 
+/// A processed transaction's receipt, as stored in the chain state once its block is processed.
+/// Hashes are 0x-prefixed hex, and the result is the hex-serialized Clarity value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionReceiptData {
+    pub txid: String,
+    pub index_block_hash: String,
+    pub block_height: u64,
+    pub tx_index: u32,
+    /// `success`, `abort_by_response` or `abort_by_post_condition`
+    pub status: String,
+    pub result: String,
+    pub stx_burned: String,
+    pub execution_cost: ExecutionCost,
+    pub events: Vec<serde_json::Value>,
+}
+
+impl TransactionReceiptData {
+    pub fn from_receipt(
+        receipt: &StacksTransactionReceipt,
+        index_block_hash: &StacksBlockId,
+        block_height: u64,
+        tx_index: u32,
+    ) -> TransactionReceiptData {
+        let txid = receipt.transaction.txid();
+        let committed = match receipt.result {
+            Value::Response(ref response_data) => response_data.committed,
+            _ => true,
+        };
+        let status = if receipt.post_condition_aborted {
+            "abort_by_post_condition"
+        } else if committed {
+            "success"
+        } else {
+            "abort_by_response"
+        };
+        let events = receipt
+            .events
+            .iter()
+            .map(|event| event.json_serialize(&txid, !receipt.post_condition_aborted))
+            .collect();
+
+        TransactionReceiptData {
+            txid: format!("0x{}", &txid),
+            index_block_hash: format!("0x{}", index_block_hash),
+            block_height,
+            tx_index,
+            status: status.to_string(),
+            result: format!("0x{}", receipt.result.serialize_to_hex()),
+            stx_burned: receipt.stx_burned.to_string(),
+            execution_cost: receipt.execution_cost.clone(),
+            events,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum StacksTransactionEvent {
     SmartContractEvent(SmartContractEventData),
@@ -43,7 +97,39 @@ pub enum StacksTransactionEvent {
     FTEvent(FTEventType),
 }
 
+/// The `type` of each kind of event, as it appears in an event's JSON serialization
+pub const TRANSACTION_EVENT_TYPES: &[&str] = &[
+    "contract_event",
+    "stx_transfer_event",
+    "stx_mint_event",
+    "stx_burn_event",
+    "stx_lock_event",
+    "nft_transfer_event",
+    "nft_mint_event",
+    "ft_transfer_event",
+    "ft_mint_event",
+];
+
 impl StacksTransactionEvent {
+    /// The `type` of this event, as it appears in its JSON serialization
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            StacksTransactionEvent::SmartContractEvent(_) => "contract_event",
+            StacksTransactionEvent::STXEvent(STXEventType::STXTransferEvent(_)) => {
+                "stx_transfer_event"
+            }
+            StacksTransactionEvent::STXEvent(STXEventType::STXMintEvent(_)) => "stx_mint_event",
+            StacksTransactionEvent::STXEvent(STXEventType::STXBurnEvent(_)) => "stx_burn_event",
+            StacksTransactionEvent::STXEvent(STXEventType::STXLockEvent(_)) => "stx_lock_event",
+            StacksTransactionEvent::NFTEvent(NFTEventType::NFTTransferEvent(_)) => {
+                "nft_transfer_event"
+            }
+            StacksTransactionEvent::NFTEvent(NFTEventType::NFTMintEvent(_)) => "nft_mint_event",
+            StacksTransactionEvent::FTEvent(FTEventType::FTTransferEvent(_)) => "ft_transfer_event",
+            StacksTransactionEvent::FTEvent(FTEventType::FTMintEvent(_)) => "ft_mint_event",
+        }
+    }
+
     pub fn json_serialize(&self, txid: &Txid, committed: bool) -> serde_json::Value {
         match self {
             StacksTransactionEvent::SmartContractEvent(event_data) => json!({
@@ -196,18 +282,12 @@ pub struct NFTTransferEventData {
 
 impl NFTTransferEventData {
     pub fn json_serialize(&self) -> serde_json::Value {
-        let raw_value = {
-            let mut bytes = vec![];
-            self.value.consensus_serialize(&mut bytes).unwrap();
-            let formatted_bytes: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
-            formatted_bytes
-        };
         json!({
             "asset_identifier": format!("{}", self.asset_identifier),
             "sender": format!("{}",self.sender),
             "recipient": format!("{}",self.recipient),
             "value": self.value,
-            "raw_value": format!("0x{}", raw_value.join("")),
+            "raw_value": format!("0x{}", self.value.serialize_to_hex()),
         })
     }
 }
@@ -221,17 +301,11 @@ pub struct NFTMintEventData {
 
 impl NFTMintEventData {
     pub fn json_serialize(&self) -> serde_json::Value {
-        let raw_value = {
-            let mut bytes = vec![];
-            self.value.consensus_serialize(&mut bytes).unwrap();
-            let formatted_bytes: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
-            formatted_bytes
-        };
         json!({
             "asset_identifier": format!("{}", self.asset_identifier),
             "recipient": format!("{}",self.recipient),
             "value": self.value,
-            "raw_value": format!("0x{}", raw_value.join("")),
+            "raw_value": format!("0x{}", self.value.serialize_to_hex()),
         })
     }
 }
@@ -280,17 +354,11 @@ pub struct SmartContractEventData {
 
 impl SmartContractEventData {
     pub fn json_serialize(&self) -> serde_json::Value {
-        let raw_value = {
-            let mut bytes = vec![];
-            self.value.consensus_serialize(&mut bytes).unwrap();
-            let formatted_bytes: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
-            formatted_bytes
-        };
         json!({
             "contract_identifier": self.key.0.to_string(),
             "topic": self.key.1,
             "value": self.value,
-            "raw_value": format!("0x{}", raw_value.join("")),
+            "raw_value": format!("0x{}", self.value.serialize_to_hex()),
         })
     }
 }
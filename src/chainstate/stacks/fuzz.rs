@@ -0,0 +1,400 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Robustness checks for the consensus wire format.
+//!
+//! `check_consensus_deserialize()` feeds untrusted bytes to the decoders of every type a peer
+//! can send us, and panics if a decoder accepts something that does not survive a re-encoding
+//! roundtrip.  It is driven both by the randomized tests below and by the `cargo fuzz` targets
+//! in `fuzz/`.
+
+use std::fmt;
+
+use chainstate::stacks::*;
+use net::StacksMessage;
+use net::StacksMessageCodec;
+
+fn check_roundtrip<T: StacksMessageCodec + fmt::Debug + PartialEq>(bytes: &[u8]) -> bool {
+    let obj = match T::consensus_deserialize(&mut &bytes[..]) {
+        Ok(obj) => obj,
+        Err(_) => {
+            return false;
+        }
+    };
+
+    let mut reencoded = vec![];
+    obj.consensus_serialize(&mut reencoded)
+        .expect("BUG: failed to re-encode a decoded object");
+
+    let redecoded = T::consensus_deserialize(&mut &reencoded[..])
+        .expect("BUG: failed to decode a re-encoded object");
+
+    assert_eq!(obj, redecoded);
+    true
+}
+
+/// Try to decode `bytes` as each kind of message a peer can send.  Malformed input must be
+/// rejected with an error (never a panic), and anything that does decode must decode to the
+/// same value after being re-encoded.  Returns true if any decoder accepted the bytes.
+pub fn check_consensus_deserialize(bytes: &[u8]) -> bool {
+    let mut accepted = false;
+    accepted |= check_roundtrip::<StacksTransaction>(bytes);
+    accepted |= check_roundtrip::<StacksBlock>(bytes);
+    accepted |= check_roundtrip::<StacksMicroblock>(bytes);
+    accepted |= check_roundtrip::<StacksMessage>(bytes);
+    accepted
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chainstate::stacks::test::{codec_all_transactions, make_codec_test_block};
+    use net::codec::test::check_codec_and_corruption;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, RngCore, SeedableRng};
+
+    use util::hash::*;
+    use util::strings::StacksString;
+
+    use vm::types::Value;
+
+    // fixed seed, so failures are reproducible
+    const SEED: u64 = 0x5eed_c0dec;
+
+    fn random_bytes<R: Rng>(rng: &mut R, len: usize) -> Vec<u8> {
+        let mut bytes = vec![0u8; len];
+        rng.fill_bytes(&mut bytes);
+        bytes
+    }
+
+    fn random_signature<R: Rng>(rng: &mut R) -> MessageSignature {
+        let mut sig = [0u8; 65];
+        rng.fill_bytes(&mut sig);
+        MessageSignature(sig)
+    }
+
+    fn random_value<R: Rng>(rng: &mut R, depth: u32) -> Value {
+        let kind = if depth > 0 {
+            rng.gen_range(0, 7)
+        } else {
+            rng.gen_range(0, 4)
+        };
+        match kind {
+            0 => Value::Int(rng.gen()),
+            1 => Value::UInt(rng.gen()),
+            2 => Value::Bool(rng.gen()),
+            3 => {
+                let len = rng.gen_range(0, 64);
+                Value::buff_from(random_bytes(rng, len)).unwrap()
+            }
+            4 => {
+                if rng.gen() {
+                    Value::some(random_value(rng, depth - 1)).unwrap()
+                } else {
+                    Value::none()
+                }
+            }
+            5 => {
+                if rng.gen() {
+                    Value::okay(random_value(rng, depth - 1)).unwrap()
+                } else {
+                    Value::error(random_value(rng, depth - 1)).unwrap()
+                }
+            }
+            _ => {
+                let len = rng.gen_range(0, 8);
+                Value::list_from((0..len).map(|_| Value::Int(rng.gen())).collect()).unwrap()
+            }
+        }
+    }
+
+    fn random_code_body<R: Rng>(rng: &mut R) -> StacksString {
+        let len = rng.gen_range(0, 256);
+        let code: String = (0..len)
+            .map(|_| match rng.gen_range(0, 20) {
+                0 => '\n',
+                1 => '\t',
+                _ => rng.gen_range(0x20u8, 0x7f) as char,
+            })
+            .collect();
+        StacksString::from_string(&code).unwrap()
+    }
+
+    fn randomize_spending_condition<R: Rng>(
+        rng: &mut R,
+        spending_condition: &mut TransactionSpendingCondition,
+    ) {
+        match *spending_condition {
+            TransactionSpendingCondition::Singlesig(ref mut data) => {
+                data.signer = Hash160(rng.gen());
+                data.nonce = rng.gen();
+                data.fee_rate = rng.gen();
+                data.signature = random_signature(rng);
+            }
+            TransactionSpendingCondition::Multisig(ref mut data) => {
+                data.signer = Hash160(rng.gen());
+                data.nonce = rng.gen();
+                data.fee_rate = rng.gen();
+                for field in data.fields.iter_mut() {
+                    if let TransactionAuthField::Signature(_, ref mut sig) = *field {
+                        *sig = random_signature(rng);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Make a random transaction by picking one of each kind of transaction we support, and
+    /// then randomizing all of the fields that the codec does not constrain.
+    fn random_transaction<R: Rng>(
+        rng: &mut R,
+        templates: &[StacksTransaction],
+    ) -> StacksTransaction {
+        let mut tx = templates[rng.gen_range(0, templates.len())].clone();
+
+        tx.version = if rng.gen() {
+            TransactionVersion::Mainnet
+        } else {
+            TransactionVersion::Testnet
+        };
+        tx.chain_id = rng.gen();
+        tx.post_condition_mode = if rng.gen() {
+            TransactionPostConditionMode::Allow
+        } else {
+            TransactionPostConditionMode::Deny
+        };
+
+        match tx.auth {
+            TransactionAuth::Standard(ref mut origin) => {
+                randomize_spending_condition(rng, origin);
+            }
+            TransactionAuth::Sponsored(ref mut origin, ref mut sponsor) => {
+                randomize_spending_condition(rng, origin);
+                randomize_spending_condition(rng, sponsor);
+            }
+        }
+
+        for post_condition in tx.post_conditions.iter_mut() {
+            match *post_condition {
+                TransactionPostCondition::STX(_, _, ref mut amount) => {
+                    *amount = rng.gen();
+                }
+                TransactionPostCondition::Fungible(_, _, _, ref mut amount) => {
+                    *amount = rng.gen();
+                }
+                TransactionPostCondition::Nonfungible(_, _, ref mut value, _) => {
+                    *value = random_value(rng, 2);
+                }
+            }
+        }
+
+        match tx.payload {
+            TransactionPayload::TokenTransfer(_, ref mut amount, ref mut memo) => {
+                *amount = rng.gen();
+                rng.fill_bytes(&mut memo.0);
+            }
+            TransactionPayload::ContractCall(ref mut cc) => {
+                let num_args = rng.gen_range(0, 5);
+                cc.function_args = (0..num_args).map(|_| random_value(rng, 3)).collect();
+            }
+            TransactionPayload::SmartContract(ref mut sc) => {
+                sc.code_body = random_code_body(rng);
+            }
+            TransactionPayload::Coinbase(ref mut payload) => {
+                payload.0 = rng.gen();
+            }
+            TransactionPayload::PoisonMicroblock(ref mut h1, ref mut h2) => {
+                h1.sequence = rng.gen();
+                h1.tx_merkle_root = Sha512Trunc256Sum(rng.gen());
+                h1.signature = random_signature(rng);
+                h2.sequence = rng.gen();
+                h2.tx_merkle_root = Sha512Trunc256Sum(rng.gen());
+                h2.signature = random_signature(rng);
+            }
+        }
+
+        tx
+    }
+
+    fn all_templates(anchor_mode: &TransactionAnchorMode) -> Vec<StacksTransaction> {
+        codec_all_transactions(
+            &TransactionVersion::Testnet,
+            0x80000000,
+            anchor_mode,
+            &TransactionPostConditionMode::Allow,
+        )
+    }
+
+    fn merkle_root(txs: &Vec<StacksTransaction>) -> Sha512Trunc256Sum {
+        let txid_vecs = txs.iter().map(|tx| tx.txid().as_bytes().to_vec()).collect();
+        MerkleTree::<Sha512Trunc256Sum>::new(&txid_vecs).root()
+    }
+
+    /// Every strict prefix of a valid encoding must be rejected.
+    fn check_prefixes_rejected<T: StacksMessageCodec + fmt::Debug>(bytes: &Vec<u8>) {
+        for i in 0..bytes.len() {
+            assert!(
+                T::consensus_deserialize(&mut &bytes[0..i]).is_err(),
+                "decoded a {}-byte prefix of {}",
+                i,
+                to_hex(bytes)
+            );
+        }
+    }
+
+    /// Flip, overwrite, insert, and delete random bytes in a valid encoding.
+    fn check_mutations<R: Rng>(rng: &mut R, bytes: &Vec<u8>, num_mutations: usize) {
+        for _ in 0..num_mutations {
+            let mut mutated = bytes.clone();
+            let num_edits = rng.gen_range(1, 4);
+            for _ in 0..num_edits {
+                if mutated.len() == 0 {
+                    break;
+                }
+                let i = rng.gen_range(0, mutated.len());
+                match rng.gen_range(0, 4) {
+                    0 => mutated[i] ^= 1 << rng.gen_range(0, 8),
+                    1 => mutated[i] = rng.gen(),
+                    2 => mutated.insert(i, rng.gen()),
+                    _ => {
+                        mutated.remove(i);
+                    }
+                }
+            }
+            check_consensus_deserialize(&mutated);
+        }
+    }
+
+    #[test]
+    fn codec_fuzz_transactions() {
+        let mut rng = StdRng::seed_from_u64(SEED);
+        let templates: Vec<StacksTransaction> = [
+            TransactionAnchorMode::OnChainOnly,
+            TransactionAnchorMode::OffChainOnly,
+            TransactionAnchorMode::Any,
+        ]
+        .iter()
+        .map(|anchor_mode| all_templates(anchor_mode))
+        .flatten()
+        .collect();
+
+        for _ in 0..256 {
+            let tx = random_transaction(&mut rng, &templates);
+            let tx_bytes = tx.serialize_to_vec();
+
+            check_codec_and_corruption::<StacksTransaction>(&tx, &tx_bytes);
+            check_prefixes_rejected::<StacksTransaction>(&tx_bytes);
+
+            let (_, tx_len) =
+                StacksTransaction::consensus_deserialize_with_len(&mut &tx_bytes[..]).unwrap();
+            assert_eq!(tx_len as usize, tx_bytes.len());
+
+            assert!(check_consensus_deserialize(&tx_bytes));
+            check_mutations(&mut rng, &tx_bytes, 16);
+        }
+    }
+
+    #[test]
+    fn codec_fuzz_blocks() {
+        let mut rng = StdRng::seed_from_u64(SEED + 1);
+        let templates: Vec<StacksTransaction> = [
+            TransactionAnchorMode::OnChainOnly,
+            TransactionAnchorMode::Any,
+        ]
+        .iter()
+        .map(|anchor_mode| all_templates(anchor_mode))
+        .flatten()
+        .filter(|tx| match tx.payload {
+            TransactionPayload::Coinbase(_) => false,
+            _ => true,
+        })
+        .collect();
+
+        let block_template = make_codec_test_block(1);
+        let coinbase_template = block_template.txs[0].clone();
+
+        for _ in 0..32 {
+            let mut block = block_template.clone();
+            let mut coinbase = coinbase_template.clone();
+            coinbase.payload = TransactionPayload::Coinbase(CoinbasePayload(rng.gen()));
+
+            block.txs = vec![coinbase];
+            for _ in 0..rng.gen_range(0, 16) {
+                block.txs.push(random_transaction(&mut rng, &templates));
+            }
+            block.header.parent_block = BlockHeaderHash(rng.gen());
+            block.header.parent_microblock_sequence = rng.gen();
+            block.header.tx_merkle_root = merkle_root(&block.txs);
+
+            let block_bytes = block.serialize_to_vec();
+            check_codec_and_corruption::<StacksBlock>(&block, &block_bytes);
+            assert!(check_consensus_deserialize(&block_bytes));
+            check_mutations(&mut rng, &block_bytes, 64);
+
+            // a block with an inconsistent Merkle root is rejected
+            block.header.tx_merkle_root = Sha512Trunc256Sum(rng.gen());
+            assert!(
+                StacksBlock::consensus_deserialize(&mut &block.serialize_to_vec()[..]).is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn codec_fuzz_microblocks() {
+        let mut rng = StdRng::seed_from_u64(SEED + 2);
+        let templates: Vec<StacksTransaction> = [
+            TransactionAnchorMode::OffChainOnly,
+            TransactionAnchorMode::Any,
+        ]
+        .iter()
+        .map(|anchor_mode| all_templates(anchor_mode))
+        .flatten()
+        .collect();
+
+        for _ in 0..32 {
+            let txs: Vec<StacksTransaction> = (0..rng.gen_range(1, 16))
+                .map(|_| random_transaction(&mut rng, &templates))
+                .collect();
+
+            let mut mblock = StacksMicroblock::first_unsigned(&BlockHeaderHash(rng.gen()), txs);
+            mblock.header.sequence = rng.gen();
+            mblock.header.signature = random_signature(&mut rng);
+
+            let mblock_bytes = mblock.serialize_to_vec();
+            check_codec_and_corruption::<StacksMicroblock>(&mblock, &mblock_bytes);
+            assert!(check_consensus_deserialize(&mblock_bytes));
+            check_mutations(&mut rng, &mblock_bytes, 64);
+        }
+    }
+
+    #[test]
+    fn codec_fuzz_arbitrary_bytes() {
+        let mut rng = StdRng::seed_from_u64(SEED + 3);
+        for _ in 0..4096 {
+            let len = rng.gen_range(0, 512);
+            let bytes = random_bytes(&mut rng, len);
+            check_consensus_deserialize(&bytes);
+        }
+
+        // length prefixes that claim far more data than is present
+        let mut bytes = vec![0x80, 0x00, 0x00, 0x00, 0x01];
+        bytes.extend_from_slice(&[0xff; 64]);
+        assert!(!check_consensus_deserialize(&bytes));
+        assert!(!check_consensus_deserialize(&[]));
+    }
+}
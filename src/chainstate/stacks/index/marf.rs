@@ -20,6 +20,7 @@ use std::io;
 use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 
 use std::path::PathBuf;
+use std::time::Instant;
 
 use rusqlite::{Connection, Transaction};
 use sha2::Digest;
@@ -44,8 +45,10 @@ use chainstate::stacks::index::{
 };
 
 use chainstate::stacks::index::trie::Trie;
+use chainstate::stacks::index::trie_sql;
 
 use chainstate::stacks::index::Error;
+use monitoring;
 use std::ops::DerefMut;
 use util::hash::Sha512Trunc256Sum;
 use util::log;
@@ -891,6 +894,30 @@ impl<T: MarfTrieId> MARF<T> {
         MARF::do_insert_leaf(storage, block_hash, path, value, false)
     }
 
+    // like insert_leaf_in_batch, but don't update any hashes either -- the caller must call
+    // Trie::rehash_trie() once the batch is inserted
+    fn insert_leaf_deferred_hash(
+        storage: &mut TrieStorageTransaction<T>,
+        block_hash: &T,
+        path: &TriePath,
+        leaf_value: &TrieLeaf,
+    ) -> Result<(), Error> {
+        if storage.readonly() {
+            unreachable!("CORRUPTION: constructed read-only TrieStorageTransaction instance");
+        }
+
+        let mut value = leaf_value.clone();
+        let mut cursor = MARF::walk_cow(storage, block_hash, path)?;
+
+        debug!(
+            "MARF Insert (deferred hash) in {}: '{}' = '{}' (...{:?})",
+            block_hash, path, leaf_value.data, &leaf_value.path
+        );
+
+        Trie::add_value(storage, &mut cursor, &mut value)?;
+        Trie::update_root_ptrs(storage, &cursor)
+    }
+
     /// Instantiate the MARF from a TrieFileStorage instance
     pub fn from_storage(storage: TrieFileStorage<T>) -> MARF<T> {
         MARF {
@@ -924,10 +951,12 @@ impl<T: MarfTrieId> MARF<T> {
 
         let path = TriePath::from_key(key);
 
+        let read_start = Instant::now();
         let result = MARF::get_path(storage, block_hash, &path).or_else(|e| match e {
             Error::NotFoundError => Ok(None),
             _ => Err(e),
         });
+        monitoring::observe_marf_io_latency("read", read_start);
 
         // restore
         storage
@@ -1045,25 +1074,22 @@ impl<T: MarfTrieId> MARF<T> {
 
         let (cur_block_hash, cur_block_id) = conn.get_cur_block_and_id();
 
-        let last = keys.len() - 1;
-
-        let mut result = keys[0..last]
+        // hash each node once, after every key is inserted, instead of once per insert
+        let result = keys
             .iter()
-            .zip(values[0..last].iter())
+            .zip(values.iter())
             .try_for_each(|(key, value)| {
                 let marf_leaf = TrieLeaf::from_value(&vec![], value.clone());
                 let path = TriePath::from_key(key);
 
-                MARF::insert_leaf_in_batch(conn, block_hash, &path, &marf_leaf)
+                MARF::insert_leaf_deferred_hash(conn, block_hash, &path, &marf_leaf)
+            })
+            .and_then(|_| {
+                conn.open_block(block_hash)?;
+                Trie::rehash_trie(conn)?;
+                Ok(())
             });
 
-        if result.is_ok() {
-            // last insert updates the root with the skiplist hash
-            let marf_leaf = TrieLeaf::from_value(&vec![], values[last].clone());
-            let path = TriePath::from_key(&keys[last]);
-            result = MARF::insert_leaf(conn, block_hash, &path, &marf_leaf);
-        }
-
         // restore
         conn.open_block_maybe_id(&cur_block_hash, cur_block_id)?;
 
@@ -1203,9 +1229,11 @@ impl<T: MarfTrieId> MARF<T> {
             return Err(Error::ReadOnlyError);
         }
         if let Some(_tip) = self.open_chain_tip.take() {
+            let commit_start = Instant::now();
             let mut tx = self.storage.transaction()?;
             tx.flush()?;
             tx.commit_tx();
+            monitoring::observe_marf_io_latency("commit", commit_start);
         }
         Ok(())
     }
@@ -1222,9 +1250,11 @@ impl<T: MarfTrieId> MARF<T> {
             return Err(Error::UnconfirmedError);
         }
         if let Some(_tip) = self.open_chain_tip.take() {
+            let commit_start = Instant::now();
             let mut tx = self.storage.transaction()?;
             tx.flush_mined(bhh)?;
             tx.commit_tx();
+            monitoring::observe_marf_io_latency("commit", commit_start);
         }
         Ok(())
     }
@@ -1239,9 +1269,11 @@ impl<T: MarfTrieId> MARF<T> {
             return Err(Error::UnconfirmedError);
         }
         if let Some(_tip) = self.open_chain_tip.take() {
+            let commit_start = Instant::now();
             let mut tx = self.storage.transaction()?;
             tx.flush_to(real_bhh)?;
             tx.commit_tx();
+            monitoring::observe_marf_io_latency("commit", commit_start);
         }
         Ok(())
     }
@@ -1311,6 +1343,17 @@ impl<T: MarfTrieId> MARF<T> {
         result
     }
 
+    /// Set the number of trie nodes (and, separately, node hashes) the storage keeps cached in
+    /// RAM.  A capacity of 0 disables the cache.
+    pub fn set_node_cache_capacity(&mut self, capacity: usize) {
+        self.storage.connection().set_node_cache_capacity(capacity)
+    }
+
+    /// Get and reset the storage's node cache (hits, misses)
+    pub fn node_cache_stats(&mut self) -> (u64, u64) {
+        self.storage.connection().node_cache_stats()
+    }
+
     /// Access internal storage
     #[cfg(test)]
     pub fn borrow_storage_backend(&mut self) -> TrieStorageConnection<T> {
@@ -1353,6 +1396,111 @@ impl<T: MarfTrieId> MARF<T> {
     pub fn get_root_hash_at(&mut self, block_hash: &T) -> Result<TrieHash, Error> {
         self.storage.connection().get_root_hash_at(block_hash)
     }
+
+    /// Visit every node of the trie for a block, depth-first from its root.  If
+    /// `follow_backptrs` is true, back-pointers are followed into ancestor tries, so every node
+    /// of the state as of this block is visited.  Otherwise, only the nodes this block's trie
+    /// stores are visited.
+    /// The visitor is given the path bytes leading up to the node (not including the node's own
+    /// path), the block whose trie stores the node, the node, and its hash.
+    pub fn walk_trie<F>(
+        &mut self,
+        block_hash: &T,
+        follow_backptrs: bool,
+        mut visitor: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(&[u8], &T, &TrieNodeType, &TrieHash),
+    {
+        let mut storage = self.storage.connection();
+        let (cur_block_hash, cur_block_id) = storage.get_cur_block_and_id();
+
+        let result: Result<(), Error> = (|| {
+            storage.open_block(block_hash)?;
+            let block_id = storage.get_cur_block_identifier()?;
+
+            // (block, its identifier, pointer to the node in it, path bytes up to the node)
+            let mut frontier = vec![(block_hash.clone(), block_id, storage.root_trieptr(), vec![])];
+
+            while let Some((node_block_hash, node_block_id, ptr, path_prefix)) = frontier.pop() {
+                storage.open_block_known_id(&node_block_hash, node_block_id)?;
+                let (node, node_hash) = storage.read_nodetype(&ptr)?;
+                visitor(&path_prefix, &node_block_hash, &node, &node_hash);
+
+                let mut child_prefix = path_prefix.clone();
+                child_prefix.extend_from_slice(node.path_bytes());
+
+                // push in reverse, so children are visited in order
+                for child_ptr in node.ptrs().iter().rev() {
+                    if child_ptr.id() == TrieNodeID::Empty as u8 {
+                        continue;
+                    }
+
+                    let mut path = child_prefix.clone();
+                    path.push(child_ptr.chr());
+
+                    if is_backptr(child_ptr.id()) {
+                        if !follow_backptrs {
+                            continue;
+                        }
+                        let back_block_hash = storage
+                            .get_block_from_local_id(child_ptr.back_block())?
+                            .clone();
+                        frontier.push((
+                            back_block_hash,
+                            child_ptr.back_block(),
+                            child_ptr.from_backptr(),
+                            path,
+                        ));
+                    } else {
+                        frontier.push((
+                            node_block_hash.clone(),
+                            node_block_id,
+                            child_ptr.clone(),
+                            path,
+                        ));
+                    }
+                }
+            }
+            Ok(())
+        })();
+
+        storage
+            .open_block_maybe_id(&cur_block_hash, cur_block_id)
+            .map_err(|e| Error::RestoreMarfBlockError(Box::new(e)))?;
+        result
+    }
+
+    /// Check that the `max_tries` most recently confirmed tries can be read in full, and that
+    /// their leaves hash to the hashes stored with them.  A trie that was only partly written, or
+    /// was damaged since, fails.
+    /// Returns the tries that failed, oldest first, and why.
+    pub fn check_tries(&mut self, max_tries: u32) -> Result<Vec<(T, String)>, Error> {
+        let block_hashes: Vec<(u32, T)> =
+            trie_sql::get_latest_confirmed_block_hashes(self.sqlite_conn(), max_tries)?;
+
+        let mut bad_tries = vec![];
+        for (_, block_hash) in block_hashes.into_iter() {
+            let mut bad_leaf = None;
+            let result = self.walk_trie(&block_hash, false, |_, _, node, node_hash| {
+                if let TrieNodeType::Leaf(ref leaf) = node {
+                    let leaf_hash = get_leaf_hash(leaf);
+                    if bad_leaf.is_none() && leaf_hash != *node_hash {
+                        bad_leaf = Some(format!(
+                            "Leaf {:?} hashes to {}, but is stored with hash {}",
+                            leaf, &leaf_hash, node_hash
+                        ));
+                    }
+                }
+            });
+            match (result, bad_leaf) {
+                (Err(e), _) => bad_tries.push((block_hash, e.to_string())),
+                (Ok(()), Some(msg)) => bad_tries.push((block_hash, msg)),
+                (Ok(()), None) => {}
+            }
+        }
+        Ok(bad_tries)
+    }
 }
 
 #[cfg(test)]
@@ -1378,6 +1526,101 @@ mod test {
     use util::get_epoch_time_ms;
     use util::hash::to_hex;
 
+    #[test]
+    fn marf_walk_trie() {
+        let f = TrieFileStorage::new_memory().unwrap();
+        let mut marf = MARF::from_storage(f);
+
+        let block_1 = BlockHeaderHash::from_bytes(&[1u8; 32]).unwrap();
+        let block_2 = BlockHeaderHash::from_bytes(&[2u8; 32]).unwrap();
+
+        marf.begin(&BlockHeaderHash::sentinel(), &block_1).unwrap();
+        marf.insert("foo", MARFValue::from_value("1")).unwrap();
+        marf.insert("bar", MARFValue::from_value("2")).unwrap();
+        marf.commit().unwrap();
+
+        marf.begin(&block_1, &block_2).unwrap();
+        marf.insert("baz", MARFValue::from_value("3")).unwrap();
+        marf.commit().unwrap();
+
+        let walk =
+            |marf: &mut MARF<BlockHeaderHash>, block: &BlockHeaderHash, follow_backptrs: bool| {
+                let mut leaves = vec![];
+                let mut num_nodes = 0;
+                marf.walk_trie(
+                    block,
+                    follow_backptrs,
+                    |path_prefix, node_block, node, _| {
+                        num_nodes += 1;
+                        if let TrieNodeType::Leaf(ref leaf) = node {
+                            let mut path = path_prefix.to_vec();
+                            path.extend_from_slice(&leaf.path);
+                            assert_eq!(path.len(), 32);
+                            leaves.push((node_block.clone(), leaf.data.clone()));
+                        }
+                    },
+                )
+                .unwrap();
+                (leaves, num_nodes)
+            };
+
+        let (leaves, _) = walk(&mut marf, &block_1, true);
+        assert!(leaves.contains(&(block_1.clone(), MARFValue::from_value("1"))));
+        assert!(leaves.contains(&(block_1.clone(), MARFValue::from_value("2"))));
+
+        let (leaves, num_nodes) = walk(&mut marf, &block_2, true);
+        assert!(leaves.contains(&(block_1.clone(), MARFValue::from_value("1"))));
+        assert!(leaves.contains(&(block_1.clone(), MARFValue::from_value("2"))));
+        assert!(leaves.contains(&(block_2.clone(), MARFValue::from_value("3"))));
+
+        let (own_leaves, own_num_nodes) = walk(&mut marf, &block_2, false);
+        assert!(own_leaves.contains(&(block_2.clone(), MARFValue::from_value("3"))));
+        assert!(own_leaves.iter().all(|(block, _)| *block == block_2));
+        assert!(own_num_nodes < num_nodes);
+
+        // no such block
+        let block_3 = BlockHeaderHash::from_bytes(&[3u8; 32]).unwrap();
+        assert!(marf.walk_trie(&block_3, true, |_, _, _, _| {}).is_err());
+    }
+
+    #[test]
+    fn marf_check_tries() {
+        let f = TrieFileStorage::new_memory().unwrap();
+        let mut marf = MARF::from_storage(f);
+        marf.set_node_cache_capacity(0);
+
+        let block_1 = BlockHeaderHash::from_bytes(&[1u8; 32]).unwrap();
+        let block_2 = BlockHeaderHash::from_bytes(&[2u8; 32]).unwrap();
+
+        marf.begin(&BlockHeaderHash::sentinel(), &block_1).unwrap();
+        marf.insert("foo", MARFValue::from_value("1")).unwrap();
+        marf.commit().unwrap();
+
+        marf.begin(&block_1, &block_2).unwrap();
+        marf.insert("bar", MARFValue::from_value("2")).unwrap();
+        marf.commit().unwrap();
+
+        assert_eq!(marf.check_tries(10).unwrap(), vec![]);
+
+        // cut off the end of block 2's trie, as if it had only been partly written
+        marf.sqlite_conn()
+            .execute(
+                "UPDATE marf_data SET data = substr(data, 1, 40) WHERE block_hash = ?",
+                &[&block_2],
+            )
+            .unwrap();
+
+        let bad_tries = marf.check_tries(10).unwrap();
+        assert_eq!(bad_tries.len(), 1);
+        assert_eq!(bad_tries[0].0, block_2);
+
+        // only the most recent trie is checked
+        assert_eq!(marf.check_tries(1).unwrap().len(), 1);
+
+        trie_sql::drop_confirmed_trie(marf.sqlite_conn(), &block_2).unwrap();
+        assert_eq!(marf.check_tries(10).unwrap(), vec![]);
+    }
+
     #[test]
     fn marf_insert_different_leaf_same_block_100() {
         let filename = "/tmp/rust_marf_insert_different_leaf_same_block_100";
@@ -3512,4 +3755,64 @@ mod test {
             assert!(false);
         }
     }
+
+    #[test]
+    fn marf_insert_batch_same_root_hash_as_insert() {
+        let mut m_batch: MARF<BlockHeaderHash> = MARF::from_path(":memory:").unwrap();
+        let mut m_single: MARF<BlockHeaderHash> = MARF::from_path(":memory:").unwrap();
+
+        // a fork: 0 -> 1 -> 2 -> 3, and 1 -> 4
+        let blocks = [
+            (BlockHeaderHash::sentinel(), BlockHeaderHash([0u8; 32])),
+            (BlockHeaderHash([0u8; 32]), BlockHeaderHash([1u8; 32])),
+            (BlockHeaderHash([1u8; 32]), BlockHeaderHash([2u8; 32])),
+            (BlockHeaderHash([2u8; 32]), BlockHeaderHash([3u8; 32])),
+            (BlockHeaderHash([1u8; 32]), BlockHeaderHash([4u8; 32])),
+        ];
+
+        for (i, (parent, block)) in blocks.iter().enumerate() {
+            let mut keys = vec![];
+            let mut values = vec![];
+            for j in 0..64 {
+                // overwrite half of the parent's keys, and add new ones
+                let key = format!("{}::{}", (i / 2) + (j % 2), j);
+                keys.push(key);
+                values.push(MARFValue::from_value(&format!("{}-{}", i, j)));
+            }
+
+            m_batch.begin(parent, block).unwrap();
+            m_batch.insert_batch(&keys, values.clone()).unwrap();
+            let batch_root_hash = m_batch.get_root_hash_at(block).unwrap();
+            m_batch.commit().unwrap();
+
+            m_single.begin(parent, block).unwrap();
+            for (key, value) in keys.iter().zip(values.into_iter()) {
+                m_single.insert(key, value).unwrap();
+            }
+            let single_root_hash = m_single.get_root_hash_at(block).unwrap();
+            m_single.commit().unwrap();
+
+            assert_eq!(batch_root_hash, single_root_hash);
+        }
+
+        for (_, block) in blocks.iter() {
+            assert_eq!(
+                m_batch.get_root_hash_at(block).unwrap(),
+                m_single.get_root_hash_at(block).unwrap()
+            );
+        }
+
+        // proofs still work against the batch-inserted tries
+        let key = "2::5".to_string();
+        let tip = BlockHeaderHash([3u8; 32]);
+        let value = m_batch.get(&tip, &key).unwrap().unwrap();
+        assert_eq!(value, MARFValue::from_value("3-5"));
+        merkle_test_marf_key_value(
+            &mut m_batch.borrow_storage_backend(),
+            &tip,
+            &key,
+            &"3-5".to_string(),
+            None,
+        );
+    }
 }
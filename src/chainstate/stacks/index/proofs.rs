@@ -55,7 +55,10 @@ use chainstate::stacks::index::trie::Trie;
 use chainstate::stacks::index::Error;
 
 use net::{codec::read_next, StacksMessageCodec};
-use util::{hash::to_hex, log};
+use util::{
+    hash::{hex_bytes, to_hex},
+    log,
+};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ProofTriePtr<T: MarfTrieId> {
@@ -248,7 +251,7 @@ impl<T: MarfTrieId> PartialEq for TrieMerkleProofType<T> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct TrieMerkleProof<T: MarfTrieId>(pub Vec<TrieMerkleProofType<T>>);
 
 impl<T: MarfTrieId> Deref for TrieMerkleProof<T> {
@@ -391,6 +394,17 @@ impl<T: MarfTrieId> StacksMessageCodec for TrieMerkleProofType<T> {
     }
 }
 
+impl<T: MarfTrieId> StacksMessageCodec for TrieMerkleProof<T> {
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), ::net::Error> {
+        self.0.consensus_serialize(fd)
+    }
+
+    fn consensus_deserialize<R: Read>(fd: &mut R) -> Result<TrieMerkleProof<T>, ::net::Error> {
+        let proof: Vec<TrieMerkleProofType<T>> = read_next(fd)?;
+        Ok(TrieMerkleProof(proof))
+    }
+}
+
 impl<T: MarfTrieId> TrieMerkleProof<T> {
     pub fn to_hex(&self) -> String {
         let mut marf_proof = vec![];
@@ -399,6 +413,22 @@ impl<T: MarfTrieId> TrieMerkleProof<T> {
         to_hex(&marf_proof)
     }
 
+    /// Decode a proof from the hex string produced by `to_hex()` (e.g. the `proof` field of an
+    /// RPC response).
+    pub fn from_hex(hex_proof: &str) -> Result<TrieMerkleProof<T>, ::net::Error> {
+        let bytes = hex_bytes(hex_proof).map_err(|_| {
+            ::net::Error::DeserializeError("Trie Merkle Proof is not a hex string".into())
+        })?;
+        let mut cursor = io::Cursor::new(&bytes);
+        let proof = TrieMerkleProof::consensus_deserialize(&mut cursor)?;
+        if (cursor.position() as usize) != bytes.len() {
+            return Err(::net::Error::DeserializeError(
+                "Trailing bytes after Trie Merkle Proof".into(),
+            ));
+        }
+        Ok(proof)
+    }
+
     fn make_proof_hashes(
         node: &TrieNodeType,
         all_hashes: &Vec<TrieHash>,
@@ -1377,6 +1407,19 @@ impl<T: MarfTrieId> TrieMerkleProof<T> {
         TrieMerkleProof::<T>::verify_proof(&self.0, &path, &marf_value, root_hash, root_to_block)
     }
 
+    /// Verify this proof of a key/value pair, as inserted with `MARF::insert()`
+    pub fn verify_entry(
+        &self,
+        key: &str,
+        value: &str,
+        root_hash: &TrieHash,
+        root_to_block: &HashMap<TrieHash, T>,
+    ) -> bool {
+        let path = TriePath::from_key(key);
+        let marf_value = MARFValue::from_value(value);
+        self.verify(&path, &marf_value, root_hash, root_to_block)
+    }
+
     /// Walk down the trie pointed to by s until we reach a backptr or a leaf
     fn walk_to_leaf_or_backptr(
         storage: &mut TrieStorageConnection<T>,
@@ -1595,6 +1638,20 @@ impl<T: MarfTrieId> TrieMerkleProof<T> {
     }
 }
 
+/// Verify that `proof` shows `key` mapping to `value` in the MARF whose root hash is
+/// `root_hash`.  This needs no access to the MARF itself -- only the proof and the mapping from
+/// each ancestor trie's root hash to its block, which a light client gets from the block headers
+/// it has already verified.
+pub fn verify_proof<T: MarfTrieId>(
+    root_hash: &TrieHash,
+    key: &str,
+    value: &str,
+    proof: &TrieMerkleProof<T>,
+    root_to_block: &HashMap<TrieHash, T>,
+) -> bool {
+    proof.verify_entry(key, value, root_hash, root_to_block)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -1670,4 +1727,67 @@ mod test {
         let marf_value_1 = MARFValue::from_value(&old_v);
         assert!(proof_1.verify(&triepath_1, &marf_value_1, &root_hash_1, &root_to_block));
     }
+
+    #[test]
+    fn proof_codec_and_standalone_verify() {
+        let mut m = MARF::from_path(":memory:").unwrap();
+
+        let sentinel_block = BlockHeaderHash::sentinel();
+        let block_0 = BlockHeaderHash([0u8; 32]);
+        let block_1 = BlockHeaderHash([1u8; 32]);
+
+        let k1 = "K1".to_string();
+        let v1 = "V1".to_string();
+        let k2 = "K2".to_string();
+        let v2 = "V2".to_string();
+
+        m.begin(&sentinel_block, &block_0).unwrap();
+        m.insert(&k1, MARFValue::from_value(&v1)).unwrap();
+        m.commit().unwrap();
+
+        m.begin(&block_0, &block_1).unwrap();
+        m.insert(&k2, MARFValue::from_value(&v2)).unwrap();
+        let (_, root_hash_1) = Trie::read_root(&mut m.borrow_storage_backend()).unwrap();
+        m.commit().unwrap();
+
+        let root_to_block = m
+            .borrow_storage_backend()
+            .read_root_to_block_table()
+            .unwrap();
+
+        // k1 was inserted in an ancestor block, so its proof crosses a shunt
+        let (value, proof) = m.get_with_proof(&block_1, &k1).unwrap().unwrap();
+        assert_eq!(value, MARFValue::from_value(&v1));
+
+        let decoded: TrieMerkleProof<BlockHeaderHash> =
+            TrieMerkleProof::from_hex(&proof.to_hex()).unwrap();
+        assert_eq!(decoded, proof);
+
+        assert!(verify_proof(
+            &root_hash_1,
+            &k1,
+            &v1,
+            &decoded,
+            &root_to_block
+        ));
+        assert!(!verify_proof(
+            &root_hash_1,
+            &k1,
+            &v2,
+            &decoded,
+            &root_to_block
+        ));
+        assert!(!verify_proof(
+            &root_hash_1,
+            &k2,
+            &v1,
+            &decoded,
+            &root_to_block
+        ));
+
+        assert!(TrieMerkleProof::<BlockHeaderHash>::from_hex("zz").is_err());
+        assert!(
+            TrieMerkleProof::<BlockHeaderHash>::from_hex(&format!("{}00", proof.to_hex())).is_err()
+        );
+    }
 }
@@ -20,7 +20,7 @@ use std::io;
 use std::io::{BufWriter, Cursor, Read, Seek, SeekFrom, Write};
 
 use std::char::from_digit;
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 
@@ -57,6 +57,7 @@ use std::convert::{TryFrom, TryInto};
 
 use chainstate::stacks::index::Error;
 
+use util::db::sqlite_set_wal_mode;
 use util::db::tx_begin_immediate;
 use util::db::tx_busy_handler;
 use util::db::Error as db_error;
@@ -642,6 +643,147 @@ pub struct TrieStorageConnection<'a, T: MarfTrieId> {
     pub test_genesis_block: &'a mut Option<T>,
 }
 
+/// Default number of trie nodes (and, separately, node hashes) kept in RAM by a TrieFileStorage
+pub const TRIE_NODE_CACHE_DEFAULT_CAPACITY: usize = 16384;
+
+/// Least-recently-used cache of trie data read from committed tries.
+/// Entries are keyed by the block hash of the trie and the node's offset within it.  A confirmed
+/// trie never changes once it is committed, so entries never go stale -- only unconfirmed tries
+/// are rewritten in place, and the cache is cleared whenever that happens.
+struct TrieLRUCache<V: Clone> {
+    capacity: usize,
+    next_tick: u64,
+    entries: HashMap<([u8; 32], u32), (V, u64)>,
+    lru: BTreeMap<u64, ([u8; 32], u32)>,
+}
+
+impl<V: Clone> TrieLRUCache<V> {
+    fn new(capacity: usize) -> TrieLRUCache<V> {
+        TrieLRUCache {
+            capacity: capacity,
+            next_tick: 0,
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+        }
+    }
+
+    fn get(&mut self, key: &([u8; 32], u32)) -> Option<V> {
+        let tick = self.next_tick;
+        match self.entries.get_mut(key) {
+            Some(&mut (ref value, ref mut last_used)) => {
+                self.lru.remove(last_used);
+                self.lru.insert(tick, key.clone());
+                *last_used = tick;
+                self.next_tick += 1;
+                Some(value.clone())
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&mut self, key: ([u8; 32], u32), value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Some((_, last_used)) = self.entries.remove(&key) {
+            self.lru.remove(&last_used);
+        }
+        while self.entries.len() >= self.capacity {
+            let oldest_tick = match self.lru.keys().next() {
+                Some(tick) => *tick,
+                None => break,
+            };
+            if let Some(oldest_key) = self.lru.remove(&oldest_tick) {
+                self.entries.remove(&oldest_key);
+            }
+        }
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        self.lru.insert(tick, key.clone());
+        self.entries.insert(key, (value, tick));
+    }
+
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > self.capacity {
+            let oldest_tick = match self.lru.keys().next() {
+                Some(tick) => *tick,
+                None => break,
+            };
+            if let Some(oldest_key) = self.lru.remove(&oldest_tick) {
+                self.entries.remove(&oldest_key);
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.lru.clear();
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// Cache of the nodes and node hashes read from committed tries, so that walking the same
+/// intermediate nodes again (e.g. for each key looked up while processing a block) does not go
+/// back to the database.
+struct TrieNodeCache {
+    nodes: TrieLRUCache<(TrieNodeType, TrieHash)>,
+    hashes: TrieLRUCache<TrieHash>,
+    hits: u64,
+    misses: u64,
+}
+
+impl TrieNodeCache {
+    fn new(capacity: usize) -> TrieNodeCache {
+        TrieNodeCache {
+            nodes: TrieLRUCache::new(capacity),
+            hashes: TrieLRUCache::new(capacity),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.nodes.capacity
+    }
+
+    fn set_capacity(&mut self, capacity: usize) {
+        self.nodes.set_capacity(capacity);
+        self.hashes.set_capacity(capacity);
+    }
+
+    fn get_node(&mut self, key: &([u8; 32], u32)) -> Option<(TrieNodeType, TrieHash)> {
+        let res = self.nodes.get(key);
+        if res.is_some() {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+        res
+    }
+
+    fn get_hash(&mut self, key: &([u8; 32], u32)) -> Option<TrieHash> {
+        let res = match self.nodes.get(key) {
+            Some((_, hash)) => Some(hash),
+            None => self.hashes.get(key),
+        };
+        if res.is_some() {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+        res
+    }
+
+    fn clear(&mut self) {
+        self.nodes.clear();
+        self.hashes.clear();
+    }
+}
+
 ///
 ///  TrieStorageTransientData holds all the data that _isn't_ committed
 ///   to the underlying SQL storage. Used internally to simplify
@@ -668,6 +810,8 @@ struct TrieStorageTransientData<T: MarfTrieId> {
 
     block_hash_cache: HashMap<u32, T>,
 
+    node_cache: TrieNodeCache,
+
     readonly: bool,
     unconfirmed: bool,
 }
@@ -760,6 +904,9 @@ impl<T: MarfTrieId> TrieFileStorage<T> {
 
         let mut db = Connection::open_with_flags(db_path, open_flags)?;
         db.busy_handler(Some(tx_busy_handler))?;
+        if !readonly && db_path != ":memory:" {
+            sqlite_set_wal_mode(&db)?;
+        }
 
         let db_path = db_path.to_string();
 
@@ -790,6 +937,8 @@ impl<T: MarfTrieId> TrieFileStorage<T> {
                 trie_ancestor_hash_bytes_cache: None,
                 block_hash_cache: HashMap::new(),
 
+                node_cache: TrieNodeCache::new(TRIE_NODE_CACHE_DEFAULT_CAPACITY),
+
                 readonly: readonly,
                 unconfirmed: unconfirmed,
             },
@@ -856,6 +1005,8 @@ impl<T: MarfTrieId> TrieFileStorage<T> {
                 trie_ancestor_hash_bytes_cache: None,
                 block_hash_cache: self.data.block_hash_cache.clone(),
 
+                node_cache: TrieNodeCache::new(self.data.node_cache.capacity()),
+
                 readonly: true,
                 unconfirmed: true,
             },
@@ -904,6 +1055,8 @@ impl<'a, T: MarfTrieId> TrieStorageTransaction<'a, T> {
                 trie_ancestor_hash_bytes_cache: None,
                 block_hash_cache: HashMap::new(),
 
+                node_cache: TrieNodeCache::new(self.data.node_cache.capacity()),
+
                 readonly: true,
                 unconfirmed: true,
             },
@@ -1126,6 +1279,7 @@ impl<'a, T: MarfTrieId> TrieStorageTransaction<'a, T> {
         self.data.cur_block_id = None;
         self.data.last_extended = None;
         self.clear_cached_ancestor_hashes_bytes();
+        self.data.node_cache.clear();
 
         Ok(())
     }
@@ -1511,14 +1665,60 @@ impl<'a, T: MarfTrieId> TrieStorageConnection<'a, T> {
             }
         }
 
-        // some other block or ptr, or cache miss
-        match self.data.cur_block_id {
-            Some(block_id) => trie_sql::get_node_hash_bytes(&self.db, block_id, ptr),
+        // some other block or ptr
+        let block_id = match self.data.cur_block_id {
+            Some(block_id) => block_id,
             None => {
                 error!("Not found (no file is open)");
-                Err(Error::NotFoundError)
+                return Err(Error::NotFoundError);
             }
+        };
+
+        let cache_key = self.node_cache_key(ptr);
+        if let Some(ref key) = cache_key {
+            if let Some(hash) = self.data.node_cache.get_hash(key) {
+                return Ok(hash);
+            }
+        }
+
+        let hash = trie_sql::get_node_hash_bytes(&self.db, block_id, ptr)?;
+        if let Some(key) = cache_key {
+            self.data.node_cache.hashes.insert(key, hash.clone());
+        }
+        Ok(hash)
+    }
+
+    /// Key for the node at `ptr` in the currently-open trie in the node cache, or None if the
+    /// currently-open trie is not cacheable.  Unconfirmed tries can be rewritten under the same
+    /// block hash, so nothing is cached by storage that can read them.
+    fn node_cache_key(&self, ptr: &TriePtr) -> Option<([u8; 32], u32)> {
+        if self.data.unconfirmed
+            || self.data.node_cache.capacity() == 0
+            || self.data.cur_block == T::sentinel()
+        {
+            return None;
         }
+        Some((self.data.cur_block.clone().to_bytes(), ptr.ptr()))
+    }
+
+    /// Set the maximum number of nodes (and, separately, node hashes) to keep in the node cache.
+    /// A capacity of 0 disables the cache.
+    pub fn set_node_cache_capacity(&mut self, capacity: usize) {
+        self.data.node_cache.set_capacity(capacity);
+    }
+
+    /// Number of nodes and node hashes currently in the node cache
+    pub fn node_cache_len(&self) -> usize {
+        self.data.node_cache.nodes.len() + self.data.node_cache.hashes.len()
+    }
+
+    /// Get and reset the node cache's (hits, misses)
+    pub fn node_cache_stats(&mut self) -> (u64, u64) {
+        let hits = self.data.node_cache.hits;
+        let misses = self.data.node_cache.misses;
+        self.data.node_cache.hits = 0;
+        self.data.node_cache.misses = 0;
+        (hits, misses)
     }
 
     // NOTE: ptr will not be treated as a backptr
@@ -1544,13 +1744,29 @@ impl<'a, T: MarfTrieId> TrieStorageConnection<'a, T> {
         }
 
         // some other block
-        match self.data.cur_block_id {
-            Some(id) => trie_sql::read_node_type(&self.db, id, &clear_ptr),
+        let block_id = match self.data.cur_block_id {
+            Some(id) => id,
             None => {
                 error!("Not found (no file is open)");
-                Err(Error::NotFoundError)
+                return Err(Error::NotFoundError);
+            }
+        };
+
+        let cache_key = self.node_cache_key(&clear_ptr);
+        if let Some(ref key) = cache_key {
+            if let Some(node_and_hash) = self.data.node_cache.get_node(key) {
+                return Ok(node_and_hash);
             }
         }
+
+        let (node, hash) = trie_sql::read_node_type(&self.db, block_id, &clear_ptr)?;
+        if let Some(key) = cache_key {
+            self.data
+                .node_cache
+                .nodes
+                .insert(key, (node.clone(), hash.clone()));
+        }
+        Ok((node, hash))
     }
 
     pub fn write_nodetype(
@@ -1739,6 +1955,85 @@ pub mod test {
         return true;
     }
 
+    #[test]
+    fn trie_lru_cache_evicts_least_recently_used() {
+        let mut cache: TrieLRUCache<u32> = TrieLRUCache::new(2);
+        let k1 = ([1u8; 32], 1);
+        let k2 = ([2u8; 32], 2);
+        let k3 = ([3u8; 32], 3);
+
+        cache.insert(k1.clone(), 1);
+        cache.insert(k2.clone(), 2);
+        assert_eq!(cache.get(&k1), Some(1));
+
+        // k2 is now the least-recently used
+        cache.insert(k3.clone(), 3);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&k2), None);
+        assert_eq!(cache.get(&k1), Some(1));
+        assert_eq!(cache.get(&k3), Some(3));
+
+        // re-inserting does not grow the cache
+        cache.insert(k3.clone(), 4);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&k3), Some(4));
+
+        cache.set_capacity(1);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(&k3), Some(4));
+
+        cache.set_capacity(0);
+        cache.insert(k1.clone(), 1);
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn marf_node_cache() {
+        let mut marf = MARF::from_path(":memory:").unwrap();
+        let mut block_header = BlockHeaderHash::sentinel();
+        let mut keys = vec![];
+
+        for i in 0..4u8 {
+            let next_block_header = BlockHeaderHash([i; 32]);
+            marf.begin(&block_header, &next_block_header).unwrap();
+            for j in 0..32 {
+                let key = format!("{}::{}", i, j);
+                marf.insert(&key, MARFValue::from_value(&key)).unwrap();
+                keys.push(key);
+            }
+            marf.commit().unwrap();
+            block_header = next_block_header;
+        }
+
+        let read_all = |marf: &mut MARF<BlockHeaderHash>| {
+            for key in keys.iter() {
+                assert_eq!(
+                    marf.get(&block_header, key).unwrap().unwrap(),
+                    MARFValue::from_value(key)
+                );
+            }
+        };
+
+        // first pass fills the cache
+        read_all(&mut marf);
+        let (_, misses) = marf.node_cache_stats();
+        assert!(misses > 0);
+        assert!(marf.borrow_storage_backend().node_cache_len() > 0);
+
+        // second pass is served from it
+        read_all(&mut marf);
+        let (hits, misses) = marf.node_cache_stats();
+        assert!(hits > 0);
+        assert_eq!(misses, 0);
+
+        // disabling the cache drops its contents, and reads still work
+        marf.set_node_cache_capacity(0);
+        assert_eq!(marf.borrow_storage_backend().node_cache_len(), 0);
+        read_all(&mut marf);
+        assert_eq!(marf.node_cache_stats(), (0, 0));
+        assert_eq!(marf.borrow_storage_backend().node_cache_len(), 0);
+    }
+
     fn load_store_trie_m_n_same(m: u64, n: u64, same: bool) {
         let test_name = format!(
             "/tmp/load_store_trie_{}_{}_{}",
@@ -884,6 +884,97 @@ impl Trie {
         Trie::recalculate_root_hash(storage, cursor, true)
     }
 
+    /// Unwind a TrieCursor to point each node on its path at the (possibly moved) child below it,
+    /// but without recalculating any hashes.  Used when inserting a batch of keys, where each
+    /// node's hash is calculated just once by `rehash_trie()` after the last insert.
+    pub fn update_root_ptrs<T: MarfTrieId>(
+        storage: &mut TrieStorageConnection<T>,
+        cursor: &TrieCursor<T>,
+    ) -> Result<(), Error> {
+        assert!(cursor.node_ptrs.len() > 0);
+
+        let mut ptrs = cursor.node_ptrs.clone();
+        let mut child_ptr = ptrs.pop().unwrap();
+
+        while let Some(ptr) = ptrs.pop() {
+            if is_backptr(ptr.id()) {
+                // not altered -- only queued as part of walking a backptr skiplist
+                continue;
+            }
+
+            let (mut node, cur_hash) = storage.read_nodetype(&ptr)?;
+            assert!(!node.is_leaf());
+
+            // this child_ptr _must_ be in the node.
+            let updated = node.replace(&child_ptr);
+            if !updated {
+                trace!(
+                    "FAILED TO UPDATE {:?} WITH {:?}: {:?}",
+                    &node,
+                    &child_ptr,
+                    cursor
+                );
+                assert!(updated);
+            }
+
+            // hash is stale, but will be recalculated by rehash_trie()
+            storage.write_nodetype(ptr.ptr(), &node, cur_hash)?;
+
+            child_ptr = ptr;
+            child_ptr.id = clear_backptr(child_ptr.id);
+        }
+
+        // must be at the root
+        assert_eq!(child_ptr, storage.root_trieptr());
+        Ok(())
+    }
+
+    /// Recalculate the hash of every node in the trie being extended, children before parents,
+    /// and mix the ancestor root hashes into the root node's hash.  The result is the same as
+    /// calling `update_root_hash()` after every insert, but each node is hashed only once.
+    /// s must point to the block that contains the trie's root.
+    /// Returns the new root hash.
+    pub fn rehash_trie<T: MarfTrieId>(
+        storage: &mut TrieStorageConnection<T>,
+    ) -> Result<TrieHash, Error> {
+        let root_ptr = storage.root_trieptr();
+        let mut root_hash = None;
+
+        // (ptr, whether its children have been rehashed)
+        let mut stack = vec![(root_ptr.clone(), false)];
+        while let Some((ptr, children_rehashed)) = stack.pop() {
+            let (node, _) = storage.read_nodetype(&ptr)?;
+            if node.is_leaf() {
+                // leaf hashes only depend on the leaf
+                continue;
+            }
+
+            if !children_rehashed {
+                stack.push((ptr, true));
+                for child_ptr in node.ptrs().iter() {
+                    if child_ptr.id() != TrieNodeID::Empty as u8 && !is_backptr(child_ptr.id()) {
+                        stack.push((child_ptr.clone(), false));
+                    }
+                }
+                continue;
+            }
+
+            let content_hash = get_nodetype_hash(storage, &node)?;
+            let h = if ptr.ptr() == root_ptr.ptr() {
+                let h = Trie::get_trie_root_hash(storage, &content_hash)?;
+                debug!("Next root hash is {} (rehashed trie)", h);
+                root_hash = Some(h.clone());
+                h
+            } else {
+                content_hash
+            };
+
+            storage.write_nodetype(ptr.ptr(), &node, h)?;
+        }
+
+        root_hash.ok_or_else(|| Error::CorruptionError("Root node is a leaf".to_string()))
+    }
+
     pub fn update_root_node_hash<T: MarfTrieId>(
         storage: &mut TrieStorageConnection<T>,
         cursor: &TrieCursor<T>,
@@ -368,6 +368,40 @@ pub fn lock_bhh_for_extension<T: MarfTrieId>(
     Ok(true)
 }
 
+/// Get the block IDs and hashes of the `count` most recently confirmed tries, oldest first
+pub fn get_latest_confirmed_block_hashes<T: MarfTrieId>(
+    conn: &Connection,
+    count: u32,
+) -> Result<Vec<(u32, T)>, Error> {
+    let mut s = conn.prepare(
+        "SELECT block_id, block_hash FROM marf_data WHERE unconfirmed = 0 ORDER BY block_id DESC LIMIT ?",
+    )?;
+    let rows = s.query_and_then(&[count], |row| -> Result<(u32, T), Error> {
+        Ok((row.get("block_id"), row.get("block_hash")))
+    })?;
+    let mut block_hashes = rows.collect::<Result<Vec<_>, Error>>()?;
+    block_hashes.reverse();
+    Ok(block_hashes)
+}
+
+/// Delete a confirmed trie.  No other trie may have back-pointers into it.
+pub fn drop_confirmed_trie<T: MarfTrieId>(conn: &Connection, bhh: &T) -> Result<(), Error> {
+    conn.execute(
+        "DELETE FROM marf_data WHERE block_hash = ? AND unconfirmed = 0",
+        &[bhh],
+    )?;
+    Ok(())
+}
+
+pub fn count_locks(conn: &Connection) -> Result<u32, Error> {
+    let result = conn.query_row(
+        "SELECT COUNT(*) AS count FROM block_extension_locks",
+        NO_PARAMS,
+        |row| row.get("count"),
+    )?;
+    Ok(result)
+}
+
 pub fn count_blocks(conn: &Connection) -> Result<u32, Error> {
     let result = conn.query_row(
         "SELECT IFNULL(MAX(block_id), 0) AS count FROM marf_data WHERE unconfirmed = 0",
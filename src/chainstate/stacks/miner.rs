@@ -20,10 +20,12 @@ use chainstate::stacks::events::StacksTransactionReceipt;
 use chainstate::stacks::index::TrieHash;
 use chainstate::stacks::Error;
 use chainstate::stacks::*;
+use std::cmp;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fs;
 use std::mem;
+use std::time::Instant;
 
 use net::codec::{read_next, write_next};
 use net::Error as net_error;
@@ -50,6 +52,14 @@ use core::*;
 
 use vm::database::BurnStateDB;
 
+/// Most mempool candidates the miner executes in its scratch block, to rank them by cost
+pub const MAX_SIMULATED_CANDIDATES: usize = 1000;
+/// Most time, in milliseconds, the miner spends executing candidates in its scratch block
+pub const MAX_SIMULATION_TIME_MS: u128 = 5_000;
+/// The miner stops executing candidates in its scratch block once they use up this many block
+/// budgets (or epoch sizes), since no block could hold them all
+pub const SIMULATION_BUDGET_MULTIPLE: u64 = 2;
+
 ///
 ///    Independent structure for building microblocks:
 ///       StacksBlockBuilder cannot be used, since microblocks should only be broadcasted
@@ -112,6 +122,49 @@ impl<'a> StacksMicroblockBuilder<'a> {
         })
     }
 
+    /// Resume a microblock stream off of the given anchored block, after the given microblocks
+    /// (in sequence order) that this miner already produced.  Their transactions are replayed,
+    /// so the next microblock builds on the state they leave behind and never repeats them.
+    pub fn resume(
+        anchor_block: BlockHeaderHash,
+        anchor_block_consensus_hash: ConsensusHash,
+        chainstate: &'a mut StacksChainState,
+        burn_dbconn: &'a dyn BurnStateDB,
+        initial_cost: ExecutionCost,
+        bytes_so_far: u64,
+        prev_microblocks: &Vec<StacksMicroblock>,
+    ) -> Result<StacksMicroblockBuilder<'a>, Error> {
+        let mut builder = StacksMicroblockBuilder::new(
+            anchor_block,
+            anchor_block_consensus_hash,
+            chainstate,
+            burn_dbconn,
+            initial_cost,
+            bytes_so_far,
+        )?;
+
+        if let Some(last_microblock) = prev_microblocks.last() {
+            let clarity_tx = builder
+                .clarity_tx
+                .as_mut()
+                .expect("BUG: microblock builder is closed");
+            StacksChainState::process_microblocks_transactions(clarity_tx, prev_microblocks)
+                .map_err(|(e, _)| e)?;
+
+            let considered = builder
+                .considered
+                .as_mut()
+                .expect("BUG: microblock builder is closed");
+            for tx in prev_microblocks.iter().flat_map(|mblock| mblock.txs.iter()) {
+                considered.insert(tx.txid());
+                builder.bytes_so_far += tx.tx_len();
+            }
+            builder.prev_microblock_header = Some(last_microblock.header.clone());
+        }
+
+        Ok(builder)
+    }
+
     fn make_next_microblock(
         &mut self,
         txs_to_broadcast: Vec<StacksTransaction>,
@@ -275,7 +328,7 @@ impl<'a> StacksMicroblockBuilder<'a> {
 
         let mut bytes_so_far = self.bytes_so_far;
 
-        let result = mem_pool.iterate_candidates(
+        let result = mem_pool.iterate_candidates_by_fee_rate(
             &self.anchor_block_consensus_hash,
             &self.anchor_block,
             self.anchor_block_height,
@@ -374,6 +427,7 @@ impl StacksBlockBuilder {
             miner_privkey: StacksPrivateKey::new(), // caller should overwrite this, or refrain from mining microblocks
             miner_payouts: None,
             miner_id: miner_id,
+            burn_transfer_ops: vec![],
         }
     }
 
@@ -628,11 +682,28 @@ impl StacksBlockBuilder {
     pub fn mine_anchored_block<'a>(&mut self, clarity_tx: &mut ClarityTx<'a>) -> StacksBlock {
         assert!(!self.anchored_done);
 
+        // materialize STX transfers sent through the burnchain
+        StacksChainState::process_transfer_stx_ops(clarity_tx, &self.burn_transfer_ops);
+
+        // remember which anchored block signs microblocks with this key
+        StacksChainState::insert_microblock_pubkey_hash(
+            clarity_tx,
+            &self.header.microblock_pubkey_hash,
+            self.header.total_work.work,
+        )
+        .expect("FATAL: failed to record microblock public key hash");
+
         // add miner payments
-        if let Some(ref mature_miner_rewards) = self.miner_payouts {
+        if let Some((ref mut mature_miner_rewards, ref mature_miner_rewards_info)) =
+            self.miner_payouts
+        {
             // grant in order by miner, then users
-            StacksChainState::process_matured_miner_rewards(clarity_tx, mature_miner_rewards)
-                .expect("FATAL: failed to process miner rewards");
+            StacksChainState::process_matured_miner_rewards(
+                clarity_tx,
+                mature_miner_rewards,
+                mature_miner_rewards_info,
+            )
+            .expect("FATAL: failed to process miner rewards");
         }
 
         let txid_vecs = self
@@ -742,9 +813,17 @@ impl StacksBlockBuilder {
     ) -> Result<ClarityTx<'a>, Error> {
         // find matured miner rewards, so we can grant them within the Clarity DB tx.
         let matured_miner_rewards_opt = {
-            let mut tx = chainstate.headers_tx_begin()?;
-            StacksChainState::find_mature_miner_rewards(&mut tx, &self.chain_tip, None)?
-                .map(|(rewards, _)| rewards)
+            let epoch_id = StacksChainState::get_child_epoch(
+                chainstate.headers_db(),
+                burn_dbconn,
+                &self.chain_tip,
+            );
+            StacksChainState::find_mature_miner_rewards(
+                &mut chainstate.index_conn(),
+                &self.chain_tip,
+                epoch_id,
+                None,
+            )?
         };
 
         self.miner_payouts = matured_miner_rewards_opt;
@@ -932,6 +1011,115 @@ impl StacksBlockBuilder {
         Ok(builder)
     }
 
+    /// Execute the given candidate transactions, in order, in a scratch block built off of the
+    /// same parent as the given builder.  The scratch block is thrown away afterwards.  Stops
+    /// after MAX_SIMULATED_CANDIDATES candidates or MAX_SIMULATION_TIME_MS milliseconds, or once
+    /// the executed candidates use up SIMULATION_BUDGET_MULTIPLE times the block budget.
+    /// Returns the execution cost of each candidate that could be mined, by txid, and how many
+    /// of the candidates were executed.
+    fn simulate_candidates(
+        builder: &StacksBlockBuilder,
+        chainstate_handle: &StacksChainState,
+        burn_dbconn: &dyn BurnStateDB,
+        coinbase_tx: &StacksTransaction,
+        candidates: &[MemPoolTxInfo],
+        execution_budget: &ExecutionCost,
+    ) -> Result<(HashMap<Txid, ExecutionCost>, usize), Error> {
+        let mut costs = HashMap::new();
+        if candidates.len() == 0 {
+            return Ok((costs, 0));
+        }
+
+        let mut cost_bound = execution_budget.clone();
+        if cost_bound.multiply(SIMULATION_BUDGET_MULTIPLE).is_err() {
+            cost_bound = ExecutionCost::max_value();
+        }
+        let len_bound = (MAX_EPOCH_SIZE as u64) * SIMULATION_BUDGET_MULTIPLE;
+        let mut len_so_far = 0;
+        let mut num_simulated = 0;
+        let start = Instant::now();
+
+        let mut scratch_builder = builder.clone();
+        let (mut scratch_chainstate, _) =
+            chainstate_handle.reopen_limited(ExecutionCost::max_value())?;
+        let mut scratch_tx = scratch_builder.epoch_begin(&mut scratch_chainstate, burn_dbconn)?;
+        StacksChainState::process_transaction(&mut scratch_tx, coinbase_tx, true)?;
+
+        for txinfo in candidates.iter().take(MAX_SIMULATED_CANDIDATES) {
+            if start.elapsed().as_millis() >= MAX_SIMULATION_TIME_MS {
+                debug!(
+                    "Out of time to simulate candidates after {} of {}",
+                    num_simulated,
+                    candidates.len()
+                );
+                break;
+            }
+            if scratch_tx.cost_so_far().exceeds(&cost_bound) || len_so_far > len_bound {
+                debug!(
+                    "Simulated candidates exceed the block budget after {} of {}",
+                    num_simulated,
+                    candidates.len()
+                );
+                break;
+            }
+
+            num_simulated += 1;
+            let cost_before = scratch_tx.cost_so_far();
+            match StacksChainState::process_transaction(&mut scratch_tx, &txinfo.tx, true) {
+                Ok(_) => {
+                    let mut cost = scratch_tx.cost_so_far();
+                    cost.sub(&cost_before)
+                        .expect("BUG: block cost decreased while executing a transaction");
+                    costs.insert(txinfo.tx.txid(), cost);
+                    len_so_far += txinfo.metadata.len;
+                }
+                Err(e) => {
+                    test_debug!(
+                        "Candidate tx {} failed in scratch block: {:?}",
+                        &txinfo.tx.txid(),
+                        &e
+                    );
+                }
+            }
+        }
+
+        scratch_tx.rollback_block();
+        Ok((costs, num_simulated))
+    }
+
+    /// How much a transaction pays per part-per-million of the block budget it uses up (scaled up
+    /// by a million), measured along the dimension it uses the most of.  Its length counts
+    /// against the epoch size.
+    fn fee_per_budget_share(
+        fee: u64,
+        cost: &ExecutionCost,
+        tx_len: u64,
+        budget: &ExecutionCost,
+    ) -> u64 {
+        let dimensions = [
+            (cost.runtime, budget.runtime),
+            (cost.write_length, budget.write_length),
+            (cost.write_count, budget.write_count),
+            (cost.read_length, budget.read_length),
+            (cost.read_count, budget.read_count),
+            (tx_len, MAX_EPOCH_SIZE as u64),
+        ];
+        let share = dimensions
+            .iter()
+            .map(|(used, limit)| {
+                if *limit == 0 {
+                    0
+                } else {
+                    (*used as u128) * 1_000_000 / (*limit as u128)
+                }
+            })
+            .max()
+            .unwrap_or(0);
+
+        let fee_per_share = (fee as u128) * 1_000_000 / cmp::max(share, 1);
+        cmp::min(fee_per_share, u64::max_value() as u128) as u64
+    }
+
     /// Given access to the mempool, mine an anchored block with no more than the given execution cost.
     ///   returns the assembled block, and the consumed execution budget.
     pub fn build_anchored_block(
@@ -943,6 +1131,7 @@ impl StacksBlockBuilder {
         proof: VRFProof, // proof over the burnchain's last seed
         pubkey_hash: Hash160,
         coinbase_tx: &StacksTransaction,
+        burn_transfer_ops: &Vec<TransferStxOp>, // STX transfers sent through the burnchain since the parent
         execution_budget: ExecutionCost,
     ) -> Result<(StacksBlock, ExecutionCost, u64), Error> {
         if let TransactionPayload::Coinbase(..) = coinbase_tx.payload {
@@ -972,81 +1161,112 @@ impl StacksBlockBuilder {
             total_burn,
             pubkey_hash,
         )?;
+        builder.burn_transfer_ops = burn_transfer_ops.clone();
 
-        let mut epoch_tx = builder.epoch_begin(&mut chainstate, burn_dbconn)?;
-        builder.try_mine_tx(&mut epoch_tx, coinbase_tx)?;
-
-        let mut considered = HashSet::new(); // txids of all transactions we looked at
-        let mut mined_origin_nonces: HashMap<StacksAddress, u64> = HashMap::new(); // map addrs of mined transaction origins to the nonces we used
-        let mut mined_sponsor_nonces: HashMap<StacksAddress, u64> = HashMap::new(); // map addrs of mined transaction sponsors to the nonces we used
-
-        let result = mempool.iterate_candidates(
+        let mut candidates = vec![];
+        mempool.iterate_candidates_by_fee_rate::<_, Error>(
             &tip_consensus_hash,
             &tip_block_hash,
             tip_height,
             &mut header_reader_chainstate,
             |available_txs| {
-                for txinfo in available_txs.into_iter() {
-                    // skip transactions early if we can
-                    if considered.contains(&txinfo.tx.txid()) {
-                        continue;
-                    }
-                    if let Some(nonce) = mined_origin_nonces.get(&txinfo.tx.origin_address()) {
-                        if *nonce >= txinfo.tx.get_origin_nonce() {
-                            continue;
-                        }
-                    }
-                    if let Some(sponsor_addr) = txinfo.tx.sponsor_address() {
-                        if let Some(nonce) = mined_sponsor_nonces.get(&sponsor_addr) {
-                            if let Some(sponsor_nonce) = txinfo.tx.get_sponsor_nonce() {
-                                if *nonce >= sponsor_nonce {
-                                    continue;
-                                }
-                            }
-                        }
-                    }
+                candidates.extend(available_txs);
+                Ok(())
+            },
+        )?;
 
-                    considered.insert(txinfo.tx.txid());
+        // learn what each candidate costs to execute, so the ones that pay the most per unit of
+        // block budget can go first
+        let (costs, num_simulated) = StacksBlockBuilder::simulate_candidates(
+            &builder,
+            chainstate_handle,
+            burn_dbconn,
+            coinbase_tx,
+            &candidates,
+            &execution_budget,
+        )?;
+        // the candidates we ran out of time or budget to simulate go last, in fee rate order
+        let unsimulated = candidates.split_off(num_simulated);
+        let mut considered = HashSet::new(); // txids of all transactions we looked at
+        let mut candidates = MemPoolDB::order_by_priority(
+            candidates
+                .into_iter()
+                .filter(|txinfo| {
+                    costs.contains_key(&txinfo.tx.txid()) && considered.insert(txinfo.tx.txid())
+                })
+                .collect(),
+            |txinfo| {
+                StacksBlockBuilder::fee_per_budget_share(
+                    txinfo.tx.get_fee_rate(),
+                    &costs[&txinfo.tx.txid()],
+                    txinfo.metadata.len,
+                    &execution_budget,
+                )
+            },
+        );
+        candidates.extend(
+            unsimulated
+                .into_iter()
+                .filter(|txinfo| considered.insert(txinfo.tx.txid())),
+        );
 
-                    match builder.try_mine_tx_with_len(
-                        &mut epoch_tx,
-                        &txinfo.tx,
-                        txinfo.metadata.len,
-                    ) {
-                        Ok(_) => {}
-                        Err(Error::BlockTooBigError) => {
-                            // done mining -- our execution budget is exceeded.
-                            // Make the block from the transactions we did manage to get
-                            debug!("Block budget exceeded on tx {}", &txinfo.tx.txid());
-                        }
-                        Err(Error::InvalidStacksTransaction(_, true)) => {
-                            // if we have an invalid transaction that was quietly ignored, don't warn here either
-                            continue;
-                        }
-                        Err(e) => {
-                            warn!("Failed to apply tx {}: {:?}", &txinfo.tx.txid(), &e);
+        let mut epoch_tx = builder.epoch_begin(&mut chainstate, burn_dbconn)?;
+        builder.try_mine_tx(&mut epoch_tx, coinbase_tx)?;
+
+        let mut mined_origin_nonces: HashMap<StacksAddress, u64> = HashMap::new(); // map addrs of mined transaction origins to the nonces we used
+        let mut mined_sponsor_nonces: HashMap<StacksAddress, u64> = HashMap::new(); // map addrs of mined transaction sponsors to the nonces we used
+
+        for txinfo in candidates.into_iter() {
+            // skip transactions early if we can
+            if let Some(nonce) = mined_origin_nonces.get(&txinfo.tx.origin_address()) {
+                if *nonce >= txinfo.tx.get_origin_nonce() {
+                    continue;
+                }
+            }
+            if let Some(sponsor_addr) = txinfo.tx.sponsor_address() {
+                if let Some(nonce) = mined_sponsor_nonces.get(&sponsor_addr) {
+                    if let Some(sponsor_nonce) = txinfo.tx.get_sponsor_nonce() {
+                        if *nonce >= sponsor_nonce {
                             continue;
                         }
                     }
+                }
+            }
 
-                    mined_origin_nonces
-                        .insert(txinfo.tx.origin_address(), txinfo.tx.get_origin_nonce());
-                    if let (Some(sponsor_addr), Some(sponsor_nonce)) =
-                        (txinfo.tx.sponsor_address(), txinfo.tx.get_sponsor_nonce())
-                    {
-                        mined_sponsor_nonces.insert(sponsor_addr, sponsor_nonce);
-                    }
+            // don't bother executing a transaction that won't fit in what's left of the budget
+            if let Some(cost) = costs.get(&txinfo.tx.txid()) {
+                let mut cost_after = epoch_tx.cost_so_far();
+                if cost_after.add(cost).is_err() || cost_after.exceeds(&execution_budget) {
+                    debug!(
+                        "Skip tx {}: it would exceed the block budget",
+                        &txinfo.tx.txid()
+                    );
+                    continue;
                 }
-                Ok(())
-            },
-        );
+            }
 
-        match result {
-            Ok(_) => {}
-            Err(e) => {
-                warn!("Failure building block: {}", e);
-                epoch_tx.rollback_block();
-                return Err(e);
+            match builder.try_mine_tx_with_len(&mut epoch_tx, &txinfo.tx, txinfo.metadata.len) {
+                Ok(_) => {}
+                Err(Error::BlockTooBigError) => {
+                    // this transaction doesn't fit, but a cheaper one might
+                    debug!("Block budget exceeded on tx {}", &txinfo.tx.txid());
+                    continue;
+                }
+                Err(Error::InvalidStacksTransaction(_, true)) => {
+                    // if we have an invalid transaction that was quietly ignored, don't warn here either
+                    continue;
+                }
+                Err(e) => {
+                    warn!("Failed to apply tx {}: {:?}", &txinfo.tx.txid(), &e);
+                    continue;
+                }
+            }
+
+            mined_origin_nonces.insert(txinfo.tx.origin_address(), txinfo.tx.get_origin_nonce());
+            if let (Some(sponsor_addr), Some(sponsor_nonce)) =
+                (txinfo.tx.sponsor_address(), txinfo.tx.get_sponsor_nonce())
+            {
+                mined_sponsor_nonces.insert(sponsor_addr, sponsor_nonce);
             }
         }
 
@@ -5656,6 +5876,7 @@ pub mod test {
                         vrf_proof,
                         Hash160([tenure_id as u8; 20]),
                         &coinbase_tx,
+                        &vec![],
                         ExecutionCost::max_value(),
                     )
                     .unwrap();
@@ -5776,6 +5997,7 @@ pub mod test {
                         vrf_proof,
                         Hash160([tenure_id as u8; 20]),
                         &coinbase_tx,
+                        &vec![],
                         ExecutionCost::max_value(),
                     )
                     .unwrap();
@@ -5931,6 +6153,7 @@ pub mod test {
                         vrf_proof,
                         Hash160([tenure_id as u8; 20]),
                         &coinbase_tx,
+                        &vec![],
                         ExecutionCost::max_value(),
                     )
                     .unwrap();
@@ -6143,6 +6366,7 @@ pub mod test {
                         vrf_proof,
                         Hash160([tenure_id as u8; 20]),
                         &coinbase_tx,
+                        &vec![],
                         execution_cost,
                     )
                     .unwrap();
@@ -6171,6 +6395,51 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn test_fee_per_budget_share() {
+        let budget = ExecutionCost {
+            write_length: 1000,
+            write_count: 1000,
+            read_length: 1000,
+            read_count: 1000,
+            runtime: 1_000_000,
+        };
+
+        // a transfer-sized transaction mostly uses up its length
+        let cheap = ExecutionCost {
+            write_length: 0,
+            write_count: 1,
+            read_length: 0,
+            read_count: 1,
+            runtime: 100,
+        };
+        // uses 1000 ppm of the write count
+        assert_eq!(
+            StacksBlockBuilder::fee_per_budget_share(1000, &cheap, 180, &budget),
+            1_000_000
+        );
+
+        // a transaction paying twice as much for 10x the runtime is worth less per unit of
+        // budget
+        let expensive = ExecutionCost {
+            write_length: 0,
+            write_count: 1,
+            read_length: 0,
+            read_count: 1,
+            runtime: 10_000,
+        };
+        assert_eq!(
+            StacksBlockBuilder::fee_per_budget_share(2000, &expensive, 180, &budget),
+            200_000
+        );
+
+        // zero-cost transactions don't divide by zero
+        assert_eq!(
+            StacksBlockBuilder::fee_per_budget_share(5, &ExecutionCost::zero(), 0, &budget),
+            5_000_000
+        );
+    }
+
     #[test]
     fn test_build_anchored_blocks_multiple_chaintips() {
         let mut privks = vec![];
@@ -6291,6 +6560,7 @@ pub mod test {
                         vrf_proof,
                         Hash160([tenure_id as u8; 20]),
                         &coinbase_tx,
+                        &vec![],
                         execution_cost,
                     )
                     .unwrap();
@@ -6398,6 +6668,7 @@ pub mod test {
                         vrf_proof,
                         Hash160([tenure_id as u8; 20]),
                         &coinbase_tx,
+                        &vec![],
                         ExecutionCost::max_value(),
                     )
                     .unwrap();
@@ -6592,6 +6863,7 @@ pub mod test {
                         vrf_proof,
                         Hash160([tenure_id as u8; 20]),
                         &coinbase_tx,
+                        &vec![],
                         ExecutionCost::max_value(),
                     )
                     .unwrap();
@@ -6749,7 +7021,7 @@ pub mod test {
 
                 let coinbase_tx = make_coinbase(miner, tenure_id as usize);
 
-                let mut anchored_block = StacksBlockBuilder::build_anchored_block(chainstate, &sortdb.index_conn(), &mempool, &parent_tip, tip.total_burn, vrf_proof, Hash160([tenure_id as u8; 20]), &coinbase_tx, ExecutionCost::max_value()).unwrap();
+                let mut anchored_block = StacksBlockBuilder::build_anchored_block(chainstate, &sortdb.index_conn(), &mempool, &parent_tip, tip.total_burn, vrf_proof, Hash160([tenure_id as u8; 20]), &coinbase_tx, &vec![], ExecutionCost::max_value()).unwrap();
 
                 if tenure_id == bad_block_tenure {
                     // corrupt the block
@@ -7000,6 +7272,7 @@ pub mod test {
                         vrf_proof,
                         Hash160([tenure_id as u8; 20]),
                         &coinbase_tx,
+                        &vec![],
                         ExecutionCost::max_value(),
                     )
                     .unwrap();
@@ -20,6 +20,7 @@ pub mod block;
 pub mod boot;
 pub mod db;
 pub mod events;
+pub mod fuzz;
 pub mod index;
 pub mod miner;
 pub mod transaction;
@@ -48,10 +49,11 @@ use util::vrf::VRFProof;
 use address::AddressHashMode;
 use burnchains::BurnchainHeaderHash;
 use burnchains::Txid;
-use chainstate::burn::operations::LeaderBlockCommitOp;
+use chainstate::burn::operations::{LeaderBlockCommitOp, TransferStxOp};
 use chainstate::burn::{BlockHeaderHash, ConsensusHash};
 
 use chainstate::stacks::db::accounts::MinerReward;
+use chainstate::stacks::db::MinerRewardInfo;
 use chainstate::stacks::db::StacksHeaderInfo;
 use chainstate::stacks::index::Error as marf_error;
 use chainstate::stacks::index::{TrieHash, TRIEHASH_ENCODED_SIZE};
@@ -783,6 +785,26 @@ pub struct StacksTransactionSigner {
     check_overlap: bool,
 }
 
+/// Assembles, funds, and signs a standard (non-sponsored) transaction from its parts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionBuilder {
+    version: TransactionVersion,
+    chain_id: u32,
+    payload: Option<TransactionPayload>,
+    anchor_mode: Option<TransactionAnchorMode>,
+    post_condition_mode: TransactionPostConditionMode,
+    post_conditions: Vec<TransactionPostCondition>,
+    nonce: u64,
+    fee: u64,
+    /// if set, the fee is this many microSTX per byte of the signed transaction
+    fee_rate: Option<u64>,
+    /// the origin's public keys (in address order), and the private keys that sign for them
+    signer_pubkeys: Vec<StacksPublicKey>,
+    signer_privkeys: Vec<StacksPrivateKey>,
+    signatures_required: u16,
+    multisig: bool,
+}
+
 /// How much work has gone into this chain so far?
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct StacksWorkScore {
@@ -848,8 +870,9 @@ pub struct StacksBlockBuilder {
     bytes_so_far: u64,
     prev_microblock_header: StacksMicroblockHeader,
     miner_privkey: StacksPrivateKey,
-    miner_payouts: Option<Vec<MinerReward>>,
+    miner_payouts: Option<(Vec<MinerReward>, MinerRewardInfo)>,
     miner_id: usize,
+    burn_transfer_ops: Vec<TransferStxOp>,
 }
 
 // maximum amount of data a leader can send during its epoch (2MB)
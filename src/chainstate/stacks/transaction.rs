@@ -38,7 +38,7 @@ use util::hash::Sha512Trunc256Sum;
 use util::retry::BoundReader;
 use util::secp256k1::MessageSignature;
 use vm::ast::build_ast;
-use vm::types::{QualifiedContractIdentifier, StandardPrincipalData};
+use vm::types::{PrincipalData, QualifiedContractIdentifier, StandardPrincipalData};
 use vm::{SymbolicExpression, SymbolicExpressionType, Value};
 
 use vm::representations::{ClarityName, ContractName};
@@ -878,6 +878,35 @@ impl StacksTransactionSigner {
         })
     }
 
+    /// Resume signing a transaction that has already been partially signed, such as a multisig
+    /// transaction that is being passed from one of its signers to the next.  The signatures
+    /// already present are replayed to find the sighash that the next private key must sign.
+    pub fn new_partially_signed(
+        tx: &StacksTransaction,
+    ) -> Result<StacksTransactionSigner, net_error> {
+        let origin_sighash = tx
+            .auth
+            .origin()
+            .next_sighash(&tx.sign_begin(), &TransactionAuthFlags::AuthStandard)?;
+
+        let (sighash, origin_done) = match tx.auth.sponsor() {
+            Some(sponsor_condition) if sponsor_condition.num_signatures() > 0 => (
+                sponsor_condition
+                    .next_sighash(&origin_sighash, &TransactionAuthFlags::AuthSponsored)?,
+                true,
+            ),
+            _ => (origin_sighash, false),
+        };
+
+        Ok(StacksTransactionSigner {
+            tx: tx.clone(),
+            sighash: sighash,
+            origin_done: origin_done,
+            check_oversign: true,
+            check_overlap: true,
+        })
+    }
+
     pub fn resume(&mut self, tx: &StacksTransaction) -> () {
         self.tx = tx.clone()
     }
@@ -1003,6 +1032,229 @@ impl StacksTransactionSigner {
     }
 }
 
+impl TransactionBuilder {
+    pub fn new(version: TransactionVersion, chain_id: u32) -> TransactionBuilder {
+        TransactionBuilder {
+            version: version,
+            chain_id: chain_id,
+            payload: None,
+            anchor_mode: None,
+            post_condition_mode: TransactionPostConditionMode::Deny,
+            post_conditions: vec![],
+            nonce: 0,
+            fee: 0,
+            fee_rate: None,
+            signer_pubkeys: vec![],
+            signer_privkeys: vec![],
+            signatures_required: 0,
+            multisig: false,
+        }
+    }
+
+    pub fn with_payload(mut self, payload: TransactionPayload) -> TransactionBuilder {
+        self.payload = Some(payload);
+        self
+    }
+
+    pub fn token_transfer(
+        self,
+        recipient: PrincipalData,
+        amount: u64,
+        memo: TokenTransferMemo,
+    ) -> TransactionBuilder {
+        self.with_payload(TransactionPayload::TokenTransfer(recipient, amount, memo))
+    }
+
+    pub fn contract_deploy(self, name: &str, code: &str) -> Result<TransactionBuilder, Error> {
+        let payload = TransactionPayload::new_smart_contract(name, code).ok_or_else(|| {
+            Error::InvalidStacksTransaction(
+                format!("Invalid contract name or code for '{}'", name),
+                false,
+            )
+        })?;
+        Ok(self.with_payload(payload))
+    }
+
+    pub fn contract_call(
+        self,
+        contract_address: StacksAddress,
+        contract_name: &str,
+        function_name: &str,
+        args: Vec<Value>,
+    ) -> Result<TransactionBuilder, Error> {
+        let payload = TransactionPayload::new_contract_call(
+            contract_address,
+            contract_name,
+            function_name,
+            args,
+        )
+        .ok_or_else(|| {
+            Error::InvalidStacksTransaction(
+                format!(
+                    "Invalid contract or function name '{}.{}'",
+                    contract_name, function_name
+                ),
+                false,
+            )
+        })?;
+        Ok(self.with_payload(payload))
+    }
+
+    pub fn with_nonce(mut self, nonce: u64) -> TransactionBuilder {
+        self.nonce = nonce;
+        self
+    }
+
+    /// Pay exactly this fee.
+    pub fn with_fee(mut self, fee: u64) -> TransactionBuilder {
+        self.fee = fee;
+        self.fee_rate = None;
+        self
+    }
+
+    /// Pay `fee_rate` microSTX per byte of the signed transaction.
+    pub fn with_fee_rate(mut self, fee_rate: u64) -> TransactionBuilder {
+        self.fee_rate = Some(fee_rate);
+        self
+    }
+
+    pub fn with_anchor_mode(mut self, anchor_mode: TransactionAnchorMode) -> TransactionBuilder {
+        self.anchor_mode = Some(anchor_mode);
+        self
+    }
+
+    pub fn with_post_condition_mode(
+        mut self,
+        post_condition_mode: TransactionPostConditionMode,
+    ) -> TransactionBuilder {
+        self.post_condition_mode = post_condition_mode;
+        self
+    }
+
+    pub fn with_post_condition(
+        mut self,
+        post_condition: TransactionPostCondition,
+    ) -> TransactionBuilder {
+        self.post_conditions.push(post_condition);
+        self
+    }
+
+    /// Sign as a single-signature (p2pkh) origin.
+    pub fn with_signer(mut self, privk: StacksPrivateKey) -> TransactionBuilder {
+        self.signer_pubkeys = vec![StacksPublicKey::from_private(&privk)];
+        self.signer_privkeys = vec![privk];
+        self.signatures_required = 1;
+        self.multisig = false;
+        self
+    }
+
+    /// Sign as a `signatures_required`-of-`pubkeys.len()` multisig (p2sh) origin.  The order
+    /// of `pubkeys` determines the origin address.  The first `signatures_required` public keys
+    /// (in that order) with a private key in `privks` will sign.
+    pub fn with_multisig_signers(
+        mut self,
+        signatures_required: u16,
+        pubkeys: Vec<StacksPublicKey>,
+        privks: Vec<StacksPrivateKey>,
+    ) -> TransactionBuilder {
+        self.signer_pubkeys = pubkeys;
+        self.signer_privkeys = privks;
+        self.signatures_required = signatures_required;
+        self.multisig = true;
+        self
+    }
+
+    fn make_unsigned(&self, fee: u64) -> Result<StacksTransaction, Error> {
+        let payload = self.payload.clone().ok_or_else(|| {
+            Error::InvalidStacksTransaction("Transaction has no payload".to_string(), false)
+        })?;
+
+        let mut spending_condition = if self.multisig {
+            TransactionSpendingCondition::new_multisig_p2sh(
+                self.signatures_required,
+                self.signer_pubkeys.clone(),
+            )
+        } else {
+            self.signer_pubkeys
+                .get(0)
+                .and_then(|pubk| TransactionSpendingCondition::new_singlesig_p2pkh(pubk.clone()))
+        }
+        .ok_or_else(|| {
+            Error::InvalidStacksTransaction("Transaction has no valid signers".to_string(), false)
+        })?;
+        spending_condition.set_nonce(self.nonce);
+        spending_condition.set_fee_rate(fee);
+
+        let mut tx = StacksTransaction::new(
+            self.version,
+            TransactionAuth::Standard(spending_condition),
+            payload,
+        );
+        tx.chain_id = self.chain_id;
+        tx.post_condition_mode = self.post_condition_mode.clone();
+        tx.post_conditions = self.post_conditions.clone();
+        if let Some(ref anchor_mode) = self.anchor_mode {
+            tx.anchor_mode = anchor_mode.clone();
+        }
+        Ok(tx)
+    }
+
+    fn sign(&self, tx: &StacksTransaction) -> Result<StacksTransaction, Error> {
+        let mut tx_signer = StacksTransactionSigner::new(tx);
+        let mut num_signatures = 0;
+        for pubk in self.signer_pubkeys.iter() {
+            let privk_opt = if num_signatures < self.signatures_required {
+                self.signer_privkeys
+                    .iter()
+                    .find(|privk| StacksPublicKey::from_private(privk) == *pubk)
+            } else {
+                None
+            };
+
+            match privk_opt {
+                Some(privk) => {
+                    tx_signer.sign_origin(privk).map_err(Error::NetError)?;
+                    num_signatures += 1;
+                }
+                None => {
+                    if self.multisig {
+                        tx_signer.append_origin(pubk).map_err(Error::NetError)?;
+                    }
+                }
+            }
+        }
+
+        tx_signer.get_tx().ok_or_else(|| {
+            Error::InvalidStacksTransaction(
+                format!(
+                    "Not enough private keys to sign: have {}, need {}",
+                    num_signatures, self.signatures_required
+                ),
+                false,
+            )
+        })
+    }
+
+    /// Assemble and sign the transaction.  If a fee rate was given, the fee is the fee rate
+    /// times the length of the signed transaction.
+    pub fn build(&self) -> Result<StacksTransaction, Error> {
+        let fee = match self.fee_rate {
+            Some(fee_rate) => {
+                // the fee is encoded with a fixed width, so it does not change the length
+                let tx_len = self.sign(&self.make_unsigned(0)?)?.serialize_to_vec().len() as u64;
+                tx_len.checked_mul(fee_rate).ok_or(Error::InvalidFee)?
+            }
+            None => self.fee,
+        };
+        self.sign(&self.make_unsigned(fee)?)
+    }
+
+    /// Assemble and sign the transaction, and encode it for the wire.
+    pub fn build_serialized(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.build()?.serialize_to_vec())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -3835,6 +4087,241 @@ mod test {
         }
     }
 
+    #[test]
+    fn tx_stacks_transaction_sign_verify_p2sh_partially_signed() {
+        let privk_1 = StacksPrivateKey::from_hex(
+            "6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001",
+        )
+        .unwrap();
+        let privk_2 = StacksPrivateKey::from_hex(
+            "2a584d899fed1d24e26b524f202763c8ab30260167429f157f1c119f550fa6af01",
+        )
+        .unwrap();
+        let privk_3 = StacksPrivateKey::from_hex(
+            "d5200dee706ee53ae98a03fba6cf4fdcc5084c30cfa9e1b3462dcdeaa3e0f1d201",
+        )
+        .unwrap();
+
+        let pubk_1 = StacksPublicKey::from_private(&privk_1);
+        let pubk_2 = StacksPublicKey::from_private(&privk_2);
+        let pubk_3 = StacksPublicKey::from_private(&privk_3);
+
+        let multisig_condition = TransactionSpendingCondition::new_multisig_p2sh(
+            2,
+            vec![pubk_1.clone(), pubk_2.clone(), pubk_3.clone()],
+        )
+        .unwrap();
+
+        // standard multisig origin, signed by each signer in turn
+        let origin_auth = TransactionAuth::Standard(multisig_condition.clone());
+        for tx in tx_stacks_transaction_test_txs(&origin_auth) {
+            let mut tx_signer = StacksTransactionSigner::new(&tx);
+            tx_signer.sign_origin(&privk_1).unwrap();
+            tx_signer.sign_origin(&privk_2).unwrap();
+            tx_signer.append_origin(&pubk_3).unwrap();
+            let expected_tx = tx_signer.get_tx().unwrap();
+
+            // first signer signs and ships the partially-signed tx to the second
+            let mut tx_signer = StacksTransactionSigner::new(&tx);
+            tx_signer.sign_origin(&privk_1).unwrap();
+            let partial_tx = tx_signer.get_tx_incomplete();
+            assert!(partial_tx.verify().is_err());
+
+            let mut partial_tx_bytes = vec![];
+            partial_tx
+                .consensus_serialize(&mut partial_tx_bytes)
+                .unwrap();
+            let partial_tx =
+                StacksTransaction::consensus_deserialize(&mut &partial_tx_bytes[..]).unwrap();
+
+            let mut tx_signer = StacksTransactionSigner::new_partially_signed(&partial_tx).unwrap();
+            tx_signer.sign_origin(&privk_2).unwrap();
+            tx_signer.append_origin(&pubk_3).unwrap();
+            let signed_tx = tx_signer.get_tx().unwrap();
+
+            assert_eq!(signed_tx, expected_tx);
+            signed_tx.verify().unwrap();
+        }
+
+        // singlesig origin, multisig sponsor, signed by each sponsor in turn
+        let origin_auth = TransactionAuth::Sponsored(
+            TransactionSpendingCondition::new_singlesig_p2pkh(pubk_3.clone()).unwrap(),
+            multisig_condition.clone(),
+        );
+        for tx in tx_stacks_transaction_test_txs(&origin_auth) {
+            let mut tx_signer = StacksTransactionSigner::new(&tx);
+            tx_signer.sign_origin(&privk_3).unwrap();
+            let origin_tx = tx_signer.get_tx_incomplete();
+
+            // nothing signed by the sponsor yet
+            let mut tx_signer = StacksTransactionSigner::new_partially_signed(&origin_tx).unwrap();
+            tx_signer.sign_sponsor(&privk_1).unwrap();
+            let partial_tx = tx_signer.get_tx_incomplete();
+
+            let mut tx_signer = StacksTransactionSigner::new_partially_signed(&partial_tx).unwrap();
+            tx_signer.sign_sponsor(&privk_2).unwrap();
+            tx_signer.append_sponsor(&pubk_3).unwrap();
+            let signed_tx = tx_signer.get_tx().unwrap();
+            signed_tx.verify().unwrap();
+
+            // the origin can't sign again once the sponsor has started
+            let mut tx_signer = StacksTransactionSigner::new_partially_signed(&partial_tx).unwrap();
+            assert!(tx_signer.sign_origin(&privk_3).is_err());
+        }
+    }
+
+    #[test]
+    fn tx_stacks_transaction_builder() {
+        let privk_1 = StacksPrivateKey::from_hex(
+            "6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001",
+        )
+        .unwrap();
+        let privk_2 = StacksPrivateKey::from_hex(
+            "2a584d899fed1d24e26b524f202763c8ab30260167429f157f1c119f550fa6af01",
+        )
+        .unwrap();
+        let privk_3 = StacksPrivateKey::from_hex(
+            "d5200dee706ee53ae98a03fba6cf4fdcc5084c30cfa9e1b3462dcdeaa3e0f1d201",
+        )
+        .unwrap();
+
+        let pubk_1 = StacksPublicKey::from_private(&privk_1);
+        let pubk_2 = StacksPublicKey::from_private(&privk_2);
+        let pubk_3 = StacksPublicKey::from_private(&privk_3);
+
+        let recipient = PrincipalData::from(StacksAddress {
+            version: C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            bytes: Hash160([0xff; 20]),
+        });
+        let contract_addr = StacksAddress {
+            version: C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            bytes: Hash160([0x01; 20]),
+        };
+
+        // single-sig token transfer, paying 2 microSTX per byte
+        let builder = TransactionBuilder::new(TransactionVersion::Testnet, 0x80000000)
+            .token_transfer(recipient.clone(), 123, TokenTransferMemo([0u8; 34]))
+            .with_nonce(5)
+            .with_fee_rate(2)
+            .with_signer(privk_1.clone());
+        let tx = builder.build().unwrap();
+        let tx_bytes = tx.serialize_to_vec();
+
+        tx.verify().unwrap();
+        assert_eq!(tx.chain_id, 0x80000000);
+        assert_eq!(tx.get_origin_nonce(), 5);
+        assert_eq!(tx.get_fee_rate(), 2 * (tx_bytes.len() as u64));
+        assert_eq!(
+            tx.origin_address(),
+            StacksAddress::from_public_keys(
+                C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+                &AddressHashMode::SerializeP2PKH,
+                1,
+                &vec![pubk_1.clone()]
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            tx.payload,
+            TransactionPayload::TokenTransfer(recipient.clone(), 123, TokenTransferMemo([0u8; 34]))
+        );
+
+        // signing is deterministic, and the wire format decodes to the same transaction
+        assert_eq!(builder.build_serialized().unwrap(), tx_bytes);
+        assert_eq!(
+            StacksTransaction::consensus_deserialize(&mut &tx_bytes[..]).unwrap(),
+            tx
+        );
+
+        // contract deploys and calls, with a fixed fee
+        let builder = TransactionBuilder::new(TransactionVersion::Testnet, 0)
+            .with_fee(1000)
+            .with_signer(privk_1.clone());
+
+        let tx = builder
+            .clone()
+            .contract_deploy("hello-world", "(define-data-var x int 0)")
+            .unwrap()
+            .build()
+            .unwrap();
+        tx.verify().unwrap();
+        assert_eq!(tx.get_fee_rate(), 1000);
+        assert_eq!(
+            tx.payload,
+            TransactionPayload::new_smart_contract("hello-world", "(define-data-var x int 0)")
+                .unwrap()
+        );
+        assert!(builder
+            .clone()
+            .contract_deploy("not a contract name", "(define-data-var x int 0)")
+            .is_err());
+
+        let tx = builder
+            .clone()
+            .contract_call(
+                contract_addr.clone(),
+                "hello-world",
+                "set-x",
+                vec![Value::Int(1)],
+            )
+            .unwrap()
+            .with_anchor_mode(TransactionAnchorMode::OnChainOnly)
+            .with_post_condition_mode(TransactionPostConditionMode::Allow)
+            .build()
+            .unwrap();
+        tx.verify().unwrap();
+        assert_eq!(tx.anchor_mode, TransactionAnchorMode::OnChainOnly);
+        assert_eq!(tx.post_condition_mode, TransactionPostConditionMode::Allow);
+        assert!(builder
+            .clone()
+            .contract_call(
+                contract_addr.clone(),
+                "hello-world",
+                "not a function",
+                vec![]
+            )
+            .is_err());
+
+        // missing payload or signer
+        assert!(builder.build().is_err());
+        assert!(TransactionBuilder::new(TransactionVersion::Testnet, 0)
+            .token_transfer(recipient.clone(), 123, TokenTransferMemo([0u8; 34]))
+            .build()
+            .is_err());
+
+        // 2-of-3 multisig, signed by the first and third keys
+        let pubkeys = vec![pubk_1.clone(), pubk_2.clone(), pubk_3.clone()];
+        let builder = TransactionBuilder::new(TransactionVersion::Testnet, 0)
+            .token_transfer(recipient.clone(), 123, TokenTransferMemo([0u8; 34]))
+            .with_fee_rate(1);
+
+        let tx = builder
+            .clone()
+            .with_multisig_signers(2, pubkeys.clone(), vec![privk_3.clone(), privk_1.clone()])
+            .build()
+            .unwrap();
+        tx.verify().unwrap();
+        assert_eq!(tx.get_fee_rate(), tx.serialize_to_vec().len() as u64);
+        assert_eq!(tx.auth().origin().num_signatures(), 2);
+        assert_eq!(
+            tx.origin_address(),
+            StacksAddress::from_public_keys(
+                C32_ADDRESS_VERSION_TESTNET_MULTISIG,
+                &AddressHashMode::SerializeP2SH,
+                2,
+                &pubkeys
+            )
+            .unwrap()
+        );
+
+        // not enough private keys
+        assert!(builder
+            .clone()
+            .with_multisig_signers(2, pubkeys.clone(), vec![privk_2.clone()])
+            .build()
+            .is_err());
+    }
+
     #[test]
     fn tx_stacks_transaction_sign_verify_sponsored_p2sh() {
         let origin_privk = StacksPrivateKey::from_hex(
@@ -40,15 +40,21 @@ use util::hash::Sha512Trunc256Sum;
 
 use vm::analysis;
 use vm::analysis::contract_interface_builder::build_contract_interface;
+use vm::analysis::strict_checker::{check_strict, StrictProfile};
 use vm::analysis::{errors::CheckResult, AnalysisDatabase, ContractAnalysis};
 use vm::ast::build_ast;
 use vm::contexts::OwnedEnvironment;
 use vm::costs::LimitedCostTracker;
+use vm::coverage::{CoverageData, CoverageReporter};
+use vm::database::migration::migrate;
 use vm::database::{
     ClarityDatabase, HeadersDB, MarfedKV, MemoryBackingStore, STXBalance, SqliteConnection,
     NULL_BURN_STATE_DB, NULL_HEADER_DB,
 };
+use vm::debug::{Debugger, StdioDebugInterface};
+use vm::docs;
 use vm::errors::{Error, InterpreterResult, RuntimeErrorType};
+use vm::trace::{ExecutionTracer, TraceWeight};
 use vm::types::{PrincipalData, QualifiedContractIdentifier};
 use vm::{execute as vm_execute, SymbolicExpression, SymbolicExpressionType, Value};
 
@@ -58,6 +64,8 @@ use burnchains::BurnchainHeaderHash;
 use chainstate::burn::VRFSeed;
 use chainstate::stacks::StacksAddress;
 
+use core::StacksEpochId;
+
 use serde::Serialize;
 
 #[cfg(test)]
@@ -80,16 +88,23 @@ fn print_usage(invoked_by: &str) {
 where command is one of:
 
   initialize         to initialize a local VM state database.
-  check              to typecheck a potential contract definition.
+  check              to typecheck a potential contract definition.  With --strict, or with
+                     --manifest [manifest.json], lint warnings are errors.
   launch             to launch a initialize a new contract in the local state database.
   eval               to evaluate (in read-only mode) a program in a given contract context.
   eval_at_chaintip   like `eval`, but does not advance to a new block.
   eval_at_block      like `eval_at_chaintip`, but accepts a index-block-hash to evaluate at,
                      must be passed eval string via stdin.
   eval_raw           to typecheck and evaluate an expression without a contract or database context.
-  repl               to typecheck and evaluate expressions in a stdin/stdout loop.
-  execute            to execute a public function of a defined contract.
+  repl               to typecheck and evaluate expressions in a stdin/stdout loop.  Prefix an
+                     expression with ::debug to step through its evaluation.
+  execute            to execute a public function of a defined contract.  With --trace
+                     [trace.folded], write a flamegraph of the call's expressions, and with
+                     --coverage [lcov.info], the lcov coverage of the contracts it ran.
   generate_address   to generate a random Stacks public address for testing purposes.
+  docs               to verify the API reference examples, and with --json, print the reference.
+  migrate_db         to upgrade a local VM state database to the current metadata layout.  With
+                     --dry_run, report what would change without changing anything.
 ",
         invoked_by
     );
@@ -139,10 +154,113 @@ fn run_analysis(
         analysis_db,
         save_contract,
         LimitedCostTracker::new_max_limit(),
+        StacksEpochId::latest(),
     )
     .map_err(|(e, _)| e)
 }
 
+/// Remove the `--strict` and `--manifest [manifest.json]` flags from `args`, and return the
+/// strict profile they select.  `--strict` makes every lint an error, and a manifest's `strict`
+/// list makes only the lints it names errors.  Without either, no lint is an error.
+fn take_strict_profile(invoked_by: &str, args: &mut Vec<String>) -> StrictProfile {
+    let mut profile = StrictProfile::default();
+    if let Some(i) = args.iter().position(|arg| arg == "--strict") {
+        args.remove(i);
+        profile = StrictProfile::all();
+    }
+    if let Some(i) = args.iter().position(|arg| arg == "--manifest") {
+        if i + 1 >= args.len() {
+            eprintln!(
+                "Usage: {} {} --manifest [manifest.json]",
+                invoked_by, args[0]
+            );
+            panic_test!();
+        }
+        let path = args.remove(i + 1);
+        args.remove(i);
+        let manifest = friendly_expect(
+            fs::read_to_string(&path),
+            &format!("Error reading manifest: {}", path),
+        );
+        profile = friendly_expect(
+            StrictProfile::from_manifest(&manifest),
+            &format!("Invalid manifest: {}", path),
+        );
+    }
+    profile
+}
+
+/// Remove the `--trace [trace.folded]` and `--trace-weight [time | cost]` flags from `args`, and
+/// return where to write the trace and what to weigh it by, if tracing was asked for.  The
+/// trace is weighed by time unless `--trace-weight cost` is given.
+fn take_trace_options(invoked_by: &str, args: &mut Vec<String>) -> Option<(String, TraceWeight)> {
+    let mut weight = TraceWeight::Time;
+    if let Some(i) = args.iter().position(|arg| arg == "--trace-weight") {
+        let weight_name = if i + 1 < args.len() {
+            args.remove(i + 1)
+        } else {
+            "".to_string()
+        };
+        args.remove(i);
+        weight = match weight_name.as_str() {
+            "time" => TraceWeight::Time,
+            "cost" => TraceWeight::Cost,
+            _ => {
+                eprintln!(
+                    "Usage: {} {} --trace-weight [time | cost]",
+                    invoked_by, args[0]
+                );
+                panic_test!();
+            }
+        };
+    }
+    if let Some(i) = args.iter().position(|arg| arg == "--trace") {
+        if i + 1 >= args.len() {
+            eprintln!("Usage: {} {} --trace [trace.folded]", invoked_by, args[0]);
+            panic_test!();
+        }
+        let path = args.remove(i + 1);
+        args.remove(i);
+        return Some((path, weight));
+    }
+    None
+}
+
+/// Remove the `--coverage [lcov.info]` flag from `args`, and return where to write the coverage
+/// report, if one was asked for
+fn take_coverage_path(invoked_by: &str, args: &mut Vec<String>) -> Option<String> {
+    let i = args.iter().position(|arg| arg == "--coverage")?;
+    if i + 1 >= args.len() {
+        eprintln!("Usage: {} {} --coverage [lcov.info]", invoked_by, args[0]);
+        panic_test!();
+    }
+    let path = args.remove(i + 1);
+    args.remove(i);
+    Some(path)
+}
+
+/// The lcov report of `coverage`, with a record for each contract that ran.  Contracts are
+/// named by their source file, `<contract-name>.clar`.
+fn coverage_lcov_report(coverage: &CoverageData, db: &mut ClarityDatabase) -> String {
+    let mut report = String::new();
+    for contract_identifier in coverage.contracts().iter() {
+        let source = match db.get_contract_src(contract_identifier) {
+            Some(source) => source,
+            None => continue,
+        };
+        let expressions = friendly_expect(
+            parse(contract_identifier, &source),
+            &format!("Failed to parse contract {}", contract_identifier),
+        );
+        report.push_str(&coverage.lcov_record(
+            contract_identifier,
+            &format!("{}.clar", &contract_identifier.name),
+            &expressions,
+        ));
+    }
+    report
+}
+
 fn create_or_open_db(path: &String) -> Connection {
     let open_flags = match fs::metadata(path) {
         Err(e) => {
@@ -545,10 +663,33 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) {
                 friendly_expect(c32_address(22, &random_bytes), "Failed to generate address");
             println!("{}", addr);
         }
+        "docs" => {
+            let (json, failed) = docs::make_json_verified_api_reference();
+            if args.len() >= 2 && args[1] == "--json" {
+                println!("{}", json);
+            } else if args.len() >= 2 {
+                eprintln!("Usage: {} {} [--json]", invoked_by, args[0]);
+                panic_test!();
+            }
+
+            if failed.len() > 0 {
+                eprintln!(
+                    "Failed to verify the examples of {} function(s): {}",
+                    failed.len(),
+                    failed.join(", ")
+                );
+                panic_test!();
+            }
+            if args.len() == 1 {
+                println!("All API reference examples verified.");
+            }
+        }
         "check" => {
+            let mut args = args.to_vec();
+            let strict_profile = take_strict_profile(invoked_by, &mut args);
             if args.len() < 2 {
                 eprintln!(
-                    "Usage: {} {} [program-file.clar] (vm-state.db)",
+                    "Usage: {} {} [program-file.clar] (vm-state.db) [--strict | --manifest manifest.json]",
                     invoked_by, args[0]
                 );
                 panic_test!();
@@ -598,6 +739,18 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) {
                 panic_test!();
             });
 
+            match check_strict(&contract_analysis, &strict_profile) {
+                Ok(warnings) => {
+                    for warning in warnings.iter() {
+                        eprintln!("{}", &warning.error.diagnostic);
+                    }
+                }
+                Err(e) => {
+                    println!("{}", &e.diagnostic);
+                    panic_test!();
+                }
+            }
+
             match args.last() {
                 Some(s) if s == "--output_analysis" => {
                     println!(
@@ -643,6 +796,13 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) {
                     }
                 };
 
+                let debugging = content.trim_start().starts_with("::debug");
+                let content = if debugging {
+                    content.trim_start()["::debug".len()..].to_string()
+                } else {
+                    content
+                };
+
                 let mut ast = match parse(&contract_id, &content) {
                     Ok(val) => val,
                     Err(error) => {
@@ -659,7 +819,18 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) {
                     }
                 }
 
-                let eval_result = match exec_env.eval_raw(&content) {
+                if debugging {
+                    println!("Debugging, type 'help' for the debugger commands");
+                    exec_env
+                        .global_context
+                        .add_eval_hook(Box::new(Debugger::new(Box::new(StdioDebugInterface))));
+                }
+                let eval_result = exec_env.eval_raw(&content);
+                if debugging {
+                    exec_env.global_context.eval_hooks = None;
+                }
+
+                let eval_result = match eval_result {
                     Ok(val) => val,
                     Err(error) => {
                         println!("Execution error:\n{}", error);
@@ -772,6 +943,61 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) {
                 }
             }
         }
+        "migrate_db" => {
+            let mut args = args.to_vec();
+            let dry_run = if let Some(ix) = args.iter().position(|x| x == "--dry_run") {
+                args.remove(ix);
+                true
+            } else {
+                false
+            };
+
+            if args.len() != 2 {
+                eprintln!(
+                    "Usage: {} {} [--dry_run] [vm-state.db]",
+                    invoked_by, args[0]
+                );
+                panic_test!();
+            }
+
+            let vm_filename = &args[1];
+            let mut cli_db_path_buf = PathBuf::from(vm_filename);
+            cli_db_path_buf.push("cli.sqlite");
+            let cli_db_path = friendly_expect_opt(
+                cli_db_path_buf.to_str(),
+                &format!("FATAL: failed to convert '{}' to a string", vm_filename),
+            )
+            .to_string();
+            let tip = get_cli_chain_tip(&create_or_open_db(&cli_db_path));
+
+            let mut marf_kv = friendly_expect(
+                MarfedKV::open(vm_filename, None),
+                "Failed to open VM database.",
+            );
+            let report = friendly_expect(
+                migrate(&mut marf_kv, &tip, dry_run),
+                "Failed to migrate VM database.",
+            );
+
+            for (version, description, rewritten) in report.applied.iter() {
+                println!(
+                    "Version {}: {} ({} entries rewritten)",
+                    version, description, rewritten
+                );
+            }
+            println!("State root at {}: {} (unchanged)", tip, report.state_root);
+            if report.dry_run {
+                println!(
+                    "Dry run: the database was left at version {}.",
+                    report.from_version
+                );
+            } else {
+                println!(
+                    "Database migrated from version {} to version {}.",
+                    report.from_version, report.to_version
+                );
+            }
+        }
         "eval_at_block" => {
             if args.len() != 4 {
                 eprintln!(
@@ -897,8 +1123,11 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) {
             }
         }
         "execute" => {
+            let mut args = args.to_vec();
+            let trace_options = take_trace_options(invoked_by, &mut args);
+            let coverage_path = take_coverage_path(invoked_by, &mut args);
             if args.len() < 5 {
-                eprintln!("Usage: {} {} [vm-state.db] [contract-identifier] [public-function-name] [sender-address] [args...]", invoked_by, args[0]);
+                eprintln!("Usage: {} {} [vm-state.db] [contract-identifier] [public-function-name] [sender-address] [args...] [--trace trace.folded [--trace-weight time | cost]] [--coverage lcov.info]", invoked_by, args[0]);
                 panic_test!();
             }
             let vm_filename = &args[1];
@@ -941,17 +1170,46 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) {
                 .collect();
 
             let result = in_block(vm_filename, marf_kv, |mut marf| {
-                let result = {
+                let (result, coverage) = {
                     let db = marf.as_clarity_db(&header_db, &NULL_BURN_STATE_DB);
                     let mut vm_env =
                         OwnedEnvironment::new_cost_limited(db, LimitedCostTracker::new_max_limit());
-                    vm_env.execute_transaction(
+                    let trace = trace_options.as_ref().map(|_| {
+                        let tracer = ExecutionTracer::new();
+                        let trace = tracer.trace();
+                        vm_env.add_eval_hook(Box::new(tracer));
+                        trace
+                    });
+                    let coverage = coverage_path.as_ref().map(|_| {
+                        let reporter = CoverageReporter::new();
+                        let coverage = reporter.coverage();
+                        vm_env.add_eval_hook(Box::new(reporter));
+                        coverage
+                    });
+                    let result = vm_env.execute_transaction(
                         Value::Principal(sender),
                         contract_identifier,
                         &tx_name,
                         &arguments,
-                    )
+                    );
+                    if let (Some(trace), Some((trace_path, weight))) =
+                        (trace, trace_options.as_ref())
+                    {
+                        friendly_expect(
+                            fs::write(trace_path, trace.borrow().folded_stacks(*weight)),
+                            &format!("Failed to write trace to {}", trace_path),
+                        );
+                    }
+                    (result, coverage)
                 };
+                if let (Some(coverage), Some(coverage_path)) = (coverage, coverage_path.as_ref()) {
+                    let mut db = marf.as_clarity_db(&header_db, &NULL_BURN_STATE_DB);
+                    let report = coverage_lcov_report(&coverage.borrow(), &mut db);
+                    friendly_expect(
+                        fs::write(coverage_path, report),
+                        &format!("Failed to write coverage to {}", coverage_path),
+                    );
+                }
                 (marf, result)
             });
 
@@ -986,6 +1244,7 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) {
 #[cfg(test)]
 mod test {
     use super::*;
+    use vm::database::ClarityBackingStore;
     #[test]
     fn test_initial_alloc() {
         let db_name = format!("/tmp/db_{}", rand::thread_rng().gen::<i32>());
@@ -1024,6 +1283,38 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_migrate_db() {
+        let db_name = format!("/tmp/db_{}", rand::thread_rng().gen::<i32>());
+
+        invoke_command("test", &["initialize".to_string(), db_name.clone()]);
+        invoke_command(
+            "test",
+            &[
+                "launch".to_string(),
+                "S1G2081040G2081040G2081040G208105NK8PE5.tokens".to_string(),
+                "sample-contracts/tokens.clar".to_string(),
+                db_name.clone(),
+            ],
+        );
+
+        invoke_command(
+            "test",
+            &[
+                "migrate_db".to_string(),
+                "--dry_run".to_string(),
+                db_name.clone(),
+            ],
+        );
+        invoke_command("test", &["migrate_db".to_string(), db_name.clone()]);
+
+        let mut marf_kv = MarfedKV::open(&db_name, None).unwrap();
+        assert_eq!(
+            marf_kv.get_side_store().get_db_version().unwrap(),
+            vm::database::migration::CLARITY_DB_VERSION
+        );
+    }
+
     #[test]
     fn test_samples() {
         let db_name = format!("/tmp/db_{}", rand::thread_rng().gen::<i32>());
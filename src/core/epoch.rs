@@ -0,0 +1,298 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Stacks epochs.
+//!
+//! An epoch is a span of burnchain blocks over which one set of consensus rules applies.  Each
+//! network activates its epochs at fixed burnchain block heights, so a node that is still syncing
+//! processes every block under the rules that were in force when it was mined, and a node that
+//! has been upgraded ahead of an activation keeps agreeing with the ones that haven't until the
+//! activation height is reached.  VM natives, cost functions, and block validation rules are
+//! gated on the epoch they activate in.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+/// End height of the last epoch.  It's the largest height the sortition DB can store.
+pub const STACKS_EPOCH_MAX: u64 = i64::max_value() as u64;
+
+/// Burnchain block heights at which the epochs after Stacks 2.0 activate on mainnet.  Stacks 2.0
+/// itself starts at the first burnchain block.
+pub const STACKS_EPOCH_ACTIVATIONS_MAINNET: &'static [(StacksEpochId, u64)] =
+    &[(StacksEpochId::Epoch21, 781_551)];
+
+/// Burnchain block heights at which the epochs after Stacks 2.0 activate on testnet.
+pub const STACKS_EPOCH_ACTIVATIONS_TESTNET: &'static [(StacksEpochId, u64)] =
+    &[(StacksEpochId::Epoch21, 2_422_101)];
+
+/// Burnchain block heights at which the epochs after Stacks 2.0 activate on regtest.  Every epoch
+/// is active from the first block.
+pub const STACKS_EPOCH_ACTIVATIONS_REGTEST: &'static [(StacksEpochId, u64)] =
+    &[(StacksEpochId::Epoch21, 0)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[repr(u32)]
+pub enum StacksEpochId {
+    /// Stacks 1.0, which ran on its own.  Stacks 2.0 nodes accept no blocks in this epoch.
+    Epoch10 = 0x01000,
+    Epoch20 = 0x02000,
+    /// Stacks 2.1:  the `index-of`, `slice?`, `to-consensus-buff?` and `from-consensus-buff?`
+    /// natives, `try!` on responses with an unknown error type, the `pre-stx` and `transfer-stx`
+    /// burnchain operations, segwit funding, microblock poison signature checks, and tx fees paid
+    /// to miners.
+    Epoch21 = 0x0200a,
+}
+
+impl StacksEpochId {
+    pub fn latest() -> StacksEpochId {
+        StacksEpochId::Epoch21
+    }
+}
+
+impl TryFrom<u32> for StacksEpochId {
+    type Error = ();
+
+    fn try_from(value: u32) -> Result<StacksEpochId, Self::Error> {
+        match value {
+            x if x == StacksEpochId::Epoch10 as u32 => Ok(StacksEpochId::Epoch10),
+            x if x == StacksEpochId::Epoch20 as u32 => Ok(StacksEpochId::Epoch20),
+            x if x == StacksEpochId::Epoch21 as u32 => Ok(StacksEpochId::Epoch21),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for StacksEpochId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StacksEpochId::Epoch10 => write!(f, "1.0"),
+            StacksEpochId::Epoch20 => write!(f, "2.0"),
+            StacksEpochId::Epoch21 => write!(f, "2.1"),
+        }
+    }
+}
+
+/// An epoch and the burnchain blocks it spans
+#[derive(Debug, Clone, PartialEq)]
+pub struct StacksEpoch {
+    pub epoch_id: StacksEpochId,
+    /// height of the epoch's first burnchain block
+    pub start_height: u64,
+    /// height of the first burnchain block after the epoch
+    pub end_height: u64,
+}
+
+impl StacksEpoch {
+    /// Make the epoch schedule of a chain whose Stacks 2.0 blocks start at burnchain height
+    /// `epoch_2_0_height`, and whose later epochs activate at the given heights.  The
+    /// activations must be in order.  An epoch that activates before Stacks 2.0 starts is active
+    /// from the first Stacks 2.0 block, leaving the epochs it supersedes empty.
+    pub fn all(epoch_2_0_height: u64, activations: &[(StacksEpochId, u64)]) -> Vec<StacksEpoch> {
+        let mut starts = vec![
+            (StacksEpochId::Epoch10, 0),
+            (StacksEpochId::Epoch20, epoch_2_0_height),
+        ];
+        for &(epoch_id, start_height) in activations.iter() {
+            starts.push((epoch_id, start_height.max(epoch_2_0_height)));
+        }
+
+        let mut epochs = Vec::with_capacity(starts.len());
+        for (i, &(epoch_id, start_height)) in starts.iter().enumerate() {
+            let end_height = match starts.get(i + 1) {
+                Some(&(_, next_start_height)) => next_start_height,
+                None => STACKS_EPOCH_MAX,
+            };
+            epochs.push(StacksEpoch {
+                epoch_id,
+                start_height,
+                end_height,
+            });
+        }
+        epochs
+    }
+
+    /// Make the epoch schedule of a chain that follows the latest rules from burnchain height
+    /// `epoch_2_0_height` on
+    pub fn all_latest(epoch_2_0_height: u64) -> Vec<StacksEpoch> {
+        StacksEpoch::all(
+            epoch_2_0_height,
+            &[(StacksEpochId::latest(), epoch_2_0_height)],
+        )
+    }
+
+    /// Get the ID of the epoch that contains the given burnchain block height.  Heights past the
+    /// end of the schedule follow the latest epoch's rules.
+    pub fn epoch_id_at(epochs: &[StacksEpoch], height: u64) -> StacksEpochId {
+        match StacksEpoch::find_epoch(epochs, height) {
+            Some(index) => epochs[index].epoch_id,
+            None => StacksEpochId::latest(),
+        }
+    }
+
+    /// Find the index of the epoch that contains the given burnchain block height
+    pub fn find_epoch(epochs: &[StacksEpoch], height: u64) -> Option<usize> {
+        epochs
+            .iter()
+            .position(|epoch| epoch.start_height <= height && height < epoch.end_height)
+    }
+
+    /// Check that an epoch schedule starts at height 0, covers every height up to
+    /// STACKS_EPOCH_MAX without gaps or overlaps, and lists its epochs in increasing order.
+    pub fn validate_epochs(epochs: &[StacksEpoch]) -> Result<(), String> {
+        let mut next_start_height = 0;
+        let mut last_epoch_id = None;
+        for epoch in epochs.iter() {
+            if epoch.start_height != next_start_height {
+                return Err(format!(
+                    "Epoch {} starts at {}, but the previous epoch ends at {}",
+                    epoch.epoch_id, epoch.start_height, next_start_height
+                ));
+            }
+            if epoch.end_height < epoch.start_height {
+                return Err(format!(
+                    "Epoch {} ends at {}, before it starts at {}",
+                    epoch.epoch_id, epoch.end_height, epoch.start_height
+                ));
+            }
+            if let Some(last_epoch_id) = last_epoch_id {
+                if epoch.epoch_id <= last_epoch_id {
+                    return Err(format!(
+                        "Epoch {} comes after epoch {}",
+                        epoch.epoch_id, last_epoch_id
+                    ));
+                }
+            }
+            next_start_height = epoch.end_height;
+            last_epoch_id = Some(epoch.epoch_id);
+        }
+        if next_start_height != STACKS_EPOCH_MAX {
+            return Err(format!(
+                "Epochs end at {}, not at {}",
+                next_start_height, STACKS_EPOCH_MAX
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stacks_epochs() {
+        let epochs = StacksEpoch::all(100, &[]);
+        assert_eq!(
+            epochs,
+            vec![
+                StacksEpoch {
+                    epoch_id: StacksEpochId::Epoch10,
+                    start_height: 0,
+                    end_height: 100,
+                },
+                StacksEpoch {
+                    epoch_id: StacksEpochId::Epoch20,
+                    start_height: 100,
+                    end_height: STACKS_EPOCH_MAX,
+                },
+            ]
+        );
+        StacksEpoch::validate_epochs(&epochs).unwrap();
+        StacksEpoch::validate_epochs(&StacksEpoch::all(100, STACKS_EPOCH_ACTIVATIONS_MAINNET))
+            .unwrap();
+        StacksEpoch::validate_epochs(&StacksEpoch::all(100, STACKS_EPOCH_ACTIVATIONS_TESTNET))
+            .unwrap();
+        StacksEpoch::validate_epochs(&StacksEpoch::all(100, STACKS_EPOCH_ACTIVATIONS_REGTEST))
+            .unwrap();
+
+        assert_eq!(StacksEpoch::find_epoch(&epochs, 0), Some(0));
+        assert_eq!(StacksEpoch::find_epoch(&epochs, 99), Some(0));
+        assert_eq!(StacksEpoch::find_epoch(&epochs, 100), Some(1));
+        assert_eq!(
+            StacksEpoch::find_epoch(&epochs, STACKS_EPOCH_MAX - 1),
+            Some(1)
+        );
+        assert_eq!(StacksEpoch::find_epoch(&epochs, STACKS_EPOCH_MAX), None);
+
+        // Stacks 2.0 from the start
+        let epochs = StacksEpoch::all(0, &[]);
+        StacksEpoch::validate_epochs(&epochs).unwrap();
+        assert_eq!(StacksEpoch::find_epoch(&epochs, 0), Some(1));
+
+        // Stacks 2.1 at 200
+        let epochs = StacksEpoch::all(100, &[(StacksEpochId::Epoch21, 200)]);
+        StacksEpoch::validate_epochs(&epochs).unwrap();
+        assert_eq!(epochs.len(), 3);
+        assert_eq!(
+            StacksEpoch::epoch_id_at(&epochs, 99),
+            StacksEpochId::Epoch10
+        );
+        assert_eq!(
+            StacksEpoch::epoch_id_at(&epochs, 199),
+            StacksEpochId::Epoch20
+        );
+        assert_eq!(
+            StacksEpoch::epoch_id_at(&epochs, 200),
+            StacksEpochId::Epoch21
+        );
+        assert_eq!(
+            StacksEpoch::epoch_id_at(&epochs, STACKS_EPOCH_MAX),
+            StacksEpochId::latest()
+        );
+
+        // Stacks 2.1 activating before Stacks 2.0 starts leaves Stacks 2.0 empty
+        let epochs = StacksEpoch::all(100, &[(StacksEpochId::Epoch21, 0)]);
+        StacksEpoch::validate_epochs(&epochs).unwrap();
+        assert_eq!(epochs[1].start_height, epochs[1].end_height);
+        assert_eq!(
+            StacksEpoch::epoch_id_at(&epochs, 99),
+            StacksEpochId::Epoch10
+        );
+        assert_eq!(
+            StacksEpoch::epoch_id_at(&epochs, 100),
+            StacksEpochId::Epoch21
+        );
+        assert_eq!(epochs, StacksEpoch::all_latest(100));
+
+        // gap
+        let mut bad_epochs = StacksEpoch::all(100, &[]);
+        bad_epochs[1].start_height = 101;
+        assert!(StacksEpoch::validate_epochs(&bad_epochs).is_err());
+
+        // out of order
+        let mut bad_epochs = StacksEpoch::all(100, &[]);
+        bad_epochs[0].epoch_id = StacksEpochId::Epoch20;
+        assert!(StacksEpoch::validate_epochs(&bad_epochs).is_err());
+
+        // doesn't reach the end
+        let mut bad_epochs = StacksEpoch::all(100, &[]);
+        bad_epochs[1].end_height = 1000;
+        assert!(StacksEpoch::validate_epochs(&bad_epochs).is_err());
+
+        assert!(StacksEpoch::validate_epochs(&[]).is_err());
+
+        for epoch_id in [
+            StacksEpochId::Epoch10,
+            StacksEpochId::Epoch20,
+            StacksEpochId::Epoch21,
+        ]
+        .iter()
+        {
+            assert_eq!(StacksEpochId::try_from(*epoch_id as u32), Ok(*epoch_id));
+        }
+        assert_eq!(StacksEpochId::try_from(0), Err(()));
+    }
+}
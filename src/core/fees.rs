@@ -0,0 +1,303 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Fee estimation.
+//!
+//! Each time the mempool sees a new canonical Stacks chain tip, it records how full the tip's
+//! block was, and the fee rate (per byte) of each transaction the block mined.  If the mempool
+//! had seen the transaction before, it also records how many blocks the transaction waited to be
+//! mined.  A `FeeEstimator` built from the last `FEE_ESTIMATE_WINDOW` blocks recommends three fee
+//! rates:  the median fee rate of transactions mined within `HIGH_PRIORITY_MAX_DELAY` blocks
+//! (high), within `MEDIUM_PRIORITY_MAX_DELAY` blocks (medium), and of all mined transactions
+//! (low).  If recent blocks had plenty of room, any fee rate will do for a low-priority
+//! transaction.
+//!
+//! A transaction's `fee_rate` field is the total fee it pays, in microSTX:  that is what the
+//! chainstate debits from the paying account.  Everywhere else, a "fee rate" is that fee divided
+//! by the transaction's length in bytes, as computed by `fee_rate_per_byte()`.  The mempool admits,
+//! ranks and evicts transactions by it, and the fee samples record it.
+
+use chainstate::stacks::db::blocks::MINIMUM_TX_FEE_RATE_PER_BYTE;
+use std::cmp;
+use vm::costs::ExecutionCost;
+
+/// Number of recent Stacks blocks to estimate fees from
+pub const FEE_ESTIMATE_WINDOW: u64 = 32;
+/// A high-priority transaction should be mined in the next block
+pub const HIGH_PRIORITY_MAX_DELAY: u64 = 1;
+/// A medium-priority transaction should be mined within this many blocks
+pub const MEDIUM_PRIORITY_MAX_DELAY: u64 = 3;
+/// If the median block is at most this full (in percent), blocks have room to spare
+pub const UNCONGESTED_BLOCK_FILL: u64 = 50;
+/// If the median block is at least this full (in percent), blocks are congested
+pub const CONGESTED_BLOCK_FILL: u64 = 90;
+
+/// A transaction mined in a recent block
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeSample {
+    /// fee paid per byte
+    pub fee_rate: u64,
+    /// number of blocks between the mempool accepting the transaction and a block mining it, if
+    /// the mempool saw it before it was mined
+    pub delay: Option<u64>,
+}
+
+/// The fee samples of a recent block
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockFeeStats {
+    pub block_height: u64,
+    /// how much of the block's execution budget it used, in percent
+    pub fill: u64,
+    pub samples: Vec<FeeSample>,
+}
+
+/// Recommended fee rates (per byte)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeeRateEstimate {
+    pub low: u64,
+    pub medium: u64,
+    pub high: u64,
+}
+
+impl FeeRateEstimate {
+    /// The total fees to pay for a transaction of the given length
+    pub fn fees_for_len(&self, len: u64) -> FeeRateEstimate {
+        FeeRateEstimate {
+            low: self.low.saturating_mul(len),
+            medium: self.medium.saturating_mul(len),
+            high: self.high.saturating_mul(len),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeEstimator {
+    blocks: Vec<BlockFeeStats>,
+}
+
+/// The fee rate (per byte) of a transaction of `len` bytes that pays `fee` in total
+pub fn fee_rate_per_byte(fee: u64, len: u64) -> u64 {
+    fee / cmp::max(len, 1)
+}
+
+/// How much of `limit` the given cost uses, in percent, measured along its fullest dimension
+pub fn block_fill_percent(cost: &ExecutionCost, limit: &ExecutionCost) -> u64 {
+    let dimensions = [
+        (cost.runtime, limit.runtime),
+        (cost.write_length, limit.write_length),
+        (cost.write_count, limit.write_count),
+        (cost.read_length, limit.read_length),
+        (cost.read_count, limit.read_count),
+    ];
+    dimensions
+        .iter()
+        .map(|(used, budget)| {
+            if *budget == 0 {
+                0
+            } else {
+                ((*used as u128) * 100 / (*budget as u128)) as u64
+            }
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+fn median(mut values: Vec<u64>) -> Option<u64> {
+    if values.len() == 0 {
+        return None;
+    }
+    values.sort();
+    Some(values[values.len() / 2])
+}
+
+impl FeeEstimator {
+    pub fn new(blocks: Vec<BlockFeeStats>) -> FeeEstimator {
+        FeeEstimator { blocks }
+    }
+
+    /// Number of mined transactions the estimate is based on
+    pub fn num_samples(&self) -> u64 {
+        self.blocks.iter().map(|b| b.samples.len() as u64).sum()
+    }
+
+    /// Number of blocks the estimate is based on
+    pub fn num_blocks(&self) -> u64 {
+        self.blocks.len() as u64
+    }
+
+    /// How full the median recent block was, in percent (0 if there are no blocks)
+    pub fn median_block_fill(&self) -> u64 {
+        median(self.blocks.iter().map(|b| b.fill).collect()).unwrap_or(0)
+    }
+
+    fn median_fee_rate(&self, max_delay: Option<u64>) -> Option<u64> {
+        let fee_rates = self
+            .blocks
+            .iter()
+            .flat_map(|b| b.samples.iter())
+            .filter(|sample| match (max_delay, sample.delay) {
+                (None, _) => true,
+                (Some(max_delay), Some(delay)) => delay <= max_delay,
+                (Some(_), None) => false,
+            })
+            .map(|sample| sample.fee_rate)
+            .collect();
+        median(fee_rates)
+    }
+
+    /// Recommend low, medium and high fee rates.  Each is at least the minimum fee rate, and no
+    /// higher priority is cheaper than a lower one.
+    pub fn estimate_fee_rates(&self) -> FeeRateEstimate {
+        let low = if self.median_block_fill() <= UNCONGESTED_BLOCK_FILL {
+            MINIMUM_TX_FEE_RATE_PER_BYTE
+        } else {
+            self.median_fee_rate(None)
+                .unwrap_or(MINIMUM_TX_FEE_RATE_PER_BYTE)
+                .max(MINIMUM_TX_FEE_RATE_PER_BYTE)
+        };
+        let medium = self
+            .median_fee_rate(Some(MEDIUM_PRIORITY_MAX_DELAY))
+            .unwrap_or(low)
+            .max(low);
+        let high = self
+            .median_fee_rate(Some(HIGH_PRIORITY_MAX_DELAY))
+            .unwrap_or(medium)
+            .max(medium);
+
+        FeeRateEstimate { low, medium, high }
+    }
+
+    /// Lowest fee rate a miner should consider when assembling a block, if any.  If recent blocks
+    /// were congested, transactions paying less than the low estimate would be unlikely to fit
+    /// anyway.
+    pub fn block_assembly_min_fee_rate(&self) -> Option<u64> {
+        if self.median_block_fill() >= CONGESTED_BLOCK_FILL {
+            Some(self.estimate_fee_rates().low)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn block(height: u64, fill: u64, samples: &[(u64, Option<u64>)]) -> BlockFeeStats {
+        BlockFeeStats {
+            block_height: height,
+            fill,
+            samples: samples
+                .iter()
+                .map(|(fee_rate, delay)| FeeSample {
+                    fee_rate: *fee_rate,
+                    delay: *delay,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_block_fill_percent() {
+        let limit = ExecutionCost {
+            runtime: 1000,
+            write_length: 100,
+            write_count: 10,
+            read_length: 100,
+            read_count: 0,
+        };
+        let cost = ExecutionCost {
+            runtime: 250,
+            write_length: 50,
+            write_count: 1,
+            read_length: 0,
+            read_count: 5,
+        };
+        assert_eq!(block_fill_percent(&cost, &limit), 50);
+        assert_eq!(block_fill_percent(&ExecutionCost::zero(), &limit), 0);
+        assert_eq!(block_fill_percent(&limit, &limit), 100);
+    }
+
+    #[test]
+    fn test_estimate_without_samples() {
+        let estimator = FeeEstimator::new(vec![]);
+        let estimate = estimator.estimate_fee_rates();
+        assert_eq!(
+            estimate,
+            FeeRateEstimate {
+                low: MINIMUM_TX_FEE_RATE_PER_BYTE,
+                medium: MINIMUM_TX_FEE_RATE_PER_BYTE,
+                high: MINIMUM_TX_FEE_RATE_PER_BYTE,
+            }
+        );
+        assert_eq!(estimator.block_assembly_min_fee_rate(), None);
+        assert_eq!(estimator.num_samples(), 0);
+    }
+
+    #[test]
+    fn test_estimate_congested_blocks() {
+        let estimator = FeeEstimator::new(vec![
+            block(1, 95, &[(50, Some(1)), (10, Some(5)), (20, Some(3))]),
+            block(2, 100, &[(40, Some(1)), (5, None), (30, Some(2))]),
+            block(3, 92, &[(60, Some(0)), (8, Some(10))]),
+        ]);
+        assert_eq!(estimator.num_samples(), 8);
+        assert_eq!(estimator.median_block_fill(), 95);
+
+        let estimate = estimator.estimate_fee_rates();
+        // all: 5, 8, 10, 20, 30, 40, 50, 60
+        assert_eq!(estimate.low, 30);
+        // delay <= 3: 20, 30, 40, 50, 60
+        assert_eq!(estimate.medium, 40);
+        // delay <= 1: 40, 50, 60
+        assert_eq!(estimate.high, 50);
+        assert_eq!(estimator.block_assembly_min_fee_rate(), Some(30));
+
+        let fees = estimate.fees_for_len(200);
+        assert_eq!(fees.low, 6000);
+        assert_eq!(fees.medium, 8000);
+        assert_eq!(fees.high, 10000);
+    }
+
+    #[test]
+    fn test_estimate_uncongested_blocks() {
+        let estimator = FeeEstimator::new(vec![
+            block(1, 10, &[(50, Some(1)), (100, None)]),
+            block(2, 20, &[(3, Some(7))]),
+        ]);
+        let estimate = estimator.estimate_fee_rates();
+        // blocks had room, so the minimum fee rate will do
+        assert_eq!(estimate.low, MINIMUM_TX_FEE_RATE_PER_BYTE);
+        // no samples mined within 3 blocks besides the fast one
+        assert_eq!(estimate.medium, 50);
+        assert_eq!(estimate.high, 50);
+        assert_eq!(estimator.block_assembly_min_fee_rate(), None);
+    }
+
+    #[test]
+    fn test_estimate_is_monotonic() {
+        // fast transactions paid less than slow ones
+        let estimator = FeeEstimator::new(vec![block(
+            1,
+            70,
+            &[(2, Some(1)), (90, Some(8)), (80, Some(9))],
+        )]);
+        let estimate = estimator.estimate_fee_rates();
+        assert_eq!(estimate.low, 80);
+        assert_eq!(estimate.medium, 80);
+        assert_eq!(estimate.high, 80);
+    }
+}
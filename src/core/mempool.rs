@@ -23,6 +23,7 @@ use rusqlite::Transaction;
 use rusqlite::NO_PARAMS;
 
 use std::cmp;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
 use std::ops::Deref;
 use std::ops::DerefMut;
 
@@ -34,13 +35,17 @@ use net::StacksMessageCodec;
 use chainstate::burn::BlockHeaderHash;
 use chainstate::stacks::{
     db::blocks::MemPoolRejection, db::StacksChainState, index::Error as MarfError,
-    Error as ChainstateError, StacksAddress, StacksBlockHeader, StacksTransaction,
+    Error as ChainstateError, StacksAddress, StacksBlockHeader, StacksBlockId, StacksTransaction,
+    TransactionPayload,
 };
 use std::fs;
 use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 
+use util::db::query_int;
 use util::db::query_row;
+use util::db::query_row_columns;
 use util::db::query_rows;
 use util::db::tx_begin_immediate;
 use util::db::tx_busy_handler;
@@ -50,14 +55,60 @@ use util::db::FromColumn;
 use util::db::{DBConn, DBTx, FromRow};
 use util::get_epoch_time_secs;
 
+use core::fees::{
+    block_fill_percent, fee_rate_per_byte, BlockFeeStats, FeeEstimator, FeeSample,
+    FEE_ESTIMATE_WINDOW,
+};
 use core::FIRST_BURNCHAIN_CONSENSUS_HASH;
 use core::FIRST_STACKS_BLOCK_HASH;
 
+use monitoring;
+
+use vm::analysis::strict_checker::StrictProfile;
+use vm::costs::ExecutionCost;
+use vm::database::BurnStateDB;
+use vm::types::PrincipalData;
+
 use rusqlite::Error as SqliteError;
 
 // maximum number of confirmations a transaction can have before it's garbage-collected
 pub const MEMPOOL_MAX_TRANSACTION_AGE: u64 = 256;
 pub const MAXIMUM_MEMPOOL_TX_CHAINING: u64 = 5;
+// how far ahead of its account's next nonce a transaction can be, and still be held in the pending
+// lane until the transactions before it arrive
+pub const MAXIMUM_MEMPOOL_NONCE_GAP: u64 = 25;
+// maximum number of transactions an address can have waiting in the pending lane, as an origin or
+// a sponsor
+pub const MAXIMUM_MEMPOOL_PENDING_PER_ADDRESS: u64 = 10;
+// maximum number of transactions the pending lane holds
+pub const MEMPOOL_MAX_PENDING_TRANSACTIONS: u64 = 10_000;
+// maximum number of seconds a transaction can sit in the mempool before it's garbage-collected
+pub const MEMPOOL_MAX_TRANSACTION_AGE_SECS: u64 = 3 * 24 * 3600;
+// maximum number of transactions the mempool holds before evicting the lowest-fee ones
+pub const MEMPOOL_MAX_TRANSACTIONS: u64 = 100_000;
+
+/// Limits on how long transactions stay in the mempool, and how many it holds.
+/// A limit of 0 disables it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemPoolGCPolicy {
+    /// drop transactions accepted at a Stacks block height this many blocks below the chain tip
+    pub max_tx_age_blocks: u64,
+    /// drop transactions accepted this many seconds ago
+    pub max_tx_age_secs: u64,
+    /// drop the lowest-fee-rate transactions once the mempool holds more than this many,
+    /// counting the ones waiting in the pending lane
+    pub max_txs: u64,
+}
+
+impl Default for MemPoolGCPolicy {
+    fn default() -> MemPoolGCPolicy {
+        MemPoolGCPolicy {
+            max_tx_age_blocks: MEMPOOL_MAX_TRANSACTION_AGE,
+            max_tx_age_secs: MEMPOOL_MAX_TRANSACTION_AGE_SECS,
+            max_txs: MEMPOOL_MAX_TRANSACTIONS,
+        }
+    }
+}
 
 pub struct MemPoolAdmitter {
     // mempool admission should have its own chain state view.
@@ -68,6 +119,8 @@ pub struct MemPoolAdmitter {
     chainstate: StacksChainState,
     cur_block: BlockHeaderHash,
     cur_consensus_hash: ConsensusHash,
+    // if set, smart contracts must pass these lints to be admitted
+    strict_profile: Option<StrictProfile>,
 }
 
 impl MemPoolAdmitter {
@@ -80,6 +133,7 @@ impl MemPoolAdmitter {
             chainstate,
             cur_block,
             cur_consensus_hash,
+            strict_profile: None,
         }
     }
 
@@ -88,6 +142,15 @@ impl MemPoolAdmitter {
         self.cur_block = cur_block.clone();
     }
 
+    /// Can the paying account afford a transaction that is to wait in the pending lane?
+    pub fn will_admit_pending_tx(
+        &mut self,
+        tx: &StacksTransaction,
+    ) -> Result<(), MemPoolRejection> {
+        self.chainstate
+            .will_admit_pending_mempool_tx(&self.cur_consensus_hash, &self.cur_block, tx)
+    }
+
     pub fn will_admit_tx(
         &mut self,
         mempool_conn: &DBConn,
@@ -100,7 +163,16 @@ impl MemPoolAdmitter {
             &self.cur_block,
             tx,
             tx_size,
-        )
+        )?;
+        if let Some(ref profile) = self.strict_profile {
+            self.chainstate.will_admit_strict_contract(
+                &self.cur_consensus_hash,
+                &self.cur_block,
+                tx,
+                profile,
+            )?;
+        }
+        Ok(())
     }
 }
 
@@ -114,7 +186,7 @@ pub struct MemPoolTxInfo {
 pub struct MemPoolTxMetadata {
     pub txid: Txid,
     pub len: u64,
-    pub fee_rate: u64,
+    pub fee_rate: u64,      // the transaction's total fee; see fee_rate_per_byte()
     pub estimated_fee: u64, // upper bound on what the fee to pay will be
     pub consensus_hash: ConsensusHash,
     pub block_header_hash: BlockHeaderHash,
@@ -207,10 +279,127 @@ const MEMPOOL_SQL: &'static [&'static str] = &[
     "#,
 ];
 
+// Fee samples of recently-mined blocks.  Created on open, so mempools from before fee estimation
+// get them too.
+const FEE_ESTIMATE_SQL: &'static str = r#"
+    CREATE TABLE IF NOT EXISTS fee_estimate_blocks(
+        index_block_hash TEXT NOT NULL,
+        block_height INTEGER NOT NULL,
+        fill INTEGER NOT NULL,      -- percent of the block's execution budget used
+        PRIMARY KEY (index_block_hash)
+    );
+    CREATE TABLE IF NOT EXISTS fee_estimate_samples(
+        index_block_hash TEXT NOT NULL,
+        block_height INTEGER NOT NULL,
+        fee_rate INTEGER NOT NULL,  -- fee per byte
+        delay INTEGER               -- blocks the tx waited in the mempool, if it was seen
+    );
+    CREATE INDEX IF NOT EXISTS fee_estimate_blocks_by_height ON fee_estimate_blocks(block_height);
+    CREATE INDEX IF NOT EXISTS fee_estimate_samples_by_height ON fee_estimate_samples(block_height);
+"#;
+
+// Transactions whose nonces are ahead of their accounts' next nonces.  They are not mined, and
+// move into the mempool once the transactions that fill the gap do (or get mined).  Created on
+// open, so mempools from before the pending lane get it too.
+const MEMPOOL_PENDING_SQL: &'static str = r#"
+    CREATE TABLE IF NOT EXISTS mempool_pending(
+        txid TEXT NOT NULL,
+        origin_address TEXT NOT NULL,
+        origin_nonce INTEGER NOT NULL,
+        sponsor_address TEXT NOT NULL,
+        sponsor_nonce INTEGER NOT NULL,
+        estimated_fee INTEGER NOT NULL,     -- same as mempool.estimated_fee
+        accept_time INTEGER NOT NULL,
+        tx BLOB NOT NULL,
+        PRIMARY KEY (txid),
+        UNIQUE (origin_address, origin_nonce),
+        UNIQUE (sponsor_address, sponsor_nonce)
+    );
+    CREATE INDEX IF NOT EXISTS pending_by_origin ON mempool_pending(origin_address, origin_nonce);
+    CREATE INDEX IF NOT EXISTS pending_by_sponsor ON mempool_pending(sponsor_address, sponsor_nonce);
+    CREATE INDEX IF NOT EXISTS pending_by_timestamp ON mempool_pending(accept_time);
+"#;
+
+struct PendingTxRow {
+    txid: Txid,
+    estimated_fee: u64,
+    tx: StacksTransaction,
+}
+
+impl FromRow<PendingTxRow> for PendingTxRow {
+    fn from_row<'a>(row: &'a Row) -> Result<PendingTxRow, db_error> {
+        let txid = Txid::from_column(row, "txid")?;
+        let estimated_fee = u64::from_column(row, "estimated_fee")?;
+        let tx_bytes: Vec<u8> = row.get("tx");
+        let tx = StacksTransaction::consensus_deserialize(&mut &tx_bytes[..])
+            .map_err(|_e| db_error::ParseError)?;
+        Ok(PendingTxRow {
+            txid,
+            estimated_fee,
+            tx,
+        })
+    }
+}
+
+/// An account's nonces, as seen by the mempool on top of a chain tip
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemPoolAccountNonces {
+    /// the account's next nonce on the chain tip
+    pub confirmed_nonce: u64,
+    /// the highest nonce the account uses in the mempool (including the pending lane), if it
+    /// uses any at or above `confirmed_nonce`
+    pub mempool_max_nonce: Option<u64>,
+    /// the nonces between `confirmed_nonce` and `mempool_max_nonce` that no transaction uses.
+    /// The account's transactions after the first of these wait in the pending lane.
+    pub missing_nonces: Vec<u64>,
+}
+
+struct FeeEstimateBlockRow {
+    index_block_hash: StacksBlockId,
+    block_height: u64,
+    fill: u64,
+}
+
+impl FromRow<FeeEstimateBlockRow> for FeeEstimateBlockRow {
+    fn from_row<'a>(row: &'a Row) -> Result<FeeEstimateBlockRow, db_error> {
+        let index_block_hash = StacksBlockId::from_column(row, "index_block_hash")?;
+        let block_height = u64::from_column(row, "block_height")?;
+        let fill = u64::from_column(row, "fill")?;
+        Ok(FeeEstimateBlockRow {
+            index_block_hash,
+            block_height,
+            fill,
+        })
+    }
+}
+
+struct FeeSampleRow {
+    index_block_hash: StacksBlockId,
+    sample: FeeSample,
+}
+
+impl FromRow<FeeSampleRow> for FeeSampleRow {
+    fn from_row<'a>(row: &'a Row) -> Result<FeeSampleRow, db_error> {
+        let index_block_hash = StacksBlockId::from_column(row, "index_block_hash")?;
+        let fee_rate = u64::from_column(row, "fee_rate")?;
+        let delay: Option<i64> = row.get("delay");
+        Ok(FeeSampleRow {
+            index_block_hash,
+            sample: FeeSample {
+                fee_rate,
+                delay: delay.map(|d| d as u64),
+            },
+        })
+    }
+}
+
 pub struct MemPoolDB {
     db: DBConn,
     path: String,
     admitter: MemPoolAdmitter,
+    gc_policy: MemPoolGCPolicy,
+    // chain tip as of the last sweep for consumed nonces
+    last_gc_tip: Option<StacksBlockId>,
 }
 
 pub struct MemPoolTx<'a> {
@@ -331,6 +520,24 @@ impl MemPoolDB {
         mainnet: bool,
         chain_id: u32,
         chainstate_path: &str,
+    ) -> Result<MemPoolDB, db_error> {
+        let mut path = PathBuf::from(chainstate_path);
+        path.push("mempool.db");
+        let db_path = path
+            .to_str()
+            .ok_or_else(|| db_error::ParseError)?
+            .to_string();
+
+        MemPoolDB::open_at(mainnet, chain_id, chainstate_path, &db_path)
+    }
+
+    /// Open the mempool db at `db_path`, which need not be within the chainstate directory.
+    /// The chainstate must be instantiated already.
+    pub fn open_at(
+        mainnet: bool,
+        chain_id: u32,
+        chainstate_path: &str,
+        db_path: &str,
     ) -> Result<MemPoolDB, db_error> {
         match fs::metadata(chainstate_path) {
             Ok(md) => {
@@ -346,22 +553,14 @@ impl MemPoolDB {
         let (chainstate, _) = StacksChainState::open(mainnet, chain_id, chainstate_path)
             .map_err(|e| db_error::Other(format!("Failed to open chainstate: {:?}", &e)))?;
 
-        let mut path = PathBuf::from(chainstate.root_path.clone());
-
         let admitter = MemPoolAdmitter::new(
             chainstate,
             BlockHeaderHash([0u8; 32]),
             ConsensusHash([0u8; 20]),
         );
 
-        path.push("mempool.db");
-        let db_path = path
-            .to_str()
-            .ok_or_else(|| db_error::ParseError)?
-            .to_string();
-
         let mut create_flag = false;
-        let open_flags = if fs::metadata(&db_path).is_err() {
+        let open_flags = if fs::metadata(db_path).is_err() {
             // need to create
             create_flag = true;
             OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE
@@ -371,7 +570,7 @@ impl MemPoolDB {
         };
 
         let mut conn =
-            DBConn::open_with_flags(&db_path, open_flags).map_err(db_error::SqliteError)?;
+            DBConn::open_with_flags(db_path, open_flags).map_err(db_error::SqliteError)?;
         conn.busy_handler(Some(tx_busy_handler))
             .map_err(db_error::SqliteError)?;
 
@@ -379,14 +578,34 @@ impl MemPoolDB {
             // instantiate!
             MemPoolDB::instantiate_mempool_db(&mut conn)?;
         }
+        conn.execute_batch(FEE_ESTIMATE_SQL)
+            .map_err(db_error::SqliteError)?;
+        conn.execute_batch(MEMPOOL_PENDING_SQL)
+            .map_err(db_error::SqliteError)?;
 
         Ok(MemPoolDB {
             db: conn,
             path: db_path.to_string(),
             admitter: admitter,
+            gc_policy: MemPoolGCPolicy::default(),
+            last_gc_tip: None,
         })
     }
 
+    pub fn gc_policy(&self) -> &MemPoolGCPolicy {
+        &self.gc_policy
+    }
+
+    pub fn set_gc_policy(&mut self, gc_policy: MemPoolGCPolicy) {
+        self.gc_policy = gc_policy;
+    }
+
+    /// Require smart contracts to pass the given lints before they are admitted.  Intended for
+    /// private networks; `None` admits any contract the chain would accept.
+    pub fn set_strict_profile(&mut self, strict_profile: Option<StrictProfile>) {
+        self.admitter.strict_profile = strict_profile;
+    }
+
     fn walk(
         &self,
         chainstate: &mut StacksChainState,
@@ -590,6 +809,130 @@ impl MemPoolDB {
         }
     }
 
+    ///
+    /// Iterate over candidates in the mempool, highest fee rate first.
+    ///  Gathers every candidate transaction that iterate_candidates() would
+    ///  visit, and passes them to todo in a single batch, ordered by
+    ///  order_by_fee_rate().  If recent blocks were congested, candidates paying
+    ///  less than the fee estimator's block assembly minimum are left out.
+    pub fn iterate_candidates_by_fee_rate<F, E>(
+        &self,
+        tip_consensus_hash: &ConsensusHash,
+        tip_block_hash: &BlockHeaderHash,
+        tip_height: u64,
+        chainstate: &mut StacksChainState,
+        mut todo: F,
+    ) -> Result<(), E>
+    where
+        F: FnMut(Vec<MemPoolTxInfo>) -> Result<(), E>,
+        E: From<db_error> + From<ChainstateError>,
+    {
+        let mut candidates = vec![];
+        self.iterate_candidates::<_, E>(
+            tip_consensus_hash,
+            tip_block_hash,
+            tip_height,
+            chainstate,
+            |available_txs| {
+                candidates.extend(available_txs);
+                Ok(())
+            },
+        )?;
+
+        if let Some(min_fee_rate) =
+            MemPoolDB::get_fee_estimator(&self.db, tip_height)?.block_assembly_min_fee_rate()
+        {
+            debug!(
+                "Recent blocks are congested; skip mempool transactions paying less than {} per byte",
+                min_fee_rate
+            );
+            candidates.retain(|txinfo| {
+                fee_rate_per_byte(txinfo.metadata.fee_rate, txinfo.metadata.len) >= min_fee_rate
+            });
+        }
+
+        if candidates.len() == 0 {
+            return Ok(());
+        }
+
+        debug!(
+            "Considering {} mempool transactions off of {}/{} in fee-rate order",
+            candidates.len(),
+            tip_consensus_hash,
+            tip_block_hash
+        );
+        todo(MemPoolDB::order_by_fee_rate(candidates))
+    }
+
+    /// Order transactions so that the highest fee rates (per byte) come first, without ever
+    /// placing a transaction before one from the same origin with a lower nonce (it could not be
+    /// mined before it anyway).  Each origin's pending transactions are considered in nonce order, and
+    /// the origin whose next transaction pays the highest fee rate goes next.  Ties are broken
+    /// in favor of the transaction that was accepted first.
+    pub fn order_by_fee_rate(txs: Vec<MemPoolTxInfo>) -> Vec<MemPoolTxInfo> {
+        MemPoolDB::order_by_priority(txs, |txinfo| {
+            fee_rate_per_byte(txinfo.metadata.fee_rate, txinfo.metadata.len)
+        })
+    }
+
+    /// Order transactions like order_by_fee_rate(), but by the given priority instead of by fee
+    /// rate.
+    pub fn order_by_priority<F>(txs: Vec<MemPoolTxInfo>, priority: F) -> Vec<MemPoolTxInfo>
+    where
+        F: Fn(&MemPoolTxInfo) -> u64,
+    {
+        let num_txs = txs.len();
+        let mut origins: HashMap<StacksAddress, usize> = HashMap::new();
+        let mut queues: Vec<Vec<MemPoolTxInfo>> = vec![];
+        for txinfo in txs.into_iter() {
+            let idx = match origins.get(&txinfo.metadata.origin_address) {
+                Some(idx) => *idx,
+                None => {
+                    origins.insert(txinfo.metadata.origin_address.clone(), queues.len());
+                    queues.push(vec![]);
+                    queues.len() - 1
+                }
+            };
+            queues[idx].push(txinfo);
+        }
+
+        let mut queues: Vec<VecDeque<MemPoolTxInfo>> = queues
+            .into_iter()
+            .map(|mut queue| {
+                queue.sort_by_key(|txinfo| txinfo.metadata.origin_nonce);
+                queue.into()
+            })
+            .collect();
+
+        // (priority, earliest acceptance, queue index) of each origin's next transaction
+        let mut heap = BinaryHeap::new();
+        for (idx, queue) in queues.iter().enumerate() {
+            if let Some(txinfo) = queue.front() {
+                heap.push((
+                    priority(txinfo),
+                    cmp::Reverse(txinfo.metadata.accept_time),
+                    cmp::Reverse(idx),
+                ));
+            }
+        }
+
+        let mut ordered = Vec::with_capacity(num_txs);
+        while let Some((_, _, cmp::Reverse(idx))) = heap.pop() {
+            let txinfo = queues[idx]
+                .pop_front()
+                .expect("BUG: no transaction for queued origin");
+            ordered.push(txinfo);
+            if let Some(next_txinfo) = queues[idx].front() {
+                heap.push((
+                    priority(next_txinfo),
+                    cmp::Reverse(next_txinfo.metadata.accept_time),
+                    cmp::Reverse(idx),
+                ));
+            }
+        }
+        ordered
+    }
+
     pub fn conn(&self) -> &DBConn {
         &self.db
     }
@@ -769,6 +1112,49 @@ impl MemPoolDB {
         Ok(cmp::max(as_origin, as_sponsor))
     }
 
+    /// Get the nonces an address uses as an origin or a sponsor in the mempool, including the
+    /// pending lane, in ascending order.
+    pub fn get_nonces_for_address(
+        conn: &DBConn,
+        address: &StacksAddress,
+    ) -> Result<Vec<u64>, db_error> {
+        let sql = "SELECT origin_nonce AS nonce FROM mempool WHERE origin_address = ?1
+                   UNION SELECT sponsor_nonce AS nonce FROM mempool WHERE sponsor_address = ?1
+                   UNION SELECT origin_nonce AS nonce FROM mempool_pending WHERE origin_address = ?1
+                   UNION SELECT sponsor_nonce AS nonce FROM mempool_pending WHERE sponsor_address = ?1
+                   ORDER BY nonce ASC"
+            .to_string();
+        let args: &[&dyn ToSql] = &[&address.to_string()];
+        query_row_columns(conn, &sql, args, "nonce")
+    }
+
+    /// Work out which of an account's nonces the mempool has transactions for, given its next
+    /// nonce on the chain tip.  Nonces below `confirmed_nonce` (e.g. of transactions accepted on
+    /// another fork) are ignored.
+    pub fn get_account_nonces(
+        conn: &DBConn,
+        address: &StacksAddress,
+        confirmed_nonce: u64,
+    ) -> Result<MemPoolAccountNonces, db_error> {
+        let nonces: Vec<u64> = MemPoolDB::get_nonces_for_address(conn, address)?
+            .into_iter()
+            .filter(|nonce| *nonce >= confirmed_nonce)
+            .collect();
+
+        let mut missing_nonces = vec![];
+        let mut expected = confirmed_nonce;
+        for nonce in nonces.iter() {
+            missing_nonces.extend(expected..*nonce);
+            expected = *nonce + 1;
+        }
+
+        Ok(MemPoolAccountNonces {
+            confirmed_nonce,
+            mempool_max_nonce: nonces.last().cloned(),
+            missing_nonces,
+        })
+    }
+
     /// Add a transaction to the mempool.  If it already exists, then replace it if the given fee
     /// is higher than the one that's already there.
     /// Carry out the mempool admission test before adding.
@@ -802,32 +1188,24 @@ impl MemPoolDB {
             }
         };
 
-        // if so, is this a replace-by-fee? or a replace-in-chain-tip?
-        let add_tx = if let Some(prior_tx) = prior_tx {
-            if estimated_fee > prior_tx.estimated_fee {
-                // is this a replace-by-fee ?
-                true
-            } else if !tx.is_block_in_fork(
-                &prior_tx.consensus_hash,
-                &prior_tx.block_header_hash,
-                consensus_hash,
-                block_header_hash,
-            )? {
-                // is this a replace-across-fork ?
-                true
-            } else {
+        // if so, is this a replace-by-fee? or a replace-across-fork?
+        if let Some(prior_tx) = prior_tx {
+            if estimated_fee <= prior_tx.estimated_fee
+                && tx.is_block_in_fork(
+                    &prior_tx.consensus_hash,
+                    &prior_tx.block_header_hash,
+                    consensus_hash,
+                    block_header_hash,
+                )?
+            {
                 // there's a >= fee tx in this fork, cannot add
                 info!("TX conflicts with sponsor/origin nonce in same fork with >= fee: new_txid={}, old_txid={}, origin_addr={}, origin_nonce={}, sponsor_addr={}, sponsor_nonce={}, new_fee={}, old_fee={}",
                       txid, prior_tx.txid, origin_address, origin_nonce, sponsor_address, sponsor_nonce, estimated_fee, prior_tx.estimated_fee);
-                false
+                return Err(MemPoolRejection::ConflictingNonceInMempool(
+                    prior_tx.txid,
+                    prior_tx.estimated_fee,
+                ));
             }
-        } else {
-            // no conflicting TX with this origin/sponsor, go ahead and add
-            true
-        };
-
-        if !add_tx {
-            return Err(MemPoolRejection::ConflictingNonceInMempool);
         }
 
         let sql = "INSERT OR REPLACE INTO mempool (
@@ -867,6 +1245,218 @@ impl MemPoolDB {
         Ok(())
     }
 
+    /// Estimate the fee a transaction will pay.  This is an upper bound, and is what the mempool
+    /// and its pending lane store as `estimated_fee`, and compare when one transaction replaces
+    /// another.  The transaction's `fee_rate` field is already its total fee.
+    fn estimate_tx_fee(tx: &StacksTransaction) -> u64 {
+        tx.get_fee_rate()
+    }
+
+    /// Hold a transaction in the pending lane until the transactions before it arrive.  If another
+    /// pending transaction uses the same origin or sponsor nonce, then replace it only if this one
+    /// pays a higher fee.
+    /// The paying account must be able to afford the transaction at the given chain tip, and
+    /// neither the pending lane nor the transaction's origin and sponsor can already have too
+    /// many transactions waiting in it.
+    fn add_pending_tx<'a>(
+        mempool_tx: &mut MemPoolTx<'a>,
+        consensus_hash: &ConsensusHash,
+        block_hash: &BlockHeaderHash,
+        tx: &StacksTransaction,
+    ) -> Result<(), MemPoolRejection> {
+        let txid = tx.txid();
+        let mut tx_bytes = vec![];
+        tx.consensus_serialize(&mut tx_bytes)
+            .map_err(MemPoolRejection::SerializationFailure)?;
+        let estimated_fee = MemPoolDB::estimate_tx_fee(tx);
+
+        mempool_tx
+            .admitter
+            .set_block(&block_hash, (*consensus_hash).clone());
+        mempool_tx.admitter.will_admit_pending_tx(tx)?;
+
+        let origin_address = tx.origin_address();
+        let origin_nonce = tx.get_origin_nonce();
+        let (sponsor_address, sponsor_nonce) =
+            if let (Some(addr), Some(nonce)) = (tx.sponsor_address(), tx.get_sponsor_nonce()) {
+                (addr, nonce)
+            } else {
+                (origin_address.clone(), origin_nonce)
+            };
+
+        let sql = "SELECT * FROM mempool_pending WHERE (origin_address = ?1 AND origin_nonce = ?2) OR (sponsor_address = ?3 AND sponsor_nonce = ?4)";
+        let args: &[&dyn ToSql] = &[
+            &origin_address.to_string(),
+            &u64_to_sql(origin_nonce)?,
+            &sponsor_address.to_string(),
+            &u64_to_sql(sponsor_nonce)?,
+        ];
+        let prior_txs: Vec<PendingTxRow> = query_rows(mempool_tx, sql, args)?;
+        for prior_tx in prior_txs.iter() {
+            if prior_tx.txid == txid {
+                // already pending
+                return Ok(());
+            }
+            if estimated_fee <= prior_tx.estimated_fee {
+                return Err(MemPoolRejection::ConflictingNonceInMempool(
+                    prior_tx.txid.clone(),
+                    prior_tx.estimated_fee,
+                ));
+            }
+        }
+
+        // a replacement doesn't take up any more room
+        if prior_txs.len() == 0 {
+            let num_pending = query_int(
+                mempool_tx,
+                &"SELECT COUNT(*) FROM mempool_pending".to_string(),
+                NO_PARAMS,
+            )? as u64;
+            if num_pending >= MEMPOOL_MAX_PENDING_TRANSACTIONS {
+                return Err(MemPoolRejection::TooManyPending);
+            }
+
+            let mut addresses = vec![&origin_address];
+            if sponsor_address != origin_address {
+                addresses.push(&sponsor_address);
+            }
+            for address in addresses.into_iter() {
+                let sql = "SELECT COUNT(*) FROM mempool_pending WHERE origin_address = ?1 OR sponsor_address = ?1".to_string();
+                let args: &[&dyn ToSql] = &[&address.to_string()];
+                let num_address_pending = query_int(mempool_tx, &sql, args)? as u64;
+                if num_address_pending >= MAXIMUM_MEMPOOL_PENDING_PER_ADDRESS {
+                    return Err(MemPoolRejection::TooManyPending);
+                }
+            }
+        }
+        for prior_tx in prior_txs.iter() {
+            mempool_tx
+                .execute(
+                    "DELETE FROM mempool_pending WHERE txid = ?1",
+                    &[&prior_tx.txid],
+                )
+                .map_err(|e| MemPoolRejection::DBError(db_error::SqliteError(e)))?;
+        }
+
+        debug!(
+            "Hold tx {} in the pending lane: origin {} nonce {}, sponsor {} nonce {}",
+            &txid, &origin_address, origin_nonce, &sponsor_address, sponsor_nonce
+        );
+        let sql = "INSERT INTO mempool_pending (
+            txid,
+            origin_address,
+            origin_nonce,
+            sponsor_address,
+            sponsor_nonce,
+            estimated_fee,
+            accept_time,
+            tx)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)";
+        let args: &[&dyn ToSql] = &[
+            &txid,
+            &origin_address.to_string(),
+            &u64_to_sql(origin_nonce)?,
+            &sponsor_address.to_string(),
+            &u64_to_sql(sponsor_nonce)?,
+            &u64_to_sql(estimated_fee)?,
+            &u64_to_sql(get_epoch_time_secs())?,
+            &tx_bytes,
+        ];
+        mempool_tx
+            .execute(sql, args)
+            .map_err(|e| MemPoolRejection::DBError(db_error::SqliteError(e)))?;
+        Ok(())
+    }
+
+    /// Move the pending transactions of the given addresses whose nonce gaps have been filled
+    /// into the mempool, as of the given chain tip.  Pending transactions that can no longer be
+    /// admitted (e.g. because their nonces were consumed) are dropped; those still ahead of
+    /// their accounts' next nonces keep waiting.
+    fn release_pending_txs<'a>(
+        mempool_tx: &mut MemPoolTx<'a>,
+        consensus_hash: &ConsensusHash,
+        block_hash: &BlockHeaderHash,
+        addresses: &[StacksAddress],
+    ) -> Result<(), db_error> {
+        // releasing one account's transaction can unblock another's, if one sponsors the other
+        let mut released = true;
+        while released {
+            released = false;
+            for address in addresses.iter() {
+                let sql = "SELECT * FROM mempool_pending WHERE origin_address = ?1 OR sponsor_address = ?1 ORDER BY origin_nonce ASC, sponsor_nonce ASC";
+                let args: &[&dyn ToSql] = &[&address.to_string()];
+                let pending_txs: Vec<PendingTxRow> = query_rows(mempool_tx, sql, args)?;
+
+                for pending_tx in pending_txs.into_iter() {
+                    let keep = match MemPoolDB::tx_submit_at_nonce(
+                        mempool_tx,
+                        consensus_hash,
+                        block_hash,
+                        &pending_tx.tx,
+                        true,
+                    ) {
+                        Ok(()) => {
+                            debug!("Release tx {} from the pending lane", &pending_tx.txid);
+                            released = true;
+                            false
+                        }
+                        Err(MemPoolRejection::BadNonces(ref mismatch))
+                            if mismatch.actual > mismatch.expected =>
+                        {
+                            true
+                        }
+                        Err(MemPoolRejection::DBError(e)) => {
+                            return Err(e);
+                        }
+                        Err(e) => {
+                            debug!(
+                                "Drop tx {} from the pending lane: {:?}",
+                                &pending_tx.txid, &e
+                            );
+                            false
+                        }
+                    };
+                    if !keep {
+                        mempool_tx
+                            .execute(
+                                "DELETE FROM mempool_pending WHERE txid = ?1",
+                                &[&pending_tx.txid],
+                            )
+                            .map_err(db_error::SqliteError)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Move every pending transaction whose nonce gap has been filled into the mempool, as of the
+    /// given chain tip.  Used when the tip changes, since the gaps can be filled by mined
+    /// transactions the mempool never saw.
+    fn release_all_pending_txs<'a>(
+        mempool_tx: &mut MemPoolTx<'a>,
+        consensus_hash: &ConsensusHash,
+        block_hash: &BlockHeaderHash,
+    ) -> Result<(), db_error> {
+        let sql = "SELECT origin_address AS address FROM mempool_pending UNION SELECT sponsor_address AS address FROM mempool_pending".to_string();
+        let addresses =
+            query_row_columns::<StacksAddress, _>(mempool_tx, &sql, NO_PARAMS, "address")?;
+        MemPoolDB::release_pending_txs(mempool_tx, consensus_hash, block_hash, &addresses)
+    }
+
+    /// Remove a transaction from the mempool, whether or not it is waiting on a nonce gap.
+    /// Returns true if it was there.
+    pub fn evict_tx<'a>(tx: &mut MemPoolTx<'a>, txid: &Txid) -> Result<bool, db_error> {
+        let args: &[&dyn ToSql] = &[txid];
+        let num_evicted = tx
+            .execute("DELETE FROM mempool WHERE txid = ?1", args)
+            .map_err(db_error::SqliteError)?;
+        let num_pending_evicted = tx
+            .execute("DELETE FROM mempool_pending WHERE txid = ?1", args)
+            .map_err(db_error::SqliteError)?;
+        Ok(num_evicted + num_pending_evicted > 0)
+    }
+
     /// Garbage-collect the mempool.  Remove transactions that have a given number of
     /// confirmations.
     pub fn garbage_collect<'a>(tx: &mut MemPoolTx<'a>, min_height: u64) -> Result<(), db_error> {
@@ -877,6 +1467,304 @@ impl MemPoolDB {
         Ok(())
     }
 
+    /// Garbage-collect the mempool.  Remove transactions that were accepted before a given time.
+    pub fn garbage_collect_by_time<'a>(
+        tx: &mut MemPoolTx<'a>,
+        min_accept_time: u64,
+    ) -> Result<(), db_error> {
+        let sql = "DELETE FROM mempool WHERE accept_time < ?1";
+        let args: &[&dyn ToSql] = &[&u64_to_sql(min_accept_time)?];
+        tx.execute(sql, args).map_err(db_error::SqliteError)?;
+
+        let sql = "DELETE FROM mempool_pending WHERE accept_time < ?1";
+        tx.execute(sql, args).map_err(db_error::SqliteError)?;
+        Ok(())
+    }
+
+    /// Garbage-collect the mempool.  If it holds more than `max_txs` transactions, counting the
+    /// ones in the pending lane, then remove the ones with the lowest fee rates per byte (most
+    /// recently-accepted first) until it doesn't.  Pending transactions can't be mined yet, so
+    /// they are removed first.
+    pub fn garbage_collect_by_size<'a>(
+        tx: &mut MemPoolTx<'a>,
+        max_txs: u64,
+    ) -> Result<(), db_error> {
+        let num_txs = query_int(tx, &"SELECT COUNT(*) FROM mempool".to_string(), NO_PARAMS)? as u64;
+        let num_pending = query_int(
+            tx,
+            &"SELECT COUNT(*) FROM mempool_pending".to_string(),
+            NO_PARAMS,
+        )? as u64;
+        if num_txs + num_pending <= max_txs {
+            return Ok(());
+        }
+
+        let num_evict = num_txs + num_pending - max_txs;
+        let num_evict_pending = cmp::min(num_evict, num_pending);
+        debug!(
+            "Mempool has {} transactions and {} pending; evicting the {} with the lowest fee rates",
+            num_txs, num_pending, num_evict
+        );
+        if num_evict_pending > 0 {
+            let sql = "DELETE FROM mempool_pending WHERE txid IN (SELECT txid FROM mempool_pending ORDER BY CAST(estimated_fee AS REAL) / MAX(LENGTH(tx), 1) ASC, accept_time DESC LIMIT ?1)";
+            let args: &[&dyn ToSql] = &[&u64_to_sql(num_evict_pending)?];
+            tx.execute(sql, args).map_err(db_error::SqliteError)?;
+        }
+        if num_evict > num_evict_pending {
+            let sql = "DELETE FROM mempool WHERE txid IN (SELECT txid FROM mempool ORDER BY CAST(fee_rate AS REAL) / MAX(length, 1) ASC, accept_time DESC LIMIT ?1)";
+            let args: &[&dyn ToSql] = &[&u64_to_sql(num_evict - num_evict_pending)?];
+            tx.execute(sql, args).map_err(db_error::SqliteError)?;
+        }
+        Ok(())
+    }
+
+    /// Garbage-collect the mempool.  Remove transactions whose origin or sponsor nonce has
+    /// already been used in the fork ending at the given chain tip, since they can never be mined
+    /// on it.
+    pub fn garbage_collect_consumed_nonces<'a>(
+        tx: &mut MemPoolTx<'a>,
+        burn_dbconn: &dyn BurnStateDB,
+        tip_consensus_hash: &ConsensusHash,
+        tip_block_hash: &BlockHeaderHash,
+    ) -> Result<(), db_error> {
+        let sql = "SELECT origin_address AS address FROM mempool UNION SELECT sponsor_address AS address FROM mempool".to_string();
+        let addrs = query_row_columns::<StacksAddress, _>(tx, &sql, NO_PARAMS, "address")?;
+        let tip = StacksBlockHeader::make_index_block_hash(tip_consensus_hash, tip_block_hash);
+
+        for addr in addrs.into_iter() {
+            let principal = PrincipalData::from(addr.clone());
+            let nonce =
+                tx.admitter
+                    .chainstate
+                    .with_read_only_clarity_tx(burn_dbconn, &tip, |clarity_tx| {
+                        clarity_tx.with_clarity_db_readonly(|clarity_db| {
+                            clarity_db.get_account_nonce(&principal)
+                        })
+                    });
+            if nonce == 0 {
+                continue;
+            }
+
+            let sql = "DELETE FROM mempool WHERE (origin_address = ?1 AND origin_nonce < ?2) OR (sponsor_address = ?1 AND sponsor_nonce < ?2)";
+            let args: &[&dyn ToSql] = &[&addr.to_string(), &u64_to_sql(nonce)?];
+            tx.execute(sql, args).map_err(db_error::SqliteError)?;
+        }
+        Ok(())
+    }
+
+    /// Record the fee samples of the block at the given chain tip, if it has not been sampled
+    /// yet, and forget the samples of blocks that have left the fee estimation window.
+    fn record_block_fees<'a>(
+        tx: &mut MemPoolTx<'a>,
+        tip_consensus_hash: &ConsensusHash,
+        tip_block_hash: &BlockHeaderHash,
+        tip_height: u64,
+    ) -> Result<(), db_error> {
+        let min_height = tip_height.saturating_sub(FEE_ESTIMATE_WINDOW);
+        let args: &[&dyn ToSql] = &[&u64_to_sql(min_height)?];
+        tx.execute(
+            "DELETE FROM fee_estimate_blocks WHERE block_height <= ?1",
+            args,
+        )
+        .map_err(db_error::SqliteError)?;
+        tx.execute(
+            "DELETE FROM fee_estimate_samples WHERE block_height <= ?1",
+            args,
+        )
+        .map_err(db_error::SqliteError)?;
+
+        let tip = StacksBlockHeader::make_index_block_hash(tip_consensus_hash, tip_block_hash);
+        let sql =
+            "SELECT COUNT(*) FROM fee_estimate_blocks WHERE index_block_hash = ?1".to_string();
+        let args: &[&dyn ToSql] = &[&tip];
+        if query_int(tx, &sql, args)? > 0 {
+            return Ok(());
+        }
+
+        let block = match StacksChainState::load_block(
+            &tx.admitter.chainstate.blocks_path,
+            tip_consensus_hash,
+            tip_block_hash,
+        ) {
+            Ok(Some(block)) => block,
+            Ok(None) => {
+                return Ok(());
+            }
+            Err(e) => {
+                return Err(db_error::Other(format!(
+                    "Failed to load block {}: {:?}",
+                    &tip, &e
+                )));
+            }
+        };
+
+        let receipts = tx
+            .admitter
+            .chainstate
+            .get_block_transaction_receipts(&tip)
+            .map_err(|e| db_error::Other(format!("Failed to load receipts: {:?}", &e)))?;
+        let mut block_cost = ExecutionCost::zero();
+        for receipt in receipts.iter() {
+            if block_cost.add(&receipt.execution_cost).is_err() {
+                block_cost = ExecutionCost::max_value();
+                break;
+            }
+        }
+        let fill = block_fill_percent(&block_cost, &tx.admitter.chainstate.block_limit);
+
+        let mut samples = vec![];
+        for block_tx in block.txs.iter() {
+            if let TransactionPayload::Coinbase(_) = block_tx.payload {
+                continue;
+            }
+            let len = block_tx.serialize_to_vec().len() as u64;
+            let delay = MemPoolDB::get_tx(tx, &block_tx.txid())?
+                .map(|txinfo| tip_height.saturating_sub(txinfo.metadata.block_height));
+            samples.push(FeeSample {
+                fee_rate: fee_rate_per_byte(block_tx.get_fee_rate(), len),
+                delay,
+            });
+        }
+
+        test_debug!(
+            "Fee samples of {} (height {}, {}% full): {:?}",
+            &tip,
+            tip_height,
+            fill,
+            &samples
+        );
+
+        let sql = "INSERT INTO fee_estimate_blocks (index_block_hash, block_height, fill) VALUES (?1, ?2, ?3)";
+        let args: &[&dyn ToSql] = &[&tip, &u64_to_sql(tip_height)?, &u64_to_sql(fill)?];
+        tx.execute(sql, args).map_err(db_error::SqliteError)?;
+
+        let sql = "INSERT INTO fee_estimate_samples (index_block_hash, block_height, fee_rate, delay) VALUES (?1, ?2, ?3, ?4)";
+        for sample in samples.into_iter() {
+            let delay = match sample.delay {
+                Some(delay) => Some(u64_to_sql(delay)?),
+                None => None,
+            };
+            let args: &[&dyn ToSql] = &[
+                &tip,
+                &u64_to_sql(tip_height)?,
+                &u64_to_sql(sample.fee_rate)?,
+                &delay,
+            ];
+            tx.execute(sql, args).map_err(db_error::SqliteError)?;
+        }
+        Ok(())
+    }
+
+    /// Get a fee estimator over the blocks sampled in the fee estimation window that ends at the
+    /// given chain tip height.
+    pub fn get_fee_estimator(conn: &DBConn, tip_height: u64) -> Result<FeeEstimator, db_error> {
+        let min_height = tip_height.saturating_sub(FEE_ESTIMATE_WINDOW);
+        let args: &[&dyn ToSql] = &[&u64_to_sql(min_height)?];
+
+        let sql = "SELECT * FROM fee_estimate_blocks WHERE block_height > ?1 ORDER BY block_height";
+        let block_rows: Vec<FeeEstimateBlockRow> = query_rows(conn, sql, args)?;
+        let sql = "SELECT * FROM fee_estimate_samples WHERE block_height > ?1";
+        let sample_rows: Vec<FeeSampleRow> = query_rows(conn, sql, args)?;
+
+        let mut block_indexes = HashMap::new();
+        let mut blocks = vec![];
+        for row in block_rows.into_iter() {
+            block_indexes.insert(row.index_block_hash, blocks.len());
+            blocks.push(BlockFeeStats {
+                block_height: row.block_height,
+                fill: row.fill,
+                samples: vec![],
+            });
+        }
+        for row in sample_rows.into_iter() {
+            if let Some(idx) = block_indexes.get(&row.index_block_hash) {
+                blocks[*idx].samples.push(row.sample);
+            }
+        }
+        Ok(FeeEstimator::new(blocks))
+    }
+
+    /// Garbage-collect the mempool according to its GC policy, as of the given canonical chain
+    /// tip:  drop transactions that are too old (by block height or by acceptance time), drop
+    /// transactions whose nonces were consumed on the tip's fork and release the pending
+    /// transactions whose nonce gaps were filled (only checked when the tip changes), and then
+    /// drop the lowest-fee transactions if the mempool is still too big.
+    pub fn garbage_collect_with_policy(
+        &mut self,
+        burn_dbconn: &dyn BurnStateDB,
+        tip_consensus_hash: &ConsensusHash,
+        tip_block_hash: &BlockHeaderHash,
+        tip_height: u64,
+    ) -> Result<(), db_error> {
+        let policy = self.gc_policy.clone();
+        let tip = StacksBlockHeader::make_index_block_hash(tip_consensus_hash, tip_block_hash);
+        let new_tip = self.last_gc_tip.as_ref() != Some(&tip);
+
+        let mut mempool_tx = self.tx_begin()?;
+        if new_tip {
+            // sample the tip's fees before its transactions get garbage-collected
+            MemPoolDB::record_block_fees(
+                &mut mempool_tx,
+                tip_consensus_hash,
+                tip_block_hash,
+                tip_height,
+            )?;
+        }
+
+        if policy.max_tx_age_blocks > 0 && tip_height > policy.max_tx_age_blocks {
+            let min_height = tip_height - policy.max_tx_age_blocks;
+            debug!(
+                "Remove all transactions beneath block height {}",
+                min_height
+            );
+            MemPoolDB::garbage_collect(&mut mempool_tx, min_height)?;
+        }
+
+        if policy.max_tx_age_secs > 0 {
+            let min_accept_time = get_epoch_time_secs().saturating_sub(policy.max_tx_age_secs);
+            MemPoolDB::garbage_collect_by_time(&mut mempool_tx, min_accept_time)?;
+        }
+
+        if new_tip {
+            debug!(
+                "Remove all transactions with consumed nonces as of {}/{}",
+                tip_consensus_hash, tip_block_hash
+            );
+            MemPoolDB::garbage_collect_consumed_nonces(
+                &mut mempool_tx,
+                burn_dbconn,
+                tip_consensus_hash,
+                tip_block_hash,
+            )?;
+            MemPoolDB::release_all_pending_txs(
+                &mut mempool_tx,
+                tip_consensus_hash,
+                tip_block_hash,
+            )?;
+        }
+
+        if policy.max_txs > 0 {
+            MemPoolDB::garbage_collect_by_size(&mut mempool_tx, policy.max_txs)?;
+        }
+
+        mempool_tx.commit()?;
+        self.last_gc_tip = Some(tip);
+        self.update_size_metric();
+        Ok(())
+    }
+
+    /// Report how many transactions the mempool holds to the monitoring gauge
+    fn update_size_metric(&self) {
+        match query_int(
+            self.conn(),
+            &"SELECT COUNT(*) FROM mempool".to_string(),
+            NO_PARAMS,
+        ) {
+            Ok(num_txs) => monitoring::update_mempool_size_gauge(num_txs),
+            Err(e) => warn!("Failed to count mempool transactions: {:?}", &e),
+        }
+    }
+
     /// Scan the chain tip for all available transactions (but do not remove them!)
     pub fn poll(
         &mut self,
@@ -905,13 +1793,51 @@ impl MemPoolDB {
         .collect()
     }
 
-    /// Submit a transaction to the mempool at a particular chain tip.
+    /// Submit a transaction to the mempool at a particular chain tip.  A transaction whose nonce
+    /// is ahead of its account's next nonce (by at most MAXIMUM_MEMPOOL_NONCE_GAP) is held in
+    /// the pending lane instead of being rejected.  Once a transaction is added, the pending
+    /// transactions of its origin and sponsor that it unblocks are added too.
     pub fn tx_submit(
         mempool_tx: &mut MemPoolTx,
         consensus_hash: &ConsensusHash,
         block_hash: &BlockHeaderHash,
         tx: StacksTransaction,
         do_admission_checks: bool,
+    ) -> Result<(), MemPoolRejection> {
+        match MemPoolDB::tx_submit_at_nonce(
+            mempool_tx,
+            consensus_hash,
+            block_hash,
+            &tx,
+            do_admission_checks,
+        ) {
+            Ok(()) => {}
+            Err(MemPoolRejection::BadNonces(ref mismatch))
+                if mismatch.actual > mismatch.expected
+                    && mismatch.actual - mismatch.expected <= MAXIMUM_MEMPOOL_NONCE_GAP =>
+            {
+                return MemPoolDB::add_pending_tx(mempool_tx, consensus_hash, block_hash, &tx);
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+
+        let mut addresses = vec![tx.origin_address()];
+        if let Some(sponsor_address) = tx.sponsor_address() {
+            addresses.push(sponsor_address);
+        }
+        MemPoolDB::release_pending_txs(mempool_tx, consensus_hash, block_hash, &addresses)?;
+        Ok(())
+    }
+
+    /// Add a transaction to the mempool, if its nonces are the next ones for its accounts.
+    fn tx_submit_at_nonce(
+        mempool_tx: &mut MemPoolTx,
+        consensus_hash: &ConsensusHash,
+        block_hash: &BlockHeaderHash,
+        tx: &StacksTransaction,
+        do_admission_checks: bool,
     ) -> Result<(), MemPoolRejection> {
         test_debug!(
             "Mempool submit {} at {}/{}",
@@ -960,18 +1886,13 @@ impl MemPoolDB {
                 (origin_address.clone(), origin_nonce)
             };
 
-        // TODO; estimate the true fee using Clarity analysis data.  For now, just do fee_rate
-        let estimated_fee = fee_rate
-            .checked_mul(len)
-            .ok_or(MemPoolRejection::Other("Fee numeric overflow".to_string()))?;
+        let estimated_fee = MemPoolDB::estimate_tx_fee(tx);
 
         if do_admission_checks {
             mempool_tx
                 .admitter
                 .set_block(&block_hash, (*consensus_hash).clone());
-            mempool_tx
-                .admitter
-                .will_admit_tx(&mempool_tx.tx, &tx, len)?;
+            mempool_tx.admitter.will_admit_tx(&mempool_tx.tx, tx, len)?;
         }
 
         MemPoolDB::try_add_tx(
@@ -999,9 +1920,13 @@ impl MemPoolDB {
         block_hash: &BlockHeaderHash,
         tx: StacksTransaction,
     ) -> Result<(), MemPoolRejection> {
+        let validation_start = Instant::now();
         let mut mempool_tx = self.tx_begin().map_err(MemPoolRejection::DBError)?;
-        MemPoolDB::tx_submit(&mut mempool_tx, consensus_hash, block_hash, tx, true)?;
+        let result = MemPoolDB::tx_submit(&mut mempool_tx, consensus_hash, block_hash, tx, true);
+        monitoring::observe_tx_validation_time(validation_start);
+        result?;
         mempool_tx.commit().map_err(MemPoolRejection::DBError)?;
+        self.update_size_metric();
         Ok(())
     }
 
@@ -1018,6 +1943,7 @@ impl MemPoolDB {
         let mut mempool_tx = self.tx_begin().map_err(MemPoolRejection::DBError)?;
         MemPoolDB::tx_submit(&mut mempool_tx, consensus_hash, block_hash, tx, false)?;
         mempool_tx.commit().map_err(MemPoolRejection::DBError)?;
+        self.update_size_metric();
         Ok(())
     }
 
@@ -1042,7 +1968,7 @@ impl MemPoolDB {
 mod tests {
 
     use address::AddressHashMode;
-    use burnchains::Address;
+    use burnchains::{Address, Txid};
     use chainstate::burn::{BlockHeaderHash, VRFSeed};
     use net::{Error as NetError, StacksMessageCodec};
     use util::{hash::hex_bytes, hash::to_hex, hash::*, log, secp256k1::*, strings::StacksString};
@@ -1057,21 +1983,32 @@ mod tests {
 
     use chainstate::stacks::{
         db::blocks::MemPoolRejection, db::StacksChainState, index::MarfTrieId, CoinbasePayload,
-        Error as ChainstateError, StacksAddress, StacksBlockHeader, StacksMicroblockHeader,
-        StacksPrivateKey, StacksPublicKey, StacksTransaction, StacksTransactionSigner,
-        TokenTransferMemo, TransactionAnchorMode, TransactionAuth, TransactionContractCall,
-        TransactionPayload, TransactionPostConditionMode, TransactionSmartContract,
-        TransactionSpendingCondition, TransactionVersion, C32_ADDRESS_VERSION_MAINNET_SINGLESIG,
-        C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+        Error as ChainstateError, StacksAddress, StacksBlockHeader, StacksBlockId,
+        StacksMicroblockHeader, StacksPrivateKey, StacksPublicKey, StacksTransaction,
+        StacksTransactionSigner, TokenTransferMemo, TransactionAnchorMode, TransactionAuth,
+        TransactionContractCall, TransactionPayload, TransactionPostConditionMode,
+        TransactionSmartContract, TransactionSpendingCondition, TransactionVersion,
+        C32_ADDRESS_VERSION_MAINNET_SINGLESIG, C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
     };
 
-    use super::MemPoolDB;
-    use util::db::{DBConn, FromRow};
+    use super::{
+        MemPoolAccountNonces, MemPoolDB, MemPoolGCPolicy, MemPoolTxInfo, MemPoolTxMetadata,
+        MAXIMUM_MEMPOOL_NONCE_GAP, MAXIMUM_MEMPOOL_PENDING_PER_ADDRESS,
+    };
+    use core::fees::FEE_ESTIMATE_WINDOW;
+    use rusqlite::types::ToSql;
+    use rusqlite::NO_PARAMS;
+    use util::db::{query_int, u64_to_sql, DBConn, FromRow};
+    use util::get_epoch_time_secs;
+    use vm::analysis::strict_checker::StrictProfile;
+    use vm::analysis::CheckErrors;
 
     use chainstate::burn::ConsensusHash;
     use chainstate::stacks::db::test::chainstate_path;
     use chainstate::stacks::db::test::instantiate_chainstate;
+    use chainstate::stacks::db::test::instantiate_chainstate_with_balances;
     use chainstate::stacks::test::codec_all_transactions;
+    use core::{FIRST_BURNCHAIN_CONSENSUS_HASH, FIRST_STACKS_BLOCK_HASH};
 
     const FOO_CONTRACT: &'static str = "(define-public (foo) (ok 1))
                                         (define-public (bar (x uint)) (ok x))";
@@ -1237,7 +2174,9 @@ mod tests {
         )
         .unwrap_err();
         assert!(match err_resp {
-            MemPoolRejection::ConflictingNonceInMempool => true,
+            MemPoolRejection::ConflictingNonceInMempool(ref conflicting_txid, _) => {
+                *conflicting_txid == prior_txid
+            }
             _ => false,
         });
 
@@ -1245,6 +2184,205 @@ mod tests {
         assert!(!MemPoolDB::db_has_tx(&mempool_tx, &txid).unwrap());
     }
 
+    #[test]
+    fn mempool_strict_contract_admission() {
+        let mut chainstate =
+            instantiate_chainstate(false, 0x80000000, "mempool_strict_contract_admission");
+
+        let b_1 = (ConsensusHash([0x1; 20]), BlockHeaderHash([0x4; 32]));
+        {
+            let (chainstate_tx, clar_tx) = chainstate.chainstate_tx_begin().unwrap();
+            let c_tx = StacksChainState::chainstate_block_begin(
+                &chainstate_tx,
+                clar_tx,
+                &NULL_BURN_STATE_DB,
+                &ConsensusHash::empty(),
+                &BlockHeaderHash::sentinel(),
+                &b_1.0,
+                &b_1.1,
+            );
+            c_tx.commit_block();
+        }
+
+        let privk = StacksPrivateKey::from_hex(SK_1).unwrap();
+        let make_contract_tx = |code: &str| {
+            StacksTransaction::new(
+                TransactionVersion::Testnet,
+                TransactionAuth::from_p2pkh(&privk).unwrap(),
+                TransactionPayload::new_smart_contract("strict", code).unwrap(),
+            )
+        };
+
+        let unchecked = make_contract_tx(
+            "(define-public (pay (amount uint) (recipient principal))
+               (begin (stx-transfer? amount tx-sender recipient) (ok true)))",
+        );
+        let panics =
+            make_contract_tx("(define-read-only (get (a (optional int))) (unwrap-panic a))");
+        // contracts that don't type-check are left for the chain to reject
+        let invalid = make_contract_tx("(define-public (foo) (+ 1 true))");
+
+        let profile = StrictProfile::from_lint_names(&["unchecked-responses".to_string()]).unwrap();
+
+        match chainstate
+            .will_admit_strict_contract(&b_1.0, &b_1.1, &unchecked, &profile)
+            .unwrap_err()
+        {
+            MemPoolRejection::StrictAnalysisFailure(e) => match e.err {
+                CheckErrors::UncheckedResponse(_) => {}
+                _ => panic!("Expected an unchecked response, got {:?}", &e),
+            },
+            e => panic!("Expected a strict analysis failure, got {:?}", &e),
+        }
+        chainstate
+            .will_admit_strict_contract(&b_1.0, &b_1.1, &panics, &profile)
+            .unwrap();
+        chainstate
+            .will_admit_strict_contract(&b_1.0, &b_1.1, &invalid, &StrictProfile::all())
+            .unwrap();
+        assert!(chainstate
+            .will_admit_strict_contract(&b_1.0, &b_1.1, &panics, &StrictProfile::all())
+            .is_err());
+    }
+
+    #[test]
+    fn mempool_pending_nonce_lane() {
+        let privk = StacksPrivateKey::from_hex(SK_1).unwrap();
+        let addr = StacksAddress::from_public_keys(
+            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            &AddressHashMode::SerializeP2PKH,
+            1,
+            &vec![StacksPublicKey::from_private(&privk)],
+        )
+        .unwrap();
+        let recipient = StacksAddress::from_public_keys(
+            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            &AddressHashMode::SerializeP2PKH,
+            1,
+            &vec![StacksPublicKey::from_private(
+                &StacksPrivateKey::from_hex(SK_2).unwrap(),
+            )],
+        )
+        .unwrap();
+
+        let _chainstate = instantiate_chainstate_with_balances(
+            false,
+            0x80000000,
+            "mempool_pending_nonce_lane",
+            vec![(addr.clone(), 1_000_000)],
+        );
+        let chainstate_path = chainstate_path("mempool_pending_nonce_lane");
+        let mut mempool = MemPoolDB::open(false, 0x80000000, &chainstate_path).unwrap();
+
+        let make_transfer_from = |privk: &StacksPrivateKey, nonce: u64| {
+            let mut tx = StacksTransaction::new(
+                TransactionVersion::Testnet,
+                TransactionAuth::from_p2pkh(privk).unwrap(),
+                TransactionPayload::TokenTransfer(
+                    recipient.clone().into(),
+                    100,
+                    TokenTransferMemo([0u8; 34]),
+                ),
+            );
+            tx.chain_id = 0x80000000;
+            tx.set_origin_nonce(nonce);
+            tx.set_fee_rate(1000);
+            let mut signer = StacksTransactionSigner::new(&tx);
+            signer.sign_origin(privk).unwrap();
+            signer.get_tx().unwrap()
+        };
+        let make_transfer = |nonce: u64| make_transfer_from(&privk, nonce);
+        let tip = (FIRST_BURNCHAIN_CONSENSUS_HASH, FIRST_STACKS_BLOCK_HASH);
+
+        // nonce 2 waits for nonces 0 and 1
+        let tx_2 = make_transfer(2);
+        mempool.submit(&tip.0, &tip.1, tx_2.clone()).unwrap();
+        assert!(!mempool.has_tx(&tx_2.txid()));
+        assert_eq!(
+            MemPoolDB::get_account_nonces(mempool.conn(), &addr, 0).unwrap(),
+            MemPoolAccountNonces {
+                confirmed_nonce: 0,
+                mempool_max_nonce: Some(2),
+                missing_nonces: vec![0, 1],
+            }
+        );
+
+        let tx_0 = make_transfer(0);
+        mempool.submit(&tip.0, &tip.1, tx_0.clone()).unwrap();
+        assert!(mempool.has_tx(&tx_0.txid()));
+        assert!(!mempool.has_tx(&tx_2.txid()));
+        assert_eq!(
+            MemPoolDB::get_account_nonces(mempool.conn(), &addr, 0)
+                .unwrap()
+                .missing_nonces,
+            vec![1]
+        );
+
+        // filling the gap releases nonce 2
+        let tx_1 = make_transfer(1);
+        mempool.submit(&tip.0, &tip.1, tx_1.clone()).unwrap();
+        assert!(mempool.has_tx(&tx_1.txid()));
+        assert!(mempool.has_tx(&tx_2.txid()));
+        assert_eq!(
+            MemPoolDB::get_account_nonces(mempool.conn(), &addr, 0).unwrap(),
+            MemPoolAccountNonces {
+                confirmed_nonce: 0,
+                mempool_max_nonce: Some(2),
+                missing_nonces: vec![],
+            }
+        );
+
+        // too far ahead to wait
+        let tx_far = make_transfer(3 + MAXIMUM_MEMPOOL_NONCE_GAP + 1);
+        match mempool.submit(&tip.0, &tip.1, tx_far).unwrap_err() {
+            MemPoolRejection::BadNonces(_) => {}
+            e => panic!("Expected a nonce rejection, got {:?}", &e),
+        }
+
+        // an account that can't pay for it can't park a transaction
+        let unfunded = StacksPrivateKey::from_hex(SK_3).unwrap();
+        match mempool
+            .submit(&tip.0, &tip.1, make_transfer_from(&unfunded, 1))
+            .unwrap_err()
+        {
+            MemPoolRejection::NotEnoughFunds(..) => {}
+            e => panic!("Expected an insufficient funds rejection, got {:?}", &e),
+        }
+
+        // only so many transactions per account can wait
+        for i in 0..MAXIMUM_MEMPOOL_PENDING_PER_ADDRESS {
+            mempool
+                .submit(&tip.0, &tip.1, make_transfer(4 + i))
+                .unwrap();
+        }
+        let tx_over = make_transfer(4 + MAXIMUM_MEMPOOL_PENDING_PER_ADDRESS);
+        match mempool.submit(&tip.0, &tip.1, tx_over).unwrap_err() {
+            MemPoolRejection::TooManyPending => {}
+            e => panic!("Expected a pending lane rejection, got {:?}", &e),
+        }
+        assert_eq!(
+            MemPoolDB::get_account_nonces(mempool.conn(), &addr, 0)
+                .unwrap()
+                .mempool_max_nonce,
+            Some(3 + MAXIMUM_MEMPOOL_PENDING_PER_ADDRESS)
+        );
+
+        // waiting transactions count towards the size limit, and are evicted first
+        let mut mempool_tx = mempool.tx_begin().unwrap();
+        MemPoolDB::garbage_collect_by_size(&mut mempool_tx, 5).unwrap();
+        mempool_tx.commit().unwrap();
+        assert!(mempool.has_tx(&tx_0.txid()));
+        assert!(mempool.has_tx(&tx_1.txid()));
+        assert!(mempool.has_tx(&tx_2.txid()));
+        let num_pending = query_int(
+            mempool.conn(),
+            &"SELECT COUNT(*) FROM mempool_pending".to_string(),
+            NO_PARAMS,
+        )
+        .unwrap();
+        assert_eq!(num_pending, 2);
+    }
+
     #[test]
     fn mempool_db_load_store_replace_tx() {
         let _chainstate =
@@ -1435,7 +2573,7 @@ mod tests {
             )
             .unwrap_err()
             {
-                MemPoolRejection::ConflictingNonceInMempool => true,
+                MemPoolRejection::ConflictingNonceInMempool(..) => true,
                 _ => false,
             });
 
@@ -1493,4 +2631,227 @@ mod tests {
         .unwrap();
         assert_eq!(txs.len(), 0);
     }
+
+    #[test]
+    fn mempool_garbage_collect_by_time_and_size() {
+        let _chainstate = instantiate_chainstate(false, 0x80000000, "mempool_gc_by_time_and_size");
+        let chainstate_path = chainstate_path("mempool_gc_by_time_and_size");
+        let mut mempool = MemPoolDB::open(false, 0x80000000, &chainstate_path).unwrap();
+
+        let tx = codec_all_transactions(
+            &TransactionVersion::Testnet,
+            0x80000000,
+            &TransactionAnchorMode::Any,
+            &TransactionPostConditionMode::Allow,
+        )
+        .pop()
+        .unwrap();
+
+        // ten transactions from different origins, with fee rates 1..10, accepted at times
+        // 1000..1009
+        let mut mempool_tx = mempool.tx_begin().unwrap();
+        let mut txids = vec![];
+        for i in 0..10u64 {
+            let mut tx = tx.clone();
+            tx.set_fee_rate(i + 1);
+
+            let txid = tx.txid();
+            let mut tx_bytes = vec![];
+            tx.consensus_serialize(&mut tx_bytes).unwrap();
+            let len = tx_bytes.len() as u64;
+            let origin_address = StacksAddress {
+                version: 22,
+                bytes: Hash160::from_data(&i.to_be_bytes()),
+            };
+
+            MemPoolDB::try_add_tx(
+                &mut mempool_tx,
+                &ConsensusHash([0x1; 20]),
+                &BlockHeaderHash([0x2; 32]),
+                txid.clone(),
+                tx_bytes,
+                tx.get_fee_rate() * len,
+                tx.get_fee_rate(),
+                100,
+                &origin_address,
+                0,
+                &origin_address,
+                0,
+            )
+            .unwrap();
+
+            let args: &[&dyn ToSql] = &[&((1000 + i) as i64), &txid];
+            mempool_tx
+                .execute("UPDATE mempool SET accept_time = ?1 WHERE txid = ?2", args)
+                .unwrap();
+            txids.push(txid);
+        }
+
+        // expire the two oldest
+        MemPoolDB::garbage_collect_by_time(&mut mempool_tx, 1002).unwrap();
+        for (i, txid) in txids.iter().enumerate() {
+            assert_eq!(MemPoolDB::db_has_tx(&mempool_tx, txid).unwrap(), i >= 2);
+        }
+
+        // no-op if under the cap
+        MemPoolDB::garbage_collect_by_size(&mut mempool_tx, 8).unwrap();
+        assert_eq!(MemPoolDB::get_all_txs(&mempool_tx).unwrap().len(), 8);
+
+        // evict the three lowest fee rates
+        MemPoolDB::garbage_collect_by_size(&mut mempool_tx, 5).unwrap();
+        for (i, txid) in txids.iter().enumerate() {
+            assert_eq!(MemPoolDB::db_has_tx(&mempool_tx, txid).unwrap(), i >= 5);
+        }
+        mempool_tx.commit().unwrap();
+
+        // the default policy keeps everything accepted just now
+        assert_eq!(*mempool.gc_policy(), MemPoolGCPolicy::default());
+        let max_tx_age_secs = mempool.gc_policy().max_tx_age_secs;
+        let now = get_epoch_time_secs();
+
+        let mut mempool_tx = mempool.tx_begin().unwrap();
+        let args: &[&dyn ToSql] = &[&(now as i64)];
+        mempool_tx
+            .execute("UPDATE mempool SET accept_time = ?1", args)
+            .unwrap();
+        MemPoolDB::garbage_collect_by_time(&mut mempool_tx, now - max_tx_age_secs).unwrap();
+        assert_eq!(MemPoolDB::get_all_txs(&mempool_tx).unwrap().len(), 5);
+        mempool_tx.commit().unwrap();
+
+        // evict one by hand
+        let mut mempool_tx = mempool.tx_begin().unwrap();
+        assert!(MemPoolDB::evict_tx(&mut mempool_tx, &txids[5]).unwrap());
+        assert!(!MemPoolDB::evict_tx(&mut mempool_tx, &txids[5]).unwrap());
+        assert_eq!(MemPoolDB::get_all_txs(&mempool_tx).unwrap().len(), 4);
+        mempool_tx.commit().unwrap();
+    }
+
+    #[test]
+    fn mempool_order_by_fee_rate() {
+        let tx = codec_all_transactions(
+            &TransactionVersion::Testnet,
+            0x80000000,
+            &TransactionAnchorMode::Any,
+            &TransactionPostConditionMode::Allow,
+        )
+        .pop()
+        .unwrap();
+
+        let make_txinfo = |origin: u8, nonce: u64, fee_rate: u64, accept_time: u64| {
+            let origin_address = StacksAddress {
+                version: C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+                bytes: Hash160([origin; 20]),
+            };
+            MemPoolTxInfo {
+                tx: tx.clone(),
+                metadata: MemPoolTxMetadata {
+                    txid: Txid([origin; 32]),
+                    len: 100,
+                    fee_rate: fee_rate,
+                    estimated_fee: fee_rate * 100,
+                    consensus_hash: ConsensusHash([0x1; 20]),
+                    block_header_hash: BlockHeaderHash([0x2; 32]),
+                    block_height: 1,
+                    origin_address: origin_address.clone(),
+                    origin_nonce: nonce,
+                    sponsor_address: origin_address,
+                    sponsor_nonce: nonce,
+                    accept_time: accept_time,
+                },
+            }
+        };
+
+        let txs = vec![
+            // origin 1 pays little for its first tx, but a lot for its second
+            make_txinfo(1, 1, 500, 10),
+            make_txinfo(1, 0, 10, 10),
+            // origin 2 pays a medium amount for each tx
+            make_txinfo(2, 0, 100, 11),
+            make_txinfo(2, 1, 100, 12),
+            // origin 3 ties with origin 2, but was accepted earlier
+            make_txinfo(3, 0, 100, 5),
+            // origin 4 pays the most
+            make_txinfo(4, 7, 1000, 20),
+        ];
+
+        let ordered: Vec<_> = MemPoolDB::order_by_fee_rate(txs)
+            .into_iter()
+            .map(|txinfo| {
+                (
+                    txinfo.metadata.origin_address.bytes.0[0],
+                    txinfo.metadata.origin_nonce,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            ordered,
+            vec![(4, 7), (3, 0), (2, 0), (2, 1), (1, 0), (1, 1)]
+        );
+
+        // a long transaction that pays more in total, but less per byte, goes last
+        let mut long_txinfo = make_txinfo(5, 0, 900, 1);
+        long_txinfo.metadata.len = 1000;
+        let ordered: Vec<_> =
+            MemPoolDB::order_by_fee_rate(vec![long_txinfo, make_txinfo(6, 0, 100, 2)])
+                .into_iter()
+                .map(|txinfo| txinfo.metadata.origin_address.bytes.0[0])
+                .collect();
+        assert_eq!(ordered, vec![6, 5]);
+
+        assert_eq!(MemPoolDB::order_by_fee_rate(vec![]).len(), 0);
+    }
+
+    #[test]
+    fn mempool_fee_estimator() {
+        let _chainstate = instantiate_chainstate(false, 0x80000000, "mempool_fee_estimator");
+        let chainstate_path = chainstate_path("mempool_fee_estimator");
+        let mempool = MemPoolDB::open(false, 0x80000000, &chainstate_path).unwrap();
+
+        // one block per height, each 95% full, with a fast and a slow transaction
+        for height in 1..(FEE_ESTIMATE_WINDOW + 5) {
+            let block_id = StacksBlockId([height as u8; 32]);
+            let args: &[&dyn ToSql] = &[&block_id, &u64_to_sql(height).unwrap()];
+            mempool
+                .conn()
+                .execute(
+                    "INSERT INTO fee_estimate_blocks (index_block_hash, block_height, fill) VALUES (?1, ?2, 95)",
+                    args,
+                )
+                .unwrap();
+            for (fee_rate, delay) in &[(100 + height, Some(1)), (10, None)] {
+                let args: &[&dyn ToSql] = &[
+                    &block_id,
+                    &u64_to_sql(height).unwrap(),
+                    &u64_to_sql(*fee_rate).unwrap(),
+                    &delay.map(|d| u64_to_sql(d).unwrap()),
+                ];
+                mempool
+                    .conn()
+                    .execute(
+                        "INSERT INTO fee_estimate_samples (index_block_hash, block_height, fee_rate, delay) VALUES (?1, ?2, ?3, ?4)",
+                        args,
+                    )
+                    .unwrap();
+            }
+        }
+
+        // only the last FEE_ESTIMATE_WINDOW blocks count
+        let tip_height = FEE_ESTIMATE_WINDOW + 4;
+        let estimator = MemPoolDB::get_fee_estimator(mempool.conn(), tip_height).unwrap();
+        assert_eq!(estimator.num_blocks(), FEE_ESTIMATE_WINDOW);
+        assert_eq!(estimator.num_samples(), 2 * FEE_ESTIMATE_WINDOW);
+        assert_eq!(estimator.median_block_fill(), 95);
+
+        let estimate = estimator.estimate_fee_rates();
+        assert_eq!(estimate.high, 100 + 5 + FEE_ESTIMATE_WINDOW / 2);
+        assert_eq!(estimate.medium, estimate.high);
+        assert_eq!(estimate.low, 100 + 5);
+        assert_eq!(estimator.block_assembly_min_fee_rate(), Some(estimate.low));
+
+        // nothing sampled before the window
+        let estimator = MemPoolDB::get_fee_estimator(mempool.conn(), 200).unwrap();
+        assert_eq!(estimator.num_blocks(), 0);
+        assert_eq!(estimator.block_assembly_min_fee_rate(), None);
+    }
 }
@@ -21,7 +21,10 @@ use chainstate::burn::{BlockHeaderHash, ConsensusHash};
 use chainstate::coordinator::comm::CoordinatorCommunication;
 use util::log;
 
+pub mod epoch;
+pub mod fees;
 pub mod mempool;
+pub use self::epoch::{StacksEpoch, StacksEpochId};
 pub use self::mempool::MemPoolDB;
 
 // fork set identifier -- to be mixed with the consensus hash (encodes the version)
@@ -280,6 +280,74 @@ impl Transaction {
         Sha256dHash::from_data(&raw_vec)
     }
 
+    /// Computes the BIP143 signature hash of a version-0 witness input, which commits to the
+    /// value of the output being spent.  For p2wpkh, `script_code` is the p2pkh script of the
+    /// public key hash; for p2wsh, it is the witness script.
+    ///
+    /// # Panics
+    /// Panics if `input_index` is greater than or equal to `self.input.len()`
+    ///
+    pub fn segwit_signature_hash(
+        &self,
+        input_index: usize,
+        script_code: &Script,
+        value: u64,
+        sighash_u32: u32,
+    ) -> Sha256dHash {
+        assert!(input_index < self.input.len()); // Panic on OOB
+
+        let (sighash, anyone_can_pay) =
+            SigHashType::from_u32(sighash_u32).split_anyonecanpay_flag();
+
+        let zero_hash = Sha256dHash::default();
+
+        let hash_prevouts = if !anyone_can_pay {
+            let mut raw_vec = vec![];
+            for input in self.input.iter() {
+                raw_vec.append(&mut serialize(&input.previous_output).unwrap());
+            }
+            Sha256dHash::from_data(&raw_vec)
+        } else {
+            zero_hash
+        };
+
+        let hash_sequence = if !anyone_can_pay && sighash == SigHashType::All {
+            let mut raw_vec = vec![];
+            for input in self.input.iter() {
+                raw_vec.write_all(&input.sequence.to_le_bytes()).unwrap();
+            }
+            Sha256dHash::from_data(&raw_vec)
+        } else {
+            zero_hash
+        };
+
+        let hash_outputs = if sighash != SigHashType::Single && sighash != SigHashType::None {
+            let mut raw_vec = vec![];
+            for output in self.output.iter() {
+                raw_vec.append(&mut serialize(output).unwrap());
+            }
+            Sha256dHash::from_data(&raw_vec)
+        } else if sighash == SigHashType::Single && input_index < self.output.len() {
+            Sha256dHash::from_data(&serialize(&self.output[input_index]).unwrap())
+        } else {
+            zero_hash
+        };
+
+        let input = &self.input[input_index];
+        let mut raw_vec = vec![];
+        raw_vec.write_all(&self.version.to_le_bytes()).unwrap();
+        raw_vec.write_all(hash_prevouts.as_bytes()).unwrap();
+        raw_vec.write_all(hash_sequence.as_bytes()).unwrap();
+        raw_vec.append(&mut serialize(&input.previous_output).unwrap());
+        raw_vec.append(&mut serialize(script_code).unwrap());
+        raw_vec.write_all(&value.to_le_bytes()).unwrap();
+        raw_vec.write_all(&input.sequence.to_le_bytes()).unwrap();
+        raw_vec.write_all(hash_outputs.as_bytes()).unwrap();
+        raw_vec.write_all(&self.lock_time.to_le_bytes()).unwrap();
+        raw_vec.write_all(&sighash_u32.to_le_bytes()).unwrap();
+        Sha256dHash::from_data(&raw_vec)
+    }
+
     /// Gets the "weight" of this transaction, as defined by BIP141. For transactions with an empty
     /// witness, this is simply the consensus-serialized size times 4. For transactions with a
     /// witness, this is the non-witness consensus-serialized size multiplied by 3 plus the
@@ -666,6 +734,24 @@ mod tests {
     // These test vectors were stolen from libbtc, which is Copyright 2014 Jonas Schnelli MIT
     // They were transformed by replacing {...} with run_test_sighash(...), then the ones containing
     // OP_CODESEPARATOR in their pubkeys were removed
+    #[test]
+    fn test_segwit_sighash() {
+        // native p2wpkh example from BIP143
+        let raw_tx = hex_bytes("0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000").unwrap();
+        let tx: Transaction = deserialize(&raw_tx).unwrap();
+        let script_code =
+            Script::from(hex_bytes("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").unwrap());
+
+        let sighash = tx.segwit_signature_hash(1, &script_code, 600000000, 0x01);
+        assert_eq!(
+            sighash.as_bytes().to_vec(),
+            hex_bytes("c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670").unwrap()
+        );
+
+        // commits to the value being spent
+        assert!(tx.segwit_signature_hash(1, &script_code, 600000001, 0x01) != sighash);
+    }
+
     #[test]
     fn test_sighash() {
         run_test_sighash("907c2bc503ade11cc3b04eb2918b6f547b0630ab569273824748c87ea14b0696526c66ba740200000004ab65ababfd1f9bdd4ef073c7afc4ae00da8a66f429c917a0081ad1e1dabce28d373eab81d8628de802000000096aab5253ab52000052ad042b5f25efb33beec9f3364e8a9139e8439d9d7e26529c3c30b6c3fd89f8684cfd68ea0200000009ab53526500636a52ab599ac2fe02a526ed040000000008535300516352515164370e010000000003006300ab2ec229", "", 2, 1864164639, "31af167a6cf3f9d5f6875caa4d31704ceb0eba078d132b78dab52c3b8997317e");
@@ -21,12 +21,23 @@
 #![allow(non_upper_case_globals)]
 #![cfg_attr(test, allow(unused_variables, unused_assignments))]
 
+extern crate chacha20poly1305;
 extern crate curve25519_dalek;
 extern crate ed25519_dalek;
+extern crate flate2;
+extern crate hmac;
+#[cfg(any(target_arch = "wasm32", feature = "pure_rust_crypto"))]
+extern crate k256;
+extern crate pbkdf2;
 extern crate rand;
 extern crate rand_chacha;
 extern crate rusqlite;
+#[cfg(feature = "rocksdb_store")]
+extern crate rocksdb;
+#[cfg(not(any(target_arch = "wasm32", feature = "pure_rust_crypto")))]
 extern crate secp256k1;
+#[cfg(not(any(target_arch = "wasm32", feature = "native_crypto", feature = "pure_rust_crypto")))]
+compile_error!("Build with either the `native_crypto` (default) or the `pure_rust_crypto` feature");
 extern crate serde;
 extern crate tini;
 #[macro_use]
@@ -36,6 +47,9 @@ extern crate mio;
 extern crate percent_encoding;
 extern crate regex;
 extern crate ripemd160;
+#[cfg(feature = "rpc_tls")]
+extern crate rustls;
+extern crate sha1;
 extern crate sha2;
 extern crate sha3;
 extern crate time;
@@ -55,6 +69,8 @@ extern crate serde_json;
 #[cfg(test)]
 #[macro_use]
 extern crate assert_json_diff;
+#[cfg(test)]
+extern crate webpki;
 
 #[cfg(feature = "monitoring_prom")]
 #[macro_use]
@@ -372,6 +372,251 @@ fn main() {
         return;
     }
 
+    if argv[1] == "dump-snapshot"
+        || argv[1] == "dump-block-commits"
+        || argv[1] == "dump-leader-keys"
+    {
+        use chainstate::burn::db::sortdb::SortitionDB;
+
+        if argv.len() < 4 {
+            eprintln!(
+                "Usage: {} {} SORTITION_DB_PATH BURN_BLOCK_HEIGHT|CONSENSUS_HASH",
+                &argv[0], &argv[1]
+            );
+            eprintln!("       BURN_BLOCK_HEIGHT is looked up in the canonical burnchain fork");
+            process::exit(1);
+        }
+
+        let sortdb = SortitionDB::open(&argv[2], false).expect("Failed to open sortition DB");
+        let snapshot_opt = match argv[3].parse::<u64>() {
+            Ok(height) => sortdb
+                .index_handle_at_tip()
+                .get_block_snapshot_by_height(height)
+                .expect("Failed to query sortition DB"),
+            Err(_) => {
+                let consensus_hash = ConsensusHash::from_hex(&argv[3]).unwrap_or_else(|_| {
+                    eprintln!("Not a block height or consensus hash: {}", &argv[3]);
+                    process::exit(1);
+                });
+                SortitionDB::get_block_snapshot_consensus(sortdb.conn(), &consensus_hash)
+                    .expect("Failed to query sortition DB")
+            }
+        };
+        let snapshot = snapshot_opt.unwrap_or_else(|| {
+            eprintln!("No such snapshot: {}", &argv[3]);
+            process::exit(1);
+        });
+
+        if argv[1] == "dump-snapshot" {
+            println!("{:#?}", &snapshot);
+        } else if argv[1] == "dump-block-commits" {
+            let block_commits =
+                SortitionDB::get_block_commits_by_block(sortdb.conn(), &snapshot.sortition_id)
+                    .expect("Failed to query sortition DB");
+            println!("{:#?}", &block_commits);
+        } else {
+            let leader_keys =
+                SortitionDB::get_leader_keys_by_block(sortdb.conn(), &snapshot.sortition_id)
+                    .expect("Failed to query sortition DB");
+            println!("{:#?}", &leader_keys);
+        }
+        process::exit(0);
+    }
+
+    if argv[1] == "dump-headers" {
+        use chainstate::stacks::db::StacksChainState;
+
+        if argv.len() < 4 {
+            eprintln!(
+                "Usage: {} dump-headers CHAINSTATE_PATH START_HEIGHT [END_HEIGHT]",
+                &argv[0]
+            );
+            process::exit(1);
+        }
+
+        let parse_height = |arg: &String| -> u64 {
+            arg.parse().unwrap_or_else(|_| {
+                eprintln!("Not a block height: {}", arg);
+                process::exit(1);
+            })
+        };
+        let start_height = parse_height(&argv[3]);
+        let end_height = if argv.len() > 4 {
+            parse_height(&argv[4])
+        } else {
+            start_height
+        };
+
+        let (chainstate, _) =
+            StacksChainState::open(false, 0x80000000, &argv[2]).expect("Failed to open chainstate");
+        let headers = StacksChainState::get_stacks_block_headers_in_range(
+            chainstate.headers_db(),
+            start_height,
+            end_height,
+        )
+        .expect("Failed to load block headers");
+
+        for header in headers.iter() {
+            println!("{}: {:#?}", &header.index_block_hash(), header);
+        }
+        process::exit(0);
+    }
+
+    if argv[1] == "marf-walk" {
+        use chainstate::stacks::index::node::TrieNodeType;
+
+        if argv.len() < 4 {
+            eprintln!(
+                "Usage: {} marf-walk MARF_PATH BLOCK_ID_HASH [--no-backptrs]",
+                &argv[0]
+            );
+            eprintln!("       --no-backptrs only walks the nodes stored in the block's own trie");
+            process::exit(1);
+        }
+
+        let block_id = StacksBlockId::from_hex(&argv[3]).expect("Bad MARF block hash");
+        let follow_backptrs = !(argv.len() > 4 && argv[4] == "--no-backptrs");
+        let mut marf: MARF<StacksBlockId> = MARF::from_path(&argv[2]).expect("Failed to open MARF");
+
+        marf.walk_trie(
+            &block_id,
+            follow_backptrs,
+            |path_prefix, node_block_id, node, node_hash| {
+                let indent = " ".repeat(path_prefix.len());
+                match node {
+                    TrieNodeType::Leaf(ref leaf) => {
+                        let mut path = path_prefix.to_vec();
+                        path.extend_from_slice(&leaf.path);
+                        println!(
+                            "{}leaf {} = {} (block {})",
+                            &indent,
+                            to_hex(&path),
+                            &leaf.data,
+                            node_block_id
+                        );
+                    }
+                    _ => {
+                        println!(
+                            "{}{:?} hash={} (block {})",
+                            &indent, node, node_hash, node_block_id
+                        );
+                    }
+                }
+            },
+        )
+        .expect("Failed to walk MARF");
+        process::exit(0);
+    }
+
+    if argv[1] == "replay-blocks" {
+        use chainstate::burn::db::sortdb::SortitionDB;
+        use chainstate::stacks::db::StacksChainState;
+
+        let usage = format!(
+            "Usage: {} replay-blocks CHAINSTATE_PATH SORTITION_DB_PATH --start START_HEIGHT --end END_HEIGHT",
+            &argv[0]
+        );
+        if argv.len() < 8 {
+            eprintln!("{}", &usage);
+            process::exit(1);
+        }
+
+        let chainstate_path = &argv[2];
+        let sort_path = &argv[3];
+
+        let mut start_height = None;
+        let mut end_height = None;
+        let mut i = 4;
+        while i + 1 < argv.len() {
+            let height: u64 = argv[i + 1].parse().unwrap_or_else(|_| {
+                eprintln!("Not a block height: {}", &argv[i + 1]);
+                process::exit(1);
+            });
+            match argv[i].as_str() {
+                "--start" => start_height = Some(height),
+                "--end" => end_height = Some(height),
+                _ => {
+                    eprintln!("{}", &usage);
+                    process::exit(1);
+                }
+            }
+            i += 2;
+        }
+        let (start_height, end_height) = match (start_height, end_height) {
+            (Some(start), Some(end)) if start <= end => (start, end),
+            _ => {
+                eprintln!("{}", &usage);
+                process::exit(1);
+            }
+        };
+
+        let (chainstate, _) = StacksChainState::open(false, 0x80000000, chainstate_path)
+            .expect("Failed to open chainstate");
+        let sortdb = SortitionDB::open(sort_path, false).expect("Failed to open sortition DB");
+
+        let headers = StacksChainState::get_stacks_block_headers_in_range(
+            chainstate.headers_db(),
+            start_height,
+            end_height,
+        )
+        .expect("Failed to load block headers");
+
+        let mut num_inconsistent = 0;
+        for header in headers.iter() {
+            // the boot block isn't executed, so there's nothing to replay
+            if header.block_height == 0 {
+                continue;
+            }
+
+            let index_block_hash = header.index_block_hash();
+            let replay = match chainstate.replay_block(&sortdb.index_conn(), &index_block_hash) {
+                Ok(replay) => replay,
+                Err(e) => {
+                    println!(
+                        "{} {}: FAILED to replay: {:?}",
+                        header.block_height, &index_block_hash, &e
+                    );
+                    num_inconsistent += 1;
+                    continue;
+                }
+            };
+
+            if replay.is_consistent() {
+                println!(
+                    "{} {}: OK{}",
+                    replay.block_height,
+                    &replay.index_block_hash,
+                    if replay.receipts_recorded {
+                        ""
+                    } else {
+                        " (state root only; no receipts recorded)"
+                    }
+                );
+            } else {
+                println!(
+                    "{} {}: MISMATCH",
+                    replay.block_height, &replay.index_block_hash
+                );
+                for mismatch in replay.mismatches.iter() {
+                    println!("    {}", mismatch);
+                }
+                num_inconsistent += 1;
+            }
+        }
+
+        println!(
+            "Replayed {} block(s) between heights {} and {}: {} inconsistent",
+            headers.iter().filter(|h| h.block_height > 0).count(),
+            start_height,
+            end_height,
+            num_inconsistent
+        );
+        if num_inconsistent > 0 {
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
     if argv[1] == "replay-chainstate" {
         use burnchains::bitcoin::indexer::BitcoinIndexer;
         use burnchains::db::BurnchainDB;
@@ -14,6 +14,10 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::time::Instant;
+
+use vm::costs::ExecutionCost;
+
 #[cfg(feature = "monitoring_prom")]
 mod prometheus;
 
@@ -122,3 +126,75 @@ pub fn update_active_miners_count_gauge(value: i64) {
     #[cfg(feature = "monitoring_prom")]
     prometheus::ACTIVE_MINERS_COUNT_GAUGE.set(value);
 }
+
+#[allow(unused_variables)]
+pub fn update_sortition_lag_gauge(value: i64) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::SORTITION_LAG_GAUGE.set(value);
+}
+
+#[allow(unused_variables)]
+pub fn update_peer_counts_gauge(inbound: i64, outbound: i64) {
+    #[cfg(feature = "monitoring_prom")]
+    {
+        prometheus::INBOUND_PEERS_GAUGE.set(inbound);
+        prometheus::OUTBOUND_PEERS_GAUGE.set(outbound);
+    }
+}
+
+#[allow(unused_variables)]
+pub fn update_mempool_size_gauge(value: i64) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::MEMPOOL_SIZE_GAUGE.set(value);
+}
+
+/// Record how long a mempool transaction took to validate and store, since `start`
+#[allow(unused_variables)]
+pub fn observe_tx_validation_time(start: Instant) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::TX_VALIDATION_TIME_HISTOGRAM.observe(elapsed_secs(start));
+}
+
+/// Record the execution cost a processed block consumed, in each cost dimension
+#[allow(unused_variables)]
+pub fn observe_block_execution_cost(cost: &ExecutionCost) {
+    #[cfg(feature = "monitoring_prom")]
+    {
+        let dimensions = [
+            ("runtime", cost.runtime),
+            ("read_count", cost.read_count),
+            ("read_length", cost.read_length),
+            ("write_count", cost.write_count),
+            ("write_length", cost.write_length),
+        ];
+        for (dimension, value) in dimensions.iter() {
+            prometheus::BLOCK_EXECUTION_COST_HISTOGRAM
+                .with_label_values(&[dimension])
+                .observe(*value as f64);
+        }
+    }
+}
+
+/// Record how long a MARF operation (`"read"` or `"commit"`) took, since `start`
+#[allow(unused_variables)]
+pub fn observe_marf_io_latency(op: &str, start: Instant) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::MARF_IO_LATENCY_HISTOGRAM
+        .with_label_values(&[op])
+        .observe(elapsed_secs(start));
+}
+
+/// Record how many bytes a subsystem (`"burnchain"`, `"chainstate"`, ...) uses on disk
+#[allow(unused_variables)]
+pub fn update_disk_usage_gauge(subsystem: &str, bytes: u64) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::DISK_USAGE_GAUGE
+        .with_label_values(&[subsystem])
+        .set(bytes as i64);
+}
+
+#[cfg(feature = "monitoring_prom")]
+fn elapsed_secs(start: Instant) -> f64 {
+    let elapsed = start.elapsed();
+    elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1_000_000_000.0
+}
@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use prometheus::{IntCounter, IntGauge};
+use prometheus::{exponential_buckets, Histogram, HistogramVec, IntCounter, IntGauge, IntGaugeVec};
 
 lazy_static! {
     pub static ref RPC_CALL_COUNTER: IntCounter = register_int_counter!(opts!(
@@ -142,4 +142,54 @@ lazy_static! {
         "Total number of active miners.",
         labels! {"handler" => "all",}
     )).unwrap();
+
+    pub static ref SORTITION_LAG_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_sortition_lag",
+        "Number of burnchain blocks downloaded but not yet processed into sortitions.",
+        labels! {"handler" => "all",}
+    )).unwrap();
+
+    pub static ref INBOUND_PEERS_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_inbound_peers",
+        "Number of inbound p2p conversations.",
+        labels! {"handler" => "all",}
+    )).unwrap();
+
+    pub static ref OUTBOUND_PEERS_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_outbound_peers",
+        "Number of outbound p2p conversations.",
+        labels! {"handler" => "all",}
+    )).unwrap();
+
+    pub static ref MEMPOOL_SIZE_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_mempool_transactions",
+        "Number of transactions in the mempool.",
+        labels! {"handler" => "all",}
+    )).unwrap();
+
+    pub static ref TX_VALIDATION_TIME_HISTOGRAM: Histogram = register_histogram!(histogram_opts!(
+        "stacks_node_tx_validation_seconds",
+        "Time spent checking and storing a transaction submitted to the mempool.",
+        vec![0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0]
+    )).unwrap();
+
+    pub static ref BLOCK_EXECUTION_COST_HISTOGRAM: HistogramVec = register_histogram_vec!(
+        "stacks_node_block_execution_cost",
+        "Clarity execution cost consumed by each processed block, by cost dimension.",
+        &["dimension"],
+        exponential_buckets(1.0, 4.0, 16).unwrap()
+    ).unwrap();
+
+    pub static ref MARF_IO_LATENCY_HISTOGRAM: HistogramVec = register_histogram_vec!(
+        "stacks_node_marf_io_seconds",
+        "Latency of MARF reads and of MARF commits to disk.",
+        &["op"],
+        vec![0.00001, 0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0]
+    ).unwrap();
+
+    pub static ref DISK_USAGE_GAUGE: IntGaugeVec = register_int_gauge_vec!(
+        "stacks_node_disk_usage_bytes",
+        "Bytes on disk used by each of the node's subsystems.",
+        &["subsystem"]
+    ).unwrap();
 }
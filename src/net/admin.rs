@@ -0,0 +1,178 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Admin-only RPC endpoints.
+//!
+//! Requests under `/v2/admin/` control the node instead of querying it:  they ban a peer, evict a
+//! mempool transaction, checkpoint the chainstate databases, and pause or resume the miner.  A
+//! client may make them if it sends the `rpc_admin_token` in an `Authorization: Bearer` header,
+//! or if it authenticated with a TLS client certificate (see net::tls).  If neither is
+//! configured, the admin endpoints are disabled.
+//!
+//! Peer bans are carried out by the peer network once the HTTP server has run.  Everything else
+//! is handed to the subsystem that owns it through an `RPCAdminControls`, which the node gives
+//! the RPC server.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use chainstate::coordinator::comm::CoordinatorChannels;
+
+/// How long an admin ban lasts, if the request doesn't say
+pub const DEFAULT_ADMIN_BAN_DURATION: u64 = 24 * 3600;
+
+/// Whether or not a client may make admin requests
+#[derive(Debug, Clone, PartialEq)]
+pub enum RPCAdminAuth {
+    Allowed,
+    /// the admin endpoints are not enabled on this node
+    Disabled,
+    /// the client has neither the admin token nor a client certificate
+    Unauthorized,
+}
+
+/// Decide whether a client may make admin requests.  `admin_token` is the node's configured
+/// token, and `bearer_token` is the one the client sent.  `client_certs_accepted` is true if
+/// the node authenticates TLS client certificates, and `client_authenticated` is true if this
+/// client presented one.
+pub fn authorize_admin(
+    admin_token: Option<&str>,
+    bearer_token: Option<&str>,
+    client_certs_accepted: bool,
+    client_authenticated: bool,
+) -> RPCAdminAuth {
+    if admin_token.is_none() && !client_certs_accepted {
+        return RPCAdminAuth::Disabled;
+    }
+    if client_authenticated {
+        return RPCAdminAuth::Allowed;
+    }
+    match (admin_token, bearer_token) {
+        (Some(expected), Some(given)) if tokens_equal(expected, given) => RPCAdminAuth::Allowed,
+        _ => RPCAdminAuth::Unauthorized,
+    }
+}
+
+/// Compare two tokens in time that depends only on their lengths
+fn tokens_equal(expected: &str, given: &str) -> bool {
+    if expected.len() != given.len() {
+        return false;
+    }
+    expected
+        .bytes()
+        .zip(given.bytes())
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+/// Handles on the node's subsystems that admin requests act on
+#[derive(Clone, Default)]
+pub struct RPCAdminControls {
+    /// the chains coordinator, which checkpoints the chainstate
+    coordinator: Option<CoordinatorChannels>,
+    /// shared with the miner, which only starts tenures while it is set.  None if this node
+    /// doesn't mine.
+    mining_enabled: Option<Arc<AtomicBool>>,
+}
+
+impl RPCAdminControls {
+    pub fn new(
+        coordinator: Option<CoordinatorChannels>,
+        mining_enabled: Option<Arc<AtomicBool>>,
+    ) -> RPCAdminControls {
+        RPCAdminControls {
+            coordinator: coordinator,
+            mining_enabled: mining_enabled,
+        }
+    }
+
+    /// Ask the coordinator to checkpoint the chainstate.  Returns false if there's no
+    /// coordinator to ask, or if it has stopped.
+    pub fn flush_chainstate(&self) -> bool {
+        match self.coordinator {
+            Some(ref coordinator) => coordinator.announce_flush_chainstate(),
+            None => false,
+        }
+    }
+
+    /// Pause or resume the miner.  Returns the previous setting, or None if this node doesn't
+    /// mine.
+    pub fn set_mining_enabled(&self, enabled: bool) -> Option<bool> {
+        self.mining_enabled
+            .as_ref()
+            .map(|mining_enabled| mining_enabled.swap(enabled, Ordering::SeqCst))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_authorize_admin() {
+        // nothing configured
+        assert_eq!(
+            authorize_admin(None, Some("secret"), false, false),
+            RPCAdminAuth::Disabled
+        );
+
+        // token
+        assert_eq!(
+            authorize_admin(Some("secret"), Some("secret"), false, false),
+            RPCAdminAuth::Allowed
+        );
+        assert_eq!(
+            authorize_admin(Some("secret"), Some("secreT"), false, false),
+            RPCAdminAuth::Unauthorized
+        );
+        assert_eq!(
+            authorize_admin(Some("secret"), Some("secret2"), false, false),
+            RPCAdminAuth::Unauthorized
+        );
+        assert_eq!(
+            authorize_admin(Some("secret"), None, false, false),
+            RPCAdminAuth::Unauthorized
+        );
+
+        // client certificate
+        assert_eq!(
+            authorize_admin(None, None, true, true),
+            RPCAdminAuth::Allowed
+        );
+        assert_eq!(
+            authorize_admin(None, Some("secret"), true, false),
+            RPCAdminAuth::Unauthorized
+        );
+        assert_eq!(
+            authorize_admin(Some("secret"), None, true, true),
+            RPCAdminAuth::Allowed
+        );
+    }
+
+    #[test]
+    fn test_admin_controls() {
+        let controls = RPCAdminControls::default();
+        assert!(!controls.flush_chainstate());
+        assert_eq!(controls.set_mining_enabled(false), None);
+
+        let mining_enabled = Arc::new(AtomicBool::new(true));
+        let controls = RPCAdminControls::new(None, Some(mining_enabled.clone()));
+        assert_eq!(controls.set_mining_enabled(false), Some(true));
+        assert!(!mining_enabled.load(Ordering::SeqCst));
+        assert_eq!(controls.set_mining_enabled(true), Some(false));
+        assert!(mining_enabled.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,364 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! API keys for the public RPC interface.
+//!
+//! A node may require each RPC request to carry an API key in its `X-Api-Key` header.  Each key
+//! belongs to a labeled tenant, and may make at most a configured number of requests per minute.
+//! The keys' usage is counted in the same table, so operators can meter each tenant.  Only the
+//! SHA-256 hashes of the keys are stored.
+
+use std::fs;
+
+use rand::thread_rng;
+use rand::RngCore;
+
+use rusqlite::types::ToSql;
+use rusqlite::Row;
+use rusqlite::{Connection, OpenFlags, NO_PARAMS};
+
+use util::db::tx_busy_handler;
+use util::db::DBConn;
+use util::db::Error as db_error;
+use util::db::{query_row, query_rows, u64_to_sql, FromColumn, FromRow};
+use util::hash::{to_hex, Sha256Sum};
+
+/// Length of a quota window, in seconds
+pub const RPC_API_KEY_QUOTA_WINDOW: u64 = 60;
+
+const RPC_API_KEYS_SQL: &'static [&'static str] = &[r#"
+    CREATE TABLE rpc_api_keys(
+        label TEXT PRIMARY KEY,
+        key_hash TEXT UNIQUE NOT NULL,
+        requests_per_minute INTEGER NOT NULL,
+        created_at INTEGER NOT NULL,
+        revoked_at INTEGER,
+        total_requests INTEGER NOT NULL,
+        rejected_requests INTEGER NOT NULL,
+        last_used_at INTEGER NOT NULL,
+        window_start INTEGER NOT NULL,
+        window_requests INTEGER NOT NULL
+    );"#];
+
+/// An API key's tenant, quota, and usage
+#[derive(Debug, Clone, PartialEq)]
+pub struct RPCApiKeyInfo {
+    pub label: String,
+    /// requests allowed per minute (0 means no limit)
+    pub requests_per_minute: u64,
+    pub created_at: u64,
+    /// when the key was revoked, if it was
+    pub revoked_at: Option<u64>,
+    /// requests served with this key
+    pub total_requests: u64,
+    /// requests refused because the key was over its quota
+    pub rejected_requests: u64,
+    pub last_used_at: u64,
+    window_start: u64,
+    window_requests: u64,
+}
+
+impl FromRow<RPCApiKeyInfo> for RPCApiKeyInfo {
+    fn from_row<'a>(row: &'a Row) -> Result<RPCApiKeyInfo, db_error> {
+        let label: String = row.get("label");
+        let requests_per_minute = u64::from_column(row, "requests_per_minute")?;
+        let created_at = u64::from_column(row, "created_at")?;
+        let revoked_at: Option<i64> = row.get("revoked_at");
+        let total_requests = u64::from_column(row, "total_requests")?;
+        let rejected_requests = u64::from_column(row, "rejected_requests")?;
+        let last_used_at = u64::from_column(row, "last_used_at")?;
+        let window_start = u64::from_column(row, "window_start")?;
+        let window_requests = u64::from_column(row, "window_requests")?;
+
+        Ok(RPCApiKeyInfo {
+            label,
+            requests_per_minute,
+            created_at,
+            revoked_at: revoked_at.map(|t| t as u64),
+            total_requests,
+            rejected_requests,
+            last_used_at,
+            window_start,
+            window_requests,
+        })
+    }
+}
+
+/// Whether a request may be served
+#[derive(Debug, Clone, PartialEq)]
+pub enum RPCApiKeyCheck {
+    /// the key is valid and within its quota
+    Allowed,
+    /// the request had no key
+    Missing,
+    /// the key is unknown or revoked
+    Invalid,
+    /// the key has used up its quota; it may be used again in this many seconds
+    QuotaExceeded(u64),
+}
+
+pub struct RPCApiKeys {
+    conn: DBConn,
+}
+
+impl RPCApiKeys {
+    /// Open the API key table at the given path, creating it if it doesn't exist.
+    pub fn open(path: &str) -> Result<RPCApiKeys, db_error> {
+        let create_flag = fs::metadata(path).is_err();
+        let open_flags = if create_flag {
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE
+        } else {
+            OpenFlags::SQLITE_OPEN_READ_WRITE
+        };
+
+        let conn = Connection::open_with_flags(path, open_flags).map_err(db_error::SqliteError)?;
+        conn.busy_handler(Some(tx_busy_handler))?;
+        RPCApiKeys::from_connection(conn, create_flag)
+    }
+
+    #[cfg(test)]
+    pub fn open_memory() -> Result<RPCApiKeys, db_error> {
+        let conn = Connection::open_in_memory().map_err(db_error::SqliteError)?;
+        RPCApiKeys::from_connection(conn, true)
+    }
+
+    fn from_connection(conn: DBConn, instantiate: bool) -> Result<RPCApiKeys, db_error> {
+        if instantiate {
+            for cmd in RPC_API_KEYS_SQL {
+                conn.execute(cmd, NO_PARAMS)
+                    .map_err(db_error::SqliteError)?;
+            }
+        }
+        Ok(RPCApiKeys { conn })
+    }
+
+    fn hash_key(key: &str) -> String {
+        Sha256Sum::from_data(key.as_bytes()).to_hex()
+    }
+
+    /// Make a new API key for the tenant with the given label, and return it.  The key itself is
+    /// not stored, so this is the only time it is available.
+    pub fn add(&self, label: &str, requests_per_minute: u64, now: u64) -> Result<String, db_error> {
+        if self.get(label)?.is_some() {
+            return Err(db_error::ExistsError);
+        }
+
+        let mut key_bytes = [0u8; 32];
+        thread_rng().fill_bytes(&mut key_bytes);
+        let key = to_hex(&key_bytes);
+
+        let args: &[&dyn ToSql] = &[
+            &label.to_string(),
+            &RPCApiKeys::hash_key(&key),
+            &u64_to_sql(requests_per_minute)?,
+            &u64_to_sql(now)?,
+        ];
+        self.conn
+            .execute(
+                "INSERT INTO rpc_api_keys (label, key_hash, requests_per_minute, created_at, revoked_at, total_requests, rejected_requests, last_used_at, window_start, window_requests) \
+                 VALUES (?1, ?2, ?3, ?4, NULL, 0, 0, 0, 0, 0)",
+                args,
+            )
+            .map_err(db_error::SqliteError)?;
+        Ok(key)
+    }
+
+    /// Revoke the key of the tenant with the given label.  Returns false if there is no such
+    /// key, or if it was already revoked.
+    pub fn revoke(&self, label: &str, now: u64) -> Result<bool, db_error> {
+        let args: &[&dyn ToSql] = &[&u64_to_sql(now)?, &label.to_string()];
+        let num_revoked = self
+            .conn
+            .execute(
+                "UPDATE rpc_api_keys SET revoked_at = ?1 WHERE label = ?2 AND revoked_at IS NULL",
+                args,
+            )
+            .map_err(db_error::SqliteError)?;
+        Ok(num_revoked > 0)
+    }
+
+    pub fn get(&self, label: &str) -> Result<Option<RPCApiKeyInfo>, db_error> {
+        let args: &[&dyn ToSql] = &[&label.to_string()];
+        query_row(
+            &self.conn,
+            "SELECT * FROM rpc_api_keys WHERE label = ?1",
+            args,
+        )
+    }
+
+    /// Get every key, revoked or not, ordered by label.
+    pub fn list(&self) -> Result<Vec<RPCApiKeyInfo>, db_error> {
+        query_rows(
+            &self.conn,
+            "SELECT * FROM rpc_api_keys ORDER BY label",
+            NO_PARAMS,
+        )
+    }
+
+    /// Decide whether a request made with the given key at time `now` may be served, and count
+    /// it against the key's quota and usage.
+    pub fn authorize(&self, key: Option<&str>, now: u64) -> Result<RPCApiKeyCheck, db_error> {
+        let key = match key {
+            Some(key) => key,
+            None => {
+                return Ok(RPCApiKeyCheck::Missing);
+            }
+        };
+
+        let args: &[&dyn ToSql] = &[&RPCApiKeys::hash_key(key)];
+        let info: RPCApiKeyInfo = match query_row(
+            &self.conn,
+            "SELECT * FROM rpc_api_keys WHERE key_hash = ?1 AND revoked_at IS NULL",
+            args,
+        )? {
+            Some(info) => info,
+            None => {
+                return Ok(RPCApiKeyCheck::Invalid);
+            }
+        };
+
+        let window_start = now - (now % RPC_API_KEY_QUOTA_WINDOW);
+        let window_requests = if info.window_start == window_start {
+            info.window_requests
+        } else {
+            0
+        };
+
+        let window_start_sql = u64_to_sql(window_start)?;
+        if info.requests_per_minute > 0 && window_requests >= info.requests_per_minute {
+            let args: &[&dyn ToSql] = &[&window_start_sql, &info.label];
+            self.conn
+                .execute(
+                    "UPDATE rpc_api_keys SET rejected_requests = rejected_requests + 1, window_start = ?1 WHERE label = ?2",
+                    args,
+                )
+                .map_err(db_error::SqliteError)?;
+            return Ok(RPCApiKeyCheck::QuotaExceeded(
+                window_start + RPC_API_KEY_QUOTA_WINDOW - now,
+            ));
+        }
+
+        let args: &[&dyn ToSql] = &[
+            &window_start_sql,
+            &u64_to_sql(window_requests + 1)?,
+            &u64_to_sql(now)?,
+            &info.label,
+        ];
+        self.conn
+            .execute(
+                "UPDATE rpc_api_keys SET total_requests = total_requests + 1, window_start = ?1, window_requests = ?2, last_used_at = ?3 WHERE label = ?4",
+                args,
+            )
+            .map_err(db_error::SqliteError)?;
+        Ok(RPCApiKeyCheck::Allowed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_api_keys_add_revoke_list() {
+        let keys = RPCApiKeys::open_memory().unwrap();
+        let key_a = keys.add("tenant-a", 600, 1000).unwrap();
+        let key_b = keys.add("tenant-b", 0, 1001).unwrap();
+        assert_eq!(key_a.len(), 64);
+        assert!(key_a != key_b);
+
+        // labels are unique
+        match keys.add("tenant-a", 10, 1002) {
+            Err(db_error::ExistsError) => {}
+            x => panic!("Expected ExistsError, got {:?}", &x),
+        }
+
+        let list = keys.list().unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].label, "tenant-a");
+        assert_eq!(list[0].requests_per_minute, 600);
+        assert_eq!(list[0].created_at, 1000);
+        assert_eq!(list[0].revoked_at, None);
+        assert_eq!(list[1].label, "tenant-b");
+
+        assert_eq!(
+            keys.authorize(Some(&key_a), 1010).unwrap(),
+            RPCApiKeyCheck::Allowed
+        );
+        assert!(keys.revoke("tenant-a", 1020).unwrap());
+        assert!(!keys.revoke("tenant-a", 1030).unwrap());
+        assert!(!keys.revoke("tenant-c", 1030).unwrap());
+        assert_eq!(
+            keys.get("tenant-a").unwrap().unwrap().revoked_at,
+            Some(1020)
+        );
+
+        assert_eq!(
+            keys.authorize(Some(&key_a), 1040).unwrap(),
+            RPCApiKeyCheck::Invalid
+        );
+        assert_eq!(
+            keys.authorize(Some(&key_b), 1040).unwrap(),
+            RPCApiKeyCheck::Allowed
+        );
+        assert_eq!(
+            keys.authorize(Some("not-a-key"), 1040).unwrap(),
+            RPCApiKeyCheck::Invalid
+        );
+        assert_eq!(keys.authorize(None, 1040).unwrap(), RPCApiKeyCheck::Missing);
+    }
+
+    #[test]
+    fn test_api_keys_quota() {
+        let keys = RPCApiKeys::open_memory().unwrap();
+        let key = keys.add("tenant", 3, 0).unwrap();
+        let unlimited_key = keys.add("unlimited", 0, 0).unwrap();
+
+        // window is [1200, 1260)
+        for i in 0..3 {
+            assert_eq!(
+                keys.authorize(Some(&key), 1210 + i).unwrap(),
+                RPCApiKeyCheck::Allowed
+            );
+        }
+        assert_eq!(
+            keys.authorize(Some(&key), 1215).unwrap(),
+            RPCApiKeyCheck::QuotaExceeded(45)
+        );
+        assert_eq!(
+            keys.authorize(Some(&key), 1259).unwrap(),
+            RPCApiKeyCheck::QuotaExceeded(1)
+        );
+
+        // next window
+        assert_eq!(
+            keys.authorize(Some(&key), 1260).unwrap(),
+            RPCApiKeyCheck::Allowed
+        );
+
+        let info = keys.get("tenant").unwrap().unwrap();
+        assert_eq!(info.total_requests, 4);
+        assert_eq!(info.rejected_requests, 2);
+        assert_eq!(info.last_used_at, 1260);
+
+        for i in 0..100 {
+            assert_eq!(
+                keys.authorize(Some(&unlimited_key), 1200 + i).unwrap(),
+                RPCApiKeyCheck::Allowed
+            );
+        }
+        assert_eq!(keys.get("unlimited").unwrap().unwrap().total_requests, 100);
+    }
+}
@@ -0,0 +1,1035 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Atlas: off-chain data ("attachments") whose hashes are committed to on-chain.
+//!
+//! A smart contract declares an attachment by printing a tuple of the form
+//! `{ attachment: { hash: (buff 20), metadata: <any value> } }`, where `hash` is the Hash160 of
+//! the attachment's bytes (a BNS zone file, for example).  A node only tracks the attachments of
+//! the contracts listed in its `AtlasConfig`.  When a block with such an event is processed, the
+//! chains coordinator records an `AttachmentInstance` in the `AtlasDB`, and the peer network asks
+//! its neighbors' RPC interfaces for the attachment (`GET /v2/attachments/{hash}`) until one of
+//! them serves it.  Nodes serve the attachments they have, so each one replicates across the
+//! network.
+//!
+//! Clients upload attachments with `POST /v2/attachments`.  An attachment whose hash has not been
+//! declared on-chain yet is held for a while, in case the transaction that declares it gets
+//! mined; the node holds only a bounded number of these.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs;
+use std::net::SocketAddr;
+
+use rusqlite::types::ToSql;
+use rusqlite::Row;
+use rusqlite::{Connection, OpenFlags, NO_PARAMS};
+
+use burnchains::Txid;
+use chainstate::stacks::db::StacksChainState;
+use chainstate::stacks::events::{StacksTransactionEvent, StacksTransactionReceipt};
+use chainstate::stacks::StacksBlockId;
+
+use net::p2p::PeerNetwork;
+use net::Error as net_error;
+use net::{HttpRequestMetadata, HttpRequestType, HttpResponseType, PeerHost};
+
+use util::db::tx_busy_handler;
+use util::db::DBConn;
+use util::db::Error as db_error;
+use util::db::{
+    query_count, query_row, query_row_columns, query_rows, u64_to_sql, FromColumn, FromRow,
+};
+use util::get_epoch_time_secs;
+use util::hash::{hex_bytes, to_hex, Hash160};
+use util::strings::UrlString;
+
+use vm::database::ClaritySerializable;
+use vm::types::{QualifiedContractIdentifier, SequenceData, Value};
+
+use serde::de::Deserialize;
+use serde::de::Error as de_Error;
+
+/// Largest attachment any node will accept over HTTP, in bytes
+pub const MAX_ATTACHMENT_SIZE: u64 = 1_048_576;
+
+/// How long to wait before asking the same neighbors again for an attachment that none of them
+/// had, in seconds
+pub const ATTACHMENT_RETRY_INTERVAL: u64 = 60;
+
+const ATLASDB_SQL: &'static [&'static str] = &[
+    r#"
+    CREATE TABLE attachments(
+        hash TEXT PRIMARY KEY,
+        content BLOB NOT NULL,
+        was_instantiated INTEGER NOT NULL,
+        created_at INTEGER NOT NULL
+    );"#,
+    r#"
+    CREATE TABLE attachment_instances(
+        content_hash TEXT NOT NULL,
+        contract_id TEXT NOT NULL,
+        index_block_hash TEXT NOT NULL,
+        block_height INTEGER NOT NULL,
+        txid TEXT NOT NULL,
+        metadata TEXT NOT NULL,
+        is_available INTEGER NOT NULL,
+        created_at INTEGER NOT NULL,
+        PRIMARY KEY(content_hash, index_block_hash, txid)
+    );"#,
+    "CREATE INDEX IF NOT EXISTS attachment_instances_by_availability ON attachment_instances(is_available, content_hash);",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AtlasConfig {
+    /// contracts whose attachments this node fetches and serves
+    pub contracts: HashSet<QualifiedContractIdentifier>,
+    /// largest attachment this node accepts, in bytes
+    pub attachments_max_size: u64,
+    /// most attachments this node holds that have not been declared on-chain (yet)
+    pub max_uninstantiated_attachments: u64,
+    /// how long to hold an attachment that has not been declared on-chain, in seconds
+    pub uninstantiated_attachments_expire_after: u64,
+    /// most attachment downloads in flight at once
+    pub max_inflight_attachments: u64,
+}
+
+impl Default for AtlasConfig {
+    fn default() -> AtlasConfig {
+        AtlasConfig {
+            contracts: HashSet::new(),
+            attachments_max_size: 16_384,
+            max_uninstantiated_attachments: 10_000,
+            uninstantiated_attachments_expire_after: 3600,
+            max_inflight_attachments: 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    #[serde(
+        serialize_with = "attachment_content_serialize",
+        deserialize_with = "attachment_content_deserialize"
+    )]
+    pub content: Vec<u8>,
+}
+
+fn attachment_content_serialize<S: serde::Serializer>(
+    content: &Vec<u8>,
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&to_hex(content))
+}
+
+fn attachment_content_deserialize<'de, D: serde::Deserializer<'de>>(
+    d: D,
+) -> Result<Vec<u8>, D::Error> {
+    let content_hex = String::deserialize(d)?;
+    hex_bytes(&content_hex).map_err(de_Error::custom)
+}
+
+impl FromRow<Attachment> for Attachment {
+    fn from_row<'a>(row: &'a Row) -> Result<Attachment, db_error> {
+        let content: Vec<u8> = row.get("content");
+        Ok(Attachment { content })
+    }
+}
+
+impl Attachment {
+    pub fn new(content: Vec<u8>) -> Attachment {
+        Attachment { content }
+    }
+
+    /// The hash a contract commits to for this attachment
+    pub fn hash(&self) -> Hash160 {
+        Hash160::from_data(&self.content)
+    }
+}
+
+/// An on-chain declaration of an attachment
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttachmentInstance {
+    pub content_hash: Hash160,
+    pub contract_id: QualifiedContractIdentifier,
+    pub index_block_hash: StacksBlockId,
+    pub block_height: u64,
+    pub txid: Txid,
+    /// hex-encoded consensus serialization of the declaration's metadata value
+    pub metadata: String,
+}
+
+impl FromRow<AttachmentInstance> for AttachmentInstance {
+    fn from_row<'a>(row: &'a Row) -> Result<AttachmentInstance, db_error> {
+        let content_hash = Hash160::from_column(row, "content_hash")?;
+        let contract_id_str: String = row.get("contract_id");
+        let contract_id = QualifiedContractIdentifier::parse(&contract_id_str)
+            .map_err(|_e| db_error::ParseError)?;
+        let index_block_hash = StacksBlockId::from_column(row, "index_block_hash")?;
+        let block_height = u64::from_column(row, "block_height")?;
+        let txid = Txid::from_column(row, "txid")?;
+        let metadata: String = row.get("metadata");
+
+        Ok(AttachmentInstance {
+            content_hash,
+            contract_id,
+            index_block_hash,
+            block_height,
+            txid,
+            metadata,
+        })
+    }
+}
+
+impl AttachmentInstance {
+    /// Decode an attachment declaration from the value a contract printed.
+    /// Returns None if the value is not of the form
+    /// `{ attachment: { hash: (buff 20), metadata: <any value> } }`.
+    pub fn try_new_from_value(
+        value: &Value,
+        contract_id: &QualifiedContractIdentifier,
+        index_block_hash: &StacksBlockId,
+        block_height: u64,
+        txid: &Txid,
+    ) -> Option<AttachmentInstance> {
+        let attachment = match value {
+            Value::Tuple(ref data) => match data.get("attachment") {
+                Ok(Value::Tuple(ref attachment)) => attachment,
+                _ => {
+                    return None;
+                }
+            },
+            _ => {
+                return None;
+            }
+        };
+
+        let content_hash = match attachment.get("hash") {
+            Ok(Value::Sequence(SequenceData::Buffer(ref buff))) => Hash160::from_bytes(&buff.data)?,
+            _ => {
+                return None;
+            }
+        };
+
+        let metadata = attachment.get("metadata").ok()?.serialize();
+
+        Some(AttachmentInstance {
+            content_hash,
+            contract_id: contract_id.clone(),
+            index_block_hash: index_block_hash.clone(),
+            block_height,
+            txid: txid.clone(),
+            metadata,
+        })
+    }
+
+    /// Find the attachment declarations printed by the tracked contracts in a block's receipts
+    pub fn find_in_receipts(
+        config: &AtlasConfig,
+        receipts: &Vec<StacksTransactionReceipt>,
+        index_block_hash: &StacksBlockId,
+        block_height: u64,
+    ) -> Vec<AttachmentInstance> {
+        let mut instances = vec![];
+        if config.contracts.len() == 0 {
+            return instances;
+        }
+        for receipt in receipts.iter() {
+            if receipt.post_condition_aborted {
+                continue;
+            }
+            let txid = receipt.transaction.txid();
+            for event in receipt.events.iter() {
+                if let StacksTransactionEvent::SmartContractEvent(ref event_data) = event {
+                    let (ref contract_id, ref topic) = event_data.key;
+                    if topic != "print" || !config.contracts.contains(contract_id) {
+                        continue;
+                    }
+                    if let Some(instance) = AttachmentInstance::try_new_from_value(
+                        &event_data.value,
+                        contract_id,
+                        index_block_hash,
+                        block_height,
+                        &txid,
+                    ) {
+                        instances.push(instance);
+                    }
+                }
+            }
+        }
+        instances
+    }
+}
+
+pub struct AtlasDB {
+    conn: DBConn,
+    config: AtlasConfig,
+}
+
+impl AtlasDB {
+    /// Open the attachment store at the given path, creating it if it doesn't exist.
+    pub fn open(path: &str, config: AtlasConfig) -> Result<AtlasDB, db_error> {
+        let create_flag = fs::metadata(path).is_err();
+        let open_flags = if create_flag {
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE
+        } else {
+            OpenFlags::SQLITE_OPEN_READ_WRITE
+        };
+
+        let conn = Connection::open_with_flags(path, open_flags).map_err(db_error::SqliteError)?;
+        conn.busy_handler(Some(tx_busy_handler))?;
+        AtlasDB::from_connection(conn, create_flag, config)
+    }
+
+    #[cfg(test)]
+    pub fn open_memory(config: AtlasConfig) -> Result<AtlasDB, db_error> {
+        let conn = Connection::open_in_memory().map_err(db_error::SqliteError)?;
+        AtlasDB::from_connection(conn, true, config)
+    }
+
+    fn from_connection(
+        conn: DBConn,
+        instantiate: bool,
+        config: AtlasConfig,
+    ) -> Result<AtlasDB, db_error> {
+        if instantiate {
+            for cmd in ATLASDB_SQL {
+                conn.execute(cmd, NO_PARAMS)
+                    .map_err(db_error::SqliteError)?;
+            }
+        }
+        Ok(AtlasDB { conn, config })
+    }
+
+    pub fn config(&self) -> &AtlasConfig {
+        &self.config
+    }
+
+    /// Record an on-chain attachment declaration.  If we already hold the attachment, it is
+    /// marked as declared.  Returns true if we already hold it.
+    pub fn insert_attachment_instance(
+        &self,
+        instance: &AttachmentInstance,
+        now: u64,
+    ) -> Result<bool, db_error> {
+        let has_attachment = self.has_attachment(&instance.content_hash)?;
+        let args: &[&dyn ToSql] = &[
+            &instance.content_hash,
+            &instance.contract_id.to_string(),
+            &instance.index_block_hash,
+            &u64_to_sql(instance.block_height)?,
+            &instance.txid,
+            &instance.metadata,
+            &has_attachment,
+            &u64_to_sql(now)?,
+        ];
+        self.conn
+            .execute(
+                "INSERT OR IGNORE INTO attachment_instances (content_hash, contract_id, index_block_hash, block_height, txid, metadata, is_available, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                args,
+            )
+            .map_err(db_error::SqliteError)?;
+
+        if has_attachment {
+            self.conn
+                .execute(
+                    "UPDATE attachments SET was_instantiated = 1 WHERE hash = ?1",
+                    &[&instance.content_hash],
+                )
+                .map_err(db_error::SqliteError)?;
+        }
+        Ok(has_attachment)
+    }
+
+    /// Is there an on-chain declaration of this attachment?
+    pub fn has_attachment_instance(&self, content_hash: &Hash160) -> Result<bool, db_error> {
+        let count = query_count(
+            &self.conn,
+            &"SELECT COUNT(*) FROM attachment_instances WHERE content_hash = ?1".to_string(),
+            &[content_hash],
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Do we hold this attachment, declared or not?
+    fn has_attachment(&self, content_hash: &Hash160) -> Result<bool, db_error> {
+        let count = query_count(
+            &self.conn,
+            &"SELECT COUNT(*) FROM attachments WHERE hash = ?1".to_string(),
+            &[content_hash],
+        )?;
+        Ok(count > 0)
+    }
+
+    /// Store an attachment that has been declared on-chain, and mark its declarations as
+    /// available.
+    pub fn insert_instantiated_attachment(
+        &self,
+        attachment: &Attachment,
+        now: u64,
+    ) -> Result<(), db_error> {
+        let content_hash = attachment.hash();
+        let args: &[&dyn ToSql] = &[&content_hash, &attachment.content, &u64_to_sql(now)?];
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO attachments (hash, content, was_instantiated, created_at) VALUES (?1, ?2, 1, ?3)",
+                args,
+            )
+            .map_err(db_error::SqliteError)?;
+        self.conn
+            .execute(
+                "UPDATE attachment_instances SET is_available = 1 WHERE content_hash = ?1",
+                &[&content_hash],
+            )
+            .map_err(db_error::SqliteError)?;
+        Ok(())
+    }
+
+    /// Hold an attachment that has not been declared on-chain yet, evicting the ones held for
+    /// too long first.  Returns false if we already hold as many as we're allowed to.
+    pub fn insert_uninstantiated_attachment(
+        &self,
+        attachment: &Attachment,
+        now: u64,
+    ) -> Result<bool, db_error> {
+        self.expire_uninstantiated_attachments(now)?;
+
+        let content_hash = attachment.hash();
+        if self.has_attachment(&content_hash)? {
+            return Ok(true);
+        }
+
+        let count = query_count(
+            &self.conn,
+            &"SELECT COUNT(*) FROM attachments WHERE was_instantiated = 0".to_string(),
+            NO_PARAMS,
+        )?;
+        if (count as u64) >= self.config.max_uninstantiated_attachments {
+            return Ok(false);
+        }
+
+        let args: &[&dyn ToSql] = &[&content_hash, &attachment.content, &u64_to_sql(now)?];
+        self.conn
+            .execute(
+                "INSERT INTO attachments (hash, content, was_instantiated, created_at) VALUES (?1, ?2, 0, ?3)",
+                args,
+            )
+            .map_err(db_error::SqliteError)?;
+        Ok(true)
+    }
+
+    /// Drop the undeclared attachments that have been held for too long
+    pub fn expire_uninstantiated_attachments(&self, now: u64) -> Result<(), db_error> {
+        let cutoff = now.saturating_sub(self.config.uninstantiated_attachments_expire_after);
+        self.conn
+            .execute(
+                "DELETE FROM attachments WHERE was_instantiated = 0 AND created_at < ?1",
+                &[&u64_to_sql(cutoff)?],
+            )
+            .map_err(db_error::SqliteError)?;
+        Ok(())
+    }
+
+    /// Get an attachment that has been declared on-chain.  Undeclared attachments are not
+    /// served.
+    pub fn get_attachment(&self, content_hash: &Hash160) -> Result<Option<Attachment>, db_error> {
+        query_row(
+            &self.conn,
+            "SELECT content FROM attachments WHERE hash = ?1 AND was_instantiated = 1",
+            &[content_hash],
+        )
+    }
+
+    /// Get the hashes of declared attachments we don't hold yet, oldest declarations first
+    pub fn get_missing_attachment_hashes(&self, limit: u64) -> Result<Vec<Hash160>, db_error> {
+        query_row_columns(
+            &self.conn,
+            &"SELECT content_hash FROM attachment_instances WHERE is_available = 0 GROUP BY content_hash ORDER BY MIN(created_at) ASC LIMIT ?1".to_string(),
+            &[&u64_to_sql(limit)?],
+            "content_hash",
+        )
+    }
+
+    /// Get the on-chain declarations of an attachment
+    pub fn get_attachment_instances(
+        &self,
+        content_hash: &Hash160,
+    ) -> Result<Vec<AttachmentInstance>, db_error> {
+        query_rows(
+            &self.conn,
+            "SELECT * FROM attachment_instances WHERE content_hash = ?1 ORDER BY block_height ASC",
+            &[content_hash],
+        )
+    }
+
+    /// Get one on-chain declaration of an attachment, if there is one
+    pub fn get_attachment_instance(
+        &self,
+        content_hash: &Hash160,
+        index_block_hash: &StacksBlockId,
+    ) -> Result<Option<AttachmentInstance>, db_error> {
+        let args: &[&dyn ToSql] = &[content_hash, index_block_hash];
+        query_row(
+            &self.conn,
+            "SELECT * FROM attachment_instances WHERE content_hash = ?1 AND index_block_hash = ?2",
+            args,
+        )
+    }
+}
+
+/// A request for an attachment, sent to a neighbor's RPC interface
+#[derive(Debug, Clone, PartialEq)]
+struct AttachmentRequest {
+    content_hash: Hash160,
+    data_url: UrlString,
+    event_id: usize,
+    started_at: u64,
+}
+
+/// Fetches the declared attachments we don't hold yet from our neighbors
+#[derive(Debug)]
+pub struct AttachmentsDownloader {
+    /// requests in flight
+    inflight: Vec<AttachmentRequest>,
+    /// for each missing attachment, the data URLs we already asked for it
+    tried_urls: HashMap<Hash160, HashSet<UrlString>>,
+    /// attachments no neighbor had, and when we may ask for them again
+    retry_at: HashMap<Hash160, u64>,
+}
+
+impl AttachmentsDownloader {
+    pub fn new() -> AttachmentsDownloader {
+        AttachmentsDownloader {
+            inflight: vec![],
+            tried_urls: HashMap::new(),
+            retry_at: HashMap::new(),
+        }
+    }
+
+    pub fn num_inflight(&self) -> usize {
+        self.inflight.len()
+    }
+
+    fn is_inflight(&self, content_hash: &Hash160) -> bool {
+        self.inflight
+            .iter()
+            .any(|request| request.content_hash == *content_hash)
+    }
+
+    /// Pick a data URL we haven't asked for this attachment yet.  If we've asked them all, start
+    /// over once the retry interval passes.
+    fn next_url(
+        &mut self,
+        content_hash: &Hash160,
+        data_urls: &Vec<UrlString>,
+        now: u64,
+    ) -> Option<UrlString> {
+        if let Some(retry_at) = self.retry_at.get(content_hash) {
+            if *retry_at > now {
+                return None;
+            }
+        }
+        self.retry_at.remove(content_hash);
+
+        let tried = self
+            .tried_urls
+            .entry(content_hash.clone())
+            .or_insert_with(HashSet::new);
+        for data_url in data_urls.iter() {
+            if !tried.contains(data_url) {
+                tried.insert(data_url.clone());
+                return Some(data_url.clone());
+            }
+        }
+
+        // asked everyone
+        tried.clear();
+        self.retry_at
+            .insert(content_hash.clone(), now + ATTACHMENT_RETRY_INTERVAL);
+        None
+    }
+
+    /// Forget about an attachment we now hold
+    fn finish(&mut self, content_hash: &Hash160) -> () {
+        self.tried_urls.remove(content_hash);
+        self.retry_at.remove(content_hash);
+    }
+}
+
+impl PeerNetwork {
+    /// The data URLs of our authenticated neighbors whose RPC interfaces we can reach without a
+    /// DNS lookup
+    fn get_attachment_data_urls(&self) -> Vec<(UrlString, SocketAddr)> {
+        let mut data_urls = vec![];
+        for (_, convo) in self.peers.iter() {
+            if !convo.is_authenticated() || convo.data_url.len() == 0 {
+                continue;
+            }
+            match PeerHost::try_from_url(&convo.data_url) {
+                Some(PeerHost::IP(addr, port)) => {
+                    data_urls.push((convo.data_url.clone(), addr.to_socketaddr(port)));
+                }
+                _ => {
+                    test_debug!(
+                        "{:?}: Will not fetch attachments from {:?}: not an IP address",
+                        &self.local_peer,
+                        &convo.data_url
+                    );
+                }
+            }
+        }
+        data_urls.sort_by(|a, b| a.0.cmp(&b.0));
+        data_urls.dedup_by(|a, b| a.0 == b.0);
+        data_urls
+    }
+
+    /// Collect the responses to our attachment requests, storing the attachments we get.
+    /// Returns the attachments fetched, and the HTTP connections to close.
+    fn attachments_try_finish(
+        &mut self,
+        atlasdb: &AtlasDB,
+        downloader: &mut AttachmentsDownloader,
+        now: u64,
+    ) -> (Vec<Attachment>, Vec<usize>) {
+        let mut fetched = vec![];
+        let mut dead = vec![];
+        let mut pending = vec![];
+
+        for request in downloader.inflight.drain(..) {
+            let timed_out = request.started_at + self.connection_opts.timeout < now;
+            match self.http.get_conversation(request.event_id) {
+                None => {
+                    if self.http.is_connecting(request.event_id) && !timed_out {
+                        pending.push(request);
+                    } else {
+                        debug!(
+                            "{:?}: Failed to connect to {:?} for attachment {}",
+                            &self.local_peer, &request.data_url, &request.content_hash
+                        );
+                        dead.push(request.event_id);
+                    }
+                }
+                Some(ref mut convo) => match convo.try_get_response() {
+                    None => {
+                        if timed_out {
+                            debug!(
+                                "{:?}: Request to {:?} for attachment {} timed out",
+                                &self.local_peer, &request.data_url, &request.content_hash
+                            );
+                            dead.push(request.event_id);
+                        } else {
+                            pending.push(request);
+                        }
+                    }
+                    Some(HttpResponseType::GetAttachment(_, response)) => {
+                        if response.attachment.hash() != request.content_hash {
+                            info!(
+                                "{:?}: Invalid attachment from {:?}: did not ask for {}",
+                                &self.local_peer,
+                                &request.data_url,
+                                &response.attachment.hash()
+                            );
+                            dead.push(request.event_id);
+                            continue;
+                        }
+                        if response.attachment.content.len() as u64
+                            > atlasdb.config().attachments_max_size
+                        {
+                            info!(
+                                "{:?}: Attachment {} from {:?} is too big ({} bytes)",
+                                &self.local_peer,
+                                &request.content_hash,
+                                &request.data_url,
+                                response.attachment.content.len()
+                            );
+                            continue;
+                        }
+                        debug!(
+                            "{:?}: Got attachment {} from {:?}",
+                            &self.local_peer, &request.content_hash, &request.data_url
+                        );
+                        match atlasdb.insert_instantiated_attachment(&response.attachment, now) {
+                            Ok(_) => {
+                                downloader.finish(&request.content_hash);
+                                fetched.push(response.attachment);
+                            }
+                            Err(e) => {
+                                warn!(
+                                    "{:?}: Failed to store attachment {}: {:?}",
+                                    &self.local_peer, &request.content_hash, &e
+                                );
+                            }
+                        }
+                    }
+                    Some(HttpResponseType::NotFound(..)) => {
+                        debug!(
+                            "{:?}: {:?} does not have attachment {}",
+                            &self.local_peer, &request.data_url, &request.content_hash
+                        );
+                    }
+                    Some(http_response) => {
+                        info!(
+                            "{:?}: Got bad HTTP response from {:?}: {:?}",
+                            &self.local_peer, &request.data_url, &http_response
+                        );
+                        dead.push(request.event_id);
+                    }
+                },
+            }
+        }
+
+        downloader.inflight = pending;
+        (fetched, dead)
+    }
+
+    /// Ask our neighbors for the declared attachments we don't hold yet
+    fn attachments_begin(
+        &mut self,
+        atlasdb: &AtlasDB,
+        downloader: &mut AttachmentsDownloader,
+        chainstate: &mut StacksChainState,
+        now: u64,
+    ) -> Result<(), net_error> {
+        let max_inflight = atlasdb.config().max_inflight_attachments;
+        if (downloader.num_inflight() as u64) >= max_inflight {
+            return Ok(());
+        }
+
+        let data_urls = self.get_attachment_data_urls();
+        if data_urls.len() == 0 {
+            return Ok(());
+        }
+        let urls: Vec<UrlString> = data_urls.iter().map(|(url, _)| url.clone()).collect();
+
+        let missing = atlasdb
+            .get_missing_attachment_hashes(max_inflight * 4)
+            .map_err(net_error::DBError)?;
+
+        for content_hash in missing.into_iter() {
+            if (downloader.num_inflight() as u64) >= max_inflight {
+                break;
+            }
+            if downloader.is_inflight(&content_hash) {
+                continue;
+            }
+            let data_url = match downloader.next_url(&content_hash, &urls, now) {
+                Some(data_url) => data_url,
+                None => {
+                    continue;
+                }
+            };
+            let addr = match data_urls.iter().find(|(url, _)| *url == data_url) {
+                Some((_, addr)) => addr.clone(),
+                None => {
+                    continue;
+                }
+            };
+            let peerhost = match PeerHost::try_from_url(&data_url) {
+                Some(peerhost) => peerhost,
+                None => {
+                    continue;
+                }
+            };
+
+            let request = HttpRequestType::GetAttachment(
+                HttpRequestMetadata::from_host(peerhost),
+                content_hash.clone(),
+            );
+            match self.connect_or_send_http_request(data_url.clone(), addr, request, chainstate) {
+                Ok(event_id) => {
+                    debug!(
+                        "{:?}: Request attachment {} from {:?}",
+                        &self.local_peer, &content_hash, &data_url
+                    );
+                    downloader.inflight.push(AttachmentRequest {
+                        content_hash,
+                        data_url,
+                        event_id,
+                        started_at: now,
+                    });
+                }
+                Err(e) => {
+                    debug!(
+                        "{:?}: Failed to request attachment {} from {:?}: {:?}",
+                        &self.local_peer, &content_hash, &data_url, &e
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Drive the attachments downloader: store the attachments our neighbors sent us, and ask
+    /// for more.  Returns the attachments fetched.
+    pub fn download_attachments(
+        &mut self,
+        atlasdb: &AtlasDB,
+        chainstate: &mut StacksChainState,
+    ) -> Result<Vec<Attachment>, net_error> {
+        let mut downloader = match self.attachments_downloader.take() {
+            Some(downloader) => downloader,
+            None => AttachmentsDownloader::new(),
+        };
+
+        let now = get_epoch_time_secs();
+        let (fetched, dead) = self.attachments_try_finish(atlasdb, &mut downloader, now);
+
+        let _ = PeerNetwork::with_network_state(self, |ref mut network, ref mut network_state| {
+            for event_id in dead.into_iter() {
+                debug!(
+                    "{:?}: De-register HTTP connection {} for attachments",
+                    &network.local_peer, event_id
+                );
+                network.http.deregister_http(network_state, event_id);
+            }
+            Ok(())
+        });
+
+        let res = self.attachments_begin(atlasdb, &mut downloader, chainstate, now);
+        self.attachments_downloader = Some(downloader);
+        res.and_then(|_| Ok(fetched))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::convert::TryFrom;
+
+    use vm::types::TupleData;
+
+    fn make_contract_id() -> QualifiedContractIdentifier {
+        QualifiedContractIdentifier::parse("ST000000000000000000002AMW42H.bns").unwrap()
+    }
+
+    fn make_declaration(content_hash: &Hash160) -> Value {
+        let attachment = TupleData::from_data(vec![
+            (
+                "hash".into(),
+                Value::buff_from(content_hash.as_bytes().to_vec()).unwrap(),
+            ),
+            ("metadata".into(), Value::UInt(7)),
+        ])
+        .unwrap();
+        Value::Tuple(
+            TupleData::from_data(vec![("attachment".into(), Value::Tuple(attachment))]).unwrap(),
+        )
+    }
+
+    fn make_instance(content_hash: &Hash160, block: u8) -> AttachmentInstance {
+        AttachmentInstance::try_new_from_value(
+            &make_declaration(content_hash),
+            &make_contract_id(),
+            &StacksBlockId([block; 32]),
+            block as u64,
+            &Txid([block; 32]),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_attachment_instance_from_value() {
+        let attachment = Attachment::new("hello world".as_bytes().to_vec());
+        let content_hash = attachment.hash();
+
+        let instance = make_instance(&content_hash, 1);
+        assert_eq!(instance.content_hash, content_hash);
+        assert_eq!(instance.contract_id, make_contract_id());
+        assert_eq!(instance.block_height, 1);
+        assert_eq!(instance.metadata, Value::UInt(7).serialize());
+
+        // not declarations
+        let contract_id = make_contract_id();
+        let block_id = StacksBlockId([0x01; 32]);
+        let txid = Txid([0x01; 32]);
+        for value in [
+            Value::UInt(1),
+            Value::Tuple(
+                TupleData::from_data(vec![("attachment".into(), Value::UInt(1))]).unwrap(),
+            ),
+            Value::Tuple(
+                TupleData::from_data(vec![(
+                    "attachment".into(),
+                    Value::Tuple(
+                        TupleData::from_data(vec![
+                            ("hash".into(), Value::UInt(1)),
+                            ("metadata".into(), Value::UInt(7)),
+                        ])
+                        .unwrap(),
+                    ),
+                )])
+                .unwrap(),
+            ),
+            Value::Tuple(
+                TupleData::from_data(vec![(
+                    "attachment".into(),
+                    Value::Tuple(
+                        TupleData::from_data(vec![(
+                            "hash".into(),
+                            Value::buff_from(content_hash.as_bytes().to_vec()).unwrap(),
+                        )])
+                        .unwrap(),
+                    ),
+                )])
+                .unwrap(),
+            ),
+        ]
+        .iter()
+        {
+            assert!(AttachmentInstance::try_new_from_value(
+                value,
+                &contract_id,
+                &block_id,
+                1,
+                &txid
+            )
+            .is_none());
+        }
+
+        // JSON round-trip
+        let json = serde_json::to_string(&attachment).unwrap();
+        assert_eq!(
+            json,
+            format!("{{\"content\":\"{}\"}}", to_hex(&attachment.content))
+        );
+        let attachment_2: Attachment = serde_json::from_str(&json).unwrap();
+        assert_eq!(attachment, attachment_2);
+    }
+
+    #[test]
+    fn test_atlasdb_attachments() {
+        let mut config = AtlasConfig::default();
+        config.contracts.insert(make_contract_id());
+        config.max_uninstantiated_attachments = 2;
+        config.uninstantiated_attachments_expire_after = 100;
+        let atlasdb = AtlasDB::open_memory(config).unwrap();
+
+        let attachment_1 = Attachment::new(vec![1, 2, 3]);
+        let attachment_2 = Attachment::new(vec![4, 5, 6]);
+        let attachment_3 = Attachment::new(vec![7, 8, 9]);
+        let attachment_4 = Attachment::new(vec![10, 11, 12]);
+
+        // declared, but not held
+        let instance_1 = make_instance(&attachment_1.hash(), 1);
+        assert!(!atlasdb
+            .insert_attachment_instance(&instance_1, 1000)
+            .unwrap());
+        assert!(atlasdb
+            .has_attachment_instance(&attachment_1.hash())
+            .unwrap());
+        assert_eq!(
+            atlasdb.get_missing_attachment_hashes(10).unwrap(),
+            vec![attachment_1.hash()]
+        );
+        assert_eq!(atlasdb.get_attachment(&attachment_1.hash()).unwrap(), None);
+        assert_eq!(
+            atlasdb
+                .get_attachment_instance(&attachment_1.hash(), &StacksBlockId([1; 32]))
+                .unwrap(),
+            Some(instance_1.clone())
+        );
+
+        // fetched
+        atlasdb
+            .insert_instantiated_attachment(&attachment_1, 1001)
+            .unwrap();
+        assert_eq!(atlasdb.get_missing_attachment_hashes(10).unwrap(), vec![]);
+        assert_eq!(
+            atlasdb.get_attachment(&attachment_1.hash()).unwrap(),
+            Some(attachment_1.clone())
+        );
+
+        // uploaded before being declared: held, but not served
+        assert!(atlasdb
+            .insert_uninstantiated_attachment(&attachment_2, 1000)
+            .unwrap());
+        assert!(atlasdb
+            .insert_uninstantiated_attachment(&attachment_3, 1050)
+            .unwrap());
+        assert_eq!(atlasdb.get_attachment(&attachment_2.hash()).unwrap(), None);
+
+        // too many held
+        assert!(!atlasdb
+            .insert_uninstantiated_attachment(&attachment_4, 1050)
+            .unwrap());
+
+        // declared once held
+        let instance_2 = make_instance(&attachment_2.hash(), 2);
+        assert!(atlasdb
+            .insert_attachment_instance(&instance_2, 1060)
+            .unwrap());
+        assert_eq!(
+            atlasdb.get_attachment(&attachment_2.hash()).unwrap(),
+            Some(attachment_2.clone())
+        );
+        assert_eq!(atlasdb.get_missing_attachment_hashes(10).unwrap(), vec![]);
+        assert_eq!(
+            atlasdb
+                .get_attachment_instances(&attachment_2.hash())
+                .unwrap(),
+            vec![instance_2]
+        );
+
+        // attachment_3 expires, making room for attachment_4
+        assert!(atlasdb
+            .insert_uninstantiated_attachment(&attachment_4, 1151)
+            .unwrap());
+        let instance_3 = make_instance(&attachment_3.hash(), 3);
+        assert!(!atlasdb
+            .insert_attachment_instance(&instance_3, 1152)
+            .unwrap());
+        assert_eq!(
+            atlasdb.get_missing_attachment_hashes(10).unwrap(),
+            vec![attachment_3.hash()]
+        );
+    }
+
+    #[test]
+    fn test_attachments_downloader_next_url() {
+        let mut downloader = AttachmentsDownloader::new();
+        let content_hash = Hash160([0x01; 20]);
+        let urls = vec![
+            UrlString::try_from("http://1.2.3.4:20443".to_string()).unwrap(),
+            UrlString::try_from("http://5.6.7.8:20443".to_string()).unwrap(),
+        ];
+
+        assert_eq!(
+            downloader.next_url(&content_hash, &urls, 1000),
+            Some(urls[0].clone())
+        );
+        assert_eq!(
+            downloader.next_url(&content_hash, &urls, 1000),
+            Some(urls[1].clone())
+        );
+
+        // asked everyone; wait before asking again
+        assert_eq!(downloader.next_url(&content_hash, &urls, 1000), None);
+        assert_eq!(
+            downloader.next_url(&content_hash, &urls, 1000 + ATTACHMENT_RETRY_INTERVAL - 1),
+            None
+        );
+        assert_eq!(
+            downloader.next_url(&content_hash, &urls, 1000 + ATTACHMENT_RETRY_INTERVAL),
+            Some(urls[0].clone())
+        );
+
+        downloader.finish(&content_hash);
+        assert_eq!(
+            downloader.next_url(&content_hash, &urls, 1000),
+            Some(urls[0].clone())
+        );
+    }
+}
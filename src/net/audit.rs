@@ -0,0 +1,340 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Persistent audit log of the RPC requests a node has served, for abuse forensics on shared
+//! infrastructure.  Each request is recorded with its endpoint, query parameters, caller,
+//! response status code, and latency.  Request bodies are never recorded.
+//!
+//! How much of the caller's address is kept is configurable -- see `AuditCallerPrivacy`.
+//! Records older than the configured retention period are deleted.
+
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+
+use rand::thread_rng;
+use rand::RngCore;
+
+use rusqlite::types::ToSql;
+use rusqlite::Row;
+use rusqlite::{Connection, OpenFlags, NO_PARAMS};
+
+use util::db::tx_busy_handler;
+use util::db::DBConn;
+use util::db::Error as db_error;
+use util::db::{query_rows, u64_to_sql, FromColumn, FromRow};
+use util::hash::{to_hex, Sha256Sum};
+
+/// How often to delete expired records, in seconds
+const AUDIT_LOG_PRUNE_INTERVAL: u64 = 60;
+
+const RPC_AUDIT_LOG_SQL: &'static [&'static str] = &[
+    r#"
+    CREATE TABLE rpc_audit_log(
+        timestamp INTEGER NOT NULL,
+        endpoint TEXT NOT NULL,
+        params TEXT NOT NULL,
+        caller TEXT NOT NULL,
+        status_code INTEGER NOT NULL,
+        latency_ms INTEGER NOT NULL
+    );"#,
+    "CREATE INDEX IF NOT EXISTS rpc_audit_log_by_timestamp ON rpc_audit_log(timestamp);",
+];
+
+/// How a caller's IP address is recorded in the audit log
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuditCallerPrivacy {
+    /// record the full IP address
+    Full,
+    /// record only the network prefix (/24 for IPv4, /48 for IPv6)
+    Anonymized,
+    /// record a salted hash of the IP address.  The salt is regenerated each time the log is
+    /// opened and never stored, so the same caller can be correlated across requests made
+    /// while the node is running, but its address cannot be recovered from the log.
+    Hashed,
+    /// do not record the caller at all
+    Omitted,
+}
+
+impl AuditCallerPrivacy {
+    pub fn from_str(s: &str) -> Option<AuditCallerPrivacy> {
+        match s {
+            "full" => Some(AuditCallerPrivacy::Full),
+            "anonymized" => Some(AuditCallerPrivacy::Anonymized),
+            "hashed" => Some(AuditCallerPrivacy::Hashed),
+            "omitted" => Some(AuditCallerPrivacy::Omitted),
+            _ => None,
+        }
+    }
+}
+
+/// A single served RPC request
+#[derive(Debug, Clone, PartialEq)]
+pub struct RPCAuditRecord {
+    pub timestamp: u64,
+    /// request path, without the query string
+    pub endpoint: String,
+    /// query string, if any
+    pub params: String,
+    pub caller: SocketAddr,
+    pub status_code: u16,
+    pub latency_ms: u64,
+}
+
+/// An audit record as stored in the log, with the caller already redacted
+#[derive(Debug, Clone, PartialEq)]
+pub struct RPCAuditEntry {
+    pub timestamp: u64,
+    pub endpoint: String,
+    pub params: String,
+    pub caller: String,
+    pub status_code: u16,
+    pub latency_ms: u64,
+}
+
+impl FromRow<RPCAuditEntry> for RPCAuditEntry {
+    fn from_row<'a>(row: &'a Row) -> Result<RPCAuditEntry, db_error> {
+        let timestamp = u64::from_column(row, "timestamp")?;
+        let endpoint: String = row.get("endpoint");
+        let params: String = row.get("params");
+        let caller: String = row.get("caller");
+        let status_code: u16 = row.get("status_code");
+        let latency_ms = u64::from_column(row, "latency_ms")?;
+
+        Ok(RPCAuditEntry {
+            timestamp,
+            endpoint,
+            params,
+            caller,
+            status_code,
+            latency_ms,
+        })
+    }
+}
+
+pub struct RPCAuditLog {
+    conn: DBConn,
+    caller_privacy: AuditCallerPrivacy,
+    salt: [u8; 32],
+    /// how long to keep records, in seconds (0 means forever)
+    retention: u64,
+    last_prune: u64,
+}
+
+impl RPCAuditLog {
+    /// Open the audit log at the given path, creating it if it doesn't exist.
+    pub fn open(
+        path: &str,
+        caller_privacy: AuditCallerPrivacy,
+        retention: u64,
+    ) -> Result<RPCAuditLog, db_error> {
+        let create_flag = fs::metadata(path).is_err();
+        let open_flags = if create_flag {
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE
+        } else {
+            OpenFlags::SQLITE_OPEN_READ_WRITE
+        };
+
+        let conn = Connection::open_with_flags(path, open_flags).map_err(db_error::SqliteError)?;
+        conn.busy_handler(Some(tx_busy_handler))?;
+        RPCAuditLog::from_connection(conn, create_flag, caller_privacy, retention)
+    }
+
+    #[cfg(test)]
+    pub fn open_memory(
+        caller_privacy: AuditCallerPrivacy,
+        retention: u64,
+    ) -> Result<RPCAuditLog, db_error> {
+        let conn = Connection::open_in_memory().map_err(db_error::SqliteError)?;
+        RPCAuditLog::from_connection(conn, true, caller_privacy, retention)
+    }
+
+    fn from_connection(
+        conn: DBConn,
+        instantiate: bool,
+        caller_privacy: AuditCallerPrivacy,
+        retention: u64,
+    ) -> Result<RPCAuditLog, db_error> {
+        if instantiate {
+            for cmd in RPC_AUDIT_LOG_SQL {
+                conn.execute(cmd, NO_PARAMS)
+                    .map_err(db_error::SqliteError)?;
+            }
+        }
+
+        let mut salt = [0u8; 32];
+        thread_rng().fill_bytes(&mut salt);
+
+        Ok(RPCAuditLog {
+            conn,
+            caller_privacy,
+            salt,
+            retention,
+            last_prune: 0,
+        })
+    }
+
+    /// Render a caller's address according to the configured privacy mode
+    fn redact_caller(&self, caller: &SocketAddr) -> String {
+        match self.caller_privacy {
+            AuditCallerPrivacy::Full => caller.ip().to_string(),
+            AuditCallerPrivacy::Anonymized => match caller.ip() {
+                IpAddr::V4(addr) => {
+                    let octets = addr.octets();
+                    format!("{}.{}.{}.0/24", octets[0], octets[1], octets[2])
+                }
+                IpAddr::V6(addr) => {
+                    let segments = addr.segments();
+                    format!("{:x}:{:x}:{:x}::/48", segments[0], segments[1], segments[2])
+                }
+            },
+            AuditCallerPrivacy::Hashed => {
+                let mut bytes = self.salt.to_vec();
+                bytes.extend_from_slice(caller.ip().to_string().as_bytes());
+                to_hex(&Sha256Sum::from_data(&bytes).as_bytes()[0..16])
+            }
+            AuditCallerPrivacy::Omitted => "".to_string(),
+        }
+    }
+
+    /// Store a served request, and periodically delete expired records.
+    pub fn record(&mut self, record: &RPCAuditRecord) -> Result<(), db_error> {
+        let caller = self.redact_caller(&record.caller);
+        let args: &[&dyn ToSql] = &[
+            &u64_to_sql(record.timestamp)?,
+            &record.endpoint,
+            &record.params,
+            &caller,
+            &record.status_code,
+            &u64_to_sql(record.latency_ms)?,
+        ];
+        self.conn
+            .execute(
+                "INSERT INTO rpc_audit_log (timestamp, endpoint, params, caller, status_code, latency_ms) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                args,
+            )
+            .map_err(db_error::SqliteError)?;
+
+        if self.last_prune + AUDIT_LOG_PRUNE_INTERVAL < record.timestamp {
+            self.prune(record.timestamp)?;
+            self.last_prune = record.timestamp;
+        }
+        Ok(())
+    }
+
+    /// Delete all records that have outlived the retention period, as of `now`.
+    pub fn prune(&mut self, now: u64) -> Result<(), db_error> {
+        if self.retention == 0 || now < self.retention {
+            return Ok(());
+        }
+        let args: &[&dyn ToSql] = &[&u64_to_sql(now - self.retention)?];
+        self.conn
+            .execute("DELETE FROM rpc_audit_log WHERE timestamp < ?1", args)
+            .map_err(db_error::SqliteError)?;
+        Ok(())
+    }
+
+    /// Get all records at or after the given time, oldest first.
+    pub fn get_entries_since(&self, timestamp: u64) -> Result<Vec<RPCAuditEntry>, db_error> {
+        let args: &[&dyn ToSql] = &[&u64_to_sql(timestamp)?];
+        query_rows(
+            &self.conn,
+            "SELECT * FROM rpc_audit_log WHERE timestamp >= ?1 ORDER BY timestamp, rowid",
+            args,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn make_record(timestamp: u64, caller: &str) -> RPCAuditRecord {
+        RPCAuditRecord {
+            timestamp,
+            endpoint: "/v2/accounts/SP000000000000000000002Q6VF78".to_string(),
+            params: "proof=0".to_string(),
+            caller: caller.parse().unwrap(),
+            status_code: 200,
+            latency_ms: 12,
+        }
+    }
+
+    #[test]
+    fn test_audit_log_caller_privacy() {
+        let mut log = RPCAuditLog::open_memory(AuditCallerPrivacy::Full, 0).unwrap();
+        log.record(&make_record(1, "1.2.3.4:5678")).unwrap();
+        log.record(&make_record(2, "[2001:db8:1:2::1]:5678"))
+            .unwrap();
+        let entries = log.get_entries_since(0).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].caller, "1.2.3.4");
+        assert_eq!(
+            entries[0].endpoint,
+            "/v2/accounts/SP000000000000000000002Q6VF78"
+        );
+        assert_eq!(entries[0].params, "proof=0");
+        assert_eq!(entries[0].status_code, 200);
+        assert_eq!(entries[0].latency_ms, 12);
+        assert_eq!(entries[1].caller, "2001:db8:1:2::1");
+
+        let mut log = RPCAuditLog::open_memory(AuditCallerPrivacy::Anonymized, 0).unwrap();
+        log.record(&make_record(1, "1.2.3.4:5678")).unwrap();
+        log.record(&make_record(2, "[2001:db8:1:2::1]:5678"))
+            .unwrap();
+        let entries = log.get_entries_since(0).unwrap();
+        assert_eq!(entries[0].caller, "1.2.3.0/24");
+        assert_eq!(entries[1].caller, "2001:db8:1::/48");
+
+        let mut log = RPCAuditLog::open_memory(AuditCallerPrivacy::Hashed, 0).unwrap();
+        log.record(&make_record(1, "1.2.3.4:5678")).unwrap();
+        log.record(&make_record(2, "1.2.3.4:9999")).unwrap();
+        log.record(&make_record(3, "1.2.3.5:5678")).unwrap();
+        let entries = log.get_entries_since(0).unwrap();
+        assert_eq!(entries[0].caller.len(), 32);
+        assert!(entries[0].caller.find("1.2.3.4").is_none());
+        assert_eq!(entries[0].caller, entries[1].caller);
+        assert!(entries[0].caller != entries[2].caller);
+
+        let mut log = RPCAuditLog::open_memory(AuditCallerPrivacy::Omitted, 0).unwrap();
+        log.record(&make_record(1, "1.2.3.4:5678")).unwrap();
+        assert_eq!(log.get_entries_since(0).unwrap()[0].caller, "");
+    }
+
+    #[test]
+    fn test_audit_log_retention() {
+        let mut log = RPCAuditLog::open_memory(AuditCallerPrivacy::Full, 100).unwrap();
+        for i in 0..10 {
+            log.record(&make_record(1000 + i * 20, "1.2.3.4:5678"))
+                .unwrap();
+        }
+        // expired records were deleted as of the last prune, at 1160
+        let entries = log.get_entries_since(0).unwrap();
+        assert_eq!(entries.len(), 7);
+        assert_eq!(entries[0].timestamp, 1060);
+        assert_eq!(log.get_entries_since(1100).unwrap().len(), 5);
+
+        log.prune(1200).unwrap();
+        let entries = log.get_entries_since(0).unwrap();
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[0].timestamp, 1100);
+
+        // no retention limit
+        let mut log = RPCAuditLog::open_memory(AuditCallerPrivacy::Full, 0).unwrap();
+        log.record(&make_record(1, "1.2.3.4:5678")).unwrap();
+        log.prune(1_000_000_000).unwrap();
+        assert_eq!(log.get_entries_since(0).unwrap().len(), 1);
+    }
+}
@@ -98,8 +98,19 @@ pub const HEALTH_POINT_LIFETIME: u64 = 12 * 3600; // 12 hours
 pub const NUM_BLOCK_POINTS: usize = 32;
 pub const BLOCK_POINT_LIFETIME: u64 = 600;
 
+pub const NUM_DOWNLOAD_POINTS: usize = 32;
+pub const DOWNLOAD_POINT_LIFETIME: u64 = 3600;
+
 pub const MAX_PEER_HEARTBEAT_INTERVAL: usize = 3600 * 6; // 6 hours
 
+/// A block or microblock stream we downloaded from a neighbor's data URL
+#[derive(Debug, Clone, PartialEq)]
+pub struct NeighborDownloadPoint {
+    pub time: u64,       // when the download finished
+    pub latency_ms: u64, // how long it took, from sending the request to getting the whole reply
+    pub num_bytes: u64,  // how big the reply was
+}
+
 /// Statistics on relayer hints in Stacks messages.  Used to deduce network choke points.
 #[derive(Debug, Clone)]
 pub struct RelayStats {
@@ -141,12 +152,14 @@ pub struct NeighborStats {
     pub msgs_rx: u64,
     pub msgs_rx_unsolicited: u64,
     pub msgs_err: u64,
+    pub msgs_invalid: u64, // messages that broke the protocol (a subset of msgs_err)
     pub healthpoints: VecDeque<NeighborHealthPoint>,
     pub msg_rx_counts: HashMap<StacksMessageID, u64>,
     pub block_push_rx_counts: VecDeque<(u64, u64)>, // (count, num bytes)
     pub microblocks_push_rx_counts: VecDeque<(u64, u64)>, // (count, num bytes)
     pub transaction_push_rx_counts: VecDeque<(u64, u64)>, // (count, num bytes)
     pub relayed_messages: HashMap<NeighborAddress, RelayStats>,
+    pub download_points: VecDeque<NeighborDownloadPoint>,
 }
 
 impl NeighborStats {
@@ -164,12 +177,14 @@ impl NeighborStats {
             msgs_rx: 0,
             msgs_rx_unsolicited: 0,
             msgs_err: 0,
+            msgs_invalid: 0,
             healthpoints: VecDeque::new(),
             msg_rx_counts: HashMap::new(),
             block_push_rx_counts: VecDeque::new(),
             microblocks_push_rx_counts: VecDeque::new(),
             transaction_push_rx_counts: VecDeque::new(),
             relayed_messages: HashMap::new(),
+            download_points: VecDeque::new(),
         }
     }
 
@@ -223,6 +238,17 @@ impl NeighborStats {
         }
     }
 
+    pub fn add_download(&mut self, latency_ms: u64, num_bytes: u64) -> () {
+        self.download_points.push_back(NeighborDownloadPoint {
+            time: get_epoch_time_secs(),
+            latency_ms: latency_ms,
+            num_bytes: num_bytes,
+        });
+        while self.download_points.len() > NUM_DOWNLOAD_POINTS {
+            self.download_points.pop_front();
+        }
+    }
+
     pub fn take_relayers(&mut self) -> HashMap<NeighborAddress, RelayStats> {
         let ret = mem::replace(&mut self.relayed_messages, HashMap::new());
         ret
@@ -287,6 +313,81 @@ impl NeighborStats {
         NeighborStats::get_bandwidth(&self.transaction_push_rx_counts, BLOCK_POINT_LIFETIME)
     }
 
+    /// Get the mean latency of this peer's recent block and microblock downloads, in
+    /// milliseconds.  Returns None if we haven't downloaded anything from it recently.
+    pub fn get_download_latency(&self) -> Option<u64> {
+        let now = get_epoch_time_secs();
+        let mut total_latency = 0;
+        let mut count = 0;
+        for point in self.download_points.iter() {
+            if now < point.time + DOWNLOAD_POINT_LIFETIME {
+                total_latency += point.latency_ms;
+                count += 1;
+            }
+        }
+        if count == 0 {
+            None
+        } else {
+            Some(total_latency / count)
+        }
+    }
+
+    /// Get this peer's recent block and microblock download throughput, in bytes per second.
+    pub fn get_download_throughput(&self) -> f64 {
+        let now = get_epoch_time_secs();
+        let mut total_latency = 0;
+        let mut total_bytes = 0;
+        for point in self.download_points.iter() {
+            if now < point.time + DOWNLOAD_POINT_LIFETIME {
+                total_latency += point.latency_ms;
+                total_bytes += point.num_bytes;
+            }
+        }
+        if total_latency == 0 {
+            0.0
+        } else {
+            (total_bytes as f64) * 1000.0 / (total_latency as f64)
+        }
+    }
+
+    /// Estimate how long this peer would take to serve a download of `num_bytes` bytes, in
+    /// milliseconds, from its recent throughput.  If none of its recent downloads carried any
+    /// data (e.g. they all timed out), this is its mean download latency.  Returns None if we
+    /// haven't downloaded anything from it recently.
+    pub fn get_expected_download_time(&self, num_bytes: u64) -> Option<u64> {
+        let latency = match self.get_download_latency() {
+            Some(latency) => latency,
+            None => {
+                return None;
+            }
+        };
+        let throughput = self.get_download_throughput();
+        if throughput <= 0.0 {
+            Some(latency)
+        } else {
+            Some(((num_bytes as f64) * 1000.0 / throughput) as u64)
+        }
+    }
+
+    /// Get the mean size of this peer's recent block and microblock downloads that carried any
+    /// data, in bytes.  Returns None if there weren't any.
+    pub fn get_download_size(&self) -> Option<u64> {
+        let now = get_epoch_time_secs();
+        let mut total_bytes = 0;
+        let mut count = 0;
+        for point in self.download_points.iter() {
+            if now < point.time + DOWNLOAD_POINT_LIFETIME && point.num_bytes > 0 {
+                total_bytes += point.num_bytes;
+                count += 1;
+            }
+        }
+        if count == 0 {
+            None
+        } else {
+            Some(total_bytes / count)
+        }
+    }
+
     /// Determine how many of a particular message this peer has received
     pub fn get_message_recv_count(&self, msg_id: StacksMessageID) -> u64 {
         *(self.msg_rx_counts.get(&msg_id).unwrap_or(&0))
@@ -1597,6 +1698,7 @@ impl ConversationP2P {
         if !self.process_relayers(local_peer, preamble, &relayers) {
             debug!("Drop pushed blocks -- invalid relayers {:?}", &relayers);
             self.stats.msgs_err += 1;
+            self.stats.msgs_invalid += 1;
             return Err(net_error::InvalidMessage);
         }
 
@@ -1637,6 +1739,7 @@ impl ConversationP2P {
                 &relayers
             );
             self.stats.msgs_err += 1;
+            self.stats.msgs_invalid += 1;
             return Err(net_error::InvalidMessage);
         }
 
@@ -1672,6 +1775,7 @@ impl ConversationP2P {
                 &relayers
             );
             self.stats.msgs_err += 1;
+            self.stats.msgs_invalid += 1;
             return Err(net_error::InvalidMessage);
         }
 
@@ -1915,6 +2019,7 @@ impl ConversationP2P {
                             &self
                         );
                         self.stats.msgs_err += 1;
+                        self.stats.msgs_invalid += 1;
                         self.stats.add_healthpoint(false);
                         return Err(e);
                     }
@@ -2371,6 +2476,7 @@ mod test {
             burnchain.first_block_height,
             &burnchain.first_block_hash,
             get_epoch_time_secs(),
+            &burnchain.get_stacks_epochs(),
             true,
         )
         .unwrap();
@@ -4153,6 +4259,47 @@ mod test {
             assert_eq!(stats.num_bytes, (msg.preamble.payload_len - 1) as u64);
         }
     }
+
+    #[test]
+    fn neighbor_stats_download() {
+        let mut stats = NeighborStats::new(true);
+        assert_eq!(stats.get_download_latency(), None);
+        assert_eq!(stats.get_download_throughput(), 0.0);
+        assert_eq!(stats.get_expected_download_time(1000), None);
+        assert_eq!(stats.get_download_size(), None);
+
+        // a peer that only timed out is expected to take as long as its timeouts
+        stats.add_download(5000, 0);
+        assert_eq!(stats.get_download_throughput(), 0.0);
+        assert_eq!(stats.get_expected_download_time(1000), Some(5000));
+        assert_eq!(stats.get_download_size(), None);
+        stats.download_points.clear();
+
+        stats.add_download(100, 1000);
+        stats.add_download(300, 3000);
+        assert_eq!(stats.get_download_latency(), Some(200));
+        assert_eq!(stats.get_download_throughput(), 10000.0);
+        assert_eq!(stats.get_download_size(), Some(2000));
+
+        // big downloads take longer than the mean latency
+        assert_eq!(stats.get_expected_download_time(2000), Some(200));
+        assert_eq!(stats.get_expected_download_time(20000), Some(2000));
+
+        // only the most recent points count
+        for _ in 0..NUM_DOWNLOAD_POINTS {
+            stats.add_download(50, 500);
+        }
+        assert_eq!(stats.download_points.len(), NUM_DOWNLOAD_POINTS);
+        assert_eq!(stats.get_download_latency(), Some(50));
+
+        // stale points don't count
+        for point in stats.download_points.iter_mut() {
+            point.time -= DOWNLOAD_POINT_LIFETIME;
+        }
+        assert_eq!(stats.get_download_latency(), None);
+        assert_eq!(stats.get_expected_download_time(1000), None);
+        assert_eq!(stats.get_download_size(), None);
+    }
 }
 
 // TODO: test bandwidth limits
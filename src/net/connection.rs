@@ -34,6 +34,7 @@ use std::sync::mpsc::TrySendError;
 use mio;
 use mio::net as mio_net;
 
+use net::audit::AuditCallerPrivacy;
 use net::codec::*;
 use net::Error as net_error;
 use net::HttpRequestPreamble;
@@ -46,6 +47,7 @@ use net::RelayData;
 use net::StacksHttp;
 use net::StacksMessageCodec;
 use net::StacksP2P;
+use net::DENY_BAN_DURATION;
 use net::MAX_MESSAGE_LEN;
 
 use net::download::BLOCK_DOWNLOAD_INTERVAL;
@@ -330,6 +332,7 @@ pub struct ConnectionOptions {
     pub soft_num_clients: u64,
     pub max_neighbors_per_host: u64,
     pub max_clients_per_host: u64,
+    pub max_clients_per_subnet: u64,
     pub soft_max_neighbors_per_host: u64,
     pub soft_max_neighbors_per_org: u64,
     pub soft_max_clients_per_host: u64,
@@ -365,6 +368,57 @@ pub struct ConnectionOptions {
     pub max_buffered_microblocks_available: u64,
     pub max_buffered_blocks: u64,
     pub max_buffered_microblocks: u64,
+    pub rpc_audit_log_path: Option<String>,
+    pub rpc_audit_log_caller_privacy: AuditCallerPrivacy,
+    pub rpc_audit_log_retention: u64,
+    /// require RPC requests to carry a registered API key
+    pub require_rpc_api_keys: bool,
+    /// ban a peer once its misbehavior score (see net::reputation) reaches this
+    pub ban_score_threshold: u64,
+    /// how long such a ban lasts, in seconds
+    pub ban_duration: u64,
+    /// ask the router to forward our p2p port with NAT-PMP or UPnP (see net::portmap)
+    pub enable_port_mapping: bool,
+    /// how long each port mapping lasts before we renew it, in seconds
+    pub port_mapping_lifetime: u64,
+    /// serve WebSocket event subscriptions at /v2/events (see net::websocket)
+    pub enable_websocket_events: bool,
+    /// how many WebSocket subscribers we serve at once
+    pub max_websocket_clients: u64,
+    /// how many requests an HTTP client may make on one connection before we close it (0 for no
+    /// limit)
+    pub max_http_requests_per_connection: u64,
+    /// how many read-only function calls per second an HTTP connection may make, once its burst
+    /// is spent (0 for no limit)
+    pub read_only_call_rate: u64,
+    /// how many read-only function calls an HTTP connection may make at once
+    pub read_only_call_burst: u64,
+    /// PEM certificate chain and private key to serve the RPC interface over TLS with (see
+    /// net::tls)
+    pub rpc_tls_cert_path: Option<String>,
+    pub rpc_tls_key_path: Option<String>,
+    /// PEM certificates of the CAs whose client certificates authenticate a TLS client
+    pub rpc_tls_client_ca_path: Option<String>,
+    /// bearer token that authorizes requests to the admin endpoints (see net::admin)
+    pub rpc_admin_token: Option<String>,
+    /// origins that browsers may call the RPC interface from.  "*" allows any origin, and an
+    /// empty list sends no CORS headers.
+    pub rpc_cors_allowed_origins: Vec<String>,
+    /// how long browsers may cache a CORS preflight response, in seconds (0 to not say)
+    pub rpc_cors_max_age: u64,
+    /// how long clients may cache responses that depend on the chain tip, in seconds (0 for
+    /// no-cache)
+    pub rpc_cache_max_age: u64,
+    /// how long clients may cache responses that never change, like a block fetched by its
+    /// hash, in seconds (0 for no-cache)
+    pub rpc_immutable_cache_max_age: u64,
+    /// extra headers to send with every RPC response, as (name, value) pairs
+    pub rpc_response_headers: Vec<(String, String)>,
+    /// compress blocks, microblocks, contract interfaces, and contract source sent to RPC
+    /// clients that accept gzip or deflate
+    pub rpc_compression: bool,
+    /// response bodies smaller than this many bytes are sent uncompressed
+    pub rpc_compression_min_size: u64,
 
     // fault injection
     pub disable_neighbor_walk: bool,
@@ -399,6 +453,7 @@ impl std::default::Default for ConnectionOptions {
             soft_num_clients: 128, // how many inbound connections we can have, before we start pruning them
             max_neighbors_per_host: 10, // how many outbound connections we can have per IP address, full-stop
             max_clients_per_host: 10, // how many inbound connections we can have per IP address, full-stop
+            max_clients_per_subnet: 20, // how many inbound connections we can have per /24 (IPv4) or /48 (IPv6), full-stop
             soft_max_neighbors_per_host: 10, // how many outbound connections we can have per IP address, before we start pruning them
             soft_max_neighbors_per_org: 10, // how many outbound connections we can have per AS-owning organization, before we start pruning them
             soft_max_clients_per_host: 10, // how many inbound connections we can have per IP address, before we start pruning them,
@@ -440,6 +495,30 @@ impl std::default::Default for ConnectionOptions {
             max_buffered_microblocks_available: 1,
             max_buffered_blocks: 1,
             max_buffered_microblocks: 10,
+            rpc_audit_log_path: None, // no RPC audit log by default
+            rpc_audit_log_caller_privacy: AuditCallerPrivacy::Anonymized,
+            rpc_audit_log_retention: 3600 * 24 * 7, // keep a week of RPC audit records
+            require_rpc_api_keys: false,            // the RPC interface is open by default
+            ban_score_threshold: 100, // ban a peer once its misbehavior score reaches this
+            ban_duration: DENY_BAN_DURATION, // how long a peer is banned for, once its score is too high
+            enable_port_mapping: false,      // don't touch the router unless asked to
+            port_mapping_lifetime: 3600,     // renew port mappings every half hour
+            enable_websocket_events: false,  // no WebSocket event stream unless asked for
+            max_websocket_clients: 64,
+            max_http_requests_per_connection: 1000, // close a keep-alive connection after this many requests
+            read_only_call_rate: 10, // read-only calls per second per connection, once the burst is spent
+            read_only_call_burst: 100,
+            rpc_tls_cert_path: None, // plaintext RPC by default
+            rpc_tls_key_path: None,
+            rpc_tls_client_ca_path: None,
+            rpc_admin_token: None, // admin endpoints off unless configured
+            rpc_cors_allowed_origins: vec!["*".to_string()],
+            rpc_cors_max_age: 600,
+            rpc_cache_max_age: 0,
+            rpc_immutable_cache_max_age: 365 * 24 * 3600,
+            rpc_response_headers: vec![],
+            rpc_compression: true,
+            rpc_compression_min_size: 1024,
 
             // no faults on by default
             disable_neighbor_walk: false,
@@ -464,9 +464,15 @@ impl PeerDB {
         }
     }
 
+    /// Clear CIDR denies and expired bans.  Bans that are still in effect are kept, so that they
+    /// survive a restart.
     fn reset_denies<'a>(tx: &mut Transaction<'a>) -> Result<(), db_error> {
-        tx.execute("UPDATE frontier SET denied = 0", NO_PARAMS)
-            .map_err(db_error::SqliteError)?;
+        let args: &[&dyn ToSql] = &[&i64::max_value(), &u64_to_sql(util::get_epoch_time_secs())?];
+        tx.execute(
+            "UPDATE frontier SET denied = 0 WHERE denied = ?1 OR denied < ?2",
+            args,
+        )
+        .map_err(db_error::SqliteError)?;
         Ok(())
     }
 
@@ -2139,5 +2145,31 @@ mod test {
 
         assert_eq!(n1.allowed, -1);
         assert_eq!(n2.allowed, -1);
+
+        // a ban that is still in effect survives a refresh
+        let ban_deadline = util::get_epoch_time_secs() + 3600;
+        {
+            let mut tx = db.tx_begin().unwrap();
+            PeerDB::set_deny_peer(
+                &mut tx,
+                neighbor_2.addr.network_id,
+                &neighbor_2.addr.addrbytes,
+                neighbor_2.addr.port,
+                ban_deadline,
+            )
+            .unwrap();
+            PeerDB::refresh_denies(&mut tx).unwrap();
+            tx.commit().unwrap();
+        }
+
+        let n2 = PeerDB::get_peer(
+            db.conn(),
+            neighbor_2.addr.network_id,
+            &neighbor_2.addr.addrbytes,
+            neighbor_2.addr.port,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(n2.denied, ban_deadline as i64);
     }
 }
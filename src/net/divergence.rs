@@ -0,0 +1,368 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Consensus divergence detection.
+//!
+//! Whenever the chainstate rejects an anchored block, it saves the block, the microblocks it
+//! confirmed, and the relevant parts of its own state under `divergence/rejected/` in the
+//! chainstate directory.  The peer network periodically checks these blocks against its
+//! neighbors' block inventories.  If most of the neighbors that know about the block's sortition
+//! claim to have the block, then this node computed a different validity or MARF root for it
+//! than the rest of the network did, and it will never make progress past it on its own.
+//!
+//! When that happens, the saved block is moved to `divergence/bundles/` along with the list of
+//! neighbors that have it, and the node logs the divergence and reports the block in
+//! `GET /v2/info` until the operator removes the bundle.  Rejected blocks that the network does
+//! not appear to have are forgotten after `REJECTED_BLOCK_LIFETIME` seconds.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde_json;
+
+use chainstate::burn::{BlockHeaderHash, ConsensusHash};
+use chainstate::stacks::index::TrieHash;
+use chainstate::stacks::StacksBlockId;
+
+use net::inv::{InvState, NodeStatus};
+use net::NeighborKey;
+
+/// How often to check rejected blocks against our neighbors' inventories, in seconds
+pub const DIVERGENCE_CHECK_INTERVAL: u64 = 60;
+
+/// How long to remember a rejected block that the network does not appear to have, in seconds
+pub const REJECTED_BLOCK_LIFETIME: u64 = 86400;
+
+/// Minimum number of neighbors whose inventories must cover a block's sortition before we
+/// decide whether or not the network accepted it
+pub const DIVERGENCE_MIN_NEIGHBORS: u64 = 3;
+
+const RECORD_FILE: &'static str = "record.json";
+const BLOCK_FILE: &'static str = "block.bin";
+const MICROBLOCKS_FILE: &'static str = "microblocks.bin";
+const PEERS_FILE: &'static str = "peers.json";
+
+/// What this node knew about a block when it rejected it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RejectedBlockRecord {
+    pub consensus_hash: ConsensusHash,
+    pub block_hash: BlockHeaderHash,
+    pub index_block_hash: StacksBlockId,
+    /// height of the burnchain block whose sortition selected this block
+    pub burn_block_height: u64,
+    pub stacks_block_height: u64,
+    pub parent_index_block_hash: StacksBlockId,
+    /// MARF root of the parent block's state, as computed by this node
+    pub parent_state_index_root: TrieHash,
+    /// MARF root of this block's state, as claimed by its miner
+    pub state_index_root: TrieHash,
+    /// number of parent microblocks the block confirmed
+    pub num_microblocks: u64,
+    pub commit_burn: u64,
+    pub sortition_burn: u64,
+    /// why this node rejected the block
+    pub reason: String,
+    /// when this node rejected the block, in seconds since the epoch
+    pub rejected_at: u64,
+}
+
+fn rejected_blocks_path(root_path: &str) -> PathBuf {
+    Path::new(root_path).join("divergence").join("rejected")
+}
+
+fn bundles_path(root_path: &str) -> PathBuf {
+    Path::new(root_path).join("divergence").join("bundles")
+}
+
+fn json_error(e: serde_json::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Save a block the chainstate is about to reject.  `block_bytes` and `microblocks_bytes` are
+/// the consensus serializations of the anchored block and the microblocks it confirmed.
+pub fn save_rejected_block(
+    root_path: &str,
+    record: &RejectedBlockRecord,
+    block_bytes: &[u8],
+    microblocks_bytes: &[u8],
+) -> io::Result<()> {
+    let dir = rejected_blocks_path(root_path).join(record.index_block_hash.to_hex());
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join(BLOCK_FILE), block_bytes)?;
+    fs::write(dir.join(MICROBLOCKS_FILE), microblocks_bytes)?;
+
+    // write the record last, so a half-written directory is never loaded
+    let record_bytes = serde_json::to_vec_pretty(record).map_err(json_error)?;
+    fs::write(dir.join(RECORD_FILE), record_bytes)
+}
+
+fn load_records(dir: &Path) -> io::Result<Vec<(PathBuf, RejectedBlockRecord)>> {
+    let mut ret = vec![];
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+            return Ok(ret);
+        }
+        Err(e) => {
+            return Err(e);
+        }
+    };
+    for entry in entries {
+        let path = entry?.path();
+        let record_bytes = match fs::read(path.join(RECORD_FILE)) {
+            Ok(bytes) => bytes,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                continue;
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        };
+        match serde_json::from_slice::<RejectedBlockRecord>(&record_bytes) {
+            Ok(record) => ret.push((path, record)),
+            Err(e) => {
+                warn!("Unreadable rejected block record in {:?}: {:?}", &path, &e);
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// Load all saved rejected blocks that have not yet been found to be divergent
+pub fn load_rejected_blocks(root_path: &str) -> io::Result<Vec<RejectedBlockRecord>> {
+    Ok(load_records(&rejected_blocks_path(root_path))?
+        .into_iter()
+        .map(|(_, record)| record)
+        .collect())
+}
+
+/// Load all the blocks for which a diagnostic bundle has been written
+pub fn load_divergent_blocks(root_path: &str) -> io::Result<Vec<RejectedBlockRecord>> {
+    Ok(load_records(&bundles_path(root_path))?
+        .into_iter()
+        .map(|(_, record)| record)
+        .collect())
+}
+
+/// Find the online neighbors whose inventories say they have the block chosen by the sortition
+/// at `burn_block_height`.  Also returns how many online neighbors' inventories cover that
+/// sortition at all.
+pub fn find_inventory_claims(
+    inv_state: &InvState,
+    burn_block_height: u64,
+) -> (Vec<NeighborKey>, u64) {
+    let mut claims = vec![];
+    let mut num_known = 0;
+    for (nk, stats) in inv_state.block_stats.iter() {
+        if stats.status != NodeStatus::Online {
+            continue;
+        }
+        if stats.inv.first_block_height + stats.inv.num_sortitions <= burn_block_height {
+            // this neighbor hasn't told us about this sortition yet
+            continue;
+        }
+        num_known += 1;
+        if stats.inv.has_ith_block(burn_block_height) {
+            claims.push(nk.clone());
+        }
+    }
+    (claims, num_known)
+}
+
+/// Does a majority of the neighbors that know about a sortition claim a block we rejected?
+pub fn is_divergent(num_claims: u64, num_known: u64) -> bool {
+    num_known >= DIVERGENCE_MIN_NEIGHBORS && 2 * num_claims > num_known
+}
+
+/// Check each saved rejected block against our neighbors' inventories.  Blocks that most of our
+/// neighbors have are turned into diagnostic bundles, and are returned.  Blocks older than
+/// `REJECTED_BLOCK_LIFETIME` that the network does not have are deleted.
+pub fn check_rejected_blocks(
+    root_path: &str,
+    inv_state: &InvState,
+    now: u64,
+) -> io::Result<Vec<RejectedBlockRecord>> {
+    let mut divergent = vec![];
+    for (path, record) in load_records(&rejected_blocks_path(root_path))?.into_iter() {
+        let (claims, num_known) = find_inventory_claims(inv_state, record.burn_block_height);
+        if is_divergent(claims.len() as u64, num_known) {
+            let peers: Vec<String> = claims.iter().map(|nk| nk.to_string()).collect();
+            let peers_bytes = serde_json::to_vec_pretty(&peers).map_err(json_error)?;
+            fs::write(path.join(PEERS_FILE), peers_bytes)?;
+
+            let bundle_path = bundles_path(root_path).join(record.index_block_hash.to_hex());
+            fs::create_dir_all(bundles_path(root_path))?;
+            if fs::metadata(&bundle_path).is_ok() {
+                fs::remove_dir_all(&bundle_path)?;
+            }
+            fs::rename(&path, &bundle_path)?;
+
+            error!(
+                "CONSENSUS DIVERGENCE: {} of {} neighbors accepted block {}/{} ({}), which this node rejected: {}.  Diagnostic bundle written to {:?}",
+                claims.len(),
+                num_known,
+                &record.consensus_hash,
+                &record.block_hash,
+                &record.index_block_hash,
+                &record.reason,
+                &bundle_path
+            );
+            divergent.push(record);
+        } else if record.rejected_at + REJECTED_BLOCK_LIFETIME < now {
+            debug!(
+                "Forget rejected block {}/{}: {} of {} neighbors have it",
+                &record.consensus_hash,
+                &record.block_hash,
+                claims.len(),
+                num_known
+            );
+            fs::remove_dir_all(&path)?;
+        }
+    }
+    Ok(divergent)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use net::inv::NeighborBlockStats;
+    use net::PeerAddress;
+
+    use std::collections::{HashMap, HashSet};
+
+    fn make_record(
+        index_byte: u8,
+        burn_block_height: u64,
+        rejected_at: u64,
+    ) -> RejectedBlockRecord {
+        RejectedBlockRecord {
+            consensus_hash: ConsensusHash([index_byte; 20]),
+            block_hash: BlockHeaderHash([index_byte; 32]),
+            index_block_hash: StacksBlockId([index_byte; 32]),
+            burn_block_height,
+            stacks_block_height: 1,
+            parent_index_block_hash: StacksBlockId([0x00; 32]),
+            parent_state_index_root: TrieHash([0x01; 32]),
+            state_index_root: TrieHash([0x02; 32]),
+            num_microblocks: 0,
+            commit_burn: 100,
+            sortition_burn: 100,
+            reason: "state root mismatch".to_string(),
+            rejected_at,
+        }
+    }
+
+    /// Make an inventory state with one neighbor per entry of `invs`, whose inventory covers
+    /// `num_sortitions` sortitions starting at burn height 100.
+    fn make_inv_state(invs: &[(u8, u64)]) -> InvState {
+        let mut block_stats = HashMap::new();
+        for (i, (block_bits, num_sortitions)) in invs.iter().enumerate() {
+            let nk = NeighborKey {
+                peer_version: 0x18000000,
+                network_id: 0x80000000,
+                addrbytes: PeerAddress([i as u8; 16]),
+                port: 20444,
+            };
+            let mut stats = NeighborBlockStats::new(nk.clone(), 100);
+            stats.inv.block_inv = vec![*block_bits];
+            stats.inv.microblocks_inv = vec![0];
+            stats.inv.num_sortitions = *num_sortitions;
+            block_stats.insert(nk, stats);
+        }
+        let mut inv_state = InvState::new(100, 60, 60, HashSet::new());
+        inv_state.block_stats = block_stats;
+        inv_state
+    }
+
+    #[test]
+    fn test_is_divergent() {
+        assert!(!is_divergent(0, 0));
+        assert!(!is_divergent(2, 2));
+        assert!(is_divergent(2, 3));
+        assert!(!is_divergent(2, 4));
+        assert!(is_divergent(3, 4));
+    }
+
+    #[test]
+    fn test_find_inventory_claims() {
+        // bit 2 is burn height 102
+        let inv_state = make_inv_state(&[(0x04, 8), (0x04, 8), (0x00, 8), (0x04, 2)]);
+
+        let (claims, num_known) = find_inventory_claims(&inv_state, 102);
+        assert_eq!(claims.len(), 2);
+        assert_eq!(num_known, 3);
+
+        let (claims, num_known) = find_inventory_claims(&inv_state, 101);
+        assert_eq!(claims.len(), 0);
+        assert_eq!(num_known, 4);
+
+        // before the first sortition
+        let (claims, num_known) = find_inventory_claims(&inv_state, 99);
+        assert_eq!(claims.len(), 0);
+        assert_eq!(num_known, 4);
+    }
+
+    #[test]
+    fn test_check_rejected_blocks() {
+        let root_path = "/tmp/test_check_rejected_blocks";
+        if fs::metadata(root_path).is_ok() {
+            fs::remove_dir_all(root_path).unwrap();
+        }
+
+        let accepted = make_record(0x01, 102, 1000);
+        let unknown = make_record(0x02, 103, 1000);
+        let expired = make_record(0x03, 101, 10);
+        for record in [&accepted, &unknown, &expired].iter() {
+            save_rejected_block(root_path, record, &[0x01, 0x02, 0x03], &[]).unwrap();
+        }
+        assert_eq!(load_rejected_blocks(root_path).unwrap().len(), 3);
+        assert_eq!(load_divergent_blocks(root_path).unwrap().len(), 0);
+
+        // three of four neighbors have the block at height 102, and none have the others
+        let inv_state = make_inv_state(&[(0x04, 8), (0x04, 8), (0x04, 8), (0x00, 8)]);
+        let divergent =
+            check_rejected_blocks(root_path, &inv_state, 10 + REJECTED_BLOCK_LIFETIME + 1).unwrap();
+        assert_eq!(divergent, vec![accepted.clone()]);
+
+        // the accepted block became a bundle, with the block and the neighbors that have it
+        assert_eq!(
+            load_divergent_blocks(root_path).unwrap(),
+            vec![accepted.clone()]
+        );
+        let bundle_path = bundles_path(root_path).join(accepted.index_block_hash.to_hex());
+        assert_eq!(
+            fs::read(bundle_path.join(BLOCK_FILE)).unwrap(),
+            vec![0x01, 0x02, 0x03]
+        );
+        let peers: Vec<String> =
+            serde_json::from_slice(&fs::read(bundle_path.join(PEERS_FILE)).unwrap()).unwrap();
+        assert_eq!(peers.len(), 3);
+
+        // the expired block was forgotten, but the recent one is kept
+        assert_eq!(
+            load_rejected_blocks(root_path).unwrap(),
+            vec![unknown.clone()]
+        );
+
+        // nothing new on the next pass
+        let divergent =
+            check_rejected_blocks(root_path, &inv_state, 10 + REJECTED_BLOCK_LIFETIME + 1).unwrap();
+        assert_eq!(divergent.len(), 0);
+        assert_eq!(load_divergent_blocks(root_path).unwrap().len(), 1);
+    }
+}
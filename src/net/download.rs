@@ -52,6 +52,7 @@ use net::server::HttpPeer;
 use net::db::*;
 
 use net::p2p::PeerNetwork;
+use net::reputation::Misbehavior;
 
 use util::db::DBConn;
 use util::db::Error as db_error;
@@ -79,8 +80,10 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::VecDeque;
 
+use std::cmp;
 use std::io::Read;
 use std::io::Write;
+use std::mem;
 
 use std::convert::TryFrom;
 
@@ -115,6 +118,30 @@ pub const BLOCK_REREQUEST_INTERVAL: u64 = 60;
 #[cfg(test)]
 pub const BLOCK_REREQUEST_INTERVAL: u64 = 30;
 
+/// If a request for a block or microblock stream has been outstanding for this many milliseconds,
+/// then send the same request to the next-fastest peer that has the data, and use whichever
+/// reply arrives first.
+pub const BLOCK_DOWNLOAD_HEDGE_MS: u128 = 5000;
+
+/// Latency to assume for a peer we haven't downloaded anything from yet, when deciding which
+/// peers to ask first.
+pub const BLOCK_DOWNLOAD_DEFAULT_LATENCY_MS: u64 = 1000;
+
+/// Size to plan for when deciding which peers to ask first, if none of our recent downloads
+/// carried any data.
+pub const BLOCK_DOWNLOAD_DEFAULT_SIZE: u64 = 100_000;
+
+/// If a request for a block or microblock stream has been outstanding for this many milliseconds,
+/// then give up on it, back off from the peer's data URL, and ask the next peer in line.
+#[cfg(not(test))]
+pub const BLOCK_DOWNLOAD_REQUEST_TIMEOUT_MS: u128 = 30000;
+#[cfg(test)]
+pub const BLOCK_DOWNLOAD_REQUEST_TIMEOUT_MS: u128 = 15000;
+
+/// Each consecutive failure of a data URL doubles how long it is blocked for, up to
+/// BLOCK_DOWNLOAD_BAN_URL * 2^BLOCK_DOWNLOAD_MAX_BACKOFF_EXPONENT seconds.
+pub const BLOCK_DOWNLOAD_MAX_BACKOFF_EXPONENT: u32 = 5;
+
 /// This module is responsible for downloading blocks and microblocks from other peers, using block
 /// inventory state (see src/net/inv.rs)
 
@@ -128,6 +155,7 @@ pub struct BlockRequestKey {
     pub child_block_header: Option<StacksBlockHeader>, // only used if asking for a microblock; used to confirm the stream's continuity
     pub sortition_height: u64,
     pub download_start: u64,
+    pub request_start_ms: u128, // when the request was sent
 }
 
 impl BlockRequestKey {
@@ -149,6 +177,7 @@ impl BlockRequestKey {
             child_block_header: child_block_header,
             sortition_height: sortition_height,
             download_start: get_epoch_time_secs(),
+            request_start_ms: get_epoch_time_ms(),
         }
     }
 }
@@ -214,6 +243,7 @@ pub struct BlockDownloader {
     broken_neighbors: Vec<NeighborKey>, // disconnect peers who report invalid block inventories too
 
     blocked_urls: HashMap<UrlString, u64>, // URLs that chronically don't work, and when we can try them again
+    url_failures: HashMap<UrlString, u32>, // consecutive failures of each URL, to back off from it
 
     /// how often to download
     download_interval: u64,
@@ -221,6 +251,16 @@ pub struct BlockDownloader {
     /// when did we last request a given block hash
     requested_blocks: HashMap<StacksBlockId, u64>,
     requested_microblocks: HashMap<StacksBlockId, u64>,
+
+    /// blocks and microblock streams we've sent a second (hedged) request for
+    hedged_requests: HashSet<StacksBlockId>,
+
+    /// (neighbor, latency in ms, size in bytes) of each completed download, to be added to the
+    /// neighbor's stats
+    download_timings: Vec<(NeighborKey, u64, u64)>,
+
+    /// neighbors whose requests timed out, to be counted against their reputations
+    timed_out_neighbors: Vec<NeighborKey>,
 }
 
 impl BlockDownloader {
@@ -262,10 +302,15 @@ impl BlockDownloader {
             broken_peers: vec![],
             broken_neighbors: vec![],
             blocked_urls: HashMap::new(),
+            url_failures: HashMap::new(),
 
             download_interval: download_interval,
             requested_blocks: HashMap::new(),
             requested_microblocks: HashMap::new(),
+
+            hedged_requests: HashSet::new(),
+            download_timings: vec![],
+            timed_out_neighbors: vec![],
         }
     }
 
@@ -287,6 +332,8 @@ impl BlockDownloader {
         self.broken_peers.clear();
         self.broken_neighbors.clear();
 
+        self.hedged_requests.clear();
+
         // perserve sortition height
         // preserve download accounting
     }
@@ -427,10 +474,7 @@ impl BlockDownloader {
                         self.dead_peers.push(event_id);
 
                         // don't try this again for a while
-                        self.blocked_urls.insert(
-                            block_key.data_url,
-                            get_epoch_time_secs() + BLOCK_DOWNLOAD_BAN_URL,
-                        );
+                        self.block_url(block_key.data_url);
                     }
                 }
                 Some(ref mut convo) => {
@@ -458,6 +502,20 @@ impl BlockDownloader {
                                         &block_key.consensus_hash,
                                         block.block_hash()
                                     );
+                                    self.download_timings.push((
+                                        block_key.neighbor.clone(),
+                                        get_epoch_time_ms()
+                                            .saturating_sub(block_key.request_start_ms)
+                                            as u64,
+                                        block.serialize_to_vec().len() as u64,
+                                    ));
+                                    self.url_failures.remove(&block_key.data_url);
+                                    if self.blocks.keys().any(|key| {
+                                        key.index_block_hash == block_key.index_block_hash
+                                    }) {
+                                        // hedged request; already got it from someone else
+                                        continue;
+                                    }
                                     self.blocks.insert(block_key, block);
                                 }
                             }
@@ -486,6 +544,34 @@ impl BlockDownloader {
             }
         }
 
+        // stop waiting on requests that lost a race with their hedged request
+        let fetched: HashSet<StacksBlockId> = self
+            .blocks
+            .keys()
+            .map(|key| key.index_block_hash.clone())
+            .collect();
+        let mut cancelled = vec![];
+        pending_block_requests.retain(|block_key, event_id| {
+            if fetched.contains(&block_key.index_block_hash) {
+                debug!(
+                    "Cancel request {} to {:?} ({:?}) for block {}: already fetched",
+                    event_id, &block_key.neighbor, &block_key.data_url, &block_key.index_block_hash
+                );
+                cancelled.push(*event_id);
+                false
+            } else {
+                true
+            }
+        });
+        for event_id in cancelled.into_iter() {
+            // the connection may still be carrying another request
+            if !pending_block_requests.values().any(|id| *id == event_id) {
+                self.dead_peers.push(event_id);
+            }
+        }
+
+        self.expire_requests(&mut pending_block_requests, "block");
+
         // are we done?
         if pending_block_requests.len() == 0 {
             self.state = BlockDownloaderState::GetMicroblocksBegin;
@@ -531,10 +617,7 @@ impl BlockDownloader {
                         self.dead_peers.push(event_id);
 
                         // don't try this again for a while
-                        self.blocked_urls.insert(
-                            block_key.data_url,
-                            get_epoch_time_secs() + BLOCK_DOWNLOAD_BAN_URL,
-                        );
+                        self.block_url(block_key.data_url);
                     }
                 }
                 Some(ref mut convo) => {
@@ -560,6 +643,23 @@ impl BlockDownloader {
                                         &block_key.index_block_hash,
                                         microblocks[0].block_hash()
                                     );
+                                    self.download_timings.push((
+                                        block_key.neighbor.clone(),
+                                        get_epoch_time_ms()
+                                            .saturating_sub(block_key.request_start_ms)
+                                            as u64,
+                                        microblocks
+                                            .iter()
+                                            .map(|mblock| mblock.serialize_to_vec().len() as u64)
+                                            .sum(),
+                                    ));
+                                    self.url_failures.remove(&block_key.data_url);
+                                    if self.microblocks.keys().any(|key| {
+                                        key.index_block_hash == block_key.index_block_hash
+                                    }) {
+                                        // hedged request; already got it from someone else
+                                        continue;
+                                    }
                                     self.microblocks.insert(block_key, microblocks);
                                 }
                             }
@@ -586,6 +686,37 @@ impl BlockDownloader {
             }
         }
 
+        // stop waiting on requests that lost a race with their hedged request
+        let fetched: HashSet<StacksBlockId> = self
+            .microblocks
+            .keys()
+            .map(|key| key.index_block_hash.clone())
+            .collect();
+        let mut cancelled = vec![];
+        pending_microblock_requests.retain(|block_key, event_id| {
+            if fetched.contains(&block_key.index_block_hash) {
+                debug!(
+                    "Cancel request {} to {:?} ({:?}) for microblocks built by {}: already fetched",
+                    event_id, &block_key.neighbor, &block_key.data_url, &block_key.index_block_hash
+                );
+                cancelled.push(*event_id);
+                false
+            } else {
+                true
+            }
+        });
+        for event_id in cancelled.into_iter() {
+            // the connection may still be carrying another request
+            if !pending_microblock_requests
+                .values()
+                .any(|id| *id == event_id)
+            {
+                self.dead_peers.push(event_id);
+            }
+        }
+
+        self.expire_requests(&mut pending_microblock_requests, "microblocks built by");
+
         // are we done?
         if pending_microblock_requests.len() == 0 {
             self.state = BlockDownloaderState::Done;
@@ -599,6 +730,110 @@ impl BlockDownloader {
         return Ok(false);
     }
 
+    /// Don't use a data URL again until its backoff expires.  The backoff doubles with each
+    /// consecutive failure, and is reset once the URL serves us something.
+    fn block_url(&mut self, data_url: UrlString) -> () {
+        let failures = self.url_failures.entry(data_url.clone()).or_insert(0);
+        let backoff =
+            BLOCK_DOWNLOAD_BAN_URL << cmp::min(*failures, BLOCK_DOWNLOAD_MAX_BACKOFF_EXPONENT);
+        *failures = failures.saturating_add(1);
+
+        debug!(
+            "Block {:?} for {} seconds ({} consecutive failures)",
+            &data_url, backoff, *failures
+        );
+        self.blocked_urls
+            .insert(data_url, get_epoch_time_secs() + backoff);
+    }
+
+    /// Give up on requests that have been outstanding for longer than
+    /// BLOCK_DOWNLOAD_REQUEST_TIMEOUT_MS.  The peer is charged with a download that took that long
+    /// (so it drops down the list of peers to ask), and its data URL is blocked with backoff.  The
+    /// block or microblock stream stays in blocks_to_try/microblocks_to_try, so the next pass
+    /// will ask the next peer in line for it.
+    fn expire_requests(
+        &mut self,
+        pending_requests: &mut HashMap<BlockRequestKey, usize>,
+        request_name: &str,
+    ) -> () {
+        let now_ms = get_epoch_time_ms();
+        let expired: Vec<BlockRequestKey> = pending_requests
+            .keys()
+            .filter(|key| {
+                now_ms.saturating_sub(key.request_start_ms) > BLOCK_DOWNLOAD_REQUEST_TIMEOUT_MS
+            })
+            .cloned()
+            .collect();
+
+        for key in expired.into_iter() {
+            let event_id = pending_requests
+                .remove(&key)
+                .expect("BUG: expired request is not pending");
+
+            info!(
+                "Request {} to {:?} ({:?}) for {} {} timed out after {}ms",
+                event_id,
+                &key.neighbor,
+                &key.data_url,
+                request_name,
+                &key.index_block_hash,
+                BLOCK_DOWNLOAD_REQUEST_TIMEOUT_MS
+            );
+
+            self.download_timings.push((
+                key.neighbor.clone(),
+                BLOCK_DOWNLOAD_REQUEST_TIMEOUT_MS as u64,
+                0,
+            ));
+            self.timed_out_neighbors.push(key.neighbor.clone());
+            self.block_url(key.data_url);
+
+            // the connection may still be carrying another request
+            if !pending_requests.values().any(|id| *id == event_id) {
+                self.dead_peers.push(event_id);
+            }
+        }
+    }
+
+    /// Order the request keys for a block by when we expect each peer could finish serving it:
+    /// how long it would take to download it at its recent throughput, times one more than the
+    /// number of requests we have already sent to its data URL in this pass.  This spreads a
+    /// pass's requests across all the peers that can serve them, while still giving fast peers
+    /// more of the work.
+    /// The sort is stable, so peers with the same expected finish time keep their order.
+    fn schedule_request_keys<F>(
+        request_keys: &mut VecDeque<BlockRequestKey>,
+        url_load: &HashMap<UrlString, u64>,
+        get_download_time: F,
+    ) -> ()
+    where
+        F: Fn(&NeighborKey) -> u64,
+    {
+        let mut keys: Vec<BlockRequestKey> = request_keys.drain(..).collect();
+        keys.sort_by_key(|key| {
+            let load = url_load.get(&key.data_url).cloned().unwrap_or(0);
+            get_download_time(&key.neighbor).saturating_mul(load + 1)
+        });
+        request_keys.extend(keys.into_iter());
+    }
+
+    /// Find the in-flight requests that have been outstanding for longer than
+    /// BLOCK_DOWNLOAD_HEDGE_MS, and whose block or microblock stream has not been hedged yet.
+    fn find_slow_requests(
+        inflight: &HashMap<BlockRequestKey, usize>,
+        hedged_requests: &HashSet<StacksBlockId>,
+        now_ms: u128,
+    ) -> Vec<BlockRequestKey> {
+        inflight
+            .keys()
+            .filter(|key| {
+                now_ms.saturating_sub(key.request_start_ms) > BLOCK_DOWNLOAD_HEDGE_MS
+                    && !hedged_requests.contains(&key.index_block_hash)
+            })
+            .cloned()
+            .collect()
+    }
+
     /// Get the availability of each block in the given sortition range, using the inv state.
     /// Return the local block headers, paired with the list of peers that can serve them.
     /// Possibly less than the given range request.
@@ -938,6 +1173,62 @@ impl PeerNetwork {
         }
     }
 
+    /// Estimate how long a neighbor would take to serve a download of `num_bytes` bytes, in
+    /// milliseconds, from the throughput of our recent downloads from it.  Falls back to
+    /// BLOCK_DOWNLOAD_DEFAULT_LATENCY_MS if we haven't downloaded anything from it recently.
+    fn get_expected_download_time(&self, neighbor_key: &NeighborKey, num_bytes: u64) -> u64 {
+        let expected_time = match self.events.get(neighbor_key) {
+            Some(ref event_id) => match self.peers.get(event_id) {
+                Some(ref convo) => convo.stats.get_expected_download_time(num_bytes),
+                None => None,
+            },
+            None => None,
+        };
+        expected_time.unwrap_or(BLOCK_DOWNLOAD_DEFAULT_LATENCY_MS)
+    }
+
+    /// Get the mean size of our recent downloads from all neighbors, in bytes -- what we expect
+    /// the next block or microblock stream to weigh.
+    fn get_expected_download_size(&self) -> u64 {
+        let sizes: Vec<u64> = self
+            .peers
+            .values()
+            .filter_map(|convo| convo.stats.get_download_size())
+            .collect();
+        if sizes.len() == 0 {
+            BLOCK_DOWNLOAD_DEFAULT_SIZE
+        } else {
+            sizes.iter().sum::<u64>() / (sizes.len() as u64)
+        }
+    }
+
+    /// Add the downloader's completed download timings to each neighbor's stats, and count
+    /// timed-out requests against the neighbors' reputations
+    fn update_download_stats(&mut self) -> () {
+        let (timings, timed_out) = match self.block_downloader {
+            Some(ref mut downloader) => (
+                mem::replace(&mut downloader.download_timings, vec![]),
+                mem::replace(&mut downloader.timed_out_neighbors, vec![]),
+            ),
+            None => (vec![], vec![]),
+        };
+        for neighbor_key in timed_out.into_iter() {
+            if let Err(e) = self.penalize_neighbor(&neighbor_key, Misbehavior::Timeout) {
+                warn!(
+                    "{:?}: Failed to penalize {:?}: {:?}",
+                    &self.local_peer, &neighbor_key, &e
+                );
+            }
+        }
+        for (neighbor_key, latency_ms, num_bytes) in timings.into_iter() {
+            if let Some(event_id) = self.events.get(&neighbor_key) {
+                if let Some(convo) = self.peers.get_mut(event_id) {
+                    convo.stats.add_download(latency_ms, num_bytes);
+                }
+            }
+        }
+    }
+
     /// Do we need to download an anchored block?
     /// already have an anchored block?
     fn need_anchored_block(
@@ -1224,6 +1515,10 @@ impl PeerNetwork {
             let block_urls: HashSet<UrlString> = HashSet::new();
             (&mut neighbors[..]).shuffle(&mut thread_rng());
 
+            // ask the fastest peers first (the sort is stable, so ties stay shuffled)
+            let expected_size = self.get_expected_download_size();
+            neighbors.sort_by_key(|nk| self.get_expected_download_time(nk, expected_size));
+
             let mut requests = VecDeque::new();
             for nk in neighbors.drain(..) {
                 let data_url = match self.get_data_url(&nk) {
@@ -1656,7 +1951,8 @@ impl PeerNetwork {
         })
     }
 
-    fn connect_or_send_http_request(
+    /// Connect to a peer's data URL if we are not connected already, and send it an HTTP request
+    pub fn connect_or_send_http_request(
         &mut self,
         data_url: UrlString,
         addr: SocketAddr,
@@ -1699,6 +1995,7 @@ impl PeerNetwork {
     fn begin_request<F>(
         network: &mut PeerNetwork,
         dns_lookups: &HashMap<UrlString, Option<Vec<SocketAddr>>>,
+        blocked_urls: &HashMap<UrlString, u64>,
         request_name: &str,
         request_keys: &mut VecDeque<BlockRequestKey>,
         chainstate: &mut StacksChainState,
@@ -1709,7 +2006,20 @@ impl PeerNetwork {
     {
         loop {
             match request_keys.pop_front() {
-                Some(key) => {
+                Some(mut key) => {
+                    if let Some(deadline) = blocked_urls.get(&key.data_url) {
+                        if get_epoch_time_secs() < *deadline {
+                            debug!(
+                                "{:?}: Will not request {} {:?} from {:?} until after {}",
+                                &network.local_peer,
+                                request_name,
+                                &key.index_block_hash,
+                                &key.data_url,
+                                deadline
+                            );
+                            continue;
+                        }
+                    }
                     if let Some(Some(ref sockaddrs)) = dns_lookups.get(&key.data_url) {
                         assert!(sockaddrs.len() > 0);
 
@@ -1731,6 +2041,7 @@ impl PeerNetwork {
                                 chainstate,
                             ) {
                                 Ok(handle) => {
+                                    key.request_start_ms = get_epoch_time_ms();
                                     debug!(
                                         "{:?}: Begin HTTP request for {} {} to {:?} ({:?})",
                                         &network.local_peer,
@@ -1776,12 +2087,18 @@ impl PeerNetwork {
         PeerNetwork::with_downloader_state(self, |ref mut network, ref mut downloader| {
             let mut priority = PeerNetwork::prioritize_requests(&downloader.blocks_to_try);
             let mut requests = HashMap::new();
+            let mut url_load = HashMap::new();
+            let expected_size = network.get_expected_download_size();
             for sortition_height in priority.drain(..) {
                 match downloader.blocks_to_try.get_mut(&sortition_height) {
                     Some(ref mut keys) => {
+                        BlockDownloader::schedule_request_keys(keys, &url_load, |nk| {
+                            network.get_expected_download_time(nk, expected_size)
+                        });
                         match PeerNetwork::begin_request(
                             network,
                             &downloader.dns_lookups,
+                            &downloader.blocked_urls,
                             "anchored block",
                             keys,
                             chainstate,
@@ -1793,6 +2110,7 @@ impl PeerNetwork {
                             },
                         ) {
                             Some((key, handle)) => {
+                                *url_load.entry(key.data_url.clone()).or_insert(0) += 1;
                                 requests.insert(key.clone(), handle);
                             }
                             None => {}
@@ -1812,10 +2130,98 @@ impl PeerNetwork {
         })
     }
 
+    /// Send a second request for each block or microblock stream whose request has been
+    /// outstanding for longer than BLOCK_DOWNLOAD_HEDGE_MS, to the next peer in line for it.
+    /// Each block or microblock stream is hedged at most once per download pass.
+    fn hedge_requests(
+        network: &mut PeerNetwork,
+        downloader: &mut BlockDownloader,
+        chainstate: &mut StacksChainState,
+        microblocks: bool,
+    ) -> () {
+        let now_ms = get_epoch_time_ms();
+        let hedged_requests = &mut downloader.hedged_requests;
+        let dns_lookups = &downloader.dns_lookups;
+        let blocked_urls = &downloader.blocked_urls;
+        let (inflight, to_try) = if microblocks {
+            (
+                &mut downloader.getmicroblocks_requests,
+                &mut downloader.microblocks_to_try,
+            )
+        } else {
+            (
+                &mut downloader.getblock_requests,
+                &mut downloader.blocks_to_try,
+            )
+        };
+
+        let slow_requests = BlockDownloader::find_slow_requests(inflight, hedged_requests, now_ms);
+
+        for slow_key in slow_requests.into_iter() {
+            hedged_requests.insert(slow_key.index_block_hash.clone());
+
+            let keys = match to_try.get_mut(&slow_key.sortition_height) {
+                Some(keys) => keys,
+                None => {
+                    continue;
+                }
+            };
+
+            // no sense asking the same server twice
+            keys.retain(|key| key.data_url != slow_key.data_url);
+
+            debug!(
+                "{:?}: Request to {:?} ({:?}) for {} {} is taking longer than {}ms; also asking the next peer",
+                &network.local_peer,
+                &slow_key.neighbor,
+                &slow_key.data_url,
+                if microblocks {
+                    "microblocks built by"
+                } else {
+                    "anchored block"
+                },
+                &slow_key.index_block_hash,
+                BLOCK_DOWNLOAD_HEDGE_MS
+            );
+
+            if let Some((key, handle)) = PeerNetwork::begin_request(
+                network,
+                dns_lookups,
+                blocked_urls,
+                if microblocks {
+                    "microblock stream"
+                } else {
+                    "anchored block"
+                },
+                keys,
+                chainstate,
+                |peerhost, index_block_hash| {
+                    if microblocks {
+                        HttpRequestType::GetMicroblocksConfirmed(
+                            HttpRequestMetadata::from_host(peerhost),
+                            index_block_hash,
+                        )
+                    } else {
+                        HttpRequestType::GetBlock(
+                            HttpRequestMetadata::from_host(peerhost),
+                            index_block_hash,
+                        )
+                    }
+                },
+            ) {
+                inflight.insert(key, handle);
+            }
+        }
+    }
+
     /// Try to see if all blocks are finished downloading
-    pub fn block_getblocks_try_finish(&mut self) -> Result<bool, net_error> {
+    pub fn block_getblocks_try_finish(
+        &mut self,
+        chainstate: &mut StacksChainState,
+    ) -> Result<bool, net_error> {
         test_debug!("{:?}: block_getblocks_try_finish", &self.local_peer);
         PeerNetwork::with_downloader_state(self, |ref mut network, ref mut downloader| {
+            PeerNetwork::hedge_requests(network, downloader, chainstate, false);
             downloader.getblocks_try_finish(&mut network.http)
         })
     }
@@ -1829,12 +2235,18 @@ impl PeerNetwork {
         PeerNetwork::with_downloader_state(self, |ref mut network, ref mut downloader| {
             let mut priority = PeerNetwork::prioritize_requests(&downloader.microblocks_to_try);
             let mut requests = HashMap::new();
+            let mut url_load = HashMap::new();
+            let expected_size = network.get_expected_download_size();
             for sortition_height in priority.drain(..) {
                 match downloader.microblocks_to_try.get_mut(&sortition_height) {
                     Some(ref mut keys) => {
+                        BlockDownloader::schedule_request_keys(keys, &url_load, |nk| {
+                            network.get_expected_download_time(nk, expected_size)
+                        });
                         match PeerNetwork::begin_request(
                             network,
                             &downloader.dns_lookups,
+                            &downloader.blocked_urls,
                             "microblock stream",
                             keys,
                             chainstate,
@@ -1846,6 +2258,7 @@ impl PeerNetwork {
                             },
                         ) {
                             Some((key, handle)) => {
+                                *url_load.entry(key.data_url.clone()).or_insert(0) += 1;
                                 requests.insert(key.clone(), handle);
                             }
                             None => {}
@@ -1866,9 +2279,13 @@ impl PeerNetwork {
     }
 
     /// Try to see if all microblocks are finished downloading
-    pub fn block_getmicroblocks_try_finish(&mut self) -> Result<bool, net_error> {
+    pub fn block_getmicroblocks_try_finish(
+        &mut self,
+        chainstate: &mut StacksChainState,
+    ) -> Result<bool, net_error> {
         test_debug!("{:?}: block_getmicroblocks_try_finish", &self.local_peer);
         PeerNetwork::with_downloader_state(self, |ref mut network, ref mut downloader| {
+            PeerNetwork::hedge_requests(network, downloader, chainstate, true);
             downloader.getmicroblocks_try_finish(&mut network.http)
         })
     }
@@ -1899,8 +2316,16 @@ impl PeerNetwork {
         let now = get_epoch_time_secs();
 
         PeerNetwork::with_downloader_state(self, |ref mut network, ref mut downloader| {
-            // extract blocks and microblocks downloaded
-            for (request_key, block) in downloader.blocks.drain() {
+            // extract blocks and microblocks downloaded.  They arrive in whatever order the peers
+            // answered, so hand them off in sortition order -- that way, a parent block is
+            // stored before its children in the same pass.
+            let mut downloaded_blocks: Vec<_> = downloader.blocks.drain().collect();
+            downloaded_blocks.sort_by_key(|(request_key, _)| request_key.sortition_height);
+
+            let mut downloaded_microblocks: Vec<_> = downloader.microblocks.drain().collect();
+            downloaded_microblocks.sort_by_key(|(request_key, _)| request_key.sortition_height);
+
+            for (request_key, block) in downloaded_blocks.into_iter() {
                 debug!(
                     "Downloaded block {}/{} ({}) at sortition height {}",
                     &request_key.consensus_hash,
@@ -1920,7 +2345,7 @@ impl PeerNetwork {
                     .blocks_to_try
                     .remove(&request_key.sortition_height);
             }
-            for (request_key, microblock_stream) in downloader.microblocks.drain() {
+            for (request_key, microblock_stream) in downloaded_microblocks.into_iter() {
                 let block_header = StacksChainState::load_block_header(
                     &chainstate.blocks_path,
                     &request_key.consensus_hash,
@@ -2215,13 +2640,13 @@ impl PeerNetwork {
                     self.block_getblocks_begin(chainstate)?;
                 }
                 BlockDownloaderState::GetBlocksFinish => {
-                    self.block_getblocks_try_finish()?;
+                    self.block_getblocks_try_finish(chainstate)?;
                 }
                 BlockDownloaderState::GetMicroblocksBegin => {
                     self.block_getmicroblocks_begin(chainstate)?;
                 }
                 BlockDownloaderState::GetMicroblocksFinish => {
-                    self.block_getmicroblocks_try_finish()?;
+                    self.block_getmicroblocks_try_finish(chainstate)?;
                 }
                 BlockDownloaderState::Done => {
                     // did a pass.
@@ -2250,6 +2675,8 @@ impl PeerNetwork {
             }
         }
 
+        self.update_download_stats();
+
         // remove dead/broken peers
         let (broken_http_peers, broken_p2p_peers) = match self.block_downloader {
             Some(ref mut downloader) => downloader.clear_broken_peers(),
@@ -2287,6 +2714,7 @@ pub mod test {
     use net::test::*;
     use net::*;
     use std::collections::HashMap;
+    use std::collections::HashSet;
     use util::sleep_ms;
     use util::test::*;
 
@@ -2466,6 +2894,147 @@ pub mod test {
         })
     }
 
+    fn make_test_request_key(port: u16, sortition_height: u64) -> BlockRequestKey {
+        BlockRequestKey::new(
+            NeighborKey {
+                peer_version: 0x18000000,
+                network_id: 0x80000000,
+                addrbytes: PeerAddress([0u8; 16]),
+                port: port,
+            },
+            UrlString::try_from(format!("http://127.0.0.1:{}", port).as_str()).unwrap(),
+            ConsensusHash([sortition_height as u8; 20]),
+            BlockHeaderHash([sortition_height as u8; 32]),
+            StacksBlockId([sortition_height as u8; 32]),
+            None,
+            sortition_height,
+        )
+    }
+
+    #[test]
+    fn test_schedule_request_keys() {
+        let latency = |nk: &NeighborKey| match nk.port {
+            1 => 100,
+            2 => 300,
+            _ => 1000,
+        };
+        let mut url_load = HashMap::new();
+        let mut keys: VecDeque<BlockRequestKey> = vec![3, 2, 1]
+            .into_iter()
+            .map(|port| make_test_request_key(port, 1))
+            .collect();
+
+        // fastest first when nothing is in-flight
+        BlockDownloader::schedule_request_keys(&mut keys, &url_load, latency);
+        let ports: Vec<u16> = keys.iter().map(|k| k.neighbor.port).collect();
+        assert_eq!(ports, vec![1, 2, 3]);
+
+        // 100ms * 3 == 300ms * 1, and the sort is stable
+        url_load.insert(keys[0].data_url.clone(), 2);
+        BlockDownloader::schedule_request_keys(&mut keys, &url_load, latency);
+        let ports: Vec<u16> = keys.iter().map(|k| k.neighbor.port).collect();
+        assert_eq!(ports, vec![1, 2, 3]);
+
+        // the fast peer is busy enough that the slower ones will finish sooner
+        url_load.insert(keys[0].data_url.clone(), 10);
+        BlockDownloader::schedule_request_keys(&mut keys, &url_load, latency);
+        let ports: Vec<u16> = keys.iter().map(|k| k.neighbor.port).collect();
+        assert_eq!(ports, vec![2, 3, 1]);
+    }
+
+    #[test]
+    fn test_block_url_backoff() {
+        let mut downloader = BlockDownloader::new(1000, 0, 10);
+        let key = make_test_request_key(1, 1);
+
+        let mut last_deadline = 0;
+        for i in 0..(BLOCK_DOWNLOAD_MAX_BACKOFF_EXPONENT + 3) {
+            let now = get_epoch_time_secs();
+            downloader.block_url(key.data_url.clone());
+            let deadline = *downloader.blocked_urls.get(&key.data_url).unwrap();
+            let backoff =
+                BLOCK_DOWNLOAD_BAN_URL << cmp::min(i, BLOCK_DOWNLOAD_MAX_BACKOFF_EXPONENT);
+            assert!(deadline >= now + backoff && deadline <= get_epoch_time_secs() + backoff);
+            assert!(deadline >= last_deadline);
+            last_deadline = deadline;
+        }
+        assert_eq!(
+            *downloader.url_failures.get(&key.data_url).unwrap(),
+            BLOCK_DOWNLOAD_MAX_BACKOFF_EXPONENT + 3
+        );
+    }
+
+    #[test]
+    fn test_expire_requests() {
+        let mut downloader = BlockDownloader::new(1000, 0, 10);
+        let mut pending = HashMap::new();
+
+        let fresh = make_test_request_key(1, 1);
+        let mut stale = make_test_request_key(2, 2);
+        stale.request_start_ms = get_epoch_time_ms() - BLOCK_DOWNLOAD_REQUEST_TIMEOUT_MS - 1;
+        let mut stale_shared = make_test_request_key(1, 3);
+        stale_shared.request_start_ms = stale.request_start_ms;
+
+        pending.insert(fresh.clone(), 10);
+        pending.insert(stale.clone(), 11);
+        pending.insert(stale_shared.clone(), 10);
+
+        downloader.expire_requests(&mut pending, "block");
+
+        assert_eq!(pending.len(), 1);
+        assert!(pending.contains_key(&fresh));
+
+        // event 10 still carries a live request, so only event 11 is disconnected
+        assert_eq!(downloader.dead_peers, vec![11]);
+
+        // both slow peers are charged with a timeout, and backed off from
+        assert_eq!(downloader.download_timings.len(), 2);
+        for (_, latency, num_bytes) in downloader.download_timings.iter() {
+            assert_eq!(*latency, BLOCK_DOWNLOAD_REQUEST_TIMEOUT_MS as u64);
+            assert_eq!(*num_bytes, 0);
+        }
+        assert_eq!(downloader.timed_out_neighbors.len(), 2);
+        assert!(downloader.blocked_urls.contains_key(&stale.data_url));
+        assert!(downloader.blocked_urls.contains_key(&stale_shared.data_url));
+    }
+
+    #[test]
+    fn test_find_slow_requests() {
+        let now_ms = get_epoch_time_ms();
+        let mut inflight = HashMap::new();
+        let mut hedged_requests = HashSet::new();
+
+        let fresh = make_test_request_key(1, 1);
+        let mut slow = make_test_request_key(2, 2);
+        slow.request_start_ms = now_ms - BLOCK_DOWNLOAD_HEDGE_MS - 1;
+        let mut slow_hedged = make_test_request_key(3, 3);
+        slow_hedged.request_start_ms = slow.request_start_ms;
+        hedged_requests.insert(slow_hedged.index_block_hash.clone());
+
+        inflight.insert(fresh.clone(), 10);
+        inflight.insert(slow.clone(), 11);
+        inflight.insert(slow_hedged.clone(), 12);
+
+        assert_eq!(
+            BlockDownloader::find_slow_requests(&inflight, &hedged_requests, now_ms),
+            vec![slow.clone()]
+        );
+
+        // a block or microblock stream is only hedged once per pass
+        hedged_requests.insert(slow.index_block_hash.clone());
+        assert_eq!(
+            BlockDownloader::find_slow_requests(&inflight, &hedged_requests, now_ms),
+            vec![]
+        );
+
+        // ...and hedging starts over with the next pass
+        hedged_requests.clear();
+        let mut slow_requests =
+            BlockDownloader::find_slow_requests(&inflight, &hedged_requests, now_ms);
+        slow_requests.sort_by_key(|key| key.neighbor.port);
+        assert_eq!(slow_requests, vec![slow, slow_hedged]);
+    }
+
     fn get_blocks_inventory(
         peer: &mut TestPeer,
         start_height: u64,
@@ -0,0 +1,343 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! STX faucet for test networks.
+//!
+//! The faucet sends a fixed amount of STX from a configured key to whoever asks for it via
+//! `POST /v2/faucet/stx`.  Each recipient address and each caller IP address may only be
+//! served once per configured interval; the requests served are tracked in a local table.
+//! The RPC handler refuses to run the faucet on mainnet.
+
+use std::fs;
+use std::net::IpAddr;
+
+use rusqlite::types::ToSql;
+use rusqlite::{Connection, OpenFlags, NO_PARAMS};
+
+use burnchains::Txid;
+use chainstate::stacks::Error as chainstate_error;
+use chainstate::stacks::{
+    StacksAddress, StacksPrivateKey, StacksPublicKey, StacksTransaction, TokenTransferMemo,
+    TransactionBuilder, TransactionVersion, C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+};
+
+use address::AddressHashMode;
+
+use util::db::tx_busy_handler;
+use util::db::DBConn;
+use util::db::Error as db_error;
+use util::db::{query_int, u64_to_sql};
+
+use vm::types::PrincipalData;
+
+const FAUCET_SQL: &'static [&'static str] = &[
+    r#"
+    CREATE TABLE faucet_requests(
+        recipient TEXT NOT NULL,
+        caller TEXT NOT NULL,
+        txid TEXT NOT NULL,
+        timestamp INTEGER NOT NULL
+    );"#,
+    "CREATE INDEX IF NOT EXISTS faucet_requests_by_recipient ON faucet_requests(recipient, timestamp);",
+    "CREATE INDEX IF NOT EXISTS faucet_requests_by_caller ON faucet_requests(caller, timestamp);",
+];
+
+pub struct Faucet {
+    conn: DBConn,
+    privkey: StacksPrivateKey,
+    address: StacksAddress,
+    /// microSTX sent per request
+    pub amount: u64,
+    /// fee paid per transfer, in microSTX
+    pub fee: u64,
+    /// how often a recipient may be sent STX, in seconds
+    pub address_interval: u64,
+    /// how often a caller may request STX, in seconds
+    pub caller_interval: u64,
+}
+
+impl Faucet {
+    /// Open the faucet's request table at the given path, creating it if it doesn't exist.
+    pub fn open(
+        path: &str,
+        privkey: StacksPrivateKey,
+        amount: u64,
+        fee: u64,
+        address_interval: u64,
+        caller_interval: u64,
+    ) -> Result<Faucet, db_error> {
+        let create_flag = fs::metadata(path).is_err();
+        let open_flags = if create_flag {
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE
+        } else {
+            OpenFlags::SQLITE_OPEN_READ_WRITE
+        };
+
+        let conn = Connection::open_with_flags(path, open_flags).map_err(db_error::SqliteError)?;
+        conn.busy_handler(Some(tx_busy_handler))?;
+        Faucet::from_connection(
+            conn,
+            create_flag,
+            privkey,
+            amount,
+            fee,
+            address_interval,
+            caller_interval,
+        )
+    }
+
+    #[cfg(test)]
+    pub fn open_memory(
+        privkey: StacksPrivateKey,
+        amount: u64,
+        fee: u64,
+        address_interval: u64,
+        caller_interval: u64,
+    ) -> Result<Faucet, db_error> {
+        let conn = Connection::open_in_memory().map_err(db_error::SqliteError)?;
+        Faucet::from_connection(
+            conn,
+            true,
+            privkey,
+            amount,
+            fee,
+            address_interval,
+            caller_interval,
+        )
+    }
+
+    fn from_connection(
+        conn: DBConn,
+        instantiate: bool,
+        privkey: StacksPrivateKey,
+        amount: u64,
+        fee: u64,
+        address_interval: u64,
+        caller_interval: u64,
+    ) -> Result<Faucet, db_error> {
+        if instantiate {
+            for cmd in FAUCET_SQL {
+                conn.execute(cmd, NO_PARAMS)
+                    .map_err(db_error::SqliteError)?;
+            }
+        }
+
+        let address = StacksAddress::from_public_keys(
+            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            &AddressHashMode::SerializeP2PKH,
+            1,
+            &vec![StacksPublicKey::from_private(&privkey)],
+        )
+        .expect("FATAL: failed to make a p2pkh address");
+
+        Ok(Faucet {
+            conn,
+            privkey,
+            address,
+            amount,
+            fee,
+            address_interval,
+            caller_interval,
+        })
+    }
+
+    /// The address the faucet sends STX from
+    pub fn address(&self) -> &StacksAddress {
+        &self.address
+    }
+
+    fn seconds_until_allowed(
+        &self,
+        column: &str,
+        value: &String,
+        interval: u64,
+        now: u64,
+    ) -> Result<u64, db_error> {
+        if interval == 0 {
+            return Ok(0);
+        }
+        let sql = format!(
+            "SELECT IFNULL(MAX(timestamp), 0) FROM faucet_requests WHERE {} = ?1 AND timestamp > ?2",
+            column
+        );
+        let args: &[&dyn ToSql] = &[value, &u64_to_sql(now.saturating_sub(interval))?];
+        let last_request = query_int(&self.conn, &sql, args)? as u64;
+        if last_request == 0 {
+            Ok(0)
+        } else {
+            Ok((last_request + interval).saturating_sub(now))
+        }
+    }
+
+    /// How many seconds the caller must wait before the faucet will send STX to `recipient` on
+    /// its behalf, as of `now`.  Returns 0 if the request can be served.
+    pub fn retry_after(
+        &self,
+        recipient: &PrincipalData,
+        caller: &IpAddr,
+        now: u64,
+    ) -> Result<u64, db_error> {
+        let recipient_wait = self.seconds_until_allowed(
+            "recipient",
+            &recipient.to_string(),
+            self.address_interval,
+            now,
+        )?;
+        let caller_wait =
+            self.seconds_until_allowed("caller", &caller.to_string(), self.caller_interval, now)?;
+        Ok(recipient_wait.max(caller_wait))
+    }
+
+    /// Make and sign a transfer of the faucet amount to `recipient`.
+    pub fn make_transfer(
+        &self,
+        chain_id: u32,
+        nonce: u64,
+        recipient: &PrincipalData,
+    ) -> Result<StacksTransaction, chainstate_error> {
+        TransactionBuilder::new(TransactionVersion::Testnet, chain_id)
+            .token_transfer(recipient.clone(), self.amount, TokenTransferMemo([0u8; 34]))
+            .with_nonce(nonce)
+            .with_fee(self.fee)
+            .with_signer(self.privkey.clone())
+            .build()
+    }
+
+    /// Remember that the faucet served a request.
+    pub fn record(
+        &self,
+        recipient: &PrincipalData,
+        caller: &IpAddr,
+        txid: &Txid,
+        now: u64,
+    ) -> Result<(), db_error> {
+        let args: &[&dyn ToSql] = &[
+            &recipient.to_string(),
+            &caller.to_string(),
+            &txid.to_hex(),
+            &u64_to_sql(now)?,
+        ];
+        self.conn
+            .execute(
+                "INSERT INTO faucet_requests (recipient, caller, txid, timestamp) VALUES (?1, ?2, ?3, ?4)",
+                args,
+            )
+            .map_err(db_error::SqliteError)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chainstate::stacks::TransactionPayload;
+    use util::hash::Hash160;
+
+    fn make_principal(byte: u8) -> PrincipalData {
+        StacksAddress {
+            version: C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            bytes: Hash160([byte; 20]),
+        }
+        .into()
+    }
+
+    fn make_faucet(address_interval: u64, caller_interval: u64) -> Faucet {
+        let privk = StacksPrivateKey::from_hex(
+            "6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001",
+        )
+        .unwrap();
+        Faucet::open_memory(privk, 500_000_000, 180, address_interval, caller_interval).unwrap()
+    }
+
+    #[test]
+    fn test_faucet_transfer() {
+        let faucet = make_faucet(3600, 60);
+        let recipient = make_principal(1);
+
+        let tx = faucet.make_transfer(0x80000000, 7, &recipient).unwrap();
+        tx.verify().unwrap();
+        assert_eq!(tx.origin_address(), *faucet.address());
+        assert_eq!(tx.get_origin_nonce(), 7);
+        assert_eq!(tx.get_fee_rate(), 180);
+        assert_eq!(tx.chain_id, 0x80000000);
+        assert_eq!(tx.version, TransactionVersion::Testnet);
+        match tx.payload {
+            TransactionPayload::TokenTransfer(ref addr, ref amount, _) => {
+                assert_eq!(*addr, recipient);
+                assert_eq!(*amount, 500_000_000);
+            }
+            _ => panic!("not a token transfer"),
+        }
+    }
+
+    #[test]
+    fn test_faucet_rate_limit() {
+        let faucet = make_faucet(3600, 60);
+        let recipient_1 = make_principal(1);
+        let recipient_2 = make_principal(2);
+        let caller_1: IpAddr = "1.2.3.4".parse().unwrap();
+        let caller_2: IpAddr = "5.6.7.8".parse().unwrap();
+
+        assert_eq!(
+            faucet.retry_after(&recipient_1, &caller_1, 10000).unwrap(),
+            0
+        );
+        faucet
+            .record(&recipient_1, &caller_1, &Txid([0x01; 32]), 10000)
+            .unwrap();
+
+        // same recipient, from anywhere
+        assert_eq!(
+            faucet.retry_after(&recipient_1, &caller_2, 10000).unwrap(),
+            3600
+        );
+        assert_eq!(
+            faucet.retry_after(&recipient_1, &caller_2, 13000).unwrap(),
+            600
+        );
+        assert_eq!(
+            faucet.retry_after(&recipient_1, &caller_2, 13600).unwrap(),
+            0
+        );
+
+        // same caller, to anyone
+        assert_eq!(
+            faucet.retry_after(&recipient_2, &caller_1, 10030).unwrap(),
+            30
+        );
+        assert_eq!(
+            faucet.retry_after(&recipient_2, &caller_1, 10060).unwrap(),
+            0
+        );
+
+        // neither
+        assert_eq!(
+            faucet.retry_after(&recipient_2, &caller_2, 10000).unwrap(),
+            0
+        );
+
+        // no limits
+        let faucet = make_faucet(0, 0);
+        faucet
+            .record(&recipient_1, &caller_1, &Txid([0x01; 32]), 10000)
+            .unwrap();
+        assert_eq!(
+            faucet.retry_after(&recipient_1, &caller_1, 10000).unwrap(),
+            0
+        );
+    }
+}
@@ -23,17 +23,27 @@ use std::io;
 use std::io::prelude::*;
 use std::io::{Read, Write};
 use std::mem;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::str;
 use std::str::FromStr;
 
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json;
 
+use net::admin::DEFAULT_ADMIN_BAN_DURATION;
+use net::atlas::{Attachment, MAX_ATTACHMENT_SIZE};
 use net::codec::{read_next, write_next};
+use net::connection::ConnectionOptions;
+use net::AdminBanPeerRequestBody;
+use net::AdminSetMiningRequestBody;
 use net::CallReadOnlyRequestBody;
 use net::ClientError;
 use net::Error as net_error;
+use net::HttpByteRange;
+use net::HttpContentEncoding;
 use net::HttpContentType;
 use net::HttpRequestMetadata;
 use net::HttpRequestPreamble;
@@ -43,10 +53,16 @@ use net::HttpResponsePreamble;
 use net::HttpResponseType;
 use net::HttpVersion;
 use net::MessageSequence;
+use net::MultiCallReadOnlyCallBody;
+use net::MultiCallReadOnlyRequestBody;
 use net::NeighborAddress;
+use net::NeighborDirection;
+use net::NeighborState;
+use net::NeighborsQuery;
 use net::PeerAddress;
 use net::PeerHost;
 use net::ProtocolFamily;
+use net::ReadOnlyCall;
 use net::StacksHttpMessage;
 use net::StacksHttpPreamble;
 use net::StacksMessageCodec;
@@ -55,15 +71,18 @@ use net::HTTP_PREAMBLE_MAX_NUM_HEADERS;
 use net::HTTP_REQUEST_ID_RESERVED;
 use net::MAX_MESSAGE_LEN;
 use net::MAX_MICROBLOCKS_UNCONFIRMED;
+use net::MAX_MULTI_CALL_READS;
 
 use burnchains::{Address, Txid};
 use chainstate::burn::BlockHeaderHash;
 use chainstate::stacks::{
-    StacksAddress, StacksBlock, StacksBlockId, StacksMicroblock, StacksPublicKey, StacksTransaction,
+    StacksAddress, StacksBlock, StacksBlockHeader, StacksBlockId, StacksMicroblock,
+    StacksPublicKey, StacksTransaction,
 };
 
 use util::hash::hex_bytes;
 use util::hash::to_hex;
+use util::hash::Hash160;
 use util::log;
 use util::retry::BoundReader;
 use util::retry::RetryReader;
@@ -90,6 +109,8 @@ use time;
 lazy_static! {
     static ref PATH_GETINFO: Regex = Regex::new(r#"^/v2/info$"#).unwrap();
     static ref PATH_GETPOXINFO: Regex = Regex::new(r#"^/v2/pox$"#).unwrap();
+    static ref PATH_GETBURNETA: Regex = Regex::new(r#"^/v2/burn_eta$"#).unwrap();
+    static ref PATH_GETFORKS: Regex = Regex::new(r#"^/v2/forks$"#).unwrap();
     static ref PATH_GETNEIGHBORS: Regex = Regex::new(r#"^/v2/neighbors$"#).unwrap();
     static ref PATH_GETBLOCK: Regex = Regex::new(r#"^/v2/blocks/([0-9a-f]{64})$"#).unwrap();
     static ref PATH_GETMICROBLOCKS_INDEXED: Regex =
@@ -99,12 +120,30 @@ lazy_static! {
     static ref PATH_GETMICROBLOCKS_UNCONFIRMED: Regex =
         Regex::new(r#"^/v2/microblocks/unconfirmed/([0-9a-f]{64})/([0-9]{1,5})$"#).unwrap();
     static ref PATH_POSTTRANSACTION: Regex = Regex::new(r#"^/v2/transactions$"#).unwrap();
+    static ref PATH_GET_TRANSACTION_RECEIPT: Regex =
+        Regex::new(r#"^/v2/transactions/([0-9a-f]{64})/receipt$"#).unwrap();
     static ref PATH_POSTMICROBLOCK: Regex = Regex::new(r#"^/v2/microblocks$"#).unwrap();
+    static ref PATH_POST_VALIDATE_BLOCK: Regex = Regex::new(r#"^/v2/blocks/validate$"#).unwrap();
+    static ref PATH_POST_FAUCET_STX: Regex = Regex::new(r#"^/v2/faucet/stx$"#).unwrap();
+    static ref PATH_GET_ATTACHMENT: Regex =
+        Regex::new(r#"^/v2/attachments/([0-9a-f]{40})$"#).unwrap();
+    static ref PATH_POST_ATTACHMENT: Regex = Regex::new(r#"^/v2/attachments$"#).unwrap();
+    static ref PATH_GET_EVENTS: Regex = Regex::new(r#"^/v2/events$"#).unwrap();
     static ref PATH_GET_ACCOUNT: Regex = Regex::new(&format!(
         "^/v2/accounts/(?P<principal>{})$",
         *PRINCIPAL_DATA_REGEX
     ))
     .unwrap();
+    static ref PATH_GET_ACCOUNT_NONCES: Regex = Regex::new(&format!(
+        "^/v2/accounts/(?P<principal>{})/nonces$",
+        *PRINCIPAL_DATA_REGEX
+    ))
+    .unwrap();
+    static ref PATH_GET_MINER_REWARDS: Regex = Regex::new(&format!(
+        "^/v2/miners/(?P<address>{})/rewards$",
+        *STANDARD_PRINCIPAL_REGEX
+    ))
+    .unwrap();
     static ref PATH_GET_MAP_ENTRY: Regex = Regex::new(&format!(
         "^/v2/map_entry/(?P<address>{})/(?P<contract>{})/(?P<map>{})$",
         *STANDARD_PRINCIPAL_REGEX, *CONTRACT_NAME_REGEX, *CLARITY_NAME_REGEX
@@ -115,6 +154,8 @@ lazy_static! {
         *STANDARD_PRINCIPAL_REGEX, *CONTRACT_NAME_REGEX, *CLARITY_NAME_REGEX
     ))
     .unwrap();
+    static ref PATH_POST_MULTI_CALL_READ_ONLY: Regex =
+        Regex::new("^/v2/contracts/multi-call-read$").unwrap();
     static ref PATH_GET_CONTRACT_SRC: Regex = Regex::new(&format!(
         "^/v2/contracts/source/(?P<address>{})/(?P<contract>{})$",
         *STANDARD_PRINCIPAL_REGEX, *CONTRACT_NAME_REGEX
@@ -126,6 +167,13 @@ lazy_static! {
     ))
     .unwrap();
     static ref PATH_GET_TRANSFER_COST: Regex = Regex::new("^/v2/fees/transfer$").unwrap();
+    static ref PATH_GET_FEE_ESTIMATE: Regex = Regex::new("^/v2/fees/transaction$").unwrap();
+    static ref PATH_POST_ADMIN_BAN_PEER: Regex = Regex::new("^/v2/admin/peers/ban$").unwrap();
+    static ref PATH_POST_ADMIN_EVICT_TRANSACTION: Regex =
+        Regex::new("^/v2/admin/mempool/evict/([0-9a-f]{64})$").unwrap();
+    static ref PATH_POST_ADMIN_FLUSH_CHAINSTATE: Regex =
+        Regex::new("^/v2/admin/chainstate/flush$").unwrap();
+    static ref PATH_POST_ADMIN_MINER: Regex = Regex::new("^/v2/admin/miner$").unwrap();
     static ref PATH_OPTIONS_WILDCARD: Regex = Regex::new("^/v2/.{0,4096}$").unwrap();
 }
 
@@ -527,10 +575,54 @@ impl<'a, R: Read> Read for HttpChunkedTransferReader<'a, R> {
     }
 }
 
+/// Compresses a response body as it is written
+enum HttpCompressor {
+    Gzip(GzEncoder<Vec<u8>>),
+    Deflate(ZlibEncoder<Vec<u8>>),
+}
+
+impl HttpCompressor {
+    fn new(encoding: HttpContentEncoding) -> HttpCompressor {
+        match encoding {
+            HttpContentEncoding::Gzip => {
+                HttpCompressor::Gzip(GzEncoder::new(vec![], Compression::default()))
+            }
+            HttpContentEncoding::Deflate => {
+                HttpCompressor::Deflate(ZlibEncoder::new(vec![], Compression::default()))
+            }
+        }
+    }
+
+    /// Compress `buf`, and take whatever compressed output is ready
+    fn compress(&mut self, buf: &[u8]) -> io::Result<Vec<u8>> {
+        let output = match *self {
+            HttpCompressor::Gzip(ref mut encoder) => {
+                encoder.write_all(buf)?;
+                encoder.get_mut()
+            }
+            HttpCompressor::Deflate(ref mut encoder) => {
+                encoder.write_all(buf)?;
+                encoder.get_mut()
+            }
+        };
+        Ok(mem::replace(output, vec![]))
+    }
+
+    /// Finish the compressed stream, and take the rest of its output
+    fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            HttpCompressor::Gzip(encoder) => encoder.finish(),
+            HttpCompressor::Deflate(encoder) => encoder.finish(),
+        }
+    }
+}
+
 pub struct HttpChunkedTransferWriterState {
     chunk_size: usize,
     chunk_buf: Vec<u8>,
     corked: bool,
+    /// if set, data is compressed before it's chunked
+    compressor: Option<HttpCompressor>,
 }
 
 impl HttpChunkedTransferWriterState {
@@ -539,8 +631,19 @@ impl HttpChunkedTransferWriterState {
             chunk_size: chunk_size,
             chunk_buf: vec![],
             corked: false,
+            compressor: None,
         }
     }
+
+    /// Make state for writing a body compressed with `encoding`, or as-is if it's None
+    pub fn new_encoded(
+        chunk_size: usize,
+        encoding: Option<HttpContentEncoding>,
+    ) -> HttpChunkedTransferWriterState {
+        let mut state = HttpChunkedTransferWriterState::new(chunk_size);
+        state.compressor = encoding.map(HttpCompressor::new);
+        state
+    }
 }
 
 pub struct HttpChunkedTransferWriter<'a, 'state, W: Write> {
@@ -601,10 +704,8 @@ impl<'a, 'state, W: Write> HttpChunkedTransferWriter<'a, 'state, W> {
     pub fn corked(&self) -> bool {
         self.state.corked
     }
-}
 
-impl<'a, 'state, W: Write> Write for HttpChunkedTransferWriter<'a, 'state, W> {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    fn write_chunked(&mut self, buf: &[u8]) -> io::Result<usize> {
         let mut written = 0;
         while written < buf.len() && !self.state.corked {
             if self.state.chunk_buf.len() > 0 {
@@ -631,10 +732,30 @@ impl<'a, 'state, W: Write> Write for HttpChunkedTransferWriter<'a, 'state, W> {
         }
         Ok(written)
     }
+}
+
+impl<'a, 'state, W: Write> Write for HttpChunkedTransferWriter<'a, 'state, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.state.corked {
+            return Ok(0);
+        }
+        let compressed = match self.state.compressor {
+            Some(ref mut compressor) => compressor.compress(buf)?,
+            None => {
+                return self.write_chunked(buf);
+            }
+        };
+        self.write_chunked(&compressed)?;
+        Ok(buf.len())
+    }
 
     fn flush(&mut self) -> io::Result<()> {
         // send out any buffered chunk data
         if !self.state.corked {
+            if let Some(compressor) = self.state.compressor.take() {
+                let compressed = compressor.finish()?;
+                self.write_chunked(&compressed)?;
+            }
             self.flush_chunk().and_then(|nw| {
                 if nw > 0 {
                     // send empty chunk
@@ -825,7 +946,101 @@ impl HttpRequestPreamble {
     }
 }
 
-fn empty_headers<W: Write>(_fd: &mut W) -> Result<(), net_error> {
+/// Decode a Clarity value in an RPC request body.  It is either the hex string of the value's
+/// consensus serialization, or the value's JSON encoding (see `vm::representations::json`).
+fn value_from_request_json(json: &serde_json::Value) -> Option<Value> {
+    match json.as_str() {
+        Some(hex) => Value::deserialize_from_hex(hex, None).ok(),
+        None => Value::try_from_json(json).ok(),
+    }
+}
+
+impl HttpByteRange {
+    /// Parse the value of a Range header.  Only a single range of bytes is supported; anything
+    /// else is treated as if there were no Range header, as RFC 7233 allows.
+    pub fn parse(value: &str) -> Option<HttpByteRange> {
+        let value = value.trim();
+        if !value.starts_with("bytes=") {
+            return None;
+        }
+        let spec = &value["bytes=".len()..];
+        if spec.contains(',') {
+            return None;
+        }
+        let mut parts = spec.splitn(2, '-');
+        let first = parts.next()?.trim();
+        let last = parts.next()?.trim();
+        match (first.is_empty(), last.is_empty()) {
+            (true, false) => last.parse::<u64>().ok().map(HttpByteRange::Suffix),
+            (false, true) => first.parse::<u64>().ok().map(HttpByteRange::From),
+            (false, false) => {
+                let first = first.parse::<u64>().ok()?;
+                let last = last.parse::<u64>().ok()?;
+                if first > last {
+                    return None;
+                }
+                Some(HttpByteRange::FromTo(first, last))
+            }
+            (true, true) => None,
+        }
+    }
+
+    /// Resolve this range against data of length `len`, as the offset of its first byte and
+    /// the offset just past its last byte.  Returns None if no byte of the data is in range.
+    pub fn resolve(&self, len: u64) -> Option<(u64, u64)> {
+        match *self {
+            HttpByteRange::FromTo(first, last) => {
+                if first >= len {
+                    None
+                } else {
+                    Some((first, last.saturating_add(1).min(len)))
+                }
+            }
+            HttpByteRange::From(first) => {
+                if first >= len {
+                    None
+                } else {
+                    Some((first, len))
+                }
+            }
+            HttpByteRange::Suffix(count) => {
+                if count == 0 || len == 0 {
+                    None
+                } else {
+                    Some((len.saturating_sub(count), len))
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for HttpByteRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HttpByteRange::FromTo(first, last) => write!(f, "bytes={}-{}", first, last),
+            HttpByteRange::From(first) => write!(f, "bytes={}-", first),
+            HttpByteRange::Suffix(count) => write!(f, "bytes=-{}", count),
+        }
+    }
+}
+
+fn range_headers<W: Write>(fd: &mut W, md: &HttpRequestMetadata) -> Result<(), net_error> {
+    if let Some(ref range) = md.range {
+        fd.write_all(format!("Range: {}\r\n", range).as_bytes())
+            .map_err(net_error::WriteError)?;
+    }
+    Ok(())
+}
+
+fn api_key_headers<W: Write>(fd: &mut W, md: &HttpRequestMetadata) -> Result<(), net_error> {
+    if let Some(ref api_key) = md.api_key {
+        fd.write_all(format!("X-Api-Key: {}\r\n", api_key).as_bytes())
+            .map_err(net_error::WriteError)?;
+    }
+    if let Some(ref admin_token) = md.admin_token {
+        fd.write_all(format!("Authorization: Bearer {}\r\n", admin_token).as_bytes())
+            .map_err(net_error::WriteError)?;
+    }
     Ok(())
 }
 
@@ -852,6 +1067,166 @@ fn keep_alive_headers<W: Write>(fd: &mut W, md: &HttpResponseMetadata) -> Result
     Ok(())
 }
 
+/// The CORS request headers and methods the RPC interface allows
+const CORS_ALLOW_HEADERS: &'static str = "origin, content-type, x-api-key";
+const CORS_ALLOW_METHODS: &'static str = "POST, GET, OPTIONS";
+
+/// The CORS, caching, and extra headers a server sends with its responses (see the `rpc_cors_*`,
+/// `rpc_*cache_max_age`, and `rpc_response_headers` connection options)
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpResponseHeaders {
+    /// origins that may make cross-origin requests.  "*" allows any, and none disables CORS.
+    pub cors_allowed_origins: Vec<String>,
+    /// Access-Control-Max-Age of preflight responses (0 to not send it)
+    pub cors_max_age: u64,
+    /// max-age of successful responses that depend on the chain tip (0 for no-cache)
+    pub cache_max_age: u64,
+    /// max-age of successful responses that never change (0 for no-cache)
+    pub immutable_cache_max_age: u64,
+    pub extra: Vec<(String, String)>,
+}
+
+impl HttpResponseHeaders {
+    pub fn from_connection_options(opts: &ConnectionOptions) -> HttpResponseHeaders {
+        HttpResponseHeaders {
+            cors_allowed_origins: opts.rpc_cors_allowed_origins.clone(),
+            cors_max_age: opts.rpc_cors_max_age,
+            cache_max_age: opts.rpc_cache_max_age,
+            immutable_cache_max_age: opts.rpc_immutable_cache_max_age,
+            extra: opts.rpc_response_headers.clone(),
+        }
+    }
+
+    /// Parse a configured extra header of the form `Name: value`
+    pub fn parse_header(line: &str) -> Option<(String, String)> {
+        let mut parts = line.splitn(2, ':');
+        let name = parts.next()?.trim();
+        let value = parts.next()?.trim();
+        if name.len() == 0
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c))
+            || value.chars().any(|c| c == '\r' || c == '\n')
+        {
+            return None;
+        }
+        Some((name.to_string(), value.to_string()))
+    }
+
+    fn allows_any_origin(&self) -> bool {
+        self.cors_allowed_origins
+            .iter()
+            .any(|allowed| allowed == "*")
+    }
+
+    /// The Access-Control-Allow-Origin value to answer a request from `origin` with, if any
+    fn allowed_origin<'a>(&'a self, origin: Option<&'a str>) -> Option<&'a str> {
+        if self.allows_any_origin() {
+            return Some("*");
+        }
+        origin.filter(|origin| {
+            self.cors_allowed_origins
+                .iter()
+                .any(|allowed| allowed == origin)
+        })
+    }
+
+    fn write<W: Write>(
+        &self,
+        fd: &mut W,
+        md: &HttpResponseMetadata,
+        status_code: u16,
+        preflight: bool,
+    ) -> Result<(), net_error> {
+        let mut headers = vec![];
+        if self.cors_allowed_origins.len() > 0 {
+            if let Some(allowed_origin) =
+                self.allowed_origin(md.origin.as_ref().map(|origin| origin.as_str()))
+            {
+                headers.push(("Access-Control-Allow-Origin", allowed_origin.to_string()));
+                headers.push((
+                    "Access-Control-Allow-Headers",
+                    CORS_ALLOW_HEADERS.to_string(),
+                ));
+                headers.push((
+                    "Access-Control-Allow-Methods",
+                    CORS_ALLOW_METHODS.to_string(),
+                ));
+                if preflight && self.cors_max_age > 0 {
+                    headers.push(("Access-Control-Max-Age", format!("{}", self.cors_max_age)));
+                }
+            }
+            if !self.allows_any_origin() {
+                // the answer depends on the Origin header, so caches must key on it
+                headers.push(("Vary", "Origin".to_string()));
+            }
+        }
+
+        let max_age = if status_code < 200 || status_code >= 300 {
+            0
+        } else if md.immutable {
+            self.immutable_cache_max_age
+        } else {
+            self.cache_max_age
+        };
+        headers.push((
+            "Cache-Control",
+            match (max_age, md.immutable) {
+                (0, _) => "no-cache".to_string(),
+                (max_age, true) => format!("public, max-age={}, immutable", max_age),
+                (max_age, false) => format!("public, max-age={}", max_age),
+            },
+        ));
+
+        for (name, value) in headers.iter() {
+            fd.write_all(format!("{}: {}\r\n", name, value).as_bytes())
+                .map_err(net_error::WriteError)?;
+        }
+        for (name, value) in self.extra.iter() {
+            fd.write_all(format!("{}: {}\r\n", name, value).as_bytes())
+                .map_err(net_error::WriteError)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for HttpResponseHeaders {
+    fn default() -> HttpResponseHeaders {
+        HttpResponseHeaders::from_connection_options(&ConnectionOptions::default())
+    }
+}
+
+/// Write the headers of a response the server sends:  its keep-alive headers, and those
+/// `protocol` is configured to send
+fn response_headers<W: Write>(
+    fd: &mut W,
+    protocol: &StacksHttp,
+    md: &HttpResponseMetadata,
+    status_code: u16,
+) -> Result<(), net_error> {
+    keep_alive_headers(fd, md)?;
+    protocol.response_headers.write(fd, md, status_code, false)
+}
+
+/// Write the Content-Encoding header of a compressed response
+fn content_encoding_headers<W: Write>(
+    fd: &mut W,
+    encoding: Option<HttpContentEncoding>,
+) -> Result<(), net_error> {
+    match encoding {
+        Some(encoding) => fd
+            .write_all(
+                format!(
+                    "Content-Encoding: {}\r\nVary: Accept-Encoding\r\n",
+                    encoding.as_str()
+                )
+                .as_bytes(),
+            )
+            .map_err(net_error::WriteError),
+        None => Ok(()),
+    }
+}
+
 fn write_headers<W: Write>(fd: &mut W, headers: &HashMap<String, String>) -> Result<(), net_error> {
     for (ref key, ref value) in headers.iter() {
         fd.write_all(key.as_str().as_bytes())
@@ -1006,15 +1381,30 @@ impl StacksMessageCodec for HttpRequestPreamble {
                             Err(_) => None,
                         }
                     } else if key == "connection" {
-                        // parse
-                        if value.to_lowercase() == "close" {
-                            keep_alive = false;
-                        } else if value.to_lowercase() == "keep-alive" {
-                            keep_alive = true;
-                        } else {
-                            return Err(net_error::DeserializeError(
-                                "Inavlid HTTP request: invalid Connection: header".to_string(),
-                            ));
+                        // parse.  This is a list of options, and "upgrade" asks us to switch
+                        // protocols (i.e. to a WebSocket).
+                        let mut upgrade = false;
+                        for option in value.to_lowercase().split(',') {
+                            match option.trim() {
+                                "close" => {
+                                    keep_alive = false;
+                                }
+                                "keep-alive" => {
+                                    keep_alive = true;
+                                }
+                                "upgrade" => {
+                                    upgrade = true;
+                                }
+                                _ => {
+                                    return Err(net_error::DeserializeError(
+                                        "Inavlid HTTP request: invalid Connection: header"
+                                            .to_string(),
+                                    ));
+                                }
+                            }
+                        }
+                        if upgrade {
+                            headers.insert(key, "upgrade".to_string());
                         }
                     } else {
                         headers.insert(key, value);
@@ -1063,6 +1453,7 @@ impl HttpResponsePreamble {
     }
 
     pub fn ok_JSON_from_md<W: Write>(
+        protocol: &StacksHttp,
         fd: &mut W,
         md: &HttpResponseMetadata,
     ) -> Result<(), net_error> {
@@ -1073,7 +1464,7 @@ impl HttpResponsePreamble {
             md.content_length.clone(),
             &HttpContentType::JSON,
             md.request_id,
-            |ref mut fd| keep_alive_headers(fd, md),
+            |ref mut fd| response_headers(fd, protocol, md, 200),
         )
     }
 
@@ -1097,12 +1488,6 @@ impl HttpResponsePreamble {
             .map_err(net_error::WriteError)?;
         fd.write_all(rfc7231_now().as_bytes())
             .map_err(net_error::WriteError)?;
-        fd.write_all("\r\nAccess-Control-Allow-Origin: *".as_bytes())
-            .map_err(net_error::WriteError)?;
-        fd.write_all("\r\nAccess-Control-Allow-Headers: origin, content-type".as_bytes())
-            .map_err(net_error::WriteError)?;
-        fd.write_all("\r\nAccess-Control-Allow-Methods: POST, GET, OPTIONS".as_bytes())
-            .map_err(net_error::WriteError)?;
         fd.write_all("\r\nContent-Type: ".as_bytes())
             .map_err(net_error::WriteError)?;
         fd.write_all(content_type.as_str().as_bytes())
@@ -1214,6 +1599,14 @@ impl HttpResponsePreamble {
     pub fn add_CORS_headers(&mut self) -> () {
         self.headers
             .insert("Access-Control-Allow-Origin".to_string(), "*".to_string());
+        self.headers.insert(
+            "Access-Control-Allow-Headers".to_string(),
+            CORS_ALLOW_HEADERS.to_string(),
+        );
+        self.headers.insert(
+            "Access-Control-Allow-Methods".to_string(),
+            CORS_ALLOW_METHODS.to_string(),
+        );
     }
 
     // do we have Transfer-Encoding: chunked?
@@ -1427,6 +1820,8 @@ impl HttpRequestType {
         )] = &[
             ("GET", &PATH_GETINFO, &HttpRequestType::parse_getinfo),
             ("GET", &PATH_GETPOXINFO, &HttpRequestType::parse_getpoxinfo),
+            ("GET", &PATH_GETBURNETA, &HttpRequestType::parse_getburneta),
+            ("GET", &PATH_GETFORKS, &HttpRequestType::parse_getforks),
             (
                 "GET",
                 &PATH_GETNEIGHBORS,
@@ -1458,11 +1853,51 @@ impl HttpRequestType {
                 &PATH_POSTMICROBLOCK,
                 &HttpRequestType::parse_postmicroblock,
             ),
+            (
+                "POST",
+                &PATH_POST_VALIDATE_BLOCK,
+                &HttpRequestType::parse_post_validate_block,
+            ),
+            (
+                "POST",
+                &PATH_POST_FAUCET_STX,
+                &HttpRequestType::parse_post_faucet_stx,
+            ),
+            (
+                "GET",
+                &PATH_GET_ATTACHMENT,
+                &HttpRequestType::parse_get_attachment,
+            ),
+            (
+                "POST",
+                &PATH_POST_ATTACHMENT,
+                &HttpRequestType::parse_post_attachment,
+            ),
+            (
+                "GET",
+                &PATH_GET_EVENTS,
+                &HttpRequestType::parse_websocket_upgrade,
+            ),
+            (
+                "GET",
+                &PATH_GET_TRANSACTION_RECEIPT,
+                &HttpRequestType::parse_get_transaction_receipt,
+            ),
             (
                 "GET",
                 &PATH_GET_ACCOUNT,
                 &HttpRequestType::parse_get_account,
             ),
+            (
+                "GET",
+                &PATH_GET_ACCOUNT_NONCES,
+                &HttpRequestType::parse_get_account_nonces,
+            ),
+            (
+                "GET",
+                &PATH_GET_MINER_REWARDS,
+                &HttpRequestType::parse_get_miner_rewards,
+            ),
             (
                 "POST",
                 &PATH_GET_MAP_ENTRY,
@@ -1473,6 +1908,11 @@ impl HttpRequestType {
                 &PATH_GET_TRANSFER_COST,
                 &HttpRequestType::parse_get_transfer_cost,
             ),
+            (
+                "GET",
+                &PATH_GET_FEE_ESTIMATE,
+                &HttpRequestType::parse_get_fee_estimate,
+            ),
             (
                 "GET",
                 &PATH_GET_CONTRACT_SRC,
@@ -1488,6 +1928,31 @@ impl HttpRequestType {
                 &PATH_POST_CALL_READ_ONLY,
                 &HttpRequestType::parse_call_read_only,
             ),
+            (
+                "POST",
+                &PATH_POST_MULTI_CALL_READ_ONLY,
+                &HttpRequestType::parse_multi_call_read_only,
+            ),
+            (
+                "POST",
+                &PATH_POST_ADMIN_BAN_PEER,
+                &HttpRequestType::parse_admin_ban_peer,
+            ),
+            (
+                "POST",
+                &PATH_POST_ADMIN_EVICT_TRANSACTION,
+                &HttpRequestType::parse_admin_evict_transaction,
+            ),
+            (
+                "POST",
+                &PATH_POST_ADMIN_FLUSH_CHAINSTATE,
+                &HttpRequestType::parse_admin_flush_chainstate,
+            ),
+            (
+                "POST",
+                &PATH_POST_ADMIN_MINER,
+                &HttpRequestType::parse_admin_set_mining,
+            ),
             (
                 "OPTIONS",
                 &PATH_OPTIONS_WILDCARD,
@@ -1574,25 +2039,38 @@ impl HttpRequestType {
         ))
     }
 
-    fn parse_getneighbors<R: Read>(
+    fn parse_getburneta<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
         _regex: &Captures,
-        _query: Option<&str>,
+        query: Option<&str>,
         _fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
         if preamble.get_content_length() != 0 {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: expected 0-length body for GetNeighbors".to_string(),
+                "Invalid Http request: expected 0-length body for GetBurnEta".to_string(),
             ));
         }
 
-        Ok(HttpRequestType::GetNeighbors(
+        let height = query
+            .and_then(|query_string| {
+                form_urlencoded::parse(query_string.as_bytes())
+                    .find(|(key, _v)| key == "height")
+                    .and_then(|(_k, value)| value.parse::<u64>().ok())
+            })
+            .ok_or_else(|| {
+                net_error::ClientError(ClientError::Message(
+                    "Missing or invalid burn block height".to_string(),
+                ))
+            })?;
+
+        Ok(HttpRequestType::GetBurnEta(
             HttpRequestMetadata::from_preamble(preamble),
+            height,
         ))
     }
 
-    fn parse_get_transfer_cost<R: Read>(
+    fn parse_getforks<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
         _regex: &Captures,
@@ -1601,74 +2079,196 @@ impl HttpRequestType {
     ) -> Result<HttpRequestType, net_error> {
         if preamble.get_content_length() != 0 {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: expected 0-length body for GetTransferCost".to_string(),
+                "Invalid Http request: expected 0-length body for GetForks".to_string(),
             ));
         }
 
-        Ok(HttpRequestType::GetTransferCost(
+        Ok(HttpRequestType::GetForks(
             HttpRequestMetadata::from_preamble(preamble),
         ))
     }
 
-    /// check whether the given option query string
-    ///   sets proof=0 (setting proof to false).
-    /// Defaults to _true_
-    fn get_proof_query(query: Option<&str>) -> bool {
-        let no_proof = if let Some(query_string) = query {
-            form_urlencoded::parse(query_string.as_bytes())
-                .find(|(key, _v)| key == "proof")
-                .map(|(_k, value)| value == "0")
-                .unwrap_or(false)
-        } else {
-            false
-        };
-
-        !no_proof
-    }
-
-    /// get the chain tip optional query argument (`tip`)
-    /// Take the first value we can parse.
-    fn get_chain_tip_query(query: Option<&str>) -> Option<StacksBlockId> {
-        match query {
-            Some(query_string) => {
-                for (key, value) in form_urlencoded::parse(query_string.as_bytes()) {
-                    if key != "tip" {
-                        continue;
-                    }
-
-                    if let Ok(tip) = StacksBlockId::from_hex(&value) {
-                        return Some(tip);
-                    }
-                }
-                return None;
-            }
-            None => {
-                return None;
-            }
-        }
-    }
-
-    fn parse_get_account<R: Read>(
+    fn parse_getneighbors<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
-        captures: &Captures,
+        _regex: &Captures,
         query: Option<&str>,
         _fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
         if preamble.get_content_length() != 0 {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: expected 0-length body for GetAccount".to_string(),
+                "Invalid Http request: expected 0-length body for GetNeighbors".to_string(),
             ));
         }
 
-        let principal = PrincipalData::parse(&captures["principal"]).map_err(|_e| {
-            net_error::DeserializeError("Failed to parse account principal".into())
-        })?;
-
-        let with_proof = HttpRequestType::get_proof_query(query);
-        let tip = HttpRequestType::get_chain_tip_query(query);
+        let neighbors_query = HttpRequestType::get_neighbors_query(query)?;
 
-        Ok(HttpRequestType::GetAccount(
+        Ok(HttpRequestType::GetNeighbors(
+            HttpRequestMetadata::from_preamble(preamble),
+            neighbors_query,
+        ))
+    }
+
+    /// Parse the filters and pagination of a GetNeighbors request.  Unknown keys are ignored.
+    fn get_neighbors_query(query: Option<&str>) -> Result<NeighborsQuery, net_error> {
+        let mut neighbors_query = NeighborsQuery::default();
+        let query_string = match query {
+            Some(query_string) => query_string,
+            None => {
+                return Ok(neighbors_query);
+            }
+        };
+
+        let invalid = |key: &str| {
+            net_error::ClientError(ClientError::Message(format!("Invalid value for '{}'", key)))
+        };
+
+        for (key, value) in form_urlencoded::parse(query_string.as_bytes()) {
+            match key.as_ref() {
+                "direction" => {
+                    neighbors_query.direction = match value.as_ref() {
+                        "inbound" => Some(NeighborDirection::Inbound),
+                        "outbound" => Some(NeighborDirection::Outbound),
+                        _ => {
+                            return Err(invalid("direction"));
+                        }
+                    };
+                }
+                "state" => {
+                    neighbors_query.state = match value.as_ref() {
+                        "allowed" => Some(NeighborState::Allowed),
+                        "banned" => Some(NeighborState::Banned),
+                        _ => {
+                            return Err(invalid("state"));
+                        }
+                    };
+                }
+                "address" => {
+                    let addr = value.parse::<IpAddr>().map_err(|_e| invalid("address"))?;
+                    neighbors_query.address = Some(PeerAddress::from_ip(&addr));
+                }
+                "offset" => {
+                    neighbors_query.offset =
+                        value.parse::<u32>().map_err(|_e| invalid("offset"))?;
+                }
+                "limit" => {
+                    neighbors_query.limit =
+                        Some(value.parse::<u32>().map_err(|_e| invalid("limit"))?);
+                }
+                _ => {}
+            }
+        }
+        Ok(neighbors_query)
+    }
+
+    fn parse_get_transfer_cost<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _regex: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetTransferCost".to_string(),
+            ));
+        }
+
+        Ok(HttpRequestType::GetTransferCost(
+            HttpRequestMetadata::from_preamble(preamble),
+        ))
+    }
+
+    fn parse_get_fee_estimate<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _regex: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetFeeEstimate".to_string(),
+            ));
+        }
+
+        let len = query
+            .and_then(|query_string| {
+                form_urlencoded::parse(query_string.as_bytes())
+                    .find(|(key, _v)| key == "len")
+                    .and_then(|(_k, value)| value.parse::<u64>().ok())
+            })
+            .ok_or_else(|| {
+                net_error::ClientError(ClientError::Message(
+                    "Missing or invalid transaction length".to_string(),
+                ))
+            })?;
+
+        Ok(HttpRequestType::GetFeeEstimate(
+            HttpRequestMetadata::from_preamble(preamble),
+            len,
+        ))
+    }
+
+    /// check whether the given option query string
+    ///   sets proof=0 (setting proof to false).
+    /// Defaults to _true_
+    fn get_proof_query(query: Option<&str>) -> bool {
+        let no_proof = if let Some(query_string) = query {
+            form_urlencoded::parse(query_string.as_bytes())
+                .find(|(key, _v)| key == "proof")
+                .map(|(_k, value)| value == "0")
+                .unwrap_or(false)
+        } else {
+            false
+        };
+
+        !no_proof
+    }
+
+    /// get the chain tip optional query argument (`tip`)
+    /// Take the first value we can parse.
+    fn get_chain_tip_query(query: Option<&str>) -> Option<StacksBlockId> {
+        match query {
+            Some(query_string) => {
+                for (key, value) in form_urlencoded::parse(query_string.as_bytes()) {
+                    if key != "tip" {
+                        continue;
+                    }
+
+                    if let Ok(tip) = StacksBlockId::from_hex(&value) {
+                        return Some(tip);
+                    }
+                }
+                return None;
+            }
+            None => {
+                return None;
+            }
+        }
+    }
+
+    fn parse_get_account<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetAccount".to_string(),
+            ));
+        }
+
+        let principal = PrincipalData::parse(&captures["principal"]).map_err(|_e| {
+            net_error::DeserializeError("Failed to parse account principal".into())
+        })?;
+
+        let with_proof = HttpRequestType::get_proof_query(query);
+        let tip = HttpRequestType::get_chain_tip_query(query);
+
+        Ok(HttpRequestType::GetAccount(
             HttpRequestMetadata::from_preamble(preamble),
             principal,
             tip,
@@ -1676,6 +2276,57 @@ impl HttpRequestType {
         ))
     }
 
+    fn parse_get_account_nonces<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetAccountNonces".to_string(),
+            ));
+        }
+
+        let principal = PrincipalData::parse(&captures["principal"]).map_err(|_e| {
+            net_error::DeserializeError("Failed to parse account principal".into())
+        })?;
+
+        let tip = HttpRequestType::get_chain_tip_query(query);
+
+        Ok(HttpRequestType::GetAccountNonces(
+            HttpRequestMetadata::from_preamble(preamble),
+            principal,
+            tip,
+        ))
+    }
+
+    fn parse_get_miner_rewards<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetMinerRewards".to_string(),
+            ));
+        }
+
+        let address = StacksAddress::from_string(&captures["address"])
+            .ok_or_else(|| net_error::DeserializeError("Failed to parse miner address".into()))?;
+
+        let tip = HttpRequestType::get_chain_tip_query(query);
+
+        Ok(HttpRequestType::GetMinerRewards(
+            HttpRequestMetadata::from_preamble(preamble),
+            address,
+            tip,
+        ))
+    }
+
     fn parse_get_map_entry<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
@@ -1705,11 +2356,11 @@ impl HttpRequestType {
         let map_name = ClarityName::try_from(captures["map"].to_string())
             .map_err(|_e| net_error::DeserializeError("Failed to parse contract name".into()))?;
 
-        let value_hex: String = serde_json::from_reader(fd)
+        let value_json: serde_json::Value = serde_json::from_reader(fd)
             .map_err(|_e| net_error::DeserializeError("Failed to parse JSON body".into()))?;
 
-        let value = Value::try_deserialize_hex_untyped(&value_hex)
-            .map_err(|_e| net_error::DeserializeError("Failed to deserialize key value".into()))?;
+        let value = value_from_request_json(&value_json)
+            .ok_or_else(|| net_error::DeserializeError("Failed to deserialize key value".into()))?;
 
         let with_proof = HttpRequestType::get_proof_query(query);
         let tip = HttpRequestType::get_chain_tip_query(query);
@@ -1763,7 +2414,7 @@ impl HttpRequestType {
         let arguments = body
             .arguments
             .into_iter()
-            .map(|hex| Value::try_deserialize_hex_untyped(&hex).ok())
+            .map(|arg| value_from_request_json(&arg))
             .collect::<Option<Vec<Value>>>()
             .ok_or_else(|| {
                 net_error::DeserializeError("Failed to deserialize argument value".into())
@@ -1782,6 +2433,184 @@ impl HttpRequestType {
         ))
     }
 
+    fn parse_multi_call_read_only<R: Read>(
+        protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _captures: &Captures,
+        query: Option<&str>,
+        fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        let content_len = preamble.get_content_length();
+        let max_len = protocol
+            .maximum_call_argument_size
+            .saturating_mul(MAX_MULTI_CALL_READS as u32);
+        if !(content_len > 0 && content_len < max_len) {
+            return Err(net_error::DeserializeError(format!(
+                "Invalid Http request: invalid body length for MultiCallReadOnly ({})",
+                content_len
+            )));
+        }
+
+        if preamble.content_type != Some(HttpContentType::JSON) {
+            return Err(net_error::DeserializeError(
+                "Invalid content-type: expected application/json".to_string(),
+            ));
+        }
+
+        let body: MultiCallReadOnlyRequestBody = serde_json::from_reader(fd)
+            .map_err(|_e| net_error::DeserializeError("Failed to parse JSON body".into()))?;
+
+        if body.calls.len() == 0 || body.calls.len() > MAX_MULTI_CALL_READS {
+            return Err(net_error::DeserializeError(format!(
+                "Invalid Http request: expected between 1 and {} calls",
+                MAX_MULTI_CALL_READS
+            )));
+        }
+
+        let sender = PrincipalData::parse(&body.sender)
+            .map_err(|_e| net_error::DeserializeError("Failed to parse sender principal".into()))?;
+
+        let mut calls = Vec::with_capacity(body.calls.len());
+        for call in body.calls.into_iter() {
+            let contract_address =
+                StacksAddress::from_string(&call.contract_address).ok_or_else(|| {
+                    net_error::DeserializeError("Failed to parse contract address".into())
+                })?;
+            let contract_name = ContractName::try_from(call.contract_name).map_err(|_e| {
+                net_error::DeserializeError("Failed to parse contract name".into())
+            })?;
+            let function_name = ClarityName::try_from(call.function_name).map_err(|_e| {
+                net_error::DeserializeError("Failed to parse function name".into())
+            })?;
+            let arguments = call
+                .arguments
+                .into_iter()
+                .map(|arg| value_from_request_json(&arg))
+                .collect::<Option<Vec<Value>>>()
+                .ok_or_else(|| {
+                    net_error::DeserializeError("Failed to deserialize argument value".into())
+                })?;
+            calls.push(ReadOnlyCall {
+                contract_address,
+                contract_name,
+                function_name,
+                arguments,
+            });
+        }
+
+        let tip = HttpRequestType::get_chain_tip_query(query);
+
+        Ok(HttpRequestType::MultiCallReadOnly(
+            HttpRequestMetadata::from_preamble(preamble),
+            sender,
+            calls,
+            tip,
+        ))
+    }
+
+    /// Admin requests with a body carry a small JSON object
+    fn parse_admin_json_body<R: Read, T: DeserializeOwned>(
+        preamble: &HttpRequestPreamble,
+        fd: &mut R,
+    ) -> Result<T, net_error> {
+        if preamble.get_content_length() == 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected non-zero-length body".to_string(),
+            ));
+        }
+
+        if preamble.content_type != Some(HttpContentType::JSON) {
+            return Err(net_error::DeserializeError(
+                "Invalid content-type: expected application/json".to_string(),
+            ));
+        }
+
+        serde_json::from_reader(fd)
+            .map_err(|_e| net_error::DeserializeError("Failed to parse JSON body".into()))
+    }
+
+    fn parse_admin_ban_peer<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _captures: &Captures,
+        _query: Option<&str>,
+        fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        let body: AdminBanPeerRequestBody = HttpRequestType::parse_admin_json_body(preamble, fd)?;
+        let addr = body.address.parse::<IpAddr>().map_err(|_e| {
+            net_error::ClientError(ClientError::Message("Failed to parse address".to_string()))
+        })?;
+
+        Ok(HttpRequestType::AdminBanPeer(
+            HttpRequestMetadata::from_preamble(preamble),
+            PeerAddress::from_ip(&addr),
+            body.port,
+            body.duration.unwrap_or(DEFAULT_ADMIN_BAN_DURATION),
+        ))
+    }
+
+    fn parse_admin_evict_transaction<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for AdminEvictTransaction"
+                    .to_string(),
+            ));
+        }
+
+        let txid_str = captures
+            .get(1)
+            .ok_or(net_error::DeserializeError(
+                "Failed to match path to txid".to_string(),
+            ))?
+            .as_str();
+        let txid = Txid::from_hex(txid_str)
+            .map_err(|_e| net_error::DeserializeError("Failed to parse txid".to_string()))?;
+
+        Ok(HttpRequestType::AdminEvictTransaction(
+            HttpRequestMetadata::from_preamble(preamble),
+            txid,
+        ))
+    }
+
+    fn parse_admin_flush_chainstate<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _captures: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for AdminFlushChainstate".to_string(),
+            ));
+        }
+
+        Ok(HttpRequestType::AdminFlushChainstate(
+            HttpRequestMetadata::from_preamble(preamble),
+        ))
+    }
+
+    fn parse_admin_set_mining<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _captures: &Captures,
+        _query: Option<&str>,
+        fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        let body: AdminSetMiningRequestBody = HttpRequestType::parse_admin_json_body(preamble, fd)?;
+
+        Ok(HttpRequestType::AdminSetMining(
+            HttpRequestMetadata::from_preamble(preamble),
+            body.enabled,
+        ))
+    }
+
     fn parse_get_contract_arguments(
         preamble: &HttpRequestPreamble,
         captures: &Captures,
@@ -1967,6 +2796,37 @@ impl HttpRequestType {
         ))
     }
 
+    fn parse_get_transaction_receipt<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetTransactionReceipt"
+                    .to_string(),
+            ));
+        }
+
+        let txid_str = captures
+            .get(1)
+            .ok_or(net_error::DeserializeError(
+                "Failed to match path to txid group".to_string(),
+            ))?
+            .as_str();
+
+        let txid = Txid::from_hex(txid_str)
+            .map_err(|_e| net_error::DeserializeError("Failed to parse txid".to_string()))?;
+
+        Ok(HttpRequestType::GetTransactionReceipt(
+            HttpRequestMetadata::from_preamble(preamble),
+            txid,
+            HttpRequestType::get_chain_tip_query(query),
+        ))
+    }
+
     fn parse_posttransaction<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
@@ -2055,6 +2915,216 @@ impl HttpRequestType {
         ))
     }
 
+    fn parse_post_validate_block<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _regex: &Captures,
+        query: Option<&str>,
+        fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() == 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected non-zero-length body for ValidateBlock".to_string(),
+            ));
+        }
+
+        // content-type must be given, and must be application/octet-stream
+        match preamble.content_type {
+            None => {
+                return Err(net_error::DeserializeError(
+                    "Missing Content-Type for block".to_string(),
+                ));
+            }
+            Some(ref c) => {
+                if *c != HttpContentType::Bytes {
+                    return Err(net_error::DeserializeError(
+                        "Wrong Content-Type for block; expected application/octet-stream"
+                            .to_string(),
+                    ));
+                }
+            }
+        };
+
+        // don't use StacksBlock::consensus_deserialize(), since it rejects blocks that fail the
+        // static checks we want to report on
+        let header: StacksBlockHeader = read_next(fd)?;
+        let txs: Vec<StacksTransaction> = read_next(fd)?;
+        let tip = HttpRequestType::get_chain_tip_query(query);
+
+        Ok(HttpRequestType::ValidateBlock(
+            HttpRequestMetadata::from_preamble(preamble),
+            StacksBlock { header, txs },
+            tip,
+        ))
+    }
+
+    fn parse_post_faucet_stx<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _regex: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for PostFaucetStx".to_string(),
+            ));
+        }
+
+        let recipient = query
+            .and_then(|query_string| {
+                form_urlencoded::parse(query_string.as_bytes())
+                    .find(|(key, _v)| key == "address")
+                    .and_then(|(_k, value)| PrincipalData::parse(&value).ok())
+            })
+            .ok_or_else(|| {
+                net_error::ClientError(ClientError::Message(
+                    "Missing or invalid recipient address".to_string(),
+                ))
+            })?;
+
+        Ok(HttpRequestType::PostFaucetStx(
+            HttpRequestMetadata::from_preamble(preamble),
+            recipient,
+        ))
+    }
+
+    fn parse_get_attachment<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetAttachment".to_string(),
+            ));
+        }
+
+        let content_hash_str = captures
+            .get(1)
+            .ok_or(net_error::DeserializeError(
+                "Failed to match path to attachment hash group".to_string(),
+            ))?
+            .as_str();
+
+        let content_hash = Hash160::from_hex(content_hash_str).map_err(|_e| {
+            net_error::DeserializeError("Failed to parse attachment hash".to_string())
+        })?;
+
+        Ok(HttpRequestType::GetAttachment(
+            HttpRequestMetadata::from_preamble(preamble),
+            content_hash,
+        ))
+    }
+
+    fn parse_post_attachment<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _regex: &Captures,
+        _query: Option<&str>,
+        fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        let content_length = preamble.get_content_length() as u64;
+        if content_length == 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected non-zero-length body for PostAttachment"
+                    .to_string(),
+            ));
+        }
+        if content_length > MAX_ATTACHMENT_SIZE {
+            return Err(net_error::ClientError(ClientError::Message(format!(
+                "Attachment is too big: at most {} bytes allowed",
+                MAX_ATTACHMENT_SIZE
+            ))));
+        }
+
+        // content-type must be given, and must be application/octet-stream
+        match preamble.content_type {
+            None => {
+                return Err(net_error::DeserializeError(
+                    "Missing Content-Type for attachment".to_string(),
+                ));
+            }
+            Some(ref c) => {
+                if *c != HttpContentType::Bytes {
+                    return Err(net_error::DeserializeError(
+                        "Wrong Content-Type for attachment; expected application/octet-stream"
+                            .to_string(),
+                    ));
+                }
+            }
+        };
+
+        let mut content = vec![];
+        fd.take(content_length)
+            .read_to_end(&mut content)
+            .map_err(net_error::ReadError)?;
+        if (content.len() as u64) != content_length {
+            return Err(net_error::DeserializeError(
+                "Attachment is shorter than its Content-Length".to_string(),
+            ));
+        }
+
+        Ok(HttpRequestType::PostAttachment(
+            HttpRequestMetadata::from_preamble(preamble),
+            Attachment::new(content),
+        ))
+    }
+
+    fn parse_websocket_upgrade<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _captures: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for WebSocketUpgrade".to_string(),
+            ));
+        }
+
+        let is_upgrade = preamble.headers.get("connection").map(|v| v.as_str()) == Some("upgrade")
+            && preamble
+                .headers
+                .get("upgrade")
+                .map(|v| v.to_lowercase() == "websocket")
+                .unwrap_or(false);
+        if !is_upgrade {
+            return Err(net_error::ClientError(ClientError::Message(
+                "Expected a WebSocket upgrade request".to_string(),
+            )));
+        }
+
+        if preamble
+            .headers
+            .get("sec-websocket-version")
+            .map(|v| v.trim() != "13")
+            .unwrap_or(true)
+        {
+            return Err(net_error::ClientError(ClientError::Message(
+                "Unsupported WebSocket version; expected 13".to_string(),
+            )));
+        }
+
+        // the key is 16 random bytes, base64-encoded
+        let client_key = match preamble.headers.get("sec-websocket-key") {
+            Some(key) if key.trim().len() == 24 => key.trim().to_string(),
+            _ => {
+                return Err(net_error::ClientError(ClientError::Message(
+                    "Missing or invalid Sec-WebSocket-Key".to_string(),
+                )));
+            }
+        };
+
+        Ok(HttpRequestType::WebSocketUpgrade(
+            HttpRequestMetadata::from_preamble(preamble),
+            client_key,
+        ))
+    }
+
     fn parse_options_preflight<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
@@ -2068,24 +3138,78 @@ impl HttpRequestType {
         ))
     }
 
+    /// Is this a request to one of the `/v2/admin/*` endpoints (see net::admin)?
+    pub fn is_admin(&self) -> bool {
+        match self {
+            HttpRequestType::AdminBanPeer(..)
+            | HttpRequestType::AdminEvictTransaction(..)
+            | HttpRequestType::AdminFlushChainstate(..)
+            | HttpRequestType::AdminSetMining(..) => true,
+            _ => false,
+        }
+    }
+
+    /// Is the answer to this request the same for as long as the node runs?  Blocks,
+    /// microblocks, and attachments are content-addressed, so their responses can be cached
+    /// indefinitely.
+    pub fn is_immutable(&self) -> bool {
+        match self {
+            HttpRequestType::GetBlock(..)
+            | HttpRequestType::GetMicroblocksIndexed(..)
+            | HttpRequestType::GetMicroblocksConfirmed(..)
+            | HttpRequestType::GetAttachment(..) => true,
+            _ => false,
+        }
+    }
+
+    /// May the response to this request be compressed?  Only the large payloads are:  blocks,
+    /// microblocks, contract interfaces, and contract source code.
+    pub fn is_compressible(&self) -> bool {
+        match self {
+            HttpRequestType::GetBlock(..)
+            | HttpRequestType::GetMicroblocksIndexed(..)
+            | HttpRequestType::GetMicroblocksConfirmed(..)
+            | HttpRequestType::GetMicroblocksUnconfirmed(..)
+            | HttpRequestType::GetContractABI(..)
+            | HttpRequestType::GetContractSrc(..) => true,
+            _ => false,
+        }
+    }
+
     pub fn metadata(&self) -> &HttpRequestMetadata {
         match *self {
             HttpRequestType::GetInfo(ref md) => md,
             HttpRequestType::GetPoxInfo(ref md, _) => md,
-            HttpRequestType::GetNeighbors(ref md) => md,
+            HttpRequestType::GetBurnEta(ref md, _) => md,
+            HttpRequestType::GetFeeEstimate(ref md, _) => md,
+            HttpRequestType::GetForks(ref md) => md,
+            HttpRequestType::GetNeighbors(ref md, _) => md,
             HttpRequestType::GetBlock(ref md, _) => md,
             HttpRequestType::GetMicroblocksIndexed(ref md, _) => md,
             HttpRequestType::GetMicroblocksConfirmed(ref md, _) => md,
             HttpRequestType::GetMicroblocksUnconfirmed(ref md, _, _) => md,
             HttpRequestType::PostTransaction(ref md, _) => md,
+            HttpRequestType::GetTransactionReceipt(ref md, ..) => md,
             HttpRequestType::PostMicroblock(ref md, ..) => md,
+            HttpRequestType::ValidateBlock(ref md, ..) => md,
+            HttpRequestType::PostFaucetStx(ref md, _) => md,
+            HttpRequestType::GetAttachment(ref md, _) => md,
+            HttpRequestType::PostAttachment(ref md, _) => md,
+            HttpRequestType::WebSocketUpgrade(ref md, _) => md,
             HttpRequestType::GetAccount(ref md, ..) => md,
+            HttpRequestType::GetAccountNonces(ref md, ..) => md,
+            HttpRequestType::GetMinerRewards(ref md, ..) => md,
             HttpRequestType::GetMapEntry(ref md, ..) => md,
             HttpRequestType::GetTransferCost(ref md) => md,
             HttpRequestType::GetContractABI(ref md, ..) => md,
             HttpRequestType::GetContractSrc(ref md, ..) => md,
             HttpRequestType::CallReadOnlyFunction(ref md, ..) => md,
+            HttpRequestType::MultiCallReadOnly(ref md, ..) => md,
             HttpRequestType::OptionsPreflight(ref md, ..) => md,
+            HttpRequestType::AdminBanPeer(ref md, ..) => md,
+            HttpRequestType::AdminEvictTransaction(ref md, ..) => md,
+            HttpRequestType::AdminFlushChainstate(ref md) => md,
+            HttpRequestType::AdminSetMining(ref md, ..) => md,
             HttpRequestType::ClientError(ref md, ..) => md,
         }
     }
@@ -2094,20 +3218,36 @@ impl HttpRequestType {
         match *self {
             HttpRequestType::GetInfo(ref mut md) => md,
             HttpRequestType::GetPoxInfo(ref mut md, _) => md,
-            HttpRequestType::GetNeighbors(ref mut md) => md,
+            HttpRequestType::GetBurnEta(ref mut md, _) => md,
+            HttpRequestType::GetFeeEstimate(ref mut md, _) => md,
+            HttpRequestType::GetForks(ref mut md) => md,
+            HttpRequestType::GetNeighbors(ref mut md, _) => md,
             HttpRequestType::GetBlock(ref mut md, _) => md,
             HttpRequestType::GetMicroblocksIndexed(ref mut md, _) => md,
             HttpRequestType::GetMicroblocksConfirmed(ref mut md, _) => md,
             HttpRequestType::GetMicroblocksUnconfirmed(ref mut md, _, _) => md,
             HttpRequestType::PostTransaction(ref mut md, _) => md,
+            HttpRequestType::GetTransactionReceipt(ref mut md, ..) => md,
             HttpRequestType::PostMicroblock(ref mut md, ..) => md,
+            HttpRequestType::ValidateBlock(ref mut md, ..) => md,
+            HttpRequestType::PostFaucetStx(ref mut md, _) => md,
+            HttpRequestType::GetAttachment(ref mut md, _) => md,
+            HttpRequestType::PostAttachment(ref mut md, _) => md,
+            HttpRequestType::WebSocketUpgrade(ref mut md, _) => md,
             HttpRequestType::GetAccount(ref mut md, ..) => md,
+            HttpRequestType::GetAccountNonces(ref mut md, ..) => md,
+            HttpRequestType::GetMinerRewards(ref mut md, ..) => md,
             HttpRequestType::GetMapEntry(ref mut md, ..) => md,
             HttpRequestType::GetTransferCost(ref mut md) => md,
             HttpRequestType::GetContractABI(ref mut md, ..) => md,
             HttpRequestType::GetContractSrc(ref mut md, ..) => md,
             HttpRequestType::CallReadOnlyFunction(ref mut md, ..) => md,
+            HttpRequestType::MultiCallReadOnly(ref mut md, ..) => md,
             HttpRequestType::OptionsPreflight(ref mut md, ..) => md,
+            HttpRequestType::AdminBanPeer(ref mut md, ..) => md,
+            HttpRequestType::AdminEvictTransaction(ref mut md, ..) => md,
+            HttpRequestType::AdminFlushChainstate(ref mut md) => md,
+            HttpRequestType::AdminSetMining(ref mut md, ..) => md,
             HttpRequestType::ClientError(ref mut md, ..) => md,
         }
     }
@@ -2122,6 +3262,37 @@ impl HttpRequestType {
         }
     }
 
+    fn make_neighbors_query_string(neighbors_query: &NeighborsQuery) -> String {
+        let mut args = vec![];
+        if let Some(direction) = neighbors_query.direction {
+            args.push(match direction {
+                NeighborDirection::Inbound => "direction=inbound".to_string(),
+                NeighborDirection::Outbound => "direction=outbound".to_string(),
+            });
+        }
+        if let Some(state) = neighbors_query.state {
+            args.push(match state {
+                NeighborState::Allowed => "state=allowed".to_string(),
+                NeighborState::Banned => "state=banned".to_string(),
+            });
+        }
+        if let Some(ref addr) = neighbors_query.address {
+            args.push(format!("address={}", addr.to_socketaddr(0).ip()));
+        }
+        if neighbors_query.offset > 0 {
+            args.push(format!("offset={}", neighbors_query.offset));
+        }
+        if let Some(limit) = neighbors_query.limit {
+            args.push(format!("limit={}", limit));
+        }
+
+        if args.len() > 0 {
+            format!("?{}", args.join("&"))
+        } else {
+            "".to_string()
+        }
+    }
+
     pub fn request_path(&self) -> String {
         match self {
             HttpRequestType::GetInfo(_md) => "/v2/info".to_string(),
@@ -2129,7 +3300,12 @@ impl HttpRequestType {
                 "/v2/pox{}",
                 HttpRequestType::make_query_string(tip_opt.as_ref(), true)
             ),
-            HttpRequestType::GetNeighbors(_md) => "/v2/neighbors".to_string(),
+            HttpRequestType::GetBurnEta(_md, height) => format!("/v2/burn_eta?height={}", height),
+            HttpRequestType::GetForks(_md) => "/v2/forks".to_string(),
+            HttpRequestType::GetNeighbors(_md, neighbors_query) => format!(
+                "/v2/neighbors{}",
+                HttpRequestType::make_neighbors_query_string(neighbors_query)
+            ),
             HttpRequestType::GetBlock(_md, block_hash) => {
                 format!("/v2/blocks/{}", block_hash.to_hex())
             }
@@ -2145,15 +3321,42 @@ impl HttpRequestType {
                 min_seq
             ),
             HttpRequestType::PostTransaction(_md, ..) => "/v2/transactions".to_string(),
+            HttpRequestType::GetTransactionReceipt(_md, txid, tip_opt) => format!(
+                "/v2/transactions/{}/receipt{}",
+                txid.to_hex(),
+                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
+            ),
             HttpRequestType::PostMicroblock(_md, _, tip_opt) => format!(
                 "/v2/microblocks{}",
                 HttpRequestType::make_query_string(tip_opt.as_ref(), true)
             ),
+            HttpRequestType::ValidateBlock(_md, _, tip_opt) => format!(
+                "/v2/blocks/validate{}",
+                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
+            ),
+            HttpRequestType::PostFaucetStx(_md, recipient) => {
+                format!("/v2/faucet/stx?address={}", recipient)
+            }
+            HttpRequestType::GetAttachment(_md, content_hash) => {
+                format!("/v2/attachments/{}", content_hash.to_hex())
+            }
+            HttpRequestType::PostAttachment(_md, ..) => "/v2/attachments".to_string(),
+            HttpRequestType::WebSocketUpgrade(_md, ..) => "/v2/events".to_string(),
             HttpRequestType::GetAccount(_md, principal, tip_opt, with_proof) => format!(
                 "/v2/accounts/{}{}",
                 &principal.to_string(),
                 HttpRequestType::make_query_string(tip_opt.as_ref(), *with_proof)
             ),
+            HttpRequestType::GetAccountNonces(_md, principal, tip_opt) => format!(
+                "/v2/accounts/{}/nonces{}",
+                &principal.to_string(),
+                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
+            ),
+            HttpRequestType::GetMinerRewards(_md, address, tip_opt) => format!(
+                "/v2/miners/{}/rewards{}",
+                &address.to_string(),
+                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
+            ),
             HttpRequestType::GetMapEntry(
                 _md,
                 contract_addr,
@@ -2170,6 +3373,9 @@ impl HttpRequestType {
                 HttpRequestType::make_query_string(tip_opt.as_ref(), *with_proof)
             ),
             HttpRequestType::GetTransferCost(_md) => "/v2/fees/transfer".into(),
+            HttpRequestType::GetFeeEstimate(_md, len) => {
+                format!("/v2/fees/transaction?len={}", len)
+            }
             HttpRequestType::GetContractABI(_, contract_addr, contract_name, tip_opt) => format!(
                 "/v2/contracts/interface/{}/{}{}",
                 contract_addr,
@@ -2203,7 +3409,17 @@ impl HttpRequestType {
                 func_name.as_str(),
                 HttpRequestType::make_query_string(tip_opt.as_ref(), true)
             ),
+            HttpRequestType::MultiCallReadOnly(_, _, _, tip_opt) => format!(
+                "/v2/contracts/multi-call-read{}",
+                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
+            ),
             HttpRequestType::OptionsPreflight(_md, path) => path.to_string(),
+            HttpRequestType::AdminBanPeer(..) => "/v2/admin/peers/ban".to_string(),
+            HttpRequestType::AdminEvictTransaction(_md, txid) => {
+                format!("/v2/admin/mempool/evict/{}", txid)
+            }
+            HttpRequestType::AdminFlushChainstate(_md) => "/v2/admin/chainstate/flush".to_string(),
+            HttpRequestType::AdminSetMining(..) => "/v2/admin/miner".to_string(),
             HttpRequestType::ClientError(_md, e) => match e {
                 ClientError::NotFound(path) => path.to_string(),
                 _ => "error path unknown".into(),
@@ -2211,6 +3427,35 @@ impl HttpRequestType {
         }
     }
 
+    fn send_admin_json<W: Write, T: Serialize>(
+        fd: &mut W,
+        md: &HttpRequestMetadata,
+        request_path: &str,
+        request_body: &T,
+    ) -> Result<(), net_error> {
+        let mut request_body_bytes = vec![];
+        serde_json::to_writer(&mut request_body_bytes, request_body).map_err(|e| {
+            net_error::SerializeError(format!(
+                "Failed to serialize admin request to JSON: {:?}",
+                &e
+            ))
+        })?;
+
+        HttpRequestPreamble::new_serialized(
+            fd,
+            &md.version,
+            "POST",
+            request_path,
+            &md.peer,
+            md.keep_alive,
+            Some(request_body_bytes.len() as u32),
+            Some(&HttpContentType::JSON),
+            |fd| api_key_headers(fd, md),
+        )?;
+        fd.write_all(&request_body_bytes)
+            .map_err(net_error::WriteError)
+    }
+
     pub fn send<W: Write>(&self, _protocol: &mut StacksHttp, fd: &mut W) -> Result<(), net_error> {
         match self {
             HttpRequestType::PostTransaction(md, tx) => {
@@ -2226,7 +3471,7 @@ impl HttpRequestType {
                     md.keep_alive,
                     Some(tx_bytes.len() as u32),
                     Some(&HttpContentType::Bytes),
-                    empty_headers,
+                    |fd| api_key_headers(fd, md),
                 )?;
                 fd.write_all(&tx_bytes).map_err(net_error::WriteError)?;
             }
@@ -2243,10 +3488,78 @@ impl HttpRequestType {
                     md.keep_alive,
                     Some(mb_bytes.len() as u32),
                     Some(&HttpContentType::Bytes),
-                    empty_headers,
+                    |fd| api_key_headers(fd, md),
                 )?;
                 fd.write_all(&mb_bytes).map_err(net_error::WriteError)?;
             }
+            HttpRequestType::ValidateBlock(md, block, ..) => {
+                let mut block_bytes = vec![];
+                write_next(&mut block_bytes, block)?;
+
+                HttpRequestPreamble::new_serialized(
+                    fd,
+                    &md.version,
+                    "POST",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
+                    Some(block_bytes.len() as u32),
+                    Some(&HttpContentType::Bytes),
+                    |fd| api_key_headers(fd, md),
+                )?;
+                fd.write_all(&block_bytes).map_err(net_error::WriteError)?;
+            }
+            HttpRequestType::PostAttachment(md, attachment) => {
+                HttpRequestPreamble::new_serialized(
+                    fd,
+                    &md.version,
+                    "POST",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
+                    Some(attachment.content.len() as u32),
+                    Some(&HttpContentType::Bytes),
+                    |fd| api_key_headers(fd, md),
+                )?;
+                fd.write_all(&attachment.content)
+                    .map_err(net_error::WriteError)?;
+            }
+            HttpRequestType::WebSocketUpgrade(md, client_key) => {
+                HttpRequestPreamble::new_serialized(
+                    fd,
+                    &md.version,
+                    "GET",
+                    &self.request_path(),
+                    &md.peer,
+                    true,
+                    None,
+                    None,
+                    |fd| {
+                        api_key_headers(fd, md)?;
+                        fd.write_all(
+                            format!(
+                                "Connection: Upgrade\r\nUpgrade: websocket\r\nSec-WebSocket-Version: 13\r\nSec-WebSocket-Key: {}\r\n",
+                                client_key
+                            )
+                            .as_bytes(),
+                        )
+                        .map_err(net_error::WriteError)
+                    },
+                )?;
+            }
+            HttpRequestType::PostFaucetStx(md, ..) => {
+                HttpRequestPreamble::new_serialized(
+                    fd,
+                    &md.version,
+                    "POST",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
+                    Some(0),
+                    None,
+                    |fd| api_key_headers(fd, md),
+                )?;
+            }
             HttpRequestType::GetMapEntry(
                 md,
                 _contract_addr,
@@ -2269,7 +3582,7 @@ impl HttpRequestType {
                     md.keep_alive,
                     Some(request_json.as_bytes().len() as u32),
                     Some(&HttpContentType::JSON),
-                    empty_headers,
+                    |fd| api_key_headers(fd, md),
                 )?;
                 fd.write_all(&request_json.as_bytes())
                     .map_err(net_error::WriteError)?;
@@ -2288,7 +3601,7 @@ impl HttpRequestType {
                     let mut arg_bytes = vec![];
                     arg.serialize_write(&mut arg_bytes)
                         .map_err(net_error::WriteError)?;
-                    args.push(to_hex(&arg_bytes));
+                    args.push(to_hex(&arg_bytes).into());
                 }
 
                 let request_body = CallReadOnlyRequestBody {
@@ -2313,11 +3626,82 @@ impl HttpRequestType {
                     md.keep_alive,
                     Some(request_body_bytes.len() as u32),
                     Some(&HttpContentType::JSON),
-                    empty_headers,
+                    |fd| api_key_headers(fd, md),
+                )?;
+                fd.write_all(&request_body_bytes)
+                    .map_err(net_error::WriteError)?;
+            }
+            HttpRequestType::MultiCallReadOnly(md, sender, calls, ..) => {
+                let mut call_bodies = vec![];
+                for call in calls.iter() {
+                    let mut args = vec![];
+                    for arg in call.arguments.iter() {
+                        let mut arg_bytes = vec![];
+                        arg.serialize_write(&mut arg_bytes)
+                            .map_err(net_error::WriteError)?;
+                        args.push(to_hex(&arg_bytes).into());
+                    }
+                    call_bodies.push(MultiCallReadOnlyCallBody {
+                        contract_address: call.contract_address.to_string(),
+                        contract_name: call.contract_name.as_str().to_string(),
+                        function_name: call.function_name.as_str().to_string(),
+                        arguments: args,
+                    });
+                }
+
+                let request_body = MultiCallReadOnlyRequestBody {
+                    sender: sender.to_string(),
+                    calls: call_bodies,
+                };
+
+                let mut request_body_bytes = vec![];
+                serde_json::to_writer(&mut request_body_bytes, &request_body).map_err(|e| {
+                    net_error::SerializeError(format!(
+                        "Failed to serialize read-only calls to JSON: {:?}",
+                        &e
+                    ))
+                })?;
+
+                HttpRequestPreamble::new_serialized(
+                    fd,
+                    &md.version,
+                    "POST",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
+                    Some(request_body_bytes.len() as u32),
+                    Some(&HttpContentType::JSON),
+                    |fd| api_key_headers(fd, md),
                 )?;
                 fd.write_all(&request_body_bytes)
                     .map_err(net_error::WriteError)?;
             }
+            HttpRequestType::AdminBanPeer(md, addr, port, duration) => {
+                let request_body = AdminBanPeerRequestBody {
+                    address: addr.to_socketaddr(*port).ip().to_string(),
+                    port: *port,
+                    duration: Some(*duration),
+                };
+                HttpRequestType::send_admin_json(fd, md, &self.request_path(), &request_body)?;
+            }
+            HttpRequestType::AdminSetMining(md, enabled) => {
+                let request_body = AdminSetMiningRequestBody { enabled: *enabled };
+                HttpRequestType::send_admin_json(fd, md, &self.request_path(), &request_body)?;
+            }
+            HttpRequestType::AdminEvictTransaction(md, ..)
+            | HttpRequestType::AdminFlushChainstate(md) => {
+                HttpRequestPreamble::new_serialized(
+                    fd,
+                    &md.version,
+                    "POST",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
+                    None,
+                    None,
+                    |fd| api_key_headers(fd, md),
+                )?;
+            }
             other_type => {
                 let md = other_type.metadata();
                 let request_path = other_type.request_path();
@@ -2330,7 +3714,10 @@ impl HttpRequestType {
                     md.keep_alive,
                     None,
                     None,
-                    empty_headers,
+                    |fd| {
+                        api_key_headers(fd, md)?;
+                        range_headers(fd, md)
+                    },
                 )?;
             }
         }
@@ -2445,6 +3832,48 @@ impl HttpResponseType {
         Ok(item)
     }
 
+    /// Parse the body of a 206 Partial Content response as raw bytes
+    fn parse_partial_content<R: Read>(
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let max_len = MAX_MESSAGE_LEN as u64;
+        let mut bytes = vec![];
+        if preamble.is_chunked() && len_hint.is_none() {
+            let mut chunked_fd = HttpChunkedTransferReader::from_reader(fd, max_len);
+            chunked_fd
+                .read_to_end(&mut bytes)
+                .map_err(net_error::ReadError)?;
+        } else {
+            let content_length = match (preamble.content_length, len_hint) {
+                (Some(l), _) => l as u64,
+                (None, Some(l)) => l as u64,
+                (None, None) => {
+                    return Err(net_error::DeserializeError(
+                        "Invalid headers: need either Transfer-Encoding or Content-Length"
+                            .to_string(),
+                    ));
+                }
+            };
+            if content_length > max_len {
+                return Err(net_error::DeserializeError(
+                    "Invalid Content-Length header: too long".to_string(),
+                ));
+            }
+            let mut bound_fd = BoundReader::from_reader(fd, content_length);
+            bound_fd
+                .read_to_end(&mut bytes)
+                .map_err(net_error::ReadError)?;
+        }
+
+        Ok(HttpResponseType::PartialContent(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            bytes,
+        ))
+    }
+
     fn parse_json<R: Read, T: serde::de::DeserializeOwned>(
         preamble: &HttpResponsePreamble,
         fd: &mut R,
@@ -2548,6 +3977,14 @@ impl HttpResponseType {
         if preamble.status_code >= 400 {
             return HttpResponseType::parse_error(protocol, request_version, preamble, fd);
         }
+        if preamble.status_code == 206 {
+            return HttpResponseType::parse_partial_content(
+                request_version,
+                preamble,
+                fd,
+                len_hint,
+            );
+        }
 
         // TODO: make this static somehow
         let RESPONSE_METHODS: &[(
@@ -2562,6 +3999,12 @@ impl HttpResponseType {
         )] = &[
             (&PATH_GETINFO, &HttpResponseType::parse_peerinfo),
             (&PATH_GETPOXINFO, &HttpResponseType::parse_poxinfo),
+            (&PATH_GETBURNETA, &HttpResponseType::parse_burneta),
+            (
+                &PATH_GET_FEE_ESTIMATE,
+                &HttpResponseType::parse_fee_estimate,
+            ),
+            (&PATH_GETFORKS, &HttpResponseType::parse_forks),
             (&PATH_GETNEIGHBORS, &HttpResponseType::parse_neighbors),
             (&PATH_GETBLOCK, &HttpResponseType::parse_block),
             (
@@ -2577,11 +4020,28 @@ impl HttpResponseType {
                 &HttpResponseType::parse_microblocks_unconfirmed,
             ),
             (&PATH_POSTTRANSACTION, &HttpResponseType::parse_txid),
+            (
+                &PATH_GET_TRANSACTION_RECEIPT,
+                &HttpResponseType::parse_transaction_receipt,
+            ),
+            (&PATH_POST_FAUCET_STX, &HttpResponseType::parse_txid),
             (
                 &PATH_POSTMICROBLOCK,
                 &HttpResponseType::parse_microblock_hash,
             ),
+            (
+                &PATH_POST_VALIDATE_BLOCK,
+                &HttpResponseType::parse_block_validation,
+            ),
             (&PATH_GET_ACCOUNT, &HttpResponseType::parse_get_account),
+            (
+                &PATH_GET_ACCOUNT_NONCES,
+                &HttpResponseType::parse_get_account_nonces,
+            ),
+            (
+                &PATH_GET_MINER_REWARDS,
+                &HttpResponseType::parse_get_miner_rewards,
+            ),
             (
                 &PATH_GET_CONTRACT_SRC,
                 &HttpResponseType::parse_get_contract_src,
@@ -2594,7 +4054,35 @@ impl HttpResponseType {
                 &PATH_POST_CALL_READ_ONLY,
                 &HttpResponseType::parse_call_read_only,
             ),
+            (
+                &PATH_POST_MULTI_CALL_READ_ONLY,
+                &HttpResponseType::parse_multi_call_read_only,
+            ),
             (&PATH_GET_MAP_ENTRY, &HttpResponseType::parse_get_map_entry),
+            (
+                &PATH_GET_ATTACHMENT,
+                &HttpResponseType::parse_get_attachment,
+            ),
+            (
+                &PATH_POST_ATTACHMENT,
+                &HttpResponseType::parse_attachment_hash,
+            ),
+            (
+                &PATH_POST_ADMIN_BAN_PEER,
+                &HttpResponseType::parse_admin_action,
+            ),
+            (
+                &PATH_POST_ADMIN_EVICT_TRANSACTION,
+                &HttpResponseType::parse_admin_action,
+            ),
+            (
+                &PATH_POST_ADMIN_FLUSH_CHAINSTATE,
+                &HttpResponseType::parse_admin_action,
+            ),
+            (
+                &PATH_POST_ADMIN_MINER,
+                &HttpResponseType::parse_admin_action,
+            ),
         ];
 
         // use url::Url to parse path and query string
@@ -2673,6 +4161,65 @@ impl HttpResponseType {
         ))
     }
 
+    fn parse_burneta<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let burn_eta =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::BurnEta(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            burn_eta,
+        ))
+    }
+
+    fn parse_fee_estimate<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let fee_estimate =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::FeeEstimate(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            fee_estimate,
+        ))
+    }
+
+    fn parse_block_validation<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let validation =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::BlockValidation(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            validation,
+        ))
+    }
+
+    fn parse_forks<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let forks = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::Forks(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            forks,
+        ))
+    }
+
     fn parse_neighbors<R: Read>(
         _protocol: &mut StacksHttp,
         request_version: HttpVersion,
@@ -2733,6 +4280,34 @@ impl HttpResponseType {
         ))
     }
 
+    fn parse_get_account_nonces<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let nonces = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetAccountNonces(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            nonces,
+        ))
+    }
+
+    fn parse_get_miner_rewards<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let rewards = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetMinerRewards(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            rewards,
+        ))
+    }
+
     fn parse_get_map_entry<R: Read>(
         _protocol: &mut StacksHttp,
         request_version: HttpVersion,
@@ -2748,47 +4323,90 @@ impl HttpResponseType {
         ))
     }
 
-    fn parse_get_contract_src<R: Read>(
+    fn parse_transaction_receipt<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let receipt = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::TransactionReceipt(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            receipt,
+        ))
+    }
+
+    fn parse_get_contract_src<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let src_data =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetContractSrc(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            src_data,
+        ))
+    }
+
+    fn parse_get_contract_abi<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let abi = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetContractABI(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            abi,
+        ))
+    }
+
+    fn parse_call_read_only<R: Read>(
         _protocol: &mut StacksHttp,
         request_version: HttpVersion,
         preamble: &HttpResponsePreamble,
         fd: &mut R,
         len_hint: Option<usize>,
     ) -> Result<HttpResponseType, net_error> {
-        let src_data =
+        let call_data =
             HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
-        Ok(HttpResponseType::GetContractSrc(
+        Ok(HttpResponseType::CallReadOnlyFunction(
             HttpResponseMetadata::from_preamble(request_version, preamble),
-            src_data,
+            call_data,
         ))
     }
 
-    fn parse_get_contract_abi<R: Read>(
+    fn parse_multi_call_read_only<R: Read>(
         _protocol: &mut StacksHttp,
         request_version: HttpVersion,
         preamble: &HttpResponsePreamble,
         fd: &mut R,
         len_hint: Option<usize>,
     ) -> Result<HttpResponseType, net_error> {
-        let abi = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
-        Ok(HttpResponseType::GetContractABI(
+        let call_data =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::MultiCallReadOnly(
             HttpResponseMetadata::from_preamble(request_version, preamble),
-            abi,
+            call_data,
         ))
     }
 
-    fn parse_call_read_only<R: Read>(
+    fn parse_admin_action<R: Read>(
         _protocol: &mut StacksHttp,
         request_version: HttpVersion,
         preamble: &HttpResponsePreamble,
         fd: &mut R,
         len_hint: Option<usize>,
     ) -> Result<HttpResponseType, net_error> {
-        let call_data =
-            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
-        Ok(HttpResponseType::CallReadOnlyFunction(
+        let action = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::AdminAction(
             HttpResponseMetadata::from_preamble(request_version, preamble),
-            call_data,
+            action,
         ))
     }
 
@@ -2852,6 +4470,45 @@ impl HttpResponseType {
         ))
     }
 
+    fn parse_get_attachment<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        // hex-encoded content, plus JSON framing
+        let attachment_data =
+            HttpResponseType::parse_json(preamble, fd, len_hint, 2 * MAX_ATTACHMENT_SIZE + 64)?;
+        Ok(HttpResponseType::GetAttachment(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            attachment_data,
+        ))
+    }
+
+    fn parse_attachment_hash<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let hash_hex: String = HttpResponseType::parse_json(preamble, fd, len_hint, 42)?;
+        if hash_hex.len() != 40 {
+            return Err(net_error::DeserializeError(
+                "Invalid attachment hash: expected 40 bytes".to_string(),
+            ));
+        }
+
+        let content_hash = Hash160::from_hex(&hash_hex).map_err(|_e| {
+            net_error::DeserializeError("Failed to decode attachment hash hex".to_string())
+        })?;
+        Ok(HttpResponseType::AttachmentHash(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            content_hash,
+        ))
+    }
+
     fn parse_microblock_hash<R: Read>(
         _protocol: &mut StacksHttp,
         request_version: HttpVersion,
@@ -2882,6 +4539,8 @@ impl HttpResponseType {
             402 => "Payment Required",
             403 => "Forbidden",
             404 => "Not Found",
+            416 => "Range Not Satisfiable",
+            429 => "Too Many Requests",
             500 => "Internal Server Error",
             503 => "Service Temporarily Unavailable",
             _ => "Error",
@@ -2890,6 +4549,7 @@ impl HttpResponseType {
 
     fn error_response<W: Write>(
         &self,
+        protocol: &StacksHttp,
         fd: &mut W,
         code: u16,
         message: &str,
@@ -2902,7 +4562,7 @@ impl HttpResponseType {
             Some(message.len() as u32),
             &HttpContentType::Text,
             md.request_id,
-            |ref mut fd| keep_alive_headers(fd, md),
+            |ref mut fd| response_headers(fd, protocol, md, code),
         )?;
         fd.write_all(message.as_bytes())
             .map_err(net_error::WriteError)?;
@@ -2913,20 +4573,34 @@ impl HttpResponseType {
         match *self {
             HttpResponseType::PeerInfo(ref md, _) => md,
             HttpResponseType::PoxInfo(ref md, _) => md,
+            HttpResponseType::BurnEta(ref md, _) => md,
+            HttpResponseType::FeeEstimate(ref md, _) => md,
+            HttpResponseType::Forks(ref md, _) => md,
+            HttpResponseType::BlockValidation(ref md, _) => md,
             HttpResponseType::Neighbors(ref md, _) => md,
             HttpResponseType::Block(ref md, _) => md,
             HttpResponseType::BlockStream(ref md) => md,
+            HttpResponseType::PartialStream(ref md, ..) => md,
+            HttpResponseType::PartialContent(ref md, _) => md,
             HttpResponseType::Microblocks(ref md, _) => md,
             HttpResponseType::MicroblockStream(ref md) => md,
             HttpResponseType::TransactionID(ref md, _) => md,
+            HttpResponseType::TransactionReceipt(ref md, _) => md,
             HttpResponseType::MicroblockHash(ref md, _) => md,
             HttpResponseType::TokenTransferCost(ref md, _) => md,
             HttpResponseType::GetMapEntry(ref md, _) => md,
             HttpResponseType::GetAccount(ref md, _) => md,
+            HttpResponseType::GetAccountNonces(ref md, _) => md,
+            HttpResponseType::GetMinerRewards(ref md, _) => md,
             HttpResponseType::GetContractABI(ref md, _) => md,
             HttpResponseType::GetContractSrc(ref md, _) => md,
+            HttpResponseType::GetAttachment(ref md, _) => md,
+            HttpResponseType::AttachmentHash(ref md, _) => md,
+            HttpResponseType::WebSocketUpgrade(ref md, _) => md,
             HttpResponseType::CallReadOnlyFunction(ref md, _) => md,
+            HttpResponseType::MultiCallReadOnly(ref md, _) => md,
             HttpResponseType::OptionsPreflight(ref md) => md,
+            HttpResponseType::AdminAction(ref md, _) => md,
             // errors
             HttpResponseType::BadRequestJSON(ref md, _) => md,
             HttpResponseType::BadRequest(ref md, _) => md,
@@ -2940,44 +4614,87 @@ impl HttpResponseType {
         }
     }
 
-    fn send_bytestream<W: Write, T: StacksMessageCodec>(
+    fn send_text<W: Write>(
         protocol: &mut StacksHttp,
         md: &HttpResponseMetadata,
         fd: &mut W,
-        message: &T,
+        text: &[u8],
     ) -> Result<(), net_error> {
         if md.content_length.is_some() {
             // have explicit content-length, so we can send as-is
-            write_next(fd, message)
+            fd.write_all(text).map_err(net_error::WriteError)
         } else {
             // no content-length, so send as chunk-encoded
             let mut write_state = HttpChunkedTransferWriterState::new(protocol.chunk_size as usize);
             let mut encoder = HttpChunkedTransferWriter::from_writer_state(fd, &mut write_state);
-            write_next(&mut encoder, message)?;
+            encoder.write_all(text).map_err(net_error::WriteError)?;
             encoder.flush().map_err(net_error::WriteError)?;
             Ok(())
         }
     }
 
-    fn send_text<W: Write>(
+    /// Send a 200 response with a body that is compressed if the client accepts it and it's big
+    /// enough to be worth it
+    fn send_compressible<W: Write>(
         protocol: &mut StacksHttp,
         md: &HttpResponseMetadata,
         fd: &mut W,
-        text: &[u8],
+        content_type: &HttpContentType,
+        body: &[u8],
     ) -> Result<(), net_error> {
-        if md.content_length.is_some() {
-            // have explicit content-length, so we can send as-is
-            fd.write_all(text).map_err(net_error::WriteError)
+        let encoding = protocol.response_encoding(md, Some(body.len()));
+        let content_length = match encoding {
+            Some(_) => None,
+            None => md.content_length.clone(),
+        };
+        HttpResponsePreamble::new_serialized(
+            fd,
+            200,
+            "OK",
+            content_length,
+            content_type,
+            md.request_id,
+            |ref mut fd| {
+                response_headers(fd, protocol, md, 200)?;
+                content_encoding_headers(fd, encoding)
+            },
+        )?;
+        if content_length.is_some() {
+            fd.write_all(body).map_err(net_error::WriteError)
         } else {
-            // no content-length, so send as chunk-encoded
-            let mut write_state = HttpChunkedTransferWriterState::new(protocol.chunk_size as usize);
+            let mut write_state =
+                HttpChunkedTransferWriterState::new_encoded(protocol.chunk_size as usize, encoding);
             let mut encoder = HttpChunkedTransferWriter::from_writer_state(fd, &mut write_state);
-            encoder.write_all(text).map_err(net_error::WriteError)?;
+            encoder.write_all(body).map_err(net_error::WriteError)?;
             encoder.flush().map_err(net_error::WriteError)?;
             Ok(())
         }
     }
 
+    /// Send the preamble of a streamed 200 response, whose body the caller will send with the
+    /// coding `protocol.take_stream_encoding()` gives
+    fn send_stream_preamble<W: Write>(
+        protocol: &mut StacksHttp,
+        md: &HttpResponseMetadata,
+        fd: &mut W,
+    ) -> Result<(), net_error> {
+        let encoding = protocol.response_encoding(md, None);
+        HttpResponsePreamble::new_serialized(
+            fd,
+            200,
+            "OK",
+            None,
+            &HttpContentType::Bytes,
+            md.request_id,
+            |ref mut fd| {
+                response_headers(fd, protocol, md, 200)?;
+                content_encoding_headers(fd, encoding)
+            },
+        )?;
+        protocol.stream_encoding = encoding;
+        Ok(())
+    }
+
     fn send_json<W: Write, T: serde::ser::Serialize>(
         protocol: &mut StacksHttp,
         md: &HttpResponseMetadata,
@@ -3000,94 +4717,180 @@ impl HttpResponseType {
         }
     }
 
+    /// Get the HTTP status code this response is sent with
+    pub fn status_code(&self) -> u16 {
+        match *self {
+            HttpResponseType::BadRequestJSON(..) | HttpResponseType::BadRequest(..) => 400,
+            HttpResponseType::Unauthorized(..) => 401,
+            HttpResponseType::PaymentRequired(..) => 402,
+            HttpResponseType::Forbidden(..) => 403,
+            HttpResponseType::NotFound(..) => 404,
+            HttpResponseType::ServerError(..) => 500,
+            HttpResponseType::ServiceUnavailable(..) => 503,
+            HttpResponseType::Error(_, ref error_code, _) => *error_code,
+            HttpResponseType::WebSocketUpgrade(..) => 101,
+            _ => 200,
+        }
+    }
+
     pub fn send<W: Write>(&self, protocol: &mut StacksHttp, fd: &mut W) -> Result<(), net_error> {
+        protocol.last_response_code = Some(self.status_code());
         match *self {
             HttpResponseType::GetAccount(ref md, ref account_data) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponsePreamble::ok_JSON_from_md(protocol, fd, md)?;
                 HttpResponseType::send_json(protocol, md, fd, account_data)?;
             }
+            HttpResponseType::GetAccountNonces(ref md, ref nonces) => {
+                HttpResponsePreamble::ok_JSON_from_md(protocol, fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, nonces)?;
+            }
+            HttpResponseType::GetMinerRewards(ref md, ref rewards) => {
+                HttpResponsePreamble::ok_JSON_from_md(protocol, fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, rewards)?;
+            }
             HttpResponseType::GetContractABI(ref md, ref data) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
-                HttpResponseType::send_json(protocol, md, fd, data)?;
+                let body = serde_json::to_vec(data).map_err(|e| {
+                    net_error::SerializeError(format!("Failed to send as JSON: {:?}", &e))
+                })?;
+                HttpResponseType::send_compressible(
+                    protocol,
+                    md,
+                    fd,
+                    &HttpContentType::JSON,
+                    &body,
+                )?;
             }
             HttpResponseType::GetContractSrc(ref md, ref data) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                let body = serde_json::to_vec(data).map_err(|e| {
+                    net_error::SerializeError(format!("Failed to send as JSON: {:?}", &e))
+                })?;
+                HttpResponseType::send_compressible(
+                    protocol,
+                    md,
+                    fd,
+                    &HttpContentType::JSON,
+                    &body,
+                )?;
+            }
+            HttpResponseType::TransactionReceipt(ref md, ref receipt) => {
+                HttpResponsePreamble::ok_JSON_from_md(protocol, fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, receipt)?;
+            }
+            HttpResponseType::GetAttachment(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(protocol, fd, md)?;
                 HttpResponseType::send_json(protocol, md, fd, data)?;
             }
             HttpResponseType::TokenTransferCost(ref md, ref cost) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponsePreamble::ok_JSON_from_md(protocol, fd, md)?;
                 HttpResponseType::send_json(protocol, md, fd, cost)?;
             }
             HttpResponseType::CallReadOnlyFunction(ref md, ref data) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponsePreamble::ok_JSON_from_md(protocol, fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::MultiCallReadOnly(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(protocol, fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::AdminAction(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(protocol, fd, md)?;
                 HttpResponseType::send_json(protocol, md, fd, data)?;
             }
             HttpResponseType::GetMapEntry(ref md, ref map_data) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponsePreamble::ok_JSON_from_md(protocol, fd, md)?;
                 HttpResponseType::send_json(protocol, md, fd, map_data)?;
             }
             HttpResponseType::PeerInfo(ref md, ref peer_info) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponsePreamble::ok_JSON_from_md(protocol, fd, md)?;
                 HttpResponseType::send_json(protocol, md, fd, peer_info)?;
             }
             HttpResponseType::PoxInfo(ref md, ref pox_info) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponsePreamble::ok_JSON_from_md(protocol, fd, md)?;
                 HttpResponseType::send_json(protocol, md, fd, pox_info)?;
             }
+            HttpResponseType::BurnEta(ref md, ref burn_eta) => {
+                HttpResponsePreamble::ok_JSON_from_md(protocol, fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, burn_eta)?;
+            }
+            HttpResponseType::FeeEstimate(ref md, ref fee_estimate) => {
+                HttpResponsePreamble::ok_JSON_from_md(protocol, fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, fee_estimate)?;
+            }
+            HttpResponseType::Forks(ref md, ref forks) => {
+                HttpResponsePreamble::ok_JSON_from_md(protocol, fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, forks)?;
+            }
+            HttpResponseType::BlockValidation(ref md, ref validation) => {
+                HttpResponsePreamble::ok_JSON_from_md(protocol, fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, validation)?;
+            }
             HttpResponseType::Neighbors(ref md, ref neighbor_data) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponsePreamble::ok_JSON_from_md(protocol, fd, md)?;
                 HttpResponseType::send_json(protocol, md, fd, neighbor_data)?;
             }
             HttpResponseType::Block(ref md, ref block) => {
-                HttpResponsePreamble::new_serialized(
+                let mut body = vec![];
+                write_next(&mut body, block)?;
+                HttpResponseType::send_compressible(
+                    protocol,
+                    md,
                     fd,
-                    200,
-                    "OK",
-                    md.content_length.clone(),
                     &HttpContentType::Bytes,
-                    md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    &body,
                 )?;
-                HttpResponseType::send_bytestream(protocol, md, fd, block)?;
             }
             HttpResponseType::BlockStream(ref md) => {
                 // only send the preamble.  The caller will need to figure out how to send along
                 // the block data itself.
+                HttpResponseType::send_stream_preamble(protocol, md, fd)?;
+            }
+            HttpResponseType::PartialStream(ref md, first, last, len) => {
+                // only send the preamble, as for a BlockStream or MicroblockStream
                 HttpResponsePreamble::new_serialized(
                     fd,
-                    200,
-                    "OK",
+                    206,
+                    "Partial Content",
                     None,
                     &HttpContentType::Bytes,
                     md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    |ref mut fd| {
+                        response_headers(fd, protocol, md, 206)?;
+                        fd.write_all(
+                            format!("Content-Range: bytes {}-{}/{}\r\n", first, last, len)
+                                .as_bytes(),
+                        )
+                        .map_err(net_error::WriteError)
+                    },
                 )?;
             }
-            HttpResponseType::Microblocks(ref md, ref microblocks) => {
+            HttpResponseType::PartialContent(ref md, ref bytes) => {
                 HttpResponsePreamble::new_serialized(
                     fd,
-                    200,
-                    "OK",
+                    206,
+                    "Partial Content",
                     md.content_length.clone(),
                     &HttpContentType::Bytes,
                     md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    |ref mut fd| response_headers(fd, protocol, md, 206),
                 )?;
-                HttpResponseType::send_bytestream(protocol, md, fd, microblocks)?;
+                HttpResponseType::send_text(protocol, md, fd, bytes)?;
             }
-            HttpResponseType::MicroblockStream(ref md) => {
-                // only send the preamble.  The caller will need to figure out how to send along
-                // the microblock data itself.
-                HttpResponsePreamble::new_serialized(
+            HttpResponseType::Microblocks(ref md, ref microblocks) => {
+                let mut body = vec![];
+                write_next(&mut body, microblocks)?;
+                HttpResponseType::send_compressible(
+                    protocol,
+                    md,
                     fd,
-                    200,
-                    "OK",
-                    None,
                     &HttpContentType::Bytes,
-                    md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    &body,
                 )?;
             }
+            HttpResponseType::MicroblockStream(ref md) => {
+                // only send the preamble.  The caller will need to figure out how to send along
+                // the microblock data itself.
+                HttpResponseType::send_stream_preamble(protocol, md, fd)?;
+            }
             HttpResponseType::TransactionID(ref md, ref txid) => {
                 let txid_bytes = txid.to_hex();
                 HttpResponsePreamble::new_serialized(
@@ -3097,7 +4900,7 @@ impl HttpResponseType {
                     md.content_length.clone(),
                     &HttpContentType::JSON,
                     md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    |ref mut fd| response_headers(fd, protocol, md, 200),
                 )?;
                 HttpResponseType::send_json(protocol, md, fd, &txid_bytes)?;
             }
@@ -3110,10 +4913,35 @@ impl HttpResponseType {
                     md.content_length.clone(),
                     &HttpContentType::JSON,
                     md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    |ref mut fd| response_headers(fd, protocol, md, 200),
                 )?;
                 HttpResponseType::send_json(protocol, md, fd, &mblock_bytes)?;
             }
+            HttpResponseType::AttachmentHash(ref md, ref content_hash) => {
+                let hash_bytes = content_hash.to_hex();
+                HttpResponsePreamble::new_serialized(
+                    fd,
+                    200,
+                    "OK",
+                    md.content_length.clone(),
+                    &HttpContentType::JSON,
+                    md.request_id,
+                    |ref mut fd| response_headers(fd, protocol, md, 200),
+                )?;
+                HttpResponseType::send_json(protocol, md, fd, &hash_bytes)?;
+            }
+            HttpResponseType::WebSocketUpgrade(ref _md, ref accept_key) => {
+                // a 101 has no body, so it has no Content-Type or Content-Length either
+                fd.write_all(
+                    format!(
+                        "HTTP/1.1 101 Switching Protocols\r\nServer: stacks/2.0\r\nDate: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                        rfc7231_now(),
+                        accept_key
+                    )
+                    .as_bytes(),
+                )
+                .map_err(net_error::WriteError)?;
+            }
             HttpResponseType::OptionsPreflight(ref md) => {
                 HttpResponsePreamble::new_serialized(
                     fd,
@@ -3122,7 +4950,10 @@ impl HttpResponseType {
                     None,
                     &HttpContentType::Text,
                     md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    |ref mut fd| {
+                        keep_alive_headers(fd, md)?;
+                        protocol.response_headers.write(fd, md, 200, true)
+                    },
                 )?;
                 HttpResponseType::send_text(protocol, md, fd, "".as_bytes())?;
             }
@@ -3134,21 +4965,33 @@ impl HttpResponseType {
                     md.content_length.clone(),
                     &HttpContentType::JSON,
                     md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    |ref mut fd| response_headers(fd, protocol, md, 400),
                 )?;
                 HttpResponseType::send_json(protocol, md, fd, data)?;
             }
-            HttpResponseType::BadRequest(_, ref msg) => self.error_response(fd, 400, msg)?,
-            HttpResponseType::Unauthorized(_, ref msg) => self.error_response(fd, 401, msg)?,
-            HttpResponseType::PaymentRequired(_, ref msg) => self.error_response(fd, 402, msg)?,
-            HttpResponseType::Forbidden(_, ref msg) => self.error_response(fd, 403, msg)?,
-            HttpResponseType::NotFound(_, ref msg) => self.error_response(fd, 404, msg)?,
-            HttpResponseType::ServerError(_, ref msg) => self.error_response(fd, 500, msg)?,
+            HttpResponseType::BadRequest(_, ref msg) => {
+                self.error_response(protocol, fd, 400, msg)?
+            }
+            HttpResponseType::Unauthorized(_, ref msg) => {
+                self.error_response(protocol, fd, 401, msg)?
+            }
+            HttpResponseType::PaymentRequired(_, ref msg) => {
+                self.error_response(protocol, fd, 402, msg)?
+            }
+            HttpResponseType::Forbidden(_, ref msg) => {
+                self.error_response(protocol, fd, 403, msg)?
+            }
+            HttpResponseType::NotFound(_, ref msg) => {
+                self.error_response(protocol, fd, 404, msg)?
+            }
+            HttpResponseType::ServerError(_, ref msg) => {
+                self.error_response(protocol, fd, 500, msg)?
+            }
             HttpResponseType::ServiceUnavailable(_, ref msg) => {
-                self.error_response(fd, 503, msg)?
+                self.error_response(protocol, fd, 503, msg)?
             }
             HttpResponseType::Error(_, ref error_code, ref msg) => {
-                self.error_response(fd, *error_code, msg)?
+                self.error_response(protocol, fd, *error_code, msg)?
             }
         };
         Ok(())
@@ -3206,7 +5049,10 @@ impl MessageSequence for StacksHttpMessage {
             StacksHttpMessage::Request(ref req) => match req {
                 HttpRequestType::GetInfo(_) => "HTTP(GetInfo)",
                 HttpRequestType::GetPoxInfo(_, _) => "HTTP(GetPoxInfo)",
-                HttpRequestType::GetNeighbors(_) => "HTTP(GetNeighbors)",
+                HttpRequestType::GetBurnEta(_, _) => "HTTP(GetBurnEta)",
+                HttpRequestType::GetFeeEstimate(_, _) => "HTTP(GetFeeEstimate)",
+                HttpRequestType::GetForks(_) => "HTTP(GetForks)",
+                HttpRequestType::GetNeighbors(..) => "HTTP(GetNeighbors)",
                 HttpRequestType::GetBlock(_, _) => "HTTP(GetBlock)",
                 HttpRequestType::GetMicroblocksIndexed(_, _) => "HTTP(GetMicroblocksIndexed)",
                 HttpRequestType::GetMicroblocksConfirmed(_, _) => "HTTP(GetMicroblocksConfirmed)",
@@ -3214,33 +5060,60 @@ impl MessageSequence for StacksHttpMessage {
                     "HTTP(GetMicroblocksUnconfirmed)"
                 }
                 HttpRequestType::PostTransaction(_, _) => "HTTP(PostTransaction)",
+                HttpRequestType::GetTransactionReceipt(..) => "HTTP(GetTransactionReceipt)",
                 HttpRequestType::PostMicroblock(..) => "HTTP(PostMicroblock)",
+                HttpRequestType::ValidateBlock(..) => "HTTP(ValidateBlock)",
+                HttpRequestType::PostFaucetStx(_, _) => "HTTP(PostFaucetStx)",
+                HttpRequestType::GetAttachment(_, _) => "HTTP(GetAttachment)",
+                HttpRequestType::PostAttachment(_, _) => "HTTP(PostAttachment)",
+                HttpRequestType::WebSocketUpgrade(_, _) => "HTTP(WebSocketUpgrade)",
                 HttpRequestType::GetAccount(..) => "HTTP(GetAccount)",
+                HttpRequestType::GetAccountNonces(..) => "HTTP(GetAccountNonces)",
+                HttpRequestType::GetMinerRewards(..) => "HTTP(GetMinerRewards)",
                 HttpRequestType::GetMapEntry(..) => "HTTP(GetMapEntry)",
                 HttpRequestType::GetTransferCost(_) => "HTTP(GetTransferCost)",
                 HttpRequestType::GetContractABI(..) => "HTTP(GetContractABI)",
                 HttpRequestType::GetContractSrc(..) => "HTTP(GetContractSrc)",
                 HttpRequestType::CallReadOnlyFunction(..) => "HTTP(CallReadOnlyFunction)",
+                HttpRequestType::MultiCallReadOnly(..) => "HTTP(MultiCallReadOnly)",
                 HttpRequestType::OptionsPreflight(..) => "HTTP(OptionsPreflight)",
+                HttpRequestType::AdminBanPeer(..) => "HTTP(AdminBanPeer)",
+                HttpRequestType::AdminEvictTransaction(..) => "HTTP(AdminEvictTransaction)",
+                HttpRequestType::AdminFlushChainstate(..) => "HTTP(AdminFlushChainstate)",
+                HttpRequestType::AdminSetMining(..) => "HTTP(AdminSetMining)",
                 HttpRequestType::ClientError(..) => "HTTP(ClientError)",
             },
             StacksHttpMessage::Response(ref res) => match res {
                 HttpResponseType::TokenTransferCost(_, _) => "HTTP(TokenTransferCost)",
                 HttpResponseType::GetMapEntry(_, _) => "HTTP(GetMapEntry)",
                 HttpResponseType::GetAccount(_, _) => "HTTP(GetAccount)",
+                HttpResponseType::GetAccountNonces(_, _) => "HTTP(GetAccountNonces)",
+                HttpResponseType::GetMinerRewards(_, _) => "HTTP(GetMinerRewards)",
                 HttpResponseType::GetContractABI(..) => "HTTP(GetContractABI)",
                 HttpResponseType::GetContractSrc(..) => "HTTP(GetContractSrc)",
+                HttpResponseType::GetAttachment(..) => "HTTP(GetAttachment)",
+                HttpResponseType::AttachmentHash(_, _) => "HTTP(AttachmentHash)",
+                HttpResponseType::WebSocketUpgrade(_, _) => "HTTP(WebSocketUpgrade)",
                 HttpResponseType::CallReadOnlyFunction(..) => "HTTP(CallReadOnlyFunction)",
+                HttpResponseType::MultiCallReadOnly(..) => "HTTP(MultiCallReadOnly)",
                 HttpResponseType::PeerInfo(_, _) => "HTTP(PeerInfo)",
                 HttpResponseType::PoxInfo(_, _) => "HTTP(PeerInfo)",
+                HttpResponseType::BurnEta(_, _) => "HTTP(BurnEta)",
+                HttpResponseType::FeeEstimate(_, _) => "HTTP(FeeEstimate)",
+                HttpResponseType::Forks(_, _) => "HTTP(Forks)",
+                HttpResponseType::BlockValidation(_, _) => "HTTP(BlockValidation)",
                 HttpResponseType::Neighbors(_, _) => "HTTP(Neighbors)",
                 HttpResponseType::Block(_, _) => "HTTP(Block)",
                 HttpResponseType::BlockStream(_) => "HTTP(BlockStream)",
+                HttpResponseType::PartialStream(..) => "HTTP(PartialStream)",
+                HttpResponseType::PartialContent(..) => "HTTP(PartialContent)",
                 HttpResponseType::Microblocks(_, _) => "HTTP(Microblocks)",
                 HttpResponseType::MicroblockStream(_) => "HTTP(MicroblockStream)",
                 HttpResponseType::TransactionID(_, _) => "HTTP(Transaction)",
+                HttpResponseType::TransactionReceipt(_, _) => "HTTP(TransactionReceipt)",
                 HttpResponseType::MicroblockHash(_, _) => "HTTP(Microblock)",
                 HttpResponseType::OptionsPreflight(_) => "HTTP(OptionsPreflight)",
+                HttpResponseType::AdminAction(..) => "HTTP(AdminAction)",
                 HttpResponseType::BadRequestJSON(..) | HttpResponseType::BadRequest(..) => {
                     "HTTP(400)"
                 }
@@ -3353,6 +5226,16 @@ pub struct StacksHttp {
     chunk_size: usize,
     /// Maximum size of call arguments
     pub maximum_call_argument_size: u32,
+    /// Status code of the last response we sent
+    last_response_code: Option<u16>,
+    /// CORS, caching, and extra headers to send with each response
+    pub response_headers: HttpResponseHeaders,
+    /// Whether or not to compress large responses for clients that accept it, and how big a
+    /// response body must be to bother
+    pub compress_responses: bool,
+    pub compression_min_size: u64,
+    /// Content coding of the streamed response whose preamble we last sent
+    stream_encoding: Option<HttpContentEncoding>,
 }
 
 impl StacksHttp {
@@ -3363,6 +5246,11 @@ impl StacksHttp {
             request_path: None,
             chunk_size: 8192,
             maximum_call_argument_size: 20 * BOUND_VALUE_SERIALIZATION_HEX,
+            last_response_code: None,
+            response_headers: HttpResponseHeaders::default(),
+            compress_responses: false,
+            compression_min_size: 0,
+            stream_encoding: None,
         }
     }
 
@@ -3370,6 +5258,33 @@ impl StacksHttp {
         self.chunk_size = size;
     }
 
+    /// Get and clear the status code of the last response we sent
+    pub fn take_last_response_code(&mut self) -> Option<u16> {
+        self.last_response_code.take()
+    }
+
+    /// Get and clear the content coding of the last streamed response whose preamble we sent.
+    /// The stream's body must be written with it.
+    pub fn take_stream_encoding(&mut self) -> Option<HttpContentEncoding> {
+        self.stream_encoding.take()
+    }
+
+    /// The coding to compress a response body with, if any.  `len` is the body's length, or
+    /// None if it is streamed (streams are always large enough to compress).
+    fn response_encoding(
+        &self,
+        md: &HttpResponseMetadata,
+        len: Option<usize>,
+    ) -> Option<HttpContentEncoding> {
+        if !self.compress_responses {
+            return None;
+        }
+        match len {
+            Some(len) if (len as u64) < self.compression_min_size => None,
+            _ => md.content_encoding,
+        }
+    }
+
     pub fn num_pending(&self) -> usize {
         if self.reply.is_some() {
             1
@@ -3754,14 +5669,21 @@ impl ProtocolFamily for StacksHttp {
 #[cfg(test)]
 mod test {
     use super::*;
+    use chainstate::burn::ConsensusHash;
     use net::codec::test::check_codec_and_corruption;
     use net::test::*;
+    use net::ContractSrcResponse;
+    use net::GetAttachmentResponse;
+    use net::RPCFeeEstimateData;
     use net::RPCNeighbor;
     use net::RPCNeighborsInfo;
+    use net::{RPCBlockValidationData, RPCTxValidationError};
+    use net::{RPCForkTipData, RPCForksData};
     use std::error::Error;
 
     use burnchains::Txid;
     use chainstate::stacks::db::blocks::test::make_sample_microblock_stream;
+    use chainstate::stacks::events::TransactionReceiptData;
     use chainstate::stacks::test::make_codec_test_block;
     use chainstate::stacks::StacksAddress;
     use chainstate::stacks::StacksBlock;
@@ -3773,6 +5695,10 @@ mod test {
     use chainstate::stacks::TransactionPayload;
     use chainstate::stacks::TransactionPostConditionMode;
     use chainstate::stacks::TransactionVersion;
+    use core::fees::FeeRateEstimate;
+    use vm::costs::ExecutionCost;
+    use vm::database::ClaritySerializable;
+    use vm::types::TupleData;
 
     use chainstate::stacks::StacksPrivateKey;
 
@@ -3781,6 +5707,7 @@ mod test {
     use util::hash::MerkleTree;
     use util::hash::Sha512Trunc256Sum;
 
+    use flate2::read::{GzDecoder, ZlibDecoder};
     use rand;
     use rand::RngCore;
 
@@ -3910,6 +5837,88 @@ mod test {
         }
     }
 
+    /// Undo the chunked transfer coding, and then the content coding, of a response body
+    fn decode_body(chunked: &[u8], encoding: Option<HttpContentEncoding>) -> Vec<u8> {
+        let mut cursor = io::Cursor::new(chunked);
+        let mut decoder =
+            HttpChunkedTransferReader::from_reader(&mut cursor, MAX_MESSAGE_LEN as u64);
+        let mut encoded = vec![];
+        decoder.read_to_end(&mut encoded).unwrap();
+
+        let mut body = vec![];
+        match encoding {
+            Some(HttpContentEncoding::Gzip) => {
+                GzDecoder::new(&encoded[..]).read_to_end(&mut body).unwrap();
+            }
+            Some(HttpContentEncoding::Deflate) => {
+                ZlibDecoder::new(&encoded[..])
+                    .read_to_end(&mut body)
+                    .unwrap();
+            }
+            None => {
+                body = encoded;
+            }
+        }
+        body
+    }
+
+    #[test]
+    fn test_http_chunked_encode_compressed() {
+        let input_vec: Vec<String> = (0..100)
+            .map(|i| format!("segment {} of a highly compressible body; ", i))
+            .collect();
+        let input: Vec<u8> = input_vec.concat().into_bytes();
+
+        for encoding in [HttpContentEncoding::Gzip, HttpContentEncoding::Deflate].iter() {
+            let mut bytes = vec![];
+            {
+                let mut write_state =
+                    HttpChunkedTransferWriterState::new_encoded(64, Some(*encoding));
+                let mut encoder =
+                    HttpChunkedTransferWriter::from_writer_state(&mut bytes, &mut write_state);
+                for segment in input_vec.iter() {
+                    assert_eq!(encoder.write(segment.as_bytes()).unwrap(), segment.len());
+                }
+                encoder.flush().unwrap();
+            }
+
+            assert!(bytes.len() < input.len());
+            assert!(bytes.ends_with("0\r\n\r\n".as_bytes()));
+            assert_eq!(decode_body(&bytes, Some(*encoding)), input);
+        }
+    }
+
+    #[test]
+    fn test_http_content_encoding_negotiation() {
+        let tests = vec![
+            ("gzip", Some(HttpContentEncoding::Gzip)),
+            ("deflate", Some(HttpContentEncoding::Deflate)),
+            ("gzip, deflate, br", Some(HttpContentEncoding::Gzip)),
+            ("deflate, gzip", Some(HttpContentEncoding::Gzip)),
+            ("GZIP", Some(HttpContentEncoding::Gzip)),
+            ("x-gzip", Some(HttpContentEncoding::Gzip)),
+            ("gzip;q=0.5, deflate", Some(HttpContentEncoding::Deflate)),
+            (
+                "gzip; q=0, deflate;q=0.1",
+                Some(HttpContentEncoding::Deflate),
+            ),
+            ("*", Some(HttpContentEncoding::Gzip)),
+            ("gzip;q=0, *", Some(HttpContentEncoding::Deflate)),
+            ("gzip;q=0, deflate;q=0, *", None),
+            ("br, identity", None),
+            ("gzip;q=zero", None),
+            ("", None),
+        ];
+        for (header, expected) in tests.iter() {
+            assert_eq!(
+                HttpContentEncoding::from_accept_encoding(header),
+                *expected,
+                "Accept-Encoding: {}",
+                header
+            );
+        }
+    }
+
     #[test]
     fn test_http_chunked_decode() {
         let tests = vec![
@@ -4517,16 +6526,261 @@ mod test {
             "CORS header is missing"
         );
         assert!(
-            txt.find("Access-Control-Allow-Headers: origin, content-type\r\n")
+            txt.find("Access-Control-Allow-Headers: origin, content-type, x-api-key\r\n")
+                .is_some(),
+            "CORS header is missing"
+        );
+        assert!(
+            txt.find("Access-Control-Allow-Methods: POST, GET, OPTIONS\r\n")
                 .is_some(),
             "CORS header is missing"
         );
-        assert!(
-            txt.find("Access-Control-Allow-Methods: POST, GET, OPTIONS\r\n")
-                .is_some(),
-            "CORS header is missing"
+        assert!(txt.find("Connection: ").is_none()); // not sent if keep_alive is true
+    }
+
+    #[test]
+    fn test_http_response_headers() {
+        fn send_response(
+            headers: &HttpResponseHeaders,
+            origin: Option<&str>,
+            immutable: bool,
+            response: fn(HttpResponseMetadata) -> HttpResponseType,
+        ) -> String {
+            let mut md = HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true);
+            md.origin = origin.map(|o| o.to_string());
+            md.immutable = immutable;
+
+            let mut protocol = StacksHttp::new();
+            protocol.response_headers = headers.clone();
+
+            let mut bytes = vec![];
+            response(md).send(&mut protocol, &mut bytes).unwrap();
+            String::from_utf8(bytes).unwrap()
+        }
+        fn ok(md: HttpResponseMetadata) -> HttpResponseType {
+            HttpResponseType::TransactionID(md, Txid([0x11; 32]))
+        }
+        fn not_found(md: HttpResponseMetadata) -> HttpResponseType {
+            HttpResponseType::NotFound(md, "not found".to_string())
+        }
+        fn preflight(md: HttpResponseMetadata) -> HttpResponseType {
+            HttpResponseType::OptionsPreflight(md)
+        }
+
+        // defaults: any origin, no caching of tip-dependent responses
+        let defaults = HttpResponseHeaders::default();
+        let txt = send_response(&defaults, Some("https://example.com"), false, ok);
+        assert!(txt.find("Access-Control-Allow-Origin: *\r\n").is_some());
+        assert!(txt
+            .find("Access-Control-Allow-Headers: origin, content-type, x-api-key\r\n")
+            .is_some());
+        assert!(txt
+            .find("Access-Control-Allow-Methods: POST, GET, OPTIONS\r\n")
+            .is_some());
+        assert!(txt.find("Vary: ").is_none());
+        assert!(txt.find("Access-Control-Max-Age: ").is_none());
+        assert!(txt.find("Cache-Control: no-cache\r\n").is_some());
+
+        let txt = send_response(&defaults, None, true, ok);
+        assert!(txt
+            .find(&format!(
+                "Cache-Control: public, max-age={}, immutable\r\n",
+                defaults.immutable_cache_max_age
+            ))
+            .is_some());
+
+        // errors are never cached
+        let txt = send_response(&defaults, None, true, not_found);
+        assert!(txt.find("Cache-Control: no-cache\r\n").is_some());
+
+        let txt = send_response(&defaults, Some("https://example.com"), false, preflight);
+        assert!(txt
+            .find(&format!(
+                "Access-Control-Max-Age: {}\r\n",
+                defaults.cors_max_age
+            ))
+            .is_some());
+
+        // an allow-list echoes a listed origin, and only that
+        let listed = HttpResponseHeaders {
+            cors_allowed_origins: vec![
+                "https://example.com".to_string(),
+                "https://example.org".to_string(),
+            ],
+            cors_max_age: 0,
+            cache_max_age: 30,
+            immutable_cache_max_age: 0,
+            extra: vec![
+                (
+                    "Strict-Transport-Security".to_string(),
+                    "max-age=86400".to_string(),
+                ),
+                ("X-Frame-Options".to_string(), "DENY".to_string()),
+            ],
+        };
+        let txt = send_response(&listed, Some("https://example.org"), false, preflight);
+        assert!(txt
+            .find("Access-Control-Allow-Origin: https://example.org\r\n")
+            .is_some());
+        assert!(txt.find("Vary: Origin\r\n").is_some());
+        assert!(txt.find("Access-Control-Max-Age: ").is_none());
+        assert!(txt
+            .find("Strict-Transport-Security: max-age=86400\r\n")
+            .is_some());
+        assert!(txt.find("X-Frame-Options: DENY\r\n").is_some());
+
+        let txt = send_response(&listed, Some("https://example.net"), false, ok);
+        assert!(txt.find("Access-Control-Allow-").is_none());
+        assert!(txt.find("Vary: Origin\r\n").is_some());
+        assert!(txt.find("Cache-Control: public, max-age=30\r\n").is_some());
+
+        let txt = send_response(&listed, None, true, ok);
+        assert!(txt.find("Access-Control-Allow-").is_none());
+        assert!(txt.find("Cache-Control: no-cache\r\n").is_some());
+
+        let txt = send_response(&listed, None, false, not_found);
+        assert!(txt.find("Cache-Control: no-cache\r\n").is_some());
+        assert!(txt.find("X-Frame-Options: DENY\r\n").is_some());
+
+        // no origins disables CORS
+        let disabled = HttpResponseHeaders {
+            cors_allowed_origins: vec![],
+            ..HttpResponseHeaders::default()
+        };
+        let txt = send_response(&disabled, Some("https://example.com"), false, ok);
+        assert!(txt.find("Access-Control-").is_none());
+        assert!(txt.find("Vary: ").is_none());
+
+        assert_eq!(
+            HttpResponseHeaders::parse_header("X-Frame-Options: DENY"),
+            Some(("X-Frame-Options".to_string(), "DENY".to_string()))
+        );
+        assert_eq!(
+            HttpResponseHeaders::parse_header("Content-Security-Policy:default-src 'self'"),
+            Some((
+                "Content-Security-Policy".to_string(),
+                "default-src 'self'".to_string()
+            ))
+        );
+        assert_eq!(
+            HttpResponseHeaders::parse_header("X-Frame-Options DENY"),
+            None
+        );
+        assert_eq!(HttpResponseHeaders::parse_header(": DENY"), None);
+        assert_eq!(HttpResponseHeaders::parse_header("X Frame: DENY"), None);
+        assert_eq!(
+            HttpResponseHeaders::parse_header("X-Frame-Options: DENY\r\nSet-Cookie: a=b"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_http_response_compression() {
+        let source = ContractSrcResponse {
+            source: "(define-read-only (hello) \"hello world\")\n".repeat(100),
+            publish_height: 123,
+            marf_proof: None,
+        };
+        let body = serde_json::to_vec(&source).unwrap();
+
+        // (compress?, min size, accepted coding, expected coding)
+        let tests = vec![
+            (
+                true,
+                0,
+                Some(HttpContentEncoding::Gzip),
+                Some(HttpContentEncoding::Gzip),
+            ),
+            (
+                true,
+                0,
+                Some(HttpContentEncoding::Deflate),
+                Some(HttpContentEncoding::Deflate),
+            ),
+            (
+                true,
+                body.len() as u64,
+                Some(HttpContentEncoding::Gzip),
+                Some(HttpContentEncoding::Gzip),
+            ),
+            (
+                true,
+                body.len() as u64 + 1,
+                Some(HttpContentEncoding::Gzip),
+                None,
+            ),
+            (true, 0, None, None),
+            (false, 0, Some(HttpContentEncoding::Gzip), None),
+        ];
+        for (compress, min_size, accepted, expected) in tests.iter() {
+            let mut protocol = StacksHttp::new();
+            protocol.compress_responses = *compress;
+            protocol.compression_min_size = *min_size;
+
+            let mut md = HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true);
+            md.content_encoding = *accepted;
+
+            let mut bytes = vec![];
+            HttpResponseType::GetContractSrc(md.clone(), source.clone())
+                .send(&mut protocol, &mut bytes)
+                .unwrap();
+
+            let preamble_len = bytes
+                .windows(4)
+                .position(|w| w == "\r\n\r\n".as_bytes())
+                .unwrap()
+                + 4;
+            let preamble = str::from_utf8(&bytes[0..preamble_len]).unwrap();
+            match expected {
+                Some(encoding) => {
+                    assert!(preamble
+                        .find(&format!("Content-Encoding: {}\r\n", encoding.as_str()))
+                        .is_some());
+                    assert!(preamble.find("Vary: Accept-Encoding\r\n").is_some());
+                }
+                None => {
+                    assert!(preamble.find("Content-Encoding: ").is_none());
+                }
+            }
+            assert_eq!(decode_body(&bytes[preamble_len..], *expected), body);
+
+            // streamed responses are compressed no matter how small, and the caller learns how
+            let mut bytes = vec![];
+            HttpResponseType::BlockStream(md.clone())
+                .send(&mut protocol, &mut bytes)
+                .unwrap();
+            let stream_encoding = if *compress { *accepted } else { None };
+            assert_eq!(protocol.take_stream_encoding(), stream_encoding);
+            assert_eq!(protocol.take_stream_encoding(), None);
+            assert_eq!(
+                str::from_utf8(&bytes)
+                    .unwrap()
+                    .find("Content-Encoding: ")
+                    .is_some(),
+                stream_encoding.is_some()
+            );
+        }
+
+        // only large payloads are compressed
+        let mut preamble = HttpRequestPreamble::new(
+            HttpVersion::Http11,
+            "GET".to_string(),
+            "/v2/info".to_string(),
+            "localhost".to_string(),
+            20443,
+            true,
+        );
+        preamble.add_header("Accept-Encoding".to_string(), "gzip".to_string());
+        let md = HttpRequestMetadata::from_preamble(&preamble);
+        assert_eq!(md.accept_encoding, Some(HttpContentEncoding::Gzip));
+
+        let req = HttpRequestType::GetBlock(md.clone(), StacksBlockId([0x22; 32]));
+        assert_eq!(
+            HttpResponseMetadata::from(&req).content_encoding,
+            Some(HttpContentEncoding::Gzip)
         );
-        assert!(txt.find("Connection: ").is_none()); // not sent if keep_alive is true
+        let req = HttpRequestType::GetInfo(md.clone());
+        assert_eq!(HttpResponseMetadata::from(&req).content_encoding, None);
     }
 
     #[test]
@@ -4710,15 +6964,28 @@ mod test {
                 12345,
             ),
             keep_alive: true,
+            api_key: None,
+            range: None,
+            admin_token: None,
+            origin: None,
+            accept_encoding: None,
         };
         let http_request_metadata_dns = HttpRequestMetadata {
             version: HttpVersion::Http11,
             peer: PeerHost::DNS("www.foo.com".to_string(), 80),
             keep_alive: true,
+            api_key: None,
+            range: None,
+            admin_token: None,
+            origin: None,
+            accept_encoding: None,
         };
 
         let tests = vec![
-            HttpRequestType::GetNeighbors(http_request_metadata_ip.clone()),
+            HttpRequestType::GetNeighbors(
+                http_request_metadata_ip.clone(),
+                NeighborsQuery::default(),
+            ),
             HttpRequestType::GetBlock(http_request_metadata_dns.clone(), StacksBlockId([2u8; 32])),
             HttpRequestType::GetMicroblocksIndexed(
                 http_request_metadata_ip.clone(),
@@ -4823,6 +7090,488 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_http_request_api_key() {
+        let mut md = HttpRequestMetadata::new("127.0.0.1".to_string(), 20443);
+        md.api_key = Some("0123abcd".to_string());
+        let request = HttpRequestType::GetInfo(md);
+
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new();
+        http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+            .unwrap();
+        assert!(str::from_utf8(&bytes)
+            .unwrap()
+            .find("X-Api-Key: 0123abcd\r\n")
+            .is_some());
+
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        match http.read_payload(&preamble, &bytes[offset..]).unwrap() {
+            (StacksHttpMessage::Request(parsed), _) => {
+                assert_eq!(parsed.metadata().api_key, Some("0123abcd".to_string()));
+            }
+            x => panic!("Expected a request, got {:?}", &x),
+        }
+
+        // no header, no key
+        let request_str =
+            "GET /v2/info HTTP/1.1\r\nUser-Agent: stacks/2.0\r\nHost: 127.0.0.1:20443\r\n\r\n";
+        let (preamble, offset) = http.read_preamble(request_str.as_bytes()).unwrap();
+        match http
+            .read_payload(&preamble, &request_str.as_bytes()[offset..])
+            .unwrap()
+        {
+            (StacksHttpMessage::Request(parsed), _) => {
+                assert_eq!(parsed.metadata().api_key, None);
+            }
+            x => panic!("Expected a request, got {:?}", &x),
+        }
+    }
+
+    #[test]
+    fn test_http_byte_range() {
+        assert_eq!(
+            HttpByteRange::parse("bytes=10-49"),
+            Some(HttpByteRange::FromTo(10, 49))
+        );
+        assert_eq!(
+            HttpByteRange::parse("bytes=10-"),
+            Some(HttpByteRange::From(10))
+        );
+        assert_eq!(
+            HttpByteRange::parse("bytes=-10"),
+            Some(HttpByteRange::Suffix(10))
+        );
+        for bad_range in [
+            "bytes=49-10",
+            "bytes=-",
+            "bytes=0-1,5-6",
+            "items=0-1",
+            "bytes=a-b",
+        ]
+        .iter()
+        {
+            assert_eq!(HttpByteRange::parse(bad_range), None);
+        }
+
+        assert_eq!(HttpByteRange::FromTo(10, 49).resolve(100), Some((10, 50)));
+        assert_eq!(HttpByteRange::FromTo(10, 490).resolve(100), Some((10, 100)));
+        assert_eq!(HttpByteRange::FromTo(100, 490).resolve(100), None);
+        assert_eq!(HttpByteRange::From(99).resolve(100), Some((99, 100)));
+        assert_eq!(HttpByteRange::From(100).resolve(100), None);
+        assert_eq!(HttpByteRange::Suffix(10).resolve(100), Some((90, 100)));
+        assert_eq!(HttpByteRange::Suffix(1000).resolve(100), Some((0, 100)));
+        assert_eq!(HttpByteRange::Suffix(0).resolve(100), None);
+
+        // the range goes out with the request
+        let mut md = HttpRequestMetadata::new("127.0.0.1".to_string(), 20443);
+        md.range = Some(HttpByteRange::FromTo(10, 49));
+        let request = HttpRequestType::GetBlock(md, StacksBlockId([2u8; 32]));
+
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new();
+        http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+            .unwrap();
+        assert!(str::from_utf8(&bytes)
+            .unwrap()
+            .find("Range: bytes=10-49\r\n")
+            .is_some());
+
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        match http.read_payload(&preamble, &bytes[offset..]).unwrap() {
+            (StacksHttpMessage::Request(parsed), _) => {
+                assert_eq!(parsed.metadata().range, Some(HttpByteRange::FromTo(10, 49)));
+            }
+            x => panic!("Expected a request, got {:?}", &x),
+        }
+
+        // a ranged stream's preamble says which bytes it carries
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new();
+        http.begin_request(
+            HttpVersion::Http11,
+            format!("/v2/blocks/{}", StacksBlockId([2u8; 32]).to_hex()),
+        );
+        http.write_message(
+            &mut bytes,
+            &StacksHttpMessage::Response(HttpResponseType::PartialStream(
+                HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+                10,
+                49,
+                100,
+            )),
+        )
+        .unwrap();
+        let txt = str::from_utf8(&bytes).unwrap();
+        assert!(txt.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+        assert!(txt.find("Content-Range: bytes 10-49/100\r\n").is_some());
+        assert!(txt.find("Transfer-Encoding: chunked").is_some());
+
+        // and a partial body is received as bytes
+        let body = vec![7u8; 40];
+        for content_length in [Some(40), None].iter() {
+            let mut bytes = vec![];
+            let mut http = StacksHttp::new();
+            http.begin_request(
+                HttpVersion::Http11,
+                format!("/v2/blocks/{}", StacksBlockId([2u8; 32]).to_hex()),
+            );
+            http.write_message(
+                &mut bytes,
+                &StacksHttpMessage::Response(HttpResponseType::PartialContent(
+                    HttpResponseMetadata::new(HttpVersion::Http11, 123, *content_length, true),
+                    body.clone(),
+                )),
+            )
+            .unwrap();
+
+            let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+            let message = if content_length.is_some() {
+                http.read_payload(&preamble, &bytes[offset..]).unwrap().0
+            } else {
+                let (message_opt, _) = http
+                    .stream_payload(&preamble, &mut &bytes[offset..])
+                    .unwrap();
+                message_opt.unwrap().0
+            };
+            match message {
+                StacksHttpMessage::Response(HttpResponseType::PartialContent(_, parsed)) => {
+                    assert_eq!(parsed, body);
+                }
+                x => panic!("Expected a partial response, got {:?}", &x),
+            }
+        }
+    }
+
+    #[test]
+    fn test_http_request_json_values() {
+        let key = Value::Tuple(
+            TupleData::from_data(vec![
+                ("a".into(), Value::UInt(1)),
+                ("b".into(), Value::none()),
+            ])
+            .unwrap(),
+        );
+        let bodies = [
+            // JSON-encoded arguments and hex-encoded arguments can be mixed
+            format!(
+                "{{\"sender\":\"ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R\",\"arguments\":[{},\"0x{}\"]}}",
+                key.to_json(),
+                Value::Int(-1).serialize()
+            ),
+            format!("{}", key.to_json()),
+        ];
+        let paths = [
+            "/v2/contracts/call-read/ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R/hello-world/ro-test",
+            "/v2/map_entry/ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R/hello-world/unit-map",
+        ];
+
+        for (body, path) in bodies.iter().zip(paths.iter()) {
+            let request_str = format!("POST {} HTTP/1.1\r\nUser-Agent: stacks/2.0\r\nHost: 127.0.0.1:20443\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", path, body.len(), body);
+            let mut http = StacksHttp::new();
+            let (preamble, offset) = http.read_preamble(request_str.as_bytes()).unwrap();
+            match http
+                .read_payload(&preamble, &request_str.as_bytes()[offset..])
+                .unwrap()
+            {
+                (
+                    StacksHttpMessage::Request(HttpRequestType::CallReadOnlyFunction(
+                        _,
+                        _,
+                        _,
+                        _,
+                        _,
+                        arguments,
+                        _,
+                    )),
+                    _,
+                ) => {
+                    assert_eq!(arguments, vec![key.clone(), Value::Int(-1)]);
+                }
+                (
+                    StacksHttpMessage::Request(HttpRequestType::GetMapEntry(
+                        _,
+                        _,
+                        _,
+                        _,
+                        map_key,
+                        _,
+                        _,
+                    )),
+                    _,
+                ) => {
+                    assert_eq!(map_key, key);
+                }
+                x => panic!("Expected a request, got {:?}", &x),
+            }
+        }
+
+        // not a Clarity value
+        let body = "{\"type\":\"uint\",\"value\":\"-1\"}";
+        let request_str = format!("POST /v2/map_entry/ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R/hello-world/unit-map HTTP/1.1\r\nUser-Agent: stacks/2.0\r\nHost: 127.0.0.1:20443\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+        let mut http = StacksHttp::new();
+        let (preamble, offset) = http.read_preamble(request_str.as_bytes()).unwrap();
+        assert!(http
+            .read_payload(&preamble, &request_str.as_bytes()[offset..])
+            .is_err());
+    }
+
+    #[test]
+    fn test_http_request_attachment() {
+        let md = HttpRequestMetadata::new("127.0.0.1".to_string(), 20443);
+        let attachment = Attachment::new("hello world".as_bytes().to_vec());
+        let requests = vec![
+            HttpRequestType::GetAttachment(md.clone(), attachment.hash()),
+            HttpRequestType::PostAttachment(md.clone(), attachment.clone()),
+        ];
+
+        for request in requests.into_iter() {
+            let mut bytes = vec![];
+            let mut http = StacksHttp::new();
+            http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+                .unwrap();
+
+            let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+            match http.read_payload(&preamble, &bytes[offset..]).unwrap() {
+                (StacksHttpMessage::Request(parsed), _) => {
+                    assert_eq!(parsed.request_path(), request.request_path());
+                    match (parsed, request) {
+                        (
+                            HttpRequestType::GetAttachment(_, parsed_hash),
+                            HttpRequestType::GetAttachment(_, hash),
+                        ) => assert_eq!(parsed_hash, hash),
+                        (
+                            HttpRequestType::PostAttachment(_, parsed_attachment),
+                            HttpRequestType::PostAttachment(_, attachment),
+                        ) => assert_eq!(parsed_attachment, attachment),
+                        x => panic!("Mismatched requests: {:?}", &x),
+                    }
+                }
+                x => panic!("Expected a request, got {:?}", &x),
+            }
+        }
+
+        // too big
+        let mut request_bytes = format!("POST /v2/attachments HTTP/1.1\r\nUser-Agent: stacks/2.0\r\nHost: 127.0.0.1:20443\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\n\r\n", MAX_ATTACHMENT_SIZE + 1).into_bytes();
+        request_bytes.append(&mut vec![0u8; (MAX_ATTACHMENT_SIZE + 1) as usize]);
+        let mut http = StacksHttp::new();
+        let (preamble, offset) = http.read_preamble(&request_bytes).unwrap();
+        match http
+            .read_payload(&preamble, &request_bytes[offset..])
+            .unwrap()
+        {
+            (
+                StacksHttpMessage::Request(HttpRequestType::ClientError(
+                    _,
+                    ClientError::Message(msg),
+                )),
+                _,
+            ) => {
+                assert!(msg.find("too big").is_some());
+            }
+            x => panic!("Expected a client error, got {:?}", &x),
+        }
+    }
+
+    #[test]
+    fn test_http_request_neighbors_query() {
+        let md = HttpRequestMetadata::new("127.0.0.1".to_string(), 20443);
+        let queries = vec![
+            NeighborsQuery::default(),
+            NeighborsQuery {
+                direction: Some(NeighborDirection::Outbound),
+                state: Some(NeighborState::Banned),
+                address: Some(PeerAddress::from_ipv4(1, 2, 3, 4)),
+                offset: 10,
+                limit: Some(5),
+            },
+            NeighborsQuery {
+                direction: Some(NeighborDirection::Inbound),
+                state: Some(NeighborState::Allowed),
+                address: Some(PeerAddress([
+                    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+                ])),
+                offset: 0,
+                limit: None,
+            },
+        ];
+
+        for query in queries.into_iter() {
+            let request = HttpRequestType::GetNeighbors(md.clone(), query.clone());
+            let mut bytes = vec![];
+            let mut http = StacksHttp::new();
+            http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+                .unwrap();
+
+            let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+            match http.read_payload(&preamble, &bytes[offset..]).unwrap() {
+                (StacksHttpMessage::Request(HttpRequestType::GetNeighbors(_, parsed_query)), _) => {
+                    assert_eq!(parsed_query, query);
+                }
+                x => panic!("Expected a request, got {:?}", &x),
+            }
+        }
+
+        assert_eq!(
+            HttpRequestType::GetNeighbors(md.clone(), NeighborsQuery::default()).request_path(),
+            "/v2/neighbors"
+        );
+
+        for bad_query in [
+            "direction=sideways",
+            "state=ignored",
+            "address=not-an-ip",
+            "offset=-1",
+            "limit=many",
+        ]
+        .iter()
+        {
+            let request_str = format!(
+                "GET /v2/neighbors?{} HTTP/1.1\r\nUser-Agent: stacks/2.0\r\nHost: 127.0.0.1:20443\r\n\r\n",
+                bad_query
+            );
+            let mut http = StacksHttp::new();
+            let (preamble, offset) = http.read_preamble(request_str.as_bytes()).unwrap();
+            match http
+                .read_payload(&preamble, &request_str.as_bytes()[offset..])
+                .unwrap()
+            {
+                (
+                    StacksHttpMessage::Request(HttpRequestType::ClientError(
+                        _,
+                        ClientError::Message(_),
+                    )),
+                    _,
+                ) => {}
+                x => panic!("Expected a client error, got {:?}", &x),
+            }
+        }
+    }
+
+    #[test]
+    fn test_http_request_multi_call_read_only() {
+        let md = HttpRequestMetadata::new("127.0.0.1".to_string(), 20443);
+        let contract_address =
+            StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R").unwrap();
+        let calls = vec![
+            ReadOnlyCall {
+                contract_address: contract_address.clone(),
+                contract_name: ContractName::try_from("hello-world".to_string()).unwrap(),
+                function_name: ClarityName::try_from("ro-test".to_string()).unwrap(),
+                arguments: vec![],
+            },
+            ReadOnlyCall {
+                contract_address: contract_address.clone(),
+                contract_name: ContractName::try_from("hello-world".to_string()).unwrap(),
+                function_name: ClarityName::try_from("ro-args".to_string()).unwrap(),
+                arguments: vec![Value::Int(-1), Value::some(Value::UInt(2)).unwrap()],
+            },
+        ];
+        let request = HttpRequestType::MultiCallReadOnly(
+            md,
+            contract_address.to_account_principal(),
+            calls.clone(),
+            Some(StacksBlockId([1u8; 32])),
+        );
+
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new();
+        http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+            .unwrap();
+
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        match http.read_payload(&preamble, &bytes[offset..]).unwrap() {
+            (
+                StacksHttpMessage::Request(HttpRequestType::MultiCallReadOnly(
+                    _,
+                    parsed_sender,
+                    parsed_calls,
+                    parsed_tip,
+                )),
+                _,
+            ) => {
+                assert_eq!(parsed_sender, contract_address.to_account_principal());
+                assert_eq!(parsed_calls, calls);
+                assert_eq!(parsed_tip, Some(StacksBlockId([1u8; 32])));
+            }
+            x => panic!("Expected a request, got {:?}", &x),
+        }
+
+        // no calls, and too many calls
+        let call = "{\"contract_address\":\"ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R\",\"contract_name\":\"hello-world\",\"function_name\":\"ro-test\",\"arguments\":[]}";
+        for num_calls in [0, MAX_MULTI_CALL_READS + 1].iter() {
+            let body = format!(
+                "{{\"sender\":\"ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R\",\"calls\":[{}]}}",
+                vec![call; *num_calls].join(",")
+            );
+            let request_str = format!("POST /v2/contracts/multi-call-read HTTP/1.1\r\nUser-Agent: stacks/2.0\r\nHost: 127.0.0.1:20443\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+            let mut http = StacksHttp::new();
+            let (preamble, offset) = http.read_preamble(request_str.as_bytes()).unwrap();
+            assert!(http
+                .read_payload(&preamble, &request_str.as_bytes()[offset..])
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn test_http_request_admin() {
+        let mut md = HttpRequestMetadata::new("127.0.0.1".to_string(), 20443);
+        md.admin_token = Some("0123abcd".to_string());
+        let requests = vec![
+            HttpRequestType::AdminBanPeer(
+                md.clone(),
+                PeerAddress::from_ipv4(1, 2, 3, 4),
+                20444,
+                3600,
+            ),
+            HttpRequestType::AdminEvictTransaction(md.clone(), Txid([1u8; 32])),
+            HttpRequestType::AdminFlushChainstate(md.clone()),
+            HttpRequestType::AdminSetMining(md.clone(), false),
+        ];
+
+        for request in requests.into_iter() {
+            let mut bytes = vec![];
+            let mut http = StacksHttp::new();
+            http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+                .unwrap();
+            assert!(str::from_utf8(&bytes)
+                .unwrap()
+                .find("Authorization: Bearer 0123abcd\r\n")
+                .is_some());
+
+            let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+            match http.read_payload(&preamble, &bytes[offset..]).unwrap() {
+                (StacksHttpMessage::Request(mut parsed), _) => {
+                    assert_eq!(parsed.metadata().admin_token, md.admin_token);
+                    *parsed.metadata_mut() = md.clone();
+                    assert_eq!(parsed, request);
+                }
+                x => panic!("Expected a request, got {:?}", &x),
+            }
+        }
+
+        // the ban duration defaults
+        let body = "{\"address\":\"1.2.3.4\",\"port\":20444}";
+        let request_str = format!("POST /v2/admin/peers/ban HTTP/1.1\r\nUser-Agent: stacks/2.0\r\nHost: 127.0.0.1:20443\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+        let mut http = StacksHttp::new();
+        let (preamble, offset) = http.read_preamble(request_str.as_bytes()).unwrap();
+        match http
+            .read_payload(&preamble, &request_str.as_bytes()[offset..])
+            .unwrap()
+        {
+            (
+                StacksHttpMessage::Request(HttpRequestType::AdminBanPeer(md, addr, port, duration)),
+                _,
+            ) => {
+                assert_eq!(md.admin_token, None);
+                assert_eq!(addr, PeerAddress::from_ipv4(1, 2, 3, 4));
+                assert_eq!(port, 20444);
+                assert_eq!(duration, DEFAULT_ADMIN_BAN_DURATION);
+            }
+            x => panic!("Expected a request, got {:?}", &x),
+        }
+    }
+
     #[test]
     fn test_http_request_type_codec_err() {
         let bad_content_lengths = vec![
@@ -4877,6 +7626,7 @@ mod test {
                     )
                     .unwrap(),
                     authenticated: true,
+                    last_contact_time: Some(1234567890),
                 },
                 RPCNeighbor {
                     network_id: 3,
@@ -4891,6 +7641,7 @@ mod test {
                     )
                     .unwrap(),
                     authenticated: false,
+                    last_contact_time: None,
                 },
             ],
             inbound: vec![],
@@ -4969,6 +7720,102 @@ mod test {
                 "/v2/transactions".to_string(),
             ),
             // length is unknown
+            (
+                HttpResponseType::FeeEstimate(
+                    HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+                    RPCFeeEstimateData {
+                        estimated_len: 200,
+                        fee_rates: FeeRateEstimate {
+                            low: 1,
+                            medium: 2,
+                            high: 3,
+                        },
+                        fees: FeeRateEstimate {
+                            low: 200,
+                            medium: 400,
+                            high: 600,
+                        },
+                        median_block_fill: 95,
+                        num_blocks: 32,
+                        sample_size: 100,
+                    },
+                ),
+                "/v2/fees/transaction?len=200".to_string(),
+            ),
+            // length is unknown
+            (
+                HttpResponseType::Forks(
+                    HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+                    RPCForksData {
+                        canonical_tip: StacksBlockId([0x11; 32]),
+                        stacks_tip_height: 100,
+                        forks: vec![
+                            RPCForkTipData {
+                                index_block_hash: StacksBlockId([0x11; 32]),
+                                consensus_hash: ConsensusHash([0x12; 20]),
+                                block_hash: BlockHeaderHash([0x13; 32]),
+                                block_height: 100,
+                                total_burn: 1000,
+                                canonical: true,
+                                fork_point: StacksBlockId([0x11; 32]),
+                                fork_point_height: 100,
+                                length: 0,
+                            },
+                            RPCForkTipData {
+                                index_block_hash: StacksBlockId([0x21; 32]),
+                                consensus_hash: ConsensusHash([0x22; 20]),
+                                block_hash: BlockHeaderHash([0x23; 32]),
+                                block_height: 98,
+                                total_burn: 950,
+                                canonical: false,
+                                fork_point: StacksBlockId([0x31; 32]),
+                                fork_point_height: 96,
+                                length: 2,
+                            },
+                        ],
+                    },
+                ),
+                "/v2/forks".to_string(),
+            ),
+            // length is unknown
+            (
+                HttpResponseType::BlockValidation(
+                    HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+                    RPCBlockValidationData {
+                        block_hash: BlockHeaderHash([0x11; 32]),
+                        parent_index_block_hash: StacksBlockId([0x22; 32]),
+                        valid: false,
+                        state_index_root: None,
+                        execution_cost: ExecutionCost::zero(),
+                        total_fees: 0,
+                        block_errors: vec![],
+                        tx_errors: vec![RPCTxValidationError {
+                            tx_index: 1,
+                            txid: Txid([0x33; 32]).to_hex(),
+                            reason: "Bad signature".to_string(),
+                        }],
+                    },
+                ),
+                "/v2/blocks/validate".to_string(),
+            ),
+            // length is unknown
+            (
+                HttpResponseType::TransactionReceipt(
+                    HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+                    TransactionReceiptData {
+                        txid: format!("0x{}", Txid([0x1; 32])),
+                        index_block_hash: format!("0x{}", StacksBlockId([0x2; 32])),
+                        block_height: 3,
+                        tx_index: 4,
+                        status: "success".to_string(),
+                        result: "0x0703".to_string(),
+                        stx_burned: "0".to_string(),
+                        execution_cost: ExecutionCost::zero(),
+                        events: vec![],
+                    },
+                ),
+                format!("/v2/transactions/{}/receipt", Txid([0x1; 32]).to_hex()),
+            ),
             (
                 HttpResponseType::Neighbors(
                     HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
@@ -5000,6 +7847,25 @@ mod test {
                 ),
                 "/v2/transactions".to_string(),
             ),
+            (
+                HttpResponseType::GetAttachment(
+                    HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+                    GetAttachmentResponse {
+                        attachment: Attachment::new(vec![0x01, 0x02, 0x03]),
+                    },
+                ),
+                format!(
+                    "/v2/attachments/{}",
+                    Attachment::new(vec![0x01, 0x02, 0x03]).hash().to_hex()
+                ),
+            ),
+            (
+                HttpResponseType::AttachmentHash(
+                    HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+                    Hash160([0x1; 20]),
+                ),
+                "/v2/attachments".to_string(),
+            ),
             // errors without error messages
             (
                 HttpResponseType::BadRequest(
@@ -14,22 +14,32 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+pub mod admin;
 pub mod asn;
+pub mod apikeys;
+pub mod atlas;
+pub mod audit;
 pub mod chat;
 pub mod codec;
 pub mod connection;
 pub mod db;
 pub mod dns;
+pub mod divergence;
 pub mod download;
+pub mod faucet;
 pub mod http;
 pub mod inv;
 pub mod neighbors;
 pub mod p2p;
 pub mod poll;
+pub mod portmap;
 pub mod prune;
 pub mod relay;
+pub mod reputation;
 pub mod rpc;
 pub mod server;
+pub mod tls;
+pub mod websocket;
 
 use std::borrow::Borrow;
 use std::cmp::PartialEq;
@@ -61,6 +71,7 @@ use serde_json;
 
 use regex::Regex;
 
+use core::fees::FeeRateEstimate;
 use core::mempool::*;
 
 use burnchains::BurnchainHeaderHash;
@@ -73,11 +84,13 @@ use chainstate::burn::ConsensusHash;
 use chainstate::burn::db::sortdb::PoxId;
 
 use chainstate::stacks::db::blocks::MemPoolRejection;
+use chainstate::stacks::events::TransactionReceiptData;
 use chainstate::stacks::{
     Error as chain_error, StacksAddress, StacksBlock, StacksBlockId, StacksMicroblock,
     StacksPublicKey, StacksTransaction,
 };
 
+use chainstate::stacks::index::TrieHash;
 use chainstate::stacks::Error as chainstate_error;
 
 use vm::{
@@ -106,7 +119,9 @@ use serde::ser::Error as ser_Error;
 
 use chainstate::stacks::index::Error as marf_error;
 use vm::clarity::Error as clarity_error;
+use vm::costs::ExecutionCost;
 
+use self::atlas::Attachment;
 use self::dns::*;
 
 use core::POX_REWARD_CYCLE_LENGTH;
@@ -215,6 +230,8 @@ pub enum Error {
     ConnectionCycle,
     /// Requested data not found
     NotFoundError,
+    /// Failed to set up TLS
+    TlsError(String),
 }
 
 /// Enum for passing data for ClientErrors
@@ -297,6 +314,7 @@ impl fmt::Display for Error {
             Error::StaleView => write!(f, "State view is stale"),
             Error::ConnectionCycle => write!(f, "Tried to connect to myself"),
             Error::NotFoundError => write!(f, "Requested data not found"),
+            Error::TlsError(ref s) => fmt::Display::fmt(s, f),
         }
     }
 }
@@ -355,6 +373,7 @@ impl error::Error for Error {
             Error::StaleView => None,
             Error::ConnectionCycle => None,
             Error::NotFoundError => None,
+            Error::TlsError(_) => None,
         }
     }
 }
@@ -635,6 +654,59 @@ impl FromStr for HttpContentType {
     }
 }
 
+/// content codings a response body can be compressed with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HttpContentEncoding {
+    Gzip,
+    /// the zlib format, as HTTP's "deflate" coding means
+    Deflate,
+}
+
+impl HttpContentEncoding {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            HttpContentEncoding::Gzip => "gzip",
+            HttpContentEncoding::Deflate => "deflate",
+        }
+    }
+
+    /// Choose the coding to compress a response with, given the request's Accept-Encoding
+    /// header.  The coding with the highest quality value wins, and gzip wins ties.  Returns
+    /// None if the client accepts neither.
+    pub fn from_accept_encoding(header: &str) -> Option<HttpContentEncoding> {
+        let mut gzip_q = None;
+        let mut deflate_q = None;
+        let mut any_q = None;
+        for item in header.split(',') {
+            let mut params = item.split(';');
+            let coding = params.next().unwrap_or("").trim().to_lowercase();
+            let mut q = 1.0;
+            for param in params {
+                let param = param.trim();
+                if param.starts_with("q=") || param.starts_with("Q=") {
+                    q = param[2..].trim().parse::<f64>().unwrap_or(0.0);
+                }
+            }
+            match coding.as_str() {
+                "gzip" | "x-gzip" => gzip_q = Some(q),
+                "deflate" => deflate_q = Some(q),
+                "*" => any_q = Some(q),
+                _ => {}
+            }
+        }
+
+        let gzip_q = gzip_q.or(any_q).unwrap_or(0.0);
+        let deflate_q = deflate_q.or(any_q).unwrap_or(0.0);
+        if gzip_q > 0.0 && gzip_q >= deflate_q {
+            Some(HttpContentEncoding::Gzip)
+        } else if deflate_q > 0.0 {
+            Some(HttpContentEncoding::Deflate)
+        } else {
+            None
+        }
+    }
+}
+
 /// HTTP request preamble
 #[derive(Debug, Clone, PartialEq)]
 pub struct HttpRequestPreamble {
@@ -1000,6 +1072,9 @@ pub struct RPCPeerInfoData {
     pub stacks_tip_consensus_hash: String,
     pub unanchored_tip: StacksBlockId,
     pub exit_at_block_height: Option<u64>,
+    /// blocks this node rejected, but most of its neighbors accepted (see net::divergence)
+    #[serde(default)]
+    pub divergent_blocks: Vec<StacksBlockId>,
 }
 
 /// The data we return on GET /v2/pox
@@ -1016,6 +1091,78 @@ pub struct RPCPoxInfoData {
     pub total_liquid_supply_ustx: u128,
 }
 
+/// The data we return on GET /v2/burn_eta
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCBurnEtaData {
+    pub target_burn_block_height: u64,
+    pub burn_block_height: u64,
+    pub blocks_remaining: u64,
+    pub avg_burn_block_time_secs: f64,
+    pub sample_size: u64,
+    pub estimated_seconds: u64,
+    pub estimated_seconds_low: u64,
+    pub estimated_seconds_high: u64,
+    pub estimated_time: u64,
+}
+
+/// The data we return on GET /v2/fees/transaction
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCFeeEstimateData {
+    pub estimated_len: u64,
+    pub fee_rates: FeeRateEstimate,
+    pub fees: FeeRateEstimate,
+    pub median_block_fill: u64,
+    pub num_blocks: u64,
+    pub sample_size: u64,
+}
+
+/// A Stacks fork tip, as returned on GET /v2/forks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCForkTipData {
+    pub index_block_hash: StacksBlockId,
+    pub consensus_hash: ConsensusHash,
+    pub block_hash: BlockHeaderHash,
+    pub block_height: u64,
+    pub total_burn: u64,
+    /// whether or not this is the tip of the canonical fork
+    pub canonical: bool,
+    /// the highest block this fork has in common with the canonical fork
+    pub fork_point: StacksBlockId,
+    pub fork_point_height: u64,
+    /// how many blocks this fork has built since diverging from the canonical fork
+    pub length: u64,
+}
+
+/// The data we return on GET /v2/forks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCForksData {
+    pub canonical_tip: StacksBlockId,
+    pub stacks_tip_height: u64,
+    pub forks: Vec<RPCForkTipData>,
+}
+
+/// A transaction in a proposed block that failed validation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCTxValidationError {
+    pub tx_index: u32,
+    pub txid: String,
+    pub reason: String,
+}
+
+/// The data we return on POST /v2/blocks/validate
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCBlockValidationData {
+    pub block_hash: BlockHeaderHash,
+    pub parent_index_block_hash: StacksBlockId,
+    pub valid: bool,
+    /// the state root the block would produce, if all of its transactions could be evaluated
+    pub state_index_root: Option<TrieHash>,
+    pub execution_cost: ExecutionCost,
+    pub total_fees: u64,
+    pub block_errors: Vec<String>,
+    pub tx_errors: Vec<RPCTxValidationError>,
+}
+
 #[derive(Debug, Clone, PartialEq, Copy, Hash)]
 #[repr(u8)]
 pub enum HttpVersion {
@@ -1028,17 +1175,51 @@ pub struct HttpRequestMetadata {
     pub version: HttpVersion,
     pub peer: PeerHost,
     pub keep_alive: bool,
+    /// the X-Api-Key header, if given
+    pub api_key: Option<String>,
+    /// the Range header, if given and well-formed
+    pub range: Option<HttpByteRange>,
+    /// the token of a `Bearer` Authorization header, if given
+    pub admin_token: Option<String>,
+    /// the Origin header of a cross-origin request, if given
+    pub origin: Option<String>,
+    /// the coding the client would like compressed responses in, from its Accept-Encoding
+    /// header
+    pub accept_encoding: Option<HttpContentEncoding>,
+}
+
+/// A single range of bytes from an HTTP Range header
+#[derive(Debug, Clone, Copy, PartialEq, Hash)]
+pub enum HttpByteRange {
+    /// from the first offset through the second, inclusive
+    FromTo(u64, u64),
+    /// from the offset to the end
+    From(u64),
+    /// the last this-many bytes
+    Suffix(u64),
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MapEntryResponse {
     pub data: String,
+    /// the entry in the JSON encoding of Clarity values (see `vm::representations::json`)
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json: Option<serde_json::Value>,
+    /// index block hash of the chain tip the entry was read at, whose state root the proof is
+    /// against
+    pub tip: StacksBlockId,
     #[serde(rename = "proof")]
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub marf_proof: Option<String>,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GetAttachmentResponse {
+    pub attachment: Attachment,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ContractSrcResponse {
     pub source: String,
@@ -1055,17 +1236,33 @@ pub struct CallReadOnlyResponse {
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub result: Option<String>,
+    /// the result in the JSON encoding of Clarity values (see `vm::representations::json`)
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json: Option<serde_json::Value>,
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cause: Option<String>,
 }
 
+/// The results of a batch of read-only calls, all evaluated at the same chain tip
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MultiCallReadOnlyResponse {
+    /// index block hash of the chain tip the calls were evaluated at
+    pub tip: StacksBlockId,
+    /// the result of each call, in the order the calls were given
+    pub results: Vec<CallReadOnlyResponse>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AccountEntryResponse {
     pub balance: String,
     pub locked: String,
     pub unlock_height: u64,
     pub nonce: u64,
+    /// index block hash of the chain tip the account was read at, whose state root the proofs
+    /// are against
+    pub tip: StacksBlockId,
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
     pub balance_proof: Option<String>,
@@ -1074,6 +1271,66 @@ pub struct AccountEntryResponse {
     pub nonce_proof: Option<String>,
 }
 
+/// An account's nonces on the chain tip and in the mempool, so a wallet can tell which nonce to
+/// use next and which transactions it still has to send
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountNoncesResponse {
+    /// the account's next nonce on the chain tip
+    pub confirmed_nonce: u64,
+    /// the highest nonce of the account's transactions in the mempool, if it has any
+    pub mempool_max_nonce: Option<u64>,
+    /// nonces between the confirmed nonce and the highest mempool nonce that no transaction in
+    /// the mempool uses.  The transactions after the first of these are held until it is filled.
+    pub missing_nonces: Vec<u64>,
+    /// index block hash of the chain tip the nonces were read at
+    pub tip: StacksBlockId,
+}
+
+/// A reward an address earned in one Stacks block, by mining it or by supporting its miner.
+/// uSTX amounts are hex-encoded big-endian u128s.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MinerRewardEntry {
+    pub index_block_hash: StacksBlockId,
+    pub block_height: u64,
+    /// true if the address mined the block, false if it supported its miner with a burn
+    pub miner: bool,
+    pub burnchain_commit_burn: u64,
+    /// height of the Stacks block that pays the reward out
+    pub maturity_height: u64,
+    pub matured: bool,
+    /// the coinbase and tx fees the block scheduled
+    pub scheduled_coinbase: String,
+    pub scheduled_tx_fees_anchored: String,
+    pub scheduled_tx_fees_streamed: String,
+    /// what the reward paid, once it has matured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub paid_coinbase: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub paid_tx_fees_anchored: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub paid_tx_fees_streamed_produced: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub paid_tx_fees_streamed_confirmed: Option<String>,
+}
+
+/// The rewards an address earned in the most recent blocks it mined or supported, most recent
+/// first
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MinerRewardsResponse {
+    pub address: String,
+    pub rewards: Vec<MinerRewardEntry>,
+    /// total uSTX paid out by the rewards that have matured
+    pub total_matured: String,
+    /// total coinbase and tx fees scheduled by the rewards that have not matured yet
+    pub total_pending: String,
+    /// index block hash of the chain tip the rewards were read at
+    pub tip: StacksBlockId,
+}
+
 /// Request ID to use or expect from non-Stacks HTTP clients.
 /// In particular, if a HTTP response does not contain the x-request-id header, then it's assumed
 /// to be this value.  This is needed to support fetching immutables like block and microblock data
@@ -1086,6 +1343,11 @@ impl HttpRequestMetadata {
             version: HttpVersion::Http11,
             peer: PeerHost::from_host_port(host, port),
             keep_alive: true,
+            api_key: None,
+            range: None,
+            admin_token: None,
+            origin: None,
+            accept_encoding: None,
         }
     }
 
@@ -1094,6 +1356,11 @@ impl HttpRequestMetadata {
             version: HttpVersion::Http11,
             peer: peer_host,
             keep_alive: true,
+            api_key: None,
+            range: None,
+            admin_token: None,
+            origin: None,
+            accept_encoding: None,
         }
     }
 
@@ -1102,6 +1369,21 @@ impl HttpRequestMetadata {
             version: preamble.version,
             peer: preamble.host.clone(),
             keep_alive: preamble.keep_alive,
+            api_key: preamble.headers.get("x-api-key").cloned(),
+            range: preamble
+                .headers
+                .get("range")
+                .and_then(|range| HttpByteRange::parse(range)),
+            admin_token: preamble
+                .headers
+                .get("authorization")
+                .and_then(|auth| auth.strip_prefix("Bearer "))
+                .map(|token| token.trim().to_string()),
+            origin: preamble.headers.get("origin").cloned(),
+            accept_encoding: preamble
+                .headers
+                .get("accept-encoding")
+                .and_then(|header| HttpContentEncoding::from_accept_encoding(header)),
         }
     }
 }
@@ -1109,7 +1391,56 @@ impl HttpRequestMetadata {
 #[derive(Serialize, Deserialize)]
 pub struct CallReadOnlyRequestBody {
     pub sender: String,
-    pub arguments: Vec<String>,
+    /// each argument is either the hex string of its consensus serialization, or its JSON
+    /// encoding (see `vm::representations::json`)
+    pub arguments: Vec<serde_json::Value>,
+}
+
+/// One read-only call in a batch
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadOnlyCall {
+    pub contract_address: StacksAddress,
+    pub contract_name: ContractName,
+    pub function_name: ClarityName,
+    pub arguments: Vec<Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MultiCallReadOnlyCallBody {
+    pub contract_address: String,
+    pub contract_name: String,
+    pub function_name: String,
+    /// encoded as in `CallReadOnlyRequestBody`
+    pub arguments: Vec<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MultiCallReadOnlyRequestBody {
+    pub sender: String,
+    pub calls: Vec<MultiCallReadOnlyCallBody>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AdminBanPeerRequestBody {
+    pub address: String,
+    pub port: u16,
+    /// seconds; defaults to `admin::DEFAULT_ADMIN_BAN_DURATION`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AdminSetMiningRequestBody {
+    pub enabled: bool,
+}
+
+/// Struct given back from a call to a `/v2/admin/*` endpoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCAdminResponse {
+    /// the action taken, e.g. `ban-peer`
+    pub action: String,
+    pub detail: String,
 }
 
 /// Items in the NeighborsInfo -- combines NeighborKey and NeighborAddress
@@ -1122,6 +1453,10 @@ pub struct RPCNeighbor {
     pub port: u16,
     pub public_key_hash: Hash160,
     pub authenticated: bool,
+    /// when we last completed a handshake with this peer, if it is in the PeerDB
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_contact_time: Option<u64>,
 }
 
 impl RPCNeighbor {
@@ -1133,10 +1468,36 @@ impl RPCNeighbor {
             port: nk.port,
             public_key_hash: pkh,
             authenticated: auth,
+            last_contact_time: None,
         }
     }
 }
 
+/// Which connections a `/v2/neighbors` request lists
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NeighborDirection {
+    Inbound,
+    Outbound,
+}
+
+/// Which PeerDB state a `/v2/neighbors` request filters on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NeighborState {
+    Allowed,
+    Banned,
+}
+
+/// Filters and pagination for a `/v2/neighbors` request.  The offset and limit apply to each
+/// list in the response separately.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NeighborsQuery {
+    pub direction: Option<NeighborDirection>,
+    pub state: Option<NeighborState>,
+    pub address: Option<PeerAddress>,
+    pub offset: u32,
+    pub limit: Option<u32>,
+}
+
 /// Struct given back from a call to `/v2/neighbors`.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RPCNeighborsInfo {
@@ -1150,19 +1511,27 @@ pub struct RPCNeighborsInfo {
 pub enum HttpRequestType {
     GetInfo(HttpRequestMetadata),
     GetPoxInfo(HttpRequestMetadata, Option<StacksBlockId>),
-    GetNeighbors(HttpRequestMetadata),
+    GetBurnEta(HttpRequestMetadata, u64),
+    GetFeeEstimate(HttpRequestMetadata, u64),
+    GetForks(HttpRequestMetadata),
+    GetNeighbors(HttpRequestMetadata, NeighborsQuery),
     GetBlock(HttpRequestMetadata, StacksBlockId),
     GetMicroblocksIndexed(HttpRequestMetadata, StacksBlockId),
     GetMicroblocksConfirmed(HttpRequestMetadata, StacksBlockId),
     GetMicroblocksUnconfirmed(HttpRequestMetadata, StacksBlockId, u16),
     PostTransaction(HttpRequestMetadata, StacksTransaction),
+    GetTransactionReceipt(HttpRequestMetadata, Txid, Option<StacksBlockId>),
     PostMicroblock(HttpRequestMetadata, StacksMicroblock, Option<StacksBlockId>),
+    ValidateBlock(HttpRequestMetadata, StacksBlock, Option<StacksBlockId>),
+    PostFaucetStx(HttpRequestMetadata, PrincipalData),
     GetAccount(
         HttpRequestMetadata,
         PrincipalData,
         Option<StacksBlockId>,
         bool,
     ),
+    GetAccountNonces(HttpRequestMetadata, PrincipalData, Option<StacksBlockId>),
+    GetMinerRewards(HttpRequestMetadata, StacksAddress, Option<StacksBlockId>),
     GetMapEntry(
         HttpRequestMetadata,
         StacksAddress,
@@ -1181,6 +1550,12 @@ pub enum HttpRequestType {
         Vec<Value>,
         Option<StacksBlockId>,
     ),
+    MultiCallReadOnly(
+        HttpRequestMetadata,
+        PrincipalData,
+        Vec<ReadOnlyCall>,
+        Option<StacksBlockId>,
+    ),
     GetTransferCost(HttpRequestMetadata),
     GetContractSrc(
         HttpRequestMetadata,
@@ -1195,7 +1570,16 @@ pub enum HttpRequestType {
         ContractName,
         Option<StacksBlockId>,
     ),
+    GetAttachment(HttpRequestMetadata, Hash160),
+    PostAttachment(HttpRequestMetadata, Attachment),
+    /// upgrade to a WebSocket event stream; carries the client's Sec-WebSocket-Key
+    WebSocketUpgrade(HttpRequestMetadata, String),
     OptionsPreflight(HttpRequestMetadata, String),
+    /// ban a peer's address and port for a number of seconds
+    AdminBanPeer(HttpRequestMetadata, PeerAddress, u16, u64),
+    AdminEvictTransaction(HttpRequestMetadata, Txid),
+    AdminFlushChainstate(HttpRequestMetadata),
+    AdminSetMining(HttpRequestMetadata, bool),
     /// catch-all for any errors we should surface from parsing
     ClientError(HttpRequestMetadata, ClientError),
 }
@@ -1207,6 +1591,14 @@ pub struct HttpResponseMetadata {
     pub client_keep_alive: bool,
     pub request_id: u32,
     pub content_length: Option<u32>,
+    /// the Origin header of the request, which decides the CORS headers sent back
+    pub origin: Option<String>,
+    /// if true, the response never changes (e.g. a block fetched by its hash), so it may be
+    /// cached for longer
+    pub immutable: bool,
+    /// the coding the response body may be compressed with, if it's a large payload the client
+    /// accepts compressed
+    pub content_encoding: Option<HttpContentEncoding>,
 }
 
 impl HttpResponseMetadata {
@@ -1230,6 +1622,9 @@ impl HttpResponseMetadata {
             client_keep_alive: client_keep_alive,
             request_id: request_id,
             content_length: content_length,
+            origin: None,
+            immutable: false,
+            content_encoding: None,
         }
     }
 
@@ -1242,6 +1637,9 @@ impl HttpResponseMetadata {
             client_keep_alive: preamble.keep_alive,
             request_id: preamble.request_id,
             content_length: preamble.content_length.clone(),
+            origin: None,
+            immutable: false,
+            content_encoding: None,
         }
     }
 
@@ -1251,6 +1649,9 @@ impl HttpResponseMetadata {
             client_keep_alive: false,
             request_id: HttpResponseMetadata::make_request_id(),
             content_length: Some(0),
+            origin: None,
+            immutable: false,
+            content_encoding: None,
         }
     }
 }
@@ -1258,12 +1659,18 @@ impl HttpResponseMetadata {
 impl From<&HttpRequestType> for HttpResponseMetadata {
     fn from(req: &HttpRequestType) -> HttpResponseMetadata {
         let metadata = req.metadata();
-        HttpResponseMetadata::new(
+        let mut response_metadata = HttpResponseMetadata::new(
             metadata.version,
             HttpResponseMetadata::make_request_id(),
             None,
             metadata.keep_alive,
-        )
+        );
+        response_metadata.origin = metadata.origin.clone();
+        response_metadata.immutable = req.is_immutable();
+        if req.is_compressible() {
+            response_metadata.content_encoding = metadata.accept_encoding;
+        }
+        response_metadata
     }
 }
 
@@ -1272,20 +1679,38 @@ impl From<&HttpRequestType> for HttpResponseMetadata {
 pub enum HttpResponseType {
     PeerInfo(HttpResponseMetadata, RPCPeerInfoData),
     PoxInfo(HttpResponseMetadata, RPCPoxInfoData),
+    BurnEta(HttpResponseMetadata, RPCBurnEtaData),
+    FeeEstimate(HttpResponseMetadata, RPCFeeEstimateData),
+    Forks(HttpResponseMetadata, RPCForksData),
     Neighbors(HttpResponseMetadata, RPCNeighborsInfo),
     Block(HttpResponseMetadata, StacksBlock),
     BlockStream(HttpResponseMetadata),
+    /// 206 Partial Content for a ranged block or microblock stream; carries the first and last
+    /// byte offsets sent, and the total length
+    PartialStream(HttpResponseMetadata, u64, u64, u64),
+    /// a 206 Partial Content response, as received
+    PartialContent(HttpResponseMetadata, Vec<u8>),
     Microblocks(HttpResponseMetadata, Vec<StacksMicroblock>),
     MicroblockStream(HttpResponseMetadata),
     TransactionID(HttpResponseMetadata, Txid),
+    TransactionReceipt(HttpResponseMetadata, TransactionReceiptData),
     MicroblockHash(HttpResponseMetadata, BlockHeaderHash),
+    BlockValidation(HttpResponseMetadata, RPCBlockValidationData),
     TokenTransferCost(HttpResponseMetadata, u64),
     GetMapEntry(HttpResponseMetadata, MapEntryResponse),
     CallReadOnlyFunction(HttpResponseMetadata, CallReadOnlyResponse),
+    MultiCallReadOnly(HttpResponseMetadata, MultiCallReadOnlyResponse),
     GetAccount(HttpResponseMetadata, AccountEntryResponse),
+    GetAccountNonces(HttpResponseMetadata, AccountNoncesResponse),
+    GetMinerRewards(HttpResponseMetadata, MinerRewardsResponse),
     GetContractABI(HttpResponseMetadata, ContractInterface),
     GetContractSrc(HttpResponseMetadata, ContractSrcResponse),
+    GetAttachment(HttpResponseMetadata, GetAttachmentResponse),
+    AttachmentHash(HttpResponseMetadata, Hash160),
+    /// 101 Switching Protocols; carries the Sec-WebSocket-Accept value
+    WebSocketUpgrade(HttpResponseMetadata, String),
     OptionsPreflight(HttpResponseMetadata),
+    AdminAction(HttpResponseMetadata, RPCAdminResponse),
     // peer-given error responses
     BadRequest(HttpResponseMetadata, String),
     BadRequestJSON(HttpResponseMetadata, serde_json::Value),
@@ -1592,6 +2017,9 @@ pub const NUM_NEIGHBORS: usize = 32;
 // maximum number of unconfirmed microblocks can get streamed to us
 pub const MAX_MICROBLOCKS_UNCONFIRMED: usize = 1024;
 
+// maximum number of read-only calls in one multi-call-read request
+pub const MAX_MULTI_CALL_READS: usize = 32;
+
 // how long a peer will be denied for if it misbehaves
 #[cfg(test)]
 pub const DENY_BAN_DURATION: u64 = 30; // seconds
@@ -1611,6 +2039,7 @@ pub struct NetworkResult {
     pub pushed_microblocks: HashMap<NeighborKey, Vec<(Vec<RelayData>, MicroblocksData)>>, // all microblocks pushed to us, and the relay hints from the message
     pub uploaded_transactions: Vec<StacksTransaction>, // transactions sent to us by the http server
     pub uploaded_microblocks: Vec<MicroblocksData>,    // microblocks sent to us by the http server
+    pub attachments: Vec<Attachment>,                  // attachments we downloaded
     pub num_state_machine_passes: u64,
     pub num_inv_sync_passes: u64,
 }
@@ -1627,6 +2056,7 @@ impl NetworkResult {
             pushed_microblocks: HashMap::new(),
             uploaded_transactions: vec![],
             uploaded_microblocks: vec![],
+            attachments: vec![],
             num_state_machine_passes: num_state_machine_passes,
             num_inv_sync_passes: num_inv_sync_passes,
         }
@@ -1805,6 +2235,8 @@ pub mod test {
                 BlockstackOperationType::LeaderKeyRegister(ref op) => op.consensus_serialize(fd),
                 BlockstackOperationType::LeaderBlockCommit(ref op) => op.consensus_serialize(fd),
                 BlockstackOperationType::UserBurnSupport(ref op) => op.consensus_serialize(fd),
+                BlockstackOperationType::PreStx(ref op) => op.consensus_serialize(fd),
+                BlockstackOperationType::TransferStx(ref op) => op.consensus_serialize(fd),
             }
         }
 
@@ -2173,6 +2605,7 @@ pub mod test {
                 config.burnchain.first_block_height,
                 &config.burnchain.first_block_hash,
                 0,
+                &config.burnchain.get_stacks_epochs(),
                 true,
             )
             .unwrap();
@@ -2487,6 +2920,12 @@ pub mod test {
                     BlockstackOperationType::UserBurnSupport(ref mut data) => {
                         data.burn_header_hash = (*bhh).clone();
                     }
+                    BlockstackOperationType::PreStx(ref mut data) => {
+                        data.burn_header_hash = (*bhh).clone();
+                    }
+                    BlockstackOperationType::TransferStx(ref mut data) => {
+                        data.burn_header_hash = (*bhh).clone();
+                    }
                 }
             }
         }
@@ -17,6 +17,7 @@
 use std::mem;
 
 use net::asn::ASEntry4;
+use net::atlas::AttachmentsDownloader;
 use net::db::PeerDB;
 use net::Error as net_error;
 use net::Neighbor;
@@ -35,7 +36,10 @@ use net::chat::NeighborStats;
 
 use net::relay::RelayerStats;
 
+use net::divergence::{self, DIVERGENCE_CHECK_INTERVAL};
 use net::download::BlockDownloader;
+use net::portmap::{PortMapper, PortMapping};
+use net::reputation::{Misbehavior, PeerReputation};
 
 use net::poll::NetworkPollState;
 use net::poll::NetworkState;
@@ -85,6 +89,8 @@ use chainstate::stacks::{StacksBlockHeader, MAX_BLOCK_LEN, MAX_TRANSACTION_LEN};
 use util::get_epoch_time_secs;
 use util::log;
 
+use monitoring;
+
 use rand::prelude::*;
 use rand::thread_rng;
 
@@ -95,12 +101,19 @@ use net::inv::*;
 use net::relay::*;
 use net::rpc::RPCHandlerArgs;
 
+/// How many outbound peers that are not initial peers must agree on our public IP address before
+/// we believe them
+pub const PUBLIC_IP_MIN_VOTES: usize = 2;
+
 /// inter-thread request to send a p2p message from another thread in this program.
 #[derive(Debug)]
 pub enum NetworkRequest {
     Ban(Vec<NeighborKey>),
+    Penalize(Vec<(NeighborKey, Misbehavior)>), // count misbehavior against these peers' reputations
     AdvertizeBlocks(BlocksAvailableMap), // announce to all wanting neighbors that we have these blocks
     AdvertizeMicroblocks(BlocksAvailableMap), // announce to all wanting neighbors that we have these confirmed microblock streams
+    PushBlocks(PushData<(ConsensusHash, StacksBlock)>), // send these new blocks to outbound neighbors whose inventories lack them
+    PushMicroblocks(PushData<MicroblocksData>), // send these new confirmed microblock streams to outbound neighbors whose inventories lack them
     Relay(NeighborKey, StacksMessage),
     Broadcast(Vec<RelayData>, StacksMessageType),
 }
@@ -146,6 +159,15 @@ impl NetworkHandle {
         self.send_request(req)
     }
 
+    /// Count misbehavior against peers' reputations, banning them if it gets too high
+    pub fn penalize_peers(
+        &mut self,
+        penalties: Vec<(NeighborKey, Misbehavior)>,
+    ) -> Result<(), net_error> {
+        let req = NetworkRequest::Penalize(penalties);
+        self.send_request(req)
+    }
+
     /// Advertize blocks
     pub fn advertize_blocks(&mut self, blocks: BlocksAvailableMap) -> Result<(), net_error> {
         let req = NetworkRequest::AdvertizeBlocks(blocks);
@@ -158,6 +180,24 @@ impl NetworkHandle {
         self.send_request(req)
     }
 
+    /// Push new blocks to the outbound neighbors that don't have them yet
+    pub fn push_blocks(
+        &mut self,
+        blocks: PushData<(ConsensusHash, StacksBlock)>,
+    ) -> Result<(), net_error> {
+        let req = NetworkRequest::PushBlocks(blocks);
+        self.send_request(req)
+    }
+
+    /// Push new confirmed microblock streams to the outbound neighbors that don't have them yet
+    pub fn push_microblocks(
+        &mut self,
+        microblocks: PushData<MicroblocksData>,
+    ) -> Result<(), net_error> {
+        let req = NetworkRequest::PushMicroblocks(microblocks);
+        self.send_request(req)
+    }
+
     /// Relay a message to a peer via the p2p network thread, expecting no reply.
     /// Called from outside the p2p thread by other threads.
     pub fn relay_signed_message(
@@ -218,6 +258,7 @@ pub struct PeerNetwork {
     pub events: HashMap<NeighborKey, usize>,
     pub connecting: HashMap<usize, (mio_net::TcpStream, bool, u64)>, // (socket, outbound?, connection sent timestamp)
     pub bans: HashSet<usize>,
+    pub reputation: PeerReputation,
 
     // ongoing messages the network is sending via the p2p interface (not bound to a specific
     // conversation).
@@ -265,6 +306,9 @@ pub struct PeerNetwork {
     // peer block download state
     pub block_downloader: Option<BlockDownloader>,
 
+    // attachment download state
+    pub attachments_downloader: Option<AttachmentsDownloader>,
+
     // do we need to do a prune at the end of the work state cycle?
     pub do_prune: bool,
 
@@ -287,7 +331,13 @@ pub struct PeerNetwork {
     public_ip_requested_at: u64,
     public_ip_learned_at: u64,
     public_ip_reply_handle: Option<ReplyHandleP2P>,
+    public_ip_reply_peer: Option<(NeighborKey, bool)>, // who we asked, and whether or not it's an initial peer
     public_ip_retries: u64,
+    public_ip_votes: HashMap<PeerAddress, HashSet<NeighborKey>>, // what other outbound peers said our IP address is
+
+    // port forwarded to us by the router, if we asked for one
+    pub port_mapping: Option<PortMapping>,
+    port_mapping_rx: Option<Receiver<PortMapping>>,
 
     // how many loops of the state-machine have occured?
     // Used to coordinate with the chain synchronization logic to ensure that the node has at least
@@ -300,16 +350,19 @@ pub struct PeerNetwork {
     // how many downloader passes have we done?
     pub num_downloader_passes: u64,
 
-    // to whom did we send a block or microblock stream as part of our anti-entropy protocol, and
-    // when did we send it?
-    antientropy_blocks: HashMap<NeighborKey, HashMap<StacksBlockId, u64>>,
-    antientropy_microblocks: HashMap<NeighborKey, HashMap<StacksBlockId, u64>>,
+    // to whom did we send a block or microblock stream as part of our anti-entropy protocol (or
+    // our push protocol for new blocks), and when did we send it?
+    pub antientropy_blocks: HashMap<NeighborKey, HashMap<StacksBlockId, u64>>,
+    pub antientropy_microblocks: HashMap<NeighborKey, HashMap<StacksBlockId, u64>>,
     antientropy_last_burnchain_tip: BurnchainHeaderHash,
 
     // pending messages (BlocksAvailable, MicroblocksAvailable, BlocksData, Microblocks) that we
     // can't process yet, but might be able to process on the next chain view update
     pub pending_messages: HashMap<usize, Vec<StacksMessage>>,
 
+    // when did we last check our rejected blocks against our neighbors' inventories?
+    last_divergence_check: u64,
+
     // fault injection -- force disconnects
     fault_last_disconnect: u64,
 }
@@ -353,6 +406,7 @@ impl PeerNetwork {
             events: HashMap::new(),
             connecting: HashMap::new(),
             bans: HashSet::new(),
+            reputation: PeerReputation::new(),
 
             relay_handles: HashMap::new(),
             relayer_stats: RelayerStats::new(),
@@ -383,6 +437,7 @@ impl PeerNetwork {
             header_cache: BlockHeaderCache::new(),
 
             block_downloader: None,
+            attachments_downloader: None,
 
             do_prune: false,
 
@@ -403,7 +458,12 @@ impl PeerNetwork {
             public_ip_learned_at: 0,
             public_ip_confirmed: false,
             public_ip_reply_handle: None,
+            public_ip_reply_peer: None,
             public_ip_retries: 0,
+            public_ip_votes: HashMap::new(),
+
+            port_mapping: None,
+            port_mapping_rx: None,
 
             num_state_machine_passes: 0,
             num_inv_sync_passes: 0,
@@ -415,6 +475,8 @@ impl PeerNetwork {
 
             pending_messages: HashMap::new(),
 
+            last_divergence_check: 0,
+
             fault_last_disconnect: 0,
         }
     }
@@ -446,6 +508,19 @@ impl PeerNetwork {
             port: my_addr.port(),
         };
 
+        if self.connection_opts.enable_port_mapping {
+            let mapper =
+                PortMapper::new(my_addr.port(), self.connection_opts.port_mapping_lifetime);
+            match mapper.spawn() {
+                Ok(port_mapping_rx) => {
+                    self.port_mapping_rx = Some(port_mapping_rx);
+                }
+                Err(e) => {
+                    warn!("Failed to start port mapper thread: {:?}", &e);
+                }
+            }
+        }
+
         Ok(())
     }
 
@@ -929,6 +1004,12 @@ impl PeerNetwork {
                 }
                 Ok(())
             }
+            NetworkRequest::Penalize(penalties) => {
+                for (neighbor_key, misbehavior) in penalties.into_iter() {
+                    self.penalize_neighbor(&neighbor_key, misbehavior)?;
+                }
+                Ok(())
+            }
             NetworkRequest::AdvertizeBlocks(blocks) => {
                 if !(cfg!(test) && self.connection_opts.disable_block_advertisement) {
                     self.advertize_blocks(blocks)?;
@@ -941,6 +1022,18 @@ impl PeerNetwork {
                 }
                 Ok(())
             }
+            NetworkRequest::PushBlocks(blocks) => {
+                if !(cfg!(test) && self.connection_opts.disable_block_advertisement) {
+                    self.push_blocks(blocks);
+                }
+                Ok(())
+            }
+            NetworkRequest::PushMicroblocks(microblocks) => {
+                if !(cfg!(test) && self.connection_opts.disable_block_advertisement) {
+                    self.push_microblocks(microblocks);
+                }
+                Ok(())
+            }
             NetworkRequest::Relay(neighbor_key, msg) => self
                 .relay_signed_message(&neighbor_key, msg)
                 .and_then(|_| Ok(())),
@@ -1190,6 +1283,7 @@ impl PeerNetwork {
     /// Check to see if we can register the given socket
     /// * we can't have registered this neighbor already
     /// * if this is inbound, we can't add more than self.num_clients
+    /// * if this is inbound, we can't add more than self.max_clients_per_subnet from one subnet
     pub fn can_register_peer(
         &mut self,
         neighbor_key: &NeighborKey,
@@ -1238,9 +1332,71 @@ impl PeerNetwork {
             return Err(net_error::TooManyPeers);
         }
 
+        // consider rate-limits on in-bound peers from the same subnet
+        if !outbound
+            && self.count_inbound_subnet_conversations(&neighbor_key.addrbytes)
+                >= self.connection_opts.max_clients_per_subnet
+        {
+            info!(
+                "{:?}: Too many inbound connections from the subnet of {:?}",
+                &self.local_peer, neighbor_key
+            );
+            return Err(net_error::TooManyPeers);
+        }
+
         Ok(())
     }
 
+    /// If we're at our limit of connections in this direction, then evict a peer to make room for
+    /// a new one.  Fails if we're at our limit, but no peer can be evicted.
+    fn make_room_for_peer(
+        &mut self,
+        neighbor_key: &NeighborKey,
+        outbound: bool,
+    ) -> Result<(), net_error> {
+        let num_outbound = PeerNetwork::count_outbound_conversations(&self.peers);
+        let (num_peers, max_peers) = if outbound {
+            (num_outbound, self.connection_opts.num_neighbors)
+        } else {
+            (
+                (self.peers.len() as u64) - num_outbound,
+                self.connection_opts.num_clients,
+            )
+        };
+        if num_peers < max_peers {
+            return Ok(());
+        }
+
+        if cfg!(test) && self.connection_opts.disable_network_prune {
+            // leave it to can_register_peer()
+            return Ok(());
+        }
+
+        let protected = self.get_protected_events()?;
+        match self.find_eviction_candidate(outbound, &protected) {
+            Some(event_id) => {
+                debug!(
+                    "{:?}: evict event {} ({:?}) to make room for {:?} (outbound={})",
+                    &self.local_peer,
+                    event_id,
+                    self.get_event_neighbor_key(event_id),
+                    neighbor_key,
+                    outbound
+                );
+                self.deregister_peer(event_id);
+                Ok(())
+            }
+            None => {
+                info!(
+                    "{:?}: Too many {} connections, and none can be evicted",
+                    &self.local_peer,
+                    if outbound { "outbound" } else { "inbound" }
+                );
+                Err(net_error::TooManyPeers)
+            }
+        }
+    }
+
     /// Check to see if we can register a peer with a given public key in a given direction
     pub fn can_register_peer_with_pubkey(
         &mut self,
@@ -1324,7 +1480,14 @@ impl PeerNetwork {
             ),
         };
 
-        match self.can_register_peer(&neighbor_key, outbound) {
+        let register_res = match self.can_register_peer(&neighbor_key, outbound) {
+            Ok(_) => self.make_room_for_peer(&neighbor_key, outbound),
+            Err(net_error::TooManyPeers) => self
+                .make_room_for_peer(&neighbor_key, outbound)
+                .and_then(|_| self.can_register_peer(&neighbor_key, outbound)),
+            Err(e) => Err(e),
+        };
+        match register_res {
             Ok(_) => {}
             Err(e) => {
                 debug!(
@@ -1471,6 +1634,119 @@ impl PeerNetwork {
         self.deregister_neighbor(neighbor);
     }
 
+    /// Count a neighbor's misbehavior against its reputation.  If its misbehavior score reaches
+    /// connection_opts.ban_score_threshold, then ban it for connection_opts.ban_duration seconds
+    /// and disconnect from it.  Allowed neighbors are never banned.
+    /// Returns true if the neighbor was banned.
+    pub fn penalize_neighbor(
+        &mut self,
+        neighbor_key: &NeighborKey,
+        misbehavior: Misbehavior,
+    ) -> Result<bool, net_error> {
+        let now = get_epoch_time_secs();
+        self.reputation.prune(now);
+        let score = self.reputation.record(neighbor_key, misbehavior, now);
+        debug!(
+            "{:?}: Neighbor {:?} misbehaved ({}); misbehavior score is now {}",
+            &self.local_peer, neighbor_key, misbehavior, score
+        );
+
+        if score < self.connection_opts.ban_score_threshold {
+            return Ok(false);
+        }
+        if cfg!(test) && self.connection_opts.disable_network_bans {
+            return Ok(false);
+        }
+
+        if let Some(neighbor) = PeerDB::get_peer(
+            self.peerdb.conn(),
+            neighbor_key.network_id,
+            &neighbor_key.addrbytes,
+            neighbor_key.port,
+        )? {
+            if neighbor.is_allowed() {
+                debug!(
+                    "Misbehaving neighbor {:?} is allowed; will not punish",
+                    &neighbor.addr
+                );
+                self.reputation.clear(neighbor_key);
+                return Ok(false);
+            }
+        }
+
+        let deny_deadline = now + self.connection_opts.ban_duration;
+        info!(
+            "{:?}: Ban neighbor {:?} until {}: misbehavior score {} reached {} (last: {})",
+            &self.local_peer,
+            neighbor_key,
+            deny_deadline,
+            score,
+            self.connection_opts.ban_score_threshold,
+            misbehavior
+        );
+
+        self.ban_neighbor(neighbor_key, deny_deadline)?;
+        Ok(true)
+    }
+
+    /// Deny a neighbor until `deny_deadline`, and disconnect from it
+    fn ban_neighbor(
+        &mut self,
+        neighbor_key: &NeighborKey,
+        deny_deadline: u64,
+    ) -> Result<(), net_error> {
+        let mut tx = self.peerdb.tx_begin()?;
+        PeerDB::set_deny_peer(
+            &mut tx,
+            neighbor_key.network_id,
+            &neighbor_key.addrbytes,
+            neighbor_key.port,
+            deny_deadline,
+        )?;
+        tx.commit()?;
+
+        self.reputation.clear(neighbor_key);
+        self.relayer_stats.process_neighbor_ban(neighbor_key);
+        self.deregister_neighbor(neighbor_key);
+        Ok(())
+    }
+
+    /// Ban a peer's address and port for `duration` seconds at the operator's request (see
+    /// net::admin), and disconnect from it if we're connected.  Unlike `penalize_neighbor`, this
+    /// doesn't spare allowed peers.
+    pub fn ban_peer_address(
+        &mut self,
+        addrbytes: &PeerAddress,
+        port: u16,
+        duration: u64,
+    ) -> Result<(), net_error> {
+        let deny_deadline = get_epoch_time_secs().saturating_add(duration);
+        let mut neighbor_keys: Vec<NeighborKey> = self
+            .events
+            .keys()
+            .filter(|nk| nk.addrbytes == *addrbytes && nk.port == port)
+            .cloned()
+            .collect();
+        if neighbor_keys.len() == 0 {
+            // not connected, so deny it preemptively
+            neighbor_keys.push(NeighborKey {
+                peer_version: self.burnchain.peer_version,
+                network_id: self.local_peer.network_id,
+                addrbytes: addrbytes.clone(),
+                port: port,
+            });
+        }
+
+        for neighbor_key in neighbor_keys.iter() {
+            info!(
+                "{:?}: Ban neighbor {:?} until {} by admin request",
+                &self.local_peer, neighbor_key, deny_deadline
+            );
+            self.ban_neighbor(neighbor_key, deny_deadline)?;
+        }
+        Ok(())
+    }
+
     /// Sign a p2p message to be sent to a particular peer we're having a conversation with.
     /// The peer must already be connected.
     pub fn sign_for_peer(
@@ -1669,6 +1945,7 @@ impl PeerNetwork {
     ) -> (Vec<usize>, HashMap<usize, Vec<StacksMessage>>) {
         let mut to_remove = vec![];
         let mut unhandled: HashMap<usize, Vec<StacksMessage>> = HashMap::new();
+        let mut violations = vec![];
 
         for event_id in &poll_state.ready {
             if !self.sockets.contains_key(&event_id) {
@@ -1689,6 +1966,7 @@ impl PeerNetwork {
                 Some(ref mut convo) => {
                     // activity on a p2p socket
                     debug!("{:?}: process p2p data from {:?}", &self.local_peer, convo);
+                    let msgs_invalid = convo.stats.msgs_invalid;
                     let mut convo_unhandled = match PeerNetwork::process_p2p_conversation(
                         &self.local_peer,
                         &mut self.peerdb,
@@ -1715,6 +1993,11 @@ impl PeerNetwork {
                         }
                     };
 
+                    // count each message that broke the protocol against the peer
+                    for _ in msgs_invalid..convo.stats.msgs_invalid {
+                        violations.push(convo.to_neighbor_key());
+                    }
+
                     // forward along unhandled messages from this peer
                     if unhandled.contains_key(event_id) {
                         unhandled
@@ -1732,6 +2015,15 @@ impl PeerNetwork {
             }
         }
 
+        for neighbor_key in violations.into_iter() {
+            if let Err(e) = self.penalize_neighbor(&neighbor_key, Misbehavior::ProtocolViolation) {
+                warn!(
+                    "{:?}: Failed to penalize {:?}: {:?}",
+                    &self.local_peer, &neighbor_key, &e
+                );
+            }
+        }
+
         (to_remove, unhandled)
     }
 
@@ -1864,13 +2156,9 @@ impl PeerNetwork {
         ret
     }
 
-    /// Prune inbound and outbound connections if we can
-    fn prune_connections(&mut self) -> () {
-        if cfg!(test) && self.connection_opts.disable_network_prune {
-            return;
-        }
-
-        test_debug!("Prune connections");
+    /// Get the connections we must not prune or evict: those to allowed peers, and those that the
+    /// neighbor walk is using.
+    fn get_protected_events(&self) -> Result<HashSet<usize>, net_error> {
         let mut safe: HashSet<usize> = HashSet::new();
         let now = get_epoch_time_secs();
 
@@ -1890,7 +2178,7 @@ impl PeerNetwork {
                 },
                 Err(e) => {
                     debug!("Failed to query {:?}: {:?}", &nk, &e);
-                    return;
+                    return Err(net_error::DBError(e));
                 }
             };
             if neighbor.allowed < 0 || (neighbor.allowed as u64) > now {
@@ -1915,6 +2203,23 @@ impl PeerNetwork {
             None => {}
         };
 
+        Ok(safe)
+    }
+
+    /// Prune inbound and outbound connections if we can
+    fn prune_connections(&mut self) -> () {
+        if cfg!(test) && self.connection_opts.disable_network_prune {
+            return;
+        }
+
+        test_debug!("Prune connections");
+        let safe = match self.get_protected_events() {
+            Ok(safe) => safe,
+            Err(_) => {
+                return;
+            }
+        };
+
         self.prune_frontier(&safe);
     }
 
@@ -2061,33 +2366,45 @@ impl PeerNetwork {
 
         debug!("{:?}: begin obtaining public IP address", &self.local_peer);
 
-        // pick a random outbound conversation to one of the initial neighbors
-        let mut idx = thread_rng().gen::<usize>() % self.peers.len();
-        for _ in 0..self.peers.len() + 1 {
-            let event_id = match self.peers.keys().skip(idx).next() {
-                Some(eid) => *eid,
-                None => {
-                    idx = 0;
-                    continue;
-                }
-            };
-            idx = (idx + 1) % self.peers.len();
+        // prefer an outbound conversation to one of the initial neighbors, since we trust them.
+        // Otherwise, ask an outbound neighbor we haven't asked yet, and wait for others to agree
+        // with it.
+        let mut initial_event_ids = vec![];
+        let mut other_event_ids = vec![];
+        for (event_id, convo) in self.peers.iter() {
+            if !convo.is_authenticated() || !convo.is_outbound() {
+                continue;
+            }
 
-            if let Some(convo) = self.peers.get_mut(&event_id) {
-                if !convo.is_authenticated() || !convo.is_outbound() {
-                    continue;
+            if PeerDB::is_initial_peer(
+                self.peerdb.conn(),
+                convo.peer_network_id,
+                &convo.peer_addrbytes,
+                convo.peer_port,
+            )? {
+                initial_event_ids.push(*event_id);
+            } else {
+                let nk = convo.to_neighbor_key();
+                if !self
+                    .public_ip_votes
+                    .values()
+                    .any(|voters| voters.contains(&nk))
+                {
+                    other_event_ids.push(*event_id);
                 }
+            }
+        }
 
-                if !PeerDB::is_initial_peer(
-                    self.peerdb.conn(),
-                    convo.peer_network_id,
-                    &convo.peer_addrbytes,
-                    convo.peer_port,
-                )? {
-                    continue;
-                }
+        let (event_ids, trusted) = if initial_event_ids.len() > 0 {
+            (initial_event_ids, true)
+        } else {
+            (other_event_ids, false)
+        };
 
+        if let Some(event_id) = event_ids.choose(&mut thread_rng()).cloned() {
+            if let Some(convo) = self.peers.get_mut(&event_id) {
                 debug!("Ask {:?} for my IP address", &convo);
+                let nk = convo.to_neighbor_key();
 
                 let nonce = thread_rng().gen::<u32>();
                 let natpunch_request = convo
@@ -2114,7 +2431,7 @@ impl PeerNetwork {
                 })?;
 
                 self.public_ip_reply_handle = Some(rh);
-                break;
+                self.public_ip_reply_peer = Some((nk, trusted));
             }
         }
 
@@ -2174,6 +2491,24 @@ impl PeerNetwork {
                 Ok(message) => match message.payload {
                     StacksMessageType::NatPunchReply(data) => {
                         // peer offers us our public IP address.
+                        // Unless it's an initial peer, make sure others agree.
+                        if let Some((nk, false)) = self.public_ip_reply_peer.take() {
+                            let voters = self
+                                .public_ip_votes
+                                .entry(data.addrbytes.clone())
+                                .or_insert(HashSet::new());
+                            voters.insert(nk);
+                            if voters.len() < PUBLIC_IP_MIN_VOTES {
+                                debug!(
+                                    "{:?}: {} peer(s) say my IP address is {:?}",
+                                    &self.local_peer,
+                                    voters.len(),
+                                    &data.addrbytes
+                                );
+                                return Ok(true);
+                            }
+                        }
+
                         info!(
                             "{:?}: learned that my IP address is {:?}",
                             &self.local_peer, &data.addrbytes
@@ -2181,11 +2516,12 @@ impl PeerNetwork {
                         self.public_ip_confirmed = true;
                         self.public_ip_learned_at = get_epoch_time_secs();
                         self.public_ip_retries = 0;
+                        self.public_ip_votes.clear();
 
                         // if our IP address changed, then disconnect witih everyone
                         let old_ip = self.local_peer.public_ip_address.clone();
                         self.local_peer.public_ip_address =
-                            Some((data.addrbytes, self.bind_nk.port));
+                            Some((data.addrbytes, self.public_port()));
 
                         if old_ip != self.local_peer.public_ip_address {
                             info!("IP address changed from {:?} to {:?}; closing all connections and re-establishing them", &old_ip, &self.local_peer.public_ip_address);
@@ -2269,6 +2605,7 @@ impl PeerNetwork {
         debug!("{:?}: reset public IP query state", &self.local_peer);
 
         self.public_ip_reply_handle = None;
+        self.public_ip_reply_peer = None;
         self.public_ip_confirmed = false;
 
         if self.public_ip_learned {
@@ -2277,6 +2614,76 @@ impl PeerNetwork {
         }
     }
 
+    /// The port other peers can reach us on -- the router's external port, if it forwards one to
+    /// us
+    fn public_port(&self) -> u16 {
+        match self.port_mapping {
+            Some(ref mapping) => mapping.external_port,
+            None => self.bind_nk.port,
+        }
+    }
+
+    /// Pick up any new port mapping from the port mapper thread, and advertise the router's
+    /// external address and port in our handshakes (unless our public IP address was given to us)
+    fn update_port_mapping(&mut self) -> () {
+        let mut new_mapping = None;
+        if let Some(ref port_mapping_rx) = self.port_mapping_rx {
+            loop {
+                match port_mapping_rx.try_recv() {
+                    Ok(mapping) => {
+                        new_mapping = Some(mapping);
+                    }
+                    Err(TryRecvError::Empty) => {
+                        break;
+                    }
+                    Err(TryRecvError::Disconnected) => {
+                        warn!("{:?}: port mapper thread exited", &self.local_peer);
+                        self.port_mapping_rx = None;
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mapping = match new_mapping {
+            Some(mapping) => mapping,
+            None => {
+                return;
+            }
+        };
+        if self.port_mapping.as_ref() == Some(&mapping) {
+            return;
+        }
+
+        debug!(
+            "{:?}: router forwards external port {} to port {} ({:?})",
+            &self.local_peer, mapping.external_port, mapping.internal_port, &mapping.external_addr
+        );
+        self.port_mapping = Some(mapping.clone());
+
+        if !self.public_ip_learned {
+            return;
+        }
+
+        let old_ip = self.local_peer.public_ip_address.clone();
+        self.local_peer.public_ip_address = match (mapping.external_addr, old_ip) {
+            (Some(addrbytes), _) => {
+                self.public_ip_learned_at = get_epoch_time_secs();
+                Some((addrbytes, mapping.external_port))
+            }
+            (None, Some((addrbytes, _))) => Some((addrbytes, mapping.external_port)),
+            (None, None) => None,
+        };
+
+        if old_ip != self.local_peer.public_ip_address {
+            info!(
+                "{:?}: public address changed from {:?} to {:?} after port mapping; closing all connections and re-establishing them",
+                &self.local_peer, &old_ip, &self.local_peer.public_ip_address
+            );
+            self.disconnect_all();
+        }
+    }
+
     /// Learn our publicly-routable IP address
     fn do_get_public_ip(&mut self) -> Result<bool, net_error> {
         if !self.need_public_ip() {
@@ -3967,6 +4374,21 @@ impl PeerNetwork {
         // update our relay statistics, so we know who to forward messages to
         self.update_relayer_stats(&network_result);
 
+        let num_outbound = PeerNetwork::count_outbound_conversations(&self.peers);
+        monitoring::update_peer_counts_gauge(
+            (self.peers.len() as u64 - num_outbound) as i64,
+            num_outbound as i64,
+        );
+
+        // did the rest of the network accept a block we rejected?
+        if self.last_divergence_check + DIVERGENCE_CHECK_INTERVAL < get_epoch_time_secs() {
+            self.check_consensus_divergence(chainstate);
+            self.last_divergence_check = get_epoch_time_secs();
+        }
+
+        // pick up any new port mapping from the router
+        self.update_port_mapping();
+
         // finally, handle network I/O requests from other threads, and get back reply handles to them.
         // do this after processing new sockets, so we don't accidentally re-use an event ID.
         self.dispatch_requests();
@@ -3986,6 +4408,27 @@ impl PeerNetwork {
         Ok(())
     }
 
+    /// Check the blocks our chainstate rejected against our neighbors' block inventories, and
+    /// write a diagnostic bundle for each one that most of them accepted.
+    fn check_consensus_divergence(&self, chainstate: &StacksChainState) -> () {
+        let inv_state = match self.inv_state {
+            Some(ref inv_state) => inv_state,
+            None => {
+                return;
+            }
+        };
+        if let Err(e) = divergence::check_rejected_blocks(
+            &chainstate.root_path,
+            inv_state,
+            get_epoch_time_secs(),
+        ) {
+            warn!(
+                "{:?}: failed to check rejected blocks for divergence: {:?}",
+                &self.local_peer, &e
+            );
+        }
+    }
+
     /// Top-level main-loop circuit to take.
     /// -- polls the peer network and http network server sockets to get new sockets and detect ready sockets
     /// -- carries out network conversations
@@ -4041,6 +4484,15 @@ impl PeerNetwork {
             Ok(())
         })?;
 
+        for (addrbytes, port, duration) in self.http.take_admin_bans() {
+            if let Err(e) = self.ban_peer_address(&addrbytes, port, duration) {
+                warn!(
+                    "{:?}: Failed to ban {:?}:{}: {:?}",
+                    &self.local_peer, &addrbytes, port, &e
+                );
+            }
+        }
+
         self.dispatch_network(
             &mut result,
             sortdb,
@@ -4050,6 +4502,20 @@ impl PeerNetwork {
             p2p_poll_state,
         )?;
 
+        if let Some(atlasdb) = handler_args.atlasdb {
+            match self.download_attachments(atlasdb, chainstate) {
+                Ok(attachments) => {
+                    result.attachments = attachments;
+                }
+                Err(e) => {
+                    warn!(
+                        "{:?}: Failed to download attachments: {:?}",
+                        &self.local_peer, &e
+                    );
+                }
+            }
+        }
+
         debug!("<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<< End Network Dispatch <<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<");
         Ok(result)
     }
@@ -4063,6 +4529,7 @@ mod test {
     use burnchains::*;
     use net::codec::*;
     use net::db::*;
+    use net::portmap::PortMapProtocol;
     use net::*;
     use std::thread;
     use std::time;
@@ -4318,6 +4785,240 @@ mod test {
         })
     }
 
+    #[test]
+    fn test_penalize_neighbor() {
+        let mut neighbor = make_test_neighbor(2300);
+        neighbor.allowed = 0;
+        neighbor.denied = 0;
+        let allowed = make_test_neighbor(2301);
+
+        let mut p2p = make_test_p2p_network(&vec![neighbor.clone(), allowed.clone()]);
+        let get_neighbor = |p2p: &PeerNetwork, nk: &NeighborKey| {
+            PeerDB::get_peer(p2p.peerdb.conn(), nk.network_id, &nk.addrbytes, nk.port)
+                .unwrap()
+                .unwrap()
+        };
+
+        // misbehavior accumulates until the threshold is reached
+        for _ in 0..4 {
+            assert!(!p2p
+                .penalize_neighbor(&neighbor.addr, Misbehavior::ProtocolViolation)
+                .unwrap());
+        }
+        assert_eq!(
+            p2p.reputation
+                .get_score(&neighbor.addr, get_epoch_time_secs()),
+            80
+        );
+        assert!(!get_neighbor(&p2p, &neighbor.addr).is_denied());
+
+        let now = get_epoch_time_secs();
+        assert!(p2p
+            .penalize_neighbor(&neighbor.addr, Misbehavior::ProtocolViolation)
+            .unwrap());
+
+        // ban is stored in the peer DB, and the score starts over
+        let banned = get_neighbor(&p2p, &neighbor.addr);
+        assert!(banned.is_denied());
+        assert!(banned.denied as u64 >= now + p2p.connection_opts.ban_duration);
+        assert_eq!(
+            p2p.reputation
+                .get_score(&neighbor.addr, get_epoch_time_secs()),
+            0
+        );
+
+        // allowed peers are never banned
+        for _ in 0..10 {
+            assert!(!p2p
+                .penalize_neighbor(&allowed.addr, Misbehavior::InvalidBlock)
+                .unwrap());
+        }
+        assert!(get_neighbor(&p2p, &allowed.addr).is_allowed());
+    }
+
+    #[test]
+    fn test_update_port_mapping() {
+        let mut p2p = make_test_p2p_network(&vec![]);
+        p2p.bind_nk.port = 2400;
+        assert_eq!(p2p.public_port(), 2400);
+
+        let (mapping_tx, mapping_rx) = sync_channel(1);
+        p2p.port_mapping_rx = Some(mapping_rx);
+
+        // router doesn't tell us its address, so we only learn the port
+        let mapping = PortMapping {
+            protocol: PortMapProtocol::UPnP,
+            internal_port: 2400,
+            external_port: 2401,
+            external_addr: None,
+            expires_at: get_epoch_time_secs() + 3600,
+        };
+        mapping_tx.send(mapping.clone()).unwrap();
+        p2p.update_port_mapping();
+        assert_eq!(p2p.port_mapping, Some(mapping));
+        assert_eq!(p2p.public_port(), 2401);
+        assert_eq!(p2p.local_peer.public_ip_address, None);
+
+        // router tells us its address, so we advertise it
+        let mapping = PortMapping {
+            protocol: PortMapProtocol::NatPmp,
+            internal_port: 2400,
+            external_port: 2402,
+            external_addr: Some(PeerAddress::from_ipv4(203, 0, 113, 7)),
+            expires_at: get_epoch_time_secs() + 3600,
+        };
+        mapping_tx.send(mapping.clone()).unwrap();
+        p2p.update_port_mapping();
+        assert_eq!(
+            p2p.local_peer.public_ip_address,
+            Some((PeerAddress::from_ipv4(203, 0, 113, 7), 2402))
+        );
+
+        // a given public IP address is left alone
+        p2p.public_ip_learned = false;
+        p2p.local_peer.public_ip_address = Some((PeerAddress::from_ipv4(198, 51, 100, 1), 2400));
+        mapping_tx
+            .send(PortMapping {
+                external_port: 2403,
+                ..mapping
+            })
+            .unwrap();
+        p2p.update_port_mapping();
+        assert_eq!(p2p.public_port(), 2403);
+        assert_eq!(
+            p2p.local_peer.public_ip_address,
+            Some((PeerAddress::from_ipv4(198, 51, 100, 1), 2400))
+        );
+
+        // mapper thread went away
+        drop(mapping_tx);
+        p2p.update_port_mapping();
+        assert!(p2p.port_mapping_rx.is_none());
+    }
+
+    #[test]
+    fn test_find_eviction_candidate() {
+        let mut p2p = make_test_p2p_network(&vec![]);
+        let now = get_epoch_time_secs();
+
+        let add_peer = |p2p: &mut PeerNetwork,
+                        event_id: usize,
+                        addrbytes: PeerAddress,
+                        outbound: bool,
+                        first_contact_time: u64| {
+            let nk = NeighborKey {
+                peer_version: p2p.peer_version,
+                network_id: p2p.local_peer.network_id,
+                addrbytes: addrbytes,
+                port: 20000 + (event_id as u16),
+            };
+            let mut convo = ConversationP2P::new(
+                p2p.local_peer.network_id,
+                p2p.peer_version,
+                &p2p.burnchain,
+                &nk.addrbytes.to_socketaddr(nk.port),
+                &p2p.connection_opts,
+                outbound,
+                event_id,
+            );
+            convo.stats.first_contact_time = first_contact_time;
+            p2p.peers.insert(event_id, convo);
+            p2p.events.insert(nk.clone(), event_id);
+            nk
+        };
+
+        // long-lived peers
+        let mut keys = HashMap::new();
+        for i in 1..5 {
+            keys.insert(
+                i,
+                add_peer(
+                    &mut p2p,
+                    i,
+                    PeerAddress::from_ipv4(1, 2, 3, i as u8),
+                    false,
+                    100 + i as u64,
+                ),
+            );
+        }
+        // lots of newer peers from one subnet
+        for i in 5..13 {
+            keys.insert(
+                i,
+                add_peer(
+                    &mut p2p,
+                    i,
+                    PeerAddress::from_ipv4(5, 6, 7, i as u8),
+                    false,
+                    1000 + i as u64,
+                ),
+            );
+        }
+        // one peer from another subnet
+        keys.insert(
+            13,
+            add_peer(
+                &mut p2p,
+                13,
+                PeerAddress::from_ipv4(9, 9, 9, 1),
+                false,
+                2000,
+            ),
+        );
+
+        // the long-lived, healthy, and diverse peers are protected, so the youngest peer of the
+        // crowded subnet goes
+        assert_eq!(
+            p2p.find_eviction_candidate(false, &HashSet::new()),
+            Some(12)
+        );
+
+        // misbehaving peers go first, even if they're long-lived
+        p2p.reputation.record(&keys[&2], Misbehavior::Timeout, now);
+        assert_eq!(p2p.find_eviction_candidate(false, &HashSet::new()), Some(2));
+        p2p.reputation.clear(&keys[&2]);
+
+        // if everyone else is protected, then no one goes
+        let mut preserve = HashSet::new();
+        preserve.insert(12);
+        assert_eq!(p2p.find_eviction_candidate(false, &preserve), None);
+
+        // outbound peers are only ranked, so the youngest one goes
+        add_peer(
+            &mut p2p,
+            20,
+            PeerAddress::from_ipv4(127, 0, 0, 1),
+            true,
+            now - 100000,
+        );
+        add_peer(
+            &mut p2p,
+            21,
+            PeerAddress::from_ipv4(127, 0, 0, 1),
+            true,
+            now - 10,
+        );
+        assert_eq!(p2p.find_eviction_candidate(true, &HashSet::new()), Some(21));
+
+        // subnets
+        assert_eq!(
+            PeerNetwork::subnet_group(&PeerAddress::from_ipv4(5, 6, 7, 8)),
+            Some(PeerAddress::from_ipv4(5, 6, 7, 0))
+        );
+        assert_eq!(
+            PeerNetwork::subnet_group(&PeerAddress::from_ipv4(127, 0, 0, 1)),
+            None
+        );
+        assert_eq!(
+            p2p.count_inbound_subnet_conversations(&PeerAddress::from_ipv4(5, 6, 7, 100)),
+            8
+        );
+        assert_eq!(
+            p2p.count_inbound_subnet_conversations(&PeerAddress::from_ipv4(127, 0, 0, 1)),
+            0
+        );
+    }
+
     #[test]
     #[ignore]
     fn test_dispatch_requests_connect_and_ban() {
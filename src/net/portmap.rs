@@ -0,0 +1,745 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Port mapping for nodes behind a home router.
+//!
+//! A node behind a NAT can't accept inbound connections unless the router forwards its p2p port.
+//! If `enable_port_mapping` is set, the peer network asks the router to do so -- first with
+//! NAT-PMP (RFC 6886), and then with UPnP IGD -- and renews the mapping at half its lifetime.
+//! The router's external address and port are then advertised in our handshakes.
+//!
+//! Both protocols block on the router, so the mapper runs in its own thread and hands each
+//! mapping it obtains to the peer network over a channel.
+
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::mpsc::sync_channel;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::SyncSender;
+use std::sync::mpsc::TrySendError;
+use std::thread;
+use std::time::Duration;
+
+use net::PeerAddress;
+
+use util::get_epoch_time_secs;
+use util::log;
+use util::sleep_ms;
+
+/// NAT-PMP servers listen on this port on the gateway
+pub const NATPMP_PORT: u16 = 5351;
+
+/// SSDP multicast address for UPnP discovery
+pub const SSDP_MULTICAST_ADDR: &'static str = "239.255.255.250:1900";
+
+/// How long to wait for a router to answer, in milliseconds
+const PORT_MAP_TIMEOUT_MS: u64 = 2000;
+
+/// How long to wait before trying again after failing to map the port, in seconds
+const PORT_MAP_RETRY_INTERVAL: u64 = 60;
+
+const PORT_MAPPING_DESCRIPTION: &'static str = "stacks-node";
+
+const NATPMP_VERSION: u8 = 0;
+const NATPMP_OP_EXTERNAL_ADDRESS: u8 = 0;
+const NATPMP_OP_MAP_TCP: u8 = 2;
+
+const UPNP_SERVICE_TYPES: [&'static str; 3] = [
+    "urn:schemas-upnp-org:service:WANIPConnection:2",
+    "urn:schemas-upnp-org:service:WANIPConnection:1",
+    "urn:schemas-upnp-org:service:WANPPPConnection:1",
+];
+
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to talk to the router
+    Io(io::Error),
+    /// No router answered
+    NoGateway,
+    /// The router answered with something we could not parse
+    Malformed(String),
+    /// The router refused the request
+    Refused(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => fmt::Display::fmt(e, f),
+            Error::NoGateway => write!(f, "No NAT-PMP or UPnP gateway found"),
+            Error::Malformed(ref s) => write!(f, "Malformed gateway response: {}", s),
+            Error::Refused(ref s) => write!(f, "Gateway refused request: {}", s),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortMapProtocol {
+    NatPmp,
+    UPnP,
+}
+
+/// A TCP port forwarded to us by the router
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortMapping {
+    pub protocol: PortMapProtocol,
+    pub internal_port: u16,
+    pub external_port: u16,
+    /// The router's external address, if it told us and it is publicly routable
+    pub external_addr: Option<PeerAddress>,
+    pub expires_at: u64,
+}
+
+/// Where to send UPnP requests for the router's WAN connection service
+#[derive(Debug, Clone, PartialEq)]
+struct UPnPControl {
+    host: String,
+    path: String,
+    service_type: String,
+    local_addr: IpAddr,
+}
+
+pub struct PortMapper {
+    internal_port: u16,
+    lifetime: u64,
+    natpmp_gateway: Option<Ipv4Addr>,
+    upnp_control: Option<UPnPControl>,
+}
+
+/// Is this an address other hosts on the Internet can reach?
+fn is_public_ipv4(addr: &Ipv4Addr) -> bool {
+    let octets = addr.octets();
+    let carrier_grade_nat = octets[0] == 100 && (octets[1] & 0xc0) == 64;
+    !(addr.is_private()
+        || addr.is_loopback()
+        || addr.is_link_local()
+        || addr.is_unspecified()
+        || addr.is_broadcast()
+        || carrier_grade_nat)
+}
+
+fn public_peer_address(addr: &Ipv4Addr) -> Option<PeerAddress> {
+    if is_public_ipv4(addr) {
+        let octets = addr.octets();
+        Some(PeerAddress::from_ipv4(
+            octets[0], octets[1], octets[2], octets[3],
+        ))
+    } else {
+        None
+    }
+}
+
+/// Find the default gateway in the contents of /proc/net/route
+fn parse_default_gateway(route_table: &str) -> Option<Ipv4Addr> {
+    for line in route_table.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 3 || fields[1] != "00000000" {
+            continue;
+        }
+        // the kernel prints the address's network-order bytes as a host-order u32
+        if let Ok(gateway) = u32::from_str_radix(fields[2], 16) {
+            let gateway = Ipv4Addr::from(gateway.to_ne_bytes());
+            if !gateway.is_unspecified() {
+                return Some(gateway);
+            }
+        }
+    }
+    None
+}
+
+fn default_gateway() -> Option<Ipv4Addr> {
+    fs::read_to_string("/proc/net/route")
+        .ok()
+        .and_then(|route_table| parse_default_gateway(&route_table))
+}
+
+fn natpmp_encode_map_request(internal_port: u16, external_port: u16, lifetime: u32) -> [u8; 12] {
+    let mut request = [0u8; 12];
+    request[0] = NATPMP_VERSION;
+    request[1] = NATPMP_OP_MAP_TCP;
+    request[4..6].copy_from_slice(&internal_port.to_be_bytes());
+    request[6..8].copy_from_slice(&external_port.to_be_bytes());
+    request[8..12].copy_from_slice(&lifetime.to_be_bytes());
+    request
+}
+
+/// Check a NAT-PMP response's header for the given request opcode
+fn natpmp_check_response(response: &[u8], op: u8, len: usize) -> Result<(), Error> {
+    if response.len() < len {
+        return Err(Error::Malformed(format!(
+            "NAT-PMP response has {} bytes, expected {}",
+            response.len(),
+            len
+        )));
+    }
+    if response[0] != NATPMP_VERSION || response[1] != op | 0x80 {
+        return Err(Error::Malformed(format!(
+            "unexpected NAT-PMP version {} opcode {}",
+            response[0], response[1]
+        )));
+    }
+    let result_code = u16::from_be_bytes([response[2], response[3]]);
+    if result_code != 0 {
+        return Err(Error::Refused(format!(
+            "NAT-PMP result code {}",
+            result_code
+        )));
+    }
+    Ok(())
+}
+
+/// Decode the external address from a NAT-PMP external address response
+fn natpmp_decode_address_response(response: &[u8]) -> Result<Ipv4Addr, Error> {
+    natpmp_check_response(response, NATPMP_OP_EXTERNAL_ADDRESS, 12)?;
+    Ok(Ipv4Addr::new(
+        response[8],
+        response[9],
+        response[10],
+        response[11],
+    ))
+}
+
+/// Decode (internal port, external port, lifetime) from a NAT-PMP mapping response
+fn natpmp_decode_map_response(response: &[u8]) -> Result<(u16, u16, u32), Error> {
+    natpmp_check_response(response, NATPMP_OP_MAP_TCP, 16)?;
+    let internal_port = u16::from_be_bytes([response[8], response[9]]);
+    let external_port = u16::from_be_bytes([response[10], response[11]]);
+    let lifetime = u32::from_be_bytes([response[12], response[13], response[14], response[15]]);
+    Ok((internal_port, external_port, lifetime))
+}
+
+/// Send a NAT-PMP request, retrying with backoff as RFC 6886 asks
+fn natpmp_request(gateway: &Ipv4Addr, request: &[u8]) -> Result<Vec<u8>, Error> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.connect(SocketAddr::new(IpAddr::V4(gateway.clone()), NATPMP_PORT))?;
+
+    let mut timeout = 250;
+    let mut buf = [0u8; 16];
+    while timeout <= PORT_MAP_TIMEOUT_MS {
+        socket.send(request)?;
+        socket.set_read_timeout(Some(Duration::from_millis(timeout)))?;
+        match socket.recv(&mut buf) {
+            Ok(len) => {
+                return Ok(buf[0..len].to_vec());
+            }
+            Err(e) => {
+                if e.kind() != io::ErrorKind::WouldBlock && e.kind() != io::ErrorKind::TimedOut {
+                    return Err(Error::Io(e));
+                }
+            }
+        }
+        timeout *= 2;
+    }
+    Err(Error::NoGateway)
+}
+
+/// Get the value of the first <tag>...</tag> in an XML document
+fn xml_tag_value(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let start = xml.find(&open)? + open.len();
+    let end = xml[start..].find(&close)? + start;
+    Some(xml[start..end].trim().to_string())
+}
+
+/// Find the LOCATION header in an SSDP response
+fn parse_ssdp_location(response: &str) -> Option<String> {
+    for line in response.lines() {
+        if let Some(colon) = line.find(':') {
+            if line[0..colon].trim().eq_ignore_ascii_case("location") {
+                return Some(line[colon + 1..].trim().to_string());
+            }
+        }
+    }
+    None
+}
+
+/// Split an http:// URL into its host:port and its path
+fn split_http_url(url: &str) -> Option<(String, String)> {
+    if !url.starts_with("http://") {
+        return None;
+    }
+    let rest = &url["http://".len()..];
+    match rest.find('/') {
+        Some(slash) => Some((rest[0..slash].to_string(), rest[slash..].to_string())),
+        None => Some((rest.to_string(), "/".to_string())),
+    }
+}
+
+/// Find the (service type, control URL) of the router's WAN connection service in its device
+/// description
+fn find_upnp_control_url(description: &str) -> Option<(String, String)> {
+    for service_type in UPNP_SERVICE_TYPES.iter() {
+        let service_tag = format!("<serviceType>{}</serviceType>", service_type);
+        if let Some(start) = description.find(&service_tag) {
+            let service = &description[start..];
+            let service = match service.find("</service>") {
+                Some(end) => &service[0..end],
+                None => service,
+            };
+            if let Some(control_url) = xml_tag_value(service, "controlURL") {
+                return Some((service_type.to_string(), control_url));
+            }
+        }
+    }
+    None
+}
+
+/// Send an HTTP/1.1 request, and return the status code, the body, and the local address we
+/// used to reach the host.
+fn http_request(
+    host: &str,
+    method: &str,
+    path: &str,
+    headers: &[(&str, String)],
+    body: &str,
+) -> Result<(u16, String, IpAddr), Error> {
+    let addr = host
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| Error::Malformed(format!("could not resolve {}", host)))?;
+
+    let timeout = Duration::from_millis(PORT_MAP_TIMEOUT_MS);
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    let mut request = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Length: {}\r\n",
+        method,
+        path,
+        host,
+        body.len()
+    );
+    for (name, value) in headers.iter() {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+    request.push_str(body);
+    stream.write_all(request.as_bytes())?;
+
+    let mut response = vec![];
+    stream.read_to_end(&mut response)?;
+    let response = String::from_utf8_lossy(&response).to_string();
+
+    let status = response
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| Error::Malformed("no HTTP status line".to_string()))?;
+    let body = match response.find("\r\n\r\n") {
+        Some(i) => response[i + 4..].to_string(),
+        None => "".to_string(),
+    };
+    Ok((status, body, stream.local_addr()?.ip()))
+}
+
+impl UPnPControl {
+    /// Find the router with SSDP, and look up its WAN connection service
+    fn discover() -> Result<UPnPControl, Error> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.set_read_timeout(Some(Duration::from_millis(PORT_MAP_TIMEOUT_MS)))?;
+        let search = format!(
+            "M-SEARCH * HTTP/1.1\r\nHOST: {}\r\nST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\n\r\n",
+            SSDP_MULTICAST_ADDR
+        );
+        socket.send_to(search.as_bytes(), SSDP_MULTICAST_ADDR)?;
+
+        let mut buf = [0u8; 2048];
+        let location = match socket.recv_from(&mut buf) {
+            Ok((len, _)) => parse_ssdp_location(&String::from_utf8_lossy(&buf[0..len]))
+                .ok_or_else(|| Error::Malformed("SSDP response has no location".to_string()))?,
+            Err(e) => {
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut {
+                    return Err(Error::NoGateway);
+                }
+                return Err(Error::Io(e));
+            }
+        };
+
+        let (host, path) = split_http_url(&location)
+            .ok_or_else(|| Error::Malformed(format!("unsupported location {}", &location)))?;
+        let (status, description, local_addr) = http_request(&host, "GET", &path, &[], "")?;
+        if status != 200 {
+            return Err(Error::Refused(format!(
+                "HTTP {} fetching {}",
+                status, &location
+            )));
+        }
+
+        let (service_type, control_url) = find_upnp_control_url(&description)
+            .ok_or_else(|| Error::Malformed("no WAN connection service".to_string()))?;
+
+        let (host, path) = if control_url.starts_with("http://") {
+            split_http_url(&control_url).ok_or_else(|| {
+                Error::Malformed(format!("unsupported control URL {}", &control_url))
+            })?
+        } else if control_url.starts_with("/") {
+            (host, control_url)
+        } else {
+            (host, format!("/{}", control_url))
+        };
+
+        Ok(UPnPControl {
+            host,
+            path,
+            service_type,
+            local_addr,
+        })
+    }
+
+    /// Invoke a SOAP action on the WAN connection service, and return the response body
+    fn call(&self, action: &str, args: &[(&str, String)]) -> Result<String, Error> {
+        let mut arg_xml = String::new();
+        for (name, value) in args.iter() {
+            arg_xml.push_str(&format!("<{}>{}</{}>", name, value, name));
+        }
+        let body = format!(
+            "<?xml version=\"1.0\"?>\r\n<s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\"><s:Body><u:{} xmlns:u=\"{}\">{}</u:{}></s:Body></s:Envelope>",
+            action, &self.service_type, arg_xml, action
+        );
+        let headers = [
+            ("Content-Type", "text/xml; charset=\"utf-8\"".to_string()),
+            (
+                "SOAPAction",
+                format!("\"{}#{}\"", &self.service_type, action),
+            ),
+        ];
+        let (status, response, _) = http_request(&self.host, "POST", &self.path, &headers, &body)?;
+        if status != 200 {
+            let reason = xml_tag_value(&response, "errorDescription")
+                .unwrap_or_else(|| format!("HTTP {}", status));
+            return Err(Error::Refused(format!("{}: {}", action, reason)));
+        }
+        Ok(response)
+    }
+}
+
+impl PortMapper {
+    pub fn new(internal_port: u16, lifetime: u64) -> PortMapper {
+        PortMapper {
+            internal_port,
+            lifetime,
+            natpmp_gateway: None,
+            upnp_control: None,
+        }
+    }
+
+    fn natpmp_map_port(&mut self) -> Result<PortMapping, Error> {
+        let gateway = match self.natpmp_gateway {
+            Some(ref gateway) => gateway.clone(),
+            None => default_gateway().ok_or(Error::NoGateway)?,
+        };
+
+        let response = natpmp_request(&gateway, &[NATPMP_VERSION, NATPMP_OP_EXTERNAL_ADDRESS])?;
+        let external_addr = natpmp_decode_address_response(&response)?;
+
+        let lifetime = if self.lifetime > (u32::max_value() as u64) {
+            u32::max_value()
+        } else {
+            self.lifetime as u32
+        };
+        let request = natpmp_encode_map_request(self.internal_port, self.internal_port, lifetime);
+        let response = natpmp_request(&gateway, &request)?;
+        let (internal_port, external_port, lifetime) = natpmp_decode_map_response(&response)?;
+        if internal_port != self.internal_port {
+            return Err(Error::Malformed(format!(
+                "NAT-PMP mapped port {} instead of {}",
+                internal_port, self.internal_port
+            )));
+        }
+
+        self.natpmp_gateway = Some(gateway);
+        Ok(PortMapping {
+            protocol: PortMapProtocol::NatPmp,
+            internal_port,
+            external_port,
+            external_addr: public_peer_address(&external_addr),
+            expires_at: get_epoch_time_secs() + (lifetime as u64),
+        })
+    }
+
+    fn upnp_map_port(&mut self) -> Result<PortMapping, Error> {
+        let control = match self.upnp_control.take() {
+            Some(control) => control,
+            None => UPnPControl::discover()?,
+        };
+
+        control.call(
+            "AddPortMapping",
+            &[
+                ("NewRemoteHost", "".to_string()),
+                ("NewExternalPort", format!("{}", self.internal_port)),
+                ("NewProtocol", "TCP".to_string()),
+                ("NewInternalPort", format!("{}", self.internal_port)),
+                ("NewInternalClient", format!("{}", &control.local_addr)),
+                ("NewEnabled", "1".to_string()),
+                (
+                    "NewPortMappingDescription",
+                    PORT_MAPPING_DESCRIPTION.to_string(),
+                ),
+                ("NewLeaseDuration", format!("{}", self.lifetime)),
+            ],
+        )?;
+
+        // not all routers will tell us
+        let external_addr = control
+            .call("GetExternalIPAddress", &[])
+            .ok()
+            .and_then(|response| xml_tag_value(&response, "NewExternalIPAddress"))
+            .and_then(|addr| addr.parse::<Ipv4Addr>().ok())
+            .and_then(|addr| public_peer_address(&addr));
+
+        self.upnp_control = Some(control);
+        Ok(PortMapping {
+            protocol: PortMapProtocol::UPnP,
+            internal_port: self.internal_port,
+            external_port: self.internal_port,
+            external_addr,
+            expires_at: get_epoch_time_secs() + self.lifetime,
+        })
+    }
+
+    /// Ask the router to forward our port, trying NAT-PMP first and then UPnP
+    pub fn map_port(&mut self) -> Result<PortMapping, Error> {
+        match self.natpmp_map_port() {
+            Ok(mapping) => {
+                return Ok(mapping);
+            }
+            Err(e) => {
+                debug!(
+                    "Failed to map port {} with NAT-PMP: {}",
+                    self.internal_port, &e
+                );
+                self.natpmp_gateway = None;
+            }
+        }
+        match self.upnp_map_port() {
+            Ok(mapping) => Ok(mapping),
+            Err(e) => {
+                debug!(
+                    "Failed to map port {} with UPnP: {}",
+                    self.internal_port, &e
+                );
+                Err(e)
+            }
+        }
+    }
+
+    /// Ask the router to stop forwarding our port
+    pub fn unmap_port(&mut self, mapping: &PortMapping) -> Result<(), Error> {
+        match mapping.protocol {
+            PortMapProtocol::NatPmp => {
+                let gateway = self.natpmp_gateway.clone().ok_or(Error::NoGateway)?;
+                let request = natpmp_encode_map_request(mapping.internal_port, 0, 0);
+                let response = natpmp_request(&gateway, &request)?;
+                natpmp_decode_map_response(&response)?;
+            }
+            PortMapProtocol::UPnP => {
+                let control = self.upnp_control.as_ref().ok_or(Error::NoGateway)?;
+                control.call(
+                    "DeletePortMapping",
+                    &[
+                        ("NewRemoteHost", "".to_string()),
+                        ("NewExternalPort", format!("{}", mapping.external_port)),
+                        ("NewProtocol", "TCP".to_string()),
+                    ],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Map the port, and keep renewing the mapping at half its lifetime.  Each mapping is sent to
+    /// `mapping_tx`.  Once the receiver goes away, remove the mapping and stop.
+    fn run(mut self, mapping_tx: SyncSender<PortMapping>) -> () {
+        loop {
+            let next_attempt = match self.map_port() {
+                Ok(mapping) => {
+                    debug!(
+                        "Mapped port {} to external port {} with {:?} until {}",
+                        mapping.internal_port,
+                        mapping.external_port,
+                        mapping.protocol,
+                        mapping.expires_at
+                    );
+                    let now = get_epoch_time_secs();
+                    let renew_at = now + (mapping.expires_at.saturating_sub(now) / 2).max(1);
+                    match mapping_tx.try_send(mapping.clone()) {
+                        Ok(_) | Err(TrySendError::Full(_)) => {}
+                        Err(TrySendError::Disconnected(_)) => {
+                            if let Err(e) = self.unmap_port(&mapping) {
+                                debug!("Failed to remove port mapping: {}", &e);
+                            }
+                            return;
+                        }
+                    }
+                    renew_at
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to map port {} on the router: {}",
+                        self.internal_port, &e
+                    );
+                    get_epoch_time_secs() + PORT_MAP_RETRY_INTERVAL
+                }
+            };
+
+            while get_epoch_time_secs() < next_attempt {
+                sleep_ms(1000);
+            }
+        }
+    }
+
+    /// Run the port mapper in its own thread, and return the channel on which it sends each
+    /// mapping it obtains.  Dropping the receiver stops the thread.
+    pub fn spawn(self) -> Result<Receiver<PortMapping>, io::Error> {
+        let (mapping_tx, mapping_rx) = sync_channel(1);
+        thread::Builder::new()
+            .name("port-mapper".to_string())
+            .spawn(move || self.run(mapping_tx))?;
+        Ok(mapping_rx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_default_gateway() {
+        let route_table =
+            "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+                           eth0\t0000A8C0\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0\n\
+                           eth0\t00000000\t0101A8C0\t0003\t0\t0\t100\t00000000\t0\t0\t0\n";
+        assert_eq!(
+            parse_default_gateway(route_table),
+            Some(Ipv4Addr::new(192, 168, 1, 1))
+        );
+        assert_eq!(parse_default_gateway("Iface\tDestination\tGateway\n"), None);
+    }
+
+    #[test]
+    fn test_natpmp_codec() {
+        let request = natpmp_encode_map_request(20444, 20445, 3600);
+        assert_eq!(
+            request,
+            [0, 2, 0, 0, 0x4f, 0xdc, 0x4f, 0xdd, 0x00, 0x00, 0x0e, 0x10]
+        );
+
+        let response = [
+            0, 130, 0, 0, 0, 0, 0, 1, 0x4f, 0xdc, 0x4f, 0xdd, 0x00, 0x00, 0x07, 0x08,
+        ];
+        assert_eq!(
+            natpmp_decode_map_response(&response).unwrap(),
+            (20444, 20445, 1800)
+        );
+
+        let response = [0, 128, 0, 0, 0, 0, 0, 1, 203, 0, 113, 7];
+        assert_eq!(
+            natpmp_decode_address_response(&response).unwrap(),
+            Ipv4Addr::new(203, 0, 113, 7)
+        );
+
+        // refused
+        let response = [
+            0, 130, 0, 2, 0, 0, 0, 1, 0x4f, 0xdc, 0x4f, 0xdd, 0x00, 0x00, 0x07, 0x08,
+        ];
+        match natpmp_decode_map_response(&response) {
+            Err(Error::Refused(_)) => {}
+            x => panic!("expected refusal, got {:?}", &x),
+        }
+
+        // wrong opcode
+        match natpmp_decode_map_response(&[0, 128, 0, 0, 0, 0, 0, 1, 203, 0, 113, 7, 0, 0, 0, 0]) {
+            Err(Error::Malformed(_)) => {}
+            x => panic!("expected malformed, got {:?}", &x),
+        }
+
+        // too short
+        match natpmp_decode_address_response(&[0, 128, 0, 0]) {
+            Err(Error::Malformed(_)) => {}
+            x => panic!("expected malformed, got {:?}", &x),
+        }
+    }
+
+    #[test]
+    fn test_public_peer_address() {
+        assert_eq!(
+            public_peer_address(&Ipv4Addr::new(203, 0, 113, 7)),
+            Some(PeerAddress::from_ipv4(203, 0, 113, 7))
+        );
+        assert_eq!(public_peer_address(&Ipv4Addr::new(192, 168, 1, 2)), None);
+        assert_eq!(public_peer_address(&Ipv4Addr::new(10, 0, 0, 1)), None);
+        assert_eq!(public_peer_address(&Ipv4Addr::new(100, 64, 0, 1)), None);
+        assert_eq!(public_peer_address(&Ipv4Addr::new(0, 0, 0, 0)), None);
+    }
+
+    #[test]
+    fn test_upnp_discovery_parsing() {
+        let ssdp_response = "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=120\r\nLocation: http://192.168.1.1:5000/rootDesc.xml\r\nST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\n\r\n";
+        let location = parse_ssdp_location(ssdp_response).unwrap();
+        assert_eq!(location, "http://192.168.1.1:5000/rootDesc.xml");
+        assert_eq!(
+            split_http_url(&location),
+            Some(("192.168.1.1:5000".to_string(), "/rootDesc.xml".to_string()))
+        );
+        assert_eq!(
+            split_http_url("http://192.168.1.1"),
+            Some(("192.168.1.1".to_string(), "/".to_string()))
+        );
+        assert_eq!(split_http_url("https://192.168.1.1/"), None);
+
+        let description = "<root><device><serviceList>\
+            <service><serviceType>urn:schemas-upnp-org:service:Layer3Forwarding:1</serviceType><controlURL>/ctl/L3F</controlURL></service>\
+            <service><serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType><controlURL>/ctl/IPConn</controlURL></service>\
+            </serviceList></device></root>";
+        assert_eq!(
+            find_upnp_control_url(description),
+            Some((
+                "urn:schemas-upnp-org:service:WANIPConnection:1".to_string(),
+                "/ctl/IPConn".to_string()
+            ))
+        );
+        assert_eq!(find_upnp_control_url("<root></root>"), None);
+
+        let response = "<s:Envelope><s:Body><u:GetExternalIPAddressResponse><NewExternalIPAddress> 203.0.113.7 </NewExternalIPAddress></u:GetExternalIPAddressResponse></s:Body></s:Envelope>";
+        assert_eq!(
+            xml_tag_value(response, "NewExternalIPAddress"),
+            Some("203.0.113.7".to_string())
+        );
+        assert_eq!(xml_tag_value(response, "NewExternalPort"), None);
+    }
+}
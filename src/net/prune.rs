@@ -38,6 +38,7 @@ use util::db::Error as db_error;
 use std::net::Shutdown;
 use std::net::SocketAddr;
 
+use std::cmp;
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::collections::HashSet;
@@ -49,7 +50,59 @@ use util::log;
 use rand::prelude::*;
 use rand::thread_rng;
 
+/// When evicting an inbound peer, never pick one of this many longest-connected peers
+pub const EVICTION_PROTECT_LONG_LIVED: usize = 4;
+/// When evicting an inbound peer, never pick one of this many healthiest peers
+pub const EVICTION_PROTECT_HEALTHY: usize = 4;
+/// When evicting an inbound peer, never pick one of this many peers from the least-represented
+/// subnets
+pub const EVICTION_PROTECT_SUBNETS: usize = 4;
+
 impl PeerNetwork {
+    /// The subnet a peer address belongs to, for connection limits -- its /24 if it's IPv4, or its
+    /// /48 if it's IPv6.  Loopback addresses don't belong to one, since they're all the same host.
+    pub fn subnet_group(addrbytes: &PeerAddress) -> Option<PeerAddress> {
+        let mut group = addrbytes.clone();
+        match addrbytes.ipv4_octets() {
+            Some(octets) => {
+                if octets[0] == 127 {
+                    return None;
+                }
+                group.0[15] = 0;
+            }
+            None => {
+                if addrbytes.0[0..15] == [0u8; 15] && addrbytes.0[15] == 1 {
+                    return None;
+                }
+                for i in 6..16 {
+                    group.0[i] = 0;
+                }
+            }
+        }
+        Some(group)
+    }
+
+    /// Count how many inbound conversations come from the same subnet as the given address
+    pub fn count_inbound_subnet_conversations(&self, addrbytes: &PeerAddress) -> u64 {
+        let group = match PeerNetwork::subnet_group(addrbytes) {
+            Some(group) => group,
+            None => {
+                return 0;
+            }
+        };
+        let mut ret = 0;
+        for (nk, event_id) in self.events.iter() {
+            if let Some(convo) = self.peers.get(event_id) {
+                if !convo.stats.outbound
+                    && PeerNetwork::subnet_group(&nk.addrbytes) == Some(group.clone())
+                {
+                    ret += 1;
+                }
+            }
+        }
+        ret
+    }
+
     /// Find out which organizations have which of our outbound neighbors.
     /// Gives back a map from the organization ID to the list of (neighbor, neighbor-stats) tuples
     fn org_neighbor_distribution(
@@ -488,4 +541,97 @@ impl PeerNetwork {
             }
         }
     }
+
+    /// Pick a peer to disconnect to make room for a new connection in the same direction.
+    ///
+    /// Misbehaving peers go first.  Otherwise, we evict the least valuable peer from the
+    /// best-represented subnet, so that a single subnet can't crowd everyone else out.  Inbound
+    /// peers that have been connected the longest, that are the healthiest, or that come from
+    /// under-represented subnets are never picked; outbound peers are ranked by uptime and health
+    /// instead, so that the neighbor walk can always make room for the peers it discovers.
+    /// Connections in `preserve` are never picked.
+    pub fn find_eviction_candidate(
+        &self,
+        outbound: bool,
+        preserve: &HashSet<usize>,
+    ) -> Option<usize> {
+        let now = get_epoch_time_secs();
+        let mut candidates: Vec<(usize, NeighborKey, NeighborStats)> = vec![];
+        for (nk, event_id) in self.events.iter() {
+            if preserve.contains(event_id) {
+                continue;
+            }
+            if let Some(convo) = self.peers.get(event_id) {
+                if convo.stats.outbound == outbound {
+                    // a peer that hasn't said anything yet has only just connected
+                    let mut stats = convo.stats.clone();
+                    if stats.first_contact_time == 0 {
+                        stats.first_contact_time = now;
+                    }
+                    candidates.push((*event_id, nk.clone(), stats));
+                }
+            }
+        }
+
+        // misbehaving peers go first
+        if let Some((event_id, _, _)) = candidates
+            .iter()
+            .filter(|(_, nk, _)| self.reputation.get_score(nk, now) > 0)
+            .max_by_key(|(_, nk, _)| self.reputation.get_score(nk, now))
+        {
+            return Some(*event_id);
+        }
+
+        if !outbound {
+            // protect the longest-lived peers
+            candidates.sort_by_key(|(_, _, stats)| stats.first_contact_time);
+            let protected = cmp::min(EVICTION_PROTECT_LONG_LIVED, candidates.len());
+            candidates.drain(0..protected);
+
+            // protect the healthiest peers
+            candidates.sort_by(|(_, _, stats1), (_, _, stats2)| {
+                stats2
+                    .get_health_score()
+                    .partial_cmp(&stats1.get_health_score())
+                    .unwrap_or(Ordering::Equal)
+            });
+            let protected = cmp::min(EVICTION_PROTECT_HEALTHY, candidates.len());
+            candidates.drain(0..protected);
+
+            // protect peers from the least-represented subnets
+            let subnet_counts = PeerNetwork::count_subnets(&candidates);
+            candidates.sort_by_key(|(_, nk, _)| {
+                subnet_counts
+                    .get(&PeerNetwork::subnet_group(&nk.addrbytes))
+                    .cloned()
+                    .unwrap_or(0)
+            });
+            let protected = cmp::min(EVICTION_PROTECT_SUBNETS, candidates.len());
+            candidates.drain(0..protected);
+        }
+
+        // evict the least valuable peer from the best-represented subnet
+        let subnet_counts = PeerNetwork::count_subnets(&candidates);
+        let (evict_subnet, _) = subnet_counts.iter().max_by_key(|(_, count)| **count)?;
+        candidates
+            .into_iter()
+            .filter(|(_, nk, _)| &PeerNetwork::subnet_group(&nk.addrbytes) == evict_subnet)
+            .min_by(|(_, _, stats1), (_, _, stats2)| {
+                PeerNetwork::compare_neighbor_uptime_health(stats1, stats2)
+            })
+            .map(|(event_id, _, _)| event_id)
+    }
+
+    fn count_subnets(
+        candidates: &Vec<(usize, NeighborKey, NeighborStats)>,
+    ) -> HashMap<Option<PeerAddress>, usize> {
+        let mut subnet_counts = HashMap::new();
+        for (_, nk, _) in candidates.iter() {
+            let count = subnet_counts
+                .entry(PeerNetwork::subnet_group(&nk.addrbytes))
+                .or_insert(0);
+            *count += 1;
+        }
+        subnet_counts
+    }
 }
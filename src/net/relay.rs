@@ -31,12 +31,14 @@ use net::db::*;
 use net::http::*;
 use net::p2p::*;
 use net::poll::*;
+use net::reputation::Misbehavior;
 use net::rpc::*;
 use net::Error as net_error;
 use net::*;
 
 use chainstate::burn::ConsensusHash;
 use chainstate::coordinator::comm::CoordinatorChannels;
+use chainstate::stacks::db::blocks::MemPoolRejection;
 use chainstate::stacks::db::{StacksChainState, StacksEpochReceipt, StacksHeaderInfo};
 use chainstate::stacks::events::StacksTransactionReceipt;
 use chainstate::stacks::StacksBlockHeader;
@@ -62,6 +64,10 @@ use vm::costs::ExecutionCost;
 
 pub type BlocksAvailableMap = HashMap<BurnchainHeaderHash, (u64, ConsensusHash)>;
 
+/// New anchored blocks or confirmed microblock streams to push to our neighbors, each with the
+/// height of the sortition that selected it and the neighbors who already sent it to us.
+pub type PushData<T> = Vec<(u64, T, Vec<NeighborKey>)>;
+
 pub const MAX_RELAYER_STATS: usize = 4096;
 pub const MAX_RECENT_MESSAGES: usize = 256;
 pub const MAX_RECENT_MESSAGE_AGE: usize = 600; // seconds; equal to the expected epoch length
@@ -90,6 +96,8 @@ pub struct RelayerStats {
 
 pub struct ProcessedNetReceipts {
     pub mempool_txs_added: Vec<StacksTransaction>,
+    /// unconfirmed microblocks we had not seen before
+    pub microblocks_added: Vec<MicroblocksData>,
 }
 
 /// Private trait for keeping track of messages that can be relayed, so we can identify the peers
@@ -1038,37 +1046,161 @@ impl Relayer {
         Ok(ret)
     }
 
+    /// Gather the anchored blocks and confirmed microblock streams from a network result that
+    /// turned out to be new, so the p2p thread can push them to the outbound neighbors that lack
+    /// them.  The sortition heights come from the blocks-available data we just loaded for them.
+    /// A block pushed to us by a neighbor is not pushed back to that neighbor.
+    pub fn load_push_data(
+        network_result: &NetworkResult,
+        blocks_available: &BlocksAvailableMap,
+        mblocks_available: &BlocksAvailableMap,
+    ) -> (
+        PushData<(ConsensusHash, StacksBlock)>,
+        PushData<MicroblocksData>,
+    ) {
+        let mut block_heights = HashMap::new();
+        for (_, (height, consensus_hash)) in blocks_available.iter() {
+            block_heights.insert(consensus_hash.clone(), *height);
+        }
+
+        let mut blocks: PushData<(ConsensusHash, StacksBlock)> = vec![];
+        let mut block_indexes: HashMap<StacksBlockId, usize> = HashMap::new();
+
+        for (consensus_hash, block, _) in network_result.blocks.iter() {
+            let height = match block_heights.get(consensus_hash) {
+                Some(height) => *height,
+                None => {
+                    continue;
+                }
+            };
+            let index_block_hash =
+                StacksBlockHeader::make_index_block_hash(consensus_hash, &block.block_hash());
+            if !block_indexes.contains_key(&index_block_hash) {
+                block_indexes.insert(index_block_hash, blocks.len());
+                blocks.push((height, (consensus_hash.clone(), block.clone()), vec![]));
+            }
+        }
+
+        for (neighbor_key, blocks_datas) in network_result.pushed_blocks.iter() {
+            for blocks_data in blocks_datas.iter() {
+                for (consensus_hash, block) in blocks_data.blocks.iter() {
+                    let height = match block_heights.get(consensus_hash) {
+                        Some(height) => *height,
+                        None => {
+                            continue;
+                        }
+                    };
+                    let index_block_hash = StacksBlockHeader::make_index_block_hash(
+                        consensus_hash,
+                        &block.block_hash(),
+                    );
+                    let i = match block_indexes.get(&index_block_hash) {
+                        Some(i) => *i,
+                        None => {
+                            block_indexes.insert(index_block_hash, blocks.len());
+                            blocks.push((height, (consensus_hash.clone(), block.clone()), vec![]));
+                            blocks.len() - 1
+                        }
+                    };
+                    if !blocks[i].2.contains(neighbor_key) {
+                        blocks[i].2.push(neighbor_key.clone());
+                    }
+                }
+            }
+        }
+
+        let mut mblock_heights = HashMap::new();
+        for (_, (height, consensus_hash)) in mblocks_available.iter() {
+            mblock_heights.insert(consensus_hash.clone(), *height);
+        }
+
+        let mut microblocks: PushData<MicroblocksData> = vec![];
+        let mut mblock_indexes = HashSet::new();
+
+        for (consensus_hash, microblock_stream, _) in network_result.confirmed_microblocks.iter() {
+            if microblock_stream.len() == 0 {
+                continue;
+            }
+            let height = match mblock_heights.get(consensus_hash) {
+                Some(height) => *height,
+                None => {
+                    continue;
+                }
+            };
+            let index_anchor_block = StacksBlockHeader::make_index_block_hash(
+                consensus_hash,
+                &microblock_stream[0].header.prev_block,
+            );
+            if mblock_indexes.insert(index_anchor_block.clone()) {
+                microblocks.push((
+                    height,
+                    MicroblocksData {
+                        index_anchor_block: index_anchor_block,
+                        microblocks: microblock_stream.clone(),
+                    },
+                    vec![],
+                ));
+            }
+        }
+
+        (blocks, microblocks)
+    }
+
     /// Store a single transaction
-    /// Return true if stored; false if it was a dup
+    /// Return Ok(true) if stored; Ok(false) if it was a dup, and Err(..) if it was rejected
     fn store_transaction(
         mempool: &mut MemPoolDB,
         consensus_hash: &ConsensusHash,
         block_hash: &BlockHeaderHash,
         tx: StacksTransaction,
-    ) -> bool {
+    ) -> Result<bool, MemPoolRejection> {
         let txid = tx.txid();
         if mempool.has_tx(&txid) {
             debug!("Already have tx {}", txid);
-            return false;
+            return Ok(false);
         }
 
         if let Err(e) = mempool.submit(consensus_hash, block_hash, tx) {
             info!("Reject transaction {}: {:?}", txid, &e);
-            return false;
+            return Err(e);
         }
 
         debug!("Stored tx {}", txid);
-        return true;
+        return Ok(true);
+    }
+
+    /// Is a mempool rejection the relaying peer's fault?  A transaction that is malformed or
+    /// badly signed is invalid no matter what chain state it is checked against, so an honest
+    /// peer would not have relayed it.  A transaction that is merely stale (e.g. its nonce was
+    /// used, or its fee is too low for us) is not.
+    fn is_invalid_transaction(rejection: &MemPoolRejection) -> bool {
+        match rejection {
+            MemPoolRejection::SerializationFailure(_)
+            | MemPoolRejection::DeserializationFailure(_)
+            | MemPoolRejection::FailedToValidate(_)
+            | MemPoolRejection::BadAddressVersionByte
+            | MemPoolRejection::NoCoinbaseViaMempool
+            | MemPoolRejection::PoisonMicroblocksDoNotConflict
+            | MemPoolRejection::InvalidMicroblocks => true,
+            _ => false,
+        }
     }
 
     /// Store all new transactions we received, and return the list of transactions that we need to
-    /// forward (as well as their relay hints).  Also, garbage-collect the mempool.
+    /// forward (as well as their relay hints), and the neighbors that pushed us invalid
+    /// transactions.  Also, garbage-collect the mempool.
     fn process_transactions(
         network_result: &mut NetworkResult,
         sortdb: &SortitionDB,
         chainstate: &StacksChainState,
         mempool: &mut MemPoolDB,
-    ) -> Result<Vec<(Vec<RelayData>, StacksTransaction)>, net_error> {
+    ) -> Result<
+        (
+            Vec<(Vec<RelayData>, StacksTransaction)>,
+            Vec<(NeighborKey, Misbehavior)>,
+        ),
+        net_error,
+    > {
         let (consensus_hash, block_hash, chain_height) =
             match chainstate.get_stacks_chain_tip(sortdb)? {
                 Some(tip) => (tip.consensus_hash, tip.anchored_block_hash, tip.height),
@@ -1077,17 +1209,27 @@ impl Relayer {
                         "No Stacks chain tip; dropping {} transaction(s)",
                         network_result.pushed_transactions.len()
                     );
-                    return Ok(vec![]);
+                    return Ok((vec![], vec![]));
                 }
             };
 
         let mut ret = vec![];
+        let mut penalties = vec![];
 
         // messages pushed via the p2p network
-        for (_nk, tx_data) in network_result.pushed_transactions.iter() {
+        for (nk, tx_data) in network_result.pushed_transactions.iter() {
             for (relayers, tx) in tx_data.iter() {
-                if Relayer::store_transaction(mempool, &consensus_hash, &block_hash, tx.clone()) {
-                    ret.push((relayers.clone(), tx.clone()));
+                match Relayer::store_transaction(mempool, &consensus_hash, &block_hash, tx.clone())
+                {
+                    Ok(true) => {
+                        ret.push((relayers.clone(), tx.clone()));
+                    }
+                    Ok(false) => {}
+                    Err(rejection) => {
+                        if Relayer::is_invalid_transaction(&rejection) {
+                            penalties.push((nk.clone(), Misbehavior::InvalidTransaction));
+                        }
+                    }
                 }
             }
         }
@@ -1099,19 +1241,14 @@ impl Relayer {
         }
 
         // garbage-collect
-        if chain_height > MEMPOOL_MAX_TRANSACTION_AGE {
-            let min_height = chain_height - MEMPOOL_MAX_TRANSACTION_AGE;
-            let mut mempool_tx = mempool.tx_begin()?;
-
-            debug!(
-                "Remove all transactions beneath block height {}",
-                min_height
-            );
-            MemPoolDB::garbage_collect(&mut mempool_tx, min_height)?;
-            mempool_tx.commit()?;
-        }
+        mempool.garbage_collect_with_policy(
+            &sortdb.index_conn(),
+            &consensus_hash,
+            &block_hash,
+            chain_height,
+        )?;
 
-        Ok(ret)
+        Ok((ret, penalties))
     }
 
     pub fn advertize_blocks(&mut self, available: BlocksAvailableMap) -> Result<(), net_error> {
@@ -1166,23 +1303,54 @@ impl Relayer {
         mempool: &mut MemPoolDB,
         coord_comms: Option<&CoordinatorChannels>,
     ) -> Result<ProcessedNetReceipts, net_error> {
+        let mut microblocks_added = vec![];
         match Relayer::process_new_blocks(network_result, sortdb, chainstate, coord_comms) {
             Ok((new_blocks, new_confirmed_microblocks, new_microblocks, bad_block_neighbors)) => {
                 // attempt to relay messages (note that this is all best-effort).
                 // punish bad peers
                 if bad_block_neighbors.len() > 0 {
                     debug!(
-                        "{:?}: Ban {} peers",
+                        "{:?}: Penalize {} peers for invalid blocks",
                         &_local_peer,
                         bad_block_neighbors.len()
                     );
-                    if let Err(e) = self.p2p.ban_peers(bad_block_neighbors) {
-                        warn!("Failed to ban bad-block peers: {:?}", &e);
+                    let penalties = bad_block_neighbors
+                        .into_iter()
+                        .map(|nk| (nk, Misbehavior::InvalidBlock))
+                        .collect();
+                    if let Err(e) = self.p2p.penalize_peers(penalties) {
+                        warn!("Failed to penalize bad-block peers: {:?}", &e);
                     }
                 }
 
-                // have the p2p thread tell our neighbors about newly-discovered blocks
+                // have the p2p thread push newly-discovered blocks and confirmed microblock
+                // streams straight to the outbound neighbors whose inventories lack them, so
+                // they don't have to wait for their next inventory sync to find out
                 let available = Relayer::load_blocks_available_data(sortdb, new_blocks)?;
+                let mblocks_available =
+                    Relayer::load_blocks_available_data(sortdb, new_confirmed_microblocks)?;
+                let (push_blocks, push_microblocks) =
+                    Relayer::load_push_data(network_result, &available, &mblocks_available);
+
+                if push_blocks.len() > 0 {
+                    debug!("{:?}: Push {} new blocks", &_local_peer, push_blocks.len());
+                    if let Err(e) = self.p2p.push_blocks(push_blocks) {
+                        warn!("Failed to push new blocks: {:?}", &e);
+                    }
+                }
+
+                if push_microblocks.len() > 0 {
+                    debug!(
+                        "{:?}: Push {} new confirmed microblock streams",
+                        &_local_peer,
+                        push_microblocks.len()
+                    );
+                    if let Err(e) = self.p2p.push_microblocks(push_microblocks) {
+                        warn!("Failed to push new confirmed microblocks: {:?}", &e);
+                    }
+                }
+
+                // have the p2p thread tell our neighbors about newly-discovered blocks
                 if available.len() > 0 {
                     debug!("{:?}: Blocks available: {}", &_local_peer, available.len());
                     if let Err(e) = self.p2p.advertize_blocks(available) {
@@ -1191,8 +1359,6 @@ impl Relayer {
                 }
 
                 // have the p2p thread tell our neighbors about newly-discovered confirmed microblock streams
-                let mblocks_available =
-                    Relayer::load_blocks_available_data(sortdb, new_confirmed_microblocks)?;
                 if mblocks_available.len() > 0 {
                     debug!(
                         "{:?}: Confirmed microblock streams available: {}",
@@ -1218,6 +1384,7 @@ impl Relayer {
                             mblocks_msg.microblocks.len(),
                             &mblocks_msg.index_anchor_block
                         );
+                        microblocks_added.push(mblocks_msg.clone());
                         let msg = StacksMessageType::Microblocks(mblocks_msg);
                         if let Err(e) = self.p2p.broadcast_message(relayers, msg) {
                             warn!("Failed to broadcast microblock: {:?}", &e);
@@ -1236,7 +1403,19 @@ impl Relayer {
             &_local_peer,
             network_result.pushed_transactions.len()
         );
-        let new_txs = Relayer::process_transactions(network_result, sortdb, chainstate, mempool)?;
+        let (new_txs, penalties) =
+            Relayer::process_transactions(network_result, sortdb, chainstate, mempool)?;
+
+        if penalties.len() > 0 {
+            debug!(
+                "{:?}: Penalize {} peers for invalid transactions",
+                &_local_peer,
+                penalties.len()
+            );
+            if let Err(e) = self.p2p.penalize_peers(penalties) {
+                warn!("Failed to penalize peers: {:?}", &e);
+            }
+        }
 
         if new_txs.len() > 0 {
             debug!(
@@ -1256,7 +1435,10 @@ impl Relayer {
             }
         }
 
-        let receipts = ProcessedNetReceipts { mempool_txs_added };
+        let receipts = ProcessedNetReceipts {
+            mempool_txs_added,
+            microblocks_added,
+        };
 
         Ok(receipts)
     }
@@ -1512,6 +1694,164 @@ impl PeerNetwork {
         Ok(())
     }
 
+    /// Find the authenticated outbound neighbors whose block inventories say they don't have the
+    /// anchored block (or confirmed microblock stream) selected at the given sortition height.
+    /// Neighbors in `exclude` are skipped.
+    fn find_push_recipients(
+        &mut self,
+        block_height: u64,
+        microblocks: bool,
+        exclude: &Vec<NeighborKey>,
+    ) -> Vec<NeighborKey> {
+        let res = PeerNetwork::with_inv_state(self, |ref mut network, ref mut inv_state| {
+            let mut recipients = vec![];
+            for (neighbor, stats) in inv_state.block_stats.iter() {
+                if exclude.contains(neighbor) {
+                    continue;
+                }
+
+                let has_data = if microblocks {
+                    stats.inv.has_ith_microblock_stream(block_height)
+                } else {
+                    stats.inv.has_ith_block(block_height)
+                };
+                if has_data {
+                    continue;
+                }
+
+                let event_id = match network.events.get(neighbor) {
+                    Some(event_id) => *event_id,
+                    None => {
+                        continue;
+                    }
+                };
+                match network.peers.get(&event_id) {
+                    Some(convo) => {
+                        if convo.is_authenticated() && convo.is_outbound() {
+                            recipients.push((*neighbor).clone());
+                        }
+                    }
+                    None => {}
+                }
+            }
+            Ok(recipients)
+        });
+        match res {
+            Ok(recipients) => recipients,
+            Err(_) => vec![],
+        }
+    }
+
+    /// Record that we are pushing a block or microblock stream to a neighbor.
+    /// Returns false if we already pushed it to them within the last `retry` seconds, in which
+    /// case it should not be sent again.
+    fn record_push(
+        pushed: &mut HashMap<NeighborKey, HashMap<StacksBlockId, u64>>,
+        neighbor_key: &NeighborKey,
+        index_block_hash: &StacksBlockId,
+        retry: u64,
+        now: u64,
+    ) -> bool {
+        let push_set = pushed
+            .entry(neighbor_key.clone())
+            .or_insert_with(HashMap::new);
+        if let Some(ts) = push_set.get(index_block_hash) {
+            if ts.saturating_add(retry) > now {
+                return false;
+            }
+        }
+        push_set.insert(index_block_hash.clone(), now);
+        true
+    }
+
+    /// Push new anchored blocks to the outbound neighbors that don't have them, instead of
+    /// waiting for them to notice in their next inventory sync.  At most `max_block_push` blocks
+    /// are sent per call.  Pushes are recorded alongside the anti-entropy protocol's, so
+    /// neither protocol sends the same block to the same neighbor twice in a row.
+    pub fn push_blocks(&mut self, blocks: PushData<(ConsensusHash, StacksBlock)>) -> () {
+        let now = get_epoch_time_secs();
+        let mut num_pushed = 0;
+        for (block_height, (consensus_hash, block), exclude) in blocks.into_iter() {
+            let index_block_hash =
+                StacksBlockHeader::make_index_block_hash(&consensus_hash, &block.block_hash());
+            let recipients = self.find_push_recipients(block_height, false, &exclude);
+            for recipient in recipients.into_iter() {
+                if num_pushed >= self.connection_opts.max_block_push {
+                    debug!(
+                        "{:?}: Pushed {} blocks; will not push more",
+                        &self.local_peer, num_pushed
+                    );
+                    return;
+                }
+                if !PeerNetwork::record_push(
+                    &mut self.antientropy_blocks,
+                    &recipient,
+                    &index_block_hash,
+                    self.connection_opts.antientropy_retry,
+                    now,
+                ) {
+                    continue;
+                }
+
+                debug!(
+                    "{:?}: Push block {} to outbound peer {}",
+                    &self.local_peer, &index_block_hash, &recipient
+                );
+                let blocks_data = BlocksData {
+                    blocks: vec![(consensus_hash.clone(), block.clone())],
+                };
+                self.broadcast_message(
+                    vec![recipient],
+                    vec![],
+                    StacksMessageType::Blocks(blocks_data),
+                );
+                num_pushed += 1;
+            }
+        }
+    }
+
+    /// Push new confirmed microblock streams to the outbound neighbors that don't have them.
+    /// At most `max_microblock_push` streams are sent per call.
+    pub fn push_microblocks(&mut self, microblocks: PushData<MicroblocksData>) -> () {
+        let now = get_epoch_time_secs();
+        let mut num_pushed = 0;
+        for (block_height, microblocks_data, exclude) in microblocks.into_iter() {
+            let recipients = self.find_push_recipients(block_height, true, &exclude);
+            for recipient in recipients.into_iter() {
+                if num_pushed >= self.connection_opts.max_microblock_push {
+                    debug!(
+                        "{:?}: Pushed {} confirmed microblock streams; will not push more",
+                        &self.local_peer, num_pushed
+                    );
+                    return;
+                }
+                if !PeerNetwork::record_push(
+                    &mut self.antientropy_microblocks,
+                    &recipient,
+                    &microblocks_data.index_anchor_block,
+                    self.connection_opts.antientropy_retry,
+                    now,
+                ) {
+                    continue;
+                }
+
+                debug!(
+                    "{:?}: Push {} confirmed microblocks off of {} to outbound peer {}",
+                    &self.local_peer,
+                    microblocks_data.microblocks.len(),
+                    &microblocks_data.index_anchor_block,
+                    &recipient
+                );
+                self.broadcast_message(
+                    vec![recipient],
+                    vec![],
+                    StacksMessageType::Microblocks(microblocks_data.clone()),
+                );
+                num_pushed += 1;
+            }
+        }
+    }
+
     /// Update accounting information for relayed messages from a network result.
     /// This influences selecting next-hop neighbors to get data from us.
     pub fn update_relayer_stats(&mut self, network_result: &NetworkResult) -> () {
@@ -1930,6 +2270,142 @@ mod test {
         assert_eq!(*ranking.get(&nk_3).unwrap(), 4 - 2 + 1);
     }
 
+    #[test]
+    fn test_relayer_load_push_data() {
+        let nk_1 = NeighborKey {
+            peer_version: 12345,
+            network_id: 0x80000000,
+            addrbytes: PeerAddress([
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0x10, 0x11, 0x12, 0x13,
+            ]),
+            port: 54321,
+        };
+
+        let nk_2 = NeighborKey {
+            peer_version: 12345,
+            network_id: 0x80000000,
+            addrbytes: PeerAddress([
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0x20, 0x21, 0x22, 0x23,
+            ]),
+            port: 54321,
+        };
+
+        let block_1 = make_codec_test_block(1);
+        let block_2 = make_codec_test_block(2);
+        let block_3 = make_codec_test_block(3);
+
+        let ch_1 = ConsensusHash([0x11; 20]);
+        let ch_2 = ConsensusHash([0x22; 20]);
+        let ch_3 = ConsensusHash([0x33; 20]);
+
+        let mut network_result = NetworkResult::new(0, 0);
+
+        // downloaded, and also pushed to us by nk_1
+        network_result
+            .blocks
+            .push((ch_1.clone(), block_1.clone(), 123));
+
+        // pushed to us by both nk_1 and nk_2
+        network_result.pushed_blocks.insert(
+            nk_1.clone(),
+            vec![BlocksData {
+                blocks: vec![
+                    (ch_1.clone(), block_1.clone()),
+                    (ch_2.clone(), block_2.clone()),
+                ],
+            }],
+        );
+        network_result.pushed_blocks.insert(
+            nk_2.clone(),
+            vec![BlocksData {
+                blocks: vec![(ch_2.clone(), block_2.clone()), (ch_3.clone(), block_3)],
+            }],
+        );
+
+        // a downloaded microblock stream, and an empty one
+        let mblocks = vec![StacksMicroblock::first_unsigned(
+            &block_2.block_hash(),
+            block_2.txs.clone(),
+        )];
+        network_result
+            .confirmed_microblocks
+            .push((ch_2.clone(), mblocks.clone(), 123));
+        network_result
+            .confirmed_microblocks
+            .push((ch_1.clone(), vec![], 123));
+
+        // block_3 was not new to us
+        let mut blocks_available = BlocksAvailableMap::new();
+        blocks_available.insert(BurnchainHeaderHash([0x01; 32]), (10, ch_1.clone()));
+        blocks_available.insert(BurnchainHeaderHash([0x02; 32]), (11, ch_2.clone()));
+
+        let mut mblocks_available = BlocksAvailableMap::new();
+        mblocks_available.insert(BurnchainHeaderHash([0x01; 32]), (10, ch_1.clone()));
+        mblocks_available.insert(BurnchainHeaderHash([0x02; 32]), (11, ch_2.clone()));
+
+        let (mut push_blocks, push_microblocks) =
+            Relayer::load_push_data(&network_result, &blocks_available, &mblocks_available);
+
+        assert_eq!(push_blocks.len(), 2);
+        push_blocks.sort_by(|a, b| a.0.cmp(&b.0));
+
+        assert_eq!(push_blocks[0].0, 10);
+        assert_eq!(push_blocks[0].1, (ch_1.clone(), block_1.clone()));
+        assert_eq!(push_blocks[0].2, vec![nk_1.clone()]);
+
+        assert_eq!(push_blocks[1].0, 11);
+        assert_eq!(push_blocks[1].1, (ch_2.clone(), block_2.clone()));
+        assert_eq!(push_blocks[1].2.len(), 2);
+        assert!(push_blocks[1].2.contains(&nk_1));
+        assert!(push_blocks[1].2.contains(&nk_2));
+
+        assert_eq!(push_microblocks.len(), 1);
+        assert_eq!(push_microblocks[0].0, 11);
+        assert_eq!(
+            push_microblocks[0].1,
+            MicroblocksData {
+                index_anchor_block: StacksBlockHeader::make_index_block_hash(
+                    &ch_2,
+                    &block_2.block_hash()
+                ),
+                microblocks: mblocks,
+            }
+        );
+        assert_eq!(push_microblocks[0].2.len(), 0);
+
+        // pushes are not repeated until the retry interval passes
+        let mut pushed = HashMap::new();
+        let index_block_hash = StacksBlockId([0x01; 32]);
+        assert!(PeerNetwork::record_push(
+            &mut pushed,
+            &nk_1,
+            &index_block_hash,
+            3600,
+            1000
+        ));
+        assert!(!PeerNetwork::record_push(
+            &mut pushed,
+            &nk_1,
+            &index_block_hash,
+            3600,
+            2000
+        ));
+        assert!(PeerNetwork::record_push(
+            &mut pushed,
+            &nk_2,
+            &index_block_hash,
+            3600,
+            2000
+        ));
+        assert!(PeerNetwork::record_push(
+            &mut pushed,
+            &nk_1,
+            &index_block_hash,
+            3600,
+            4600
+        ));
+    }
+
     #[test]
     #[ignore]
     fn test_get_blocks_and_microblocks_3_peers_push_available() {
@@ -0,0 +1,232 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Peer reputation.
+//!
+//! Each time a peer misbehaves -- sends a malformed message, relays an invalid block or
+//! transaction, or lets a request time out -- a penalty is added to its misbehavior score.  An
+//! honest peer can do some of these things now and then, so the penalties differ, and the score
+//! halves every `REPUTATION_HALF_LIFE` seconds.  Once the score reaches the connection options'
+//! `ban_score_threshold`, the peer network bans the peer for `ban_duration` seconds and records
+//! the ban in the PeerDB, so it survives a restart.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use net::NeighborKey;
+
+/// A peer's misbehavior score halves after this many seconds
+pub const REPUTATION_HALF_LIFE: u64 = 3600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Misbehavior {
+    /// sent a message that violates the p2p protocol
+    ProtocolViolation,
+    /// relayed an anchored block or microblock that failed validation
+    InvalidBlock,
+    /// relayed a transaction that no chain state could make valid
+    InvalidTransaction,
+    /// did not answer a request in time
+    Timeout,
+}
+
+impl Misbehavior {
+    /// How many points this misbehavior adds to a peer's score
+    pub fn penalty(&self) -> u64 {
+        match self {
+            Misbehavior::ProtocolViolation => 20,
+            Misbehavior::InvalidBlock => 100,
+            Misbehavior::InvalidTransaction => 10,
+            Misbehavior::Timeout => 5,
+        }
+    }
+}
+
+impl fmt::Display for Misbehavior {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Misbehavior::ProtocolViolation => "protocol violation",
+            Misbehavior::InvalidBlock => "invalid block",
+            Misbehavior::InvalidTransaction => "invalid transaction",
+            Misbehavior::Timeout => "timeout",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ReputationScore {
+    score: u64,
+    last_update: u64,
+}
+
+impl ReputationScore {
+    /// The score as of `now`, after decay
+    fn current(&self, now: u64) -> u64 {
+        let half_lives = now.saturating_sub(self.last_update) / REPUTATION_HALF_LIFE;
+        if half_lives >= 64 {
+            0
+        } else {
+            self.score >> half_lives
+        }
+    }
+
+    /// Apply the decay up to `now`.  Only whole half-lives are applied, so the part of the
+    /// current half-life that has already passed still counts towards the next one.
+    fn decay(&mut self, now: u64) -> () {
+        let half_lives = now.saturating_sub(self.last_update) / REPUTATION_HALF_LIFE;
+        self.score = self.current(now);
+        if half_lives >= 64 {
+            self.last_update = now;
+        } else {
+            self.last_update += half_lives * REPUTATION_HALF_LIFE;
+        }
+    }
+}
+
+/// Misbehavior scores of the peers we have heard from recently
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerReputation {
+    scores: HashMap<NeighborKey, ReputationScore>,
+}
+
+impl PeerReputation {
+    pub fn new() -> PeerReputation {
+        PeerReputation {
+            scores: HashMap::new(),
+        }
+    }
+
+    /// Add a misbehavior to a peer's score, and return its new score
+    pub fn record(
+        &mut self,
+        neighbor_key: &NeighborKey,
+        misbehavior: Misbehavior,
+        now: u64,
+    ) -> u64 {
+        let entry = self
+            .scores
+            .entry(neighbor_key.clone())
+            .or_insert(ReputationScore {
+                score: 0,
+                last_update: now,
+            });
+
+        entry.decay(now);
+        entry.score = entry.score.saturating_add(misbehavior.penalty());
+        entry.score
+    }
+
+    /// Get a peer's current score
+    pub fn get_score(&self, neighbor_key: &NeighborKey, now: u64) -> u64 {
+        self.scores
+            .get(neighbor_key)
+            .map(|score| score.current(now))
+            .unwrap_or(0)
+    }
+
+    /// Forget a peer's score (e.g. because it has been banned)
+    pub fn clear(&mut self, neighbor_key: &NeighborKey) -> () {
+        self.scores.remove(neighbor_key);
+    }
+
+    /// Forget the peers whose scores have decayed to nothing
+    pub fn prune(&mut self, now: u64) -> () {
+        self.scores.retain(|_, score| score.current(now) > 0);
+    }
+
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use net::PeerAddress;
+
+    fn neighbor_key(port: u16) -> NeighborKey {
+        NeighborKey {
+            peer_version: 0x18000000,
+            network_id: 0x80000000,
+            addrbytes: PeerAddress([0u8; 16]),
+            port: port,
+        }
+    }
+
+    #[test]
+    fn test_reputation_accumulates_and_decays() {
+        let mut reputation = PeerReputation::new();
+        let nk = neighbor_key(1);
+        let other = neighbor_key(2);
+
+        assert_eq!(
+            reputation.record(&nk, Misbehavior::ProtocolViolation, 1000),
+            20
+        );
+        assert_eq!(reputation.record(&nk, Misbehavior::Timeout, 1000), 25);
+        assert_eq!(reputation.get_score(&nk, 1000), 25);
+        assert_eq!(reputation.get_score(&other, 1000), 0);
+
+        // halves every half-life
+        assert_eq!(
+            reputation.get_score(&nk, 1000 + REPUTATION_HALF_LIFE - 1),
+            25
+        );
+        assert_eq!(reputation.get_score(&nk, 1000 + REPUTATION_HALF_LIFE), 12);
+        assert_eq!(
+            reputation.get_score(&nk, 1000 + 2 * REPUTATION_HALF_LIFE),
+            6
+        );
+
+        // new penalties add to the decayed score
+        assert_eq!(
+            reputation.record(
+                &nk,
+                Misbehavior::InvalidTransaction,
+                1000 + REPUTATION_HALF_LIFE
+            ),
+            22
+        );
+
+        reputation.record(&other, Misbehavior::InvalidBlock, 1000);
+        assert_eq!(reputation.len(), 2);
+
+        // eventually, scores decay to nothing and are forgotten
+        reputation.prune(1000 + 10 * REPUTATION_HALF_LIFE);
+        assert_eq!(reputation.len(), 0);
+
+        reputation.record(&nk, Misbehavior::InvalidBlock, 1000);
+        reputation.clear(&nk);
+        assert_eq!(reputation.get_score(&nk, 1000), 0);
+        assert_eq!(reputation.get_score(&nk, u64::max_value()), 0);
+    }
+
+    #[test]
+    fn test_reputation_decays_under_repeated_offenses() {
+        let mut reputation = PeerReputation::new();
+        let nk = neighbor_key(1);
+
+        // a peer that times out slightly more often than once per half-life still sees its
+        // score decay, so it never climbs to a ban
+        let mut now = 1000;
+        for _ in 0..100 {
+            assert!(reputation.record(&nk, Misbehavior::Timeout, now) <= 10);
+            now += REPUTATION_HALF_LIFE - 1;
+        }
+        assert_eq!(reputation.get_score(&nk, now), 5);
+    }
+}
@@ -21,28 +21,43 @@ use std::fmt;
 use std::io;
 use std::io::prelude::*;
 use std::io::{Read, Seek, SeekFrom, Write};
+use std::mem;
 use std::net::SocketAddr;
+use std::sync::Mutex;
 
 use core::mempool::*;
+use core::StacksEpochId;
+use net::admin::{authorize_admin, RPCAdminAuth, RPCAdminControls};
+use net::apikeys::{RPCApiKeyCheck, RPCApiKeys};
+use net::atlas::{AtlasDB, Attachment};
+use net::audit::RPCAuditRecord;
 use net::connection::ConnectionHttp;
 use net::connection::ConnectionOptions;
 use net::connection::ReplyHandleHttp;
 use net::db::PeerDB;
+use net::divergence;
+use net::faucet::Faucet;
 use net::http::*;
 use net::p2p::PeerMap;
 use net::p2p::PeerNetwork;
+use net::websocket::websocket_accept_key;
 use net::ClientError;
 use net::Error as net_error;
+use net::GetAttachmentResponse;
 use net::HttpRequestMetadata;
 use net::HttpRequestType;
 use net::HttpResponseMetadata;
 use net::HttpResponseType;
 use net::MicroblocksData;
 use net::NeighborAddress;
+use net::NeighborDirection;
+use net::NeighborState;
 use net::NeighborsData;
+use net::NeighborsQuery;
 use net::PeerAddress;
 use net::PeerHost;
 use net::ProtocolFamily;
+use net::RPCAdminResponse;
 use net::StacksHttp;
 use net::StacksHttpMessage;
 use net::StacksMessageCodec;
@@ -50,12 +65,20 @@ use net::StacksMessageType;
 use net::UrlString;
 use net::HTTP_REQUEST_ID_RESERVED;
 use net::MAX_NEIGHBORS_DATA_LEN;
-use net::{AccountEntryResponse, CallReadOnlyResponse, ContractSrcResponse, MapEntryResponse};
+use net::{
+    AccountEntryResponse, AccountNoncesResponse, CallReadOnlyResponse, ContractSrcResponse,
+    MapEntryResponse, MinerRewardEntry, MinerRewardsResponse, MultiCallReadOnlyResponse,
+    ReadOnlyCall,
+};
+use net::{Neighbor, NeighborKey};
+use net::{RPCBlockValidationData, RPCTxValidationError};
+use net::{RPCBurnEtaData, RPCFeeEstimateData, RPCPeerInfoData, RPCPoxInfoData};
+use net::{RPCForkTipData, RPCForksData};
 use net::{RPCNeighbor, RPCNeighborsInfo};
-use net::{RPCPeerInfoData, RPCPoxInfoData};
 use std::collections::HashMap;
 use std::collections::VecDeque;
 
+use burnchains::timing::{BurnBlockTimeModel, BURN_BLOCK_TIME_SAMPLE_WINDOW};
 use burnchains::Burnchain;
 use burnchains::BurnchainHeaderHash;
 use burnchains::BurnchainView;
@@ -64,8 +87,10 @@ use burnchains::*;
 use chainstate::burn::db::sortdb::SortitionDB;
 use chainstate::burn::BlockHeaderHash;
 use chainstate::burn::ConsensusHash;
+use chainstate::stacks::db::reader::ChainstateReader;
 use chainstate::stacks::db::{
-    blocks::MINIMUM_TX_FEE_RATE_PER_BYTE, BlockStreamData, StacksChainState,
+    blocks::MINIMUM_TX_FEE_RATE_PER_BYTE, BlockStreamData, StacksChainState, StacksForkTip,
+    StacksHeaderInfo,
 };
 use chainstate::stacks::Error as chain_error;
 use chainstate::stacks::*;
@@ -75,6 +100,7 @@ use rusqlite::{DatabaseName, NO_PARAMS};
 
 use util::db::DBConn;
 use util::db::Error as db_error;
+use util::get_epoch_time_ms;
 use util::get_epoch_time_secs;
 use util::hash::to_hex;
 use util::hash::Hash160;
@@ -82,11 +108,9 @@ use util::hash::Hash160;
 use crate::version_string;
 
 use vm::{
-    clarity::ClarityConnection,
+    clarity::{ClarityConnection, ClarityReadOnlyConnection},
     costs::{ExecutionCost, LimitedCostTracker},
-    database::{
-        marf::ContractCommitment, ClarityDatabase, ClaritySerializable, MarfedKV, STXBalance,
-    },
+    database::{marf::ContractCommitment, BurnStateDB, ClarityDatabase, MarfedKV, STXBalance},
     types::{PrincipalData, QualifiedContractIdentifier, StandardPrincipalData},
     ClarityName, ContractName, SymbolicExpression, Value,
 };
@@ -99,6 +123,78 @@ pub const STREAM_CHUNK_SIZE: u64 = 4096;
 #[derive(Default)]
 pub struct RPCHandlerArgs<'a> {
     pub exit_at_block_height: Option<&'a u64>,
+    /// STX faucet to serve POST /v2/faucet/stx with, if enabled (never on mainnet)
+    pub faucet: Option<&'a Faucet>,
+    /// API keys that requests must carry, if the node requires them
+    pub api_keys: Option<&'a RPCApiKeys>,
+    /// attachment store to serve /v2/attachments with, if the node tracks attachments
+    pub atlasdb: Option<&'a AtlasDB>,
+    /// read-only chain state to answer account, map entry, and read-only function call queries
+    /// with, so they don't contend with block processing
+    pub chainstate_reader: Option<&'a Mutex<ChainstateReader>>,
+    /// handles on the node's subsystems for the admin endpoints to act on
+    pub admin: Option<&'a RPCAdminControls>,
+}
+
+/// A token bucket that limits how often a conversation may make expensive requests.  It holds
+/// at most `capacity` tokens and refills at `refill_rate` tokens per second.  Tokens are counted
+/// in thousandths, so slow refill rates still accrue between requests.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenBucket {
+    capacity: u64,
+    refill_rate: u64,
+    millitokens: u64,
+    last_refill_ms: u128,
+}
+
+impl TokenBucket {
+    /// Make a full bucket
+    pub fn new(capacity: u64, refill_rate: u64, now_ms: u128) -> TokenBucket {
+        TokenBucket {
+            capacity: capacity,
+            refill_rate: refill_rate,
+            millitokens: capacity.saturating_mul(1000),
+            last_refill_ms: now_ms,
+        }
+    }
+
+    fn refill(&mut self, now_ms: u128) -> () {
+        if now_ms <= self.last_refill_ms {
+            return;
+        }
+        let elapsed_ms = now_ms - self.last_refill_ms;
+        let accrued = (elapsed_ms as u64).saturating_mul(self.refill_rate);
+        self.millitokens = self
+            .millitokens
+            .saturating_add(accrued)
+            .min(self.capacity.saturating_mul(1000));
+        self.last_refill_ms = now_ms;
+    }
+
+    /// Take `count` tokens at time `now_ms`, if there are that many.  A request for more tokens
+    /// than the bucket can hold only needs a full bucket.
+    pub fn try_take(&mut self, count: u64, now_ms: u128) -> bool {
+        self.refill(now_ms);
+        let needed = count.min(self.capacity).saturating_mul(1000);
+        if self.millitokens < needed {
+            return false;
+        }
+        self.millitokens -= needed;
+        true
+    }
+
+    /// How many seconds until `count` tokens can be taken
+    pub fn retry_after(&self, count: u64) -> u64 {
+        let needed = count.min(self.capacity).saturating_mul(1000);
+        if self.millitokens >= needed {
+            return 0;
+        }
+        if self.refill_rate == 0 {
+            return u64::max_value();
+        }
+        let missing = needed - self.millitokens;
+        (missing + self.refill_rate * 1000 - 1) / (self.refill_rate * 1000)
+    }
 }
 
 pub struct ConversationHttp {
@@ -116,6 +212,15 @@ pub struct ConversationHttp {
     last_request_timestamp: u64, // absolute timestamp of the last time we received at least 1 byte in a request
     last_response_timestamp: u64, // absolute timestamp of the last time we sent at least 1 byte in a response
     connection_time: u64,         // when this converation was instantiated
+    max_request_count: u64, // how many requests we serve before closing the connection (0 for no limit)
+    read_only_call_bucket: Option<TokenBucket>, // limits the rate of read-only calls and block proposals
+    tls_client_authenticated: bool, // did the client present a trusted TLS client certificate?
+
+    // who may make admin requests, and the peer bans they asked for that the peer network has
+    // yet to carry out
+    admin_token: Option<String>,
+    admin_client_certs: bool,
+    admin_bans: Vec<(PeerAddress, u16, u64)>,
 
     // ongoing block streams
     reply_streams: VecDeque<(
@@ -128,6 +233,14 @@ pub struct ConversationHttp {
     pending_request: Option<ReplyHandleHttp>,
     pending_response: Option<HttpResponseType>,
     pending_error_response: Option<HttpResponseType>,
+
+    // served requests not yet written to the RPC audit log, if we keep one
+    audit_requests: bool,
+    audit_records: Vec<RPCAuditRecord>,
+
+    // whether or not we may upgrade to a WebSocket event stream, and whether we did
+    websocket_enabled: bool,
+    websocket_upgraded: bool,
 }
 
 impl fmt::Display for ConversationHttp {
@@ -185,6 +298,16 @@ impl RPCPeerInfoData {
             Some(ref unconfirmed) => unconfirmed.unconfirmed_chain_tip.clone(),
             None => StacksBlockId([0x00; 32]),
         };
+        let divergent_blocks = match divergence::load_divergent_blocks(&chainstate.root_path) {
+            Ok(records) => records
+                .into_iter()
+                .map(|record| record.index_block_hash)
+                .collect(),
+            Err(e) => {
+                warn!("Failed to load divergent blocks: {:?}", &e);
+                vec![]
+            }
+        };
 
         Ok(RPCPeerInfoData {
             peer_version: burnchain.peer_version,
@@ -200,6 +323,7 @@ impl RPCPeerInfoData {
             stacks_tip_consensus_hash: stacks_tip_consensus_hash.to_hex(),
             unanchored_tip: unconfirmed_tip,
             exit_at_block_height: exit_at_block_height.cloned(),
+            divergent_blocks,
         })
     }
 }
@@ -294,60 +418,328 @@ impl RPCPoxInfoData {
     }
 }
 
+impl RPCBurnEtaData {
+    /// Estimate when the burnchain block at `target_height` will arrive, as seen at time `now`.
+    /// If it already has arrived on the canonical burnchain fork, then its timestamp is reported.
+    pub fn from_db(
+        sortdb: &SortitionDB,
+        target_height: u64,
+        now: u64,
+    ) -> Result<RPCBurnEtaData, net_error> {
+        let tip =
+            SortitionDB::get_canonical_burn_chain_tip(sortdb.conn()).map_err(net_error::DBError)?;
+        let model = BurnBlockTimeModel::from_tip(sortdb, &tip, BURN_BLOCK_TIME_SAMPLE_WINDOW)
+            .map_err(net_error::DBError)?;
+        let eta = model.estimate(target_height, now);
+
+        let estimated_time = if eta.blocks_remaining == 0 {
+            match SortitionDB::get_ancestor_snapshot(
+                &sortdb.index_conn(),
+                target_height,
+                &tip.sortition_id,
+            )
+            .map_err(net_error::DBError)?
+            {
+                Some(sn) => sn.burn_header_timestamp,
+                None => eta.timestamp,
+            }
+        } else {
+            eta.timestamp
+        };
+
+        Ok(RPCBurnEtaData {
+            target_burn_block_height: target_height,
+            burn_block_height: tip.block_height,
+            blocks_remaining: eta.blocks_remaining,
+            avg_burn_block_time_secs: model.mean_block_time,
+            sample_size: model.num_samples,
+            estimated_seconds: eta.seconds,
+            estimated_seconds_low: eta.seconds_low,
+            estimated_seconds_high: eta.seconds_high,
+            estimated_time,
+        })
+    }
+}
+
+impl RPCFeeEstimateData {
+    /// Estimate the fee rates and fees of a transaction of length `len`, based on the blocks
+    /// mined recently on the canonical Stacks fork.
+    pub fn from_db(
+        sortdb: &SortitionDB,
+        mempool: &MemPoolDB,
+        len: u64,
+    ) -> Result<RPCFeeEstimateData, net_error> {
+        let tip =
+            SortitionDB::get_canonical_burn_chain_tip(sortdb.conn()).map_err(net_error::DBError)?;
+        let estimator =
+            MemPoolDB::get_fee_estimator(mempool.conn(), tip.canonical_stacks_tip_height)
+                .map_err(net_error::DBError)?;
+        let fee_rates = estimator.estimate_fee_rates();
+        let fees = fee_rates.fees_for_len(len);
+
+        Ok(RPCFeeEstimateData {
+            estimated_len: len,
+            fee_rates,
+            fees,
+            median_block_fill: estimator.median_block_fill(),
+            num_blocks: estimator.num_blocks(),
+            sample_size: estimator.num_samples(),
+        })
+    }
+}
+
+/// How far below the canonical Stacks tip we look for competing fork tips
+pub const FORK_TIPS_MAX_DEPTH: u64 = 100;
+
+/// How many of the blocks an address mined or supported we report rewards for
+pub const MINER_REWARDS_MAX_BLOCKS: u64 = 100;
+
+impl RPCForksData {
+    /// Load the tips of all recent Stacks forks, and where each one diverges from the canonical
+    /// fork.
+    pub fn from_db(
+        sortdb: &SortitionDB,
+        chainstate: &StacksChainState,
+    ) -> Result<RPCForksData, net_error> {
+        let (consensus_hash, block_hash) =
+            SortitionDB::get_canonical_stacks_chain_tip_hash(sortdb.conn())
+                .map_err(net_error::DBError)?;
+        let canonical_header = StacksChainState::get_anchored_block_header_info(
+            chainstate.headers_db(),
+            &consensus_hash,
+            &block_hash,
+        )?
+        .ok_or(net_error::NotFoundError)?;
+
+        let canonical_tip = StacksForkTip {
+            index_block_hash: StacksBlockHeader::make_index_block_hash(
+                &consensus_hash,
+                &block_hash,
+            ),
+            consensus_hash,
+            block_hash,
+            block_height: canonical_header.block_height,
+            total_burn: canonical_header.anchored_header.total_work.burn,
+        };
+
+        let mut tips = chainstate.get_fork_tips(
+            canonical_tip
+                .block_height
+                .saturating_sub(FORK_TIPS_MAX_DEPTH),
+        )?;
+
+        // databases that predate fork tracking won't have the canonical tip yet
+        if tips
+            .iter()
+            .find(|tip| tip.index_block_hash == canonical_tip.index_block_hash)
+            .is_none()
+        {
+            tips.insert(0, canonical_tip.clone());
+        }
+
+        let mut forks = Vec::with_capacity(tips.len());
+        for tip in tips.into_iter() {
+            let (fork_point_height, fork_point) =
+                match chainstate.get_fork_point(&canonical_tip, &tip)? {
+                    Some(fp) => fp,
+                    None => {
+                        debug!(
+                            "No fork point between {} and {}",
+                            &canonical_tip.index_block_hash, &tip.index_block_hash
+                        );
+                        continue;
+                    }
+                };
+
+            forks.push(RPCForkTipData {
+                canonical: tip.index_block_hash == canonical_tip.index_block_hash,
+                length: tip.block_height.saturating_sub(fork_point_height),
+                fork_point,
+                fork_point_height,
+                index_block_hash: tip.index_block_hash,
+                consensus_hash: tip.consensus_hash,
+                block_hash: tip.block_hash,
+                block_height: tip.block_height,
+                total_burn: tip.total_burn,
+            });
+        }
+
+        Ok(RPCForksData {
+            canonical_tip: canonical_tip.index_block_hash,
+            stacks_tip_height: canonical_tip.block_height,
+            forks,
+        })
+    }
+}
+
+impl RPCBlockValidationData {
+    /// Validate a proposed anchored block as if it were mined on top of `parent`, in the current
+    /// canonical burnchain block.
+    pub fn from_db(
+        sortdb: &SortitionDB,
+        chainstate: &StacksChainState,
+        parent: &StacksHeaderInfo,
+        block: &StacksBlock,
+    ) -> Result<RPCBlockValidationData, net_error> {
+        let burn_tip =
+            SortitionDB::get_canonical_burn_chain_tip(sortdb.conn()).map_err(net_error::DBError)?;
+        let transfer_stx_ops = StacksChainState::get_transfer_stx_ops(
+            sortdb.conn(),
+            parent.burn_header_height,
+            &burn_tip.burn_header_hash,
+        )?;
+        let validation = chainstate.validate_block_proposal(
+            &sortdb.index_conn(),
+            parent,
+            block,
+            &transfer_stx_ops,
+        )?;
+
+        Ok(RPCBlockValidationData {
+            block_hash: block.block_hash(),
+            parent_index_block_hash: parent.index_block_hash(),
+            valid: validation.is_valid(),
+            state_index_root: validation.state_index_root,
+            execution_cost: validation.execution_cost,
+            total_fees: validation.total_fees as u64,
+            block_errors: validation.block_errors,
+            tx_errors: validation
+                .tx_errors
+                .into_iter()
+                .map(|e| RPCTxValidationError {
+                    tx_index: e.tx_index as u32,
+                    txid: e.txid.to_hex(),
+                    reason: e.reason,
+                })
+                .collect(),
+        })
+    }
+}
+
 impl RPCNeighborsInfo {
-    /// Load neighbor address information from the peer network
+    /// Load neighbor address information from the peer network, keeping only the neighbors that
+    /// match the query.  Each neighbor's last contact time comes from the PeerDB.  If the query
+    /// filters on a PeerDB state, the sample lists every peer in the PeerDB in that state instead
+    /// of a random sample of fresh peers.
     pub fn from_p2p(
         network_id: u32,
         peers: &PeerMap,
         chain_view: &BurnchainView,
         peerdb: &PeerDB,
+        query: &NeighborsQuery,
     ) -> Result<RPCNeighborsInfo, net_error> {
-        let neighbor_sample = PeerDB::get_random_neighbors(
-            peerdb.conn(),
-            network_id,
-            MAX_NEIGHBORS_DATA_LEN,
-            chain_view.burn_block_height,
-            false,
-        )
-        .map_err(net_error::DBError)?;
+        let neighbor_sample = match query.state {
+            Some(_) => PeerDB::get_all_peers(peerdb.conn())
+                .map_err(net_error::DBError)?
+                .into_iter()
+                .filter(|n| n.addr.network_id == network_id)
+                .collect(),
+            None => PeerDB::get_random_neighbors(
+                peerdb.conn(),
+                network_id,
+                MAX_NEIGHBORS_DATA_LEN,
+                chain_view.burn_block_height,
+                false,
+            )
+            .map_err(net_error::DBError)?,
+        };
 
-        let sample: Vec<RPCNeighbor> = neighbor_sample
-            .into_iter()
-            .map(|n| {
-                RPCNeighbor::from_neighbor_key_and_pubkh(
-                    n.addr.clone(),
-                    Hash160::from_node_public_key(&n.public_key),
-                    true,
-                )
-            })
-            .collect();
+        let mut sample = vec![];
+        for n in neighbor_sample.into_iter() {
+            if !RPCNeighborsInfo::matches_query(peerdb, Some(&n), &n.addr, query)? {
+                continue;
+            }
+            let mut neighbor = RPCNeighbor::from_neighbor_key_and_pubkh(
+                n.addr.clone(),
+                Hash160::from_node_public_key(&n.public_key),
+                true,
+            );
+            neighbor.last_contact_time = Some(n.last_contact_time);
+            sample.push(neighbor);
+        }
 
         let mut inbound = vec![];
         let mut outbound = vec![];
         for (_, convo) in peers.iter() {
             let nk = convo.to_neighbor_key();
             let naddr = convo.to_neighbor_address();
+            let db_neighbor = PeerDB::get_peer(peerdb.conn(), network_id, &nk.addrbytes, nk.port)
+                .map_err(net_error::DBError)?;
+            if !RPCNeighborsInfo::matches_query(peerdb, db_neighbor.as_ref(), &nk, query)? {
+                continue;
+            }
+
+            let mut neighbor = RPCNeighbor::from_neighbor_key_and_pubkh(
+                nk,
+                naddr.public_key_hash,
+                convo.is_authenticated(),
+            );
+            neighbor.last_contact_time = db_neighbor.map(|n| n.last_contact_time);
             if convo.is_outbound() {
-                outbound.push(RPCNeighbor::from_neighbor_key_and_pubkh(
-                    nk,
-                    naddr.public_key_hash,
-                    convo.is_authenticated(),
-                ));
+                outbound.push(neighbor);
             } else {
-                inbound.push(RPCNeighbor::from_neighbor_key_and_pubkh(
-                    nk,
-                    naddr.public_key_hash,
-                    convo.is_authenticated(),
-                ));
+                inbound.push(neighbor);
+            }
+        }
+
+        // the sample is not a set of connections, so it has no direction
+        match query.direction {
+            Some(NeighborDirection::Inbound) => {
+                sample.clear();
+                outbound.clear();
+            }
+            Some(NeighborDirection::Outbound) => {
+                sample.clear();
+                inbound.clear();
             }
+            None => {}
         }
 
         Ok(RPCNeighborsInfo {
-            sample: sample,
-            inbound: inbound,
-            outbound: outbound,
+            sample: RPCNeighborsInfo::paginate(sample, query),
+            inbound: RPCNeighborsInfo::paginate(inbound, query),
+            outbound: RPCNeighborsInfo::paginate(outbound, query),
         })
     }
+
+    /// Does a neighbor match the query's address and PeerDB state filters?
+    fn matches_query(
+        peerdb: &PeerDB,
+        db_neighbor: Option<&Neighbor>,
+        nk: &NeighborKey,
+        query: &NeighborsQuery,
+    ) -> Result<bool, net_error> {
+        if let Some(ref addr) = query.address {
+            if nk.addrbytes != *addr {
+                return Ok(false);
+            }
+        }
+        match query.state {
+            Some(NeighborState::Allowed) => {
+                Ok(db_neighbor.map(|n| n.is_allowed()).unwrap_or(false))
+            }
+            Some(NeighborState::Banned) => {
+                if db_neighbor.map(|n| n.is_denied()).unwrap_or(false) {
+                    return Ok(true);
+                }
+                PeerDB::is_address_denied(peerdb.conn(), &nk.addrbytes).map_err(net_error::DBError)
+            }
+            None => Ok(true),
+        }
+    }
+
+    /// Sort neighbors by address, so pages are stable, and apply the query's offset and limit
+    fn paginate(mut neighbors: Vec<RPCNeighbor>, query: &NeighborsQuery) -> Vec<RPCNeighbor> {
+        neighbors.sort_by(|a, b| {
+            (a.addrbytes.as_bytes(), a.port).cmp(&(b.addrbytes.as_bytes(), b.port))
+        });
+        let limit = query.limit.unwrap_or(u32::max_value()) as usize;
+        neighbors
+            .into_iter()
+            .skip(query.offset as usize)
+            .take(limit)
+            .collect()
+    }
 }
 
 impl ConversationHttp {
@@ -362,6 +754,9 @@ impl ConversationHttp {
     ) -> ConversationHttp {
         let mut stacks_http = StacksHttp::new();
         stacks_http.maximum_call_argument_size = conn_opts.maximum_call_argument_size;
+        stacks_http.response_headers = HttpResponseHeaders::from_connection_options(conn_opts);
+        stacks_http.compress_responses = conn_opts.rpc_compression;
+        stacks_http.compression_min_size = conn_opts.rpc_compression_min_size;
         ConversationHttp {
             network_id: network_id,
             connection: ConnectionHttp::new(stacks_http, conn_opts, None),
@@ -381,9 +776,59 @@ impl ConversationHttp {
             last_request_timestamp: 0,
             last_response_timestamp: 0,
             connection_time: get_epoch_time_secs(),
+            max_request_count: conn_opts.max_http_requests_per_connection,
+            read_only_call_bucket: if conn_opts.read_only_call_rate > 0 {
+                Some(TokenBucket::new(
+                    conn_opts.read_only_call_burst,
+                    conn_opts.read_only_call_rate,
+                    get_epoch_time_ms(),
+                ))
+            } else {
+                None
+            },
+            tls_client_authenticated: false,
+            admin_token: conn_opts.rpc_admin_token.clone(),
+            admin_client_certs: conn_opts.rpc_tls_cert_path.is_some()
+                && conn_opts.rpc_tls_client_ca_path.is_some(),
+            admin_bans: vec![],
+            audit_requests: conn_opts.rpc_audit_log_path.is_some(),
+            audit_records: vec![],
+            websocket_enabled: false,
+            websocket_upgraded: false,
         }
     }
 
+    /// Record whether or not the client authenticated with a TLS client certificate
+    pub fn set_tls_client_authenticated(&mut self, authenticated: bool) -> () {
+        self.tls_client_authenticated = authenticated;
+    }
+
+    /// Did the client authenticate with a TLS client certificate?
+    pub fn is_tls_client_authenticated(&self) -> bool {
+        self.tls_client_authenticated
+    }
+
+    /// Allow or forbid upgrading this conversation to a WebSocket event stream
+    pub fn set_websocket_enabled(&mut self, enabled: bool) -> () {
+        self.websocket_enabled = enabled;
+    }
+
+    /// Did we agree to upgrade this conversation to a WebSocket?  If so, once the 101 response
+    /// is flushed, the socket no longer speaks HTTP.
+    pub fn is_websocket_upgraded(&self) -> bool {
+        self.websocket_upgraded
+    }
+
+    /// Take the requests served since the last call, to be written to the RPC audit log
+    pub fn take_audit_records(&mut self) -> Vec<RPCAuditRecord> {
+        mem::replace(&mut self.audit_records, vec![])
+    }
+
+    /// Take the peer bans requested since the last call, as (address, port, duration) triples
+    pub fn take_admin_bans(&mut self) -> Vec<(PeerAddress, u16, u64)> {
+        mem::replace(&mut self.admin_bans, vec![])
+    }
+
     /// How many ongoing requests do we have on this conversation?
     pub fn num_pending_outbound(&self) -> usize {
         self.reply_streams.len()
@@ -543,6 +988,32 @@ impl ConversationHttp {
         }
     }
 
+    /// Handle a GET burn block ETA.
+    /// The response will be synchronously written to the given fd (so use a fd that can buffer!)
+    fn handle_getburneta<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        target_height: u64,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        match RPCBurnEtaData::from_db(sortdb, target_height, get_epoch_time_secs()) {
+            Ok(eta) => {
+                let response = HttpResponseType::BurnEta(response_metadata, eta);
+                response.send(http, fd)
+            }
+            Err(e) => {
+                warn!("Failed to estimate burn block arrival {:?}: {:?}", req, &e);
+                let response = HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to estimate burn block arrival".to_string(),
+                );
+                response.send(http, fd)
+            }
+        }
+    }
+
     /// Handle a GET neighbors
     /// The response will be synchronously written to the given fd (so use a fd that can buffer!)
     fn handle_getneighbors<W: Write>(
@@ -553,9 +1024,11 @@ impl ConversationHttp {
         chain_view: &BurnchainView,
         peers: &PeerMap,
         peerdb: &PeerDB,
+        query: &NeighborsQuery,
     ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        let neighbor_data = RPCNeighborsInfo::from_p2p(network_id, peers, chain_view, peerdb)?;
+        let neighbor_data =
+            RPCNeighborsInfo::from_p2p(network_id, peers, chain_view, peerdb, query)?;
         let response = HttpResponseType::Neighbors(response_metadata, neighbor_data);
         response.send(http, fd)
     }
@@ -596,10 +1069,74 @@ impl ConversationHttp {
                 response.send(http, fd).and_then(|_| Ok(None))
             }
             Ok(true) => {
+                // an empty block is either invalid or pruned -- either way, not servable
+                let block_path = StacksChainState::get_index_block_path(
+                    &chainstate.blocks_path,
+                    index_block_hash,
+                )?;
+                if StacksChainState::get_file_size(&block_path).unwrap_or(0) == 0 {
+                    let response = HttpResponseType::NotFound(
+                        response_metadata,
+                        format!("No such block {}", index_block_hash.to_hex()),
+                    );
+                    return response.send(http, fd).and_then(|_| Ok(None));
+                }
+
                 // yup! start streaming it back
                 let stream = BlockStreamData::new_block(index_block_hash.clone());
                 let response = HttpResponseType::BlockStream(response_metadata);
-                response.send(http, fd).and_then(|_| Ok(Some(stream)))
+                ConversationHttp::send_stream_preamble(
+                    http,
+                    fd,
+                    req,
+                    chainstate,
+                    index_block_hash,
+                    stream,
+                    response,
+                )
+            }
+        }
+    }
+
+    /// Send the preamble of a stream of a block or a confirmed microblock stream, which are both
+    /// read out of the chunk store.  If the request has a byte range, only that range is
+    /// streamed, with a 206 response; otherwise `whole` is sent.  Returns the stream to keep
+    /// sending, or None if the range could not be satisfied and a 416 response was sent instead.
+    fn send_stream_preamble<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        chainstate: &StacksChainState,
+        index_hash: &StacksBlockId,
+        stream: BlockStreamData,
+        whole: HttpResponseType,
+    ) -> Result<Option<BlockStreamData>, net_error> {
+        let range = match req.metadata().range {
+            Some(range) => range,
+            None => {
+                return whole.send(http, fd).and_then(|_| Ok(Some(stream)));
+            }
+        };
+
+        let response_metadata = HttpResponseMetadata::from(req);
+        let data_path =
+            StacksChainState::get_index_block_path(&chainstate.blocks_path, index_hash)?;
+        let len = StacksChainState::get_file_size(&data_path)?;
+        match range.resolve(len) {
+            Some((start, end)) => {
+                let response =
+                    HttpResponseType::PartialStream(response_metadata, start, end - 1, len);
+                response
+                    .send(http, fd)
+                    .and_then(|_| Ok(Some(stream.with_range(start, end))))
+            }
+            None => {
+                let response = HttpResponseType::Error(
+                    response_metadata,
+                    416,
+                    format!("Range not satisfiable: length is {}", len),
+                );
+                response.send(http, fd).and_then(|_| Ok(None))
             }
         }
     }
@@ -652,7 +1189,15 @@ impl ConversationHttp {
                 let stream =
                     BlockStreamData::new_microblock_confirmed(index_microblock_hash.clone());
                 let response = HttpResponseType::MicroblockStream(response_metadata);
-                response.send(http, fd).and_then(|_| Ok(Some(stream)))
+                ConversationHttp::send_stream_preamble(
+                    http,
+                    fd,
+                    req,
+                    chainstate,
+                    &index_microblock_hash,
+                    stream,
+                    response,
+                )
             }
         }
     }
@@ -706,7 +1251,15 @@ impl ConversationHttp {
                 let stream =
                     BlockStreamData::new_microblock_confirmed(index_microblock_hash.clone());
                 let response = HttpResponseType::MicroblockStream(response_metadata);
-                response.send(http, fd).and_then(|_| Ok(Some(stream)))
+                ConversationHttp::send_stream_preamble(
+                    http,
+                    fd,
+                    req,
+                    chainstate,
+                    index_microblock_hash,
+                    stream,
+                    response,
+                )
             }
         }
     }
@@ -728,110 +1281,623 @@ impl ConversationHttp {
         response.send(http, fd).map(|_| ())
     }
 
+    /// Handle a GET fee estimate for a transaction of the given length.
+    /// The response will be synchronously written to the given fd (so use a fd that can buffer!)
+    fn handle_get_fee_estimate<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        mempool: &MemPoolDB,
+        len: u64,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        match RPCFeeEstimateData::from_db(sortdb, mempool, len) {
+            Ok(fee_estimate) => {
+                let response = HttpResponseType::FeeEstimate(response_metadata, fee_estimate);
+                response.send(http, fd)
+            }
+            Err(e) => {
+                warn!("Failed to estimate transaction fees {:?}: {:?}", req, &e);
+                let response = HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to estimate transaction fees".to_string(),
+                );
+                response.send(http, fd)
+            }
+        }
+    }
+
+    /// Handle a GET on the set of known Stacks fork tips.
+    /// The response will be synchronously written to the given fd (so use a fd that can buffer!)
+    fn handle_getforks<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &StacksChainState,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        match RPCForksData::from_db(sortdb, chainstate) {
+            Ok(forks) => {
+                let response = HttpResponseType::Forks(response_metadata, forks);
+                response.send(http, fd)
+            }
+            Err(e) => {
+                warn!("Failed to load Stacks fork tips {:?}: {:?}", req, &e);
+                let response = HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to load Stacks fork tips".to_string(),
+                );
+                response.send(http, fd)
+            }
+        }
+    }
+
+    /// Run to_do on the Clarity state at `tip`.  Confirmed state is read through the node's
+    /// read-only chain state handle, if it has one, so the query neither waits for nor holds up
+    /// block processing.  Unconfirmed state is only in `chainstate`.
+    fn with_read_only_clarity_tx<F, R>(
+        chainstate: &mut StacksChainState,
+        chainstate_reader: Option<&Mutex<ChainstateReader>>,
+        burn_dbconn: &dyn BurnStateDB,
+        tip: &StacksBlockId,
+        to_do: F,
+    ) -> R
+    where
+        F: FnOnce(&mut ClarityReadOnlyConnection) -> R,
+    {
+        let unconfirmed = match chainstate.unconfirmed_state {
+            Some(ref unconfirmed_state) => *tip == unconfirmed_state.unconfirmed_chain_tip,
+            None => false,
+        };
+        match chainstate_reader {
+            Some(reader) if !unconfirmed => reader
+                .lock()
+                .expect("FATAL: chainstate reader lock is poisoned")
+                .with_read_only_clarity_tx(burn_dbconn, tip, to_do),
+            _ => chainstate.maybe_read_only_clarity_tx(burn_dbconn, tip, to_do),
+        }
+    }
+
     /// Handle a GET on an existing account, given the current chain tip.  Optionally supplies a
-    /// MARF proof for each account detail loaded from the chain tip.
+    /// MARF proof for each account detail loaded from the chain tip, and reports the tip so the
+    /// proofs can be checked against its state root.
     fn handle_get_account_entry<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
         sortdb: &SortitionDB,
         chainstate: &mut StacksChainState,
+        chainstate_reader: Option<&Mutex<ChainstateReader>>,
         tip: &StacksBlockId,
         account: &PrincipalData,
         with_proof: bool,
     ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
 
-        let data = chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
-            clarity_tx.with_clarity_db_readonly(|clarity_db| {
-                let key = ClarityDatabase::make_key_for_account_balance(&account);
-                let block_height = clarity_db.get_current_burnchain_block_height() as u64;
-                let (balance, balance_proof) = clarity_db
-                    .get_with_proof::<STXBalance>(&key)
-                    .map(|(a, b)| (a, format!("0x{}", b.to_hex())))
-                    .unwrap_or_else(|| (STXBalance::zero(), "".into()));
-                let balance_proof = if with_proof {
-                    Some(balance_proof)
-                } else {
-                    None
-                };
-                let key = ClarityDatabase::make_key_for_account_nonce(&account);
-                let (nonce, nonce_proof) = clarity_db
-                    .get_with_proof(&key)
-                    .map(|(a, b)| (a, format!("0x{}", b.to_hex())))
-                    .unwrap_or_else(|| (0, "".into()));
-                let nonce_proof = if with_proof { Some(nonce_proof) } else { None };
-
-                let unlocked = balance.get_available_balance_at_block(block_height);
-                let (locked, unlock_height) = balance.get_locked_balance_at_block(block_height);
-
-                let balance = format!("0x{}", to_hex(&unlocked.to_be_bytes()));
-                let locked = format!("0x{}", to_hex(&locked.to_be_bytes()));
-
-                AccountEntryResponse {
-                    balance,
-                    locked,
-                    unlock_height,
-                    nonce,
-                    balance_proof,
-                    nonce_proof,
-                }
-            })
-        });
+        let data = ConversationHttp::with_read_only_clarity_tx(
+            chainstate,
+            chainstate_reader,
+            &sortdb.index_conn(),
+            tip,
+            |clarity_tx| {
+                clarity_tx.with_clarity_db_readonly(|clarity_db| {
+                    let key = ClarityDatabase::make_key_for_account_balance(&account);
+                    let block_height = clarity_db.get_current_burnchain_block_height() as u64;
+                    let (balance, balance_proof) = clarity_db
+                        .get_with_proof::<STXBalance>(&key)
+                        .map(|(a, b)| (a, format!("0x{}", b.to_hex())))
+                        .unwrap_or_else(|| (STXBalance::zero(), "".into()));
+                    let balance_proof = if with_proof {
+                        Some(balance_proof)
+                    } else {
+                        None
+                    };
+                    let key = ClarityDatabase::make_key_for_account_nonce(&account);
+                    let (nonce, nonce_proof) = clarity_db
+                        .get_with_proof(&key)
+                        .map(|(a, b)| (a, format!("0x{}", b.to_hex())))
+                        .unwrap_or_else(|| (0, "".into()));
+                    let nonce_proof = if with_proof { Some(nonce_proof) } else { None };
+
+                    let unlocked = balance.get_available_balance_at_block(block_height);
+                    let (locked, unlock_height) = balance.get_locked_balance_at_block(block_height);
+
+                    let balance = format!("0x{}", to_hex(&unlocked.to_be_bytes()));
+                    let locked = format!("0x{}", to_hex(&locked.to_be_bytes()));
+
+                    AccountEntryResponse {
+                        balance,
+                        locked,
+                        unlock_height,
+                        nonce,
+                        tip: tip.clone(),
+                        balance_proof,
+                        nonce_proof,
+                    }
+                })
+            },
+        );
 
         let response = HttpResponseType::GetAccount(response_metadata, data);
 
         response.send(http, fd).map(|_| ())
     }
 
-    /// Handle a GET on a smart contract's data map, given the current chain tip.  Optionally
-    /// supplies a MARF proof for the value.
-    fn handle_get_map_entry<W: Write>(
+    /// Check the API key a request was made with, and count it against the key's quota.  If the
+    /// request may not be served, reply with a 401 or 429 error and return false.  CORS preflight
+    /// requests never carry the key, so they are always allowed.
+    fn handle_check_api_key<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
-        sortdb: &SortitionDB,
-        chainstate: &mut StacksChainState,
-        tip: &StacksBlockId,
-        contract_addr: &StacksAddress,
-        contract_name: &ContractName,
-        map_name: &ClarityName,
-        key: &Value,
-        with_proof: bool,
-    ) -> Result<(), net_error> {
-        let response_metadata = HttpResponseMetadata::from(req);
-        let contract_identifier =
-            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
+        api_keys: &RPCApiKeys,
+    ) -> Result<bool, net_error> {
+        if let HttpRequestType::OptionsPreflight(..) = req {
+            return Ok(true);
+        }
 
-        let data = chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
-            clarity_tx.with_clarity_db_readonly(|clarity_db| {
-                let key = ClarityDatabase::make_key_for_data_map_entry(
-                    &contract_identifier,
-                    map_name,
-                    key,
+        let response_metadata = HttpResponseMetadata::from(req);
+        let api_key = req.metadata().api_key.as_ref().map(|key| key.as_str());
+        let response = match api_keys
+            .authorize(api_key, get_epoch_time_secs())
+            .map_err(net_error::DBError)?
+        {
+            RPCApiKeyCheck::Allowed => {
+                return Ok(true);
+            }
+            RPCApiKeyCheck::Missing => HttpResponseType::Unauthorized(
+                response_metadata,
+                "Missing X-Api-Key header".to_string(),
+            ),
+            RPCApiKeyCheck::Invalid => HttpResponseType::Unauthorized(
+                response_metadata,
+                "Invalid or revoked API key".to_string(),
+            ),
+            RPCApiKeyCheck::QuotaExceeded(retry_after) => HttpResponseType::Error(
+                response_metadata,
+                429,
+                format!(
+                    "API key request quota exceeded; try again in {} seconds",
+                    retry_after
+                ),
+            ),
+        };
+        response.send(http, fd).map(|_| false)
+    }
+
+    /// Check that the client may make an admin request.  If not, reply with a 403 error if the
+    /// admin endpoints are disabled, or a 401 error otherwise, and return false.
+    fn handle_check_admin_auth<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        admin_token: Option<&str>,
+        client_certs_accepted: bool,
+        client_authenticated: bool,
+    ) -> Result<bool, net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let bearer_token = req
+            .metadata()
+            .admin_token
+            .as_ref()
+            .map(|token| token.as_str());
+        let response = match authorize_admin(
+            admin_token,
+            bearer_token,
+            client_certs_accepted,
+            client_authenticated,
+        ) {
+            RPCAdminAuth::Allowed => {
+                return Ok(true);
+            }
+            RPCAdminAuth::Disabled => HttpResponseType::Forbidden(
+                response_metadata,
+                "Admin endpoints are not enabled on this node".to_string(),
+            ),
+            RPCAdminAuth::Unauthorized => HttpResponseType::Unauthorized(
+                response_metadata,
+                "Missing or invalid admin token or client certificate".to_string(),
+            ),
+        };
+        response.send(http, fd).map(|_| false)
+    }
+
+    fn handle_admin_response<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        action: &str,
+        detail: String,
+    ) -> Result<(), net_error> {
+        info!("Admin request: {}: {}", action, &detail);
+        let response_metadata = HttpResponseMetadata::from(req);
+        let response = HttpResponseType::AdminAction(
+            response_metadata,
+            RPCAdminResponse {
+                action: action.to_string(),
+                detail: detail,
+            },
+        );
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Remove a transaction from the mempool, or reply with a 404 if it isn't there
+    fn handle_admin_evict_transaction<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        mempool: &mut MemPoolDB,
+        txid: &Txid,
+    ) -> Result<(), net_error> {
+        let evicted = {
+            let mut mempool_tx = mempool.tx_begin().map_err(net_error::DBError)?;
+            let evicted = MemPoolDB::evict_tx(&mut mempool_tx, txid).map_err(net_error::DBError)?;
+            mempool_tx.commit().map_err(net_error::DBError)?;
+            evicted
+        };
+
+        if !evicted {
+            let response_metadata = HttpResponseMetadata::from(req);
+            return HttpResponseType::NotFound(
+                response_metadata,
+                format!("No such transaction in the mempool: {}", txid),
+            )
+            .send(http, fd)
+            .map(|_| ());
+        }
+
+        ConversationHttp::handle_admin_response(
+            http,
+            fd,
+            req,
+            "evict-transaction",
+            format!("evicted {}", txid),
+        )
+    }
+
+    /// Ask the chains coordinator to checkpoint the chainstate databases
+    fn handle_admin_flush_chainstate<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        admin: Option<&RPCAdminControls>,
+    ) -> Result<(), net_error> {
+        if !admin.map(|admin| admin.flush_chainstate()).unwrap_or(false) {
+            let response_metadata = HttpResponseMetadata::from(req);
+            return HttpResponseType::ServiceUnavailable(
+                response_metadata,
+                "The chains coordinator is not available".to_string(),
+            )
+            .send(http, fd)
+            .map(|_| ());
+        }
+
+        ConversationHttp::handle_admin_response(
+            http,
+            fd,
+            req,
+            "flush-chainstate",
+            "chainstate flush requested".to_string(),
+        )
+    }
+
+    /// Pause or resume the miner, or reply with a 404 if this node doesn't mine
+    fn handle_admin_set_mining<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        admin: Option<&RPCAdminControls>,
+        enabled: bool,
+    ) -> Result<(), net_error> {
+        let was_enabled = match admin.and_then(|admin| admin.set_mining_enabled(enabled)) {
+            Some(was_enabled) => was_enabled,
+            None => {
+                let response_metadata = HttpResponseMetadata::from(req);
+                return HttpResponseType::NotFound(
+                    response_metadata,
+                    "This node is not a miner".to_string(),
+                )
+                .send(http, fd)
+                .map(|_| ());
+            }
+        };
+
+        ConversationHttp::handle_admin_response(
+            http,
+            fd,
+            req,
+            "set-mining",
+            format!(
+                "mining {} (was {})",
+                if enabled { "enabled" } else { "disabled" },
+                if was_enabled { "enabled" } else { "disabled" }
+            ),
+        )
+    }
+
+    /// Charge an expensive request against the conversation's rate limit -- one token per
+    /// read-only function call, and one per transaction in a block proposal, since validating it
+    /// executes the whole block.  Returns Ok(true) if the request may proceed; otherwise, replies
+    /// with a 429 and returns Ok(false).  Other requests are not rate-limited.
+    fn handle_check_rate_limit<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        bucket: &mut TokenBucket,
+    ) -> Result<bool, net_error> {
+        let num_calls = match req {
+            HttpRequestType::CallReadOnlyFunction(..) => 1,
+            HttpRequestType::MultiCallReadOnly(_, _, ref calls, _) => calls.len() as u64,
+            HttpRequestType::ValidateBlock(_, ref block, _) => (block.txs.len() as u64).max(1),
+            _ => {
+                return Ok(true);
+            }
+        };
+
+        if bucket.try_take(num_calls, get_epoch_time_ms()) {
+            return Ok(true);
+        }
+
+        let response = HttpResponseType::Error(
+            HttpResponseMetadata::from(req),
+            429,
+            format!(
+                "Too many expensive requests; try again in {} seconds",
+                bucket.retry_after(num_calls)
+            ),
+        );
+        response.send(http, fd).map(|_| false)
+    }
+
+    /// Handle a GET on an account's nonces:  its next nonce on the given chain tip, the highest
+    /// nonce it uses in the mempool, and the nonces in between that no mempool transaction uses.
+    fn handle_get_account_nonces<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        chainstate_reader: Option<&Mutex<ChainstateReader>>,
+        mempool: &MemPoolDB,
+        tip: &StacksBlockId,
+        account: &PrincipalData,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+
+        let confirmed_nonce = ConversationHttp::with_read_only_clarity_tx(
+            chainstate,
+            chainstate_reader,
+            &sortdb.index_conn(),
+            tip,
+            |clarity_tx| {
+                clarity_tx
+                    .with_clarity_db_readonly(|clarity_db| clarity_db.get_account_nonce(account))
+            },
+        );
+
+        let mempool_nonces = match account {
+            PrincipalData::Standard(ref principal) => MemPoolDB::get_account_nonces(
+                mempool.conn(),
+                &StacksAddress::from(principal.clone()),
+                confirmed_nonce,
+            ),
+            // contracts do not send transactions
+            PrincipalData::Contract(_) => Ok(MemPoolAccountNonces {
+                confirmed_nonce,
+                mempool_max_nonce: None,
+                missing_nonces: vec![],
+            }),
+        };
+
+        let response = match mempool_nonces {
+            Ok(nonces) => HttpResponseType::GetAccountNonces(
+                response_metadata,
+                AccountNoncesResponse {
+                    confirmed_nonce: nonces.confirmed_nonce,
+                    mempool_max_nonce: nonces.mempool_max_nonce,
+                    missing_nonces: nonces.missing_nonces,
+                    tip: tip.clone(),
+                },
+            ),
+            Err(e) => {
+                warn!("Failed to load mempool nonces of {}: {:?}", account, &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    format!("Failed to load mempool nonces of {}", account),
+                )
+            }
+        };
+
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Handle a GET on the rewards an address earned in the most recent blocks it mined or
+    /// supported on the given chain tip:  what each block scheduled, and what each reward paid out
+    /// once it matured.
+    fn handle_get_miner_rewards<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &StacksChainState,
+        tip: &StacksBlockId,
+        address: &StacksAddress,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let ustx = |amount: u128| format!("0x{}", to_hex(&amount.to_be_bytes()));
+
+        let tip_info = match StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            chainstate.headers_db(),
+            tip,
+        ) {
+            Ok(Some(tip_info)) => tip_info,
+            Ok(None) => {
+                let response = HttpResponseType::NotFound(
+                    response_metadata,
+                    format!("No such Stacks block {}", tip),
                 );
-                let (value, marf_proof) = clarity_db
-                    .get_with_proof::<Value>(&key)
-                    .map(|(a, b)| (a, format!("0x{}", b.to_hex())))
-                    .unwrap_or_else(|| {
-                        test_debug!("No value for '{}' in {}", &key, tip);
-                        (Value::none(), "".into())
+                return response.send(http, fd).map(|_| ());
+            }
+            Err(e) => {
+                warn!("Failed to load Stacks block {}: {:?}", tip, &e);
+                let response = HttpResponseType::ServerError(
+                    response_metadata,
+                    format!("Failed to load Stacks block {}", tip),
+                );
+                return response.send(http, fd).map(|_| ());
+            }
+        };
+
+        let burn_dbconn = sortdb.index_conn();
+        let records_res = StacksChainState::get_miner_reward_records(
+            &mut chainstate.index_conn(),
+            &burn_dbconn,
+            &tip_info,
+            address,
+            MINER_REWARDS_MAX_BLOCKS,
+        );
+
+        // rewards that have yet to mature are estimated with the rules the next block follows
+        let pays_tx_fees =
+            StacksChainState::get_child_epoch(chainstate.headers_db(), &burn_dbconn, &tip_info)
+                >= StacksEpochId::Epoch21;
+
+        let response = match records_res {
+            Ok(records) => {
+                let mut total_matured = 0u128;
+                let mut total_pending = 0u128;
+                let mut rewards = vec![];
+                for record in records.into_iter() {
+                    let schedule = &record.schedule;
+                    match record.paid {
+                        Some(ref paid) => {
+                            total_matured += paid.total();
+                        }
+                        None => {
+                            total_pending += if schedule.miner && pays_tx_fees {
+                                schedule.coinbase
+                                    + schedule.tx_fees_anchored
+                                    + schedule.tx_fees_streamed
+                            } else {
+                                schedule.coinbase
+                            };
+                        }
+                    }
+                    rewards.push(MinerRewardEntry {
+                        index_block_hash: StacksBlockHeader::make_index_block_hash(
+                            &schedule.consensus_hash,
+                            &schedule.block_hash,
+                        ),
+                        block_height: schedule.stacks_block_height,
+                        miner: schedule.miner,
+                        burnchain_commit_burn: schedule.burnchain_commit_burn,
+                        maturity_height: record.maturity_height,
+                        matured: record.paid.is_some(),
+                        scheduled_coinbase: ustx(schedule.coinbase),
+                        scheduled_tx_fees_anchored: ustx(schedule.tx_fees_anchored),
+                        scheduled_tx_fees_streamed: ustx(schedule.tx_fees_streamed),
+                        paid_coinbase: record.paid.as_ref().map(|paid| ustx(paid.coinbase)),
+                        paid_tx_fees_anchored: record.paid.as_ref().map(|paid| {
+                            ustx(paid.tx_fees_anchored_shared + paid.tx_fees_anchored_exclusive)
+                        }),
+                        paid_tx_fees_streamed_produced: record
+                            .paid
+                            .as_ref()
+                            .map(|paid| ustx(paid.tx_fees_streamed_produced)),
+                        paid_tx_fees_streamed_confirmed: record
+                            .paid
+                            .as_ref()
+                            .map(|paid| ustx(paid.tx_fees_streamed_confirmed)),
                     });
-                let marf_proof = if with_proof {
-                    test_debug!(
-                        "Return a MARF proof of '{}' of {} bytes",
-                        &key,
-                        marf_proof.as_bytes().len()
-                    );
-                    Some(marf_proof)
-                } else {
-                    None
-                };
+                }
+                HttpResponseType::GetMinerRewards(
+                    response_metadata,
+                    MinerRewardsResponse {
+                        address: address.to_string(),
+                        rewards,
+                        total_matured: ustx(total_matured),
+                        total_pending: ustx(total_pending),
+                        tip: tip.clone(),
+                    },
+                )
+            }
+            Err(e) => {
+                warn!("Failed to load miner rewards of {}: {:?}", address, &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    format!("Failed to load miner rewards of {}", address),
+                )
+            }
+        };
 
-                let data = format!("0x{}", value.serialize());
-                MapEntryResponse { data, marf_proof }
-            })
-        });
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Handle a GET on a smart contract's data map, given the current chain tip.  Optionally
+    /// supplies a MARF proof for the value.
+    fn handle_get_map_entry<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        chainstate_reader: Option<&Mutex<ChainstateReader>>,
+        tip: &StacksBlockId,
+        contract_addr: &StacksAddress,
+        contract_name: &ContractName,
+        map_name: &ClarityName,
+        key: &Value,
+        with_proof: bool,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let contract_identifier =
+            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
+
+        let data = ConversationHttp::with_read_only_clarity_tx(
+            chainstate,
+            chainstate_reader,
+            &sortdb.index_conn(),
+            tip,
+            |clarity_tx| {
+                clarity_tx.with_clarity_db_readonly(|clarity_db| {
+                    let key = ClarityDatabase::make_key_for_data_map_entry(
+                        &contract_identifier,
+                        map_name,
+                        key,
+                    );
+                    // there is no proof for an entry that doesn't exist, so it's omitted
+                    let (value, marf_proof) = clarity_db
+                        .get_with_proof::<Value>(&key)
+                        .map(|(a, b)| (a, Some(format!("0x{}", b.to_hex()))))
+                        .unwrap_or_else(|| {
+                            test_debug!("No value for '{}' in {}", &key, tip);
+                            (Value::none(), None)
+                        });
+                    let marf_proof = if with_proof {
+                        if let Some(ref marf_proof) = marf_proof {
+                            test_debug!(
+                                "Return a MARF proof of '{}' of {} bytes",
+                                &key,
+                                marf_proof.as_bytes().len()
+                            );
+                        }
+                        marf_proof
+                    } else {
+                        None
+                    };
+
+                    let data = format!("0x{}", value.serialize_to_hex());
+                    MapEntryResponse {
+                        data,
+                        json: Some(value.to_json()),
+                        tip: tip.clone(),
+                        marf_proof,
+                    }
+                })
+            },
+        );
 
         let response = HttpResponseType::GetMapEntry(response_metadata, data);
 
@@ -846,6 +1912,7 @@ impl ConversationHttp {
         req: &HttpRequestType,
         sortdb: &SortitionDB,
         chainstate: &mut StacksChainState,
+        chainstate_reader: Option<&Mutex<ChainstateReader>>,
         tip: &StacksBlockId,
         contract_addr: &StacksAddress,
         contract_name: &ContractName,
@@ -865,21 +1932,29 @@ impl ConversationHttp {
             .map(|x| SymbolicExpression::atom_value(x.clone()))
             .collect();
 
-        let data = chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
-            clarity_tx.with_readonly_clarity_env(sender.clone(), cost_track, |env| {
-                env.execute_contract(&contract_identifier, function.as_str(), &args, true)
-            })
-        });
+        let data = ConversationHttp::with_read_only_clarity_tx(
+            chainstate,
+            chainstate_reader,
+            &sortdb.index_conn(),
+            tip,
+            |clarity_tx| {
+                clarity_tx.with_readonly_clarity_env(sender.clone(), cost_track, |env| {
+                    env.execute_contract(&contract_identifier, function.as_str(), &args, true)
+                })
+            },
+        );
 
         let response = match data {
             Ok(data) => CallReadOnlyResponse {
                 okay: true,
-                result: Some(format!("0x{}", data.serialize())),
+                result: Some(format!("0x{}", data.serialize_to_hex())),
+                json: Some(data.to_json()),
                 cause: None,
             },
             Err(e) => CallReadOnlyResponse {
                 okay: false,
                 result: None,
+                json: None,
                 cause: Some(e.to_string()),
             },
         };
@@ -888,6 +1963,87 @@ impl ConversationHttp {
         response.send(http, fd).map(|_| ())
     }
 
+    /// Handle a POST to run a batch of read-only function calls on the given chain tip.  All
+    /// calls are evaluated in the same read-only Clarity transaction, so they all see the same
+    /// state; each gets its own read-only cost budget.  Returns a MultiCallReadOnlyResponse
+    /// with the result of each call, in order.
+    fn handle_multi_readonly_function_call<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        chainstate_reader: Option<&Mutex<ChainstateReader>>,
+        tip: &StacksBlockId,
+        sender: &PrincipalData,
+        calls: &[ReadOnlyCall],
+        options: &ConnectionOptions,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+
+        let results = ConversationHttp::with_read_only_clarity_tx(
+            chainstate,
+            chainstate_reader,
+            &sortdb.index_conn(),
+            tip,
+            |clarity_tx| {
+                calls
+                    .iter()
+                    .map(|call| {
+                        let contract_identifier = QualifiedContractIdentifier::new(
+                            call.contract_address.clone().into(),
+                            call.contract_name.clone(),
+                        );
+                        let cost_track =
+                            LimitedCostTracker::new(options.read_only_call_limit.clone());
+                        let args: Vec<_> = call
+                            .arguments
+                            .iter()
+                            .map(|x| SymbolicExpression::atom_value(x.clone()))
+                            .collect();
+
+                        let data = clarity_tx.with_readonly_clarity_env(
+                            sender.clone(),
+                            cost_track,
+                            |env| {
+                                env.execute_contract(
+                                    &contract_identifier,
+                                    call.function_name.as_str(),
+                                    &args,
+                                    true,
+                                )
+                            },
+                        );
+
+                        match data {
+                            Ok(data) => CallReadOnlyResponse {
+                                okay: true,
+                                result: Some(format!("0x{}", data.serialize_to_hex())),
+                                json: Some(data.to_json()),
+                                cause: None,
+                            },
+                            Err(e) => CallReadOnlyResponse {
+                                okay: false,
+                                result: None,
+                                json: None,
+                                cause: Some(e.to_string()),
+                            },
+                        }
+                    })
+                    .collect()
+            },
+        );
+
+        let response = HttpResponseType::MultiCallReadOnly(
+            response_metadata,
+            MultiCallReadOnlyResponse {
+                tip: tip.clone(),
+                results,
+            },
+        );
+        response.send(http, fd).map(|_| ())
+    }
+
     /// Handle a GET to fetch a contract's source code, given the chain tip.  Optionally returns a
     /// MARF proof as well.
     fn handle_get_contract_src<W: Write>(
@@ -974,6 +2130,34 @@ impl ConversationHttp {
         response.send(http, fd).map(|_| ())
     }
 
+    /// Handle a GET for a transaction's receipt, as of the given chain tip.
+    fn handle_get_transaction_receipt<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        chainstate: &StacksChainState,
+        tip: &StacksBlockId,
+        txid: &Txid,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let response = match chainstate.get_transaction_receipt(tip, txid) {
+            Ok(Some(receipt)) => HttpResponseType::TransactionReceipt(response_metadata, receipt),
+            Ok(None) => HttpResponseType::NotFound(
+                response_metadata,
+                format!("No receipt for transaction {}", txid),
+            ),
+            Err(e) => {
+                warn!("Failed to load receipt for transaction {}: {:?}", txid, &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    format!("Failed to load receipt for transaction {}", txid),
+                )
+            }
+        };
+
+        response.send(http, fd).map(|_| ())
+    }
+
     /// Handle a GET unconfirmed microblock stream.  Start streaming the reply.
     /// The response's preamble (but not the block data) will be synchronously written to the fd
     /// (so use a fd that can buffer!)
@@ -1135,18 +2319,301 @@ impl ConversationHttp {
         response.send(http, fd).and_then(|_| Ok(accepted))
     }
 
-    /// Handle a microblock.  Directly submit it to the microblock store so the client can see any
-    /// rejection reasons up-front (different from how the peer network handles it).  Indicate
-    /// whether or not the microblock was accepted (and thus needs to be forwarded) in the return
-    /// value.
-    fn handle_post_microblock<W: Write>(
+    /// Handle a POST to the STX faucet.  Send the faucet amount to the recipient, unless the
+    /// recipient or caller has been sent STX too recently.  Returns the transfer if it was
+    /// accepted into the mempool (and thus needs to be forwarded).
+    fn handle_post_faucet_stx<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
-        consensus_hash: ConsensusHash,
-        block_hash: BlockHeaderHash,
+        caller: &SocketAddr,
+        sortdb: &SortitionDB,
         chainstate: &mut StacksChainState,
-        microblock: StacksMicroblock,
+        mempool: &mut MemPoolDB,
+        faucet_opt: Option<&Faucet>,
+        recipient: &PrincipalData,
+    ) -> Result<Option<StacksTransaction>, net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let faucet = match faucet_opt {
+            Some(faucet) if !chainstate.mainnet => faucet,
+            _ => {
+                let response = HttpResponseType::NotFound(
+                    response_metadata,
+                    "Faucet is not enabled".to_string(),
+                );
+                return response.send(http, fd).map(|_| None);
+            }
+        };
+
+        let tip = match chainstate.get_stacks_chain_tip(sortdb)? {
+            Some(tip) => tip,
+            None => {
+                warn!("Failed to load Stacks chain tip");
+                let response = HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to load Stacks chain tip".to_string(),
+                );
+                return response.send(http, fd).map(|_| None);
+            }
+        };
+
+        let now = get_epoch_time_secs();
+        let retry_after = faucet
+            .retry_after(recipient, &caller.ip(), now)
+            .map_err(net_error::DBError)?;
+        if retry_after > 0 {
+            let response = HttpResponseType::Error(
+                response_metadata,
+                429,
+                format!(
+                    "Too many faucet requests; try again in {} seconds",
+                    retry_after
+                ),
+            );
+            return response.send(http, fd).map(|_| None);
+        }
+
+        // the faucet may have transfers pending in the mempool already
+        let tip_index_hash =
+            StacksBlockHeader::make_index_block_hash(&tip.consensus_hash, &tip.anchored_block_hash);
+        let faucet_principal = PrincipalData::from(faucet.address().clone());
+        let chain_nonce = chainstate.with_read_only_clarity_tx(
+            &sortdb.index_conn(),
+            &tip_index_hash,
+            |clarity_tx| {
+                clarity_tx.with_clarity_db_readonly(|clarity_db| {
+                    clarity_db.get_account_nonce(&faucet_principal)
+                })
+            },
+        );
+        let mempool_nonce = MemPoolDB::get_next_nonce_for_address(mempool.conn(), faucet.address())
+            .map_err(net_error::DBError)?;
+        let nonce = chain_nonce.max(mempool_nonce);
+
+        let tx = match faucet.make_transfer(chainstate.chain_id, nonce, recipient) {
+            Ok(tx) => tx,
+            Err(e) => {
+                warn!("Failed to make faucet transfer to {}: {:?}", recipient, &e);
+                let response = HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to make faucet transfer".to_string(),
+                );
+                return response.send(http, fd).map(|_| None);
+            }
+        };
+
+        let txid = tx.txid();
+        match mempool.submit(&tip.consensus_hash, &tip.anchored_block_hash, tx.clone()) {
+            Ok(_) => {
+                debug!(
+                    "Faucet sent {} uSTX to {} in {}",
+                    faucet.amount, recipient, &txid
+                );
+                faucet
+                    .record(recipient, &caller.ip(), &txid, now)
+                    .map_err(net_error::DBError)?;
+                let response = HttpResponseType::TransactionID(response_metadata, txid);
+                response.send(http, fd).map(|_| Some(tx))
+            }
+            Err(e) => {
+                warn!("Faucet transfer {} rejected: {:?}", &txid, &e);
+                let response =
+                    HttpResponseType::BadRequestJSON(response_metadata, e.into_json(&txid));
+                response.send(http, fd).map(|_| None)
+            }
+        }
+    }
+
+    /// Handle a GET for an attachment.  Only attachments that have been declared on-chain are
+    /// served.
+    fn handle_get_attachment<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        atlasdb_opt: Option<&AtlasDB>,
+        content_hash: &Hash160,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let atlasdb = match atlasdb_opt {
+            Some(atlasdb) => atlasdb,
+            None => {
+                let response = HttpResponseType::NotFound(
+                    response_metadata,
+                    "Attachments are not enabled".to_string(),
+                );
+                return response.send(http, fd);
+            }
+        };
+
+        let response = match atlasdb.get_attachment(content_hash) {
+            Ok(Some(attachment)) => HttpResponseType::GetAttachment(
+                response_metadata,
+                GetAttachmentResponse { attachment },
+            ),
+            Ok(None) => HttpResponseType::NotFound(
+                response_metadata,
+                format!("No such attachment: {}", content_hash),
+            ),
+            Err(e) => {
+                warn!("Failed to load attachment {}: {:?}", content_hash, &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to load attachment".to_string(),
+                )
+            }
+        };
+        response.send(http, fd)
+    }
+
+    /// Handle a POSTed attachment.  If it has been declared on-chain, we store it and serve it;
+    /// otherwise, we hold on to it for a while in case the declaration gets mined.
+    fn handle_post_attachment<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        atlasdb_opt: Option<&AtlasDB>,
+        attachment: &Attachment,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let atlasdb = match atlasdb_opt {
+            Some(atlasdb) => atlasdb,
+            None => {
+                let response = HttpResponseType::NotFound(
+                    response_metadata,
+                    "Attachments are not enabled".to_string(),
+                );
+                return response.send(http, fd);
+            }
+        };
+
+        let max_size = atlasdb.config().attachments_max_size;
+        if (attachment.content.len() as u64) > max_size {
+            let response = HttpResponseType::BadRequest(
+                response_metadata,
+                format!("Attachment is too big: at most {} bytes allowed", max_size),
+            );
+            return response.send(http, fd);
+        }
+
+        let content_hash = attachment.hash();
+        let now = get_epoch_time_secs();
+        let stored = atlasdb
+            .has_attachment_instance(&content_hash)
+            .and_then(|declared| {
+                if declared {
+                    atlasdb
+                        .insert_instantiated_attachment(attachment, now)
+                        .map(|_| true)
+                } else {
+                    atlasdb.insert_uninstantiated_attachment(attachment, now)
+                }
+            });
+
+        let response = match stored {
+            Ok(true) => {
+                debug!("Accepted attachment {}", &content_hash);
+                HttpResponseType::AttachmentHash(response_metadata, content_hash)
+            }
+            Ok(false) => HttpResponseType::ServiceUnavailable(
+                response_metadata,
+                "Too many undeclared attachments; try again later".to_string(),
+            ),
+            Err(e) => {
+                warn!("Failed to store attachment {}: {:?}", &content_hash, &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to store attachment".to_string(),
+                )
+            }
+        };
+        response.send(http, fd)
+    }
+
+    /// Handle a request to upgrade to a WebSocket event stream.
+    /// Returns true if we agreed to the upgrade.
+    fn handle_websocket_upgrade<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        websocket_enabled: bool,
+        client_key: &str,
+    ) -> Result<bool, net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        if !websocket_enabled {
+            let response = HttpResponseType::ServiceUnavailable(
+                response_metadata,
+                "WebSocket events are not available".to_string(),
+            );
+            return response.send(http, fd).and_then(|_| Ok(false));
+        }
+
+        let response =
+            HttpResponseType::WebSocketUpgrade(response_metadata, websocket_accept_key(client_key));
+        response.send(http, fd).and_then(|_| Ok(true))
+    }
+
+    /// Handle a block proposal.  Fully validate it against the given parent block, but don't store
+    /// it.  The response will be synchronously written to the given fd.
+    fn handle_post_validate_block<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        parent_block_id: &StacksBlockId,
+        sortdb: &SortitionDB,
+        chainstate: &StacksChainState,
+        block: &StacksBlock,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let parent = match StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            chainstate.headers_db(),
+            parent_block_id,
+        ) {
+            Ok(Some(parent)) => parent,
+            Ok(None) => {
+                let response = HttpResponseType::NotFound(
+                    response_metadata,
+                    format!("No such parent block {}", parent_block_id.to_hex()),
+                );
+                return response.send(http, fd);
+            }
+            Err(e) => {
+                warn!("Failed to load parent block {:?}: {:?}", req, &e);
+                let response = HttpResponseType::ServerError(
+                    response_metadata,
+                    format!("Failed to load parent block {}", parent_block_id.to_hex()),
+                );
+                return response.send(http, fd);
+            }
+        };
+
+        match RPCBlockValidationData::from_db(sortdb, chainstate, &parent, block) {
+            Ok(validation) => {
+                let response = HttpResponseType::BlockValidation(response_metadata, validation);
+                response.send(http, fd)
+            }
+            Err(e) => {
+                warn!("Failed to validate block {:?}: {:?}", req, &e);
+                let response = HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to validate block".to_string(),
+                );
+                response.send(http, fd)
+            }
+        }
+    }
+
+    /// Handle a microblock.  Directly submit it to the microblock store so the client can see any
+    /// rejection reasons up-front (different from how the peer network handles it).  Indicate
+    /// whether or not the microblock was accepted (and thus needs to be forwarded) in the return
+    /// value.
+    fn handle_post_microblock<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        consensus_hash: ConsensusHash,
+        block_hash: BlockHeaderHash,
+        chainstate: &mut StacksChainState,
+        microblock: StacksMicroblock,
     ) -> Result<bool, net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
         let (response, accepted) = match chainstate.preprocess_streamed_microblock(
@@ -1192,7 +2659,7 @@ impl ConversationHttp {
     /// peer network (like a transaction or a block or microblock)
     pub fn handle_request(
         &mut self,
-        req: HttpRequestType,
+        mut req: HttpRequestType,
         chain_view: &BurnchainView,
         peers: &PeerMap,
         sortdb: &SortitionDB,
@@ -1203,10 +2670,52 @@ impl ConversationHttp {
     ) -> Result<Option<StacksMessageType>, net_error> {
         monitoring::increment_rpc_calls_counter();
 
+        if self.max_request_count > 0 && self.total_request_count >= self.max_request_count {
+            // this is the last request we'll serve on this connection
+            req.metadata_mut().keep_alive = false;
+        }
+
         let mut reply = self.connection.make_relay_handle(self.conn_id)?;
         let keep_alive = req.metadata().keep_alive;
         let mut ret = None;
 
+        if req.is_admin() {
+            // admin requests are authorized on their own, and don't count against API keys
+            if !ConversationHttp::handle_check_admin_auth(
+                &mut self.connection.protocol,
+                &mut reply,
+                &req,
+                self.admin_token.as_ref().map(|token| token.as_str()),
+                self.admin_client_certs,
+                self.tls_client_authenticated,
+            )? {
+                self.reply_streams.push_back((reply, None, keep_alive));
+                return Ok(None);
+            }
+        } else if let Some(api_keys) = handler_opts.api_keys {
+            if !ConversationHttp::handle_check_api_key(
+                &mut self.connection.protocol,
+                &mut reply,
+                &req,
+                api_keys,
+            )? {
+                self.reply_streams.push_back((reply, None, keep_alive));
+                return Ok(None);
+            }
+        }
+
+        if let Some(ref mut bucket) = self.read_only_call_bucket {
+            if !ConversationHttp::handle_check_rate_limit(
+                &mut self.connection.protocol,
+                &mut reply,
+                &req,
+                bucket,
+            )? {
+                self.reply_streams.push_back((reply, None, keep_alive));
+                return Ok(None);
+            }
+        }
+
         let stream_opt = match req {
             HttpRequestType::GetInfo(ref _md) => {
                 ConversationHttp::handle_getinfo(
@@ -1242,7 +2751,17 @@ impl ConversationHttp {
                 }
                 None
             }
-            HttpRequestType::GetNeighbors(ref _md) => {
+            HttpRequestType::GetBurnEta(ref _md, ref target_height) => {
+                ConversationHttp::handle_getburneta(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    sortdb,
+                    *target_height,
+                )?;
+                None
+            }
+            HttpRequestType::GetNeighbors(ref _md, ref query) => {
                 ConversationHttp::handle_getneighbors(
                     &mut self.connection.protocol,
                     &mut reply,
@@ -1251,6 +2770,7 @@ impl ConversationHttp {
                     chain_view,
                     peers,
                     peerdb,
+                    query,
                 )?;
                 None
             }
@@ -1308,19 +2828,167 @@ impl ConversationHttp {
                         &req,
                         sortdb,
                         chainstate,
+                        handler_opts.chainstate_reader,
+                        &tip,
+                        principal,
+                        *with_proof,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetMapEntry(
+                ref _md,
+                ref contract_addr,
+                ref contract_name,
+                ref map_name,
+                ref key,
+                ref tip_opt,
+                ref with_proof,
+            ) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_opt.as_ref(),
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_map_entry(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        handler_opts.chainstate_reader,
+                        &tip,
+                        contract_addr,
+                        contract_name,
+                        map_name,
+                        key,
+                        *with_proof,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetTransferCost(ref _md) => {
+                ConversationHttp::handle_token_transfer_cost(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                )?;
+                None
+            }
+            HttpRequestType::GetFeeEstimate(ref _md, ref len) => {
+                ConversationHttp::handle_get_fee_estimate(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    sortdb,
+                    mempool,
+                    *len,
+                )?;
+                None
+            }
+            HttpRequestType::GetForks(ref _md) => {
+                ConversationHttp::handle_getforks(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    sortdb,
+                    chainstate,
+                )?;
+                None
+            }
+            HttpRequestType::GetContractABI(
+                ref _md,
+                ref contract_addr,
+                ref contract_name,
+                ref tip_opt,
+            ) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_opt.as_ref(),
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_contract_abi(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        contract_addr,
+                        contract_name,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::CallReadOnlyFunction(
+                ref _md,
+                ref ctrct_addr,
+                ref ctrct_name,
+                ref as_sender,
+                ref func_name,
+                ref args,
+                ref tip_opt,
+            ) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_opt.as_ref(),
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_readonly_function_call(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        handler_opts.chainstate_reader,
+                        &tip,
+                        ctrct_addr,
+                        ctrct_name,
+                        func_name,
+                        as_sender,
+                        args,
+                        &self.connection.options,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::MultiCallReadOnly(ref _md, ref as_sender, ref calls, ref tip_opt) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_opt.as_ref(),
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_multi_readonly_function_call(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        handler_opts.chainstate_reader,
                         &tip,
-                        principal,
-                        *with_proof,
+                        as_sender,
+                        calls,
+                        &self.connection.options,
                     )?;
                 }
                 None
             }
-            HttpRequestType::GetMapEntry(
+            HttpRequestType::GetContractSrc(
                 ref _md,
                 ref contract_addr,
                 ref contract_name,
-                ref map_name,
-                ref key,
                 ref tip_opt,
                 ref with_proof,
             ) => {
@@ -1332,7 +3000,7 @@ impl ConversationHttp {
                     sortdb,
                     chainstate,
                 )? {
-                    ConversationHttp::handle_get_map_entry(
+                    ConversationHttp::handle_get_contract_src(
                         &mut self.connection.protocol,
                         &mut reply,
                         &req,
@@ -1341,27 +3009,12 @@ impl ConversationHttp {
                         &tip,
                         contract_addr,
                         contract_name,
-                        map_name,
-                        key,
                         *with_proof,
                     )?;
                 }
                 None
             }
-            HttpRequestType::GetTransferCost(ref _md) => {
-                ConversationHttp::handle_token_transfer_cost(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                )?;
-                None
-            }
-            HttpRequestType::GetContractABI(
-                ref _md,
-                ref contract_addr,
-                ref contract_name,
-                ref tip_opt,
-            ) => {
+            HttpRequestType::GetAccountNonces(ref _md, ref principal, ref tip_opt) => {
                 if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
                     &mut self.connection.protocol,
                     &mut reply,
@@ -1370,28 +3023,21 @@ impl ConversationHttp {
                     sortdb,
                     chainstate,
                 )? {
-                    ConversationHttp::handle_get_contract_abi(
+                    ConversationHttp::handle_get_account_nonces(
                         &mut self.connection.protocol,
                         &mut reply,
                         &req,
                         sortdb,
                         chainstate,
+                        handler_opts.chainstate_reader,
+                        mempool,
                         &tip,
-                        contract_addr,
-                        contract_name,
+                        principal,
                     )?;
                 }
                 None
             }
-            HttpRequestType::CallReadOnlyFunction(
-                ref _md,
-                ref ctrct_addr,
-                ref ctrct_name,
-                ref as_sender,
-                ref func_name,
-                ref args,
-                ref tip_opt,
-            ) => {
+            HttpRequestType::GetMinerRewards(ref _md, ref address, ref tip_opt) => {
                 if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
                     &mut self.connection.protocol,
                     &mut reply,
@@ -1400,30 +3046,19 @@ impl ConversationHttp {
                     sortdb,
                     chainstate,
                 )? {
-                    ConversationHttp::handle_readonly_function_call(
+                    ConversationHttp::handle_get_miner_rewards(
                         &mut self.connection.protocol,
                         &mut reply,
                         &req,
                         sortdb,
                         chainstate,
                         &tip,
-                        ctrct_addr,
-                        ctrct_name,
-                        func_name,
-                        as_sender,
-                        args,
-                        &self.connection.options,
+                        address,
                     )?;
                 }
                 None
             }
-            HttpRequestType::GetContractSrc(
-                ref _md,
-                ref contract_addr,
-                ref contract_name,
-                ref tip_opt,
-                ref with_proof,
-            ) => {
+            HttpRequestType::GetTransactionReceipt(ref _md, ref txid, ref tip_opt) => {
                 if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
                     &mut self.connection.protocol,
                     &mut reply,
@@ -1432,16 +3067,13 @@ impl ConversationHttp {
                     sortdb,
                     chainstate,
                 )? {
-                    ConversationHttp::handle_get_contract_src(
+                    ConversationHttp::handle_get_transaction_receipt(
                         &mut self.connection.protocol,
                         &mut reply,
                         &req,
-                        sortdb,
                         chainstate,
                         &tip,
-                        contract_addr,
-                        contract_name,
-                        *with_proof,
+                        txid,
                     )?;
                 }
                 None
@@ -1475,6 +3107,27 @@ impl ConversationHttp {
                 }
                 None
             }
+            HttpRequestType::ValidateBlock(ref _md, ref block, ref tip_opt) => {
+                if let Some(parent_block_id) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_opt.as_ref(),
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_post_validate_block(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        &parent_block_id,
+                        sortdb,
+                        chainstate,
+                        block,
+                    )?;
+                }
+                None
+            }
             HttpRequestType::PostMicroblock(ref _md, ref mblock, ref tip_opt) => {
                 if let Some((consensus_hash, block_hash)) =
                     ConversationHttp::handle_load_stacks_chain_tip_hashes(
@@ -1507,6 +3160,55 @@ impl ConversationHttp {
                 }
                 None
             }
+            HttpRequestType::PostFaucetStx(ref _md, ref recipient) => {
+                if let Some(tx) = ConversationHttp::handle_post_faucet_stx(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    &self.peer_addr,
+                    sortdb,
+                    chainstate,
+                    mempool,
+                    handler_opts.faucet,
+                    recipient,
+                )? {
+                    // forward to peer network
+                    ret = Some(StacksMessageType::Transaction(tx));
+                }
+                None
+            }
+            HttpRequestType::GetAttachment(ref _md, ref content_hash) => {
+                ConversationHttp::handle_get_attachment(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    handler_opts.atlasdb,
+                    content_hash,
+                )?;
+                None
+            }
+            HttpRequestType::PostAttachment(ref _md, ref attachment) => {
+                ConversationHttp::handle_post_attachment(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    handler_opts.atlasdb,
+                    attachment,
+                )?;
+                None
+            }
+            HttpRequestType::WebSocketUpgrade(ref _md, ref client_key) => {
+                if ConversationHttp::handle_websocket_upgrade(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    self.websocket_enabled,
+                    client_key,
+                )? {
+                    self.websocket_upgraded = true;
+                }
+                None
+            }
             HttpRequestType::OptionsPreflight(ref _md, ref _path) => {
                 let response_metadata = HttpResponseMetadata::from(&req);
                 let response = HttpResponseType::OptionsPreflight(response_metadata);
@@ -1515,6 +3217,51 @@ impl ConversationHttp {
                     .map(|_| ())?;
                 None
             }
+            HttpRequestType::AdminBanPeer(ref _md, ref addrbytes, ref port, ref duration) => {
+                // the peer network carries out the ban once the HTTP server has run
+                self.admin_bans.push((addrbytes.clone(), *port, *duration));
+                ConversationHttp::handle_admin_response(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    "ban-peer",
+                    format!(
+                        "banning {} for {} seconds",
+                        addrbytes.to_socketaddr(*port),
+                        duration
+                    ),
+                )?;
+                None
+            }
+            HttpRequestType::AdminEvictTransaction(ref _md, ref txid) => {
+                ConversationHttp::handle_admin_evict_transaction(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    mempool,
+                    txid,
+                )?;
+                None
+            }
+            HttpRequestType::AdminFlushChainstate(ref _md) => {
+                ConversationHttp::handle_admin_flush_chainstate(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    handler_opts.admin,
+                )?;
+                None
+            }
+            HttpRequestType::AdminSetMining(ref _md, ref enabled) => {
+                ConversationHttp::handle_admin_set_mining(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    handler_opts.admin,
+                    *enabled,
+                )?;
+                None
+            }
             HttpRequestType::ClientError(ref _md, ref err) => {
                 let response_metadata = HttpResponseMetadata::from(&req);
                 let response = match err {
@@ -1534,6 +3281,7 @@ impl ConversationHttp {
             }
         };
 
+        let stream_encoding = self.connection.protocol.take_stream_encoding();
         match stream_opt {
             None => {
                 self.reply_streams.push_back((reply, None, keep_alive));
@@ -1542,7 +3290,10 @@ impl ConversationHttp {
                 self.reply_streams.push_back((
                     reply,
                     Some((
-                        HttpChunkedTransferWriterState::new(STREAM_CHUNK_SIZE as usize),
+                        HttpChunkedTransferWriterState::new_encoded(
+                            STREAM_CHUNK_SIZE as usize,
+                            stream_encoding,
+                        ),
                         stream,
                     )),
                     keep_alive,
@@ -1788,6 +3539,11 @@ impl ConversationHttp {
             return Ok(vec![]);
         }
 
+        // if we're switching to a WebSocket, then there are no more HTTP requests.
+        if self.websocket_upgraded {
+            return Ok(vec![]);
+        }
+
         // handle in-bound HTTP request(s)
         let num_inbound = self.connection.inbox_len();
         let mut ret = vec![];
@@ -1806,6 +3562,15 @@ impl ConversationHttp {
                     // new request
                     self.total_request_count += 1;
                     self.last_request_timestamp = get_epoch_time_secs();
+
+                    let audit_path = if self.audit_requests {
+                        Some(req.request_path())
+                    } else {
+                        None
+                    };
+                    self.connection.protocol.take_last_response_code();
+                    let start_time = get_epoch_time_ms();
+
                     let msg_opt = self.handle_request(
                         req,
                         chain_view,
@@ -1816,9 +3581,28 @@ impl ConversationHttp {
                         mempool,
                         handler_args,
                     )?;
+
+                    let status_code = self.connection.protocol.take_last_response_code();
+                    if let Some(path) = audit_path {
+                        let (endpoint, params) = match path.find('?') {
+                            Some(i) => (path[..i].to_string(), path[(i + 1)..].to_string()),
+                            None => (path, "".to_string()),
+                        };
+                        self.audit_records.push(RPCAuditRecord {
+                            timestamp: self.last_request_timestamp,
+                            endpoint: endpoint,
+                            params: params,
+                            caller: self.peer_addr.clone(),
+                            status_code: status_code.unwrap_or(0),
+                            latency_ms: (get_epoch_time_ms().saturating_sub(start_time)) as u64,
+                        });
+                    }
                     if let Some(msg) = msg_opt {
                         ret.push(msg);
                     }
+                    if self.websocket_upgraded {
+                        break;
+                    }
                 }
                 StacksHttpMessage::Response(resp) => {
                     // Is there someone else waiting for this message?  If so, pass it along.
@@ -1911,9 +3695,48 @@ impl ConversationHttp {
         )
     }
 
+    /// Make a new burn block ETA request to this endpoint
+    pub fn new_getburneta(&self, target_height: u64) -> HttpRequestType {
+        HttpRequestType::GetBurnEta(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            target_height,
+        )
+    }
+
+    /// Make a new fee estimate request to this endpoint
+    pub fn new_get_fee_estimate(&self, len: u64) -> HttpRequestType {
+        HttpRequestType::GetFeeEstimate(HttpRequestMetadata::from_host(self.peer_host.clone()), len)
+    }
+
+    /// Make a new request to validate a proposed block built on the given parent
+    pub fn new_post_validate_block(
+        &self,
+        block: StacksBlock,
+        parent_block_id: Option<StacksBlockId>,
+    ) -> HttpRequestType {
+        HttpRequestType::ValidateBlock(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            block,
+            parent_block_id,
+        )
+    }
+
+    /// Make a new getforks request to this endpoint
+    pub fn new_getforks(&self) -> HttpRequestType {
+        HttpRequestType::GetForks(HttpRequestMetadata::from_host(self.peer_host.clone()))
+    }
+
     /// Make a new getneighbors request to this endpoint
     pub fn new_getneighbors(&self) -> HttpRequestType {
-        HttpRequestType::GetNeighbors(HttpRequestMetadata::from_host(self.peer_host.clone()))
+        self.new_getneighbors_query(NeighborsQuery::default())
+    }
+
+    /// Make a new getneighbors request to this endpoint, with filters and pagination
+    pub fn new_getneighbors_query(&self, query: NeighborsQuery) -> HttpRequestType {
+        HttpRequestType::GetNeighbors(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            query,
+        )
     }
 
     /// Make a new getblock request to this endpoint
@@ -1964,6 +3787,19 @@ impl ConversationHttp {
         HttpRequestType::PostTransaction(HttpRequestMetadata::from_host(self.peer_host.clone()), tx)
     }
 
+    /// Make a new request for a transaction's receipt
+    pub fn new_get_transaction_receipt(
+        &self,
+        txid: Txid,
+        tip_opt: Option<StacksBlockId>,
+    ) -> HttpRequestType {
+        HttpRequestType::GetTransactionReceipt(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            txid,
+            tip_opt,
+        )
+    }
+
     /// Make a new post-microblock request
     pub fn new_post_microblock(
         &self,
@@ -1977,6 +3813,77 @@ impl ConversationHttp {
         )
     }
 
+    /// Make a new request for STX from the faucet
+    pub fn new_post_faucet_stx(&self, recipient: PrincipalData) -> HttpRequestType {
+        HttpRequestType::PostFaucetStx(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            recipient,
+        )
+    }
+
+    /// Make a new request to ban a peer.  Admin requests need `admin_token` set in their
+    /// metadata, unless the client authenticates with a TLS certificate.
+    pub fn new_admin_ban_peer(
+        &self,
+        addrbytes: PeerAddress,
+        port: u16,
+        duration: u64,
+    ) -> HttpRequestType {
+        HttpRequestType::AdminBanPeer(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            addrbytes,
+            port,
+            duration,
+        )
+    }
+
+    /// Make a new request to evict a transaction from the mempool
+    pub fn new_admin_evict_transaction(&self, txid: Txid) -> HttpRequestType {
+        HttpRequestType::AdminEvictTransaction(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            txid,
+        )
+    }
+
+    /// Make a new request to checkpoint the chainstate
+    pub fn new_admin_flush_chainstate(&self) -> HttpRequestType {
+        HttpRequestType::AdminFlushChainstate(HttpRequestMetadata::from_host(
+            self.peer_host.clone(),
+        ))
+    }
+
+    /// Make a new request to pause or resume the miner
+    pub fn new_admin_set_mining(&self, enabled: bool) -> HttpRequestType {
+        HttpRequestType::AdminSetMining(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            enabled,
+        )
+    }
+
+    /// Make a new request for an attachment
+    pub fn new_get_attachment(&self, content_hash: Hash160) -> HttpRequestType {
+        HttpRequestType::GetAttachment(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            content_hash,
+        )
+    }
+
+    /// Make a new attachment upload
+    pub fn new_post_attachment(&self, attachment: Attachment) -> HttpRequestType {
+        HttpRequestType::PostAttachment(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            attachment,
+        )
+    }
+
+    /// Make a new request to upgrade to a WebSocket event stream
+    pub fn new_websocket_upgrade(&self, client_key: String) -> HttpRequestType {
+        HttpRequestType::WebSocketUpgrade(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            client_key,
+        )
+    }
+
     /// Make a new request for an account
     pub fn new_getaccount(
         &self,
@@ -2065,6 +3972,21 @@ impl ConversationHttp {
             tip_opt,
         )
     }
+
+    /// Make a new request to run a batch of read-only functions
+    pub fn new_multicallreadonly(
+        &self,
+        sender: PrincipalData,
+        calls: Vec<ReadOnlyCall>,
+        tip_opt: Option<StacksBlockId>,
+    ) -> HttpRequestType {
+        HttpRequestType::MultiCallReadOnly(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            sender,
+            calls,
+            tip_opt,
+        )
+    }
 }
 
 #[cfg(test)]
@@ -2557,6 +4479,39 @@ mod test {
         assert!(check_result(&req, &resp, &mut peer_1, &mut peer_2));
     }
 
+    #[test]
+    fn test_token_bucket() {
+        let mut bucket = TokenBucket::new(10, 2, 1000);
+
+        // starts full
+        assert!(bucket.try_take(8, 1000));
+        assert!(!bucket.try_take(3, 1000));
+        assert_eq!(bucket.retry_after(3), 1);
+        assert!(bucket.try_take(2, 1000));
+        assert!(!bucket.try_take(1, 1000));
+
+        // refills at 2 tokens a second, in fractions of a token
+        assert!(!bucket.try_take(1, 1400));
+        assert!(bucket.try_take(1, 1500));
+        assert!(!bucket.try_take(4, 2500));
+        assert_eq!(bucket.retry_after(4), 1);
+        assert!(bucket.try_take(4, 3500));
+
+        // never holds more than its capacity
+        assert!(bucket.try_take(10, 100_000));
+        assert!(!bucket.try_take(1, 100_000));
+
+        // asking for more than the capacity needs a full bucket
+        assert!(!bucket.try_take(20, 104_000));
+        assert!(bucket.try_take(20, 105_000));
+
+        // a bucket that never refills
+        let mut bucket = TokenBucket::new(1, 0, 0);
+        assert!(bucket.try_take(1, 0));
+        assert!(!bucket.try_take(1, 1_000_000));
+        assert_eq!(bucket.retry_after(1), u64::max_value());
+    }
+
     #[test]
     #[ignore]
     fn test_rpc_getinfo() {
@@ -2602,10 +4557,59 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_getpoxinfo() {
-        let pox_server_info = RefCell::new(None);
+    fn test_rpc_getpoxinfo() {
+        let pox_server_info = RefCell::new(None);
+        test_rpc(
+            "test_rpc_getpoxinfo",
+            40000,
+            40001,
+            50000,
+            50001,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let mut sortdb = peer_server.sortdb.as_mut().unwrap();
+                let chainstate = &mut peer_server.stacks_node.as_mut().unwrap().chainstate;
+                let stacks_block_id = {
+                    let tip = chainstate.get_stacks_chain_tip(sortdb).unwrap().unwrap();
+                    StacksBlockHeader::make_index_block_hash(
+                        &tip.consensus_hash,
+                        &tip.anchored_block_hash,
+                    )
+                };
+                let pox_info = RPCPoxInfoData::from_db(
+                    &mut sortdb,
+                    chainstate,
+                    &stacks_block_id,
+                    &ConnectionOptions::default(),
+                )
+                .unwrap();
+                *pox_server_info.borrow_mut() = Some(pox_info);
+                convo_client.new_getpoxinfo(None)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::PoxInfo(response_md, pox_data) => {
+                        assert_eq!(Some((*pox_data).clone()), *pox_server_info.borrow());
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_getburneta() {
+        let burn_eta_server_info = RefCell::new(None);
         test_rpc(
-            "test_rpc_getpoxinfo",
+            "test_rpc_getburneta",
             40000,
             40001,
             50000,
@@ -2614,30 +4618,23 @@ mod test {
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let mut sortdb = peer_server.sortdb.as_mut().unwrap();
-                let chainstate = &mut peer_server.stacks_node.as_mut().unwrap().chainstate;
-                let stacks_block_id = {
-                    let tip = chainstate.get_stacks_chain_tip(sortdb).unwrap().unwrap();
-                    StacksBlockHeader::make_index_block_hash(
-                        &tip.consensus_hash,
-                        &tip.anchored_block_hash,
-                    )
-                };
-                let pox_info = RPCPoxInfoData::from_db(
-                    &mut sortdb,
-                    chainstate,
-                    &stacks_block_id,
-                    &ConnectionOptions::default(),
-                )
-                .unwrap();
-                *pox_server_info.borrow_mut() = Some(pox_info);
-                convo_client.new_getpoxinfo(None)
+                let sortdb = peer_server.sortdb.as_ref().unwrap();
+                let tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn()).unwrap();
+                let burn_eta =
+                    RPCBurnEtaData::from_db(sortdb, tip.block_height + 10, get_epoch_time_secs())
+                        .unwrap();
+                *burn_eta_server_info.borrow_mut() = Some(burn_eta);
+                convo_client.new_getburneta(tip.block_height + 10)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::PoxInfo(response_md, pox_data) => {
-                        assert_eq!(Some((*pox_data).clone()), *pox_server_info.borrow());
+                    HttpResponseType::BurnEta(response_md, burn_eta) => {
+                        let expected = burn_eta_server_info.borrow().clone().unwrap();
+                        assert_eq!(burn_eta.blocks_remaining, 10);
+                        assert_eq!(burn_eta.burn_block_height, expected.burn_block_height);
+                        assert_eq!(burn_eta.estimated_seconds, expected.estimated_seconds);
+                        assert!(burn_eta.estimated_time >= expected.estimated_time);
                         true
                     }
                     _ => {
@@ -2668,6 +4665,44 @@ mod test {
                     HttpResponseType::Neighbors(response_md, neighbor_info) => {
                         assert_eq!(neighbor_info.sample.len(), 1);
                         assert_eq!(neighbor_info.sample[0].port, peer_client.config.server_port); // we see ourselves as the neighbor
+                        assert!(neighbor_info.sample[0].last_contact_time.is_some());
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_getneighbors_query() {
+        test_rpc(
+            "test_rpc_getneighbors_query",
+            40250,
+            40251,
+            50250,
+            50251,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_getneighbors_query(NeighborsQuery {
+                    state: Some(NeighborState::Banned),
+                    address: Some(PeerAddress::from_ipv4(127, 0, 0, 1)),
+                    ..NeighborsQuery::default()
+                })
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                match http_response {
+                    HttpResponseType::Neighbors(response_md, neighbor_info) => {
+                        // nobody is banned
+                        assert_eq!(neighbor_info.sample.len(), 0);
+                        assert_eq!(neighbor_info.inbound.len(), 0);
+                        assert_eq!(neighbor_info.outbound.len(), 0);
                         true
                     }
                     _ => {
@@ -3312,6 +5347,15 @@ mod test {
                         assert_eq!(data.nonce, 4);
                         let balance = u128::from_str_radix(&data.balance[2..], 16).unwrap();
                         assert_eq!(balance, 1000000000 - 123);
+                        assert_eq!(
+                            data.tip,
+                            peer_client
+                                .chainstate()
+                                .unconfirmed_state
+                                .as_ref()
+                                .unwrap()
+                                .unconfirmed_chain_tip
+                        );
                         true
                     }
                     _ => {
@@ -3350,7 +5394,7 @@ mod test {
                             .unwrap(),
                     ),
                     None,
-                    false,
+                    true,
                 )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
@@ -3365,6 +5409,11 @@ mod test {
                             ))
                             .unwrap()
                         );
+                        assert_eq!(
+                            Value::try_from_json(data.json.as_ref().unwrap()).unwrap(),
+                            Value::try_deserialize_hex_untyped(&data.data).unwrap()
+                        );
+                        assert!(data.marf_proof.is_some());
                         true
                     }
                     _ => {
@@ -3425,6 +5474,15 @@ mod test {
                             ))
                             .unwrap()
                         );
+                        assert_eq!(
+                            data.tip,
+                            peer_client
+                                .chainstate()
+                                .unconfirmed_state
+                                .as_ref()
+                                .unwrap()
+                                .unconfirmed_chain_tip
+                        );
                         true
                     }
                     _ => {
@@ -3609,4 +5667,360 @@ mod test {
             },
         );
     }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_multi_call_read_only() {
+        test_rpc(
+            "test_rpc_multi_call_read_only",
+            40240,
+            40241,
+            50240,
+            50241,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let unconfirmed_tip = peer_client
+                    .chainstate()
+                    .unconfirmed_state
+                    .as_ref()
+                    .unwrap()
+                    .unconfirmed_chain_tip
+                    .clone();
+                let addr = StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                    .unwrap();
+                let calls = vec![
+                    ReadOnlyCall {
+                        contract_address: addr.clone(),
+                        contract_name: "hello-world-unconfirmed".try_into().unwrap(),
+                        function_name: "ro-test".try_into().unwrap(),
+                        arguments: vec![],
+                    },
+                    ReadOnlyCall {
+                        contract_address: addr.clone(),
+                        contract_name: "hello-world".try_into().unwrap(),
+                        function_name: "get-bar".try_into().unwrap(),
+                        arguments: vec![],
+                    },
+                    ReadOnlyCall {
+                        contract_address: addr.clone(),
+                        contract_name: "no-such-contract".try_into().unwrap(),
+                        function_name: "ro-test".try_into().unwrap(),
+                        arguments: vec![],
+                    },
+                ];
+                convo_client.new_multicallreadonly(
+                    addr.to_account_principal(),
+                    calls,
+                    Some(unconfirmed_tip),
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let unconfirmed_tip = peer_server
+                    .chainstate()
+                    .unconfirmed_state
+                    .as_ref()
+                    .unwrap()
+                    .unconfirmed_chain_tip
+                    .clone();
+                match http_response {
+                    HttpResponseType::MultiCallReadOnly(response_md, data) => {
+                        assert_eq!(data.tip, unconfirmed_tip);
+                        assert_eq!(data.results.len(), 3);
+
+                        assert!(data.results[0].okay);
+                        assert_eq!(
+                            Value::try_deserialize_hex_untyped(
+                                &data.results[0].result.clone().unwrap()
+                            )
+                            .unwrap(),
+                            Value::okay(Value::Int(1)).unwrap()
+                        );
+
+                        assert!(data.results[1].okay);
+                        assert_eq!(
+                            Value::try_deserialize_hex_untyped(
+                                &data.results[1].result.clone().unwrap()
+                            )
+                            .unwrap(),
+                            Value::okay(Value::Int(0)).unwrap()
+                        );
+
+                        // one failed call does not fail the batch
+                        assert!(!data.results[2].okay);
+                        assert!(data.results[2]
+                            .cause
+                            .clone()
+                            .unwrap()
+                            .find("NoSuchContract")
+                            .is_some());
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_get_transaction_receipt() {
+        let contract_txid_cell = RefCell::new(None);
+
+        test_rpc(
+            "test_rpc_get_transaction_receipt",
+            40200,
+            40201,
+            50200,
+            50201,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                // the contract is the second transaction in the tip block
+                let sortdb = peer_server.sortdb.take().unwrap();
+                let tip = peer_server
+                    .chainstate()
+                    .get_stacks_chain_tip(&sortdb)
+                    .unwrap()
+                    .unwrap();
+                peer_server.sortdb = Some(sortdb);
+
+                let block = StacksChainState::load_block(
+                    &peer_server.chainstate().blocks_path,
+                    &tip.consensus_hash,
+                    &tip.anchored_block_hash,
+                )
+                .unwrap()
+                .unwrap();
+
+                let txid = block.txs[1].txid();
+                *contract_txid_cell.borrow_mut() = Some(txid.clone());
+                convo_client.new_get_transaction_receipt(txid, None)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::TransactionReceipt(response_md, receipt) => {
+                        let txid = (*contract_txid_cell.borrow()).clone().unwrap();
+                        assert_eq!(receipt.txid, format!("0x{}", &txid));
+                        assert_eq!(receipt.tx_index, 1);
+                        assert_eq!(receipt.status, "success");
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_get_fee_estimate() {
+        test_rpc(
+            "test_rpc_get_fee_estimate",
+            40210,
+            40211,
+            50210,
+            50211,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| { convo_client.new_get_fee_estimate(200) },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::FeeEstimate(response_md, fee_estimate) => {
+                        // nothing has been sampled, so the minimum fee rate will do
+                        assert_eq!(fee_estimate.estimated_len, 200);
+                        assert_eq!(fee_estimate.fee_rates.low, MINIMUM_TX_FEE_RATE_PER_BYTE);
+                        assert_eq!(fee_estimate.fee_rates.high, MINIMUM_TX_FEE_RATE_PER_BYTE);
+                        assert_eq!(fee_estimate.fees.medium, 200 * MINIMUM_TX_FEE_RATE_PER_BYTE);
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_getforks() {
+        test_rpc(
+            "test_rpc_getforks",
+            40220,
+            40221,
+            50220,
+            50221,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| { convo_client.new_getforks() },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::Forks(response_md, forks) => {
+                        // only the canonical fork has been mined
+                        let sortdb = peer_server.sortdb.take().unwrap();
+                        let tip = peer_server
+                            .chainstate()
+                            .get_stacks_chain_tip(&sortdb)
+                            .unwrap()
+                            .unwrap();
+                        peer_server.sortdb = Some(sortdb);
+
+                        let tip_id = StacksBlockHeader::make_index_block_hash(
+                            &tip.consensus_hash,
+                            &tip.anchored_block_hash,
+                        );
+                        assert_eq!(forks.canonical_tip, tip_id);
+                        assert_eq!(forks.stacks_tip_height, tip.height);
+                        assert_eq!(forks.forks.len(), 1);
+                        assert!(forks.forks[0].canonical);
+                        assert_eq!(forks.forks[0].index_block_hash, tip_id);
+                        assert_eq!(forks.forks[0].fork_point, tip_id);
+                        assert_eq!(forks.forks[0].length, 0);
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_post_validate_block() {
+        test_rpc(
+            "test_rpc_post_validate_block",
+            40230,
+            40231,
+            50230,
+            50231,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                // re-validate the tip block against its own parent
+                let sortdb = peer_server.sortdb.take().unwrap();
+                let tip = peer_server
+                    .chainstate()
+                    .get_stacks_chain_tip(&sortdb)
+                    .unwrap()
+                    .unwrap();
+                peer_server.sortdb = Some(sortdb);
+
+                let block = StacksChainState::load_block(
+                    &peer_server.chainstate().blocks_path,
+                    &tip.consensus_hash,
+                    &tip.anchored_block_hash,
+                )
+                .unwrap()
+                .unwrap();
+
+                let parent_block_id = StacksBlockHeader::make_index_block_hash(
+                    &tip.parent_consensus_hash,
+                    &tip.parent_anchored_block_hash,
+                );
+                convo_client.new_post_validate_block(block, Some(parent_block_id))
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::BlockValidation(response_md, validation) => {
+                        assert!(validation.valid);
+                        assert_eq!(validation.block_errors.len(), 0);
+                        assert_eq!(validation.tx_errors.len(), 0);
+                        assert!(validation.state_index_root.is_some());
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_faucet_disabled() {
+        test_rpc(
+            "test_rpc_faucet_disabled",
+            40190,
+            40191,
+            50190,
+            50191,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_post_faucet_stx(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .into(),
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::NotFound(response_md, msg) => {
+                        assert_eq!(msg, "Faucet is not enabled");
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn test_rpc_admin_disabled() {
+        test_rpc(
+            "test_rpc_admin_disabled",
+            40192,
+            40193,
+            50192,
+            50193,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let mut request = convo_client.new_admin_flush_chainstate();
+                request.metadata_mut().admin_token = Some("0123abcd".to_string());
+                request
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::Forbidden(response_md, msg) => {
+                        assert_eq!(msg, "Admin endpoints are not enabled on this node");
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 }
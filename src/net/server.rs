@@ -17,9 +17,11 @@
  along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
 */
 
+use std::fmt;
 use std::io::Error as io_error;
 use std::io::ErrorKind;
 use std::io::{Read, Write};
+use std::mem;
 
 use std::collections::HashMap;
 use std::collections::VecDeque;
@@ -31,12 +33,17 @@ use std::sync::mpsc::SendError;
 use std::sync::mpsc::SyncSender;
 use std::sync::mpsc::TryRecvError;
 
+use net::audit::RPCAuditLog;
 use net::connection::*;
 use net::db::*;
 use net::http::*;
 use net::p2p::PeerMap;
 use net::poll::*;
 use net::rpc::*;
+use net::tls::{HttpSocket, RPCTlsConfig, TlsSession};
+use net::websocket::{
+    WebSocketConversation, WebSocketEventReceiver, WEBSOCKET_CLOSE_TRY_AGAIN_LATER,
+};
 use net::Error as net_error;
 use net::*;
 
@@ -79,6 +86,24 @@ pub struct HttpPeer {
 
     // connection options
     pub connection_opts: ConnectionOptions,
+
+    // log of served RPC requests, if enabled
+    pub audit_log: Option<RPCAuditLog>,
+
+    // conversations that were upgraded to WebSocket event streams.  Their sockets stay in
+    // `sockets`.
+    pub websockets: HashMap<usize, WebSocketConversation>,
+
+    // where WebSocket events come from, if we serve them
+    websocket_events: Option<WebSocketEventReceiver>,
+
+    // TLS settings, if we serve the RPC interface over TLS, and the TLS state of each inbound
+    // connection
+    tls_config: Option<RPCTlsConfig>,
+    tls_sessions: HashMap<usize, TlsSession>,
+
+    // peer bans requested through the admin endpoints, for the peer network to carry out
+    admin_bans: Vec<(PeerAddress, u16, u64)>,
 }
 
 impl HttpPeer {
@@ -89,6 +114,44 @@ impl HttpPeer {
         conn_opts: ConnectionOptions,
         server_handle: usize,
     ) -> HttpPeer {
+        let audit_log = match conn_opts.rpc_audit_log_path {
+            Some(ref path) => match RPCAuditLog::open(
+                path,
+                conn_opts.rpc_audit_log_caller_privacy.clone(),
+                conn_opts.rpc_audit_log_retention,
+            ) {
+                Ok(log) => Some(log),
+                Err(e) => {
+                    warn!("Failed to open RPC audit log {}: {:?}", path, &e);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let tls_config = match (&conn_opts.rpc_tls_cert_path, &conn_opts.rpc_tls_key_path) {
+            (Some(ref cert_path), Some(ref key_path)) => {
+                match RPCTlsConfig::from_files(
+                    cert_path,
+                    key_path,
+                    conn_opts
+                        .rpc_tls_client_ca_path
+                        .as_ref()
+                        .map(|path| path.as_str()),
+                ) {
+                    Ok(config) => Some(config),
+                    Err(e) => {
+                        // don't fall back to plaintext if we were asked for TLS
+                        panic!("Failed to load RPC TLS certificate or key: {:?}", &e);
+                    }
+                }
+            }
+            (None, None) => None,
+            _ => {
+                panic!("RPC TLS needs both rpc_tls_cert_path and rpc_tls_key_path");
+            }
+        };
+
         HttpPeer {
             network_id: network_id,
             chain_view: chain_view,
@@ -100,6 +163,15 @@ impl HttpPeer {
 
             burnchain: burnchain,
             connection_opts: conn_opts,
+            audit_log: audit_log,
+
+            websockets: HashMap::new(),
+            websocket_events: None,
+
+            tls_config: tls_config,
+            tls_sessions: HashMap::new(),
+
+            admin_bans: vec![],
         }
     }
 
@@ -107,6 +179,23 @@ impl HttpPeer {
         self.http_server_handle = h;
     }
 
+    /// Take the peer bans requested through the admin endpoints since the last call, as
+    /// (address, port, duration) triples
+    pub fn take_admin_bans(&mut self) -> Vec<(PeerAddress, u16, u64)> {
+        mem::replace(&mut self.admin_bans, vec![])
+    }
+
+    /// Serve WebSocket event subscriptions, with events taken from the given receiver
+    pub fn set_websocket_events(&mut self, receiver: WebSocketEventReceiver) -> () {
+        self.websocket_events = Some(receiver);
+    }
+
+    /// Can another client upgrade to a WebSocket event stream?
+    fn can_upgrade_websocket(&self) -> bool {
+        self.websocket_events.is_some()
+            && (self.websockets.len() as u64) < self.connection_opts.max_websocket_clients
+    }
+
     /// Is there a HTTP conversation open to this data_url that is not in progress?
     pub fn find_free_conversation(&self, data_url: &UrlString) -> Option<usize> {
         for (event_id, convo) in self.peers.iter() {
@@ -179,6 +268,11 @@ impl HttpPeer {
                 count += 1;
             }
         }
+        for (_, websocket) in self.websockets.iter() {
+            if websocket.get_peer_addr().ip() == peer_addr.ip() {
+                count += 1;
+            }
+        }
         count
     }
 
@@ -189,7 +283,8 @@ impl HttpPeer {
         outbound_url: Option<&UrlString>,
     ) -> Result<(), net_error> {
         if outbound_url.is_none()
-            && (self.peers.len() as u64) + 1 > self.connection_opts.num_clients
+            && ((self.peers.len() + self.websockets.len()) as u64) + 1
+                > self.connection_opts.num_clients
         {
             // inbound
             debug!("HTTP: too many inbound peers total");
@@ -288,6 +383,12 @@ impl HttpPeer {
             }
         }
 
+        if outbound_url.is_none() {
+            if let Some(ref tls_config) = self.tls_config {
+                self.tls_sessions.insert(event_id, tls_config.new_session());
+            }
+        }
+
         self.sockets.insert(event_id, socket);
         self.peers.insert(event_id, new_convo);
         Ok(())
@@ -296,6 +397,8 @@ impl HttpPeer {
     /// Deregister a socket/event pair
     pub fn deregister_http(&mut self, network_state: &mut NetworkState, event_id: usize) -> () {
         self.peers.remove(&event_id);
+        self.websockets.remove(&event_id);
+        self.tls_sessions.remove(&event_id);
 
         match self.sockets.remove(&event_id) {
             None => {}
@@ -344,6 +447,17 @@ impl HttpPeer {
             }
         }
 
+        for (event_id, websocket) in self.websockets.iter_mut() {
+            if !websocket.check_alive(now) {
+                debug!(
+                    "Removing unresponsive WebSocket {} ({:?})",
+                    event_id,
+                    websocket.get_peer_addr()
+                );
+                to_remove.push(*event_id);
+            }
+        }
+
         for event_id in to_remove.drain(0..) {
             self.deregister_http(network_state, event_id);
         }
@@ -351,8 +465,8 @@ impl HttpPeer {
 
     /// Saturate a conversation's socket -- either sends the whole request, or fills the socket
     /// buffer.
-    pub fn saturate_http_socket(
-        client_sock: &mut mio::net::TcpStream,
+    pub fn saturate_http_socket<S: Write + fmt::Debug>(
+        client_sock: &mut S,
         convo: &mut ConversationHttp,
         chainstate: &mut StacksChainState,
     ) -> Result<(), net_error> {
@@ -435,7 +549,7 @@ impl HttpPeer {
         chainstate: &mut StacksChainState,
         mempool: &mut MemPoolDB,
         event_id: usize,
-        client_sock: &mut mio_net::TcpStream,
+        client_sock: &mut HttpSocket,
         convo: &mut ConversationHttp,
         handler_args: &RPCHandlerArgs,
     ) -> Result<(bool, Vec<StacksMessageType>), net_error> {
@@ -497,6 +611,7 @@ impl HttpPeer {
             }
             Ok(_) => {}
         }
+        convo.set_tls_client_authenticated(client_sock.is_client_authenticated());
 
         // react to inbound messages -- do we need to send something out, or fulfill requests
         // to other threads?  Try to chat even if the recv() failed, since we'll want to at
@@ -524,7 +639,10 @@ impl HttpPeer {
         if !convo_dead {
             // (continue) sending out data in this conversation, if the conversation is still
             // ongoing
-            match convo.send(client_sock, chainstate) {
+            match convo.send(client_sock, chainstate).and_then(|_| {
+                // push out any encrypted data the socket didn't take last time
+                client_sock.flush().map_err(net_error::WriteError)
+            }) {
                 Ok(_) => {}
                 Err(e) => {
                     debug!(
@@ -539,6 +657,108 @@ impl HttpPeer {
         Ok((!convo_dead, msgs))
     }
 
+    /// Process network traffic on a WebSocket.
+    /// Returns whether or not it is still alive.
+    fn process_websocket_conversation(
+        event_id: usize,
+        client_sock: &mut HttpSocket,
+        websocket: &mut WebSocketConversation,
+    ) -> bool {
+        if let Err(e) = websocket.recv(client_sock) {
+            debug!(
+                "Failed to receive WebSocket data on event {} (socket {:?}): {:?}",
+                event_id, &client_sock, &e
+            );
+            return false;
+        }
+        if let Err(e) = websocket.send(client_sock) {
+            debug!(
+                "Failed to send WebSocket data on event {} (socket {:?}): {:?}",
+                event_id, &client_sock, &e
+            );
+            return false;
+        }
+        true
+    }
+
+    /// Turn HTTP conversations that agreed to a WebSocket upgrade into WebSocket conversations,
+    /// once their 101 response has been sent.
+    fn upgrade_websockets(&mut self) -> () {
+        let upgraded: Vec<usize> = self
+            .peers
+            .iter()
+            .filter(|(_, convo)| convo.is_websocket_upgraded() && convo.is_drained())
+            .map(|(event_id, _)| *event_id)
+            .collect();
+
+        for event_id in upgraded.into_iter() {
+            if let Some(convo) = self.peers.remove(&event_id) {
+                debug!("Upgraded HTTP conversation {:?} to a WebSocket", &convo);
+                let mut websocket =
+                    WebSocketConversation::new(event_id, convo.get_peer_addr().clone());
+                if (self.websockets.len() as u64) >= self.connection_opts.max_websocket_clients {
+                    // lost a race with another client
+                    websocket.close(WEBSOCKET_CLOSE_TRY_AGAIN_LATER, "too many subscribers");
+                }
+                self.websockets.insert(event_id, websocket);
+            }
+        }
+    }
+
+    /// Hand out new events to WebSocket subscribers.
+    /// Returns the list of subscribers that have fallen too far behind, and must be dropped.
+    fn dispatch_websocket_events(&mut self) -> Vec<usize> {
+        let mut to_remove = vec![];
+        let events = match self.websocket_events {
+            Some(ref mut receiver) => receiver.take_events(),
+            None => {
+                return to_remove;
+            }
+        };
+
+        for event in events.iter() {
+            for (event_id, websocket) in self.websockets.iter_mut() {
+                if let Err(e) = websocket.notify(event) {
+                    info!(
+                        "Dropping slow WebSocket {} ({:?}): {:?}",
+                        event_id,
+                        websocket.get_peer_addr(),
+                        &e
+                    );
+                    to_remove.push(*event_id);
+                }
+            }
+        }
+        to_remove.sort();
+        to_remove.dedup();
+        to_remove
+    }
+
+    /// Send queued data to WebSocket subscribers, but don't block.
+    /// Returns the list of WebSockets that are broken, or that we have finished closing.
+    fn flush_websockets(&mut self) -> Vec<usize> {
+        let mut close = vec![];
+        for (event_id, websocket) in self.websockets.iter_mut() {
+            match self.sockets.get_mut(event_id) {
+                Some(client_sock) => {
+                    let mut client_sock =
+                        HttpSocket::new(client_sock, self.tls_sessions.get_mut(event_id));
+                    if let Err(e) = websocket.send(&mut client_sock) {
+                        debug!("Broken WebSocket {}: {:?}", event_id, &e);
+                        close.push(*event_id);
+                    } else if websocket.is_closed() {
+                        debug!("Closed WebSocket {}", event_id);
+                        close.push(*event_id);
+                    }
+                }
+                None => {
+                    close.push(*event_id);
+                }
+            }
+        }
+        close
+    }
+
     /// Is an event in the process of connecting?
     pub fn is_connecting(&self, event_id: usize) -> bool {
         self.connecting.contains_key(&event_id)
@@ -590,6 +810,7 @@ impl HttpPeer {
     ) -> (Vec<StacksMessageType>, Vec<usize>) {
         let mut to_remove = vec![];
         let mut msgs = vec![];
+        let websocket_enabled = self.can_upgrade_websocket();
         for event_id in &poll_state.ready {
             if !self.sockets.contains_key(&event_id) {
                 test_debug!("Rogue socket event {}", event_id);
@@ -603,12 +824,25 @@ impl HttpPeer {
                 to_remove.push(*event_id);
                 continue;
             }
-            let client_sock = client_sock_opt.unwrap();
+            let mut client_sock = HttpSocket::new(
+                client_sock_opt.unwrap(),
+                self.tls_sessions.get_mut(event_id),
+            );
+
+            if let Some(websocket) = self.websockets.get_mut(event_id) {
+                // activity on a WebSocket
+                if !HttpPeer::process_websocket_conversation(*event_id, &mut client_sock, websocket)
+                {
+                    to_remove.push(*event_id);
+                }
+                continue;
+            }
 
             match self.peers.get_mut(event_id) {
                 Some(ref mut convo) => {
                     // activity on a http socket
                     test_debug!("Process HTTP data from {:?}", convo);
+                    convo.set_websocket_enabled(websocket_enabled);
                     match HttpPeer::process_http_conversation(
                         &self.chain_view,
                         peers,
@@ -617,7 +851,7 @@ impl HttpPeer {
                         chainstate,
                         mempool,
                         *event_id,
-                        client_sock,
+                        &mut client_sock,
                         convo,
                         handler_args,
                     ) {
@@ -629,9 +863,17 @@ impl HttpPeer {
                         }
                         Err(_e) => {
                             to_remove.push(*event_id);
-                            continue;
                         }
                     };
+
+                    if let Some(ref mut audit_log) = self.audit_log {
+                        for record in convo.take_audit_records() {
+                            if let Err(e) = audit_log.record(&record) {
+                                warn!("Failed to write RPC audit record {:?}: {:?}", &record, &e);
+                            }
+                        }
+                    }
+                    self.admin_bans.append(&mut convo.take_admin_bans());
                 }
                 None => {
                     warn!("Rogue event {} for socket {:?}", event_id, &client_sock);
@@ -658,7 +900,12 @@ impl HttpPeer {
                     close.push(*event_id);
                 }
             }
-            if convo.is_drained() && !convo.is_keep_alive() {
+            let tls_pending = self
+                .tls_sessions
+                .get(event_id)
+                .map(|session| session.has_pending_output())
+                .unwrap_or(false);
+            if convo.is_drained() && !convo.is_keep_alive() && !tls_pending {
                 // did some work, but nothing more to do and we're not keep-alive
                 debug!("Close drained HTTP connection {:?}", convo);
                 close.push(*event_id);
@@ -717,6 +964,15 @@ impl HttpPeer {
             self.deregister_http(network_state, close_event);
         }
 
+        // switch conversations over to WebSockets, and stream them new events
+        self.upgrade_websockets();
+        for slow_event in self.dispatch_websocket_events() {
+            self.deregister_http(network_state, slow_event);
+        }
+        for close_event in self.flush_websockets() {
+            self.deregister_http(network_state, close_event);
+        }
+
         // remove timed-out requests
         for (_, convo) in self.peers.iter_mut() {
             convo.clear_timeouts();
@@ -0,0 +1,1118 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! WebSocket event subscriptions.
+//!
+//! A client upgrades a `GET /v2/events` request to a WebSocket (RFC 6455), and then tells the
+//! node which events it wants by sending a text message of the form
+//! `{ "subscribe": ["block", "microblock", "mempool", "reorg"], "addresses": ["ST..."] }`.
+//! The `addresses` list is optional; if given, only the block, microblock and mempool events
+//! whose transactions involve one of those principals are sent.  Each event is sent as a text
+//! message holding a JSON object with an `event` name and the event's `data`.
+//!
+//! The node produces events on its own threads and hands them to the HTTP server through a
+//! `WebSocketEventSender`.  The HTTP server drains them with a `WebSocketEventReceiver`, which
+//! also notices when a new block does not build on the last one and emits a `reorg` event.
+
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::io;
+use std::io::{Read, Write};
+use std::net::SocketAddr;
+use std::sync::mpsc::sync_channel;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::SyncSender;
+use std::sync::mpsc::TryRecvError;
+use std::sync::mpsc::TrySendError;
+
+use sha1::{Digest, Sha1};
+
+use burnchains::Txid;
+use chainstate::burn::{BlockHeaderHash, ConsensusHash};
+use chainstate::stacks::db::StacksHeaderInfo;
+use chainstate::stacks::{
+    StacksBlock, StacksBlockId, StacksMicroblock, StacksTransaction, TransactionPayload,
+};
+use net::Error as net_error;
+
+use util::get_epoch_time_secs;
+
+/// Appended to the client's Sec-WebSocket-Key to compute Sec-WebSocket-Accept
+pub const WEBSOCKET_GUID: &'static str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Largest frame we accept from a client.  Clients only send subscription requests.
+pub const MAX_WEBSOCKET_FRAME_LEN: u64 = 65536;
+
+/// Largest backlog of unsent data we keep for a client before we drop it as too slow
+pub const MAX_WEBSOCKET_OUTBOX_LEN: usize = 16 * 1024 * 1024;
+
+/// Most addresses a client may filter on
+pub const MAX_WEBSOCKET_ADDRESSES: usize = 256;
+
+/// How many events may be waiting for the HTTP server before new ones are dropped
+pub const WEBSOCKET_EVENT_BUFFER: usize = 1024;
+
+/// Ping a client after this many seconds of silence, and drop it after three times as many
+pub const WEBSOCKET_PING_INTERVAL: u64 = 30;
+
+/// Close status codes (RFC 6455, section 7.4.1)
+pub const WEBSOCKET_CLOSE_NORMAL: u16 = 1000;
+pub const WEBSOCKET_CLOSE_PROTOCOL_ERROR: u16 = 1002;
+pub const WEBSOCKET_CLOSE_UNSUPPORTED_DATA: u16 = 1003;
+pub const WEBSOCKET_CLOSE_TOO_BIG: u16 = 1009;
+pub const WEBSOCKET_CLOSE_TRY_AGAIN_LATER: u16 = 1013;
+
+const BASE64_ALPHABET: &'static [u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let b0 = chunk[0] as u32;
+        let b1 = if chunk.len() > 1 { chunk[1] as u32 } else { 0 };
+        let b2 = if chunk.len() > 2 { chunk[2] as u32 } else { 0 };
+        let triple = (b0 << 16) | (b1 << 8) | b2;
+
+        out.push(BASE64_ALPHABET[((triple >> 18) & 0x3f) as usize] as char);
+        out.push(BASE64_ALPHABET[((triple >> 12) & 0x3f) as usize] as char);
+        if chunk.len() > 1 {
+            out.push(BASE64_ALPHABET[((triple >> 6) & 0x3f) as usize] as char);
+        } else {
+            out.push('=');
+        }
+        if chunk.len() > 2 {
+            out.push(BASE64_ALPHABET[(triple & 0x3f) as usize] as char);
+        } else {
+            out.push('=');
+        }
+    }
+    out
+}
+
+/// Compute the Sec-WebSocket-Accept value for a client's Sec-WebSocket-Key
+pub fn websocket_accept_key(client_key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.input(client_key.trim().as_bytes());
+    hasher.input(WEBSOCKET_GUID.as_bytes());
+    base64_encode(hasher.result().as_slice())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WebSocketOpcode {
+    Continuation = 0x0,
+    Text = 0x1,
+    Binary = 0x2,
+    Close = 0x8,
+    Ping = 0x9,
+    Pong = 0xa,
+}
+
+impl WebSocketOpcode {
+    fn from_u8(byte: u8) -> Option<WebSocketOpcode> {
+        match byte {
+            0x0 => Some(WebSocketOpcode::Continuation),
+            0x1 => Some(WebSocketOpcode::Text),
+            0x2 => Some(WebSocketOpcode::Binary),
+            0x8 => Some(WebSocketOpcode::Close),
+            0x9 => Some(WebSocketOpcode::Ping),
+            0xa => Some(WebSocketOpcode::Pong),
+            _ => None,
+        }
+    }
+
+    fn is_control(&self) -> bool {
+        (*self as u8) & 0x8 != 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebSocketFrame {
+    pub fin: bool,
+    pub opcode: WebSocketOpcode,
+    pub payload: Vec<u8>,
+}
+
+impl WebSocketFrame {
+    pub fn new(opcode: WebSocketOpcode, payload: Vec<u8>) -> WebSocketFrame {
+        WebSocketFrame {
+            fin: true,
+            opcode: opcode,
+            payload: payload,
+        }
+    }
+
+    pub fn text(text: &str) -> WebSocketFrame {
+        WebSocketFrame::new(WebSocketOpcode::Text, text.as_bytes().to_vec())
+    }
+
+    pub fn close(status: u16, reason: &str) -> WebSocketFrame {
+        let mut payload = status.to_be_bytes().to_vec();
+        // control frame payloads are at most 125 bytes
+        let reason_bytes = reason.as_bytes();
+        let reason_len = reason_bytes.len().min(123);
+        payload.extend_from_slice(&reason_bytes[0..reason_len]);
+        WebSocketFrame::new(WebSocketOpcode::Close, payload)
+    }
+
+    /// Encode this frame.  Servers send unmasked frames; clients must mask theirs.
+    pub fn encode(&self, mask: Option<[u8; 4]>) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.payload.len() + 14);
+        let fin_bit = if self.fin { 0x80 } else { 0x00 };
+        bytes.push(fin_bit | (self.opcode as u8));
+
+        let mask_bit = if mask.is_some() { 0x80 } else { 0x00 };
+        let len = self.payload.len();
+        if len < 126 {
+            bytes.push(mask_bit | (len as u8));
+        } else if len <= (u16::max_value() as usize) {
+            bytes.push(mask_bit | 126);
+            bytes.extend_from_slice(&(len as u16).to_be_bytes());
+        } else {
+            bytes.push(mask_bit | 127);
+            bytes.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+
+        match mask {
+            Some(mask_key) => {
+                bytes.extend_from_slice(&mask_key);
+                for (i, byte) in self.payload.iter().enumerate() {
+                    bytes.push(byte ^ mask_key[i % 4]);
+                }
+            }
+            None => {
+                bytes.extend_from_slice(&self.payload);
+            }
+        }
+        bytes
+    }
+
+    /// Decode the frame at the start of `buf`.  Returns the frame and the number of bytes it
+    /// took up, or None if `buf` does not yet hold a whole frame.
+    pub fn decode(
+        buf: &[u8],
+        require_mask: bool,
+        max_len: u64,
+    ) -> Result<Option<(WebSocketFrame, usize)>, net_error> {
+        if buf.len() < 2 {
+            return Ok(None);
+        }
+
+        let fin = buf[0] & 0x80 != 0;
+        if buf[0] & 0x70 != 0 {
+            return Err(net_error::DeserializeError(
+                "WebSocket frame uses reserved bits".to_string(),
+            ));
+        }
+        let opcode = WebSocketOpcode::from_u8(buf[0] & 0x0f).ok_or(net_error::DeserializeError(
+            format!("Unknown WebSocket opcode {}", buf[0] & 0x0f),
+        ))?;
+
+        let masked = buf[1] & 0x80 != 0;
+        if require_mask && !masked {
+            return Err(net_error::DeserializeError(
+                "Client WebSocket frame is not masked".to_string(),
+            ));
+        }
+
+        let mut offset = 2;
+        let len = match buf[1] & 0x7f {
+            126 => {
+                if buf.len() < offset + 2 {
+                    return Ok(None);
+                }
+                let mut len_bytes = [0u8; 2];
+                len_bytes.copy_from_slice(&buf[offset..offset + 2]);
+                offset += 2;
+                u16::from_be_bytes(len_bytes) as u64
+            }
+            127 => {
+                if buf.len() < offset + 8 {
+                    return Ok(None);
+                }
+                let mut len_bytes = [0u8; 8];
+                len_bytes.copy_from_slice(&buf[offset..offset + 8]);
+                offset += 8;
+                u64::from_be_bytes(len_bytes)
+            }
+            short_len => short_len as u64,
+        };
+
+        if len > max_len {
+            return Err(net_error::OverflowError(format!(
+                "WebSocket frame is too big ({} > {})",
+                len, max_len
+            )));
+        }
+        if opcode.is_control() && (len > 125 || !fin) {
+            return Err(net_error::DeserializeError(
+                "Invalid WebSocket control frame".to_string(),
+            ));
+        }
+
+        let mask_key = if masked {
+            if buf.len() < offset + 4 {
+                return Ok(None);
+            }
+            let mut mask_key = [0u8; 4];
+            mask_key.copy_from_slice(&buf[offset..offset + 4]);
+            offset += 4;
+            Some(mask_key)
+        } else {
+            None
+        };
+
+        let len = len as usize;
+        if buf.len() < offset + len {
+            return Ok(None);
+        }
+
+        let mut payload = buf[offset..offset + len].to_vec();
+        if let Some(mask_key) = mask_key {
+            for (i, byte) in payload.iter_mut().enumerate() {
+                *byte ^= mask_key[i % 4];
+            }
+        }
+
+        let frame = WebSocketFrame {
+            fin: fin,
+            opcode: opcode,
+            payload: payload,
+        };
+        Ok(Some((frame, offset + len)))
+    }
+}
+
+/// Kinds of events a client can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WebSocketTopic {
+    Block,
+    Microblock,
+    Mempool,
+    Reorg,
+}
+
+impl WebSocketTopic {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WebSocketTopic::Block => "block",
+            WebSocketTopic::Microblock => "microblock",
+            WebSocketTopic::Mempool => "mempool",
+            WebSocketTopic::Reorg => "reorg",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<WebSocketTopic> {
+        match name {
+            "block" => Some(WebSocketTopic::Block),
+            "microblock" => Some(WebSocketTopic::Microblock),
+            "mempool" => Some(WebSocketTopic::Mempool),
+            "reorg" => Some(WebSocketTopic::Reorg),
+            _ => None,
+        }
+    }
+}
+
+/// Principals a transaction involves: its origin and sponsor, the recipient of a token
+/// transfer, and the contract it calls or deploys
+pub fn transaction_principals(tx: &StacksTransaction) -> Vec<String> {
+    let origin = tx.origin_address();
+    let mut principals = vec![origin.to_string()];
+    if let Some(sponsor) = tx.sponsor_address() {
+        principals.push(sponsor.to_string());
+    }
+    match tx.payload {
+        TransactionPayload::TokenTransfer(ref recipient, ..) => {
+            principals.push(recipient.to_string());
+        }
+        TransactionPayload::ContractCall(ref cc) => {
+            principals.push(format!("{}.{}", &cc.address, &cc.contract_name));
+        }
+        TransactionPayload::SmartContract(ref sc) => {
+            principals.push(format!("{}.{}", &origin, &sc.name));
+        }
+        _ => {}
+    }
+    principals
+}
+
+fn txs_summary(txs: &[StacksTransaction]) -> (Vec<Txid>, HashSet<String>) {
+    let mut txids = vec![];
+    let mut principals = HashSet::new();
+    for tx in txs.iter() {
+        txids.push(tx.txid());
+        principals.extend(transaction_principals(tx).into_iter());
+    }
+    (txids, principals)
+}
+
+fn principals_json(principals: &HashSet<String>) -> serde_json::Value {
+    let mut sorted: Vec<_> = principals.iter().cloned().collect();
+    sorted.sort();
+    json!(sorted)
+}
+
+/// An event pushed to WebSocket subscribers
+#[derive(Debug, Clone, PartialEq)]
+pub enum WebSocketEvent {
+    /// a new canonical anchored block
+    Block {
+        index_block_hash: StacksBlockId,
+        parent_index_block_hash: StacksBlockId,
+        block_hash: BlockHeaderHash,
+        consensus_hash: ConsensusHash,
+        block_height: u64,
+        burn_block_height: u32,
+        txids: Vec<Txid>,
+        principals: HashSet<String>,
+    },
+    /// new unconfirmed microblocks off of an anchored block
+    Microblocks {
+        index_anchor_block: StacksBlockId,
+        microblock_hashes: Vec<BlockHeaderHash>,
+        txids: Vec<Txid>,
+        principals: HashSet<String>,
+    },
+    /// a transaction admitted to the mempool
+    MempoolTransaction {
+        txid: Txid,
+        principals: HashSet<String>,
+    },
+    /// the canonical chain tip moved to a block that does not build on the last one
+    Reorg {
+        old_tip: StacksBlockId,
+        new_tip: StacksBlockId,
+        block_height: u64,
+    },
+}
+
+impl WebSocketEvent {
+    pub fn from_block(
+        block: &StacksBlock,
+        metadata: &StacksHeaderInfo,
+        parent_index_block_hash: &StacksBlockId,
+    ) -> WebSocketEvent {
+        let block_hash = block.block_hash();
+        let (txids, principals) = txs_summary(&block.txs);
+        WebSocketEvent::Block {
+            index_block_hash: StacksBlockId::new(&metadata.consensus_hash, &block_hash),
+            parent_index_block_hash: parent_index_block_hash.clone(),
+            block_hash: block_hash,
+            consensus_hash: metadata.consensus_hash.clone(),
+            block_height: metadata.block_height,
+            burn_block_height: metadata.burn_header_height,
+            txids: txids,
+            principals: principals,
+        }
+    }
+
+    pub fn from_microblocks(
+        index_anchor_block: &StacksBlockId,
+        microblocks: &[StacksMicroblock],
+    ) -> WebSocketEvent {
+        let mut microblock_hashes = vec![];
+        let mut txids = vec![];
+        let mut principals = HashSet::new();
+        for mblock in microblocks.iter() {
+            microblock_hashes.push(mblock.block_hash());
+            let (mut mblock_txids, mblock_principals) = txs_summary(&mblock.txs);
+            txids.append(&mut mblock_txids);
+            principals.extend(mblock_principals.into_iter());
+        }
+        WebSocketEvent::Microblocks {
+            index_anchor_block: index_anchor_block.clone(),
+            microblock_hashes: microblock_hashes,
+            txids: txids,
+            principals: principals,
+        }
+    }
+
+    pub fn from_mempool_tx(tx: &StacksTransaction) -> WebSocketEvent {
+        WebSocketEvent::MempoolTransaction {
+            txid: tx.txid(),
+            principals: transaction_principals(tx).into_iter().collect(),
+        }
+    }
+
+    pub fn topic(&self) -> WebSocketTopic {
+        match self {
+            WebSocketEvent::Block { .. } => WebSocketTopic::Block,
+            WebSocketEvent::Microblocks { .. } => WebSocketTopic::Microblock,
+            WebSocketEvent::MempoolTransaction { .. } => WebSocketTopic::Mempool,
+            WebSocketEvent::Reorg { .. } => WebSocketTopic::Reorg,
+        }
+    }
+
+    /// The principals this event's transactions involve, if it has any
+    pub fn principals(&self) -> Option<&HashSet<String>> {
+        match self {
+            WebSocketEvent::Block { ref principals, .. } => Some(principals),
+            WebSocketEvent::Microblocks { ref principals, .. } => Some(principals),
+            WebSocketEvent::MempoolTransaction { ref principals, .. } => Some(principals),
+            WebSocketEvent::Reorg { .. } => None,
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let data = match self {
+            WebSocketEvent::Block {
+                index_block_hash,
+                parent_index_block_hash,
+                block_hash,
+                consensus_hash,
+                block_height,
+                burn_block_height,
+                txids,
+                principals,
+            } => json!({
+                "index_block_hash": format!("0x{}", index_block_hash),
+                "parent_index_block_hash": format!("0x{}", parent_index_block_hash),
+                "block_hash": format!("0x{}", block_hash),
+                "consensus_hash": format!("0x{}", consensus_hash),
+                "block_height": block_height,
+                "burn_block_height": burn_block_height,
+                "txids": txids.iter().map(|txid| format!("0x{}", txid)).collect::<Vec<_>>(),
+                "principals": principals_json(principals),
+            }),
+            WebSocketEvent::Microblocks {
+                index_anchor_block,
+                microblock_hashes,
+                txids,
+                principals,
+            } => json!({
+                "index_anchor_block": format!("0x{}", index_anchor_block),
+                "microblock_hashes": microblock_hashes.iter().map(|hash| format!("0x{}", hash)).collect::<Vec<_>>(),
+                "txids": txids.iter().map(|txid| format!("0x{}", txid)).collect::<Vec<_>>(),
+                "principals": principals_json(principals),
+            }),
+            WebSocketEvent::MempoolTransaction { txid, principals } => json!({
+                "txid": format!("0x{}", txid),
+                "principals": principals_json(principals),
+            }),
+            WebSocketEvent::Reorg {
+                old_tip,
+                new_tip,
+                block_height,
+            } => json!({
+                "old_tip": format!("0x{}", old_tip),
+                "new_tip": format!("0x{}", new_tip),
+                "block_height": block_height,
+            }),
+        };
+        json!({
+            "event": self.topic().name(),
+            "data": data,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct WebSocketSubscribeRequest {
+    subscribe: Vec<String>,
+    addresses: Option<Vec<String>>,
+}
+
+/// The events a WebSocket client has asked for
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebSocketSubscription {
+    pub topics: HashSet<WebSocketTopic>,
+    /// if set, only send events whose transactions involve one of these principals
+    pub principals: Option<HashSet<String>>,
+}
+
+impl WebSocketSubscription {
+    /// Nothing is sent until the client subscribes
+    pub fn empty() -> WebSocketSubscription {
+        WebSocketSubscription {
+            topics: HashSet::new(),
+            principals: None,
+        }
+    }
+
+    /// Parse a client's subscription request
+    pub fn from_json(text: &str) -> Result<WebSocketSubscription, String> {
+        let request: WebSocketSubscribeRequest = serde_json::from_str(text)
+            .map_err(|e| format!("Invalid subscription request: {}", e))?;
+
+        let mut topics = HashSet::new();
+        for name in request.subscribe.iter() {
+            let topic = WebSocketTopic::from_name(name)
+                .ok_or(format!("Unknown subscription topic \"{}\"", name))?;
+            topics.insert(topic);
+        }
+
+        let principals = match request.addresses {
+            Some(addresses) => {
+                if addresses.len() > MAX_WEBSOCKET_ADDRESSES {
+                    return Err(format!(
+                        "Too many addresses: at most {} allowed",
+                        MAX_WEBSOCKET_ADDRESSES
+                    ));
+                }
+                Some(addresses.into_iter().collect())
+            }
+            None => None,
+        };
+
+        Ok(WebSocketSubscription {
+            topics: topics,
+            principals: principals,
+        })
+    }
+
+    pub fn matches(&self, event: &WebSocketEvent) -> bool {
+        if !self.topics.contains(&event.topic()) {
+            return false;
+        }
+        match (self.principals.as_ref(), event.principals()) {
+            (Some(wanted), Some(involved)) => wanted.iter().any(|p| involved.contains(p)),
+            _ => true,
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut topics: Vec<_> = self.topics.iter().map(|t| t.name()).collect();
+        topics.sort();
+        let mut principals = self
+            .principals
+            .as_ref()
+            .map(|p| p.iter().cloned().collect::<Vec<_>>());
+        if let Some(ref mut principals) = principals {
+            principals.sort();
+        }
+        json!({
+            "subscribed": topics,
+            "addresses": principals,
+        })
+    }
+}
+
+/// Producer side of the event channel.  Events are dropped, not queued without bound, if the
+/// HTTP server falls behind.
+#[derive(Debug, Clone)]
+pub struct WebSocketEventSender {
+    sender: SyncSender<WebSocketEvent>,
+}
+
+impl WebSocketEventSender {
+    pub fn send(&self, event: WebSocketEvent) -> () {
+        match self.sender.try_send(event) {
+            Ok(_) => {}
+            Err(TrySendError::Full(event)) => {
+                warn!(
+                    "WebSocket event buffer is full; dropping {:?}",
+                    &event.topic()
+                );
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                debug!("WebSocket event receiver is gone");
+            }
+        }
+    }
+}
+
+/// Consumer side of the event channel, owned by the HTTP server
+pub struct WebSocketEventReceiver {
+    receiver: Receiver<WebSocketEvent>,
+    last_tip: Option<StacksBlockId>,
+}
+
+impl WebSocketEventReceiver {
+    /// Take all pending events, adding a reorg event before any block that does not build on
+    /// the block before it
+    pub fn take_events(&mut self) -> Vec<WebSocketEvent> {
+        let mut events = vec![];
+        loop {
+            let event = match self.receiver.try_recv() {
+                Ok(event) => event,
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => {
+                    break;
+                }
+            };
+
+            if let WebSocketEvent::Block {
+                ref index_block_hash,
+                ref parent_index_block_hash,
+                ref block_height,
+                ..
+            } = event
+            {
+                if let Some(ref last_tip) = self.last_tip {
+                    if last_tip != parent_index_block_hash {
+                        events.push(WebSocketEvent::Reorg {
+                            old_tip: last_tip.clone(),
+                            new_tip: index_block_hash.clone(),
+                            block_height: *block_height,
+                        });
+                    }
+                }
+                self.last_tip = Some(index_block_hash.clone());
+            }
+            events.push(event);
+        }
+        events
+    }
+}
+
+/// Make a channel for passing events from the node to the HTTP server
+pub fn websocket_event_channel() -> (WebSocketEventSender, WebSocketEventReceiver) {
+    let (sender, receiver) = sync_channel(WEBSOCKET_EVENT_BUFFER);
+    (
+        WebSocketEventSender { sender: sender },
+        WebSocketEventReceiver {
+            receiver: receiver,
+            last_tip: None,
+        },
+    )
+}
+
+/// A WebSocket connection to a subscriber, after the HTTP upgrade
+pub struct WebSocketConversation {
+    conn_id: usize,
+    peer_addr: SocketAddr,
+    subscription: WebSocketSubscription,
+    inbox: Vec<u8>,
+    outbox: VecDeque<u8>,
+    close_sent: bool,
+    close_received: bool,
+    last_recv_time: u64,
+    last_ping_time: u64,
+}
+
+impl WebSocketConversation {
+    pub fn new(conn_id: usize, peer_addr: SocketAddr) -> WebSocketConversation {
+        let now = get_epoch_time_secs();
+        WebSocketConversation {
+            conn_id: conn_id,
+            peer_addr: peer_addr,
+            subscription: WebSocketSubscription::empty(),
+            inbox: vec![],
+            outbox: VecDeque::new(),
+            close_sent: false,
+            close_received: false,
+            last_recv_time: now,
+            last_ping_time: now,
+        }
+    }
+
+    pub fn get_peer_addr(&self) -> &SocketAddr {
+        &self.peer_addr
+    }
+
+    pub fn get_subscription(&self) -> &WebSocketSubscription {
+        &self.subscription
+    }
+
+    fn queue_frame(&mut self, frame: WebSocketFrame) -> () {
+        if self.close_sent {
+            return;
+        }
+        if frame.opcode == WebSocketOpcode::Close {
+            self.close_sent = true;
+        }
+        self.outbox.extend(frame.encode(None).into_iter());
+    }
+
+    /// Start closing the connection
+    pub fn close(&mut self, status: u16, reason: &str) -> () {
+        debug!(
+            "Close WebSocket {} ({:?}): {} {}",
+            self.conn_id, &self.peer_addr, status, reason
+        );
+        self.queue_frame(WebSocketFrame::close(status, reason));
+    }
+
+    fn handle_text(&mut self, text: &str) -> () {
+        let reply = match WebSocketSubscription::from_json(text) {
+            Ok(subscription) => {
+                debug!(
+                    "WebSocket {} ({:?}) subscribes to {:?}",
+                    self.conn_id, &self.peer_addr, &subscription
+                );
+                self.subscription = subscription;
+                self.subscription.to_json()
+            }
+            Err(msg) => json!({ "error": msg }),
+        };
+        self.queue_frame(WebSocketFrame::text(&reply.to_string()));
+    }
+
+    fn handle_frame(&mut self, frame: WebSocketFrame) -> () {
+        if !frame.fin || frame.opcode == WebSocketOpcode::Continuation {
+            self.close(
+                WEBSOCKET_CLOSE_UNSUPPORTED_DATA,
+                "fragmented messages are not supported",
+            );
+            return;
+        }
+
+        match frame.opcode {
+            WebSocketOpcode::Text => match String::from_utf8(frame.payload) {
+                Ok(text) => self.handle_text(&text),
+                Err(_) => self.close(WEBSOCKET_CLOSE_PROTOCOL_ERROR, "text is not utf-8"),
+            },
+            WebSocketOpcode::Binary => {
+                self.close(
+                    WEBSOCKET_CLOSE_UNSUPPORTED_DATA,
+                    "binary messages are not supported",
+                );
+            }
+            WebSocketOpcode::Ping => {
+                self.queue_frame(WebSocketFrame::new(WebSocketOpcode::Pong, frame.payload));
+            }
+            WebSocketOpcode::Pong => {}
+            WebSocketOpcode::Close => {
+                self.close_received = true;
+                self.queue_frame(WebSocketFrame::close(WEBSOCKET_CLOSE_NORMAL, ""));
+            }
+            WebSocketOpcode::Continuation => unreachable!(),
+        }
+    }
+
+    /// Read and handle whatever the client has sent
+    pub fn recv<R: Read>(&mut self, fd: &mut R) -> Result<usize, net_error> {
+        let mut total_read = 0;
+        let mut socket_closed = false;
+        loop {
+            let mut buf = [0u8; 4096];
+            match fd.read(&mut buf) {
+                Ok(0) => {
+                    socket_closed = true;
+                    break;
+                }
+                Ok(count) => {
+                    total_read += count;
+                    self.inbox.extend_from_slice(&buf[0..count]);
+                }
+                Err(e) => {
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || (cfg!(windows) && e.kind() == io::ErrorKind::TimedOut)
+                    {
+                        break;
+                    } else if e.kind() == io::ErrorKind::BrokenPipe
+                        || e.kind() == io::ErrorKind::ConnectionReset
+                    {
+                        socket_closed = true;
+                        break;
+                    } else {
+                        return Err(net_error::RecvError(format!("Failed to read: {:?}", &e)));
+                    }
+                }
+            }
+        }
+
+        if total_read > 0 {
+            self.last_recv_time = get_epoch_time_secs();
+        }
+
+        loop {
+            if self.close_received {
+                break;
+            }
+            match WebSocketFrame::decode(&self.inbox, true, MAX_WEBSOCKET_FRAME_LEN) {
+                Ok(Some((frame, consumed))) => {
+                    self.inbox.drain(0..consumed);
+                    self.handle_frame(frame);
+                }
+                Ok(None) => {
+                    break;
+                }
+                Err(net_error::OverflowError(msg)) => {
+                    self.inbox.clear();
+                    self.close(WEBSOCKET_CLOSE_TOO_BIG, &msg);
+                    break;
+                }
+                Err(e) => {
+                    self.inbox.clear();
+                    self.close(WEBSOCKET_CLOSE_PROTOCOL_ERROR, "malformed frame");
+                    debug!(
+                        "Bad WebSocket frame from {} ({:?}): {:?}",
+                        self.conn_id, &self.peer_addr, &e
+                    );
+                    break;
+                }
+            }
+        }
+
+        if socket_closed && total_read == 0 {
+            return Err(net_error::PermanentlyDrained);
+        }
+        Ok(total_read)
+    }
+
+    /// Write as much queued data as the socket will take
+    pub fn send<W: Write>(&mut self, fd: &mut W) -> Result<usize, net_error> {
+        let mut total_sent = 0;
+        while self.outbox.len() > 0 {
+            let nw = {
+                let (front, _) = self.outbox.as_slices();
+                match fd.write(front) {
+                    Ok(nw) => nw,
+                    Err(e) => {
+                        if e.kind() == io::ErrorKind::WouldBlock
+                            || (cfg!(windows) && e.kind() == io::ErrorKind::TimedOut)
+                        {
+                            break;
+                        }
+                        return Err(net_error::WriteError(e));
+                    }
+                }
+            };
+            if nw == 0 {
+                break;
+            }
+            self.outbox.drain(0..nw);
+            total_sent += nw;
+        }
+        Ok(total_sent)
+    }
+
+    /// Queue an event for the client, if it subscribed to it.  Fails if the client has fallen
+    /// too far behind.
+    pub fn notify(&mut self, event: &WebSocketEvent) -> Result<(), net_error> {
+        if self.close_sent || !self.subscription.matches(event) {
+            return Ok(());
+        }
+        if self.outbox.len() > MAX_WEBSOCKET_OUTBOX_LEN {
+            return Err(net_error::OutboxOverflow);
+        }
+        self.queue_frame(WebSocketFrame::text(&event.to_json().to_string()));
+        Ok(())
+    }
+
+    /// Ping the client if it has been quiet for a while.  Returns false if it has been quiet for
+    /// too long, and should be dropped.
+    pub fn check_alive(&mut self, now: u64) -> bool {
+        if self.last_recv_time + 3 * WEBSOCKET_PING_INTERVAL < now {
+            return false;
+        }
+        if self.last_recv_time + WEBSOCKET_PING_INTERVAL < now
+            && self.last_ping_time + WEBSOCKET_PING_INTERVAL < now
+        {
+            self.last_ping_time = now;
+            self.queue_frame(WebSocketFrame::new(WebSocketOpcode::Ping, vec![]));
+        }
+        true
+    }
+
+    /// Have we sent our close frame, and is there nothing left to send?
+    pub fn is_closed(&self) -> bool {
+        self.close_sent && self.outbox.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chainstate::stacks::{
+        StacksPrivateKey, TokenTransferMemo, TransactionAuth, TransactionVersion,
+    };
+    use std::io::Cursor;
+    use vm::types::PrincipalData;
+
+    #[test]
+    fn test_websocket_accept_key() {
+        // example from RFC 6455, section 1.3
+        assert_eq!(
+            websocket_accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_websocket_frame_codec() {
+        let payloads = vec![vec![], vec![0x41; 125], vec![0x42; 126], vec![0x43; 70000]];
+        for payload in payloads.into_iter() {
+            let frame = WebSocketFrame::new(WebSocketOpcode::Binary, payload);
+
+            let unmasked = frame.encode(None);
+            assert_eq!(
+                WebSocketFrame::decode(&unmasked, false, 1 << 20).unwrap(),
+                Some((frame.clone(), unmasked.len()))
+            );
+            // servers insist on masked frames
+            assert!(WebSocketFrame::decode(&unmasked, true, 1 << 20).is_err());
+
+            let masked = frame.encode(Some([1, 2, 3, 4]));
+            assert_eq!(
+                WebSocketFrame::decode(&masked, true, 1 << 20).unwrap(),
+                Some((frame.clone(), masked.len()))
+            );
+
+            // partial frames need more data
+            for i in 0..masked.len().min(200) {
+                assert_eq!(
+                    WebSocketFrame::decode(&masked[0..i], true, 1 << 20).unwrap(),
+                    None
+                );
+            }
+        }
+
+        let big = WebSocketFrame::new(WebSocketOpcode::Text, vec![0x44; 1000]);
+        match WebSocketFrame::decode(&big.encode(Some([0; 4])), true, 999) {
+            Err(net_error::OverflowError(_)) => {}
+            res => panic!("Expected overflow, got {:?}", res),
+        }
+    }
+
+    fn make_transfer(recipient: &PrincipalData) -> StacksTransaction {
+        let privk = StacksPrivateKey::from_hex(
+            "6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001",
+        )
+        .unwrap();
+        let auth = TransactionAuth::from_p2pkh(&privk).unwrap();
+        StacksTransaction::new(
+            TransactionVersion::Testnet,
+            auth,
+            TransactionPayload::TokenTransfer(recipient.clone(), 123, TokenTransferMemo([0u8; 34])),
+        )
+    }
+
+    fn masked_text(text: &str) -> Vec<u8> {
+        WebSocketFrame::text(text).encode(Some([9, 8, 7, 6]))
+    }
+
+    fn read_frames(bytes: &[u8]) -> Vec<WebSocketFrame> {
+        let mut frames = vec![];
+        let mut offset = 0;
+        while let Some((frame, consumed)) =
+            WebSocketFrame::decode(&bytes[offset..], false, 1 << 20).unwrap()
+        {
+            frames.push(frame);
+            offset += consumed;
+        }
+        assert_eq!(offset, bytes.len());
+        frames
+    }
+
+    #[test]
+    fn test_websocket_subscriptions() {
+        let recipient = PrincipalData::parse("ST2MVNFYF6H9DCMAV3HVNHTJVVE3CFWT1JYMH1EZB").unwrap();
+        let tx = make_transfer(&recipient);
+        let mempool_event = WebSocketEvent::from_mempool_tx(&tx);
+        let reorg_event = WebSocketEvent::Reorg {
+            old_tip: StacksBlockId([0x01; 32]),
+            new_tip: StacksBlockId([0x02; 32]),
+            block_height: 10,
+        };
+
+        let mut convo = WebSocketConversation::new(1, "127.0.0.1:20443".parse().unwrap());
+
+        // nothing is sent before the client subscribes
+        convo.notify(&mempool_event).unwrap();
+        assert_eq!(convo.outbox.len(), 0);
+
+        // subscribe to mempool events for another address
+        let request = masked_text(
+            r#"{"subscribe":["mempool","reorg"],"addresses":["ST000000000000000000002AMW42H"]}"#,
+        );
+        convo.recv(&mut Cursor::new(request)).unwrap();
+        assert_eq!(
+            convo.get_subscription().topics,
+            vec![WebSocketTopic::Mempool, WebSocketTopic::Reorg]
+                .into_iter()
+                .collect()
+        );
+        convo.notify(&mempool_event).unwrap();
+        convo.notify(&reorg_event).unwrap();
+
+        // subscribe to the recipient's address
+        let mut requests = masked_text(&format!(
+            r#"{{"subscribe":["mempool"],"addresses":["{}"]}}"#,
+            &recipient
+        ));
+        // bad requests are answered with an error
+        requests.append(&mut masked_text(r#"{"subscribe":["nope"]}"#));
+        convo.recv(&mut Cursor::new(requests)).unwrap();
+        convo.notify(&mempool_event).unwrap();
+        convo.notify(&reorg_event).unwrap();
+
+        let mut sent = vec![];
+        convo.send(&mut sent).unwrap();
+        let frames = read_frames(&sent);
+        let messages: Vec<serde_json::Value> = frames
+            .iter()
+            .map(|frame| serde_json::from_slice(&frame.payload).unwrap())
+            .collect();
+
+        assert_eq!(messages.len(), 5);
+        assert_eq!(messages[0]["subscribed"], json!(["mempool", "reorg"]));
+        assert_eq!(messages[1]["event"], "reorg");
+        assert_eq!(messages[2]["subscribed"], json!(["mempool"]));
+        assert!(messages[3]["error"]
+            .as_str()
+            .unwrap()
+            .contains("Unknown subscription topic"));
+        assert_eq!(messages[4]["event"], "mempool");
+        assert_eq!(messages[4]["data"]["txid"], format!("0x{}", tx.txid()));
+
+        // ping is answered with pong; close with close
+        let mut frames =
+            WebSocketFrame::new(WebSocketOpcode::Ping, vec![1, 2, 3]).encode(Some([1; 4]));
+        frames
+            .append(&mut WebSocketFrame::close(WEBSOCKET_CLOSE_NORMAL, "bye").encode(Some([2; 4])));
+        convo.recv(&mut Cursor::new(frames)).unwrap();
+
+        let mut sent = vec![];
+        convo.send(&mut sent).unwrap();
+        let frames = read_frames(&sent);
+        assert_eq!(
+            frames[0],
+            WebSocketFrame::new(WebSocketOpcode::Pong, vec![1, 2, 3])
+        );
+        assert_eq!(frames[1].opcode, WebSocketOpcode::Close);
+        assert!(convo.is_closed());
+    }
+
+    #[test]
+    fn test_websocket_event_receiver_reorgs() {
+        let (sender, mut receiver) = websocket_event_channel();
+        let block_event = |id: u8, parent: u8, height: u64| WebSocketEvent::Block {
+            index_block_hash: StacksBlockId([id; 32]),
+            parent_index_block_hash: StacksBlockId([parent; 32]),
+            block_hash: BlockHeaderHash([id; 32]),
+            consensus_hash: ConsensusHash([id; 20]),
+            block_height: height,
+            burn_block_height: height as u32,
+            txids: vec![],
+            principals: HashSet::new(),
+        };
+
+        sender.send(block_event(1, 0, 1));
+        sender.send(block_event(2, 1, 2));
+        // forks off of block 1
+        sender.send(block_event(3, 1, 2));
+
+        let events = receiver.take_events();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0], block_event(1, 0, 1));
+        assert_eq!(events[1], block_event(2, 1, 2));
+        assert_eq!(
+            events[2],
+            WebSocketEvent::Reorg {
+                old_tip: StacksBlockId([2; 32]),
+                new_tip: StacksBlockId([3; 32]),
+                block_height: 2,
+            }
+        );
+        assert_eq!(events[3], block_event(3, 1, 2));
+        assert_eq!(receiver.take_events().len(), 0);
+    }
+}
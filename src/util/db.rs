@@ -493,6 +493,77 @@ pub fn tx_busy_handler(run_count: i32) -> bool {
     true
 }
 
+/// Put a database into write-ahead-log mode, so that readers see a consistent snapshot without
+/// blocking its writer, and its writer doesn't block them.  The mode persists in the database
+/// file, so a read-only connection opened later uses it too.
+pub fn sqlite_set_wal_mode(conn: &Connection) -> Result<(), sqlite_error> {
+    let mode: String = conn.query_row("PRAGMA journal_mode = WAL", NO_PARAMS, |row| row.get(0))?;
+    if mode.to_lowercase() != "wal" {
+        warn!(
+            "Database did not switch to WAL mode (still in '{}' mode)",
+            &mode
+        );
+    }
+    Ok(())
+}
+
+/// Copy everything in a write-ahead-logged database's log into the database file, and truncate
+/// the log.  Readers that are still using the log can keep the checkpoint from completing; the
+/// rest of the log is then copied in by a later checkpoint.
+pub fn sqlite_wal_checkpoint(conn: &Connection) -> Result<(), sqlite_error> {
+    let (busy, log_frames, checkpointed_frames): (i64, i64, i64) =
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", NO_PARAMS, |row| {
+            (row.get(0), row.get(1), row.get(2))
+        })?;
+    if busy != 0 {
+        debug!(
+            "Database checkpoint was blocked ({} of {} frames copied)",
+            checkpointed_frames, log_frames
+        );
+    }
+    Ok(())
+}
+
+/// Run SQLite's quick integrity check over a database.  Returns the damage it found, if any.
+pub fn sqlite_quick_check(conn: &Connection) -> Result<Vec<String>, sqlite_error> {
+    let mut stmt = conn.prepare("PRAGMA quick_check")?;
+    let rows = stmt.query_map(NO_PARAMS, |row| row.get::<_, String>(0))?;
+
+    let mut damage = vec![];
+    for row in rows {
+        let msg = row?;
+        if msg != "ok" {
+            damage.push(msg);
+        }
+    }
+    Ok(damage)
+}
+
+/// What a database integrity check found, and what it did about it
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct IntegrityReport {
+    /// inconsistencies that can be repaired in place
+    pub problems: Vec<String>,
+    /// what was done to repair them, if repairs were asked for
+    pub repairs: Vec<String>,
+    /// damage that can't be repaired in place.  The database has to be restored from a snapshot,
+    /// or synced again.
+    pub unrepairable: Vec<String>,
+}
+
+impl IntegrityReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.len() == 0 && self.unrepairable.len() == 0
+    }
+
+    /// Add another check's findings to this one's
+    pub fn extend(&mut self, other: IntegrityReport) {
+        self.problems.extend(other.problems);
+        self.repairs.extend(other.repairs);
+        self.unrepairable.extend(other.unrepairable);
+    }
+}
+
 /// Begin an immediate-mode transaction, and handle busy errors with exponential backoff.
 /// Handling busy errors when the tx begins is preferable to doing it when the tx commits, since
 /// then we don't have to worry about any extra rollback logic.
@@ -19,12 +19,55 @@ use slog_term::{CountingWriter, Decorator, RecordDecorator, Serializer};
 use std::env;
 use std::io;
 use std::io::Write;
-use std::sync::Mutex;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, RwLock};
 use std::thread;
 use std::time::{Duration, SystemTime};
 
 lazy_static! {
     pub static ref LOGGER: Logger = make_logger();
+    /// Per-module log levels set with `set_module_loglevels()`, longest module path first
+    static ref MODULE_LOGLEVELS: RwLock<Vec<(String, slog::Level)>> = RwLock::new(vec![]);
+    /// The Stacks chain tip reported in JSON log records
+    static ref LOG_STACKS_TIP: Mutex<String> = Mutex::new(String::new());
+}
+
+/// Log level set with `set_loglevel()`, as given by `slog::Level::as_usize()`.  0 if unset.
+static LOGLEVEL_OVERRIDE: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether any per-module log levels are set, so the log macros can skip looking them up
+static MODULE_LOGLEVELS_SET: AtomicBool = AtomicBool::new(false);
+
+/// Log format set with `set_log_format()`: 0 if unset, 1 for text, 2 for JSON
+static LOG_FORMAT_OVERRIDE: AtomicUsize = AtomicUsize::new(0);
+
+/// The burnchain height reported in JSON log records
+static LOG_BURN_HEIGHT: AtomicU64 = AtomicU64::new(0);
+
+/// How log records are written to stderr
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    /// One human-readable line per record
+    Text,
+    /// One JSON object per record, with the fields `module`, `burn_height` and `stacks_tip`,
+    /// plus `event` on records that name one
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<LogFormat, String> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!(
+                "unknown log format '{}' (expected 'text' or 'json')",
+                s
+            )),
+        }
+    }
 }
 
 struct TermFormat<D: Decorator> {
@@ -103,6 +146,15 @@ fn make_json_logger() -> Logger {
     let def_keys = o!("file" => FnValue(move |info| {
                           info.file()
                       }),
+                      "module" => FnValue(move |info| {
+                          info.module()
+                      }),
+                      "burn_height" => FnValue(move |_| {
+                          LOG_BURN_HEIGHT.load(Ordering::SeqCst)
+                      }),
+                      "stacks_tip" => FnValue(move |_| {
+                          LOG_STACKS_TIP.lock().unwrap().clone()
+                      }),
                       "line" => FnValue(move |info| {
                           info.line()
                       }),
@@ -112,7 +164,8 @@ fn make_json_logger() -> Logger {
     );
 
     let drain = Mutex::new(slog_json::Json::default(std::io::stderr())).map(slog::Fuse);
-    let filtered_drain = slog::LevelFilter::new(drain, get_loglevel()).fuse();
+    // the logging macros check get_loglevel() themselves, which can change at runtime
+    let filtered_drain = slog::LevelFilter::new(drain, slog::Level::Trace).fuse();
     slog::Logger::root(filtered_drain, def_keys)
 }
 
@@ -122,7 +175,7 @@ fn make_json_logger() -> Logger {
 }
 
 fn make_logger() -> Logger {
-    if env::var("BLOCKSTACK_LOG_JSON") == Ok("1".into()) {
+    if get_log_format() == LogFormat::Json {
         make_json_logger()
     } else {
         let plain = slog_term::PlainSyncDecorator::new(std::io::stderr());
@@ -133,7 +186,95 @@ fn make_logger() -> Logger {
     }
 }
 
+/// Override the log level the environment selects, or go back to it with `None`
+pub fn set_loglevel(level: Option<slog::Level>) {
+    let level_num = level.map(|level| level.as_usize()).unwrap_or(0);
+    LOGLEVEL_OVERRIDE.store(level_num, Ordering::SeqCst);
+}
+
+/// Select how log records are written, instead of the format the environment selects.  This
+/// only takes effect if called before the first record is logged.
+pub fn set_log_format(format: LogFormat) {
+    let format_num = match format {
+        LogFormat::Text => 1,
+        LogFormat::Json => 2,
+    };
+    LOG_FORMAT_OVERRIDE.store(format_num, Ordering::SeqCst);
+}
+
+pub fn get_log_format() -> LogFormat {
+    match LOG_FORMAT_OVERRIDE.load(Ordering::SeqCst) {
+        1 => LogFormat::Text,
+        2 => LogFormat::Json,
+        _ => {
+            if env::var("BLOCKSTACK_LOG_JSON") == Ok("1".into()) {
+                LogFormat::Json
+            } else {
+                LogFormat::Text
+            }
+        }
+    }
+}
+
+/// Update the chain position reported in every JSON log record
+pub fn set_log_burn_height(burn_height: u64) {
+    LOG_BURN_HEIGHT.store(burn_height, Ordering::SeqCst);
+}
+
+/// Update the Stacks chain tip reported in every JSON log record
+pub fn set_log_stacks_tip(stacks_tip: &str) {
+    let mut tip = LOG_STACKS_TIP.lock().unwrap();
+    tip.clear();
+    tip.push_str(stacks_tip);
+}
+
+/// Parse a per-module log level, written as `<module path>=<level>`, e.g.
+/// `blockstack_lib::net=debug`
+pub fn parse_module_loglevel(spec: &str) -> Result<(String, slog::Level), String> {
+    let mut parts = spec.splitn(2, '=');
+    let module = parts.next().unwrap_or("").trim();
+    let level = parts.next().unwrap_or("").trim();
+    if module.is_empty() || level.is_empty() {
+        return Err(format!(
+            "invalid module log level '{}' (expected <module>=<level>)",
+            spec
+        ));
+    }
+    let level = level
+        .parse::<slog::Level>()
+        .map_err(|_| format!("invalid log level '{}' for module {}", level, module))?;
+    Ok((module.to_string(), level))
+}
+
+/// Replace the per-module log levels.  A module's level applies to all of its submodules that
+/// don't have a level of their own, and takes precedence over `set_loglevel()`.
+pub fn set_module_loglevels(levels: Vec<(String, slog::Level)>) {
+    let mut levels = levels;
+    levels.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+    let is_set = levels.len() > 0;
+    *MODULE_LOGLEVELS.write().unwrap() = levels;
+    MODULE_LOGLEVELS_SET.store(is_set, Ordering::SeqCst);
+}
+
+/// The log level of the given module, as given by `module_path!()`
+pub fn get_module_loglevel(module: &str) -> slog::Level {
+    if MODULE_LOGLEVELS_SET.load(Ordering::SeqCst) {
+        let levels = MODULE_LOGLEVELS.read().unwrap();
+        for (prefix, level) in levels.iter() {
+            if module == prefix
+                || (module.starts_with(prefix.as_str()) && module[prefix.len()..].starts_with("::"))
+            {
+                return *level;
+            }
+        }
+    }
+    get_loglevel()
+}
+
 pub fn get_loglevel() -> slog::Level {
+    if let Some(level) = slog::Level::from_usize(LOGLEVEL_OVERRIDE.load(Ordering::SeqCst)) {
+        return level;
+    }
     if env::var("BLOCKSTACK_TRACE") == Ok("1".into()) {
         slog::Level::Trace
     } else if env::var("BLOCKSTACK_DEBUG") == Ok("1".into()) {
@@ -146,7 +287,7 @@ pub fn get_loglevel() -> slog::Level {
 #[macro_export]
 macro_rules! trace {
     ($($arg:tt)*) => ({
-        let cur_level = ::util::log::get_loglevel();
+        let cur_level = ::util::log::get_module_loglevel(module_path!());
         if slog::Level::Trace.is_at_least(cur_level) {
             slog_trace!($crate::util::log::LOGGER, $($arg)*)
         }
@@ -156,7 +297,7 @@ macro_rules! trace {
 #[macro_export]
 macro_rules! error {
     ($($arg:tt)*) => ({
-        let cur_level = ::util::log::get_loglevel();
+        let cur_level = ::util::log::get_module_loglevel(module_path!());
         if slog::Level::Error.is_at_least(cur_level) {
             slog_error!($crate::util::log::LOGGER, $($arg)*)
         }
@@ -166,7 +307,7 @@ macro_rules! error {
 #[macro_export]
 macro_rules! warn {
     ($($arg:tt)*) => ({
-        let cur_level = ::util::log::get_loglevel();
+        let cur_level = ::util::log::get_module_loglevel(module_path!());
         if slog::Level::Warning.is_at_least(cur_level) {
             slog_warn!($crate::util::log::LOGGER, $($arg)*)
         }
@@ -176,7 +317,7 @@ macro_rules! warn {
 #[macro_export]
 macro_rules! info {
     ($($arg:tt)*) => ({
-        let cur_level = ::util::log::get_loglevel();
+        let cur_level = ::util::log::get_module_loglevel(module_path!());
         if slog::Level::Info.is_at_least(cur_level) {
             slog_info!($crate::util::log::LOGGER, $($arg)*)
         }
@@ -186,7 +327,7 @@ macro_rules! info {
 #[macro_export]
 macro_rules! debug {
     ($($arg:tt)*) => ({
-        let cur_level = ::util::log::get_loglevel();
+        let cur_level = ::util::log::get_module_loglevel(module_path!());
         if slog::Level::Debug.is_at_least(cur_level) {
             slog_debug!($crate::util::log::LOGGER, $($arg)*)
         }
@@ -196,9 +337,53 @@ macro_rules! debug {
 #[macro_export]
 macro_rules! fatal {
     ($($arg:tt)*) => ({
-        let cur_level = ::util::log::get_loglevel();
+        let cur_level = ::util::log::get_module_loglevel(module_path!());
         if slog::Level::Critical.is_at_least(cur_level) {
             slog_crit!($crate::util::log::LOGGER, $($arg)*)
         }
     })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_module_loglevels() {
+        assert_eq!(
+            parse_module_loglevel("blockstack_lib::net=debug"),
+            Ok(("blockstack_lib::net".to_string(), slog::Level::Debug))
+        );
+        assert!(parse_module_loglevel("blockstack_lib::net").is_err());
+        assert!(parse_module_loglevel("=debug").is_err());
+        assert!(parse_module_loglevel("blockstack_lib::net=loud").is_err());
+
+        set_module_loglevels(vec![
+            ("blockstack_lib::net".to_string(), slog::Level::Debug),
+            ("blockstack_lib::net::p2p".to_string(), slog::Level::Trace),
+        ]);
+        assert_eq!(
+            get_module_loglevel("blockstack_lib::net::chat"),
+            slog::Level::Debug
+        );
+        assert_eq!(
+            get_module_loglevel("blockstack_lib::net"),
+            slog::Level::Debug
+        );
+        assert_eq!(
+            get_module_loglevel("blockstack_lib::net::p2p"),
+            slog::Level::Trace
+        );
+        assert_eq!(
+            get_module_loglevel("blockstack_lib::network"),
+            get_loglevel()
+        );
+        assert_eq!(
+            get_module_loglevel("blockstack_lib::chainstate"),
+            get_loglevel()
+        );
+
+        set_module_loglevels(vec![]);
+        assert_eq!(get_module_loglevel("blockstack_lib::net"), get_loglevel());
+    }
+}
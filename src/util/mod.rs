@@ -25,6 +25,7 @@ pub mod pair;
 pub mod pipe;
 pub mod retry;
 pub mod secp256k1;
+pub mod secretbox;
 pub mod strings;
 pub mod uint;
 pub mod vrf;
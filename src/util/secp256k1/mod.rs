@@ -0,0 +1,189 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! secp256k1 keys and signatures.
+//!
+//! By default these are backed by libsecp256k1 (C).  On wasm targets, or when built with
+//! the `pure_rust_crypto` feature, they are backed by the pure-Rust `k256` crate instead.
+//! Leave out the default `native_crypto` feature to keep libsecp256k1 out of the build.
+//! Both backends produce identical keys and (RFC6979) signatures; the consensus test
+//! vectors below must pass on either.
+
+#[cfg(not(any(target_arch = "wasm32", feature = "pure_rust_crypto")))]
+mod native;
+#[cfg(not(any(target_arch = "wasm32", feature = "pure_rust_crypto")))]
+pub use self::native::*;
+
+#[cfg(any(target_arch = "wasm32", feature = "pure_rust_crypto"))]
+mod pure;
+#[cfg(any(target_arch = "wasm32", feature = "pure_rust_crypto"))]
+pub use self::pure::*;
+
+use util::db::Error as db_error;
+use util::db::FromColumn;
+
+use rusqlite::Row;
+
+pub struct MessageSignature(pub [u8; 65]);
+impl_array_newtype!(MessageSignature, u8, 65);
+impl_array_hexstring_fmt!(MessageSignature);
+impl_byte_array_newtype!(MessageSignature, u8, 65);
+impl_byte_array_serde!(MessageSignature);
+pub const MESSAGE_SIGNATURE_ENCODED_SIZE: u32 = 65;
+
+impl MessageSignature {
+    pub fn empty() -> MessageSignature {
+        // NOTE: this cannot be a valid signature
+        MessageSignature([0u8; 65])
+    }
+
+    #[cfg(test)]
+    // test method for generating place-holder data
+    pub fn from_raw(sig: &Vec<u8>) -> MessageSignature {
+        let mut buf = [0u8; 65];
+        if sig.len() < 65 {
+            buf.copy_from_slice(&sig[..]);
+        } else {
+            buf.copy_from_slice(&sig[..65]);
+        }
+        MessageSignature(buf)
+    }
+}
+
+/// Make public keys loadable from a sqlite database
+impl FromColumn<Secp256k1PublicKey> for Secp256k1PublicKey {
+    fn from_column<'a>(row: &'a Row, column_name: &str) -> Result<Secp256k1PublicKey, db_error> {
+        let pubkey_hex: String = row.get(column_name);
+        let pubkey =
+            Secp256k1PublicKey::from_hex(&pubkey_hex).map_err(|_e| db_error::ParseError)?;
+        Ok(pubkey)
+    }
+}
+
+/// Make private keys loadable from a sqlite database
+impl FromColumn<Secp256k1PrivateKey> for Secp256k1PrivateKey {
+    fn from_column<'a>(row: &'a Row, column_name: &str) -> Result<Secp256k1PrivateKey, db_error> {
+        let privkey_hex: String = row.get(column_name);
+        let privkey =
+            Secp256k1PrivateKey::from_hex(&privkey_hex).map_err(|_e| db_error::ParseError)?;
+        Ok(privkey)
+    }
+}
+
+#[cfg(test)]
+mod consensus_tests {
+    use super::*;
+
+    use burnchains::PrivateKey;
+    use burnchains::PublicKey;
+    use util::hash::{hex_bytes, to_hex};
+
+    // sha256("hello world")
+    const HELLO_WORLD_HASH: &str =
+        "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+    const PRIVATE_KEY: &str = "6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001";
+    const PUBLIC_KEY: &str = "02db3ee269c096bb07fad458f7b1cfada089a72dba6404c2d1a516efd59f2699e5";
+    // RFC6979 signature of HELLO_WORLD_HASH by PRIVATE_KEY, as (recovery id, r, s)
+    const SIGNATURE: &str = "00ad9b466ec9b5b3ad66ab7807f6c4850b36bda21102f8f11e1c26ba04fe2e39d733965916064470728a1cc06c48c8df82027c1c8eaeb7351fc37547218a3db758";
+    // the same signature with s negated (and the recovery id flipped to match)
+    const SIGNATURE_HIGH_S: &str = "01ad9b466ec9b5b3ad66ab7807f6c4850b36bda21102f8f11e1c26ba04fe2e39d7cc69a6e9f9bb8f8d75e33f93b737207cb832c05800916b1bfc5d176b45f889e9";
+
+    fn clarity_signature(sig: &str) -> Vec<u8> {
+        // Clarity orders signatures as (r, s, recovery id)
+        let sig_bytes = hex_bytes(sig).unwrap();
+        let mut clarity_sig = sig_bytes[1..].to_vec();
+        clarity_sig.push(sig_bytes[0]);
+        clarity_sig
+    }
+
+    #[test]
+    fn test_vector_public_key() {
+        let privk = Secp256k1PrivateKey::from_hex(PRIVATE_KEY).unwrap();
+        let pubk = Secp256k1PublicKey::from_private(&privk);
+        assert_eq!(pubk.to_hex(), PUBLIC_KEY);
+        assert_eq!(privk.to_hex(), PRIVATE_KEY);
+
+        let mut privk_uncompressed = privk.clone();
+        privk_uncompressed.set_compress_public(false);
+        assert_eq!(
+            Secp256k1PublicKey::from_private(&privk_uncompressed).to_hex(),
+            "04db3ee269c096bb07fad458f7b1cfada089a72dba6404c2d1a516efd59f2699e51f33f7aa914645646cf480508b9ee5ecf8e90f1efcab554bb48009329226209e"
+        );
+    }
+
+    #[test]
+    fn test_vector_sign() {
+        let privk = Secp256k1PrivateKey::from_hex(PRIVATE_KEY).unwrap();
+        let sig = privk.sign(&hex_bytes(HELLO_WORLD_HASH).unwrap()).unwrap();
+        assert_eq!(sig.to_hex(), SIGNATURE);
+
+        // only 32-byte hashes can be signed
+        assert!(privk.sign(&[0u8; 31]).is_err());
+    }
+
+    #[test]
+    fn test_vector_recover_and_verify() {
+        let msg = hex_bytes(HELLO_WORLD_HASH).unwrap();
+        let pubk = Secp256k1PublicKey::from_hex(PUBLIC_KEY).unwrap();
+        let sig = MessageSignature::from_raw(&hex_bytes(SIGNATURE).unwrap());
+
+        assert_eq!(
+            Secp256k1PublicKey::recover_to_pubkey(&msg, &sig)
+                .unwrap()
+                .to_hex(),
+            PUBLIC_KEY
+        );
+        assert_eq!(pubk.verify(&msg, &sig), Ok(true));
+        assert!(sig.is_low_s());
+
+        // a high-S signature still recovers the signer, but does not verify
+        let sig_high_s = MessageSignature::from_raw(&hex_bytes(SIGNATURE_HIGH_S).unwrap());
+        assert_eq!(
+            Secp256k1PublicKey::recover_to_pubkey(&msg, &sig_high_s)
+                .unwrap()
+                .to_hex(),
+            PUBLIC_KEY
+        );
+        assert_eq!(
+            pubk.verify(&msg, &sig_high_s),
+            Err("Invalid signature: high-S")
+        );
+        assert!(!sig_high_s.is_low_s());
+        assert!(!MessageSignature::empty().is_low_s());
+    }
+
+    #[test]
+    fn test_vector_clarity_recover_and_verify() {
+        let msg = hex_bytes(HELLO_WORLD_HASH).unwrap();
+        let pubk = hex_bytes(PUBLIC_KEY).unwrap();
+
+        let recovered = secp256k1_recover(&msg, &clarity_signature(SIGNATURE)).unwrap();
+        assert_eq!(to_hex(&recovered), PUBLIC_KEY);
+        assert!(secp256k1_verify(&msg, &clarity_signature(SIGNATURE), &pubk).is_ok());
+
+        // secp256k1-recover? accepts high-S signatures, but secp256k1-verify does not
+        let recovered = secp256k1_recover(&msg, &clarity_signature(SIGNATURE_HIGH_S)).unwrap();
+        assert_eq!(to_hex(&recovered), PUBLIC_KEY);
+        assert!(secp256k1_verify(&msg, &clarity_signature(SIGNATURE_HIGH_S), &pubk).is_err());
+
+        // invalid recovery ids and all-zero signatures are rejected
+        let mut bad_recid = clarity_signature(SIGNATURE);
+        bad_recid[64] = 4;
+        assert!(secp256k1_recover(&msg, &bad_recid).is_err());
+        assert!(secp256k1_recover(&msg, &[0u8; 65]).is_err());
+        assert!(secp256k1_verify(&msg, &[0u8; 65], &pubk).is_err());
+    }
+}
@@ -34,10 +34,7 @@ use serde::de::Error as de_Error;
 use serde::ser::Error as ser_Error;
 use serde::Serialize;
 
-use util::db::Error as db_error;
-use util::db::FromColumn;
-
-use rusqlite::Row;
+use super::MessageSignature;
 
 use rand::thread_rng;
 use rand::RngCore;
@@ -47,6 +44,8 @@ use chainstate::stacks::StacksPublicKey;
 // per-thread Secp256k1 context
 thread_local!(static _secp256k1: Secp256k1<secp256k1::All> = Secp256k1::new());
 
+pub type Secp256k1Error = LibSecp256k1Error;
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub struct Secp256k1PublicKey {
     // serde is broken for secp256k1, so do it ourselves
@@ -69,31 +68,7 @@ pub struct Secp256k1PrivateKey {
     compress_public: bool,
 }
 
-pub struct MessageSignature(pub [u8; 65]);
-impl_array_newtype!(MessageSignature, u8, 65);
-impl_array_hexstring_fmt!(MessageSignature);
-impl_byte_array_newtype!(MessageSignature, u8, 65);
-impl_byte_array_serde!(MessageSignature);
-pub const MESSAGE_SIGNATURE_ENCODED_SIZE: u32 = 65;
-
 impl MessageSignature {
-    pub fn empty() -> MessageSignature {
-        // NOTE: this cannot be a valid signature
-        MessageSignature([0u8; 65])
-    }
-
-    #[cfg(test)]
-    // test method for generating place-holder data
-    pub fn from_raw(sig: &Vec<u8>) -> MessageSignature {
-        let mut buf = [0u8; 65];
-        if sig.len() < 65 {
-            buf.copy_from_slice(&sig[..]);
-        } else {
-            buf.copy_from_slice(&sig[..65]);
-        }
-        MessageSignature(buf)
-    }
-
     pub fn from_secp256k1_recoverable(sig: &LibSecp256k1RecoverableSignature) -> MessageSignature {
         let (recid, bytes) = sig.serialize_compact();
         let mut ret_bytes = [0u8; 65];
@@ -122,6 +97,19 @@ impl MessageSignature {
             Err(_) => None,
         }
     }
+
+    /// Is this a well-formed signature whose s is in the lower half of the curve order?
+    pub fn is_low_s(&self) -> bool {
+        match self.to_secp256k1_recoverable() {
+            Some(sig) => {
+                let sig_standard = sig.to_standard();
+                let mut sig_low_s = sig_standard.clone();
+                sig_low_s.normalize_s();
+                sig_low_s == sig_standard
+            }
+            None => false,
+        }
+    }
 }
 
 impl Secp256k1PublicKey {
@@ -257,16 +245,6 @@ impl PublicKey for Secp256k1PublicKey {
     }
 }
 
-/// Make public keys loadable from a sqlite database
-impl FromColumn<Secp256k1PublicKey> for Secp256k1PublicKey {
-    fn from_column<'a>(row: &'a Row, column_name: &str) -> Result<Secp256k1PublicKey, db_error> {
-        let pubkey_hex: String = row.get(column_name);
-        let pubkey =
-            Secp256k1PublicKey::from_hex(&pubkey_hex).map_err(|_e| db_error::ParseError)?;
-        Ok(pubkey)
-    }
-}
-
 impl Secp256k1PrivateKey {
     pub fn new() -> Secp256k1PrivateKey {
         let mut rng = rand::thread_rng();
@@ -357,16 +335,6 @@ impl PrivateKey for Secp256k1PrivateKey {
     }
 }
 
-/// Make private keys loadable from a sqlite database
-impl FromColumn<Secp256k1PrivateKey> for Secp256k1PrivateKey {
-    fn from_column<'a>(row: &'a Row, column_name: &str) -> Result<Secp256k1PrivateKey, db_error> {
-        let privkey_hex: String = row.get(column_name);
-        let privkey =
-            Secp256k1PrivateKey::from_hex(&privkey_hex).map_err(|_e| db_error::ParseError)?;
-        Ok(privkey)
-    }
-}
-
 fn secp256k1_pubkey_serialize<S: serde::Serializer>(
     pubk: &LibSecp256k1PublicKey,
     s: S,
@@ -0,0 +1,363 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use k256::ecdsa::signature::hazmat::PrehashVerifier;
+use k256::ecdsa::Error as K256Error;
+use k256::ecdsa::RecoveryId as K256RecoveryId;
+use k256::ecdsa::Signature as K256Signature;
+use k256::ecdsa::SigningKey as K256SigningKey;
+use k256::ecdsa::VerifyingKey as K256VerifyingKey;
+
+use burnchains::PrivateKey;
+use burnchains::PublicKey;
+use util::hash::{hex_bytes, to_hex};
+
+use serde::de::Deserialize;
+use serde::de::Error as de_Error;
+use serde::Serialize;
+
+use super::MessageSignature;
+
+use rand::RngCore;
+
+pub type Secp256k1Error = K256Error;
+
+const COMPRESSED_PUBLIC_KEY_SIZE: usize = 33;
+const MESSAGE_SIZE: usize = 32;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub struct Secp256k1PublicKey {
+    #[serde(
+        serialize_with = "secp256k1_pubkey_serialize",
+        deserialize_with = "secp256k1_pubkey_deserialize"
+    )]
+    key: K256VerifyingKey,
+    compressed: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub struct Secp256k1PrivateKey {
+    // k256 signing keys are not Copy, so hold the (validated) scalar bytes instead
+    #[serde(
+        serialize_with = "secp256k1_privkey_serialize",
+        deserialize_with = "secp256k1_privkey_deserialize"
+    )]
+    key: [u8; 32],
+    compress_public: bool,
+}
+
+impl MessageSignature {
+    pub fn from_secp256k1_recoverable(
+        sig: &K256Signature,
+        recid: K256RecoveryId,
+    ) -> MessageSignature {
+        let mut ret_bytes = [0u8; 65];
+        ret_bytes[0] = recid.to_byte(); // recovery ID will be 0, 1, 2, or 3
+        ret_bytes[1..].copy_from_slice(&sig.to_bytes()[..]);
+        MessageSignature(ret_bytes)
+    }
+
+    pub fn to_secp256k1_recoverable(&self) -> Option<(K256Signature, K256RecoveryId)> {
+        let recid = K256RecoveryId::from_byte(self.0[0])?;
+        let sig = K256Signature::from_slice(&self.0[1..]).ok()?;
+        Some((sig, recid))
+    }
+
+    /// Is this a well-formed signature whose s is in the lower half of the curve order?
+    pub fn is_low_s(&self) -> bool {
+        match self.to_secp256k1_recoverable() {
+            Some((sig, _)) => sig.normalize_s().is_none(),
+            None => false,
+        }
+    }
+}
+
+/// Recover the signing key like libsecp256k1 does, which (unlike k256) accepts high-S
+/// signatures.  Negating s flips the parity of R, so recover from the low-S equivalent.
+fn recover_prehash(
+    msg: &[u8],
+    sig: &K256Signature,
+    recid: K256RecoveryId,
+) -> Result<K256VerifyingKey, K256Error> {
+    match sig.normalize_s() {
+        Some(low_s_sig) => {
+            let low_s_recid = K256RecoveryId::new(!recid.is_y_odd(), recid.is_x_reduced());
+            K256VerifyingKey::recover_from_prehash(msg, &low_s_sig, low_s_recid)
+        }
+        None => K256VerifyingKey::recover_from_prehash(msg, sig, recid),
+    }
+}
+
+impl Secp256k1PublicKey {
+    #[cfg(test)]
+    pub fn new() -> Secp256k1PublicKey {
+        Secp256k1PublicKey::from_private(&Secp256k1PrivateKey::new())
+    }
+
+    pub fn from_hex(hex_string: &str) -> Result<Secp256k1PublicKey, &'static str> {
+        let data = hex_bytes(hex_string).map_err(|_e| "Failed to decode hex public key")?;
+        Secp256k1PublicKey::from_slice(&data[..]).map_err(|_e| "Invalid public key hex string")
+    }
+
+    pub fn from_slice(data: &[u8]) -> Result<Secp256k1PublicKey, &'static str> {
+        match K256VerifyingKey::from_sec1_bytes(data) {
+            Ok(pubkey_res) => Ok(Secp256k1PublicKey {
+                key: pubkey_res,
+                compressed: data.len() == COMPRESSED_PUBLIC_KEY_SIZE,
+            }),
+            Err(_e) => Err("Invalid public key: failed to load"),
+        }
+    }
+
+    pub fn from_private(privk: &Secp256k1PrivateKey) -> Secp256k1PublicKey {
+        Secp256k1PublicKey {
+            key: *privk.signing_key().verifying_key(),
+            compressed: privk.compress_public,
+        }
+    }
+
+    pub fn to_hex(&self) -> String {
+        to_hex(&self.to_bytes())
+    }
+
+    pub fn to_bytes_compressed(&self) -> Vec<u8> {
+        self.key.to_encoded_point(true).as_bytes().to_vec()
+    }
+
+    pub fn compressed(&self) -> bool {
+        self.compressed
+    }
+
+    pub fn set_compressed(&mut self, value: bool) {
+        self.compressed = value;
+    }
+
+    /// recover message and signature to public key (will be compressed)
+    pub fn recover_to_pubkey(
+        msg: &[u8],
+        sig: &MessageSignature,
+    ) -> Result<Secp256k1PublicKey, &'static str> {
+        if msg.len() != MESSAGE_SIZE {
+            return Err("Invalid message: failed to decode data hash: must be a 32-byte hash");
+        }
+
+        let (k256_sig, recid) = sig
+            .to_secp256k1_recoverable()
+            .ok_or("Invalid signature: failed to decode recoverable signature")?;
+
+        let recovered_pubkey = recover_prehash(msg, &k256_sig, recid)
+            .map_err(|_e| "Invalid signature: failed to recover public key")?;
+
+        Ok(Secp256k1PublicKey {
+            key: recovered_pubkey,
+            compressed: true,
+        })
+    }
+}
+
+impl PublicKey for Secp256k1PublicKey {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.key
+            .to_encoded_point(self.compressed)
+            .as_bytes()
+            .to_vec()
+    }
+
+    fn verify(&self, data_hash: &[u8], sig: &MessageSignature) -> Result<bool, &'static str> {
+        if data_hash.len() != MESSAGE_SIZE {
+            return Err("Invalid message: failed to decode data hash: must be a 32-byte hash");
+        }
+
+        let (k256_sig, recid) = sig
+            .to_secp256k1_recoverable()
+            .ok_or("Invalid signature: failed to decode recoverable signature")?;
+
+        let recovered_pubkey = recover_prehash(data_hash, &k256_sig, recid)
+            .map_err(|_e| "Invalid signature: failed to recover public key")?;
+
+        if recovered_pubkey != self.key {
+            test_debug!("{:?} != {:?}", &recovered_pubkey, &self.key);
+            return Ok(false);
+        }
+
+        // must be low-S
+        if k256_sig.normalize_s().is_some() {
+            return Err("Invalid signature: high-S");
+        }
+
+        Ok(true)
+    }
+}
+
+impl Secp256k1PrivateKey {
+    pub fn new() -> Secp256k1PrivateKey {
+        let mut rng = rand::thread_rng();
+        loop {
+            // keep trying to generate valid bytes
+            let mut random_32_bytes = [0u8; 32];
+            rng.fill_bytes(&mut random_32_bytes);
+            if K256SigningKey::from_slice(&random_32_bytes).is_ok() {
+                return Secp256k1PrivateKey {
+                    key: random_32_bytes,
+                    compress_public: true,
+                };
+            }
+        }
+    }
+
+    pub fn from_hex(hex_string: &str) -> Result<Secp256k1PrivateKey, &'static str> {
+        let data = hex_bytes(hex_string).map_err(|_e| "Failed to decode hex private key")?;
+        Secp256k1PrivateKey::from_slice(&data[..]).map_err(|_e| "Invalid private key hex string")
+    }
+
+    pub fn from_slice(data: &[u8]) -> Result<Secp256k1PrivateKey, &'static str> {
+        if data.len() < 32 {
+            return Err("Invalid private key: shorter than 32 bytes");
+        }
+        if data.len() > 33 {
+            return Err("Invalid private key: greater than 33 bytes");
+        }
+        let compress_public = if data.len() == 33 {
+            // compressed byte tag?
+            if data[32] != 0x01 {
+                return Err("Invalid private key: invalid compressed byte marker");
+            }
+            true
+        } else {
+            false
+        };
+        if K256SigningKey::from_slice(&data[0..32]).is_err() {
+            return Err("Invalid private key: failed to load");
+        }
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&data[0..32]);
+        Ok(Secp256k1PrivateKey {
+            key: key,
+            compress_public: compress_public,
+        })
+    }
+
+    pub fn compress_public(&self) -> bool {
+        self.compress_public
+    }
+
+    pub fn set_compress_public(&mut self, value: bool) {
+        self.compress_public = value;
+    }
+
+    pub fn to_hex(&self) -> String {
+        to_hex(&self.to_bytes())
+    }
+
+    fn signing_key(&self) -> K256SigningKey {
+        K256SigningKey::from_slice(&self.key).expect("FATAL: private key was validated on load")
+    }
+}
+
+impl PrivateKey for Secp256k1PrivateKey {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bits = self.key.to_vec();
+        if self.compress_public {
+            bits.push(0x01);
+        }
+        bits
+    }
+
+    fn sign(&self, data_hash: &[u8]) -> Result<MessageSignature, &'static str> {
+        if data_hash.len() != MESSAGE_SIZE {
+            return Err("Invalid message: failed to decode data hash: must be a 32-byte hash");
+        }
+
+        // RFC6979 nonces, normalized to low-S, exactly as libsecp256k1 signs
+        let (sig, recid) = self
+            .signing_key()
+            .sign_prehash_recoverable(data_hash)
+            .map_err(|_e| "Failed to sign message")?;
+        Ok(MessageSignature::from_secp256k1_recoverable(&sig, recid))
+    }
+}
+
+fn secp256k1_pubkey_serialize<S: serde::Serializer>(
+    pubk: &K256VerifyingKey,
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    let key_hex = to_hex(&pubk.to_encoded_point(true).as_bytes().to_vec());
+    s.serialize_str(&key_hex.as_str())
+}
+
+fn secp256k1_pubkey_deserialize<'de, D: serde::Deserializer<'de>>(
+    d: D,
+) -> Result<K256VerifyingKey, D::Error> {
+    let key_hex = String::deserialize(d)?;
+    let key_bytes = hex_bytes(&key_hex).map_err(de_Error::custom)?;
+
+    K256VerifyingKey::from_sec1_bytes(&key_bytes[..]).map_err(de_Error::custom)
+}
+
+fn secp256k1_privkey_serialize<S: serde::Serializer>(
+    privk: &[u8; 32],
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    let key_hex = to_hex(&privk.to_vec());
+    s.serialize_str(&key_hex.as_str())
+}
+
+fn secp256k1_privkey_deserialize<'de, D: serde::Deserializer<'de>>(
+    d: D,
+) -> Result<[u8; 32], D::Error> {
+    let key_hex = String::deserialize(d)?;
+    let key_bytes = hex_bytes(&key_hex).map_err(de_Error::custom)?;
+
+    K256SigningKey::from_slice(&key_bytes[..]).map_err(de_Error::custom)?;
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&key_bytes[..]);
+    Ok(key)
+}
+
+pub fn secp256k1_recover(
+    message_arr: &[u8],
+    serialized_signature_arr: &[u8],
+) -> Result<[u8; 33], K256Error> {
+    if message_arr.len() != MESSAGE_SIZE {
+        return Err(K256Error::new());
+    }
+
+    let rec_id =
+        K256RecoveryId::from_byte(serialized_signature_arr[64]).ok_or_else(K256Error::new)?;
+    let recovered_sig = K256Signature::from_slice(&serialized_signature_arr[..64])?;
+    let recovered_pub = recover_prehash(message_arr, &recovered_sig, rec_id)?;
+
+    let mut recovered_serialized = [0u8; 33]; // 33 bytes version
+    recovered_serialized.copy_from_slice(recovered_pub.to_encoded_point(true).as_bytes());
+
+    Ok(recovered_serialized)
+}
+
+pub fn secp256k1_verify(
+    message_arr: &[u8],
+    serialized_signature_arr: &[u8],
+    pubkey_arr: &[u8],
+) -> Result<(), K256Error> {
+    if message_arr.len() != MESSAGE_SIZE {
+        return Err(K256Error::new());
+    }
+
+    let expanded_sig = K256Signature::from_slice(&serialized_signature_arr[..64])?; // ignore 65th byte if present
+    let pubkey = K256VerifyingKey::from_sec1_bytes(pubkey_arr)?;
+
+    // like libsecp256k1, this rejects high-S signatures
+    pubkey.verify_prehash(message_arr, &expanded_sig)
+}
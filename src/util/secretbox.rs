@@ -0,0 +1,169 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Passphrase-based encryption of small secrets, such as key material kept on disk.
+//!
+//! A sealed box is laid out as `version || salt || nonce || ciphertext || tag`.  A fresh random
+//! salt is drawn for each box, and stretched together with the passphrase into a key with
+//! PBKDF2-HMAC-SHA256.  The plaintext is sealed under that key and a random nonce with the
+//! ChaCha20-Poly1305 AEAD, with the version, salt and nonce as associated data, so a wrong
+//! passphrase or a damaged box is detected instead of decrypting to garbage.
+
+use std::error;
+use std::fmt;
+
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hmac::Hmac;
+use pbkdf2::pbkdf2;
+use sha2::Sha256;
+
+use rand::thread_rng;
+use rand::RngCore;
+
+const SECRETBOX_VERSION: u8 = 1;
+const SALT_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const HEADER_LEN: usize = 1 + SALT_LEN + NONCE_LEN;
+
+/// How many PBKDF2 rounds stretch a passphrase into a key
+const KEY_STRETCH_ROUNDS: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The box is too short to hold a header
+    Truncated,
+    /// The box was sealed by a version of this code that we don't know
+    UnsupportedVersion(u8),
+    /// The passphrase is wrong, or the box was tampered with
+    BadTag,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Truncated => write!(f, "Sealed data is truncated"),
+            Error::UnsupportedVersion(v) => write!(f, "Unsupported sealed data version {}", v),
+            Error::BadTag => write!(f, "Wrong passphrase, or the sealed data is damaged"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+/// Stretch the passphrase and salt into a key
+fn derive_cipher(passphrase: &[u8], salt: &[u8]) -> ChaCha20Poly1305 {
+    let mut key = [0u8; 32];
+    pbkdf2::<Hmac<Sha256>>(passphrase, salt, KEY_STRETCH_ROUNDS, &mut key);
+    ChaCha20Poly1305::new(Key::from_slice(&key))
+}
+
+/// Encrypt `plaintext` under `passphrase`
+pub fn seal(passphrase: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    let mut sealed = Vec::with_capacity(HEADER_LEN + plaintext.len() + TAG_LEN);
+    sealed.push(SECRETBOX_VERSION);
+    sealed.resize(HEADER_LEN, 0);
+    thread_rng().fill_bytes(&mut sealed[1..HEADER_LEN]);
+
+    let cipher = derive_cipher(passphrase, &sealed[1..1 + SALT_LEN]);
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&sealed[1 + SALT_LEN..HEADER_LEN]),
+            Payload {
+                msg: plaintext,
+                aad: &sealed[..HEADER_LEN],
+            },
+        )
+        .expect("FATAL: failed to encrypt");
+
+    sealed.extend_from_slice(&ciphertext);
+    sealed
+}
+
+/// Decrypt data sealed with `seal()` under `passphrase`
+pub fn open(passphrase: &[u8], sealed: &[u8]) -> Result<Vec<u8>, Error> {
+    if sealed.len() < HEADER_LEN + TAG_LEN {
+        return Err(Error::Truncated);
+    }
+    if sealed[0] != SECRETBOX_VERSION {
+        return Err(Error::UnsupportedVersion(sealed[0]));
+    }
+    let salt = &sealed[1..1 + SALT_LEN];
+    let nonce = &sealed[1 + SALT_LEN..HEADER_LEN];
+
+    let cipher = derive_cipher(passphrase, salt);
+    cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: &sealed[HEADER_LEN..],
+                aad: &sealed[..HEADER_LEN],
+            },
+        )
+        .map_err(|_| Error::BadTag)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_seal_open() {
+        let secret = b"the miner's VRF keys".to_vec();
+        let sealed = seal(b"passphrase", &secret);
+        assert_eq!(sealed.len(), HEADER_LEN + secret.len() + TAG_LEN);
+        assert!(sealed[HEADER_LEN..HEADER_LEN + secret.len()] != secret[..]);
+        assert_eq!(open(b"passphrase", &sealed), Ok(secret.clone()));
+
+        // a fresh salt every time
+        assert!(seal(b"passphrase", &secret) != sealed);
+
+        assert_eq!(open(b"wrong passphrase", &sealed), Err(Error::BadTag));
+
+        let mut tampered = sealed.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 0x01;
+        assert_eq!(open(b"passphrase", &tampered), Err(Error::BadTag));
+
+        let mut tampered = sealed.clone();
+        tampered[HEADER_LEN] ^= 0x01;
+        assert_eq!(open(b"passphrase", &tampered), Err(Error::BadTag));
+
+        let mut tampered = sealed.clone();
+        tampered[1] ^= 0x01;
+        assert_eq!(open(b"passphrase", &tampered), Err(Error::BadTag));
+
+        let mut future = sealed.clone();
+        future[0] = 2;
+        assert_eq!(
+            open(b"passphrase", &future),
+            Err(Error::UnsupportedVersion(2))
+        );
+
+        assert_eq!(
+            open(b"passphrase", &sealed[..HEADER_LEN + TAG_LEN - 1]),
+            Err(Error::Truncated)
+        );
+
+        let empty = seal(b"passphrase", &[]);
+        assert_eq!(open(b"passphrase", &empty), Ok(vec![]));
+    }
+}
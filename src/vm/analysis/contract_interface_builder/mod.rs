@@ -16,6 +16,7 @@
 
 use std::collections::{BTreeMap, BTreeSet};
 use vm::analysis::types::ContractAnalysis;
+use vm::types::signatures::{SequenceSubtype, StringSubtype};
 use vm::types::{FixedFunction, FunctionArg, FunctionType, TupleTypeSignature, TypeSignature};
 use vm::ClarityName;
 
@@ -38,6 +39,7 @@ pub fn build_contract_interface(contract_analysis: &ContractAnalysis) -> Contrac
         type_map: _,
         cost_track: _,
         contract_interface: _,
+        epoch: _,
     } = contract_analysis;
 
     contract_interface
@@ -89,9 +91,101 @@ pub fn build_contract_interface(contract_analysis: &ContractAnalysis) -> Contrac
             fungible_tokens,
         ));
 
+    contract_interface.standards = detect_token_standards(contract_analysis);
+
     contract_interface
 }
 
+/// What a token standard function must return: a response, whose ok type is checked
+type OkTypeCheck = fn(&TypeSignature) -> bool;
+
+/// The functions a contract must expose (as public or read-only functions) to be labeled with
+/// a token standard: name, number of arguments (which are all `uint`s), and a check of the ok
+/// type of the response returned.  Error types are left to the contract.
+const FT_STANDARD_FUNCTIONS: &[(&str, usize, OkTypeCheck)] = &[
+    ("get-name", 0, is_string),
+    ("get-symbol", 0, is_string),
+    ("get-decimals", 0, is_uint),
+    ("get-token-uri", 0, is_optional_string),
+];
+
+const NFT_STANDARD_FUNCTIONS: &[(&str, usize, OkTypeCheck)] = &[
+    ("get-last-token-id", 0, is_uint),
+    ("get-token-uri", 1, is_optional_string),
+    ("get-owner", 1, is_optional_principal),
+];
+
+fn is_string(type_sig: &TypeSignature) -> bool {
+    match type_sig {
+        TypeSignature::SequenceType(SequenceSubtype::StringType(StringSubtype::ASCII(_)))
+        | TypeSignature::SequenceType(SequenceSubtype::StringType(StringSubtype::UTF8(_))) => true,
+        _ => false,
+    }
+}
+
+fn is_uint(type_sig: &TypeSignature) -> bool {
+    *type_sig == TypeSignature::UIntType
+}
+
+// an optional that is always `none` has no inner type
+fn is_optional_string(type_sig: &TypeSignature) -> bool {
+    match type_sig {
+        TypeSignature::OptionalType(inner) => **inner == TypeSignature::NoType || is_string(inner),
+        _ => false,
+    }
+}
+
+fn is_optional_principal(type_sig: &TypeSignature) -> bool {
+    match type_sig {
+        TypeSignature::OptionalType(inner) => match **inner {
+            TypeSignature::NoType | TypeSignature::PrincipalType => true,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn exposes_functions(
+    contract_analysis: &ContractAnalysis,
+    functions: &[(&str, usize, OkTypeCheck)],
+) -> bool {
+    functions.iter().all(|(name, arg_count, ok_type_check)| {
+        let function_type = contract_analysis
+            .get_read_only_function_type(name)
+            .or_else(|| contract_analysis.get_public_function_type(name));
+        match function_type {
+            Some(FunctionType::Fixed(FixedFunction { args, returns })) => {
+                let args_match =
+                    args.len() == *arg_count && args.iter().all(|arg| is_uint(&arg.signature));
+                let ok_type_matches = match returns {
+                    TypeSignature::ResponseType(response_types) => ok_type_check(&response_types.0),
+                    _ => false,
+                };
+                args_match && ok_type_matches
+            }
+            _ => false,
+        }
+    })
+}
+
+/// The token standards -- `ft` and `nft` -- a contract implements, so that wallets can find its
+/// tokens without knowing the contract.  A contract implements a standard if it defines a token
+/// of that kind and exposes the standard's functions with the expected types.
+pub fn detect_token_standards(contract_analysis: &ContractAnalysis) -> Vec<String> {
+    let mut standards = vec![];
+    if contract_analysis.fungible_tokens.len() > 0
+        && exposes_functions(contract_analysis, FT_STANDARD_FUNCTIONS)
+    {
+        standards.push("ft".to_string());
+    }
+    if contract_analysis.non_fungible_tokens.len() > 0
+        && exposes_functions(contract_analysis, NFT_STANDARD_FUNCTIONS)
+    {
+        standards.push("nft".to_string());
+    }
+    standards
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ContractInterfaceFunctionAccess {
     private,
@@ -370,6 +464,9 @@ pub struct ContractInterface {
     pub maps: Vec<ContractInterfaceMap>,
     pub fungible_tokens: Vec<ContractInterfaceFungibleTokens>,
     pub non_fungible_tokens: Vec<ContractInterfaceNonFungibleTokens>,
+    /// the token standards the contract implements; see `detect_token_standards`
+    #[serde(default)]
+    pub standards: Vec<String>,
 }
 
 impl ContractInterface {
@@ -380,6 +477,7 @@ impl ContractInterface {
             maps: Vec::new(),
             fungible_tokens: Vec::new(),
             non_fungible_tokens: Vec::new(),
+            standards: Vec::new(),
         }
     }
 
@@ -67,6 +67,9 @@ pub enum CheckErrors {
     ExpectedOptionalOrResponseValue(Value),
     CouldNotDetermineResponseOkType,
     CouldNotDetermineResponseErrType,
+    // unwrap!, unwrap-err!, and try! on a value that can only be the case they return early on
+    CouldNotNarrowToOkType(TypeSignature),
+    CouldNotNarrowToErrType(TypeSignature),
 
     CouldNotDetermineMatchTypes,
 
@@ -172,6 +175,12 @@ pub enum CheckErrors {
 
     WriteAttemptedInReadOnly,
     AtBlockClosureMustBeReadOnly,
+
+    // strict lints (see strict_checker) -- never consensus errors
+    UnusedFunctionArgument(String, String),
+    ImplicitPanic(String),
+    UncheckedResponse(TypeSignature),
+    SameBlockRandomness,
 }
 
 #[derive(Debug, PartialEq)]
@@ -332,6 +341,8 @@ impl DiagnosableError for CheckErrors {
             CheckErrors::ExpectedResponseValue(found_type) => format!("expecting expression of type 'response', found '{}'", found_type),
             CheckErrors::CouldNotDetermineResponseOkType => format!("attempted to obtain 'ok' value from response, but 'ok' type is indeterminate"),
             CheckErrors::CouldNotDetermineResponseErrType => format!("attempted to obtain 'err' value from response, but 'err' type is indeterminate"),
+            CheckErrors::CouldNotNarrowToOkType(found_type) => format!("expecting expression that can be 'some' or 'ok', found '{}', which always fails to unwrap", found_type),
+            CheckErrors::CouldNotNarrowToErrType(found_type) => format!("expecting expression that can be 'err', found '{}', which always fails to unwrap", found_type),
             CheckErrors::CouldNotDetermineMatchTypes => format!("attempted to match on an (optional) or (response) type where either the some, ok, or err type is indeterminate. you may wish to use unwrap-panic or unwrap-err-panic instead."),
             CheckErrors::BadTupleFieldName => format!("invalid tuple field name"),
             CheckErrors::ExpectedTuple(type_signature) => format!("expecting tuple, found '{}'", type_signature),
@@ -396,6 +407,10 @@ impl DiagnosableError for CheckErrors {
             CheckErrors::ContractOfExpectsTrait => format!("trait reference expected"),
             CheckErrors::InvalidCharactersDetected => format!("invalid characters detected"),
             CheckErrors::InvalidSecp65k1Signature => format!("invalid seckp256k1 signature"),
+            CheckErrors::UnusedFunctionArgument(function_name, arg_name) => format!("function '{}' never uses its argument '{}'", function_name, arg_name),
+            CheckErrors::ImplicitPanic(function_name) => format!("'{}' aborts the transaction without an error code", function_name),
+            CheckErrors::UncheckedResponse(response_type) => format!("response of type {} is discarded without being checked", response_type),
+            CheckErrors::SameBlockRandomness => format!("the VRF seed of the latest block is known to the miner who decides whether to include this transaction"),
             CheckErrors::TypeAlreadyAnnotatedFailure | CheckErrors::CheckerImplementationFailure => {
                 format!("internal error - please file an issue on github.com/blockstack/blockstack-core")
             },
@@ -416,6 +431,15 @@ impl DiagnosableError for CheckErrors {
             CheckErrors::NoSuchBlockInfoProperty(_) => Some(format!(
                 "properties available: time, header-hash, burnchain-header-hash, vrf-seed"
             )),
+            CheckErrors::ImplicitPanic(_) => Some(format!(
+                "use 'unwrap!' or 'unwrap-err!' to return an error code instead"
+            )),
+            CheckErrors::UncheckedResponse(_) => Some(format!(
+                "use 'try!' or 'unwrap!' to check the response, or 'asserts!' to check that it is ok"
+            )),
+            CheckErrors::SameBlockRandomness => Some(format!(
+                "commit to an outcome at one block height, and resolve it with the VRF seed of a later block"
+            )),
             _ => None,
         }
     }
@@ -18,11 +18,13 @@ pub mod analysis_db;
 pub mod contract_interface_builder;
 pub mod errors;
 pub mod read_only_checker;
+pub mod strict_checker;
 pub mod trait_checker;
 pub mod type_checker;
 pub mod types;
 
 pub use self::types::{AnalysisPass, ContractAnalysis};
+use core::StacksEpochId;
 use vm::costs::LimitedCostTracker;
 use vm::database::STORE_CONTRACT_SRC_INTERFACE;
 use vm::representations::SymbolicExpression;
@@ -75,6 +77,7 @@ pub fn type_check(
         analysis_db,
         insert_contract,
         LimitedCostTracker::new_max_limit(),
+        StacksEpochId::latest(),
     )
     .map_err(|(e, _cost_tracker)| e)
 }
@@ -85,12 +88,14 @@ pub fn run_analysis(
     analysis_db: &mut AnalysisDatabase,
     save_contract: bool,
     cost_tracker: LimitedCostTracker,
+    epoch: StacksEpochId,
 ) -> Result<ContractAnalysis, (CheckError, LimitedCostTracker)> {
     let mut contract_analysis = ContractAnalysis::new(
         contract_identifier.clone(),
         expressions.to_vec(),
         cost_tracker,
     );
+    contract_analysis.epoch = epoch;
     let result = analysis_db.execute(|db| {
         ReadOnlyChecker::run_pass(&mut contract_analysis, db)?;
         TypeChecker::run_pass(&mut contract_analysis, db)?;
@@ -14,6 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use core::StacksEpochId;
 use vm::analysis::types::{AnalysisPass, ContractAnalysis};
 use vm::functions::define::DefineFunctionsParsed;
 use vm::functions::tuples;
@@ -39,6 +40,8 @@ mod tests;
 pub struct ReadOnlyChecker<'a, 'b> {
     db: &'a mut AnalysisDatabase<'b>,
     defined_functions: HashMap<ClarityName, bool>,
+    /// the epoch whose natives the contract can call
+    epoch: StacksEpochId,
 }
 
 impl<'a, 'b> AnalysisPass for ReadOnlyChecker<'a, 'b> {
@@ -46,17 +49,18 @@ impl<'a, 'b> AnalysisPass for ReadOnlyChecker<'a, 'b> {
         contract_analysis: &mut ContractAnalysis,
         analysis_db: &mut AnalysisDatabase,
     ) -> CheckResult<()> {
-        let mut command = ReadOnlyChecker::new(analysis_db);
+        let mut command = ReadOnlyChecker::new(analysis_db, contract_analysis.epoch);
         command.run(contract_analysis)?;
         Ok(())
     }
 }
 
 impl<'a, 'b> ReadOnlyChecker<'a, 'b> {
-    fn new(db: &'a mut AnalysisDatabase<'b>) -> ReadOnlyChecker<'a, 'b> {
+    fn new(db: &'a mut AnalysisDatabase<'b>, epoch: StacksEpochId) -> ReadOnlyChecker<'a, 'b> {
         Self {
             db,
             defined_functions: HashMap::new(),
+            epoch,
         }
     }
 
@@ -155,10 +159,15 @@ impl<'a, 'b> ReadOnlyChecker<'a, 'b> {
         Ok(result)
     }
 
+    /// Checks the value expressions of an implicit tuple definition.
+    /// From Stacks 2.1 on, like `check_all_read_only`, this _cannot_ short-circuit on read-only.
+    /// Before then, it stops at the first writing entry, so contracts admitted under the old
+    /// rules still are.
     fn is_implicit_tuple_definition_read_only(
         &mut self,
         tuples: &[SymbolicExpression],
     ) -> CheckResult<bool> {
+        let mut result = true;
         for tuple_expr in tuples.iter() {
             let pair = tuple_expr
                 .match_list()
@@ -167,11 +176,13 @@ impl<'a, 'b> ReadOnlyChecker<'a, 'b> {
                 return Err(CheckErrors::TupleExpectsPairs.into());
             }
 
-            if !self.check_read_only(&pair[1])? {
+            let val_read_only = self.check_read_only(&pair[1])?;
+            if !val_read_only && self.epoch < StacksEpochId::Epoch21 {
                 return Ok(false);
             }
+            result = result && val_read_only;
         }
-        Ok(true)
+        Ok(result)
     }
 
     fn try_native_function_check(
@@ -179,7 +190,7 @@ impl<'a, 'b> ReadOnlyChecker<'a, 'b> {
         function: &str,
         args: &[SymbolicExpression],
     ) -> Option<CheckResult<bool>> {
-        NativeFunctions::lookup_by_name(function)
+        NativeFunctions::lookup_by_name_at_epoch(function, self.epoch)
             .map(|function| self.check_native_function(&function, args))
     }
 
@@ -197,9 +208,13 @@ impl<'a, 'b> ReadOnlyChecker<'a, 'b> {
             | Secp256k1Verify | ConsSome | ConsOkay | ConsError | DefaultTo | UnwrapRet
             | UnwrapErrRet | IsOkay | IsNone | Asserts | Unwrap | UnwrapErr | Match | IsErr
             | IsSome | TryRet | ToUInt | ToInt | Append | Concat | AsMaxLen | ContractOf
-            | PrincipalOf | ListCons | GetBlockInfo | TupleGet | Len | Print | AsContract
-            | Begin | FetchVar | GetStxBalance | GetTokenBalance | GetAssetOwner => {
-                self.check_all_read_only(args)
+            | PrincipalOf | ListCons | GetBlockInfo | TupleGet | Len | IndexOf | Slice | Print
+            | AsContract | Begin | FetchVar | GetStxBalance | GetTokenBalance | GetAssetOwner
+            | ToConsensusBuff => self.check_all_read_only(args),
+            FromConsensusBuff => {
+                // the first argument is a type signature, not an expression
+                check_argument_count(2, args)?;
+                self.check_read_only(&args[1])
             }
             AtBlock => {
                 check_argument_count(2, args)?;
@@ -229,18 +244,23 @@ impl<'a, 'b> ReadOnlyChecker<'a, 'b> {
 
                 let binding_list = args[0].match_list().ok_or(CheckErrors::BadLetSyntax)?;
 
+                let mut bindings_read_only = true;
                 for pair in binding_list.iter() {
                     let pair_expression = pair.match_list().ok_or(CheckErrors::BadSyntaxBinding)?;
                     if pair_expression.len() != 2 {
                         return Err(CheckErrors::BadSyntaxBinding.into());
                     }
 
-                    if !self.check_read_only(&pair_expression[1])? {
+                    let binding_read_only = self.check_read_only(&pair_expression[1])?;
+                    if !binding_read_only && self.epoch < StacksEpochId::Epoch21 {
+                        // before Stacks 2.1, the rest of the bindings and the body went unchecked
                         return Ok(false);
                     }
+                    bindings_read_only = bindings_read_only && binding_read_only;
                 }
 
-                self.check_all_read_only(&args[1..args.len()])
+                let body_read_only = self.check_all_read_only(&args[1..args.len()])?;
+                Ok(bindings_read_only && body_read_only)
             }
             Map | Filter => {
                 check_argument_count(2, args)?;
@@ -264,20 +284,7 @@ impl<'a, 'b> ReadOnlyChecker<'a, 'b> {
                 //     read-only or not.
                 self.check_function_application_read_only(args)
             }
-            TupleCons => {
-                for pair in args.iter() {
-                    let pair_expression =
-                        pair.match_list().ok_or(CheckErrors::TupleExpectsPairs)?;
-                    if pair_expression.len() != 2 {
-                        return Err(CheckErrors::TupleExpectsPairs.into());
-                    }
-
-                    if !self.check_read_only(&pair_expression[1])? {
-                        return Ok(false);
-                    }
-                }
-                Ok(true)
-            }
+            TupleCons => self.is_implicit_tuple_definition_read_only(args),
             ContractCall => {
                 check_arguments_at_least(2, args)?;
 
@@ -14,11 +14,32 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use vm::analysis::{mem_type_check, type_check, AnalysisDatabase, CheckError, CheckErrors};
+use core::StacksEpochId;
+use vm::analysis::{
+    mem_type_check, run_analysis, type_check, AnalysisDatabase, CheckError, CheckErrors,
+    ContractAnalysis,
+};
 use vm::ast::parse;
+use vm::costs::LimitedCostTracker;
 use vm::database::MemoryBackingStore;
 use vm::types::QualifiedContractIdentifier;
 
+fn check_at_epoch(snippet: &str, epoch: StacksEpochId) -> Result<ContractAnalysis, CheckErrors> {
+    let contract_identifier = QualifiedContractIdentifier::transient();
+    let mut contract = parse(&contract_identifier, snippet).unwrap();
+    let mut marf = MemoryBackingStore::new();
+    let mut analysis_db = marf.as_analysis_db();
+    run_analysis(
+        &contract_identifier,
+        &mut contract,
+        &mut analysis_db,
+        false,
+        LimitedCostTracker::new_max_limit(),
+        epoch,
+    )
+    .map_err(|(e, _)| e.err)
+}
+
 #[test]
 fn test_argument_count_violations() {
     let examples = [
@@ -61,9 +82,37 @@ fn test_at_block_violations() {
     ];
 
     for contract in examples.iter() {
-        let err = mem_type_check(contract).unwrap_err();
+        for epoch in [StacksEpochId::Epoch20, StacksEpochId::Epoch21].iter() {
+            let err = check_at_epoch(contract, *epoch).unwrap_err();
+            eprintln!("{}", err);
+            assert_eq!(err, CheckErrors::AtBlockClosureMustBeReadOnly)
+        }
+    }
+
+    // from Stacks 2.1 on, once a `let` binding or tuple entry is known to be writing,
+    //  the remaining bindings and the body must still be checked.  Before then, they
+    //  were skipped, and these contracts were admitted.
+    let examples = [
+        "(define-data-var foo int 1)
+         (define-private (foo-bar)
+           (let ((a (var-set foo 2))
+                 (b (at-block (sha256 0) (var-set foo 0))))
+             a))",
+        "(define-data-var foo int 1)
+         (define-private (foo-bar)
+           (let ((a (var-set foo 2)))
+             (at-block (sha256 0) (var-set foo 0))))",
+        "(define-data-var foo int 1)
+         (define-private (foo-bar)
+           (tuple (a (var-set foo 2))
+                  (b (at-block (sha256 0) (var-set foo 0)))))",
+    ];
+
+    for contract in examples.iter() {
+        check_at_epoch(contract, StacksEpochId::Epoch20).unwrap();
+        let err = check_at_epoch(contract, StacksEpochId::Epoch21).unwrap_err();
         eprintln!("{}", err);
-        assert_eq!(err.err, CheckErrors::AtBlockClosureMustBeReadOnly)
+        assert_eq!(err, CheckErrors::AtBlockClosureMustBeReadOnly)
     }
 }
 
@@ -0,0 +1,312 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Optional lints over an analyzed contract.
+//!
+//! None of these lints are consensus rules -- a contract that fails them is still a valid
+//! contract.  `clarity-cli check` reports them as warnings, and a `StrictProfile` upgrades the
+//! lints it selects to errors, so that projects can enforce them with the same analysis the chain
+//! runs.
+
+use std::collections::BTreeSet;
+
+use serde_json;
+
+use vm::analysis::type_checker::contexts::TypeMap;
+use vm::analysis::types::ContractAnalysis;
+use vm::diagnostic::Level;
+use vm::functions::define::DefineFunctionsParsed;
+use vm::functions::NativeFunctions;
+use vm::representations::{ClarityName, SymbolicExpression};
+use vm::types::{TypeSignature, Value};
+
+pub use super::errors::{CheckError, CheckErrors};
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StrictLint {
+    /// a function argument is never used in the function's body
+    UnusedArguments,
+    /// `unwrap-panic` or `unwrap-err-panic` aborts the transaction without an error code
+    ImplicitPanics,
+    /// a response value is computed and then discarded without being checked
+    UncheckedResponses,
+    /// the VRF seed of the current or latest block is used as a source of randomness.  The
+    /// miner of the current block knows it, and can choose whether to include the transaction.
+    SameBlockRandomness,
+}
+
+impl StrictLint {
+    pub const ALL: [StrictLint; 4] = [
+        StrictLint::UnusedArguments,
+        StrictLint::ImplicitPanics,
+        StrictLint::UncheckedResponses,
+        StrictLint::SameBlockRandomness,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            StrictLint::UnusedArguments => "unused-arguments",
+            StrictLint::ImplicitPanics => "implicit-panics",
+            StrictLint::UncheckedResponses => "unchecked-responses",
+            StrictLint::SameBlockRandomness => "same-block-randomness",
+        }
+    }
+
+    pub fn lookup_by_name(name: &str) -> Option<StrictLint> {
+        StrictLint::ALL
+            .iter()
+            .find(|lint| lint.name() == name)
+            .cloned()
+    }
+}
+
+/// Which lints are errors
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StrictProfile {
+    pub lints: BTreeSet<StrictLint>,
+}
+
+/// The parts of a project manifest that configure the strict profile, e.g.
+/// `{ "strict": ["unused-arguments", "unchecked-responses"] }`
+#[derive(Debug, Deserialize)]
+struct ProjectManifest {
+    #[serde(default)]
+    strict: Vec<String>,
+}
+
+impl StrictProfile {
+    /// Every lint is an error
+    pub fn all() -> StrictProfile {
+        StrictProfile {
+            lints: StrictLint::ALL.iter().cloned().collect(),
+        }
+    }
+
+    pub fn from_lint_names(names: &[String]) -> Result<StrictProfile, String> {
+        let mut lints = BTreeSet::new();
+        for name in names.iter() {
+            let lint = StrictLint::lookup_by_name(name)
+                .ok_or_else(|| format!("unknown strict lint '{}'", name))?;
+            lints.insert(lint);
+        }
+        Ok(StrictProfile { lints })
+    }
+
+    /// Load the profile from a JSON project manifest
+    pub fn from_manifest(manifest: &str) -> Result<StrictProfile, String> {
+        let manifest: ProjectManifest = serde_json::from_str(manifest)
+            .map_err(|e| format!("invalid project manifest: {}", e))?;
+        StrictProfile::from_lint_names(&manifest.strict)
+    }
+
+    pub fn is_error(&self, lint: StrictLint) -> bool {
+        self.lints.contains(&lint)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LintFinding {
+    pub lint: StrictLint,
+    pub error: CheckError,
+}
+
+impl LintFinding {
+    fn new(lint: StrictLint, err: CheckErrors, expr: &SymbolicExpression) -> LintFinding {
+        let mut error = CheckError::new(err);
+        error.set_expression(expr);
+        LintFinding { lint, error }
+    }
+}
+
+/// Run every lint over an analyzed contract.
+pub fn run_lints(contract_analysis: &ContractAnalysis) -> Vec<LintFinding> {
+    let mut findings = vec![];
+    for exp in contract_analysis.expressions.iter() {
+        match DefineFunctionsParsed::try_parse(exp) {
+            Ok(Some(DefineFunctionsParsed::PrivateFunction { signature, body }))
+            | Ok(Some(DefineFunctionsParsed::ReadOnlyFunction { signature, body }))
+            | Ok(Some(DefineFunctionsParsed::PublicFunction { signature, body })) => {
+                check_unused_arguments(signature, body, &mut findings);
+            }
+            _ => {}
+        }
+        check_expression(exp, contract_analysis.type_map.as_ref(), &mut findings);
+    }
+    findings
+}
+
+/// Run every lint over an analyzed contract, and fail with the first finding that `profile`
+/// treats as an error.  Otherwise, return the findings as warnings.
+pub fn check_strict(
+    contract_analysis: &ContractAnalysis,
+    profile: &StrictProfile,
+) -> Result<Vec<LintFinding>, CheckError> {
+    let mut warnings = vec![];
+    for mut finding in run_lints(contract_analysis).into_iter() {
+        if profile.is_error(finding.lint) {
+            return Err(finding.error);
+        }
+        finding.error.diagnostic.level = Level::Warning;
+        warnings.push(finding);
+    }
+    Ok(warnings)
+}
+
+fn uses_name(expr: &SymbolicExpression, name: &ClarityName) -> bool {
+    if let Some(atom) = expr.match_atom() {
+        atom == name
+    } else if let Some(list) = expr.match_list() {
+        list.iter().any(|e| uses_name(e, name))
+    } else {
+        false
+    }
+}
+
+fn check_unused_arguments(
+    signature: &[SymbolicExpression],
+    body: &SymbolicExpression,
+    findings: &mut Vec<LintFinding>,
+) {
+    let function_name = match signature.first().and_then(|e| e.match_atom()) {
+        Some(name) => name,
+        None => {
+            return;
+        }
+    };
+    for arg in signature[1..].iter() {
+        let arg_name = match arg
+            .match_list()
+            .and_then(|pair| pair.first())
+            .and_then(|e| e.match_atom())
+        {
+            Some(name) => name,
+            None => {
+                continue;
+            }
+        };
+        if !uses_name(body, arg_name) {
+            findings.push(LintFinding::new(
+                StrictLint::UnusedArguments,
+                CheckErrors::UnusedFunctionArgument(
+                    function_name.to_string(),
+                    arg_name.to_string(),
+                ),
+                arg,
+            ));
+        }
+    }
+}
+
+/// Flag any of `exprs` that evaluate to a response, but are not the value of their enclosing
+/// expression.
+fn check_discarded_responses(
+    exprs: &[SymbolicExpression],
+    type_map: Option<&TypeMap>,
+    findings: &mut Vec<LintFinding>,
+) {
+    let type_map = match type_map {
+        Some(type_map) => type_map,
+        None => {
+            return;
+        }
+    };
+    if exprs.len() < 2 {
+        return;
+    }
+    for expr in exprs[..exprs.len() - 1].iter() {
+        if let Some(response_type @ &TypeSignature::ResponseType(_)) = type_map.get_type(expr) {
+            findings.push(LintFinding::new(
+                StrictLint::UncheckedResponses,
+                CheckErrors::UncheckedResponse(response_type.clone()),
+                expr,
+            ));
+        }
+    }
+}
+
+/// Is `expr` the height of the current block, or of the block before it?
+fn is_latest_block_height(expr: &SymbolicExpression) -> bool {
+    if let Some(atom) = expr.match_atom() {
+        return atom.as_str() == "block-height";
+    }
+    match expr.match_list() {
+        Some([function_name, height, offset]) => {
+            function_name.match_atom().map(|name| name.as_str()) == Some("-")
+                && height.match_atom().map(|name| name.as_str()) == Some("block-height")
+                && match offset.match_literal_value() {
+                    Some(Value::UInt(offset)) => *offset <= 1,
+                    _ => false,
+                }
+        }
+        _ => false,
+    }
+}
+
+fn check_expression(
+    expr: &SymbolicExpression,
+    type_map: Option<&TypeMap>,
+    findings: &mut Vec<LintFinding>,
+) {
+    let list = match expr.match_list() {
+        Some(list) => list,
+        None => {
+            return;
+        }
+    };
+
+    if let Some((function_name, args)) = list.split_first() {
+        if let Some(function_name) = function_name.match_atom() {
+            match NativeFunctions::lookup_by_name(function_name) {
+                Some(NativeFunctions::Unwrap) | Some(NativeFunctions::UnwrapErr) => {
+                    findings.push(LintFinding::new(
+                        StrictLint::ImplicitPanics,
+                        CheckErrors::ImplicitPanic(function_name.to_string()),
+                        expr,
+                    ));
+                }
+                Some(NativeFunctions::Begin) => {
+                    check_discarded_responses(args, type_map, findings);
+                }
+                Some(NativeFunctions::Let) => {
+                    if let Some((_bindings, body)) = args.split_first() {
+                        check_discarded_responses(body, type_map, findings);
+                    }
+                }
+                Some(NativeFunctions::GetBlockInfo) => {
+                    if let [property, height] = args {
+                        if property.match_atom().map(|name| name.as_str()) == Some("vrf-seed")
+                            && is_latest_block_height(height)
+                        {
+                            findings.push(LintFinding::new(
+                                StrictLint::SameBlockRandomness,
+                                CheckErrors::SameBlockRandomness,
+                                expr,
+                            ));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    for child in list.iter() {
+        check_expression(child, type_map, findings);
+    }
+}
@@ -0,0 +1,167 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use vm::analysis::mem_type_check;
+use vm::analysis::strict_checker::{check_strict, run_lints, StrictLint, StrictProfile};
+use vm::analysis::CheckErrors;
+use vm::diagnostic::Level;
+
+fn lint(contract: &str) -> Vec<(StrictLint, CheckErrors)> {
+    let (_, analysis) = mem_type_check(contract).unwrap();
+    run_lints(&analysis)
+        .into_iter()
+        .map(|finding| (finding.lint, finding.error.err))
+        .collect()
+}
+
+#[test]
+fn test_unused_arguments() {
+    let findings = lint(
+        "(define-private (add (a int) (b int) (c int)) (+ a c))
+         (define-read-only (get-a (a int)) (let ((b a)) b))
+         (define-public (noop (who principal)) (ok true))",
+    );
+    assert_eq!(
+        findings,
+        vec![
+            (
+                StrictLint::UnusedArguments,
+                CheckErrors::UnusedFunctionArgument("add".to_string(), "b".to_string())
+            ),
+            (
+                StrictLint::UnusedArguments,
+                CheckErrors::UnusedFunctionArgument("noop".to_string(), "who".to_string())
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_implicit_panics() {
+    let findings = lint(
+        "(define-read-only (get-some (a (optional int))) (unwrap-panic a))
+         (define-read-only (get-err (a (response int int))) (unwrap-err-panic a))
+         (define-read-only (get-default (a (optional int))) (default-to 0 a))",
+    );
+    assert_eq!(
+        findings,
+        vec![
+            (
+                StrictLint::ImplicitPanics,
+                CheckErrors::ImplicitPanic("unwrap-panic".to_string())
+            ),
+            (
+                StrictLint::ImplicitPanics,
+                CheckErrors::ImplicitPanic("unwrap-err-panic".to_string())
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_unchecked_responses() {
+    let findings = lint(
+        "(define-public (pay (amount uint) (recipient principal))
+           (begin
+             (stx-transfer? amount tx-sender recipient)
+             (ok true)))
+         (define-public (pay-checked (amount uint) (recipient principal))
+           (begin
+             (try! (stx-transfer? amount tx-sender recipient))
+             (ok true)))
+         (define-public (pay-let (amount uint) (recipient principal))
+           (let ((fee u1))
+             (stx-transfer? fee tx-sender recipient)
+             (stx-transfer? amount tx-sender recipient)))",
+    );
+    assert_eq!(findings.len(), 2);
+    for (lint, err) in findings.into_iter() {
+        assert_eq!(lint, StrictLint::UncheckedResponses);
+        match err {
+            CheckErrors::UncheckedResponse(_) => {}
+            _ => panic!("Expected an unchecked response, got {:?}", &err),
+        }
+    }
+}
+
+#[test]
+fn test_same_block_randomness() {
+    let findings = lint(
+        "(define-read-only (seed-now) (get-block-info? vrf-seed block-height))
+         (define-read-only (seed-latest) (get-block-info? vrf-seed (- block-height u1)))
+         (define-read-only (seed-at (height uint)) (get-block-info? vrf-seed height))
+         (define-read-only (seed-older) (get-block-info? vrf-seed (- block-height u2)))
+         (define-read-only (hash-latest) (get-block-info? header-hash (- block-height u1)))",
+    );
+    assert_eq!(
+        findings,
+        vec![
+            (
+                StrictLint::SameBlockRandomness,
+                CheckErrors::SameBlockRandomness
+            ),
+            (
+                StrictLint::SameBlockRandomness,
+                CheckErrors::SameBlockRandomness
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_strict_profile() {
+    let contract = "(define-private (add (a int) (b int)) a)
+                    (define-read-only (get-some (a (optional int))) (unwrap-panic a))";
+    let (_, analysis) = mem_type_check(contract).unwrap();
+
+    // nothing is an error by default
+    let warnings = check_strict(&analysis, &StrictProfile::default()).unwrap();
+    assert_eq!(warnings.len(), 2);
+    for warning in warnings.iter() {
+        assert_eq!(warning.error.diagnostic.level, Level::Warning);
+    }
+
+    // only the selected lints are errors
+    let profile = StrictProfile::from_lint_names(&["implicit-panics".to_string()]).unwrap();
+    let err = check_strict(&analysis, &profile).unwrap_err();
+    assert_eq!(
+        err.err,
+        CheckErrors::ImplicitPanic("unwrap-panic".to_string())
+    );
+    assert_eq!(err.diagnostic.level, Level::Error);
+
+    let err = check_strict(&analysis, &StrictProfile::all()).unwrap_err();
+    assert_eq!(
+        err.err,
+        CheckErrors::UnusedFunctionArgument("add".to_string(), "b".to_string())
+    );
+
+    // profiles from project manifests
+    let profile = StrictProfile::from_manifest(
+        r#"{ "strict": ["unused-arguments", "unchecked-responses"] }"#,
+    )
+    .unwrap();
+    assert!(profile.is_error(StrictLint::UnusedArguments));
+    assert!(!profile.is_error(StrictLint::ImplicitPanics));
+    assert!(profile.is_error(StrictLint::UncheckedResponses));
+
+    assert_eq!(
+        StrictProfile::from_manifest("{}").unwrap(),
+        StrictProfile::default()
+    );
+    assert!(StrictProfile::from_manifest(r#"{ "strict": ["no-such-lint"] }"#).is_err());
+    assert!(StrictProfile::from_manifest("not json").is_err());
+}
@@ -72,7 +72,7 @@ fn test_expected_response_type() {
 
 #[test]
 fn test_could_not_determine_response_ok_type() {
-    let snippet = "(unwrap! (err \"error\") 0)";
+    let snippet = "(unwrap-panic (err \"error\"))";
     let err = mem_type_check(snippet).unwrap_err();
     assert!(format!("{}", err.diagnostic)
         .contains("attempted to obtain 'ok' value from response, but 'ok' type is indeterminate"));
@@ -80,13 +80,31 @@ fn test_could_not_determine_response_ok_type() {
 
 #[test]
 fn test_could_not_determine_response_err_type() {
-    let snippet = "(unwrap-err! (ok 1) 0)";
+    let snippet = "(unwrap-err-panic (ok 1))";
     let err = mem_type_check(snippet).unwrap_err();
     assert!(format!("{}", err.diagnostic).contains(
         "attempted to obtain 'err' value from response, but 'err' type is indeterminate"
     ));
 }
 
+#[test]
+fn test_could_not_narrow_to_ok_type() {
+    let snippet = "(unwrap! (err \"error\") 0)";
+    let err = mem_type_check(snippet).unwrap_err();
+    assert!(format!("{}", err.diagnostic).contains(
+        "expecting expression that can be 'some' or 'ok', found '(response UnknownType (string-ascii 5))', which always fails to unwrap"
+    ));
+}
+
+#[test]
+fn test_could_not_narrow_to_err_type() {
+    let snippet = "(unwrap-err! (ok 1) 0)";
+    let err = mem_type_check(snippet).unwrap_err();
+    assert!(format!("{}", err.diagnostic).contains(
+        "expecting expression that can be 'err', found '(response int UnknownType)', which always fails to unwrap"
+    ));
+}
+
 #[test]
 fn test_bad_tuple_field_name() {
     let snippet = "(get 1 (tuple (value 100)))";
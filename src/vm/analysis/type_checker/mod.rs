@@ -18,6 +18,7 @@ pub mod contexts;
 //mod maps;
 pub mod natives;
 
+use core::StacksEpochId;
 use std::collections::{BTreeMap, HashMap};
 use std::convert::TryInto;
 use vm::costs::{
@@ -73,6 +74,8 @@ pub struct TypeChecker<'a, 'b> {
     function_return_tracker: Option<Option<TypeSignature>>,
     db: &'a mut AnalysisDatabase<'b>,
     pub cost_track: LimitedCostTracker,
+    /// the epoch whose natives the contract can call
+    epoch: StacksEpochId,
 }
 
 impl CostTracker for TypeChecker<'_, '_> {
@@ -96,7 +99,7 @@ impl AnalysisPass for TypeChecker<'_, '_> {
         analysis_db: &mut AnalysisDatabase,
     ) -> CheckResult<()> {
         let cost_track = contract_analysis.take_contract_cost_tracker();
-        let mut command = TypeChecker::new(analysis_db, cost_track);
+        let mut command = TypeChecker::new(analysis_db, cost_track, contract_analysis.epoch);
         // run the analysis, and replace the cost tracker whether or not the
         //   analysis succeeded.
         match command.run(contract_analysis) {
@@ -298,10 +301,12 @@ impl<'a, 'b> TypeChecker<'a, 'b> {
     fn new(
         db: &'a mut AnalysisDatabase<'b>,
         cost_track: LimitedCostTracker,
+        epoch: StacksEpochId,
     ) -> TypeChecker<'a, 'b> {
         Self {
             db,
             cost_track,
+            epoch,
             contract_context: ContractContext::new(),
             function_return_tracker: None,
             type_map: TypeMap::new(),
@@ -574,7 +579,9 @@ impl<'a, 'b> TypeChecker<'a, 'b> {
         args: &[SymbolicExpression],
         context: &TypingContext,
     ) -> Option<TypeResult> {
-        if let Some(ref native_function) = NativeFunctions::lookup_by_name(function) {
+        if let Some(ref native_function) =
+            NativeFunctions::lookup_by_name_at_epoch(function, self.epoch)
+        {
             let typed_function = TypedNativeFunction::type_native_function(native_function);
             Some(typed_function.type_check_appliction(self, args, context))
         } else {
@@ -0,0 +1,73 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::cmp;
+use std::convert::TryFrom;
+
+use vm::analysis::type_checker::{
+    check_argument_count, CheckErrors, TypeChecker, TypeResult, TypingContext,
+};
+use vm::costs::cost_functions;
+use vm::representations::SymbolicExpression;
+use vm::types::signatures::BufferLength;
+use vm::types::SequenceSubtype::BufferType;
+use vm::types::{TypeSignature, MAX_VALUE_SIZE};
+
+/// The result of `(to-consensus-buff? value)` is typed as a buffer as long as the largest
+/// serialization of `value`'s type, up to the maximum buffer length.
+pub fn check_special_to_consensus_buff(
+    checker: &mut TypeChecker,
+    args: &[SymbolicExpression],
+    context: &TypingContext,
+) -> TypeResult {
+    check_argument_count(1, args)?;
+
+    let input_type = checker.type_check(&args[0], context)?;
+
+    runtime_cost!(
+        cost_functions::ANALYSIS_TYPE_CHECK,
+        checker,
+        input_type.type_size()?
+    )?;
+
+    let buffer_len = input_type
+        .max_serialized_size()
+        .map_or(MAX_VALUE_SIZE, |size| cmp::min(size, MAX_VALUE_SIZE));
+
+    Ok(TypeSignature::OptionalType(Box::new(
+        TypeSignature::SequenceType(BufferType(BufferLength::try_from(buffer_len)?)),
+    )))
+}
+
+/// `(from-consensus-buff? type buffer)` takes a type signature as its first argument, and
+/// returns an optional of that type.
+pub fn check_special_from_consensus_buff(
+    checker: &mut TypeChecker,
+    args: &[SymbolicExpression],
+    context: &TypingContext,
+) -> TypeResult {
+    check_argument_count(2, args)?;
+
+    let expected_type = TypeSignature::parse_type_repr(&args[0], checker)?;
+
+    let input_type = checker.type_check(&args[1], context)?;
+    match input_type {
+        TypeSignature::SequenceType(BufferType(_)) => {}
+        _ => return Err(CheckErrors::TypeError(TypeSignature::max_buffer(), input_type).into()),
+    }
+
+    Ok(TypeSignature::new_option(expected_type)?)
+}
@@ -18,12 +18,14 @@ use vm::representations::{ClarityName, SymbolicExpression};
 use vm::types::TypeSignature;
 
 use vm::analysis::type_checker::{
-    check_argument_count, check_arguments_at_least, no_type, CheckError, CheckErrors, TypeChecker,
-    TypeResult, TypingContext,
+    check_argument_count, check_arguments_at_least, no_type, CheckError, CheckErrors, CheckResult,
+    TypeChecker, TypeResult, TypingContext,
 };
 
 use vm::costs::{analysis_typecheck_cost, cost_functions};
 
+use core::StacksEpochId;
+
 pub fn check_special_okay(
     checker: &mut TypeChecker,
     args: &[SymbolicExpression],
@@ -176,6 +178,50 @@ fn inner_unwrap_err(input: TypeSignature, checker: &mut TypeChecker) -> TypeResu
     }
 }
 
+/// Narrow the input of `unwrap!` or `try!` to its `some` or `ok` type.  Also returns whether
+/// the unwrap can fail: a response whose `err` type is indeterminate is always `ok`, so the
+/// unwrap never returns early.
+fn narrow_unwrap(
+    input: TypeSignature,
+    checker: &mut TypeChecker,
+) -> CheckResult<(TypeSignature, bool)> {
+    runtime_cost!(cost_functions::ANALYSIS_OPTION_CHECK, checker, 1)?;
+
+    match input {
+        TypeSignature::OptionalType(ref input_type) if input_type.is_no_type() => {
+            Err(CheckErrors::CouldNotNarrowToOkType(input).into())
+        }
+        TypeSignature::OptionalType(input_type) => Ok((*input_type, true)),
+        TypeSignature::ResponseType(ref response_type) if response_type.0.is_no_type() => {
+            Err(CheckErrors::CouldNotNarrowToOkType(input.clone()).into())
+        }
+        TypeSignature::ResponseType(response_type) => {
+            let (ok_type, err_type) = *response_type;
+            Ok((ok_type, !err_type.is_no_type()))
+        }
+        _ => Err(CheckErrors::ExpectedOptionalOrResponseType(input).into()),
+    }
+}
+
+/// Narrow the input of `unwrap-err!` to its `err` type, and return whether the unwrap can fail.
+fn narrow_unwrap_err(
+    input: TypeSignature,
+    checker: &mut TypeChecker,
+) -> CheckResult<(TypeSignature, bool)> {
+    runtime_cost!(cost_functions::ANALYSIS_OPTION_CHECK, checker, 1)?;
+
+    match input {
+        TypeSignature::ResponseType(ref response_type) if response_type.1.is_no_type() => {
+            Err(CheckErrors::CouldNotNarrowToErrType(input.clone()).into())
+        }
+        TypeSignature::ResponseType(response_type) => {
+            let (ok_type, err_type) = *response_type;
+            Ok((err_type, !ok_type.is_no_type()))
+        }
+        _ => Err(CheckErrors::ExpectedResponseType(input).into()),
+    }
+}
+
 pub fn check_special_unwrap_or_ret(
     checker: &mut TypeChecker,
     args: &[SymbolicExpression],
@@ -186,9 +232,18 @@ pub fn check_special_unwrap_or_ret(
     let input = checker.type_check(&args[0], context)?;
     let on_error = checker.type_check(&args[1], context)?;
 
-    checker.track_return_type(on_error)?;
+    // before Stacks 2.1, the early return always counts
+    if checker.epoch < StacksEpochId::Epoch21 {
+        checker.track_return_type(on_error)?;
+        return inner_unwrap(input, checker);
+    }
 
-    inner_unwrap(input, checker)
+    let (unwrapped_type, can_fail) = narrow_unwrap(input, checker)?;
+    if can_fail {
+        checker.track_return_type(on_error)?;
+    }
+
+    Ok(unwrapped_type)
 }
 
 pub fn check_special_unwrap_err_or_ret(
@@ -201,9 +256,17 @@ pub fn check_special_unwrap_err_or_ret(
     let input = checker.type_check(&args[0], context)?;
     let on_error = checker.type_check(&args[1], context)?;
 
-    checker.track_return_type(on_error)?;
+    if checker.epoch < StacksEpochId::Epoch21 {
+        checker.track_return_type(on_error)?;
+        return inner_unwrap_err(input, checker);
+    }
 
-    inner_unwrap_err(input, checker)
+    let (unwrapped_type, can_fail) = narrow_unwrap_err(input, checker)?;
+    if can_fail {
+        checker.track_return_type(on_error)?;
+    }
+
+    Ok(unwrapped_type)
 }
 
 pub fn check_special_try_ret(
@@ -215,6 +278,29 @@ pub fn check_special_try_ret(
 
     let input = checker.type_check(&args[0], context)?;
 
+    if checker.epoch < StacksEpochId::Epoch21 {
+        return inner_try_ret_2_0(input, checker);
+    }
+
+    // `try!` returns early with the `none` or `err` it unwrapped
+    let early_return_type = match input {
+        TypeSignature::OptionalType(_) => TypeSignature::new_option(TypeSignature::NoType)?,
+        TypeSignature::ResponseType(ref response_type) => {
+            TypeSignature::new_response(TypeSignature::NoType, response_type.1.clone())?
+        }
+        _ => return Err(CheckErrors::ExpectedOptionalOrResponseType(input).into()),
+    };
+
+    let (unwrapped_type, can_fail) = narrow_unwrap(input, checker)?;
+    if can_fail {
+        checker.track_return_type(early_return_type)?;
+    }
+
+    Ok(unwrapped_type)
+}
+
+/// `try!` before Stacks 2.1:  the input must be able to fail
+fn inner_try_ret_2_0(input: TypeSignature, checker: &mut TypeChecker) -> TypeResult {
     runtime_cost!(cost_functions::ANALYSIS_OPTION_CHECK, checker, 1)?;
 
     match input {
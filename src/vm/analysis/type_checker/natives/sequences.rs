@@ -35,7 +35,9 @@ fn get_simple_native_or_user_define(
     checker: &mut TypeChecker,
 ) -> CheckResult<FunctionType> {
     runtime_cost!(cost_functions::ANALYSIS_LOOKUP_FUNCTION, checker, 1)?;
-    if let Some(ref native_function) = NativeFunctions::lookup_by_name(function_name) {
+    if let Some(ref native_function) =
+        NativeFunctions::lookup_by_name_at_epoch(function_name, checker.epoch)
+    {
         if let TypedNativeFunction::Simple(SimpleNativeFunction(function_type)) =
             TypedNativeFunction::type_native_function(native_function)
         {
@@ -321,3 +323,77 @@ pub fn check_special_len(
 
     Ok(TypeSignature::UIntType)
 }
+
+pub fn check_special_index_of(
+    checker: &mut TypeChecker,
+    args: &[SymbolicExpression],
+    context: &TypingContext,
+) -> TypeResult {
+    check_argument_count(2, args)?;
+
+    let sequence_type = checker.type_check(&args[0], context)?;
+    let item_type = checker.type_check(&args[1], context)?;
+
+    runtime_cost!(cost_functions::ANALYSIS_ITERABLE_FUNC, checker, 1)?;
+
+    let unit_type = match sequence_type {
+        TypeSignature::SequenceType(ref sequence) => sequence.unit_type(),
+        _ => return Err(CheckErrors::ExpectedSequence(sequence_type.clone()).into()),
+    };
+
+    analysis_typecheck_cost(checker, &unit_type, &item_type)?;
+
+    if !unit_type.admits_type(&item_type) {
+        return Err(CheckErrors::TypeError(unit_type, item_type).into());
+    }
+
+    Ok(TypeSignature::OptionalType(Box::new(
+        TypeSignature::UIntType,
+    )))
+}
+
+/// The length of `(slice? seq left right)`'s result is at most the length of `seq`, and when
+/// `left` and `right` are literals, at most `right - left`.
+pub fn check_special_slice(
+    checker: &mut TypeChecker,
+    args: &[SymbolicExpression],
+    context: &TypingContext,
+) -> TypeResult {
+    check_argument_count(3, args)?;
+
+    let sequence_type = checker.type_check(&args[0], context)?;
+    checker.type_check_expects(&args[1], context, &TypeSignature::UIntType)?;
+    checker.type_check_expects(&args[2], context, &TypeSignature::UIntType)?;
+
+    runtime_cost!(cost_functions::ANALYSIS_ITERABLE_FUNC, checker, 1)?;
+
+    let literal_len = match (args[1].match_literal_value(), args[2].match_literal_value()) {
+        (Some(Value::UInt(left)), Some(Value::UInt(right))) => {
+            Some(u32::try_from(right.saturating_sub(*left)).unwrap_or(u32::max_value()))
+        }
+        _ => None,
+    };
+
+    let sliced_type = match sequence_type {
+        TypeSignature::SequenceType(ListType(list)) => {
+            let (entry_type, max_len) = list.destruct();
+            let max_len = literal_len.map_or(max_len, |len| max_len.min(len));
+            TypeSignature::SequenceType(ListType(ListTypeData::new_list(entry_type, max_len)?))
+        }
+        TypeSignature::SequenceType(BufferType(len)) => {
+            let max_len = literal_len.map_or(u32::from(&len), |sliced| u32::from(&len).min(sliced));
+            TypeSignature::SequenceType(BufferType(BufferLength::try_from(max_len)?))
+        }
+        TypeSignature::SequenceType(StringType(ASCII(len))) => {
+            let max_len = literal_len.map_or(u32::from(&len), |sliced| u32::from(&len).min(sliced));
+            TypeSignature::SequenceType(StringType(ASCII(BufferLength::try_from(max_len)?)))
+        }
+        TypeSignature::SequenceType(StringType(UTF8(len))) => {
+            let max_len = literal_len.map_or(u32::from(&len), |sliced| u32::from(&len).min(sliced));
+            TypeSignature::SequenceType(StringType(UTF8(StringUTF8Length::try_from(max_len)?)))
+        }
+        _ => return Err(CheckErrors::ExpectedSequence(sequence_type).into()),
+    };
+
+    Ok(TypeSignature::OptionalType(Box::new(sliced_type)))
+}
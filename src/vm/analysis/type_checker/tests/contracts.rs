@@ -357,7 +357,8 @@ fn test_names_tokens_contracts_interface() {
             { "name": "d-var3", "access": "variable", "type": { "buffer": { "length": 5 } } }
         ],
         "fungible_tokens": [],
-        "non_fungible_tokens": []
+        "non_fungible_tokens": [],
+        "standards": []
     }"#).unwrap();
 
     eprintln!("{}", test_contract_json_str);
@@ -365,6 +366,71 @@ fn test_names_tokens_contracts_interface() {
     assert_json_eq!(test_contract_json, test_contract_json_expected);
 }
 
+#[test]
+fn test_token_standards_interface() {
+    const FT_CONTRACT: &str = "
+        (define-fungible-token stackaroos)
+        (define-read-only (get-name) (ok \"Stackaroos\"))
+        (define-read-only (get-symbol) (ok u\"ROO\"))
+        (define-read-only (get-decimals) (ok u6))
+        (define-read-only (get-token-uri) (ok none))
+        (define-read-only (get-balance (owner principal))
+          (ok (ft-get-balance stackaroos owner)))
+    ";
+    const NFT_CONTRACT: &str = "
+        (define-non-fungible-token names uint)
+        (define-data-var last-id uint u0)
+        (define-read-only (get-last-token-id) (ok (var-get last-id)))
+        (define-read-only (get-token-uri (id uint)) (ok (some \"https://example.com\")))
+        (define-public (get-owner (id uint)) (ok (nft-get-owner? names id)))
+    ";
+
+    let standards = |contract: &str| {
+        let contract_analysis = mem_type_check(contract).unwrap().1;
+        build_contract_interface(&contract_analysis).standards
+    };
+
+    assert_eq!(standards(FT_CONTRACT), vec!["ft".to_string()]);
+    assert_eq!(standards(NFT_CONTRACT), vec!["nft".to_string()]);
+    assert_eq!(
+        standards(&format!(
+            "{}{}",
+            FT_CONTRACT,
+            NFT_CONTRACT.replace("get-token-uri", "get-nft-uri")
+        )),
+        vec!["ft".to_string()]
+    );
+
+    // the functions alone are not enough: the contract must define a token
+    assert_eq!(
+        standards(
+            &FT_CONTRACT
+                .replace("(define-fungible-token stackaroos)", "")
+                .replace("(ft-get-balance stackaroos owner)", "u0")
+        ),
+        Vec::<String>::new()
+    );
+    // and they must have the standard's types
+    assert_eq!(
+        standards(&FT_CONTRACT.replace("(ok u6)", "(ok 6)")),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        standards(
+            &NFT_CONTRACT
+                .replace("(get-owner (id uint))", "(get-owner (id int))")
+                .replace("(nft-get-owner? names id)", "none")
+        ),
+        Vec::<String>::new()
+    );
+
+    let interface_json: serde_json::Value = serde_json::from_str(
+        &build_contract_interface(&mem_type_check(FT_CONTRACT).unwrap().1).serialize(),
+    )
+    .unwrap();
+    assert_eq!(interface_json["standards"], json!(["ft"]));
+}
+
 #[test]
 fn test_names_tokens_contracts() {
     let tokens_contract_id = QualifiedContractIdentifier::local("tokens").unwrap();
@@ -577,14 +643,14 @@ fn test_expects() {
     let err = mem_type_check(notype_response_type).unwrap_err();
     eprintln!("notype_response_type returned check error: {}", err);
     assert!(match &err.err {
-        &CheckErrors::CouldNotDetermineResponseErrType => true,
+        &CheckErrors::CouldNotNarrowToErrType(_) => true,
         _ => false,
     });
 
     let err = mem_type_check(notype_response_type_2).unwrap_err();
     eprintln!("notype_response_type_2 returned check error: {}", err);
     assert!(match &err.err {
-        &CheckErrors::CouldNotDetermineResponseOkType => true,
+        &CheckErrors::CouldNotNarrowToOkType(_) => true,
         _ => false,
     });
 }
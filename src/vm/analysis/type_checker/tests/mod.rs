@@ -16,6 +16,7 @@
 
 use vm::analysis::errors::CheckErrors;
 use vm::analysis::mem_type_check;
+use vm::analysis::run_analysis;
 use vm::analysis::type_check;
 use vm::analysis::type_checker::{TypeChecker, TypeResult, TypingContext};
 use vm::analysis::types::ContractAnalysis;
@@ -33,6 +34,9 @@ use vm::database::MemoryBackingStore;
 use vm::types::TypeSignature::{BoolType, IntType, PrincipalType, SequenceType, UIntType};
 use vm::types::{SequenceSubtype::*, StringSubtype::*};
 
+use core::StacksEpochId;
+use vm::costs::LimitedCostTracker;
+
 use std::convert::TryInto;
 
 mod assets;
@@ -42,6 +46,28 @@ fn type_check_helper(exp: &str) -> TypeResult {
     mem_type_check(exp).map(|(type_sig_opt, _)| type_sig_opt.unwrap())
 }
 
+/// Type check `snippet` under the rules of `epoch`, and return the type of its last expression
+fn type_check_at_epoch(snippet: &str, epoch: StacksEpochId) -> Result<String, CheckErrors> {
+    let contract_identifier = QualifiedContractIdentifier::transient();
+    let mut contract = parse(&contract_identifier, snippet).unwrap();
+    let mut marf = MemoryBackingStore::new();
+    let mut analysis_db = marf.as_analysis_db();
+    run_analysis(
+        &contract_identifier,
+        &mut contract,
+        &mut analysis_db,
+        false,
+        LimitedCostTracker::new_max_limit(),
+        epoch,
+    )
+    .map(|analysis| {
+        let last_expr = analysis.expressions.last().unwrap();
+        let type_map = analysis.type_map.as_ref().unwrap();
+        format!("{}", type_map.get_type(last_expr).unwrap())
+    })
+    .map_err(|(e, _)| e.err)
+}
+
 fn buff_type(size: u32) -> TypeSignature {
     TypeSignature::SequenceType(BufferType(size.try_into().unwrap())).into()
 }
@@ -58,6 +84,7 @@ fn test_get_block_info() {
         "(get-block-info? vrf-seed u1)",
         "(get-block-info? header-hash u1)",
         "(get-block-info? burnchain-header-hash u1)",
+        "(get-block-info? id-header-hash u1)",
         "(get-block-info? miner-address u1)",
     ];
     let expected = [
@@ -66,6 +93,7 @@ fn test_get_block_info() {
         "(optional (buff 32))",
         "(optional (buff 32))",
         "(optional (buff 32))",
+        "(optional (buff 32))",
         "(optional principal)",
     ];
 
@@ -221,6 +249,39 @@ fn test_stx_ops() {
     }
 }
 
+#[test]
+fn test_as_contract() {
+    let good = [
+        "(as-contract tx-sender)",
+        "(as-contract contract-caller)",
+        "(as-contract (stx-transfer? u10 tx-sender 'SM2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQVX8X0G))",
+        "(as-contract (+ 1 2))",
+    ];
+    let expected = ["principal", "principal", "(response bool uint)", "int"];
+
+    let bad = [
+        "(as-contract)",
+        "(as-contract tx-sender tx-sender)",
+        "(as-contract (stx-get-balance true))",
+    ];
+    let bad_expected = [
+        CheckErrors::IncorrectArgumentCount(1, 0),
+        CheckErrors::IncorrectArgumentCount(1, 2),
+        CheckErrors::TypeError(PrincipalType, BoolType),
+    ];
+
+    for (good_test, expected) in good.iter().zip(expected.iter()) {
+        assert_eq!(
+            expected,
+            &format!("{}", type_check_helper(&good_test).unwrap())
+        );
+    }
+
+    for (bad_test, expected) in bad.iter().zip(bad_expected.iter()) {
+        assert_eq!(expected, &type_check_helper(&bad_test).unwrap_err().err);
+    }
+}
+
 #[test]
 fn test_destructuring_opts() {
     let good = [
@@ -251,6 +312,15 @@ fn test_destructuring_opts() {
                (some false)
                (some (> u2 (try! (t1 x))))))
          (t2 u3)",
+        "(try! (ok 3))",
+        // unwrapping a response that is always ok never returns early, so the early return
+        //   value doesn't constrain the function's return type
+        "(define-private (t1 (x uint)) (+ x (unwrap! (ok u1) false) (try! (ok u2))))
+         (t1 u3)",
+        "(define-private (t1 (x uint)) (- x (unwrap-err! (err u1) false)))
+         (t1 u3)",
+        "(default-to (ok u1) (some (err 2)))",
+        "(default-to 1 none)",
     ];
 
     let expected = [
@@ -265,6 +335,11 @@ fn test_destructuring_opts() {
         "(response uint bool)",
         "(response bool bool)",
         "(optional bool)",
+        "int",
+        "uint",
+        "uint",
+        "(response uint int)",
+        "int",
     ];
 
     assert_eq!(expected.len(), good.len());
@@ -276,7 +351,21 @@ fn test_destructuring_opts() {
         ),
         (
             "(unwrap! (err 3) 2)",
-            CheckErrors::CouldNotDetermineResponseOkType,
+            CheckErrors::CouldNotNarrowToOkType(
+                TypeSignature::new_response(TypeSignature::NoType, TypeSignature::IntType).unwrap(),
+            ),
+        ),
+        (
+            "(unwrap! none 2)",
+            CheckErrors::CouldNotNarrowToOkType(
+                TypeSignature::new_option(TypeSignature::NoType).unwrap(),
+            ),
+        ),
+        (
+            "(unwrap-err! (ok 3) 2)",
+            CheckErrors::CouldNotNarrowToErrType(
+                TypeSignature::new_response(TypeSignature::IntType, TypeSignature::NoType).unwrap(),
+            ),
         ),
         (
             "(unwrap-err-panic (ok 3))",
@@ -377,13 +466,16 @@ fn test_destructuring_opts() {
             ),
         ),
         (
-            "(try! (ok 3))",
-            CheckErrors::CouldNotDetermineResponseErrType,
+            "(try! none)",
+            CheckErrors::CouldNotNarrowToOkType(
+                TypeSignature::new_option(TypeSignature::NoType).unwrap(),
+            ),
         ),
-        ("(try! none)", CheckErrors::CouldNotDetermineResponseOkType),
         (
             "(try! (err 3))",
-            CheckErrors::CouldNotDetermineResponseOkType,
+            CheckErrors::CouldNotNarrowToOkType(
+                TypeSignature::new_response(TypeSignature::NoType, TypeSignature::IntType).unwrap(),
+            ),
         ),
         (
             "(try! 3)",
@@ -404,6 +496,43 @@ fn test_destructuring_opts() {
     }
 }
 
+#[test]
+fn test_unwrap_narrowing_gated_by_epoch() {
+    // before Stacks 2.1, an unwrap that cannot fail still has to be able to
+    assert_eq!(
+        type_check_at_epoch("(try! (ok 1))", StacksEpochId::Epoch20).unwrap_err(),
+        CheckErrors::CouldNotDetermineResponseErrType
+    );
+    assert_eq!(
+        type_check_at_epoch("(try! (ok 1))", StacksEpochId::Epoch21).unwrap(),
+        "int"
+    );
+
+    // ...and its early return value constrains the function's return type
+    let never_returns_early = "(define-private (t1 (x uint)) (+ x (unwrap! (ok u1) false)))
+         (t1 u3)";
+    assert!(type_check_at_epoch(never_returns_early, StacksEpochId::Epoch20).is_err());
+    assert_eq!(
+        type_check_at_epoch(never_returns_early, StacksEpochId::Epoch21).unwrap(),
+        "uint"
+    );
+
+    assert_eq!(
+        type_check_at_epoch("(unwrap! (err 3) 2)", StacksEpochId::Epoch20).unwrap_err(),
+        CheckErrors::CouldNotDetermineResponseOkType
+    );
+    assert_eq!(
+        type_check_at_epoch("(unwrap-err! (ok 3) 2)", StacksEpochId::Epoch20).unwrap_err(),
+        CheckErrors::CouldNotDetermineResponseErrType
+    );
+    assert_eq!(
+        type_check_at_epoch("(unwrap-err! (ok 3) 2)", StacksEpochId::Epoch21).unwrap_err(),
+        CheckErrors::CouldNotNarrowToErrType(
+            TypeSignature::new_response(TypeSignature::IntType, TypeSignature::NoType).unwrap(),
+        )
+    );
+}
+
 #[test]
 fn test_at_block() {
     let good = [("(at-block (sha256 u0) u1)", "uint")];
@@ -552,6 +681,44 @@ fn test_simple_hash_checks() {
     }
 }
 
+#[test]
+fn test_secp256k1_checks() {
+    let good = [
+        "(secp256k1-recover? (sha256 u1) 0x0101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101)",
+        "(secp256k1-verify (keccak256 u1) 0x0101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101 0x020202020202020202020202020202020202020202020202020202020202020202)",
+        "(match (secp256k1-recover? (sha256 u1) 0x0101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101)
+           pubkey (secp256k1-verify (sha256 u1) 0x0101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101 pubkey)
+           err-code false)",
+    ];
+    let expected = ["(response (buff 33) uint)", "bool", "bool"];
+
+    let bad = [
+        "(secp256k1-recover? (sha512 u1) 0x0101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101)",
+        "(secp256k1-recover? (sha256 u1) 0x020202020202020202020202020202020202020202020202020202020202020202)",
+        "(secp256k1-verify (sha256 u1) 0x0101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101 0x0101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101)",
+        "(secp256k1-recover? (sha256 u1))",
+        "(secp256k1-verify (sha256 u1) 0x0101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101)",
+    ];
+    let bad_expected = [
+        CheckErrors::TypeError(buff_type(32), buff_type(64)),
+        CheckErrors::TypeError(buff_type(65), buff_type(33)),
+        CheckErrors::TypeError(buff_type(33), buff_type(65)),
+        CheckErrors::IncorrectArgumentCount(2, 1),
+        CheckErrors::IncorrectArgumentCount(3, 2),
+    ];
+
+    for (good_test, expected) in good.iter().zip(expected.iter()) {
+        assert_eq!(
+            expected,
+            &format!("{}", type_check_helper(&good_test).unwrap())
+        );
+    }
+
+    for (bad_test, expected) in bad.iter().zip(bad_expected.iter()) {
+        assert_eq!(expected, &type_check_helper(&bad_test).unwrap_err().err);
+    }
+}
+
 #[test]
 fn test_simple_ifs() {
     let good = [
@@ -873,6 +1040,168 @@ fn test_buff_as_max_len() {
     }
 }
 
+#[test]
+fn test_index_of() {
+    let good = [
+        "(index-of (list 1 2 3) 2)",
+        "(index-of 0x010203 0x02)",
+        "(index-of \"blockstack\" \"k\")",
+        "(index-of u\"blockstack\" u\"k\")",
+    ];
+    let expected = "(optional uint)";
+
+    for good_test in good.iter() {
+        assert_eq!(
+            expected,
+            &format!("{}", type_check_helper(&good_test).unwrap())
+        );
+    }
+
+    let bad = [
+        "(index-of (list 1 2 3) u2)",
+        "(index-of 0x010203 0x0203)",
+        "(index-of \"blockstack\" 0x01)",
+        "(index-of 1 1)",
+        "(index-of (list 1 2 3))",
+    ];
+    let bad_expected = [
+        CheckErrors::TypeError(IntType, UIntType),
+        CheckErrors::TypeError(buff_type(1), buff_type(2)),
+        CheckErrors::TypeError(TypeSignature::min_string_ascii(), buff_type(1)),
+        CheckErrors::ExpectedSequence(IntType),
+        CheckErrors::IncorrectArgumentCount(2, 1),
+    ];
+
+    for (bad_test, expected) in bad.iter().zip(bad_expected.iter()) {
+        assert_eq!(expected, &type_check_helper(&bad_test).unwrap_err().err);
+    }
+}
+
+#[test]
+fn test_slice() {
+    let good = [
+        "(slice? (list 1 2 3 4 5) u1 u3)",
+        "(slice? (list 1 2 3) u1 u10)",
+        "(slice? (list 1 2 3) u2 u1)",
+        "(slice? (concat 0x01020304 0x0506) u1 (+ u1 u2))",
+        "(slice? \"blockstack\" u5 u10)",
+        "(slice? u\"blockstack\" u0 u5)",
+    ];
+    let expected = [
+        "(optional (list 2 int))",
+        "(optional (list 3 int))",
+        "(optional (list 0 int))",
+        "(optional (buff 6))",
+        "(optional (string-ascii 5))",
+        "(optional (string-utf8 5))",
+    ];
+
+    for (good_test, expected) in good.iter().zip(expected.iter()) {
+        assert_eq!(
+            expected,
+            &format!("{}", type_check_helper(&good_test).unwrap())
+        );
+    }
+
+    let bad = [
+        "(slice? (list 1 2 3) 1 u2)",
+        "(slice? (list 1 2 3) u1 2)",
+        "(slice? 1 u0 u1)",
+        "(slice? (list 1 2 3) u1)",
+    ];
+    let bad_expected = [
+        CheckErrors::TypeError(UIntType, IntType),
+        CheckErrors::TypeError(UIntType, IntType),
+        CheckErrors::ExpectedSequence(IntType),
+        CheckErrors::IncorrectArgumentCount(3, 2),
+    ];
+
+    for (bad_test, expected) in bad.iter().zip(bad_expected.iter()) {
+        assert_eq!(expected, &type_check_helper(&bad_test).unwrap_err().err);
+    }
+}
+
+#[test]
+fn test_consensus_buff() {
+    let good = [
+        "(to-consensus-buff? 1)",
+        "(to-consensus-buff? true)",
+        "(to-consensus-buff? none)",
+        "(to-consensus-buff? (ok u1))",
+        "(to-consensus-buff? (list 1 2 3))",
+        "(to-consensus-buff? \"abc\")",
+        "(to-consensus-buff? u\"abc\")",
+        "(to-consensus-buff? {a: 1, bc: 0x01})",
+        "(to-consensus-buff? tx-sender)",
+        "(from-consensus-buff? int 0x0000000000000000000000000000000001)",
+        "(from-consensus-buff? (tuple (a int) (b (list 2 uint))) 0x01)",
+        "(from-consensus-buff? (optional (string-ascii 10)) (unwrap-panic (to-consensus-buff? \"abc\")))",
+    ];
+    let expected = [
+        "(optional (buff 17))",
+        "(optional (buff 1))",
+        "(optional (buff 1))",
+        "(optional (buff 18))",
+        "(optional (buff 56))",
+        "(optional (buff 8))",
+        "(optional (buff 17))",
+        "(optional (buff 33))",
+        "(optional (buff 151))",
+        "(optional int)",
+        "(optional (tuple (a int) (b (list 2 uint))))",
+        "(optional (optional (string-ascii 10)))",
+    ];
+
+    for (good_test, expected) in good.iter().zip(expected.iter()) {
+        assert_eq!(
+            expected,
+            &format!("{}", type_check_helper(&good_test).unwrap())
+        );
+    }
+
+    let bad = [
+        "(to-consensus-buff? 1 2)",
+        "(from-consensus-buff? int 1)",
+        "(from-consensus-buff? int)",
+    ];
+    let bad_expected = [
+        CheckErrors::IncorrectArgumentCount(1, 2),
+        CheckErrors::TypeError(TypeSignature::max_buffer(), IntType),
+        CheckErrors::IncorrectArgumentCount(2, 1),
+    ];
+
+    for (bad_test, expected) in bad.iter().zip(bad_expected.iter()) {
+        assert_eq!(expected, &type_check_helper(&bad_test).unwrap_err().err);
+    }
+}
+
+#[test]
+fn test_consensus_buff_gated_by_epoch() {
+    for (snippet, function_name) in [
+        ("(to-consensus-buff? 1)", "to-consensus-buff?"),
+        ("(from-consensus-buff? int 0x01)", "from-consensus-buff?"),
+    ]
+    .iter()
+    {
+        type_check_at_epoch(snippet, StacksEpochId::Epoch21).unwrap();
+        assert_eq!(
+            type_check_at_epoch(snippet, StacksEpochId::Epoch20).unwrap_err(),
+            CheckErrors::UnknownFunction(function_name.to_string())
+        );
+    }
+
+    // before Stacks 2.1, a contract's own definition is called instead
+    let shadowed = "(define-private (to-consensus-buff? (x int)) x) (to-consensus-buff? 1)";
+    assert_eq!(
+        type_check_at_epoch(shadowed, StacksEpochId::Epoch20).unwrap(),
+        "int"
+    );
+    assert_eq!(
+        type_check_at_epoch(shadowed, StacksEpochId::Epoch21).unwrap(),
+        "(optional (buff 17))"
+    );
+}
+
 #[test]
 fn test_native_append() {
     let good = ["(append (list 2 3) 4)", "(append (list u0) u0)"];
@@ -988,6 +1317,36 @@ fn test_lists_in_defines() {
         "(list 5 int)",
         &format!("{}", mem_type_check(good).unwrap().0.unwrap())
     );
+
+    // the results of map and filter are bounded by the length of the list they are given, and
+    // the result of fold has the type of its initial value
+    let good = [
+        "(define-private (double (x int)) (* 2 x))
+         (map double (list 1 2 3))",
+        "(define-private (test (x int)) (is-eq 0 (mod x 2)))
+         (define-private (evens (l (list 10 int))) (filter test l))
+         (evens (list 1 2))",
+        "(define-private (to-hash (x uint)) (sha256 x))
+         (define-private (hashes (l (list 7 uint))) (map to-hash l))
+         (hashes (list u1))",
+        "(define-private (sum (x int) (acc int)) (+ x acc))
+         (fold sum (list 1 2 3) 0)",
+        "(define-private (count-some (x (optional int)) (acc uint))
+           (if (is-some x) (+ acc u1) acc))
+         (fold count-some (list (some 1) none (some 3)) u0)",
+    ];
+    let expected = [
+        "(list 3 int)",
+        "(list 10 int)",
+        "(list 7 (buff 32))",
+        "int",
+        "uint",
+    ];
+
+    for (good_test, expected) in good.iter().zip(expected.iter()) {
+        let type_sig = mem_type_check(good_test).unwrap().0.unwrap();
+        assert_eq!(expected, &type_sig.to_string());
+    }
 }
 
 #[test]
@@ -1163,7 +1522,9 @@ fn test_response_inference() {
     let bad_expected = [
         CheckErrors::TypeError("(response bool int)".into(), BoolType),
         CheckErrors::ReturnTypesMustMatch(IntType, BoolType),
-        CheckErrors::CouldNotDetermineResponseOkType,
+        CheckErrors::CouldNotNarrowToOkType(
+            TypeSignature::new_response(TypeSignature::NoType, TypeSignature::IntType).unwrap(),
+        ),
     ];
 
     for (good_test, expected) in good.iter().zip(expected.iter()) {
@@ -24,6 +24,8 @@ use vm::types::signatures::FunctionSignature;
 use vm::types::{FunctionType, QualifiedContractIdentifier, TraitIdentifier, TypeSignature};
 use vm::{ClarityName, SymbolicExpression};
 
+use core::StacksEpochId;
+
 const DESERIALIZE_FAIL_MESSAGE: &str =
     "PANIC: Failed to deserialize bad database data in contract analysis.";
 const SERIALIZE_FAIL_MESSAGE: &str =
@@ -56,6 +58,9 @@ pub struct ContractAnalysis {
     pub type_map: Option<TypeMap>,
     #[serde(skip)]
     pub cost_track: Option<LimitedCostTracker>,
+    /// the epoch whose natives the contract can call
+    #[serde(skip, default = "StacksEpochId::latest")]
+    pub epoch: StacksEpochId,
 }
 
 impl ContractAnalysis {
@@ -80,6 +85,7 @@ impl ContractAnalysis {
             fungible_tokens: BTreeSet::new(),
             non_fungible_tokens: BTreeMap::new(),
             cost_track: Some(cost_track),
+            epoch: StacksEpochId::latest(),
         }
     }
 
@@ -35,7 +35,9 @@ use chainstate::burn::BlockHeaderHash;
 use chainstate::stacks::events::StacksTransactionEvent;
 use chainstate::stacks::index::marf::MARF;
 use chainstate::stacks::index::{MarfTrieId, TrieHash};
-use chainstate::stacks::StacksBlockId;
+use chainstate::stacks::{StacksBlockHeader, StacksBlockId};
+
+use core::{StacksEpochId, BOOT_BLOCK_HASH, BURNCHAIN_BOOT_CONSENSUS_HASH};
 
 use std::error;
 use std::fmt;
@@ -67,6 +69,8 @@ pub struct ClarityBlockConnection<'a> {
     header_db: &'a dyn HeadersDB,
     burn_state_db: &'a dyn BurnStateDB,
     cost_track: Option<LimitedCostTracker>,
+    /// the epoch whose rules the block follows
+    epoch: StacksEpochId,
 }
 
 ///
@@ -80,6 +84,7 @@ pub struct ClarityTransactionConnection<'a> {
     header_db: &'a dyn HeadersDB,
     burn_state_db: &'a dyn BurnStateDB,
     cost_track: &'a mut Option<LimitedCostTracker>,
+    epoch: StacksEpochId,
 }
 
 pub struct ClarityReadOnlyConnection<'a> {
@@ -203,6 +208,16 @@ impl ClarityBlockConnection<'_> {
             None => ExecutionCost::zero(),
         }
     }
+
+    /// Get the epoch whose rules this block follows
+    pub fn get_epoch(&self) -> StacksEpochId {
+        self.epoch
+    }
+
+    #[cfg(test)]
+    pub fn set_epoch(&mut self, epoch: StacksEpochId) {
+        self.epoch = epoch;
+    }
 }
 
 impl ClarityInstance {
@@ -247,6 +262,7 @@ impl ClarityInstance {
         datastore.begin(current, next);
 
         let cost_track = Some(LimitedCostTracker::new(self.block_limit.clone()));
+        let epoch = ClarityInstance::get_epoch_of(current, header_db, burn_state_db);
 
         ClarityBlockConnection {
             datastore,
@@ -254,6 +270,7 @@ impl ClarityInstance {
             burn_state_db,
             parent: self,
             cost_track,
+            epoch,
         }
     }
 
@@ -271,6 +288,7 @@ impl ClarityInstance {
         datastore.begin_unconfirmed(current);
 
         let cost_track = Some(LimitedCostTracker::new(self.block_limit.clone()));
+        let epoch = ClarityInstance::get_epoch_of(current, header_db, burn_state_db);
 
         ClarityBlockConnection {
             datastore,
@@ -278,6 +296,36 @@ impl ClarityInstance {
             burn_state_db,
             parent: self,
             cost_track,
+            epoch,
+        }
+    }
+
+    /// Get the epoch whose rules a block built on `parent` follows:  the epoch of the burnchain
+    /// block that selected the parent.  Blocks built on the boot block are Stacks 2.0 blocks, and
+    /// if the burnchain's epochs aren't known, the latest epoch's rules apply.
+    pub fn get_epoch_of(
+        parent: &StacksBlockId,
+        header_db: &dyn HeadersDB,
+        burn_state_db: &dyn BurnStateDB,
+    ) -> StacksEpochId {
+        let boot_block = StacksBlockHeader::make_index_block_hash(
+            &BURNCHAIN_BOOT_CONSENSUS_HASH,
+            &BOOT_BLOCK_HASH,
+        );
+        if *parent == boot_block {
+            return StacksEpochId::Epoch20;
+        }
+
+        let epoch_id = header_db
+            .get_burn_block_height_for_block(parent)
+            .and_then(|burn_height| burn_state_db.get_stacks_epoch(burn_height))
+            .map(|epoch| epoch.epoch_id)
+            .unwrap_or_else(StacksEpochId::latest);
+
+        if epoch_id < StacksEpochId::Epoch20 {
+            StacksEpochId::Epoch20
+        } else {
+            epoch_id
         }
     }
 
@@ -525,6 +573,7 @@ impl<'a> ClarityBlockConnection<'a> {
             header_db,
             burn_state_db,
             log: Some(log),
+            epoch: self.epoch,
         }
     }
 
@@ -637,12 +686,18 @@ impl<'a> ClarityTransactionConnection<'a> {
         }
     }
 
+    /// Get the epoch whose rules this transaction follows
+    pub fn get_epoch(&self) -> StacksEpochId {
+        self.epoch
+    }
+
     /// Analyze a provided smart contract, but do not write the analysis to the AnalysisDatabase
     pub fn analyze_smart_contract(
         &mut self,
         identifier: &QualifiedContractIdentifier,
         contract_content: &str,
     ) -> Result<(ContractAST, ContractAnalysis), Error> {
+        let epoch = self.epoch;
         using!(self.cost_track, "cost tracker", |mut cost_track| {
             self.inner_with_analysis_db(|db| {
                 let ast_result = ast::build_ast(identifier, contract_content, &mut cost_track);
@@ -658,6 +713,7 @@ impl<'a> ClarityTransactionConnection<'a> {
                     db,
                     false,
                     cost_track,
+                    epoch,
                 );
 
                 match result {
@@ -682,6 +738,7 @@ impl<'a> ClarityTransactionConnection<'a> {
             &mut OwnedEnvironment,
         ) -> Result<(R, AssetMap, Vec<StacksTransactionEvent>), Error>,
     {
+        let epoch = self.epoch;
         using!(self.log, "log", |log| {
             using!(self.cost_track, "cost tracker", |cost_track| {
                 let rollback_wrapper = RollbackWrapper::from_persisted_log(self.store, log);
@@ -695,6 +752,7 @@ impl<'a> ClarityTransactionConnection<'a> {
                 //   so we can abort on call_back's boolean retun
                 db.begin();
                 let mut vm_env = OwnedEnvironment::new_cost_limited(db, cost_track);
+                vm_env.set_epoch(epoch);
                 let result = to_do(&mut vm_env);
                 let (mut db, cost_track) = vm_env
                     .destruct()
@@ -890,8 +948,106 @@ mod tests {
     use vm::database::{
         ClarityBackingStore, MarfedKV, STXBalance, NULL_BURN_STATE_DB, NULL_HEADER_DB,
     };
+    use vm::functions::NativeFunctions;
     use vm::types::{StandardPrincipalData, Value};
 
+    #[test]
+    pub fn test_natives_gated_by_epoch() {
+        // nothing is available before Stacks 2.0
+        assert!(NativeFunctions::lookup_by_name_at_epoch("+", StacksEpochId::Epoch20).is_some());
+        assert!(NativeFunctions::lookup_by_name_at_epoch("+", StacksEpochId::Epoch10).is_none());
+
+        let marf = MarfedKV::temporary();
+        let mut clarity_instance = ClarityInstance::new(marf, ExecutionCost::max_value());
+        let sender: PrincipalData = StandardPrincipalData::transient().into();
+        let uses_native = QualifiedContractIdentifier::local("uses-native").unwrap();
+        let defines_native = QualifiedContractIdentifier::local("defines-native").unwrap();
+        let uses_native_src = "(define-public (foo) (ok (index-of (list 1 2 3) 2)))";
+        let defines_native_src = "(define-private (index-of (x int)) (+ x 1))
+             (define-public (foo) (ok (index-of 1)))";
+
+        fn deploy(
+            conn: &mut ClarityBlockConnection,
+            identifier: &QualifiedContractIdentifier,
+            contract: &str,
+        ) -> Result<(), Error> {
+            conn.as_transaction(|tx| {
+                let (ct_ast, ct_analysis) = tx.analyze_smart_contract(identifier, contract)?;
+                tx.initialize_smart_contract(identifier, &ct_ast, contract, |_, _| false)?;
+                tx.save_analysis(identifier, &ct_analysis)
+                    .map_err(Error::from)
+            })
+        }
+
+        {
+            // without burnchain epochs, blocks follow the latest rules
+            let mut conn = clarity_instance.begin_block(
+                &StacksBlockId::sentinel(),
+                &StacksBlockId([0 as u8; 32]),
+                &NULL_HEADER_DB,
+                &NULL_BURN_STATE_DB,
+            );
+            assert_eq!(conn.get_epoch(), StacksEpochId::latest());
+
+            // before Stacks 2.1, index-of is a free name
+            conn.epoch = StacksEpochId::Epoch20;
+            match deploy(&mut conn, &uses_native, uses_native_src).unwrap_err() {
+                Error::Analysis(e) => {
+                    assert_eq!(e.err, CheckErrors::UnknownFunction("index-of".to_string()))
+                }
+                e => panic!("Unexpected error: {:?}", &e),
+            }
+            deploy(&mut conn, &defines_native, defines_native_src).unwrap();
+            conn.commit_block();
+        }
+
+        {
+            let mut conn = clarity_instance.begin_block(
+                &StacksBlockId([0 as u8; 32]),
+                &StacksBlockId([1 as u8; 32]),
+                &NULL_HEADER_DB,
+                &NULL_BURN_STATE_DB,
+            );
+            assert_eq!(conn.get_epoch(), StacksEpochId::Epoch21);
+
+            // from Stacks 2.1 on, it's the native...
+            deploy(&mut conn, &uses_native, uses_native_src).unwrap();
+            assert!(deploy(
+                &mut conn,
+                &QualifiedContractIdentifier::local("defines-native-2").unwrap(),
+                defines_native_src
+            )
+            .is_err());
+            assert_eq!(
+                conn.as_transaction(|tx| tx.run_contract_call(
+                    &sender,
+                    &uses_native,
+                    "foo",
+                    &[],
+                    |_, _| false
+                ))
+                .unwrap()
+                .0,
+                Value::okay(Value::some(Value::UInt(1)).unwrap()).unwrap()
+            );
+
+            // ...except in contracts deployed before
+            assert_eq!(
+                conn.as_transaction(|tx| tx.run_contract_call(
+                    &sender,
+                    &defines_native,
+                    "foo",
+                    &[],
+                    |_, _| false
+                ))
+                .unwrap()
+                .0,
+                Value::okay(Value::Int(2)).unwrap()
+            );
+            conn.commit_block();
+        }
+    }
+
     #[test]
     pub fn bad_syntax_test() {
         let marf = MarfedKV::temporary();
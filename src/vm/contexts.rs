@@ -39,6 +39,8 @@ use chainstate::burn::{BlockHeaderHash, VRFSeed};
 use chainstate::stacks::events::*;
 use chainstate::stacks::StacksBlockId;
 
+use core::StacksEpochId;
+
 use serde::Serialize;
 
 pub const MAX_CONTEXT_DEPTH: u16 = 256;
@@ -54,6 +56,26 @@ pub struct Environment<'a, 'b> {
     pub caller: Option<Value>,
 }
 
+/// Observes the evaluation of expressions, for tools like the debugger.  `eval` calls
+/// `will_begin_eval` before it evaluates `expr`, and `did_finish_eval` with the result
+/// afterwards.  Hooks do not change what is evaluated or what it costs.
+pub trait EvalHook {
+    fn will_begin_eval(
+        &mut self,
+        env: &mut Environment,
+        context: &LocalContext,
+        expr: &SymbolicExpression,
+    );
+
+    fn did_finish_eval(
+        &mut self,
+        env: &mut Environment,
+        context: &LocalContext,
+        expr: &SymbolicExpression,
+        res: &Result<Value>,
+    );
+}
+
 pub struct OwnedEnvironment<'a> {
     context: GlobalContext<'a>,
     default_contract: ContractContext,
@@ -70,7 +92,8 @@ pub enum AssetMapEntry {
 
 /**
 The AssetMap is used to track which assets have been transfered from whom
-during the execution of a transaction.
+during the execution of a transaction, and which have been minted to whom.
+Post-conditions only look at the transfers.
 */
 #[derive(Debug, Clone)]
 pub struct AssetMap {
@@ -78,6 +101,9 @@ pub struct AssetMap {
     burn_map: HashMap<PrincipalData, u128>,
     token_map: HashMap<PrincipalData, HashMap<AssetIdentifier, u128>>,
     asset_map: HashMap<PrincipalData, HashMap<AssetIdentifier, Vec<Value>>>,
+    // keyed by recipient
+    token_mint_map: HashMap<PrincipalData, HashMap<AssetIdentifier, u128>>,
+    asset_mint_map: HashMap<PrincipalData, HashMap<AssetIdentifier, Vec<Value>>>,
 }
 
 #[derive(Debug, Clone)]
@@ -97,6 +123,10 @@ pub struct GlobalContext<'a> {
     pub database: ClarityDatabase<'a>,
     read_only: Vec<bool>,
     pub cost_track: LimitedCostTracker,
+    /// hooks called around every expression `eval` evaluates, e.g. by the debugger
+    pub eval_hooks: Option<Vec<Box<dyn EvalHook>>>,
+    /// the epoch whose rules contracts deployed in this context follow
+    pub epoch: StacksEpochId,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -110,6 +140,22 @@ pub struct ContractContext {
     //  used for ensuring that they never are defined twice.
     pub persisted_names: HashSet<ClarityName>,
     pub data_size: u64,
+    /// the epoch the contract was deployed in.  It can call the natives of that epoch, and
+    /// define functions named after natives that came later.  Stacks 2.0 contracts are stored
+    /// without it, so that their serialization doesn't change.
+    #[serde(
+        default = "stacks_2_0_epoch",
+        skip_serializing_if = "is_stacks_2_0_epoch"
+    )]
+    pub epoch: StacksEpochId,
+}
+
+fn stacks_2_0_epoch() -> StacksEpochId {
+    StacksEpochId::Epoch20
+}
+
+fn is_stacks_2_0_epoch(epoch: &StacksEpochId) -> bool {
+    *epoch == StacksEpochId::Epoch20
 }
 
 pub struct LocalContext<'a> {
@@ -136,6 +182,8 @@ impl AssetMap {
             burn_map: HashMap::new(),
             token_map: HashMap::new(),
             asset_map: HashMap::new(),
+            token_mint_map: HashMap::new(),
+            asset_mint_map: HashMap::new(),
         }
     }
 
@@ -172,6 +220,23 @@ impl AssetMap {
             .ok_or(RuntimeErrorType::ArithmeticOverflow.into())
     }
 
+    // This will get the next amount for a (principal, asset) entry in the token mint table.
+    fn get_next_mint_amount(
+        &self,
+        principal: &PrincipalData,
+        asset: &AssetIdentifier,
+        amount: u128,
+    ) -> Result<u128> {
+        let current_amount = match self.token_mint_map.get(principal) {
+            Some(principal_map) => *principal_map.get(&asset).unwrap_or(&0),
+            None => 0,
+        };
+
+        current_amount
+            .checked_add(amount)
+            .ok_or(RuntimeErrorType::ArithmeticOverflow.into())
+    }
+
     pub fn add_stx_transfer(&mut self, principal: &PrincipalData, amount: u128) -> Result<()> {
         let next_amount = self.get_next_stx_amount(principal, amount)?;
         self.stx_map.insert(principal.clone(), next_amount);
@@ -224,12 +289,43 @@ impl AssetMap {
         Ok(())
     }
 
-    // This will add any asset transfer data from other to self,
+    pub fn add_token_mint(
+        &mut self,
+        recipient: &PrincipalData,
+        asset: AssetIdentifier,
+        amount: u128,
+    ) -> Result<()> {
+        let next_amount = self.get_next_mint_amount(recipient, &asset, amount)?;
+
+        self.token_mint_map
+            .entry(recipient.clone())
+            .or_insert_with(HashMap::new)
+            .insert(asset, next_amount);
+
+        Ok(())
+    }
+
+    pub fn add_asset_mint(
+        &mut self,
+        recipient: &PrincipalData,
+        asset: AssetIdentifier,
+        minted: Value,
+    ) {
+        self.asset_mint_map
+            .entry(recipient.clone())
+            .or_insert_with(HashMap::new)
+            .entry(asset)
+            .or_insert_with(Vec::new)
+            .push(minted);
+    }
+
+    // This will add any asset transfer and mint data from other to self,
     //   aborting _all_ changes in the event of an error, leaving self unchanged
     pub fn commit_other(&mut self, mut other: AssetMap) -> Result<()> {
         let mut to_add = Vec::new();
         let mut stx_to_add = Vec::new();
         let mut stx_burn_to_add = Vec::new();
+        let mut mints_to_add = Vec::new();
 
         for (principal, mut principal_map) in other.token_map.drain() {
             for (asset, amount) in principal_map.drain() {
@@ -238,6 +334,13 @@ impl AssetMap {
             }
         }
 
+        for (principal, mut principal_map) in other.token_mint_map.drain() {
+            for (asset, amount) in principal_map.drain() {
+                let next_amount = self.get_next_mint_amount(&principal, &asset, amount)?;
+                mints_to_add.push((principal.clone(), asset, next_amount));
+            }
+        }
+
         for (principal, stx_amount) in other.stx_map.drain() {
             let next_amount = self.get_next_stx_amount(&principal, stx_amount)?;
             stx_to_add.push((principal.clone(), next_amount));
@@ -282,6 +385,24 @@ impl AssetMap {
             principal_map.insert(asset, amount);
         }
 
+        for (principal, mut principal_map) in other.asset_mint_map.drain() {
+            for (asset, mut minted) in principal_map.drain() {
+                self.asset_mint_map
+                    .entry(principal.clone())
+                    .or_insert_with(HashMap::new)
+                    .entry(asset)
+                    .or_insert_with(Vec::new)
+                    .append(&mut minted);
+            }
+        }
+
+        for (principal, asset, amount) in mints_to_add.drain(..) {
+            self.token_mint_map
+                .entry(principal)
+                .or_insert_with(HashMap::new)
+                .insert(asset, amount);
+        }
+
         Ok(())
     }
 
@@ -388,6 +509,29 @@ impl AssetMap {
             None => None,
         }
     }
+
+    /// how many of a fungible token were minted to `principal`
+    pub fn get_minted_fungible_tokens(
+        &self,
+        principal: &PrincipalData,
+        asset_identifier: &AssetIdentifier,
+    ) -> Option<u128> {
+        self.token_mint_map
+            .get(principal)
+            .and_then(|assets| assets.get(asset_identifier))
+            .cloned()
+    }
+
+    /// which non-fungible tokens were minted to `principal`, in the order they were minted
+    pub fn get_minted_nonfungible_tokens(
+        &self,
+        principal: &PrincipalData,
+        asset_identifier: &AssetIdentifier,
+    ) -> Option<&Vec<Value>> {
+        self.asset_mint_map
+            .get(principal)
+            .and_then(|assets| assets.get(asset_identifier))
+    }
 }
 
 impl fmt::Display for AssetMap {
@@ -413,6 +557,20 @@ impl fmt::Display for AssetMap {
         for (principal, stx_burn_amount) in self.burn_map.iter() {
             write!(f, "{} burned {} microSTX\n", principal, stx_burn_amount)?;
         }
+        for (principal, principal_map) in self.token_mint_map.iter() {
+            for (asset, amount) in principal_map.iter() {
+                write!(f, "{} received {} minted {}\n", principal, amount, asset)?;
+            }
+        }
+        for (principal, principal_map) in self.asset_mint_map.iter() {
+            for (asset, minted) in principal_map.iter() {
+                write!(f, "{} received minted [", principal)?;
+                for m in minted {
+                    write!(f, "{}, ", m)?;
+                }
+                write!(f, "] {}\n", asset)?;
+            }
+        }
         write!(f, "]")
     }
 }
@@ -443,6 +601,17 @@ impl<'a> OwnedEnvironment<'a> {
         }
     }
 
+    pub fn add_eval_hook(&mut self, hook: Box<dyn EvalHook>) {
+        self.context.add_eval_hook(hook);
+    }
+
+    /// Follow the given epoch's rules:  contracts deployed in this environment, and code run
+    /// outside of a contract, can only call that epoch's natives.
+    pub fn set_epoch(&mut self, epoch: StacksEpochId) {
+        self.context.epoch = epoch;
+        self.default_contract.epoch = epoch;
+    }
+
     pub fn get_exec_environment<'b>(&'b mut self, sender: Option<Value>) -> Environment<'b, 'a> {
         Environment::new(
             &mut self.context,
@@ -801,32 +970,23 @@ impl<'a, 'b> Environment<'a, 'b> {
     ) -> Result<Value> {
         let make_read_only = function.is_read_only();
 
-        if make_read_only {
-            self.global_context.begin_read_only();
-        } else {
-            self.global_context.begin();
-        }
-
         let next_contract_context = next_contract_context.unwrap_or(self.contract_context);
+        let call_stack = &mut *self.call_stack;
+        let sender = self.sender.clone();
+        let caller = self.caller.clone();
+
+        self.global_context
+            .execute_in_savepoint(make_read_only, |global_context| {
+                let mut nested_env = Environment::new(
+                    global_context,
+                    next_contract_context,
+                    call_stack,
+                    sender,
+                    caller,
+                );
 
-        let result = {
-            let mut nested_env = Environment::new(
-                &mut self.global_context,
-                next_contract_context,
-                self.call_stack,
-                self.sender.clone(),
-                self.caller.clone(),
-            );
-
-            function.execute_apply(args, &mut nested_env)
-        };
-
-        if make_read_only {
-            self.global_context.roll_back();
-            result
-        } else {
-            self.global_context.handle_tx_result(result)
-        }
+                function.execute_apply(args, &mut nested_env)
+            })
     }
 
     pub fn evaluate_at_block(
@@ -1105,6 +1265,16 @@ impl<'a> GlobalContext<'a> {
             read_only: Vec::new(),
             asset_maps: Vec::new(),
             event_batches: Vec::new(),
+            eval_hooks: None,
+            epoch: StacksEpochId::latest(),
+        }
+    }
+
+    /// Call `hook` before and after each expression this context evaluates
+    pub fn add_eval_hook(&mut self, hook: Box<dyn EvalHook>) {
+        match self.eval_hooks {
+            Some(ref mut hooks) => hooks.push(hook),
+            None => self.eval_hooks = Some(vec![hook]),
         }
     }
 
@@ -1148,6 +1318,36 @@ impl<'a> GlobalContext<'a> {
             .add_token_transfer(sender, asset_identifier, transfered)
     }
 
+    pub fn log_asset_mint(
+        &mut self,
+        recipient: &PrincipalData,
+        contract_identifier: &QualifiedContractIdentifier,
+        asset_name: &ClarityName,
+        minted: Value,
+    ) {
+        let asset_identifier = AssetIdentifier {
+            contract_identifier: contract_identifier.clone(),
+            asset_name: asset_name.clone(),
+        };
+        self.get_asset_map()
+            .add_asset_mint(recipient, asset_identifier, minted)
+    }
+
+    pub fn log_token_mint(
+        &mut self,
+        recipient: &PrincipalData,
+        contract_identifier: &QualifiedContractIdentifier,
+        asset_name: &ClarityName,
+        minted: u128,
+    ) -> Result<()> {
+        let asset_identifier = AssetIdentifier {
+            contract_identifier: contract_identifier.clone(),
+            asset_name: asset_name.clone(),
+        };
+        self.get_asset_map()
+            .add_token_mint(recipient, asset_identifier, minted)
+    }
+
     pub fn log_stx_transfer(&mut self, sender: &PrincipalData, transfered: u128) -> Result<()> {
         self.get_asset_map().add_stx_transfer(sender, transfered)
     }
@@ -1235,6 +1435,30 @@ impl<'a> GlobalContext<'a> {
         self.database.roll_back();
     }
 
+    /// Run `f` in a savepoint nested in the current one.  Its writes, asset transfers, and events
+    /// are kept if it returns a committed response, and rolled back -- leaving the enclosing
+    /// savepoint as it was -- if it returns an error response or fails.  A read-only savepoint
+    /// is always rolled back.
+    pub fn execute_in_savepoint<F>(&mut self, read_only: bool, f: F) -> Result<Value>
+    where
+        F: FnOnce(&mut GlobalContext<'a>) -> Result<Value>,
+    {
+        if read_only {
+            self.begin_read_only();
+        } else {
+            self.begin();
+        }
+
+        let result = f(self);
+
+        if read_only {
+            self.roll_back();
+            result
+        } else {
+            self.handle_tx_result(result)
+        }
+    }
+
     pub fn handle_tx_result(&mut self, result: Result<Value>) -> Result<Value> {
         if let Ok(result) = result {
             if let Value::Response(data) = result {
@@ -1278,6 +1502,7 @@ impl ContractContext {
             implemented_traits: HashSet::new(),
             persisted_names: HashSet::new(),
             data_size: 0,
+            epoch: StacksEpochId::latest(),
         }
     }
 
@@ -1301,7 +1526,7 @@ impl ContractContext {
     }
 
     pub fn is_name_used(&self, name: &str) -> bool {
-        is_reserved(name)
+        is_reserved(name, self.epoch)
             || self.variables.contains_key(name)
             || self.functions.contains_key(name)
             || self.persisted_names.contains(name)
@@ -1455,6 +1680,64 @@ mod test {
         assert_eq!(table[&p1][&t1], AssetMapEntry::Token(1));
     }
 
+    #[test]
+    fn test_asset_map_mints() {
+        let a_contract_id = QualifiedContractIdentifier::local("a").unwrap();
+        let b_contract_id = QualifiedContractIdentifier::local("b").unwrap();
+
+        let p1 = PrincipalData::Contract(a_contract_id.clone());
+        let p2 = PrincipalData::Contract(b_contract_id.clone());
+
+        let t1 = AssetIdentifier {
+            contract_identifier: a_contract_id.clone(),
+            asset_name: "a".into(),
+        };
+        let t2 = AssetIdentifier {
+            contract_identifier: b_contract_id.clone(),
+            asset_name: "a".into(),
+        };
+
+        let mut am1 = AssetMap::new();
+        let mut am2 = AssetMap::new();
+
+        am1.add_token_mint(&p1, t1.clone(), 10).unwrap();
+        am2.add_token_mint(&p1, t1.clone(), 15).unwrap();
+        am2.add_token_mint(&p2, t1.clone(), 1).unwrap();
+
+        am1.add_asset_mint(&p1, t2.clone(), Value::Int(1));
+        am2.add_asset_mint(&p1, t2.clone(), Value::Int(2));
+        am2.add_token_transfer(&p2, t1.clone(), 5).unwrap();
+
+        am1.commit_other(am2).unwrap();
+
+        assert_eq!(am1.get_minted_fungible_tokens(&p1, &t1), Some(25));
+        assert_eq!(am1.get_minted_fungible_tokens(&p2, &t1), Some(1));
+        assert_eq!(am1.get_minted_fungible_tokens(&p2, &t2), None);
+        assert_eq!(
+            am1.get_minted_nonfungible_tokens(&p1, &t2),
+            Some(&vec![Value::Int(1), Value::Int(2)])
+        );
+
+        // mints are not spent by anyone, so post-conditions never see them
+        let table = am1.to_table();
+        assert_eq!(table.len(), 1);
+        assert_eq!(table[&p2][&t1], AssetMapEntry::Token(5));
+
+        // a mint that overflows aborts the whole commit
+        let mut am1 = AssetMap::new();
+        let mut am2 = AssetMap::new();
+        am1.add_token_mint(&p1, t1.clone(), u128::max_value())
+            .unwrap();
+        am2.add_token_mint(&p1, t1.clone(), 1).unwrap();
+        am2.add_token_transfer(&p2, t1.clone(), 5).unwrap();
+        am1.commit_other(am2).unwrap_err();
+        assert_eq!(
+            am1.get_minted_fungible_tokens(&p1, &t1),
+            Some(u128::max_value())
+        );
+        assert_eq!(am1.to_table().len(), 0);
+    }
+
     #[test]
     fn test_asset_map_combinations() {
         let a_contract_id = QualifiedContractIdentifier::local("a").unwrap();
@@ -37,6 +37,7 @@ impl Contract {
         global_context: &mut GlobalContext,
     ) -> Result<Contract> {
         let mut contract_context = ContractContext::new(contract_identifier);
+        contract_context.epoch = global_context.epoch;
 
         eval_all(&contract.expressions, &mut contract_context, global_context)?;
 
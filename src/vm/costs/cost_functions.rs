@@ -151,6 +151,10 @@ def_runtime_cost!(AND { Linear(1, 1) });
 def_runtime_cost!(APPEND { Linear(1, 1) });
 def_runtime_cost!(CONCAT { Linear(1, 1) });
 def_runtime_cost!(AS_MAX_LEN { Constant(1) });
+def_runtime_cost!(INDEX_OF { Linear(1, 1) });
+def_runtime_cost!(SLICE { Linear(1, 1) });
+def_runtime_cost!(TO_CONSENSUS_BUFF { Linear(1, 1) });
+def_runtime_cost!(FROM_CONSENSUS_BUFF { Linear(1, 1) });
 
 def_runtime_cost!(CONTRACT_CALL { Constant(1) });
 def_runtime_cost!(CONTRACT_OF { Constant(1) });
@@ -0,0 +1,392 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Code coverage for Clarity contracts.  The `CoverageReporter` is an `EvalHook` that counts
+//! how often each expression is evaluated, keyed by where the expression starts in its
+//! contract's source, and the `CoverageData` it fills renders the counts of a contract as an
+//! lcov record.  Source locations are only kept in developer mode, so without it nothing is
+//! counted.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::rc::Rc;
+
+use core::StacksEpochId;
+
+use vm::contexts::{EvalHook, LocalContext};
+use vm::debug::expression_location;
+use vm::errors::InterpreterResult as Result;
+use vm::functions::define::DefineFunctionsParsed;
+use vm::is_reserved;
+use vm::types::QualifiedContractIdentifier;
+use vm::{Environment, SymbolicExpression, Value};
+
+/// How often each expression of each contract was evaluated
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoverageData {
+    hits: HashMap<QualifiedContractIdentifier, HashMap<(u32, u32), u64>>,
+}
+
+pub struct CoverageReporter {
+    coverage: Rc<RefCell<CoverageData>>,
+}
+
+/// A function defined by a contract, and the expression that is its body
+struct FunctionDefinition<'a> {
+    name: String,
+    line: u32,
+    body: &'a SymbolicExpression,
+}
+
+/// The parts of a contract that can be covered
+struct CoverableCode<'a> {
+    functions: Vec<FunctionDefinition<'a>>,
+    /// expressions that get evaluated at run time, outermost first
+    evaluated: Vec<&'a SymbolicExpression>,
+}
+
+impl<'a> CoverableCode<'a> {
+    fn from_expressions(expressions: &'a [SymbolicExpression]) -> CoverableCode<'a> {
+        let mut functions = vec![];
+        let mut roots = vec![];
+        for expr in expressions.iter() {
+            match DefineFunctionsParsed::try_parse(expr) {
+                Ok(Some(DefineFunctionsParsed::PrivateFunction { signature, body }))
+                | Ok(Some(DefineFunctionsParsed::ReadOnlyFunction { signature, body }))
+                | Ok(Some(DefineFunctionsParsed::PublicFunction { signature, body })) => {
+                    if let (Some(name), Some((line, _))) = (
+                        signature.first().and_then(|name| name.match_atom()),
+                        expression_location(expr),
+                    ) {
+                        functions.push(FunctionDefinition {
+                            name: name.to_string(),
+                            line,
+                            body,
+                        });
+                    }
+                    roots.push(body);
+                }
+                Ok(Some(DefineFunctionsParsed::Constant { value, .. })) => roots.push(value),
+                Ok(Some(DefineFunctionsParsed::PersistedVariable { initial, .. })) => {
+                    roots.push(initial)
+                }
+                Ok(Some(DefineFunctionsParsed::BoundedFungibleToken { max_supply, .. })) => {
+                    roots.push(max_supply)
+                }
+                Ok(Some(_)) | Err(_) => {}
+                Ok(None) => roots.push(expr),
+            }
+        }
+
+        let function_names: HashSet<&str> = functions.iter().map(|f| f.name.as_str()).collect();
+        let mut evaluated = vec![];
+        for root in roots.into_iter() {
+            evaluated.push(root);
+            collect_applications(root, &function_names, &mut evaluated);
+        }
+
+        CoverableCode {
+            functions,
+            evaluated,
+        }
+    }
+}
+
+/// Is `expr` a call to a native or user-defined function?  Lists that are not -- like the
+/// bindings of a `let` or the entries of a `tuple` -- are never evaluated themselves.
+fn is_application(expr: &SymbolicExpression, function_names: &HashSet<&str>) -> bool {
+    match expr
+        .match_list()
+        .and_then(|list| list.first())
+        .and_then(|head| head.match_atom())
+    {
+        Some(name) => {
+            is_reserved(name, StacksEpochId::latest()) || function_names.contains(&**name)
+        }
+        None => false,
+    }
+}
+
+fn collect_applications<'a>(
+    expr: &'a SymbolicExpression,
+    function_names: &HashSet<&str>,
+    applications: &mut Vec<&'a SymbolicExpression>,
+) {
+    if let Some(list) = expr.match_list() {
+        for child in list.iter() {
+            if is_application(child, function_names) {
+                applications.push(child);
+            }
+            collect_applications(child, function_names, applications);
+        }
+    }
+}
+
+/// The expressions `expr` chooses between, if it is a branch
+fn branch_arms(expr: &SymbolicExpression) -> Option<Vec<&SymbolicExpression>> {
+    let list = expr.match_list()?;
+    let head = list.first()?.match_atom()?;
+    match (&**head, list.len()) {
+        ("if", 4) => Some(vec![&list[2], &list[3]]),
+        // (match optional some-name some-branch none-branch)
+        ("match", 5) => Some(vec![&list[3], &list[4]]),
+        // (match response ok-name ok-branch err-name err-branch)
+        ("match", 6) => Some(vec![&list[3], &list[5]]),
+        _ => None,
+    }
+}
+
+impl CoverageData {
+    pub fn new() -> CoverageData {
+        CoverageData {
+            hits: HashMap::new(),
+        }
+    }
+
+    /// Count an evaluation of the expression starting at `location` in `contract_identifier`
+    pub fn record(
+        &mut self,
+        contract_identifier: &QualifiedContractIdentifier,
+        location: (u32, u32),
+    ) {
+        if !self.hits.contains_key(contract_identifier) {
+            self.hits
+                .insert(contract_identifier.clone(), HashMap::new());
+        }
+        if let Some(contract_hits) = self.hits.get_mut(contract_identifier) {
+            *contract_hits.entry(location).or_insert(0) += 1;
+        }
+    }
+
+    /// How often the expression starting at `location` in `contract_identifier` was evaluated
+    pub fn hits(
+        &self,
+        contract_identifier: &QualifiedContractIdentifier,
+        location: (u32, u32),
+    ) -> u64 {
+        self.hits
+            .get(contract_identifier)
+            .and_then(|contract_hits| contract_hits.get(&location))
+            .cloned()
+            .unwrap_or(0)
+    }
+
+    fn expression_hits(
+        &self,
+        contract_identifier: &QualifiedContractIdentifier,
+        expr: &SymbolicExpression,
+    ) -> Option<u64> {
+        expression_location(expr).map(|location| self.hits(contract_identifier, location))
+    }
+
+    /// The contracts with at least one evaluated expression
+    pub fn contracts(&self) -> Vec<QualifiedContractIdentifier> {
+        let mut contracts: Vec<_> = self.hits.keys().cloned().collect();
+        contracts.sort_by_key(|contract_identifier| contract_identifier.to_string());
+        contracts
+    }
+
+    /// The lcov record of the contract `contract_identifier`, whose source is in `source_name`
+    /// and parses to `expressions`.  A line counts as run as often as the most-evaluated
+    /// function call starting on it, and each `if` and `match` is a branch.
+    pub fn lcov_record(
+        &self,
+        contract_identifier: &QualifiedContractIdentifier,
+        source_name: &str,
+        expressions: &[SymbolicExpression],
+    ) -> String {
+        let code = CoverableCode::from_expressions(expressions);
+        let mut record = format!("TN:\nSF:{}\n", source_name);
+
+        let mut functions_hit = 0;
+        for function in code.functions.iter() {
+            record.push_str(&format!("FN:{},{}\n", function.line, &function.name));
+        }
+        for function in code.functions.iter() {
+            let calls = self
+                .expression_hits(contract_identifier, function.body)
+                .unwrap_or(0);
+            if calls > 0 {
+                functions_hit += 1;
+            }
+            record.push_str(&format!("FNDA:{},{}\n", calls, &function.name));
+        }
+        record.push_str(&format!(
+            "FNF:{}\nFNH:{}\n",
+            code.functions.len(),
+            functions_hit
+        ));
+
+        let mut branches = 0;
+        let mut branches_hit = 0;
+        for (block, expr) in code.evaluated.iter().enumerate() {
+            let (arms, (line, _), reached) = match (branch_arms(expr), expression_location(expr)) {
+                (Some(arms), Some(location)) => {
+                    (arms, location, self.hits(contract_identifier, location) > 0)
+                }
+                _ => continue,
+            };
+            for (branch, arm) in arms.iter().enumerate() {
+                branches += 1;
+                let taken = self.expression_hits(contract_identifier, arm).unwrap_or(0);
+                if taken > 0 {
+                    branches_hit += 1;
+                }
+                if reached {
+                    record.push_str(&format!("BRDA:{},{},{},{}\n", line, block, branch, taken));
+                } else {
+                    record.push_str(&format!("BRDA:{},{},{},-\n", line, block, branch));
+                }
+            }
+        }
+        record.push_str(&format!("BRF:{}\nBRH:{}\n", branches, branches_hit));
+
+        let mut lines: BTreeMap<u32, u64> = BTreeMap::new();
+        for expr in code.evaluated.iter() {
+            if let Some(location) = expression_location(expr) {
+                let hits = self.hits(contract_identifier, location);
+                let line_hits = lines.entry(location.0).or_insert(0);
+                *line_hits = (*line_hits).max(hits);
+            }
+        }
+        for (line, hits) in lines.iter() {
+            record.push_str(&format!("DA:{},{}\n", line, hits));
+        }
+        let lines_hit = lines.values().filter(|hits| **hits > 0).count();
+        record.push_str(&format!("LF:{}\nLH:{}\n", lines.len(), lines_hit));
+
+        record.push_str("end_of_record\n");
+        record
+    }
+}
+
+impl CoverageReporter {
+    pub fn new() -> CoverageReporter {
+        CoverageReporter {
+            coverage: Rc::new(RefCell::new(CoverageData::new())),
+        }
+    }
+
+    /// The counts this reporter records into.  They can be read once the covered code has run.
+    pub fn coverage(&self) -> Rc<RefCell<CoverageData>> {
+        self.coverage.clone()
+    }
+}
+
+impl EvalHook for CoverageReporter {
+    fn will_begin_eval(
+        &mut self,
+        env: &mut Environment,
+        _context: &LocalContext,
+        expr: &SymbolicExpression,
+    ) {
+        if let Some(location) = expression_location(expr) {
+            self.coverage
+                .borrow_mut()
+                .record(&env.contract_context.contract_identifier, location);
+        }
+    }
+
+    fn did_finish_eval(
+        &mut self,
+        _env: &mut Environment,
+        _context: &LocalContext,
+        _expr: &SymbolicExpression,
+        _res: &Result<Value>,
+    ) {
+    }
+}
+
+#[cfg(all(test, feature = "developer-mode"))]
+mod test {
+    use super::*;
+
+    use vm::ast::build_ast;
+    use vm::contexts::OwnedEnvironment;
+    use vm::tests::{execute, symbols_from_values, with_memory_environment};
+
+    const SIGN_CONTRACT: &str = "(define-read-only (sign (n int))
+  (if (< n 0)
+      (- 0 1)
+      (if (is-eq n 0)
+          0
+          1)))
+(define-public (get-sign (n int))
+  (ok (sign n)))
+(define-public (unused)
+  (ok (let ((a 1))
+        (+ a 1))))";
+
+    #[test]
+    fn test_coverage_lcov_record() {
+        with_memory_environment(
+            |owned_env: &mut OwnedEnvironment| {
+                let contract_id = QualifiedContractIdentifier::local("sign").unwrap();
+                owned_env
+                    .initialize_contract(contract_id.clone(), SIGN_CONTRACT)
+                    .unwrap();
+
+                let reporter = CoverageReporter::new();
+                let coverage = reporter.coverage();
+                owned_env.add_eval_hook(Box::new(reporter));
+
+                let sender = execute("'SZ2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQ9H6DPR");
+                for n in [5, 7, -2].iter() {
+                    owned_env
+                        .execute_transaction(
+                            sender.clone(),
+                            contract_id.clone(),
+                            "get-sign",
+                            &symbols_from_values(vec![Value::Int(*n)]),
+                        )
+                        .unwrap();
+                }
+
+                let expressions = build_ast(&contract_id, SIGN_CONTRACT, &mut ())
+                    .unwrap()
+                    .expressions;
+                let coverage = coverage.borrow();
+                assert_eq!(coverage.contracts(), vec![contract_id.clone()]);
+                let record =
+                    coverage.lcov_record(&contract_id, "contracts/sign.clar", &expressions);
+                let lines: Vec<&str> = record.lines().collect();
+
+                assert_eq!(lines[0], "TN:");
+                assert_eq!(lines[1], "SF:contracts/sign.clar");
+                assert!(lines.contains(&"FN:1,sign"));
+                assert!(lines.contains(&"FN:7,get-sign"));
+                assert!(lines.contains(&"FN:9,unused"));
+                assert!(lines.contains(&"FNDA:3,sign"));
+                assert!(lines.contains(&"FNDA:3,get-sign"));
+                assert!(lines.contains(&"FNDA:0,unused"));
+                assert!(lines.contains(&"FNF:3"));
+                assert!(lines.contains(&"FNH:2"));
+
+                // the outer `if` took each of its branches, the inner one only its else branch
+                assert!(lines.contains(&"BRF:4"));
+                assert!(lines.contains(&"BRH:3"));
+
+                assert!(lines.contains(&"DA:2,3"));
+                assert!(lines.contains(&"DA:3,1"));
+                assert!(lines.contains(&"DA:4,2"));
+                assert!(lines.contains(&"DA:10,0"));
+                assert!(lines.contains(&"DA:11,0"));
+                assert_eq!(lines.last(), Some(&"end_of_record"));
+            },
+            true,
+        );
+    }
+}
@@ -36,11 +36,12 @@ use chainstate::stacks::StacksBlockHeader;
 use chainstate::stacks::{StacksAddress, StacksBlockId};
 
 use util::db::{DBConn, FromRow};
-use util::hash::{Sha256Sum, Sha512Trunc256Sum};
+use util::hash::{Hash160, Sha256Sum, Sha512Trunc256Sum};
 use vm::costs::CostOverflowingMath;
 use vm::database::structures::{
     ClarityDeserializable, ClaritySerializable, ContractMetadata, DataMapMetadata,
-    DataVariableMetadata, FungibleTokenMetadata, NonFungibleTokenMetadata, STXBalance, SimmedBlock,
+    DataVariableMetadata, FungibleTokenMetadata, MicroblockPoisonReport, NonFungibleTokenMetadata,
+    STXBalance, SimmedBlock,
 };
 use vm::database::RollbackWrapper;
 use vm::database::{ClarityBackingStore, MarfedKV};
@@ -49,6 +50,7 @@ use chainstate::burn::db::sortdb::{
     SortitionDB, SortitionDBConn, SortitionHandleConn, SortitionHandleTx, SortitionId,
 };
 
+use core::StacksEpoch;
 use core::{
     FIRST_BURNCHAIN_BLOCK_HASH, FIRST_BURNCHAIN_BLOCK_HEIGHT, FIRST_BURNCHAIN_BLOCK_TIMESTAMP,
     FIRST_BURNCHAIN_CONSENSUS_HASH, FIRST_STACKS_BLOCK_HASH, POX_REWARD_CYCLE_LENGTH,
@@ -103,6 +105,8 @@ pub trait BurnStateDB {
         height: u32,
         sortition_id: &SortitionId,
     ) -> Option<BurnchainHeaderHash>;
+    /// Get the Stacks epoch that contains the given burnchain block height
+    fn get_stacks_epoch(&self, height: u32) -> Option<StacksEpoch>;
 }
 
 fn get_stacks_header_info(conn: &DBConn, id_bhh: &StacksBlockId) -> Option<StacksHeaderInfo> {
@@ -215,6 +219,11 @@ impl BurnStateDB for SortitionHandleTx<'_> {
             _ => return None,
         }
     }
+
+    fn get_stacks_epoch(&self, height: u32) -> Option<StacksEpoch> {
+        SortitionDB::get_stacks_epoch(self.tx(), height as u64)
+            .expect("BUG: failed to get the Stacks epoch from the sortition DB")
+    }
 }
 
 impl BurnStateDB for SortitionDBConn<'_> {
@@ -236,6 +245,11 @@ impl BurnStateDB for SortitionDBConn<'_> {
             _ => return None,
         }
     }
+
+    fn get_stacks_epoch(&self, height: u32) -> Option<StacksEpoch> {
+        SortitionDB::get_stacks_epoch(self.conn(), height as u64)
+            .expect("BUG: failed to get the Stacks epoch from the sortition DB")
+    }
 }
 
 impl BurnStateDB for &dyn BurnStateDB {
@@ -250,6 +264,10 @@ impl BurnStateDB for &dyn BurnStateDB {
     ) -> Option<BurnchainHeaderHash> {
         (*self).get_burn_header_hash(height, sortition_id)
     }
+
+    fn get_stacks_epoch(&self, height: u32) -> Option<StacksEpoch> {
+        (*self).get_stacks_epoch(height)
+    }
 }
 
 pub struct NullHeadersDB {}
@@ -336,6 +354,10 @@ impl BurnStateDB for NullBurnStateDB {
     ) -> Option<BurnchainHeaderHash> {
         None
     }
+
+    fn get_stacks_epoch(&self, _height: u32) -> Option<StacksEpoch> {
+        None
+    }
 }
 
 impl<'a> ClarityDatabase<'a> {
@@ -1123,6 +1145,49 @@ impl<'a> ClarityDatabase<'a> {
     }
 }
 
+// microblock stream equivocation
+impl<'a> ClarityDatabase<'a> {
+    fn make_key_for_microblock_pubkey_hash_height(pubkey_hash: &Hash160) -> String {
+        format!("microblock-pubkey-hash::{}", pubkey_hash)
+    }
+
+    fn make_key_for_microblock_poison(height: u64) -> String {
+        format!("microblock-poison::{}", height)
+    }
+
+    /// Remember the height of the anchored block whose microblocks are signed by this key
+    pub fn insert_microblock_pubkey_hash_height(&mut self, pubkey_hash: &Hash160, height: u64) {
+        let key = ClarityDatabase::make_key_for_microblock_pubkey_hash_height(pubkey_hash);
+        self.put(&key, &height);
+    }
+
+    pub fn get_microblock_pubkey_hash_height(&mut self, pubkey_hash: &Hash160) -> Option<u64> {
+        let key = ClarityDatabase::make_key_for_microblock_pubkey_hash_height(pubkey_hash);
+        self.get(&key)
+    }
+
+    /// Record that the miner of the anchored block at this height signed two microblocks with
+    /// the given sequence number
+    pub fn insert_microblock_poison(
+        &mut self,
+        height: u64,
+        reporter: &StandardPrincipalData,
+        sequence: u16,
+    ) {
+        let key = ClarityDatabase::make_key_for_microblock_poison(height);
+        let report = MicroblockPoisonReport {
+            reporter: reporter.clone(),
+            sequence,
+        };
+        self.put(&key, &report);
+    }
+
+    pub fn get_microblock_poison_report(&mut self, height: u64) -> Option<MicroblockPoisonReport> {
+        let key = ClarityDatabase::make_key_for_microblock_poison(height);
+        self.get(&key)
+    }
+}
+
 // access burnchain state
 impl<'a> ClarityDatabase<'a> {
     pub fn get_burn_block_height(&self, sortition_id: &SortitionId) -> Option<u32> {
@@ -26,6 +26,9 @@ use chainstate::stacks::StacksBlockId;
 use std::convert::TryInto;
 use util::hash::{hex_bytes, to_hex, Sha512Trunc256Sum};
 use vm::analysis::AnalysisDatabase;
+use vm::database::migration::CLARITY_DB_VERSION;
+#[cfg(feature = "rocksdb_store")]
+use vm::database::rocksdb_store::RocksDBValueStore;
 use vm::database::{
     BurnStateDB, ClarityDatabase, ClarityDeserializable, ClaritySerializable, HeadersDB,
     SqliteConnection, NULL_BURN_STATE_DB, NULL_HEADER_DB,
@@ -48,12 +51,22 @@ pub struct MarfedKV {
     // Since the MARF only stores 32 bytes of value,
     //   we need another storage
     side_store: SqliteConnection,
+    // if set, holds the values instead of the side store's data table
+    value_store: Option<Box<dyn ClarityValueStore>>,
 }
 
+/// The directory, next to the MARF, that a RocksDB value store lives in.  If it exists, the
+/// MarfedKV opened on that path reads and writes values there instead of in the side store.
+pub const ROCKSDB_VALUE_STORE_DIR: &str = "values.rocksdb";
+
 pub struct MemoryBackingStore {
     side_store: SqliteConnection,
 }
 
+/// The storage that the Clarity database reads and writes through.  Keys map to values as of
+/// a chain tip, which `set_block_hash` can move for time-shifted evaluation; contract metadata
+/// is kept in the side store.  `MarfedKV` is the implementation that commits to the MARF;
+/// `MemoryBackingStore` keeps everything in an in-memory side store, for tests and tools.
 // These functions generally _do not_ return errors, rather, any errors in the underlying storage
 //    will _panic_. The rationale for this is that under no condition should the interpreter
 //    attempt to continue processing in the event of an unexpected storage error.
@@ -142,6 +155,15 @@ pub trait ClarityBackingStore {
     }
 }
 
+/// Where a MarfedKV keeps the values its MARF commits to, keyed by the hex of their hashes.
+/// Values are content-addressed, so a store never has to forget one: a value written in a
+/// block that is later rolled back is simply never looked up.  Like `ClarityBackingStore`,
+/// implementations panic on storage errors.
+pub trait ClarityValueStore: Send {
+    fn get_value(&mut self, value_hash: &str) -> Option<String>;
+    fn put_values(&mut self, items: &[(String, String)]);
+}
+
 pub struct ContractCommitment {
     pub hash: Sha512Trunc256Sum,
     pub block_height: u32,
@@ -164,10 +186,50 @@ impl ClarityDeserializable<ContractCommitment> for ContractCommitment {
 }
 
 impl MarfedKV {
+    /// Open the RocksDB value store under `path_str`, if there is one
+    #[cfg(feature = "rocksdb_store")]
+    fn open_value_store(
+        path_str: &str,
+        readonly: bool,
+    ) -> Result<Option<Box<dyn ClarityValueStore>>> {
+        let mut path = PathBuf::from(path_str);
+        path.push(ROCKSDB_VALUE_STORE_DIR);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let store = if readonly {
+            RocksDBValueStore::open_readonly(&path)?
+        } else {
+            RocksDBValueStore::open(&path)?
+        };
+        Ok(Some(Box::new(store)))
+    }
+
+    #[cfg(not(feature = "rocksdb_store"))]
+    fn open_value_store(
+        path_str: &str,
+        _readonly: bool,
+    ) -> Result<Option<Box<dyn ClarityValueStore>>> {
+        let mut path = PathBuf::from(path_str);
+        path.push(ROCKSDB_VALUE_STORE_DIR);
+        if path.exists() {
+            return Err(InterpreterError::ValueStoreError(format!(
+                "{} holds a RocksDB value store, but this build lacks the `rocksdb_store` feature",
+                path_str
+            ))
+            .into());
+        }
+        Ok(None)
+    }
+
     fn setup_db(
         path_str: &str,
         unconfirmed: bool,
-    ) -> Result<(SqliteConnection, MARF<StacksBlockId>)> {
+    ) -> Result<(
+        SqliteConnection,
+        MARF<StacksBlockId>,
+        Option<Box<dyn ClarityValueStore>>,
+    )> {
         let mut path = PathBuf::from(path_str);
 
         std::fs::create_dir_all(&path)
@@ -187,6 +249,13 @@ impl MarfedKV {
             .to_string();
 
         let side_store = SqliteConnection::initialize(&data_path)?;
+        let db_version = side_store.get_db_version()?;
+        if db_version < CLARITY_DB_VERSION {
+            warn!(
+                "Clarity database {} is at version {}, not {}: migrate it with `clarity-cli migrate_db`",
+                path_str, db_version, CLARITY_DB_VERSION
+            );
+        }
         let marf = if unconfirmed {
             MARF::from_path_unconfirmed(&marf_path)
                 .map_err(|err| InterpreterError::MarfFailure(IncomparableError { err }))?
@@ -194,12 +263,13 @@ impl MarfedKV {
             MARF::from_path(&marf_path)
                 .map_err(|err| InterpreterError::MarfFailure(IncomparableError { err }))?
         };
+        let value_store = MarfedKV::open_value_store(path_str, false)?;
 
-        Ok((side_store, marf))
+        Ok((side_store, marf, value_store))
     }
 
     pub fn open(path_str: &str, miner_tip: Option<&StacksBlockId>) -> Result<MarfedKV> {
-        let (side_store, marf) = MarfedKV::setup_db(path_str, false)?;
+        let (side_store, marf, value_store) = MarfedKV::setup_db(path_str, false)?;
         let chain_tip = match miner_tip {
             Some(ref miner_tip) => *miner_tip.clone(),
             None => StacksBlockId::sentinel(),
@@ -209,11 +279,50 @@ impl MarfedKV {
             marf,
             chain_tip,
             side_store,
+            value_store,
         })
     }
 
     pub fn open_unconfirmed(path_str: &str, miner_tip: Option<&StacksBlockId>) -> Result<MarfedKV> {
-        let (side_store, marf) = MarfedKV::setup_db(path_str, true)?;
+        let (side_store, marf, value_store) = MarfedKV::setup_db(path_str, true)?;
+        let chain_tip = match miner_tip {
+            Some(ref miner_tip) => *miner_tip.clone(),
+            None => StacksBlockId::sentinel(),
+        };
+
+        Ok(MarfedKV {
+            marf,
+            chain_tip,
+            side_store,
+            value_store,
+        })
+    }
+
+    /// Open an existing MARF and side-store without write access.  Each read sees the state
+    /// committed as of the read, so this can be used while another MarfedKV on the same path
+    /// processes blocks.
+    pub fn open_readonly(path_str: &str, miner_tip: Option<&StacksBlockId>) -> Result<MarfedKV> {
+        let mut path = PathBuf::from(path_str);
+
+        path.push("marf");
+        let marf_path = path
+            .to_str()
+            .ok_or_else(|| InterpreterError::BadFileName)?
+            .to_string();
+
+        path.pop();
+        path.push("data.sqlite");
+        let data_path = path
+            .to_str()
+            .ok_or_else(|| InterpreterError::BadFileName)?
+            .to_string();
+
+        let side_store = SqliteConnection::open_readonly(&data_path)?;
+        let marf = TrieFileStorage::open_readonly(&marf_path)
+            .map(MARF::from_storage)
+            .map_err(|err| InterpreterError::MarfFailure(IncomparableError { err }))?;
+        let value_store = MarfedKV::open_value_store(path_str, true)?;
+
         let chain_tip = match miner_tip {
             Some(ref miner_tip) => *miner_tip.clone(),
             None => StacksBlockId::sentinel(),
@@ -223,6 +332,7 @@ impl MarfedKV {
             marf,
             chain_tip,
             side_store,
+            value_store,
         })
     }
 
@@ -248,6 +358,7 @@ impl MarfedKV {
             marf,
             chain_tip,
             side_store,
+            value_store: None,
         }
     }
 
@@ -394,9 +505,28 @@ impl MarfedKV {
         &mut self.marf
     }
 
+    fn get_value(&mut self, value_hash: &str) -> Option<String> {
+        match self.value_store {
+            Some(ref mut value_store) => value_store.get_value(value_hash),
+            None => self.side_store.get(value_hash),
+        }
+    }
+
+    fn put_values(&mut self, items: &[(String, String)]) {
+        match self.value_store {
+            Some(ref mut value_store) => value_store.put_values(items),
+            None => self.side_store.put_all(items),
+        }
+    }
+
+    /// true if this MarfedKV keeps its values in a store other than the side store
+    pub fn has_value_store(&self) -> bool {
+        self.value_store.is_some()
+    }
+
     pub fn put(&mut self, key: &str, value: &str) {
         let marf_value = MARFValue::from_value(value);
-        self.side_store.put(&marf_value.to_hex(), value);
+        self.put_values(&[(marf_value.to_hex(), value.to_string())]);
 
         self.marf
             .insert(key, marf_value)
@@ -479,7 +609,7 @@ impl ClarityBackingStore for MarfedKV {
             .expect("ERROR: Unexpected MARF Failure on GET")
             .map(|(marf_value, proof)| {
                 let side_key = marf_value.to_hex();
-                let data = self.side_store.get(&side_key).expect(&format!(
+                let data = self.get_value(&side_key).expect(&format!(
                     "ERROR: MARF contained value_hash not found in side storage: {}",
                     side_key
                 ));
@@ -506,7 +636,7 @@ impl ClarityBackingStore for MarfedKV {
             .map(|marf_value| {
                 let side_key = marf_value.to_hex();
                 trace!("MarfedKV get side-key for {:?}: {:?}", key, &side_key);
-                self.side_store.get(&side_key).expect(&format!(
+                self.get_value(&side_key).expect(&format!(
                     "ERROR: MARF contained value_hash not found in side storage: {}",
                     side_key
                 ))
@@ -516,13 +646,15 @@ impl ClarityBackingStore for MarfedKV {
     fn put_all(&mut self, mut items: Vec<(String, String)>) {
         let mut keys = Vec::new();
         let mut values = Vec::new();
+        let mut side_items = Vec::new();
         for (key, value) in items.drain(..) {
             trace!("MarfedKV put '{}' = '{}'", &key, &value);
             let marf_value = MARFValue::from_value(&value);
-            self.side_store.put(&marf_value.to_hex(), &value);
+            side_items.push((marf_value.to_hex(), value));
             keys.push(key);
             values.push(marf_value);
         }
+        self.put_values(&side_items);
         self.marf
             .insert_batch(&keys, values)
             .expect("ERROR: Unexpected MARF Failure");
@@ -588,9 +720,7 @@ impl ClarityBackingStore for MemoryBackingStore {
         0
     }
 
-    fn put_all(&mut self, mut items: Vec<(String, String)>) {
-        for (key, value) in items.drain(..) {
-            self.side_store.put(&key, &value);
-        }
+    fn put_all(&mut self, items: Vec<(String, String)>) {
+        self.side_store.put_all(&items);
     }
 }
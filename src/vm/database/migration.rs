@@ -0,0 +1,291 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Versions of the layout of the metadata in the Clarity side store, and the migrations that
+//! upgrade a store from one version to the next in place.  Migrations only ever rewrite
+//! metadata: the contract state committed to the MARF is consensus-critical, so a migrated
+//! store must have the same state root it had before, which `migrate` checks.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use chainstate::stacks::index::TrieHash;
+use chainstate::stacks::StacksBlockId;
+use vm::database::clarity_db::StoreType;
+use vm::database::structures::{DataMapMetadata, DataVariableMetadata};
+use vm::database::{ClarityBackingStore, ClarityDatabase, MarfedKV, SqliteConnection};
+use vm::errors::{IncomparableError, InterpreterError, InterpreterResult as Result};
+
+/// The version of the metadata layout this software reads and writes
+pub const CLARITY_DB_VERSION: u32 = 1;
+
+struct Migration {
+    /// the version a store is at once this migration has run
+    version: u32,
+    description: &'static str,
+    /// returns the number of metadata entries rewritten
+    apply: fn(&SqliteConnection) -> Result<u64>,
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    description: "check and re-encode data-var and map metadata",
+    apply: reencode_data_metadata,
+}];
+
+/// What a run of `migrate` did, or would have done
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationReport {
+    pub from_version: u32,
+    pub to_version: u32,
+    /// the version, description, and number of rewritten entries of each migration run
+    pub applied: Vec<(u32, &'static str, u64)>,
+    /// the state root at the migrated chain tip, which the migrations leave unchanged
+    pub state_root: TrieHash,
+    /// if true, the migrations were rolled back
+    pub dry_run: bool,
+}
+
+/// Rewrite every metadata entry starting with `key_prefix` as the serialization of its parsed
+/// `T`.  An entry that does not parse fails the migration.
+fn reencode_metadata<T: Serialize + DeserializeOwned>(
+    side_store: &SqliteConnection,
+    key_prefix: &str,
+) -> Result<u64> {
+    let mut rewritten = 0;
+    for (blockhash, key, value) in side_store.get_metadata_entries(key_prefix)? {
+        let parsed: T = serde_json::from_str(&value)
+            .map_err(|_| InterpreterError::BadMetadataEntry(key.clone()))?;
+        let reencoded = serde_json::to_string(&parsed)
+            .map_err(|_| InterpreterError::BadMetadataEntry(key.clone()))?;
+        if reencoded != value {
+            side_store.replace_metadata_entry(&blockhash, &key, &reencoded)?;
+            rewritten += 1;
+        }
+    }
+    Ok(rewritten)
+}
+
+fn reencode_data_metadata(side_store: &SqliteConnection) -> Result<u64> {
+    let variables = reencode_metadata::<DataVariableMetadata>(
+        side_store,
+        &ClarityDatabase::make_metadata_key(StoreType::VariableMeta, ""),
+    )?;
+    let maps = reencode_metadata::<DataMapMetadata>(
+        side_store,
+        &ClarityDatabase::make_metadata_key(StoreType::DataMapMeta, ""),
+    )?;
+    Ok(variables + maps)
+}
+
+fn state_root_at(marf_kv: &mut MarfedKV, tip: &StacksBlockId) -> Result<TrieHash> {
+    marf_kv
+        .get_marf()
+        .get_root_hash_at(tip)
+        .map_err(|err| InterpreterError::MarfFailure(IncomparableError { err }).into())
+}
+
+fn run_migrations(
+    side_store: &SqliteConnection,
+    from_version: u32,
+) -> Result<Vec<(u32, &'static str, u64)>> {
+    let mut applied = vec![];
+    for migration in MIGRATIONS.iter() {
+        if migration.version <= from_version {
+            continue;
+        }
+        let rewritten = (migration.apply)(side_store)?;
+        side_store.set_db_version(migration.version)?;
+        applied.push((migration.version, migration.description, rewritten));
+    }
+    Ok(applied)
+}
+
+/// Upgrade the metadata in `marf_kv`'s side store to `CLARITY_DB_VERSION`, and check that the
+/// state root at `tip` is unchanged.  The migrations run in one savepoint, which is rolled back
+/// if any of them fails, if the state root changed, or if `dry_run` is set.
+pub fn migrate(
+    marf_kv: &mut MarfedKV,
+    tip: &StacksBlockId,
+    dry_run: bool,
+) -> Result<MigrationReport> {
+    let state_root = state_root_at(marf_kv, tip)?;
+
+    let from_version = marf_kv.get_side_store().get_db_version()?;
+    if from_version > CLARITY_DB_VERSION {
+        return Err(InterpreterError::UnsupportedDatabaseVersion(from_version).into());
+    }
+
+    marf_kv.get_side_store().begin_migration()?;
+
+    let applied = run_migrations(marf_kv.get_side_store(), from_version).and_then(|applied| {
+        let migrated_state_root = state_root_at(marf_kv, tip)?;
+        if migrated_state_root != state_root {
+            return Err(InterpreterError::MigrationChangedStateRoot(
+                state_root.to_hex(),
+                migrated_state_root.to_hex(),
+            )
+            .into());
+        }
+        Ok(applied)
+    });
+
+    match applied {
+        Ok(applied) => {
+            if dry_run {
+                marf_kv.get_side_store().rollback_migration()?;
+            } else {
+                marf_kv.get_side_store().commit_migration()?;
+            }
+            Ok(MigrationReport {
+                from_version,
+                to_version: CLARITY_DB_VERSION,
+                applied,
+                state_root,
+                dry_run,
+            })
+        }
+        Err(e) => {
+            marf_kv.get_side_store().rollback_migration()?;
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use vm::database::{NULL_BURN_STATE_DB, NULL_HEADER_DB};
+    use vm::errors::Error;
+    use vm::types::{QualifiedContractIdentifier, TypeSignature};
+
+    fn make_marf_kv() -> (MarfedKV, StacksBlockId) {
+        let mut marf_kv = MarfedKV::temporary();
+        let tip = StacksBlockId([1; 32]);
+        marf_kv.begin(&StacksBlockId::sentinel(), &tip);
+        {
+            let mut db = marf_kv.as_clarity_db(&NULL_HEADER_DB, &NULL_BURN_STATE_DB);
+            let contract_id = QualifiedContractIdentifier::local("counter").unwrap();
+            db.begin();
+            db.insert_contract_hash(&contract_id, "(define-data-var counter int 0)")
+                .unwrap();
+            db.create_variable(&contract_id, "counter", TypeSignature::IntType);
+            db.commit();
+        }
+        marf_kv.commit_to(&tip);
+        (marf_kv, tip)
+    }
+
+    #[test]
+    fn test_new_store_is_current() {
+        let (mut marf_kv, tip) = make_marf_kv();
+        assert_eq!(
+            marf_kv.get_side_store().get_db_version().unwrap(),
+            CLARITY_DB_VERSION
+        );
+
+        let report = migrate(&mut marf_kv, &tip, false).unwrap();
+        assert_eq!(report.from_version, CLARITY_DB_VERSION);
+        assert_eq!(report.applied.len(), 0);
+    }
+
+    #[test]
+    fn test_migrate_unversioned_store() {
+        let (mut marf_kv, tip) = make_marf_kv();
+        let state_root = marf_kv.get_marf().get_root_hash_at(&tip).unwrap();
+
+        // an unversioned store, whose variable metadata is encoded with extra whitespace
+        let side_store = marf_kv.get_side_store();
+        side_store.set_db_version(0).unwrap();
+        let key_prefix = ClarityDatabase::make_metadata_key(StoreType::VariableMeta, "");
+        let entries = side_store.get_metadata_entries(&key_prefix).unwrap();
+        assert_eq!(entries.len(), 1);
+        let (blockhash, key, value) = &entries[0];
+        let spaced = value.replace(":", " : ");
+        side_store
+            .replace_metadata_entry(blockhash, key, &spaced)
+            .unwrap();
+
+        let report = migrate(&mut marf_kv, &tip, true).unwrap();
+        assert_eq!(report.from_version, 0);
+        assert_eq!(report.applied, vec![(1, MIGRATIONS[0].description, 1)]);
+        assert_eq!(report.state_root, state_root);
+
+        // a dry run leaves the store as it was
+        assert_eq!(marf_kv.get_side_store().get_db_version().unwrap(), 0);
+        assert_eq!(
+            marf_kv
+                .get_side_store()
+                .get_metadata_entries(&key_prefix)
+                .unwrap()[0]
+                .2,
+            spaced
+        );
+
+        let report = migrate(&mut marf_kv, &tip, false).unwrap();
+        assert_eq!(report.applied, vec![(1, MIGRATIONS[0].description, 1)]);
+        assert_eq!(marf_kv.get_side_store().get_db_version().unwrap(), 1);
+        assert_eq!(
+            &marf_kv
+                .get_side_store()
+                .get_metadata_entries(&key_prefix)
+                .unwrap()[0]
+                .2,
+            value
+        );
+
+        // and the contract's metadata still loads
+        let mut db = marf_kv.as_clarity_db(&NULL_HEADER_DB, &NULL_BURN_STATE_DB);
+        let contract_id = QualifiedContractIdentifier::local("counter").unwrap();
+        assert_eq!(
+            db.load_variable(&contract_id, "counter")
+                .unwrap()
+                .value_type,
+            TypeSignature::IntType
+        );
+    }
+
+    #[test]
+    fn test_migrate_bad_entry() {
+        let (mut marf_kv, tip) = make_marf_kv();
+
+        let side_store = marf_kv.get_side_store();
+        side_store.set_db_version(0).unwrap();
+        let key_prefix = ClarityDatabase::make_metadata_key(StoreType::VariableMeta, "");
+        let (blockhash, key, _) = side_store.get_metadata_entries(&key_prefix).unwrap()[0].clone();
+        side_store
+            .replace_metadata_entry(&blockhash, &key, "{\"value_type\": 1}")
+            .unwrap();
+
+        assert_eq!(
+            migrate(&mut marf_kv, &tip, false).unwrap_err(),
+            Error::Interpreter(InterpreterError::BadMetadataEntry(key))
+        );
+        assert_eq!(marf_kv.get_side_store().get_db_version().unwrap(), 0);
+
+        // a store newer than this software is left alone
+        marf_kv
+            .get_side_store()
+            .set_db_version(CLARITY_DB_VERSION + 1)
+            .unwrap();
+        assert_eq!(
+            migrate(&mut marf_kv, &tip, false).unwrap_err(),
+            Error::Interpreter(InterpreterError::UnsupportedDatabaseVersion(
+                CLARITY_DB_VERSION + 1
+            ))
+        );
+    }
+}
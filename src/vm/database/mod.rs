@@ -17,6 +17,9 @@
 mod clarity_db;
 mod key_value_wrapper;
 pub mod marf;
+pub mod migration;
+#[cfg(feature = "rocksdb_store")]
+pub mod rocksdb_store;
 mod sqlite;
 mod structures;
 
@@ -27,6 +30,8 @@ pub use self::clarity_db::{
     STORE_CONTRACT_SRC_INTERFACE,
 };
 pub use self::key_value_wrapper::{RollbackWrapper, RollbackWrapperPersistedLog};
-pub use self::marf::{ClarityBackingStore, MarfedKV, MemoryBackingStore};
+pub use self::marf::{ClarityBackingStore, ClarityValueStore, MarfedKV, MemoryBackingStore};
 pub use self::sqlite::SqliteConnection;
-pub use self::structures::{ClarityDeserializable, ClaritySerializable, STXBalance};
+pub use self::structures::{
+    ClarityDeserializable, ClaritySerializable, MicroblockPoisonReport, STXBalance,
+};
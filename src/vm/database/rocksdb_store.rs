@@ -0,0 +1,300 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A RocksDB store for the values the Clarity MARF commits to, which a MarfedKV uses in place
+//! of the side store's data table when a `values.rocksdb` directory sits next to its MARF.
+//! Contract metadata stays in the side store.  On large archival nodes the data table is most
+//! of the side store, and RocksDB's batched writes and background compaction keep up with it
+//! better than SQLite's B-tree.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+
+use rocksdb::{DBCompressionType, Options, WriteBatch, DB};
+
+use vm::database::marf::ROCKSDB_VALUE_STORE_DIR;
+use vm::database::{ClarityValueStore, SqliteConnection};
+use vm::errors::{InterpreterError, InterpreterResult as Result};
+
+/// how many values `import_values` copies per write batch
+const IMPORT_BATCH_SIZE: u32 = 10_000;
+
+lazy_static! {
+    // RocksDB lets only one handle per process write to a store, so every MarfedKV opened on a
+    // store -- the chainstate's, the unconfirmed state's, ... -- shares it
+    static ref OPEN_STORES: Mutex<HashMap<PathBuf, Weak<DB>>> = Mutex::new(HashMap::new());
+}
+
+pub struct RocksDBValueStore {
+    db: Arc<DB>,
+    path: PathBuf,
+    // set for a read-only handle of its own, which must be reopened to see newer writes
+    reopen_on_miss: bool,
+}
+
+fn store_error(e: rocksdb::Error) -> InterpreterError {
+    InterpreterError::ValueStoreError(e.to_string())
+}
+
+impl RocksDBValueStore {
+    fn options() -> Options {
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.set_compression_type(DBCompressionType::Lz4);
+        opts.set_level_compaction_dynamic_level_bytes(true);
+        opts
+    }
+
+    fn registry_key(path: &Path) -> PathBuf {
+        fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+    }
+
+    /// Open the store at `path`, creating it if it does not exist
+    pub fn open(path: &Path) -> Result<RocksDBValueStore> {
+        fs::create_dir_all(path).map_err(|_| InterpreterError::FailedToCreateDataDirectory)?;
+        let key = RocksDBValueStore::registry_key(path);
+
+        let mut open_stores = OPEN_STORES
+            .lock()
+            .expect("FATAL: RocksDB value store registry poisoned");
+        let db = match open_stores.get(&key).and_then(|db| db.upgrade()) {
+            Some(db) => db,
+            None => {
+                let db =
+                    Arc::new(DB::open(&RocksDBValueStore::options(), path).map_err(store_error)?);
+                open_stores.insert(key, Arc::downgrade(&db));
+                db
+            }
+        };
+
+        Ok(RocksDBValueStore {
+            db,
+            path: path.to_path_buf(),
+            reopen_on_miss: false,
+        })
+    }
+
+    /// Open the store at `path` without write access.  If this process already writes to it,
+    /// that handle is shared.  Otherwise, the handle sees the store as of when it was opened, so
+    /// it reopens itself when a value is missing, in case the value was written since.
+    pub fn open_readonly(path: &Path) -> Result<RocksDBValueStore> {
+        let key = RocksDBValueStore::registry_key(path);
+        let shared = OPEN_STORES
+            .lock()
+            .expect("FATAL: RocksDB value store registry poisoned")
+            .get(&key)
+            .and_then(|db| db.upgrade());
+        if let Some(db) = shared {
+            return Ok(RocksDBValueStore {
+                db,
+                path: path.to_path_buf(),
+                reopen_on_miss: false,
+            });
+        }
+
+        let db = DB::open_for_read_only(&RocksDBValueStore::options(), path, false)
+            .map_err(store_error)?;
+        Ok(RocksDBValueStore {
+            db: Arc::new(db),
+            path: path.to_path_buf(),
+            reopen_on_miss: true,
+        })
+    }
+
+    fn read(&self, value_hash: &str) -> Option<String> {
+        self.db
+            .get(value_hash.as_bytes())
+            .expect("ERROR: Unexpected RocksDB failure on GET")
+            .map(|bytes| {
+                String::from_utf8(bytes).expect("ERROR: Non-UTF-8 value in RocksDB value store")
+            })
+    }
+}
+
+impl ClarityValueStore for RocksDBValueStore {
+    fn get_value(&mut self, value_hash: &str) -> Option<String> {
+        if let Some(value) = self.read(value_hash) {
+            return Some(value);
+        }
+        if !self.reopen_on_miss {
+            return None;
+        }
+        *self = RocksDBValueStore::open_readonly(&self.path)
+            .expect("ERROR: Failed to reopen RocksDB value store");
+        self.read(value_hash)
+    }
+
+    fn put_values(&mut self, items: &[(String, String)]) {
+        let mut batch = WriteBatch::default();
+        for (value_hash, value) in items.iter() {
+            batch.put(value_hash.as_bytes(), value.as_bytes());
+        }
+        self.db
+            .write(batch)
+            .expect("ERROR: Unexpected RocksDB failure on PUT");
+    }
+}
+
+/// Move the Clarity state in `clarity_state_dir` onto a RocksDB value store, by copying every
+/// value in the side store's data table into one, and return the number of values copied.  The
+/// copy is made in a scratch directory that is only renamed into place once it is complete, so
+/// an interrupted import is simply picked up again (values are content-addressed, so copying
+/// one twice is harmless).  The data table itself is left as it was.
+///
+/// Does nothing if the state is already on a RocksDB value store, and just creates an empty one
+/// if there is no state yet.
+pub fn import_values(clarity_state_dir: &str) -> Result<u64> {
+    let mut store_path = PathBuf::from(clarity_state_dir);
+    store_path.push(ROCKSDB_VALUE_STORE_DIR);
+    if store_path.exists() {
+        return Ok(0);
+    }
+
+    fs::create_dir_all(clarity_state_dir)
+        .map_err(|_| InterpreterError::FailedToCreateDataDirectory)?;
+
+    let mut scratch_path = PathBuf::from(clarity_state_dir);
+    scratch_path.push(format!("{}.import", ROCKSDB_VALUE_STORE_DIR));
+
+    let mut data_path = PathBuf::from(clarity_state_dir);
+    data_path.push("data.sqlite");
+
+    let mut copied = 0;
+    {
+        let mut store = RocksDBValueStore::open(&scratch_path)?;
+        if data_path.exists() {
+            let side_store = SqliteConnection::open(
+                data_path
+                    .to_str()
+                    .ok_or_else(|| InterpreterError::BadFileName)?,
+            )?;
+            let mut after_key = "".to_string();
+            loop {
+                let page = side_store.get_data_entries(&after_key, IMPORT_BATCH_SIZE)?;
+                match page.last() {
+                    Some((last_key, _)) => after_key = last_key.clone(),
+                    None => break,
+                }
+                store.put_values(&page);
+                copied += page.len() as u64;
+                debug!(
+                    "Copied {} values into the RocksDB value store in {}",
+                    copied, clarity_state_dir
+                );
+            }
+        }
+        store.db.flush().map_err(store_error)?;
+    }
+
+    fs::rename(&scratch_path, &store_path)
+        .map_err(|e| InterpreterError::ValueStoreError(e.to_string()))?;
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chainstate::stacks::StacksBlockId;
+    use rand::Rng;
+    use std::env;
+    use util::hash::to_hex;
+    use vm::database::{ClarityBackingStore, MarfedKV};
+
+    fn temp_dir() -> String {
+        let mut path = env::temp_dir();
+        path.push(to_hex(&rand::thread_rng().gen::<[u8; 32]>()));
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_import_values() {
+        let path = temp_dir();
+        let first = StacksBlockId([1; 32]);
+        let second = StacksBlockId([2; 32]);
+
+        {
+            let mut marf_kv = MarfedKV::open(&path, None).unwrap();
+            assert!(!marf_kv.has_value_store());
+            marf_kv.begin(&StacksBlockId::sentinel(), &first);
+            marf_kv.put_all(vec![
+                ("a".to_string(), "apple".to_string()),
+                ("b".to_string(), "banana".to_string()),
+            ]);
+            marf_kv.commit_to(&first);
+        }
+
+        assert_eq!(import_values(&path).unwrap(), 2);
+        // once moved, there is nothing left to do
+        assert_eq!(import_values(&path).unwrap(), 0);
+
+        let mut marf_kv = MarfedKV::open(&path, None).unwrap();
+        assert!(marf_kv.has_value_store());
+        // other handles in this process share the store
+        let unconfirmed = MarfedKV::open_unconfirmed(&path, None).unwrap();
+        assert!(unconfirmed.has_value_store());
+        marf_kv.set_chain_tip(&first);
+        assert_eq!(marf_kv.get("a"), Some("apple".to_string()));
+        assert_eq!(marf_kv.get("b"), Some("banana".to_string()));
+
+        // new values only go to the RocksDB store, but read the same
+        marf_kv.begin(&first, &second);
+        marf_kv.put_all(vec![("c".to_string(), "cherry".to_string())]);
+        marf_kv.commit_to(&second);
+
+        let mut reader = MarfedKV::open_readonly(&path, Some(&second)).unwrap();
+        assert_eq!(reader.get("c"), Some("cherry".to_string()));
+        assert_eq!(
+            marf_kv
+                .get_side_store()
+                .get_data_entries("", 10)
+                .unwrap()
+                .len(),
+            2
+        );
+
+        // and a reader opened before a value was written sees it
+        let third = StacksBlockId([3; 32]);
+        marf_kv.begin(&second, &third);
+        marf_kv.put_all(vec![("d".to_string(), "date".to_string())]);
+        marf_kv.commit_to(&third);
+        reader.set_chain_tip(&third);
+        assert_eq!(reader.get("d"), Some("date".to_string()));
+    }
+
+    #[test]
+    fn test_import_fresh_state() {
+        let path = temp_dir();
+        assert_eq!(import_values(&path).unwrap(), 0);
+
+        let mut marf_kv = MarfedKV::open(&path, None).unwrap();
+        assert!(marf_kv.has_value_store());
+        let first = StacksBlockId([1; 32]);
+        marf_kv.begin(&StacksBlockId::sentinel(), &first);
+        marf_kv.put_all(vec![("a".to_string(), "apple".to_string())]);
+        marf_kv.commit_to(&first);
+        assert_eq!(marf_kv.get("a"), Some("apple".to_string()));
+        assert_eq!(
+            marf_kv
+                .get_side_store()
+                .get_data_entries("", 10)
+                .unwrap()
+                .len(),
+            0
+        );
+    }
+}
@@ -14,17 +14,29 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+//! The Clarity side store: an SQLite database (`data.sqlite`, next to the MARF) in WAL mode,
+//! which can be inspected with the `sqlite3` shell while a node runs.  Its tables are
+//!
+//! * `data_table`, mapping the hex of each MARF value hash to the value it is the hash of
+//!   (unless the values are kept in a RocksDB store instead; see `marf::ClarityValueStore`),
+//! * `metadata_table`, holding contract metadata -- analyses, data-var and map types, etc. --
+//!   under `clr-meta::{contract hash}::{key}` at the block it was written in, and
+//! * `db_config`, holding the version of the metadata layout (see `migration`).
+//!
+//! Each block's writes are made in one savepoint, so they commit or roll back together.
+
 use rusqlite::types::{FromSql, ToSql};
 use rusqlite::{
-    Connection, Error as SqliteError, ErrorCode as SqliteErrorCode, OptionalExtension, Row,
-    Savepoint, NO_PARAMS,
+    Connection, Error as SqliteError, ErrorCode as SqliteErrorCode, OpenFlags, OptionalExtension,
+    Row, Savepoint, NO_PARAMS,
 };
 
 use chainstate::stacks::StacksBlockId;
 
-use util::db::tx_busy_handler;
+use util::db::{sqlite_set_wal_mode, tx_busy_handler};
 
 use vm::contracts::Contract;
+use vm::database::migration::CLARITY_DB_VERSION;
 use vm::errors::{
     Error, IncomparableError, InterpreterError, InterpreterResult as Result, RuntimeErrorType,
 };
@@ -35,12 +47,13 @@ pub struct SqliteConnection {
     conn: Connection,
 }
 
+// statements run for every read and write are prepared once per connection, and cached
 fn sqlite_put(conn: &Connection, key: &str, value: &str) {
     let params: [&dyn ToSql; 2] = [&key, &value];
-    match conn.execute(
-        "REPLACE INTO data_table (key, value) VALUES (?, ?)",
-        &params,
-    ) {
+    match conn
+        .prepare_cached("REPLACE INTO data_table (key, value) VALUES (?, ?)")
+        .and_then(|mut stmt| stmt.execute(&params))
+    {
         Ok(_) => {}
         Err(e) => {
             error!("Failed to insert/replace ({},{}): {:?}", key, value, &e);
@@ -53,12 +66,8 @@ fn sqlite_get(conn: &Connection, key: &str) -> Option<String> {
     trace!("sqlite_get {}", key);
     let params: [&dyn ToSql; 1] = [&key];
     let res = match conn
-        .query_row(
-            "SELECT value FROM data_table WHERE key = ?",
-            &params,
-            |row| row.get(0),
-        )
-        .optional()
+        .prepare_cached("SELECT value FROM data_table WHERE key = ?")
+        .and_then(|mut stmt| stmt.query_row(&params, |row| row.get(0)).optional())
     {
         Ok(x) => x,
         Err(e) => {
@@ -83,6 +92,20 @@ impl SqliteConnection {
         sqlite_get(&self.conn, key)
     }
 
+    /// Write a batch of entries in one transaction, rather than one transaction per entry
+    pub fn put_all(&mut self, items: &[(String, String)]) {
+        let batch = self.conn.execute_batch("SAVEPOINT put_all").and_then(|_| {
+            for (key, value) in items.iter() {
+                sqlite_put(&self.conn, key, value);
+            }
+            self.conn.execute_batch("RELEASE SAVEPOINT put_all")
+        });
+        if let Err(e) = batch {
+            error!("Failed to write batch of {} entries: {:?}", items.len(), &e);
+            panic!(SQL_FAIL_MESSAGE);
+        }
+    }
+
     pub fn insert_metadata(
         &mut self,
         bhh: &StacksBlockId,
@@ -93,10 +116,11 @@ impl SqliteConnection {
         let key = format!("clr-meta::{}::{}", contract_hash, key);
         let params: [&dyn ToSql; 3] = [&bhh, &key, &value.to_string()];
 
-        match self.conn.execute(
-            "INSERT INTO metadata_table (blockhash, key, value) VALUES (?, ?, ?)",
-            &params,
-        ) {
+        match self
+            .conn
+            .prepare_cached("INSERT INTO metadata_table (blockhash, key, value) VALUES (?, ?, ?)")
+            .and_then(|mut stmt| stmt.execute(&params))
+        {
             Ok(_) => {}
             Err(e) => {
                 error!(
@@ -136,12 +160,8 @@ impl SqliteConnection {
 
         match self
             .conn
-            .query_row(
-                "SELECT value FROM metadata_table WHERE blockhash = ? AND key = ?",
-                &params,
-                |row| row.get(0),
-            )
-            .optional()
+            .prepare_cached("SELECT value FROM metadata_table WHERE blockhash = ? AND key = ?")
+            .and_then(|mut stmt| stmt.query_row(&params, |row| row.get(0)).optional())
         {
             Ok(x) => x,
             Err(e) => {
@@ -254,6 +274,14 @@ impl SqliteConnection {
 impl SqliteConnection {
     pub fn initialize(filename: &str) -> Result<Self> {
         let contract_db = Self::inner_open(filename)?;
+        if filename != ":memory:" {
+            sqlite_set_wal_mode(&contract_db.conn)
+                .map_err(|x| InterpreterError::SqliteError(IncomparableError { err: x }))?;
+        }
+
+        // a store created before the layout was versioned has tables, but no version
+        let is_new = contract_db.check_schema().is_err();
+
         contract_db
             .conn
             .execute(
@@ -273,8 +301,20 @@ impl SqliteConnection {
             )
             .map_err(|x| InterpreterError::SqliteError(IncomparableError { err: x }))?;
 
+        contract_db
+            .conn
+            .execute(
+                "CREATE TABLE IF NOT EXISTS db_config (version INTEGER NOT NULL)",
+                NO_PARAMS,
+            )
+            .map_err(|x| InterpreterError::SqliteError(IncomparableError { err: x }))?;
+
         contract_db.check_schema()?;
 
+        if is_new {
+            contract_db.set_db_version(CLARITY_DB_VERSION)?;
+        }
+
         Ok(contract_db)
     }
     pub fn memory() -> Result<Self> {
@@ -299,6 +339,19 @@ impl SqliteConnection {
         Ok(())
     }
 
+    /// Open an existing store without write access
+    pub fn open_readonly(filename: &str) -> Result<Self> {
+        let conn = Connection::open_with_flags(filename, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|x| InterpreterError::SqliteError(IncomparableError { err: x }))?;
+
+        conn.busy_handler(Some(tx_busy_handler))
+            .map_err(|x| InterpreterError::SqliteError(IncomparableError { err: x }))?;
+
+        let contract_db = SqliteConnection { conn };
+        contract_db.check_schema()?;
+        Ok(contract_db)
+    }
+
     pub fn inner_open(filename: &str) -> Result<Self> {
         let conn = Connection::open(filename)
             .map_err(|x| InterpreterError::SqliteError(IncomparableError { err: x }))?;
@@ -309,6 +362,104 @@ impl SqliteConnection {
         Ok(SqliteConnection { conn })
     }
 
+    /// The version of the layout of this store's metadata.  Stores created before the layout
+    /// was versioned are at version 0.
+    pub fn get_db_version(&self) -> Result<u32> {
+        self.conn
+            .query_row("SELECT version FROM db_config LIMIT 1", NO_PARAMS, |row| {
+                row.get::<_, i64>(0)
+            })
+            .optional()
+            .map(|version| version.unwrap_or(0) as u32)
+            .map_err(|x| InterpreterError::SqliteError(IncomparableError { err: x }).into())
+    }
+
+    pub fn set_db_version(&self, version: u32) -> Result<()> {
+        self.conn
+            .execute_batch("DELETE FROM db_config")
+            .and_then(|_| {
+                self.conn.execute(
+                    "INSERT INTO db_config (version) VALUES (?)",
+                    &[&(version as i64)],
+                )
+            })
+            .map(|_| ())
+            .map_err(|x| InterpreterError::SqliteError(IncomparableError { err: x }).into())
+    }
+
+    /// Every metadata entry of every contract, at every block, whose key starts with
+    /// `key_prefix`, as `(blockhash, full key, value)` triples
+    pub fn get_metadata_entries(&self, key_prefix: &str) -> Result<Vec<(String, String, String)>> {
+        let pattern = format!("clr-meta::%::{}%", key_prefix);
+        let mut stmt = self
+            .conn
+            .prepare("SELECT blockhash, key, value FROM metadata_table WHERE key LIKE ?")
+            .map_err(|x| InterpreterError::SqliteError(IncomparableError { err: x }))?;
+        let rows = stmt
+            .query_map(&[&pattern], |row| (row.get(0), row.get(1), row.get(2)))
+            .map_err(|x| InterpreterError::SqliteError(IncomparableError { err: x }))?;
+
+        let mut entries = vec![];
+        for row in rows {
+            entries.push(
+                row.map_err(|x| InterpreterError::SqliteError(IncomparableError { err: x }))?,
+            );
+        }
+        Ok(entries)
+    }
+
+    /// Up to `limit` entries of the data table, in key order, whose keys come after `after_key`.
+    /// Used to copy the values out page by page, without holding them all in memory.
+    pub fn get_data_entries(&self, after_key: &str, limit: u32) -> Result<Vec<(String, String)>> {
+        let params: [&dyn ToSql; 2] = [&after_key, &limit];
+        let mut stmt = self
+            .conn
+            .prepare("SELECT key, value FROM data_table WHERE key > ? ORDER BY key LIMIT ?")
+            .map_err(|x| InterpreterError::SqliteError(IncomparableError { err: x }))?;
+        let rows = stmt
+            .query_map(&params, |row| (row.get(0), row.get(1)))
+            .map_err(|x| InterpreterError::SqliteError(IncomparableError { err: x }))?;
+
+        let mut entries = vec![];
+        for row in rows {
+            entries.push(
+                row.map_err(|x| InterpreterError::SqliteError(IncomparableError { err: x }))?,
+            );
+        }
+        Ok(entries)
+    }
+
+    /// Replace the value of a metadata entry returned by `get_metadata_entries`
+    pub fn replace_metadata_entry(&self, blockhash: &str, key: &str, value: &str) -> Result<()> {
+        let params: [&dyn ToSql; 3] = [&value, &blockhash, &key];
+        self.conn
+            .execute(
+                "UPDATE metadata_table SET value = ? WHERE blockhash = ? AND key = ?",
+                &params,
+            )
+            .map(|_| ())
+            .map_err(|x| InterpreterError::SqliteError(IncomparableError { err: x }).into())
+    }
+
+    /// begin, commit, or roll back the savepoint a migration runs in
+    pub fn begin_migration(&self) -> Result<()> {
+        self.conn
+            .execute_batch("SAVEPOINT migration")
+            .map_err(|x| InterpreterError::SqliteError(IncomparableError { err: x }).into())
+    }
+
+    pub fn commit_migration(&self) -> Result<()> {
+        self.conn
+            .execute_batch("RELEASE SAVEPOINT migration")
+            .map_err(|x| InterpreterError::SqliteError(IncomparableError { err: x }).into())
+    }
+
+    pub fn rollback_migration(&self) -> Result<()> {
+        self.conn
+            .execute_batch("ROLLBACK TO SAVEPOINT migration; RELEASE SAVEPOINT migration")
+            .map_err(|x| InterpreterError::SqliteError(IncomparableError { err: x }).into())
+    }
+
     #[cfg(test)]
     pub fn mut_conn(&mut self) -> &mut Connection {
         &mut self.conn
@@ -325,3 +476,54 @@ fn test_rollback() {
     conn.rollback(&bhh);
     conn.commit(&bhh); // shouldn't be on the stack!
 }
+
+#[cfg(test)]
+#[test]
+fn test_put_all() {
+    let mut conn = SqliteConnection::memory().unwrap();
+    let items: Vec<(String, String)> = (0..100)
+        .map(|i| (format!("key-{}", i), format!("value-{}", i)))
+        .collect();
+
+    let bhh = StacksBlockId([1; 32]);
+    conn.begin(&bhh);
+    conn.put_all(&items);
+    conn.put_all(&[("key-0".to_string(), "replaced".to_string())]);
+    conn.commit(&bhh);
+
+    assert_eq!(conn.get("key-0"), Some("replaced".to_string()));
+    for (key, value) in items.iter().skip(1) {
+        assert_eq!(conn.get(key).as_ref(), Some(value));
+    }
+    assert_eq!(conn.get("key-100"), None);
+
+    // a batch written in a block that is rolled back is gone, too
+    let bhh = StacksBlockId([2; 32]);
+    conn.begin(&bhh);
+    conn.put_all(&[("key-100".to_string(), "value-100".to_string())]);
+    conn.rollback(&bhh);
+    assert_eq!(conn.get("key-100"), None);
+}
+
+#[cfg(test)]
+#[test]
+fn test_get_data_entries() {
+    let mut conn = SqliteConnection::memory().unwrap();
+    let items: Vec<(String, String)> = (0..25)
+        .map(|i| (format!("key-{:02}", i), format!("value-{}", i)))
+        .collect();
+    conn.put_all(&items);
+
+    let mut paged = vec![];
+    let mut after_key = "".to_string();
+    loop {
+        let page = conn.get_data_entries(&after_key, 10).unwrap();
+        if page.len() == 0 {
+            break;
+        }
+        assert!(page.len() <= 10);
+        after_key = page.last().unwrap().0.clone();
+        paged.extend(page);
+    }
+    assert_eq!(paged, items);
+}
@@ -19,7 +19,10 @@ use std::io::Write;
 use util::hash::{hex_bytes, to_hex};
 use vm::contracts::Contract;
 use vm::errors::{Error, IncomparableError, InterpreterError, InterpreterResult, RuntimeErrorType};
-use vm::types::{OptionalData, PrincipalData, TupleTypeSignature, TypeSignature, Value, NONE};
+use vm::types::{
+    OptionalData, PrincipalData, StandardPrincipalData, TupleTypeSignature, TypeSignature, Value,
+    NONE,
+};
 
 pub trait ClaritySerializable {
     fn serialize(&self) -> String;
@@ -103,6 +106,16 @@ pub struct SimmedBlock {
 
 clarity_serializable!(SimmedBlock);
 
+/// A report that the miner of an anchored block signed two conflicting microblocks
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MicroblockPoisonReport {
+    pub reporter: StandardPrincipalData,
+    /// sequence number of the conflicting microblocks
+    pub sequence: u16,
+}
+
+clarity_serializable!(MicroblockPoisonReport);
+
 clarity_serializable!(PrincipalData);
 clarity_serializable!(i128);
 clarity_serializable!(u128);
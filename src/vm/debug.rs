@@ -0,0 +1,628 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An interactive debugger for Clarity code.  The `Debugger` is an `EvalHook`: it stops before
+//! an expression is evaluated when a breakpoint matches it, or when the user is stepping, and
+//! takes commands from a `DebugInterface` until told to resume.
+
+use std::collections::HashSet;
+use std::io;
+use std::io::Write;
+
+use vm::contexts::{EvalHook, LocalContext};
+use vm::errors::InterpreterResult as Result;
+use vm::types::QualifiedContractIdentifier;
+use vm::{Environment, SymbolicExpression, Value};
+
+/// Longest expression source shown when stopping or in a backtrace
+const MAX_EXPRESSION_DISPLAY_LEN: usize = 72;
+
+const HELP_TEXT: &str = "Debugger commands:
+  c, continue        resume until the next breakpoint
+  s, step            stop at the next expression evaluated
+  n, next            stop at the next expression that is not nested in this one
+  f, finish          stop when this expression finishes, and show its value
+  b <function>       break when <function> is called
+  b <line>[:<col>]   break at the expression starting at <line> (and <col>)
+  d <id>             delete breakpoint <id>
+  breakpoints        list the breakpoints
+  bt, backtrace      show the expressions being evaluated
+  locals             show the variables in scope
+  p <name>           show the value of the variable <name>
+  reads              show the data map reads made so far
+  q, quit            delete all breakpoints and run to completion
+  h, help            show this text";
+
+/// Where the debugger reads its commands and writes its output
+pub trait DebugInterface {
+    /// Read the next command, or None if there are no more
+    fn read_command(&mut self) -> Option<String>;
+    /// Show a line of output
+    fn write_line(&mut self, line: &str);
+}
+
+/// Debug on the terminal
+pub struct StdioDebugInterface;
+
+impl DebugInterface for StdioDebugInterface {
+    fn read_command(&mut self) -> Option<String> {
+        let mut stdout = io::stdout();
+        let _ = stdout.write(b"(debug) ");
+        let _ = stdout.flush();
+
+        let mut buffer = String::new();
+        match io::stdin().read_line(&mut buffer) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(buffer),
+        }
+    }
+
+    fn write_line(&mut self, line: &str) {
+        println!("{}", line);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BreakpointLocation {
+    /// a call to the function with this name, directly or by `contract-call?`
+    Function(String),
+    /// the expression starting at this line and, if given, column of its contract's source.
+    /// Source locations are only known in developer mode.
+    Source { line: u32, column: Option<u32> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breakpoint {
+    pub id: usize,
+    pub location: BreakpointLocation,
+}
+
+/// A `map-get?` made while debugging
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataMapRead {
+    pub contract_identifier: QualifiedContractIdentifier,
+    pub map_name: String,
+    /// source of the key expression
+    pub key: String,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StepState {
+    Continue,
+    Step,
+    /// stop at the next expression at this depth or shallower
+    Next(usize),
+    /// stop when the expression at this depth finishes
+    Finish(usize),
+}
+
+struct Frame {
+    contract_identifier: QualifiedContractIdentifier,
+    source: String,
+    location: Option<(u32, u32)>,
+}
+
+pub struct Debugger {
+    interface: Box<dyn DebugInterface>,
+    breakpoints: Vec<Breakpoint>,
+    next_breakpoint_id: usize,
+    state: StepState,
+    stack: Vec<Frame>,
+    reads: Vec<DataMapRead>,
+}
+
+/// The line and column where `expr` starts in its contract's source, if known.  Source
+/// locations are only kept in developer mode.
+#[cfg(feature = "developer-mode")]
+pub fn expression_location(expr: &SymbolicExpression) -> Option<(u32, u32)> {
+    if expr.span.start_line == 0 {
+        None
+    } else {
+        Some((expr.span.start_line, expr.span.start_column))
+    }
+}
+
+#[cfg(not(feature = "developer-mode"))]
+pub fn expression_location(_expr: &SymbolicExpression) -> Option<(u32, u32)> {
+    None
+}
+
+fn describe_expression(expr: &SymbolicExpression) -> String {
+    let source = expr.to_string();
+    if source.chars().count() > MAX_EXPRESSION_DISPLAY_LEN {
+        let truncated: String = source.chars().take(MAX_EXPRESSION_DISPLAY_LEN).collect();
+        format!("{}...", truncated)
+    } else {
+        source
+    }
+}
+
+/// The function `expr` calls, if it is a function call
+pub fn called_function_name(expr: &SymbolicExpression) -> Option<&str> {
+    let list = expr.match_list()?;
+    let head = list.first()?.match_atom()?;
+    if &**head == "contract-call?" {
+        list.get(2)?.match_atom().map(|name| &**name)
+    } else {
+        Some(&**head)
+    }
+}
+
+impl Frame {
+    fn describe(&self) -> String {
+        match self.location {
+            Some((line, column)) => format!(
+                "{} at {}:{}: {}",
+                &self.contract_identifier, line, column, &self.source
+            ),
+            None => format!("{}: {}", &self.contract_identifier, &self.source),
+        }
+    }
+}
+
+impl BreakpointLocation {
+    fn parse(text: &str) -> Option<BreakpointLocation> {
+        if text.is_empty() {
+            return None;
+        }
+        let mut parts = text.splitn(2, ':');
+        let first = parts.next()?;
+        match first.parse::<u32>() {
+            Ok(line) => {
+                let column = match parts.next() {
+                    Some(column) => Some(column.parse::<u32>().ok()?),
+                    None => None,
+                };
+                Some(BreakpointLocation::Source { line, column })
+            }
+            // function names never start with a digit
+            Err(_) => Some(BreakpointLocation::Function(text.to_string())),
+        }
+    }
+}
+
+impl Debugger {
+    /// A debugger that stops at the first expression evaluated
+    pub fn new(interface: Box<dyn DebugInterface>) -> Debugger {
+        Debugger {
+            interface,
+            breakpoints: vec![],
+            next_breakpoint_id: 1,
+            state: StepState::Step,
+            stack: vec![],
+            reads: vec![],
+        }
+    }
+
+    /// Stop only at breakpoints, instead of at the first expression evaluated
+    pub fn run_to_breakpoint(mut self) -> Debugger {
+        self.state = StepState::Continue;
+        self
+    }
+
+    pub fn add_breakpoint(&mut self, location: BreakpointLocation) -> usize {
+        let id = self.next_breakpoint_id;
+        self.next_breakpoint_id += 1;
+        self.breakpoints.push(Breakpoint { id, location });
+        id
+    }
+
+    pub fn delete_breakpoint(&mut self, id: usize) -> bool {
+        let count = self.breakpoints.len();
+        self.breakpoints.retain(|breakpoint| breakpoint.id != id);
+        self.breakpoints.len() != count
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn reads(&self) -> &[DataMapRead] {
+        &self.reads
+    }
+
+    /// The breakpoint that matches the expression on top of the stack, if any
+    fn matching_breakpoint(&self, expr: &SymbolicExpression) -> Option<usize> {
+        let frame = self.stack.last()?;
+        let parent = if self.stack.len() > 1 {
+            self.stack.get(self.stack.len() - 2)
+        } else {
+            None
+        };
+        let function_name = called_function_name(expr);
+
+        for breakpoint in self.breakpoints.iter() {
+            let hit = match breakpoint.location {
+                BreakpointLocation::Function(ref name) => function_name == Some(name.as_str()),
+                BreakpointLocation::Source { line, column } => match frame.location {
+                    Some((expr_line, expr_column)) => {
+                        let same_line_as_parent = parent
+                            .map(|parent| {
+                                parent.contract_identifier == frame.contract_identifier
+                                    && parent.location.map(|(l, _)| l) == Some(expr_line)
+                            })
+                            .unwrap_or(false);
+                        match column {
+                            Some(column) => expr_line == line && expr_column == column,
+                            // stop once per line, at its outermost expression
+                            None => expr_line == line && !same_line_as_parent,
+                        }
+                    }
+                    None => false,
+                },
+            };
+            if hit {
+                return Some(breakpoint.id);
+            }
+        }
+        None
+    }
+
+    fn show_locals(&mut self, env: &Environment, context: &LocalContext) {
+        let mut shown = HashSet::new();
+        let mut lines = vec![];
+        let mut scope = Some(context);
+        while let Some(local) = scope {
+            let mut names: Vec<_> = local.variables.keys().collect();
+            names.sort();
+            for name in names.into_iter() {
+                if shown.insert(name.to_string()) {
+                    lines.push(format!("{} = {}", &**name, &local.variables[name]));
+                }
+            }
+            scope = local.parent;
+        }
+
+        let mut names: Vec<_> = env.contract_context.variables.keys().collect();
+        names.sort();
+        for name in names.into_iter() {
+            if shown.insert(name.to_string()) {
+                lines.push(format!(
+                    "{} = {} (constant)",
+                    &**name, &env.contract_context.variables[name]
+                ));
+            }
+        }
+
+        if lines.is_empty() {
+            self.interface.write_line("No variables in scope");
+        }
+        for line in lines.iter() {
+            self.interface.write_line(line);
+        }
+    }
+
+    fn show_variable(&mut self, env: &Environment, context: &LocalContext, name: &str) {
+        let line = match context
+            .lookup_variable(name)
+            .or_else(|| env.contract_context.variables.get(name))
+        {
+            Some(value) => format!("{} = {}", name, value),
+            None => format!("No variable named {} in scope", name),
+        };
+        self.interface.write_line(&line);
+    }
+
+    fn show_backtrace(&mut self) {
+        let lines: Vec<String> = self
+            .stack
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(i, frame)| format!("#{} {}", i, frame.describe()))
+            .collect();
+        for line in lines.iter() {
+            self.interface.write_line(line);
+        }
+    }
+
+    fn show_reads(&mut self) {
+        if self.reads.is_empty() {
+            self.interface.write_line("No data map reads");
+        }
+        let lines: Vec<String> = self
+            .reads
+            .iter()
+            .map(|read| {
+                format!(
+                    "{}.{}[{}] => {}",
+                    &read.contract_identifier, &read.map_name, &read.key, &read.value
+                )
+            })
+            .collect();
+        for line in lines.iter() {
+            self.interface.write_line(line);
+        }
+    }
+
+    fn show_breakpoints(&mut self) {
+        if self.breakpoints.is_empty() {
+            self.interface.write_line("No breakpoints");
+        }
+        let lines: Vec<String> = self
+            .breakpoints
+            .iter()
+            .map(|breakpoint| match breakpoint.location {
+                BreakpointLocation::Function(ref name) => {
+                    format!("Breakpoint {}: function {}", breakpoint.id, name)
+                }
+                BreakpointLocation::Source {
+                    line,
+                    column: Some(column),
+                } => format!("Breakpoint {}: {}:{}", breakpoint.id, line, column),
+                BreakpointLocation::Source { line, column: None } => {
+                    format!("Breakpoint {}: line {}", breakpoint.id, line)
+                }
+            })
+            .collect();
+        for line in lines.iter() {
+            self.interface.write_line(line);
+        }
+    }
+
+    /// Take commands until one resumes evaluation.  `finished` is true if the expression on
+    /// top of the stack has already been evaluated.
+    fn prompt(&mut self, env: &Environment, context: &LocalContext, finished: bool) {
+        let depth = self.stack.len();
+        loop {
+            let command = match self.interface.read_command() {
+                Some(command) => command,
+                None => {
+                    // nobody left to take commands from
+                    self.breakpoints.clear();
+                    self.state = StepState::Continue;
+                    return;
+                }
+            };
+            let mut words = command.trim().splitn(2, ' ');
+            let verb = words.next().unwrap_or("");
+            let argument = words.next().unwrap_or("").trim();
+
+            match verb {
+                "c" | "continue" => {
+                    self.state = StepState::Continue;
+                    return;
+                }
+                "s" | "step" => {
+                    self.state = StepState::Step;
+                    return;
+                }
+                "n" | "next" => {
+                    self.state = StepState::Next(depth);
+                    return;
+                }
+                "f" | "finish" => {
+                    self.state = if finished {
+                        StepState::Finish(depth.saturating_sub(1))
+                    } else {
+                        StepState::Finish(depth)
+                    };
+                    return;
+                }
+                "q" | "quit" => {
+                    self.breakpoints.clear();
+                    self.state = StepState::Continue;
+                    return;
+                }
+                "b" | "break" => match BreakpointLocation::parse(argument) {
+                    Some(location) => {
+                        let id = self.add_breakpoint(location);
+                        self.interface.write_line(&format!("Breakpoint {} set", id));
+                    }
+                    None => self
+                        .interface
+                        .write_line("Usage: b <function> | b <line>[:<col>]"),
+                },
+                "d" | "delete" => match argument.parse::<usize>() {
+                    Ok(id) if self.delete_breakpoint(id) => {
+                        self.interface
+                            .write_line(&format!("Breakpoint {} deleted", id));
+                    }
+                    _ => self
+                        .interface
+                        .write_line(&format!("No breakpoint {}", argument)),
+                },
+                "breakpoints" => self.show_breakpoints(),
+                "bt" | "backtrace" => self.show_backtrace(),
+                "locals" => self.show_locals(env, context),
+                "p" | "print" => self.show_variable(env, context, argument),
+                "reads" => self.show_reads(),
+                "h" | "help" => self.interface.write_line(HELP_TEXT),
+                "" => {}
+                _ => self
+                    .interface
+                    .write_line(&format!("Unknown command {}, try 'help'", verb)),
+            }
+        }
+    }
+}
+
+impl EvalHook for Debugger {
+    fn will_begin_eval(
+        &mut self,
+        env: &mut Environment,
+        context: &LocalContext,
+        expr: &SymbolicExpression,
+    ) {
+        self.stack.push(Frame {
+            contract_identifier: env.contract_context.contract_identifier.clone(),
+            source: describe_expression(expr),
+            location: expression_location(expr),
+        });
+        let depth = self.stack.len();
+
+        let breakpoint = self.matching_breakpoint(expr);
+        let stepped = match self.state {
+            StepState::Step => true,
+            StepState::Next(next_depth) => depth <= next_depth,
+            StepState::Continue | StepState::Finish(_) => false,
+        };
+        if breakpoint.is_none() && !stepped {
+            return;
+        }
+
+        if let Some(id) = breakpoint {
+            self.interface.write_line(&format!("Breakpoint {}", id));
+        }
+        let current = self.stack[depth - 1].describe();
+        self.interface.write_line(&current);
+        self.prompt(env, context, false);
+    }
+
+    fn did_finish_eval(
+        &mut self,
+        env: &mut Environment,
+        context: &LocalContext,
+        expr: &SymbolicExpression,
+        res: &Result<Value>,
+    ) {
+        if let (Some(list), Ok(value)) = (expr.match_list(), res) {
+            if list.len() == 3 && list[0].match_atom().map(|name| &**name) == Some("map-get?") {
+                if let Some(map_name) = list[1].match_atom() {
+                    self.reads.push(DataMapRead {
+                        contract_identifier: env.contract_context.contract_identifier.clone(),
+                        map_name: map_name.to_string(),
+                        key: describe_expression(&list[2]),
+                        value: value.clone(),
+                    });
+                }
+            }
+        }
+
+        if self.state == StepState::Finish(self.stack.len()) {
+            let line = match res {
+                Ok(value) => format!("=> {}", value),
+                Err(e) => format!("=> error: {}", e),
+            };
+            self.interface.write_line(&line);
+            self.prompt(env, context, true);
+        }
+
+        self.stack.pop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    use vm::contexts::OwnedEnvironment;
+    use vm::tests::{execute, symbols_from_values, with_memory_environment};
+
+    struct ScriptedInterface {
+        commands: VecDeque<String>,
+        output: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl DebugInterface for ScriptedInterface {
+        fn read_command(&mut self) -> Option<String> {
+            self.commands.pop_front()
+        }
+
+        fn write_line(&mut self, line: &str) {
+            self.output.borrow_mut().push(line.to_string());
+        }
+    }
+
+    const BALANCES_CONTRACT: &str = "
+        (define-map balances ((owner principal)) ((amount int)))
+        (define-private (get-balance (who principal))
+          (default-to 0 (get amount (map-get? balances (tuple (owner who))))))
+        (define-public (deposit (who principal))
+          (begin
+            (map-set balances (tuple (owner who)) (tuple (amount 10)))
+            (ok (get-balance who))))";
+
+    #[test]
+    fn test_debugger_function_breakpoint() {
+        with_memory_environment(
+            |owned_env: &mut OwnedEnvironment| {
+                let contract_id = QualifiedContractIdentifier::local("balances").unwrap();
+                owned_env
+                    .initialize_contract(contract_id.clone(), BALANCES_CONTRACT)
+                    .unwrap();
+
+                let output = Rc::new(RefCell::new(vec![]));
+                let interface = ScriptedInterface {
+                    commands: vec!["locals", "p who", "f", "reads", "bt", "c"]
+                        .into_iter()
+                        .map(|command| command.to_string())
+                        .collect(),
+                    output: output.clone(),
+                };
+                let mut debugger = Debugger::new(Box::new(interface)).run_to_breakpoint();
+                debugger.add_breakpoint(BreakpointLocation::Function("get-balance".into()));
+                owned_env.add_eval_hook(Box::new(debugger));
+
+                let p1 = execute("'SZ2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQ9H6DPR");
+                let (result, _, _) = owned_env
+                    .execute_transaction(
+                        p1.clone(),
+                        contract_id,
+                        "deposit",
+                        &symbols_from_values(vec![p1.clone()]),
+                    )
+                    .unwrap();
+                assert_eq!(result, Value::okay(Value::Int(10)).unwrap());
+
+                let output = output.borrow();
+                assert_eq!(output[0], "Breakpoint 1");
+                assert!(output[1].contains("get-balance"));
+                let who = format!("who = {}", &p1);
+                // locals, then p who
+                assert_eq!(output[2], who);
+                assert_eq!(output[3], who);
+                // finishing the call shows its value
+                assert_eq!(output[4], "=> 10");
+                assert!(output[5].contains(".balances.balances["));
+                // the backtrace starts at the finished call, inside the body of deposit
+                assert!(output[6].starts_with("#0 "));
+                assert!(output[6].contains("get-balance"));
+                assert_eq!(output.len(), 6 + 3);
+            },
+            true,
+        );
+    }
+
+    #[test]
+    fn test_parse_breakpoint_location() {
+        assert_eq!(
+            BreakpointLocation::parse("get-balance"),
+            Some(BreakpointLocation::Function("get-balance".into()))
+        );
+        assert_eq!(
+            BreakpointLocation::parse("12"),
+            Some(BreakpointLocation::Source {
+                line: 12,
+                column: None
+            })
+        );
+        assert_eq!(
+            BreakpointLocation::parse("12:5"),
+            Some(BreakpointLocation::Source {
+                line: 12,
+                column: Some(5)
+            })
+        );
+        assert_eq!(BreakpointLocation::parse("12:x"), None);
+        assert_eq!(BreakpointLocation::parse(""), None);
+    }
+}
@@ -18,10 +18,12 @@ use std::fmt;
 use vm::representations::Span;
 
 /// In a near future, we can go further in our static analysis and provide different levels
-/// of diagnostics, such as warnings, hints, best practices, etc.
+/// of diagnostics, such as hints, best practices, etc.
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub enum Level {
     Error,
+    /// a strict lint that is not enforced (see vm::analysis::strict_checker)
+    Warning,
 }
 
 pub trait DiagnosableError {
@@ -0,0 +1,225 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use burnchains::BurnchainHeaderHash;
+use chainstate::burn::db::sortdb::SortitionId;
+use chainstate::burn::{BlockHeaderHash, VRFSeed};
+use chainstate::stacks::{index::MarfTrieId, StacksAddress, StacksBlockId};
+use core::StacksEpoch;
+
+use vm::{
+    ast,
+    contexts::OwnedEnvironment,
+    database::{BurnStateDB, HeadersDB, MarfedKV, STXBalance},
+    eval_all, execute, ContractContext, GlobalContext, LimitedCostTracker,
+    QualifiedContractIdentifier,
+};
+
+/// The contract that the `contract-call?` examples call into.
+const SAMPLE_TOKENS_CONTRACT: &str = include_str!("../../../sample-contracts/tokens.clar");
+
+struct DocHeadersDB {}
+const DOC_HEADER_DB: DocHeadersDB = DocHeadersDB {};
+
+impl HeadersDB for DocHeadersDB {
+    fn get_burn_header_hash_for_block(&self, _bhh: &StacksBlockId) -> Option<BurnchainHeaderHash> {
+        None
+    }
+    fn get_vrf_seed_for_block(&self, _bhh: &StacksBlockId) -> Option<VRFSeed> {
+        Some(
+            VRFSeed::from_hex("f490de2920c8a35fabeb13208852aa28c76f9be9b03a4dd2b3c075f7a26923b4")
+                .unwrap(),
+        )
+    }
+    fn get_stacks_block_header_hash_for_block(
+        &self,
+        _id_bhh: &StacksBlockId,
+    ) -> Option<BlockHeaderHash> {
+        Some(
+            BlockHeaderHash::from_hex(
+                "374708fff7719dd5979ec875d56cd2286f6d3cf7ec317a3b25632aab28ec37bb",
+            )
+            .unwrap(),
+        )
+    }
+    fn get_burn_block_time_for_block(&self, _id_bhh: &StacksBlockId) -> Option<u64> {
+        Some(1557860301)
+    }
+    fn get_burn_block_height_for_block(&self, _id_bhh: &StacksBlockId) -> Option<u32> {
+        Some(567890)
+    }
+    fn get_miner_address(&self, _id_bhh: &StacksBlockId) -> Option<StacksAddress> {
+        None
+    }
+    fn get_total_liquid_ustx(&self, _id_bhh: &StacksBlockId) -> u128 {
+        1592653589333333u128
+    }
+}
+
+struct DocBurnStateDB {}
+const DOC_POX_STATE_DB: DocBurnStateDB = DocBurnStateDB {};
+
+impl BurnStateDB for DocBurnStateDB {
+    fn get_burn_block_height(&self, _sortition_id: &SortitionId) -> Option<u32> {
+        Some(5678)
+    }
+    fn get_burn_header_hash(
+        &self,
+        _height: u32,
+        _sortition_id: &SortitionId,
+    ) -> Option<BurnchainHeaderHash> {
+        Some(
+            BurnchainHeaderHash::from_hex(
+                "e67141016c88a7f1203eca0b4312f2ed141531f59303a1c267d7d83ab6b977d8",
+            )
+            .unwrap(),
+        )
+    }
+    fn get_stacks_epoch(&self, _height: u32) -> Option<StacksEpoch> {
+        None
+    }
+}
+
+/// Executes the examples of the API reference against a scratch VM state,
+///  checking every `;; Returns` annotation against the evaluated result,
+///  and that every `;; Throws` line fails to evaluate.
+pub struct ExampleRunner {
+    marf: MarfedKV,
+}
+
+impl ExampleRunner {
+    pub fn new() -> ExampleRunner {
+        let mut marf = MarfedKV::temporary();
+        marf.begin(&StacksBlockId::sentinel(), &StacksBlockId([0; 32]));
+
+        // first, load the samples for contract-call
+        // and give the doc environment's contract some STX
+        {
+            let conn = marf.as_clarity_db(&DOC_HEADER_DB, &DOC_POX_STATE_DB);
+            let contract_id = QualifiedContractIdentifier::local("tokens").unwrap();
+            let mut env = OwnedEnvironment::new(conn);
+            let balance = STXBalance::initial(1000);
+            env.execute_in_env(
+                QualifiedContractIdentifier::local("tokens").unwrap().into(),
+                |e| {
+                    e.global_context.database.set_account_stx_balance(
+                        &QualifiedContractIdentifier::local("docs-test")
+                            .unwrap()
+                            .into(),
+                        &balance,
+                    );
+                    Ok(())
+                },
+            )
+            .expect("FATAL: failed to fund the docs contract");
+            env.initialize_contract(contract_id, SAMPLE_TOKENS_CONTRACT)
+                .expect("FATAL: failed to instantiate the sample tokens contract");
+        }
+
+        marf.commit_to(&StacksBlockId([0; 32]));
+
+        ExampleRunner { marf }
+    }
+
+    /// Run a single API example.
+    pub fn run(&mut self, example: &str) -> Result<(), String> {
+        let without_throws: String = example
+            .lines()
+            .filter(|x| !x.contains(";; Throws"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.execute_returns(&without_throws)?;
+
+        for expect_err in example.lines().filter(|x| x.contains(";; Throws")) {
+            if let Ok(result) = execute(expect_err) {
+                return Err(format!(
+                    "Expected an error from `{}`, but got {:?}",
+                    expect_err, result
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn execute_returns(&mut self, program: &str) -> Result<(), String> {
+        // start the next block,
+        //  we never commit it so that we can reuse the initialization
+        self.marf
+            .begin(&StacksBlockId([0; 32]), &StacksBlockId([1; 32]));
+
+        // execute the program, iterating at each ";; Returns" comment
+        // there are maybe more rust-y ways of doing this, but this is the simplest.
+        let mut segments = vec![];
+        let mut current_segment: String = "".into();
+        for line in program.lines() {
+            current_segment.push_str(line);
+            current_segment.push_str("\n");
+            if line.contains(";;") && line.contains("Returns ") {
+                segments.push(current_segment);
+                current_segment = "".into();
+            }
+        }
+        if current_segment.len() > 0 {
+            segments.push(current_segment);
+        }
+
+        let result = {
+            let conn = self.marf.as_clarity_db(&DOC_HEADER_DB, &DOC_POX_STATE_DB);
+            let contract_id = QualifiedContractIdentifier::local("docs-test").unwrap();
+            let mut contract_context = ContractContext::new(contract_id.clone());
+            let mut global_context = GlobalContext::new(conn, LimitedCostTracker::new_max_limit());
+
+            global_context.execute(|g| {
+                for segment in segments.iter() {
+                    let expected = if segment.contains("Returns ") {
+                        let expects_start = segment.rfind("Returns ").unwrap() + "Returns ".len();
+                        Some(segment[expects_start..].trim().to_string())
+                    } else {
+                        None
+                    };
+
+                    let parsed = match ast::build_ast(&contract_id, segment, &mut ()) {
+                        Ok(contract_ast) => contract_ast.expressions,
+                        Err(e) => return Ok(Err(format!("Failed to parse `{}`: {}", segment, e))),
+                    };
+                    let result = match eval_all(&parsed, &mut contract_context, g) {
+                        Ok(result) => result,
+                        Err(e) => {
+                            return Ok(Err(format!("Failed to evaluate `{}`: {}", segment, e)))
+                        }
+                    };
+
+                    if let Some(expected) = expected {
+                        let actual = result.map(|x| x.to_string()).unwrap_or("".into());
+                        if expected != actual {
+                            return Ok(Err(format!(
+                                "`{}` returned {}, but the example states {}",
+                                segment.trim(),
+                                actual,
+                                expected
+                            )));
+                        }
+                    }
+                }
+                Ok(Ok(()))
+            })
+        };
+
+        self.marf.rollback();
+
+        result.map_err(|e| format!("Failed to execute example: {}", e))?
+    }
+}
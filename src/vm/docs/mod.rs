@@ -14,14 +14,20 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use core::StacksEpochId;
 use vm::analysis::type_checker::natives::SimpleNativeFunction;
 use vm::analysis::type_checker::TypedNativeFunction;
+use vm::callables::CallableType;
+use vm::costs::SimpleCostSpecification;
 use vm::functions::define::DefineFunctions;
-use vm::functions::NativeFunctions;
+use vm::functions::{lookup_reserved_functions, NativeFunctions};
 use vm::types::{FixedFunction, FunctionType, Value};
 use vm::variables::NativeVariables;
 
+use self::examples::ExampleRunner;
+
 pub mod contracts;
+pub mod examples;
 
 #[derive(Serialize)]
 struct ReferenceAPIs {
@@ -47,6 +53,24 @@ struct FunctionAPI {
     example: String,
 }
 
+#[derive(Serialize)]
+struct VerifiedReferenceAPIs {
+    functions: Vec<VerifiedFunctionAPI>,
+    keywords: Vec<KeywordAPI>,
+}
+
+/// A function's reference entry, along with its cost specification
+///  (for natives that are charged through the function registry) and
+///  the outcome of executing its example against the VM.
+#[derive(Serialize)]
+struct VerifiedFunctionAPI {
+    #[serde(flatten)]
+    api: FunctionAPI,
+    cost_function: Option<SimpleCostSpecification>,
+    example_verified: bool,
+    example_error: Option<String>,
+}
+
 struct SimpleFunctionAPI {
     name: Option<&'static str>,
     signature: &'static str,
@@ -523,6 +547,64 @@ const LEN_API: SpecialAPI = SpecialAPI {
 ",
 };
 
+const INDEX_OF_API: SpecialAPI = SpecialAPI {
+    input_type: "buff|list A, buff|A",
+    output_type: "(optional uint)",
+    signature: "(index-of sequence item)",
+    description: "The `index-of` function returns the position of the first occurrence of `item` in
+a buffer, string or list, or `none` if `item` does not occur in it. When searching a buffer or a string,
+`item` must be a buffer or a string of length 1.",
+    example: "(index-of (list 1 2 3 4 5) 3) ;; Returns (some u2)
+(index-of \"blockstack\" \"k\") ;; Returns (some u4)
+(index-of 0x01020304 0x05) ;; Returns none
+",
+};
+
+const SLICE_API: SpecialAPI = SpecialAPI {
+    input_type: "buff|list A, uint, uint",
+    output_type: "(optional buff|list A)",
+    signature: "(slice? sequence left-position right-position)",
+    description: "The `slice?` function returns the part of a buffer, string or list between
+`left-position` (included) and `right-position` (excluded). It returns `none` if `left-position` is greater
+than `right-position`, or if `right-position` is greater than the length of the sequence.
+The result is typed with the max length of the input sequence or, if both positions are literals,
+with at most `right-position - left-position` items.",
+    example: "(slice? \"blockstack\" u5 u10) ;; Returns (some \"stack\")
+(slice? (list 1 2 3 4 5) u1 u3) ;; Returns (some (2 3))
+(slice? 0x010203 u2 u1) ;; Returns none
+(slice? 0x010203 u1 u4) ;; Returns none
+",
+};
+
+const TO_CONSENSUS_BUFF_API: SpecialAPI = SpecialAPI {
+    input_type: "any",
+    output_type: "(optional buff)",
+    signature: "(to-consensus-buff? value)",
+    description: "The `to-consensus-buff?` function serializes the input value into a buffer, using the
+consensus serialization of Clarity values (the same serialization that Stacks transactions and
+read-only function calls use). This function returns `none` if the serialization is too large to fit
+in a buffer. The max length of the output buffer is the length of the largest serialization
+of the input's type.",
+    example: "(to-consensus-buff? 1) ;; Returns (some 0x0000000000000000000000000000000001)
+(to-consensus-buff? (some u1)) ;; Returns (some 0x0a0100000000000000000000000000000001)
+(to-consensus-buff? true) ;; Returns (some 0x03)
+",
+};
+
+const FROM_CONSENSUS_BUFF_API: SpecialAPI = SpecialAPI {
+    input_type: "type-signature(t), buff",
+    output_type: "(optional t)",
+    signature: "(from-consensus-buff? type-signature buffer)",
+    description: "The `from-consensus-buff?` function deserializes a buffer into a Clarity value of the
+given type, using the consensus serialization of Clarity values. If the buffer is not exactly
+the serialization of a value of that type, this function returns `none`.",
+    example: "(from-consensus-buff? int 0x0000000000000000000000000000000001) ;; Returns (some 1)
+(from-consensus-buff? uint 0x0000000000000000000000000000000001) ;; Returns none
+(from-consensus-buff? bool 0x0300) ;; Returns none
+(from-consensus-buff? (optional uint) 0x0a0100000000000000000000000000000001) ;; Returns (some (some u1))
+",
+};
+
 const LIST_API: SpecialAPI = SpecialAPI {
     input_type: "A, ...",
     output_type: "(list A)",
@@ -1044,6 +1126,12 @@ and block times are accurate only to within two hours. See [BIP113](https://gith
 
 The `header-hash`, `burnchain-header-hash`, `id-header-hash`, and `vrf-seed` properties return a 32-byte buffer.
 
+The `vrf-seed` property is a pseudorandom value that every node computes the same way, so contracts can use it as a
+source of randomness. **Warning**: the seed of the latest block is known to the miner of the current block, who can
+choose whether to include a transaction depending on how the seed would resolve it. Commit to an outcome at one block
+height, and resolve it with the seed of a later block. `clarity-cli check` warns about `vrf-seed` lookups of
+`block-height` or `(- block-height u1)`.
+
 The `miner-address` property returns a `principal` corresponding to the miner of the given block.
 
 The `id-header-hash` is the block identifier value that must be used as input to the `at-block` function.
@@ -1477,6 +1565,10 @@ fn make_api_reference(function: &NativeFunctions) -> FunctionAPI {
         Concat => make_for_special(&CONCAT_API, name),
         AsMaxLen => make_for_special(&ASSERTS_MAX_LEN_API, name),
         Len => make_for_special(&LEN_API, name),
+        IndexOf => make_for_special(&INDEX_OF_API, name),
+        Slice => make_for_special(&SLICE_API, name),
+        ToConsensusBuff => make_for_special(&TO_CONSENSUS_BUFF_API, name),
+        FromConsensusBuff => make_for_special(&FROM_CONSENSUS_BUFF_API, name),
         ListCons => make_for_special(&LIST_API, name),
         FetchEntry => make_for_special(&FETCH_ENTRY_API, name),
         SetEntry => make_for_special(&SET_ENTRY_API, name),
@@ -1612,142 +1704,59 @@ pub fn make_json_api_reference() -> String {
     )
 }
 
-#[cfg(test)]
-mod test {
-    use super::make_all_api_reference;
-    use super::make_json_api_reference;
-    use burnchains::BurnchainHeaderHash;
-    use chainstate::burn::db::sortdb::SortitionId;
-    use chainstate::burn::{BlockHeaderHash, VRFSeed};
-    use chainstate::stacks::{index::MarfTrieId, StacksAddress, StacksBlockId};
-
-    use vm::{
-        ast,
-        contexts::OwnedEnvironment,
-        database::{BurnStateDB, HeadersDB, MarfedKV, STXBalance},
-        eval_all, execute, ContractContext, Error, GlobalContext, LimitedCostTracker,
-        QualifiedContractIdentifier, Value,
-    };
-
-    struct DocHeadersDB {}
-    const DOC_HEADER_DB: DocHeadersDB = DocHeadersDB {};
-
-    impl HeadersDB for DocHeadersDB {
-        fn get_burn_header_hash_for_block(
-            &self,
-            _bhh: &StacksBlockId,
-        ) -> Option<BurnchainHeaderHash> {
-            None
-        }
-        fn get_vrf_seed_for_block(&self, _bhh: &StacksBlockId) -> Option<VRFSeed> {
-            Some(
-                VRFSeed::from_hex(
-                    "f490de2920c8a35fabeb13208852aa28c76f9be9b03a4dd2b3c075f7a26923b4",
-                )
-                .unwrap(),
-            )
-        }
-        fn get_stacks_block_header_hash_for_block(
-            &self,
-            _id_bhh: &StacksBlockId,
-        ) -> Option<BlockHeaderHash> {
-            Some(
-                BlockHeaderHash::from_hex(
-                    "374708fff7719dd5979ec875d56cd2286f6d3cf7ec317a3b25632aab28ec37bb",
-                )
-                .unwrap(),
-            )
-        }
-        fn get_burn_block_time_for_block(&self, _id_bhh: &StacksBlockId) -> Option<u64> {
-            Some(1557860301)
-        }
-        fn get_burn_block_height_for_block(&self, _id_bhh: &StacksBlockId) -> Option<u32> {
-            Some(567890)
-        }
-        fn get_miner_address(&self, _id_bhh: &StacksBlockId) -> Option<StacksAddress> {
-            None
-        }
-        fn get_total_liquid_ustx(&self, _id_bhh: &StacksBlockId) -> u128 {
-            1592653589333333u128
-        }
+fn get_cost_function(function_name: &str) -> Option<SimpleCostSpecification> {
+    match lookup_reserved_functions(function_name, StacksEpochId::latest()) {
+        Some(CallableType::NativeFunction(_, _, cost_function)) => Some(cost_function),
+        _ => None,
     }
+}
 
-    struct DocBurnStateDB {}
-    const DOC_POX_STATE_DB: DocBurnStateDB = DocBurnStateDB {};
+fn make_all_verified_api_reference() -> VerifiedReferenceAPIs {
+    let ReferenceAPIs {
+        functions,
+        keywords,
+    } = make_all_api_reference();
+    let mut runner = ExampleRunner::new();
+
+    let functions = functions
+        .into_iter()
+        .map(|api| {
+            let example_error = runner.run(&api.example).err();
+            VerifiedFunctionAPI {
+                cost_function: get_cost_function(&api.name),
+                example_verified: example_error.is_none(),
+                example_error,
+                api,
+            }
+        })
+        .collect();
 
-    impl BurnStateDB for DocBurnStateDB {
-        fn get_burn_block_height(&self, _sortition_id: &SortitionId) -> Option<u32> {
-            Some(5678)
-        }
-        fn get_burn_header_hash(
-            &self,
-            height: u32,
-            _sortition_id: &SortitionId,
-        ) -> Option<BurnchainHeaderHash> {
-            Some(
-                BurnchainHeaderHash::from_hex(
-                    "e67141016c88a7f1203eca0b4312f2ed141531f59303a1c267d7d83ab6b977d8",
-                )
-                .unwrap(),
-            )
-        }
+    VerifiedReferenceAPIs {
+        functions,
+        keywords,
     }
+}
 
-    fn docs_execute(marf: &mut MarfedKV, program: &str) {
-        // start the next block,
-        //  we never commit it so that we can reuse the initialization
-        marf.begin(&StacksBlockId([0; 32]), &StacksBlockId([1; 32]));
-
-        // execute the program, iterating at each ";; Returns" comment
-        // there are maybe more rust-y ways of doing this, but this is the simplest.
-        let mut segments = vec![];
-        let mut current_segment: String = "".into();
-        for line in program.lines() {
-            current_segment.push_str(line);
-            current_segment.push_str("\n");
-            if line.contains(";;") && line.contains("Returns ") {
-                segments.push(current_segment);
-                current_segment = "".into();
-            }
-        }
-        if current_segment.len() > 0 {
-            segments.push(current_segment);
-        }
+/// Produce the API reference with every function's example executed against the VM.
+///  Returns the JSON reference, and the names of the functions whose examples failed.
+pub fn make_json_verified_api_reference() -> (String, Vec<String>) {
+    let api_out = make_all_verified_api_reference();
+    let failed = api_out
+        .functions
+        .iter()
+        .filter(|f| !f.example_verified)
+        .map(|f| f.api.name.clone())
+        .collect();
+    let json = serde_json::to_string(&api_out).expect("Failed to serialize documentation");
+    (json, failed)
+}
 
-        let conn = marf.as_clarity_db(&DOC_HEADER_DB, &DOC_POX_STATE_DB);
-        let contract_id = QualifiedContractIdentifier::local("docs-test").unwrap();
-        let mut contract_context = ContractContext::new(contract_id.clone());
-        let mut global_context = GlobalContext::new(conn, LimitedCostTracker::new_max_limit());
-
-        global_context
-            .execute(|g| {
-                for segment in segments.iter() {
-                    let expected = if segment.contains("Returns ") {
-                        let expects_start = segment.rfind("Returns ").unwrap() + "Returns ".len();
-                        Some(segment[expects_start..].trim().to_string())
-                    } else {
-                        None
-                    };
-
-                    eprintln!("{}", segment);
-
-                    let result = {
-                        let parsed = ast::build_ast(&contract_id, segment, &mut ())
-                            .unwrap()
-                            .expressions;
-                        eval_all(&parsed, &mut contract_context, g).unwrap()
-                    };
-
-                    if let Some(expected) = expected {
-                        assert_eq!(expected, result.unwrap().to_string());
-                    }
-                }
-                Ok(())
-            })
-            .unwrap();
-
-        marf.rollback();
-    }
+#[cfg(test)]
+mod test {
+    use super::examples::ExampleRunner;
+    use super::make_all_api_reference;
+    use super::make_all_verified_api_reference;
+    use super::make_json_api_reference;
 
     #[test]
     fn ensure_docgen_runs() {
@@ -1759,51 +1768,33 @@ mod test {
     #[test]
     fn test_examples() {
         let apis = make_all_api_reference();
-        let mut marf = MarfedKV::temporary();
-        marf.begin(&StacksBlockId::sentinel(), &StacksBlockId([0; 32]));
+        let mut runner = ExampleRunner::new();
 
-        // first, load the samples for contract-call
-        // and give the doc environment's contract some STX
-        {
-            let conn = marf.as_clarity_db(&DOC_HEADER_DB, &DOC_POX_STATE_DB);
-            let contract_id = QualifiedContractIdentifier::local("tokens").unwrap();
-            let mut env = OwnedEnvironment::new(conn);
-            let balance = STXBalance::initial(1000);
-            env.execute_in_env(
-                QualifiedContractIdentifier::local("tokens").unwrap().into(),
-                |e| {
-                    e.global_context.database.set_account_stx_balance(
-                        &QualifiedContractIdentifier::local("docs-test")
-                            .unwrap()
-                            .into(),
-                        &balance,
-                    );
-                    Ok(())
-                },
-            )
-            .unwrap();
-            env.initialize_contract(
-                contract_id,
-                &std::fs::read_to_string("sample-contracts/tokens.clar").unwrap(),
-            )
-            .unwrap();
+        for func_api in apis.functions.iter() {
+            if let Err(e) = runner.run(&func_api.example) {
+                panic!("Example for `{}` failed: {}", func_api.name, e);
+            }
         }
+    }
 
-        marf.test_commit();
-
+    #[test]
+    fn test_verified_reference() {
+        let apis = make_all_verified_api_reference();
         for func_api in apis.functions.iter() {
-            let example = &func_api.example;
-            let without_throws: String = example
-                .lines()
-                .filter(|x| !x.contains(";; Throws"))
-                .collect::<Vec<_>>()
-                .join("\n");
-            let the_throws = example.lines().filter(|x| x.contains(";; Throws"));
-            docs_execute(&mut marf, &without_throws);
-            for expect_err in the_throws {
-                eprintln!("{}", expect_err);
-                execute(expect_err).unwrap_err();
-            }
+            assert!(func_api.example_verified, "{}", func_api.api.name);
         }
+
+        let add = apis.functions.iter().find(|f| f.api.name == "+").unwrap();
+        assert!(add.cost_function.is_some());
+
+        let let_api = apis.functions.iter().find(|f| f.api.name == "let").unwrap();
+        assert!(let_api.cost_function.is_none());
+
+        // an example whose annotation doesn't match is caught
+        let mut runner = ExampleRunner::new();
+        assert!(runner.run("(+ 1 2) ;; Returns 4").is_err());
+        assert!(runner.run("(+ 1 2) ;; Returns 3").is_ok());
+        assert!(runner.run("(+ 1 u2) ;; Throws").is_ok());
+        assert!(runner.run("(+ 1 2) ;; Throws").is_err());
     }
 }
@@ -60,6 +60,10 @@ pub enum InterpreterError {
     FailureConstructingTupleWithType,
     FailureConstructingListWithType,
     InsufficientBalance,
+    UnsupportedDatabaseVersion(u32),
+    BadMetadataEntry(String),
+    MigrationChangedStateRoot(String, String),
+    ValueStoreError(String),
 }
 
 /// RuntimeErrors are errors that smart contracts are expected
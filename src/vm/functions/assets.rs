@@ -216,7 +216,7 @@ pub fn special_stx_burn(
 
         burner_balance
             .debit(amount, block_height)
-            .expect("STX underflow");
+            .map_err(|_| RuntimeErrorType::ArithmeticUnderflow)?;
 
         env.add_memory(TypeSignature::PrincipalType.size() as u64)?;
         env.add_memory(STXBalance::size_of as u64)?;
@@ -277,6 +277,13 @@ pub fn special_mint_token(
             final_to_bal,
         )?;
 
+        env.global_context.log_token_mint(
+            to_principal,
+            &env.contract_context.contract_identifier,
+            token_name,
+            amount,
+        )?;
+
         let asset_identifier = AssetIdentifier {
             contract_identifier: env.contract_context.contract_identifier.clone(),
             asset_name: token_name.clone(),
@@ -333,6 +340,13 @@ pub fn special_mint_asset(
             to_principal,
         )?;
 
+        env.global_context.log_asset_mint(
+            to_principal,
+            &env.contract_context.contract_identifier,
+            asset_name,
+            asset.clone(),
+        );
+
         let asset_identifier = AssetIdentifier {
             contract_identifier: env.contract_context.contract_identifier.clone(),
             asset_name: asset_name.clone(),
@@ -0,0 +1,79 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use vm::costs::cost_functions;
+use vm::errors::{check_argument_count, CheckErrors, InterpreterResult as Result};
+use vm::representations::SymbolicExpression;
+use vm::types::{BuffData, SequenceData, TypeSignature, Value};
+use vm::{eval, Environment, LocalContext};
+
+/// `(to-consensus-buff? value)` -- the consensus serialization of `value`, or none if that
+/// is too large for a buffer.
+pub fn special_to_consensus_buff(
+    args: &[SymbolicExpression],
+    env: &mut Environment,
+    context: &LocalContext,
+) -> Result<Value> {
+    check_argument_count(1, args)?;
+
+    let value = eval(&args[0], env, context)?;
+
+    runtime_cost!(cost_functions::TO_CONSENSUS_BUFF, env, value.size())?;
+
+    let mut serialized = vec![];
+    value
+        .serialize_write(&mut serialized)
+        .expect("IOError filling byte buffer.");
+
+    // serializations too large to be wrapped in an optional are none
+    match Value::buff_from(serialized).and_then(Value::some) {
+        Ok(value) => Ok(value),
+        Err(_) => Ok(Value::none()),
+    }
+}
+
+/// `(from-consensus-buff? type buffer)` -- the value of `type` whose consensus serialization
+/// is `buffer`, or none if there is no such value.
+pub fn special_from_consensus_buff(
+    args: &[SymbolicExpression],
+    env: &mut Environment,
+    context: &LocalContext,
+) -> Result<Value> {
+    check_argument_count(2, args)?;
+
+    let expected_type = TypeSignature::parse_type_repr(&args[0], env)?;
+    let input = eval(&args[1], env, context)?;
+
+    runtime_cost!(cost_functions::FROM_CONSENSUS_BUFF, env, input.size())?;
+
+    let bytes = match input {
+        Value::Sequence(SequenceData::Buffer(BuffData { data })) => data,
+        _ => {
+            return Err(CheckErrors::TypeError(
+                TypeSignature::max_buffer(),
+                TypeSignature::type_of(&input),
+            )
+            .into())
+        }
+    };
+
+    // the whole buffer must be the serialization of a single value
+    let mut remaining = bytes.as_slice();
+    match Value::deserialize_read(&mut remaining, Some(&expected_type)) {
+        Ok(value) if remaining.is_empty() => Value::some(value),
+        _ => Ok(Value::none()),
+    }
+}
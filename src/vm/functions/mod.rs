@@ -17,6 +17,7 @@
 mod arithmetic;
 mod assets;
 mod boolean;
+mod conversions;
 mod crypto;
 mod database;
 pub mod define;
@@ -42,6 +43,7 @@ use vm::{eval, Environment, LocalContext};
 
 use address::AddressHashMode;
 use chainstate::stacks::{StacksAddress, C32_ADDRESS_VERSION_TESTNET_SINGLESIG};
+use core::StacksEpochId;
 pub use vm::functions::assets::{get_stx_balance_snapshot, stx_transfer_consolidated};
 pub use vm::functions::special::handle_contract_call_special_cases;
 
@@ -72,6 +74,10 @@ define_named_enum!(NativeFunctions {
     Concat("concat"),
     AsMaxLen("as-max-len?"),
     Len("len"),
+    IndexOf("index-of"),
+    Slice("slice?"),
+    ToConsensusBuff("to-consensus-buff?"),
+    FromConsensusBuff("from-consensus-buff?"),
     ListCons("list"),
     FetchVar("var-get"),
     SetVar("var-set"),
@@ -123,10 +129,29 @@ define_named_enum!(NativeFunctions {
     StxBurn("stx-burn?"),
 });
 
-pub fn lookup_reserved_functions(name: &str) -> Option<CallableType> {
+impl NativeFunctions {
+    /// Get the first epoch in which contracts may call this function.  Natives added after
+    /// Stacks 2.0 are unknown to contracts until their epoch activates.
+    pub fn get_min_epoch(&self) -> StacksEpochId {
+        use vm::functions::NativeFunctions::*;
+        match self {
+            IndexOf | Slice | ToConsensusBuff | FromConsensusBuff => StacksEpochId::Epoch21,
+            _ => StacksEpochId::Epoch20,
+        }
+    }
+
+    /// Look up a native that contracts may call in `epoch`.  Before its epoch, a native's name
+    /// is free for contracts to use.
+    pub fn lookup_by_name_at_epoch(name: &str, epoch: StacksEpochId) -> Option<NativeFunctions> {
+        NativeFunctions::lookup_by_name(name)
+            .filter(|native_function| native_function.get_min_epoch() <= epoch)
+    }
+}
+
+pub fn lookup_reserved_functions(name: &str, epoch: StacksEpochId) -> Option<CallableType> {
     use vm::callables::CallableType::{NativeFunction, SpecialFunction};
     use vm::functions::NativeFunctions::*;
-    if let Some(native_function) = NativeFunctions::lookup_by_name(name) {
+    if let Some(native_function) = NativeFunctions::lookup_by_name_at_epoch(name, epoch) {
         let callable = match native_function {
             Add => NativeFunction(
                 "native_add",
@@ -225,6 +250,16 @@ pub fn lookup_reserved_functions(name: &str) -> Option<CallableType> {
                 NativeHandle::SingleArg(&sequences::native_len),
                 cost_functions::LEN,
             ),
+            IndexOf => SpecialFunction("special_index_of", &sequences::special_index_of),
+            Slice => SpecialFunction("special_slice", &sequences::special_slice),
+            ToConsensusBuff => SpecialFunction(
+                "special_to_consensus_buff",
+                &conversions::special_to_consensus_buff,
+            ),
+            FromConsensusBuff => SpecialFunction(
+                "special_from_consensus_buff",
+                &conversions::special_from_consensus_buff,
+            ),
             ListCons => SpecialFunction("special_list_cons", &sequences::list_cons),
             FetchEntry => SpecialFunction("special_map-get?", &database::special_fetch_entry),
             SetEntry => SpecialFunction("special_set-entry", &database::special_set_entry),
@@ -521,7 +556,7 @@ fn special_let(
 
     finally_drop_memory!( env, memory_use; {
         handle_binding_list::<_, Error>(bindings, |binding_name, var_sexp| {
-            if is_reserved(binding_name) ||
+            if is_reserved(binding_name, env.contract_context.epoch) ||
                 env.contract_context.lookup_function(binding_name).is_some() ||
                 inner_context.lookup_variable(binding_name).is_some() {
                     return Err(CheckErrors::NameAlreadyUsed(binding_name.clone().into()).into())
@@ -15,7 +15,7 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::cmp;
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use vm::costs::{cost_functions, CostOverflowingMath};
 use vm::errors::{
     check_argument_count, CheckErrors, InterpreterResult as Result, RuntimeErrorType,
@@ -234,6 +234,72 @@ pub fn special_as_max_len(
     }
 }
 
+pub fn special_index_of(
+    args: &[SymbolicExpression],
+    env: &mut Environment,
+    context: &LocalContext,
+) -> Result<Value> {
+    check_argument_count(2, args)?;
+
+    let sequence = eval(&args[0], env, context)?;
+    let item = eval(&args[1], env, context)?;
+
+    runtime_cost!(cost_functions::INDEX_OF, env, sequence.size())?;
+
+    match sequence {
+        Value::Sequence(ref sequence_data) => match sequence_data.index_of(&item) {
+            Some(index) => Value::some(Value::UInt(index as u128)),
+            None => Ok(Value::none()),
+        },
+        _ => Err(CheckErrors::ExpectedSequence(TypeSignature::type_of(&sequence)).into()),
+    }
+}
+
+pub fn special_slice(
+    args: &[SymbolicExpression],
+    env: &mut Environment,
+    context: &LocalContext,
+) -> Result<Value> {
+    check_argument_count(3, args)?;
+
+    let sequence = eval(&args[0], env, context)?;
+    let left = eval(&args[1], env, context)?;
+    let right = eval(&args[2], env, context)?;
+
+    runtime_cost!(cost_functions::SLICE, env, sequence.size())?;
+
+    let (left, right) = match (left, right) {
+        (Value::UInt(left), Value::UInt(right)) => (left, right),
+        (Value::UInt(_), right) => {
+            return Err(CheckErrors::TypeError(
+                TypeSignature::UIntType,
+                TypeSignature::type_of(&right),
+            )
+            .into())
+        }
+        (left, _) => {
+            return Err(CheckErrors::TypeError(
+                TypeSignature::UIntType,
+                TypeSignature::type_of(&left),
+            )
+            .into())
+        }
+    };
+
+    match sequence {
+        Value::Sequence(ref sequence_data) => {
+            // positions past the end of any sequence are never valid
+            let left = usize::try_from(left).unwrap_or(usize::max_value());
+            let right = usize::try_from(right).unwrap_or(usize::max_value());
+            match sequence_data.slice(left, right) {
+                Some(sliced) => Value::some(Value::Sequence(sliced)),
+                None => Ok(Value::none()),
+            }
+        }
+        _ => Err(CheckErrors::ExpectedSequence(TypeSignature::type_of(&sequence)).into()),
+    }
+}
+
 pub fn native_len(sequence: Value) -> Result<Value> {
     match sequence {
         Value::Sequence(sequence_data) => Ok(Value::UInt(sequence_data.len() as u128)),
@@ -29,8 +29,11 @@ pub mod contracts;
 pub mod ast;
 pub mod clarity;
 pub mod contexts;
+pub mod coverage;
 pub mod database;
+pub mod debug;
 pub mod representations;
+pub mod trace;
 
 mod callables;
 mod functions;
@@ -60,6 +63,7 @@ pub use vm::representations::{
     ClarityName, ContractName, SymbolicExpression, SymbolicExpressionType,
 };
 
+use core::StacksEpochId;
 use std::convert::TryInto;
 pub use vm::contexts::MAX_CONTEXT_DEPTH;
 pub use vm::functions::{get_stx_balance_snapshot, stx_transfer_consolidated};
@@ -99,7 +103,7 @@ fn lookup_variable(name: &str, context: &LocalContext, env: &mut Environment) ->
 pub fn lookup_function(name: &str, env: &mut Environment) -> Result<CallableType> {
     runtime_cost!(cost_functions::LOOKUP_FUNCTION, env, 0)?;
 
-    if let Some(result) = functions::lookup_reserved_functions(name) {
+    if let Some(result) = functions::lookup_reserved_functions(name, env.contract_context.epoch) {
         Ok(result)
     } else {
         let user_function = env
@@ -194,6 +198,30 @@ pub fn eval<'a>(
     exp: &SymbolicExpression,
     env: &'a mut Environment,
     context: &LocalContext,
+) -> Result<Value> {
+    if let Some(mut eval_hooks) = env.global_context.eval_hooks.take() {
+        for hook in eval_hooks.iter_mut() {
+            hook.will_begin_eval(env, context, exp);
+        }
+        env.global_context.eval_hooks = Some(eval_hooks);
+    }
+
+    let res = eval_expression(exp, env, context);
+
+    if let Some(mut eval_hooks) = env.global_context.eval_hooks.take() {
+        for hook in eval_hooks.iter_mut() {
+            hook.did_finish_eval(env, context, exp, &res);
+        }
+        env.global_context.eval_hooks = Some(eval_hooks);
+    }
+
+    res
+}
+
+fn eval_expression(
+    exp: &SymbolicExpression,
+    env: &mut Environment,
+    context: &LocalContext,
 ) -> Result<Value> {
     use vm::representations::SymbolicExpressionType::{
         Atom, AtomValue, Field, List, LiteralValue, TraitReference,
@@ -216,8 +244,9 @@ pub fn eval<'a>(
     }
 }
 
-pub fn is_reserved(name: &str) -> bool {
-    if let Some(_result) = functions::lookup_reserved_functions(name) {
+/// Is `name` reserved for the natives and keywords of `epoch`?
+pub fn is_reserved(name: &str, epoch: StacksEpochId) -> bool {
+    if let Some(_result) = functions::lookup_reserved_functions(name, epoch) {
         true
     } else if variables::is_reserved_name(name) {
         true
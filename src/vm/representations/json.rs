@@ -0,0 +1,264 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The JSON encoding of Clarity values, for RPC clients that would rather not implement the
+//! consensus serialization.  Every value is an object whose `type` names what kind of value it
+//! is, and whose `value` holds its contents:
+//!
+//! ```text
+//! 1                    {"type": "int", "value": "1"}
+//! u1                   {"type": "uint", "value": "1"}
+//! true                 {"type": "bool", "value": true}
+//! 'SP000...            {"type": "principal", "value": "SP000..."}
+//! 0x0102               {"type": "buff", "value": "0x0102"}
+//! "abc"                {"type": "string-ascii", "value": "abc"}
+//! u"abc"               {"type": "string-utf8", "value": "abc"}
+//! none                 {"type": "none"}
+//! (some 1)             {"type": "some", "value": {"type": "int", "value": "1"}}
+//! (ok 1)               {"type": "ok", "value": {"type": "int", "value": "1"}}
+//! (err 1)              {"type": "err", "value": {"type": "int", "value": "1"}}
+//! (list 1)             {"type": "list", "value": [{"type": "int", "value": "1"}]}
+//! {a: 1}               {"type": "tuple", "value": {"a": {"type": "int", "value": "1"}}}
+//! ```
+//!
+//! Integers are strings, because JSON numbers cannot hold all 128-bit integers.  Decoding
+//! constructs values the way literals in a contract are constructed, e.g., a list's max length
+//! is its length.
+
+use std::convert::TryFrom;
+
+use serde_json::Value as JSONValue;
+
+use util::hash::{hex_bytes, to_hex};
+use vm::errors::CheckErrors;
+use vm::representations::ClarityName;
+use vm::types::serialization::SerializationError;
+use vm::types::{
+    BuffData, CharType, OptionalData, PrincipalData, SequenceData, TupleData, Value, MAX_TYPE_DEPTH,
+};
+
+impl Value {
+    /// The JSON encoding of this value
+    pub fn to_json(&self) -> JSONValue {
+        match self {
+            Value::Int(value) => json!({"type": "int", "value": value.to_string()}),
+            Value::UInt(value) => json!({"type": "uint", "value": value.to_string()}),
+            Value::Bool(value) => json!({"type": "bool", "value": value}),
+            Value::Principal(principal) => {
+                json!({"type": "principal", "value": principal.to_string()})
+            }
+            Value::Sequence(SequenceData::Buffer(BuffData { data })) => {
+                json!({"type": "buff", "value": format!("0x{}", to_hex(data))})
+            }
+            Value::Sequence(SequenceData::String(CharType::ASCII(string))) => json!({
+                "type": "string-ascii",
+                "value": String::from_utf8_lossy(&string.data),
+            }),
+            Value::Sequence(SequenceData::String(CharType::UTF8(string))) => json!({
+                "type": "string-utf8",
+                "value": String::from_utf8_lossy(&string.data.concat()),
+            }),
+            Value::Sequence(SequenceData::List(list)) => json!({
+                "type": "list",
+                "value": list.data.iter().map(|item| item.to_json()).collect::<Vec<_>>(),
+            }),
+            Value::Optional(OptionalData { data: None }) => json!({"type": "none"}),
+            Value::Optional(OptionalData { data: Some(value) }) => {
+                json!({"type": "some", "value": value.to_json()})
+            }
+            Value::Response(response) => json!({
+                "type": if response.committed { "ok" } else { "err" },
+                "value": response.data.to_json(),
+            }),
+            Value::Tuple(tuple) => {
+                let mut fields = serde_json::Map::new();
+                for (name, value) in tuple.data_map.iter() {
+                    fields.insert(name.to_string(), value.to_json());
+                }
+                json!({"type": "tuple", "value": fields})
+            }
+        }
+    }
+
+    /// Decode the JSON encoding of a value
+    pub fn try_from_json(json: &JSONValue) -> Result<Value, SerializationError> {
+        Value::inner_try_from_json(json, 0)
+    }
+
+    fn inner_try_from_json(json: &JSONValue, depth: u8) -> Result<Value, SerializationError> {
+        if depth >= MAX_TYPE_DEPTH {
+            return Err(CheckErrors::TypeSignatureTooDeep.into());
+        }
+
+        let value_type = json
+            .get("type")
+            .and_then(|value_type| value_type.as_str())
+            .ok_or_else(|| "Expected a JSON object with a `type` string")?;
+        let value = json.get("value");
+        let value_str = || {
+            value
+                .and_then(|value| value.as_str())
+                .ok_or_else(|| SerializationError::from("Expected a `value` string"))
+        };
+        let inner_value = || {
+            value
+                .ok_or_else(|| SerializationError::from("Expected a `value`"))
+                .and_then(|value| Value::inner_try_from_json(value, depth + 1))
+        };
+
+        let result = match value_type {
+            "int" => Ok(Value::Int(
+                value_str()?.parse().map_err(|_| "Invalid int value")?,
+            )),
+            "uint" => Ok(Value::UInt(
+                value_str()?.parse().map_err(|_| "Invalid uint value")?,
+            )),
+            "bool" => {
+                let value = value
+                    .and_then(|value| value.as_bool())
+                    .ok_or_else(|| "Expected a `value` boolean")?;
+                Ok(Value::Bool(value))
+            }
+            "principal" => PrincipalData::parse(value_str()?).map(Value::Principal),
+            "buff" => {
+                let hex = value_str()?;
+                let hex = if hex.starts_with("0x") {
+                    &hex[2..]
+                } else {
+                    hex
+                };
+                let bytes = hex_bytes(hex).map_err(|_| "Invalid buff value")?;
+                Value::buff_from(bytes)
+            }
+            "string-ascii" => Value::string_ascii_from_bytes(value_str()?.as_bytes().to_vec()),
+            "string-utf8" => Value::string_utf8_from_bytes(value_str()?.as_bytes().to_vec()),
+            "none" => Ok(Value::none()),
+            "some" => Value::some(inner_value()?),
+            "ok" => Value::okay(inner_value()?),
+            "err" => Value::error(inner_value()?),
+            "list" => {
+                let items = value
+                    .and_then(|value| value.as_array())
+                    .ok_or_else(|| "Expected a `value` array")?
+                    .iter()
+                    .map(|item| Value::inner_try_from_json(item, depth + 1))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Value::list_from(items)
+            }
+            "tuple" => {
+                let fields = value
+                    .and_then(|value| value.as_object())
+                    .ok_or_else(|| "Expected a `value` object")?;
+                let mut data = Vec::with_capacity(fields.len());
+                for (name, field) in fields.iter() {
+                    let name = ClarityName::try_from(name.to_string())
+                        .map_err(|_| "Invalid tuple field name")?;
+                    data.push((name, Value::inner_try_from_json(field, depth + 1)?));
+                }
+                TupleData::from_data(data).map(Value::from)
+            }
+            _ => {
+                return Err(SerializationError::DeserializationError(format!(
+                    "Unknown value type `{}`",
+                    value_type
+                )))
+            }
+        };
+
+        result.map_err(|e| SerializationError::DeserializationError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use vm::execute;
+
+    #[test]
+    fn test_json_round_trip() {
+        let values = [
+            "-170141183460469231731687303715884105728",
+            "u340282366920938463463374607431768211455",
+            "false",
+            "'SZ2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQ9H6DPR",
+            "'SZ2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQ9H6DPR.tokens",
+            "0xdeadbeef",
+            "\"hello \\\"world\\\"\"",
+            "u\"hello \\u{1F98A}\"",
+            "none",
+            "(some (ok u1))",
+            "(err (list 1 2 3))",
+            "(list (some 1) none)",
+            "{a: 1, b: (list {c: 0x01}), d: (err u1)}",
+        ];
+
+        for value in values.iter() {
+            let value = execute(value).unwrap().unwrap();
+            let json = value.to_json();
+            assert_eq!(Value::try_from_json(&json).unwrap(), value);
+
+            // survives a trip through the JSON text, too
+            let text = serde_json::to_string(&json).unwrap();
+            let json = serde_json::from_str(&text).unwrap();
+            assert_eq!(Value::try_from_json(&json).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_json_encoding() {
+        assert_eq!(
+            execute("{a: -1, b: (some u\"\\u{1F98A}\")}")
+                .unwrap()
+                .unwrap()
+                .to_json(),
+            json!({
+                "type": "tuple",
+                "value": {
+                    "a": {"type": "int", "value": "-1"},
+                    "b": {"type": "some", "value": {"type": "string-utf8", "value": "\u{1F98A}"}},
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_json_decode_errors() {
+        let bad = [
+            json!(1),
+            json!({"value": "1"}),
+            json!({"type": "int", "value": 1}),
+            json!({"type": "uint", "value": "-1"}),
+            json!({"type": "bool", "value": "true"}),
+            json!({"type": "principal", "value": "SP000"}),
+            json!({"type": "buff", "value": "0x0"}),
+            json!({"type": "string-ascii", "value": "\u{1F98A}"}),
+            json!({"type": "some"}),
+            json!({"type": "list", "value": [{"type": "int", "value": "1"}, {"type": "uint", "value": "1"}]}),
+            json!({"type": "tuple", "value": {"not a name": {"type": "int", "value": "1"}}}),
+            json!({"type": "unit"}),
+        ];
+
+        for json in bad.iter() {
+            assert!(Value::try_from_json(json).is_err(), "{}", json);
+        }
+
+        let mut deep = json!({"type": "none"});
+        for _i in 0..MAX_TYPE_DEPTH {
+            deep = json!({"type": "some", "value": deep});
+        }
+        assert!(Value::try_from_json(&deep).is_err());
+    }
+}
@@ -22,6 +22,8 @@ use std::ops::Deref;
 use vm::errors::RuntimeErrorType;
 use vm::types::{QualifiedContractIdentifier, TraitIdentifier, Value};
 
+pub mod json;
+
 pub const MAX_STRING_LEN: u8 = 128;
 
 macro_rules! guarded_string {
@@ -116,6 +116,30 @@ const ASSET_NAMES: &str =
                     (err u3))
                   (err u4))))";
 
+const NESTED_INNER: &str = "(define-fungible-token gems)
+         (define-non-fungible-token badges uint)
+         (define-data-var calls uint u0)
+         (define-read-only (get-calls) (var-get calls))
+         (define-public (give (to principal) (amount uint) (badge uint) (succeed bool))
+           (begin
+             (var-set calls (+ (var-get calls) u1))
+             (try! (ft-transfer? gems amount tx-sender to))
+             (try! (nft-transfer? badges badge tx-sender to))
+             (if succeed (ok (var-get calls)) (err u100))))
+         (begin (ft-mint? gems u100 'SZ2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQ9H6DPR)
+                (nft-mint? badges u1 'SZ2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQ9H6DPR)
+                (nft-mint? badges u2 'SZ2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQ9H6DPR))";
+
+const NESTED_MIDDLE: &str =
+    "(define-public (relay (to principal) (succeed-second bool) (succeed bool))
+           (let ((first (contract-call? .inner give to u10 u1 true))
+                 (second (contract-call? .inner give to u20 u2 succeed-second)))
+             (if succeed (ok (list first second)) (err (list first second)))))";
+
+const NESTED_OUTER: &str = "(define-public (call-relay (to principal) (succeed-second bool))
+           (let ((relayed (contract-call? .middle relay to succeed-second true)))
+             (ok relayed)))";
+
 fn execute_transaction(
     env: &mut OwnedEnvironment,
     issuer: Value,
@@ -135,7 +159,12 @@ fn test_native_stx_ops(owned_env: &mut OwnedEnvironment) {
                         (stx-transfer? amount p contract-principal)))
                     (define-public (from-contract (amount uint) (t principal))
                       (let ((contract-principal 'SZ2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQ9H6DPR.tokens))
-                        (as-contract (stx-transfer? amount contract-principal t))))";
+                        (as-contract (stx-transfer? amount contract-principal t))))
+                    (define-public (xfer-and-burn-then-fail (amount uint) (p principal) (t principal))
+                      (begin
+                        (unwrap-panic (stx-transfer? amount p t))
+                        (unwrap-panic (stx-burn? amount p))
+                        (err u100)))";
 
     let contract_second = "(define-public (send-to-other (amount uint))
                              (as-contract
@@ -517,6 +546,32 @@ fn test_native_stx_ops(owned_env: &mut OwnedEnvironment) {
             .unwrap(),
         &AssetMapEntry::STX(100)
     );
+
+    // transfers and burns in a transaction that returns an err are rolled back
+
+    let (result, asset_map, events) = execute_transaction(
+        owned_env,
+        p2.clone(),
+        &token_contract_id,
+        "xfer-and-burn-then-fail",
+        &symbols_from_values(vec![Value::UInt(100), p2.clone(), p3.clone()]),
+    )
+    .unwrap();
+
+    assert!(is_err_code(&result, 100));
+    assert_eq!(asset_map.to_table().len(), 0);
+    assert_eq!(events.len(), 0);
+
+    let (result, _asset_map, _events) = execute_transaction(
+        owned_env,
+        p2.clone(),
+        &token_contract_id,
+        "balance-stx",
+        &symbols_from_values(vec![p2.clone()]),
+    )
+    .unwrap();
+
+    assert_eq!(result, Value::UInt(480));
 }
 
 fn test_simple_token_system(owned_env: &mut OwnedEnvironment) {
@@ -972,6 +1027,10 @@ fn test_simple_naming_system(owned_env: &mut OwnedEnvironment) {
     .unwrap();
 
     assert!(is_err_code(&result, 1));
+    assert_eq!(
+        asset_map.get_minted_nonfungible_tokens(&p1_principal.clone().into(), &names_identifier),
+        None
+    );
     assert_eq!(asset_map.to_table().len(), 0);
 
     let (result, asset_map, _events) = execute_transaction(
@@ -984,6 +1043,11 @@ fn test_simple_naming_system(owned_env: &mut OwnedEnvironment) {
     .unwrap();
 
     assert!(is_committed(&result));
+    // mints are tracked, but are not transfers
+    assert_eq!(
+        asset_map.get_minted_nonfungible_tokens(&p1_principal.clone().into(), &names_identifier),
+        Some(&vec![Value::Int(5)])
+    );
     assert_eq!(asset_map.to_table().len(), 0);
 
     // let's transfer name
@@ -1106,9 +1170,130 @@ fn test_simple_naming_system(owned_env: &mut OwnedEnvironment) {
     assert!(is_err_code(&result, 5));
 }
 
+fn test_nested_savepoints(owned_env: &mut OwnedEnvironment) {
+    let p1 = execute("'SZ2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQ9H6DPR");
+    let p2 = execute("'SM2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQVX8X0G");
+
+    let p1_principal = match p1 {
+        Value::Principal(PrincipalData::Standard(ref data)) => data.clone(),
+        _ => panic!(),
+    };
+
+    let inner_contract_id = QualifiedContractIdentifier::new(p1_principal.clone(), "inner".into());
+    let middle_contract_id =
+        QualifiedContractIdentifier::new(p1_principal.clone(), "middle".into());
+    let outer_contract_id = QualifiedContractIdentifier::new(p1_principal.clone(), "outer".into());
+
+    let gems = AssetIdentifier {
+        contract_identifier: inner_contract_id.clone(),
+        asset_name: "gems".into(),
+    };
+    let badges = AssetIdentifier {
+        contract_identifier: inner_contract_id.clone(),
+        asset_name: "badges".into(),
+    };
+
+    owned_env
+        .initialize_contract(inner_contract_id.clone(), NESTED_INNER)
+        .unwrap();
+    owned_env
+        .initialize_contract(middle_contract_id.clone(), NESTED_MIDDLE)
+        .unwrap();
+    owned_env
+        .initialize_contract(outer_contract_id.clone(), NESTED_OUTER)
+        .unwrap();
+
+    let ok_uint = |x| Value::okay(Value::UInt(x)).unwrap();
+    let err_uint = |x| Value::error(Value::UInt(x)).unwrap();
+
+    // both inner calls succeed, but the middle call fails: nothing it did survives
+    let (result, asset_map, _events) = execute_transaction(
+        owned_env,
+        p1.clone(),
+        &middle_contract_id,
+        "relay",
+        &symbols_from_values(vec![p2.clone(), Value::Bool(true), Value::Bool(false)]),
+    )
+    .unwrap();
+    assert_eq!(
+        result,
+        Value::error(Value::list_from(vec![ok_uint(1), ok_uint(2)]).unwrap()).unwrap()
+    );
+    assert_eq!(asset_map.to_table().len(), 0);
+    assert_eq!(
+        owned_env
+            .eval_read_only(&inner_contract_id, "(get-calls)")
+            .unwrap()
+            .0,
+        Value::UInt(0)
+    );
+
+    // the second inner call fails: only its writes and transfers are rolled back, and the
+    //  middle call carries on with its error response
+    let (result, asset_map, _events) = execute_transaction(
+        owned_env,
+        p1.clone(),
+        &outer_contract_id,
+        "call-relay",
+        &symbols_from_values(vec![p2.clone(), Value::Bool(false)]),
+    )
+    .unwrap();
+    assert_eq!(
+        result,
+        Value::okay(
+            Value::okay(Value::list_from(vec![ok_uint(1), err_uint(100)]).unwrap()).unwrap()
+        )
+        .unwrap()
+    );
+
+    let table = asset_map.to_table();
+    let p1_transfers = &table[&PrincipalData::Standard(p1_principal.clone())];
+    assert_eq!(p1_transfers.len(), 2);
+    assert_eq!(p1_transfers[&gems], AssetMapEntry::Token(10));
+    assert_eq!(
+        p1_transfers[&badges],
+        AssetMapEntry::Asset(vec![Value::UInt(1)])
+    );
+    assert_eq!(table.len(), 1);
+
+    assert_eq!(
+        owned_env
+            .eval_read_only(&inner_contract_id, "(get-calls)")
+            .unwrap()
+            .0,
+        Value::UInt(1)
+    );
+    assert_eq!(
+        owned_env
+            .eval_read_only(
+                &inner_contract_id,
+                "(list (ft-get-balance gems 'SZ2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQ9H6DPR)
+                       (ft-get-balance gems 'SM2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQVX8X0G))"
+            )
+            .unwrap()
+            .0,
+        Value::list_from(vec![Value::UInt(90), Value::UInt(10)]).unwrap()
+    );
+    assert_eq!(
+        owned_env
+            .eval_read_only(
+                &inner_contract_id,
+                "(list (nft-get-owner? badges u1) (nft-get-owner? badges u2))"
+            )
+            .unwrap()
+            .0,
+        Value::list_from(vec![
+            Value::some(p2.clone()).unwrap(),
+            Value::some(p1.clone()).unwrap()
+        ])
+        .unwrap()
+    );
+}
+
 #[test]
 fn test_all() {
     let to_test = [
+        test_nested_savepoints,
         test_overlapping_nfts,
         test_simple_token_system,
         test_simple_naming_system,
@@ -103,6 +103,8 @@ fn test_get_block_info_eval() {
         "(define-private (test-func) (get-block-info? header-hash u1))",
         "(define-private (test-func) (get-block-info? burnchain-header-hash u1))",
         "(define-private (test-func) (get-block-info? vrf-seed u1))",
+        "(define-private (test-func) (get-block-info? id-header-hash u1))",
+        "(define-private (test-func) (get-block-info? miner-address u1))",
     ];
 
     let expected = [
@@ -114,6 +116,8 @@ fn test_get_block_info_eval() {
         Ok(Value::none()),
         Ok(Value::none()),
         Ok(Value::none()),
+        Ok(Value::none()),
+        Ok(Value::none()),
     ];
     /*    let expected = [
         Ok(Value::UInt(0)),
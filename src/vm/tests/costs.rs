@@ -74,6 +74,10 @@ pub fn get_simple_test(function: &NativeFunctions) -> &'static str {
         Concat => "(concat list-bar list-bar)",
         AsMaxLen => "(as-max-len? list-bar u3)",
         Len => "(len list-bar)",
+        IndexOf => "(index-of list-bar 2)",
+        Slice => "(slice? list-bar u1 u2)",
+        ToConsensusBuff => "(to-consensus-buff? list-bar)",
+        FromConsensusBuff => "(from-consensus-buff? int 0x0000000000000000000000000000000001)",
         ListCons => "(list 1 2 3 4)",
         FetchEntry => "(map-get? map-foo {a: 1})",
         SetEntry => "(map-set map-foo {a: 1} {b: 2})",
@@ -429,6 +429,91 @@ fn test_simple_list_assert_max_len() {
     }
 }
 
+#[test]
+fn test_simple_index_of() {
+    let tests = [
+        "(index-of (list 1 2 3 2) 2)",
+        "(index-of (list 1 2 3) 4)",
+        "(index-of 0x010203 0x03)",
+        "(index-of \"blockstack\" \"k\")",
+        "(index-of u\"abc\\u{1F98A}\" u\"\\u{1F98A}\")",
+        "(index-of (list) 1)",
+    ];
+
+    let expected = [
+        Value::some(Value::UInt(1)).unwrap(),
+        Value::none(),
+        Value::some(Value::UInt(2)).unwrap(),
+        Value::some(Value::UInt(4)).unwrap(),
+        Value::some(Value::UInt(3)).unwrap(),
+        Value::none(),
+    ];
+
+    for (test, expected) in tests.iter().zip(expected.iter()) {
+        assert_eq!(expected.clone(), execute(test).unwrap().unwrap());
+    }
+
+    assert_eq!(
+        execute("(index-of 0x010203)").unwrap_err(),
+        CheckErrors::IncorrectArgumentCount(2, 1).into()
+    );
+
+    assert_eq!(
+        execute("(index-of 1 1)").unwrap_err(),
+        CheckErrors::ExpectedSequence(IntType).into()
+    );
+}
+
+#[test]
+fn test_simple_slice() {
+    let tests = [
+        "(slice? (list 1 2 3 4 5) u1 u3)",
+        "(slice? (list 1 2 3) u0 u3)",
+        "(slice? 0x010203 u3 u3)",
+        "(slice? (list 1 2 3) u2 u1)",
+        "(slice? (list 1 2 3) u1 u4)",
+        "(slice? 0x0102030405 u1 u3)",
+        "(slice? \"blockstack\" u5 u10)",
+        "(slice? 0x01 u0 u340282366920938463463374607431768211455)",
+    ];
+
+    let expected = [
+        Value::some(Value::list_from(vec![Value::Int(2), Value::Int(3)]).unwrap()).unwrap(),
+        Value::some(Value::list_from(vec![Value::Int(1), Value::Int(2), Value::Int(3)]).unwrap())
+            .unwrap(),
+        Value::some(Value::buff_from(vec![]).unwrap()).unwrap(),
+        Value::none(),
+        Value::none(),
+        Value::some(Value::buff_from(vec![2, 3]).unwrap()).unwrap(),
+        Value::some(Value::string_ascii_from_bytes("stack".into()).unwrap()).unwrap(),
+        Value::none(),
+    ];
+
+    for (test, expected) in tests.iter().zip(expected.iter()) {
+        assert_eq!(expected.clone(), execute(test).unwrap().unwrap());
+    }
+
+    assert_eq!(
+        execute("(slice? 0x010203 u1)").unwrap_err(),
+        CheckErrors::IncorrectArgumentCount(3, 2).into()
+    );
+
+    assert_eq!(
+        execute("(slice? 0x010203 1 u2)").unwrap_err(),
+        CheckErrors::TypeError(UIntType, IntType).into()
+    );
+
+    assert_eq!(
+        execute("(slice? 0x010203 u1 2)").unwrap_err(),
+        CheckErrors::TypeError(UIntType, IntType).into()
+    );
+
+    assert_eq!(
+        execute("(slice? 1 u0 u1)").unwrap_err(),
+        CheckErrors::ExpectedSequence(IntType).into()
+    );
+}
+
 #[test]
 fn test_simple_map_buffer() {
     let test1 = "(define-private (incr (x (buff 1))) 0x31)
@@ -29,6 +29,8 @@ use vm::types::{PrincipalData, ResponseData, SequenceData, SequenceSubtype};
 use vm::{eval, execute as vm_execute};
 use vm::{CallStack, ContractContext, Environment, GlobalContext, LocalContext, Value};
 
+use core::StacksEpochId;
+
 use address::c32;
 use address::AddressHashMode;
 use chainstate::stacks::StacksPublicKey;
@@ -297,6 +299,132 @@ fn test_secp256k1_errors() {
     }
 }
 
+#[test]
+fn test_consensus_buff_round_trip() {
+    let values = [
+        "1",
+        "u1",
+        "true",
+        "none",
+        "(some (ok u1))",
+        "(err 2)",
+        "(list 1 2 3)",
+        "0x0102",
+        "\"stack\"",
+        "u\"stack\\u{1F98A}\"",
+        "'SZ2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQ9H6DPR",
+        "'SZ2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQ9H6DPR.tokens",
+        "(tuple (a 1) (b (list u2 u3)))",
+    ];
+    let types = [
+        "int",
+        "uint",
+        "bool",
+        "(optional int)",
+        "(optional (response uint int))",
+        "(response bool int)",
+        "(list 3 int)",
+        "(buff 2)",
+        "(string-ascii 5)",
+        "(string-utf8 9)",
+        "principal",
+        "principal",
+        "(tuple (a int) (b (list 2 uint)))",
+    ];
+
+    for (value, type_repr) in values.iter().zip(types.iter()) {
+        let expected = execute(value);
+        let serialized = execute(&format!("(unwrap-panic (to-consensus-buff? {}))", value));
+        match serialized {
+            Value::Sequence(SequenceData::Buffer(ref buff)) => {
+                let mut expected_bytes = vec![];
+                expected.serialize_write(&mut expected_bytes).unwrap();
+                assert_eq!(buff.data, expected_bytes);
+            }
+            _ => panic!("to-consensus-buff? did not return a buffer"),
+        }
+        assert_eq!(
+            Value::some(expected).unwrap(),
+            execute(&format!(
+                "(from-consensus-buff? {} (unwrap-panic (to-consensus-buff? {})))",
+                type_repr, value
+            ))
+        );
+    }
+
+    let tests = [
+        // wrong type
+        "(from-consensus-buff? uint 0x0000000000000000000000000000000001)",
+        // trailing bytes
+        "(from-consensus-buff? int 0x000000000000000000000000000000000100)",
+        // too short
+        "(from-consensus-buff? int 0x00000000000000000000000000000001)",
+        // list longer than the expected type allows
+        "(from-consensus-buff? (list 2 int) (unwrap-panic (to-consensus-buff? (list 1 2 3))))",
+    ];
+    for test in tests.iter() {
+        assert_eq!(Value::none(), execute(test));
+    }
+
+    assert_eq!(
+        vm_execute("(from-consensus-buff? int 1)").unwrap_err(),
+        CheckErrors::TypeError(TypeSignature::max_buffer(), TypeSignature::IntType).into()
+    );
+    assert_eq!(
+        vm_execute("(to-consensus-buff? 1 2)").unwrap_err(),
+        CheckErrors::IncorrectArgumentCount(1, 2).into()
+    );
+}
+
+#[test]
+fn test_consensus_buff_gated_by_epoch() {
+    let mut marf = MemoryBackingStore::new();
+    let mut owned_env = OwnedEnvironment::new(marf.as_clarity_db());
+    let contract_id = QualifiedContractIdentifier::local("gated").unwrap();
+
+    // before Stacks 2.1, the natives don't exist, and contracts can use their names
+    owned_env.set_epoch(StacksEpochId::Epoch20);
+    for program in [
+        "(to-consensus-buff? 1)",
+        "(from-consensus-buff? int 0x0000000000000000000000000000000001)",
+    ]
+    .iter()
+    {
+        let function_name = program[1..].split(' ').next().unwrap();
+        assert_eq!(
+            owned_env
+                .initialize_contract(contract_id.clone(), program)
+                .unwrap_err(),
+            CheckErrors::UndefinedFunction(function_name.to_string()).into()
+        );
+    }
+    owned_env
+        .initialize_contract(
+            contract_id.clone(),
+            "(define-read-only (to-consensus-buff? (x int)) (+ x 1))
+             (define-read-only (foo) (to-consensus-buff? 1))",
+        )
+        .unwrap();
+
+    // ...and contracts deployed then keep their own definitions
+    owned_env.set_epoch(StacksEpochId::Epoch21);
+    assert_eq!(
+        owned_env.eval_read_only(&contract_id, "(foo)").unwrap().0,
+        Value::Int(2)
+    );
+    assert_eq!(
+        owned_env
+            .eval_read_only(&contract_id, "(to-consensus-buff? 1)")
+            .unwrap()
+            .0,
+        Value::Int(2)
+    );
+    assert_eq!(
+        execute("(to-consensus-buff? true)"),
+        Value::some(Value::buff_from(vec![0x03]).unwrap()).unwrap()
+    );
+}
+
 #[test]
 fn test_buffer_equality() {
     let tests = [
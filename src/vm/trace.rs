@@ -0,0 +1,292 @@
+// Copyright (C) 2013-2020 Blocstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Expression-level tracing, for finding the hot paths of a contract before it is deployed.
+//! The `ExecutionTracer` is an `EvalHook` that records every expression evaluated, with the
+//! cost it was charged and the time it took, and the `ExecutionTrace` it fills renders them as
+//! folded stacks, the input format of flamegraph tools.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use vm::contexts::{EvalHook, LocalContext};
+use vm::costs::ExecutionCost;
+use vm::debug::{called_function_name, expression_location};
+use vm::errors::InterpreterResult as Result;
+use vm::types::QualifiedContractIdentifier;
+use vm::{Environment, SymbolicExpression, SymbolicExpressionType, Value};
+
+/// What the folded stacks of a trace count
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceWeight {
+    /// microseconds spent evaluating
+    Time,
+    /// runtime cost charged
+    Cost,
+}
+
+/// One evaluated expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceRecord {
+    pub contract_identifier: QualifiedContractIdentifier,
+    /// frames from the outermost expression being evaluated down to this one.  A frame with a
+    /// contract identifier marks where evaluation entered that contract.
+    pub stack: Vec<String>,
+    /// cost charged for the expression, including the expressions nested in it
+    pub cost: ExecutionCost,
+    /// time spent on the expression, including the expressions nested in it
+    pub duration: Duration,
+    /// runtime cost charged for the expression itself
+    pub self_runtime: u64,
+    /// time spent on the expression itself
+    pub self_duration: Duration,
+}
+
+/// The expressions an `ExecutionTracer` recorded, in the order they finished
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutionTrace {
+    pub records: Vec<TraceRecord>,
+}
+
+struct Frame {
+    contract_identifier: QualifiedContractIdentifier,
+    label: String,
+    start_time: Instant,
+    start_cost: ExecutionCost,
+    children_runtime: u64,
+    children_duration: Duration,
+}
+
+pub struct ExecutionTracer {
+    trace: Rc<RefCell<ExecutionTrace>>,
+    frames: Vec<Frame>,
+}
+
+/// The flamegraph frame name of `expr`: the function it calls, or the variable it reads.
+/// Frame names can contain neither spaces nor semicolons.
+fn frame_label(expr: &SymbolicExpression) -> String {
+    let name = match expr.expr {
+        SymbolicExpressionType::List(ref list) => {
+            let head = list.first().and_then(|head| head.match_atom());
+            match (head, called_function_name(expr)) {
+                (Some(head), Some(function_name)) if &**head == "contract-call?" => {
+                    format!("contract-call?:{}", function_name)
+                }
+                (_, Some(function_name)) => function_name.to_string(),
+                _ => "list".to_string(),
+            }
+        }
+        SymbolicExpressionType::Atom(ref name) => name.to_string(),
+        SymbolicExpressionType::AtomValue(_) | SymbolicExpressionType::LiteralValue(_) => {
+            "literal".to_string()
+        }
+        SymbolicExpressionType::TraitReference(ref name, _) => name.to_string(),
+        SymbolicExpressionType::Field(_) => "field".to_string(),
+    };
+    match expression_location(expr) {
+        Some((line, column)) => format!("{}@{}:{}", name, line, column),
+        None => name,
+    }
+}
+
+impl ExecutionTrace {
+    pub fn new() -> ExecutionTrace {
+        ExecutionTrace { records: vec![] }
+    }
+
+    /// The trace as folded stacks: one line per distinct stack, with the frames separated by
+    /// semicolons, followed by the weight of the expressions on that stack themselves.  Stacks
+    /// with no weight are left out.
+    pub fn folded_stacks(&self, weight: TraceWeight) -> String {
+        let mut stacks: BTreeMap<String, u64> = BTreeMap::new();
+        for record in self.records.iter() {
+            let record_weight = match weight {
+                TraceWeight::Time => record.self_duration.as_micros() as u64,
+                TraceWeight::Cost => record.self_runtime,
+            };
+            let total = stacks.entry(record.stack.join(";")).or_insert(0);
+            *total = total.saturating_add(record_weight);
+        }
+
+        let mut folded = String::new();
+        for (stack, total) in stacks.into_iter() {
+            if total > 0 {
+                folded.push_str(&format!("{} {}\n", stack, total));
+            }
+        }
+        folded
+    }
+}
+
+impl ExecutionTracer {
+    pub fn new() -> ExecutionTracer {
+        ExecutionTracer {
+            trace: Rc::new(RefCell::new(ExecutionTrace::new())),
+            frames: vec![],
+        }
+    }
+
+    /// The trace this tracer records into.  It can be read once the traced code has run.
+    pub fn trace(&self) -> Rc<RefCell<ExecutionTrace>> {
+        self.trace.clone()
+    }
+
+    fn stack(&self) -> Vec<String> {
+        let mut stack = vec![];
+        let mut contract_identifier = None;
+        for frame in self.frames.iter() {
+            if contract_identifier != Some(&frame.contract_identifier) {
+                stack.push(frame.contract_identifier.to_string());
+                contract_identifier = Some(&frame.contract_identifier);
+            }
+            stack.push(frame.label.clone());
+        }
+        stack
+    }
+}
+
+impl EvalHook for ExecutionTracer {
+    fn will_begin_eval(
+        &mut self,
+        env: &mut Environment,
+        _context: &LocalContext,
+        expr: &SymbolicExpression,
+    ) {
+        self.frames.push(Frame {
+            contract_identifier: env.contract_context.contract_identifier.clone(),
+            label: frame_label(expr),
+            start_time: Instant::now(),
+            start_cost: env.global_context.cost_track.get_total(),
+            children_runtime: 0,
+            children_duration: Duration::from_secs(0),
+        });
+    }
+
+    fn did_finish_eval(
+        &mut self,
+        env: &mut Environment,
+        _context: &LocalContext,
+        _expr: &SymbolicExpression,
+        _res: &Result<Value>,
+    ) {
+        let stack = self.stack();
+        let frame = match self.frames.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+
+        let duration = frame.start_time.elapsed();
+        let mut cost = env.global_context.cost_track.get_total();
+        if cost.sub(&frame.start_cost).is_err() {
+            cost = ExecutionCost::zero();
+        }
+
+        if let Some(parent) = self.frames.last_mut() {
+            parent.children_runtime = parent.children_runtime.saturating_add(cost.runtime);
+            parent.children_duration += duration;
+        }
+
+        self.trace.borrow_mut().records.push(TraceRecord {
+            contract_identifier: frame.contract_identifier,
+            stack,
+            self_runtime: cost.runtime.saturating_sub(frame.children_runtime),
+            self_duration: duration
+                .checked_sub(frame.children_duration)
+                .unwrap_or(Duration::from_secs(0)),
+            cost,
+            duration,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use vm::contexts::OwnedEnvironment;
+    use vm::tests::{execute, symbols_from_values, with_memory_environment};
+
+    const COUNTER_CONTRACT: &str = "
+        (define-data-var counter int 0)
+        (define-private (bump (by int))
+          (var-set counter (+ (var-get counter) by)))
+        (define-public (bump-twice (by int))
+          (begin
+            (bump by)
+            (bump by)
+            (ok (var-get counter))))";
+
+    #[test]
+    fn test_execution_trace() {
+        with_memory_environment(
+            |owned_env: &mut OwnedEnvironment| {
+                let contract_id = QualifiedContractIdentifier::local("counter").unwrap();
+                owned_env
+                    .initialize_contract(contract_id.clone(), COUNTER_CONTRACT)
+                    .unwrap();
+
+                let tracer = ExecutionTracer::new();
+                let trace = tracer.trace();
+                owned_env.add_eval_hook(Box::new(tracer));
+
+                let sender = execute("'SZ2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQ9H6DPR");
+                let (result, _, _) = owned_env
+                    .execute_transaction(
+                        sender,
+                        contract_id.clone(),
+                        "bump-twice",
+                        &symbols_from_values(vec![Value::Int(3)]),
+                    )
+                    .unwrap();
+                assert_eq!(result, Value::okay(Value::Int(6)).unwrap());
+
+                let trace = trace.borrow();
+                let bump_calls: Vec<&TraceRecord> = trace
+                    .records
+                    .iter()
+                    .filter(|record| record.stack.last().unwrap().split('@').next() == Some("bump"))
+                    .collect();
+                assert_eq!(bump_calls.len(), 2);
+
+                // the body of bump-twice is the outermost expression, and it is charged for
+                // everything evaluated in it
+                let outermost = trace.records.last().unwrap();
+                assert_eq!(outermost.stack.len(), 2);
+                assert_eq!(outermost.stack[0], contract_id.to_string());
+                assert_eq!(outermost.stack[1].split('@').next(), Some("begin"));
+                for record in trace.records.iter() {
+                    assert!(record.cost.runtime <= outermost.cost.runtime);
+                    assert!(record.self_runtime <= record.cost.runtime);
+                    assert!(record.stack.starts_with(&outermost.stack));
+                }
+                let self_runtime: u64 =
+                    trace.records.iter().map(|record| record.self_runtime).sum();
+                assert_eq!(self_runtime, outermost.cost.runtime);
+
+                let folded = trace.folded_stacks(TraceWeight::Cost);
+                assert!(folded.len() > 0);
+                for line in folded.lines() {
+                    let (stack, weight) = line.split_at(line.rfind(' ').unwrap());
+                    assert!(stack.starts_with(&contract_id.to_string()));
+                    assert!(weight.trim().parse::<u64>().unwrap() > 0);
+                }
+            },
+            true,
+        );
+    }
+}
@@ -306,6 +306,58 @@ impl SequenceData {
         }?;
         Ok(())
     }
+
+    /// The position of the first element equal to `item`.  The elements of buffers and strings
+    /// are buffers and strings of length 1.
+    pub fn index_of(&self, item: &Value) -> Option<usize> {
+        match self {
+            SequenceData::Buffer(ref data) => data
+                .items()
+                .iter()
+                .position(|byte| BuffData::to_value(byte) == *item),
+            SequenceData::List(ref data) => data.items().iter().position(|value| value == item),
+            SequenceData::String(CharType::ASCII(ref data)) => data
+                .items()
+                .iter()
+                .position(|character| ASCIIData::to_value(character) == *item),
+            SequenceData::String(CharType::UTF8(ref data)) => data
+                .items()
+                .iter()
+                .position(|character| UTF8Data::to_value(character) == *item),
+        }
+    }
+
+    /// The elements from position `left` up to, but not including, position `right`, or None
+    /// if those are not positions in the sequence
+    pub fn slice(&self, left: usize, right: usize) -> Option<SequenceData> {
+        if left > right || right > self.len() {
+            return None;
+        }
+        let sliced = match self {
+            SequenceData::Buffer(ref data) => SequenceData::Buffer(BuffData {
+                data: data.data[left..right].to_vec(),
+            }),
+            SequenceData::List(ref data) => {
+                let mut type_signature = data.type_signature.clone();
+                type_signature.reduce_max_len((right - left) as u32);
+                SequenceData::List(ListData {
+                    data: data.data[left..right].to_vec(),
+                    type_signature,
+                })
+            }
+            SequenceData::String(CharType::ASCII(ref data)) => {
+                SequenceData::String(CharType::ASCII(ASCIIData {
+                    data: data.data[left..right].to_vec(),
+                }))
+            }
+            SequenceData::String(CharType::UTF8(ref data)) => {
+                SequenceData::String(CharType::UTF8(UTF8Data {
+                    data: data.data[left..right].to_vec(),
+                }))
+            }
+        };
+        Some(sliced)
+    }
 }
 
 #[derive(Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -23,13 +23,15 @@ use vm::representations::{ClarityName, ContractName, MAX_STRING_LEN};
 use vm::types::{
     BufferLength, CharType, OptionalData, PrincipalData, QualifiedContractIdentifier, ResponseData,
     SequenceData, SequenceSubtype, StandardPrincipalData, StringSubtype, StringUTF8Length,
-    TupleData, TypeSignature, Value, BOUND_VALUE_SERIALIZATION_BYTES, MAX_VALUE_SIZE,
+    TupleData, TypeSignature, Value, BOUND_VALUE_SERIALIZATION_BYTES,
+    BOUND_VALUE_SERIALIZATION_HEX, MAX_VALUE_SIZE,
 };
 
 use net::{Error as NetError, StacksMessageCodec};
 
 use serde_json::Value as JSONValue;
 use std::borrow::Borrow;
+use std::cmp;
 use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
 use util::hash::{hex_bytes, to_hex};
@@ -272,6 +274,13 @@ impl StacksMessageCodec for PrincipalData {
     }
 }
 
+/// The deepest a serialized value may nest
+const MAX_SERIALIZATION_DEPTH: u8 = 16;
+
+/// Lists and tuples give their length before their items, so a serialization can claim far more
+/// items than it holds.  Space is never reserved for more than this many items up front.
+const MAX_PREALLOCATED_ITEMS: u32 = 1024;
+
 macro_rules! check_match {
     ($item:expr, $Pattern:pat) => {
         match $item {
@@ -299,7 +308,7 @@ impl Value {
         use super::PrincipalData::*;
         use super::Value::*;
 
-        if depth >= 16 {
+        if depth >= MAX_SERIALIZATION_DEPTH {
             return Err(CheckErrors::TypeSignatureTooDeep.into());
         }
 
@@ -435,7 +444,7 @@ impl Value {
                     Some(x) => return Err(SerializationError::DeserializeExpected(x.clone())),
                 };
 
-                let mut items = Vec::with_capacity(len as usize);
+                let mut items = Vec::with_capacity(cmp::min(len, MAX_PREALLOCATED_ITEMS) as usize);
                 for _i in 0..len {
                     items.push(Value::inner_deserialize_read(r, entry_type, depth + 1)?);
                 }
@@ -471,7 +480,7 @@ impl Value {
                     Some(x) => return Err(SerializationError::DeserializeExpected(x.clone())),
                 };
 
-                let mut items = Vec::with_capacity(len as usize);
+                let mut items = Vec::with_capacity(cmp::min(len, MAX_PREALLOCATED_ITEMS) as usize);
                 for _i in 0..len {
                     let key = ClarityName::deserialize_read(r)?;
 
@@ -648,15 +657,46 @@ impl Value {
         Value::try_deserialize_hex(hex, expected)
             .expect("ERROR: Failed to parse Clarity hex string")
     }
-}
 
-impl ClaritySerializable for Value {
-    fn serialize(&self) -> String {
+    /// The hex string of this value's consensus serialization, without a `0x` prefix
+    pub fn serialize_to_hex(&self) -> String {
         let mut byte_serialization = Vec::new();
         self.serialize_write(&mut byte_serialization)
             .expect("IOError filling byte buffer.");
         to_hex(byte_serialization.as_slice())
     }
+
+    /// Decode a value from untrusted input -- e.g., an RPC request -- given as the hex string of
+    /// its consensus serialization, with or without a `0x` prefix.  If `expected` is given, the
+    /// value must be of that type.  Unlike `try_deserialize_hex`, this checks the length of the
+    /// hex string before decoding it, and rejects serializations followed by trailing bytes.
+    pub fn deserialize_from_hex(
+        hex: &str,
+        expected: Option<&TypeSignature>,
+    ) -> Result<Value, SerializationError> {
+        let hex = if hex.starts_with("0x") {
+            &hex[2..]
+        } else {
+            hex
+        };
+        if hex.len() > BOUND_VALUE_SERIALIZATION_HEX as usize {
+            return Err("Hex string too long".into());
+        }
+
+        let data = hex_bytes(hex).map_err(|_| "Bad hex string")?;
+        let mut remaining = data.as_slice();
+        let value = Value::deserialize_read(&mut remaining, expected)?;
+        if !remaining.is_empty() {
+            return Err("Trailing bytes after serialized value".into());
+        }
+        Ok(value)
+    }
+}
+
+impl ClaritySerializable for Value {
+    fn serialize(&self) -> String {
+        self.serialize_to_hex()
+    }
 }
 
 impl ClarityDeserializable<Value> for Value {
@@ -1057,6 +1097,60 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_hex_round_trip() {
+        let values = [
+            Value::Int(-1),
+            Value::some(Value::buff_from(vec![1, 2, 3]).unwrap()).unwrap(),
+            Value::list_from(vec![Value::UInt(1), Value::UInt(2)]).unwrap(),
+        ];
+        for value in values.iter() {
+            let hex = value.serialize_to_hex();
+            assert_eq!(hex, value.serialize());
+            assert_eq!(&Value::deserialize_from_hex(&hex, None).unwrap(), value);
+            assert_eq!(
+                &Value::deserialize_from_hex(&format!("0x{}", hex), None).unwrap(),
+                value
+            );
+            assert_eq!(
+                &Value::deserialize_from_hex(&hex, Some(&TypeSignature::type_of(value))).unwrap(),
+                value
+            );
+            assert!(Value::deserialize_from_hex(&hex, Some(&BoolType)).is_err());
+
+            // trailing bytes
+            assert!(Value::deserialize_from_hex(&format!("{}00", hex), None).is_err());
+        }
+
+        assert!(Value::deserialize_from_hex("0x0", None).is_err());
+        assert!(Value::deserialize_from_hex("0xzz", None).is_err());
+
+        // longer than any serialized value, even if the value it starts with is valid
+        let too_long = format!(
+            "{}{}",
+            Value::Int(1).serialize_to_hex(),
+            "0".repeat(BOUND_VALUE_SERIALIZATION_HEX as usize)
+        );
+        assert_eq!(
+            Value::deserialize_from_hex(&too_long, None).unwrap_err(),
+            SerializationError::DeserializationError("Hex string too long".to_string())
+        );
+    }
+
+    #[test]
+    fn try_deser_short_list() {
+        // a list that claims to have u32::MAX - 1 items, but has one
+        let buff = vec![11, 255, 255, 255, 254, 3];
+        assert!(Value::try_deserialize_bytes_untyped(&buff).is_err());
+
+        // a list that claims to have 2 items, and has them
+        let buff = vec![11, 0, 0, 0, 2, 3, 4];
+        assert_eq!(
+            Value::try_deserialize_bytes_untyped(&buff).unwrap(),
+            Value::list_from(vec![Value::Bool(true), Value::Bool(false)]).unwrap()
+        );
+    }
+
     #[test]
     fn test_principals() {
         let issuer =
@@ -26,6 +26,7 @@ use vm::costs::{cost_functions, CostOverflowingMath};
 use vm::errors::{CheckErrors, Error as VMError, IncomparableError, RuntimeErrorType};
 use vm::representations::{
     ClarityName, ContractName, SymbolicExpression, SymbolicExpressionType, TraitDefinition,
+    MAX_STRING_LEN,
 };
 use vm::types::{
     CharType, QualifiedContractIdentifier, SequenceData, SequencedValue, StandardPrincipalData,
@@ -1129,6 +1130,45 @@ impl TypeSignature {
             TraitReferenceType(_) => Some(1),
         }
     }
+
+    /// Returns the size of the largest consensus serialization of a value of this type, or
+    /// None if that does not fit in a u32
+    pub fn max_serialized_size(&self) -> Option<u32> {
+        // every serialized value starts with a 1-byte type prefix
+        let size = match self {
+            // NoType values are never serialized -- `none` and `(ok 1)` only have NoType parts
+            NoType => return Some(0),
+            IntType | UIntType => 16,
+            BoolType => 0,
+            // version + hash160, then a length-prefixed contract name
+            PrincipalType | TraitReferenceType(_) => 21 + 1 + MAX_STRING_LEN as u32,
+            TupleType(tuple_sig) => {
+                let mut size: u32 = 4;
+                for (name, type_signature) in tuple_sig.get_type_map().iter() {
+                    size = size
+                        .checked_add(1 + name.len() as u32)?
+                        .checked_add(type_signature.max_serialized_size()?)?;
+                }
+                size
+            }
+            SequenceType(SequenceSubtype::BufferType(len))
+            | SequenceType(SequenceSubtype::StringType(StringSubtype::ASCII(len))) => {
+                4 + u32::from(len)
+            }
+            SequenceType(SequenceSubtype::StringType(StringSubtype::UTF8(len))) => {
+                4u32.checked_add(u32::from(len).checked_mul(4)?)?
+            }
+            SequenceType(SequenceSubtype::ListType(list_type)) => 4u32.checked_add(
+                list_type
+                    .get_list_item_type()
+                    .max_serialized_size()?
+                    .checked_mul(list_type.get_max_len())?,
+            )?,
+            OptionalType(t) => t.max_serialized_size()?,
+            ResponseType(v) => cmp::max(v.0.max_serialized_size()?, v.1.max_serialized_size()?),
+        };
+        size.checked_add(1)
+    }
 }
 
 impl ListTypeData {
@@ -0,0 +1,325 @@
+use stacks::burnchains::Burnchain;
+use stacks::chainstate::stacks::db::StacksChainState;
+use stacks::vm::costs::ExecutionCost;
+use stacks::vm::database::marf::ROCKSDB_VALUE_STORE_DIR;
+
+use crate::config::{EventKeyType, EventObserverConfig};
+use crate::{helium, neon, Config, ConfigFile};
+
+/// Burnchain modes driven by the helium run loop.
+const HELIUM_MODES: &[&str] = &["helium", "mocknet", "mock"];
+/// Burnchain modes driven by the neon run loop.
+const NEON_MODES: &[&str] = &["neon", "argon", "krypton", "xenon"];
+
+/// Programmatic construction of a stacks-node, for embedding the node in
+/// test harnesses and custom deployments without going through a TOML file.
+///
+/// ```ignore
+/// let mut node = NodeBuilder::mocknet()
+///     .with_working_dir("/tmp/my-node")
+///     .with_rpc_bind("127.0.0.1:20443")
+///     .with_event_observer("127.0.0.1:3700", &["*"])
+///     .build()?;
+/// node.start(0)?;
+/// ```
+pub struct NodeBuilder {
+    config: Config,
+    config_path: Option<String>,
+    burnchain: Option<Burnchain>,
+}
+
+/// A configured node, ready to be started on the run loop matching its
+/// burnchain mode.
+pub enum StacksNode {
+    Helium(helium::RunLoop),
+    Neon(neon::RunLoop, Option<Burnchain>),
+}
+
+impl NodeBuilder {
+    pub fn from_config(config: Config) -> NodeBuilder {
+        NodeBuilder {
+            config,
+            config_path: None,
+            burnchain: None,
+        }
+    }
+
+    pub fn from_config_file(config_file: ConfigFile) -> NodeBuilder {
+        NodeBuilder::from_config(Config::from_config_file(config_file))
+    }
+
+    pub fn mocknet() -> NodeBuilder {
+        NodeBuilder::from_config_file(ConfigFile::mocknet())
+    }
+
+    pub fn helium() -> NodeBuilder {
+        NodeBuilder::from_config_file(ConfigFile::helium())
+    }
+
+    pub fn neon() -> NodeBuilder {
+        NodeBuilder::from_config_file(ConfigFile::neon())
+    }
+
+    /// Select the burnchain backend (`mocknet`, `mock`, `helium`, `neon`, `argon`, `krypton`, `xenon`).
+    pub fn with_burnchain_mode(mut self, mode: &str) -> NodeBuilder {
+        self.config.burnchain.mode = mode.to_string();
+        self
+    }
+
+    /// Point the burnchain controller at a bitcoind RPC endpoint.
+    pub fn with_bitcoind(
+        mut self,
+        peer_host: &str,
+        rpc_port: u16,
+        username: Option<String>,
+        password: Option<String>,
+    ) -> NodeBuilder {
+        self.config.burnchain.peer_host = peer_host.to_string();
+        self.config.burnchain.rpc_port = rpc_port;
+        self.config.burnchain.username = username;
+        self.config.burnchain.password = password;
+        self
+    }
+
+    /// Use an explicit burnchain configuration in the neon run loop, instead of the
+    /// one derived from the burnchain mode.
+    pub fn with_burnchain(mut self, burnchain: Burnchain) -> NodeBuilder {
+        self.burnchain = Some(burnchain);
+        self
+    }
+
+    /// Set the working directory under which the burnchain, chainstate, and peer
+    /// databases are stored.
+    pub fn with_working_dir(mut self, working_dir: &str) -> NodeBuilder {
+        self.config.node.working_dir = working_dir.to_string();
+        self.config.burnchain.spv_headers_path = self.config.node.get_default_spv_headers_path();
+        self
+    }
+
+    pub fn with_spv_headers_path(mut self, spv_headers_path: &str) -> NodeBuilder {
+        self.config.burnchain.spv_headers_path = spv_headers_path.to_string();
+        self
+    }
+
+    pub fn with_rpc_bind(mut self, rpc_bind: &str) -> NodeBuilder {
+        self.config.node.rpc_bind = rpc_bind.to_string();
+        self.config.node.data_url = format!("http://{}", rpc_bind);
+        self
+    }
+
+    pub fn with_p2p_bind(mut self, p2p_bind: &str) -> NodeBuilder {
+        self.config.node.p2p_bind = p2p_bind.to_string();
+        self
+    }
+
+    /// Set the p2p address advertised to peers.
+    pub fn with_p2p_address(mut self, p2p_address: &str) -> NodeBuilder {
+        self.config.node.p2p_address = p2p_address.to_string();
+        self
+    }
+
+    pub fn with_prometheus_bind(mut self, prometheus_bind: &str) -> NodeBuilder {
+        self.config.node.prometheus_bind = Some(prometheus_bind.to_string());
+        self
+    }
+
+    /// Bootstrap from a peer given as `pubkey@host:port`.
+    pub fn with_bootstrap_node(mut self, bootstrap_node: &str) -> NodeBuilder {
+        self.config
+            .node
+            .set_bootstrap_node(Some(bootstrap_node.to_string()));
+        self
+    }
+
+    /// Enable mining, using the given seed to derive the miner's keychain.
+    pub fn with_miner(mut self, seed: Vec<u8>) -> NodeBuilder {
+        self.config.node.miner = true;
+        self.config.node.seed = seed;
+        self
+    }
+
+    pub fn with_initial_balance(mut self, address: &str, amount: u64) -> NodeBuilder {
+        self.config.add_initial_balance(address.to_string(), amount);
+        self
+    }
+
+    /// Register an event observer. `events_keys` use the same syntax as the
+    /// `events_keys` field of an `[[events_observer]]` config section.
+    pub fn with_event_observer(
+        mut self,
+        endpoint: &str,
+        events_keys: &[&str],
+    ) -> Result<NodeBuilder, String> {
+        let mut keys = vec![];
+        for raw_key in events_keys.iter() {
+            let key = EventKeyType::from_string(raw_key)
+                .ok_or_else(|| format!("Invalid event observer key '{}'", raw_key))?;
+            keys.push(key);
+        }
+        self.config.events_observers.push(EventObserverConfig {
+            endpoint: endpoint.to_string(),
+            events_keys: keys,
+            max_retries: None,
+        });
+        Ok(self)
+    }
+
+    /// The file the config was loaded from.  A neon node reloads its log level, event
+    /// observers and mempool policy from it on SIGHUP.
+    pub fn with_config_path(mut self, config_path: &str) -> NodeBuilder {
+        self.config_path = Some(config_path.to_string());
+        self
+    }
+
+    pub fn with_block_limit(mut self, block_limit: ExecutionCost) -> NodeBuilder {
+        self.config.block_limit = block_limit;
+        self
+    }
+
+    /// Get at the underlying config, for settings that have no dedicated setter.
+    pub fn config_mut(&mut self) -> &mut Config {
+        &mut self.config
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Instantiate the run loop for the configured burnchain mode.
+    pub fn build(self) -> Result<StacksNode, String> {
+        prepare_clarity_value_store(&self.config)?;
+
+        let mode = self.config.burnchain.mode.as_str();
+        if HELIUM_MODES.contains(&mode) {
+            Ok(StacksNode::Helium(helium::RunLoop::new(self.config)))
+        } else if NEON_MODES.contains(&mode) {
+            let mut run_loop = neon::RunLoop::new(self.config);
+            if let Some(ref config_path) = self.config_path {
+                run_loop.set_config_path(config_path);
+            }
+            Ok(StacksNode::Neon(run_loop, self.burnchain))
+        } else {
+            Err(format!("Burnchain mode '{}' not supported", mode))
+        }
+    }
+}
+
+/// Make sure the Clarity state is on the value store `node.clarity_value_store` names, copying
+/// its values over from the side store the first time a node is started with `rocksdb`.
+fn prepare_clarity_value_store(config: &Config) -> Result<(), String> {
+    let clarity_state_dir = StacksChainState::get_clarity_state_index_root(
+        false,
+        config.node.chain_id,
+        &config.get_chainstate_path(),
+    );
+    let on_rocksdb = clarity_state_dir.join(ROCKSDB_VALUE_STORE_DIR).exists();
+    match config.node.clarity_value_store.as_str() {
+        "sqlite" if on_rocksdb => Err(format!(
+            "The Clarity state in {} is on a RocksDB value store, which cannot be moved back: set node.clarity_value_store = \"rocksdb\"",
+            clarity_state_dir.display()
+        )),
+        "sqlite" => Ok(()),
+        "rocksdb" => import_clarity_values(&clarity_state_dir.to_string_lossy()),
+        other => Err(format!("Clarity value store '{}' not supported", other)),
+    }
+}
+
+#[cfg(feature = "rocksdb_store")]
+fn import_clarity_values(clarity_state_dir: &str) -> Result<(), String> {
+    let copied = stacks::vm::database::rocksdb_store::import_values(clarity_state_dir)
+        .map_err(|e| format!("Failed to set up the RocksDB value store: {}", e))?;
+    if copied > 0 {
+        info!(
+            "Copied {} Clarity values from the side store into RocksDB",
+            copied
+        );
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "rocksdb_store"))]
+fn import_clarity_values(_clarity_state_dir: &str) -> Result<(), String> {
+    Err("node.clarity_value_store = \"rocksdb\" needs a stacks-node built with the `rocksdb_store` feature".to_string())
+}
+
+impl StacksNode {
+    /// Run the node. Blocks until `expected_num_rounds` rounds have been processed,
+    /// or indefinitely if it is 0. A neon node may only be started once.
+    pub fn start(&mut self, expected_num_rounds: u64) -> Result<(), String> {
+        match self {
+            StacksNode::Helium(ref mut run_loop) => run_loop
+                .start(expected_num_rounds)
+                .map_err(|e| format!("Helium runloop exited: {}", e)),
+            StacksNode::Neon(ref mut run_loop, ref mut burnchain_opt) => {
+                run_loop.start(expected_num_rounds, burnchain_opt.take());
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_by_mode() {
+        let node = NodeBuilder::mocknet()
+            .with_burnchain_mode("xenon")
+            .build()
+            .unwrap();
+        assert!(matches!(node, StacksNode::Neon(..)));
+
+        assert!(NodeBuilder::mocknet()
+            .with_burnchain_mode("unicorn")
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_builder_paths_and_observers() {
+        let builder = NodeBuilder::mocknet()
+            .with_working_dir("/tmp/stacks-node-builder-test")
+            .with_rpc_bind("127.0.0.1:30443")
+            .with_event_observer("127.0.0.1:3700", &["*", "stx"])
+            .unwrap();
+
+        let config = builder.config();
+        assert_eq!(
+            config.get_chainstate_path(),
+            "/tmp/stacks-node-builder-test/chainstate/"
+        );
+        assert_eq!(config.node.data_url, "http://127.0.0.1:30443");
+        assert_eq!(config.events_observers.len(), 1);
+        assert_eq!(config.events_observers[0].events_keys.len(), 2);
+
+        assert!(NodeBuilder::mocknet()
+            .with_event_observer("127.0.0.1:3700", &["not-a-key"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_event_observer_filters() {
+        let builder = NodeBuilder::mocknet()
+            .with_event_observer(
+                "127.0.0.1:3700",
+                &[
+                    "ST000000000000000000002AMW42H.bns",
+                    "ft_transfer_event",
+                    "ST000000000000000000002AMW42H.bns::print",
+                ],
+            )
+            .unwrap();
+
+        let keys = &builder.config().events_observers[0].events_keys;
+        assert!(matches!(keys[0], EventKeyType::ContractEvents(_)));
+        assert!(
+            matches!(keys[1], EventKeyType::EventType(ref name) if name == "ft_transfer_event")
+        );
+        assert!(matches!(keys[2], EventKeyType::SmartContractEvent(_)));
+
+        assert!(NodeBuilder::mocknet()
+            .with_event_observer("127.0.0.1:3700", &["ft_teleport_event"])
+            .is_err());
+    }
+}
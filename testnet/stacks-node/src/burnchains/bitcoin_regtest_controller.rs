@@ -1,5 +1,7 @@
 use async_std::io::ReadExt;
+use std::cmp;
 use std::io::Cursor;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Instant;
 
 use async_h1::client;
@@ -10,6 +12,7 @@ use http_types::{Method, Request, Url};
 use serde::Serialize;
 use serde_json::value::RawValue;
 
+use super::super::config::BitcoindEndpoint;
 use super::super::operations::BurnchainOpSigner;
 use super::super::Config;
 use super::{BurnchainController, BurnchainTip, Error as BurnchainControllerError};
@@ -30,7 +33,7 @@ use stacks::burnchains::PublicKey;
 use stacks::chainstate::burn::db::sortdb::SortitionDB;
 use stacks::chainstate::burn::operations::{
     leader_block_commit::OUTPUTS_PER_COMMIT, BlockstackOperationType, LeaderBlockCommitOp,
-    LeaderKeyRegisterOp, UserBurnSupportOp,
+    LeaderKeyRegisterOp, PreStxOp, TransferStxOp, UserBurnSupportOp,
 };
 use stacks::chainstate::coordinator::comm::CoordinatorChannels;
 use stacks::deps::bitcoin::blockdata::opcodes;
@@ -56,11 +59,48 @@ pub struct BitcoinRegtestController {
     burnchain_config: Option<Burnchain>,
     last_utxos: Vec<UTXO>,
     last_tx_len: u64,
-    min_relay_fee: u64, // satoshis/byte
+    min_relay_fee: u64, // satoshis/kilobyte
+    ongoing_block_commit: Option<OngoingBlockCommit>,
+}
+
+/// A block-commit that has been broadcast but not yet mined.  Until its target burn block
+/// arrives, it can be replaced with a higher-fee copy that spends the same UTXOs.
+struct OngoingBlockCommit {
+    payload: LeaderBlockCommitOp,
+    utxos: Vec<UTXO>,
+    txid: Sha256dHash,
+    /// transaction fee paid, in satoshis
+    fee: u64,
+    /// virtual size of the transaction, in bytes
+    vsize: u64,
+    /// burnchain height when the transaction was sent; it targets the next block
+    burn_height: u64,
 }
 
 const DUST_UTXO_LIMIT: u64 = 5500;
 
+/// Compute the fee for a `vsize`-byte replacement of a transaction that paid `old_fee`, such
+/// that the replacement pays at least `target_rate` (satoshis/kilobyte) and increases the
+/// absolute fee by at least `incremental_rate` times its own size, as BIP125 requires.
+/// Returns None if the replacement would cost more than `fee_cap`.
+fn get_rbf_fee(
+    old_fee: u64,
+    vsize: u64,
+    target_rate: u64,
+    incremental_rate: u64,
+    fee_cap: u64,
+) -> Option<u64> {
+    let min_fee = old_fee + (incremental_rate * vsize) / 1000;
+    let new_fee = cmp::max(min_fee, (target_rate * vsize) / 1000);
+    if new_fee > fee_cap {
+        if min_fee > fee_cap {
+            return None;
+        }
+        return Some(fee_cap);
+    }
+    Some(new_fee)
+}
+
 impl BitcoinRegtestController {
     pub fn new(config: Config, coordinator_channel: Option<CoordinatorChannels>) -> Self {
         BitcoinRegtestController::with_burnchain(config, coordinator_channel, None)
@@ -100,9 +140,16 @@ impl BitcoinRegtestController {
                 spv_headers_path: burnchain_config.spv_headers_path,
                 first_block: burnchain_config.first_block,
                 magic_bytes: burnchain_config.magic_bytes,
+                fallback_peers: burnchain_config
+                    .endpoints
+                    .iter()
+                    .map(|endpoint| (endpoint.peer_host.clone(), endpoint.peer_port))
+                    .collect(),
             }
         };
 
+        BitcoinRPCRequest::check_endpoints(&config);
+
         Self {
             use_coordinator: coordinator_channel,
             config,
@@ -113,7 +160,8 @@ impl BitcoinRegtestController {
             burnchain_config,
             last_utxos: vec![],
             last_tx_len: 0,
-            min_relay_fee: 1024, // TODO: learn from bitcoind
+            min_relay_fee: 1024, // updated from bitcoind's mempool when bumping fees
+            ongoing_block_commit: None,
         }
     }
 
@@ -133,6 +181,11 @@ impl BitcoinRegtestController {
                 spv_headers_path: burnchain_config.spv_headers_path,
                 first_block: burnchain_config.first_block,
                 magic_bytes: burnchain_config.magic_bytes,
+                fallback_peers: burnchain_config
+                    .endpoints
+                    .iter()
+                    .map(|endpoint| (endpoint.peer_host.clone(), endpoint.peer_port))
+                    .collect(),
             }
         };
 
@@ -146,7 +199,8 @@ impl BitcoinRegtestController {
             burnchain_config: None,
             last_utxos: vec![],
             last_tx_len: 0,
-            min_relay_fee: 1024, // TODO: learn from bitcoind
+            min_relay_fee: 1024, // updated from bitcoind's mempool when bumping fees
+            ongoing_block_commit: None,
         }
     }
 
@@ -337,9 +391,9 @@ impl BitcoinRegtestController {
         Ok((burnchain_tip, burnchain_height))
     }
 
-    #[cfg(test)]
-    pub fn get_all_utxos(&self, public_key: &Secp256k1PublicKey) -> Vec<UTXO> {
-        // Configure UTXO filter
+    /// Addresses whose UTXOs the miner may spend.  In segwit mode, this includes the native
+    /// p2wpkh address of the miner's key in addition to its p2pkh address.
+    fn get_miner_addresses(&self, public_key: &Secp256k1PublicKey) -> Vec<String> {
         let pkh = Hash160::from_data(&public_key.to_bytes())
             .to_bytes()
             .to_vec();
@@ -347,7 +401,21 @@ impl BitcoinRegtestController {
         let address =
             BitcoinAddress::from_bytes(network_id, BitcoinAddressType::PublicKeyHash, &pkh)
                 .expect("Public key incorrect");
-        let filter_addresses = vec![address.to_b58()];
+        let mut addresses = vec![address.to_b58()];
+
+        if self.config.burnchain.segwit {
+            let segwit_address =
+                BitcoinAddress::from_bytes(network_id, BitcoinAddressType::WitnessPubkeyHash, &pkh)
+                    .expect("Public key incorrect");
+            addresses.push(segwit_address.to_string());
+        }
+        addresses
+    }
+
+    #[cfg(test)]
+    pub fn get_all_utxos(&self, public_key: &Secp256k1PublicKey) -> Vec<UTXO> {
+        // Configure UTXO filter
+        let filter_addresses = self.get_miner_addresses(public_key);
         let _result = BitcoinRPCRequest::import_public_key(&self.config, &public_key);
 
         sleep_ms(1000);
@@ -427,14 +495,7 @@ impl BitcoinRegtestController {
         amount_required: u64,
     ) -> Option<Vec<UTXO>> {
         // Configure UTXO filter
-        let pkh = Hash160::from_data(&public_key.to_bytes())
-            .to_bytes()
-            .to_vec();
-        let (_, network_id) = self.config.burnchain.get_bitcoin_network();
-        let address =
-            BitcoinAddress::from_bytes(network_id, BitcoinAddressType::PublicKeyHash, &pkh)
-                .expect("Public key incorrect");
-        let filter_addresses = vec![address.to_b58()];
+        let filter_addresses = self.get_miner_addresses(public_key);
 
         let mut utxos = loop {
             let result = BitcoinRPCRequest::list_unspent(
@@ -539,7 +600,8 @@ impl BitcoinRegtestController {
 
         tx.output.push(identifier_output);
 
-        self.finalize_tx(&mut tx, DUST_UTXO_LIMIT, utxos, signer, attempt)?;
+        let tx_fee = self.get_tx_fee(attempt);
+        self.finalize_tx(&mut tx, DUST_UTXO_LIMIT, utxos, signer, tx_fee)?;
 
         increment_btc_ops_sent_counter();
 
@@ -559,8 +621,31 @@ impl BitcoinRegtestController {
     ) -> Option<Transaction> {
         let public_key = signer.get_public_key();
 
-        let (mut tx, utxos) = self.prepare_tx(&public_key, payload.burn_fee, attempt)?;
+        let (tx, utxos) = self.prepare_tx(&public_key, payload.burn_fee, attempt)?;
+        let tx_fee = self.get_tx_fee(attempt);
+
+        let tx = self.finalize_leader_block_commit_tx(tx, payload, utxos, signer, tx_fee)?;
+
+        increment_btc_ops_sent_counter();
+
+        info!(
+            "Miner node: submitting leader_block_commit op - {}",
+            public_key.to_hex()
+        );
+
+        Some(tx)
+    }
 
+    /// Fill in and sign a block-commit transaction that pays `tx_fee` from `utxos`, and start
+    /// tracking it so its fee can be bumped while it is unconfirmed.
+    fn finalize_leader_block_commit_tx(
+        &mut self,
+        mut tx: Transaction,
+        payload: LeaderBlockCommitOp,
+        utxos: Vec<UTXO>,
+        signer: &mut BurnchainOpSigner,
+        tx_fee: u64,
+    ) -> Option<Transaction> {
         // Serialize the payload
         let op_bytes = {
             let mut buffer = vec![];
@@ -601,16 +686,137 @@ impl BitcoinRegtestController {
                 .push(commit_to.to_bitcoin_tx_out(value_per_transfer));
         }
 
-        self.finalize_tx(&mut tx, payload.burn_fee, utxos, signer, attempt)?;
+        self.finalize_tx(&mut tx, payload.burn_fee, utxos.clone(), signer, tx_fee)?;
+
+        self.ongoing_block_commit = match BitcoinRPCRequest::get_block_count(&self.config) {
+            Ok(burn_height) => Some(OngoingBlockCommit {
+                payload,
+                utxos,
+                txid: tx.txid(),
+                fee: tx_fee,
+                vsize: self.last_tx_len,
+                burn_height,
+            }),
+            Err(e) => {
+                warn!(
+                    "Bitcoin RPC failure: could not get block count; will not bump fees: {:?}",
+                    e
+                );
+                None
+            }
+        };
 
-        increment_btc_ops_sent_counter();
+        Some(tx)
+    }
+
+    /// Replace the ongoing block-commit with a higher-fee copy if its target burn block has not
+    /// arrived yet and its fee rate has fallen behind what bitcoind's mempool demands.  The fee
+    /// is never raised above `burnchain.max_rbf_fee`.
+    /// Returns true if a replacement was broadcast.
+    pub fn bump_ongoing_block_commit(&mut self, signer: &mut BurnchainOpSigner) -> bool {
+        let ongoing = match self.ongoing_block_commit.take() {
+            Some(ongoing) => ongoing,
+            None => {
+                return false;
+            }
+        };
+
+        match BitcoinRPCRequest::get_block_count(&self.config) {
+            Ok(burn_height) => {
+                if burn_height > ongoing.burn_height {
+                    // the target block has been mined, with or without our commit
+                    debug!(
+                        "Block commit {} was sent at burn height {}; now at {}. Not bumping.",
+                        &ongoing.txid, ongoing.burn_height, burn_height
+                    );
+                    return false;
+                }
+            }
+            Err(e) => {
+                warn!("Bitcoin RPC failure: could not get block count: {:?}", e);
+                self.ongoing_block_commit = Some(ongoing);
+                return false;
+            }
+        }
+
+        let target_rate = match BitcoinRPCRequest::get_mempool_fee_rates(&self.config) {
+            Ok((min_relay_fee, target_rate)) => {
+                self.min_relay_fee = cmp::max(self.min_relay_fee, min_relay_fee);
+                cmp::max(target_rate, self.min_relay_fee)
+            }
+            Err(e) => {
+                warn!(
+                    "Bitcoin RPC failure: could not get mempool fee rates: {:?}",
+                    e
+                );
+                self.ongoing_block_commit = Some(ongoing);
+                return false;
+            }
+        };
+
+        let current_rate = (ongoing.fee * 1000) / cmp::max(ongoing.vsize, 1);
+        if current_rate >= target_rate {
+            debug!(
+                "Block commit {} pays {} sat/kB; mempool wants {} sat/kB. Not bumping.",
+                &ongoing.txid, current_rate, target_rate
+            );
+            self.ongoing_block_commit = Some(ongoing);
+            return false;
+        }
+
+        // the replacement spends the same UTXOs to the same outputs, so it is no bigger than
+        //  the original
+        let new_fee = match get_rbf_fee(
+            ongoing.fee,
+            ongoing.vsize,
+            target_rate,
+            self.min_relay_fee,
+            self.config.burnchain.max_rbf_fee,
+        ) {
+            Some(fee) => fee,
+            None => {
+                warn!(
+                    "Cannot bump fee of block commit {} (paying {}): would exceed max RBF fee {}",
+                    &ongoing.txid, ongoing.fee, self.config.burnchain.max_rbf_fee
+                );
+                self.ongoing_block_commit = Some(ongoing);
+                return false;
+            }
+        };
 
         info!(
-            "Miner node: submitting leader_block_commit op - {}",
-            public_key.to_hex()
+            "Miner node: bumping block commit {} fee from {} to {} ({} sat/kB wanted)",
+            &ongoing.txid, ongoing.fee, new_fee, target_rate
         );
 
-        Some(tx)
+        let tx = Transaction {
+            input: vec![],
+            output: vec![],
+            version: 1,
+            lock_time: 0,
+        };
+        let tx = match self.finalize_leader_block_commit_tx(
+            tx,
+            ongoing.payload.clone(),
+            ongoing.utxos.clone(),
+            signer,
+            new_fee,
+        ) {
+            Some(tx) => tx,
+            None => {
+                self.ongoing_block_commit = Some(ongoing);
+                return false;
+            }
+        };
+
+        if !self.send_transaction(SerializedTx::new(tx)) {
+            // keep tracking the transaction that bitcoind already has
+            self.ongoing_block_commit = Some(ongoing);
+            return false;
+        }
+
+        increment_btc_ops_sent_counter();
+        true
     }
 
     fn prepare_tx(
@@ -650,22 +856,29 @@ impl BitcoinRegtestController {
         Some((transaction, utxos))
     }
 
+    /// Transaction fee for the given attempt at sending an operation.  Each subsequent attempt
+    /// replaces the last one, so it must pay for its size at the minimum relay fee again.
+    fn get_tx_fee(&self, attempt: u64) -> u64 {
+        let tx_fee = self.config.burnchain.burnchain_op_tx_fee
+            + ((attempt.saturating_sub(1) * self.last_tx_len * self.min_relay_fee) / 1000);
+        cmp::max(
+            cmp::min(tx_fee, self.config.burnchain.max_rbf_fee),
+            self.config.burnchain.burnchain_op_tx_fee,
+        )
+    }
+
     fn finalize_tx(
         &mut self,
         tx: &mut Transaction,
         total_spent: u64,
         mut utxos: Vec<UTXO>,
         signer: &mut BurnchainOpSigner,
-        attempt: u64,
+        tx_fee: u64,
     ) -> Option<()> {
         // spend UTXOs in decreasing order
         utxos.sort_by(|u1, u2| u1.amount.cmp(&u2.amount));
         utxos.reverse();
 
-        // RBF
-        let tx_fee = self.config.burnchain.burnchain_op_tx_fee
-            + ((attempt.saturating_sub(1) * self.last_tx_len * self.min_relay_fee) / 1000);
-
         let public_key = signer.get_public_key();
         let mut total_consumed = 0;
 
@@ -691,15 +904,23 @@ impl BitcoinRegtestController {
             return None;
         }
         let value = total_consumed - total_spent - tx_fee;
-        debug!("Payments value: {:?}, total_consumed: {:?}, total_spent: {:?}, tx_fee: {:?}, attempt: {:?}", value, total_consumed, total_spent, tx_fee, attempt);
+        debug!(
+            "Payments value: {:?}, total_consumed: {:?}, total_spent: {:?}, tx_fee: {:?}",
+            value, total_consumed, total_spent, tx_fee
+        );
         if value >= DUST_UTXO_LIMIT {
-            let change_output = BitcoinAddress::to_p2pkh_tx_out(&change_address_hash, value);
+            let change_output = if self.config.burnchain.segwit {
+                BitcoinAddress::to_p2wpkh_tx_out(&change_address_hash, value)
+            } else {
+                BitcoinAddress::to_p2pkh_tx_out(&change_address_hash, value)
+            };
             tx.output.push(change_output);
         } else {
             debug!("Not enough change to clear dust limit. Not adding change address.");
         }
 
-        for (i, utxo) in utxos_consumed.into_iter().enumerate() {
+        // all inputs must be present before signing, since each signature commits to them
+        for utxo in utxos_consumed.iter() {
             let input = TxIn {
                 previous_output: OutPoint {
                     txid: utxo.txid,
@@ -710,10 +931,20 @@ impl BitcoinRegtestController {
                 witness: vec![],
             };
             tx.input.push(input);
+        }
 
+        for (i, utxo) in utxos_consumed.into_iter().enumerate() {
             let script_pub_key = utxo.script_pub_key.clone();
             let sig_hash_all = 0x01;
-            let sig_hash = tx.signature_hash(i, &script_pub_key, sig_hash_all);
+            let is_segwit = script_pub_key.is_v0_p2wpkh();
+            let sig_hash = if is_segwit {
+                // the script code of a p2wpkh input is the p2pkh script of the same key
+                let script_code =
+                    BitcoinAddress::to_p2pkh_tx_out(&change_address_hash, 0).script_pubkey;
+                tx.segwit_signature_hash(i, &script_code, utxo.amount, sig_hash_all)
+            } else {
+                tx.signature_hash(i, &script_pub_key, sig_hash_all)
+            };
 
             let sig1_der = {
                 let message = signer
@@ -726,19 +957,27 @@ impl BitcoinRegtestController {
                     .serialize_der()
             };
 
-            tx.input[i].script_sig = Builder::new()
-                .push_slice(&[&*sig1_der, &[sig_hash_all as u8][..]].concat())
-                .push_slice(&public_key.to_bytes())
-                .into_script();
+            if is_segwit {
+                tx.input[i].witness = vec![
+                    [&*sig1_der, &[sig_hash_all as u8][..]].concat(),
+                    public_key.to_bytes(),
+                ];
+            } else {
+                tx.input[i].script_sig = Builder::new()
+                    .push_slice(&[&*sig1_der, &[sig_hash_all as u8][..]].concat())
+                    .push_slice(&public_key.to_bytes())
+                    .into_script();
+            }
         }
 
         signer.dispose();
 
-        // remember how long the transaction is, in case we need to RBF
+        // remember how long the transaction is, in case we need to RBF.  Witness data is
+        // discounted, so this is the virtual size.
         let tx_bytes = SerializedTx::new(tx.clone());
         debug!("Send transaction: {:?}", tx_bytes.to_hex());
 
-        self.last_tx_len = tx_bytes.bytes.len() as u64;
+        self.last_tx_len = (tx.get_weight() + 3) / 4;
 
         Some(())
     }
@@ -752,6 +991,95 @@ impl BitcoinRegtestController {
         unimplemented!()
     }
 
+    fn build_pre_stx_tx(
+        &mut self,
+        payload: PreStxOp,
+        signer: &mut BurnchainOpSigner,
+        attempt: u64,
+    ) -> Option<Transaction> {
+        let public_key = signer.get_public_key();
+
+        let (mut tx, utxos) = self.prepare_tx(&public_key, DUST_UTXO_LIMIT, attempt)?;
+
+        // Serialize the payload
+        let op_bytes = {
+            let mut buffer = vec![];
+            let mut magic_bytes = self.config.burnchain.magic_bytes.as_bytes().to_vec();
+            buffer.append(&mut magic_bytes);
+            payload
+                .consensus_serialize(&mut buffer)
+                .expect("FATAL: invalid operation");
+            buffer
+        };
+
+        let consensus_output = TxOut {
+            value: 0,
+            script_pubkey: Builder::new()
+                .push_opcode(opcodes::All::OP_RETURN)
+                .push_slice(&op_bytes)
+                .into_script(),
+        };
+
+        tx.output = vec![consensus_output];
+        tx.output
+            .push(payload.output.to_bitcoin_tx_out(DUST_UTXO_LIMIT));
+
+        let tx_fee = self.get_tx_fee(attempt);
+        self.finalize_tx(&mut tx, DUST_UTXO_LIMIT, utxos, signer, tx_fee)?;
+
+        increment_btc_ops_sent_counter();
+
+        info!("Miner node: submitting pre-STX op for {}", &payload.output);
+
+        Some(tx)
+    }
+
+    fn build_transfer_stx_tx(
+        &mut self,
+        payload: TransferStxOp,
+        signer: &mut BurnchainOpSigner,
+        attempt: u64,
+    ) -> Option<Transaction> {
+        let public_key = signer.get_public_key();
+
+        let (mut tx, utxos) = self.prepare_tx(&public_key, DUST_UTXO_LIMIT, attempt)?;
+
+        // Serialize the payload
+        let op_bytes = {
+            let mut buffer = vec![];
+            let mut magic_bytes = self.config.burnchain.magic_bytes.as_bytes().to_vec();
+            buffer.append(&mut magic_bytes);
+            payload
+                .consensus_serialize(&mut buffer)
+                .expect("FATAL: invalid operation");
+            buffer
+        };
+
+        let consensus_output = TxOut {
+            value: 0,
+            script_pubkey: Builder::new()
+                .push_opcode(opcodes::All::OP_RETURN)
+                .push_slice(&op_bytes)
+                .into_script(),
+        };
+
+        tx.output = vec![consensus_output];
+        tx.output
+            .push(payload.recipient.to_bitcoin_tx_out(DUST_UTXO_LIMIT));
+
+        let tx_fee = self.get_tx_fee(attempt);
+        self.finalize_tx(&mut tx, DUST_UTXO_LIMIT, utxos, signer, tx_fee)?;
+
+        increment_btc_ops_sent_counter();
+
+        info!(
+            "Miner node: submitting transfer STX op of {} uSTX to {}",
+            payload.transfered_ustx, &payload.recipient
+        );
+
+        Some(tx)
+    }
+
     fn send_transaction(&self, transaction: SerializedTx) -> bool {
         let result = BitcoinRPCRequest::send_raw_transaction(&self.config, transaction.to_hex());
         match result {
@@ -766,10 +1094,36 @@ impl BitcoinRegtestController {
         }
     }
 
+    /// Block until bitcoind has a block at `height`, or until `timeout_secs` elapse.  Returns
+    /// false if bitcoind could not be long-polled, in which case the caller should fall back to
+    /// polling on a timer.
+    pub fn wait_for_burn_block(config: &Config, height: u64, timeout_secs: u64) -> bool {
+        match BitcoinRPCRequest::wait_for_block_height(config, height, timeout_secs * 1000) {
+            Ok(tip_height) => {
+                debug!("Bitcoind chain tip is at height {}", tip_height);
+                true
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to long-poll bitcoind for block {}: {:?}",
+                    height, &e
+                );
+                false
+            }
+        }
+    }
+
     /// wait until the ChainsCoordinator has processed sortitions up to the
     ///   canonical chain tip, or has processed up to height_to_wait
     pub fn wait_for_sortitions(&self, height_to_wait: Option<u64>) -> BurnchainTip {
         loop {
+            // read the counter before checking the DBs, so that a sortition processed in
+            //  between still wakes us up below.
+            let sortitions_processed = self
+                .use_coordinator
+                .as_ref()
+                .map(|coord_comms| coord_comms.get_sortitions_processed());
+
             let canonical_burnchain_tip = self
                 .burnchain_db
                 .as_ref()
@@ -806,8 +1160,16 @@ impl BitcoinRegtestController {
                 }
             }
 
-            // yield some time
-            sleep_ms(100);
+            match (self.use_coordinator.as_ref(), sortitions_processed) {
+                (Some(coord_comms), Some(sortitions_processed)) => {
+                    // wait for the coordinator to process the next sortition
+                    coord_comms.wait_for_sortitions_processed(sortitions_processed, 1000);
+                }
+                _ => {
+                    // yield some time
+                    sleep_ms(100);
+                }
+            }
         }
     }
 
@@ -920,6 +1282,11 @@ impl BurnchainController for BitcoinRegtestController {
         op_signer: &mut BurnchainOpSigner,
         attempt: u64,
     ) -> bool {
+        let is_block_commit = match operation {
+            BlockstackOperationType::LeaderBlockCommit(_) => true,
+            _ => false,
+        };
+
         let transaction = match operation {
             BlockstackOperationType::LeaderBlockCommit(payload) => {
                 self.build_leader_block_commit_tx(payload, op_signer, attempt)
@@ -930,6 +1297,12 @@ impl BurnchainController for BitcoinRegtestController {
             BlockstackOperationType::UserBurnSupport(payload) => {
                 self.build_user_burn_support_tx(payload, op_signer, attempt)
             }
+            BlockstackOperationType::PreStx(payload) => {
+                self.build_pre_stx_tx(payload, op_signer, attempt)
+            }
+            BlockstackOperationType::TransferStx(payload) => {
+                self.build_transfer_stx_tx(payload, op_signer, attempt)
+            }
         };
 
         let transaction = match transaction {
@@ -937,7 +1310,12 @@ impl BurnchainController for BitcoinRegtestController {
             _ => return false,
         };
 
-        self.send_transaction(transaction)
+        let sent = self.send_transaction(transaction);
+        if is_block_commit && !sent {
+            // nothing to bump
+            self.ongoing_block_commit = None;
+        }
+        sent
     }
 
     #[cfg(test)]
@@ -1091,6 +1469,8 @@ struct BitcoinRPCRequest {
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 enum RPCError {
+    /// the bitcoind node could not be reached at all, so another node may be tried
+    Unreachable(String),
     Network(String),
     Parsing(String),
     Bitcoind(String),
@@ -1098,20 +1478,24 @@ enum RPCError {
 
 type RPCResult<T> = Result<T, RPCError>;
 
+/// Index into `config.burnchain.get_endpoints()` of the bitcoind node that RPC requests are sent
+/// to first.  Moves on to the next node whenever the current one can't be reached.
+static ACTIVE_RPC_ENDPOINT: AtomicUsize = AtomicUsize::new(0);
+
 impl BitcoinRPCRequest {
-    fn build_rpc_request(config: &Config) -> Request {
+    fn build_rpc_request(endpoint: &BitcoindEndpoint) -> Request {
         let url = {
-            let url = config.burnchain.get_rpc_url();
+            let url = endpoint.get_rpc_url();
             Url::parse(&url).expect(&format!("Unable to parse {} as a URL", url))
         };
         debug!(
             "BitcoinRPC builder: {:?}:{:?}@{}",
-            &config.burnchain.username, &config.burnchain.password, &url
+            &endpoint.username, &endpoint.password, &url
         );
 
         let mut req = Request::new(Method::Post, url);
 
-        match (&config.burnchain.username, &config.burnchain.password) {
+        match (&endpoint.username, &endpoint.password) {
             (Some(username), Some(password)) => {
                 let auth_token = format!("Basic {}", encode(format!("{}:{}", username, password)));
                 req.append_header("Authorization", auth_token)
@@ -1212,6 +1596,94 @@ impl BitcoinRPCRequest {
         Ok(vec![])
     }
 
+    pub fn get_block_count(config: &Config) -> RPCResult<u64> {
+        let payload = BitcoinRPCRequest {
+            method: "getblockcount".to_string(),
+            params: vec![],
+            id: "stacks".to_string(),
+            jsonrpc: "2.0".to_string(),
+        };
+
+        let res = BitcoinRPCRequest::send(&config, payload)?;
+        match res.get("result").and_then(|r| r.as_u64()) {
+            Some(height) => Ok(height),
+            None => Err(RPCError::Parsing(format!(
+                "Unexpected getblockcount response: {}",
+                res
+            ))),
+        }
+    }
+
+    /// Long-poll bitcoind until its chain reaches `height` or `timeout_ms` elapses, and return
+    /// the height of its chain tip.
+    pub fn wait_for_block_height(config: &Config, height: u64, timeout_ms: u64) -> RPCResult<u64> {
+        let payload = BitcoinRPCRequest {
+            method: "waitforblockheight".to_string(),
+            params: vec![height.into(), timeout_ms.into()],
+            id: "stacks".to_string(),
+            jsonrpc: "2.0".to_string(),
+        };
+
+        let res = BitcoinRPCRequest::send(&config, payload)?;
+        match res
+            .get("result")
+            .and_then(|r| r.get("height"))
+            .and_then(|h| h.as_u64())
+        {
+            Some(height) => Ok(height),
+            None => Err(RPCError::Parsing(format!(
+                "Unexpected waitforblockheight response: {}",
+                res
+            ))),
+        }
+    }
+
+    /// Get the mempool's minimum relay fee rate and the fee rate needed to be mined in the next
+    /// block, both in satoshis per kilobyte.  If bitcoind cannot estimate the latter (e.g. on
+    /// regtest), the minimum relay fee rate is used for both.
+    pub fn get_mempool_fee_rates(config: &Config) -> RPCResult<(u64, u64)> {
+        let btc_per_kb_to_sat = |v: &serde_json::Value| {
+            v.as_f64()
+                .map(|btc_per_kb| (btc_per_kb * 100_000_000.0).round() as u64)
+        };
+
+        let payload = BitcoinRPCRequest {
+            method: "getmempoolinfo".to_string(),
+            params: vec![],
+            id: "stacks".to_string(),
+            jsonrpc: "2.0".to_string(),
+        };
+        let res = BitcoinRPCRequest::send(&config, payload)?;
+        let min_relay_fee = match res
+            .get("result")
+            .and_then(|r| r.get("mempoolminfee"))
+            .and_then(btc_per_kb_to_sat)
+        {
+            Some(rate) => rate,
+            None => {
+                return Err(RPCError::Parsing(format!(
+                    "Unexpected getmempoolinfo response: {}",
+                    res
+                )));
+            }
+        };
+
+        let payload = BitcoinRPCRequest {
+            method: "estimatesmartfee".to_string(),
+            params: vec![1.into()],
+            id: "stacks".to_string(),
+            jsonrpc: "2.0".to_string(),
+        };
+        let res = BitcoinRPCRequest::send(&config, payload)?;
+        let target_rate = res
+            .get("result")
+            .and_then(|r| r.get("feerate"))
+            .and_then(btc_per_kb_to_sat)
+            .unwrap_or(min_relay_fee);
+
+        Ok((min_relay_fee, cmp::max(min_relay_fee, target_rate)))
+    }
+
     pub fn send_raw_transaction(config: &Config, tx: String) -> RPCResult<()> {
         let payload = BitcoinRPCRequest {
             method: "sendrawtransaction".to_string(),
@@ -1254,8 +1726,80 @@ impl BitcoinRPCRequest {
         Ok(())
     }
 
+    /// Ping every configured bitcoind node and start sending RPC requests to the first one that
+    /// answers.
+    pub fn check_endpoints(config: &Config) {
+        let endpoints = config.burnchain.get_endpoints();
+        if endpoints.len() < 2 {
+            return;
+        }
+        let mut healthy = None;
+        for (i, endpoint) in endpoints.iter().enumerate() {
+            let payload = BitcoinRPCRequest {
+                method: "getblockcount".to_string(),
+                params: vec![],
+                id: "stacks".to_string(),
+                jsonrpc: "2.0".to_string(),
+            };
+            match BitcoinRPCRequest::send_to(endpoint, &payload) {
+                Ok(_) => {
+                    info!("Bitcoin RPC endpoint {} is healthy", endpoint.get_rpc_url());
+                    if healthy.is_none() {
+                        healthy = Some(i);
+                    }
+                }
+                Err(e) => {
+                    warn!(
+                        "Bitcoin RPC endpoint {} is unhealthy: {:?}",
+                        endpoint.get_rpc_url(),
+                        &e
+                    );
+                }
+            }
+        }
+        match healthy {
+            Some(i) => ACTIVE_RPC_ENDPOINT.store(i, Ordering::SeqCst),
+            None => error!("None of the configured Bitcoin RPC endpoints are reachable"),
+        }
+    }
+
+    /// Send a request to the active bitcoind node, failing over to the others in turn if it
+    /// can't be reached.
     fn send(config: &Config, payload: BitcoinRPCRequest) -> RPCResult<serde_json::Value> {
-        let mut request = BitcoinRPCRequest::build_rpc_request(&config);
+        let endpoints = config.burnchain.get_endpoints();
+        let active = ACTIVE_RPC_ENDPOINT.load(Ordering::SeqCst) % endpoints.len();
+        let mut last_err = String::new();
+        for i in 0..endpoints.len() {
+            let index = (active + i) % endpoints.len();
+            match BitcoinRPCRequest::send_to(&endpoints[index], &payload) {
+                Err(RPCError::Unreachable(msg)) => {
+                    warn!(
+                        "Bitcoin RPC endpoint {} is unreachable: {}",
+                        endpoints[index].get_rpc_url(),
+                        &msg
+                    );
+                    last_err = msg;
+                }
+                res => {
+                    if index != active {
+                        info!(
+                            "Bitcoin RPC: failed over to {}",
+                            endpoints[index].get_rpc_url()
+                        );
+                        ACTIVE_RPC_ENDPOINT.store(index, Ordering::SeqCst);
+                    }
+                    return res;
+                }
+            }
+        }
+        Err(RPCError::Network(last_err))
+    }
+
+    fn send_to(
+        endpoint: &BitcoindEndpoint,
+        payload: &BitcoinRPCRequest,
+    ) -> RPCResult<serde_json::Value> {
+        let mut request = BitcoinRPCRequest::build_rpc_request(endpoint);
 
         let body = match serde_json::to_vec(&json!(payload)) {
             Ok(body) => body,
@@ -1269,10 +1813,10 @@ impl BitcoinRPCRequest {
         request.set_body(body);
 
         let mut response = async_std::task::block_on(async move {
-            let stream = match TcpStream::connect(config.burnchain.get_rpc_socket_addr()).await {
+            let stream = match TcpStream::connect(endpoint.get_rpc_socket_addr()).await {
                 Ok(stream) => stream,
                 Err(err) => {
-                    return Err(RPCError::Network(format!(
+                    return Err(RPCError::Unreachable(format!(
                         "Bitcoin RPC: connection failed - {:?}",
                         err
                     )))
@@ -1282,7 +1826,7 @@ impl BitcoinRPCRequest {
             match client::connect(stream, request).await {
                 Ok(response) => Ok(response),
                 Err(err) => {
-                    return Err(RPCError::Network(format!(
+                    return Err(RPCError::Unreachable(format!(
                         "Bitcoin RPC: invoking procedure failed - {:?}",
                         err
                     )))
@@ -1323,3 +1867,27 @@ impl BitcoinRPCRequest {
         Ok(payload)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_rbf_fee() {
+        // the mempool wants less than we pay: raise the fee by the minimum increment
+        assert_eq!(get_rbf_fee(10000, 200, 1000, 1000, 50000), Some(10200));
+
+        // the mempool wants more than the minimum increment
+        assert_eq!(get_rbf_fee(10000, 200, 100000, 1000, 50000), Some(20000));
+
+        // capped, unless even the minimum increment is over the cap
+        assert_eq!(get_rbf_fee(10000, 200, 100000, 1000, 15000), Some(15000));
+        assert_eq!(get_rbf_fee(10000, 200, 100000, 1000, 10200), Some(10200));
+        assert_eq!(get_rbf_fee(10000, 200, 100000, 1000, 10199), None);
+
+        // both the increment and the target rate apply to the replacement's size
+        assert_eq!(get_rbf_fee(10000, 300, 1000, 1000, 50000), Some(10300));
+        assert_eq!(get_rbf_fee(10000, 300, 60000, 1000, 50000), Some(18000));
+        assert_eq!(get_rbf_fee(10000, 150, 60000, 1000, 50000), Some(10150));
+    }
+}
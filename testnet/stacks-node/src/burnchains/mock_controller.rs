@@ -0,0 +1,229 @@
+use std::time::Instant;
+
+use super::super::operations::BurnchainOpSigner;
+use super::super::Config;
+
+use super::{BurnchainController, BurnchainTip, Error as BurnchainControllerError};
+use stacks::burnchains::bitcoin::address::{BitcoinAddress, BitcoinAddressType};
+use stacks::burnchains::bitcoin::{BitcoinNetworkType, BitcoinTransaction};
+use stacks::burnchains::mock::{MockBurnchain, MockIndexer, MOCK_FIRST_BLOCK_HEIGHT};
+use stacks::burnchains::{Burnchain, BurnchainStateTransitionOps};
+use stacks::chainstate::burn::db::sortdb::SortitionDB;
+use stacks::chainstate::burn::operations::leader_block_commit::OUTPUTS_PER_COMMIT;
+use stacks::chainstate::burn::operations::BlockstackOperationType;
+use stacks::net::StacksMessageCodec;
+use stacks::util::hash::Hash160;
+
+const DUST_UTXO_LIMIT: u64 = 5500;
+
+/// MockBurnchainController mines blocks on an in-process mock burnchain, and syncs them
+/// through the same indexer code path a helium node uses against bitcoind.
+/// Tests can script their own transactions onto the chain with
+/// `MockBurnchain::open(&config.get_burn_db_path())`.
+pub struct MockBurnchainController {
+    config: Config,
+    chain: MockBurnchain,
+    indexer: MockIndexer,
+    db: Option<SortitionDB>,
+    chain_tip: Option<BurnchainTip>,
+    queued_transactions: Vec<BitcoinTransaction>,
+}
+
+impl MockBurnchainController {
+    pub fn generic(config: Config) -> Box<dyn BurnchainController> {
+        Box::new(Self::new(config))
+    }
+
+    fn new(config: Config) -> Self {
+        let working_dir = config.get_burn_db_path();
+        std::fs::create_dir_all(&working_dir).expect("Unable to create workdir");
+
+        let chain = MockBurnchain::open(&working_dir);
+        let indexer = MockIndexer::new(&working_dir, chain.clone());
+
+        Self {
+            config,
+            chain,
+            indexer,
+            db: None,
+            chain_tip: None,
+            queued_transactions: vec![],
+        }
+    }
+
+    fn get_burnchain(&self) -> Burnchain {
+        let mut burnchain = Burnchain::new(
+            &self.config.get_burn_db_path(),
+            &self.config.burnchain.chain,
+            "regtest",
+        )
+        .expect("Error while instantiating burnchain");
+        burnchain.first_block_height = MOCK_FIRST_BLOCK_HEIGHT;
+        burnchain.first_block_hash = self
+            .chain
+            .get_block_at(MOCK_FIRST_BLOCK_HEIGHT)
+            .expect("FATAL: mock burnchain has no genesis block")
+            .block_hash;
+        burnchain
+    }
+
+    /// Lay out an operation as a mock burnchain transaction, with the outputs its parser
+    /// expects.  Returns None if the operation can't be sent.
+    fn make_transaction(
+        &self,
+        operation: BlockstackOperationType,
+        op_signer: &mut BurnchainOpSigner,
+    ) -> Option<BitcoinTransaction> {
+        let public_key = op_signer.get_public_key();
+
+        let mut op_bytes = vec![];
+        let outputs = match operation {
+            BlockstackOperationType::LeaderKeyRegister(payload) => {
+                payload
+                    .consensus_serialize(&mut op_bytes)
+                    .expect("FATAL: invalid operation");
+                let address = BitcoinAddress {
+                    addrtype: BitcoinAddressType::PublicKeyHash,
+                    network_id: BitcoinNetworkType::Regtest,
+                    bytes: Hash160::from_data(&public_key.to_bytes()),
+                };
+                vec![MockBurnchain::make_output(&address, DUST_UTXO_LIMIT)]
+            }
+            BlockstackOperationType::LeaderBlockCommit(payload) => {
+                if OUTPUTS_PER_COMMIT != payload.commit_outs.len() {
+                    error!("Generated block commit with wrong OUTPUTS_PER_COMMIT");
+                    return None;
+                }
+                payload
+                    .consensus_serialize(&mut op_bytes)
+                    .expect("FATAL: invalid operation");
+                let value_per_transfer = payload.burn_fee / (OUTPUTS_PER_COMMIT as u64);
+                payload
+                    .commit_outs
+                    .iter()
+                    .map(|commit_to| {
+                        MockBurnchain::make_stacks_output(commit_to, value_per_transfer)
+                    })
+                    .collect()
+            }
+            BlockstackOperationType::UserBurnSupport(_) => {
+                warn!("The mock burnchain does not support user burn support operations");
+                return None;
+            }
+            BlockstackOperationType::PreStx(payload) => {
+                payload
+                    .consensus_serialize(&mut op_bytes)
+                    .expect("FATAL: invalid operation");
+                vec![MockBurnchain::make_stacks_output(
+                    &payload.output,
+                    DUST_UTXO_LIMIT,
+                )]
+            }
+            BlockstackOperationType::TransferStx(payload) => {
+                payload
+                    .consensus_serialize(&mut op_bytes)
+                    .expect("FATAL: invalid operation");
+                vec![MockBurnchain::make_stacks_output(
+                    &payload.recipient,
+                    DUST_UTXO_LIMIT,
+                )]
+            }
+        };
+
+        // the serialized operation leads with its opcode
+        let opcode = op_bytes.remove(0);
+        Some(
+            self.chain
+                .make_transaction(opcode, op_bytes, &public_key, outputs),
+        )
+    }
+
+    fn receive_blocks(&mut self) -> Result<(BurnchainTip, u64), BurnchainControllerError> {
+        let mut burnchain = self.get_burnchain();
+        let (block_snapshot, state_transition) = burnchain
+            .sync_with_indexer_deprecated(&mut self.indexer)
+            .map_err(BurnchainControllerError::IndexerError)?;
+
+        let burnchain_tip = match (state_transition, &self.chain_tip) {
+            (None, Some(chain_tip)) => chain_tip.clone(),
+            (Some(state_transition), _) => BurnchainTip {
+                block_snapshot: block_snapshot,
+                state_transition: BurnchainStateTransitionOps::from(state_transition),
+                received_at: Instant::now(),
+            },
+            (None, None) => {
+                // can happen at genesis
+                BurnchainTip {
+                    block_snapshot: block_snapshot,
+                    state_transition: BurnchainStateTransitionOps::noop(),
+                    received_at: Instant::now(),
+                }
+            }
+        };
+        self.chain_tip = Some(burnchain_tip.clone());
+
+        let (db, _) = burnchain
+            .open_db(true)
+            .map_err(BurnchainControllerError::IndexerError)?;
+        self.db = Some(db);
+
+        let block_height = burnchain_tip.block_snapshot.block_height;
+        Ok((burnchain_tip, block_height))
+    }
+}
+
+impl BurnchainController for MockBurnchainController {
+    fn sortdb_ref(&self) -> &SortitionDB {
+        self.db.as_ref().expect("BUG: did not instantiate burn DB")
+    }
+
+    fn sortdb_mut(&mut self) -> &mut SortitionDB {
+        self.db.as_mut().expect("BUG: did not instantiate burn DB")
+    }
+
+    fn get_chain_tip(&mut self) -> BurnchainTip {
+        match &self.chain_tip {
+            Some(chain_tip) => chain_tip.clone(),
+            None => {
+                unreachable!();
+            }
+        }
+    }
+
+    fn start(
+        &mut self,
+        _ignored_target_height_opt: Option<u64>,
+    ) -> Result<(BurnchainTip, u64), BurnchainControllerError> {
+        self.receive_blocks()
+    }
+
+    fn submit_operation(
+        &mut self,
+        operation: BlockstackOperationType,
+        op_signer: &mut BurnchainOpSigner,
+        _attempt: u64,
+    ) -> bool {
+        match self.make_transaction(operation, op_signer) {
+            Some(transaction) => {
+                self.queued_transactions.push(transaction);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn sync(
+        &mut self,
+        _ignored_target_height_opt: Option<u64>,
+    ) -> Result<(BurnchainTip, u64), BurnchainControllerError> {
+        // this node is responsible for mining new burnchain blocks
+        let transactions = self.queued_transactions.drain(..).collect();
+        self.chain.mine_block(transactions);
+        self.receive_blocks()
+    }
+
+    #[cfg(test)]
+    fn bootstrap_chain(&mut self, num_blocks: u64) {
+        self.chain.mine_empty_blocks(num_blocks);
+    }
+}
@@ -12,9 +12,11 @@ use stacks::burnchains::{
 };
 use stacks::chainstate::burn::db::sortdb::{PoxId, SortitionDB, SortitionHandleTx};
 use stacks::chainstate::burn::operations::{
-    BlockstackOperationType, LeaderBlockCommitOp, LeaderKeyRegisterOp, UserBurnSupportOp,
+    BlockstackOperationType, LeaderBlockCommitOp, LeaderKeyRegisterOp, PreStxOp, TransferStxOp,
+    UserBurnSupportOp,
 };
 use stacks::chainstate::burn::BlockSnapshot;
+use stacks::core::StacksEpoch;
 use stacks::util::get_epoch_time_secs;
 use stacks::util::hash::Sha256Sum;
 
@@ -97,6 +99,7 @@ impl BurnchainController for MocknetController {
             0,
             &BurnchainHeaderHash([0u8; 32]),
             get_epoch_time_secs(),
+            &StacksEpoch::all(0, &[]),
             true,
         ) {
             Ok(db) => db,
@@ -191,6 +194,27 @@ impl BurnchainController for MocknetController {
                         burn_header_hash: next_block_header.block_hash,
                     })
                 }
+                BlockstackOperationType::PreStx(payload) => {
+                    BlockstackOperationType::PreStx(PreStxOp {
+                        output: payload.output,
+                        txid,
+                        vtxindex: vtxindex,
+                        block_height: next_block_header.block_height,
+                        burn_header_hash: next_block_header.block_hash,
+                    })
+                }
+                BlockstackOperationType::TransferStx(payload) => {
+                    BlockstackOperationType::TransferStx(TransferStxOp {
+                        sender: payload.sender,
+                        recipient: payload.recipient,
+                        transfered_ustx: payload.transfered_ustx,
+                        memo: payload.memo,
+                        txid,
+                        vtxindex: vtxindex,
+                        block_height: next_block_header.block_height,
+                        burn_header_hash: next_block_header.block_hash,
+                    })
+                }
             };
             ops.push(op);
             vtxindex += 1;
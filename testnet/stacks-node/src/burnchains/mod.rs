@@ -1,7 +1,9 @@
 pub mod bitcoin_regtest_controller;
+pub mod mock_controller;
 pub mod mocknet_controller;
 
 pub use self::bitcoin_regtest_controller::BitcoinRegtestController;
+pub use self::mock_controller::MockBurnchainController;
 pub use self::mocknet_controller::MocknetController;
 
 use super::operations::BurnchainOpSigner;
@@ -8,18 +8,38 @@ use rand::RngCore;
 use stacks::burnchains::bitcoin::indexer::FIRST_BLOCK_MAINNET;
 use stacks::burnchains::bitcoin::BitcoinNetworkType;
 use stacks::burnchains::{MagicBytes, BLOCKSTACK_MAGIC_MAINNET};
+use stacks::chainstate::stacks::events::TRANSACTION_EVENT_TYPES;
+use stacks::core::mempool::MemPoolGCPolicy;
+use stacks::net::atlas::AtlasConfig;
+use stacks::net::audit::AuditCallerPrivacy;
 use stacks::net::connection::ConnectionOptions;
+use stacks::net::http::HttpResponseHeaders;
 use stacks::net::{Neighbor, NeighborKey, PeerAddress};
 use stacks::util::hash::{hex_bytes, to_hex};
+use stacks::util::log;
 use stacks::util::secp256k1::Secp256k1PrivateKey;
 use stacks::util::secp256k1::Secp256k1PublicKey;
+use stacks::vm::analysis::strict_checker::StrictProfile;
 use stacks::vm::costs::ExecutionCost;
 use stacks::vm::types::{AssetIdentifier, PrincipalData, QualifiedContractIdentifier};
 
+use super::config_schema;
+use super::miner_strategy::{CommitParams, CommitStrategy, COMMIT_STRATEGIES};
 use super::neon_node::TESTNET_PEER_VERSION;
 use super::node::TESTNET_CHAIN_ID;
 
 const MINIMUM_DUST_FEE: u64 = 5500;
+const DEFAULT_MAX_RBF_FEE: u64 = 50000;
+
+/// Burnchain backends a node can run against
+pub const BURNCHAIN_MODES: &[&str] = &[
+    "mocknet", "mock", "helium", "neon", "argon", "krypton", "xenon",
+];
+
+const DEFAULT_FAUCET_AMOUNT: u64 = 500_000_000;
+const DEFAULT_FAUCET_FEE: u64 = 180;
+const DEFAULT_FAUCET_ADDRESS_INTERVAL: u64 = 24 * 3600;
+const DEFAULT_FAUCET_CALLER_INTERVAL: u64 = 60;
 
 #[derive(Clone, Deserialize, Default)]
 pub struct ConfigFile {
@@ -29,15 +49,59 @@ pub struct ConfigFile {
     pub events_observer: Option<Vec<EventObserverConfigFile>>,
     pub connection_options: Option<ConnectionOptionsFile>,
     pub block_limit: Option<BlockLimitFile>,
+    pub faucet: Option<FaucetConfigFile>,
+    pub mempool: Option<MemPoolConfigFile>,
+    pub atlas: Option<AtlasConfigFile>,
+    pub miner: Option<MinerConfigFile>,
 }
 
 impl ConfigFile {
     pub fn from_path(path: &str) -> ConfigFile {
-        let path = File::open(path).unwrap();
-        let mut config_file_reader = BufReader::new(path);
-        let mut config_file = vec![];
-        config_file_reader.read_to_end(&mut config_file).unwrap();
-        toml::from_slice(&config_file[..]).unwrap()
+        ConfigFile::load(path).unwrap_or_else(|problems| {
+            panic!("Invalid config file {}:\n{}", path, problems.join("\n"))
+        })
+    }
+
+    /// Read a config file, apply the environment variables overriding its keys, and check it,
+    /// returning every problem found instead of stopping at the first.
+    pub fn load(path: &str) -> Result<ConfigFile, Vec<String>> {
+        let mut content = String::new();
+        File::open(path)
+            .and_then(|file| BufReader::new(file).read_to_string(&mut content))
+            .map_err(|e| vec![format!("Failed to read {}: {}", path, e)])?;
+        ConfigFile::load_str(&content)
+    }
+
+    pub fn load_str(content: &str) -> Result<ConfigFile, Vec<String>> {
+        ConfigFile::load_str_with_table(content).map(|(config_file, _)| config_file)
+    }
+
+    /// Like `load_str()`, but also return the keys as parsed, with environment overrides
+    /// applied, to compare against a later reload.
+    pub fn load_str_with_table(
+        content: &str,
+    ) -> Result<(ConfigFile, toml::value::Table), Vec<String>> {
+        let mut table: toml::value::Table =
+            toml::from_str(content).map_err(|e| vec![format!("Invalid TOML: {}", e)])?;
+
+        let mut problems = vec![];
+        config_schema::apply_env_overrides(&mut table, &mut problems);
+        problems.extend(config_schema::check_unknown_keys(&table));
+
+        match toml::Value::Table(table.clone()).try_into::<ConfigFile>() {
+            Ok(config_file) => {
+                problems.extend(config_schema::validate(&config_file));
+                if problems.is_empty() {
+                    Ok((config_file, table))
+                } else {
+                    Err(problems)
+                }
+            }
+            Err(e) => {
+                problems.push(e.to_string());
+                Err(problems)
+            }
+        }
     }
 
     pub fn from_str(content: &str) -> ConfigFile {
@@ -277,6 +341,12 @@ pub struct Config {
     pub events_observers: Vec<EventObserverConfig>,
     pub connection_options: ConnectionOptions,
     pub block_limit: ExecutionCost,
+    pub faucet: Option<FaucetConfig>,
+    pub mempool: MemPoolGCPolicy,
+    /// lints smart contracts must pass to be admitted to this node's mempool
+    pub strict_analysis: Option<StrictProfile>,
+    pub atlas: AtlasConfig,
+    pub miner: MinerConfig,
 }
 
 lazy_static! {
@@ -294,6 +364,7 @@ lazy_static! {
         soft_num_clients: 1000,
         max_neighbors_per_host: 10,
         max_clients_per_host: 1000,
+        max_clients_per_subnet: 1000,
         soft_max_neighbors_per_host: 10,
         soft_max_neighbors_per_org: 100,
         soft_max_clients_per_host: 1000,
@@ -334,6 +405,10 @@ impl Config {
                         None => default_node_config.seed,
                     },
                     working_dir: node.working_dir.unwrap_or(default_node_config.working_dir),
+                    burnchain_dir: node.burnchain_dir,
+                    chainstate_dir: node.chainstate_dir,
+                    mempool_db_path: node.mempool_db_path,
+                    peer_db_path: node.peer_db_path,
                     rpc_bind: rpc_bind.clone(),
                     p2p_bind: node.p2p_bind.unwrap_or(default_node_config.p2p_bind),
                     p2p_address: node.p2p_address.unwrap_or(rpc_bind.clone()),
@@ -356,10 +431,53 @@ impl Config {
                     wait_time_for_microblocks: node
                         .wait_time_for_microblocks
                         .unwrap_or(default_node_config.wait_time_for_microblocks),
+                    microblock_frequency: node
+                        .microblock_frequency
+                        .unwrap_or(default_node_config.microblock_frequency),
                     prometheus_bind: node.prometheus_bind,
                     pox_sync_sample_secs: node
                         .pox_sync_sample_secs
                         .unwrap_or(default_node_config.pox_sync_sample_secs),
+                    prune_block_confirmations: node.prune_block_confirmations,
+                    chain_id: match node.chain_id {
+                        Some(chain_id) => {
+                            parse_network_id(&chain_id).expect("Invalid node.chain_id")
+                        }
+                        None => default_node_config.chain_id,
+                    },
+                    peer_version: match node.peer_version {
+                        Some(peer_version) => {
+                            parse_network_id(&peer_version).expect("Invalid node.peer_version")
+                        }
+                        None => default_node_config.peer_version,
+                    },
+                    log_level: node.log_level.map(|level| {
+                        level
+                            .parse::<slog::Level>()
+                            .expect("Invalid node.log_level")
+                    }),
+                    log_modules: node
+                        .log_modules
+                        .unwrap_or(vec![])
+                        .iter()
+                        .map(|spec| {
+                            log::parse_module_loglevel(spec).expect("Invalid node.log_modules")
+                        })
+                        .collect(),
+                    clarity_value_store: node
+                        .clarity_value_store
+                        .unwrap_or(default_node_config.clarity_value_store),
+                    integrity_check_depth: node
+                        .integrity_check_depth
+                        .unwrap_or(default_node_config.integrity_check_depth),
+                    disk_usage_report_interval: node
+                        .disk_usage_report_interval
+                        .unwrap_or(default_node_config.disk_usage_report_interval),
+                    keychain_path: node.keychain_path,
+                    keychain_passphrase: node.keychain_passphrase,
+                    leader_key_lifetime: node
+                        .leader_key_lifetime
+                        .unwrap_or(default_node_config.leader_key_lifetime),
                 };
                 node_config.set_bootstrap_node(node.bootstrap_node);
                 if let Some(deny_nodes) = node.deny_nodes {
@@ -372,8 +490,9 @@ impl Config {
 
         let default_burnchain_config = BurnchainConfig::default();
         let burnchain = match config_file.burnchain {
-            Some(burnchain) => {
-                BurnchainConfig {
+            Some(mut burnchain) => {
+                let endpoints = burnchain.endpoints.take().unwrap_or(vec![]);
+                let mut burnchain_config = BurnchainConfig {
                     chain: burnchain.chain.unwrap_or(default_burnchain_config.chain),
                     mode: burnchain.mode.unwrap_or(default_burnchain_config.mode),
                     burn_fee_cap: burnchain
@@ -383,15 +502,7 @@ impl Config {
                         .commit_anchor_block_within
                         .unwrap_or(default_burnchain_config.commit_anchor_block_within),
                     peer_host: match burnchain.peer_host {
-                        Some(peer_host) => {
-                            // Using std::net::LookupHost would be preferable, but it's
-                            // unfortunately unstable at this point.
-                            // https://doc.rust-lang.org/1.6.0/std/net/struct.LookupHost.html
-                            let mut addrs_iter =
-                                format!("{}:1", peer_host).to_socket_addrs().unwrap();
-                            let sock_addr = addrs_iter.next().unwrap();
-                            format!("{}", sock_addr.ip())
-                        }
+                        Some(peer_host) => resolve_peer_host(&peer_host),
                         None => default_burnchain_config.peer_host,
                     },
                     peer_port: burnchain
@@ -423,17 +534,28 @@ impl Config {
                     poll_time_secs: burnchain
                         .poll_time_secs
                         .unwrap_or(default_burnchain_config.poll_time_secs),
-                }
+                    segwit: burnchain.segwit.unwrap_or(default_burnchain_config.segwit),
+                    max_rbf_fee: burnchain
+                        .max_rbf_fee
+                        .unwrap_or(default_burnchain_config.max_rbf_fee),
+                    endpoints: vec![],
+                    long_poll: burnchain
+                        .long_poll
+                        .unwrap_or(default_burnchain_config.long_poll),
+                };
+                burnchain_config.endpoints = endpoints
+                    .into_iter()
+                    .map(|endpoint| endpoint.into_endpoint(&burnchain_config))
+                    .collect();
+                burnchain_config
             }
             None => default_burnchain_config,
         };
 
-        let supported_modes = vec!["mocknet", "helium", "neon", "argon", "krypton", "xenon"];
-
-        if !supported_modes.contains(&burnchain.mode.as_str()) {
+        if !BURNCHAIN_MODES.contains(&burnchain.mode.as_str()) {
             panic!(
                 "Setting burnchain.network not supported (should be: {})",
-                supported_modes.join(", ")
+                BURNCHAIN_MODES.join(", ")
             )
         }
 
@@ -465,7 +587,10 @@ impl Config {
                     let events_keys: Vec<EventKeyType> = observer
                         .events_keys
                         .iter()
-                        .map(|e| EventKeyType::from_string(e).unwrap())
+                        .map(|e| {
+                            EventKeyType::from_string(e)
+                                .unwrap_or_else(|| panic!("Invalid event observer key '{}'", e))
+                        })
                         .collect();
 
                     let endpoint = format!("{}", observer.endpoint);
@@ -473,6 +598,7 @@ impl Config {
                     observers.push(EventObserverConfig {
                         endpoint,
                         events_keys,
+                        max_retries: observer.max_retries,
                     });
                 }
                 observers
@@ -485,6 +611,7 @@ impl Config {
             Ok(val) => events_observers.push(EventObserverConfig {
                 endpoint: val,
                 events_keys: vec![EventKeyType::AnyEvent],
+                max_retries: None,
             }),
             _ => (),
         };
@@ -561,6 +688,11 @@ impl Config {
                             .max_clients_per_host
                             .clone()
                     }),
+                    max_clients_per_subnet: opts.max_clients_per_subnet.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS
+                            .max_clients_per_subnet
+                            .clone()
+                    }),
                     soft_max_neighbors_per_host: opts.soft_max_neighbors_per_host.unwrap_or_else(
                         || {
                             HELIUM_DEFAULT_CONNECTION_OPTIONS
@@ -610,6 +742,94 @@ impl Config {
                     disable_inbound_walks: opts.disable_inbound_walks.unwrap_or(false),
                     disable_inbound_handshakes: opts.disable_inbound_handshakes.unwrap_or(false),
                     force_disconnect_interval: opts.force_disconnect_interval,
+                    rpc_audit_log_path: opts.rpc_audit_log_path,
+                    rpc_audit_log_caller_privacy: match opts.rpc_audit_log_caller_privacy {
+                        Some(ref mode) => AuditCallerPrivacy::from_str(mode).expect(
+                            "Invalid rpc_audit_log_caller_privacy -- expected full, anonymized, hashed, or omitted",
+                        ),
+                        None => HELIUM_DEFAULT_CONNECTION_OPTIONS
+                            .rpc_audit_log_caller_privacy
+                            .clone(),
+                    },
+                    rpc_audit_log_retention: opts.rpc_audit_log_retention.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.rpc_audit_log_retention.clone()
+                    }),
+                    require_rpc_api_keys: opts.require_rpc_api_keys.unwrap_or(false),
+                    ban_score_threshold: opts.ban_score_threshold.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.ban_score_threshold.clone()
+                    }),
+                    ban_duration: opts.ban_duration.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.ban_duration.clone()
+                    }),
+                    enable_port_mapping: opts.enable_port_mapping.unwrap_or(false),
+                    port_mapping_lifetime: opts.port_mapping_lifetime.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.port_mapping_lifetime.clone()
+                    }),
+                    enable_websocket_events: opts.enable_websocket_events.unwrap_or(false),
+                    max_websocket_clients: opts.max_websocket_clients.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.max_websocket_clients.clone()
+                    }),
+                    max_http_requests_per_connection: opts
+                        .max_http_requests_per_connection
+                        .unwrap_or_else(|| {
+                            HELIUM_DEFAULT_CONNECTION_OPTIONS
+                                .max_http_requests_per_connection
+                                .clone()
+                        }),
+                    read_only_call_rate: opts.read_only_call_rate.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.read_only_call_rate.clone()
+                    }),
+                    read_only_call_burst: opts.read_only_call_burst.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.read_only_call_burst.clone()
+                    }),
+                    rpc_tls_cert_path: opts.rpc_tls_cert_path,
+                    rpc_tls_key_path: opts.rpc_tls_key_path,
+                    rpc_tls_client_ca_path: opts.rpc_tls_client_ca_path,
+                    rpc_admin_token: opts.rpc_admin_token,
+                    rpc_cors_allowed_origins: opts.rpc_cors_allowed_origins.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS
+                            .rpc_cors_allowed_origins
+                            .clone()
+                    }),
+                    rpc_cors_max_age: opts.rpc_cors_max_age.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.rpc_cors_max_age.clone()
+                    }),
+                    rpc_cache_max_age: opts.rpc_cache_max_age.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.rpc_cache_max_age.clone()
+                    }),
+                    rpc_immutable_cache_max_age: opts.rpc_immutable_cache_max_age.unwrap_or_else(
+                        || {
+                            HELIUM_DEFAULT_CONNECTION_OPTIONS
+                                .rpc_immutable_cache_max_age
+                                .clone()
+                        },
+                    ),
+                    rpc_response_headers: opts
+                        .rpc_response_headers
+                        .map(|headers| {
+                            headers
+                                .iter()
+                                .map(|header| {
+                                    HttpResponseHeaders::parse_header(header).unwrap_or_else(|| {
+                                        panic!(
+                                            "Invalid connection_options.rpc_response_headers entry '{}'",
+                                            header
+                                        )
+                                    })
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_else(|| {
+                            HELIUM_DEFAULT_CONNECTION_OPTIONS.rpc_response_headers.clone()
+                        }),
+                    rpc_compression: opts.rpc_compression.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.rpc_compression.clone()
+                    }),
+                    rpc_compression_min_size: opts.rpc_compression_min_size.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS
+                            .rpc_compression_min_size
+                            .clone()
+                    }),
                     ..ConnectionOptions::default()
                 }
             }
@@ -635,6 +855,101 @@ impl Config {
             None => HELIUM_BLOCK_LIMIT.clone(),
         };
 
+        let faucet = match config_file.faucet {
+            Some(faucet) => Some(FaucetConfig {
+                private_key: Secp256k1PrivateKey::from_hex(&faucet.seed)
+                    .expect("Faucet seed should be a hex encoded private key"),
+                amount: faucet.amount.unwrap_or(DEFAULT_FAUCET_AMOUNT),
+                fee: faucet.fee.unwrap_or(DEFAULT_FAUCET_FEE),
+                address_interval: faucet
+                    .address_interval
+                    .unwrap_or(DEFAULT_FAUCET_ADDRESS_INTERVAL),
+                caller_interval: faucet
+                    .caller_interval
+                    .unwrap_or(DEFAULT_FAUCET_CALLER_INTERVAL),
+            }),
+            None => None,
+        };
+
+        let strict_analysis = match config_file.mempool {
+            Some(MemPoolConfigFile {
+                strict_analysis: Some(ref lints),
+                ..
+            }) => Some(
+                StrictProfile::from_lint_names(lints)
+                    .expect("Invalid mempool strict_analysis lints"),
+            ),
+            _ => None,
+        };
+
+        let default_mempool = MemPoolGCPolicy::default();
+        let mempool = match config_file.mempool {
+            Some(mempool) => MemPoolGCPolicy {
+                max_tx_age_blocks: mempool
+                    .max_tx_age_blocks
+                    .unwrap_or(default_mempool.max_tx_age_blocks),
+                max_tx_age_secs: mempool
+                    .max_tx_age_secs
+                    .unwrap_or(default_mempool.max_tx_age_secs),
+                max_txs: mempool.max_txs.unwrap_or(default_mempool.max_txs),
+            },
+            None => default_mempool,
+        };
+
+        let default_atlas = AtlasConfig::default();
+        let atlas = match config_file.atlas {
+            Some(atlas) => AtlasConfig {
+                contracts: atlas
+                    .contracts
+                    .unwrap_or(vec![])
+                    .iter()
+                    .map(|contract_id| {
+                        QualifiedContractIdentifier::parse(contract_id)
+                            .expect("Invalid atlas contract identifier")
+                    })
+                    .collect(),
+                attachments_max_size: atlas
+                    .attachments_max_size
+                    .unwrap_or(default_atlas.attachments_max_size),
+                max_uninstantiated_attachments: atlas
+                    .max_uninstantiated_attachments
+                    .unwrap_or(default_atlas.max_uninstantiated_attachments),
+                uninstantiated_attachments_expire_after: atlas
+                    .uninstantiated_attachments_expire_after
+                    .unwrap_or(default_atlas.uninstantiated_attachments_expire_after),
+                max_inflight_attachments: atlas
+                    .max_inflight_attachments
+                    .unwrap_or(default_atlas.max_inflight_attachments),
+            },
+            None => default_atlas,
+        };
+
+        let default_miner = MinerConfig::default();
+        let miner = match config_file.miner {
+            Some(miner) => {
+                let commit_strategy = match miner.commit_strategy {
+                    Some(ref name) => CommitStrategy::from_name(name).unwrap_or_else(|| {
+                        panic!(
+                            "Setting miner.commit_strategy not supported (should be: {})",
+                            COMMIT_STRATEGIES.join(", ")
+                        )
+                    }),
+                    None => default_miner.commit_strategy,
+                };
+                MinerConfig {
+                    dry_run: miner.dry_run.unwrap_or(default_miner.dry_run),
+                    commit_strategy,
+                    commit_amount: miner.commit_amount.or(default_miner.commit_amount),
+                    commit_percent: miner.commit_percent.unwrap_or(default_miner.commit_percent),
+                    commit_escalation_percent: miner
+                        .commit_escalation_percent
+                        .unwrap_or(default_miner.commit_escalation_percent),
+                    commit_window: miner.commit_window.unwrap_or(default_miner.commit_window),
+                }
+            }
+            None => default_miner,
+        };
+
         Config {
             node,
             burnchain,
@@ -642,30 +957,73 @@ impl Config {
             events_observers,
             connection_options,
             block_limit,
+            faucet,
+            mempool,
+            strict_analysis,
+            atlas,
+            miner,
         }
     }
 
     pub fn get_burnchain_path(&self) -> String {
-        format!("{}/burnchain/", self.node.working_dir)
+        format!("{}/", self.node.get_burnchain_path())
     }
 
     pub fn get_burn_db_path(&self) -> String {
-        format!("{}/burnchain/db", self.node.working_dir)
+        format!("{}/db", self.node.get_burnchain_path())
     }
 
     pub fn get_burn_db_file_path(&self) -> String {
         format!(
-            "{}/burnchain/db/{}/{}/sortition.db/",
-            self.node.working_dir, self.burnchain.chain, "regtest"
+            "{}/db/{}/{}/sortition.db/",
+            self.node.get_burnchain_path(),
+            self.burnchain.chain,
+            "regtest"
         )
     }
 
     pub fn get_chainstate_path(&self) -> String {
-        format!("{}/chainstate/", self.node.working_dir)
+        match self.node.chainstate_dir {
+            Some(ref chainstate_dir) => format!("{}/", chainstate_dir),
+            None => format!("{}/chainstate/", self.node.working_dir),
+        }
+    }
+
+    pub fn get_mempool_db_path(&self) -> String {
+        match self.node.mempool_db_path {
+            Some(ref mempool_db_path) => mempool_db_path.clone(),
+            None => format!("{}mempool.db", self.get_chainstate_path()),
+        }
     }
 
     pub fn get_peer_db_path(&self) -> String {
-        format!("{}/peer_db.sqlite", self.node.working_dir)
+        match self.node.peer_db_path {
+            Some(ref peer_db_path) => peer_db_path.clone(),
+            None => format!("{}/peer_db.sqlite", self.node.working_dir),
+        }
+    }
+
+    pub fn get_keychain_path(&self) -> String {
+        match self.node.keychain_path {
+            Some(ref keychain_path) => keychain_path.clone(),
+            None => format!("{}/keychain.dat", self.node.working_dir),
+        }
+    }
+
+    pub fn get_faucet_db_path(&self) -> String {
+        format!("{}/faucet.sqlite", self.node.working_dir)
+    }
+
+    pub fn get_rpc_api_keys_db_path(&self) -> String {
+        format!("{}/rpc_api_keys.sqlite", self.node.working_dir)
+    }
+
+    pub fn get_atlas_db_path(&self) -> String {
+        format!("{}/atlas.sqlite", self.node.working_dir)
+    }
+
+    pub fn get_sync_checkpoint_path(&self) -> String {
+        format!("{}/sync_checkpoint.json", self.node.working_dir)
     }
 
     pub fn add_initial_balance(&mut self, address: String, amount: u64) {
@@ -710,6 +1068,11 @@ impl std::default::Default for Config {
             events_observers: vec![],
             connection_options,
             block_limit,
+            faucet: None,
+            mempool: MemPoolGCPolicy::default(),
+            strict_analysis: None,
+            atlas: AtlasConfig::default(),
+            miner: MinerConfig::default(),
         }
     }
 }
@@ -734,6 +1097,14 @@ pub struct BurnchainConfig {
     pub burnchain_op_tx_fee: u64,
     pub process_exit_at_block_height: Option<u64>,
     pub poll_time_secs: u64,
+    /// spend from and send change to the miner's native segwit (p2wpkh) address
+    pub segwit: bool,
+    /// most a miner will pay in transaction fees (in satoshis) when replacing a block commit
+    pub max_rbf_fee: u64,
+    /// additional bitcoind nodes to fail over to when the primary one is unreachable
+    pub endpoints: Vec<BitcoindEndpoint>,
+    /// long-poll bitcoind for new blocks instead of only checking every `poll_time_secs`
+    pub long_poll: bool,
 }
 
 impl BurnchainConfig {
@@ -757,23 +1128,38 @@ impl BurnchainConfig {
             burnchain_op_tx_fee: MINIMUM_DUST_FEE,
             process_exit_at_block_height: None,
             poll_time_secs: 10, // TODO: this is a testnet specific value.
+            segwit: false,
+            max_rbf_fee: DEFAULT_MAX_RBF_FEE,
+            endpoints: vec![],
+            long_poll: false,
         }
     }
 
     pub fn get_rpc_url(&self) -> String {
-        let scheme = match self.rpc_ssl {
-            true => "https://",
-            false => "http://",
-        };
-        format!("{}{}:{}", scheme, self.peer_host, self.rpc_port)
+        self.get_primary_endpoint().get_rpc_url()
     }
 
     pub fn get_rpc_socket_addr(&self) -> SocketAddr {
-        let mut addrs_iter = format!("{}:{}", self.peer_host, self.rpc_port)
-            .to_socket_addrs()
-            .unwrap();
-        let sock_addr = addrs_iter.next().unwrap();
-        sock_addr
+        self.get_primary_endpoint().get_rpc_socket_addr()
+    }
+
+    /// The bitcoind node configured by the top-level `[burnchain]` settings
+    pub fn get_primary_endpoint(&self) -> BitcoindEndpoint {
+        BitcoindEndpoint {
+            peer_host: self.peer_host.clone(),
+            peer_port: self.peer_port,
+            rpc_port: self.rpc_port,
+            rpc_ssl: self.rpc_ssl,
+            username: self.username.clone(),
+            password: self.password.clone(),
+        }
+    }
+
+    /// All configured bitcoind nodes, primary first, in failover order
+    pub fn get_endpoints(&self) -> Vec<BitcoindEndpoint> {
+        let mut endpoints = vec![self.get_primary_endpoint()];
+        endpoints.extend(self.endpoints.iter().cloned());
+        endpoints
     }
 
     pub fn get_bitcoin_network(&self) -> (String, BitcoinNetworkType) {
@@ -808,6 +1194,71 @@ pub struct BurnchainConfigFile {
     pub burnchain_op_tx_fee: Option<u64>,
     pub process_exit_at_block_height: Option<u64>,
     pub poll_time_secs: Option<u64>,
+    pub segwit: Option<bool>,
+    pub max_rbf_fee: Option<u64>,
+    pub endpoints: Option<Vec<BitcoindEndpointFile>>,
+    pub long_poll: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BitcoindEndpoint {
+    pub peer_host: String,
+    pub peer_port: u16,
+    pub rpc_port: u16,
+    pub rpc_ssl: bool,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl BitcoindEndpoint {
+    pub fn get_rpc_url(&self) -> String {
+        let scheme = match self.rpc_ssl {
+            true => "https://",
+            false => "http://",
+        };
+        format!("{}{}:{}", scheme, self.peer_host, self.rpc_port)
+    }
+
+    pub fn get_rpc_socket_addr(&self) -> SocketAddr {
+        let mut addrs_iter = format!("{}:{}", self.peer_host, self.rpc_port)
+            .to_socket_addrs()
+            .unwrap();
+        let sock_addr = addrs_iter.next().unwrap();
+        sock_addr
+    }
+}
+
+#[derive(Clone, Deserialize, Default)]
+pub struct BitcoindEndpointFile {
+    pub peer_host: String,
+    pub peer_port: Option<u16>,
+    pub rpc_port: Option<u16>,
+    pub rpc_ssl: Option<bool>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl BitcoindEndpointFile {
+    /// Unset ports and credentials are inherited from the primary `[burnchain]` node
+    fn into_endpoint(self, primary: &BurnchainConfig) -> BitcoindEndpoint {
+        BitcoindEndpoint {
+            peer_host: resolve_peer_host(&self.peer_host),
+            peer_port: self.peer_port.unwrap_or(primary.peer_port),
+            rpc_port: self.rpc_port.unwrap_or(primary.rpc_port),
+            rpc_ssl: self.rpc_ssl.unwrap_or(primary.rpc_ssl),
+            username: self.username.or(primary.username.clone()),
+            password: self.password.or(primary.password.clone()),
+        }
+    }
+}
+
+fn resolve_peer_host(peer_host: &str) -> String {
+    // Using std::net::LookupHost would be preferable, but it's
+    // unfortunately unstable at this point.
+    // https://doc.rust-lang.org/1.6.0/std/net/struct.LookupHost.html
+    let mut addrs_iter = format!("{}:1", peer_host).to_socket_addrs().unwrap();
+    let sock_addr = addrs_iter.next().unwrap();
+    format!("{}", sock_addr.ip())
 }
 
 #[derive(Clone, Debug, Default)]
@@ -815,6 +1266,16 @@ pub struct NodeConfig {
     pub name: String,
     pub seed: Vec<u8>,
     pub working_dir: String,
+    /// Where the burnchain data (sortition DB, burnchain DB, SPV headers) is kept, if not in
+    /// `burnchain/` under the working directory
+    pub burnchain_dir: Option<String>,
+    /// Where the chain state (blocks, headers and the Clarity MARF) is kept, if not in
+    /// `chainstate/` under the working directory
+    pub chainstate_dir: Option<String>,
+    /// The mempool DB file, if not `mempool.db` in the chain state directory
+    pub mempool_db_path: Option<String>,
+    /// The peer DB file, if not `peer_db.sqlite` in the working directory
+    pub peer_db_path: Option<String>,
     pub rpc_bind: String,
     pub p2p_bind: String,
     pub data_url: String,
@@ -825,8 +1286,41 @@ pub struct NodeConfig {
     pub miner: bool,
     pub mine_microblocks: bool,
     pub wait_time_for_microblocks: u64,
+    /// How often (in milliseconds) the winning miner cuts a new microblock
+    pub microblock_frequency: u64,
     pub prometheus_bind: Option<String>,
     pub pox_sync_sample_secs: u64,
+    /// If set, prune the bodies of blocks buried under this many blocks
+    pub prune_block_confirmations: Option<u64>,
+    /// The chain ID that transactions are signed for, and that identifies this network to peers
+    /// and on /v2/info.  Private networks should pick one no public network uses.
+    pub chain_id: u32,
+    /// The version stamped on every p2p message.  Peers only talk to each other if the top byte
+    /// matches.
+    pub peer_version: u32,
+    /// Overrides the level `BLOCKSTACK_DEBUG` / `BLOCKSTACK_TRACE` select, and can be changed
+    /// without a restart
+    pub log_level: Option<slog::Level>,
+    /// Per-module log levels, which take precedence over `log_level` and can be changed without
+    /// a restart
+    pub log_modules: Vec<(String, slog::Level)>,
+    /// Where the Clarity state's values are kept: `sqlite` (the side store) or `rocksdb`.  An
+    /// existing node switched to `rocksdb` has its values copied over when it next starts.
+    pub clarity_value_store: String,
+    /// How many of the most recent MARF tries and Stacks blocks the startup integrity check
+    /// covers.  0 turns the check off.
+    pub integrity_check_depth: u32,
+    /// How often (in seconds) to log and export how much disk space each subsystem uses.  0
+    /// turns the report off.
+    pub disk_usage_report_interval: u64,
+    /// The file the miner's VRF and microblock keys are kept in, if not `keychain.dat` in the
+    /// working directory
+    pub keychain_path: Option<String>,
+    /// If set, the miner's keys are encrypted with this passphrase and kept across restarts
+    pub keychain_passphrase: Option<String>,
+    /// How many burn blocks a leader key is used for before the miner registers a new one.  0
+    /// keeps using the same key.
+    pub leader_key_lifetime: u64,
 }
 
 impl NodeConfig {
@@ -850,6 +1344,10 @@ impl NodeConfig {
             name: name.to_string(),
             seed: seed.to_vec(),
             working_dir: format!("/tmp/{}", testnet_id),
+            burnchain_dir: None,
+            chainstate_dir: None,
+            mempool_db_path: None,
+            peer_db_path: None,
             rpc_bind: format!("0.0.0.0:{}", rpc_port),
             p2p_bind: format!("0.0.0.0:{}", p2p_port),
             data_url: format!("http://127.0.0.1:{}", rpc_port),
@@ -860,24 +1358,39 @@ impl NodeConfig {
             miner: false,
             mine_microblocks: false,
             wait_time_for_microblocks: 5000,
+            microblock_frequency: 30000,
             prometheus_bind: None,
             pox_sync_sample_secs: 30,
+            prune_block_confirmations: None,
+            chain_id: TESTNET_CHAIN_ID,
+            peer_version: TESTNET_PEER_VERSION,
+            log_level: None,
+            log_modules: vec![],
+            clarity_value_store: "sqlite".to_string(),
+            integrity_check_depth: 256,
+            disk_usage_report_interval: 600,
+            keychain_path: None,
+            keychain_passphrase: None,
+            leader_key_lifetime: 0,
         }
     }
 
     pub fn get_burnchain_path(&self) -> String {
-        format!("{}/burnchain", self.working_dir)
+        match self.burnchain_dir {
+            Some(ref burnchain_dir) => burnchain_dir.clone(),
+            None => format!("{}/burnchain", self.working_dir),
+        }
     }
 
     pub fn get_default_spv_headers_path(&self) -> String {
         format!("{}/spv-headers.dat", self.get_burnchain_path())
     }
 
-    fn default_neighbor(addr: SocketAddr, pubk: Secp256k1PublicKey) -> Neighbor {
+    fn default_neighbor(&self, addr: SocketAddr, pubk: Secp256k1PublicKey) -> Neighbor {
         Neighbor {
             addr: NeighborKey {
-                peer_version: TESTNET_PEER_VERSION,
-                network_id: TESTNET_CHAIN_ID,
+                peer_version: self.peer_version,
+                network_id: self.chain_id,
                 addrbytes: PeerAddress::from_socketaddr(&addr),
                 port: addr.port(),
             },
@@ -903,7 +1416,7 @@ impl NodeConfig {
 
                     let mut addrs_iter = peer_addr.to_socket_addrs().unwrap();
                     let sock_addr = addrs_iter.next().unwrap();
-                    let neighbor = NodeConfig::default_neighbor(sock_addr, pubk);
+                    let neighbor = self.default_neighbor(sock_addr, pubk);
                     self.bootstrap_node = Some(neighbor);
                 }
                 _ => {}
@@ -913,7 +1426,7 @@ impl NodeConfig {
 
     pub fn add_deny_node(&mut self, deny_node: &str) {
         let sockaddr = deny_node.to_socket_addrs().unwrap().next().unwrap();
-        let neighbor = NodeConfig::default_neighbor(
+        let neighbor = self.default_neighbor(
             sockaddr,
             Secp256k1PublicKey::from_private(&Secp256k1PrivateKey::new()),
         );
@@ -944,6 +1457,7 @@ pub struct ConnectionOptionsFile {
     pub soft_num_clients: Option<u64>,
     pub max_neighbors_per_host: Option<u64>,
     pub max_clients_per_host: Option<u64>,
+    pub max_clients_per_subnet: Option<u64>,
     pub soft_max_neighbors_per_host: Option<u64>,
     pub soft_max_neighbors_per_org: Option<u64>,
     pub soft_max_clients_per_host: Option<u64>,
@@ -962,6 +1476,133 @@ pub struct ConnectionOptionsFile {
     pub disable_inbound_walks: Option<bool>,
     pub disable_inbound_handshakes: Option<bool>,
     pub force_disconnect_interval: Option<u64>,
+    pub rpc_audit_log_path: Option<String>,
+    pub rpc_audit_log_caller_privacy: Option<String>,
+    pub rpc_audit_log_retention: Option<u64>,
+    pub require_rpc_api_keys: Option<bool>,
+    pub ban_score_threshold: Option<u64>,
+    pub ban_duration: Option<u64>,
+    pub enable_port_mapping: Option<bool>,
+    pub port_mapping_lifetime: Option<u64>,
+    pub enable_websocket_events: Option<bool>,
+    pub max_websocket_clients: Option<u64>,
+    pub max_http_requests_per_connection: Option<u64>,
+    pub read_only_call_rate: Option<u64>,
+    pub read_only_call_burst: Option<u64>,
+    pub rpc_tls_cert_path: Option<String>,
+    pub rpc_tls_key_path: Option<String>,
+    pub rpc_tls_client_ca_path: Option<String>,
+    pub rpc_admin_token: Option<String>,
+    pub rpc_cors_allowed_origins: Option<Vec<String>>,
+    pub rpc_cors_max_age: Option<u64>,
+    pub rpc_cache_max_age: Option<u64>,
+    pub rpc_immutable_cache_max_age: Option<u64>,
+    pub rpc_response_headers: Option<Vec<String>>,
+    pub rpc_compression: Option<bool>,
+    pub rpc_compression_min_size: Option<u64>,
+}
+
+#[derive(Clone)]
+pub struct FaucetConfig {
+    pub private_key: Secp256k1PrivateKey,
+    pub amount: u64,
+    pub fee: u64,
+    pub address_interval: u64,
+    pub caller_interval: u64,
+}
+
+#[derive(Clone, Default, Deserialize)]
+pub struct FaucetConfigFile {
+    /// hex-encoded private key of the account the faucet sends STX from
+    pub seed: String,
+    /// microSTX sent per request
+    pub amount: Option<u64>,
+    /// fee paid per transfer, in microSTX
+    pub fee: Option<u64>,
+    /// how often a recipient may be sent STX, in seconds
+    pub address_interval: Option<u64>,
+    /// how often a single IP address may request STX, in seconds
+    pub caller_interval: Option<u64>,
+}
+
+#[derive(Clone, Default, Deserialize)]
+pub struct MemPoolConfigFile {
+    /// drop transactions accepted this many Stacks blocks ago (0 for no limit)
+    pub max_tx_age_blocks: Option<u64>,
+    /// drop transactions accepted this many seconds ago (0 for no limit)
+    pub max_tx_age_secs: Option<u64>,
+    /// evict the lowest-fee transactions beyond this many (0 for no limit)
+    pub max_txs: Option<u64>,
+    /// reject smart contracts that fail any of these lints, e.g. `["unchecked-responses"]`
+    pub strict_analysis: Option<Vec<String>>,
+}
+
+#[derive(Clone, Default, Deserialize)]
+pub struct AtlasConfigFile {
+    /// contracts whose attachments this node fetches and serves, as `ADDRESS.contract-name`
+    pub contracts: Option<Vec<String>>,
+    /// largest attachment accepted, in bytes
+    pub attachments_max_size: Option<u64>,
+    /// most attachments held that have not been declared on-chain
+    pub max_uninstantiated_attachments: Option<u64>,
+    /// how long to hold an attachment that has not been declared on-chain, in seconds
+    pub uninstantiated_attachments_expire_after: Option<u64>,
+    /// most attachment downloads in flight at once
+    pub max_inflight_attachments: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MinerConfig {
+    /// Assemble a block from the mempool every sortition and log it, but never register a leader
+    /// key or send a block commit
+    pub dry_run: bool,
+    /// how to choose how much each block commit burns (see `miner_strategy`)
+    pub commit_strategy: CommitStrategy,
+    /// the static commit amount, and where the capped strategy starts.  Defaults to
+    /// `burnchain.burn_fee_cap`.
+    pub commit_amount: Option<u64>,
+    /// percentage of the recent winning commits the proportional strategy commits
+    pub commit_percent: u64,
+    /// percentage the capped strategy raises its commit by for each round lost
+    pub commit_escalation_percent: u64,
+    /// how many recent sortitions the proportional and capped strategies look at
+    pub commit_window: u64,
+}
+
+impl Default for MinerConfig {
+    fn default() -> MinerConfig {
+        MinerConfig {
+            dry_run: false,
+            commit_strategy: CommitStrategy::Static,
+            commit_amount: None,
+            commit_percent: 100,
+            commit_escalation_percent: 10,
+            commit_window: 6,
+        }
+    }
+}
+
+impl MinerConfig {
+    /// The parameters block commits are chosen with, given the burnchain's `burn_fee_cap`
+    pub fn commit_params(&self, burn_fee_cap: u64) -> CommitParams {
+        CommitParams {
+            strategy: self.commit_strategy.clone(),
+            amount: self.commit_amount.unwrap_or(burn_fee_cap),
+            percent: self.commit_percent,
+            escalation_percent: self.commit_escalation_percent,
+            cap: burn_fee_cap,
+        }
+    }
+}
+
+#[derive(Clone, Default, Deserialize)]
+pub struct MinerConfigFile {
+    pub dry_run: Option<bool>,
+    pub commit_strategy: Option<String>,
+    pub commit_amount: Option<u64>,
+    pub commit_percent: Option<u64>,
+    pub commit_escalation_percent: Option<u64>,
+    pub commit_window: Option<u64>,
 }
 
 #[derive(Clone, Default, Deserialize)]
@@ -979,6 +1620,10 @@ pub struct NodeConfigFile {
     pub seed: Option<String>,
     pub deny_nodes: Option<String>,
     pub working_dir: Option<String>,
+    pub burnchain_dir: Option<String>,
+    pub chainstate_dir: Option<String>,
+    pub mempool_db_path: Option<String>,
+    pub peer_db_path: Option<String>,
     pub rpc_bind: Option<String>,
     pub p2p_bind: Option<String>,
     pub p2p_address: Option<String>,
@@ -988,26 +1633,66 @@ pub struct NodeConfigFile {
     pub miner: Option<bool>,
     pub mine_microblocks: Option<bool>,
     pub wait_time_for_microblocks: Option<u64>,
+    pub microblock_frequency: Option<u64>,
     pub prometheus_bind: Option<String>,
     pub pox_sync_sample_secs: Option<u64>,
+    pub prune_block_confirmations: Option<u64>,
+    /// hex (with a `0x` prefix) or decimal
+    pub chain_id: Option<String>,
+    /// hex (with a `0x` prefix) or decimal
+    pub peer_version: Option<String>,
+    /// one of `critical`, `error`, `warn`, `info`, `debug`, `trace`
+    pub log_level: Option<String>,
+    /// per-module log levels, like `"blockstack_lib::net=debug"`
+    pub log_modules: Option<Vec<String>>,
+    /// `sqlite` or `rocksdb`; the latter needs the `rocksdb_store` feature
+    pub clarity_value_store: Option<String>,
+    /// 0 turns the startup integrity check off
+    pub integrity_check_depth: Option<u32>,
+    /// seconds; 0 turns the disk usage report off
+    pub disk_usage_report_interval: Option<u64>,
+    pub keychain_path: Option<String>,
+    pub keychain_passphrase: Option<String>,
+    /// burn blocks; 0 keeps using the same leader key
+    pub leader_key_lifetime: Option<u64>,
+}
+
+/// Parse a 32-bit network identifier, given either in hex with a `0x` prefix, or in decimal
+pub fn parse_network_id(value: &str) -> Result<u32, String> {
+    if value.starts_with("0x") {
+        u32::from_str_radix(&value[2..], 16)
+            .map_err(|_| format!("invalid hex number '{}'", &value[2..]))
+    } else {
+        value
+            .parse::<u32>()
+            .map_err(|_| format!("invalid number '{}'", value))
+    }
 }
 
 #[derive(Clone, Deserialize, Default)]
 pub struct EventObserverConfigFile {
     pub endpoint: String,
     pub events_keys: Vec<String>,
+    pub max_retries: Option<u64>,
 }
 
 #[derive(Clone, Default)]
 pub struct EventObserverConfig {
     pub endpoint: String,
     pub events_keys: Vec<EventKeyType>,
+    /// How many times to retry a failed POST before dropping the payload.  If `None`, retry
+    /// until the observer accepts it.
+    pub max_retries: Option<u64>,
 }
 
 #[derive(Clone)]
 pub enum EventKeyType {
     SmartContractEvent((QualifiedContractIdentifier, String)),
     AssetEvent(AssetIdentifier),
+    /// Every event emitted by, or involving the assets of, this contract
+    ContractEvents(QualifiedContractIdentifier),
+    /// Every event of this type (e.g. `ft_transfer_event`)
+    EventType(String),
     STXEvent,
     MemPoolTransactions,
     AnyEvent,
@@ -1015,7 +1700,7 @@ pub enum EventKeyType {
 }
 
 impl EventKeyType {
-    fn from_string(raw_key: &str) -> Option<EventKeyType> {
+    pub fn from_string(raw_key: &str) -> Option<EventKeyType> {
         if raw_key == "*" {
             return Some(EventKeyType::AnyEvent);
         }
@@ -1032,9 +1717,18 @@ impl EventKeyType {
             return Some(EventKeyType::BurnchainBlocks);
         }
 
+        if TRANSACTION_EVENT_TYPES.contains(&raw_key) {
+            return Some(EventKeyType::EventType(raw_key.to_string()));
+        }
+
         let comps: Vec<_> = raw_key.split("::").collect();
         if comps.len() == 1 {
             let split: Vec<_> = comps[0].split(".").collect();
+            if split.len() == 2 {
+                return QualifiedContractIdentifier::parse(comps[0])
+                    .ok()
+                    .map(EventKeyType::ContractEvents);
+            }
             if split.len() != 3 {
                 return None;
             }
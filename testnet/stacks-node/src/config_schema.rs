@@ -0,0 +1,881 @@
+//! The keys a stacks-node config file may contain, the environment variables that override
+//! them, and the checks a config file must pass before the node will start with it.
+//!
+//! Every key has an environment variable named after its path, e.g. `node.rpc_bind` is
+//! overridden by `STACKS_NODE_RPC_BIND`.  Arrays (`events_observer`, `mstx_balance`,
+//! `burnchain.endpoints`, `atlas.contracts`, ...) are overridden as a whole with a TOML inline
+//! array, e.g. `STACKS_EVENTS_OBSERVER='[{endpoint = "127.0.0.1:3700", events_keys = ["*"]}]'`.
+
+use std::env;
+use std::path::Path;
+
+use toml::value::Table;
+use toml::Value;
+
+use stacks::net::audit::AuditCallerPrivacy;
+use stacks::util::hash::hex_bytes;
+use stacks::util::log;
+use stacks::util::secp256k1::{Secp256k1PrivateKey, Secp256k1PublicKey};
+use stacks::vm::analysis::strict_checker::StrictProfile;
+use stacks::vm::types::{PrincipalData, QualifiedContractIdentifier};
+
+use crate::config::{parse_network_id, ConfigFile, EventKeyType, BURNCHAIN_MODES};
+use crate::miner_strategy::COMMIT_STRATEGIES;
+
+/// What a config key holds, which determines how an environment variable overriding it is parsed
+pub enum FieldKind {
+    Str,
+    Int,
+    Bool,
+    /// an array of scalars
+    List,
+    /// a table with these keys
+    Table(&'static [Field]),
+    /// an array of tables with these keys
+    Tables(&'static [Field]),
+}
+
+pub type Field = (&'static str, FieldKind);
+
+const BITCOIND_ENDPOINT_FIELDS: &[Field] = &[
+    ("peer_host", FieldKind::Str),
+    ("peer_port", FieldKind::Int),
+    ("rpc_port", FieldKind::Int),
+    ("rpc_ssl", FieldKind::Bool),
+    ("username", FieldKind::Str),
+    ("password", FieldKind::Str),
+];
+
+const BURNCHAIN_FIELDS: &[Field] = &[
+    ("chain", FieldKind::Str),
+    ("burn_fee_cap", FieldKind::Int),
+    ("mode", FieldKind::Str),
+    ("commit_anchor_block_within", FieldKind::Int),
+    ("peer_host", FieldKind::Str),
+    ("peer_port", FieldKind::Int),
+    ("rpc_port", FieldKind::Int),
+    ("rpc_ssl", FieldKind::Bool),
+    ("username", FieldKind::Str),
+    ("password", FieldKind::Str),
+    ("timeout", FieldKind::Int),
+    ("spv_headers_path", FieldKind::Str),
+    ("first_block", FieldKind::Int),
+    ("magic_bytes", FieldKind::Str),
+    ("local_mining_public_key", FieldKind::Str),
+    ("burnchain_op_tx_fee", FieldKind::Int),
+    ("process_exit_at_block_height", FieldKind::Int),
+    ("poll_time_secs", FieldKind::Int),
+    ("segwit", FieldKind::Bool),
+    ("max_rbf_fee", FieldKind::Int),
+    ("endpoints", FieldKind::Tables(BITCOIND_ENDPOINT_FIELDS)),
+    ("long_poll", FieldKind::Bool),
+];
+
+const NODE_FIELDS: &[Field] = &[
+    ("name", FieldKind::Str),
+    ("seed", FieldKind::Str),
+    ("deny_nodes", FieldKind::Str),
+    ("working_dir", FieldKind::Str),
+    ("burnchain_dir", FieldKind::Str),
+    ("chainstate_dir", FieldKind::Str),
+    ("mempool_db_path", FieldKind::Str),
+    ("peer_db_path", FieldKind::Str),
+    ("rpc_bind", FieldKind::Str),
+    ("p2p_bind", FieldKind::Str),
+    ("p2p_address", FieldKind::Str),
+    ("data_url", FieldKind::Str),
+    ("bootstrap_node", FieldKind::Str),
+    ("local_peer_seed", FieldKind::Str),
+    ("miner", FieldKind::Bool),
+    ("mine_microblocks", FieldKind::Bool),
+    ("wait_time_for_microblocks", FieldKind::Int),
+    ("microblock_frequency", FieldKind::Int),
+    ("prometheus_bind", FieldKind::Str),
+    ("pox_sync_sample_secs", FieldKind::Int),
+    ("prune_block_confirmations", FieldKind::Int),
+    ("chain_id", FieldKind::Str),
+    ("peer_version", FieldKind::Str),
+    ("log_level", FieldKind::Str),
+    ("log_modules", FieldKind::List),
+    ("clarity_value_store", FieldKind::Str),
+    ("integrity_check_depth", FieldKind::Int),
+    ("disk_usage_report_interval", FieldKind::Int),
+    ("keychain_path", FieldKind::Str),
+    ("keychain_passphrase", FieldKind::Str),
+    ("leader_key_lifetime", FieldKind::Int),
+];
+
+const INITIAL_BALANCE_FIELDS: &[Field] = &[("address", FieldKind::Str), ("amount", FieldKind::Int)];
+
+const EVENT_OBSERVER_FIELDS: &[Field] = &[
+    ("endpoint", FieldKind::Str),
+    ("events_keys", FieldKind::List),
+    ("max_retries", FieldKind::Int),
+];
+
+const CONNECTION_OPTIONS_FIELDS: &[Field] = &[
+    ("inbox_maxlen", FieldKind::Int),
+    ("outbox_maxlen", FieldKind::Int),
+    ("timeout", FieldKind::Int),
+    ("idle_timeout", FieldKind::Int),
+    ("heartbeat", FieldKind::Int),
+    ("private_key_lifetime", FieldKind::Int),
+    ("num_neighbors", FieldKind::Int),
+    ("num_clients", FieldKind::Int),
+    ("soft_num_neighbors", FieldKind::Int),
+    ("soft_num_clients", FieldKind::Int),
+    ("max_neighbors_per_host", FieldKind::Int),
+    ("max_clients_per_host", FieldKind::Int),
+    ("max_clients_per_subnet", FieldKind::Int),
+    ("soft_max_neighbors_per_host", FieldKind::Int),
+    ("soft_max_neighbors_per_org", FieldKind::Int),
+    ("soft_max_clients_per_host", FieldKind::Int),
+    ("walk_interval", FieldKind::Int),
+    ("dns_timeout", FieldKind::Int),
+    ("max_inflight_blocks", FieldKind::Int),
+    ("read_only_call_limit_write_length", FieldKind::Int),
+    ("read_only_call_limit_read_length", FieldKind::Int),
+    ("read_only_call_limit_write_count", FieldKind::Int),
+    ("read_only_call_limit_read_count", FieldKind::Int),
+    ("read_only_call_limit_runtime", FieldKind::Int),
+    ("maximum_call_argument_size", FieldKind::Int),
+    ("download_interval", FieldKind::Int),
+    ("inv_sync_interval", FieldKind::Int),
+    ("public_ip_address", FieldKind::Str),
+    ("disable_inbound_walks", FieldKind::Bool),
+    ("disable_inbound_handshakes", FieldKind::Bool),
+    ("force_disconnect_interval", FieldKind::Int),
+    ("rpc_audit_log_path", FieldKind::Str),
+    ("rpc_audit_log_caller_privacy", FieldKind::Str),
+    ("rpc_audit_log_retention", FieldKind::Int),
+    ("require_rpc_api_keys", FieldKind::Bool),
+    ("ban_score_threshold", FieldKind::Int),
+    ("ban_duration", FieldKind::Int),
+    ("enable_port_mapping", FieldKind::Bool),
+    ("port_mapping_lifetime", FieldKind::Int),
+    ("enable_websocket_events", FieldKind::Bool),
+    ("max_websocket_clients", FieldKind::Int),
+    ("max_http_requests_per_connection", FieldKind::Int),
+    ("read_only_call_rate", FieldKind::Int),
+    ("read_only_call_burst", FieldKind::Int),
+    ("rpc_tls_cert_path", FieldKind::Str),
+    ("rpc_tls_key_path", FieldKind::Str),
+    ("rpc_tls_client_ca_path", FieldKind::Str),
+    ("rpc_admin_token", FieldKind::Str),
+    ("rpc_cors_allowed_origins", FieldKind::List),
+    ("rpc_cors_max_age", FieldKind::Int),
+    ("rpc_cache_max_age", FieldKind::Int),
+    ("rpc_immutable_cache_max_age", FieldKind::Int),
+    ("rpc_response_headers", FieldKind::List),
+    ("rpc_compression", FieldKind::Bool),
+    ("rpc_compression_min_size", FieldKind::Int),
+];
+
+const BLOCK_LIMIT_FIELDS: &[Field] = &[
+    ("write_length", FieldKind::Int),
+    ("read_length", FieldKind::Int),
+    ("write_count", FieldKind::Int),
+    ("read_count", FieldKind::Int),
+    ("runtime", FieldKind::Int),
+];
+
+const FAUCET_FIELDS: &[Field] = &[
+    ("seed", FieldKind::Str),
+    ("amount", FieldKind::Int),
+    ("fee", FieldKind::Int),
+    ("address_interval", FieldKind::Int),
+    ("caller_interval", FieldKind::Int),
+];
+
+const MEMPOOL_FIELDS: &[Field] = &[
+    ("max_tx_age_blocks", FieldKind::Int),
+    ("max_tx_age_secs", FieldKind::Int),
+    ("max_txs", FieldKind::Int),
+    ("strict_analysis", FieldKind::List),
+];
+
+const ATLAS_FIELDS: &[Field] = &[
+    ("contracts", FieldKind::List),
+    ("attachments_max_size", FieldKind::Int),
+    ("max_uninstantiated_attachments", FieldKind::Int),
+    ("uninstantiated_attachments_expire_after", FieldKind::Int),
+    ("max_inflight_attachments", FieldKind::Int),
+];
+
+const MINER_FIELDS: &[Field] = &[
+    ("dry_run", FieldKind::Bool),
+    ("commit_strategy", FieldKind::Str),
+    ("commit_amount", FieldKind::Int),
+    ("commit_percent", FieldKind::Int),
+    ("commit_escalation_percent", FieldKind::Int),
+    ("commit_window", FieldKind::Int),
+];
+
+/// Every key a config file may contain.  Must be kept in step with `ConfigFile`.
+pub const CONFIG_SCHEMA: &[Field] = &[
+    ("burnchain", FieldKind::Table(BURNCHAIN_FIELDS)),
+    ("node", FieldKind::Table(NODE_FIELDS)),
+    ("mstx_balance", FieldKind::Tables(INITIAL_BALANCE_FIELDS)),
+    ("events_observer", FieldKind::Tables(EVENT_OBSERVER_FIELDS)),
+    (
+        "connection_options",
+        FieldKind::Table(CONNECTION_OPTIONS_FIELDS),
+    ),
+    ("block_limit", FieldKind::Table(BLOCK_LIMIT_FIELDS)),
+    ("faucet", FieldKind::Table(FAUCET_FIELDS)),
+    ("mempool", FieldKind::Table(MEMPOOL_FIELDS)),
+    ("atlas", FieldKind::Table(ATLAS_FIELDS)),
+    ("miner", FieldKind::Table(MINER_FIELDS)),
+];
+
+/// Name of the environment variable overriding the key at `path`, e.g. `STACKS_NODE_RPC_BIND`
+pub fn env_var_name(path: &[&str]) -> String {
+    format!("STACKS_{}", path.join("_").to_uppercase())
+}
+
+/// Overwrite keys in a parsed config file with the environment variables set for them.
+/// Sections are only created if one of their keys is overridden.
+pub fn apply_env_overrides(config: &mut Table, problems: &mut Vec<String>) {
+    apply_overrides(config, &|var_name| env::var(var_name).ok(), problems);
+}
+
+/// Overwrite keys in a parsed config file with the values `lookup` returns for their
+/// environment variable names
+pub fn apply_overrides(
+    config: &mut Table,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    problems: &mut Vec<String>,
+) {
+    apply_overrides_to(config, CONFIG_SCHEMA, &mut vec![], lookup, problems);
+}
+
+fn apply_overrides_to(
+    table: &mut Table,
+    fields: &[Field],
+    path: &mut Vec<&'static str>,
+    lookup: &dyn Fn(&str) -> Option<String>,
+    problems: &mut Vec<String>,
+) {
+    for (name, kind) in fields.iter() {
+        path.push(*name);
+        match kind {
+            FieldKind::Table(subfields) => match table.get_mut(*name) {
+                Some(Value::Table(ref mut section)) => {
+                    apply_overrides_to(section, subfields, path, lookup, problems);
+                }
+                Some(_) => {}
+                None => {
+                    let mut section = Table::new();
+                    apply_overrides_to(&mut section, subfields, path, lookup, problems);
+                    if !section.is_empty() {
+                        table.insert(name.to_string(), Value::Table(section));
+                    }
+                }
+            },
+            _ => {
+                let var_name = env_var_name(path);
+                if let Some(raw) = lookup(&var_name) {
+                    match parse_env_value(kind, &raw) {
+                        Ok(value) => {
+                            table.insert(name.to_string(), value);
+                        }
+                        Err(e) => problems.push(format!("{}: {}", var_name, e)),
+                    }
+                }
+            }
+        }
+        path.pop();
+    }
+}
+
+fn parse_env_value(kind: &FieldKind, raw: &str) -> Result<Value, String> {
+    match kind {
+        FieldKind::Str => Ok(Value::String(raw.to_string())),
+        FieldKind::Int => raw
+            .parse::<i64>()
+            .map(Value::Integer)
+            .map_err(|_| format!("expected an integer, got '{}'", raw)),
+        FieldKind::Bool => raw
+            .parse::<bool>()
+            .map(Value::Boolean)
+            .map_err(|_| format!("expected true or false, got '{}'", raw)),
+        FieldKind::List | FieldKind::Tables(_) | FieldKind::Table(_) => {
+            toml::from_str::<Table>(&format!("value = {}", raw))
+                .ok()
+                .and_then(|mut parsed| parsed.remove("value"))
+                .ok_or_else(|| format!("expected a TOML inline array, got '{}'", raw))
+        }
+    }
+}
+
+/// Report every key in a parsed config file that the node does not read, with the key it was
+/// most likely meant to be.
+pub fn check_unknown_keys(config: &Table) -> Vec<String> {
+    let mut problems = vec![];
+    check_table_keys(config, CONFIG_SCHEMA, "", &mut problems);
+    problems
+}
+
+fn check_table_keys(table: &Table, fields: &[Field], prefix: &str, problems: &mut Vec<String>) {
+    for (key, value) in table.iter() {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match fields.iter().find(|(name, _)| *name == key.as_str()) {
+            Some((_, FieldKind::Table(subfields))) => {
+                if let Value::Table(section) = value {
+                    check_table_keys(section, subfields, &path, problems);
+                }
+            }
+            Some((_, FieldKind::Tables(subfields))) => {
+                if let Value::Array(entries) = value {
+                    for (i, entry) in entries.iter().enumerate() {
+                        if let Value::Table(section) = entry {
+                            let entry_path = format!("{}[{}]", path, i);
+                            check_table_keys(section, subfields, &entry_path, problems);
+                        }
+                    }
+                }
+            }
+            Some(_) => {}
+            None => match suggest_key(key, fields) {
+                Some(suggestion) => problems.push(format!(
+                    "unknown key `{}` (did you mean `{}`?)",
+                    path, suggestion
+                )),
+                None => problems.push(format!("unknown key `{}`", path)),
+            },
+        }
+    }
+}
+
+/// The closest key to `key` in the same table, or failing that, the section that does have a
+/// key named `key`.
+fn suggest_key(key: &str, fields: &[Field]) -> Option<String> {
+    let max_distance = std::cmp::max(1, key.len() / 3);
+    let closest = fields
+        .iter()
+        .map(|(name, _)| (edit_distance(key, name), name))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance);
+    if let Some((_, name)) = closest {
+        return Some(name.to_string());
+    }
+
+    for (section, kind) in CONFIG_SCHEMA.iter() {
+        match kind {
+            FieldKind::Table(subfields) | FieldKind::Tables(subfields) => {
+                if subfields.iter().any(|(name, _)| *name == key) {
+                    return Some(format!("{}.{}", section, key));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Levenshtein distance between two keys
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + if ca == *cb { 0 } else { 1 };
+            cur[j + 1] = substitution.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+/// Check that `addr` is a `host:port` the node can bind or connect to
+fn check_host_port(key: &str, addr: &str, problems: &mut Vec<String>) {
+    let mut parts = addr.rsplitn(2, ':');
+    let port = parts.next().unwrap_or("");
+    let host = parts.next().unwrap_or("");
+    if host.is_empty() {
+        problems.push(format!("{}: expected host:port, got '{}'", key, addr));
+        return;
+    }
+    match port.parse::<u16>() {
+        Ok(0) | Err(_) => problems.push(format!(
+            "{}: port must be between 1 and 65535, got '{}'",
+            key, port
+        )),
+        Ok(_) => {}
+    }
+}
+
+fn check_port(key: &str, port: Option<u16>, problems: &mut Vec<String>) {
+    if port == Some(0) {
+        problems.push(format!("{}: port must be between 1 and 65535, got 0", key));
+    }
+}
+
+fn check_nonzero(key: &str, value: Option<u64>, problems: &mut Vec<String>) {
+    if value == Some(0) {
+        problems.push(format!("{}: must be greater than 0", key));
+    }
+}
+
+/// Check that the directory a file will be written into exists
+fn check_parent_dir(key: &str, path: &str, problems: &mut Vec<String>) {
+    if path.is_empty() {
+        problems.push(format!("{}: must not be empty", key));
+        return;
+    }
+    match Path::new(path).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() && !parent.is_dir() => problems.push(
+            format!("{}: directory {} does not exist", key, parent.display()),
+        ),
+        _ => {}
+    }
+}
+
+fn check_hex(key: &str, value: &str, problems: &mut Vec<String>) {
+    if hex_bytes(value).is_err() {
+        problems.push(format!("{}: expected a hex string", key));
+    }
+}
+
+/// Check the values in a config file, returning a description of each one the node would
+/// refuse to start with.
+pub fn validate(config: &ConfigFile) -> Vec<String> {
+    let mut problems = vec![];
+
+    if let Some(ref node) = config.node {
+        for (key, dir) in [
+            ("node.working_dir", &node.working_dir),
+            ("node.burnchain_dir", &node.burnchain_dir),
+            ("node.chainstate_dir", &node.chainstate_dir),
+        ]
+        .iter()
+        {
+            if let Some(ref dir) = dir {
+                if dir.is_empty() {
+                    problems.push(format!("{}: must not be empty", key));
+                } else if Path::new(dir).exists() && !Path::new(dir).is_dir() {
+                    problems.push(format!("{}: {} exists and is not a directory", key, dir));
+                }
+            }
+        }
+        for (key, db_path) in [
+            ("node.mempool_db_path", &node.mempool_db_path),
+            ("node.peer_db_path", &node.peer_db_path),
+            ("node.keychain_path", &node.keychain_path),
+        ]
+        .iter()
+        {
+            if let Some(ref db_path) = db_path {
+                if db_path.is_empty() {
+                    problems.push(format!("{}: must not be empty", key));
+                } else if Path::new(db_path).is_dir() {
+                    problems.push(format!("{}: {} is a directory", key, db_path));
+                }
+            }
+        }
+        if let Some(ref seed) = node.seed {
+            check_hex("node.seed", seed, &mut problems);
+        }
+        if node.keychain_passphrase.as_ref().map(|p| p.is_empty()) == Some(true) {
+            problems.push("node.keychain_passphrase: must not be empty".to_string());
+        }
+        if let Some(ref seed) = node.local_peer_seed {
+            check_hex("node.local_peer_seed", seed, &mut problems);
+        }
+        for (key, addr) in [
+            ("node.rpc_bind", &node.rpc_bind),
+            ("node.p2p_bind", &node.p2p_bind),
+            ("node.p2p_address", &node.p2p_address),
+            ("node.prometheus_bind", &node.prometheus_bind),
+        ]
+        .iter()
+        {
+            if let Some(addr) = addr {
+                check_host_port(key, addr, &mut problems);
+            }
+        }
+        if let Some(ref bootstrap_node) = node.bootstrap_node {
+            match bootstrap_node.split('@').collect::<Vec<_>>()[..] {
+                [public_key, peer_addr] => {
+                    if Secp256k1PublicKey::from_hex(public_key).is_err() {
+                        problems.push(format!(
+                            "node.bootstrap_node: invalid public key '{}'",
+                            public_key
+                        ));
+                    }
+                    check_host_port("node.bootstrap_node", peer_addr, &mut problems);
+                }
+                _ => problems.push(format!(
+                    "node.bootstrap_node: expected <public key>@<host>:<port>, got '{}'",
+                    bootstrap_node
+                )),
+            }
+        }
+        if let Some(ref deny_nodes) = node.deny_nodes {
+            for deny_node in deny_nodes.split(',').filter(|part| part.len() > 0) {
+                check_host_port("node.deny_nodes", deny_node, &mut problems);
+            }
+        }
+        if let Some(ref chain_id) = node.chain_id {
+            if let Err(e) = parse_network_id(chain_id) {
+                problems.push(format!("node.chain_id: {}", e));
+            }
+        }
+        if let Some(ref peer_version) = node.peer_version {
+            if let Err(e) = parse_network_id(peer_version) {
+                problems.push(format!("node.peer_version: {}", e));
+            }
+        }
+        if let Some(ref log_level) = node.log_level {
+            if log_level.parse::<slog::Level>().is_err() {
+                problems.push(format!(
+                    "node.log_level: '{}' is not one of critical, error, warn, info, debug, trace",
+                    log_level
+                ));
+            }
+        }
+        for spec in node.log_modules.iter().flatten() {
+            if let Err(e) = log::parse_module_loglevel(spec) {
+                problems.push(format!("node.log_modules: {}", e));
+            }
+        }
+        if let Some(ref value_store) = node.clarity_value_store {
+            if value_store != "sqlite" && value_store != "rocksdb" {
+                problems.push(format!(
+                    "node.clarity_value_store: '{}' is not one of sqlite, rocksdb",
+                    value_store
+                ));
+            }
+        }
+        check_nonzero(
+            "node.microblock_frequency",
+            node.microblock_frequency,
+            &mut problems,
+        );
+    }
+
+    if let Some(ref burnchain) = config.burnchain {
+        let mode = burnchain
+            .mode
+            .as_ref()
+            .map(|m| m.as_str())
+            .unwrap_or("mocknet");
+        if !BURNCHAIN_MODES.contains(&mode) {
+            problems.push(format!(
+                "burnchain.mode: '{}' is not one of {}",
+                mode,
+                BURNCHAIN_MODES.join(", ")
+            ));
+        }
+        match burnchain.local_mining_public_key {
+            Some(ref public_key) => {
+                if Secp256k1PublicKey::from_hex(public_key).is_err() {
+                    problems.push("burnchain.local_mining_public_key: invalid public key".into());
+                }
+            }
+            None if mode == "helium" => problems
+                .push("burnchain.local_mining_public_key: required in helium mode".to_string()),
+            None => {}
+        }
+        check_port("burnchain.peer_port", burnchain.peer_port, &mut problems);
+        check_port("burnchain.rpc_port", burnchain.rpc_port, &mut problems);
+        for (i, endpoint) in burnchain.endpoints.iter().flatten().enumerate() {
+            if endpoint.peer_host.is_empty() {
+                problems.push(format!(
+                    "burnchain.endpoints[{}].peer_host: must not be empty",
+                    i
+                ));
+            }
+            check_port(
+                &format!("burnchain.endpoints[{}].peer_port", i),
+                endpoint.peer_port,
+                &mut problems,
+            );
+            check_port(
+                &format!("burnchain.endpoints[{}].rpc_port", i),
+                endpoint.rpc_port,
+                &mut problems,
+            );
+        }
+        check_nonzero(
+            "burnchain.burn_fee_cap",
+            burnchain.burn_fee_cap,
+            &mut problems,
+        );
+        check_nonzero(
+            "burnchain.burnchain_op_tx_fee",
+            burnchain.burnchain_op_tx_fee,
+            &mut problems,
+        );
+        if let (Some(max_rbf_fee), Some(tx_fee)) =
+            (burnchain.max_rbf_fee, burnchain.burnchain_op_tx_fee)
+        {
+            if max_rbf_fee < tx_fee {
+                problems.push(format!(
+                    "burnchain.max_rbf_fee: must be at least burnchain.burnchain_op_tx_fee ({})",
+                    tx_fee
+                ));
+            }
+        }
+        check_nonzero(
+            "burnchain.poll_time_secs",
+            burnchain.poll_time_secs,
+            &mut problems,
+        );
+        if burnchain.timeout == Some(0) {
+            problems.push("burnchain.timeout: must be greater than 0".to_string());
+        }
+        if let Some(ref path) = burnchain.spv_headers_path {
+            check_parent_dir("burnchain.spv_headers_path", path, &mut problems);
+        }
+    }
+
+    for (i, balance) in config.mstx_balance.iter().flatten().enumerate() {
+        if PrincipalData::parse_standard_principal(&balance.address).is_err() {
+            problems.push(format!(
+                "mstx_balance[{}].address: invalid address '{}'",
+                i, balance.address
+            ));
+        }
+    }
+
+    for (i, observer) in config.events_observer.iter().flatten().enumerate() {
+        if observer.endpoint.is_empty() {
+            problems.push(format!(
+                "events_observer[{}].endpoint: must not be empty",
+                i
+            ));
+        }
+        for key in observer.events_keys.iter() {
+            if EventKeyType::from_string(key).is_none() {
+                problems.push(format!(
+                    "events_observer[{}].events_keys: invalid event key '{}'",
+                    i, key
+                ));
+            }
+        }
+    }
+
+    if let Some(ref opts) = config.connection_options {
+        if let Some(ref addr) = opts.public_ip_address {
+            if addr.parse::<std::net::SocketAddr>().is_err() {
+                problems.push(format!(
+                    "connection_options.public_ip_address: expected ip:port, got '{}'",
+                    addr
+                ));
+            }
+        }
+        if let Some(ref mode) = opts.rpc_audit_log_caller_privacy {
+            if AuditCallerPrivacy::from_str(mode).is_none() {
+                problems.push(format!(
+                    "connection_options.rpc_audit_log_caller_privacy: '{}' is not one of full, anonymized, hashed, omitted",
+                    mode
+                ));
+            }
+        }
+        if let Some(ref path) = opts.rpc_audit_log_path {
+            check_parent_dir("connection_options.rpc_audit_log_path", path, &mut problems);
+        }
+        check_nonzero("connection_options.timeout", opts.timeout, &mut problems);
+        check_nonzero(
+            "connection_options.num_neighbors",
+            opts.num_neighbors,
+            &mut problems,
+        );
+    }
+
+    if let Some(ref faucet) = config.faucet {
+        if Secp256k1PrivateKey::from_hex(&faucet.seed).is_err() {
+            problems.push("faucet.seed: invalid private key".to_string());
+        }
+    }
+
+    if let Some(ref mempool) = config.mempool {
+        if let Some(ref lints) = mempool.strict_analysis {
+            if let Err(e) = StrictProfile::from_lint_names(lints) {
+                problems.push(format!("mempool.strict_analysis: {}", e));
+            }
+        }
+    }
+
+    if let Some(ref atlas) = config.atlas {
+        for contract_id in atlas.contracts.iter().flatten() {
+            if QualifiedContractIdentifier::parse(contract_id).is_err() {
+                problems.push(format!(
+                    "atlas.contracts: invalid contract identifier '{}'",
+                    contract_id
+                ));
+            }
+        }
+    }
+
+    if let Some(ref miner) = config.miner {
+        let is_miner = config.node.as_ref().and_then(|node| node.miner) == Some(true);
+        if miner.dry_run == Some(true) && !is_miner {
+            problems.push("miner.dry_run: requires node.miner = true".to_string());
+        }
+        if let Some(ref strategy) = miner.commit_strategy {
+            if !COMMIT_STRATEGIES.contains(&strategy.as_str()) {
+                problems.push(format!(
+                    "miner.commit_strategy: '{}' is not one of {}",
+                    strategy,
+                    COMMIT_STRATEGIES.join(", ")
+                ));
+            }
+        }
+        check_nonzero("miner.commit_amount", miner.commit_amount, &mut problems);
+        check_nonzero("miner.commit_percent", miner.commit_percent, &mut problems);
+        check_nonzero("miner.commit_window", miner.commit_window, &mut problems);
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_unknown_keys() {
+        let config: Table = toml::from_str(
+            r#"
+            [node]
+            rpc_bnd = "127.0.0.1:20443"
+            rpc_port = 18443
+
+            [burnchain]
+            mode = "neon"
+
+            [[events_observer]]
+            endpoint = "127.0.0.1:3700"
+            events_key = ["*"]
+
+            [bogus]
+            "#,
+        )
+        .unwrap();
+
+        let problems = check_unknown_keys(&config);
+        assert_eq!(
+            problems,
+            vec![
+                "unknown key `bogus`".to_string(),
+                "unknown key `events_observer[0].events_key` (did you mean `events_keys`?)"
+                    .to_string(),
+                "unknown key `node.rpc_bnd` (did you mean `rpc_bind`?)".to_string(),
+                "unknown key `node.rpc_port` (did you mean `burnchain.rpc_port`?)".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let mut vars = HashMap::new();
+        vars.insert("STACKS_ATLAS_MAX_INFLIGHT_ATTACHMENTS", "12");
+        vars.insert(
+            "STACKS_ATLAS_CONTRACTS",
+            r#"["ST000000000000000000002AMW42H.bns"]"#,
+        );
+        vars.insert("STACKS_FAUCET_AMOUNT", "lots");
+
+        let mut config: Table = toml::from_str("[node]\nminer = true\n").unwrap();
+        let mut problems = vec![];
+        apply_overrides(
+            &mut config,
+            &|var_name| vars.get(var_name).map(|v| v.to_string()),
+            &mut problems,
+        );
+
+        assert_eq!(
+            problems,
+            vec!["STACKS_FAUCET_AMOUNT: expected an integer, got 'lots'".to_string()]
+        );
+        let atlas = config["atlas"].as_table().unwrap();
+        assert_eq!(atlas["max_inflight_attachments"].as_integer(), Some(12));
+        assert_eq!(atlas["contracts"].as_array().unwrap().len(), 1);
+        assert_eq!(config["node"]["miner"].as_bool(), Some(true));
+        assert!(config.get("faucet").is_none());
+        assert_eq!(env_var_name(&["node", "rpc_bind"]), "STACKS_NODE_RPC_BIND");
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = ConfigFile::load_str(
+            r#"
+            [node]
+            rpc_bind = "127.0.0.1:0"
+            p2p_bind = "127.0.0.1"
+            chain_id = "0xnope"
+            log_modules = ["blockstack_lib::net=loud"]
+            clarity_value_store = "leveldb"
+            chainstate_dir = ""
+            peer_db_path = "/tmp"
+            keychain_passphrase = ""
+
+            [burnchain]
+            mode = "helium"
+            burn_fee_cap = 0
+            burnchain_op_tx_fee = 1000
+            max_rbf_fee = 500
+            spv_headers_path = "/no/such/dir/headers.dat"
+
+            [miner]
+            dry_run = true
+            commit_strategy = "greedy"
+            commit_window = 0
+            "#,
+        );
+        let problems = config.err().unwrap();
+        assert_eq!(
+            problems,
+            vec![
+                "node.chainstate_dir: must not be empty".to_string(),
+                "node.peer_db_path: /tmp is a directory".to_string(),
+                "node.keychain_passphrase: must not be empty".to_string(),
+                "node.rpc_bind: port must be between 1 and 65535, got '0'".to_string(),
+                "node.p2p_bind: expected host:port, got '127.0.0.1'".to_string(),
+                "node.chain_id: invalid hex number 'nope'".to_string(),
+                "node.log_modules: invalid log level 'loud' for module blockstack_lib::net"
+                    .to_string(),
+                "node.clarity_value_store: 'leveldb' is not one of sqlite, rocksdb".to_string(),
+                "burnchain.local_mining_public_key: required in helium mode".to_string(),
+                "burnchain.burn_fee_cap: must be greater than 0".to_string(),
+                "burnchain.max_rbf_fee: must be at least burnchain.burnchain_op_tx_fee (1000)"
+                    .to_string(),
+                "burnchain.spv_headers_path: directory /no/such/dir does not exist".to_string(),
+                "miner.dry_run: requires node.miner = true".to_string(),
+                "miner.commit_strategy: 'greedy' is not one of static, proportional, capped"
+                    .to_string(),
+                "miner.commit_window: must be greater than 0".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sample_configs_are_valid() {
+        for name in [
+            "Stacks.toml",
+            "conf/argon-follower-conf.toml",
+            "conf/krypton-follower-conf.toml",
+            "conf/local-follower-conf.toml",
+            "conf/local-leader-conf.toml",
+            "conf/local-second-miner.toml",
+            "conf/neon-follower-conf.toml",
+            "conf/xenon-miner-conf.toml",
+        ]
+        .iter()
+        {
+            let path = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), name);
+            if let Err(problems) = ConfigFile::load(&path) {
+                panic!("{} is invalid: {:?}", name, problems);
+            }
+        }
+    }
+}
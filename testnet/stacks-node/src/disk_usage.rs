@@ -0,0 +1,182 @@
+//! How much disk space each of the node's subsystems uses.
+//!
+//! The burnchain data, chain state, mempool and peer DB can each be kept somewhere else (see
+//! `node.burnchain_dir` and friends), so the node reports each one's usage separately.  The report
+//! is logged and exported as the `stacks_node_disk_usage_bytes` gauge every
+//! `node.disk_usage_report_interval` seconds.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use stacks::monitoring::update_disk_usage_gauge;
+
+use crate::shutdown::shutdown_requested;
+use crate::Config;
+
+/// Files SQLite keeps next to a DB file while it is open
+const SQLITE_SIDE_FILE_SUFFIXES: &[&str] = &["", "-journal", "-wal", "-shm"];
+
+/// How many bytes one subsystem uses
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiskUsage {
+    pub subsystem: &'static str,
+    pub path: String,
+    pub bytes: u64,
+}
+
+/// Total size of the files under `path`, leaving out those in `exclude`.  A path that doesn't
+/// exist takes up no space.
+fn dir_size(path: &Path, exclude: &[PathBuf]) -> io::Result<u64> {
+    if exclude.iter().any(|excluded| excluded == path) {
+        return Ok(0);
+    }
+    let md = match fs::symlink_metadata(path) {
+        Ok(md) => md,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    if !md.is_dir() {
+        return Ok(md.len());
+    }
+
+    let mut total = 0;
+    for entry in fs::read_dir(path)? {
+        total += dir_size(&entry?.path(), exclude)?;
+    }
+    Ok(total)
+}
+
+/// A SQLite DB file, and the files SQLite keeps next to it
+fn sqlite_files(db_path: &str) -> Vec<PathBuf> {
+    SQLITE_SIDE_FILE_SUFFIXES
+        .iter()
+        .map(|suffix| PathBuf::from(format!("{}{}", db_path, suffix)))
+        .collect()
+}
+
+fn files_size(paths: &[PathBuf]) -> io::Result<u64> {
+    let mut total = 0;
+    for path in paths.iter() {
+        total += dir_size(path, &[])?;
+    }
+    Ok(total)
+}
+
+/// Measure how much disk space each subsystem of the node configured by `conf` uses.  The
+/// mempool and peer DB aren't counted again if they are kept in another subsystem's directory.
+pub fn measure_disk_usage(conf: &Config) -> io::Result<Vec<DiskUsage>> {
+    let mempool_files = sqlite_files(&conf.get_mempool_db_path());
+    let peer_db_files = sqlite_files(&conf.get_peer_db_path());
+    let mut db_files = mempool_files.clone();
+    db_files.extend_from_slice(&peer_db_files);
+
+    let burnchain_path = conf.node.get_burnchain_path();
+    let chainstate_path = conf.get_chainstate_path();
+    Ok(vec![
+        DiskUsage {
+            subsystem: "burnchain",
+            bytes: dir_size(Path::new(&burnchain_path), &db_files)?,
+            path: burnchain_path,
+        },
+        DiskUsage {
+            subsystem: "chainstate",
+            bytes: dir_size(Path::new(&chainstate_path), &db_files)?,
+            path: chainstate_path,
+        },
+        DiskUsage {
+            subsystem: "mempool",
+            bytes: files_size(&mempool_files)?,
+            path: conf.get_mempool_db_path(),
+        },
+        DiskUsage {
+            subsystem: "peer_db",
+            bytes: files_size(&peer_db_files)?,
+            path: conf.get_peer_db_path(),
+        },
+    ])
+}
+
+/// Measure disk usage, and log and export it
+pub fn report_disk_usage(conf: &Config) {
+    let usage = match measure_disk_usage(conf) {
+        Ok(usage) => usage,
+        Err(e) => {
+            warn!("Failed to measure disk usage: {}", e);
+            return;
+        }
+    };
+    for subsystem in usage.iter() {
+        info!(
+            "Disk usage: {} uses {} bytes in {}",
+            subsystem.subsystem, subsystem.bytes, &subsystem.path
+        );
+        update_disk_usage_gauge(subsystem.subsystem, subsystem.bytes);
+    }
+}
+
+/// Report disk usage every `node.disk_usage_report_interval` seconds from a background thread,
+/// until the node is asked to stop.  Returns None if the report is turned off.
+pub fn spawn_disk_usage_reporter(conf: Config) -> Option<thread::JoinHandle<()>> {
+    let interval = conf.node.disk_usage_report_interval;
+    if interval == 0 {
+        return None;
+    }
+    Some(thread::spawn(move || {
+        let mut elapsed = interval;
+        while !shutdown_requested() {
+            if elapsed >= interval {
+                report_disk_usage(&conf);
+                elapsed = 0;
+            }
+            thread::sleep(Duration::from_secs(1));
+            elapsed += 1;
+        }
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_measure_disk_usage() {
+        let root = PathBuf::from("/tmp/stacks-node-tests/disk_usage");
+        if root.exists() {
+            fs::remove_dir_all(&root).unwrap();
+        }
+        fs::create_dir_all(root.join("working/burnchain/db")).unwrap();
+        fs::create_dir_all(root.join("nvme/chainstate/blocks")).unwrap();
+        fs::write(
+            root.join("working/burnchain/db/sortition.db"),
+            vec![0u8; 100],
+        )
+        .unwrap();
+        fs::write(root.join("nvme/chainstate/blocks/block"), vec![0u8; 1000]).unwrap();
+        fs::write(root.join("nvme/chainstate/mempool.db"), vec![0u8; 10]).unwrap();
+        fs::write(root.join("nvme/chainstate/mempool.db-wal"), vec![0u8; 5]).unwrap();
+        fs::write(root.join("working/peer_db.sqlite"), vec![0u8; 20]).unwrap();
+
+        let path_string = |rel_path: &str| root.join(rel_path).to_str().unwrap().to_string();
+        let mut conf = Config::default();
+        conf.node.working_dir = path_string("working");
+        conf.node.chainstate_dir = Some(path_string("nvme/chainstate"));
+
+        let usage: Vec<_> = measure_disk_usage(&conf)
+            .unwrap()
+            .into_iter()
+            .map(|usage| (usage.subsystem, usage.bytes))
+            .collect();
+        assert_eq!(
+            usage,
+            vec![
+                ("burnchain", 100),
+                ("chainstate", 1000),
+                ("mempool", 15),
+                ("peer_db", 20)
+            ]
+        );
+    }
+}
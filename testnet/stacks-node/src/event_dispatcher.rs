@@ -2,8 +2,10 @@ use stacks::chainstate::coordinator::BlockEventDispatcher;
 use stacks::chainstate::stacks::db::StacksHeaderInfo;
 use stacks::chainstate::stacks::events::StacksTransactionReceipt;
 use stacks::chainstate::stacks::StacksBlock;
+use std::cmp;
 use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
 use std::thread::sleep;
 use std::time::Duration;
 
@@ -20,10 +22,11 @@ use stacks::chainstate::stacks::events::{
 use stacks::chainstate::stacks::{
     db::accounts::MinerReward, db::MinerRewardInfo, StacksAddress, StacksBlockId, StacksTransaction,
 };
-use stacks::net::StacksMessageCodec;
+use stacks::net::websocket::{WebSocketEvent, WebSocketEventSender};
+use stacks::net::{MicroblocksData, StacksMessageCodec};
 use stacks::util::hash::bytes_to_hex;
 use stacks::vm::analysis::contract_interface_builder::build_contract_interface;
-use stacks::vm::types::{AssetIdentifier, QualifiedContractIdentifier, Value};
+use stacks::vm::types::{AssetIdentifier, PrincipalData, QualifiedContractIdentifier, Value};
 
 use super::config::{EventKeyType, EventObserverConfig};
 use super::node::ChainTip;
@@ -31,6 +34,7 @@ use super::node::ChainTip;
 #[derive(Debug, Clone)]
 struct EventObserver {
     endpoint: String,
+    max_retries: Option<u64>,
 }
 
 const STATUS_RESP_TRUE: &str = "success";
@@ -41,6 +45,11 @@ pub const PATH_MEMPOOL_TX_SUBMIT: &str = "new_mempool_tx";
 pub const PATH_BURN_BLOCK_SUBMIT: &str = "new_burn_block";
 pub const PATH_BLOCK_PROCESSED: &str = "new_block";
 
+/// Failed POSTs are retried after this long, doubling after each failure up to
+/// `MAX_RETRY_BACKOFF_MS`
+const INITIAL_RETRY_BACKOFF_MS: u64 = 1_000;
+const MAX_RETRY_BACKOFF_MS: u64 = 60_000;
+
 impl EventObserver {
     fn send_payload(&self, payload: &serde_json::Value, path: &str) {
         let body = match serde_json::to_vec(&payload) {
@@ -63,7 +72,8 @@ impl EventObserver {
             ))
         };
 
-        let backoff = Duration::from_millis((1.0 * 1_000.0) as u64);
+        let mut backoff_ms = INITIAL_RETRY_BACKOFF_MS;
+        let mut attempts: u64 = 0;
 
         loop {
             let body = body.clone();
@@ -76,7 +86,7 @@ impl EventObserver {
                 let stream = match TcpStream::connect(self.endpoint.clone()).await {
                     Ok(stream) => stream,
                     Err(err) => {
-                        println!("Event dispatcher: connection failed  - {:?}", err);
+                        warn!("Event dispatcher: connection failed  - {:?}", err);
                         return None;
                     }
                 };
@@ -84,7 +94,7 @@ impl EventObserver {
                 match client::connect(stream, req).await {
                     Ok(response) => Some(response),
                     Err(err) => {
-                        println!("Event dispatcher: rpc invokation failed  - {:?}", err);
+                        warn!("Event dispatcher: rpc invokation failed  - {:?}", err);
                         return None;
                     }
                 }
@@ -100,7 +110,20 @@ impl EventObserver {
                     );
                 }
             }
-            sleep(backoff);
+
+            attempts += 1;
+            if let Some(max_retries) = self.max_retries {
+                if attempts > max_retries {
+                    error!(
+                        "Event dispatcher: giving up on POST {} to {} after {} attempts",
+                        path, self.endpoint, attempts
+                    );
+                    return;
+                }
+            }
+
+            sleep(Duration::from_millis(backoff_ms));
+            backoff_ms = cmp::min(backoff_ms * 2, MAX_RETRY_BACKOFF_MS);
         }
     }
 
@@ -165,12 +188,6 @@ impl EventObserver {
             formatted_bytes
         };
 
-        let raw_result = {
-            let mut bytes = vec![];
-            result.consensus_serialize(&mut bytes).unwrap();
-            let formatted_bytes: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
-            formatted_bytes
-        };
         let contract_interface_json = {
             match &receipt.contract_analysis {
                 Some(analysis) => json!(build_contract_interface(analysis)),
@@ -181,7 +198,7 @@ impl EventObserver {
             "txid": format!("0x{}", tx.txid()),
             "tx_index": tx_index,
             "status": success,
-            "raw_result": format!("0x{}", raw_result.join("")),
+            "raw_result": format!("0x{}", result.serialize_to_hex()),
             "raw_tx": format!("0x{}", raw_tx.join("")),
             "contract_abi": contract_interface_json,
         })
@@ -249,16 +266,27 @@ impl EventObserver {
     }
 }
 
-#[derive(Clone)]
-pub struct EventDispatcher {
+/// The registered observers, and which of them each kind of event goes to
+#[derive(Clone, Default)]
+struct EventObservers {
     registered_observers: Vec<EventObserver>,
     contract_events_observers_lookup: HashMap<(QualifiedContractIdentifier, String), HashSet<u16>>,
     assets_observers_lookup: HashMap<AssetIdentifier, HashSet<u16>>,
+    contract_observers_lookup: HashMap<QualifiedContractIdentifier, HashSet<u16>>,
+    event_type_observers_lookup: HashMap<String, HashSet<u16>>,
     burn_block_observers_lookup: HashSet<u16>,
     mempool_observers_lookup: HashSet<u16>,
     stx_observers_lookup: HashSet<u16>,
     any_event_observers_lookup: HashSet<u16>,
+}
+
+/// Clones share their observers, so replacing them (e.g. on a config reload) takes effect in
+/// every thread dispatching events.
+#[derive(Clone)]
+pub struct EventDispatcher {
+    observers: Arc<RwLock<Arc<EventObservers>>>,
     boot_receipts: Vec<StacksTransactionReceipt>,
+    websocket_events: Option<WebSocketEventSender>,
 }
 
 impl BlockEventDispatcher for EventDispatcher {
@@ -303,17 +331,24 @@ impl BlockEventDispatcher for EventDispatcher {
 impl EventDispatcher {
     pub fn new() -> EventDispatcher {
         EventDispatcher {
-            registered_observers: vec![],
-            contract_events_observers_lookup: HashMap::new(),
-            assets_observers_lookup: HashMap::new(),
-            stx_observers_lookup: HashSet::new(),
-            any_event_observers_lookup: HashSet::new(),
-            burn_block_observers_lookup: HashSet::new(),
-            mempool_observers_lookup: HashSet::new(),
+            observers: Arc::new(RwLock::new(Arc::new(EventObservers::default()))),
             boot_receipts: vec![],
+            websocket_events: None,
         }
     }
 
+    /// The observers as of now.  Held without a lock, so a slow observer doesn't hold up
+    /// replacing them.
+    fn current_observers(&self) -> Arc<EventObservers> {
+        self.observers.read().unwrap().clone()
+    }
+
+    /// Also stream blocks, microblocks and mempool transactions to the RPC server's WebSocket
+    /// subscribers
+    pub fn set_websocket_events(&mut self, sender: WebSocketEventSender) {
+        self.websocket_events = Some(sender);
+    }
+
     pub fn process_burn_block(
         &self,
         burn_block: &BurnchainHeaderHash,
@@ -321,13 +356,18 @@ impl EventDispatcher {
         burns: u64,
     ) {
         // lazily assemble payload only if we have observers
-        let interested_observers: Vec<_> = self
+        let observers = self.current_observers();
+        let interested_observers: Vec<_> = observers
             .registered_observers
             .iter()
             .enumerate()
             .filter(|(obs_id, _observer)| {
-                self.burn_block_observers_lookup.contains(&(*obs_id as u16))
-                    || self.any_event_observers_lookup.contains(&(*obs_id as u16))
+                observers
+                    .burn_block_observers_lookup
+                    .contains(&(*obs_id as u16))
+                    || observers
+                        .any_event_observers_lookup
+                        .contains(&(*obs_id as u16))
             })
             .collect();
         if interested_observers.len() < 1 {
@@ -349,7 +389,16 @@ impl EventDispatcher {
         mature_rewards: Vec<MinerReward>,
         mature_rewards_info: Option<MinerRewardInfo>,
     ) {
-        let mut dispatch_matrix: Vec<HashSet<usize>> = self
+        if let Some(ref websocket_events) = self.websocket_events {
+            websocket_events.send(WebSocketEvent::from_block(
+                &chain_tip.block,
+                &chain_tip.metadata,
+                parent_index_hash,
+            ));
+        }
+
+        let observers = self.current_observers();
+        let mut dispatch_matrix: Vec<HashSet<usize>> = observers
             .registered_observers
             .iter()
             .map(|_| HashSet::new())
@@ -368,8 +417,9 @@ impl EventDispatcher {
             for event in receipt.events.iter() {
                 match event {
                     StacksTransactionEvent::SmartContractEvent(event_data) => {
-                        if let Some(observer_indexes) =
-                            self.contract_events_observers_lookup.get(&event_data.key)
+                        if let Some(observer_indexes) = observers
+                            .contract_events_observers_lookup
+                            .get(&event_data.key)
                         {
                             for o_i in observer_indexes {
                                 dispatch_matrix[*o_i as usize].insert(i);
@@ -380,43 +430,59 @@ impl EventDispatcher {
                     | StacksTransactionEvent::STXEvent(STXEventType::STXMintEvent(_))
                     | StacksTransactionEvent::STXEvent(STXEventType::STXBurnEvent(_))
                     | StacksTransactionEvent::STXEvent(STXEventType::STXLockEvent(_)) => {
-                        for o_i in &self.stx_observers_lookup {
+                        for o_i in &observers.stx_observers_lookup {
                             dispatch_matrix[*o_i as usize].insert(i);
                         }
                     }
                     StacksTransactionEvent::NFTEvent(NFTEventType::NFTTransferEvent(
                         event_data,
                     )) => {
-                        self.update_dispatch_matrix_if_observer_subscribed(
+                        observers.update_dispatch_matrix_if_observer_subscribed(
                             &event_data.asset_identifier,
                             i,
                             &mut dispatch_matrix,
                         );
                     }
                     StacksTransactionEvent::NFTEvent(NFTEventType::NFTMintEvent(event_data)) => {
-                        self.update_dispatch_matrix_if_observer_subscribed(
+                        observers.update_dispatch_matrix_if_observer_subscribed(
                             &event_data.asset_identifier,
                             i,
                             &mut dispatch_matrix,
                         );
                     }
                     StacksTransactionEvent::FTEvent(FTEventType::FTTransferEvent(event_data)) => {
-                        self.update_dispatch_matrix_if_observer_subscribed(
+                        observers.update_dispatch_matrix_if_observer_subscribed(
                             &event_data.asset_identifier,
                             i,
                             &mut dispatch_matrix,
                         );
                     }
                     StacksTransactionEvent::FTEvent(FTEventType::FTMintEvent(event_data)) => {
-                        self.update_dispatch_matrix_if_observer_subscribed(
+                        observers.update_dispatch_matrix_if_observer_subscribed(
                             &event_data.asset_identifier,
                             i,
                             &mut dispatch_matrix,
                         );
                     }
                 }
+                for contract_id in event_contracts(event) {
+                    if let Some(observer_indexes) =
+                        observers.contract_observers_lookup.get(contract_id)
+                    {
+                        for o_i in observer_indexes {
+                            dispatch_matrix[*o_i as usize].insert(i);
+                        }
+                    }
+                }
+                if let Some(observer_indexes) =
+                    observers.event_type_observers_lookup.get(event.type_name())
+                {
+                    for o_i in observer_indexes {
+                        dispatch_matrix[*o_i as usize].insert(i);
+                    }
+                }
                 events.push((!receipt.post_condition_aborted, tx_hash, event));
-                for o_i in &self.any_event_observers_lookup {
+                for o_i in &observers.any_event_observers_lookup {
                     dispatch_matrix[*o_i as usize].insert(i);
                 }
                 i += 1;
@@ -452,7 +518,7 @@ impl EventDispatcher {
                     .map(|event_id| &events[*event_id])
                     .collect();
 
-                self.registered_observers[observer_id].send(
+                observers.registered_observers[observer_id].send(
                     filtered_events,
                     chain_tip,
                     parent_index_hash,
@@ -465,14 +531,25 @@ impl EventDispatcher {
     }
 
     pub fn process_new_mempool_txs(&self, txs: Vec<StacksTransaction>) {
+        if let Some(ref websocket_events) = self.websocket_events {
+            for tx in txs.iter() {
+                websocket_events.send(WebSocketEvent::from_mempool_tx(tx));
+            }
+        }
+
         // lazily assemble payload only if we have observers
-        let interested_observers: Vec<_> = self
+        let observers = self.current_observers();
+        let interested_observers: Vec<_> = observers
             .registered_observers
             .iter()
             .enumerate()
             .filter(|(obs_id, _observer)| {
-                self.mempool_observers_lookup.contains(&(*obs_id as u16))
-                    || self.any_event_observers_lookup.contains(&(*obs_id as u16))
+                observers
+                    .mempool_observers_lookup
+                    .contains(&(*obs_id as u16))
+                    || observers
+                        .any_event_observers_lookup
+                        .contains(&(*obs_id as u16))
             })
             .collect();
         if interested_observers.len() < 1 {
@@ -486,10 +563,40 @@ impl EventDispatcher {
         }
     }
 
+    /// Only WebSocket subscribers hear about unconfirmed microblocks
+    pub fn process_new_microblocks(&self, microblocks: Vec<MicroblocksData>) {
+        if let Some(ref websocket_events) = self.websocket_events {
+            for mblocks_data in microblocks.iter() {
+                websocket_events.send(WebSocketEvent::from_microblocks(
+                    &mblocks_data.index_anchor_block,
+                    &mblocks_data.microblocks,
+                ));
+            }
+        }
+    }
+
     pub fn process_boot_receipts(&mut self, receipts: Vec<StacksTransactionReceipt>) {
         self.boot_receipts = receipts;
     }
 
+    /// Register an observer.  Clones of this dispatcher see it too.
+    pub fn register_observer(&mut self, conf: &EventObserverConfig) {
+        let mut observers = self.observers.write().unwrap();
+        Arc::make_mut(&mut *observers).register(conf);
+    }
+
+    /// Replace every registered observer with the given ones, in this dispatcher and all of
+    /// its clones.  Events already being sent still go to the old observers.
+    pub fn replace_observers(&self, confs: &[EventObserverConfig]) {
+        let mut replacement = EventObservers::default();
+        for conf in confs.iter() {
+            replacement.register(conf);
+        }
+        *self.observers.write().unwrap() = Arc::new(replacement);
+    }
+}
+
+impl EventObservers {
     fn update_dispatch_matrix_if_observer_subscribed(
         &self,
         asset_identifier: &AssetIdentifier,
@@ -503,11 +610,12 @@ impl EventDispatcher {
         }
     }
 
-    pub fn register_observer(&mut self, conf: &EventObserverConfig) {
+    fn register(&mut self, conf: &EventObserverConfig) {
         // let event_observer = EventObserver::new(&conf.address, conf.port);
         info!("Registering event observer at: {}", conf.endpoint);
         let event_observer = EventObserver {
             endpoint: conf.endpoint.clone(),
+            max_retries: conf.max_retries,
         };
 
         let observer_index = self.registered_observers.len() as u16;
@@ -550,6 +658,18 @@ impl EventDispatcher {
                         }
                     };
                 }
+                EventKeyType::ContractEvents(contract_id) => {
+                    self.contract_observers_lookup
+                        .entry(contract_id.clone())
+                        .or_insert_with(HashSet::new)
+                        .insert(observer_index);
+                }
+                EventKeyType::EventType(type_name) => {
+                    self.event_type_observers_lookup
+                        .entry(type_name.clone())
+                        .or_insert_with(HashSet::new)
+                        .insert(observer_index);
+                }
                 EventKeyType::AnyEvent => {
                     self.any_event_observers_lookup.insert(observer_index);
                 }
@@ -0,0 +1,77 @@
+//! Integrity checks over a stopped node's sortition DB and chain state.
+//!
+//! An unclean shutdown can leave a MARF trie half-written, or a Stacks block half-processed.
+//! The node checks for this when it starts, before anything else opens the databases, and repairs
+//! what it can:  the sortition DB is rewound below a damaged trie, and a half-processed block is
+//! rolled back so it gets processed again.  Damage that can't be repaired stops the node with a
+//! message saying so, instead of a panic further down the line.
+
+use std::fs;
+
+use stacks::chainstate::burn::db::sortdb::SortitionDB;
+use stacks::chainstate::stacks::db::StacksChainState;
+use stacks::util::db::IntegrityReport;
+
+use crate::Config;
+
+/// Check the sortition DB at `sortdb_path` and the node's chain state, covering the `depth` most
+/// recent tries and blocks of each.  Repairs them if `repair` is true.
+pub fn check_node_integrity(
+    conf: &Config,
+    sortdb_path: &str,
+    depth: u32,
+    repair: bool,
+) -> Result<IntegrityReport, String> {
+    let mut report = IntegrityReport::default();
+
+    if fs::metadata(sortdb_path).is_ok() {
+        let sortdb_report = SortitionDB::check_integrity(sortdb_path, depth, repair)
+            .map_err(|e| format!("Failed to check sortition DB: {}", e))?;
+        report.extend(sortdb_report);
+    }
+
+    let chainstate_report = StacksChainState::check_integrity(
+        false,
+        conf.node.chain_id,
+        &conf.get_chainstate_path(),
+        depth,
+        repair,
+    )
+    .map_err(|e| format!("Failed to check chain state: {}", e))?;
+    report.extend(chainstate_report);
+
+    Ok(report)
+}
+
+/// Run the integrity check the node does when it starts, and log what it found and repaired.
+/// Returns false if the node must not start.
+pub fn startup_integrity_check(conf: &Config, sortdb_path: &str) -> bool {
+    let depth = conf.node.integrity_check_depth;
+    if depth == 0 {
+        return true;
+    }
+
+    info!("Checking the integrity of the last {} blocks", depth);
+    let report = match check_node_integrity(conf, sortdb_path, depth, true) {
+        Ok(report) => report,
+        Err(e) => {
+            error!("{}", e);
+            return false;
+        }
+    };
+
+    for problem in report.problems.iter() {
+        warn!("Integrity check: {}", problem);
+    }
+    for repair in report.repairs.iter() {
+        warn!("Integrity check repaired: {}", repair);
+    }
+    if report.unrepairable.len() > 0 {
+        for damage in report.unrepairable.iter() {
+            error!("Integrity check: {}", damage);
+        }
+        error!("The node's databases are damaged beyond repair. Restore them from a snapshot with `stacks-node snapshot restore`, or delete the working directory and sync again.");
+        return false;
+    }
+    true
+}
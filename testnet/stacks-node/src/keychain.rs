@@ -1,4 +1,9 @@
 use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
 
 use super::operations::BurnchainOpSigner;
 
@@ -8,8 +13,21 @@ use stacks::chainstate::stacks::{
     StacksAddress, StacksPrivateKey, StacksPublicKey, StacksTransactionSigner, TransactionAuth,
 };
 use stacks::util::hash::Sha256Sum;
+use stacks::util::secretbox;
 use stacks::util::vrf::{VRFPrivateKey, VRFProof, VRFPublicKey, VRF};
 
+/// The key material a keychain keeps across restarts, so the miner can go on using the leader
+/// keys it registered, and never reuses a microblock key.  The keychain's own secret keys aren't
+/// part of it; they are derived from the node's seed every time.
+#[derive(Serialize, Deserialize)]
+struct KeychainState {
+    /// identifies the seed the keys were derived from
+    hashed_secret_state: String,
+    rotations: u64,
+    vrf_secret_keys: Vec<String>,
+    microblock_secret_key: Option<String>,
+}
+
 #[derive(Clone)]
 pub struct Keychain {
     secret_keys: Vec<StacksPrivateKey>,
@@ -123,6 +141,88 @@ impl Keychain {
         self.microblocks_secret_keys.last().cloned()
     }
 
+    /// The public keys of all the VRF keypairs generated so far, oldest first
+    pub fn get_vrf_public_keys(&self) -> Vec<VRFPublicKey> {
+        self.vrf_secret_keys
+            .iter()
+            .map(|sk| VRFPublicKey::from_private(sk))
+            .collect()
+    }
+
+    /// Encrypt the keychain's VRF keys and latest microblock key under `passphrase`, and write
+    /// them to `path`.  The old file is replaced atomically.  On Unix, only the owner can read
+    /// the new file.
+    pub fn save(&self, path: &str, passphrase: &str) -> Result<(), String> {
+        let state = KeychainState {
+            hashed_secret_state: self.hashed_secret_state.to_hex(),
+            rotations: self.rotations,
+            vrf_secret_keys: self.vrf_secret_keys.iter().map(|sk| sk.to_hex()).collect(),
+            microblock_secret_key: self.microblocks_secret_keys.last().map(|sk| sk.to_hex()),
+        };
+        let content = serde_json::to_vec(&state)
+            .map_err(|e| format!("Failed to serialize keychain: {}", e))?;
+        let sealed = secretbox::seal(passphrase.as_bytes(), &content);
+
+        let tmp_path = format!("{}.tmp", path);
+        let _ = fs::remove_file(&tmp_path);
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        options
+            .open(&tmp_path)
+            .and_then(|mut file| {
+                file.write_all(&sealed)?;
+                file.sync_all()
+            })
+            .map_err(|e| format!("Failed to write {}: {}", &tmp_path, e))?;
+        fs::rename(&tmp_path, path).map_err(|e| format!("Failed to write {}: {}", path, e))
+    }
+
+    /// Restore the key material saved at `path` with `save()`.  Returns false if there is no
+    /// such file yet.  Fails if the passphrase is wrong, or if the keys were saved by a
+    /// keychain with a different seed.
+    pub fn load(&mut self, path: &str, passphrase: &str) -> Result<bool, String> {
+        let sealed = match fs::read(path) {
+            Ok(sealed) => sealed,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(format!("Failed to read {}: {}", path, e)),
+        };
+        let content = secretbox::open(passphrase.as_bytes(), &sealed)
+            .map_err(|e| format!("Failed to decrypt {}: {}", path, e))?;
+        let state: KeychainState = serde_json::from_slice(&content)
+            .map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+
+        if state.hashed_secret_state != self.hashed_secret_state.to_hex() {
+            return Err(format!("{} holds the keys of a different seed", path));
+        }
+
+        let mut vrf_secret_keys = vec![];
+        for sk_hex in state.vrf_secret_keys.iter() {
+            let sk = VRFPrivateKey::from_hex(sk_hex)
+                .ok_or_else(|| format!("{} holds an invalid VRF key", path))?;
+            vrf_secret_keys.push(sk);
+        }
+        let microblocks_secret_keys = match state.microblock_secret_key {
+            Some(ref sk_hex) => {
+                let mut sk = StacksPrivateKey::from_hex(sk_hex)
+                    .map_err(|_| format!("{} holds an invalid microblock key", path))?;
+                sk.set_compress_public(true);
+                vec![sk]
+            }
+            None => vec![],
+        };
+
+        self.rotations = state.rotations;
+        self.vrf_map = vrf_secret_keys
+            .iter()
+            .map(|sk| (VRFPublicKey::from_private(sk), sk.clone()))
+            .collect();
+        self.vrf_secret_keys = vrf_secret_keys;
+        self.microblocks_secret_keys = microblocks_secret_keys;
+        Ok(true)
+    }
+
     pub fn sign_as_origin(&self, tx_signer: &mut StacksTransactionSigner) -> () {
         let num_keys = if self.secret_keys.len() < self.threshold as usize {
             self.secret_keys.len()
@@ -222,3 +322,67 @@ impl Keychain {
         BurnchainOpSigner::new(self.secret_keys[0], false)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::RngCore;
+    use stacks::util::hash::to_hex;
+
+    #[test]
+    fn test_keychain_save_load() {
+        let mut buf = [0u8; 8];
+        rand::thread_rng().fill_bytes(&mut buf);
+        let path = &format!("/tmp/stacks-node-test-keychain-{}.dat", to_hex(&buf));
+
+        let mut keychain = Keychain::default(vec![0x01; 32]);
+        assert_eq!(keychain.load(path, "passphrase"), Ok(false));
+
+        let vrf_pk_1 = keychain.rotate_vrf_keypair(100);
+        let vrf_pk_2 = keychain.rotate_vrf_keypair(200);
+        keychain.rotate_microblock_keypair();
+        let microblock_sk = keychain.rotate_microblock_keypair();
+        keychain.save(path, "passphrase").unwrap();
+
+        // only the owner can read the keys
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // the keys are not stored in the clear
+        let sealed = fs::read(path).unwrap();
+        let sealed_str = String::from_utf8_lossy(&sealed);
+        assert!(!sealed_str.contains(&microblock_sk.to_hex()));
+
+        let mut restored = Keychain::default(vec![0x01; 32]);
+        assert_eq!(restored.load(path, "passphrase"), Ok(true));
+        assert_eq!(
+            restored.get_vrf_public_keys(),
+            vec![vrf_pk_1.clone(), vrf_pk_2.clone()]
+        );
+        assert!(restored.generate_proof(&vrf_pk_2, &[0x02; 32]).is_some());
+        assert_eq!(restored.get_microblock_key(), Some(microblock_sk));
+
+        // rotations carry on where they left off
+        assert_eq!(
+            restored.rotate_vrf_keypair(300),
+            keychain.rotate_vrf_keypair(300)
+        );
+        assert_eq!(
+            restored.rotate_microblock_keypair(),
+            keychain.rotate_microblock_keypair()
+        );
+
+        let mut restored = Keychain::default(vec![0x01; 32]);
+        assert!(restored.load(path, "wrong passphrase").is_err());
+        assert!(restored.get_vrf_public_keys().is_empty());
+
+        let mut other_seed = Keychain::default(vec![0x02; 32]);
+        assert!(other_seed.load(path, "passphrase").is_err());
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,56 @@
+extern crate libc;
+extern crate rand;
+extern crate serde;
+
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
+#[macro_use]
+extern crate stacks;
+
+#[allow(unused_imports)]
+#[macro_use(o, slog_log, slog_trace, slog_debug, slog_info, slog_warn, slog_error)]
+extern crate slog;
+
+pub use stacks::util;
+
+pub mod monitoring;
+
+pub mod builder;
+pub mod burnchains;
+pub mod config;
+pub mod config_schema;
+pub mod disk_usage;
+pub mod event_dispatcher;
+pub mod integrity;
+pub mod keychain;
+pub mod miner_state;
+pub mod miner_strategy;
+pub mod neon_node;
+pub mod node;
+pub mod operations;
+pub mod reload;
+pub mod run_loop;
+pub mod shutdown;
+pub mod snapshot;
+pub mod syncctl;
+pub mod tenure;
+
+pub use self::builder::{NodeBuilder, StacksNode};
+pub use self::burnchains::{
+    BitcoinRegtestController, BurnchainController, BurnchainTip, MockBurnchainController,
+    MocknetController,
+};
+pub use self::config::{Config, ConfigFile};
+pub use self::event_dispatcher::EventDispatcher;
+pub use self::keychain::Keychain;
+pub use self::neon_node::{InitializedNeonNode, NeonGenesisNode};
+pub use self::node::{ChainTip, Node};
+pub use self::run_loop::{helium, neon};
+pub use self::tenure::Tenure;
+
+#[cfg(test)]
+pub mod tests;
@@ -1,13 +1,6 @@
 extern crate libc;
-extern crate rand;
-extern crate serde;
+extern crate stacks_node;
 
-#[macro_use]
-extern crate lazy_static;
-#[macro_use]
-extern crate serde_derive;
-#[macro_use]
-extern crate serde_json;
 #[macro_use]
 extern crate stacks;
 
@@ -17,34 +10,20 @@ extern crate slog;
 
 pub use stacks::util;
 
-pub mod monitoring;
-
-pub mod burnchains;
-pub mod config;
-pub mod event_dispatcher;
-pub mod keychain;
-pub mod neon_node;
-pub mod node;
-pub mod operations;
-pub mod run_loop;
-pub mod syncctl;
-pub mod tenure;
-
-pub use self::burnchains::{
-    BitcoinRegtestController, BurnchainController, BurnchainTip, MocknetController,
-};
-pub use self::config::{Config, ConfigFile};
-pub use self::event_dispatcher::EventDispatcher;
-pub use self::keychain::Keychain;
-pub use self::neon_node::{InitializedNeonNode, NeonGenesisNode};
-pub use self::node::{ChainTip, Node};
-pub use self::run_loop::{helium, neon};
-pub use self::tenure::Tenure;
+use stacks_node::integrity::check_node_integrity;
+use stacks_node::snapshot::{create_snapshot, restore_snapshot};
+use stacks_node::{BitcoinRegtestController, Config, ConfigFile, NodeBuilder};
 
 use pico_args::Arguments;
+use stacks::burnchains::db::BurnchainDB;
+use stacks::chainstate::burn::db::sortdb::SortitionDB;
+use stacks::net::apikeys::RPCApiKeys;
+use stacks::util::get_epoch_time_secs;
+use stacks::util::log::{self, LogFormat};
 use std::env;
 
 use std::convert::TryInto;
+use std::fs;
 use std::panic;
 use std::process;
 
@@ -74,8 +53,23 @@ fn main() {
     }));
 
     let mut args = Arguments::from_env();
+    let log_format: Option<String> = args.opt_value_from_str("--log-format").unwrap();
+    if let Some(log_format) = log_format {
+        match log_format.parse::<LogFormat>() {
+            Ok(LogFormat::Json) if !cfg!(feature = "slog_json") => {
+                eprintln!("--log-format json requires a build with the slog_json feature");
+                process::exit(1);
+            }
+            Ok(log_format) => log::set_log_format(log_format),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+    }
     let subcommand = args.subcommand().unwrap().unwrap_or_default();
 
+    let mut config_path_opt = None;
     let config_file = match subcommand.as_str() {
         "mocknet" => {
             args.finish().unwrap();
@@ -105,7 +99,32 @@ fn main() {
             let config_path: String = args.value_from_str("--config").unwrap();
             args.finish().unwrap();
             println!("==> {}", config_path);
-            ConfigFile::from_path(&config_path)
+            let config_file = load_config_file(&config_path);
+            config_path_opt = Some(config_path);
+            config_file
+        }
+        "check-config" => {
+            let config_path: String = args.value_from_str("--config").unwrap();
+            args.finish().unwrap();
+            load_config_file(&config_path);
+            println!("{}: ok", config_path);
+            return;
+        }
+        "apikeys" => {
+            apikeys_main(args);
+            return;
+        }
+        "snapshot" => {
+            snapshot_main(args);
+            return;
+        }
+        "rewind" => {
+            rewind_main(args);
+            return;
+        }
+        "check-integrity" => {
+            check_integrity_main(args);
+            return;
         }
         "version" => {
             println!(
@@ -124,6 +143,8 @@ fn main() {
     };
 
     let conf = Config::from_config_file(config_file);
+    log::set_loglevel(conf.node.log_level);
+    log::set_module_loglevels(conf.node.log_modules.clone());
     debug!("node configuration {:?}", &conf.node);
     debug!("burnchain configuration {:?}", &conf.burnchain);
     debug!("connection configuration {:?}", &conf.connection_options);
@@ -131,21 +152,207 @@ fn main() {
 
     let num_round: u64 = 0; // Infinite number of rounds
 
-    if conf.burnchain.mode == "helium" || conf.burnchain.mode == "mocknet" {
-        let mut run_loop = helium::RunLoop::new(conf);
-        if let Err(e) = run_loop.start(num_round) {
-            warn!("Helium runloop exited: {}", e);
+    let mut builder = NodeBuilder::from_config(conf);
+    if let Some(ref config_path) = config_path_opt {
+        builder = builder.with_config_path(config_path);
+    }
+
+    let mut node = match builder.build() {
+        Ok(node) => node,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = node.start(num_round) {
+        warn!("{}", e);
+        return;
+    }
+}
+
+/// Load a config file, or list everything wrong with it and exit.
+fn load_config_file(config_path: &str) -> ConfigFile {
+    match ConfigFile::load(config_path) {
+        Ok(config_file) => config_file,
+        Err(problems) => {
+            eprintln!("Invalid config file {}:", config_path);
+            for problem in problems.iter() {
+                eprintln!("  {}", problem);
+            }
+            process::exit(1);
+        }
+    }
+}
+
+/// Manage the API keys the node's RPC interface requires, if it was configured with
+/// `require_rpc_api_keys = true`.
+fn apikeys_main(mut args: Arguments) {
+    let command = args.subcommand().unwrap().unwrap_or_default();
+    let config_path: String = args.value_from_str("--config").unwrap();
+    let conf = Config::from_config_file(load_config_file(&config_path));
+    fs::create_dir_all(&conf.node.working_dir).expect("Failed to create working directory");
+    let api_keys =
+        RPCApiKeys::open(&conf.get_rpc_api_keys_db_path()).expect("Failed to open RPC API key DB");
+
+    match command.as_str() {
+        "add" => {
+            let label: String = args.value_from_str("--label").unwrap();
+            let requests_per_minute: u64 = args
+                .opt_value_from_str("--requests-per-minute")
+                .unwrap()
+                .unwrap_or(0);
+            args.finish().unwrap();
+            match api_keys.add(&label, requests_per_minute, get_epoch_time_secs()) {
+                Ok(key) => println!("{}", key),
+                Err(e) => {
+                    eprintln!("Failed to add API key for '{}': {}", label, e);
+                    process::exit(1);
+                }
+            }
+        }
+        "revoke" => {
+            let label: String = args.value_from_str("--label").unwrap();
+            args.finish().unwrap();
+            if !api_keys
+                .revoke(&label, get_epoch_time_secs())
+                .expect("Failed to revoke API key")
+            {
+                eprintln!("No active API key for '{}'", label);
+                process::exit(1);
+            }
+        }
+        "list" => {
+            args.finish().unwrap();
+            println!("label\trequests_per_minute\tcreated_at\trevoked_at\ttotal_requests\trejected_requests\tlast_used_at");
+            for info in api_keys.list().expect("Failed to list API keys") {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    info.label,
+                    info.requests_per_minute,
+                    info.created_at,
+                    info.revoked_at
+                        .map(|t| t.to_string())
+                        .unwrap_or_else(|| "-".to_string()),
+                    info.total_requests,
+                    info.rejected_requests,
+                    info.last_used_at
+                );
+            }
+        }
+        _ => {
+            print_help();
+        }
+    }
+}
+
+/// Create or restore a chainstate snapshot.  The node must not be running.
+fn snapshot_main(mut args: Arguments) {
+    let command = args.subcommand().unwrap().unwrap_or_default();
+    let config_path: String = args.value_from_str("--config").unwrap();
+    let conf = Config::from_config_file(load_config_file(&config_path));
+
+    let result = match command.as_str() {
+        "create" => {
+            let output: String = args.value_from_str("--output").unwrap();
+            args.finish().unwrap();
+            create_snapshot(&conf, &output)
+        }
+        "restore" => {
+            let input: String = args.value_from_str("--input").unwrap();
+            args.finish().unwrap();
+            restore_snapshot(&conf, &input)
+        }
+        _ => {
+            print_help();
             return;
         }
-    } else if conf.burnchain.mode == "neon"
-        || conf.burnchain.mode == "argon"
-        || conf.burnchain.mode == "krypton"
-        || conf.burnchain.mode == "xenon"
+    };
+
+    match result {
+        Ok(manifest) => {
+            println!(
+                "{}\t{}\t{}\t{} files",
+                &command,
+                manifest.tip.burn_block_height,
+                &manifest.tip.consensus_hash,
+                manifest.files.len()
+            );
+        }
+        Err(e) => {
+            eprintln!("Failed to {} snapshot: {}", &command, e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Rewind a stopped node's burnchain state to a burn block height.  Everything above it is
+/// downloaded and re-evaluated the next time the node starts.
+fn rewind_main(mut args: Arguments) {
+    let config_path: String = args.value_from_str("--config").unwrap();
+    let burn_height: u64 = args.value_from_str("--burn-height").unwrap();
+    args.finish().unwrap();
+    let conf = Config::from_config_file(load_config_file(&config_path));
+    let burnchain = BitcoinRegtestController::new_dummy(conf).get_burnchain();
+
+    let num_sortitions = match SortitionDB::rewind(&burnchain.get_db_path(), burn_height) {
+        Ok(n) => n,
+        Err(e) => {
+            eprintln!("Failed to rewind sortition DB: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let num_blocks = match BurnchainDB::open(&burnchain.get_burnchaindb_path(), true)
+        .and_then(|mut burnchain_db| burnchain_db.rewind(burn_height))
     {
-        let mut run_loop = neon::RunLoop::new(conf);
-        run_loop.start(num_round, None);
-    } else {
-        println!("Burnchain mode '{}' not supported", conf.burnchain.mode);
+        Ok(n) => n,
+        Err(e) => {
+            eprintln!("Failed to rewind burnchain DB: {}", e);
+            process::exit(1);
+        }
+    };
+
+    println!(
+        "rewind\t{}\t{} sortitions\t{} burnchain blocks",
+        burn_height, num_sortitions, num_blocks
+    );
+}
+
+/// Check a stopped node's sortition DB and chain state for damage from an unclean shutdown, and
+/// optionally repair it.  Exits non-zero if anything is left unrepaired.
+fn check_integrity_main(mut args: Arguments) {
+    let config_path: String = args.value_from_str("--config").unwrap();
+    let depth: Option<u32> = args.opt_value_from_str("--depth").unwrap();
+    let repair = args.contains("--repair");
+    args.finish().unwrap();
+    let conf = Config::from_config_file(load_config_file(&config_path));
+    let depth = depth.unwrap_or(conf.node.integrity_check_depth);
+    let burnchain = BitcoinRegtestController::new_dummy(conf.clone()).get_burnchain();
+
+    let report = match check_node_integrity(&conf, &burnchain.get_db_path(), depth, repair) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+
+    for problem in report.problems.iter() {
+        println!("problem\t{}", problem);
+    }
+    for repair in report.repairs.iter() {
+        println!("repaired\t{}", repair);
+    }
+    for damage in report.unrepairable.iter() {
+        println!("unrepairable\t{}", damage);
+    }
+
+    if report.unrepairable.len() > 0 || (!repair && report.problems.len() > 0) {
+        process::exit(1);
+    }
+    if report.is_ok() {
+        println!("{}: ok", config_path);
     }
 }
 
@@ -184,13 +391,65 @@ start\t\tStart a node with a config of your own. Can be used for joining a netwo
 \t\t  --config: path of the config (such as https://github.com/blockstack/stacks-blockchain/blob/master/testnet/Stacks.toml).
 \t\tExample:
 \t\t  stacks-node start --config=/path/to/config.toml
+\t\tAny key can be overridden with an environment variable named after it, e.g. node.rpc_bind
+\t\twith STACKS_NODE_RPC_BIND.  Send the node SIGHUP to reload node.log_level,
+\t\tnode.log_modules, events_observer and the [mempool] section without restarting it.  Send it
+\t\tSIGTERM or SIGINT to stop it once its open chain state writes finish; a second signal stops
+\t\tit right away.
+
+check-config\tCheck a config file, including environment variable overrides, and list every unknown key
+\t\tand invalid value in it.  Exits non-zero if there are any.
+\t\tArguments:
+\t\t  --config: path of the config
+\t\tExample:
+\t\t  stacks-node check-config --config=/path/to/config.toml
+
+apikeys\t\tManage the API keys RPC requests must carry, if the node's [connection_options] set
+\t\trequire_rpc_api_keys = true.  Requests pass the key in the X-Api-Key header.
+\t\tSubcommands:
+\t\t  add --config=<path> --label=<label> [--requests-per-minute=<n>]: make a key and print it
+\t\t  revoke --config=<path> --label=<label>: revoke a key
+\t\t  list --config=<path>: list keys and their usage
+\t\tExample:
+\t\t  stacks-node apikeys add --config=/path/to/config.toml --label=acme --requests-per-minute=600
+
+snapshot\tCreate or restore a snapshot of a stopped node's burnchain and chain state, so that a new
+\t\tnode can start from it instead of syncing from genesis.  Restoring verifies every file and the
+\t\tconsensus hash of the snapshot's burnchain tip.
+\t\tSubcommands:
+\t\t  create --config=<path> --output=<archive>: write a snapshot of the node's working directory
+\t\t  restore --config=<path> --input=<archive>: restore a snapshot into an empty working directory
+\t\tExample:
+\t\t  stacks-node snapshot create --config=/path/to/config.toml --output=/tmp/xenon.snapshot
+
+rewind\t\tRewind a stopped node's burnchain state to a burn block height, to recover from a deep
+\t\tburnchain reorg or a corrupted sortition DB without resyncing from genesis.  Sortitions and
+\t\tburnchain blocks above the height are re-downloaded and re-evaluated when the node next starts.
+\t\tArguments:
+\t\t  --config: path of the node's config
+\t\t  --burn-height: the highest burn block height to keep
+\t\tExample:
+\t\t  stacks-node rewind --config=/path/to/config.toml --burn-height=1900000
+
+check-integrity\tCheck a stopped node's sortition DB and chain state for damage left by an unclean shutdown:
+\t\tcorruption, half-written MARF tries, and half-processed blocks.  The node runs this check with
+\t\t--repair when it starts, unless node.integrity_check_depth is 0.
+\t\tArguments:
+\t\t  --config: path of the node's config
+\t\t  --depth: how many of the most recent tries and blocks to check (default: node.integrity_check_depth)
+\t\t  --repair: rewind or roll back what can be repaired
+\t\tExample:
+\t\t  stacks-node check-integrity --config=/path/to/config.toml --repair
 
 version\t\tDisplay informations about the current version and our release cycle.
 
 help\t\tDisplay this help.
 
+OPTIONS:
+
+--log-format\tWrite log records as `text` (the default) or as `json`, one object per line with the
+\t\tfields module, burn_height, stacks_tip and, on some records, event.  Log levels can be set per
+\t\tmodule with node.log_modules, e.g. [\"blockstack_lib::net=debug\"].
+
 ", argv[0]);
 }
-
-#[cfg(test)]
-pub mod tests;
@@ -0,0 +1,339 @@
+//! The neon miner's state machine.
+//!
+//! A miner cycles through four states, driven by the burn blocks it processes:
+//!
+//! * `RegisteringKey`: it has no usable leader key, and sends a key registration.
+//! * `WaitingKeyConfirmation`: the registration was sent, and the miner waits for a burn block to
+//!   include it.  If none has after `key_registration_timeout` burn blocks, it registers again.
+//! * `Committing`: it has a usable key, and sends a block commit for the next burn block.
+//! * `WaitingSortition`: the commit was sent, and the miner waits for the burn block it should
+//!   land in.  Until then, polling again only refreshes the commit.  Once that burn block is
+//!   processed, the miner commits again, whether or not its commit made it in.
+//!
+//! Every transition depends on burn blocks, never on how much time has passed, so a slow or
+//! bursty burnchain can't make the miner register keys it doesn't need or skip rounds.
+//!
+//! Before building the block for a new commit, the miner waits for microblocks to propagate.
+//! `tenure_sleep_ms` keeps that wait short next to the expected time until the next burn block,
+//! so on a fast burnchain the commit still goes out in time.
+
+use stacks::burnchains::timing::BurnBlockEta;
+
+/// The largest share of the expected time until the next burn block, in percent, that the miner
+/// spends waiting for microblocks before it builds its block.
+pub const MAX_TENURE_SLEEP_PERCENT: u64 = 10;
+
+/// What the miner is doing
+#[derive(Debug, Clone, PartialEq)]
+pub enum MinerState {
+    RegisteringKey,
+    /// a key registration was sent when the burnchain tip was at `sent_at`
+    WaitingKeyConfirmation {
+        sent_at: u64,
+    },
+    Committing,
+    /// a block commit was sent for the burn block at `commit_height`
+    WaitingSortition {
+        commit_height: u64,
+    },
+}
+
+/// What the miner should do now
+#[derive(Debug, Clone, PartialEq)]
+pub enum MinerAction {
+    Idle,
+    RegisterKey,
+    /// Send a block commit for the next burn block.  `first_attempt` is false if one was already
+    /// sent for it, and this one only refreshes it.
+    Commit {
+        first_attempt: bool,
+    },
+}
+
+/// What a burn block held for the miner
+#[derive(Debug, Clone, PartialEq)]
+pub struct BurnBlockObservation {
+    pub block_height: u64,
+    /// one of our key registrations was mined
+    pub key_registered: bool,
+    /// one of our block commits was mined
+    pub commit_mined: bool,
+    /// our block commit won the sortition
+    pub won_sortition: bool,
+}
+
+pub struct MinerStateMachine {
+    state: MinerState,
+    last_burn_height: Option<u64>,
+    /// how many burn blocks to wait for a key registration before sending another
+    key_registration_timeout: u64,
+}
+
+impl MinerStateMachine {
+    pub fn new(key_registration_timeout: u64) -> MinerStateMachine {
+        MinerStateMachine {
+            state: MinerState::RegisteringKey,
+            last_burn_height: None,
+            key_registration_timeout,
+        }
+    }
+
+    pub fn state(&self) -> &MinerState {
+        &self.state
+    }
+
+    fn transition(&mut self, next_state: MinerState) {
+        if self.state != next_state {
+            debug!("Miner: {:?} -> {:?}", &self.state, &next_state);
+            self.state = next_state;
+        }
+    }
+
+    /// Advance the state machine past a processed burn block
+    pub fn on_burn_block(&mut self, observation: &BurnBlockObservation) {
+        let height = observation.block_height;
+        if let Some(last_burn_height) = self.last_burn_height {
+            if height > last_burn_height + 1 {
+                info!(
+                    "Miner missed burn blocks {} to {}; resuming at burn block {}",
+                    last_burn_height + 1,
+                    height - 1,
+                    height
+                );
+            }
+        }
+        self.last_burn_height = Some(height);
+
+        match self.state {
+            MinerState::RegisteringKey | MinerState::WaitingKeyConfirmation { .. }
+                if observation.key_registered =>
+            {
+                info!("Miner: leader key registered in burn block {}", height);
+                self.transition(MinerState::Committing);
+            }
+            MinerState::WaitingKeyConfirmation { sent_at }
+                if height >= sent_at + self.key_registration_timeout =>
+            {
+                warn!(
+                    "Miner: leader key registration sent at burn block {} was not mined after {} blocks; registering again",
+                    sent_at, self.key_registration_timeout
+                );
+                self.transition(MinerState::RegisteringKey);
+            }
+            MinerState::WaitingSortition { commit_height } if observation.commit_mined => {
+                if observation.won_sortition {
+                    info!("Miner: won the sortition in burn block {}", height);
+                } else {
+                    info!("Miner: lost the sortition in burn block {}", height);
+                }
+                if height != commit_height {
+                    debug!(
+                        "Miner: block commit for burn block {} landed in burn block {}",
+                        commit_height, height
+                    );
+                }
+                self.transition(MinerState::Committing);
+            }
+            MinerState::WaitingSortition { commit_height } if height >= commit_height => {
+                warn!(
+                    "Miner: block commit for burn block {} was not mined; committing again",
+                    commit_height
+                );
+                self.transition(MinerState::Committing);
+            }
+            _ => {}
+        }
+    }
+
+    /// Decide what to do when the burnchain tip is at `tip_height`.  `has_key` says whether the
+    /// miner holds a registered leader key it can use.
+    pub fn next_action(&mut self, tip_height: u64, has_key: bool) -> MinerAction {
+        loop {
+            match self.state.clone() {
+                MinerState::RegisteringKey => {
+                    if has_key {
+                        self.transition(MinerState::Committing);
+                        continue;
+                    }
+                    self.transition(MinerState::WaitingKeyConfirmation {
+                        sent_at: tip_height,
+                    });
+                    return MinerAction::RegisterKey;
+                }
+                MinerState::WaitingKeyConfirmation { .. } => {
+                    if has_key {
+                        self.transition(MinerState::Committing);
+                        continue;
+                    }
+                    return MinerAction::Idle;
+                }
+                MinerState::Committing => {
+                    if !has_key {
+                        info!("Miner: no usable leader key");
+                        self.transition(MinerState::RegisteringKey);
+                        continue;
+                    }
+                    self.transition(MinerState::WaitingSortition {
+                        commit_height: tip_height + 1,
+                    });
+                    return MinerAction::Commit {
+                        first_attempt: true,
+                    };
+                }
+                MinerState::WaitingSortition { commit_height } => {
+                    if !has_key {
+                        info!("Miner: no usable leader key");
+                        self.transition(MinerState::RegisteringKey);
+                        continue;
+                    }
+                    if tip_height + 1 > commit_height {
+                        // the burn block the commit was for has been seen
+                        self.transition(MinerState::Committing);
+                        continue;
+                    }
+                    return MinerAction::Commit {
+                        first_attempt: false,
+                    };
+                }
+            }
+        }
+    }
+}
+
+/// How long to wait for microblocks to propagate before building an anchored block, in
+/// milliseconds:  `wait_time_for_microblocks`, but no more than MAX_TENURE_SLEEP_PERCENT percent
+/// of the time until the next burn block is expected to arrive.
+pub fn tenure_sleep_ms(wait_time_for_microblocks: u64, next_burn_block_eta: &BurnBlockEta) -> u64 {
+    let max_sleep_ms = next_burn_block_eta
+        .seconds
+        .saturating_mul(10 * MAX_TENURE_SLEEP_PERCENT);
+    wait_time_for_microblocks.min(max_sleep_ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stacks::burnchains::timing::BurnBlockTimeModel;
+
+    fn burn_block(
+        block_height: u64,
+        key_registered: bool,
+        commit_mined: bool,
+        won_sortition: bool,
+    ) -> BurnBlockObservation {
+        BurnBlockObservation {
+            block_height,
+            key_registered,
+            commit_mined,
+            won_sortition,
+        }
+    }
+
+    #[test]
+    fn test_miner_state_machine() {
+        let mut miner = MinerStateMachine::new(3);
+
+        // register a key, and only once while it confirms
+        assert_eq!(miner.next_action(100, false), MinerAction::RegisterKey);
+        assert_eq!(
+            miner.state(),
+            &MinerState::WaitingKeyConfirmation { sent_at: 100 }
+        );
+        assert_eq!(miner.next_action(100, false), MinerAction::Idle);
+        miner.on_burn_block(&burn_block(101, false, false, false));
+        assert_eq!(miner.next_action(101, false), MinerAction::Idle);
+
+        // the key is mined
+        miner.on_burn_block(&burn_block(102, true, false, false));
+        assert_eq!(miner.state(), &MinerState::Committing);
+        assert_eq!(
+            miner.next_action(102, true),
+            MinerAction::Commit {
+                first_attempt: true
+            }
+        );
+        assert_eq!(
+            miner.state(),
+            &MinerState::WaitingSortition { commit_height: 103 }
+        );
+        assert_eq!(
+            miner.next_action(102, true),
+            MinerAction::Commit {
+                first_attempt: false
+            }
+        );
+
+        // the commit is mined
+        miner.on_burn_block(&burn_block(103, false, true, true));
+        assert_eq!(miner.state(), &MinerState::Committing);
+        assert_eq!(
+            miner.next_action(103, true),
+            MinerAction::Commit {
+                first_attempt: true
+            }
+        );
+
+        // the commit misses its burn block
+        miner.on_burn_block(&burn_block(104, false, false, false));
+        assert_eq!(miner.state(), &MinerState::Committing);
+
+        // several burn blocks at once
+        assert_eq!(
+            miner.next_action(104, true),
+            MinerAction::Commit {
+                first_attempt: true
+            }
+        );
+        miner.on_burn_block(&burn_block(107, false, false, false));
+        assert_eq!(
+            miner.next_action(107, true),
+            MinerAction::Commit {
+                first_attempt: true
+            }
+        );
+
+        // the key expires
+        assert_eq!(miner.next_action(107, false), MinerAction::RegisterKey);
+        assert_eq!(
+            miner.state(),
+            &MinerState::WaitingKeyConfirmation { sent_at: 107 }
+        );
+
+        // the registration is never mined
+        miner.on_burn_block(&burn_block(108, false, false, false));
+        miner.on_burn_block(&burn_block(109, false, false, false));
+        assert_eq!(miner.next_action(109, false), MinerAction::Idle);
+        miner.on_burn_block(&burn_block(110, false, false, false));
+        assert_eq!(miner.state(), &MinerState::RegisteringKey);
+        assert_eq!(miner.next_action(110, false), MinerAction::RegisterKey);
+
+        // a key kept from before a restart is used right away
+        let mut miner = MinerStateMachine::new(3);
+        assert_eq!(
+            miner.next_action(200, true),
+            MinerAction::Commit {
+                first_attempt: true
+            }
+        );
+    }
+
+    #[test]
+    fn test_tenure_sleep_ms() {
+        // 600-second blocks leave plenty of time for the configured wait
+        let model = BurnBlockTimeModel::new(1144, 1_000_086_400, 1000, 1_000_000_000);
+        let eta = model.estimate(1145, 1_000_086_500);
+        assert_eq!(eta.seconds, 600);
+        assert_eq!(tenure_sleep_ms(5000, &eta), 5000);
+
+        // 10-second blocks only leave a second for it
+        let model = BurnBlockTimeModel::new(1100, 1_000_001_000, 1000, 1_000_000_000);
+        let eta = model.estimate(1101, 1_000_001_000);
+        assert_eq!(eta.seconds, 10);
+        assert_eq!(tenure_sleep_ms(5000, &eta), 1000);
+        assert_eq!(tenure_sleep_ms(500, &eta), 500);
+
+        // the next burn block is already here
+        let eta = model.estimate(1100, 1_000_001_000);
+        assert_eq!(tenure_sleep_ms(5000, &eta), 0);
+    }
+}
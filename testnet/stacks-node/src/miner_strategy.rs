@@ -0,0 +1,163 @@
+//! How much the neon miner burns in each block commit.
+//!
+//! `miner.commit_strategy` picks one of:
+//!
+//! * `static`: always commit `miner.commit_amount`.
+//! * `proportional`: commit `miner.commit_percent` percent of what the winners of the last
+//!   `miner.commit_window` sortitions committed, on average.  Falls back to
+//!   `miner.commit_amount` if none of them had a winner.
+//! * `capped`: start at `miner.commit_amount`, and raise the commit by
+//!   `miner.commit_escalation_percent` percent for every round in a row that the miner lost,
+//!   within the last `miner.commit_window` sortitions.  Winning a round starts over.
+//!
+//! Whatever the strategy, a commit never burns more than `burnchain.burn_fee_cap`.
+
+/// The strategies `miner.commit_strategy` can name
+pub const COMMIT_STRATEGIES: &[&str] = &["static", "proportional", "capped"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommitStrategy {
+    Static,
+    Proportional,
+    Capped,
+}
+
+impl CommitStrategy {
+    pub fn from_name(name: &str) -> Option<CommitStrategy> {
+        match name {
+            "static" => Some(CommitStrategy::Static),
+            "proportional" => Some(CommitStrategy::Proportional),
+            "capped" => Some(CommitStrategy::Capped),
+            _ => None,
+        }
+    }
+}
+
+/// What the miner commits with, once the config is resolved
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommitParams {
+    pub strategy: CommitStrategy,
+    /// the static amount, and where the capped strategy starts
+    pub amount: u64,
+    pub percent: u64,
+    pub escalation_percent: u64,
+    /// never commit more than this
+    pub cap: u64,
+}
+
+/// How one recent sortition went
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommitRound {
+    /// what the winning block commit burnt, if there was a winner
+    pub winning_burn: Option<u64>,
+    /// what our block commit burnt, if we sent one that was mined
+    pub our_burn: Option<u64>,
+    /// our block commit won
+    pub won: bool,
+}
+
+/// `amount` raised by `percent` percent, without overflowing
+fn add_percent(amount: u64, percent: u64) -> u64 {
+    let raised = (amount as u128) * (100 + percent as u128) / 100;
+    if raised > u64::max_value() as u128 {
+        u64::max_value()
+    } else {
+        raised as u64
+    }
+}
+
+/// Choose how much to burn in the next block commit.  `rounds` are the most recent sortitions,
+/// most recent first.
+pub fn choose_commit_amount(params: &CommitParams, rounds: &[CommitRound]) -> u64 {
+    let amount = match params.strategy {
+        CommitStrategy::Static => params.amount,
+        CommitStrategy::Proportional => {
+            let winning_burns: Vec<u64> = rounds
+                .iter()
+                .filter_map(|round| round.winning_burn)
+                .collect();
+            if winning_burns.len() == 0 {
+                params.amount
+            } else {
+                let total: u128 = winning_burns.iter().map(|burn| *burn as u128).sum();
+                let average = total / (winning_burns.len() as u128);
+                let proportional = average * (params.percent as u128) / 100;
+                if proportional > u64::max_value() as u128 {
+                    u64::max_value()
+                } else {
+                    proportional as u64
+                }
+            }
+        }
+        CommitStrategy::Capped => {
+            let losses = rounds
+                .iter()
+                .take_while(|round| !round.won && round.our_burn.is_some())
+                .count();
+            let mut amount = params.amount;
+            for _ in 0..losses {
+                amount = add_percent(amount, params.escalation_percent);
+                if amount >= params.cap {
+                    break;
+                }
+            }
+            amount
+        }
+    };
+    amount.min(params.cap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round(winning_burn: Option<u64>, our_burn: Option<u64>, won: bool) -> CommitRound {
+        CommitRound {
+            winning_burn,
+            our_burn,
+            won,
+        }
+    }
+
+    #[test]
+    fn test_choose_commit_amount() {
+        let mut params = CommitParams {
+            strategy: CommitStrategy::Static,
+            amount: 1000,
+            percent: 50,
+            escalation_percent: 10,
+            cap: 5000,
+        };
+        let rounds = vec![
+            round(Some(3000), Some(1000), false),
+            round(Some(1000), Some(1000), false),
+            round(None, None, false),
+            round(Some(1000), Some(1000), true),
+            round(Some(2000), Some(1000), false),
+        ];
+
+        assert_eq!(choose_commit_amount(&params, &rounds), 1000);
+        params.amount = 6000;
+        assert_eq!(choose_commit_amount(&params, &rounds), 5000);
+        params.amount = 1000;
+
+        // half of the average winning commit, (3000 + 1000 + 1000 + 2000) / 4
+        params.strategy = CommitStrategy::Proportional;
+        assert_eq!(choose_commit_amount(&params, &rounds), 875);
+        assert_eq!(choose_commit_amount(&params, &[]), 1000);
+        assert_eq!(
+            choose_commit_amount(&params, &[round(None, Some(1000), false)]),
+            1000
+        );
+        params.percent = 1000;
+        assert_eq!(choose_commit_amount(&params, &rounds), 5000);
+
+        // lost the last two rounds; a round without our commit ends the streak
+        params.strategy = CommitStrategy::Capped;
+        assert_eq!(choose_commit_amount(&params, &rounds), 1210);
+        assert_eq!(choose_commit_amount(&params, &rounds[3..]), 1000);
+        assert_eq!(choose_commit_amount(&params, &[]), 1000);
+        let losing_streak = vec![round(Some(9000), Some(1000), false); 100];
+        assert_eq!(choose_commit_amount(&params, &losing_streak), 5000);
+    }
+}
@@ -5,6 +5,9 @@ use stacks::prometheus::{gather, Encoder, TextEncoder};
 
 use http_types::{Body, Response, StatusCode};
 
+/// The only path metrics are served at
+pub const METRICS_PATH: &str = "/metrics";
+
 pub fn start_serving_prometheus_metrics(bind_address: String) {
     let addr = bind_address.clone();
 
@@ -18,7 +21,10 @@ pub fn start_serving_prometheus_metrics(bind_address: String) {
                 .local_addr()
                 .expect("Prometheus monitoring: unable to get addr")
         );
-        info!("Prometheus monitoring: server listening on {}", addr);
+        info!(
+            "Prometheus monitoring: server listening on {}{}",
+            addr, METRICS_PATH
+        );
 
         let mut incoming = listener.incoming();
         while let Some(stream) = incoming.next().await {
@@ -48,7 +54,11 @@ async fn accept(addr: String, stream: TcpStream) -> http_types::Result<()> {
         "Prometheus monitoring: starting new connection from {}",
         stream.peer_addr()?
     );
-    async_h1::accept(&addr, stream.clone(), |_| async {
+    async_h1::accept(&addr, stream.clone(), |request| async move {
+        if request.url().path() != METRICS_PATH {
+            return Ok(Response::new(StatusCode::NotFound));
+        }
+
         let encoder = TextEncoder::new();
         let metric_families = gather();
         let mut buffer = vec![];
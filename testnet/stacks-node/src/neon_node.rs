@@ -1,5 +1,7 @@
 use super::{BurnchainController, BurnchainTip, Config, EventDispatcher, Keychain};
 use crate::config::HELIUM_BLOCK_LIMIT;
+use crate::miner_state::{tenure_sleep_ms, BurnBlockObservation, MinerAction, MinerStateMachine};
+use crate::miner_strategy::{choose_commit_amount, CommitRound, CommitStrategy};
 use crate::run_loop::RegisteredKey;
 
 use std::cmp;
@@ -9,6 +11,7 @@ use std::default::Default;
 use std::net::SocketAddr;
 use std::{thread, thread::JoinHandle};
 
+use stacks::burnchains::timing::{BurnBlockTimeModel, BURN_BLOCK_TIME_SAMPLE_WINDOW};
 use stacks::burnchains::{Burnchain, BurnchainHeaderHash, Txid};
 use stacks::chainstate::burn::db::sortdb::{SortitionDB, SortitionId};
 use stacks::chainstate::burn::operations::{
@@ -17,6 +20,7 @@ use stacks::chainstate::burn::operations::{
 };
 use stacks::chainstate::burn::BlockSnapshot;
 use stacks::chainstate::burn::{BlockHeaderHash, ConsensusHash, VRFSeed};
+use stacks::chainstate::stacks::db::reader::ChainstateReader;
 use stacks::chainstate::stacks::db::{ClarityTx, StacksChainState};
 use stacks::chainstate::stacks::Error as ChainstateError;
 use stacks::chainstate::stacks::StacksBlockId;
@@ -29,21 +33,30 @@ use stacks::chainstate::stacks::{
 };
 use stacks::core::mempool::MemPoolDB;
 use stacks::net::{
+    admin::RPCAdminControls,
+    apikeys::RPCApiKeys,
+    atlas::AtlasDB,
     db::{LocalPeer, PeerDB},
     dns::DNSResolver,
+    faucet::Faucet,
     p2p::PeerNetwork,
     relay::Relayer,
     rpc::RPCHandlerArgs,
+    websocket::WebSocketEventReceiver,
     Error as NetError, NetworkResult, PeerAddress, StacksMessageCodec,
 };
-use stacks::util::get_epoch_time_secs;
 use stacks::util::hash::{to_hex, Hash160, Sha256Sum};
 use stacks::util::secp256k1::Secp256k1PrivateKey;
 use stacks::util::strings::UrlString;
 use stacks::util::vrf::VRFPublicKey;
+use stacks::util::{get_epoch_time_ms, get_epoch_time_secs};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
 
 use crate::burnchains::bitcoin_regtest_controller::BitcoinRegtestController;
+use crate::reload::MemPoolSettings;
+use crate::shutdown;
 use crate::syncctl::PoxSyncWatchdogComms;
 
 use crate::ChainTip;
@@ -61,6 +74,17 @@ pub const TESTNET_CHAIN_ID: u32 = 0x80000000;
 pub const TESTNET_PEER_VERSION: u32 = 0xfacade01;
 pub const RELAYER_MAX_BUFFER: usize = 100;
 
+/// How often the relayer prunes block bodies, if pruning is enabled
+const PRUNE_INTERVAL_SECS: u64 = 60;
+
+/// How many burn blocks before the active leader key expires the miner registers its successor,
+/// so that a new key is ready by the time the old one can't be used anymore
+const LEADER_KEY_RENEWAL_MARGIN: u64 = 6;
+
+/// How many burn blocks the miner waits for a leader key registration to be mined before it
+/// sends another one
+const LEADER_KEY_REGISTRATION_TIMEOUT: u64 = 6;
+
 struct AssembledAnchorBlock {
     parent_consensus_hash: ConsensusHash,
     my_burn_hash: BurnchainHeaderHash,
@@ -68,22 +92,54 @@ struct AssembledAnchorBlock {
     consumed_execution: ExecutionCost,
     bytes_so_far: u64,
     attempt: u64,
+    microblock_secret_key: Secp256k1PrivateKey,
+}
+
+/// The microblock stream this node is producing off of the last anchored block it won
+struct MicroblockMinerState {
+    parent_consensus_hash: ConsensusHash,
+    parent_block_hash: BlockHeaderHash,
+    miner_key: Secp256k1PrivateKey,
+    consumed_execution: ExecutionCost,
+    bytes_so_far: u64,
+    mined_microblocks: Vec<StacksMicroblock>,
+    /// when we last tried to mine a microblock, in milliseconds
+    last_mined: u128,
 }
 
 enum RelayerDirective {
     HandleNetResult(NetworkResult),
     ProcessTenure(ConsensusHash, BurnchainHeaderHash, BlockHeaderHash),
     RunTenure(RegisteredKey, BlockSnapshot),
+    /// assemble a block on this burn block and log it, without committing to it
+    DryRunTenure(BlockSnapshot),
     RegisterKey(BlockSnapshot),
+    Exit,
 }
 
 pub struct InitializedNeonNode {
     relay_channel: SyncSender<RelayerDirective>,
     burnchain_signer: BurnchainSigner,
     last_burn_block: Option<BlockSnapshot>,
+    /// burn block timing, as of the last burn block processed outside of the initial block
+    /// download
+    burn_block_time: Option<BurnBlockTimeModel>,
     active_keys: Vec<RegisteredKey>,
+    /// how many burn blocks a leader key is used for; 0 means forever
+    leader_key_lifetime: u64,
+    /// whether the registration of the active key's successor is in flight
+    renewing_key: bool,
+    miner_state: MinerStateMachine,
+    /// assemble blocks without registering keys or sending block commits
+    dry_run: bool,
+    /// the burn block the last dry-run block was assembled on
+    last_dry_run_height: Option<u64>,
     sleep_before_tenure: u64,
     is_miner: bool,
+    /// cleared through the admin RPC endpoints to pause mining
+    mining_enabled: Arc<AtomicBool>,
+    p2p_thread: Option<JoinHandle<()>>,
+    relayer_thread: Option<JoinHandle<()>>,
 }
 
 pub struct NeonGenesisNode {
@@ -91,6 +147,8 @@ pub struct NeonGenesisNode {
     keychain: Keychain,
     event_dispatcher: EventDispatcher,
     burnchain: Burnchain,
+    websocket_events: Option<WebSocketEventReceiver>,
+    mempool_settings: MemPoolSettings,
 }
 
 #[cfg(test)]
@@ -149,7 +207,11 @@ fn inner_process_tenure(
     Ok(true)
 }
 
-fn inner_generate_coinbase_tx(keychain: &mut Keychain, nonce: u64) -> StacksTransaction {
+fn inner_generate_coinbase_tx(
+    keychain: &mut Keychain,
+    nonce: u64,
+    chain_id: u32,
+) -> StacksTransaction {
     let mut tx_auth = keychain.get_transaction_auth().unwrap();
     tx_auth.set_origin_nonce(nonce);
 
@@ -158,7 +220,7 @@ fn inner_generate_coinbase_tx(keychain: &mut Keychain, nonce: u64) -> StacksTran
         tx_auth,
         TransactionPayload::Coinbase(CoinbasePayload([0u8; 32])),
     );
-    tx.chain_id = TESTNET_CHAIN_ID;
+    tx.chain_id = chain_id;
     tx.anchor_mode = TransactionAnchorMode::OnChainOnly;
     let mut tx_signer = StacksTransactionSigner::new(&tx);
     keychain.sign_as_origin(&mut tx_signer);
@@ -201,6 +263,100 @@ fn rotate_vrf_and_register(
     btc_controller.submit_operation(op, &mut one_off_signer, 1);
 }
 
+/// Log what a block assembled in dry-run mode holds, what it costs, and what mining it would earn
+fn log_dry_run_block(
+    anchored_block: &StacksBlock,
+    consumed_execution: &ExecutionCost,
+    bytes_so_far: u64,
+    stacks_height: u64,
+    burn_block: &BlockSnapshot,
+) {
+    let mut tx_fees: u128 = 0;
+    for tx in anchored_block.txs.iter() {
+        debug!(
+            "Dry run: block {} includes tx {} with fee {}",
+            anchored_block.block_hash(),
+            tx.txid(),
+            tx.get_fee_rate()
+        );
+        tx_fees += tx.get_fee_rate() as u128;
+    }
+    let coinbase = StacksChainState::get_coinbase_reward(stacks_height);
+    info!(
+        "Dry run: assembled block {} at Stacks height {} on burn block {}, with {} txs in {} bytes costing {}; it would earn {} uSTX coinbase and {} uSTX in fees once mature",
+        anchored_block.block_hash(),
+        stacks_height,
+        burn_block.block_height,
+        anchored_block.txs.len(),
+        bytes_so_far,
+        consumed_execution,
+        coinbase,
+        tx_fees;
+        "event" => "dry_run_block"
+    );
+}
+
+/// Choose how much the next block commit burns, given how the sortitions up to `burn_block` went
+fn choose_burn_fee(
+    config: &Config,
+    burn_db: &SortitionDB,
+    burn_block: &BlockSnapshot,
+    signer: &BurnchainSigner,
+    burn_fee_cap: u64,
+) -> u64 {
+    let params = config.miner.commit_params(burn_fee_cap);
+    if params.strategy == CommitStrategy::Static {
+        return choose_commit_amount(&params, &[]);
+    }
+
+    let recent = match SortitionDB::get_recent_block_commits(
+        &burn_db.index_conn(),
+        burn_block,
+        config.miner.commit_window,
+    ) {
+        Ok(recent) => recent,
+        Err(e) => {
+            warn!("Failed to load recent block commits: {:?}", &e);
+            vec![]
+        }
+    };
+    let rounds: Vec<CommitRound> = recent
+        .iter()
+        .map(|(snapshot, commits)| {
+            let winner = commits
+                .iter()
+                .find(|commit| snapshot.sortition && commit.txid == snapshot.winning_block_txid);
+            let ours = commits.iter().find(|commit| &commit.input == signer);
+            CommitRound {
+                winning_burn: winner.map(|commit| commit.burn_fee),
+                our_burn: ours.map(|commit| commit.burn_fee),
+                won: match (winner, ours) {
+                    (Some(winner), Some(ours)) => winner.txid == ours.txid,
+                    _ => false,
+                },
+            }
+        })
+        .collect();
+
+    let burn_fee = choose_commit_amount(&params, &rounds);
+    info!(
+        "Committing {} with the {:?} strategy over the last {} sortitions",
+        burn_fee,
+        &params.strategy,
+        rounds.len()
+    );
+    burn_fee
+}
+
+/// Save the keychain's key material, if the node is configured to keep it across restarts
+fn save_keychain(config: &Config, keychain: &Keychain) {
+    if let Some(ref passphrase) = config.node.keychain_passphrase {
+        if let Err(e) = keychain.save(&config.get_keychain_path(), passphrase) {
+            warn!("Failed to save the keychain: {}", e);
+        }
+    }
+}
+
 /// Constructs and returns a LeaderBlockCommitOp out of the provided params
 fn inner_generate_block_commit_op(
     input: BurnchainSigner,
@@ -242,33 +398,95 @@ fn spawn_peer(
     poll_timeout: u64,
     relay_channel: SyncSender<RelayerDirective>,
     mut sync_comms: PoxSyncWatchdogComms,
+    websocket_events: Option<WebSocketEventReceiver>,
+    mempool_settings: MemPoolSettings,
+    admin_controls: RPCAdminControls,
 ) -> Result<JoinHandle<()>, NetError> {
     let burn_db_path = config.get_burn_db_file_path();
     let stacks_chainstate_path = config.get_chainstate_path();
     let block_limit = config.block_limit;
     let exit_at_block_height = config.burnchain.process_exit_at_block_height;
+    let faucet_db_path = config.get_faucet_db_path();
+    let faucet_config = config.faucet.clone();
+    let rpc_api_keys_db_path = if config.connection_options.require_rpc_api_keys {
+        Some(config.get_rpc_api_keys_db_path())
+    } else {
+        None
+    };
+    let atlas_db_path = config.get_atlas_db_path();
+    let atlas_config = if config.atlas.contracts.len() > 0 {
+        Some(config.atlas.clone())
+    } else {
+        None
+    };
 
     this.bind(p2p_sock, rpc_sock).unwrap();
+    if let Some(websocket_events) = websocket_events {
+        this.http.set_websocket_events(websocket_events);
+    }
     let (mut dns_resolver, mut dns_client) = DNSResolver::new(10);
     let sortdb = SortitionDB::open(&burn_db_path, false).map_err(NetError::DBError)?;
 
     let (mut chainstate, _) = StacksChainState::open_with_block_limit(
         false,
-        TESTNET_CHAIN_ID,
+        config.node.chain_id,
         &stacks_chainstate_path,
         block_limit,
     )
     .map_err(|e| NetError::ChainstateError(e.to_string()))?;
 
-    let mut mem_pool = MemPoolDB::open(false, TESTNET_CHAIN_ID, &stacks_chainstate_path)
-        .map_err(NetError::DBError)?;
+    // answer RPC queries on confirmed state without contending with the relayer's writes
+    let chainstate_reader = ChainstateReader::from_chainstate(&chainstate)
+        .map(Mutex::new)
+        .map_err(|e| NetError::ChainstateError(e.to_string()))?;
+
+    let mut mem_pool = MemPoolDB::open_at(
+        false,
+        config.node.chain_id,
+        &stacks_chainstate_path,
+        &config.get_mempool_db_path(),
+    )
+    .map_err(NetError::DBError)?;
+    let mut mempool_settings_version = 0;
+    mempool_settings.apply(&mut mem_pool, &mut mempool_settings_version);
 
     // buffer up blocks to store without stalling the p2p thread
     let mut results_with_data = VecDeque::new();
 
     let server_thread = thread::spawn(move || {
+        let faucet = faucet_config.map(|faucet_config| {
+            Faucet::open(
+                &faucet_db_path,
+                faucet_config.private_key,
+                faucet_config.amount,
+                faucet_config.fee,
+                faucet_config.address_interval,
+                faucet_config.caller_interval,
+            )
+            .expect("FATAL: failed to open faucet DB")
+        });
+        if let Some(ref faucet) = faucet {
+            info!("STX faucet enabled, sending from {}", faucet.address());
+        }
+        let api_keys = rpc_api_keys_db_path.map(|path| {
+            info!("RPC requests require an API key from {}", &path);
+            RPCApiKeys::open(&path).expect("FATAL: failed to open RPC API key DB")
+        });
+        let atlasdb = atlas_config.map(|atlas_config| {
+            info!(
+                "Tracking attachments of {} contract(s)",
+                atlas_config.contracts.len()
+            );
+            AtlasDB::open(&atlas_db_path, atlas_config).expect("FATAL: failed to open atlas DB")
+        });
+
         let handler_args = RPCHandlerArgs {
             exit_at_block_height: exit_at_block_height.as_ref(),
+            faucet: faucet.as_ref(),
+            api_keys: api_keys.as_ref(),
+            atlasdb: atlasdb.as_ref(),
+            chainstate_reader: Some(&chainstate_reader),
+            admin: Some(&admin_controls),
             ..RPCHandlerArgs::default()
         };
 
@@ -277,6 +495,17 @@ fn spawn_peer(
         let mut num_inv_sync_passes = 0;
 
         while !disconnected {
+            if shutdown::shutdown_requested() {
+                // hand the relayer whatever it has not stored yet, so no downloaded block is lost
+                for next_result in results_with_data.drain(..) {
+                    if relay_channel.send(next_result).is_err() {
+                        break;
+                    }
+                }
+                break;
+            }
+            mempool_settings.apply(&mut mem_pool, &mut mempool_settings_version);
+
             let download_backpressure = results_with_data.len() > 0;
             let poll_ms = if !download_backpressure && this.has_more_downloads() {
                 // keep getting those blocks -- drive the downloader state-machine
@@ -380,7 +609,8 @@ fn spawn_miner_relayer(
     blocks_processed: BlocksProcessedCounter,
     burnchain: Burnchain,
     coord_comms: CoordinatorChannels,
-) -> Result<(), NetError> {
+    mempool_settings: MemPoolSettings,
+) -> Result<JoinHandle<()>, NetError> {
     // Note: the relayer is *the* block processor, it is responsible for writes to the chainstate --
     //   no other codepaths should be writing once this is spawned.
     //
@@ -391,23 +621,37 @@ fn spawn_miner_relayer(
 
     let (mut chainstate, _) = StacksChainState::open_with_block_limit(
         false,
-        TESTNET_CHAIN_ID,
+        config.node.chain_id,
         &stacks_chainstate_path,
         config.block_limit.clone(),
     )
     .map_err(|e| NetError::ChainstateError(e.to_string()))?;
 
-    let mut mem_pool = MemPoolDB::open(false, TESTNET_CHAIN_ID, &stacks_chainstate_path)
-        .map_err(NetError::DBError)?;
+    let mut mem_pool = MemPoolDB::open_at(
+        false,
+        config.node.chain_id,
+        &stacks_chainstate_path,
+        &config.get_mempool_db_path(),
+    )
+    .map_err(NetError::DBError)?;
+    let mut mempool_settings_version = 0;
+    mempool_settings.apply(&mut mem_pool, &mut mempool_settings_version);
 
     let mut last_mined_blocks = vec![];
+    let mut dry_run_key_opt: Option<RegisteredKey> = None;
     let burn_fee_cap = config.burnchain.burn_fee_cap;
     let mine_microblocks = config.node.mine_microblocks;
+    let microblock_frequency = config.node.microblock_frequency;
+    let mut microblock_miner_state: Option<MicroblockMinerState> = None;
 
     let mut bitcoin_controller = BitcoinRegtestController::new_dummy(config.clone());
 
-    let _relayer_handle = thread::spawn(move || {
+    let prune_block_confirmations = config.node.prune_block_confirmations;
+    let mut last_prune_time = 0;
+
+    let relayer_handle = thread::spawn(move || {
         while let Ok(mut directive) = relay_channel.recv() {
+            mempool_settings.apply(&mut mem_pool, &mut mempool_settings_version);
             match directive {
                 RelayerDirective::HandleNetResult(ref mut net_result) => {
                     debug!("Relayer: Handle network result");
@@ -426,9 +670,40 @@ fn spawn_miner_relayer(
                     if mempool_txs_added > 0 {
                         event_dispatcher.process_new_mempool_txs(net_receipts.mempool_txs_added);
                     }
+
+                    if net_receipts.microblocks_added.len() > 0 {
+                        event_dispatcher.process_new_microblocks(net_receipts.microblocks_added);
+                    }
+
+                    // keep streaming microblocks off of our last winning block
+                    if let Some(ref mut miner_state) = microblock_miner_state {
+                        if miner_state.last_mined + (microblock_frequency as u128)
+                            <= get_epoch_time_ms()
+                        {
+                            InitializedNeonNode::relayer_mine_microblock(
+                                miner_state,
+                                &mut chainstate,
+                                &sortdb,
+                                &mem_pool,
+                                &mut relayer,
+                            );
+                        }
+                    }
+
+                    if let Some(confirmations) = prune_block_confirmations {
+                        if last_prune_time + PRUNE_INTERVAL_SECS < get_epoch_time_secs() {
+                            if let Err(e) = chainstate.prune_block_bodies(confirmations) {
+                                warn!("Failed to prune block bodies: {}", e);
+                            }
+                            last_prune_time = get_epoch_time_secs();
+                        }
+                    }
                 }
                 RelayerDirective::ProcessTenure(consensus_hash, burn_hash, block_header_hash) => {
                     debug!("Relayer: Process tenure");
+
+                    // a new sortition ends whatever microblock stream we were producing
+                    microblock_miner_state = None;
                     for last_mined_block in last_mined_blocks.drain(..) {
                         let AssembledAnchorBlock {
                             parent_consensus_hash,
@@ -437,6 +712,7 @@ fn spawn_miner_relayer(
                             consumed_execution,
                             bytes_so_far,
                             attempt: _,
+                            microblock_secret_key,
                         } = last_mined_block;
                         if mined_block.block_hash() == block_header_hash
                             && burn_hash == mined_burn_hash
@@ -444,7 +720,8 @@ fn spawn_miner_relayer(
                             // we won!
                             info!(
                                 "Won sortition! stacks_header={}, burn_hash={}",
-                                block_header_hash, mined_burn_hash
+                                block_header_hash, mined_burn_hash;
+                                "event" => "sortition_won"
                             );
 
                             increment_stx_blocks_mined_counter();
@@ -503,66 +780,25 @@ fn spawn_miner_relayer(
                                 {
                                     warn!("Failed to push new block: {}", e);
                                 } else {
-                                    // should we broadcast microblocks?
+                                    // should we stream microblocks?
                                     if mine_microblocks {
-                                        let mint_result =
-                                            InitializedNeonNode::relayer_mint_microblocks(
-                                                &consensus_hash,
-                                                &block_header_hash,
-                                                &mut chainstate,
-                                                &sortdb.index_conn(),
-                                                &keychain,
-                                                consumed_execution,
-                                                bytes_so_far,
-                                                &mem_pool,
-                                            );
-                                        let mined_microblock = match mint_result {
-                                            Ok(mined_microblock) => mined_microblock,
-                                            Err(e) => {
-                                                warn!("Failed to mine microblock: {}", e);
-                                                continue;
-                                            }
+                                        let mut miner_state = MicroblockMinerState {
+                                            parent_consensus_hash: consensus_hash.clone(),
+                                            parent_block_hash: block_header_hash.clone(),
+                                            miner_key: microblock_secret_key,
+                                            consumed_execution,
+                                            bytes_so_far,
+                                            mined_microblocks: vec![],
+                                            last_mined: 0,
                                         };
-                                        // preprocess the microblock locally
-                                        match chainstate.preprocess_streamed_microblock(
-                                            &consensus_hash,
-                                            &block_header_hash,
-                                            &mined_microblock,
-                                        ) {
-                                            Ok(res) => {
-                                                if !res {
-                                                    warn!("Unhandled error while pre-processing microblock {}",
-                                                          mined_microblock.header.block_hash());
-                                                    continue;
-                                                }
-                                            }
-                                            Err(e) => {
-                                                error!(
-                                                    "Error while pre-processing microblock {}: {}",
-                                                    mined_microblock.header.block_hash(),
-                                                    e
-                                                );
-                                                continue;
-                                            }
-                                        }
-                                        // update unconfirmed state
-                                        if let Err(e) = chainstate
-                                            .refresh_unconfirmed_state(&sortdb.index_conn())
-                                        {
-                                            warn!("Failed to refresh unconfirmed state after processing microblock {}/{}-{}: {:?}", &mined_burn_hash, &block_header_hash, mined_microblock.block_hash(), &e);
-                                        }
-                                        // broadcast to peers
-                                        let microblock_hash = mined_microblock.header.block_hash();
-                                        if let Err(e) = relayer.broadcast_microblock(
-                                            &consensus_hash,
-                                            &block_header_hash,
-                                            mined_microblock,
-                                        ) {
-                                            error!(
-                                                "Failure trying to broadcast microblock {}: {}",
-                                                microblock_hash, e
-                                            );
-                                        }
+                                        InitializedNeonNode::relayer_mine_microblock(
+                                            &mut miner_state,
+                                            &mut chainstate,
+                                            &sortdb,
+                                            &mem_pool,
+                                            &mut relayer,
+                                        );
+                                        microblock_miner_state = Some(miner_state);
                                     }
                                 }
                             }
@@ -599,6 +835,34 @@ fn spawn_miner_relayer(
                         last_mined_blocks.push(last_mined_block);
                     }
                 }
+                RelayerDirective::DryRunTenure(last_burn_block) => {
+                    debug!(
+                        "Relayer: Dry-run tenure at height {} ({})",
+                        last_burn_block.block_height, &last_burn_block.burn_header_hash
+                    );
+                    // the key is never registered; it only makes the VRF proof
+                    let dry_run_key = dry_run_key_opt
+                        .get_or_insert_with(|| RegisteredKey {
+                            vrf_public_key: keychain
+                                .rotate_vrf_keypair(last_burn_block.block_height),
+                            block_height: 0,
+                            op_vtxindex: 0,
+                        })
+                        .clone();
+                    InitializedNeonNode::relayer_run_tenure(
+                        &config,
+                        dry_run_key,
+                        &mut chainstate,
+                        &mut sortdb,
+                        &burnchain,
+                        last_burn_block,
+                        &mut keychain,
+                        &mut mem_pool,
+                        burn_fee_cap,
+                        &mut bitcoin_controller,
+                        &last_mined_blocks,
+                    );
+                }
                 RelayerDirective::RegisterKey(ref last_burn_block) => {
                     debug!("Relayer: Register key");
                     rotate_vrf_and_register(
@@ -606,14 +870,19 @@ fn spawn_miner_relayer(
                         last_burn_block,
                         &mut bitcoin_controller,
                     );
+                    save_keychain(&config, &keychain);
                     bump_processed_counter(&blocks_processed);
                 }
+                RelayerDirective::Exit => {
+                    debug!("Relayer: Exit");
+                    break;
+                }
             }
         }
         debug!("Relayer exit!");
     });
 
-    Ok(())
+    Ok(relayer_handle)
 }
 
 impl InitializedNeonNode {
@@ -626,8 +895,13 @@ impl InitializedNeonNode {
         blocks_processed: BlocksProcessedCounter,
         coord_comms: CoordinatorChannels,
         sync_comms: PoxSyncWatchdogComms,
-        burnchain: Burnchain,
+        mut burnchain: Burnchain,
+        websocket_events: Option<WebSocketEventReceiver>,
+        mempool_settings: MemPoolSettings,
     ) -> InitializedNeonNode {
+        // report the p2p network's version on /v2/info
+        burnchain.peer_version = config.node.peer_version;
+
         // we can call _open_ here rather than _connect_, since connect is first called in
         //   make_genesis_block
         let sortdb = SortitionDB::open(&config.get_burn_db_file_path(), false)
@@ -679,7 +953,7 @@ impl InitializedNeonNode {
         let mut peerdb = PeerDB::connect(
             &config.get_peer_db_path(),
             true,
-            TESTNET_CHAIN_ID,
+            config.node.chain_id,
             burnchain.network_id,
             Some(node_privkey),
             config.connection_options.private_key_lifetime.clone(),
@@ -716,7 +990,7 @@ impl InitializedNeonNode {
         let mut p2p_net = PeerNetwork::new(
             peerdb,
             local_peer.clone(),
-            TESTNET_PEER_VERSION,
+            config.node.peer_version,
             burnchain.clone(),
             view,
             config.connection_options.clone(),
@@ -728,9 +1002,47 @@ impl InitializedNeonNode {
         let burnchain_signer = keychain.get_burnchain_signer();
         let relayer = Relayer::from_p2p(&mut p2p_net);
 
+        // pick up the leader keys registered before a restart, if the keychain kept them
+        let mut active_keys = vec![];
+        if miner {
+            let ic = sortdb.index_conn();
+            let sortition_tip = SortitionDB::get_canonical_burn_chain_tip(&ic)
+                .expect("Failed to get sortition tip");
+            for vrf_public_key in keychain.get_vrf_public_keys().into_iter() {
+                let leader_key_opt = SortitionDB::get_leader_key_by_public_key(
+                    &ic,
+                    &vrf_public_key,
+                    &sortition_tip.sortition_id,
+                )
+                .expect("Failed to query leader keys");
+                if let Some(leader_key) = leader_key_opt {
+                    info!(
+                        "Reusing leader key {} registered at burn block {}",
+                        &vrf_public_key.to_hex(),
+                        leader_key.block_height
+                    );
+                    active_keys.push(RegisteredKey {
+                        vrf_public_key,
+                        block_height: leader_key.block_height,
+                        op_vtxindex: leader_key.vtxindex,
+                    });
+                }
+            }
+        }
+
         let sleep_before_tenure = config.node.wait_time_for_microblocks;
 
-        spawn_miner_relayer(
+        let mining_enabled = Arc::new(AtomicBool::new(true));
+        let admin_controls = RPCAdminControls::new(
+            Some(coord_comms.clone()),
+            if miner {
+                Some(mining_enabled.clone())
+            } else {
+                None
+            },
+        );
+
+        let relayer_thread = spawn_miner_relayer(
             relayer,
             local_peer,
             config.clone(),
@@ -742,10 +1054,11 @@ impl InitializedNeonNode {
             blocks_processed.clone(),
             burnchain,
             coord_comms,
+            mempool_settings.clone(),
         )
         .expect("Failed to initialize mine/relay thread");
 
-        spawn_peer(
+        let p2p_thread = spawn_peer(
             p2p_net,
             &p2p_sock,
             &rpc_sock,
@@ -753,6 +1066,9 @@ impl InitializedNeonNode {
             5000,
             relay_send.clone(),
             sync_comms,
+            websocket_events,
+            mempool_settings,
+            admin_controls,
         )
         .expect("Failed to initialize mine/relay thread");
 
@@ -763,15 +1079,42 @@ impl InitializedNeonNode {
 
         let is_miner = miner;
 
-        let active_keys = vec![];
-
         InitializedNeonNode {
             relay_channel: relay_send,
             last_burn_block,
+            burn_block_time: None,
             burnchain_signer,
             is_miner,
+            mining_enabled,
             sleep_before_tenure,
             active_keys,
+            leader_key_lifetime: config.node.leader_key_lifetime,
+            renewing_key: false,
+            miner_state: MinerStateMachine::new(LEADER_KEY_REGISTRATION_TIMEOUT),
+            dry_run: config.miner.dry_run,
+            last_dry_run_height: None,
+            p2p_thread: Some(p2p_thread),
+            relayer_thread: Some(relayer_thread),
+        }
+    }
+
+    /// Stop the p2p and relayer threads, once the p2p thread has handed the relayer the blocks
+    /// and transactions it downloaded and the relayer has stored them.  Call this after
+    /// `shutdown::request_shutdown()` or a SIGTERM/SIGINT, which is what stops the p2p thread.
+    pub fn shutdown(&mut self) {
+        if let Some(p2p_thread) = self.p2p_thread.take() {
+            debug!("Wait for the p2p thread to exit");
+            if p2p_thread.join().is_err() {
+                error!("P2P thread panicked");
+            }
+        }
+        // the relayer handles everything queued before this
+        let _ = self.relay_channel.send(RelayerDirective::Exit);
+        if let Some(relayer_thread) = self.relayer_thread.take() {
+            debug!("Wait for the relayer thread to exit");
+            if relayer_thread.join().is_err() {
+                error!("Relayer thread panicked");
+            }
         }
     }
 
@@ -782,24 +1125,101 @@ impl InitializedNeonNode {
             return true;
         }
 
+        if !self.mining_enabled.load(Ordering::SeqCst) {
+            debug!("Mining is paused; not issuing a tenure");
+            return true;
+        }
+
+        if self.dry_run {
+            return match self.last_burn_block.clone() {
+                Some(burnchain_tip) => {
+                    if self.last_dry_run_height == Some(burnchain_tip.block_height) {
+                        return true;
+                    }
+                    self.last_dry_run_height = Some(burnchain_tip.block_height);
+                    self.relay_channel
+                        .send(RelayerDirective::DryRunTenure(burnchain_tip))
+                        .is_ok()
+                }
+                None => true,
+            };
+        }
+
         if let Some(burnchain_tip) = self.last_burn_block.clone() {
-            if let Some(key) = self.active_keys.first() {
-                debug!("Using key {:?}", &key.vrf_public_key);
-                // sleep a little before building the anchor block, to give any broadcasted
-                //   microblocks time to propagate.
-                info!(
-                    "Sleeping {} before issuing tenure",
-                    self.sleep_before_tenure
-                );
-                thread::sleep(std::time::Duration::from_millis(self.sleep_before_tenure));
-                self.relay_channel
-                    .send(RelayerDirective::RunTenure(key.clone(), burnchain_tip))
-                    .is_ok()
-            } else {
-                warn!("Skipped tenure because no active VRF key. Trying to register one.");
-                self.relay_channel
-                    .send(RelayerDirective::RegisterKey(burnchain_tip))
-                    .is_ok()
+            // block commits sent now land in the next burn block
+            let commit_height = burnchain_tip.block_height + 1;
+            let lifetime = self.leader_key_lifetime;
+            self.active_keys
+                .retain(|key| match key.expiry_height(lifetime) {
+                    Some(expiry_height) if commit_height >= expiry_height => {
+                        info!(
+                            "Leader key {} expired at burn block {}",
+                            &key.vrf_public_key.to_hex(),
+                            expiry_height
+                        );
+                        false
+                    }
+                    _ => true,
+                });
+
+            let has_key = !self.active_keys.is_empty();
+            match self
+                .miner_state
+                .next_action(burnchain_tip.block_height, has_key)
+            {
+                MinerAction::Idle => {
+                    debug!("Waiting for our leader key registration to be mined");
+                    true
+                }
+                MinerAction::RegisterKey => {
+                    info!("No active VRF key; registering one");
+                    self.relay_channel
+                        .send(RelayerDirective::RegisterKey(burnchain_tip))
+                        .is_ok()
+                }
+                MinerAction::Commit { first_attempt } => {
+                    let key = self.active_keys[0].clone();
+                    debug!("Using key {:?}", &key.vrf_public_key);
+                    let needs_successor = match key.expiry_height(lifetime) {
+                        Some(expiry_height) => {
+                            self.active_keys.len() == 1
+                                && commit_height + LEADER_KEY_RENEWAL_MARGIN >= expiry_height
+                        }
+                        None => false,
+                    };
+                    if needs_successor && !self.renewing_key {
+                        info!(
+                            "Leader key {} expires soon; registering a new one",
+                            &key.vrf_public_key.to_hex()
+                        );
+                        if self
+                            .relay_channel
+                            .send(RelayerDirective::RegisterKey(burnchain_tip.clone()))
+                            .is_err()
+                        {
+                            return false;
+                        }
+                        self.renewing_key = true;
+                    }
+
+                    if first_attempt {
+                        // sleep a little before building the anchor block, to give any broadcasted
+                        //   microblocks time to propagate -- but not so long that the block commit
+                        //   misses the next burn block.
+                        let sleep_ms = match self.burn_block_time {
+                            Some(ref model) => tenure_sleep_ms(
+                                self.sleep_before_tenure,
+                                &model.estimate(commit_height, get_epoch_time_secs()),
+                            ),
+                            None => self.sleep_before_tenure,
+                        };
+                        info!("Sleeping {} before issuing tenure", sleep_ms);
+                        thread::sleep(std::time::Duration::from_millis(sleep_ms));
+                    }
+                    self.relay_channel
+                        .send(RelayerDirective::RunTenure(key, burnchain_tip))
+                        .is_ok()
+                }
             }
         } else {
             warn!("Do not know the last burn block. As a miner, this is bad.");
@@ -831,33 +1251,104 @@ impl InitializedNeonNode {
         true
     }
 
-    fn relayer_mint_microblocks(
-        mined_block_consensus_hash: &ConsensusHash,
-        mined_block_shh: &BlockHeaderHash,
+    /// Mine the next microblock in the stream off of our last winning anchored block from the
+    /// mempool, process it locally, and push it to our peers.
+    fn relayer_mine_microblock(
+        miner_state: &mut MicroblockMinerState,
         chain_state: &mut StacksChainState,
-        burn_dbconn: &dyn BurnStateDB,
-        keychain: &Keychain,
-        consumed_execution: ExecutionCost,
-        bytes_so_far: u64,
+        sortdb: &SortitionDB,
         mem_pool: &MemPoolDB,
-    ) -> Result<StacksMicroblock, ChainstateError> {
-        let mut microblock_miner = StacksMicroblockBuilder::new(
-            mined_block_shh.clone(),
-            mined_block_consensus_hash.clone(),
-            chain_state,
-            burn_dbconn,
-            consumed_execution,
-            bytes_so_far,
-        )?;
-        let mblock_key = keychain
-            .get_microblock_key()
-            .expect("Miner attempt to mine microblocks without a microblock key");
+        relayer: &mut Relayer,
+    ) {
+        miner_state.last_mined = get_epoch_time_ms();
+
+        let mint_result = {
+            let burn_dbconn = sortdb.index_conn();
+            match StacksMicroblockBuilder::resume(
+                miner_state.parent_block_hash.clone(),
+                miner_state.parent_consensus_hash.clone(),
+                chain_state,
+                &burn_dbconn,
+                miner_state.consumed_execution.clone(),
+                miner_state.bytes_so_far,
+                &miner_state.mined_microblocks,
+            ) {
+                Ok(mut microblock_miner) => {
+                    microblock_miner.mine_next_microblock(mem_pool, &miner_state.miner_key)
+                }
+                Err(e) => Err(e),
+            }
+        };
+
+        let mined_microblock = match mint_result {
+            Ok(mined_microblock) => mined_microblock,
+            Err(ChainstateError::NoTransactionsToMine) => {
+                debug!("No transactions to mine in a microblock");
+                return;
+            }
+            Err(e) => {
+                warn!("Failed to mine microblock: {}", e);
+                return;
+            }
+        };
 
-        let mblock = microblock_miner.mine_next_microblock(mem_pool, &mblock_key)?;
+        info!(
+            "Minted microblock {} (seq={}) with {} transactions",
+            mined_microblock.block_hash(),
+            mined_microblock.header.sequence,
+            mined_microblock.txs.len();
+            "event" => "microblock_mined"
+        );
 
-        info!("Minted microblock with {} transactions", mblock.txs.len());
+        // preprocess the microblock locally
+        match chain_state.preprocess_streamed_microblock(
+            &miner_state.parent_consensus_hash,
+            &miner_state.parent_block_hash,
+            &mined_microblock,
+        ) {
+            Ok(res) => {
+                if !res {
+                    warn!(
+                        "Unhandled error while pre-processing microblock {}",
+                        mined_microblock.header.block_hash()
+                    );
+                    return;
+                }
+            }
+            Err(e) => {
+                error!(
+                    "Error while pre-processing microblock {}: {}",
+                    mined_microblock.header.block_hash(),
+                    e
+                );
+                return;
+            }
+        }
+        miner_state.mined_microblocks.push(mined_microblock.clone());
+
+        // update unconfirmed state
+        if let Err(e) = chain_state.refresh_unconfirmed_state(&sortdb.index_conn()) {
+            warn!(
+                "Failed to refresh unconfirmed state after processing microblock {}/{}-{}: {:?}",
+                &miner_state.parent_consensus_hash,
+                &miner_state.parent_block_hash,
+                mined_microblock.block_hash(),
+                &e
+            );
+        }
 
-        Ok(mblock)
+        // broadcast to peers
+        let microblock_hash = mined_microblock.header.block_hash();
+        if let Err(e) = relayer.broadcast_microblock(
+            &miner_state.parent_consensus_hash,
+            &miner_state.parent_block_hash,
+            mined_microblock,
+        ) {
+            error!(
+                "Failure trying to broadcast microblock {}: {}",
+                microblock_hash, e
+            );
+        }
     }
 
     // return stack's parent's burn header hash,
@@ -1001,6 +1492,10 @@ impl InitializedNeonNode {
                            &prev_block.parent_consensus_hash, &prev_block.anchored_block.block_hash(), prev_block.anchored_block.header.total_work.work,
                            prev_block.anchored_block.txs.len(), prev_block.my_burn_hash, parent_block_burn_height);
 
+                    // make sure our block commit still gets mined, if the mempool got busier
+                    let mut op_signer = keychain.generate_op_signer();
+                    bitcoin_controller.bump_ongoing_block_commit(&mut op_signer);
+
                     return None;
                 } else {
                     best_attempt = cmp::max(best_attempt, prev_block.attempt + 1);
@@ -1034,10 +1529,25 @@ impl InitializedNeonNode {
         // Generates a new secret key for signing the trail of microblocks
         // of the upcoming tenure.
         let microblock_secret_key = keychain.rotate_microblock_keypair();
+        save_keychain(config, keychain);
         let mblock_pubkey_hash =
             Hash160::from_node_public_key(&StacksPublicKey::from_private(&microblock_secret_key));
 
-        let coinbase_tx = inner_generate_coinbase_tx(keychain, coinbase_nonce);
+        let coinbase_tx =
+            inner_generate_coinbase_tx(keychain, coinbase_nonce, config.node.chain_id);
+
+        // STX transfers sent through the burnchain since our parent was elected
+        let burn_transfer_ops = match StacksChainState::get_transfer_stx_ops(
+            burn_db.conn(),
+            stacks_parent_header.burn_header_height,
+            &burn_block.burn_header_hash,
+        ) {
+            Ok(ops) => ops,
+            Err(e) => {
+                error!("Failure loading burnchain STX transfers: {}", e);
+                return None;
+            }
+        };
 
         let (anchored_block, consumed_execution, bytes_so_far) =
             match StacksBlockBuilder::build_anchored_block(
@@ -1049,6 +1559,7 @@ impl InitializedNeonNode {
                 vrf_proof.clone(),
                 mblock_pubkey_hash,
                 &coinbase_tx,
+                &burn_transfer_ops,
                 HELIUM_BLOCK_LIMIT.clone(),
             ) {
                 Ok(block) => block,
@@ -1067,9 +1578,21 @@ impl InitializedNeonNode {
             },
             anchored_block.block_hash(),
             anchored_block.txs.len(),
-            attempt
+            attempt;
+            "event" => "block_assembled"
         );
 
+        if config.miner.dry_run {
+            log_dry_run_block(
+                &anchored_block,
+                &consumed_execution,
+                bytes_so_far,
+                stacks_parent_header.block_height + 1,
+                &burn_block,
+            );
+            return None;
+        }
+
         // let's figure out the recipient set!
         let recipients = match get_next_recipients(
             &burn_block,
@@ -1085,10 +1608,17 @@ impl InitializedNeonNode {
             }
         };
         // let's commit
+        let burn_fee = choose_burn_fee(
+            config,
+            burn_db,
+            &burn_block,
+            &keychain.get_burnchain_signer(),
+            burn_fee_cap,
+        );
         let op = inner_generate_block_commit_op(
             keychain.get_burnchain_signer(),
             anchored_block.block_hash(),
-            burn_fee_cap,
+            burn_fee,
             &registered_key,
             parent_block_burn_height
                 .try_into()
@@ -1107,6 +1637,7 @@ impl InitializedNeonNode {
             anchored_block,
             bytes_so_far,
             attempt,
+            microblock_secret_key,
         })
     }
 
@@ -1121,6 +1652,8 @@ impl InitializedNeonNode {
     ) -> (Option<BlockSnapshot>, bool) {
         let mut last_sortitioned_block = None;
         let mut won_sortition = false;
+        let mut commit_mined = false;
+        let mut key_registered = false;
 
         let ic = sortdb.index_conn();
 
@@ -1136,6 +1669,9 @@ impl InitializedNeonNode {
         update_active_miners_count_gauge(block_commits.len() as i64);
 
         for op in block_commits.into_iter() {
+            if op.input == self.burnchain_signer {
+                commit_mined = true;
+            }
             if op.txid == block_snapshot.winning_block_txid {
                 info!(
                     "Received burnchain block #{} including block_commit_op (winning) - {} ({})",
@@ -1173,7 +1709,11 @@ impl InitializedNeonNode {
                 );
             }
             if op.address == Keychain::address_from_burnchain_signer(&self.burnchain_signer) {
-                if !ibd {
+                let known_key = self
+                    .active_keys
+                    .iter()
+                    .any(|key| key.vrf_public_key == op.public_key);
+                if !ibd && !known_key {
                     // not in initial block download, so we're not just replaying an old key.
                     // Registered key has been mined
                     self.active_keys.push(RegisteredKey {
@@ -1181,10 +1721,36 @@ impl InitializedNeonNode {
                         block_height: op.block_height as u64,
                         op_vtxindex: op.vtxindex as u32,
                     });
+                    self.renewing_key = false;
+                    key_registered = true;
+                }
+            }
+        }
+
+        if self.is_miner && !ibd {
+            match BurnBlockTimeModel::from_tip(
+                sortdb,
+                &block_snapshot,
+                BURN_BLOCK_TIME_SAMPLE_WINDOW,
+            ) {
+                Ok(model) => {
+                    self.burn_block_time = Some(model);
+                }
+                Err(e) => {
+                    warn!("Failed to measure the burn block time: {:?}", &e);
                 }
             }
         }
 
+        if self.is_miner {
+            self.miner_state.on_burn_block(&BurnBlockObservation {
+                block_height,
+                key_registered,
+                commit_mined,
+                won_sortition,
+            });
+        }
+
         // no-op on UserBurnSupport ops are not supported / produced at this point.
         self.last_burn_block = Some(block_snapshot);
 
@@ -1198,12 +1764,21 @@ impl NeonGenesisNode {
         config: Config,
         mut event_dispatcher: EventDispatcher,
         burnchain: Burnchain,
+        websocket_events: Option<WebSocketEventReceiver>,
         boot_block_exec: F,
     ) -> Self
     where
         F: FnOnce(&mut ClarityTx) -> (),
     {
-        let keychain = Keychain::default(config.node.seed.clone());
+        let mut keychain = Keychain::default(config.node.seed.clone());
+        if let Some(ref passphrase) = config.node.keychain_passphrase {
+            let keychain_path = config.get_keychain_path();
+            match keychain.load(&keychain_path, passphrase) {
+                Ok(true) => info!("Loaded the miner's keys from {}", &keychain_path),
+                Ok(false) => {}
+                Err(e) => panic!("Failed to load the miner's keys: {}", e),
+            }
+        }
         let initial_balances = config
             .initial_balances
             .iter()
@@ -1213,7 +1788,7 @@ impl NeonGenesisNode {
         // do the initial open!
         let (_chain_state, receipts) = match StacksChainState::open_and_exec(
             false,
-            TESTNET_CHAIN_ID,
+            config.node.chain_id,
             &config.get_chainstate_path(),
             Some(initial_balances),
             boot_block_exec,
@@ -1228,15 +1803,23 @@ impl NeonGenesisNode {
         };
 
         event_dispatcher.process_boot_receipts(receipts);
+        let mempool_settings = MemPoolSettings::new(&config);
 
         Self {
             keychain,
             config,
             event_dispatcher,
             burnchain,
+            websocket_events,
+            mempool_settings,
         }
     }
 
+    /// The node's mempool policy, shared with the threads it starts
+    pub fn mempool_settings(&self) -> MemPoolSettings {
+        self.mempool_settings.clone()
+    }
+
     pub fn into_initialized_leader_node(
         self,
         burnchain_tip: BurnchainTip,
@@ -1258,6 +1841,8 @@ impl NeonGenesisNode {
             coord_comms,
             sync_comms,
             self.burnchain,
+            self.websocket_events,
+            self.mempool_settings,
         )
     }
 
@@ -1282,6 +1867,8 @@ impl NeonGenesisNode {
             coord_comms,
             sync_comms,
             self.burnchain,
+            self.websocket_events,
+            self.mempool_settings,
         )
     }
 }
@@ -75,9 +75,11 @@ fn spawn_peer(
     rpc_sock: &SocketAddr,
     burn_db_path: String,
     stacks_chainstate_path: String,
+    mempool_db_path: String,
     event_dispatcher: EventDispatcher,
     exit_at_block_height: Option<u64>,
     poll_timeout: u64,
+    chain_id: u32,
 ) -> Result<JoinHandle<()>, NetError> {
     this.bind(p2p_sock, rpc_sock).unwrap();
     let server_thread = thread::spawn(move || {
@@ -96,7 +98,7 @@ fn spawn_peer(
                 }
             };
             let (mut chainstate, _) =
-                match StacksChainState::open(false, TESTNET_CHAIN_ID, &stacks_chainstate_path) {
+                match StacksChainState::open(false, chain_id, &stacks_chainstate_path) {
                     Ok(x) => x,
                     Err(e) => {
                         warn!("Error while connecting chainstate db in peer loop: {}", e);
@@ -105,15 +107,19 @@ fn spawn_peer(
                     }
                 };
 
-            let mut mem_pool =
-                match MemPoolDB::open(false, TESTNET_CHAIN_ID, &stacks_chainstate_path) {
-                    Ok(x) => x,
-                    Err(e) => {
-                        warn!("Error while connecting to mempool db in peer loop: {}", e);
-                        thread::sleep(time::Duration::from_secs(1));
-                        continue;
-                    }
-                };
+            let mut mem_pool = match MemPoolDB::open_at(
+                false,
+                chain_id,
+                &stacks_chainstate_path,
+                &mempool_db_path,
+            ) {
+                Ok(x) => x,
+                Err(e) => {
+                    warn!("Error while connecting to mempool db in peer loop: {}", e);
+                    thread::sleep(time::Duration::from_secs(1));
+                    continue;
+                }
+            };
 
             let net_result = this
                 .run(
@@ -150,7 +156,7 @@ impl Node {
 
         let chain_state_result = StacksChainState::open_and_exec(
             false,
-            TESTNET_CHAIN_ID,
+            config.node.chain_id,
             &config.get_chainstate_path(),
             Some(initial_balances),
             boot_block_exec,
@@ -205,7 +211,7 @@ impl Node {
         let sortdb_path = config.get_burn_db_file_path();
 
         let (chain_state, _) =
-            match StacksChainState::open(false, TESTNET_CHAIN_ID, &chainstate_path) {
+            match StacksChainState::open(false, config.node.chain_id, &chainstate_path) {
                 Ok(x) => x,
                 Err(_e) => panic!(),
             };
@@ -252,12 +258,13 @@ impl Node {
         let sortdb = SortitionDB::open(&self.config.get_burn_db_file_path(), true)
             .expect("Error while instantiating burnchain db");
 
-        let burnchain = Burnchain::new(
+        let mut burnchain = Burnchain::new(
             &self.config.get_burn_db_path(),
             &self.config.burnchain.chain,
             "regtest",
         )
         .expect("Error while instantiating burnchain");
+        burnchain.peer_version = self.config.node.peer_version;
 
         let view = {
             let ic = sortdb.index_conn();
@@ -306,7 +313,7 @@ impl Node {
         let mut peerdb = PeerDB::connect(
             &self.config.get_peer_db_path(),
             true,
-            TESTNET_CHAIN_ID,
+            self.config.node.chain_id,
             burnchain.network_id,
             Some(node_privkey),
             self.config.connection_options.private_key_lifetime.clone(),
@@ -345,7 +352,7 @@ impl Node {
         let p2p_net = PeerNetwork::new(
             peerdb,
             local_peer,
-            TESTNET_PEER_VERSION,
+            self.config.node.peer_version,
             burnchain,
             view,
             self.config.connection_options.clone(),
@@ -356,9 +363,11 @@ impl Node {
             &rpc_sock,
             self.config.get_burn_db_file_path(),
             self.config.get_chainstate_path(),
+            self.config.get_mempool_db_path(),
             event_dispatcher,
             exit_at_block_height,
             1000,
+            self.config.node.chain_id,
         )
         .unwrap();
 
@@ -412,6 +421,9 @@ impl Node {
                 BlockstackOperationType::UserBurnSupport(_) => {
                     // no-op, UserBurnSupport ops are not supported / produced at this point.
                 }
+                BlockstackOperationType::PreStx(_) | BlockstackOperationType::TransferStx(_) => {
+                    // no-op, STX operations are materialized by the Stacks chain.
+                }
             }
         }
 
@@ -486,8 +498,13 @@ impl Node {
             },
         };
 
-        let mem_pool = MemPoolDB::open(false, TESTNET_CHAIN_ID, &self.chain_state.root_path)
-            .expect("FATAL: failed to open mempool");
+        let mem_pool = MemPoolDB::open_at(
+            false,
+            self.config.node.chain_id,
+            &self.chain_state.root_path,
+            &self.config.get_mempool_db_path(),
+        )
+        .expect("FATAL: failed to open mempool");
 
         // Construct the coinbase transaction - 1st txn that should be handled and included in
         // the upcoming tenure.
@@ -744,7 +761,7 @@ impl Node {
             tx_auth,
             TransactionPayload::Coinbase(CoinbasePayload([0u8; 32])),
         );
-        tx.chain_id = TESTNET_CHAIN_ID;
+        tx.chain_id = self.config.node.chain_id;
         tx.anchor_mode = TransactionAnchorMode::OnChainOnly;
         let mut tx_signer = StacksTransactionSigner::new(&tx);
         self.keychain.sign_as_origin(&mut tx_signer);
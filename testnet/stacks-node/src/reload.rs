@@ -0,0 +1,284 @@
+//! Re-reading the node's config file on SIGHUP, and applying the settings that can change
+//! while the node runs: the log levels, the event observers, and the mempool policy.  Changes
+//! to any other key are logged, and take effect the next time the node starts.
+
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use toml::value::Table;
+use toml::Value;
+
+use stacks::core::mempool::{MemPoolDB, MemPoolGCPolicy};
+use stacks::util::log;
+use stacks::vm::analysis::strict_checker::StrictProfile;
+
+use crate::{Config, ConfigFile, EventDispatcher};
+
+/// Keys applied to a running node when its config is reloaded
+pub const RELOADABLE_KEYS: &[&str] = &[
+    "node.log_level",
+    "node.log_modules",
+    "events_observer",
+    "mempool.max_tx_age_blocks",
+    "mempool.max_tx_age_secs",
+    "mempool.max_txs",
+    "mempool.strict_analysis",
+];
+
+/// How often the reload thread checks whether a SIGHUP arrived
+const SIGHUP_POLL_INTERVAL_MS: u64 = 500;
+
+static SIGHUP_RECEIVED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn handle_sighup(_signal: libc::c_int) {
+    SIGHUP_RECEIVED.store(true, Ordering::SeqCst);
+}
+
+/// Make SIGHUP request a config reload, instead of terminating the process
+#[cfg(unix)]
+fn install_sighup_handler() {
+    unsafe {
+        libc::signal(libc::SIGHUP, handle_sighup as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+fn install_sighup_handler() {}
+
+struct MemPoolSettingsInner {
+    version: u64,
+    gc_policy: MemPoolGCPolicy,
+    strict_profile: Option<StrictProfile>,
+}
+
+/// The mempool policy of a running node.  Clones share it; each thread with its own
+/// `MemPoolDB` applies it again whenever it changes.
+#[derive(Clone)]
+pub struct MemPoolSettings {
+    inner: Arc<Mutex<MemPoolSettingsInner>>,
+}
+
+impl MemPoolSettings {
+    pub fn new(config: &Config) -> MemPoolSettings {
+        MemPoolSettings {
+            inner: Arc::new(Mutex::new(MemPoolSettingsInner {
+                version: 1,
+                gc_policy: config.mempool.clone(),
+                strict_profile: config.strict_analysis.clone(),
+            })),
+        }
+    }
+
+    pub fn set(&self, gc_policy: MemPoolGCPolicy, strict_profile: Option<StrictProfile>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.version += 1;
+        inner.gc_policy = gc_policy;
+        inner.strict_profile = strict_profile;
+    }
+
+    /// Apply the policy to `mem_pool` if it changed since `applied_version`, which starts at 0
+    pub fn apply(&self, mem_pool: &mut MemPoolDB, applied_version: &mut u64) {
+        let inner = self.inner.lock().unwrap();
+        if inner.version != *applied_version {
+            mem_pool.set_gc_policy(inner.gc_policy.clone());
+            mem_pool.set_strict_profile(inner.strict_profile.clone());
+            *applied_version = inner.version;
+        }
+    }
+}
+
+/// Dotted paths of the keys whose values differ between two parsed config files.  Arrays of
+/// tables, like `events_observer`, are compared as a whole.
+pub fn changed_keys(old: &Table, new: &Table) -> Vec<String> {
+    let mut changed = vec![];
+    diff_tables(old, new, "", &mut changed);
+    changed
+}
+
+fn diff_tables(old: &Table, new: &Table, prefix: &str, changed: &mut Vec<String>) {
+    let empty = Table::new();
+    let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    for key in keys.into_iter() {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match (old.get(key), new.get(key)) {
+            (Some(Value::Table(old_section)), Some(Value::Table(new_section))) => {
+                diff_tables(old_section, new_section, &path, changed)
+            }
+            (Some(Value::Table(old_section)), None) => {
+                diff_tables(old_section, &empty, &path, changed)
+            }
+            (None, Some(Value::Table(new_section))) => {
+                diff_tables(&empty, new_section, &path, changed)
+            }
+            (old_value, new_value) => {
+                if old_value != new_value {
+                    changed.push(path);
+                }
+            }
+        }
+    }
+}
+
+fn load_config(config_path: &str) -> Result<(ConfigFile, Table), Vec<String>> {
+    let content = fs::read_to_string(config_path)
+        .map_err(|e| vec![format!("Failed to read {}: {}", config_path, e)])?;
+    ConfigFile::load_str_with_table(&content)
+}
+
+/// Reloads a running node's config file on SIGHUP
+pub struct ConfigReloader {
+    config_path: String,
+    /// the keys the node started with
+    startup_keys: Table,
+    /// the keys as of the last reload
+    current_keys: Table,
+    event_dispatcher: EventDispatcher,
+    mempool_settings: MemPoolSettings,
+}
+
+impl ConfigReloader {
+    pub fn new(
+        config_path: &str,
+        event_dispatcher: EventDispatcher,
+        mempool_settings: MemPoolSettings,
+    ) -> Result<ConfigReloader, String> {
+        let (_, keys) = load_config(config_path).map_err(|problems| problems.join("; "))?;
+        Ok(ConfigReloader {
+            config_path: config_path.to_string(),
+            startup_keys: keys.clone(),
+            current_keys: keys,
+            event_dispatcher,
+            mempool_settings,
+        })
+    }
+
+    /// Reload the config on every SIGHUP, from a background thread
+    pub fn spawn(mut self) -> thread::JoinHandle<()> {
+        install_sighup_handler();
+        info!(
+            "Send SIGHUP to reload {} ({})",
+            &self.config_path,
+            RELOADABLE_KEYS.join(", ")
+        );
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_millis(SIGHUP_POLL_INTERVAL_MS));
+            if SIGHUP_RECEIVED.swap(false, Ordering::SeqCst) {
+                self.reload();
+            }
+        })
+    }
+
+    /// Re-read the config file and apply whatever changed that can be applied.  An invalid
+    /// config file is logged, and the running settings are kept.
+    pub fn reload(&mut self) {
+        info!("Reloading config from {}", &self.config_path);
+        let (config_file, keys) = match load_config(&self.config_path) {
+            Ok(loaded) => loaded,
+            Err(problems) => {
+                warn!(
+                    "Config not reloaded, {} is invalid: {}",
+                    &self.config_path,
+                    problems.join("; ")
+                );
+                return;
+            }
+        };
+        let config = Config::from_config_file(config_file);
+
+        let applied: Vec<String> = changed_keys(&self.current_keys, &keys)
+            .into_iter()
+            .filter(|key| RELOADABLE_KEYS.contains(&key.as_str()))
+            .collect();
+
+        if applied.iter().any(|key| key == "node.log_level") {
+            log::set_loglevel(config.node.log_level);
+        }
+        if applied.iter().any(|key| key == "node.log_modules") {
+            log::set_module_loglevels(config.node.log_modules.clone());
+        }
+        if applied.iter().any(|key| key == "events_observer") {
+            self.event_dispatcher
+                .replace_observers(&config.events_observers);
+        }
+        if applied.iter().any(|key| key.starts_with("mempool.")) {
+            self.mempool_settings
+                .set(config.mempool.clone(), config.strict_analysis.clone());
+        }
+
+        if applied.len() > 0 {
+            info!("Config reloaded, applied changes to {}", applied.join(", "));
+        } else {
+            info!("Config reloaded, no changes to apply");
+        }
+
+        let needs_restart: Vec<String> = changed_keys(&self.startup_keys, &keys)
+            .into_iter()
+            .filter(|key| !RELOADABLE_KEYS.contains(&key.as_str()))
+            .collect();
+        if needs_restart.len() > 0 {
+            warn!(
+                "Config changes to {} take effect only after a restart",
+                needs_restart.join(", ")
+            );
+        }
+
+        self.current_keys = keys;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_keys() {
+        let old: Table = toml::from_str(
+            r#"
+            [node]
+            rpc_bind = "127.0.0.1:20443"
+            log_level = "info"
+
+            [[events_observer]]
+            endpoint = "127.0.0.1:3700"
+            events_keys = ["*"]
+            "#,
+        )
+        .unwrap();
+        let new: Table = toml::from_str(
+            r#"
+            [node]
+            rpc_bind = "127.0.0.1:30443"
+            log_level = "info"
+
+            [[events_observer]]
+            endpoint = "127.0.0.1:3700"
+            events_keys = ["stx"]
+
+            [mempool]
+            max_txs = 100
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            changed_keys(&old, &new),
+            vec![
+                "events_observer".to_string(),
+                "mempool.max_txs".to_string(),
+                "node.rpc_bind".to_string(),
+            ]
+        );
+        assert!(changed_keys(&new, &new).is_empty());
+    }
+}
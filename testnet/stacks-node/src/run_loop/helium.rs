@@ -1,6 +1,7 @@
 use crate::burnchains::Error as BurnchainControllerError;
 use crate::{
-    BitcoinRegtestController, BurnchainController, ChainTip, Config, MocknetController, Node,
+    BitcoinRegtestController, BurnchainController, ChainTip, Config, MockBurnchainController,
+    MocknetController, Node,
 };
 use stacks::chainstate::stacks::db::ClarityTx;
 
@@ -45,6 +46,7 @@ impl RunLoop {
         let mut burnchain: Box<dyn BurnchainController> = match &self.config.burnchain.mode[..] {
             "helium" => Box::new(BitcoinRegtestController::new(self.config.clone(), None)),
             "mocknet" => MocknetController::generic(self.config.clone()),
+            "mock" => MockBurnchainController::generic(self.config.clone()),
             _ => unreachable!(),
         };
 
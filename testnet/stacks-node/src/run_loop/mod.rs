@@ -147,3 +147,15 @@ pub struct RegisteredKey {
     pub op_vtxindex: u32,
     pub vrf_public_key: VRFPublicKey,
 }
+
+impl RegisteredKey {
+    /// The first burn block height whose block commits may no longer use this key, given how
+    /// many burn blocks a key lasts.  A key whose lifetime is 0 never expires.
+    pub fn expiry_height(&self, lifetime: u64) -> Option<u64> {
+        if lifetime == 0 {
+            None
+        } else {
+            Some(self.block_height.saturating_add(lifetime))
+        }
+    }
+}
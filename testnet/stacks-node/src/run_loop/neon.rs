@@ -1,5 +1,5 @@
 use crate::{
-    neon_node, BitcoinRegtestController, BurnchainController, Config, EventDispatcher, Keychain,
+    BitcoinRegtestController, BurnchainController, Config, EventDispatcher, Keychain,
     NeonGenesisNode,
 };
 use stacks::burnchains::bitcoin::address::BitcoinAddress;
@@ -8,12 +8,20 @@ use stacks::burnchains::{Address, Burnchain};
 use stacks::chainstate::burn::db::sortdb::SortitionDB;
 use stacks::chainstate::coordinator::comm::{CoordinatorChannels, CoordinatorReceivers};
 use stacks::chainstate::coordinator::{ChainsCoordinator, CoordinatorCommunication};
+use stacks::chainstate::stacks::StacksBlockHeader;
+use stacks::net::atlas::AtlasDB;
+use stacks::net::websocket::websocket_event_channel;
 use std::cmp;
 use std::thread;
 
 use super::RunLoopCallbacks;
 
+use crate::disk_usage::spawn_disk_usage_reporter;
+use crate::integrity::startup_integrity_check;
 use crate::monitoring::start_serving_monitoring_metrics;
+use crate::reload::ConfigReloader;
+use crate::shutdown::{self, SyncCheckpoint};
+use stacks::monitoring::update_sortition_lag_gauge;
 
 use crate::syncctl::PoxSyncWatchdog;
 
@@ -21,6 +29,8 @@ use crate::syncctl::PoxSyncWatchdog;
 #[cfg(test)]
 pub struct RunLoop {
     config: Config,
+    /// the file `config` was loaded from, which is reloaded on SIGHUP
+    config_path: Option<String>,
     pub callbacks: RunLoopCallbacks,
     blocks_processed: std::sync::Arc<std::sync::atomic::AtomicU64>,
     coordinator_channels: Option<(CoordinatorReceivers, CoordinatorChannels)>,
@@ -29,6 +39,8 @@ pub struct RunLoop {
 #[cfg(not(test))]
 pub struct RunLoop {
     config: Config,
+    /// the file `config` was loaded from, which is reloaded on SIGHUP
+    config_path: Option<String>,
     pub callbacks: RunLoopCallbacks,
     coordinator_channels: Option<(CoordinatorReceivers, CoordinatorChannels)>,
 }
@@ -40,6 +52,7 @@ impl RunLoop {
         let channels = CoordinatorCommunication::instantiate();
         Self {
             config,
+            config_path: None,
             coordinator_channels: Some(channels),
             callbacks: RunLoopCallbacks::new(),
         }
@@ -50,12 +63,18 @@ impl RunLoop {
         let channels = CoordinatorCommunication::instantiate();
         Self {
             config,
+            config_path: None,
             coordinator_channels: Some(channels),
             callbacks: RunLoopCallbacks::new(),
             blocks_processed: std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0)),
         }
     }
 
+    /// Reload the log level, event observers and mempool policy from this file on SIGHUP
+    pub fn set_config_path(&mut self, config_path: &str) {
+        self.config_path = Some(config_path.to_string());
+    }
+
     pub fn get_coordinator_channel(&self) -> Option<CoordinatorChannels> {
         self.coordinator_channels.as_ref().map(|x| x.1.clone())
     }
@@ -89,6 +108,17 @@ impl RunLoop {
             .take()
             .expect("Run loop already started, can only start once after initialization.");
 
+        // Repair whatever an unclean shutdown left behind, before anything opens the databases.
+        let sortdb_path = match burnchain_opt {
+            Some(ref burnchain) => burnchain.get_db_path(),
+            None => BitcoinRegtestController::new_dummy(self.config.clone())
+                .get_burnchain()
+                .get_db_path(),
+        };
+        if !startup_integrity_check(&self.config, &sortdb_path) {
+            return;
+        }
+
         // Initialize and start the burnchain.
         let mut burnchain = BitcoinRegtestController::with_burnchain(
             self.config.clone(),
@@ -97,7 +127,10 @@ impl RunLoop {
         );
         let pox_constants = burnchain.get_pox_constants();
 
-        let is_miner = if self.config.node.miner {
+        let is_miner = if self.config.node.miner && self.config.miner.dry_run {
+            info!("Miner node: starting up in dry-run mode; no leader keys or block commits will be sent.");
+            true
+        } else if self.config.node.miner {
             let keychain = Keychain::default(self.config.node.seed.clone());
             let btc_addr = BitcoinAddress::from_bytes(
                 self.config.burnchain.get_bitcoin_network().1,
@@ -131,7 +164,7 @@ impl RunLoop {
         };
 
         let mainnet = false;
-        let chainid = neon_node::TESTNET_CHAIN_ID;
+        let chainid = self.config.node.chain_id;
         let block_limit = self.config.block_limit.clone();
         let initial_balances = self
             .config
@@ -146,12 +179,32 @@ impl RunLoop {
             event_dispatcher.register_observer(observer);
         }
 
+        // the RPC server streams events to WebSocket subscribers, if enabled
+        let websocket_events = if self.config.connection_options.enable_websocket_events {
+            let (websocket_send, websocket_recv) = websocket_event_channel();
+            event_dispatcher.set_websocket_events(websocket_send);
+            Some(websocket_recv)
+        } else {
+            None
+        };
+
         let mut coordinator_dispatcher = event_dispatcher.clone();
+        let reload_dispatcher = event_dispatcher.clone();
         let burnchain_config = burnchain.get_burnchain();
         let chainstate_path = self.config.get_chainstate_path();
         let coordinator_burnchain_config = burnchain_config.clone();
 
-        thread::spawn(move || {
+        // the coordinator records the attachments declared in each block, if we track any
+        let atlas_db = if self.config.atlas.contracts.len() > 0 {
+            Some(
+                AtlasDB::open(&self.config.get_atlas_db_path(), self.config.atlas.clone())
+                    .expect("FATAL: failed to open atlas DB"),
+            )
+        } else {
+            None
+        };
+
+        let coordinator_thread = thread::spawn(move || {
             ChainsCoordinator::run(
                 &chainstate_path,
                 coordinator_burnchain_config,
@@ -161,6 +214,7 @@ impl RunLoop {
                 block_limit,
                 &mut coordinator_dispatcher,
                 coordinator_receivers,
+                atlas_db,
                 |_| {},
             );
         });
@@ -180,13 +234,31 @@ impl RunLoop {
         )
         .unwrap();
 
+        if self.config.burnchain.long_poll {
+            let config = self.config.clone();
+            pox_watchdog.set_burnchain_block_waiter(Box::new(move |height, timeout_secs| {
+                BitcoinRegtestController::wait_for_burn_block(&config, height, timeout_secs)
+            }));
+        }
+
         // setup genesis
         let node = NeonGenesisNode::new(
             self.config.clone(),
             event_dispatcher,
             burnchain_config.clone(),
+            websocket_events,
             |_| {},
         );
+
+        if let Some(ref config_path) = self.config_path {
+            match ConfigReloader::new(config_path, reload_dispatcher, node.mempool_settings()) {
+                Ok(reloader) => {
+                    reloader.spawn();
+                }
+                Err(e) => warn!("Config reloading disabled: {}", e),
+            }
+        }
+        let shutdown_coord_comms = coordinator_senders.clone();
         let mut node = if is_miner {
             node.into_initialized_leader_node(
                 burnchain_tip.clone(),
@@ -216,13 +288,43 @@ impl RunLoop {
                 start_serving_monitoring_metrics(prometheus_bind);
             });
         }
+        spawn_disk_usage_reporter(self.config.clone());
 
         let mut burnchain_height = 1;
 
         // prepare to fetch the first reward cycle!
         target_burnchain_block_height = pox_constants.reward_cycle_length as u64;
 
+        // pick up where the last run stopped, and note that this run has not stopped yet
+        let checkpoint_path = self.config.get_sync_checkpoint_path();
+        match SyncCheckpoint::load(&checkpoint_path) {
+            Some(ref checkpoint) if checkpoint.clean_shutdown => {
+                info!(
+                    "Resuming sync from burn block {} (last clean shutdown at {})",
+                    checkpoint.burn_block_height, checkpoint.timestamp
+                );
+                target_burnchain_block_height =
+                    cmp::max(target_burnchain_block_height, checkpoint.burn_block_height);
+            }
+            Some(ref checkpoint) => {
+                warn!(
+                    "Node did not shut down cleanly after its run from {}; resuming sync from burn block {}",
+                    checkpoint.timestamp, block_height
+                );
+            }
+            None => {}
+        }
+        let running_checkpoint = SyncCheckpoint::new(block_height, block_height, None, false);
+        if let Err(e) = running_checkpoint.store(&checkpoint_path) {
+            warn!("{}", e);
+        }
+        shutdown::install_shutdown_handler();
+
         loop {
+            if shutdown::shutdown_requested() {
+                break;
+            }
+
             // wait for the p2p state-machine to do at least one pass
             debug!("Wait until we reach steady-state before processing more burnchain blocks...");
             // wait until it's okay to process the next sortitions
@@ -276,16 +378,22 @@ impl RunLoop {
                         error!("Block relayer and miner hung up, exiting.");
                         return;
                     }
+
+                    block_height = block_to_process;
+                    if shutdown::shutdown_requested() {
+                        break;
+                    }
                 }
 
-                block_height = next_height;
                 debug!(
                     "Synchronized burnchain up to block height {} (chain tip height is {})",
                     block_height, burnchain_height
                 );
             }
 
-            if block_height >= burnchain_height && !ibd {
+            update_sortition_lag_gauge(burnchain_height.saturating_sub(block_height) as i64);
+
+            if block_height >= burnchain_height && !ibd && !shutdown::shutdown_requested() {
                 // at tip, and not downloading. proceed to mine.
                 debug!(
                     "Synchronized full burnchain up to height {}. Proceeding to mine blocks",
@@ -298,5 +406,31 @@ impl RunLoop {
                 }
             }
         }
+
+        // stop in the order data flows: the p2p thread hands its downloads to the relayer, the
+        // relayer stores them and hands them to the coordinator, and the coordinator processes them
+        info!("Shutting down: stopping the p2p network and the miner/relayer");
+        node.shutdown();
+
+        info!("Shutting down: stopping the chains coordinator");
+        shutdown_coord_comms.stop_chains_coordinator();
+        if coordinator_thread.join().is_err() {
+            error!("Chains coordinator thread panicked");
+        }
+
+        let stacks_tip =
+            SortitionDB::get_canonical_stacks_chain_tip_hash(burnchain.sortdb_ref().conn())
+                .ok()
+                .map(|(consensus_hash, block_hash)| {
+                    StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_hash).to_hex()
+                });
+        let checkpoint = SyncCheckpoint::new(block_height, burnchain_height, stacks_tip, true);
+        match checkpoint.store(&checkpoint_path) {
+            Ok(()) => info!(
+                "Shut down cleanly at burn block {} (burnchain height {})",
+                block_height, burnchain_height
+            ),
+            Err(e) => error!("Shut down, but failed to record the sync checkpoint: {}", e),
+        }
     }
 }
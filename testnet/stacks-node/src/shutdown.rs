@@ -0,0 +1,124 @@
+//! Stopping a running node on SIGTERM or SIGINT without leaving half-written chain state behind,
+//! and the sync checkpoint it writes on the way out, so the next start can tell whether it was
+//! stopped cleanly and resume syncing where it left off.
+
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use stacks::util::get_epoch_time_secs;
+
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn handle_shutdown_signal(_signal: libc::c_int) {
+    if SHUTDOWN_REQUESTED.swap(true, Ordering::SeqCst) {
+        // second signal -- the operator does not want to wait
+        unsafe {
+            libc::_exit(1);
+        }
+    }
+}
+
+/// Make SIGTERM and SIGINT stop the node gracefully, instead of terminating the process.  A
+/// second signal terminates it right away.
+#[cfg(unix)]
+pub fn install_shutdown_handler() {
+    unsafe {
+        libc::signal(libc::SIGTERM, handle_shutdown_signal as libc::sighandler_t);
+        libc::signal(libc::SIGINT, handle_shutdown_signal as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn install_shutdown_handler() {}
+
+/// Ask the node to stop, as SIGTERM or SIGINT would
+pub fn request_shutdown() {
+    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Has the node been asked to stop?  Long-running loops check this between units of work, so
+/// that they never stop in the middle of a DB transaction.
+pub fn shutdown_requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Where a node's sync stood when it last stopped, or, while it runs, when it started
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncCheckpoint {
+    /// highest burnchain block whose sortition was processed
+    pub burn_block_height: u64,
+    /// highest burnchain block downloaded
+    pub burnchain_height: u64,
+    /// canonical Stacks chain tip, as an index block hash
+    pub stacks_tip: Option<String>,
+    /// whether the node stopped gracefully.  The node sets this to false while it runs, so a
+    /// checkpoint that is still false at startup means the node crashed or was killed.
+    pub clean_shutdown: bool,
+    pub timestamp: u64,
+}
+
+impl SyncCheckpoint {
+    pub fn new(
+        burn_block_height: u64,
+        burnchain_height: u64,
+        stacks_tip: Option<String>,
+        clean_shutdown: bool,
+    ) -> SyncCheckpoint {
+        SyncCheckpoint {
+            burn_block_height,
+            burnchain_height,
+            stacks_tip,
+            clean_shutdown,
+            timestamp: get_epoch_time_secs(),
+        }
+    }
+
+    /// Load the checkpoint at `path`, if there is a readable one
+    pub fn load(path: &str) -> Option<SyncCheckpoint> {
+        let content = fs::read_to_string(path).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(checkpoint) => Some(checkpoint),
+            Err(e) => {
+                warn!("Ignoring unreadable sync checkpoint {}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    /// Write the checkpoint to `path`.  The old checkpoint is replaced atomically, so a crash
+    /// while writing never leaves a torn file behind.
+    pub fn store(&self, path: &str) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize sync checkpoint: {}", e))?;
+        let tmp_path = format!("{}.tmp", path);
+        fs::write(&tmp_path, content)
+            .map_err(|e| format!("Failed to write {}: {}", &tmp_path, e))?;
+        fs::rename(&tmp_path, path).map_err(|e| format!("Failed to write {}: {}", path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_checkpoint_store_load() {
+        let path = "/tmp/stacks-node-test-sync-checkpoint.json";
+        let _ = fs::remove_file(path);
+        assert_eq!(SyncCheckpoint::load(path), None);
+
+        let checkpoint = SyncCheckpoint::new(
+            1900,
+            1905,
+            Some("a4d7ad1bc8c2b4c1de5c2d3d6f31aa2c4b07fa31ff2d1a14fefe1b1eb2bd7c4e".to_string()),
+            true,
+        );
+        checkpoint.store(path).unwrap();
+        assert_eq!(SyncCheckpoint::load(path), Some(checkpoint));
+
+        fs::write(path, "not json").unwrap();
+        assert_eq!(SyncCheckpoint::load(path), None);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,494 @@
+//! Chainstate snapshots, so a new node can start from another node's state instead of replaying
+//! the whole burnchain.
+//!
+//! A snapshot is a single archive of the node's `burnchain/` and `chainstate/` directories.  It
+//! starts with a magic number and a length-prefixed JSON manifest, which records the consensus
+//! hash of the canonical burnchain tip the snapshot was taken at and the size and SHA-256 of each
+//! file.  The files' contents follow, in manifest order.  Restoring checks every file against the
+//! manifest, and then checks that the restored sortition DB's canonical tip has the manifest's
+//! consensus hash.
+//!
+//! Paths in the manifest start with `burnchain/` or `chainstate/`, wherever the node keeps those
+//! directories, so a snapshot can be restored into a node whose directories are laid out
+//! differently.
+//!
+//! The node must be stopped while a snapshot is created or restored.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use stacks::chainstate::burn::db::sortdb::SortitionDB;
+use stacks::util::get_epoch_time_secs;
+use stacks::util::hash::to_hex;
+
+use crate::Config;
+
+const SNAPSHOT_MAGIC: &[u8; 8] = b"STXSNAP1";
+const MAX_MANIFEST_LEN: u32 = 64 * 1024 * 1024;
+
+/// The directories that go into a snapshot
+const SNAPSHOT_DIRS: &[&str] = &["burnchain", "chainstate"];
+
+/// Where each of a node's snapshot directories is on disk
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotDirs {
+    pub burnchain: PathBuf,
+    pub chainstate: PathBuf,
+}
+
+impl SnapshotDirs {
+    /// Both directories in their default places under `root`
+    pub fn under(root: &Path) -> SnapshotDirs {
+        SnapshotDirs {
+            burnchain: root.join("burnchain"),
+            chainstate: root.join("chainstate"),
+        }
+    }
+
+    pub fn from_config(conf: &Config) -> SnapshotDirs {
+        SnapshotDirs {
+            burnchain: PathBuf::from(conf.node.get_burnchain_path()),
+            chainstate: PathBuf::from(conf.get_chainstate_path()),
+        }
+    }
+
+    /// Where one of `SNAPSHOT_DIRS` is
+    fn dir(&self, name: &str) -> &Path {
+        match name {
+            "burnchain" => &self.burnchain,
+            "chainstate" => &self.chainstate,
+            _ => unreachable!("not a snapshot directory: {}", name),
+        }
+    }
+
+    /// Where a manifest path is on disk.  The path must start with one of `SNAPSHOT_DIRS`.
+    fn resolve(&self, path: &str) -> PathBuf {
+        let mut parts = path.splitn(2, '/');
+        let dir = parts.next().unwrap_or("");
+        let rest = parts.next().unwrap_or("");
+        self.dir(dir).join(rest)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotFile {
+    /// path relative to the working directory, with `/` separators
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotTip {
+    pub consensus_hash: String,
+    pub burn_block_height: u64,
+    pub burn_header_hash: String,
+    pub stacks_tip_consensus_hash: String,
+    pub stacks_tip_block_hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    pub tip: SnapshotTip,
+    pub created_at: u64,
+    pub files: Vec<SnapshotFile>,
+}
+
+/// Files that are not chainstate, or that only exist while a DB is open
+fn is_excluded(file_name: &str) -> bool {
+    file_name.starts_with("mempool.db")
+        || file_name.ends_with("-journal")
+        || file_name.ends_with("-wal")
+        || file_name.ends_with("-shm")
+        || file_name.ends_with(".tmp")
+}
+
+fn collect_files(dirs: &SnapshotDirs, rel_dir: &str, files: &mut Vec<String>) -> io::Result<()> {
+    let mut entries = fs::read_dir(dirs.resolve(rel_dir))?
+        .map(|entry| entry.map(|e| e.file_name()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    for name in entries.into_iter() {
+        let name = name
+            .to_str()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "non-UTF-8 file name"))?;
+        let rel_path = format!("{}/{}", rel_dir, name);
+        let md = fs::metadata(dirs.resolve(&rel_path))?;
+        if md.is_dir() {
+            collect_files(dirs, &rel_path, files)?;
+        } else if !is_excluded(name) {
+            files.push(rel_path);
+        }
+    }
+    Ok(())
+}
+
+/// A manifest path is only acceptable if it stays inside the directory it is restored to
+fn check_relative_path(path: &str) -> Result<PathBuf, String> {
+    let path_buf = PathBuf::from(path);
+    let in_snapshot_dir = SNAPSHOT_DIRS
+        .iter()
+        .any(|dir| path.starts_with(&format!("{}/", dir)));
+    if !in_snapshot_dir
+        || path_buf.components().any(|c| match c {
+            Component::Normal(_) => false,
+            _ => true,
+        })
+    {
+        return Err(format!("Invalid path in snapshot manifest: {}", path));
+    }
+    Ok(path_buf)
+}
+
+/// Copy exactly `size` bytes from `input` to `output`, and return their SHA-256
+fn copy_and_hash<R: Read, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    size: u64,
+) -> Result<String, String> {
+    let mut hasher = Sha256::new();
+    let mut remaining = size;
+    let mut buf = vec![0u8; 65536];
+    while remaining > 0 {
+        let want = std::cmp::min(remaining, buf.len() as u64) as usize;
+        let nr = input
+            .read(&mut buf[0..want])
+            .map_err(|e| format!("Read error: {}", e))?;
+        if nr == 0 {
+            return Err("Unexpected end of file".to_string());
+        }
+        hasher.input(&buf[0..nr]);
+        output
+            .write_all(&buf[0..nr])
+            .map_err(|e| format!("Write error: {}", e))?;
+        remaining -= nr as u64;
+    }
+    Ok(to_hex(hasher.result().as_slice()))
+}
+
+/// Write a snapshot of the snapshot directories, taken at `tip`, to `fd`
+pub fn write_snapshot<W: Write>(
+    dirs: &SnapshotDirs,
+    tip: SnapshotTip,
+    fd: &mut W,
+) -> Result<SnapshotManifest, String> {
+    let mut paths = vec![];
+    for dir in SNAPSHOT_DIRS.iter() {
+        collect_files(dirs, dir, &mut paths)
+            .map_err(|e| format!("Failed to list {}: {}", dirs.dir(dir).display(), e))?;
+    }
+
+    let mut files = vec![];
+    for path in paths.into_iter() {
+        let mut file = fs::File::open(dirs.resolve(&path))
+            .map_err(|e| format!("Failed to open {}: {}", &path, e))?;
+        let size = file
+            .metadata()
+            .map_err(|e| format!("Failed to stat {}: {}", &path, e))?
+            .len();
+        let sha256 = copy_and_hash(&mut file, &mut io::sink(), size)
+            .map_err(|e| format!("Failed to hash {}: {}", &path, e))?;
+        files.push(SnapshotFile { path, size, sha256 });
+    }
+
+    let manifest = SnapshotManifest {
+        tip,
+        created_at: get_epoch_time_secs(),
+        files,
+    };
+    let manifest_bytes = serde_json::to_vec(&manifest).map_err(|e| e.to_string())?;
+
+    fd.write_all(SNAPSHOT_MAGIC)
+        .and_then(|_| fd.write_all(&(manifest_bytes.len() as u32).to_be_bytes()))
+        .and_then(|_| fd.write_all(&manifest_bytes))
+        .map_err(|e| format!("Write error: {}", e))?;
+
+    for file in manifest.files.iter() {
+        let mut input = fs::File::open(dirs.resolve(&file.path))
+            .map_err(|e| format!("Failed to open {}: {}", &file.path, e))?;
+        let sha256 = copy_and_hash(&mut input, fd, file.size)
+            .map_err(|e| format!("Failed to archive {}: {}", &file.path, e))?;
+        if sha256 != file.sha256 {
+            return Err(format!(
+                "{} changed while the snapshot was being taken -- is the node still running?",
+                &file.path
+            ));
+        }
+    }
+
+    Ok(manifest)
+}
+
+/// Read a snapshot from `fd` into the snapshot directories, verifying each file against the
+/// manifest.  Does not check the consensus hash -- see `restore_snapshot()`.
+pub fn read_snapshot<R: Read>(fd: &mut R, dirs: &SnapshotDirs) -> Result<SnapshotManifest, String> {
+    let mut magic = [0u8; 8];
+    fd.read_exact(&mut magic)
+        .map_err(|e| format!("Failed to read snapshot header: {}", e))?;
+    if &magic != SNAPSHOT_MAGIC {
+        return Err("Not a chainstate snapshot".to_string());
+    }
+
+    let mut len_bytes = [0u8; 4];
+    fd.read_exact(&mut len_bytes)
+        .map_err(|e| format!("Failed to read snapshot header: {}", e))?;
+    let manifest_len = u32::from_be_bytes(len_bytes);
+    if manifest_len > MAX_MANIFEST_LEN {
+        return Err(format!("Snapshot manifest is too big: {}", manifest_len));
+    }
+
+    let mut manifest_bytes = vec![0u8; manifest_len as usize];
+    fd.read_exact(&mut manifest_bytes)
+        .map_err(|e| format!("Failed to read snapshot manifest: {}", e))?;
+    let manifest: SnapshotManifest = serde_json::from_slice(&manifest_bytes)
+        .map_err(|e| format!("Invalid snapshot manifest: {}", e))?;
+
+    for file in manifest.files.iter() {
+        check_relative_path(&file.path)?;
+        let path = dirs.resolve(&file.path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let mut output = fs::File::create(&path)
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        let sha256 = copy_and_hash(fd, &mut output, file.size)
+            .map_err(|e| format!("Failed to restore {}: {}", &file.path, e))?;
+        if sha256 != file.sha256 {
+            return Err(format!(
+                "Snapshot is corrupt: {} has SHA-256 {}, but the manifest says {}",
+                &file.path, sha256, &file.sha256
+            ));
+        }
+    }
+
+    let mut trailing = [0u8; 1];
+    match fd.read(&mut trailing) {
+        Ok(0) => Ok(manifest),
+        Ok(_) => Err("Snapshot is corrupt: trailing data".to_string()),
+        Err(e) => Err(format!("Read error: {}", e)),
+    }
+}
+
+fn read_tip(conf: &Config) -> Result<SnapshotTip, String> {
+    let sortdb = SortitionDB::open(&conf.get_burn_db_file_path(), false)
+        .map_err(|e| format!("Failed to open sortition DB: {:?}", e))?;
+    let tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())
+        .map_err(|e| format!("Failed to read the canonical burnchain tip: {:?}", e))?;
+    Ok(SnapshotTip {
+        consensus_hash: tip.consensus_hash.to_hex(),
+        burn_block_height: tip.block_height,
+        burn_header_hash: tip.burn_header_hash.to_hex(),
+        stacks_tip_consensus_hash: tip.canonical_stacks_tip_consensus_hash.to_hex(),
+        stacks_tip_block_hash: tip.canonical_stacks_tip_hash.to_hex(),
+    })
+}
+
+/// Write a snapshot of the node configured by `conf` to `archive_path`
+pub fn create_snapshot(conf: &Config, archive_path: &str) -> Result<SnapshotManifest, String> {
+    let tip = read_tip(conf)?;
+    let tmp_path = format!("{}.tmp", archive_path);
+    let mut fd = io::BufWriter::new(
+        fs::File::create(&tmp_path)
+            .map_err(|e| format!("Failed to create {}: {}", &tmp_path, e))?,
+    );
+    let manifest = write_snapshot(&SnapshotDirs::from_config(conf), tip, &mut fd)
+        .and_then(|manifest| {
+            fd.flush().map_err(|e| format!("Write error: {}", e))?;
+            Ok(manifest)
+        })
+        .map_err(|e| {
+            let _ = fs::remove_file(&tmp_path);
+            e
+        })?;
+    fs::rename(&tmp_path, archive_path)
+        .map_err(|e| format!("Failed to rename {} to {}: {}", &tmp_path, archive_path, e))?;
+    Ok(manifest)
+}
+
+/// Restore the snapshot at `archive_path` into the (fresh) burnchain and chain state directories
+/// of the node configured by `conf`.
+pub fn restore_snapshot(conf: &Config, archive_path: &str) -> Result<SnapshotManifest, String> {
+    let dirs = SnapshotDirs::from_config(conf);
+    for dir in SNAPSHOT_DIRS.iter() {
+        if dirs.dir(dir).exists() {
+            return Err(format!(
+                "{} already exists -- restore into an empty working directory",
+                dirs.dir(dir).display()
+            ));
+        }
+    }
+
+    let remove_restored = || {
+        for dir in SNAPSHOT_DIRS.iter() {
+            let _ = fs::remove_dir_all(dirs.dir(dir));
+        }
+    };
+
+    let mut fd = io::BufReader::new(
+        fs::File::open(archive_path)
+            .map_err(|e| format!("Failed to open {}: {}", archive_path, e))?,
+    );
+    let manifest = read_snapshot(&mut fd, &dirs).map_err(|e| {
+        remove_restored();
+        e
+    })?;
+
+    let tip = read_tip(conf).map_err(|e| {
+        remove_restored();
+        e
+    })?;
+    if tip.consensus_hash != manifest.tip.consensus_hash {
+        remove_restored();
+        return Err(format!(
+            "Restored sortition DB has tip consensus hash {}, but the snapshot claims {}",
+            &tip.consensus_hash, &manifest.tip.consensus_hash
+        ));
+    }
+
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_tip() -> SnapshotTip {
+        SnapshotTip {
+            consensus_hash: "11".repeat(20),
+            burn_block_height: 123,
+            burn_header_hash: "22".repeat(32),
+            stacks_tip_consensus_hash: "33".repeat(20),
+            stacks_tip_block_hash: "44".repeat(32),
+        }
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let path = PathBuf::from(format!("/tmp/stacks-node-tests/snapshot/{}", name));
+        if path.exists() {
+            fs::remove_dir_all(&path).unwrap();
+        }
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let src = test_dir("round_trip_src");
+        fs::create_dir_all(src.join("burnchain/db")).unwrap();
+        fs::create_dir_all(src.join("chainstate/blocks/00")).unwrap();
+        fs::write(src.join("burnchain/db/sortition.db"), b"sortition").unwrap();
+        fs::write(src.join("chainstate/blocks/00/block"), vec![7u8; 200000]).unwrap();
+        fs::write(src.join("chainstate/empty"), b"").unwrap();
+        fs::write(src.join("chainstate/mempool.db"), b"mempool").unwrap();
+        fs::write(src.join("chainstate/index.sqlite-journal"), b"journal").unwrap();
+        fs::write(src.join("peer_db.sqlite"), b"peers").unwrap();
+
+        let mut archive = vec![];
+        let manifest =
+            write_snapshot(&SnapshotDirs::under(&src), test_tip(), &mut archive).unwrap();
+        let paths: Vec<_> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "burnchain/db/sortition.db",
+                "chainstate/blocks/00/block",
+                "chainstate/empty"
+            ]
+        );
+
+        let dest = test_dir("round_trip_dest");
+        let restored = read_snapshot(&mut &archive[..], &SnapshotDirs::under(&dest)).unwrap();
+        assert_eq!(restored, manifest);
+        assert_eq!(
+            fs::read(dest.join("chainstate/blocks/00/block")).unwrap(),
+            vec![7u8; 200000]
+        );
+        assert_eq!(fs::read(dest.join("chainstate/empty")).unwrap().len(), 0);
+        assert!(!dest.join("chainstate/mempool.db").exists());
+        assert!(!dest.join("peer_db.sqlite").exists());
+
+        // into directories laid out differently
+        let dest = test_dir("round_trip_relocated");
+        let relocated = SnapshotDirs {
+            burnchain: dest.join("hdd/burnchain"),
+            chainstate: dest.join("nvme/chain"),
+        };
+        assert_eq!(
+            read_snapshot(&mut &archive[..], &relocated).unwrap(),
+            manifest
+        );
+        assert_eq!(
+            fs::read(dest.join("hdd/burnchain/db/sortition.db")).unwrap(),
+            b"sortition".to_vec()
+        );
+        assert_eq!(
+            fs::read(dest.join("nvme/chain/blocks/00/block")).unwrap(),
+            vec![7u8; 200000]
+        );
+
+        // corrupt file contents
+        let mut corrupt = archive.clone();
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 0xff;
+        let dest = test_dir("round_trip_corrupt");
+        assert!(read_snapshot(&mut &corrupt[..], &SnapshotDirs::under(&dest)).is_err());
+
+        // trailing data
+        let mut trailing = archive.clone();
+        trailing.push(0);
+        let dest = test_dir("round_trip_trailing");
+        assert!(read_snapshot(&mut &trailing[..], &SnapshotDirs::under(&dest)).is_err());
+
+        // truncated
+        let dest = test_dir("round_trip_truncated");
+        assert!(read_snapshot(
+            &mut &archive[..archive.len() - 1],
+            &SnapshotDirs::under(&dest)
+        )
+        .is_err());
+
+        // not a snapshot
+        let dest = test_dir("round_trip_garbage");
+        assert!(read_snapshot(&mut &b"garbage garbage"[..], &SnapshotDirs::under(&dest)).is_err());
+    }
+
+    #[test]
+    fn snapshot_rejects_paths_outside_working_dir() {
+        for path in [
+            "../escape",
+            "chainstate/../../escape",
+            "/etc/passwd",
+            "peer_db.sqlite",
+            "chainstate",
+        ]
+        .iter()
+        {
+            let manifest = SnapshotManifest {
+                tip: test_tip(),
+                created_at: 0,
+                files: vec![SnapshotFile {
+                    path: path.to_string(),
+                    size: 0,
+                    sha256: to_hex(Sha256::digest(b"").as_slice()),
+                }],
+            };
+            let manifest_bytes = serde_json::to_vec(&manifest).unwrap();
+            let mut archive = SNAPSHOT_MAGIC.to_vec();
+            archive.extend_from_slice(&(manifest_bytes.len() as u32).to_be_bytes());
+            archive.extend_from_slice(&manifest_bytes);
+
+            let dest = test_dir("bad_paths");
+            assert!(
+                read_snapshot(&mut &archive[..], &SnapshotDirs::under(&dest)).is_err(),
+                "accepted {}",
+                path
+            );
+        }
+        assert!(check_relative_path("chainstate/blocks/00/block").is_ok());
+    }
+}
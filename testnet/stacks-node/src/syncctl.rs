@@ -6,6 +6,7 @@ use stacks::util::get_epoch_time_secs;
 use stacks::util::sleep_ms;
 
 use crate::burnchains::BurnchainTip;
+use crate::shutdown;
 
 use std::sync::{
     atomic::{AtomicU64, Ordering},
@@ -115,6 +116,9 @@ pub struct PoxSyncWatchdog {
     relayer_comms: PoxSyncWatchdogComms,
     /// what was the last burnchain height?
     last_burnchain_height: u64,
+    /// if set, blocks until the burnchain reaches the given height or the given number of seconds
+    /// pass.  Returns false if it could not wait, in which case we poll on a timer instead.
+    burnchain_block_waiter: Option<Box<dyn FnMut(u64, u64) -> bool + Send>>,
 }
 
 const PER_SAMPLE_WAIT_MS: u64 = 1000;
@@ -154,9 +158,31 @@ impl PoxSyncWatchdog {
             chainstate: chainstate,
             relayer_comms: PoxSyncWatchdogComms::new(),
             last_burnchain_height: 0,
+            burnchain_block_waiter: None,
         })
     }
 
+    /// Wake up as soon as a new burnchain block arrives, instead of only re-syncing the burnchain
+    /// every `steady_state_burnchain_sync_interval` seconds.
+    pub fn set_burnchain_block_waiter(&mut self, waiter: Box<dyn FnMut(u64, u64) -> bool + Send>) {
+        self.burnchain_block_waiter = Some(waiter);
+    }
+
+    /// Wait up to `timeout_secs` for the burnchain to reach `height`.  Returns early once the
+    /// block arrives if we have a block waiter; otherwise just sleeps.
+    fn wait_for_burnchain_block(&mut self, height: u64, timeout_secs: u64) {
+        if let Some(ref mut waiter) = self.burnchain_block_waiter {
+            if waiter(height, timeout_secs) {
+                return;
+            }
+            debug!(
+                "PoX watchdog: could not wait for burnchain block {}; polling instead",
+                height
+            );
+        }
+        sleep_ms(1000 * timeout_secs);
+    }
+
     pub fn make_comms_handle(&self) -> PoxSyncWatchdogComms {
         self.relayer_comms.clone()
     }
@@ -412,7 +438,10 @@ impl PoxSyncWatchdog {
                     "PoX watchdog in last reward cycle -- sync after {} seconds",
                     self.steady_state_burnchain_sync_interval
                 );
-                sleep_ms(1000 * self.steady_state_burnchain_sync_interval);
+                self.wait_for_burnchain_block(
+                    burnchain_height + 1,
+                    self.steady_state_burnchain_sync_interval,
+                );
             }
             return PoxSyncWatchdog::infer_initial_burnchain_block_download(
                 burnchain,
@@ -433,6 +462,11 @@ impl PoxSyncWatchdog {
                 burnchain_height,
             );
 
+            if shutdown::shutdown_requested() {
+                debug!("PoX watchdog: node is shutting down");
+                break ibbd;
+            }
+
             let expected_first_block_deadline =
                 self.watch_start_ts + (self.estimated_block_download_time as u64);
             let expected_last_block_deadline = self.last_block_processed_ts
@@ -544,6 +578,14 @@ impl PoxSyncWatchdog {
                                 debug!("PoX watchdog: In steady-state; waiting until at least {} before burnchain synchronization", self.steady_state_resync_ts);
                                 steady_state = flat_attachable && flat_processed;
                             }
+                            if steady_state && self.burnchain_block_waiter.is_some() {
+                                // nothing left to process -- sync as soon as the next burnchain
+                                // block shows up
+                                let timeout = self.steady_state_resync_ts - now;
+                                self.wait_for_burnchain_block(burnchain_height + 1, timeout);
+                                self.steady_state_resync_ts = get_epoch_time_secs();
+                                continue;
+                            }
                             sleep_ms(PER_SAMPLE_WAIT_MS);
                             continue;
                         } else {
@@ -1,4 +1,4 @@
-use super::node::{ChainTip, TESTNET_CHAIN_ID};
+use super::node::ChainTip;
 use super::{BurnchainTip, Config};
 
 use std::thread;
@@ -14,7 +14,7 @@ use stacks::core::mempool::MemPoolDB;
 use stacks::util::hash::Hash160;
 use stacks::util::vrf::VRFProof;
 
-use stacks::vm::database::BurnStateDB;
+use stacks::chainstate::burn::db::sortdb::SortitionDBConn;
 
 pub struct TenureArtifacts {
     pub anchored_block: StacksBlock,
@@ -67,7 +67,7 @@ impl<'a> Tenure {
         }
     }
 
-    pub fn run(&mut self, burn_dbconn: &dyn BurnStateDB) -> Option<TenureArtifacts> {
+    pub fn run(&mut self, burn_dbconn: &SortitionDBConn) -> Option<TenureArtifacts> {
         info!("Node starting new tenure with VRF {:?}", self.vrf_seed);
 
         let duration_left: u128 = self.config.burnchain.commit_anchor_block_within as u128;
@@ -79,12 +79,19 @@ impl<'a> Tenure {
 
         let (mut chain_state, _) = StacksChainState::open_with_block_limit(
             false,
-            TESTNET_CHAIN_ID,
+            self.config.node.chain_id,
             &self.config.get_chainstate_path(),
             self.config.block_limit.clone(),
         )
         .unwrap();
 
+        let burn_transfer_ops = StacksChainState::get_transfer_stx_ops(
+            burn_dbconn,
+            self.parent_block.metadata.burn_header_height,
+            &self.burnchain_tip.block_snapshot.burn_header_hash,
+        )
+        .unwrap();
+
         let (anchored_block, _, _) = StacksBlockBuilder::build_anchored_block(
             &mut chain_state,
             burn_dbconn,
@@ -94,6 +101,7 @@ impl<'a> Tenure {
             self.vrf_proof.clone(),
             self.microblock_pubkeyhash.clone(),
             &self.coinbase_tx,
+            &burn_transfer_ops,
             self.config.block_limit.clone(),
         )
         .unwrap();
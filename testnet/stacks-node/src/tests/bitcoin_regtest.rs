@@ -127,6 +127,24 @@ fn bitcoind_integration_test() {
     // Start bitcoind
     let _res = controller.start_bitcoind();
 
+    run_helium_integration_test(conf);
+
+    controller.kill_bitcoind();
+}
+
+#[test]
+fn mock_burnchain_integration_test() {
+    let mut conf = super::new_test_conf();
+    conf.burnchain.commit_anchor_block_within = 2000;
+    conf.burnchain.burn_fee_cap = BITCOIND_INT_TEST_COMMITS;
+    conf.burnchain.mode = "mock".to_string();
+
+    run_helium_integration_test(conf);
+}
+
+/// Mine 6 rounds on a helium run loop, bootstrapped with 201 burnchain blocks, and check every
+/// sortition and Stacks block along the way
+fn run_helium_integration_test(conf: Config) {
     let num_rounds = 6;
     let mut run_loop = RunLoop::new(conf);
 
@@ -422,6 +440,4 @@ fn bitcoind_integration_test() {
         },
     );
     run_loop.start(num_rounds).unwrap();
-
-    controller.kill_bitcoind();
 }
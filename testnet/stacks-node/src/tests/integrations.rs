@@ -505,7 +505,7 @@ fn integration_test_get_info() {
 
                 let body = CallReadOnlyRequestBody {
                     sender: "'SP139Q3N9RXCJCD1XVA4N5RYWQ5K9XQ0T9PKQ8EE5".into(),
-                    arguments: vec![Value::UInt(1).serialize()]
+                    arguments: vec![Value::UInt(1).serialize().into()]
                 };
 
                 let res = client.post(&path)
@@ -527,7 +527,7 @@ fn integration_test_get_info() {
 
                 let body = CallReadOnlyRequestBody {
                     sender: "'SP139Q3N9RXCJCD1XVA4N5RYWQ5K9XQ0T9PKQ8EE5".into(),
-                    arguments: vec![Value::UInt(1).serialize()]
+                    arguments: vec![Value::UInt(1).serialize().into()]
                 };
 
                 let res = client.post(&path)
@@ -549,7 +549,7 @@ fn integration_test_get_info() {
 
                 let body = CallReadOnlyRequestBody {
                     sender: "'SP139Q3N9RXCJCD1XVA4N5RYWQ5K9XQ0T9PKQ8EE5".into(),
-                    arguments: vec![Value::UInt(100).serialize()]
+                    arguments: vec![Value::UInt(100).serialize().into()]
                 };
 
                 let res = client.post(&path)
@@ -1309,9 +1309,10 @@ fn mempool_errors() {
             if round == 1 {
                 // let's submit an invalid transaction!
                 eprintln!("Test: POST {} (invalid)", path);
+                // too far ahead of the spender's nonce to be held in the pending lane
                 let tx_xfer_invalid = make_stacks_transfer(
                     &spender_sk,
-                    3,
+                    30,
                     200, // bad nonce
                     &send_to,
                     456,
@@ -1345,7 +1346,7 @@ fn mempool_errors() {
                     &spender_addr.to_string()
                 );
                 assert_eq!(data.get("expected").unwrap().as_i64().unwrap(), 0);
-                assert_eq!(data.get("actual").unwrap().as_i64().unwrap(), 3);
+                assert_eq!(data.get("actual").unwrap().as_i64().unwrap(), 30);
 
                 let tx_xfer_invalid = make_stacks_transfer(
                     &spender_sk,
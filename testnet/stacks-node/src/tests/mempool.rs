@@ -3,8 +3,13 @@ use stacks::chainstate::burn::BlockHeaderHash;
 use stacks::net::{Error as NetError, StacksMessageCodec};
 use stacks::util::{hash::*, secp256k1::*};
 use stacks::vm::{
-    representations::ContractName, types::PrincipalData, types::QualifiedContractIdentifier,
-    types::StandardPrincipalData, Value,
+    analysis::errors::{CheckError, CheckErrors},
+    representations::ContractName,
+    types::PrincipalData,
+    types::QualifiedContractIdentifier,
+    types::StandardPrincipalData,
+    types::TypeSignature,
+    Value,
 };
 
 use stacks::chainstate::stacks::{
@@ -474,7 +479,42 @@ fn mempool_setup_chainstate() {
                     )
                     .unwrap_err();
                 eprintln!("Err: {:?}", e);
-                assert!(if let MemPoolRejection::BadFunctionArgument(_) = e {
+                assert!(if let MemPoolRejection::BadFunctionArgument(CheckError {
+                    err: CheckErrors::IncorrectArgumentCount(1, 2),
+                    ..
+                }) = e
+                {
+                    true
+                } else {
+                    false
+                });
+
+                let tx_bytes = make_contract_call(
+                    &contract_sk,
+                    5,
+                    200,
+                    &contract_addr,
+                    "foo_contract",
+                    "bar",
+                    &[Value::Int(1)],
+                );
+                let tx =
+                    StacksTransaction::consensus_deserialize(&mut tx_bytes.as_slice()).unwrap();
+                let e = chain_state
+                    .will_admit_mempool_tx(
+                        mempool_conn,
+                        consensus_hash,
+                        block_hash,
+                        &tx,
+                        tx_bytes.len() as u64,
+                    )
+                    .unwrap_err();
+                eprintln!("Err: {:?}", e);
+                assert!(if let MemPoolRejection::BadFunctionArgument(CheckError {
+                    err: CheckErrors::TypeError(TypeSignature::UIntType, TypeSignature::IntType),
+                    ..
+                }) = e
+                {
                     true
                 } else {
                     false
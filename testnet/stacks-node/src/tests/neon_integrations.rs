@@ -292,6 +292,7 @@ fn microblock_integration_test() {
     conf.events_observers.push(EventObserverConfig {
         endpoint: format!("localhost:{}", test_observer::EVENT_OBSERVER_PORT),
         events_keys: vec![EventKeyType::AnyEvent],
+        max_retries: None,
     });
 
     let mut btcd_controller = BitcoinCoreController::new(conf.clone());